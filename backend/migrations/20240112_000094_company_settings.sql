@@ -0,0 +1,35 @@
+-- Company Settings
+-- MSP branding details rendered on client-facing documents (invoice PDFs, statements)
+
+CREATE TABLE IF NOT EXISTS company_settings (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+
+    -- Identity
+    company_name VARCHAR(255) NOT NULL,
+    tax_id VARCHAR(100),
+
+    -- Contact details
+    address TEXT,
+    city VARCHAR(100),
+    state VARCHAR(50),
+    zip VARCHAR(20),
+    country VARCHAR(100),
+    phone VARCHAR(50),
+    email VARCHAR(255),
+    website VARCHAR(255),
+
+    -- Branding
+    logo_file_path TEXT,
+    primary_color VARCHAR(7),
+    invoice_footer TEXT, -- printed at the bottom of every invoice
+
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ
+);
+
+-- Seed a single settings row so documents always have something to render
+INSERT INTO company_settings (company_name)
+SELECT 'Resolve MSP'
+WHERE NOT EXISTS (SELECT 1 FROM company_settings);
+
+COMMENT ON TABLE company_settings IS 'MSP company branding used when rendering invoices and other client-facing documents';
@@ -0,0 +1,7 @@
+-- Client Tax Exemption
+-- Tax-exempt clients are invoiced with zero tax regardless of line item rates
+
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS tax_exempt BOOLEAN NOT NULL DEFAULT false;
+
+-- Line items record the tax actually charged
+ALTER TABLE invoice_line_items ADD COLUMN IF NOT EXISTS tax_amount DECIMAL(15,2);
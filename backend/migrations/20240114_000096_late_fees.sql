@@ -0,0 +1,20 @@
+-- Invoice Late Fees
+-- Ledger of late fees applied to overdue invoices, one row per invoice per billing month
+
+-- Account manager receives late-fee and other billing notifications for the client
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS account_manager_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS applied_late_fees (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    invoice_id UUID NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    line_item_id UUID REFERENCES invoice_line_items(id) ON DELETE SET NULL,
+    -- First day of the month the fee was assessed for
+    billing_period DATE NOT NULL,
+    balance_before DECIMAL(15,2) NOT NULL,
+    late_fee_percentage DECIMAL(5,2) NOT NULL,
+    fee_amount DECIMAL(15,2) NOT NULL,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (invoice_id, billing_period)
+);
+
+CREATE INDEX IF NOT EXISTS idx_applied_late_fees_invoice ON applied_late_fees(invoice_id);
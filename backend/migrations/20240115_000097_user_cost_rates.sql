@@ -0,0 +1,7 @@
+-- User Cost Rates
+-- Internal hourly cost per technician, used for labor cost in profitability reports
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS cost_rate DECIMAL(10,2);
+
+-- Organisation-wide fallback for users without their own cost rate
+ALTER TABLE company_settings ADD COLUMN IF NOT EXISTS default_cost_rate DECIMAL(10,2) NOT NULL DEFAULT 50.00;
@@ -0,0 +1,11 @@
+-- SLA Breach Workflow Triggers
+-- Records which SLA breaches have already fired workflow automations so each
+-- ticket fires at most once per breach type, even if its SLA flags are reset
+
+CREATE TABLE IF NOT EXISTS sla_breach_triggers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    ticket_id UUID NOT NULL REFERENCES tickets(id) ON DELETE CASCADE,
+    breach_type VARCHAR(20) NOT NULL CHECK (breach_type IN ('first_response', 'resolution')),
+    fired_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (ticket_id, breach_type)
+);
@@ -0,0 +1,16 @@
+-- Workflow Execution History
+-- Columns the workflow engine needs to load definitions and record each run,
+-- including per-action results and replays of earlier executions
+
+ALTER TABLE workflows ADD COLUMN IF NOT EXISTS actions JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE workflows ADD COLUMN IF NOT EXISTS execution_order INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE workflows ADD COLUMN IF NOT EXISTS stop_on_first_match BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE workflows ALTER COLUMN workflow_type SET DEFAULT 'event_driven';
+
+ALTER TABLE workflow_executions ADD COLUMN IF NOT EXISTS trigger_type VARCHAR(100);
+ALTER TABLE workflow_executions ADD COLUMN IF NOT EXISTS trigger_event_id UUID;
+ALTER TABLE workflow_executions ADD COLUMN IF NOT EXISTS action_results JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE workflow_executions ADD COLUMN IF NOT EXISTS replayed_from_id UUID REFERENCES workflow_executions(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_workflow_executions_workflow_started
+    ON workflow_executions(workflow_id, started_at DESC);
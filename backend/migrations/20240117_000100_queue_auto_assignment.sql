@@ -0,0 +1,17 @@
+-- Queue Auto-Assignment
+-- Round-robin cursor on queues and a log of automatic assignments
+
+ALTER TABLE ticket_queues ADD COLUMN IF NOT EXISTS last_assigned_user_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS ticket_queue_assignments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    ticket_id UUID NOT NULL REFERENCES tickets(id) ON DELETE CASCADE,
+    queue_id UUID NOT NULL REFERENCES ticket_queues(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    strategy VARCHAR(20) NOT NULL, -- round_robin, least_loaded
+    open_tickets_at_assignment BIGINT NOT NULL DEFAULT 0,
+    assigned_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_ticket_queue_assignments_queue ON ticket_queue_assignments(queue_id, assigned_at DESC);
+CREATE INDEX idx_ticket_queue_assignments_ticket ON ticket_queue_assignments(ticket_id);
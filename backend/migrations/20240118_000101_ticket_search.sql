@@ -0,0 +1,10 @@
+-- Ticket Full-Text Search
+-- Weighted search vector over subject and details, maintained as a generated column
+
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', COALESCE(subject, '')), 'A') ||
+        setweight(to_tsvector('english', COALESCE(details, '')), 'B')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_tickets_search_vector ON tickets USING GIN(search_vector);
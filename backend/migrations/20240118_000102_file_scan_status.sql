@@ -0,0 +1,5 @@
+-- File Virus Scanning
+-- Records the ClamAV verdict for each upload (not_scanned when scanning is disabled)
+
+ALTER TABLE files ADD COLUMN IF NOT EXISTS scan_status VARCHAR(20) NOT NULL DEFAULT 'not_scanned'
+    CHECK (scan_status IN ('not_scanned', 'clean', 'infected'));
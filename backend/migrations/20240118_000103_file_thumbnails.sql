@@ -0,0 +1,4 @@
+-- File Thumbnails
+-- Location of the generated PNG thumbnail for image uploads
+
+ALTER TABLE files ADD COLUMN IF NOT EXISTS thumbnail_path VARCHAR;
@@ -0,0 +1,22 @@
+-- Credential Rotation
+-- Rotation interval per credential and a history of password changes
+
+ALTER TABLE credentials ADD COLUMN IF NOT EXISTS rotation_interval_days INTEGER CHECK (rotation_interval_days > 0);
+ALTER TABLE credentials ADD COLUMN IF NOT EXISTS rotation_flagged_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS credential_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    credential_id UUID NOT NULL REFERENCES credentials(id) ON DELETE CASCADE,
+    change_type VARCHAR(20) NOT NULL CHECK (change_type IN ('created', 'updated', 'rotated')),
+    -- Keyed hash of the replaced password, used only to detect reuse
+    old_value_hash VARCHAR(64),
+    notes TEXT,
+    changed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_credential_history_credential ON credential_history(credential_id, changed_at DESC);
+
+-- Existing passwords count as set when the credential was created
+INSERT INTO credential_history (credential_id, change_type, changed_at)
+SELECT id, 'created', COALESCE(created_at, NOW()) FROM credentials WHERE password IS NOT NULL;
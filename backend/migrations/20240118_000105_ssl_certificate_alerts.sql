@@ -0,0 +1,20 @@
+-- SSL certificate live checks
+-- Records when a certificate was last checked against its live host and
+-- raises an alert for each field that differed from the stored record
+
+ALTER TABLE ssl_certificates ADD COLUMN IF NOT EXISTS last_live_check_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE IF NOT EXISTS ssl_certificate_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    certificate_id UUID NOT NULL REFERENCES ssl_certificates(id) ON DELETE CASCADE,
+    field VARCHAR(50) NOT NULL,
+    stored_value TEXT NOT NULL,
+    live_value TEXT NOT NULL,
+    host VARCHAR(255) NOT NULL,
+    port INTEGER NOT NULL,
+    acknowledged_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ssl_certificate_alerts_certificate
+    ON ssl_certificate_alerts(certificate_id, created_at DESC);
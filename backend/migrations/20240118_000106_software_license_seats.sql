@@ -0,0 +1,38 @@
+-- Software License Seats
+-- Tracks which contact or asset holds each seat of an itdoc software
+-- license, and alerts raised for over-allocated or nearly full licenses
+
+CREATE TABLE IF NOT EXISTS software_license_assignments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    license_id UUID NOT NULL REFERENCES software_licenses(id) ON DELETE CASCADE,
+    contact_id UUID REFERENCES contacts(id) ON DELETE CASCADE,
+    asset_id UUID REFERENCES assets(id) ON DELETE CASCADE,
+    notes TEXT,
+    assigned_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    assigned_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    unassigned_at TIMESTAMPTZ,
+    CHECK ((contact_id IS NULL) <> (asset_id IS NULL))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_license_assignments_active_contact
+    ON software_license_assignments(license_id, contact_id)
+    WHERE unassigned_at IS NULL AND contact_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_license_assignments_active_asset
+    ON software_license_assignments(license_id, asset_id)
+    WHERE unassigned_at IS NULL AND asset_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS software_license_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    license_id UUID NOT NULL REFERENCES software_licenses(id) ON DELETE CASCADE,
+    alert_type VARCHAR(30) NOT NULL CHECK (alert_type IN ('overage', 'high_utilization')),
+    seats INTEGER,
+    used_seats INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ
+);
+
+-- One open alert of each type per license
+CREATE UNIQUE INDEX IF NOT EXISTS idx_software_license_alerts_open
+    ON software_license_alerts(license_id, alert_type)
+    WHERE resolved_at IS NULL;
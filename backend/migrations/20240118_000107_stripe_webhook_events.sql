@@ -0,0 +1,13 @@
+-- Stripe Webhook Events
+-- Every processed Stripe event id, so redelivered webhooks are applied once
+
+CREATE TABLE IF NOT EXISTS stripe_webhook_events (
+    event_id VARCHAR(255) PRIMARY KEY,
+    event_type VARCHAR(100) NOT NULL,
+    payment_intent_id VARCHAR(255),
+    invoice_id UUID REFERENCES invoices(id) ON DELETE SET NULL,
+    outcome VARCHAR(30),
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_stripe_webhook_events_invoice ON stripe_webhook_events(invoice_id);
@@ -0,0 +1,21 @@
+-- Ticket External References
+-- Links tickets to issues in external trackers (currently GitHub) so their
+-- status can be kept in sync
+
+CREATE TABLE IF NOT EXISTS ticket_external_refs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    ticket_id UUID NOT NULL REFERENCES tickets(id) ON DELETE CASCADE,
+    integration_id UUID NOT NULL REFERENCES integrations(id) ON DELETE CASCADE,
+    provider VARCHAR(50) NOT NULL,
+    repository VARCHAR(255) NOT NULL,
+    external_id VARCHAR(100) NOT NULL,
+    external_url TEXT,
+    external_state VARCHAR(50),
+    last_synced_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (ticket_id, integration_id),
+    UNIQUE (integration_id, repository, external_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ticket_external_refs_integration ON ticket_external_refs(integration_id);
@@ -0,0 +1,10 @@
+-- Domain Cloudflare Zones
+-- Links itdoc domains to the Cloudflare zone they were imported from so
+-- integration syncs can skip zones that haven't changed
+
+ALTER TABLE domains ADD COLUMN IF NOT EXISTS cloudflare_zone_id VARCHAR(64);
+ALTER TABLE domains ADD COLUMN IF NOT EXISTS cloudflare_modified_on TIMESTAMPTZ;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_domains_cloudflare_zone_id
+    ON domains(cloudflare_zone_id) WHERE cloudflare_zone_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_domains_lower_name ON domains(LOWER(name));
@@ -0,0 +1,10 @@
+-- Audit Log Query Indexes
+-- Support the audit-log API: entity history and user activity newest first,
+-- and keyset paging over (created_at, id)
+
+CREATE INDEX IF NOT EXISTS idx_audit_logs_entity_created
+    ON audit_logs(entity_type, entity_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_logs_user_created
+    ON audit_logs(user_id, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_logs_created_id
+    ON audit_logs(created_at DESC, id DESC);
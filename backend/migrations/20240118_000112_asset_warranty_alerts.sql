@@ -0,0 +1,16 @@
+-- Asset Warranty Alerts
+-- One row per warranty warning threshold an asset has been alerted for, so
+-- the expiration monitor raises each threshold once per warranty date
+
+CREATE TABLE IF NOT EXISTS asset_warranty_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    asset_id UUID NOT NULL REFERENCES assets(id) ON DELETE CASCADE,
+    threshold_days INTEGER NOT NULL,
+    warranty_expire DATE NOT NULL,
+    alert_id UUID REFERENCES alerts(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (asset_id, threshold_days, warranty_expire)
+);
+
+CREATE INDEX IF NOT EXISTS idx_assets_warranty_expire
+    ON assets(warranty_expire) WHERE archived_at IS NULL;
@@ -0,0 +1,17 @@
+-- Password Health
+-- Strength scores move to zxcvbn's 0-4 scale and breach checks record when
+-- they last ran, so the health report can list weak and breached credentials
+
+ALTER TABLE passwords ADD COLUMN IF NOT EXISTS breach_checked_at TIMESTAMPTZ;
+
+UPDATE passwords SET strength_score = CASE
+    WHEN strength_score <= 30 THEN 0
+    WHEN strength_score <= 50 THEN 1
+    WHEN strength_score <= 70 THEN 2
+    WHEN strength_score <= 85 THEN 3
+    ELSE 4
+END
+WHERE strength_score > 4;
+
+CREATE INDEX IF NOT EXISTS idx_passwords_health
+    ON passwords(strength_score, breach_detected);
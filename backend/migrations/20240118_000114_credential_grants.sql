@@ -0,0 +1,18 @@
+-- Credential Grants
+-- Time-limited read access to a stored password for one user, without
+-- changing their role. Grants lapse at expires_at or when revoked.
+
+CREATE TABLE IF NOT EXISTS credential_grants (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    password_id UUID NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+    grantee_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    granted_by UUID NOT NULL REFERENCES users(id),
+    reason TEXT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_credential_grants_lookup
+    ON credential_grants(password_id, grantee_id, expires_at DESC)
+    WHERE revoked_at IS NULL;
@@ -0,0 +1,26 @@
+-- Inbound Email
+-- Tickets and replies created from inbound mail. Each Message-ID is
+-- processed once. Tickets, replies and attachments need a user, so mail
+-- is attributed to a built-in inactive "Email" user that can't sign in;
+-- the sending contact is kept on the reply. Its password hash isn't a
+-- valid hash, so no password matches it.
+
+INSERT INTO users (id, email, password_hash, first_name, last_name, is_active, email_verified_at)
+VALUES ('00000000-0000-0000-0000-00000000e4a1', 'inbound-email@system.resolve', '!', 'Email', 'Inbound', false, NOW())
+ON CONFLICT (id) DO NOTHING;
+
+ALTER TABLE ticket_replies ADD COLUMN IF NOT EXISTS contact_id UUID REFERENCES contacts(id) ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS inbound_emails (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id VARCHAR(500) NOT NULL UNIQUE,
+    from_email VARCHAR(255) NOT NULL,
+    subject VARCHAR(500),
+    queue_id UUID REFERENCES ticket_queues(id) ON DELETE SET NULL,
+    ticket_id UUID REFERENCES tickets(id) ON DELETE SET NULL,
+    result VARCHAR(30) NOT NULL DEFAULT 'processing', -- processing, ticket_created, reply_added, ignored
+    reason TEXT,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_inbound_emails_ticket ON inbound_emails(ticket_id);
@@ -0,0 +1,110 @@
+-- Email Template Branding
+-- Per-client branding overrides for outbound email, and branded versions of
+-- the seeded ticket update and invoice templates using {{brand.*}}
+-- variables. Seeded templates that have been edited are left alone. Adds
+-- a payment reminder template.
+
+CREATE TABLE IF NOT EXISTS client_email_branding (
+    client_id UUID PRIMARY KEY REFERENCES clients(id) ON DELETE CASCADE,
+    company_name VARCHAR(255),
+    logo_url VARCHAR(1000),
+    primary_color VARCHAR(7),
+    accent_color VARCHAR(7),
+    footer_text TEXT,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+UPDATE email_templates SET
+    subject = '[Ticket #{{ticket_number}}] Update: {{subject}}',
+    html_body = '<html>
+<body style="margin:0;padding:20px;background:#f5f5f5;font-family:Arial,sans-serif;">
+  <div style="max-width:600px;margin:0 auto;background:#ffffff;border-radius:8px;overflow:hidden;">
+    <div style="background:{{brand.primary_color}};color:#ffffff;padding:20px;text-align:center;">
+      {{brand.logo}}
+      <h1 style="margin:8px 0 0;font-size:22px;">Ticket Updated</h1>
+    </div>
+    <div style="padding:30px;">
+      <p>Hello {{client_name}},</p>
+      <p>Your ticket #{{ticket_number}} has been updated.</p>
+      <p><strong>Status:</strong> {{status}}</p>
+      <p>{{update_message}}</p>
+      <p><a href="{{portal_url}}" style="color:{{brand.primary_color}};">View ticket</a></p>
+    </div>
+    <div style="background:{{brand.accent_color}};padding:16px;text-align:center;color:#666666;font-size:13px;">{{brand.footer}}</div>
+  </div>
+</body>
+</html>',
+    text_body = 'Your ticket #{{ticket_number}} has been updated.
+
+Status: {{status}}
+
+{{update_message}}
+
+View ticket: {{portal_url}}'
+WHERE slug = 'ticket_updated' AND is_system = true AND updated_at IS NULL;
+
+UPDATE email_templates SET
+    subject = 'Invoice #{{invoice_number}} from {{brand.company_name}}',
+    html_body = '<html>
+<body style="margin:0;padding:20px;background:#f5f5f5;font-family:Arial,sans-serif;">
+  <div style="max-width:600px;margin:0 auto;background:#ffffff;border-radius:8px;overflow:hidden;">
+    <div style="background:{{brand.primary_color}};color:#ffffff;padding:20px;text-align:center;">
+      {{brand.logo}}
+      <h1 style="margin:8px 0 0;font-size:22px;">Invoice</h1>
+    </div>
+    <div style="padding:30px;">
+      <p>Hello {{client_name}},</p>
+      <p>Your invoice for {{description}} is ready.</p>
+      <p><strong>Invoice #:</strong> {{invoice_number}}<br>
+         <strong>Date:</strong> {{invoice_date}}<br>
+         <strong>Due Date:</strong> {{due_date}}</p>
+      <p style="font-size:22px;font-weight:bold;color:{{brand.primary_color}};">Amount Due: ${{amount}}</p>
+      <p><a href="{{portal_url}}" style="color:{{brand.primary_color}};">View invoice</a></p>
+    </div>
+    <div style="background:{{brand.accent_color}};padding:16px;text-align:center;color:#666666;font-size:13px;">{{brand.footer}}</div>
+  </div>
+</body>
+</html>',
+    text_body = 'Invoice #{{invoice_number}} - Amount Due: ${{amount}}
+
+Due Date: {{due_date}}
+
+View invoice: {{portal_url}}'
+WHERE slug = 'invoice_sent' AND is_system = true AND updated_at IS NULL;
+
+UPDATE email_templates SET variables = variables || '["description"]'::jsonb
+WHERE slug = 'invoice_sent' AND NOT variables ? 'description';
+
+INSERT INTO email_templates (name, slug, subject, html_body, text_body, description, category, is_system, variables) VALUES (
+    'Payment Reminder',
+    'invoice_reminder',
+    'Payment reminder: invoice #{{invoice_number}} ({{reminder_status}})',
+    '<html>
+<body style="margin:0;padding:20px;background:#f5f5f5;font-family:Arial,sans-serif;">
+  <div style="max-width:600px;margin:0 auto;background:#ffffff;border-radius:8px;overflow:hidden;">
+    <div style="background:{{brand.primary_color}};color:#ffffff;padding:20px;text-align:center;">
+      {{brand.logo}}
+      <h1 style="margin:8px 0 0;font-size:22px;">Payment Reminder</h1>
+    </div>
+    <div style="padding:30px;">
+      <p>Hello {{client_name}},</p>
+      <p>Invoice <strong>#{{invoice_number}}</strong> for <strong>${{amount}}</strong> is due on {{due_date}} ({{reminder_status}}).</p>
+      <p>{{reminder_message}}</p>
+      <p>If you have already paid, please disregard this reminder.</p>
+      <p><a href="{{portal_url}}" style="color:{{brand.primary_color}};">View invoice</a></p>
+    </div>
+    <div style="background:{{brand.accent_color}};padding:16px;text-align:center;color:#666666;font-size:13px;">{{brand.footer}}</div>
+  </div>
+</body>
+</html>',
+    'Invoice #{{invoice_number}} for ${{amount}} is due on {{due_date}} ({{reminder_status}}).
+
+{{reminder_message}}
+
+View invoice: {{portal_url}}',
+    'Sent by recurring billing before and after an invoice''s due date',
+    'invoice',
+    true,
+    '["invoice_number", "amount", "due_date", "reminder_status", "reminder_message", "client_name", "portal_url"]'::jsonb
+)
+ON CONFLICT (slug) DO NOTHING;
@@ -0,0 +1,23 @@
+-- Single running timer per user
+-- A running timer is a time entry without an end_time. Starting a timer
+-- while another runs stops the old one; auto_stopped records that it was
+-- closed that way rather than by the user.
+
+ALTER TABLE time_entries ADD COLUMN IF NOT EXISTS auto_stopped BOOLEAN NOT NULL DEFAULT false;
+
+-- Close all but the newest running timer per user before enforcing the index
+UPDATE time_entries te SET
+    end_time = NOW(),
+    duration_minutes = GREATEST(EXTRACT(EPOCH FROM (NOW() - te.start_time))::int / 60, 0),
+    auto_stopped = true,
+    updated_at = NOW()
+WHERE te.end_time IS NULL
+  AND EXISTS (
+      SELECT 1 FROM time_entries newer
+      WHERE newer.user_id = te.user_id
+        AND newer.end_time IS NULL
+        AND (newer.start_time, newer.id) > (te.start_time, te.id)
+  );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_time_entries_one_running_per_user
+    ON time_entries(user_id) WHERE end_time IS NULL;
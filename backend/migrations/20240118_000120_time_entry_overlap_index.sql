@@ -0,0 +1,9 @@
+-- Time Entry Overlap Index
+-- Supports finding a user's entries whose [start_time, end_time) interval
+-- intersects another. Running timers extend to infinity. Queries must use
+-- the same tstzrange expression for the index to apply.
+
+CREATE EXTENSION IF NOT EXISTS btree_gist;
+
+CREATE INDEX IF NOT EXISTS idx_time_entries_user_period ON time_entries
+    USING GIST (user_id, tstzrange(start_time, COALESCE(end_time, 'infinity'::timestamptz), '[)'));
@@ -0,0 +1,23 @@
+-- Time Rounding
+-- Billing increments for time, per client with per-contract overrides: an
+-- increment in minutes, a direction (up, nearest, down) and a minimum
+-- charge. time_entries keeps the raw duration_minutes; billable_minutes is
+-- the rounded duration that total_amount is priced on.
+
+ALTER TABLE clients
+    ADD COLUMN IF NOT EXISTS time_rounding_increment INTEGER CHECK (time_rounding_increment > 0),
+    ADD COLUMN IF NOT EXISTS time_rounding_direction VARCHAR(10) NOT NULL DEFAULT 'up'
+        CHECK (time_rounding_direction IN ('up', 'nearest', 'down')),
+    ADD COLUMN IF NOT EXISTS time_rounding_minimum INTEGER CHECK (time_rounding_minimum >= 0);
+
+ALTER TABLE contracts
+    ADD COLUMN IF NOT EXISTS time_rounding_increment INTEGER CHECK (time_rounding_increment > 0),
+    ADD COLUMN IF NOT EXISTS time_rounding_direction VARCHAR(10) NOT NULL DEFAULT 'up'
+        CHECK (time_rounding_direction IN ('up', 'nearest', 'down')),
+    ADD COLUMN IF NOT EXISTS time_rounding_minimum INTEGER CHECK (time_rounding_minimum >= 0);
+
+ALTER TABLE time_entries ADD COLUMN IF NOT EXISTS billable_minutes INTEGER;
+
+-- Existing entries were billed on their raw duration
+UPDATE time_entries SET billable_minutes = duration_minutes
+WHERE billable_minutes IS NULL AND duration_minutes IS NOT NULL;
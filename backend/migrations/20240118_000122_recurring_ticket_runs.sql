@@ -0,0 +1,21 @@
+-- Recurring Ticket Runs
+-- Schedules are advanced in the recurrence's own time zone so a 9 AM
+-- ticket stays at 9 AM across DST changes. anchor_day keeps monthly and
+-- longer schedules on their intended day after short months. Tickets
+-- record the recurrence and scheduled run that created them, unique per
+-- run, so a job that runs twice creates one ticket.
+
+ALTER TABLE recurring_tickets
+    ADD COLUMN IF NOT EXISTS timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+    ADD COLUMN IF NOT EXISTS anchor_day SMALLINT CHECK (anchor_day BETWEEN 1 AND 31);
+
+UPDATE recurring_tickets
+SET anchor_day = EXTRACT(DAY FROM next_run AT TIME ZONE timezone)::smallint
+WHERE anchor_day IS NULL AND frequency IN ('monthly', 'quarterly', 'yearly');
+
+ALTER TABLE tickets
+    ADD COLUMN IF NOT EXISTS recurring_ticket_id UUID REFERENCES recurring_tickets(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS recurring_run_at TIMESTAMPTZ;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tickets_recurring_run
+    ON tickets(recurring_ticket_id, recurring_run_at) WHERE recurring_ticket_id IS NOT NULL;
@@ -0,0 +1,20 @@
+-- Project Budget Alerts
+-- One row per consumption threshold a project has been alerted for, keyed on
+-- the budget at the time so raising a budget lets its thresholds fire again
+
+-- The comprehensive schema tracks expenses per project; make sure the column
+-- exists whichever expenses definition is in place
+ALTER TABLE expenses ADD COLUMN IF NOT EXISTS project_id UUID REFERENCES projects(id);
+
+CREATE INDEX IF NOT EXISTS idx_expenses_project ON expenses(project_id) WHERE project_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_time_entries_project ON time_entries(project_id) WHERE project_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS project_budget_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    threshold_percent INTEGER NOT NULL,
+    budget DECIMAL(15,2) NOT NULL,
+    alert_id UUID REFERENCES alerts(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (project_id, threshold_percent, budget)
+);
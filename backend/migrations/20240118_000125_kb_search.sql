@@ -0,0 +1,26 @@
+-- Knowledge Base Full-Text Search
+-- Weighted search vector over title, summary and content, maintained as a
+-- generated column, plus per-viewer view records used to debounce view counts
+
+ALTER TABLE kb_articles ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', COALESCE(title, '')), 'A') ||
+        setweight(to_tsvector('english', COALESCE(summary, '') || ' ' || COALESCE(excerpt, '')), 'B') ||
+        setweight(to_tsvector('english', COALESCE(content, '')), 'C')
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_kb_articles_search_vector ON kb_articles USING GIN(search_vector);
+
+-- Articles shared through the original `public` flag stay public now that
+-- searches scope on `is_public`
+UPDATE kb_articles SET is_public = true WHERE public = true AND NOT COALESCE(is_public, false);
+
+CREATE TABLE IF NOT EXISTS kb_article_views (
+    article_id UUID NOT NULL REFERENCES kb_articles(id) ON DELETE CASCADE,
+    -- A user id, or a hash of the session the viewer presented
+    viewer_key VARCHAR(128) NOT NULL,
+    viewed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (article_id, viewer_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_kb_article_views_viewed_at ON kb_article_views(viewed_at);
@@ -0,0 +1,25 @@
+-- Knowledge Base Article Versions
+-- A full snapshot of an article's text on every save, numbered per article.
+-- Reverting copies an older snapshot forward as a new version, so history is
+-- never rewritten.
+
+CREATE TABLE IF NOT EXISTS kb_article_versions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    article_id UUID NOT NULL REFERENCES kb_articles(id) ON DELETE CASCADE,
+    version_number INTEGER NOT NULL,
+    title VARCHAR(500) NOT NULL,
+    summary TEXT,
+    excerpt TEXT,
+    content TEXT NOT NULL,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- The version this one restored, when it came from a revert
+    reverted_from INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (article_id, version_number)
+);
+
+-- Existing articles start their history at their current text
+INSERT INTO kb_article_versions (article_id, version_number, title, summary, excerpt, content, author_id, created_at)
+SELECT id, 1, title, summary, excerpt, content, author_id, COALESCE(updated_at, created_at, NOW())
+FROM kb_articles
+ON CONFLICT (article_id, version_number) DO NOTHING;
@@ -0,0 +1,15 @@
+-- Knowledge Base Article Votes
+-- One helpful/not-helpful vote per article per voter, where a voter is a
+-- signed-in user or an anonymous portal session. Changing a vote updates the
+-- row, and the article's counts move with it.
+
+CREATE TABLE IF NOT EXISTS kb_article_votes (
+    article_id UUID NOT NULL REFERENCES kb_articles(id) ON DELETE CASCADE,
+    voter_key VARCHAR(128) NOT NULL,
+    helpful BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (article_id, voter_key)
+);
+
+ALTER TABLE kb_articles ADD COLUMN IF NOT EXISTS not_helpful_count INTEGER DEFAULT 0;
@@ -0,0 +1,31 @@
+-- Client Portal Tickets
+-- Tickets and replies need a user, so portal submissions are attributed to
+-- a built-in inactive "Portal" user that can't sign in; the submitting
+-- contact is kept on the ticket and reply. Replies gain an `internal` flag
+-- that hides them from the portal. Notes are always internal, whichever
+-- code path writes them.
+
+INSERT INTO users (id, email, password_hash, first_name, last_name, is_active, email_verified_at)
+VALUES ('00000000-0000-0000-0000-0000000070a1', 'client-portal@system.resolve', '!', 'Portal', 'Client', false, NOW())
+ON CONFLICT (id) DO NOTHING;
+
+ALTER TABLE ticket_replies ADD COLUMN IF NOT EXISTS internal BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE ticket_replies SET internal = true WHERE type = 'note' AND NOT internal;
+
+CREATE OR REPLACE FUNCTION ticket_replies_mark_internal()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.type = 'note' THEN
+        NEW.internal := true;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ticket_replies_mark_internal_trigger ON ticket_replies;
+CREATE TRIGGER ticket_replies_mark_internal_trigger
+    BEFORE INSERT OR UPDATE OF type, internal ON ticket_replies
+    FOR EACH ROW EXECUTE FUNCTION ticket_replies_mark_internal();
+
+CREATE INDEX IF NOT EXISTS idx_tickets_client_contact ON tickets(client_id, contact_id);
@@ -0,0 +1,33 @@
+-- Business Hours Calendar
+-- Weekly schedules and holidays for SLA due-time calculation. Schedules
+-- and holidays with no client are global; a client with its own schedule
+-- uses it instead of the global one, and observes both its own and the
+-- global holidays. Times are local to the client's primary location.
+
+ALTER TABLE locations ADD COLUMN IF NOT EXISTS timezone VARCHAR(50) DEFAULT 'UTC';
+ALTER TABLE locations ADD COLUMN IF NOT EXISTS is_primary BOOLEAN DEFAULT false;
+
+UPDATE business_hours SET timezone = 'UTC' WHERE timezone IS NULL;
+ALTER TABLE business_hours ALTER COLUMN timezone SET NOT NULL;
+ALTER TABLE business_hours ALTER COLUMN created_at SET NOT NULL;
+ALTER TABLE business_hours ADD CONSTRAINT business_hours_day_of_week_check CHECK (day_of_week BETWEEN 0 AND 6);
+ALTER TABLE business_hours ADD CONSTRAINT business_hours_window_check CHECK (end_time > start_time);
+
+CREATE INDEX IF NOT EXISTS idx_business_hours_client ON business_hours(client_id);
+
+CREATE TABLE IF NOT EXISTS business_holidays (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    client_id UUID REFERENCES clients(id) ON DELETE CASCADE,
+    holiday_date DATE NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_business_holidays_client_date
+    ON business_holidays (COALESCE(client_id, '00000000-0000-0000-0000-000000000000'::uuid), holiday_date);
+
+-- Default global schedule: Monday to Friday, 09:00-17:00
+INSERT INTO business_hours (client_id, day_of_week, start_time, end_time, timezone)
+SELECT NULL, d, '09:00', '17:00', 'UTC'
+FROM generate_series(1, 5) AS d
+WHERE NOT EXISTS (SELECT 1 FROM business_hours WHERE client_id IS NULL);
@@ -0,0 +1,19 @@
+-- Ticket SLA Timestamps
+-- The SLA due and response times that analytics and team reports read.
+-- They are set from the ticket's resolved SLA when it is created or its
+-- priority, category, queue or SLA changes; `sla_response_at` is stamped
+-- by the first agent reply. An SLA attached by hand is pinned and isn't
+-- replaced when the ticket is re-resolved.
+
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS sla_response_due TIMESTAMPTZ;
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS sla_resolution_due TIMESTAMPTZ;
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS sla_response_at TIMESTAMPTZ;
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS sla_policy_pinned BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE tickets SET
+    sla_response_due = COALESCE(sla_response_due, response_due_at),
+    sla_resolution_due = COALESCE(sla_resolution_due, resolution_due_at),
+    sla_response_at = COALESCE(sla_response_at, first_response_at)
+WHERE sla_response_due IS NULL OR sla_resolution_due IS NULL OR sla_response_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_tickets_sla_response_due ON tickets(sla_response_due) WHERE sla_response_at IS NULL;
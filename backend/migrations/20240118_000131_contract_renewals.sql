@@ -0,0 +1,34 @@
+-- Contract Renewals
+-- Each renewal extends a contract's end_date by its renewal term and is
+-- recorded in contract_renewals; renewed_by is NULL for automatic renewals.
+-- contract_renewal_reminders keeps one row per warning threshold a contract
+-- has been reminded for, keyed on the end date so a renewed period's
+-- thresholds fire again.
+
+ALTER TABLE contracts
+    ADD COLUMN IF NOT EXISTS renewal_term_months INTEGER NOT NULL DEFAULT 12 CHECK (renewal_term_months > 0);
+
+CREATE INDEX IF NOT EXISTS idx_contracts_end_date ON contracts(end_date) WHERE end_date IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS contract_renewals (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    contract_id UUID NOT NULL REFERENCES contracts(id) ON DELETE CASCADE,
+    previous_end_date DATE NOT NULL,
+    new_end_date DATE NOT NULL,
+    automatic BOOLEAN NOT NULL DEFAULT false,
+    renewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (contract_id, previous_end_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_contract_renewals_contract ON contract_renewals(contract_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS contract_renewal_reminders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    contract_id UUID NOT NULL REFERENCES contracts(id) ON DELETE CASCADE,
+    threshold_days INTEGER NOT NULL,
+    end_date DATE NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (contract_id, threshold_days, end_date)
+);
@@ -0,0 +1,25 @@
+-- Vendor Management
+-- Gives vendors the contact fields the vendor API exposes and an archived_at
+-- for soft deletes; archived vendors stay readable but can't take new
+-- expenses. expenses.vendor_id links an expense to a vendor record, with
+-- the free-text vendor column kept as the vendor's name.
+
+ALTER TABLE vendors
+    ADD COLUMN IF NOT EXISTS email VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS phone VARCHAR(50),
+    ADD COLUMN IF NOT EXISTS address TEXT,
+    ADD COLUMN IF NOT EXISTS city VARCHAR(100),
+    ADD COLUMN IF NOT EXISTS state VARCHAR(100),
+    ADD COLUMN IF NOT EXISTS zip VARCHAR(20),
+    ADD COLUMN IF NOT EXISTS contact_name VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS account_number VARCHAR(100),
+    ADD COLUMN IF NOT EXISTS payment_terms VARCHAR(50),
+    ADD COLUMN IF NOT EXISTS notes TEXT,
+    ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_vendors_active_name ON vendors(name) WHERE archived_at IS NULL;
+
+ALTER TABLE expenses
+    ADD COLUMN IF NOT EXISTS vendor_id UUID REFERENCES vendors(id);
+
+CREATE INDEX IF NOT EXISTS idx_expenses_vendor_id ON expenses(vendor_id) WHERE vendor_id IS NOT NULL;
@@ -0,0 +1,35 @@
+-- Expense Approval
+-- Expenses move submitted -> approved or rejected, and approved billable
+-- expenses become billed when an invoice picks them up. approved_by,
+-- approved_at and approval_notes record the review either way. Existing
+-- rows are mapped onto the new statuses; reimbursed expenses keep their
+-- status, which is final.
+
+UPDATE expenses SET status = CASE
+    WHEN invoice_id IS NOT NULL THEN 'billed'
+    WHEN status = 'reimbursed' THEN 'reimbursed'
+    WHEN status = 'approved' THEN 'approved'
+    WHEN status = 'rejected' THEN 'rejected'
+    ELSE 'submitted'
+END;
+
+ALTER TABLE expenses
+    ALTER COLUMN status SET DEFAULT 'submitted',
+    ALTER COLUMN status SET NOT NULL,
+    ADD COLUMN IF NOT EXISTS receipt_file_id UUID REFERENCES files(id) ON DELETE SET NULL;
+
+-- Approved billable expenses waiting for an invoice
+CREATE INDEX IF NOT EXISTS idx_expenses_unbilled ON expenses(client_id)
+    WHERE status = 'approved' AND is_billable = true AND invoice_id IS NULL;
+
+ALTER TABLE recurring_invoice_runs
+    ADD COLUMN IF NOT EXISTS expenses_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS expenses_amount DECIMAL(15,2) NOT NULL DEFAULT 0;
+
+INSERT INTO permissions (name, description, resource, action) VALUES
+    ('expenses.read', 'View expenses', 'expenses', 'read'),
+    ('expenses.create', 'Submit expenses', 'expenses', 'create'),
+    ('expenses.update', 'Update expenses', 'expenses', 'update'),
+    ('expenses.delete', 'Delete expenses', 'expenses', 'delete'),
+    ('expenses.approve', 'Approve and reject expenses', 'expenses', 'approve')
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,68 @@
+-- API Key Scopes and Quotas
+-- The password vault migration created its own api_keys table (stored
+-- third-party credentials) before the auth migration ran, so the auth
+-- table's CREATE TABLE IF NOT EXISTS never took effect. Move the vault
+-- table aside as vault_api_keys and create the auth table.
+--
+-- Keys now carry a daily request quota on top of the per-minute rate
+-- limit, are revoked rather than deleted so audit entries keep pointing at
+-- them (the audit foreign key refuses deleting a key that has entries),
+-- and record the address they were last used from.
+
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = 'api_keys' AND column_name = 'vault_id') THEN
+        ALTER TABLE audit_logs DROP CONSTRAINT IF EXISTS audit_logs_api_key_id_fkey;
+        UPDATE audit_logs SET api_key_id = NULL WHERE api_key_id IS NOT NULL;
+
+        ALTER TABLE api_keys RENAME TO vault_api_keys;
+        ALTER INDEX IF EXISTS idx_api_keys_vault_id RENAME TO idx_vault_api_keys_vault_id;
+        ALTER INDEX IF EXISTS idx_api_keys_client_id RENAME TO idx_vault_api_keys_client_id;
+        ALTER INDEX IF EXISTS idx_api_keys_service RENAME TO idx_vault_api_keys_service;
+        ALTER INDEX IF EXISTS idx_api_keys_type RENAME TO idx_vault_api_keys_type;
+        ALTER INDEX IF EXISTS idx_api_keys_expires RENAME TO idx_vault_api_keys_expires;
+        ALTER INDEX IF EXISTS idx_api_keys_active RENAME TO idx_vault_api_keys_active;
+    END IF;
+END $$;
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    key_hash VARCHAR(64) NOT NULL, -- SHA-256 hash
+    key_prefix VARCHAR(8) NOT NULL, -- First 8 chars for identification
+    scopes JSONB NOT NULL DEFAULT '[]'::jsonb,
+    expires_at TIMESTAMPTZ,
+    allowed_ips TEXT[] DEFAULT ARRAY[]::TEXT[],
+    rate_limit INTEGER DEFAULT 0, -- Requests per minute, 0 = unlimited
+    is_active BOOLEAN DEFAULT true,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ,
+    usage_count BIGINT DEFAULT 0,
+    UNIQUE(key_prefix)
+);
+
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS daily_quota INTEGER NOT NULL DEFAULT 0; -- Requests per UTC day, 0 = unlimited
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS revoked_at TIMESTAMPTZ;
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS last_used_ip VARCHAR(45);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_user_id ON api_keys(user_id);
+CREATE INDEX IF NOT EXISTS idx_api_keys_prefix ON api_keys(key_prefix) WHERE is_active = true;
+CREATE INDEX IF NOT EXISTS idx_api_keys_expires ON api_keys(expires_at) WHERE expires_at IS NOT NULL;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.table_constraints WHERE constraint_name = 'audit_logs_api_key_id_fkey') THEN
+        ALTER TABLE audit_logs ADD CONSTRAINT audit_logs_api_key_id_fkey
+            FOREIGN KEY (api_key_id) REFERENCES api_keys(id) ON DELETE RESTRICT;
+    END IF;
+END $$;
+
+-- Requests per key per UTC day, for the daily quota
+CREATE TABLE IF NOT EXISTS api_key_daily_usage (
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    usage_date DATE NOT NULL,
+    request_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (api_key_id, usage_date)
+);
@@ -0,0 +1,21 @@
+-- IP Address Management
+-- One row per address in a network that is in use or held back; any host
+-- address of the network without a row is free.
+
+CREATE TABLE IF NOT EXISTS ip_allocations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    network_id UUID NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    ip INET NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'used', -- used, reserved
+    assigned_asset_id UUID REFERENCES assets(id) ON DELETE SET NULL,
+    hostname VARCHAR(255),
+    notes TEXT,
+    allocated_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ,
+    CONSTRAINT ip_allocations_status_check CHECK (status IN ('used', 'reserved')),
+    UNIQUE(network_id, ip)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ip_allocations_network ON ip_allocations(network_id);
+CREATE INDEX IF NOT EXISTS idx_ip_allocations_asset ON ip_allocations(assigned_asset_id) WHERE assigned_asset_id IS NOT NULL;
@@ -0,0 +1,48 @@
+-- Scheduled Reports
+-- Reports rendered on a cadence and emailed to recipients. Each run keeps
+-- its rendered output so it can be downloaded again later.
+
+CREATE TABLE IF NOT EXISTS scheduled_reports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    report_type VARCHAR(30) NOT NULL, -- utilization, profitability, sla_compliance, client_summary
+    client_id UUID REFERENCES clients(id) ON DELETE CASCADE,
+    parameters JSONB NOT NULL DEFAULT '{}',
+    cadence VARCHAR(20) NOT NULL, -- daily, weekly, monthly
+    recipients TEXT[] NOT NULL DEFAULT '{}',
+    format VARCHAR(10) NOT NULL DEFAULT 'pdf', -- csv, xlsx, pdf
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    next_run_at TIMESTAMPTZ NOT NULL,
+    last_run_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ,
+    CONSTRAINT scheduled_reports_type_check
+        CHECK (report_type IN ('utilization', 'profitability', 'sla_compliance', 'client_summary')),
+    CONSTRAINT scheduled_reports_cadence_check CHECK (cadence IN ('daily', 'weekly', 'monthly')),
+    CONSTRAINT scheduled_reports_format_check CHECK (format IN ('csv', 'xlsx', 'pdf')),
+    CONSTRAINT scheduled_reports_client_summary_check
+        CHECK (report_type <> 'client_summary' OR client_id IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_reports_due ON scheduled_reports(next_run_at) WHERE is_active;
+CREATE INDEX IF NOT EXISTS idx_scheduled_reports_client ON scheduled_reports(client_id) WHERE client_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS scheduled_report_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    scheduled_report_id UUID NOT NULL REFERENCES scheduled_reports(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'running', -- running, completed, failed
+    period_start DATE NOT NULL,
+    period_end DATE NOT NULL,
+    filename VARCHAR(255),
+    content_type VARCHAR(100),
+    content BYTEA,
+    recipients_sent INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ,
+    CONSTRAINT scheduled_report_runs_status_check CHECK (status IN ('running', 'completed', 'failed'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_report_runs_report
+    ON scheduled_report_runs(scheduled_report_id, started_at DESC);
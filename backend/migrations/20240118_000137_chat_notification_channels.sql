@@ -0,0 +1,39 @@
+-- Chat Notification Channels
+-- Routes ticket and operations events to a Teams or Slack channel. The
+-- channel's incoming webhook URL is stored, encrypted, on the integration
+-- it references.
+
+CREATE TABLE IF NOT EXISTS chat_notification_channels (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(100) NOT NULL,
+    provider VARCHAR(20) NOT NULL, -- teams, slack
+    integration_id UUID NOT NULL REFERENCES integrations(id) ON DELETE CASCADE,
+    channel VARCHAR(255), -- Slack channel override, e.g. #noc
+    event_types TEXT[] NOT NULL DEFAULT '{}',
+    queue_ids UUID[] NOT NULL DEFAULT '{}', -- ticket events only for these queues (empty = all)
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    last_sent_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ,
+    CONSTRAINT chat_notification_channels_provider_check CHECK (provider IN ('teams', 'slack'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_notification_channels_events
+    ON chat_notification_channels USING GIN (event_types) WHERE enabled;
+
+CREATE TABLE IF NOT EXISTS chat_notification_deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    channel_id UUID NOT NULL REFERENCES chat_notification_channels(id) ON DELETE CASCADE,
+    event_type VARCHAR(50) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL, -- succeeded, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_status_code INTEGER,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_notification_deliveries_channel
+    ON chat_notification_deliveries(channel_id, created_at DESC);
@@ -0,0 +1,22 @@
+-- Job Run History
+-- One row per background job run, scheduled or triggered by an admin, so
+-- failures stay visible after a restart.
+
+CREATE TABLE IF NOT EXISTS job_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_name VARCHAR(100) NOT NULL, -- scheduler job key, e.g. sla_checker
+    trigger VARCHAR(20) NOT NULL DEFAULT 'schedule', -- schedule, manual
+    triggered_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'running',
+    items_processed INTEGER NOT NULL DEFAULT 0,
+    error TEXT, -- why a failed run failed
+    errors JSONB NOT NULL DEFAULT '[]', -- per-item errors from a partial failure
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ,
+    duration_ms BIGINT,
+    CONSTRAINT job_runs_trigger_check CHECK (trigger IN ('schedule', 'manual')),
+    CONSTRAINT job_runs_status_check CHECK (status IN ('running', 'completed', 'partial_failure', 'failed'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_runs_job_started ON job_runs(job_name, started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_job_runs_failed ON job_runs(started_at DESC) WHERE status = 'failed';
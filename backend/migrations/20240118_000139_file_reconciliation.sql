@@ -0,0 +1,41 @@
+-- File Reconciliation
+-- Lets a file's parent be hard-deleted and remembers when that happened, so
+-- the maintenance job can purge files nothing owns any more.
+
+ALTER TABLE files ADD COLUMN IF NOT EXISTS orphaned_at TIMESTAMPTZ;
+
+ALTER TABLE files
+    DROP CONSTRAINT IF EXISTS files_client_id_fkey,
+    DROP CONSTRAINT IF EXISTS files_ticket_id_fkey,
+    DROP CONSTRAINT IF EXISTS files_asset_id_fkey,
+    DROP CONSTRAINT IF EXISTS files_project_id_fkey,
+    DROP CONSTRAINT IF EXISTS files_kb_article_id_fkey;
+
+ALTER TABLE files
+    ADD CONSTRAINT files_client_id_fkey FOREIGN KEY (client_id) REFERENCES clients(id) ON DELETE SET NULL,
+    ADD CONSTRAINT files_ticket_id_fkey FOREIGN KEY (ticket_id) REFERENCES tickets(id) ON DELETE SET NULL,
+    ADD CONSTRAINT files_asset_id_fkey FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE SET NULL,
+    ADD CONSTRAINT files_project_id_fkey FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE SET NULL,
+    ADD CONSTRAINT files_kb_article_id_fkey FOREIGN KEY (kb_article_id) REFERENCES kb_articles(id) ON DELETE SET NULL;
+
+-- Parent references are only ever cleared by the SET NULL actions above, so
+-- losing one means the entity the file belonged to was deleted
+CREATE OR REPLACE FUNCTION mark_orphaned_file() RETURNS TRIGGER AS $$
+BEGIN
+    IF (OLD.client_id IS NOT NULL AND NEW.client_id IS NULL)
+        OR (OLD.ticket_id IS NOT NULL AND NEW.ticket_id IS NULL)
+        OR (OLD.asset_id IS NOT NULL AND NEW.asset_id IS NULL)
+        OR (OLD.project_id IS NOT NULL AND NEW.project_id IS NULL)
+        OR (OLD.kb_article_id IS NOT NULL AND NEW.kb_article_id IS NULL) THEN
+        NEW.orphaned_at := COALESCE(NEW.orphaned_at, NOW());
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS files_mark_orphaned ON files;
+CREATE TRIGGER files_mark_orphaned
+    BEFORE UPDATE OF client_id, ticket_id, asset_id, project_id, kb_article_id ON files
+    FOR EACH ROW EXECUTE FUNCTION mark_orphaned_file();
+
+CREATE INDEX IF NOT EXISTS idx_files_orphaned ON files(orphaned_at) WHERE orphaned_at IS NOT NULL;
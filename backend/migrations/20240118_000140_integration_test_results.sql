@@ -0,0 +1,6 @@
+-- Integration Test Results
+-- The last connection test for each integration, returned again for a short
+-- while instead of re-testing against the provider.
+
+ALTER TABLE integrations ADD COLUMN IF NOT EXISTS last_test_result JSONB;
+ALTER TABLE integrations ADD COLUMN IF NOT EXISTS last_tested_at TIMESTAMPTZ;
@@ -0,0 +1,27 @@
+-- Azure Cost Alerts
+-- Budget notifications received from Azure Cost Management, kept verbatim
+-- for audit. Only the first notification per subscription, budget, month
+-- and threshold opens an alert; repeats are stored flagged as duplicates.
+
+ALTER TABLE azure_subscriptions ADD COLUMN IF NOT EXISTS asset_id UUID REFERENCES assets(id) ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS azure_cost_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    azure_subscription_id UUID REFERENCES azure_subscriptions(id) ON DELETE SET NULL,
+    subscription_id VARCHAR(255) NOT NULL,
+    budget_name VARCHAR(255) NOT NULL,
+    period_start DATE NOT NULL,
+    threshold_amount DECIMAL(15,2) NOT NULL,
+    spent_amount DECIMAL(15,2) NOT NULL,
+    budget_amount DECIMAL(15,2),
+    currency VARCHAR(10),
+    alert_id UUID REFERENCES alerts(id) ON DELETE SET NULL,
+    duplicate BOOLEAN NOT NULL DEFAULT false,
+    raw_payload JSONB NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_azure_cost_alerts_dedup
+    ON azure_cost_alerts(subscription_id, budget_name, period_start, threshold_amount)
+    WHERE NOT duplicate;
+CREATE INDEX IF NOT EXISTS idx_azure_cost_alerts_received ON azure_cost_alerts(received_at);
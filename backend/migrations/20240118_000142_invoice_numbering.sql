@@ -0,0 +1,29 @@
+-- Invoice Numbering
+-- Configurable invoice number format (prefix, padding, reset cadence) and a
+-- per-period counter that is incremented inside the invoice's transaction
+
+ALTER TABLE company_settings ADD COLUMN IF NOT EXISTS invoice_number_prefix VARCHAR(50) NOT NULL DEFAULT 'INV-';
+ALTER TABLE company_settings ADD COLUMN IF NOT EXISTS invoice_number_padding INTEGER NOT NULL DEFAULT 5
+    CHECK (invoice_number_padding BETWEEN 1 AND 12);
+ALTER TABLE company_settings ADD COLUMN IF NOT EXISTS invoice_number_reset VARCHAR(10) NOT NULL DEFAULT 'never'
+    CHECK (invoice_number_reset IN ('never', 'yearly', 'monthly'));
+
+-- One counter per numbering period: 'all' when numbers never reset,
+-- otherwise the year ('2024') or month ('2024-03')
+CREATE TABLE IF NOT EXISTS invoice_number_sequences (
+    period_key VARCHAR(20) PRIMARY KEY,
+    last_value BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Carry on from the numbers already issued so the first new invoice
+-- doesn't reuse one
+INSERT INTO invoice_number_sequences (period_key, last_value)
+SELECT 'all', GREATEST(
+    COUNT(*),
+    COALESCE(MAX(CAST(SUBSTRING(number FROM '^INV-(\d+)$') AS BIGINT)), 0)
+)
+FROM invoices
+ON CONFLICT (period_key) DO NOTHING;
+
+COMMENT ON TABLE invoice_number_sequences IS 'Last invoice number issued in each numbering period';
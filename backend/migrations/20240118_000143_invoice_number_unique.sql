@@ -0,0 +1,10 @@
+-- Invoice Number Uniqueness
+-- Generated numbers are retried on conflict, which relies on this constraint;
+-- databases created before it was part of the base schema may lack it
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.table_constraints WHERE constraint_name = 'invoices_number_key') THEN
+        ALTER TABLE invoices ADD CONSTRAINT invoices_number_key UNIQUE (number);
+    END IF;
+END $$;
@@ -0,0 +1,30 @@
+-- Data Retention and Anonymization
+-- How long audit logs, closed tickets and notifications are kept, and
+-- markers for clients, contacts and tickets whose personal data was scrubbed
+
+CREATE TABLE IF NOT EXISTS retention_policies (
+    entity_type VARCHAR(30) PRIMARY KEY
+        CHECK (entity_type IN ('audit_logs', 'closed_tickets', 'notifications')),
+    retention_days INTEGER NOT NULL CHECK (retention_days > 0),
+    enabled BOOLEAN NOT NULL DEFAULT false,
+    last_run_at TIMESTAMPTZ,
+    last_affected INTEGER,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Audit logs keep the year the maintenance job already kept; the others
+-- stay off until an administrator sets a limit
+INSERT INTO retention_policies (entity_type, retention_days, enabled) VALUES
+    ('audit_logs', 365, true),
+    ('closed_tickets', 2555, false),
+    ('notifications', 90, false)
+ON CONFLICT (entity_type) DO NOTHING;
+
+ALTER TABLE clients ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
+ALTER TABLE contacts ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_tickets_closed_retention
+    ON tickets(COALESCE(closed_at, updated_at)) WHERE status = 'closed' AND anonymized_at IS NULL;
+
+COMMENT ON TABLE retention_policies IS 'Per entity type retention limits applied by the data_retention job';
@@ -0,0 +1,8 @@
+-- Ticket Reply Attachments
+-- Uploaded files can be attached to a ticket reply. The file also gets the
+-- reply's ticket, so it still lists with the ticket's files; it is seen
+-- only by those who can see the reply.
+
+ALTER TABLE files ADD COLUMN IF NOT EXISTS ticket_reply_id UUID REFERENCES ticket_replies(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_files_ticket_reply ON files(ticket_reply_id) WHERE ticket_reply_id IS NOT NULL;
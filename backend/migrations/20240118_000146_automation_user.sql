@@ -0,0 +1,8 @@
+-- Automation User
+-- Status changes made by jobs and integrations are written to the ticket's
+-- history and audit trail like anyone else's, so they need a user: a
+-- built-in inactive "Automation" user that can't sign in.
+
+INSERT INTO users (id, email, password_hash, first_name, last_name, is_active, email_verified_at)
+VALUES ('00000000-0000-0000-0000-00000000a070', 'automation@system.resolve', '!', 'Automation', 'System', false, NOW())
+ON CONFLICT (id) DO NOTHING;
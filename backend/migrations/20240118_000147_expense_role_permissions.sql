@@ -0,0 +1,19 @@
+-- Expense Role Permissions
+-- The expense approval migration added the expenses.* permissions but gave
+-- them to no role, so only admins could approve or delete expenses. Grant
+-- them along the lines of the built-in roles: technicians submit and edit
+-- their expenses, managers and billing also delete and approve them.
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id
+FROM roles r
+JOIN permissions p ON p.name = ANY(
+    CASE LOWER(r.name)
+        WHEN 'admin' THEN ARRAY['expenses.read', 'expenses.create', 'expenses.update', 'expenses.delete', 'expenses.approve']
+        WHEN 'manager' THEN ARRAY['expenses.read', 'expenses.create', 'expenses.update', 'expenses.delete', 'expenses.approve']
+        WHEN 'billing' THEN ARRAY['expenses.read', 'expenses.create', 'expenses.update', 'expenses.delete', 'expenses.approve']
+        WHEN 'technician' THEN ARRAY['expenses.read', 'expenses.create', 'expenses.update']
+        ELSE ARRAY[]::TEXT[]
+    END
+)
+ON CONFLICT DO NOTHING;
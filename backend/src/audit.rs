@@ -0,0 +1,165 @@
+//! Writing the audit trail
+//!
+//! Every handler that changes a record writes one `audit_logs` row through
+//! [`record`]: who did it, to which entity, the entity before and after
+//! the change, and the caller's IP and user agent taken from the request.
+//! Reading the trail back lives in [`crate::services::audit_log_query`].
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use uuid::Uuid;
+
+use crate::middleware::rate_limit;
+
+/// Caller details recorded alongside each entry
+#[derive(Debug, Clone, Default)]
+pub struct RequestMeta {
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl RequestMeta {
+    /// `peer` is the address the request came in on. The client IP is that
+    /// peer, or behind a trusted proxy the client it forwarded for, so a
+    /// spoofed `X-Forwarded-For` isn't recorded.
+    pub fn new(peer: Option<IpAddr>, headers: &HeaderMap) -> Self {
+        let ip_address = rate_limit::client_ip(peer, headers, rate_limit::trusted_proxies());
+
+        let user_agent = headers
+            .get("user-agent")
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        Self { ip_address, user_agent }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestMeta {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+        Ok(Self::new(peer, &parts.headers))
+    }
+}
+
+/// One `audit_logs` row, built up before [`record`]ing it
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub user_id: Option<Uuid>,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    pub old_values: Option<Value>,
+    pub new_values: Option<Value>,
+}
+
+impl AuditEvent {
+    pub fn new(user_id: Uuid, action: &str, entity_type: &str, entity_id: Uuid) -> Self {
+        Self {
+            user_id: Some(user_id),
+            action: action.to_string(),
+            entity_type: entity_type.to_string(),
+            entity_id,
+            old_values: None,
+            new_values: None,
+        }
+    }
+
+    /// The entity as it was before the change
+    pub fn before<T: Serialize>(mut self, value: &T) -> Self {
+        self.old_values = snapshot(value);
+        self
+    }
+
+    /// The entity as it is after the change
+    pub fn after<T: Serialize>(mut self, value: &T) -> Self {
+        self.new_values = snapshot(value);
+        self
+    }
+}
+
+fn snapshot<T: Serialize>(value: &T) -> Option<Value> {
+    match serde_json::to_value(value) {
+        Ok(Value::Null) => None,
+        Ok(value) => Some(value),
+        Err(e) => {
+            tracing::warn!("Failed to serialize audit snapshot: {}", e);
+            None
+        }
+    }
+}
+
+/// Write an entry. Failures are logged rather than returned so a broken
+/// audit insert never fails the change it describes.
+pub async fn record(pool: &PgPool, meta: &RequestMeta, event: AuditEvent) {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, old_values, new_values,
+                                ip_address, user_agent, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7::inet, $8, NOW())
+        "#
+    )
+    .bind(event.user_id)
+    .bind(&event.action)
+    .bind(&event.entity_type)
+    .bind(event.entity_id)
+    .bind(&event.old_values)
+    .bind(&event.new_values)
+    .bind(meta.ip_address.map(|ip| ip.to_string()))
+    .bind(&meta.user_agent)
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        tracing::warn!(
+            "Failed to record audit entry {} {} {}: {}",
+            event.action,
+            event.entity_type,
+            event.entity_id,
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_meta_ignores_spoofed_forwarded_for() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.7, 10.0.0.1"));
+        headers.insert("user-agent", HeaderValue::from_static("resolve-cli/1.2"));
+
+        let meta = RequestMeta::new(Some("198.51.100.4".parse().unwrap()), &headers);
+        assert_eq!(meta.ip_address, Some("198.51.100.4".parse().unwrap()));
+        assert_eq!(meta.user_agent.as_deref(), Some("resolve-cli/1.2"));
+
+        assert_eq!(RequestMeta::new(None, &headers).ip_address, None);
+        assert!(RequestMeta::new(None, &HeaderMap::new()).user_agent.is_none());
+    }
+
+    #[test]
+    fn test_event_snapshots() {
+        let id = Uuid::new_v4();
+        let event = AuditEvent::new(id, "UPDATE", "client", id)
+            .before(&json!({ "name": "Acme" }))
+            .after(&Option::<Value>::None);
+
+        assert_eq!(event.old_values, Some(json!({ "name": "Acme" })));
+        assert_eq!(event.new_values, None);
+    }
+}
@@ -9,7 +9,7 @@ use axum::{
     routing::{delete, get, post},
     Json, Router,
 };
-use chrono::{Duration, Utc};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
@@ -19,22 +19,32 @@ use super::api_keys::{
     CreateApiKeyRequest, CreateApiKeyResponse,
 };
 use super::middleware::AuthUser;
+use crate::audit::RequestMeta;
 use crate::error::{AppError, ApiResult};
 use crate::AppState;
 
+const API_KEY_LIST_COLUMNS: &str = "id, name, description, key_prefix, scopes, expires_at, rate_limit,
+    daily_quota, is_active, created_at, revoked_at, last_used_at, last_used_ip, usage_count";
+
 /// Response for listing API keys (doesn't include the actual key)
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, sqlx::FromRow)]
 pub struct ApiKeyListItem {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
     pub key_prefix: String,
-    pub scopes: Vec<ApiKeyScope>,
+    pub scopes: sqlx::types::Json<Vec<ApiKeyScope>>,
     pub expires_at: Option<chrono::DateTime<Utc>>,
-    pub is_active: bool,
-    pub created_at: chrono::DateTime<Utc>,
+    /// Requests per minute, 0 = unlimited
+    pub rate_limit: Option<i32>,
+    /// Requests per UTC day, 0 = unlimited
+    pub daily_quota: i32,
+    pub is_active: Option<bool>,
+    pub created_at: Option<chrono::DateTime<Utc>>,
+    pub revoked_at: Option<chrono::DateTime<Utc>>,
     pub last_used_at: Option<chrono::DateTime<Utc>>,
-    pub usage_count: i64,
+    pub last_used_ip: Option<String>,
+    pub usage_count: Option<i64>,
 }
 
 /// Request to update an API key
@@ -55,45 +65,20 @@ pub fn api_key_routes() -> Router<Arc<AppState>> {
         .route("/:id/regenerate", post(regenerate_api_key))
 }
 
-/// List all API keys for the current user
+/// List all API keys for the current user, revoked ones included
 async fn list_api_keys(
     State(state): State<Arc<AppState>>,
     AuthUser(user): AuthUser,
 ) -> ApiResult<impl IntoResponse> {
-    let keys = sqlx::query_as!(
-        ApiKeyListItem,
-        r#"
-        SELECT
-            id, name, description, key_prefix,
-            scopes as "scopes: sqlx::types::Json<Vec<ApiKeyScope>>",
-            expires_at, is_active, created_at, last_used_at, usage_count
-        FROM api_keys
-        WHERE user_id = $1
-        ORDER BY created_at DESC
-        "#,
-        user.id
-    )
+    let keys = sqlx::query_as::<_, ApiKeyListItem>(&format!(
+        "SELECT {} FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC",
+        API_KEY_LIST_COLUMNS
+    ))
+    .bind(user.id)
     .fetch_all(&state.db_pool)
     .await
     .map_err(|e| AppError::DatabaseError(e.to_string()))?;
 
-    // Map to response format
-    let keys: Vec<ApiKeyListItem> = keys
-        .into_iter()
-        .map(|k| ApiKeyListItem {
-            id: k.id,
-            name: k.name,
-            description: k.description,
-            key_prefix: k.key_prefix,
-            scopes: k.scopes,
-            expires_at: k.expires_at,
-            is_active: k.is_active,
-            created_at: k.created_at,
-            last_used_at: k.last_used_at,
-            usage_count: k.usage_count,
-        })
-        .collect();
-
     Ok(Json(keys))
 }
 
@@ -109,23 +94,19 @@ async fn create_api_key(
     // Generate the key
     let (key, prefix, hash) = generate_api_key();
 
-    // Calculate expiration
-    let expires_at = req
-        .expires_in_days
-        .map(|days| Utc::now() + Duration::days(days as i64));
-
     let key_id = Uuid::new_v4();
     let now = Utc::now();
+    let expires_at = req.expiry(now);
 
     // Store in database
     sqlx::query!(
         r#"
         INSERT INTO api_keys (
             id, user_id, name, description, key_hash, key_prefix,
-            scopes, expires_at, allowed_ips, rate_limit, is_active,
+            scopes, expires_at, allowed_ips, rate_limit, daily_quota, is_active,
             created_at, usage_count
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, true, $11, 0)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, true, $12, 0)
         "#,
         key_id,
         user.id,
@@ -137,6 +118,7 @@ async fn create_api_key(
         expires_at,
         &req.allowed_ips,
         req.rate_limit.unwrap_or(0) as i32,
+        req.daily_quota.unwrap_or(0) as i32,
         now
     )
     .execute(&state.db_pool)
@@ -162,19 +144,12 @@ async fn get_api_key(
     AuthUser(user): AuthUser,
     Path(key_id): Path<Uuid>,
 ) -> ApiResult<impl IntoResponse> {
-    let key = sqlx::query_as!(
-        ApiKeyListItem,
-        r#"
-        SELECT
-            id, name, description, key_prefix,
-            scopes as "scopes: sqlx::types::Json<Vec<ApiKeyScope>>",
-            expires_at, is_active, created_at, last_used_at, usage_count
-        FROM api_keys
-        WHERE id = $1 AND user_id = $2
-        "#,
-        key_id,
-        user.id
-    )
+    let key = sqlx::query_as::<_, ApiKeyListItem>(&format!(
+        "SELECT {} FROM api_keys WHERE id = $1 AND user_id = $2",
+        API_KEY_LIST_COLUMNS
+    ))
+    .bind(key_id)
+    .bind(user.id)
     .fetch_optional(&state.db_pool)
     .await
     .map_err(|e| AppError::DatabaseError(e.to_string()))?
@@ -183,17 +158,19 @@ async fn get_api_key(
     Ok(Json(key))
 }
 
-/// Revoke (delete) an API key
+/// Revoke an API key. It stops working at once but stays listed, so
+/// audit entries made with it still resolve.
 async fn revoke_api_key(
     State(state): State<Arc<AppState>>,
     AuthUser(user): AuthUser,
     Path(key_id): Path<Uuid>,
 ) -> ApiResult<impl IntoResponse> {
-    let result = sqlx::query!(
-        "DELETE FROM api_keys WHERE id = $1 AND user_id = $2",
-        key_id,
-        user.id
+    let result = sqlx::query(
+        "UPDATE api_keys SET is_active = false, revoked_at = NOW()
+         WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL"
     )
+    .bind(key_id)
+    .bind(user.id)
     .execute(&state.db_pool)
     .await
     .map_err(|e| AppError::DatabaseError(e.to_string()))?;
@@ -216,7 +193,7 @@ async fn regenerate_api_key(
         r#"
         SELECT name, description, scopes, expires_at, allowed_ips, rate_limit
         FROM api_keys
-        WHERE id = $1 AND user_id = $2
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
         "#,
         key_id,
         user.id
@@ -267,6 +244,7 @@ async fn regenerate_api_key(
 pub async fn validate_api_key_header(
     state: &AppState,
     headers: &HeaderMap,
+    meta: &RequestMeta,
     required_scope: Option<&ApiKeyScope>,
 ) -> ApiResult<ApiKey> {
     let auth_header = headers
@@ -289,10 +267,10 @@ pub async fn validate_api_key_header(
         r#"
         SELECT
             id, user_id, name, description, key_hash, key_prefix,
-            scopes, expires_at, allowed_ips, rate_limit, is_active,
-            created_at, last_used_at, usage_count
+            scopes, expires_at, allowed_ips, rate_limit, daily_quota, is_active,
+            created_at, revoked_at, last_used_at, last_used_ip, usage_count
         FROM api_keys
-        WHERE key_prefix = $1 AND is_active = true
+        WHERE key_prefix = $1 AND is_active = true AND revoked_at IS NULL
         "#,
         prefix
     )
@@ -321,16 +299,18 @@ pub async fn validate_api_key_header(
     if let Some(required) = required_scope {
         if !has_scope(&scopes, required) {
             return Err(AppError::InsufficientPermissions {
-                required: format!("{:?}", required),
+                required: required.as_str().to_string(),
             });
         }
     }
 
-    // Update last used timestamp
-    sqlx::query!(
-        "UPDATE api_keys SET last_used_at = NOW(), usage_count = usage_count + 1 WHERE id = $1",
-        stored_key.id
+    // Record the use
+    let client_ip = meta.ip_address.map(|ip| ip.to_string());
+    sqlx::query(
+        "UPDATE api_keys SET last_used_at = NOW(), last_used_ip = $2, usage_count = usage_count + 1 WHERE id = $1"
     )
+    .bind(stored_key.id)
+    .bind(&client_ip)
     .execute(&state.db_pool)
     .await
     .ok(); // Don't fail the request if this update fails
@@ -348,9 +328,12 @@ pub async fn validate_api_key_header(
         expires_at: stored_key.expires_at,
         allowed_ips,
         rate_limit: stored_key.rate_limit.unwrap_or(0) as u32,
+        daily_quota: stored_key.daily_quota as u32,
         is_active: stored_key.is_active,
         created_at: stored_key.created_at,
-        last_used_at: stored_key.last_used_at,
+        revoked_at: stored_key.revoked_at,
+        last_used_at: Some(Utc::now()),
+        last_used_ip: client_ip,
         usage_count: stored_key.usage_count as u64,
     })
 }
@@ -37,46 +37,92 @@ pub struct ApiKey {
     pub allowed_ips: Vec<String>,
     /// Rate limit (requests per minute, 0 = unlimited)
     pub rate_limit: u32,
+    /// Request quota per UTC day (0 = unlimited)
+    pub daily_quota: u32,
     /// Whether key is currently active
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
     pub last_used_at: Option<DateTime<Utc>>,
+    /// Address of the most recent request made with the key
+    pub last_used_ip: Option<String>,
     /// Number of times this key has been used
     pub usage_count: u64,
 }
 
-/// API Key scope/permission
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-#[serde(rename_all = "snake_case")]
+/// API Key scope/permission, written `<resource>:<access>`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ApiKeyScope {
     // Read scopes
+    #[serde(rename = "clients:read", alias = "read_clients")]
     ReadClients,
+    #[serde(rename = "tickets:read", alias = "read_tickets")]
     ReadTickets,
+    #[serde(rename = "assets:read", alias = "read_assets")]
     ReadAssets,
+    #[serde(rename = "passwords:read", alias = "read_passwords")]
     ReadPasswords,
+    #[serde(rename = "documentation:read", alias = "read_documentation")]
     ReadDocumentation,
+    #[serde(rename = "invoices:read", alias = "read_invoices")]
     ReadInvoices,
+    #[serde(rename = "reports:read", alias = "read_reports")]
     ReadReports,
 
     // Write scopes
+    #[serde(rename = "clients:write", alias = "write_clients")]
     WriteClients,
+    #[serde(rename = "tickets:write", alias = "write_tickets")]
     WriteTickets,
+    #[serde(rename = "assets:write", alias = "write_assets")]
     WriteAssets,
+    #[serde(rename = "passwords:write", alias = "write_passwords")]
     WritePasswords,
+    #[serde(rename = "documentation:write", alias = "write_documentation")]
     WriteDocumentation,
+    #[serde(rename = "invoices:write", alias = "write_invoices")]
     WriteInvoices,
 
     // Admin scopes
+    #[serde(rename = "users:manage", alias = "manage_users")]
     ManageUsers,
+    #[serde(rename = "settings:manage", alias = "manage_settings")]
     ManageSettings,
+    #[serde(rename = "integrations:manage", alias = "manage_integrations")]
     ManageIntegrations,
 
     // Special scopes
+    #[serde(rename = "*", alias = "full_access")]
     FullAccess,
+    #[serde(rename = "webhooks", alias = "webhooks_only")]
     WebhooksOnly,
 }
 
 impl ApiKeyScope {
+    /// The scope as written in requests and stored on keys
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ReadClients => "clients:read",
+            Self::ReadTickets => "tickets:read",
+            Self::ReadAssets => "assets:read",
+            Self::ReadPasswords => "passwords:read",
+            Self::ReadDocumentation => "documentation:read",
+            Self::ReadInvoices => "invoices:read",
+            Self::ReadReports => "reports:read",
+            Self::WriteClients => "clients:write",
+            Self::WriteTickets => "tickets:write",
+            Self::WriteAssets => "assets:write",
+            Self::WritePasswords => "passwords:write",
+            Self::WriteDocumentation => "documentation:write",
+            Self::WriteInvoices => "invoices:write",
+            Self::ManageUsers => "users:manage",
+            Self::ManageSettings => "settings:manage",
+            Self::ManageIntegrations => "integrations:manage",
+            Self::FullAccess => "*",
+            Self::WebhooksOnly => "webhooks",
+        }
+    }
+
     /// Get human-readable description
     pub fn description(&self) -> &'static str {
         match self {
@@ -118,8 +164,20 @@ pub struct CreateApiKeyRequest {
     pub scopes: Vec<ApiKeyScope>,
     /// Expiration in days (None = never expires)
     pub expires_in_days: Option<u32>,
+    /// Exact expiration, instead of `expires_in_days`
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
     pub allowed_ips: Vec<String>,
     pub rate_limit: Option<u32>,
+    pub daily_quota: Option<u32>,
+}
+
+impl CreateApiKeyRequest {
+    /// When a key created at `now` expires, if ever
+    pub fn expiry(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.expires_at
+            .or_else(|| self.expires_in_days.map(|days| now + chrono::Duration::days(days as i64)))
+    }
 }
 
 /// Response when creating an API key (includes the actual key)
@@ -218,6 +276,14 @@ pub fn validate_create_request(req: &CreateApiKeyRequest) -> ApiResult<()> {
         return Err(AppError::BadRequest("At least one scope is required".to_string()));
     }
 
+    if req.expires_at.is_some() && req.expires_in_days.is_some() {
+        return Err(AppError::BadRequest("Give either expires_at or expires_in_days, not both".to_string()));
+    }
+
+    if req.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(AppError::BadRequest("API key expiry must be in the future".to_string()));
+    }
+
     // Validate IP addresses if provided
     for ip in &req.allowed_ips {
         if !is_valid_ip_or_cidr(ip) {
@@ -255,7 +321,7 @@ fn base64_url_safe_encode(data: &[u8]) -> String {
 }
 
 // Helper: Constant-time string comparison
-fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
         return false;
     }
@@ -283,7 +349,7 @@ fn is_valid_ip_or_cidr(ip: &str) -> bool {
 }
 
 // Helper: Check if IP is in CIDR range (simplified)
-fn ip_in_cidr(ip: &str, cidr: &str) -> bool {
+pub(crate) fn ip_in_cidr(ip: &str, cidr: &str) -> bool {
     // Simplified implementation - in production use ipnetwork crate
     let parts: Vec<&str> = cidr.split('/').collect();
     if parts.len() != 2 {
@@ -376,6 +442,12 @@ impl Default for ApiKeyRateLimiter {
     }
 }
 
+/// The per-minute limiter shared by every request authenticated with a key
+pub fn rate_limiter() -> &'static ApiKeyRateLimiter {
+    static LIMITER: std::sync::OnceLock<ApiKeyRateLimiter> = std::sync::OnceLock::new();
+    LIMITER.get_or_init(ApiKeyRateLimiter::new)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -406,6 +478,43 @@ mod tests {
         assert!(!ApiKeyScope::ReadClients.grants(&ApiKeyScope::WriteClients));
     }
 
+    #[test]
+    fn test_scope_names() {
+        let scopes: Vec<ApiKeyScope> = serde_json::from_value(serde_json::json!(["tickets:read", "invoices:write", "*"])).unwrap();
+        assert_eq!(scopes, vec![ApiKeyScope::ReadTickets, ApiKeyScope::WriteInvoices, ApiKeyScope::FullAccess]);
+        assert_eq!(serde_json::to_value(ApiKeyScope::WriteTickets).unwrap(), "tickets:write");
+        assert_eq!(ApiKeyScope::ReadReports.as_str(), "reports:read");
+
+        // Keys stored before the rename still load
+        let old: ApiKeyScope = serde_json::from_value(serde_json::json!("read_tickets")).unwrap();
+        assert_eq!(old, ApiKeyScope::ReadTickets);
+    }
+
+    #[test]
+    fn test_create_request_expiry() {
+        let now = Utc::now();
+        let mut req = CreateApiKeyRequest {
+            name: "CI".to_string(),
+            description: None,
+            scopes: vec![ApiKeyScope::ReadTickets],
+            expires_in_days: Some(30),
+            expires_at: None,
+            allowed_ips: Vec::new(),
+            rate_limit: None,
+            daily_quota: None,
+        };
+        assert_eq!(req.expiry(now), Some(now + chrono::Duration::days(30)));
+        assert!(validate_create_request(&req).is_ok());
+
+        req.expires_at = Some(now + chrono::Duration::days(1));
+        assert!(validate_create_request(&req).is_err());
+
+        req.expires_in_days = None;
+        assert_eq!(req.expiry(now), Some(now + chrono::Duration::days(1)));
+        req.expires_at = Some(now - chrono::Duration::days(1));
+        assert!(validate_create_request(&req).is_err());
+    }
+
     #[test]
     fn test_ip_cidr() {
         assert!(ip_in_cidr("192.168.1.100", "192.168.1.0/24"));
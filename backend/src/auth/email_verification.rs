@@ -0,0 +1,154 @@
+//! Email verification for self-registered accounts
+//!
+//! `register` creates the user inactive and emails a single-use token.
+//! Confirming it through `/auth/verify-email` activates the account. Tokens
+//! are stored hashed and expire after [`TOKEN_TTL_HOURS`].
+
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::services::EmailService;
+
+pub const TOKEN_TTL_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyOutcome {
+    Verified(Uuid),
+    Expired,
+    Invalid,
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub(crate) fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Store a new token for `user_id` and return it; only its hash is kept
+pub async fn create_verification(pool: &PgPool, user_id: Uuid) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    sqlx::query("INSERT INTO email_verifications (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(hash_token(&token))
+        .bind(Utc::now() + Duration::hours(TOKEN_TTL_HOURS))
+        .execute(pool)
+        .await?;
+    Ok(token)
+}
+
+/// Redeem `token`, activating its user. A token works once.
+pub async fn verify(pool: &PgPool, token: &str) -> Result<VerifyOutcome, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let pending = sqlx::query_as::<_, (Uuid, Uuid, chrono::DateTime<Utc>)>(
+        r#"
+        SELECT id, user_id, expires_at FROM email_verifications
+        WHERE token_hash = $1 AND verified_at IS NULL
+        FOR UPDATE
+        "#
+    )
+    .bind(hash_token(token.trim()))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((verification_id, user_id, expires_at)) = pending else {
+        return Ok(VerifyOutcome::Invalid);
+    };
+    if expires_at <= Utc::now() {
+        return Ok(VerifyOutcome::Expired);
+    }
+
+    sqlx::query("UPDATE email_verifications SET verified_at = NOW() WHERE id = $1")
+        .bind(verification_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "UPDATE users SET is_active = true, email_verified_at = NOW(), updated_at = NOW() WHERE id = $1 AND email_verified_at IS NULL"
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(VerifyOutcome::Verified(user_id))
+}
+
+/// Whether the account is still waiting on verification, as opposed to
+/// verified or deactivated
+pub async fn is_pending(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>("SELECT email_verified_at IS NULL FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map(|pending| pending.unwrap_or(false))
+}
+
+pub fn verification_link(token: &str) -> String {
+    let base_url = std::env::var("APP_BASE_URL").unwrap_or_else(|_| "https://resolve.local".to_string());
+    format!("{}/api/v1/auth/verify-email?token={}", base_url.trim_end_matches('/'), token)
+}
+
+/// Plain-text notice sent best-effort; SMTP problems are logged
+pub(crate) async fn send(to: String, subject: &'static str, body: String) {
+    let config = match Config::from_env() {
+        Ok(config) if config.smtp.is_configured() => config,
+        _ => {
+            tracing::warn!("SMTP not configured; not sending \"{}\" email", subject);
+            return;
+        }
+    };
+    let email_service = match EmailService::new(&config.smtp).await {
+        Ok(service) => service,
+        Err(e) => {
+            tracing::error!("Failed to initialise email service: {}", e);
+            return;
+        }
+    };
+    let html = format!("<p>{}</p>", body.replace('\n', "<br>"));
+    if let Err(e) = email_service.send_email(&to, None, subject, &html, Some(&body)).await {
+        tracing::error!("Failed to send \"{}\" email: {}", subject, e);
+    }
+}
+
+/// Email the verification link in the background so the response doesn't
+/// wait on SMTP
+pub fn send_verification_email(email: &str, token: &str) {
+    let body = format!(
+        "Confirm your email address to finish setting up your Resolve account:\n\n{}\n\nThe link expires in {} hours. If you didn't sign up, ignore this email.",
+        verification_link(token),
+        TOKEN_TTL_HOURS
+    );
+    tokio::spawn(send(email.to_string(), "Verify your email address", body));
+}
+
+/// Sent instead of a verification email when someone registers an address
+/// that already has an account, so the API response can't reveal it
+pub fn send_existing_account_email(email: &str) {
+    let body = "Someone tried to create a Resolve account with this email address, but you already have one. \
+                You can sign in or reset your password. If this wasn't you, no action is needed."
+        .to_string();
+    tokio::spawn(send(email.to_string(), "You already have a Resolve account", body));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_random_and_hashed() {
+        let a = generate_token();
+        let b = generate_token();
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+        assert_eq!(hash_token(&a), hash_token(&a));
+        assert_ne!(hash_token(&a), a);
+    }
+}
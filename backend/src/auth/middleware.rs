@@ -1,14 +1,17 @@
 use axum::{
-    extract::{FromRequestParts, State},
+    extract::{ConnectInfo, FromRequestParts, State},
     http::{request::Parts, StatusCode},
     response::{IntoResponse, Response},
     async_trait,
     Json,
 };
+use sqlx::PgPool;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::AppState;
+use crate::middleware::rate_limit;
 use crate::error::{ApiError, AppError};
 use resolve_shared::User;
 use super::jwt;
@@ -28,6 +31,23 @@ pub struct AuthUserWithRole {
     pub permissions: Vec<String>,
 }
 
+/// Active user for a JWT's `sub` ($1), unless their sessions were revoked
+/// after the token's `iat` ($2). Revocation is compared at whole seconds,
+/// the resolution of `iat`.
+const SESSION_USER_QUERY: &str = "SELECT * FROM users WHERE id = $1 AND is_active = true
+     AND (sessions_revoked_at IS NULL OR date_trunc('second', sessions_revoked_at) <= to_timestamp($2))";
+
+/// The active user a verified JWT belongs to, or `None` when the account is
+/// inactive or its sessions were revoked after the token was issued. Every
+/// path that accepts a user JWT goes through here.
+pub(crate) async fn session_user(pool: &PgPool, claims: &jwt::Claims) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as::<_, User>(SESSION_USER_QUERY)
+        .bind(claims.sub)
+        .bind(claims.iat as f64)
+        .fetch_optional(pool)
+        .await
+}
+
 /// API Key authentication extractor
 #[derive(Debug, Clone)]
 pub struct AuthApiKey {
@@ -63,12 +83,8 @@ impl FromRequestParts<Arc<AppState>> for AuthUser {
         let token_data = jwt::verify_jwt(token)
             .map_err(|e| AppError::from(e).into_response())?;
 
-        // Load user from database
-        let user = sqlx::query_as::<_, User>(
-            "SELECT * FROM users WHERE id = $1 AND is_active = true"
-        )
-        .bind(token_data.claims.sub)
-        .fetch_optional(&state.db_pool)
+        // Load user from database; tokens issued before a password reset no longer count
+        let user = session_user(&state.db_pool, &token_data.claims)
         .await
         .map_err(|e| AppError::DatabaseError(e.to_string()).into_response())?
         .ok_or_else(|| AppError::Unauthorized("User not found or inactive".to_string()).into_response())?;
@@ -150,6 +166,11 @@ impl AuthUserWithRole {
         self.permissions.contains(&permission.to_string())
     }
 
+    /// Whether the user holds the all-powerful admin permission
+    pub fn is_admin(&self) -> bool {
+        self.permissions.contains(&"admin.all".to_string())
+    }
+
     /// Check if user has permission for a resource and action
     pub fn can(&self, resource: Resource, action: Action) -> bool {
         let permission = format!("{}.{}", resource.as_str(), action.as_str());
@@ -233,10 +254,10 @@ impl FromRequestParts<Arc<AppState>> for AuthApiKey {
             r#"
             SELECT
                 id, user_id, name, description, key_hash, key_prefix,
-                scopes, expires_at, allowed_ips, rate_limit, is_active,
-                created_at, last_used_at, usage_count
+                scopes, expires_at, allowed_ips, rate_limit, daily_quota, is_active,
+                created_at, revoked_at, last_used_at, last_used_ip, usage_count
             FROM api_keys
-            WHERE key_prefix = $1 AND is_active = true
+            WHERE key_prefix = $1 AND is_active = true AND revoked_at IS NULL
             "#,
             prefix
         )
@@ -258,20 +279,41 @@ impl FromRequestParts<Arc<AppState>> for AuthApiKey {
         }
 
         // Check IP whitelist if configured
+        // (X-Forwarded-For counts only when the connection is from a trusted proxy)
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+        let client_ip = rate_limit::client_ip(peer, &parts.headers, rate_limit::trusted_proxies()).map(|ip| ip.to_string());
         let allowed_ips: Vec<String> = stored_key.allowed_ips.clone().unwrap_or_default();
         if !allowed_ips.is_empty() {
-            // Get client IP from headers (X-Forwarded-For or X-Real-IP)
-            let client_ip = parts
-                .headers
-                .get("x-forwarded-for")
-                .or_else(|| parts.headers.get("x-real-ip"))
-                .and_then(|h| h.to_str().ok())
-                .map(|s| s.split(',').next().unwrap_or(s).trim().to_string());
-
-            if let Some(ip) = client_ip {
-                if !super::api_keys::is_ip_allowed(&ip, &allowed_ips) {
-                    return Err(AppError::Forbidden(format!("IP {} not allowed", ip)).into_response());
-                }
+            match &client_ip {
+                Some(ip) if super::api_keys::is_ip_allowed(ip, &allowed_ips) => {}
+                Some(ip) => return Err(AppError::Forbidden(format!("IP {} not allowed", ip)).into_response()),
+                None => return Err(AppError::Forbidden("Client IP unknown".to_string()).into_response()),
+            }
+        }
+
+        // Per-minute rate limit, then the daily quota
+        let rate_limit = stored_key.rate_limit.unwrap_or(0).max(0) as u32;
+        super::api_keys::rate_limiter()
+            .check_rate_limit(&stored_key.key_prefix, rate_limit)
+            .map_err(|retry_after| AppError::TooManyRequests { retry_after }.into_response())?;
+
+        let daily_quota = stored_key.daily_quota.max(0) as u32;
+        if daily_quota > 0 {
+            let used: i32 = sqlx::query_scalar(
+                "INSERT INTO api_key_daily_usage (api_key_id, usage_date, request_count)
+                 VALUES ($1, (NOW() AT TIME ZONE 'UTC')::date, 1)
+                 ON CONFLICT (api_key_id, usage_date)
+                 DO UPDATE SET request_count = api_key_daily_usage.request_count + 1
+                 RETURNING request_count"
+            )
+            .bind(stored_key.id)
+            .fetch_one(&state.db_pool)
+            .await
+            .map_err(|e| AppError::DatabaseError(e.to_string()).into_response())?;
+
+            if used as u32 > daily_quota {
+                let retry_after = seconds_until_utc_midnight(chrono::Utc::now());
+                return Err(AppError::TooManyRequests { retry_after }.into_response());
             }
         }
 
@@ -285,11 +327,12 @@ impl FromRequestParts<Arc<AppState>> for AuthApiKey {
         .map_err(|e| AppError::DatabaseError(e.to_string()).into_response())?
         .ok_or_else(|| AppError::Unauthorized("API key owner not found".to_string()).into_response())?;
 
-        // Update last used timestamp
-        sqlx::query!(
-            "UPDATE api_keys SET last_used_at = NOW(), usage_count = usage_count + 1 WHERE id = $1",
-            stored_key.id
+        // Record the use
+        sqlx::query(
+            "UPDATE api_keys SET last_used_at = NOW(), last_used_ip = $2, usage_count = usage_count + 1 WHERE id = $1"
         )
+        .bind(stored_key.id)
+        .bind(&client_ip)
         .execute(&state.db_pool)
         .await
         .ok();
@@ -308,11 +351,14 @@ impl FromRequestParts<Arc<AppState>> for AuthApiKey {
             scopes,
             expires_at: stored_key.expires_at,
             allowed_ips,
-            rate_limit: stored_key.rate_limit.unwrap_or(0) as u32,
+            rate_limit,
+            daily_quota,
             is_active: stored_key.is_active,
             created_at: stored_key.created_at,
-            last_used_at: stored_key.last_used_at,
-            usage_count: stored_key.usage_count as u64,
+            revoked_at: stored_key.revoked_at,
+            last_used_at: Some(chrono::Utc::now()),
+            last_used_ip: client_ip,
+            usage_count: stored_key.usage_count as u64 + 1,
         };
 
         Ok(AuthApiKey { key: api_key, user })
@@ -336,12 +382,95 @@ impl AuthApiKey {
             Ok(())
         } else {
             Err(AppError::InsufficientPermissions {
-                required: format!("{:?}", scope),
+                required: scope.as_str().to_string(),
             })
         }
     }
 }
 
+/// Seconds left in the UTC day, when daily quotas reset
+fn seconds_until_utc_midnight(now: chrono::DateTime<chrono::Utc>) -> u64 {
+    let tomorrow = now.date_naive().succ_opt().unwrap_or(now.date_naive());
+    let midnight = tomorrow.and_time(chrono::NaiveTime::MIN).and_utc();
+    (midnight - now).num_seconds().max(1) as u64
+}
+
+/// An API key scope an endpoint requires, for [`ApiKeyAuth`]
+pub trait RequiredScope: Send + Sync + 'static {
+    const SCOPE: ApiKeyScope;
+}
+
+/// Marker types naming each [`ApiKeyScope`] for use with [`ApiKeyAuth`]
+pub mod scopes {
+    use super::{ApiKeyScope, RequiredScope};
+
+    macro_rules! required_scopes {
+        ($($name:ident => $scope:ident),* $(,)?) => {
+            $(
+                #[derive(Debug, Clone, Copy)]
+                pub struct $name;
+
+                impl RequiredScope for $name {
+                    const SCOPE: ApiKeyScope = ApiKeyScope::$scope;
+                }
+            )*
+        };
+    }
+
+    required_scopes! {
+        ClientsRead => ReadClients,
+        ClientsWrite => WriteClients,
+        TicketsRead => ReadTickets,
+        TicketsWrite => WriteTickets,
+        AssetsRead => ReadAssets,
+        AssetsWrite => WriteAssets,
+        InvoicesRead => ReadInvoices,
+        InvoicesWrite => WriteInvoices,
+        ReportsRead => ReadReports,
+    }
+}
+
+/// The caller, by JWT or by an API key that holds scope `S`. JWT users
+/// pass straight through to the endpoint's usual permission checks; keys
+/// are checked as [`AuthApiKey`] does and then 403 without the scope.
+#[derive(Debug, Clone)]
+pub struct ApiKeyAuth<S> {
+    pub user: User,
+    /// The key used, `None` for JWT callers
+    pub key: Option<ApiKey>,
+    scope: std::marker::PhantomData<S>,
+}
+
+impl<S> ApiKeyAuth<S> {
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    pub fn is_api_key(&self) -> bool {
+        self.key.is_some()
+    }
+}
+
+#[async_trait]
+impl<S: RequiredScope> FromRequestParts<Arc<AppState>> for ApiKeyAuth<S> {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let (user, key) = match AuthEither::from_request_parts(parts, state).await? {
+            AuthEither::User(user) => (user, None),
+            AuthEither::ApiKey(auth) => {
+                auth.require_scope(&S::SCOPE).map_err(IntoResponse::into_response)?;
+                (auth.user, Some(auth.key))
+            }
+        };
+
+        Ok(ApiKeyAuth { user, key, scope: std::marker::PhantomData })
+    }
+}
+
 /// Optional authentication - returns None if no auth provided instead of error
 #[derive(Debug, Clone)]
 pub struct OptionalAuthUser(pub Option<User>);
@@ -368,13 +497,7 @@ impl FromRequestParts<Arc<AppState>> for OptionalAuthUser {
                 }
                 // Try to verify token and load user
                 if let Ok(token_data) = jwt::verify_jwt(token) {
-                    if let Ok(Some(user)) = sqlx::query_as::<_, User>(
-                        "SELECT * FROM users WHERE id = $1 AND is_active = true"
-                    )
-                    .bind(token_data.claims.sub)
-                    .fetch_optional(&state.db_pool)
-                    .await
-                    {
+                    if let Ok(Some(user)) = session_user(&state.db_pool, &token_data.claims).await {
                         return Ok(OptionalAuthUser(Some(user)));
                     }
                 }
@@ -2,6 +2,9 @@ pub mod oauth;
 pub mod jwt;
 pub mod middleware;
 pub mod totp;
+pub mod password;
+pub mod email_verification;
+pub mod password_reset;
 pub mod providers;
 pub mod oidc;
 pub mod oidc_handlers;
@@ -21,8 +24,9 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::PasswordHash;
 
+use crate::config::Argon2Config;
 use crate::AppState;
 use resolve_shared::User;
 
@@ -59,6 +63,22 @@ pub struct RegisterRequest {
     pub last_name: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct OAuthCallbackQuery {
     pub code: String,
@@ -71,6 +91,9 @@ pub fn auth_routes() -> Router<Arc<AppState>> {
         // Local authentication
         .route("/login", post(login))
         .route("/register", post(register))
+        .route("/verify-email", get(verify_email_link).post(verify_email))
+        .route("/forgot-password", post(forgot_password))
+        .route("/reset-password", post(reset_password))
         .route("/logout", post(logout))
         .route("/me", get(me))
         .route("/refresh", post(refresh_token))
@@ -94,9 +117,10 @@ async fn login(
     State(state): State<Arc<AppState>>,
     Json(req): Json<LoginRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    // First try to find user by email
+    // First try to find user by email. Inactive accounts are turned away
+    // after the password check so an unverified one gets a clear error.
     let user = sqlx::query_as::<_, User>(
-        "SELECT * FROM users WHERE email = $1 AND is_active = true"
+        "SELECT * FROM users WHERE email = $1"
     )
     .bind(&req.email)
     .fetch_optional(&state.db_pool)
@@ -117,11 +141,9 @@ async fn login(
 
     // Verify password for local auth users
     if let Some(password_hash) = &user.password_hash {
-        let parsed_hash = argon2::PasswordHash::new(password_hash)
+        let parsed_hash = PasswordHash::new(password_hash)
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let valid = argon2::Argon2::default()
-            .verify_password(req.password.as_bytes(), &parsed_hash)
-            .is_ok();
+        let valid = password::verify_password(&req.password, &parsed_hash);
 
         if !valid {
             // Increment failed login attempts
@@ -137,6 +159,29 @@ async fn login(
 
             return Err(StatusCode::UNAUTHORIZED);
         }
+
+        if !user.is_active {
+            let pending = email_verification::is_pending(&state.db_pool, user.id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            if pending {
+                return Ok((
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({
+                        "error": "email_not_verified",
+                        "message": "Verify your email address before signing in"
+                    })),
+                )
+                    .into_response());
+            }
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        // Upgrade hashes made with weaker parameters than the current config
+        let argon2_config = Argon2Config::from_env();
+        if password::needs_rehash(&parsed_hash, &argon2_config) {
+            password::upgrade_hash(&state.db_pool, user.id, password_hash, &req.password, &argon2_config).await;
+        }
     } else {
         // User only has OAuth login, password login not allowed
         return Err(StatusCode::BAD_REQUEST);
@@ -190,38 +235,52 @@ async fn login(
         expires_at: token_data.expires_at,
     };
 
-    Ok(Json(response))
+    Ok(Json(response).into_response())
 }
 
+/// Always answers the same way whether or not the email is taken. A new
+/// account starts inactive and gets a verification email; an existing one
+/// gets a notice instead (or a fresh link while still unverified).
 async fn register(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RegisterRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    // Check if user already exists
-    let existing_user = sqlx::query("SELECT id FROM users WHERE email = $1")
-        .bind(&req.email)
-        .fetch_optional(&state.db_pool)
-        .await
+    let accepted = (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "message": "Check your email to finish creating your account"
+        })),
+    );
+
+    // Hash before the lookup so both paths take about as long
+    let password_hash = password::hash_password(&req.password, &Argon2Config::from_env())
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    if existing_user.is_some() {
-        return Err(StatusCode::CONFLICT);
-    }
+    let existing_user = sqlx::query_as::<_, (Uuid, bool)>(
+        "SELECT id, email_verified_at IS NULL FROM users WHERE email = $1"
+    )
+    .bind(&req.email)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Hash password
-    use argon2::password_hash::rand_core::OsRng;
-    use argon2::password_hash::SaltString;
-    let salt = SaltString::generate(&mut OsRng);
-    let password_hash = Argon2::default()
-        .hash_password(req.password.as_bytes(), &salt)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .to_string();
+    if let Some((user_id, pending)) = existing_user {
+        if pending {
+            let token = email_verification::create_verification(&state.db_pool, user_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            email_verification::send_verification_email(&req.email, &token);
+        } else {
+            email_verification::send_existing_account_email(&req.email);
+        }
+        return Ok(accepted);
+    }
 
-    // Create user
+    // Create user, inactive until the email is verified
     let user_id = Uuid::new_v4();
     sqlx::query(
         "INSERT INTO users (id, email, first_name, last_name, password_hash, timezone, is_active, mfa_enabled, failed_login_attempts)
-         VALUES ($1, $2, $3, $4, $5, 'UTC', true, false, 0)"
+         VALUES ($1, $2, $3, $4, $5, 'UTC', false, false, 0)"
     )
     .bind(user_id)
     .bind(&req.email)
@@ -232,7 +291,109 @@ async fn register(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(StatusCode::CREATED)
+    let token = email_verification::create_verification(&state.db_pool, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    email_verification::send_verification_email(&req.email, &token);
+
+    Ok(accepted)
+}
+
+async fn verify_email_link(
+    State(state): State<Arc<AppState>>,
+    Query(req): Query<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    confirm_email(&state, &req.token).await
+}
+
+async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    confirm_email(&state, &req.token).await
+}
+
+async fn confirm_email(state: &AppState, token: &str) -> Result<axum::response::Response, StatusCode> {
+    let outcome = email_verification::verify(&state.db_pool, token)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (status, body) = match outcome {
+        email_verification::VerifyOutcome::Verified(_) => (
+            StatusCode::OK,
+            serde_json::json!({ "success": true, "message": "Email verified, you can now sign in" }),
+        ),
+        email_verification::VerifyOutcome::Expired => (
+            StatusCode::GONE,
+            serde_json::json!({
+                "error": "verification_expired",
+                "message": "This verification link has expired; register again to get a new one"
+            }),
+        ),
+        email_verification::VerifyOutcome::Invalid => (
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({
+                "error": "verification_invalid",
+                "message": "This verification link is invalid or has already been used"
+            }),
+        ),
+    };
+
+    Ok((status, Json(body)).into_response())
+}
+
+/// Always accepted, so the response doesn't reveal which emails have accounts
+async fn forgot_password(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    password_reset::request_reset(&state.db_pool, &req.email)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "message": "If an account exists for that email, a reset link is on its way"
+        })),
+    ))
+}
+
+async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let outcome = password_reset::reset_password(&state.db_pool, &req.token, &req.new_password, &Argon2Config::from_env())
+        .await
+        .map_err(|e| {
+            tracing::error!("Password reset failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let (status, body) = match outcome {
+        password_reset::ResetOutcome::Reset(_) => (
+            StatusCode::OK,
+            serde_json::json!({ "success": true, "message": "Password updated; sign in with your new password" }),
+        ),
+        password_reset::ResetOutcome::Expired => (
+            StatusCode::GONE,
+            serde_json::json!({ "error": "reset_expired", "message": "This reset link has expired; request a new one" }),
+        ),
+        password_reset::ResetOutcome::Invalid => (
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({ "error": "reset_invalid", "message": "This reset link is invalid or has already been used" }),
+        ),
+        password_reset::ResetOutcome::WeakPassword(strength) => (
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({
+                "error": "weak_password",
+                "message": strength.warning.clone().unwrap_or_else(|| "Choose a stronger password".to_string()),
+                "strength": strength
+            }),
+        ),
+    };
+
+    Ok((status, Json(body)))
 }
 
 async fn logout() -> impl IntoResponse {
@@ -423,9 +584,19 @@ pub fn extract_token(headers: &axum::http::HeaderMap) -> Option<String> {
     }
 }
 
-// Simple token verification
-pub fn verify_token(token: &str) -> Result<jwt::Claims, axum::http::StatusCode> {
-    jwt::verify_jwt(token)
+// Simple token verification. Like the AuthUser extractor, a token whose user
+// is inactive or had their sessions revoked after it was issued is refused.
+pub async fn verify_token(pool: &sqlx::PgPool, token: &str) -> Result<jwt::Claims, axum::http::StatusCode> {
+    let claims = jwt::verify_jwt(token)
         .map(|token_data| token_data.claims)
-        .map_err(|_| axum::http::StatusCode::UNAUTHORIZED)
+        .map_err(|_| axum::http::StatusCode::UNAUTHORIZED)?;
+
+    match middleware::session_user(pool, &claims).await {
+        Ok(Some(_)) => Ok(claims),
+        Ok(None) => Err(axum::http::StatusCode::UNAUTHORIZED),
+        Err(e) => {
+            tracing::error!("Error loading session user: {}", e);
+            Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
 }
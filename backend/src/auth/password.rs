@@ -0,0 +1,139 @@
+//! Password hashing
+//!
+//! New hashes are Argon2id with the cost from [`Argon2Config`]. Verification
+//! takes the parameters from the stored PHC string, so hashes made under
+//! older settings keep working, and [`needs_rehash`] flags the ones weaker
+//! than the current config so a successful login can upgrade them.
+
+use argon2::password_hash::{rand_core::OsRng, Error as HashError, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Argon2Config;
+use crate::services::password_health::{estimate_strength, PasswordStrength};
+
+/// Lowest zxcvbn score accepted for a new account password
+pub const MIN_SCORE: i32 = 3;
+
+pub fn hasher(config: &Argon2Config) -> Result<Argon2<'static>, HashError> {
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, config.params()?))
+}
+
+pub fn hash_password(password: &str, config: &Argon2Config) -> Result<String, HashError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(hasher(config)?.hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+/// Check `password` against `hash` using the algorithm and cost recorded in
+/// the hash itself
+pub fn verify_password(password: &str, hash: &PasswordHash) -> bool {
+    Argon2::default().verify_password(password.as_bytes(), hash).is_ok()
+}
+
+/// Reject passwords scoring below [`MIN_SCORE`]. `user_inputs` such as the
+/// email and name count against the password.
+pub fn check_strength(password: &str, user_inputs: &[&str]) -> Result<(), PasswordStrength> {
+    let strength = estimate_strength(password, user_inputs);
+    if strength.score < MIN_SCORE {
+        return Err(strength);
+    }
+    Ok(())
+}
+
+/// Whether `hash` is weaker than `config` on any parameter, or isn't
+/// Argon2id v19 at all. A hash stronger than the config is left alone so
+/// lowering the config never downgrades existing hashes.
+pub fn needs_rehash(hash: &PasswordHash, config: &Argon2Config) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident() || hash.version != Some(Version::V0x13.into()) {
+        return true;
+    }
+    let Ok(params) = Params::try_from(hash) else {
+        return true;
+    };
+    params.m_cost() < config.memory_kib
+        || params.t_cost() < config.iterations
+        || params.p_cost() < config.parallelism
+}
+
+/// Replace a user's `old_hash` with one under the current config. Only
+/// called after `password` has been verified; a failure is logged and
+/// doesn't affect the login.
+pub async fn upgrade_hash(pool: &PgPool, user_id: Uuid, old_hash: &str, password: &str, config: &Argon2Config) {
+    let new_hash = match hash_password(password, config) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::warn!("Failed to rehash password for user {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    // Conditional on the old hash so a concurrent password change wins
+    let result = sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1 AND password_hash = $3")
+        .bind(user_id)
+        .bind(&new_hash)
+        .bind(old_hash)
+        .execute(pool)
+        .await;
+
+    match result {
+        Ok(_) => tracing::info!("Upgraded password hash parameters for user {}", user_id),
+        Err(e) => tracing::warn!("Failed to store rehashed password for user {}: {}", user_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEAK: Argon2Config = Argon2Config { memory_kib: 8, iterations: 1, parallelism: 1 };
+
+    #[test]
+    fn test_weak_hash_still_verifies_and_needs_rehash() {
+        let weak_hash = hash_password("correct horse", &WEAK).unwrap();
+        let parsed = PasswordHash::new(&weak_hash).unwrap();
+
+        assert!(verify_password("correct horse", &parsed));
+        assert!(!verify_password("wrong horse", &parsed));
+        assert!(needs_rehash(&parsed, &Argon2Config::default()));
+        assert!(!needs_rehash(&parsed, &WEAK));
+    }
+
+    #[test]
+    fn test_current_hash_does_not_need_rehash() {
+        let config = Argon2Config::default();
+        let hash = hash_password("correct horse", &config).unwrap();
+        let parsed = PasswordHash::new(&hash).unwrap();
+
+        assert!(verify_password("correct horse", &parsed));
+        assert!(!needs_rehash(&parsed, &config));
+    }
+
+    #[test]
+    fn test_stronger_hash_is_not_downgraded() {
+        let strong = Argon2Config { memory_kib: 32, iterations: 3, parallelism: 2 };
+        let hash = hash_password("correct horse", &strong).unwrap();
+        assert!(!needs_rehash(&PasswordHash::new(&hash).unwrap(), &WEAK));
+    }
+
+    #[test]
+    fn test_check_strength() {
+        assert!(check_strength("correct-horse-battery-staple-42!", &[]).is_ok());
+        let weak = check_strength("password1", &[]).unwrap_err();
+        assert!(weak.score < MIN_SCORE);
+        assert!(check_strength("janedoe2024", &["jane", "doe", "jane.doe@example.com"]).is_err());
+    }
+
+    #[test]
+    fn test_other_variants_need_rehash() {
+        let salt = SaltString::generate(&mut OsRng);
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, WEAK.params().unwrap())
+            .hash_password(b"correct horse", &salt)
+            .unwrap()
+            .to_string();
+        let parsed = PasswordHash::new(&argon2i).unwrap();
+
+        assert!(verify_password("correct horse", &parsed));
+        assert!(needs_rehash(&parsed, &WEAK));
+    }
+}
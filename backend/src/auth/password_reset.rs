@@ -0,0 +1,133 @@
+//! Forgot-password and reset-password
+//!
+//! A reset token is single use, expires after [`TOKEN_TTL_MINUTES`] and is
+//! stored hashed. Completing a reset replaces the Argon2 hash, burns every
+//! outstanding token for the user and stamps `sessions_revoked_at`, which
+//! the auth extractors use to refuse JWTs issued before it.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::email_verification::{generate_token, hash_token, send};
+use super::password::{self, check_strength};
+use crate::config::Argon2Config;
+use crate::services::password_health::PasswordStrength;
+
+pub const TOKEN_TTL_MINUTES: i64 = 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResetError {
+    #[error("Failed to hash password: {0}")]
+    Hash(argon2::password_hash::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone)]
+pub enum ResetOutcome {
+    Reset(Uuid),
+    Expired,
+    Invalid,
+    WeakPassword(PasswordStrength),
+}
+
+/// Store a reset token for `user_id` and return it; only its hash is kept
+pub async fn create_reset(pool: &PgPool, user_id: Uuid) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    sqlx::query("INSERT INTO password_resets (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(hash_token(&token))
+        .bind(Utc::now() + Duration::minutes(TOKEN_TTL_MINUTES))
+        .execute(pool)
+        .await?;
+    Ok(token)
+}
+
+/// Email a reset link if `email` belongs to an active local account. The
+/// caller answers the same way either way.
+pub async fn request_reset(pool: &PgPool, email: &str) -> Result<(), sqlx::Error> {
+    let user_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM users WHERE email = $1 AND is_active = true AND password_hash IS NOT NULL"
+    )
+    .bind(email.trim())
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(user_id) = user_id else {
+        tracing::debug!("Password reset requested for an unknown or inactive account");
+        return Ok(());
+    };
+
+    let token = create_reset(pool, user_id).await?;
+    let body = format!(
+        "A password reset was requested for your Resolve account. Choose a new password here:\n\n{}\n\n\
+         The link expires in {} minutes and works once. If you didn't ask for this, ignore this email.",
+        reset_link(&token),
+        TOKEN_TTL_MINUTES
+    );
+    tokio::spawn(send(email.trim().to_string(), "Reset your password", body));
+    Ok(())
+}
+
+pub fn reset_link(token: &str) -> String {
+    let base_url = std::env::var("APP_BASE_URL").unwrap_or_else(|_| "https://resolve.local".to_string());
+    format!("{}/reset-password?token={}", base_url.trim_end_matches('/'), token)
+}
+
+/// Redeem `token` and set `new_password`. A weak password leaves the token
+/// usable so the user can try again.
+pub async fn reset_password(
+    pool: &PgPool,
+    token: &str,
+    new_password: &str,
+    config: &Argon2Config,
+) -> Result<ResetOutcome, ResetError> {
+    let mut tx = pool.begin().await?;
+
+    let pending = sqlx::query_as::<_, (Uuid, Uuid, DateTime<Utc>, String, String, String)>(
+        r#"
+        SELECT r.id, r.user_id, r.expires_at, u.email, u.first_name, u.last_name
+        FROM password_resets r
+        JOIN users u ON u.id = r.user_id
+        WHERE r.token_hash = $1 AND r.used_at IS NULL
+        FOR UPDATE OF r
+        "#
+    )
+    .bind(hash_token(token.trim()))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((_, user_id, expires_at, email, first_name, last_name)) = pending else {
+        return Ok(ResetOutcome::Invalid);
+    };
+    if expires_at <= Utc::now() {
+        return Ok(ResetOutcome::Expired);
+    }
+    if let Err(strength) = check_strength(new_password, &[&email, &first_name, &last_name]) {
+        return Ok(ResetOutcome::WeakPassword(strength));
+    }
+
+    let password_hash = password::hash_password(new_password, config).map_err(ResetError::Hash)?;
+
+    sqlx::query(
+        r#"
+        UPDATE users SET password_hash = $2, sessions_revoked_at = NOW(),
+            failed_login_attempts = 0, locked_until = NULL, updated_at = NOW()
+        WHERE id = $1
+        "#
+    )
+    .bind(user_id)
+    .bind(&password_hash)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE password_resets SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    tracing::info!("Password reset completed for user {}", user_id);
+    Ok(ResetOutcome::Reset(user_id))
+}
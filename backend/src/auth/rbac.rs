@@ -412,6 +412,10 @@ pub mod default_roles {
                     resource: Resource::Invoices,
                     actions: vec![Action::Read, Action::Create, Action::Update, Action::Approve],
                 },
+                Permission {
+                    resource: Resource::Expenses,
+                    actions: vec![Action::Read, Action::Create, Action::Update, Action::Delete, Action::Approve],
+                },
                 Permission {
                     resource: Resource::Reports,
                     actions: vec![Action::Read, Action::Export],
@@ -468,6 +472,10 @@ pub mod default_roles {
                     resource: Resource::Passwords,
                     actions: vec![Action::Read],
                 },
+                Permission {
+                    resource: Resource::Expenses,
+                    actions: vec![Action::Read, Action::Create, Action::Update],
+                },
             ],
             hierarchy_level: 50,
             created_at: chrono::Utc::now(),
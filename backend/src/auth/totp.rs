@@ -1,11 +1,9 @@
-use aes_gcm::{
-    aead::{Aead, KeyInit},
-    Aes256Gcm, Key, Nonce,
-};
 use base64::{Engine as _, engine::general_purpose};
 use rand::RngCore;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::keyring::{KeyPurpose, KeyRing};
+
 const TOTP_PERIOD: u64 = 30;
 const TOTP_DIGITS: usize = 6;
 
@@ -79,60 +77,11 @@ fn generate_totp(secret: &[u8], time_window: u64) -> String {
 }
 
 pub fn encrypt_mfa_secret(secret: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let key = get_encryption_key();
-    let cipher = Aes256Gcm::new(&key);
-    
-    let mut nonce_bytes = [0u8; 12];
-    rand::thread_rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    
-    let ciphertext = cipher.encrypt(nonce, secret.as_bytes())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
-    
-    // Combine nonce and ciphertext
-    let mut encrypted = Vec::with_capacity(12 + ciphertext.len());
-    encrypted.extend_from_slice(&nonce_bytes);
-    encrypted.extend_from_slice(&ciphertext);
-    
-    Ok(general_purpose::STANDARD.encode(&encrypted))
+    Ok(KeyRing::from_env(KeyPurpose::Mfa)?.encrypt_str(secret)?)
 }
 
 pub fn decrypt_mfa_secret(encrypted_secret: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let key = get_encryption_key();
-    let cipher = Aes256Gcm::new(&key);
-    
-    let encrypted = general_purpose::STANDARD.decode(encrypted_secret)?;
-    
-    if encrypted.len() < 12 {
-        return Err("Invalid encrypted data".into());
-    }
-    
-    let (nonce_bytes, ciphertext) = encrypted.split_at(12);
-    let nonce = Nonce::from_slice(nonce_bytes);
-    
-    let plaintext = cipher.decrypt(nonce, ciphertext)
-        .map_err(|e| format!("Decryption failed: {}", e))?;
-    
-    Ok(String::from_utf8(plaintext)?)
-}
-
-fn get_encryption_key() -> Key<Aes256Gcm> {
-    let key_env = std::env::var("MFA_ENCRYPTION_KEY").unwrap_or_else(|_| {
-        tracing::warn!("MFA_ENCRYPTION_KEY not set, using default (insecure for production)");
-        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef".to_string()
-    });
-    
-    let key_bytes = hex::decode(&key_env).unwrap_or_else(|_| {
-        tracing::error!("Invalid MFA_ENCRYPTION_KEY format, using default");
-        vec![0u8; 32]
-    });
-    
-    if key_bytes.len() != 32 {
-        tracing::error!("MFA_ENCRYPTION_KEY must be 32 bytes (64 hex chars), using default");
-        return *Key::<Aes256Gcm>::from_slice(&[0u8; 32]);
-    }
-    
-    *Key::<Aes256Gcm>::from_slice(&key_bytes)
+    Ok(KeyRing::from_env(KeyPurpose::Mfa)?.decrypt_str(encrypted_secret)?)
 }
 
 #[cfg(test)]
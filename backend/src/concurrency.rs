@@ -0,0 +1,105 @@
+//! Optimistic concurrency for updates
+//!
+//! A client that read a record can send what it saw, either the record's
+//! `updated_at` as `expected_updated_at` in the body or an
+//! `If-Unmodified-Since` header, and the update only applies if nobody
+//! changed the record since. Update handlers add
+//! `AND ($n::timestamptz IS NULL OR updated_at IS NULL OR updated_at <= $n)`
+//! bound to [`Precondition::unmodified_since`]; when that matches no rows but
+//! the record exists, they answer `409 Conflict` with the current record so
+//! the client can merge. Requests without either keep last-write-wins.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::convert::Infallible;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Precondition {
+    /// `If-Unmodified-Since`, moved to the end of its second since HTTP
+    /// dates drop the sub-second part `updated_at` has
+    header: Option<DateTime<Utc>>,
+}
+
+impl Precondition {
+    /// An unparseable header is ignored, as HTTP requires
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = headers
+            .get(header::IF_UNMODIFIED_SINCE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| DateTime::parse_from_rfc2822(s.trim()).ok())
+            .map(|t| t.with_timezone(&Utc) + Duration::microseconds(999_999));
+        Self { header }
+    }
+
+    /// The latest `updated_at` the update may still apply over. The body's
+    /// exact `expected_updated_at` wins over the header.
+    pub fn unmodified_since(&self, expected_updated_at: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        expected_updated_at.or(self.header)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Precondition {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ConflictBody<T> {
+    error: &'static str,
+    message: &'static str,
+    current: T,
+}
+
+/// `409 Conflict` carrying the record as it is now
+pub fn conflict<T: Serialize>(current: T) -> Response {
+    (
+        StatusCode::CONFLICT,
+        Json(ConflictBody {
+            error: "conflict",
+            message: "The record was modified since you loaded it",
+            current,
+        }),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_header_covers_its_whole_second() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_UNMODIFIED_SINCE, HeaderValue::from_static("Wed, 15 May 2024 09:30:00 GMT"));
+        let since = Precondition::from_headers(&headers).unmodified_since(None).unwrap();
+
+        let read_at = Utc.with_ymd_and_hms(2024, 5, 15, 9, 30, 0).unwrap() + Duration::milliseconds(420);
+        assert!(read_at <= since);
+        assert!(read_at + Duration::seconds(1) > since);
+    }
+
+    #[test]
+    fn test_expected_updated_at_wins_and_bad_headers_are_ignored() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_UNMODIFIED_SINCE, HeaderValue::from_static("yesterday"));
+        let precondition = Precondition::from_headers(&headers);
+        assert_eq!(precondition, Precondition::default());
+        assert_eq!(precondition.unmodified_since(None), None);
+
+        let expected = Utc.with_ymd_and_hms(2024, 5, 15, 9, 30, 0).unwrap();
+        assert_eq!(precondition.unmodified_since(Some(expected)), Some(expected));
+    }
+}
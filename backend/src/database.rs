@@ -14,6 +14,11 @@ pub struct PoolConfig {
     pub idle_timeout: Duration,
     /// Maximum lifetime of a connection
     pub max_lifetime: Duration,
+    /// Per-connection `statement_timeout`; `None` leaves the server's setting.
+    /// Migrations run on the same pool, so keep it above the slowest one.
+    pub statement_timeout: Option<Duration>,
+    /// `application_name` reported to the server, shown in `pg_stat_activity`
+    pub application_name: String,
 }
 
 impl Default for PoolConfig {
@@ -24,6 +29,8 @@ impl Default for PoolConfig {
             acquire_timeout: Duration::from_secs(30),
             idle_timeout: Duration::from_secs(600),      // 10 minutes
             max_lifetime: Duration::from_secs(1800),     // 30 minutes
+            statement_timeout: None,
+            application_name: "resolve".to_string(),
         }
     }
 }
@@ -63,9 +70,52 @@ impl PoolConfig {
             }
         }
 
+        // Seconds; 0 turns the timeout off
+        if let Ok(timeout) = std::env::var("DB_STATEMENT_TIMEOUT") {
+            if let Ok(n) = timeout.parse::<u64>() {
+                config.statement_timeout = (n > 0).then_some(Duration::from_secs(n));
+            }
+        }
+
+        if let Ok(name) = std::env::var("DB_APPLICATION_NAME") {
+            if !name.trim().is_empty() {
+                config.application_name = name.trim().to_string();
+            }
+        }
+
         config
     }
 
+    /// Pool options for this config, with session settings applied to every
+    /// new connection
+    pub fn pool_options(&self) -> PgPoolOptions {
+        let application_name = self.application_name.clone();
+        let statement_timeout_ms = self.statement_timeout.map(|t| t.as_millis().to_string());
+
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            .test_before_acquire(true)
+            .after_connect(move |conn, _meta| {
+                let application_name = application_name.clone();
+                let statement_timeout_ms = statement_timeout_ms.clone();
+                Box::pin(async move {
+                    sqlx::query(
+                        "SELECT set_config('application_name', $1, false),
+                                set_config('statement_timeout', COALESCE($2, current_setting('statement_timeout')), false)"
+                    )
+                        .bind(application_name)
+                        .bind(statement_timeout_ms)
+                        .execute(conn)
+                        .await?;
+                    Ok(())
+                })
+            })
+    }
+
     /// Create production-optimized config
     pub fn production() -> Self {
         Self {
@@ -74,6 +124,7 @@ impl PoolConfig {
             acquire_timeout: Duration::from_secs(10),
             idle_timeout: Duration::from_secs(300),      // 5 minutes
             max_lifetime: Duration::from_secs(3600),     // 1 hour
+            ..Self::default()
         }
     }
 
@@ -85,6 +136,7 @@ impl PoolConfig {
             acquire_timeout: Duration::from_secs(60),
             idle_timeout: Duration::from_secs(900),      // 15 minutes
             max_lifetime: Duration::from_secs(7200),     // 2 hours
+            ..Self::default()
         }
     }
 }
@@ -102,21 +154,17 @@ pub async fn create_pool_with_config(database_url: &str, config: PoolConfig) ->
         tracing::info!("Database created successfully");
     }
 
-    let pool = PgPoolOptions::new()
-        .max_connections(config.max_connections)
-        .min_connections(config.min_connections)
-        .acquire_timeout(config.acquire_timeout)
-        .idle_timeout(config.idle_timeout)
-        .max_lifetime(config.max_lifetime)
-        .test_before_acquire(true)
-        .connect(database_url)
-        .await?;
+    let pool = config.pool_options().connect(database_url).await?;
 
     tracing::info!(
-        "Database pool created: max={}, min={}, acquire_timeout={}s",
+        "Database pool created: max={}, min={}, acquire_timeout={}s, idle_timeout={}s, max_lifetime={}s, statement_timeout={}, application_name={}",
         config.max_connections,
         config.min_connections,
-        config.acquire_timeout.as_secs()
+        config.acquire_timeout.as_secs(),
+        config.idle_timeout.as_secs(),
+        config.max_lifetime.as_secs(),
+        config.statement_timeout.map(|t| format!("{}s", t.as_secs())).unwrap_or_else(|| "server default".to_string()),
+        config.application_name
     );
 
     Ok(pool)
@@ -80,6 +80,11 @@ impl ApiError {
         AppError::Conflict(message.into())
     }
 
+    /// Create a 503 Service Unavailable error
+    pub fn unavailable(message: impl Into<String>) -> AppError {
+        AppError::ServiceUnavailable(message.into())
+    }
+
     /// Create a validation error with a single field error
     pub fn validation_single(field: impl Into<String>, message: impl Into<String>) -> AppError {
         let mut details = HashMap::new();
@@ -124,6 +129,7 @@ pub enum AppError {
     InternalError(String),
     DatabaseError(String),
     ExternalServiceError { service: String, message: String },
+    ServiceUnavailable(String),
 
     // OAuth/OIDC errors
     OAuthError(String),
@@ -150,6 +156,7 @@ impl AppError {
             Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
             Self::InternalError(_) | Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::ExternalServiceError { .. } => StatusCode::BAD_GATEWAY,
+            Self::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
             Self::OAuthError(_) | Self::ProviderNotFound(_) | Self::ProviderDisabled(_) => {
                 StatusCode::BAD_REQUEST
             }
@@ -176,6 +183,7 @@ impl AppError {
             Self::InternalError(_) => "INTERNAL_ERROR",
             Self::DatabaseError(_) => "DATABASE_ERROR",
             Self::ExternalServiceError { .. } => "EXTERNAL_SERVICE_ERROR",
+            Self::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
             Self::OAuthError(_) => "OAUTH_ERROR",
             Self::ProviderNotFound(_) => "PROVIDER_NOT_FOUND",
             Self::ProviderDisabled(_) => "PROVIDER_DISABLED",
@@ -217,6 +225,7 @@ impl AppError {
                 tracing::error!("External service error ({}): {}", service, message);
                 format!("External service '{}' is unavailable", service)
             }
+            Self::ServiceUnavailable(msg) => msg.clone(),
             Self::OAuthError(msg) => format!("OAuth error: {}", msg),
             Self::ProviderNotFound(name) => format!("Auth provider '{}' not found", name),
             Self::ProviderDisabled(name) => format!("Auth provider '{}' is disabled", name),
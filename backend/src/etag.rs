@@ -0,0 +1,130 @@
+//! Conditional GET for cacheable resources
+//!
+//! Handlers that opt in answer with [`json`], which tags the body with a
+//! strong `ETag` (a hash of the serialized JSON, so anything that changes
+//! the response changes the tag) and answers `304 Not Modified` with no body
+//! when the request's `If-None-Match` already names it. Responses carry
+//! `Cache-Control: private, no-cache` so clients keep their copy but check
+//! it on every use.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+
+const CACHE_CONTROL: &str = "private, no-cache";
+
+/// The request's `If-None-Match`, if any
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IfNoneMatch(Option<String>);
+
+impl IfNoneMatch {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self(
+            headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_string),
+        )
+    }
+
+    /// Whether the client's copy is `etag`. Uses the weak comparison HTTP
+    /// asks for on `If-None-Match`, so a `W/` prefix added by a proxy still
+    /// matches.
+    pub fn matches(&self, etag: &str) -> bool {
+        let Some(header) = &self.0 else {
+            return false;
+        };
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        header.trim() == "*" || header.split(',').any(|tag| opaque(tag) == opaque(etag))
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfNoneMatch {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+/// Strong entity tag for a response body
+pub fn etag_for(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("\"{}\"", hex::encode(&digest[..16]))
+}
+
+/// `value` as JSON with its `ETag`, or `304 Not Modified` when the client
+/// already has it
+pub fn json<T: Serialize>(if_none_match: &IfNoneMatch, value: &T) -> Response {
+    let body = match serde_json::to_vec(value) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Error serializing response: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let etag = etag_for(&body);
+    let headers = [
+        (header::ETAG, HeaderValue::from_str(&etag).expect("hex ETag is a valid header value")),
+        (header::CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL)),
+    ];
+
+    if if_none_match.matches(&etag) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    (
+        headers,
+        [(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_none_match(value: &str) -> IfNoneMatch {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+        IfNoneMatch::from_headers(&headers)
+    }
+
+    #[test]
+    fn test_etag_follows_the_body() {
+        let etag = etag_for(br#"{"name":"Acme"}"#);
+        assert_eq!(etag, etag_for(br#"{"name":"Acme"}"#));
+        assert_ne!(etag, etag_for(br#"{"name":"Acme Ltd"}"#));
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+    }
+
+    #[test]
+    fn test_if_none_match_comparison() {
+        let etag = etag_for(b"{}");
+        assert!(if_none_match(&etag).matches(&etag));
+        assert!(if_none_match(&format!("\"other\", W/{}", etag)).matches(&etag));
+        assert!(if_none_match("*").matches(&etag));
+        assert!(!if_none_match("\"other\"").matches(&etag));
+        assert!(!IfNoneMatch::default().matches(&etag));
+    }
+
+    #[test]
+    fn test_matching_request_gets_not_modified() {
+        let value = serde_json::json!({ "name": "Acme" });
+        let fresh = json(&IfNoneMatch::default(), &value);
+        assert_eq!(fresh.status(), StatusCode::OK);
+        let etag = fresh.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let cached = json(&if_none_match(&etag), &value);
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(cached.headers()[header::ETAG], etag.as_str());
+    }
+}
@@ -1,27 +1,38 @@
+pub mod reconcile;
+pub mod scanning;
+pub mod thumbnails;
+
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    http::{StatusCode, header},
+    extract::{multipart::MultipartError, Multipart, Path, Query, State},
+    http::header,
     response::{IntoResponse, Json},
     routing::{get, post, delete},
     Router,
     body::Body,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::Arc;
 use uuid::Uuid;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
-use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::pagination::{split_total, WithTotal};
+use crate::{AppState, ApiError, ApiResult, AppError, PaginatedResponse, PaginationParams};
 use resolve_shared::File;
+use reconcile::{ReconcileOptions, ReconcileReport};
+use scanning::{ClamAvScanner, ScanStatus, ScanVerdict};
 
 pub fn file_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(list_files))
         .route("/upload", post(upload_file))
+        .route("/reconcile", post(reconcile_files))
         .route("/:id", get(get_file).delete(delete_file))
         .route("/:id/download", get(download_file))
+        .route("/:id/thumbnail", get(download_thumbnail))
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,8 +42,6 @@ pub struct ListFilesQuery {
     pub asset_id: Option<Uuid>,
     pub project_id: Option<Uuid>,
     pub kb_article_id: Option<Uuid>,
-    pub page: Option<u32>,
-    pub limit: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,126 +50,80 @@ pub struct FileResponse {
     pub file: File,
     pub download_url: String,
     pub file_size_formatted: String,
+    pub has_thumbnail: bool,
 }
 
+/// Filters for `list_files`; unset filters bind NULL and match everything
+const FILE_LIST_FILTER: &str = "($1::uuid IS NULL OR client_id = $1)
+    AND ($2::uuid IS NULL OR ticket_id = $2)
+    AND ($3::uuid IS NULL OR asset_id = $3)
+    AND ($4::uuid IS NULL OR project_id = $4)
+    AND ($5::uuid IS NULL OR kb_article_id = $5)";
+
 async fn list_files(
     State(state): State<Arc<AppState>>,
     Query(query): Query<ListFilesQuery>,
+    Query(pagination): Query<PaginationParams>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
-    let page = query.page.unwrap_or(1);
-    let limit = query.limit.unwrap_or(50).min(100);
-    let offset = (page - 1) * limit;
-
-    // Build dynamic WHERE clause based on query parameters
-    let mut conditions = Vec::new();
-    let mut params: Vec<Box<dyn sqlx::Encode<'_, sqlx::Postgres> + Send + Sync>> = Vec::new();
-    let mut param_count = 0;
-
-    if let Some(client_id) = query.client_id {
-        param_count += 1;
-        conditions.push(format!("client_id = ${}", param_count));
-        params.push(Box::new(client_id));
-    }
-
-    if let Some(ticket_id) = query.ticket_id {
-        param_count += 1;
-        conditions.push(format!("ticket_id = ${}", param_count));
-        params.push(Box::new(ticket_id));
-    }
-
-    if let Some(asset_id) = query.asset_id {
-        param_count += 1;
-        conditions.push(format!("asset_id = ${}", param_count));
-        params.push(Box::new(asset_id));
-    }
-
-    if let Some(project_id) = query.project_id {
-        param_count += 1;
-        conditions.push(format!("project_id = ${}", param_count));
-        params.push(Box::new(project_id));
-    }
-
-    if let Some(kb_article_id) = query.kb_article_id {
-        param_count += 1;
-        conditions.push(format!("kb_article_id = ${}", param_count));
-        params.push(Box::new(kb_article_id));
-    }
-
-    // For simplicity, using basic queries. In production, use a proper query builder
-    let files = if conditions.is_empty() {
-        sqlx::query_as!(
-            File,
-            r#"
-            SELECT id, client_id, ticket_id, asset_id, project_id, kb_article_id,
-                   filename, original_filename, mime_type, file_size, file_path,
-                   uploaded_by, created_at
-            FROM files
-            ORDER BY created_at DESC
-            LIMIT $1 OFFSET $2
-            "#,
-            limit as i64,
-            offset as i64
-        )
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    } else if let Some(client_id) = query.client_id {
-        sqlx::query_as!(
-            File,
-            r#"
-            SELECT id, client_id, ticket_id, asset_id, project_id, kb_article_id,
-                   filename, original_filename, mime_type, file_size, file_path,
-                   uploaded_by, created_at
-            FROM files
-            WHERE client_id = $1
-            ORDER BY created_at DESC
-            LIMIT $2 OFFSET $3
-            "#,
-            client_id,
-            limit as i64,
-            offset as i64
-        )
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    } else {
-        // Handle other single-parameter queries
-        sqlx::query_as!(
-            File,
-            r#"
-            SELECT id, client_id, ticket_id, asset_id, project_id, kb_article_id,
-                   filename, original_filename, mime_type, file_size, file_path,
-                   uploaded_by, created_at
-            FROM files
-            ORDER BY created_at DESC
-            LIMIT $1 OFFSET $2
-            "#,
-            limit as i64,
-            offset as i64
-        )
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+) -> ApiResult<Json<PaginatedResponse<FileResponse>>> {
+    let rows = sqlx::query_as::<_, WithTotal<File>>(&format!(
+        r#"
+        SELECT id, client_id, ticket_id, asset_id, project_id, kb_article_id,
+               filename, original_filename, mime_type, file_size, file_path,
+               uploaded_by, created_at, COUNT(*) OVER() AS total_count
+        FROM files
+        WHERE {}
+        ORDER BY created_at DESC, id DESC
+        LIMIT $6 OFFSET $7
+        "#,
+        FILE_LIST_FILTER
+    ))
+    .bind(query.client_id)
+    .bind(query.ticket_id)
+    .bind(query.asset_id)
+    .bind(query.project_id)
+    .bind(query.kb_article_id)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let (files, total) = split_total(rows);
+    let total = match total {
+        Some(total) => total,
+        None => {
+            sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM files WHERE {}", FILE_LIST_FILTER))
+                .bind(query.client_id)
+                .bind(query.ticket_id)
+                .bind(query.asset_id)
+                .bind(query.project_id)
+                .bind(query.kb_article_id)
+                .fetch_one(&state.db_pool)
+                .await?
+        }
     };
 
+    let file_ids: Vec<Uuid> = files.iter().map(|file| file.id).collect();
+    let with_thumbnails = files_with_thumbnails(&state.db_pool, &file_ids).await?;
+
     // Add download URLs and format file sizes
     let file_responses: Vec<FileResponse> = files.into_iter().map(|file| {
         FileResponse {
             download_url: format!("/api/v1/files/{}/download", file.id),
             file_size_formatted: format_file_size(file.file_size),
+            has_thumbnail: with_thumbnails.contains(&file.id),
             file: file,
         }
     }).collect();
 
-    Ok(Json(file_responses))
+    Ok(Json(PaginatedResponse::new(file_responses, &pagination, total)))
 }
 
 async fn get_file(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let file = sqlx::query_as!(
         File,
         r#"
@@ -173,13 +136,15 @@ async fn get_file(
         id
     )
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("File"))?;
+
+    let has_thumbnail = files_with_thumbnails(&state.db_pool, &[file.id]).await?.contains(&file.id);
 
     let file_response = FileResponse {
         download_url: format!("/api/v1/files/{}/download", file.id),
         file_size_formatted: format_file_size(file.file_size),
+        has_thumbnail,
         file,
     };
 
@@ -189,8 +154,9 @@ async fn get_file(
 async fn upload_file(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    meta: RequestMeta,
     mut multipart: Multipart,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let mut file_data = Vec::new();
     let mut original_filename = String::new();
     let mut mime_type = "application/octet-stream".to_string();
@@ -201,7 +167,7 @@ async fn upload_file(
     let mut kb_article_id: Option<Uuid> = None;
 
     // Process multipart form data
-    while let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? {
+    while let Some(field) = multipart.next_field().await.map_err(invalid_form)? {
         let name = field.name().unwrap_or("").to_string();
         
         match name.as_str() {
@@ -214,26 +180,26 @@ async fn upload_file(
                     mime_type = content_type.to_string();
                 }
                 
-                file_data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?.to_vec();
+                file_data = field.bytes().await.map_err(invalid_form)?.to_vec();
             },
             "client_id" => {
-                let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                let value = field.text().await.map_err(invalid_form)?;
                 client_id = Uuid::parse_str(&value).ok();
             },
             "ticket_id" => {
-                let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                let value = field.text().await.map_err(invalid_form)?;
                 ticket_id = Uuid::parse_str(&value).ok();
             },
             "asset_id" => {
-                let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                let value = field.text().await.map_err(invalid_form)?;
                 asset_id = Uuid::parse_str(&value).ok();
             },
             "project_id" => {
-                let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                let value = field.text().await.map_err(invalid_form)?;
                 project_id = Uuid::parse_str(&value).ok();
             },
             "kb_article_id" => {
-                let value = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                let value = field.text().await.map_err(invalid_form)?;
                 kb_article_id = Uuid::parse_str(&value).ok();
             },
             _ => {}
@@ -241,30 +207,121 @@ async fn upload_file(
     }
 
     if file_data.is_empty() || original_filename.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request("A non-empty 'file' field is required"));
     }
 
-    // Generate unique filename and file path
     let file_id = Uuid::new_v4();
-    let file_extension = std::path::Path::new(&original_filename)
+    let new_file = NewFile {
+        id: file_id,
+        client_id,
+        ticket_id,
+        asset_id,
+        project_id,
+        kb_article_id,
+        uploaded_by: auth.0.id,
+        original_filename: &original_filename,
+        mime_type: &mime_type,
+        data: &file_data,
+    };
+
+    let stored = match store_file(&state.db_pool, new_file).await {
+        Ok(stored) => stored,
+        Err(StoreFileError::Infected(signature)) => {
+            tracing::warn!("Rejected infected upload '{}' ({})", original_filename, signature);
+            log_rejected_upload(&state.db_pool, &meta, auth.0.id, file_id, &original_filename, &signature).await;
+            return Err(ApiError::validation_single("file", "File failed the virus scan"));
+        }
+        Err(StoreFileError::Scan(e)) => {
+            tracing::error!("Virus scan failed for upload '{}': {}", original_filename, e);
+            return Err(ApiError::unavailable("Virus scanning is unavailable, try again later"));
+        }
+        Err(e) => {
+            tracing::error!("Error storing upload '{}': {}", original_filename, e);
+            return Err(ApiError::internal("Failed to store file"));
+        }
+    };
+
+    // Log the upload
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UPLOAD", "file", file_id)).await;
+
+    Ok(Json(serde_json::json!({
+        "id": file_id,
+        "filename": stored.filename,
+        "original_filename": original_filename,
+        "file_size": file_data.len(),
+        "scan_status": stored.scan_status.as_str(),
+        "has_thumbnail": stored.has_thumbnail,
+        "message": "File uploaded successfully"
+    })))
+}
+
+/// A file to store, from an upload or from elsewhere such as an email
+/// attachment
+pub struct NewFile<'a> {
+    pub id: Uuid,
+    pub client_id: Option<Uuid>,
+    pub ticket_id: Option<Uuid>,
+    pub asset_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub kb_article_id: Option<Uuid>,
+    pub uploaded_by: Uuid,
+    pub original_filename: &'a str,
+    pub mime_type: &'a str,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub struct StoredFile {
+    pub id: Uuid,
+    pub filename: String,
+    pub scan_status: ScanStatus,
+    pub has_thumbnail: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreFileError {
+    #[error("File is infected: {0}")]
+    Infected(String),
+    #[error(transparent)]
+    Scan(#[from] scanning::ScanError),
+    #[error("Failed to write file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Scan, write and record a file. Nothing is written when the scanner
+/// reports it infected.
+pub async fn store_file(db_pool: &sqlx::PgPool, file: NewFile<'_>) -> Result<StoredFile, StoreFileError> {
+    // Scan before anything is written when a ClamAV daemon is configured
+    let scan_status = match ClamAvScanner::from_env() {
+        Some(scanner) => match scanner.scan(file.data).await? {
+            ScanVerdict::Infected(signature) => return Err(StoreFileError::Infected(signature)),
+            verdict => verdict.status(),
+        },
+        None => ScanStatus::NotScanned,
+    };
+
+    // Generate unique filename and file path
+    let file_extension = std::path::Path::new(file.original_filename)
         .extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("");
-    
+
     let filename = if file_extension.is_empty() {
-        file_id.to_string()
+        file.id.to_string()
     } else {
-        format!("{}.{}", file_id, file_extension)
+        format!("{}.{}", file.id, file_extension)
     };
 
     // Create upload directory if it doesn't exist
     let upload_dir = get_upload_directory();
-    fs::create_dir_all(&upload_dir).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    fs::create_dir_all(&upload_dir).await?;
 
     // Write file to disk
     let file_path = format!("{}/{}", upload_dir, filename);
-    let mut file = fs::File::create(&file_path).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    file.write_all(&file_data).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut disk_file = fs::File::create(&file_path).await?;
+    disk_file.write_all(file.data).await?;
 
     // Save file metadata to database
     sqlx::query!(
@@ -276,40 +333,40 @@ async fn upload_file(
         )
         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, NOW())
         "#,
-        file_id,
-        client_id,
-        ticket_id,
-        asset_id,
-        project_id,
-        kb_article_id,
+        file.id,
+        file.client_id,
+        file.ticket_id,
+        file.asset_id,
+        file.project_id,
+        file.kb_article_id,
         filename,
-        original_filename,
-        mime_type,
-        file_data.len() as i64,
+        file.original_filename,
+        file.mime_type,
+        file.data.len() as i64,
         file_path,
-        auth.0.id
+        file.uploaded_by
     )
-    .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .execute(db_pool)
+    .await?;
 
-    // Log the upload
-    log_audit_action(&state.db_pool, auth.0.id, "UPLOAD", "file", file_id).await;
+    // Thumbnail problems never fail the upload
+    let thumbnail_path = thumbnails::create_thumbnail(&upload_dir, file.id, file.mime_type, file.data).await;
 
-    Ok(Json(serde_json::json!({
-        "id": file_id,
-        "filename": filename,
-        "original_filename": original_filename,
-        "file_size": file_data.len(),
-        "message": "File uploaded successfully"
-    })))
+    sqlx::query("UPDATE files SET scan_status = $2, thumbnail_path = $3 WHERE id = $1")
+        .bind(file.id)
+        .bind(scan_status.as_str())
+        .bind(&thumbnail_path)
+        .execute(db_pool)
+        .await?;
+
+    Ok(StoredFile { id: file.id, filename, scan_status, has_thumbnail: thumbnail_path.is_some() })
 }
 
 async fn download_file(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let file = sqlx::query_as!(
         File,
         r#"
@@ -322,17 +379,19 @@ async fn download_file(
         id
     )
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("File"))?;
 
     // Check if file exists on disk
     if !tokio::fs::metadata(&file.file_path).await.is_ok() {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::not_found("File"));
     }
 
     // Read file content
-    let file_content = fs::read(&file.file_path).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let file_content = fs::read(&file.file_path).await.map_err(|e| {
+        tracing::error!("Error reading file {}: {}", file.id, e);
+        ApiError::internal("Failed to read file")
+    })?;
 
     // Create response with appropriate headers
     let mut headers = axum::http::HeaderMap::new();
@@ -354,41 +413,145 @@ async fn download_file(
     Ok((headers, file_content))
 }
 
+async fn download_thumbnail(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    _auth: AuthUser,
+) -> ApiResult<impl IntoResponse> {
+    let thumbnail_path = sqlx::query_scalar::<_, Option<String>>("SELECT thumbnail_path FROM files WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .flatten()
+        .ok_or_else(|| ApiError::not_found("Thumbnail"))?;
+
+    let thumbnail = fs::read(&thumbnail_path).await.map_err(|_| ApiError::not_found("Thumbnail"))?;
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "image/png".parse().unwrap());
+    headers.insert(header::CACHE_CONTROL, "private, max-age=86400".parse().unwrap());
+
+    Ok((headers, thumbnail))
+}
+
 async fn delete_file(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+    meta: RequestMeta,
+) -> ApiResult<impl IntoResponse> {
+    let thumbnail_path = sqlx::query_scalar::<_, Option<String>>("SELECT thumbnail_path FROM files WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .flatten();
+
     // Get file info before deletion
     let file = sqlx::query!(
         "SELECT file_path FROM files WHERE id = $1",
         id
     )
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("File"))?;
 
     // Delete from database
     let result = sqlx::query!("DELETE FROM files WHERE id = $1", id)
         .execute(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::not_found("File"));
     }
 
     // Delete file from disk (ignore errors if file doesn't exist)
     let _ = fs::remove_file(&file.file_path).await;
+    if let Some(thumbnail_path) = thumbnail_path {
+        let _ = fs::remove_file(thumbnail_path).await;
+    }
 
     // Log the deletion
-    log_audit_action(&state.db_pool, auth.0.id, "DELETE", "file", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "DELETE", "file", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "File deleted successfully" })))
 }
 
-fn get_upload_directory() -> String {
+#[derive(Debug, Default, Deserialize)]
+pub struct ReconcileRequest {
+    /// Defaults to a dry run
+    pub dry_run: Option<bool>,
+    #[serde(default)]
+    pub delete_unreferenced_objects: bool,
+    pub grace_hours: Option<i64>,
+}
+
+/// Compare file records with storage and report, or with `dry_run: false`
+/// fix, the mismatches. Admin only.
+async fn reconcile_files(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Json(req): Json<ReconcileRequest>,
+) -> ApiResult<Json<ReconcileReport>> {
+    if !auth.is_admin() {
+        return Err(ApiError::forbidden("File reconciliation is only available to administrators"));
+    }
+    if req.grace_hours.is_some_and(|hours| hours < 0) {
+        return Err(ApiError::validation_single("grace_hours", "grace_hours cannot be negative"));
+    }
+
+    let defaults = ReconcileOptions::default();
+    let options = ReconcileOptions {
+        dry_run: req.dry_run.unwrap_or(true),
+        delete_unreferenced_objects: req.delete_unreferenced_objects,
+        grace_period: req.grace_hours.map(chrono::Duration::hours).unwrap_or(defaults.grace_period),
+        ..defaults
+    };
+
+    let report = reconcile::reconcile(&state.db_pool, &options).await.map_err(|e| match e {
+        reconcile::ReconcileError::StorageUnavailable(_) => ApiError::conflict(e.to_string()),
+        e => {
+            tracing::error!("Error reconciling files: {}", e);
+            ApiError::internal("Failed to reconcile files")
+        }
+    })?;
+
+    if !report.dry_run && report.total() > 0 {
+        let summary = serde_json::json!({
+            "missing_objects": report.missing_objects.len(),
+            "orphaned_files": report.orphaned_files.len(),
+            "unreferenced_objects": report.unreferenced_objects.len(),
+            "unreferenced_objects_deleted": report.unreferenced_objects_deleted,
+            "deferred": report.deferred,
+        });
+        audit::record(
+            &state.db_pool,
+            &meta,
+            AuditEvent::new(auth.user.id, "RECONCILE", "file", Uuid::nil()).after(&summary),
+        )
+        .await;
+    }
+
+    Ok(Json(report))
+}
+
+fn invalid_form(e: MultipartError) -> AppError {
+    ApiError::bad_request(format!("Invalid multipart form: {}", e.body_text()))
+}
+
+/// IDs among `file_ids` that have a stored thumbnail
+async fn files_with_thumbnails(db_pool: &sqlx::PgPool, file_ids: &[Uuid]) -> Result<HashSet<Uuid>, sqlx::Error> {
+    let ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM files WHERE id = ANY($1) AND thumbnail_path IS NOT NULL"
+    )
+    .bind(file_ids)
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(ids.into_iter().collect())
+}
+
+pub(crate) fn get_upload_directory() -> String {
     std::env::var("UPLOAD_DIRECTORY").unwrap_or_else(|_| "./uploads".to_string())
 }
 
@@ -409,20 +572,18 @@ fn format_file_size(size: i64) -> String {
     }
 }
 
-async fn log_audit_action(
+async fn log_rejected_upload(
     db_pool: &sqlx::PgPool,
+    meta: &RequestMeta,
     user_id: Uuid,
-    action: &str,
-    entity_type: &str,
-    entity_id: Uuid,
+    file_id: Uuid,
+    original_filename: &str,
+    signature: &str,
 ) {
-    let _ = sqlx::query!(
-        r#"
-        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        user_id, action, entity_type, entity_id
-    )
-    .execute(db_pool)
-    .await;
-}
\ No newline at end of file
+    let rejected = serde_json::json!({
+        "original_filename": original_filename,
+        "scan_status": ScanStatus::Infected.as_str(),
+        "signature": signature,
+    });
+    audit::record(db_pool, meta, AuditEvent::new(user_id, "UPLOAD_REJECTED", "file", file_id).after(&rejected)).await;
+}
@@ -0,0 +1,271 @@
+//! Reconciles the `files` table against the upload directory.
+//!
+//! Three kinds of mismatch are found:
+//! - rows whose stored object is gone, which are deleted;
+//! - rows whose parent entity was hard-deleted (`orphaned_at` is set by a
+//!   trigger), which are deleted along with their object and thumbnail;
+//! - objects in the upload directory no row refers to, deleted only when
+//!   asked and once older than the grace period, so an upload still being
+//!   written is never touched.
+//!
+//! Runs are dry runs by default: nothing changes and the report lists what
+//! would be removed. A run that applies changes deletes at most
+//! `max_deletions` rows and objects; the rest wait for later runs. An upload
+//! directory that is missing, or empty while rows exist, looks like every
+//! object went missing (storage not mounted, wrong path), so the run stops
+//! instead.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::Path;
+use tokio::fs;
+use uuid::Uuid;
+
+use super::get_upload_directory;
+
+/// Default age before an unreferenced object counts as abandoned
+pub const DEFAULT_GRACE_HOURS: i64 = 24;
+/// Default cap on rows and objects deleted in one run
+pub const DEFAULT_MAX_DELETIONS: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct ReconcileOptions {
+    pub dry_run: bool,
+    /// Also delete objects in storage that no row refers to
+    pub delete_unreferenced_objects: bool,
+    pub grace_period: Duration,
+    pub upload_dir: String,
+    /// Rows and objects deleted per run at most
+    pub max_deletions: usize,
+}
+
+impl Default for ReconcileOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: true,
+            delete_unreferenced_objects: false,
+            grace_period: Duration::hours(DEFAULT_GRACE_HOURS),
+            upload_dir: get_upload_directory(),
+            max_deletions: DEFAULT_MAX_DELETIONS,
+        }
+    }
+}
+
+impl ReconcileOptions {
+    /// Options for the scheduled run. FILE_RECONCILE_APPLY=true makes changes
+    /// instead of reporting them; FILE_RECONCILE_DELETE_UNREFERENCED=true
+    /// also removes unreferenced objects older than FILE_RECONCILE_GRACE_HOURS.
+    /// FILE_RECONCILE_MAX_DELETIONS caps what one run deletes.
+    pub fn from_env() -> Self {
+        let flag = |name: &str| std::env::var(name).map(|v| v == "true").unwrap_or(false);
+        let grace_hours = std::env::var("FILE_RECONCILE_GRACE_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|hours| *hours >= 0)
+            .unwrap_or(DEFAULT_GRACE_HOURS);
+        let max_deletions = std::env::var("FILE_RECONCILE_MAX_DELETIONS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_DELETIONS);
+
+        Self {
+            dry_run: !flag("FILE_RECONCILE_APPLY"),
+            delete_unreferenced_objects: flag("FILE_RECONCILE_DELETE_UNREFERENCED"),
+            grace_period: Duration::hours(grace_hours),
+            max_deletions,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct FileEntry {
+    pub id: Uuid,
+    pub original_filename: String,
+    pub file_path: String,
+    #[serde(skip)]
+    pub thumbnail_path: Option<String>,
+    pub orphaned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReconcileReport {
+    pub dry_run: bool,
+    /// Rows whose stored object is missing
+    pub missing_objects: Vec<FileEntry>,
+    /// Rows whose parent entity was deleted
+    pub orphaned_files: Vec<FileEntry>,
+    /// Stored objects older than the grace period with no row
+    pub unreferenced_objects: Vec<String>,
+    /// Whether the unreferenced objects were (or would be) deleted
+    pub unreferenced_objects_deleted: bool,
+    /// Deletions left for a later run by the per-run cap
+    pub deferred: usize,
+    pub errors: Vec<String>,
+}
+
+impl ReconcileReport {
+    /// Mismatches found, whether or not they were fixed
+    pub fn total(&self) -> usize {
+        self.missing_objects.len() + self.orphaned_files.len() + self.unreferenced_objects.len()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReconcileError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Failed to read upload directory: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Upload directory {0} is missing or empty while files are recorded")]
+    StorageUnavailable(String),
+}
+
+fn file_name(path: &str) -> Option<String> {
+    Path::new(path).file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+async fn object_exists(path: &str) -> Result<bool, std::io::Error> {
+    match fs::metadata(path).await {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Remove an object, treating one that's already gone as removed
+async fn remove_object(path: &str, report: &mut ReconcileReport) {
+    match fs::remove_file(path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => report.errors.push(format!("Failed to delete {}: {}", path, e)),
+    }
+}
+
+/// Objects in `upload_dir` that no row refers to, last modified before `cutoff`
+async fn unreferenced_objects(
+    upload_dir: &str,
+    referenced: &HashSet<String>,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<String>, std::io::Error> {
+    let mut entries = match fs::read_dir(upload_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut unreferenced = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if referenced.contains(&name) {
+            continue;
+        }
+        let modified: DateTime<Utc> = metadata.modified()?.into();
+        if modified < cutoff {
+            unreferenced.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+    unreferenced.sort();
+
+    Ok(unreferenced)
+}
+
+pub async fn reconcile(pool: &PgPool, options: &ReconcileOptions) -> Result<ReconcileReport, ReconcileError> {
+    let mut report = ReconcileReport {
+        dry_run: options.dry_run,
+        unreferenced_objects_deleted: options.delete_unreferenced_objects,
+        ..ReconcileReport::default()
+    };
+
+    let storage_empty = match fs::read_dir(&options.upload_dir).await {
+        Ok(mut entries) => entries.next_entry().await?.is_none(),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(ReconcileError::StorageUnavailable(options.upload_dir.clone()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let files = sqlx::query_as::<_, FileEntry>(
+        "SELECT id, original_filename, file_path, thumbnail_path, orphaned_at FROM files ORDER BY created_at"
+    )
+    .fetch_all(pool)
+    .await?;
+    if storage_empty && !files.is_empty() {
+        return Err(ReconcileError::StorageUnavailable(options.upload_dir.clone()));
+    }
+
+    let mut referenced = HashSet::new();
+    for file in files {
+        referenced.extend(file_name(&file.file_path));
+        referenced.extend(file.thumbnail_path.as_deref().and_then(file_name));
+
+        if file.orphaned_at.is_some() {
+            tracing::info!("File {} ({}) belongs to a deleted entity", file.id, file.file_path);
+            report.orphaned_files.push(file);
+        } else if !object_exists(&file.file_path).await? {
+            tracing::warn!("File {} is missing its stored object {}", file.id, file.file_path);
+            report.missing_objects.push(file);
+        }
+    }
+
+    let cutoff = Utc::now() - options.grace_period;
+    report.unreferenced_objects = unreferenced_objects(&options.upload_dir, &referenced, cutoff).await?;
+    for path in &report.unreferenced_objects {
+        tracing::info!("Stored object {} has no file record", path);
+    }
+
+    if options.dry_run {
+        return Ok(report);
+    }
+
+    let mut stale: Vec<&FileEntry> = report.missing_objects.iter().chain(&report.orphaned_files).collect();
+    let mut unreferenced = if options.delete_unreferenced_objects {
+        report.unreferenced_objects.clone()
+    } else {
+        Vec::new()
+    };
+    let wanted = stale.len() + unreferenced.len();
+    stale.truncate(options.max_deletions);
+    unreferenced.truncate(options.max_deletions - stale.len());
+    let deferred = wanted - stale.len() - unreferenced.len();
+    if deferred > 0 {
+        tracing::warn!(
+            "File reconciliation capped at {} deletions; {} left for the next run",
+            options.max_deletions,
+            deferred
+        );
+    }
+
+    let stale_ids: Vec<Uuid> = stale.iter().map(|file| file.id).collect();
+    let stale_objects: Vec<String> = stale
+        .iter()
+        .flat_map(|file| std::iter::once(file.file_path.clone()).chain(file.thumbnail_path.clone()))
+        .collect();
+    report.deferred = deferred;
+
+    if !stale_ids.is_empty() {
+        sqlx::query("DELETE FROM files WHERE id = ANY($1)")
+            .bind(&stale_ids)
+            .execute(pool)
+            .await?;
+    }
+
+    // Rows are gone first so a failed removal leaves an unreferenced
+    // object for the next run rather than a row pointing at nothing
+    for path in stale_objects {
+        remove_object(&path, &mut report).await;
+    }
+
+    for path in unreferenced {
+        remove_object(&path, &mut report).await;
+    }
+
+    Ok(report)
+}
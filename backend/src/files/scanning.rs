@@ -0,0 +1,212 @@
+//! Upload virus scanning
+//!
+//! Streams uploaded bytes to a ClamAV daemon using the INSTREAM command
+//! before the file is stored. Scanning only happens when `CLAMAV_ADDRESS`
+//! (e.g. `127.0.0.1:3310`) is set; otherwise uploads are stored as
+//! `not_scanned`.
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Bytes sent per INSTREAM chunk; must stay under clamd's `StreamMaxLength`
+const CHUNK_SIZE: usize = 64 * 1024;
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+
+/// Value stored in `files.scan_status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanStatus {
+    NotScanned,
+    Clean,
+    Infected,
+}
+
+impl ScanStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanStatus::NotScanned => "not_scanned",
+            ScanStatus::Clean => "clean",
+            ScanStatus::Infected => "infected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Signature name reported by ClamAV
+    Infected(String),
+}
+
+impl ScanVerdict {
+    pub fn status(&self) -> ScanStatus {
+        match self {
+            ScanVerdict::Clean => ScanStatus::Clean,
+            ScanVerdict::Infected(_) => ScanStatus::Infected,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    #[error("ClamAV connection failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ClamAV scan timed out")]
+    Timeout,
+    #[error("ClamAV returned an error: {0}")]
+    Daemon(String),
+    #[error("Unexpected ClamAV response: {0}")]
+    UnexpectedResponse(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ClamAvScanner {
+    address: String,
+    timeout: Duration,
+}
+
+impl ClamAvScanner {
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Scanner for `CLAMAV_ADDRESS`, or `None` when scanning isn't configured
+    pub fn from_env() -> Option<Self> {
+        std::env::var("CLAMAV_ADDRESS")
+            .ok()
+            .filter(|address| !address.trim().is_empty())
+            .map(|address| Self::new(address.trim()))
+    }
+
+    pub async fn scan(&self, data: &[u8]) -> Result<ScanVerdict, ScanError> {
+        tokio::time::timeout(self.timeout, self.scan_stream(data))
+            .await
+            .map_err(|_| ScanError::Timeout)?
+    }
+
+    async fn scan_stream(&self, data: &[u8]) -> Result<ScanVerdict, ScanError> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+
+        // `z` prefix: null-terminated command and response
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in data.chunks(CHUNK_SIZE) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await?;
+        stream.flush().await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+
+        parse_response(&String::from_utf8_lossy(&response))
+    }
+}
+
+/// Parse a clamd reply such as `stream: OK` or `stream: Eicar-Signature FOUND`
+pub fn parse_response(response: &str) -> Result<ScanVerdict, ScanError> {
+    let response = response.trim_end_matches(['\0', '\n', '\r']).trim();
+    let result = response
+        .split_once(':')
+        .map(|(_, result)| result.trim())
+        .unwrap_or(response);
+
+    if result == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanVerdict::Infected(signature.trim().to_string()))
+    } else if let Some(message) = result.strip_suffix(" ERROR") {
+        Err(ScanError::Daemon(message.trim().to_string()))
+    } else {
+        Err(ScanError::UnexpectedResponse(response.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const EICAR: &[u8] = br"X5O!P%@AP[4\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+    /// Minimal clamd stand-in: reads one INSTREAM request and flags EICAR
+    async fn mock_clamd() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let mut command = [0u8; 10];
+            socket.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+
+            let mut body = Vec::new();
+            loop {
+                let mut len = [0u8; 4];
+                socket.read_exact(&mut len).await.unwrap();
+                let len = u32::from_be_bytes(len) as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; len];
+                socket.read_exact(&mut chunk).await.unwrap();
+                body.extend_from_slice(&chunk);
+            }
+
+            let reply: &[u8] = if body.windows(EICAR.len()).any(|w| w == EICAR) {
+                b"stream: Eicar-Test-Signature FOUND\0"
+            } else {
+                b"stream: OK\0"
+            };
+            socket.write_all(reply).await.unwrap();
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn test_clean_upload() {
+        let scanner = ClamAvScanner::new(mock_clamd().await);
+        // Larger than one chunk to exercise chunking
+        let data = vec![b'a'; CHUNK_SIZE * 2 + 17];
+
+        assert_eq!(scanner.scan(&data).await.unwrap(), ScanVerdict::Clean);
+    }
+
+    #[tokio::test]
+    async fn test_infected_upload() {
+        let scanner = ClamAvScanner::new(mock_clamd().await);
+
+        let verdict = scanner.scan(EICAR).await.unwrap();
+        assert_eq!(verdict, ScanVerdict::Infected("Eicar-Test-Signature".to_string()));
+        assert_eq!(verdict.status(), ScanStatus::Infected);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_daemon_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        assert!(ClamAvScanner::new(address).scan(b"hello").await.is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(parse_response("stream: OK\0").unwrap(), ScanVerdict::Clean);
+        assert!(matches!(
+            parse_response("INSTREAM size limit exceeded. ERROR\0"),
+            Err(ScanError::Daemon(_))
+        ));
+        assert!(matches!(parse_response("PONG"), Err(ScanError::UnexpectedResponse(_))));
+    }
+}
@@ -0,0 +1,120 @@
+//! Image thumbnails
+//!
+//! Uploads with an `image/*` MIME type get a PNG thumbnail, at most
+//! 256px on its longest side, written next to the original as
+//! `thumb_<file id>.png`. Anything that can't be decoded is skipped
+//! without failing the upload.
+
+use image::{DynamicImage, ImageOutputFormat};
+use std::io::Cursor;
+use tokio::fs;
+use uuid::Uuid;
+
+pub const THUMBNAIL_MAX_DIMENSION: u32 = 256;
+
+pub fn is_image(mime_type: &str) -> bool {
+    mime_type.to_ascii_lowercase().starts_with("image/")
+}
+
+/// Decode `data` and encode a PNG thumbnail that fits within
+/// `THUMBNAIL_MAX_DIMENSION`, preserving aspect ratio. Images already small
+/// enough are re-encoded at their original size.
+pub fn generate_thumbnail(data: &[u8]) -> Result<Vec<u8>, image::ImageError> {
+    let original = image::load_from_memory(data)?;
+
+    let thumbnail: DynamicImage = if original.width() > THUMBNAIL_MAX_DIMENSION
+        || original.height() > THUMBNAIL_MAX_DIMENSION
+    {
+        original.thumbnail(THUMBNAIL_MAX_DIMENSION, THUMBNAIL_MAX_DIMENSION)
+    } else {
+        original
+    };
+
+    let mut encoded = Cursor::new(Vec::new());
+    thumbnail.write_to(&mut encoded, ImageOutputFormat::Png)?;
+    Ok(encoded.into_inner())
+}
+
+/// Write a thumbnail for an upload into `upload_dir`, returning its path.
+/// Returns `None` for non-images and for images that fail to decode.
+pub async fn create_thumbnail(upload_dir: &str, file_id: Uuid, mime_type: &str, data: &[u8]) -> Option<String> {
+    if !is_image(mime_type) {
+        return None;
+    }
+
+    let data = data.to_vec();
+    let thumbnail = match tokio::task::spawn_blocking(move || generate_thumbnail(&data)).await {
+        Ok(Ok(thumbnail)) => thumbnail,
+        Ok(Err(e)) => {
+            tracing::warn!("Could not generate thumbnail for file {}: {}", file_id, e);
+            return None;
+        }
+        Err(e) => {
+            tracing::error!("Thumbnail task for file {} failed: {}", file_id, e);
+            return None;
+        }
+    };
+
+    let path = format!("{}/thumb_{}.png", upload_dir, file_id);
+    match fs::write(&path, thumbnail).await {
+        Ok(()) => Some(path),
+        Err(e) => {
+            tracing::warn!("Could not write thumbnail for file {}: {}", file_id, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let buffer = ImageBuffer::from_pixel(width, height, Rgb([40u8, 120, 200]));
+        let mut encoded = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(buffer)
+            .write_to(&mut encoded, ImageOutputFormat::Png)
+            .unwrap();
+        encoded.into_inner()
+    }
+
+    #[test]
+    fn test_thumbnail_preserves_aspect_ratio() {
+        let thumbnail = image::load_from_memory(&generate_thumbnail(&png(1024, 512)).unwrap()).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+        let thumbnail = image::load_from_memory(&generate_thumbnail(&png(300, 900)).unwrap()).unwrap();
+        assert_eq!(thumbnail.height(), 256);
+        assert!((85..=86).contains(&thumbnail.width()));
+    }
+
+    #[test]
+    fn test_small_images_are_not_upscaled() {
+        let thumbnail = image::load_from_memory(&generate_thumbnail(&png(64, 48)).unwrap()).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (64, 48));
+    }
+
+    #[tokio::test]
+    async fn test_create_thumbnail_for_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let upload_dir = dir.path().to_str().unwrap();
+        let file_id = Uuid::new_v4();
+
+        let path = create_thumbnail(upload_dir, file_id, "image/png", &png(800, 600)).await.unwrap();
+
+        assert_eq!(path, format!("{}/thumb_{}.png", upload_dir, file_id));
+        let written = image::open(&path).unwrap();
+        assert_eq!((written.width(), written.height()), (256, 192));
+    }
+
+    #[tokio::test]
+    async fn test_non_images_and_undecodable_images_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let upload_dir = dir.path().to_str().unwrap();
+
+        assert!(create_thumbnail(upload_dir, Uuid::new_v4(), "application/pdf", b"%PDF-1.7").await.is_none());
+        assert!(create_thumbnail(upload_dir, Uuid::new_v4(), "image/png", b"not really a png").await.is_none());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
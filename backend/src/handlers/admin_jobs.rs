@@ -0,0 +1,130 @@
+//! Background Job Administration
+//!
+//! Admin-only view of the scheduled jobs with each one's last run and
+//! current failure streak, per-job run history, and on-demand runs.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::auth::middleware::AuthUserWithRole;
+use crate::jobs::history::{self, JobRun, JobTrigger, DEFAULT_HISTORY_LIMIT};
+use crate::jobs::scheduler::{find_job, JobDefinition, JOBS};
+use crate::jobs::{JobConfig, JobError, JobRunner};
+use crate::middleware::request_id;
+use crate::{ApiError, ApiResult, AppState};
+
+pub fn admin_job_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_jobs))
+        .route("/:name/runs", get(list_job_runs))
+        .route("/:name/run", post(run_job))
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobSummary {
+    pub key: &'static str,
+    pub name: &'static str,
+    pub enabled: bool,
+    pub schedule: Option<String>,
+    pub last_run: Option<JobRun>,
+    pub consecutive_failures: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobRunQuery {
+    pub limit: Option<i64>,
+}
+
+fn require_admin(auth: &AuthUserWithRole) -> ApiResult<()> {
+    if auth.is_admin() {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("Background jobs are only available to administrators"))
+    }
+}
+
+fn job_or_404(name: &str) -> ApiResult<&'static JobDefinition> {
+    find_job(name).ok_or_else(|| ApiError::not_found(format!("Unknown job: {}", name)))
+}
+
+async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+) -> ApiResult<Json<Vec<JobSummary>>> {
+    require_admin(&auth)?;
+
+    let config = JobConfig::default();
+    let mut latest = history::latest_runs(&state.db_pool).await?;
+    let streaks = history::failure_streaks(&state.db_pool).await?;
+
+    let mut jobs = Vec::with_capacity(JOBS.len());
+    for job in JOBS {
+        jobs.push(JobSummary {
+            key: job.key,
+            name: job.name,
+            enabled: config.is_enabled(job.key),
+            schedule: config.cron(job.key),
+            last_run: latest.remove(job.key),
+            consecutive_failures: streaks.get(job.key).copied().unwrap_or(0),
+        });
+    }
+
+    Ok(Json(jobs))
+}
+
+async fn list_job_runs(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(name): Path<String>,
+    Query(query): Query<JobRunQuery>,
+) -> ApiResult<Json<Vec<JobRun>>> {
+    require_admin(&auth)?;
+    let job = job_or_404(&name)?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).clamp(1, 500);
+    Ok(Json(history::runs_for(&state.db_pool, job.key, limit).await?))
+}
+
+/// Start a run now, even if the job is disabled on the schedule. The run
+/// carries on in the background; poll its history for the outcome. Refused
+/// while the job is already running on any instance.
+async fn run_job(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(name): Path<String>,
+) -> ApiResult<(StatusCode, Json<JobRun>)> {
+    require_admin(&auth)?;
+    let job = job_or_404(&name)?;
+
+    let runner = job_runner(&state)?;
+    let run = runner
+        .start(job.key, JobTrigger::Manual(Some(auth.user.id)))
+        .await
+        .map_err(|e| match e {
+            JobError::AlreadyRunning(_) => ApiError::conflict(format!("{} is already running", job.name)),
+            e => ApiError::internal(format!("Failed to start {} job: {}", job.name, e)),
+        })?;
+    let record = run.record.clone();
+
+    tokio::spawn(request_id::propagate(async move {
+        runner.execute(run).await;
+    }));
+
+    Ok((StatusCode::ACCEPTED, Json(record)))
+}
+
+fn job_runner(state: &AppState) -> ApiResult<JobRunner> {
+    let email_service = state
+        .email_service
+        .clone()
+        .ok_or_else(|| ApiError::internal("Email is not configured, so jobs can't be run"))?;
+
+    Ok(JobRunner::new(state.db_pool.clone(), email_service, state.ws_manager.clone(), JobConfig::default()))
+}
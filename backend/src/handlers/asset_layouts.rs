@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
     http::{StatusCode, HeaderMap},
-    response::Json,
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
@@ -12,6 +12,7 @@ use std::sync::Arc;
 use uuid::Uuid;
 use crate::AppState;
 use crate::auth::{extract_token, verify_token};
+use crate::services::asset_fields;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct AssetFieldType {
@@ -237,7 +238,7 @@ async fn create_layout(
     Json(payload): Json<CreateAssetLayoutRequest>,
 ) -> Result<(StatusCode, Json<AssetLayout>), StatusCode> {
     let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    let token_data = verify_token(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let token_data = verify_token(&state.db_pool, &token).await.map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     let layout_id = Uuid::new_v4();
     let now = Utc::now();
@@ -546,34 +547,50 @@ async fn update_asset_custom_fields(
     State(state): State<Arc<AppState>>,
     Path(asset_id): Path<Uuid>,
     Json(payload): Json<UpdateAssetFieldValuesRequest>,
-) -> Result<StatusCode, StatusCode> {
-    // Update field values in a transaction
-    let mut tx = state.db_pool.begin().await.map_err(|e| {
-        tracing::error!("Error starting transaction: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    
-    for (field_id, value) in payload.field_values {
-        sqlx::query(
-            "INSERT INTO asset_field_values (asset_id, field_id, field_value, updated_at)
-             VALUES ($1, $2, $3, NOW())
-             ON CONFLICT (asset_id, field_id)
-             DO UPDATE SET field_value = $3, updated_at = NOW()"
-        )
+) -> Result<StatusCode, Response> {
+    let asset_type = sqlx::query_scalar::<_, String>("SELECT asset_type FROM assets WHERE id = $1")
         .bind(asset_id)
-        .bind(field_id)
-        .bind(&value)
-        .execute(&mut *tx)
+        .fetch_optional(&state.db_pool)
         .await
         .map_err(|e| {
-            tracing::error!("Error updating field value: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            tracing::error!("Error fetching asset: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?
+        .ok_or(StatusCode::NOT_FOUND.into_response())?;
+
+    let definitions = asset_fields::definitions_for_type(&state.db_pool, &asset_type)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching asset layout fields: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         })?;
+
+    // Values arrive keyed by field id; validation works by field name
+    let mut values = std::collections::HashMap::new();
+    for (field_id, value) in payload.field_values {
+        let name = definitions
+            .iter()
+            .find(|d| d.id == field_id)
+            .map(|d| d.field_name.clone())
+            .unwrap_or_else(|| field_id.to_string());
+        values.insert(name, serde_json::Value::String(value));
     }
+    let values = asset_fields::validate(&definitions, &values, false).map_err(IntoResponse::into_response)?;
+
+    // Update field values in a transaction
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        tracing::error!("Error starting transaction: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    
+    asset_fields::save(&mut tx, asset_id, &values).await.map_err(|e| {
+        tracing::error!("Error updating field values: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
     
     tx.commit().await.map_err(|e| {
         tracing::error!("Error committing transaction: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
     
     Ok(StatusCode::NO_CONTENT)
@@ -292,7 +292,7 @@ async fn create_location(
     Json(payload): Json<serde_json::Value>,
 ) -> Result<(StatusCode, Json<Location>), StatusCode> {
     let _token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    let _token_data = verify_token(&_token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let _token_data = verify_token(&state.db_pool, &_token).await.map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     let location_id = Uuid::new_v4();
     
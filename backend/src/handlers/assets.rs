@@ -1,17 +1,27 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Multipart, Path, Query, State},
     http::{StatusCode, HeaderMap},
-    response::Json,
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
+use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 use crate::AppState;
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::concurrency::{self, Precondition};
+use super::clients::ArchiveQuery;
 use crate::auth::{extract_token, verify_token};
+use crate::services::asset_fields::{self, FieldValue, RenderedField};
+use crate::services::asset_import::{self, AssetImportError, ImportReport};
+use crate::services::outbound_webhooks;
+use crate::validation::enums;
+use crate::{ApiError, ApiResult};
+use resolve_shared::AssetStatus;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AssetCreate {
@@ -33,6 +43,9 @@ pub struct AssetCreate {
     pub warranty_expire: Option<chrono::DateTime<Utc>>,
     pub install_date: Option<chrono::DateTime<Utc>>,
     pub notes: Option<String>,
+    /// Values for the custom fields of the asset type's layout, by field name
+    #[serde(default)]
+    pub custom_fields: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -54,6 +67,11 @@ pub struct AssetUpdate {
     pub warranty_expire: Option<chrono::DateTime<Utc>>,
     pub install_date: Option<chrono::DateTime<Utc>>,
     pub notes: Option<String>,
+    /// Custom field values to change, by field name; others are kept
+    #[serde(default)]
+    pub custom_fields: HashMap<String, serde_json::Value>,
+    /// The `updated_at` the client last saw; a newer one gets `409 Conflict`
+    pub expected_updated_at: Option<chrono::DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -64,6 +82,8 @@ pub struct AssetQuery {
     pub asset_type: Option<String>,
     pub status: Option<String>,
     pub search: Option<String>,
+    #[serde(default)]
+    pub include_archived: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -92,14 +112,25 @@ pub struct AssetWithDetails {
     pub notes: Option<String>,
     pub created_at: chrono::DateTime<Utc>,
     pub updated_at: Option<chrono::DateTime<Utc>>,
+    pub archived_at: Option<chrono::DateTime<Utc>>,
+}
+
+/// An asset with its layout's custom fields, for rendering the edit form
+#[derive(Debug, Serialize)]
+pub struct AssetResponse {
+    #[serde(flatten)]
+    pub asset: AssetWithDetails,
+    pub custom_fields: Vec<RenderedField>,
 }
 
 pub fn asset_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(list_assets).post(create_asset))
         .route("/:id", get(get_asset).put(update_asset).delete(delete_asset))
+        .route("/:id/restore", post(restore_asset))
         .route("/:id/monitoring", get(get_asset_monitoring))
         .route("/types", get(get_asset_types))
+        .route("/import", post(import_assets))
 }
 
 async fn list_assets(
@@ -114,14 +145,14 @@ async fn list_assets(
         "SELECT a.id, a.client_id, c.name as client_name, a.name, a.description, 
          a.asset_type, a.make, a.model, a.serial, a.os, a.ip, a.mac, a.uri, 
          a.status, a.location_id, l.name as location_name, a.contact_id, ct.name as contact_name,
-         a.purchase_date, a.warranty_expire, a.install_date, a.notes, a.created_at, a.updated_at
+         a.purchase_date, a.warranty_expire, a.install_date, a.notes, a.created_at, a.updated_at, a.archived_at
          FROM assets a
          LEFT JOIN clients c ON a.client_id = c.id
          LEFT JOIN locations l ON a.location_id = l.id
          LEFT JOIN contacts ct ON a.contact_id = ct.id
-         WHERE a.archived_at IS NULL
+         WHERE {} OR a.archived_at IS NULL
          ORDER BY a.name
-         LIMIT {} OFFSET {}", limit, offset))
+         LIMIT {} OFFSET {}", params.include_archived, limit, offset))
     .fetch_all(&state.db_pool)
     .await
     .map_err(|e| {
@@ -136,16 +167,23 @@ async fn create_asset(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Json(payload): Json<AssetCreate>,
-) -> Result<(StatusCode, Json<AssetWithDetails>), StatusCode> {
+) -> Result<(StatusCode, Json<AssetResponse>), Response> {
     // Extract user from token
     let token = extract_token(&headers)
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-    let _token_data = verify_token(&token)
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
-    
+        .ok_or(StatusCode::UNAUTHORIZED.into_response())?;
+    let _token_data = verify_token(&state.db_pool, &token).await
+        .map_err(|_| StatusCode::UNAUTHORIZED.into_response())?;
+
+    let status = enums::parse::<AssetStatus>(&payload.status, "status").map_err(IntoResponse::into_response)?;
+    let custom_fields = validate_custom_fields(&state, &payload.asset_type, &payload.custom_fields, true).await?;
+
     let asset_id = Uuid::new_v4();
     let now = Utc::now();
-    
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        tracing::error!("Error starting transaction: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+
     sqlx::query(
         "INSERT INTO assets (
             id, client_id, name, description, asset_type, make, model, serial,
@@ -165,7 +203,7 @@ async fn create_asset(
     .bind(payload.ip)
     .bind(payload.mac)
     .bind(payload.uri)
-    .bind(payload.status)
+    .bind(status)
     .bind(payload.location_id)
     .bind(payload.contact_id)
     .bind(payload.purchase_date)
@@ -173,31 +211,58 @@ async fn create_asset(
     .bind(payload.install_date)
     .bind(payload.notes)
     .bind(now)
-    .execute(&state.db_pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         tracing::error!("Error creating asset: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+
+    save_custom_fields(&mut tx, asset_id, &custom_fields).await?;
+    tx.commit().await.map_err(|e| {
+        tracing::error!("Error committing asset: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
     
     // Fetch the created asset
-    let asset = get_asset_by_id(&state, asset_id).await?;
-    Ok((StatusCode::CREATED, Json(asset)))
+    let asset = get_asset_by_id(&state, asset_id).await.map_err(IntoResponse::into_response)?;
+    notify_asset_changed(&state, "created", &asset).await;
+    let response = asset_response(&state, asset).await.map_err(IntoResponse::into_response)?;
+    Ok((StatusCode::CREATED, Json(response)))
 }
 
 async fn get_asset(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<Json<AssetWithDetails>, StatusCode> {
+    Query(params): Query<ArchiveQuery>,
+) -> Result<Json<AssetResponse>, StatusCode> {
     let asset = get_asset_by_id(&state, id).await?;
-    Ok(Json(asset))
+    if asset.archived_at.is_some() && !params.include_archived {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(asset_response(&state, asset).await?))
 }
 
 async fn update_asset(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    meta: RequestMeta,
+    precondition: Precondition,
     Path(id): Path<Uuid>,
     Json(payload): Json<AssetUpdate>,
-) -> Result<Json<AssetWithDetails>, StatusCode> {
+) -> Result<Json<AssetResponse>, Response> {
+    let token = extract_token(&headers)
+        .ok_or(StatusCode::UNAUTHORIZED.into_response())?;
+    let token_data = verify_token(&state.db_pool, &token).await
+        .map_err(|_| StatusCode::UNAUTHORIZED.into_response())?;
+
+    let status = enums::parse_optional::<AssetStatus>(&payload.status, "status").map_err(IntoResponse::into_response)?;
+
+    let before = get_asset_by_id(&state, id).await.map_err(IntoResponse::into_response)?;
+    let unmodified_since = precondition.unmodified_since(payload.expected_updated_at);
+    let asset_type = payload.asset_type.as_deref().unwrap_or(&before.asset_type);
+    let custom_fields = validate_custom_fields(&state, asset_type, &payload.custom_fields, false).await?;
+
     // Build dynamic update query
     let mut set_clauses = Vec::new();
     let mut param_index = 2; // $1 is for id
@@ -218,7 +283,7 @@ async fn update_asset(
     set_clauses.push(format!("updated_at = NOW()"));
     
     if set_clauses.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into_response());
     }
     
     let query = format!(
@@ -226,8 +291,13 @@ async fn update_asset(
         set_clauses.join(", ")
     );
     
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        tracing::error!("Error starting transaction: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+
     // For simplicity, use a basic update query
-    sqlx::query(
+    let updated = sqlx::query(
         "UPDATE assets SET 
          name = COALESCE($2, name),
          description = COALESCE($3, description),
@@ -242,7 +312,8 @@ async fn update_asset(
          status = COALESCE($12, status),
          notes = COALESCE($13, notes),
          updated_at = NOW()
-         WHERE id = $1"
+         WHERE id = $1
+           AND ($14::timestamptz IS NULL OR updated_at IS NULL OR updated_at <= $14)"
     )
     .bind(id)
     .bind(payload.name)
@@ -255,23 +326,57 @@ async fn update_asset(
     .bind(payload.ip)
     .bind(payload.mac)
     .bind(payload.uri)
-    .bind(payload.status)
+    .bind(status)
     .bind(payload.notes)
-    .execute(&state.db_pool)
+    .bind(unmodified_since)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         tracing::error!("Error updating asset: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
-    
-    let asset = get_asset_by_id(&state, id).await?;
-    Ok(Json(asset))
+
+    if updated.rows_affected() == 0 {
+        // Changed since the caller loaded it
+        drop(tx);
+        let asset = get_asset_by_id(&state, id).await.map_err(IntoResponse::into_response)?;
+        return Err(concurrency::conflict(asset));
+    }
+    save_custom_fields(&mut tx, id, &custom_fields).await?;
+    tx.commit().await.map_err(|e| {
+        tracing::error!("Error committing asset update: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+
+    let asset = get_asset_by_id(&state, id).await.map_err(IntoResponse::into_response)?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(token_data.sub, "UPDATE", "asset", id).before(&before).after(&asset),
+    )
+    .await;
+    notify_asset_changed(&state, "updated", &asset).await;
+    let response = asset_response(&state, asset).await.map_err(IntoResponse::into_response)?;
+    Ok(Json(response))
 }
 
 async fn delete_asset(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    meta: RequestMeta,
     Path(id): Path<Uuid>,
 ) -> Result<StatusCode, StatusCode> {
+    let token = extract_token(&headers)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let token_data = verify_token(&state.db_pool, &token).await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let before = get_asset_by_id(&state, id).await?;
+    if before.archived_at.is_some() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
     sqlx::query("UPDATE assets SET archived_at = NOW() WHERE id = $1")
         .bind(id)
         .execute(&state.db_pool)
@@ -281,9 +386,124 @@ async fn delete_asset(
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
     
+    if let Ok(asset) = get_asset_by_id(&state, id).await {
+        audit::record(
+            &state.db_pool,
+            &meta,
+            AuditEvent::new(token_data.sub, "DELETE", "asset", id).before(&before).after(&asset),
+        )
+        .await;
+        notify_asset_changed(&state, "archived", &asset).await;
+    }
+    
     Ok(StatusCode::NO_CONTENT)
 }
 
+async fn restore_asset(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+) -> Result<Json<AssetWithDetails>, StatusCode> {
+    let token = extract_token(&headers)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let token_data = verify_token(&state.db_pool, &token).await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let before = get_asset_by_id(&state, id).await?;
+    if before.archived_at.is_none() {
+        return Ok(Json(before));
+    }
+
+    sqlx::query("UPDATE assets SET archived_at = NULL, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error restoring asset: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let asset = get_asset_by_id(&state, id).await?;
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(token_data.sub, "RESTORE", "asset", id).before(&before).after(&asset),
+    )
+    .await;
+    notify_asset_changed(&state, "restored", &asset).await;
+    Ok(Json(asset))
+}
+
+/// Import assets for one client from a CSV upload. Multipart fields:
+/// `file` (the CSV), `client_id`, and `dry_run` (`true` to validate only).
+async fn import_assets(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> ApiResult<Json<ImportReport>> {
+    let token = extract_token(&headers)
+        .ok_or_else(|| ApiError::unauthorized("Missing authorization header"))?;
+    verify_token(&state.db_pool, &token).await
+        .map_err(|_| ApiError::unauthorized("Invalid token"))?;
+
+    let mut csv: Option<String> = None;
+    let mut client_id: Option<Uuid> = None;
+    let mut dry_run = false;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::bad_request(format!("Invalid multipart body: {}", e)))?
+    {
+        let name = field.name().unwrap_or("").to_string();
+        let value = field
+            .bytes()
+            .await
+            .map_err(|e| ApiError::bad_request(format!("Invalid multipart body: {}", e)))?;
+
+        match name.as_str() {
+            "file" => {
+                let text = String::from_utf8(value.to_vec())
+                    .map_err(|_| ApiError::validation_single("file", "must be UTF-8 encoded CSV"))?;
+                csv = Some(text);
+            }
+            "client_id" => {
+                let value = String::from_utf8_lossy(&value);
+                client_id = Some(
+                    Uuid::parse_str(value.trim())
+                        .map_err(|_| ApiError::validation_single("client_id", "must be a UUID"))?,
+                );
+            }
+            "dry_run" => {
+                dry_run = matches!(String::from_utf8_lossy(&value).trim(), "true" | "1" | "yes" | "on");
+            }
+            _ => {}
+        }
+    }
+
+    let csv = csv.ok_or_else(|| ApiError::validation_single("file", "is required"))?;
+    let client_id = client_id.ok_or_else(|| ApiError::validation_single("client_id", "is required"))?;
+
+    let report = asset_import::import_assets(&state.db_pool, client_id, &csv, dry_run)
+        .await
+        .map_err(|e| match e {
+            AssetImportError::ClientNotFound => ApiError::not_found("Client not found"),
+            AssetImportError::Database(e) => {
+                tracing::error!("Error importing assets: {}", e);
+                ApiError::internal("Failed to import assets")
+            }
+            e => ApiError::validation_single("file", e.to_string()),
+        })?;
+
+    Ok(Json(report))
+}
+
+async fn notify_asset_changed(state: &AppState, change: &str, asset: &AssetWithDetails) {
+    let data = serde_json::json!({ "change": change, "asset": asset });
+    outbound_webhooks::notify(&state.db_pool, outbound_webhooks::ASSET_CHANGED, data).await;
+}
+
 async fn get_asset_monitoring(
     State(_state): State<Arc<AppState>>,
     Path(_id): Path<Uuid>,
@@ -311,12 +531,44 @@ async fn get_asset_types(
 }
 
 // Helper functions
+async fn validate_custom_fields(
+    state: &AppState,
+    asset_type: &str,
+    values: &HashMap<String, serde_json::Value>,
+    creating: bool,
+) -> Result<Vec<FieldValue>, Response> {
+    let definitions = asset_fields::definitions_for_type(&state.db_pool, asset_type)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching asset layout fields: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+    asset_fields::validate(&definitions, values, creating).map_err(IntoResponse::into_response)
+}
+
+async fn save_custom_fields(conn: &mut PgConnection, asset_id: Uuid, values: &[FieldValue]) -> Result<(), Response> {
+    asset_fields::save(conn, asset_id, values).await.map_err(|e| {
+        tracing::error!("Error saving custom fields for asset {}: {}", asset_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+async fn asset_response(state: &AppState, asset: AssetWithDetails) -> Result<AssetResponse, StatusCode> {
+    let custom_fields = asset_fields::render(&state.db_pool, asset.id, &asset.asset_type)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching custom fields for asset {}: {}", asset.id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(AssetResponse { asset, custom_fields })
+}
+
 async fn get_asset_by_id(state: &AppState, id: Uuid) -> Result<AssetWithDetails, StatusCode> {
     sqlx::query_as::<_, AssetWithDetails>(
         "SELECT a.id, a.client_id, c.name as client_name, a.name, a.description, 
          a.asset_type, a.make, a.model, a.serial, a.os, a.ip, a.mac, a.uri, 
          a.status, a.location_id, l.name as location_name, a.contact_id, ct.name as contact_name,
-         a.purchase_date, a.warranty_expire, a.install_date, a.notes, a.created_at, a.updated_at
+         a.purchase_date, a.warranty_expire, a.install_date, a.notes, a.created_at, a.updated_at, a.archived_at
          FROM assets a
          LEFT JOIN clients c ON a.client_id = c.id
          LEFT JOIN locations l ON a.location_id = l.id
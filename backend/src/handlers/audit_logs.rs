@@ -0,0 +1,127 @@
+//! Audit Log API
+//!
+//! Admin-only, read-only access to `audit_logs`: a filtered search and a
+//! per-entity history. Both page newest first and accept `?cursor=` for
+//! stable deep paging while new entries are written.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::middleware::AuthUserWithRole;
+use crate::pagination::{DEFAULT_PAGE, DEFAULT_PAGE_SIZE};
+use crate::services::audit_log_query::{self, AuditLogFilters, AuditLogRecord, AuditQueryError};
+use crate::{ApiError, ApiResult, AppState, PaginatedResponse, PaginationParams};
+
+pub fn audit_log_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_audit_logs))
+        .route("/:entity_type/:entity_id", get(entity_history))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub page: Option<i64>,
+    #[serde(alias = "limit")]
+    pub per_page: Option<i64>,
+    /// Switches to cursor pagination; empty for the first page
+    pub cursor: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AuditLogQuery {
+    // Built by hand: numeric fields don't survive `#[serde(flatten)]` in query strings
+    fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            page: self.page.unwrap_or(DEFAULT_PAGE),
+            per_page: self.per_page.unwrap_or(DEFAULT_PAGE_SIZE),
+            cursor: self.cursor.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+fn require_admin(auth: &AuthUserWithRole) -> ApiResult<()> {
+    if auth.is_admin() {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("Audit logs are only available to administrators"))
+    }
+}
+
+async fn run_search(
+    state: &AppState,
+    filters: AuditLogFilters,
+    params: PaginationParams,
+) -> ApiResult<Json<PaginatedResponse<AuditLogRecord>>> {
+    if let (Some(from), Some(to)) = (filters.from, filters.to) {
+        if from > to {
+            return Err(ApiError::validation_single("from", "must not be after to"));
+        }
+    }
+
+    let page = audit_log_query::search(&state.db_pool, &filters, &params)
+        .await
+        .map_err(|e| match e {
+            AuditQueryError::InvalidCursor(_) => ApiError::validation_single("cursor", e.to_string()),
+            AuditQueryError::Database(e) => {
+                tracing::error!("Error fetching audit logs: {}", e);
+                ApiError::internal("Failed to fetch audit logs")
+            }
+        })?;
+
+    Ok(Json(page))
+}
+
+async fn list_audit_logs(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(query): Query<AuditLogQuery>,
+) -> ApiResult<Json<PaginatedResponse<AuditLogRecord>>> {
+    require_admin(&auth)?;
+
+    let params = query.pagination();
+    let filters = AuditLogFilters {
+        user_id: query.user_id,
+        entity_type: query.entity_type,
+        entity_id: query.entity_id,
+        action: query.action,
+        from: query.from,
+        to: query.to,
+    };
+
+    run_search(&state, filters, params).await
+}
+
+async fn entity_history(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path((entity_type, entity_id)): Path<(String, Uuid)>,
+    Query(query): Query<AuditLogQuery>,
+) -> ApiResult<Json<PaginatedResponse<AuditLogRecord>>> {
+    require_admin(&auth)?;
+
+    let params = query.pagination();
+    let filters = AuditLogFilters {
+        user_id: query.user_id,
+        entity_type: Some(entity_type),
+        entity_id: Some(entity_id),
+        action: query.action,
+        from: query.from,
+        to: query.to,
+    };
+
+    run_search(&state, filters, params).await
+}
@@ -10,6 +10,7 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use sqlx::Acquire;
 use uuid::Uuid;
 use chrono::{DateTime, Utc, NaiveDate, Datelike};
 use rust_decimal::Decimal;
@@ -17,7 +18,12 @@ use crate::{
     AppState, ApiResult, ApiError,
     PaginatedResponse, PaginationParams,
 };
-use crate::auth::middleware::AuthUser;
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::pagination::{split_total, WithTotal};
+use crate::services::contract_usage;
+use crate::services::expenses::{self, BillableExpense, ExpenseError};
+use crate::services::invoice_numbering::{self, InvoiceNumbering};
+use crate::services::invoice_tax::{self, TaxableLine};
 
 // ==================== Structs ====================
 
@@ -117,6 +123,8 @@ pub struct RecurringInvoiceRun {
     pub fixed_items_amount: Decimal,
     pub total_amount: Decimal,
     pub created_at: DateTime<Utc>,
+    pub expenses_count: i32,
+    pub expenses_amount: Decimal,
 }
 
 // ==================== Time to Invoice ====================
@@ -152,7 +160,10 @@ pub struct UnbilledTimeEntry {
     pub project_name: Option<String>,
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
+    /// Raw minutes worked
     pub duration_minutes: Option<i32>,
+    /// Minutes billed after rounding
+    pub billable_minutes: Option<i32>,
     pub description: Option<String>,
     pub hourly_rate: Option<Decimal>,
     pub total_amount: Option<Decimal>,
@@ -167,6 +178,9 @@ pub struct CreateInvoiceFromTimeRequest {
     pub payment_terms: Option<String>,
     pub notes: Option<String>,
     pub group_by: Option<String>, // "entry", "project", "ticket", "user"
+    /// Also bill the client's approved billable expenses
+    #[serde(default)]
+    pub include_unbilled_expenses: bool,
     pub additional_line_items: Option<Vec<AdditionalLineItem>>,
 }
 
@@ -178,6 +192,16 @@ pub struct AdditionalLineItem {
     pub tax_rate: Option<Decimal>,
 }
 
+// ==================== Invoice Numbering ====================
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceNumberingResponse {
+    #[serde(flatten)]
+    pub numbering: InvoiceNumbering,
+    /// What an invoice dated today would be numbered
+    pub next_number: String,
+}
+
 // ==================== Payment Methods ====================
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -263,6 +287,8 @@ pub fn billing_routes() -> Router<Arc<AppState>> {
         .route("/recurring/:id/run", post(run_recurring_invoice))
         .route("/recurring/:id/history", get(get_recurring_history))
         .route("/recurring/due", get(get_due_recurring_invoices))
+        // Invoice Numbering
+        .route("/invoice-numbering", get(get_invoice_numbering).put(update_invoice_numbering))
         // Payment Methods
         .route("/payment-methods", get(list_payment_methods).post(create_payment_method))
         .route("/payment-methods/:id", put(update_payment_method).delete(delete_payment_method))
@@ -280,14 +306,14 @@ async fn list_unbilled_time(
     AuthUser(user): AuthUser,
     Query(params): Query<UnbilledTimeQuery>,
 ) -> ApiResult<Json<Vec<UnbilledTimeEntry>>> {
-    let entries = sqlx::query_as!(
-        UnbilledTimeEntry,
+    let entries = sqlx::query_as::<_, UnbilledTimeEntry>(
         r#"SELECT
             te.id, te.user_id,
-            COALESCE(u.first_name || ' ' || u.last_name, 'Unknown') as "user_name!",
+            COALESCE(u.first_name || ' ' || u.last_name, 'Unknown') as user_name,
             te.ticket_id, t.number as ticket_number, t.subject as ticket_subject,
             te.project_id, p.name as project_name,
             te.start_time, te.end_time, te.duration_minutes,
+            COALESCE(te.billable_minutes, te.duration_minutes) as billable_minutes,
             te.description, te.hourly_rate, te.total_amount
          FROM time_entries te
          LEFT JOIN users u ON te.user_id = u.id
@@ -301,13 +327,13 @@ async fn list_unbilled_time(
            AND ($3::uuid IS NULL OR te.user_id = $3)
            AND ($4::date IS NULL OR te.start_time::date >= $4)
            AND ($5::date IS NULL OR te.start_time::date <= $5)
-         ORDER BY te.start_time DESC"#,
-        params.client_id,
-        params.project_id,
-        params.user_id,
-        params.from_date,
-        params.to_date
+         ORDER BY te.start_time DESC"#
     )
+    .bind(params.client_id)
+    .bind(params.project_id)
+    .bind(params.user_id)
+    .bind(params.from_date)
+    .bind(params.to_date)
     .fetch_all(&state.db_pool)
     .await
     .map_err(|e| {
@@ -380,20 +406,20 @@ async fn create_invoice_from_time(
     })?;
 
     // Verify all time entries exist, are unbilled, and belong to the client
-    let entries = sqlx::query!(
-        r#"SELECT te.id, te.description, te.duration_minutes, te.hourly_rate, te.total_amount,
+    let entries = sqlx::query_as::<_, BillableTimeEntry>(
+        r#"SELECT te.id, te.description, te.duration_minutes,
+                  COALESCE(te.billable_minutes, te.duration_minutes) as billable_minutes,
+                  te.hourly_rate, te.total_amount,
                   t.subject as ticket_subject, p.name as project_name,
-                  u.first_name || ' ' || u.last_name as user_name,
                   COALESCE(t.client_id, p.client_id) as client_id
            FROM time_entries te
            LEFT JOIN tickets t ON te.ticket_id = t.id
            LEFT JOIN projects p ON te.project_id = p.id
-           LEFT JOIN users u ON te.user_id = u.id
            WHERE te.id = ANY($1)
              AND te.billable = true
-             AND te.billed = false"#,
-        &payload.time_entry_ids
+             AND te.billed = false"#
     )
+    .bind(&payload.time_entry_ids)
     .fetch_all(&mut *tx)
     .await
     .map_err(|e| {
@@ -418,9 +444,10 @@ async fn create_invoice_from_time(
         }
     }
 
-    // Calculate totals
-    let mut subtotal = Decimal::ZERO;
-    let mut line_items_data: Vec<(String, Decimal, Decimal)> = Vec::new();
+    let tax_exempt = client_is_tax_exempt(&mut tx, payload.client_id).await?;
+
+    // Build line items as (description, quantity, unit_price, amount, tax_rate)
+    let mut line_items_data: Vec<(String, Decimal, Decimal, Decimal, Option<Decimal>)> = Vec::new();
 
     let group_by = payload.group_by.as_deref().unwrap_or("entry");
 
@@ -428,7 +455,7 @@ async fn create_invoice_from_time(
         "entry" => {
             // Each time entry becomes a line item
             for entry in &entries {
-                let hours = Decimal::from(entry.duration_minutes.unwrap_or(0)) / Decimal::from(60);
+                let hours = Decimal::from(entry.billable_minutes.unwrap_or(0)) / Decimal::from(60);
                 let rate = entry.hourly_rate.unwrap_or(Decimal::from(75));
                 let amount = entry.total_amount.unwrap_or(hours * rate);
                 let desc = format!(
@@ -439,94 +466,128 @@ async fn create_invoice_from_time(
                     entry.description.as_deref().unwrap_or("Time entry"),
                     hours
                 );
-                line_items_data.push((desc, hours, rate));
-                subtotal += amount;
+                line_items_data.push((desc, hours, rate, amount, None));
             }
         }
         _ => {
             // Aggregate all entries into one line item
-            let total_minutes: i32 = entries.iter()
-                .filter_map(|e| e.duration_minutes)
-                .sum();
-            let hours = Decimal::from(total_minutes) / Decimal::from(60);
-            let total_amount: Decimal = entries.iter()
-                .filter_map(|e| e.total_amount)
-                .sum();
-            let avg_rate = if hours > Decimal::ZERO {
-                total_amount / hours
-            } else {
-                Decimal::from(75)
-            };
-
-            line_items_data.push((
-                format!("Professional Services ({:.2} hours)", hours),
-                hours,
-                avg_rate
-            ));
-            subtotal = total_amount;
+            let summary = summarize_time_entries(
+                entries.iter().map(|e| (e.billable_minutes, e.total_amount))
+            );
+
+            if let Some(summary) = summary {
+                line_items_data.push((
+                    format!("Professional Services ({:.2} hours)", summary.hours),
+                    summary.hours,
+                    summary.unit_price,
+                    summary.amount,
+                    None,
+                ));
+            }
         }
     }
 
+    let unbilled_expenses: Vec<BillableExpense> = if payload.include_unbilled_expenses {
+        expenses::unbilled_expenses(&mut *tx, payload.client_id).await.map_err(|e| match e {
+            ExpenseError::AmountOutOfRange(_) => ApiError::validation_single("include_unbilled_expenses", e.to_string()),
+            e => {
+                tracing::error!("Error fetching unbilled expenses: {}", e);
+                ApiError::internal("Failed to fetch unbilled expenses")
+            }
+        })?
+    } else {
+        Vec::new()
+    };
+    for expense in &unbilled_expenses {
+        let amount = expense.billed_amount;
+        line_items_data.push((expense.line_description(), Decimal::ONE, amount, amount, None));
+    }
+
     // Add any additional line items
     if let Some(additional) = &payload.additional_line_items {
         for item in additional {
             let line_total = item.quantity * item.unit_price;
-            line_items_data.push((item.description.clone(), item.quantity, item.unit_price));
-            subtotal += line_total;
+            line_items_data.push((item.description.clone(), item.quantity, item.unit_price, line_total, item.tax_rate));
         }
     }
 
-    // Generate invoice number
-    let invoice_count: i64 = sqlx::query_scalar!("SELECT COUNT(*) FROM invoices")
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(|e| {
-            tracing::error!("Error counting invoices: {}", e);
-            ApiError::internal("Failed to generate invoice number")
-        })?
-        .unwrap_or(0);
+    // Lines are stored at cents, so the subtotal is the sum of the stored
+    // line totals
+    let taxable_lines: Vec<TaxableLine> = line_items_data.iter()
+        .map(|(_, _, _, amount, tax_rate)| TaxableLine { amount: invoice_tax::round_money(*amount), tax_rate: *tax_rate })
+        .collect();
+    let totals = invoice_tax::calculate_invoice_totals(&taxable_lines, None, tax_exempt);
+    let subtotal = totals.subtotal;
 
-    let invoice_number = format!("INV-{:05}", invoice_count + 1);
+    // Create the invoice, taking another number if one was entered by hand
     let invoice_id = Uuid::new_v4();
+    let mut attempt = 1;
+    let invoice_number = loop {
+        let number = invoice_numbering::next_invoice_number(&mut *tx, payload.invoice_date)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error generating invoice number: {}", e);
+                ApiError::internal("Failed to generate invoice number")
+            })?;
+
+        let mut savepoint = (&mut *tx).begin().await?;
+        let inserted = sqlx::query!(
+            r#"INSERT INTO invoices (
+                id, client_id, number, date, due_date,
+                subtotal, tax_amount, total, balance,
+                status, payment_terms, notes, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8, 'draft', $9, $10, NOW())"#,
+            invoice_id,
+            payload.client_id,
+            number,
+            payload.invoice_date,
+            payload.due_date,
+            totals.subtotal,
+            totals.tax_amount,
+            totals.total,
+            payload.payment_terms.as_deref().unwrap_or("net_30"),
+            payload.notes
+        )
+        .execute(&mut *savepoint)
+        .await;
 
-    // Create the invoice
-    sqlx::query!(
-        r#"INSERT INTO invoices (
-            id, client_id, number, date, due_date,
-            subtotal, tax_amount, total, balance,
-            status, payment_terms, notes, created_at
-        ) VALUES ($1, $2, $3, $4, $5, $6, 0, $6, $6, 'draft', $7, $8, NOW())"#,
-        invoice_id,
-        payload.client_id,
-        invoice_number,
-        payload.invoice_date,
-        payload.due_date,
-        subtotal,
-        payload.payment_terms.as_deref().unwrap_or("net_30"),
-        payload.notes
-    )
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error creating invoice: {}", e);
-        ApiError::internal("Failed to create invoice")
-    })?;
+        match inserted {
+            Ok(_) => {
+                savepoint.commit().await?;
+                break number;
+            }
+            Err(e) if invoice_numbering::is_number_conflict(&e) && attempt < invoice_numbering::MAX_NUMBER_ATTEMPTS => {
+                tracing::warn!("Invoice number {} is already in use, taking the next one", number);
+                savepoint.rollback().await?;
+                attempt += 1;
+            }
+            Err(e) => {
+                tracing::error!("Error creating invoice: {}", e);
+                return Err(ApiError::internal("Failed to create invoice"));
+            }
+        }
+    };
 
     // Create line items
-    for (i, (description, quantity, unit_price)) in line_items_data.iter().enumerate() {
+    for ((description, quantity, unit_price, _, _), taxable) in line_items_data.iter().zip(&taxable_lines) {
         let line_item_id = Uuid::new_v4();
-        let line_total = *quantity * *unit_price;
+        let line_total = taxable.amount;
+        let tax_rate = invoice_tax::effective_rate(taxable, None, tax_exempt);
+        let line_tax = invoice_tax::line_tax(taxable, None, tax_exempt);
 
         sqlx::query!(
             r#"INSERT INTO invoice_line_items (
-                id, invoice_id, description, quantity, unit_price, line_total, created_at
-            ) VALUES ($1, $2, $3, $4, $5, $6, NOW())"#,
+                id, invoice_id, description, quantity, unit_price, line_total,
+                tax_rate, tax_amount, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())"#,
             line_item_id,
             invoice_id,
             description,
             quantity,
             unit_price,
-            line_total
+            line_total,
+            tax_rate,
+            line_tax
         )
         .execute(&mut *tx)
         .await
@@ -549,39 +610,135 @@ async fn create_invoice_from_time(
         ApiError::internal("Failed to update time entries")
     })?;
 
+    expenses::mark_billed(&mut *tx, invoice_id, &unbilled_expenses).await.map_err(|e| {
+        tracing::error!("Error marking expenses as billed: {}", e);
+        ApiError::internal("Failed to update expenses")
+    })?;
+
     tx.commit().await.map_err(|e| {
         tracing::error!("Error committing transaction: {}", e);
         ApiError::internal("Failed to commit transaction")
     })?;
+    crate::middleware::prometheus::record_invoice_generated("time_entries");
 
     Ok(Json(serde_json::json!({
         "invoice_id": invoice_id,
         "invoice_number": invoice_number,
         "subtotal": subtotal,
-        "time_entries_billed": payload.time_entry_ids.len()
+        "tax_amount": totals.tax_amount,
+        "total": totals.total,
+        "time_entries_billed": payload.time_entry_ids.len(),
+        "expenses_billed": unbilled_expenses.len(),
+        "worked_minutes": entries.iter().map(|e| i64::from(e.duration_minutes.unwrap_or(0))).sum::<i64>(),
+        "billable_minutes": entries.iter().map(|e| i64::from(e.billable_minutes.unwrap_or(0))).sum::<i64>(),
+        "time_entries": entries.iter().map(|e| serde_json::json!({
+            "id": e.id,
+            "duration_minutes": e.duration_minutes,
+            "billable_minutes": e.billable_minutes,
+        })).collect::<Vec<_>>()
     })))
 }
 
+/// An unbilled time entry being turned into invoice lines
+#[derive(sqlx::FromRow)]
+struct BillableTimeEntry {
+    id: Uuid,
+    description: Option<String>,
+    /// Raw minutes worked
+    duration_minutes: Option<i32>,
+    /// Minutes billed after rounding
+    billable_minutes: Option<i32>,
+    hourly_rate: Option<Decimal>,
+    total_amount: Option<Decimal>,
+    ticket_subject: Option<String>,
+    project_name: Option<String>,
+    client_id: Option<Uuid>,
+}
+
+/// Hours, blended hourly rate and amount for time entries billed as one line
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TimeLineSummary {
+    hours: Decimal,
+    unit_price: Decimal,
+    amount: Decimal,
+}
+
+/// Summarize `(billable_minutes, total_amount)` pairs into a single line item.
+///
+/// Quantity is the billed hours, after rounding, and the unit price is derived from the
+/// billed amount, so entries at different rates blend correctly. Entries with
+/// an amount but no recorded duration are billed as a flat quantity of one.
+/// Returns `None` when there is nothing to bill.
+fn summarize_time_entries(
+    entries: impl IntoIterator<Item = (Option<i32>, Option<Decimal>)>,
+) -> Option<TimeLineSummary> {
+    let (total_minutes, amount) = entries.into_iter().fold(
+        (0i64, Decimal::ZERO),
+        |(minutes, amount), (m, a)| {
+            (minutes + i64::from(m.unwrap_or(0)), amount + a.unwrap_or(Decimal::ZERO))
+        },
+    );
+
+    let hours = Decimal::from(total_minutes) / Decimal::from(60);
+
+    if hours <= Decimal::ZERO {
+        if amount.is_zero() {
+            return None;
+        }
+        return Some(TimeLineSummary {
+            hours: Decimal::ONE,
+            unit_price: amount,
+            amount,
+        });
+    }
+
+    Some(TimeLineSummary {
+        hours: hours.round_dp(2),
+        unit_price: (amount / hours).round_dp(2),
+        amount,
+    })
+}
+
+/// Whether the client is flagged tax exempt (unknown clients are treated as taxable)
+async fn client_is_tax_exempt(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    client_id: Uuid,
+) -> ApiResult<bool> {
+    let exempt = sqlx::query_scalar::<_, bool>("SELECT tax_exempt FROM clients WHERE id = $1")
+        .bind(client_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching client tax status: {}", e);
+            ApiError::internal("Failed to fetch client tax status")
+        })?;
+
+    Ok(exempt.unwrap_or(false))
+}
+
 // ==================== Recurring Invoice Handlers ====================
 
 async fn list_recurring_templates(
     State(state): State<Arc<AppState>>,
     AuthUser(user): AuthUser,
     Query(params): Query<PaginationParams>,
-) -> ApiResult<Json<Vec<RecurringTemplateWithDetails>>> {
-    let templates = sqlx::query_as!(
-        RecurringInvoiceTemplate,
+) -> ApiResult<Json<PaginatedResponse<RecurringTemplateWithDetails>>> {
+    let rows = sqlx::query_as::<_, WithTotal<RecurringInvoiceTemplate>>(
         r#"SELECT id, client_id, contract_id, name, description,
                   frequency, interval_count, day_of_month, day_of_week,
                   start_date, end_date, next_run_date, last_run_date,
                   payment_terms, due_days, notes, terms,
                   subtotal, tax_rate,
                   include_unbilled_time, include_unbilled_expenses,
-                  auto_send, is_active, run_count, created_by, created_at, updated_at
+                  auto_send, is_active, run_count, created_by, created_at, updated_at,
+                  COUNT(*) OVER() AS total_count
            FROM recurring_invoice_templates
            WHERE is_active = true
-           ORDER BY next_run_date ASC"#
+           ORDER BY next_run_date ASC, id
+           LIMIT $1 OFFSET $2"#
     )
+    .bind(params.limit())
+    .bind(params.offset())
     .fetch_all(&state.db_pool)
     .await
     .map_err(|e| {
@@ -589,6 +746,16 @@ async fn list_recurring_templates(
         ApiError::internal("Failed to fetch recurring templates")
     })?;
 
+    let (templates, total) = split_total(rows);
+    let total = match total {
+        Some(total) => total,
+        None => {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM recurring_invoice_templates WHERE is_active = true")
+                .fetch_one(&state.db_pool)
+                .await?
+        }
+    };
+
     let mut result: Vec<RecurringTemplateWithDetails> = Vec::new();
 
     for template in templates {
@@ -632,7 +799,7 @@ async fn list_recurring_templates(
         });
     }
 
-    Ok(Json(result))
+    Ok(Json(PaginatedResponse::new(result, &params, total)))
 }
 
 async fn create_recurring_template(
@@ -911,12 +1078,32 @@ async fn run_recurring_invoice(
         .map(|item| item.quantity * item.unit_price)
         .sum();
 
+    // Time counted against the contract's included hours bills per cycle:
+    // hours within the block are covered, the rest at the overage rate
+    let contract_charges = match template.contract_id {
+        Some(contract_id) if template.include_unbilled_time => {
+            contract_usage::contract_time_charges(&mut *tx, contract_id).await?
+        }
+        _ => Vec::new(),
+    };
+    let contract_name = match template.contract_id {
+        Some(contract_id) if !contract_charges.is_empty() => contract_usage::block_contract(&mut *tx, contract_id)
+            .await?
+            .map(|c| c.name)
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    let contract_entry_ids: Vec<Uuid> = contract_charges.iter()
+        .flat_map(|charge| charge.entry_ids.iter().copied())
+        .collect();
+
     // Get unbilled time entries if enabled
-    let mut time_entries_count = 0i32;
-    let mut time_entries_amount = Decimal::ZERO;
-    let time_entry_ids: Vec<Uuid> = if template.include_unbilled_time {
+    let mut time_entries_count = contract_entry_ids.len() as i32;
+    let mut time_entries_amount: Decimal = contract_charges.iter().map(|charge| charge.overage_amount).sum();
+    let mut time_summary: Option<TimeLineSummary> = None;
+    let mut time_entry_ids: Vec<Uuid> = if template.include_unbilled_time {
         let entries = sqlx::query!(
-            r#"SELECT id, total_amount
+            r#"SELECT te.id, te.duration_minutes, te.total_amount
                FROM time_entries te
                LEFT JOIN tickets t ON te.ticket_id = t.id
                LEFT JOIN projects p ON te.project_id = p.id
@@ -928,78 +1115,190 @@ async fn run_recurring_invoice(
         )
         .fetch_all(&mut *tx)
         .await?;
+        let entries: Vec<_> = entries.into_iter()
+            .filter(|e| !contract_entry_ids.contains(&e.id))
+            .collect();
 
-        time_entries_count = entries.len() as i32;
-        time_entries_amount = entries.iter()
-            .filter_map(|e| e.total_amount)
-            .sum();
+        time_entries_count += entries.len() as i32;
+        time_summary = summarize_time_entries(
+            entries.iter().map(|e| (e.duration_minutes, e.total_amount))
+        );
+        time_entries_amount += time_summary.map(|t| t.amount).unwrap_or(Decimal::ZERO);
 
         entries.into_iter().map(|e| e.id).collect()
     } else {
         vec![]
     };
 
-    let total_amount = fixed_items_amount + time_entries_amount;
+    let unbilled_expenses: Vec<BillableExpense> = if template.include_unbilled_expenses {
+        expenses::unbilled_expenses(&mut *tx, template.client_id).await.map_err(|e| match e {
+            ExpenseError::AmountOutOfRange(_) => ApiError::conflict(e.to_string()),
+            e => {
+                tracing::error!("Error fetching unbilled expenses: {}", e);
+                ApiError::internal("Failed to fetch unbilled expenses")
+            }
+        })?
+    } else {
+        Vec::new()
+    };
+    let expenses_count = unbilled_expenses.len() as i32;
+    let expenses_amount: Decimal = unbilled_expenses.iter().map(|e| e.billed_amount).sum();
 
-    // Generate invoice number
-    let invoice_count: i64 = sqlx::query_scalar!("SELECT COUNT(*) FROM invoices")
-        .fetch_one(&mut *tx)
-        .await?
-        .unwrap_or(0);
+    // Fixed items carry their own rate; time and expenses fall back to the template default
+    let tax_exempt = client_is_tax_exempt(&mut tx, template.client_id).await?;
+    let mut taxable_lines: Vec<TaxableLine> = line_items.iter()
+        .map(|item| TaxableLine::new(item.quantity, item.unit_price, item.tax_rate))
+        .collect();
+    if let Some(summary) = time_summary {
+        taxable_lines.push(TaxableLine { amount: summary.amount, tax_rate: None });
+    }
+    for charge in &contract_charges {
+        taxable_lines.push(TaxableLine { amount: charge.overage_amount, tax_rate: None });
+    }
+    for expense in &unbilled_expenses {
+        taxable_lines.push(TaxableLine { amount: expense.billed_amount, tax_rate: None });
+    }
+    let totals = invoice_tax::calculate_invoice_totals(&taxable_lines, template.tax_rate, tax_exempt);
+    let total_amount = totals.total;
 
-    let invoice_number = format!("INV-{:05}", invoice_count + 1);
     let invoice_id = Uuid::new_v4();
     let today = Utc::now().date_naive();
     let due_date = today + chrono::Duration::days(template.due_days as i64);
 
-    // Create invoice
-    sqlx::query!(
-        r#"INSERT INTO invoices (
-            id, client_id, contract_id, number, date, due_date,
-            subtotal, tax_amount, total, balance,
-            status, payment_terms, notes, terms, created_at
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, 0, $7, $7, 'draft', $8, $9, $10, NOW())"#,
-        invoice_id,
-        template.client_id,
-        template.contract_id,
-        invoice_number,
-        today,
-        due_date,
-        total_amount,
-        template.payment_terms,
-        template.notes,
-        template.terms
-    )
-    .execute(&mut *tx)
-    .await?;
+    // Create invoice, taking another number if one was entered by hand
+    let mut attempt = 1;
+    let invoice_number = loop {
+        let number = invoice_numbering::next_invoice_number(&mut *tx, today).await?;
+
+        let mut savepoint = (&mut *tx).begin().await?;
+        let inserted = sqlx::query!(
+            r#"INSERT INTO invoices (
+                id, client_id, contract_id, number, date, due_date,
+                subtotal, tax_amount, total, balance,
+                status, payment_terms, notes, terms, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9, 'draft', $10, $11, $12, NOW())"#,
+            invoice_id,
+            template.client_id,
+            template.contract_id,
+            number,
+            today,
+            due_date,
+            totals.subtotal,
+            totals.tax_amount,
+            totals.total,
+            template.payment_terms,
+            template.notes,
+            template.terms
+        )
+        .execute(&mut *savepoint)
+        .await;
+
+        match inserted {
+            Ok(_) => {
+                savepoint.commit().await?;
+                break number;
+            }
+            Err(e) if invoice_numbering::is_number_conflict(&e) && attempt < invoice_numbering::MAX_NUMBER_ATTEMPTS => {
+                tracing::warn!("Invoice number {} is already in use, taking the next one", number);
+                savepoint.rollback().await?;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
 
     // Create line items from fixed items
-    for item in &line_items {
+    for (item, taxable) in line_items.iter().zip(&taxable_lines) {
         let line_total = item.quantity * item.unit_price;
+        let tax_rate = invoice_tax::effective_rate(taxable, template.tax_rate, tax_exempt);
+        let line_tax = invoice_tax::line_tax(taxable, template.tax_rate, tax_exempt);
         sqlx::query!(
-            r#"INSERT INTO invoice_line_items (invoice_id, description, quantity, unit_price, line_total, tax_rate)
-               VALUES ($1, $2, $3, $4, $5, $6)"#,
-            invoice_id, item.description, item.quantity, item.unit_price, line_total, item.tax_rate
+            r#"INSERT INTO invoice_line_items (invoice_id, description, quantity, unit_price, line_total, tax_rate, tax_amount)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+            invoice_id, item.description, item.quantity, item.unit_price, line_total, tax_rate, line_tax
         )
         .execute(&mut *tx)
         .await?;
     }
 
-    // Add time entries as line item if applicable
-    if time_entries_count > 0 {
-        let total_hours = time_entries_amount / Decimal::from(75); // Approximate
+    // Add time entries as a single line item at the blended rate
+    if let Some(summary) = time_summary {
+        let taxable = TaxableLine { amount: summary.amount, tax_rate: None };
+        let tax_rate = invoice_tax::effective_rate(&taxable, template.tax_rate, tax_exempt);
+        let line_tax = invoice_tax::line_tax(&taxable, template.tax_rate, tax_exempt);
         sqlx::query!(
-            r#"INSERT INTO invoice_line_items (invoice_id, description, quantity, unit_price, line_total)
-               VALUES ($1, $2, $3, $4, $5)"#,
+            r#"INSERT INTO invoice_line_items (invoice_id, description, quantity, unit_price, line_total, tax_rate, tax_amount)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
             invoice_id,
-            format!("Professional Services ({} time entries)", time_entries_count),
-            total_hours,
-            Decimal::from(75),
-            time_entries_amount
+            format!("Professional Services ({} time entries, {:.2} hours)", time_entries_count, summary.hours),
+            summary.hours,
+            summary.unit_price,
+            summary.amount,
+            tax_rate,
+            line_tax
         )
         .execute(&mut *tx)
         .await?;
+    }
 
+    // Contract time as included and overage lines for each cycle
+    for charge in &contract_charges {
+        let taxable = TaxableLine { amount: charge.overage_amount, tax_rate: None };
+        let tax_rate = invoice_tax::effective_rate(&taxable, template.tax_rate, tax_exempt);
+        let line_tax = invoice_tax::line_tax(&taxable, template.tax_rate, tax_exempt);
+        let period = charge.cycle.label();
+
+        if charge.included_hours > Decimal::ZERO {
+            sqlx::query!(
+                r#"INSERT INTO invoice_line_items (invoice_id, description, quantity, unit_price, line_total, tax_rate, tax_amount)
+                   VALUES ($1, $2, $3, 0, 0, $4, 0)"#,
+                invoice_id,
+                format!("{} included hours ({})", contract_name, period),
+                charge.included_hours,
+                tax_rate
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        if charge.overage_hours > Decimal::ZERO {
+            sqlx::query!(
+                r#"INSERT INTO invoice_line_items (invoice_id, description, quantity, unit_price, line_total, tax_rate, tax_amount)
+                   VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                invoice_id,
+                format!("{} overage hours ({})", contract_name, period),
+                charge.overage_hours,
+                charge.overage_rate,
+                charge.overage_amount,
+                tax_rate,
+                line_tax
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    // Each expense as its own line at cost plus markup
+    for expense in &unbilled_expenses {
+        let amount = expense.billed_amount;
+        let taxable = TaxableLine { amount, tax_rate: None };
+        let tax_rate = invoice_tax::effective_rate(&taxable, template.tax_rate, tax_exempt);
+        let line_tax = invoice_tax::line_tax(&taxable, template.tax_rate, tax_exempt);
+        sqlx::query!(
+            r#"INSERT INTO invoice_line_items (invoice_id, description, quantity, unit_price, line_total, tax_rate, tax_amount)
+               VALUES ($1, $2, 1, $3, $3, $4, $5)"#,
+            invoice_id,
+            expense.line_description(),
+            amount,
+            tax_rate,
+            line_tax
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    expenses::mark_billed(&mut *tx, invoice_id, &unbilled_expenses).await?;
+
+    time_entry_ids.extend(contract_entry_ids);
+    if !time_entry_ids.is_empty() {
         // Mark time entries as billed
         sqlx::query!(
             "UPDATE time_entries SET billed = true, invoice_id = $1, updated_at = NOW() WHERE id = ANY($2)",
@@ -1015,9 +1314,11 @@ async fn run_recurring_invoice(
     sqlx::query!(
         r#"INSERT INTO recurring_invoice_runs (
             id, template_id, invoice_id, run_date, status,
-            time_entries_count, time_entries_amount, fixed_items_amount, total_amount
-        ) VALUES ($1, $2, $3, $4, 'success', $5, $6, $7, $8)"#,
-        run_id, id, invoice_id, today, time_entries_count, time_entries_amount, fixed_items_amount, total_amount
+            time_entries_count, time_entries_amount, fixed_items_amount, total_amount,
+            expenses_count, expenses_amount
+        ) VALUES ($1, $2, $3, $4, 'success', $5, $6, $7, $8, $9, $10)"#,
+        run_id, id, invoice_id, today, time_entries_count, time_entries_amount, fixed_items_amount, total_amount,
+        expenses_count, expenses_amount
     )
     .execute(&mut *tx)
     .await?;
@@ -1036,14 +1337,19 @@ async fn run_recurring_invoice(
     .await?;
 
     tx.commit().await?;
+    crate::middleware::prometheus::record_invoice_generated("recurring");
 
     Ok(Json(serde_json::json!({
         "invoice_id": invoice_id,
         "invoice_number": invoice_number,
         "total_amount": total_amount,
+        "subtotal": totals.subtotal,
+        "tax_amount": totals.tax_amount,
         "fixed_items_amount": fixed_items_amount,
         "time_entries_count": time_entries_count,
-        "time_entries_amount": time_entries_amount
+        "time_entries_amount": time_entries_amount,
+        "expenses_count": expenses_count,
+        "expenses_amount": expenses_amount
     })))
 }
 
@@ -1104,6 +1410,40 @@ async fn get_due_recurring_invoices(
     Ok(Json(result))
 }
 
+// ==================== Invoice Numbering Handlers ====================
+
+async fn invoice_numbering_response(state: &AppState) -> ApiResult<InvoiceNumberingResponse> {
+    let mut conn = state.db_pool.acquire().await?;
+    let numbering = invoice_numbering::load(&mut conn).await?;
+    let next_number = invoice_numbering::peek_invoice_number(&mut conn, Utc::now().date_naive()).await?;
+
+    Ok(InvoiceNumberingResponse { numbering, next_number })
+}
+
+async fn get_invoice_numbering(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+) -> ApiResult<Json<InvoiceNumberingResponse>> {
+    Ok(Json(invoice_numbering_response(&state).await?))
+}
+
+async fn update_invoice_numbering(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Json(payload): Json<InvoiceNumbering>,
+) -> ApiResult<Json<InvoiceNumberingResponse>> {
+    if !auth.is_admin() {
+        return Err(ApiError::forbidden("Only administrators can change invoice numbering"));
+    }
+    payload.validate().map_err(|message| ApiError::validation_single("invoice_numbering", message))?;
+
+    let mut conn = state.db_pool.acquire().await?;
+    invoice_numbering::save(&mut conn, &payload).await?;
+    drop(conn);
+
+    Ok(Json(invoice_numbering_response(&state).await?))
+}
+
 // ==================== Payment Methods Handlers ====================
 
 async fn list_payment_methods(
@@ -1238,11 +1578,13 @@ async fn delete_payment_method(
 async fn list_credit_notes(
     State(state): State<Arc<AppState>>,
     Query(params): Query<PaginationParams>,
-) -> ApiResult<Json<Vec<CreditNoteWithDetails>>> {
-    let notes = sqlx::query_as!(
-        CreditNote,
-        "SELECT * FROM credit_notes ORDER BY created_at DESC LIMIT 100"
+) -> ApiResult<Json<PaginatedResponse<CreditNoteWithDetails>>> {
+    let rows = sqlx::query_as::<_, WithTotal<CreditNote>>(
+        "SELECT *, COUNT(*) OVER() AS total_count FROM credit_notes
+         ORDER BY created_at DESC, id DESC LIMIT $1 OFFSET $2"
     )
+    .bind(params.limit())
+    .bind(params.offset())
     .fetch_all(&state.db_pool)
     .await
     .map_err(|e| {
@@ -1250,6 +1592,16 @@ async fn list_credit_notes(
         ApiError::internal("Failed to fetch credit notes")
     })?;
 
+    let (notes, total) = split_total(rows);
+    let total = match total {
+        Some(total) => total,
+        None => {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM credit_notes")
+                .fetch_one(&state.db_pool)
+                .await?
+        }
+    };
+
     let mut result = Vec::new();
     for note in notes {
         let client = sqlx::query!("SELECT name FROM clients WHERE id = $1", note.client_id)
@@ -1265,7 +1617,7 @@ async fn list_credit_notes(
         });
     }
 
-    Ok(Json(result))
+    Ok(Json(PaginatedResponse::new(result, &params, total)))
 }
 
 async fn create_credit_note(
@@ -1409,3 +1761,57 @@ async fn apply_credit_note(
         "amount_applied": payload.amount
     })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_time_summary_uses_real_hours_at_non_default_rate() {
+        // Billed at $120/hr: 90 + 150 minutes = 4 hours, $480
+        let summary = summarize_time_entries([
+            (Some(90), Some(dec("180.00"))),
+            (Some(150), Some(dec("300.00"))),
+        ])
+        .unwrap();
+
+        assert_eq!(summary.hours, dec("4"));
+        assert_eq!(summary.unit_price, dec("120.00"));
+        assert_eq!(summary.amount, dec("480.00"));
+    }
+
+    #[test]
+    fn test_time_summary_blends_mixed_rates() {
+        // 1h at $100 + 1h at $150 = 2h at a blended $125
+        let summary = summarize_time_entries([
+            (Some(60), Some(dec("100.00"))),
+            (Some(60), Some(dec("150.00"))),
+        ])
+        .unwrap();
+
+        assert_eq!(summary.hours, dec("2"));
+        assert_eq!(summary.unit_price, dec("125.00"));
+        assert_eq!(summary.amount, dec("250.00"));
+    }
+
+    #[test]
+    fn test_time_summary_zero_edge_cases() {
+        assert_eq!(summarize_time_entries([]), None);
+        assert_eq!(summarize_time_entries([(Some(0), Some(Decimal::ZERO))]), None);
+        assert_eq!(summarize_time_entries([(None, None)]), None);
+
+        // Amount with no recorded duration is billed as a flat line
+        let flat = summarize_time_entries([(None, Some(dec("95.00")))]).unwrap();
+        assert_eq!(flat.hours, Decimal::ONE);
+        assert_eq!(flat.unit_price, dec("95.00"));
+
+        // Hours logged but nothing billable yields a zero rate, not a division error
+        let unpriced = summarize_time_entries([(Some(30), None)]).unwrap();
+        assert_eq!(unpriced.hours, dec("0.5"));
+        assert_eq!(unpriced.unit_price, Decimal::ZERO);
+    }
+}
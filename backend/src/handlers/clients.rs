@@ -1,17 +1,29 @@
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
-use crate::AppState;
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::concurrency::{self, Precondition};
+use crate::etag::{self, IfNoneMatch};
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::auth::rbac::{Action, Resource};
+use crate::services::client_export::{self, ClientExportFormat};
+use crate::services::contact_merge::{self, DuplicateGroup};
+use crate::services::data_retention::{self, AnonymizeError, AnonymizedClient};
+use crate::transaction::Tx;
+use crate::{ApiError, ApiResult, AppState};
+use crate::validation::{Validate, Validator};
 
 #[derive(Serialize, Deserialize)]
 pub struct ClientCreate {
+    #[serde(default)]
     pub name: String,
     pub email: Option<String>,
     pub phone: Option<String>,
@@ -21,6 +33,17 @@ pub struct ClientCreate {
     pub zip: Option<String>,
     pub billing_address: Option<String>,
     pub notes: Option<String>,
+    pub tax_exempt: Option<bool>,
+}
+
+impl Validate for ClientCreate {
+    fn rules(&self, v: Validator) -> Validator {
+        v.not_blank(&self.name, "name")
+            .error_if(self.name.trim().len() > 255, "name", "name must be 255 characters or less")
+            .email(&self.email, "email")
+            .max_length(&self.phone, "phone", 50)
+            .max_length(&self.zip, "zip", 20)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -34,6 +57,9 @@ pub struct ClientUpdate {
     pub zip: Option<String>,
     pub billing_address: Option<String>,
     pub notes: Option<String>,
+    pub tax_exempt: Option<bool>,
+    /// The `updated_at` the client last saw; a newer one gets `409 Conflict`
+    pub expected_updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,13 +67,38 @@ pub struct ClientQuery {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
     pub search: Option<String>,
+    #[serde(default)]
+    pub include_archived: bool,
 }
 
+/// `?include_archived=true` also returns soft-deleted rows
+#[derive(Debug, Default, Deserialize)]
+pub struct ArchiveQuery {
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ClientExportFormat,
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+const CLIENT_COLUMNS: &str = "id, name, email, phone, address, city, state, zip, billing_address, notes, tax_exempt,
+     created_at, updated_at, archived_at";
+
 pub fn client_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(list_clients).post(create_client))
+        .route("/export", get(export_clients))
         .route("/:id", get(get_client).put(update_client).delete(delete_client))
+        .route("/:id/restore", post(restore_client))
+        .route("/:id/anonymize", post(anonymize_client))
         .route("/:id/contacts", get(get_client_contacts))
+        .route("/:id/contacts/export", get(export_client_contacts))
+        .route("/:id/contacts/duplicates", get(get_client_duplicate_contacts))
         .route("/:id/assets", get(get_client_assets))
         .route("/:id/tickets", get(get_client_tickets))
 }
@@ -59,31 +110,19 @@ async fn list_clients(
     let limit = params.limit.unwrap_or(50);
     let offset = params.offset.unwrap_or(0);
 
-    let query = if let Some(search) = params.search {
-        sqlx::query_as!(
-            resolve_shared::Client,
-            "SELECT id, name, email, phone, address, city, state, zip, billing_address, notes, 
-             created_at, updated_at, archived_at 
-             FROM clients 
-             WHERE name ILIKE $1 OR email ILIKE $1
-             ORDER BY name 
-             LIMIT $2 OFFSET $3",
-            format!("%{}%", search),
-            limit,
-            offset
-        )
-    } else {
-        sqlx::query_as!(
-            resolve_shared::Client,
-            "SELECT id, name, email, phone, address, city, state, zip, billing_address, notes, 
-             created_at, updated_at, archived_at 
-             FROM clients 
-             ORDER BY name 
-             LIMIT $1 OFFSET $2",
-            limit,
-            offset
-        )
-    };
+    let sql = format!(
+        "SELECT {} FROM clients
+         WHERE ($1::text IS NULL OR name ILIKE $1 OR email ILIKE $1)
+           AND ($2 OR archived_at IS NULL)
+         ORDER BY name
+         LIMIT $3 OFFSET $4",
+        CLIENT_COLUMNS
+    );
+    let query = sqlx::query_as::<_, resolve_shared::Client>(&sql)
+    .bind(params.search.map(|search| format!("%{}%", search)))
+    .bind(params.include_archived)
+    .bind(limit)
+    .bind(offset);
 
     match query.fetch_all(&state.db_pool).await {
         Ok(clients) => Ok(Json(clients)),
@@ -94,14 +133,15 @@ async fn list_clients(
 async fn create_client(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<ClientCreate>,
-) -> Result<(StatusCode, Json<resolve_shared::Client>), StatusCode> {
+) -> Result<(StatusCode, Json<resolve_shared::Client>), Response> {
+    payload.validate().map_err(IntoResponse::into_response)?;
     let client_id = Uuid::new_v4();
     
     match sqlx::query_as!(
         resolve_shared::Client,
-        "INSERT INTO clients (id, name, email, phone, address, city, state, zip, billing_address, notes)
-         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-         RETURNING id, name, email, phone, address, city, state, zip, billing_address, notes, 
+        "INSERT INTO clients (id, name, email, phone, address, city, state, zip, billing_address, notes, tax_exempt)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+         RETURNING id, name, email, phone, address, city, state, zip, billing_address, notes, tax_exempt,
                    created_at, updated_at, archived_at",
         client_id,
         payload.name,
@@ -112,23 +152,34 @@ async fn create_client(
         payload.state,
         payload.zip,
         payload.billing_address,
-        payload.notes
+        payload.notes,
+        payload.tax_exempt.unwrap_or(false)
     )
     .fetch_one(&state.db_pool)
     .await
     {
         Ok(client) => Ok((StatusCode::CREATED, Json(client))),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
     }
 }
 
 async fn get_client(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<Json<resolve_shared::Client>, StatusCode> {
+    Query(params): Query<ArchiveQuery>,
+    if_none_match: IfNoneMatch,
+) -> Result<Response, StatusCode> {
+    let client = fetch_client(&state, id).await?;
+    if client.archived_at.is_some() && !params.include_archived {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(etag::json(&if_none_match, &client))
+}
+
+async fn fetch_client(state: &AppState, id: Uuid) -> Result<resolve_shared::Client, StatusCode> {
     match sqlx::query_as!(
         resolve_shared::Client,
-        "SELECT id, name, email, phone, address, city, state, zip, billing_address, notes, 
+        "SELECT id, name, email, phone, address, city, state, zip, billing_address, notes, tax_exempt,
          created_at, updated_at, archived_at 
          FROM clients WHERE id = $1",
         id
@@ -136,7 +187,7 @@ async fn get_client(
     .fetch_one(&state.db_pool)
     .await
     {
-        Ok(client) => Ok(Json(client)),
+        Ok(client) => Ok(client),
         Err(sqlx::Error::RowNotFound) => Err(StatusCode::NOT_FOUND),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
@@ -144,9 +195,15 @@ async fn get_client(
 
 async fn update_client(
     State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    precondition: Precondition,
     Path(id): Path<Uuid>,
     Json(payload): Json<ClientUpdate>,
-) -> Result<Json<resolve_shared::Client>, StatusCode> {
+) -> Result<Json<resolve_shared::Client>, Response> {
+    let before = fetch_client(&state, id).await.map_err(IntoResponse::into_response)?;
+    let unmodified_since = precondition.unmodified_since(payload.expected_updated_at);
+
     // This is a simplified update - in production you'd want to build dynamic SQL
     match sqlx::query_as!(
         resolve_shared::Client,
@@ -160,9 +217,11 @@ async fn update_client(
          zip = COALESCE($8, zip),
          billing_address = COALESCE($9, billing_address),
          notes = COALESCE($10, notes),
+         tax_exempt = COALESCE($11, tax_exempt),
          updated_at = NOW()
          WHERE id = $1
-         RETURNING id, name, email, phone, address, city, state, zip, billing_address, notes, 
+           AND ($12::timestamptz IS NULL OR updated_at IS NULL OR updated_at <= $12)
+         RETURNING id, name, email, phone, address, city, state, zip, billing_address, notes, tax_exempt,
                    created_at, updated_at, archived_at",
         id,
         payload.name,
@@ -173,47 +232,180 @@ async fn update_client(
         payload.state,
         payload.zip,
         payload.billing_address,
-        payload.notes
+        payload.notes,
+        payload.tax_exempt,
+        unmodified_since
     )
     .fetch_one(&state.db_pool)
     .await
     {
-        Ok(client) => Ok(Json(client)),
-        Err(sqlx::Error::RowNotFound) => Err(StatusCode::NOT_FOUND),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Ok(client) => {
+            audit::record(
+                &state.db_pool,
+                &meta,
+                AuditEvent::new(auth.0.id, "UPDATE", "client", id).before(&before).after(&client),
+            )
+            .await;
+            Ok(Json(client))
+        }
+        // Changed since the caller loaded it, or deleted meanwhile
+        Err(sqlx::Error::RowNotFound) => match fetch_client(&state, id).await {
+            Ok(current) => Err(concurrency::conflict(current)),
+            Err(status) => Err(status.into_response()),
+        },
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
     }
 }
 
+/// Soft-delete a client along with its active contacts and assets. The
+/// cascade shares the client's `archived_at`, which is how a restore finds
+/// the rows to bring back.
 async fn delete_client(
     State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    meta: RequestMeta,
     Path(id): Path<Uuid>,
 ) -> Result<StatusCode, StatusCode> {
-    match sqlx::query!("DELETE FROM clients WHERE id = $1", id)
-        .execute(&state.db_pool)
+    let before = fetch_client(&state, id).await?;
+    if before.archived_at.is_some() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let archived_at = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+        "UPDATE clients SET archived_at = NOW() WHERE id = $1 AND archived_at IS NULL RETURNING archived_at"
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    for table in ["contacts", "assets"] {
+        sqlx::query(&format!(
+            "UPDATE {} SET archived_at = $2 WHERE client_id = $1 AND archived_at IS NULL",
+            table
+        ))
+        .bind(id)
+        .bind(archived_at)
+        .execute(&mut *tx)
         .await
-    {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                Ok(StatusCode::NO_CONTENT)
-            } else {
-                Err(StatusCode::NOT_FOUND)
-            }
-        }
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        .map_err(|e| {
+            tracing::error!("Error archiving {} for client {}: {}", table, id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
     }
+
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let after = fetch_client(&state, id).await?;
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "DELETE", "client", id).before(&before).after(&after),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Clear `archived_at` on a client and on the contacts and assets archived
+/// with it. Anything archived on its own beforehand stays archived.
+async fn restore_client(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+) -> Result<Json<resolve_shared::Client>, StatusCode> {
+    let before = fetch_client(&state, id).await?;
+    let Some(archived_at) = before.archived_at else {
+        return Ok(Json(before));
+    };
+
+    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sqlx::query("UPDATE clients SET archived_at = NULL, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    for table in ["contacts", "assets"] {
+        sqlx::query(&format!(
+            "UPDATE {} SET archived_at = NULL WHERE client_id = $1 AND archived_at = $2",
+            table
+        ))
+        .bind(id)
+        .bind(archived_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error restoring {} for client {}: {}", table, id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    }
+
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let client = fetch_client(&state, id).await?;
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "RESTORE", "client", id).before(&before).after(&client),
+    )
+    .await;
+
+    Ok(Json(client))
+}
+
+/// Irreversibly scrub the personal data of a client and its contacts for an
+/// erasure request. Invoices and other financial records are kept. The
+/// audit entry records that it happened, not what was removed.
+async fn anonymize_client(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    mut tx: Tx,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<AnonymizedClient>> {
+    if !auth.is_admin() {
+        return Err(ApiError::forbidden("Only administrators can anonymize clients"));
+    }
+
+    let result = data_retention::anonymize_client(&mut tx, id).await.map_err(|e| match e {
+        AnonymizeError::ClientNotFound => ApiError::not_found("Client"),
+        AnonymizeError::AlreadyAnonymized => ApiError::conflict(e.to_string()),
+        AnonymizeError::Database(e) => {
+            tracing::error!("Error anonymizing client {}: {}", id, e);
+            ApiError::internal("Failed to anonymize client")
+        }
+    });
+    let anonymized = tx.finish(result).await?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.user.id, "ANONYMIZE", "client", id).after(&anonymized),
+    )
+    .await;
+
+    Ok(Json(anonymized))
 }
 
 async fn get_client_contacts(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    Query(params): Query<ArchiveQuery>,
 ) -> Result<Json<Vec<resolve_shared::Contact>>, StatusCode> {
-    match sqlx::query_as!(
-        resolve_shared::Contact,
-        "SELECT id, client_id, name, title, email, phone, extension, mobile, department, notes, 
-         is_primary as primary, created_at, updated_at, archived_at 
-         FROM contacts WHERE client_id = $1 ORDER BY is_primary DESC, name",
-        id
+    match sqlx::query_as::<_, resolve_shared::Contact>(
+        r#"SELECT id, client_id, name, title, email, phone, extension, mobile, department, notes,
+         is_primary AS "primary", created_at, updated_at, archived_at
+         FROM contacts WHERE client_id = $1 AND ($2 OR archived_at IS NULL)
+         ORDER BY is_primary DESC, name"#
     )
+    .bind(id)
+    .bind(params.include_archived)
     .fetch_all(&state.db_pool)
     .await
     {
@@ -222,18 +414,86 @@ async fn get_client_contacts(
     }
 }
 
+/// Stream every client as a file; takes `clients.export`
+async fn export_clients(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(params): Query<ExportQuery>,
+) -> ApiResult<Response> {
+    if !auth.can(Resource::Clients, Action::Export) {
+        return Err(ApiError::forbidden("You do not have permission to export clients"));
+    }
+
+    let body = Body::from_stream(client_export::export_clients(state.db_pool.clone(), params.include_archived));
+    Ok(export_response(params.format, "clients".to_string(), body))
+}
+
+/// Stream one client's contacts as a file; takes `contacts.export`
+async fn export_client_contacts(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ExportQuery>,
+) -> ApiResult<Response> {
+    if !auth.can(Resource::Contacts, Action::Export) {
+        return Err(ApiError::forbidden("You do not have permission to export contacts"));
+    }
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM clients WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db_pool)
+        .await?;
+    if !exists {
+        return Err(ApiError::not_found("Client not found"));
+    }
+
+    let body = Body::from_stream(client_export::export_contacts(state.db_pool.clone(), id, params.include_archived));
+    Ok(export_response(params.format, format!("client-{}-contacts", id), body))
+}
+
+fn export_response(format: ClientExportFormat, name: String, body: Body) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", name, format.extension()),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Groups of the client's active contacts that look like the same person
+async fn get_client_duplicate_contacts(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<DuplicateGroup>>, StatusCode> {
+    fetch_client(&state, id).await?;
+    contact_merge::find_duplicates(&state.db_pool, id)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            tracing::error!("Error finding duplicate contacts: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
 async fn get_client_assets(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    Query(params): Query<ArchiveQuery>,
 ) -> Result<Json<Vec<resolve_shared::Asset>>, StatusCode> {
-    match sqlx::query_as!(
-        resolve_shared::Asset,
-        "SELECT id, client_id, name, description, asset_type, make, model, serial, os, 
-         ip::TEXT as ip, mac::TEXT as mac, uri, status, location_id, contact_id, 
+    match sqlx::query_as::<_, resolve_shared::Asset>(
+        "SELECT id, client_id, name, description, asset_type, make, model, serial, os,
+         ip::TEXT as ip, mac::TEXT as mac, uri, status, location_id, contact_id,
          purchase_date, warranty_expire, install_date, notes, created_at, updated_at, archived_at
-         FROM assets WHERE client_id = $1 ORDER BY name",
-        id
+         FROM assets WHERE client_id = $1 AND ($2 OR archived_at IS NULL) ORDER BY name"
     )
+    .bind(id)
+    .bind(params.include_archived)
     .fetch_all(&state.db_pool)
     .await
     {
@@ -248,9 +508,10 @@ async fn get_client_tickets(
 ) -> Result<Json<Vec<resolve_shared::Ticket>>, StatusCode> {
     match sqlx::query_as!(
         resolve_shared::Ticket,
-        "SELECT id, client_id, contact_id, asset_id, number, subject, details, status, priority, 
+        r#"SELECT id, client_id, contact_id, asset_id, number, subject, details,
+         status as "status!: resolve_shared::TicketStatus", priority as "priority!: resolve_shared::Priority",
          assigned_to, billable, opened_by, created_at, updated_at, closed_at
-         FROM tickets WHERE client_id = $1 ORDER BY created_at DESC",
+         FROM tickets WHERE client_id = $1 ORDER BY created_at DESC"#,
         id
     )
     .fetch_all(&state.db_pool)
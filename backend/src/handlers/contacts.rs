@@ -0,0 +1,170 @@
+//! Contacts
+//!
+//! Contacts are listed per client under `/clients/:id/contacts`, with likely
+//! duplicates under `/clients/:id/contacts/duplicates`; these routes read,
+//! soft-delete and restore a single contact and merge duplicates.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::clients::ArchiveQuery;
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::auth::middleware::AuthUser;
+use crate::services::contact_merge::{self, MergeError, MergeResult};
+use crate::{ApiError, ApiResult, AppState};
+use resolve_shared::Contact;
+
+pub fn contact_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/merge", post(merge_contacts))
+        .route("/:id", get(get_contact).delete(delete_contact))
+        .route("/:id/restore", post(restore_contact))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeContactsRequest {
+    pub target_id: Uuid,
+    pub source_ids: Vec<Uuid>,
+}
+
+async fn fetch_contact(state: &AppState, id: Uuid) -> Result<Contact, StatusCode> {
+    sqlx::query_as::<_, Contact>(
+        r#"SELECT id, client_id, name, title, email, phone, extension, mobile, department, notes,
+         is_primary AS "primary", created_at, updated_at, archived_at
+         FROM contacts WHERE id = $1"#
+    )
+    .bind(id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error fetching contact: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn get_contact(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ArchiveQuery>,
+) -> Result<Json<Contact>, StatusCode> {
+    let contact = fetch_contact(&state, id).await?;
+    if contact.archived_at.is_some() && !params.include_archived {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(contact))
+}
+
+async fn delete_contact(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, StatusCode> {
+    let before = fetch_contact(&state, id).await?;
+    if before.archived_at.is_some() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    sqlx::query("UPDATE contacts SET archived_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error archiving contact: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let after = fetch_contact(&state, id).await?;
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "DELETE", "contact", id).before(&before).after(&after),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn restore_contact(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Contact>, StatusCode> {
+    let before = fetch_contact(&state, id).await?;
+    if before.archived_at.is_none() {
+        return Ok(Json(before));
+    }
+
+    sqlx::query("UPDATE contacts SET archived_at = NULL, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error restoring contact: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let contact = fetch_contact(&state, id).await?;
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "RESTORE", "contact", id).before(&before).after(&contact),
+    )
+    .await;
+
+    Ok(Json(contact))
+}
+
+/// Fold the source contacts into the target. Tickets, assets, credential
+/// access and the rest move to the target, and the sources are archived.
+async fn merge_contacts(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Json(req): Json<MergeContactsRequest>,
+) -> ApiResult<Json<MergeResult>> {
+    let before = fetch_contact(&state, req.target_id)
+        .await
+        .map_err(|status| match status {
+            StatusCode::NOT_FOUND => ApiError::not_found(format!("Contact {} not found", req.target_id)),
+            _ => ApiError::internal("Failed to load contact"),
+        })?;
+
+    let result = contact_merge::merge(&state.db_pool, req.target_id, &req.source_ids)
+        .await
+        .map_err(|e| match e {
+            MergeError::NoSources | MergeError::TargetIsSource => ApiError::validation_single("source_ids", e.to_string()),
+            MergeError::ContactNotFound(_) => ApiError::not_found(e.to_string()),
+            MergeError::ContactArchived(_) | MergeError::DifferentClient(_) => ApiError::conflict(e.to_string()),
+            MergeError::Database(e) => {
+                tracing::error!("Error merging contacts: {}", e);
+                ApiError::internal("Failed to merge contacts")
+            }
+        })?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "MERGE", "contact", req.target_id)
+            .before(&before)
+            .after(&serde_json::json!({
+                "contact": result.target,
+                "merged_contact_ids": result.archived,
+                "repointed": result.repointed,
+            })),
+    )
+    .await;
+
+    Ok(Json(result))
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{AppState, ApiError, ApiResult, AppError};
+use crate::auth::middleware::AuthUserWithRole;
+use crate::auth::rbac::{Action, Resource};
+use crate::services::contract_renewals::{self, ContractRenewal, ExpiringContract, RenewalError};
+use crate::services::contract_usage::{self, BillingCycle, ContractUsage, UsageError};
+
+/// Default look-ahead for the expiring contracts list
+const DEFAULT_EXPIRING_DAYS: i32 = 90;
+
+pub fn contract_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/expiring", get(expiring_contracts))
+        .route("/:id/renew", post(renew_contract))
+        .route("/:id/usage", get(get_contract_usage))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExpiringQuery {
+    pub days: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    /// Billing cycle as `YYYY-MM`, defaulting to the current month
+    pub period: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RenewContract {
+    pub notes: Option<String>,
+}
+
+fn renewal_error(e: RenewalError) -> AppError {
+    match e {
+        RenewalError::NotFound => ApiError::not_found("Contract"),
+        RenewalError::NoEndDate => ApiError::validation_single("end_date", "Contract has no end date to renew from"),
+        RenewalError::NotActive => ApiError::conflict("Only active contracts can be renewed"),
+        RenewalError::Database(e) => {
+            tracing::error!("Error renewing contract: {}", e);
+            ApiError::internal("Failed to renew contract")
+        }
+    }
+}
+
+/// Active contracts ending within `days` (default 90), soonest first
+async fn expiring_contracts(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(query): Query<ExpiringQuery>,
+) -> ApiResult<Json<Vec<ExpiringContract>>> {
+    if !auth.can(Resource::Contracts, Action::Read) {
+        return Err(ApiError::forbidden("You don't have access to contracts"));
+    }
+
+    let days = query.days.unwrap_or(DEFAULT_EXPIRING_DAYS);
+    if !(0..=3650).contains(&days) {
+        return Err(ApiError::validation_single("days", "Must be between 0 and 3650"));
+    }
+
+    let contracts = contract_renewals::expiring_contracts(&state.db_pool, Utc::now().date_naive(), days)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading expiring contracts: {}", e);
+            ApiError::internal("Failed to load expiring contracts")
+        })?;
+
+    Ok(Json(contracts))
+}
+
+/// Renew a contract for another term, extending its end date
+async fn renew_contract(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(contract_id): Path<Uuid>,
+    payload: Option<Json<RenewContract>>,
+) -> ApiResult<Json<ContractRenewal>> {
+    if !auth.can(Resource::Contracts, Action::Update) {
+        return Err(ApiError::forbidden("You don't have permission to renew contracts"));
+    }
+
+    let Json(payload) = payload.unwrap_or_default();
+    let renewal = contract_renewals::renew_contract(&state.db_pool, contract_id, Some(auth.user.id), payload.notes)
+        .await
+        .map_err(renewal_error)?;
+
+    Ok(Json(renewal))
+}
+
+/// Billable hours against the contract's included hours for a billing cycle
+async fn get_contract_usage(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(contract_id): Path<Uuid>,
+    Query(query): Query<UsageQuery>,
+) -> ApiResult<Json<ContractUsage>> {
+    if !auth.can(Resource::Contracts, Action::Read) {
+        return Err(ApiError::forbidden("You don't have access to contracts"));
+    }
+
+    let cycle = match query.period.as_deref() {
+        Some(period) => BillingCycle::parse(period)
+            .ok_or_else(|| ApiError::validation_single("period", "Expected a month as YYYY-MM"))?,
+        None => BillingCycle::containing(Utc::now().date_naive()),
+    };
+
+    let usage = contract_usage::contract_usage(&state.db_pool, contract_id, cycle)
+        .await
+        .map_err(|e| match e {
+            UsageError::NotFound => ApiError::not_found("Contract"),
+            UsageError::NoIncludedHours => ApiError::bad_request("Contract has no included hours"),
+            UsageError::Database(e) => {
+                tracing::error!("Error computing contract usage: {}", e);
+                ApiError::internal("Failed to load contract usage")
+            }
+        })?;
+
+    Ok(Json(usage))
+}
@@ -1,15 +1,19 @@
 //! Email settings and mailbox management API
 //!
 //! Provides endpoints for configuring email-to-ticket integration,
-//! testing SMTP/IMAP connections, and managing email templates.
+//! testing SMTP/IMAP connections, and managing email templates and
+//! per-client branding. Also receives the mail provider's inbound webhook.
 
 use axum::{
+    body::Bytes,
     extract::{Path, Query, State},
+    http::{header, HeaderMap},
     response::Json,
     routing::{get, post, put, delete},
     Router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
@@ -17,7 +21,11 @@ use crate::{
     AppState, ApiResult, ApiError,
     PaginatedResponse, PaginationParams,
 };
+use crate::auth::api_keys::constant_time_eq;
 use crate::auth::middleware::AuthUser;
+use crate::services::canned_response_render::TemplateVariables;
+use crate::services::email_templates::{self, ClientBranding, RenderedEmail, TemplateError};
+use crate::services::inbound_email::{self, InboundEmail, InboundOutcome, PostmarkInbound};
 
 /// Mailbox configuration for email-to-ticket
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -147,11 +155,16 @@ pub fn email_routes() -> Router<Arc<AppState>> {
         // Email templates
         .route("/templates", get(list_templates).post(create_template))
         .route("/templates/:id", get(get_template).put(update_template).delete(delete_template))
+        .route("/templates/preview", post(preview_template))
+        // Per-client branding
+        .route("/branding/:client_id", get(get_branding).put(update_branding).delete(delete_branding))
         // Email logs
         .route("/logs", get(list_email_logs))
         .route("/logs/:id", get(get_email_log))
         // Send test email
         .route("/send-test", post(send_test_email))
+        // Email-to-ticket webhook
+        .route("/inbound", post(receive_inbound_email))
 }
 
 // ==================== Mailbox Handlers ====================
@@ -647,6 +660,148 @@ async fn delete_template(
     Ok(())
 }
 
+/// Request to render a template with sample values
+#[derive(Debug, Deserialize)]
+pub struct PreviewTemplateRequest {
+    pub slug: String,
+    pub client_id: Option<Uuid>,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+/// Render a template as it would be sent, falling back to the built-in
+/// default when the slug has no active template
+async fn preview_template(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Json(req): Json<PreviewTemplateRequest>,
+) -> ApiResult<Json<RenderedEmail>> {
+    let mut variables = TemplateVariables::new();
+    for (name, value) in &req.variables {
+        variables.set(name, Some(value.as_str()));
+    }
+
+    let rendered = email_templates::render(&state.db_pool, &req.slug, req.client_id, &variables)
+        .await
+        .map_err(|e| match e {
+            TemplateError::Unknown(slug) => ApiError::not_found(format!("Template '{}'", slug)),
+            TemplateError::Database(e) => {
+                tracing::error!("Error rendering template: {}", e);
+                ApiError::internal("Failed to render template")
+            }
+        })?;
+
+    Ok(Json(rendered))
+}
+
+// ==================== Branding Handlers ====================
+
+/// Request to set a client's email branding; omitted fields use the defaults
+#[derive(Debug, Deserialize)]
+pub struct UpdateBrandingRequest {
+    pub company_name: Option<String>,
+    pub logo_url: Option<String>,
+    pub primary_color: Option<String>,
+    pub accent_color: Option<String>,
+    pub footer_text: Option<String>,
+}
+
+/// Get a client's branding overrides
+async fn get_branding(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(client_id): Path<Uuid>,
+) -> ApiResult<Json<ClientBranding>> {
+    let branding = email_templates::client_branding(&state.db_pool, client_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching branding: {}", e);
+            ApiError::internal("Failed to fetch branding")
+        })?
+        .ok_or_else(|| ApiError::not_found("Branding"))?;
+
+    Ok(Json(branding))
+}
+
+/// Create or replace a client's branding overrides
+async fn update_branding(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(client_id): Path<Uuid>,
+    Json(req): Json<UpdateBrandingRequest>,
+) -> ApiResult<Json<ClientBranding>> {
+    for (field, value) in [("primary_color", &req.primary_color), ("accent_color", &req.accent_color)] {
+        if let Some(color) = value {
+            if !email_templates::is_valid_color(color) {
+                return Err(ApiError::validation_single(field, "Must be a hex colour like #2563eb"));
+            }
+        }
+    }
+    if let Some(url) = &req.logo_url {
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(ApiError::validation_single("logo_url", "Must be an http(s) URL"));
+        }
+    }
+
+    let client_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM clients WHERE id = $1)")
+        .bind(client_id)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error checking client: {}", e);
+            ApiError::internal("Failed to check client")
+        })?;
+    if !client_exists {
+        return Err(ApiError::not_found("Client"));
+    }
+
+    let branding = sqlx::query_as::<_, ClientBranding>(
+        r#"INSERT INTO client_email_branding (
+            client_id, company_name, logo_url, primary_color, accent_color, footer_text, updated_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, NOW())
+        ON CONFLICT (client_id) DO UPDATE SET
+            company_name = EXCLUDED.company_name,
+            logo_url = EXCLUDED.logo_url,
+            primary_color = EXCLUDED.primary_color,
+            accent_color = EXCLUDED.accent_color,
+            footer_text = EXCLUDED.footer_text,
+            updated_at = NOW()
+        RETURNING *"#
+    )
+    .bind(client_id)
+    .bind(&req.company_name)
+    .bind(&req.logo_url)
+    .bind(&req.primary_color)
+    .bind(&req.accent_color)
+    .bind(&req.footer_text)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving branding: {}", e);
+        ApiError::internal("Failed to save branding")
+    })?;
+
+    Ok(Json(branding))
+}
+
+/// Remove a client's overrides so their mail uses the default branding
+async fn delete_branding(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUser,
+    Path(client_id): Path<Uuid>,
+) -> ApiResult<()> {
+    sqlx::query("DELETE FROM client_email_branding WHERE client_id = $1")
+        .bind(client_id)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error deleting branding: {}", e);
+            ApiError::internal("Failed to delete branding")
+        })?;
+
+    Ok(())
+}
+
 // ==================== Email Log Handlers ====================
 
 /// List email logs
@@ -759,6 +914,66 @@ async fn send_test_email(
     }))
 }
 
+// ==================== Inbound Email ====================
+
+#[derive(Debug, Deserialize)]
+pub struct InboundTokenQuery {
+    pub token: Option<String>,
+}
+
+/// Receive a message from the mail provider's inbound webhook. Accepts
+/// Postmark's inbound JSON or a raw RFC 5322 message (`message/rfc822`).
+/// The provider authenticates with `INBOUND_EMAIL_TOKEN`, sent as the
+/// `X-Inbound-Token` header or a `token` query parameter for providers
+/// that can only be given a URL. Without the variable set the endpoint
+/// doesn't exist.
+async fn receive_inbound_email(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<InboundTokenQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Json<InboundOutcome>> {
+    let expected = std::env::var("INBOUND_EMAIL_TOKEN").unwrap_or_default();
+    if expected.is_empty() {
+        return Err(ApiError::not_found("Inbound email endpoint"));
+    }
+
+    let provided = headers
+        .get("x-inbound-token")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or(query.token)
+        .unwrap_or_default();
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        return Err(ApiError::unauthorized("Invalid inbound email token"));
+    }
+
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/json"))
+        .unwrap_or(false);
+
+    let email = if is_json {
+        let payload: PostmarkInbound = serde_json::from_slice(&body)
+            .map_err(|e| ApiError::bad_request(format!("Invalid inbound payload: {}", e)))?;
+        InboundEmail::from(payload)
+    } else {
+        InboundEmail::from_mime(&body).ok_or_else(|| ApiError::bad_request("Invalid MIME message"))?
+    };
+
+    if email.from_email.is_empty() {
+        return Err(ApiError::validation_single("from", "Sender address is required"));
+    }
+
+    let outcome = inbound_email::ingest(&state.db_pool, &email).await.map_err(|e| {
+        tracing::error!("Failed to ingest inbound email: {}", e);
+        ApiError::internal("Failed to process inbound email")
+    })?;
+
+    Ok(Json(outcome))
+}
+
 // ==================== Helper Functions ====================
 
 /// Encrypt password for storage (placeholder - use proper encryption in production)
@@ -0,0 +1,118 @@
+//! Encryption Key Maintenance
+//!
+//! Admin-only view of the configured key rings and an endpoint that
+//! re-encrypts stored secrets under each ring's current key after a
+//! rotation.
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::auth::middleware::AuthUserWithRole;
+use crate::keyring::{KeyPurpose, KeyRing};
+use crate::services::key_rotation::{self, RotationError, RotationReport};
+use crate::{ApiError, ApiResult, AppState};
+
+pub fn encryption_key_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_key_rings))
+        .route("/rotate", post(rotate_keys))
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyRingStatus {
+    pub purpose: &'static str,
+    pub env_var: &'static str,
+    pub current_key_id: Option<String>,
+    pub key_ids: Vec<String>,
+    pub insecure: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RotateKeysRequest {
+    /// Defaults to every purpose
+    pub purposes: Option<Vec<String>>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn require_admin(auth: &AuthUserWithRole) -> ApiResult<()> {
+    if auth.is_admin() {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("Encryption keys are only available to administrators"))
+    }
+}
+
+/// Key ids only; key material is never returned
+async fn list_key_rings(auth: AuthUserWithRole) -> ApiResult<Json<Vec<KeyRingStatus>>> {
+    require_admin(&auth)?;
+
+    let rings = KeyPurpose::ALL
+        .into_iter()
+        .map(|purpose| match KeyRing::from_env(purpose) {
+            Ok(ring) => KeyRingStatus {
+                purpose: purpose.as_str(),
+                env_var: purpose.env_var(),
+                current_key_id: Some(ring.current_key_id().to_string()),
+                key_ids: ring.key_ids().into_iter().map(String::from).collect(),
+                insecure: ring.is_insecure(),
+                error: None,
+            },
+            Err(e) => KeyRingStatus {
+                purpose: purpose.as_str(),
+                env_var: purpose.env_var(),
+                current_key_id: None,
+                key_ids: Vec::new(),
+                insecure: false,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+
+    Ok(Json(rings))
+}
+
+async fn rotate_keys(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Json(req): Json<RotateKeysRequest>,
+) -> ApiResult<Json<RotationReport>> {
+    require_admin(&auth)?;
+
+    let purposes = match &req.purposes {
+        None => KeyPurpose::ALL.to_vec(),
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                KeyPurpose::parse(name)
+                    .ok_or_else(|| ApiError::validation_single("purposes", format!("unknown purpose '{}'", name)))
+            })
+            .collect::<ApiResult<Vec<_>>>()?,
+    };
+
+    tracing::info!(
+        "User {} started key rotation for {:?}{}",
+        auth.user.id,
+        purposes.iter().map(|p| p.as_str()).collect::<Vec<_>>(),
+        if req.dry_run { " (dry run)" } else { "" }
+    );
+
+    let report = key_rotation::rotate(&state.db_pool, &purposes, req.dry_run)
+        .await
+        .map_err(|e| match e {
+            RotationError::KeyRing(e) => ApiError::bad_request(e.to_string()),
+            RotationError::Database(e) => {
+                tracing::error!("Error re-encrypting stored secrets: {}", e);
+                ApiError::internal("Failed to re-encrypt stored secrets")
+            }
+        })?;
+
+    Ok(Json(report))
+}
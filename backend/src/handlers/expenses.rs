@@ -0,0 +1,361 @@
+//! Expense recording and approval
+//!
+//! An expense either references a vendor record by `vendor_id`, which must
+//! exist and not be archived, or names a one-off vendor in `vendor`. See
+//! [`crate::services::expenses`] for the approval and billing lifecycle.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use resolve_shared::ExpenseStatus;
+use crate::{AppState, ApiError, ApiResult, PaginatedResponse, PaginationParams};
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::auth::rbac::{Action, Resource};
+use crate::handlers::vendors::expense_vendor;
+use crate::services::expenses::{self, Expense, ExpenseError, Review, EXPENSE_COLUMNS};
+use crate::validation::{enums, number, Validator};
+
+pub fn expense_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_expenses).post(create_expense))
+        .route("/:id", get(get_expense).put(update_expense).delete(delete_expense))
+        .route("/:id/approve", post(approve_expense))
+        .route("/:id/reject", post(reject_expense))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExpenseQuery {
+    #[serde(flatten)]
+    pub pagination: PaginationParams,
+    pub status: Option<String>,
+    pub client_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub vendor_id: Option<Uuid>,
+    pub is_billable: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateExpense {
+    pub client_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub vendor_id: Option<Uuid>,
+    /// Vendor name when there's no `vendor_id`
+    pub vendor: Option<String>,
+    pub category: Option<String>,
+    pub description: Option<String>,
+    pub amount: Decimal,
+    pub tax_amount: Option<Decimal>,
+    pub markup_percent: Option<Decimal>,
+    pub expense_date: NaiveDate,
+    #[serde(default)]
+    pub is_billable: bool,
+    pub receipt_file_id: Option<Uuid>,
+}
+
+/// Fields to change; an edited rejected expense is submitted again
+#[derive(Debug, Deserialize)]
+pub struct UpdateExpense {
+    pub client_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub vendor_id: Option<Uuid>,
+    pub vendor: Option<String>,
+    pub category: Option<String>,
+    pub description: Option<String>,
+    pub amount: Option<Decimal>,
+    pub tax_amount: Option<Decimal>,
+    pub markup_percent: Option<Decimal>,
+    pub expense_date: Option<NaiveDate>,
+    pub is_billable: Option<bool>,
+    pub receipt_file_id: Option<Uuid>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReviewRequest {
+    pub notes: Option<String>,
+}
+
+fn validate_amounts(amount: Option<&Decimal>, tax_amount: Option<&Decimal>, markup_percent: Option<&Decimal>) -> ApiResult<()> {
+    if let Some(amount) = amount {
+        number::valid_amount(amount, "amount")?;
+    }
+    if let Some(tax) = tax_amount {
+        number::valid_amount(tax, "tax_amount")?;
+    }
+    if let Some(markup) = markup_percent {
+        number::valid_amount(markup, "markup_percent")?;
+    }
+    Ok(())
+}
+
+async fn require_receipt(pool: &PgPool, receipt_file_id: Option<Uuid>) -> ApiResult<()> {
+    let Some(file_id) = receipt_file_id else {
+        return Ok(());
+    };
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM files WHERE id = $1)")
+        .bind(file_id)
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Err(ApiError::validation_single("receipt_file_id", "File does not exist"));
+    }
+    Ok(())
+}
+
+async fn list_expenses(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Query(query): Query<ExpenseQuery>,
+) -> ApiResult<Json<PaginatedResponse<Expense>>> {
+    let status = enums::parse_optional::<ExpenseStatus>(&query.status, "status")?;
+    let filter = "($1::text IS NULL OR status = $1)
+        AND ($2::uuid IS NULL OR client_id = $2)
+        AND ($3::uuid IS NULL OR project_id = $3)
+        AND ($4::uuid IS NULL OR vendor_id = $4)
+        AND ($5::boolean IS NULL OR is_billable = $5)";
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM expenses WHERE {}", filter))
+        .bind(status)
+        .bind(query.client_id)
+        .bind(query.project_id)
+        .bind(query.vendor_id)
+        .bind(query.is_billable)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    let expenses = sqlx::query_as::<_, Expense>(&format!(
+        "SELECT {} FROM expenses WHERE {} ORDER BY expense_date DESC, created_at DESC LIMIT $6 OFFSET $7",
+        EXPENSE_COLUMNS, filter
+    ))
+    .bind(status)
+    .bind(query.client_id)
+    .bind(query.project_id)
+    .bind(query.vendor_id)
+    .bind(query.is_billable)
+    .bind(query.pagination.limit())
+    .bind(query.pagination.offset())
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(Json(PaginatedResponse::new(expenses, &query.pagination, total)))
+}
+
+/// Record an expense as submitted for approval
+async fn create_expense(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<CreateExpense>,
+) -> ApiResult<(StatusCode, Json<Expense>)> {
+    Validator::new()
+        .required_string(&payload.category, "category")
+        .max_length(&payload.category, "category", 100)
+        .required_string(&payload.description, "description")
+        .error_if(
+            payload.vendor_id.is_none() && !payload.vendor.as_deref().is_some_and(|v| !v.trim().is_empty()),
+            "vendor_id",
+            "vendor_id or vendor is required",
+        )
+        .finish()?;
+    validate_amounts(Some(&payload.amount), payload.tax_amount.as_ref(), payload.markup_percent.as_ref())?;
+    require_receipt(&state.db_pool, payload.receipt_file_id).await?;
+
+    let vendor_name = match payload.vendor_id {
+        Some(vendor_id) => expense_vendor(&state.db_pool, vendor_id).await?.name,
+        None => payload.vendor.as_deref().unwrap_or_default().trim().to_string(),
+    };
+
+    let expense = sqlx::query_as::<_, Expense>(&format!(
+        "INSERT INTO expenses (client_id, project_id, vendor_id, vendor, category, description,
+            amount, tax_amount, markup_percent, expense_date, is_billable, receipt_file_id, status, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, COALESCE($8, 0), COALESCE($9, 0), $10, $11, $12, $13, $14)
+         RETURNING {}",
+        EXPENSE_COLUMNS
+    ))
+    .bind(payload.client_id)
+    .bind(payload.project_id)
+    .bind(payload.vendor_id)
+    .bind(vendor_name)
+    .bind(payload.category.as_deref().map(str::trim))
+    .bind(payload.description.as_deref().map(str::trim))
+    .bind(payload.amount)
+    .bind(payload.tax_amount)
+    .bind(payload.markup_percent)
+    .bind(payload.expense_date)
+    .bind(payload.is_billable)
+    .bind(payload.receipt_file_id)
+    .bind(ExpenseStatus::Submitted)
+    .bind(user.id)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(expense)))
+}
+
+async fn get_expense(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(expense_id): Path<Uuid>,
+) -> ApiResult<Json<Expense>> {
+    let expense = expenses::find_expense(&state.db_pool, expense_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Expense"))?;
+
+    Ok(Json(expense))
+}
+
+async fn update_expense(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(expense_id): Path<Uuid>,
+    Json(payload): Json<UpdateExpense>,
+) -> ApiResult<Json<Expense>> {
+    let current = expenses::find_expense(&state.db_pool, expense_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Expense"))?;
+    if !expenses::is_editable(current.status) {
+        return Err(ApiError::conflict(format!("A {} expense can't be edited", current.status)));
+    }
+
+    let mut validator = Validator::new().max_length(&payload.category, "category", 100);
+    if payload.category.is_some() {
+        validator = validator.required_string(&payload.category, "category");
+    }
+    if payload.description.is_some() {
+        validator = validator.required_string(&payload.description, "description");
+    }
+    if payload.vendor.is_some() {
+        validator = validator.required_string(&payload.vendor, "vendor");
+    }
+    validator.finish()?;
+    validate_amounts(payload.amount.as_ref(), payload.tax_amount.as_ref(), payload.markup_percent.as_ref())?;
+    require_receipt(&state.db_pool, payload.receipt_file_id).await?;
+
+    let vendor_name = match payload.vendor_id {
+        Some(vendor_id) => Some(expense_vendor(&state.db_pool, vendor_id).await?.name),
+        None => payload.vendor.as_deref().map(|v| v.trim().to_string()),
+    };
+
+    // Only while still editable, in case it was reviewed meanwhile
+    let expense = sqlx::query_as::<_, Expense>(&format!(
+        "UPDATE expenses SET
+            client_id = COALESCE($2, client_id),
+            project_id = COALESCE($3, project_id),
+            vendor_id = COALESCE($4, vendor_id),
+            vendor = COALESCE($5, vendor),
+            category = COALESCE($6, category),
+            description = COALESCE($7, description),
+            amount = COALESCE($8, amount),
+            tax_amount = COALESCE($9, tax_amount),
+            markup_percent = COALESCE($10, markup_percent),
+            expense_date = COALESCE($11, expense_date),
+            is_billable = COALESCE($12, is_billable),
+            receipt_file_id = COALESCE($13, receipt_file_id),
+            status = $14,
+            updated_at = NOW()
+         WHERE id = $1 AND status IN ($14, $15)
+         RETURNING {}",
+        EXPENSE_COLUMNS
+    ))
+    .bind(expense_id)
+    .bind(payload.client_id)
+    .bind(payload.project_id)
+    .bind(payload.vendor_id)
+    .bind(vendor_name)
+    .bind(payload.category.as_deref().map(str::trim))
+    .bind(payload.description.as_deref().map(str::trim))
+    .bind(payload.amount)
+    .bind(payload.tax_amount)
+    .bind(payload.markup_percent)
+    .bind(payload.expense_date)
+    .bind(payload.is_billable)
+    .bind(payload.receipt_file_id)
+    .bind(ExpenseStatus::Submitted)
+    .bind(ExpenseStatus::Rejected)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::conflict("The expense was reviewed while you were editing it"))?;
+
+    Ok(Json(expense))
+}
+
+async fn delete_expense(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(expense_id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    if !auth.can(Resource::Expenses, Action::Delete) {
+        return Err(ApiError::forbidden("You don't have permission to delete expenses"));
+    }
+
+    let result = sqlx::query("DELETE FROM expenses WHERE id = $1 AND status NOT IN ($2, $3)")
+        .bind(expense_id)
+        .bind(ExpenseStatus::Billed)
+        .bind(ExpenseStatus::Reimbursed)
+        .execute(&state.db_pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return match expenses::find_expense(&state.db_pool, expense_id).await? {
+            Some(expense) => Err(ApiError::conflict(format!("A {} expense can't be deleted", expense.status))),
+            None => Err(ApiError::not_found("Expense")),
+        };
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn approve_expense(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(expense_id): Path<Uuid>,
+    payload: Option<Json<ReviewRequest>>,
+) -> ApiResult<Json<Expense>> {
+    review(&state, &auth, expense_id, Review::Approve, payload).await
+}
+
+async fn reject_expense(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(expense_id): Path<Uuid>,
+    payload: Option<Json<ReviewRequest>>,
+) -> ApiResult<Json<Expense>> {
+    review(&state, &auth, expense_id, Review::Reject, payload).await
+}
+
+async fn review(
+    state: &AppState,
+    auth: &AuthUserWithRole,
+    expense_id: Uuid,
+    decision: Review,
+    payload: Option<Json<ReviewRequest>>,
+) -> ApiResult<Json<Expense>> {
+    if !auth.can(Resource::Expenses, Action::Approve) {
+        return Err(ApiError::forbidden("You don't have permission to approve expenses"));
+    }
+
+    let Json(payload) = payload.unwrap_or_default();
+    let expense = expenses::review_expense(&state.db_pool, expense_id, auth.user.id, decision, payload.notes)
+        .await
+        .map_err(|e| match e {
+            ExpenseError::NotFound => ApiError::not_found("Expense"),
+            ExpenseError::AlreadyReviewed(status) => {
+                ApiError::conflict(format!("Only submitted expenses can be reviewed; this one is {}", status))
+            }
+            ExpenseError::OwnExpense => ApiError::forbidden("You can't review an expense you submitted"),
+            e => {
+                tracing::error!("Error reviewing expense: {}", e);
+                ApiError::internal("Failed to review expense")
+            }
+        })?;
+
+    Ok(Json(expense))
+}
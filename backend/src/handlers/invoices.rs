@@ -1,35 +1,71 @@
 use axum::{
     extract::{Path, Query, State},
-    http::{StatusCode, HeaderMap},
-    response::Json,
+    http::{header, StatusCode, HeaderMap},
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, patch},
     Router,
 };
 use chrono::{Utc, NaiveDate};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, PgPool};
 use std::sync::Arc;
 use uuid::Uuid;
+use base64::{Engine as _, engine::general_purpose};
+use resolve_shared::{Invoice, InvoiceLineItem, InvoiceStatus, InvoiceWithLineItems, Payment};
 use crate::AppState;
+use crate::error::{ApiError, ApiResult, AppError};
 use crate::auth::{extract_token, verify_token};
+use crate::services::{CacheService, cache_keys, ttl, invoice_pdf};
+use crate::services::invoice_payments::{self, PaymentError};
+use crate::services::{dashboard_stream, outbound_webhooks};
+use crate::services::stripe_payments::{self, StripeError};
+use crate::services::invoice_pdf::{CompanyBranding, InvoicePdfClient, InvoicePdfData, InvoicePdfLine};
+use crate::validation::{enums, number, Validate, Validator};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InvoiceCreate {
     pub client_id: Uuid,
     pub contract_id: Option<Uuid>,
     pub project_id: Option<Uuid>,
+    #[serde(default)]
     pub number: String,
     pub date: NaiveDate,
     pub due_date: NaiveDate,
+    #[serde(default)]
     pub payment_terms: String,
     pub notes: Option<String>,
     pub terms: Option<String>,
+    #[serde(default)]
     pub line_items: Vec<InvoiceLineItemCreate>,
 }
 
+impl Validate for InvoiceCreate {
+    fn rules(&self, mut v: Validator) -> Validator {
+        v = v
+            .not_blank(&self.number, "number")
+            .error_if(self.number.trim().len() > 50, "number", "number must be 50 characters or less")
+            .not_blank(&self.payment_terms, "payment_terms")
+            .error_if(self.due_date < self.date, "due_date", "due_date cannot be before date")
+            .error_if(self.line_items.is_empty(), "line_items", "At least one line item is required");
+        for (i, item) in self.line_items.iter().enumerate() {
+            v = v
+                .not_blank(&item.description, &format!("line_items[{}].description", i))
+                .error_if(
+                    item.quantity <= Decimal::ZERO,
+                    &format!("line_items[{}].quantity", i),
+                    "quantity must be positive",
+                )
+                .check(number::valid_amount(&item.unit_price, &format!("line_items[{}].unit_price", i)))
+                .range(item.tax_rate, &format!("line_items[{}].tax_rate", i), Decimal::ZERO, Decimal::ONE_HUNDRED);
+        }
+        v
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InvoiceLineItemCreate {
+    #[serde(default)]
     pub description: String,
     pub quantity: Decimal,
     pub unit_price: Decimal,
@@ -84,16 +120,13 @@ pub struct InvoiceWithDetails {
     pub updated_at: Option<chrono::DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct InvoiceLineItem {
-    pub id: Uuid,
-    pub invoice_id: Uuid,
-    pub description: String,
-    pub quantity: Decimal,
-    pub unit_price: Decimal,
-    pub line_total: Decimal,
-    pub tax_rate: Option<Decimal>,
-    pub tax_amount: Option<Decimal>,
+#[derive(Debug, Serialize)]
+pub struct OnlinePaymentResponse {
+    pub payment_intent_id: String,
+    pub client_secret: String,
+    pub publishable_key: String,
+    pub amount: Decimal,
+    pub currency: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -105,24 +138,13 @@ pub struct PaymentCreate {
     pub notes: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct Payment {
-    pub id: Uuid,
-    pub invoice_id: Uuid,
-    pub amount: Decimal,
-    pub payment_date: NaiveDate,
-    pub payment_method: Option<String>,
-    pub reference_number: Option<String>,
-    pub notes: Option<String>,
-    pub created_at: chrono::DateTime<Utc>,
-}
-
 pub fn invoice_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(list_invoices).post(create_invoice))
         .route("/:id", get(get_invoice).put(update_invoice))
         .route("/:id/line-items", get(get_invoice_line_items))
         .route("/:id/payments", get(get_invoice_payments).post(add_payment))
+        .route("/:id/pay-online", post(pay_invoice_online))
         .route("/:id/send", patch(send_invoice))
         .route("/:id/pdf", get(generate_invoice_pdf))
         .route("/stats", get(get_invoice_stats))
@@ -164,16 +186,30 @@ async fn list_invoices(
     Ok(Json(invoices))
 }
 
+/// Every problem with the request, including references that don't exist
+async fn validate_invoice_create(pool: &PgPool, payload: &InvoiceCreate) -> ApiResult<()> {
+    payload
+        .rules(Validator::new())
+        .exists(pool, "clients", Some(payload.client_id), "client_id").await?
+        .exists(pool, "contracts", payload.contract_id, "contract_id").await?
+        .exists(pool, "projects", payload.project_id, "project_id").await?
+        .finish()
+}
+
 async fn create_invoice(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Json(payload): Json<InvoiceCreate>,
-) -> Result<(StatusCode, Json<InvoiceWithDetails>), StatusCode> {
+) -> Result<(StatusCode, Json<InvoiceWithDetails>), Response> {
     // Extract user from token
     let token = extract_token(&headers)
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-    let _token_data = verify_token(&token)
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(|| StatusCode::UNAUTHORIZED.into_response())?;
+    let _token_data = verify_token(&state.db_pool, &token).await
+        .map_err(|_| StatusCode::UNAUTHORIZED.into_response())?;
+
+    validate_invoice_create(&state.db_pool, &payload)
+        .await
+        .map_err(IntoResponse::into_response)?;
     
     let invoice_id = Uuid::new_v4();
     let now = Utc::now();
@@ -196,7 +232,7 @@ async fn create_invoice(
     // Start transaction
     let mut tx = state.db_pool.begin().await.map_err(|e| {
         tracing::error!("Error starting transaction: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
     
     // Insert invoice
@@ -227,7 +263,7 @@ async fn create_invoice(
     .await
     .map_err(|e| {
         tracing::error!("Error creating invoice: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
     
     // Insert line items
@@ -258,33 +294,75 @@ async fn create_invoice(
         .await
         .map_err(|e| {
             tracing::error!("Error creating invoice line item: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         })?;
     }
     
     tx.commit().await.map_err(|e| {
         tracing::error!("Error committing transaction: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
+    crate::middleware::prometheus::record_invoice_generated("manual");
     
     // Fetch the created invoice
-    let invoice = get_invoice_by_id(&state, invoice_id).await?;
+    let invoice = get_invoice_by_id(&state, invoice_id).await.map_err(IntoResponse::into_response)?;
     Ok((StatusCode::CREATED, Json(invoice)))
 }
 
 async fn get_invoice(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<Json<InvoiceWithDetails>, StatusCode> {
-    let invoice = get_invoice_by_id(&state, id).await?;
-    Ok(Json(invoice))
+) -> Result<Json<InvoiceWithLineItems>, StatusCode> {
+    let invoice = sqlx::query_as::<_, Invoice>(
+        "SELECT id, client_id, contract_id, project_id, number, date, due_date,
+                COALESCE(subtotal, 0) as subtotal,
+                COALESCE(tax_amount, 0) as tax_amount,
+                COALESCE(total, 0) as total,
+                COALESCE(balance, 0) as balance,
+                COALESCE(status, 'draft') as status,
+                COALESCE(payment_terms, 'net_30') as payment_terms,
+                late_fee_percentage, discount_percentage, discount_amount,
+                notes, terms, COALESCE(created_at, NOW()) as created_at, updated_at
+         FROM invoices
+         WHERE id = $1"
+    )
+    .bind(id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        _ => {
+            tracing::error!("Error fetching invoice: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+
+    let client_name = sqlx::query_scalar::<_, String>("SELECT name FROM clients WHERE id = $1")
+        .bind(invoice.client_id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching invoice client: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    let line_items = fetch_line_items(&state, id).await?;
+
+    Ok(Json(InvoiceWithLineItems {
+        invoice,
+        client_name,
+        line_items,
+    }))
 }
 
 async fn update_invoice(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     Json(payload): Json<InvoiceUpdate>,
-) -> Result<Json<InvoiceWithDetails>, StatusCode> {
+) -> Result<Json<InvoiceWithDetails>, Response> {
+    let status = enums::parse_optional::<InvoiceStatus>(&payload.status, "status").map_err(IntoResponse::into_response)?;
+
     sqlx::query(
         "UPDATE invoices SET 
          date = COALESCE($2, date),
@@ -299,7 +377,7 @@ async fn update_invoice(
     .bind(id)
     .bind(payload.date)
     .bind(payload.due_date)
-    .bind(payload.status)
+    .bind(status)
     .bind(payload.payment_terms)
     .bind(payload.notes)
     .bind(payload.terms)
@@ -307,10 +385,10 @@ async fn update_invoice(
     .await
     .map_err(|e| {
         tracing::error!("Error updating invoice: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
     
-    let invoice = get_invoice_by_id(&state, id).await?;
+    let invoice = get_invoice_by_id(&state, id).await.map_err(IntoResponse::into_response)?;
     Ok(Json(invoice))
 }
 
@@ -318,21 +396,7 @@ async fn get_invoice_line_items(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<Vec<InvoiceLineItem>>, StatusCode> {
-    let line_items = sqlx::query_as::<_, InvoiceLineItem>(
-        "SELECT id, invoice_id, description, quantity, unit_price, 
-         line_total, tax_rate, tax_amount
-         FROM invoice_line_items 
-         WHERE invoice_id = $1 
-         ORDER BY created_at"
-    )
-    .bind(id)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error fetching invoice line items: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    
+    let line_items = fetch_line_items(&state, id).await?;
     Ok(Json(line_items))
 }
 
@@ -342,10 +406,10 @@ async fn get_invoice_payments(
 ) -> Result<Json<Vec<Payment>>, StatusCode> {
     let payments = sqlx::query_as::<_, Payment>(
         "SELECT id, invoice_id, amount, payment_date, payment_method,
-         reference_number, notes, created_at
+         reference_number, notes, COALESCE(created_at, NOW()) as created_at
          FROM payments 
          WHERE invoice_id = $1 
-         ORDER BY payment_date DESC"
+         ORDER BY payment_date DESC, created_at DESC"
     )
     .bind(id)
     .fetch_all(&state.db_pool)
@@ -363,83 +427,126 @@ async fn add_payment(
     headers: HeaderMap,
     Path(id): Path<Uuid>,
     Json(payload): Json<PaymentCreate>,
-) -> Result<(StatusCode, Json<Payment>), StatusCode> {
+) -> ApiResult<(StatusCode, Json<Payment>)> {
     // Extract user from token
     let token = extract_token(&headers)
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-    let _token_data = verify_token(&token)
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(|| ApiError::unauthorized("Missing authentication token"))?;
+    let _token_data = verify_token(&state.db_pool, &token).await
+        .map_err(|_| ApiError::unauthorized("Invalid authentication token"))?;
     
-    let payment_id = Uuid::new_v4();
-    let now = Utc::now();
+    let mut tx = state.db_pool.begin().await?;
     
-    // Start transaction
-    let mut tx = state.db_pool.begin().await.map_err(|e| {
-        tracing::error!("Error starting transaction: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    // Lock the invoice so concurrent payments see each other's balance
+    let (total, balance, status) = sqlx::query_as::<_, (Decimal, Decimal, String)>(
+        "SELECT COALESCE(total, 0), COALESCE(balance, 0), COALESCE(status, 'draft')
+         FROM invoices WHERE id = $1 FOR UPDATE"
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
     
-    // Insert payment
-    sqlx::query(
+    let outcome = invoice_payments::apply_payment(total, balance, &status, payload.amount)
+        .map_err(|e| match e {
+            PaymentError::InvalidStatus(_) => ApiError::conflict(e.to_string()),
+            _ => ApiError::validation_single("amount", e.to_string()),
+        })?;
+    
+    let payment = sqlx::query_as::<_, Payment>(
         "INSERT INTO payments (
-            id, invoice_id, amount, payment_date, payment_method,
-            reference_number, notes, created_at
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+            invoice_id, amount, payment_date, payment_method,
+            reference_number, notes
+        ) VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, invoice_id, amount, payment_date, payment_method,
+                  reference_number, notes, COALESCE(created_at, NOW()) as created_at"
     )
-    .bind(payment_id)
     .bind(id)
     .bind(payload.amount)
     .bind(payload.payment_date)
     .bind(payload.payment_method)
     .bind(payload.reference_number)
     .bind(payload.notes)
-    .bind(now)
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error adding payment: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .fetch_one(&mut *tx)
+    .await?;
     
-    // Update invoice balance
     sqlx::query(
-        "UPDATE invoices SET 
-         balance = balance - $2,
-         status = CASE WHEN balance - $2 <= 0 THEN 'paid' ELSE 'partial' END,
-         updated_at = NOW()
-         WHERE id = $1"
+        "UPDATE invoices SET balance = $2, status = $3, updated_at = NOW() WHERE id = $1"
     )
     .bind(id)
-    .bind(payload.amount)
+    .bind(outcome.balance)
+    .bind(&outcome.status)
     .execute(&mut *tx)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error updating invoice balance: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .await?;
     
-    tx.commit().await.map_err(|e| {
-        tracing::error!("Error committing transaction: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    tx.commit().await?;
     
-    // Fetch the created payment
-    let payment = sqlx::query_as::<_, Payment>(
-        "SELECT id, invoice_id, amount, payment_date, payment_method,
-         reference_number, notes, created_at
-         FROM payments WHERE id = $1"
-    )
-    .bind(payment_id)
-    .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error fetching payment: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    if outcome.status == "paid" {
+        outbound_webhooks::notify_invoice_paid(&state.db_pool, id).await;
+        state
+            .broadcast_notification(dashboard_stream::INVOICE_PAID, serde_json::json!({ "invoice_id": id }))
+            .await;
+    }
     
     Ok((StatusCode::CREATED, Json(payment)))
 }
 
+async fn pay_invoice_online(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<OnlinePaymentResponse>> {
+    let token = extract_token(&headers)
+        .ok_or_else(|| ApiError::unauthorized("Missing authentication token"))?;
+    let _token_data = verify_token(&state.db_pool, &token).await
+        .map_err(|_| ApiError::unauthorized("Invalid authentication token"))?;
+
+    let (number, balance, status) = sqlx::query_as::<_, (String, Decimal, String)>(
+        "SELECT number, COALESCE(balance, 0), COALESCE(status, 'draft') FROM invoices WHERE id = $1"
+    )
+    .bind(id)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+
+    if matches!(status.as_str(), "draft" | "paid" | "void" | "cancelled") {
+        return Err(ApiError::conflict(format!("A {} invoice cannot be paid online", status)));
+    }
+    if balance <= Decimal::ZERO {
+        return Err(ApiError::conflict("Invoice has no outstanding balance"));
+    }
+
+    let settings = stripe_payments::load_settings(&state.db_pool).await.map_err(|e| match e {
+        StripeError::NotConfigured => ApiError::bad_request("Online payments are not configured"),
+        e => {
+            tracing::error!("Error loading Stripe settings: {}", e);
+            ApiError::internal("Online payments are unavailable")
+        }
+    })?;
+
+    let intent = stripe_payments::create_payment_intent(&settings, id, &number, balance)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error creating Stripe PaymentIntent for invoice {}: {}", id, e);
+            AppError::ExternalServiceError {
+                service: "stripe".to_string(),
+                message: "Could not start the payment".to_string(),
+            }
+        })?;
+
+    let client_secret = intent.client_secret.clone().ok_or_else(|| {
+        tracing::error!("Stripe PaymentIntent {} returned no client secret", intent.id);
+        ApiError::internal("Online payments are unavailable")
+    })?;
+
+    Ok(Json(OnlinePaymentResponse {
+        payment_intent_id: intent.id,
+        client_secret,
+        publishable_key: settings.credentials.publishable_key,
+        amount: balance,
+        currency: settings.currency,
+    }))
+}
+
 async fn send_invoice(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
@@ -460,14 +567,59 @@ async fn send_invoice(
 }
 
 async fn generate_invoice_pdf(
-    State(_state): State<Arc<AppState>>,
-    Path(_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    // TODO: Implement PDF generation
-    Ok(Json(serde_json::json!({
-        "message": "PDF generation not yet implemented",
-        "url": "/api/v1/invoices/{}/pdf"
-    })))
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let invoice = get_invoice_by_id(&state, id).await?;
+    let filename = invoice_pdf::pdf_filename(&invoice.number);
+
+    // Cached renders are keyed by updated_at so any edit produces a fresh PDF
+    let version = invoice.updated_at.unwrap_or(invoice.created_at).timestamp_millis();
+    let cache_key = cache_keys::invoice_pdf(id, version);
+    let cache = CacheService::new(state.db_pool.clone());
+
+    let cached = match cache.get::<String>(&cache_key).await {
+        Ok(Some(encoded)) => general_purpose::STANDARD.decode(encoded).ok(),
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("Error reading cached invoice PDF: {}", e);
+            None
+        }
+    };
+
+    let pdf_bytes = match cached {
+        Some(bytes) => bytes,
+        None => {
+            let data = load_invoice_pdf_data(&state, invoice).await?;
+            let bytes = invoice_pdf::render_invoice_pdf(&data).map_err(|e| {
+                tracing::error!("Error rendering invoice PDF: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+            // Drop renders of older versions before storing this one
+            if let Err(e) = cache.invalidate_pattern(&cache_keys::invoice_pattern(id)).await {
+                tracing::warn!("Error invalidating cached invoice PDFs: {}", e);
+            }
+            let encoded = general_purpose::STANDARD.encode(&bytes);
+            if let Err(e) = cache.set(&cache_key, &encoded, ttl::STATIC * 24).await {
+                tracing::warn!("Error caching invoice PDF: {}", e);
+            }
+
+            bytes
+        }
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "application/pdf".parse().unwrap());
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{}\"", filename)
+            .parse()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+    );
+    headers.insert(header::CONTENT_LENGTH, pdf_bytes.len().to_string().parse().unwrap());
+
+    Ok((headers, pdf_bytes))
 }
 
 #[derive(Debug, Serialize)]
@@ -577,4 +729,86 @@ async fn get_invoice_by_id(state: &AppState, id: Uuid) -> Result<InvoiceWithDeta
             StatusCode::INTERNAL_SERVER_ERROR
         }
     })
-}
\ No newline at end of file
+}
+
+/// Line items in insertion order. `line_total` falls back to quantity * unit_price
+/// for legacy rows so UI totals always reconcile with the invoice header.
+async fn fetch_line_items(state: &AppState, invoice_id: Uuid) -> Result<Vec<InvoiceLineItem>, StatusCode> {
+    sqlx::query_as::<_, InvoiceLineItem>(
+        "SELECT id, invoice_id, description, quantity, unit_price,
+                COALESCE(line_total, quantity * unit_price) as line_total,
+                tax_rate, tax_amount, COALESCE(created_at, NOW()) as created_at
+         FROM invoice_line_items
+         WHERE invoice_id = $1
+         ORDER BY created_at, id"
+    )
+    .bind(invoice_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error fetching invoice line items: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn load_invoice_pdf_data(
+    state: &AppState,
+    invoice: InvoiceWithDetails,
+) -> Result<InvoicePdfData, StatusCode> {
+    let client = sqlx::query_as::<_, InvoicePdfClient>(
+        "SELECT name, email, phone, address, city, state, zip, billing_address
+         FROM clients WHERE id = $1"
+    )
+    .bind(invoice.client_id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error fetching invoice client: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let line_items = fetch_line_items(state, invoice.id)
+        .await?
+        .into_iter()
+        .map(|item| InvoicePdfLine {
+            description: item.description,
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            line_total: item.line_total,
+            tax_rate: item.tax_rate,
+        })
+        .collect();
+
+    let company = sqlx::query_as::<_, CompanyBranding>(
+        "SELECT company_name, tax_id, address, city, state, zip, country,
+                phone, email, website, invoice_footer
+         FROM company_settings
+         ORDER BY created_at
+         LIMIT 1"
+    )
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error fetching company settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .unwrap_or_default();
+
+    Ok(InvoicePdfData {
+        number: invoice.number,
+        date: invoice.date,
+        due_date: invoice.due_date,
+        status: invoice.status,
+        payment_terms: invoice.payment_terms,
+        subtotal: invoice.subtotal,
+        tax_amount: invoice.tax_amount,
+        discount_amount: invoice.discount_amount,
+        total: invoice.total,
+        balance: invoice.balance,
+        notes: invoice.notes,
+        terms: invoice.terms,
+        company,
+        client,
+        line_items,
+    })
+}
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
-    http::{StatusCode, HeaderMap},
-    response::Json,
+    http::{header, StatusCode, HeaderMap, HeaderValue},
+    response::{Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
@@ -10,8 +10,14 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::sync::Arc;
 use uuid::Uuid;
-use crate::AppState;
+use crate::{AppState, PaginatedResponse, PaginationParams};
+use crate::etag::{self, IfNoneMatch};
 use crate::auth::{extract_token, verify_token};
+use crate::auth::middleware::AuthUserWithRole;
+use crate::auth::rbac::{Action, Resource};
+use crate::services::kb_search::{self, KbSearchResult, SearchScope};
+use crate::services::kb_versions::{self, KbArticleVersion, VersionError};
+use crate::services::kb_votes::{self, VoteError, VoteTally};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CategoryCreate {
@@ -108,12 +114,15 @@ pub struct ArticleFeedback {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct SearchResult {
-    pub id: Uuid,
-    pub title: String,
-    pub excerpt: String,
-    pub category_name: Option<String>,
-    pub relevance_score: f32,
+pub struct ArticleVote {
+    pub helpful: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KbSearchQuery {
+    #[serde(alias = "search")]
+    pub q: String,
+    pub category_id: Option<Uuid>,
 }
 
 pub fn knowledge_base_routes() -> Router<Arc<AppState>> {
@@ -128,12 +137,21 @@ pub fn knowledge_base_routes() -> Router<Arc<AppState>> {
         .route("/articles/:id", get(get_article).put(update_article).delete(delete_article))
         .route("/articles/:id/view", post(increment_view_count))
         .route("/articles/:id/feedback", post(submit_feedback))
+
+        // Versions
+        .route("/:id/versions", get(list_article_versions))
+        .route("/:id/revert/:version", post(revert_article))
+
+        // Votes
+        .route("/:id/vote", post(vote_on_article))
         
         // Search
         .route("/search", get(search_articles))
         
         // Portal-specific endpoints
         .route("/portal/articles", get(list_portal_articles))
+        .route("/portal/articles/:id", get(get_portal_article))
+        .route("/portal/search", get(search_portal_articles))
         .route("/portal/categories", get(list_portal_categories))
 }
 
@@ -352,10 +370,10 @@ async fn create_article(
     // Extract user from token
     let token = extract_token(&headers)
         .ok_or(StatusCode::UNAUTHORIZED)?;
-    let token_data = verify_token(&token)
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
-    let author_id = Uuid::parse_str(&token_data.claims.sub)
-        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let author_id = verify_token(&state.db_pool, &token)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?
+        .sub;
     
     let article_id = Uuid::new_v4();
     let now = Utc::now();
@@ -400,6 +418,10 @@ async fn create_article(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
     
+    kb_versions::record_version(&mut *tx, article_id, Some(author_id), None)
+        .await
+        .map_err(version_error)?;
+
     // Add client access restrictions if specified
     if let Some(client_ids) = payload.client_ids {
         for client_id in client_ids {
@@ -438,7 +460,8 @@ async fn create_article(
 async fn get_article(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<Json<Article>, StatusCode> {
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
     let article = sqlx::query_as::<_, Article>(
         "SELECT * FROM kb_articles WHERE id = $1"
     )
@@ -452,8 +475,31 @@ async fn get_article(
             StatusCode::INTERNAL_SERVER_ERROR
         }
     })?;
-    
-    Ok(Json(article))
+
+    // A revalidated copy is still a view
+    count_view(&state, id, &headers).await;
+    Ok(etag::json(&IfNoneMatch::from_headers(&headers), &article))
+}
+
+async fn get_portal_article(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let article = sqlx::query_as::<_, Article>(
+        "SELECT * FROM kb_articles WHERE id = $1 AND is_public = true AND status = 'published'"
+    )
+    .bind(id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error fetching portal article: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    count_view(&state, id, &headers).await;
+    Ok(etag::json(&IfNoneMatch::from_headers(&headers), &article))
 }
 
 async fn update_article(
@@ -462,17 +508,20 @@ async fn update_article(
     headers: HeaderMap,
     Json(payload): Json<ArticleCreate>,
 ) -> Result<Json<Article>, StatusCode> {
-    // Verify user is authenticated
-    let _token = extract_token(&headers)
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-    
+    let editor_id = editor_id(&state, &headers).await?;
+
     let status = payload.status.unwrap_or_else(|| "draft".to_string());
     let published_at = if status == "published" {
         Some(Utc::now())
     } else {
         None
     };
-    
+
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        tracing::error!("Error starting transaction: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
     let article = sqlx::query_as::<_, Article>(
         "UPDATE kb_articles SET 
          category_id = $2, title = $3, slug = $4, content = $5, excerpt = $6,
@@ -496,7 +545,7 @@ async fn update_article(
     .bind(payload.meta_keywords)
     .bind(payload.meta_description)
     .bind(published_at)
-    .fetch_one(&state.db_pool)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| match e {
         sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
@@ -505,10 +554,55 @@ async fn update_article(
             StatusCode::INTERNAL_SERVER_ERROR
         }
     })?;
-    
+
+    kb_versions::record_version(&mut *tx, id, Some(editor_id), None)
+        .await
+        .map_err(version_error)?;
+
+    tx.commit().await.map_err(|e| {
+        tracing::error!("Error committing transaction: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
     Ok(Json(article))
 }
 
+/// The signed-in user making a change
+async fn editor_id(state: &AppState, headers: &HeaderMap) -> Result<Uuid, StatusCode> {
+    let token = extract_token(headers).ok_or(StatusCode::UNAUTHORIZED)?;
+    verify_token(&state.db_pool, &token).await.map(|claims| claims.sub)
+}
+
+fn version_error(e: VersionError) -> StatusCode {
+    match e {
+        VersionError::ArticleNotFound | VersionError::VersionNotFound(_) => StatusCode::NOT_FOUND,
+        VersionError::Database(e) => {
+            tracing::error!("Error saving article version: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn list_article_versions(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<KbArticleVersion>>, StatusCode> {
+    let versions = kb_versions::list_versions(&state.db_pool, id).await.map_err(version_error)?;
+    Ok(Json(versions))
+}
+
+async fn revert_article(
+    State(state): State<Arc<AppState>>,
+    Path((id, version)): Path<(Uuid, i32)>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<KbArticleVersion>), StatusCode> {
+    let editor_id = editor_id(&state, &headers).await?;
+    let restored = kb_versions::revert(&state.db_pool, id, version, Some(editor_id))
+        .await
+        .map_err(version_error)?;
+    Ok((StatusCode::CREATED, Json(restored)))
+}
+
 async fn delete_article(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
@@ -533,19 +627,22 @@ async fn delete_article(
 async fn increment_view_count(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
-    sqlx::query(
-        "UPDATE kb_articles SET view_count = view_count + 1 WHERE id = $1"
-    )
-    .bind(id)
-    .execute(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error incrementing view count: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    
-    Ok(StatusCode::OK)
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM kb_articles WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error checking article {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let counted = count_view(&state, id, &headers).await;
+    Ok(Json(serde_json::json!({ "counted": counted })))
 }
 
 async fn submit_feedback(
@@ -556,7 +653,7 @@ async fn submit_feedback(
 ) -> Result<StatusCode, StatusCode> {
     // Try to get user or contact ID
     let (user_id, contact_id) = if let Some(token) = extract_token(&headers) {
-        if let Ok(token_data) = verify_token(&token) {
+        if let Ok(token_data) = verify_token(&state.db_pool, &token).await {
             (Some(Uuid::parse_str(&token_data.claims.sub).unwrap_or_default()), None::<Uuid>)
         } else {
             (None, None)
@@ -603,55 +700,97 @@ async fn submit_feedback(
     Ok(StatusCode::OK)
 }
 
+/// Staff who can read the knowledge base search internal articles too;
+/// anyone else only sees what the portal shows
 async fn search_articles(
     State(state): State<Arc<AppState>>,
-    Query(params): Query<ArticleQuery>,
-) -> Result<Json<Vec<SearchResult>>, StatusCode> {
-    let search_term = params.search.unwrap_or_default();
-    let limit = params.limit.unwrap_or(20);
-    
-    let results = sqlx::query!(
-        r#"
-        SELECT 
-            a.id,
-            a.title,
-            COALESCE(a.excerpt, LEFT(a.content, 200)) as excerpt,
-            c.name as category_name,
-            ts_rank(
-                to_tsvector('english', a.title || ' ' || COALESCE(a.content, '') || ' ' || COALESCE(a.excerpt, '')),
-                plainto_tsquery('english', $1)
-            ) as relevance_score
-        FROM kb_articles a
-        LEFT JOIN kb_categories c ON a.category_id = c.id
-        WHERE 
-            a.status = 'published' AND
-            to_tsvector('english', a.title || ' ' || COALESCE(a.content, '') || ' ' || COALESCE(a.excerpt, ''))
-            @@ plainto_tsquery('english', $1)
-        ORDER BY relevance_score DESC
-        LIMIT $2
-        "#,
-        search_term,
-        limit
+    auth: Option<AuthUserWithRole>,
+    Query(params): Query<KbSearchQuery>,
+    Query(pagination): Query<PaginationParams>,
+) -> Result<Json<PaginatedResponse<KbSearchResult>>, StatusCode> {
+    let scope = match auth {
+        Some(auth) if auth.can(Resource::KnowledgeBase, Action::Read) => SearchScope::Internal,
+        _ => SearchScope::Public,
+    };
+    run_search(&state, params, pagination, scope).await
+}
+
+async fn search_portal_articles(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<KbSearchQuery>,
+    Query(pagination): Query<PaginationParams>,
+) -> Result<Json<PaginatedResponse<KbSearchResult>>, StatusCode> {
+    run_search(&state, params, pagination, SearchScope::Public).await
+}
+
+async fn run_search(
+    state: &AppState,
+    params: KbSearchQuery,
+    pagination: PaginationParams,
+    scope: SearchScope,
+) -> Result<Json<PaginatedResponse<KbSearchResult>>, StatusCode> {
+    let query = params.q.trim();
+    if query.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match kb_search::search_articles(
+        &state.db_pool,
+        query,
+        scope,
+        params.category_id,
+        pagination.limit(),
+        pagination.offset(),
     )
-    .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Error searching articles: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    
-    let search_results: Vec<SearchResult> = results
-        .into_iter()
-        .map(|r| SearchResult {
-            id: r.id,
-            title: r.title,
-            excerpt: r.excerpt.unwrap_or_default(),
-            category_name: r.category_name,
-            relevance_score: r.relevance_score.unwrap_or(0.0),
-        })
-        .collect();
-    
-    Ok(Json(search_results))
+    {
+        Ok((results, total)) => Ok(Json(PaginatedResponse::new(results, &pagination, total))),
+        Err(e) => {
+            tracing::error!("Error searching articles: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Who is viewing: a signed-in user, else the signed `kb_session` cookie,
+/// bearer token or `X-Session-Id` the portal sent. Viewers with none of
+/// these aren't counted.
+async fn viewer_key(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    if let Some(token) = extract_token(headers) {
+        return Some(match verify_token(&state.db_pool, &token).await.ok().map(|claims| claims.sub) {
+            Some(user_id) => kb_search::user_viewer_key(user_id),
+            None => kb_search::session_viewer_key(&token),
+        });
+    }
+    if let Some(session_id) = signed_session(headers) {
+        return Some(kb_search::session_viewer_key(&session_id));
+    }
+    headers
+        .get("x-session-id")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.trim().is_empty())
+        .map(kb_search::session_viewer_key)
+}
+
+/// The anonymous session from a validly signed `kb_session` cookie
+fn signed_session(headers: &HeaderMap) -> Option<String> {
+    let cookies = headers.get(header::COOKIE)?.to_str().ok()?;
+    kb_votes::session_from_cookies(cookies, &kb_votes::session_secret())
+}
+
+/// Count a view for the requesting viewer. Failures are logged rather than
+/// failing the fetch.
+async fn count_view(state: &AppState, article_id: Uuid, headers: &HeaderMap) -> bool {
+    let Some(key) = viewer_key(state, headers).await else {
+        return false;
+    };
+    match kb_search::record_view(&state.db_pool, article_id, &key).await {
+        Ok(counted) => counted,
+        Err(e) => {
+            tracing::error!("Error recording view of article {}: {}", article_id, e);
+            false
+        }
+    }
 }
 
 async fn list_portal_articles(
@@ -706,4 +845,44 @@ async fn list_portal_categories(
     })?;
     
     Ok(Json(categories))
-}
\ No newline at end of file
+}
+
+/// Vote an article helpful or not. Signed-in users vote as themselves;
+/// anonymous portal visitors vote as their signed session, which is issued
+/// as a cookie on their first vote.
+async fn vote_on_article(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<ArticleVote>,
+) -> Result<(HeaderMap, Json<VoteTally>), StatusCode> {
+    let mut response_headers = HeaderMap::new();
+
+    let user_id = match extract_token(&headers) {
+        Some(token) => verify_token(&state.db_pool, &token).await.ok().map(|claims| claims.sub),
+        None => None,
+    };
+    let voter_key = match (user_id, signed_session(&headers)) {
+        (Some(user_id), _) => kb_search::user_viewer_key(user_id),
+        (None, Some(session_id)) => kb_search::session_viewer_key(&session_id),
+        (None, None) => {
+            let (session_id, cookie) = kb_votes::new_session(&kb_votes::session_secret());
+            let cookie = HeaderValue::from_str(&kb_votes::session_cookie(&cookie))
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            response_headers.insert(header::SET_COOKIE, cookie);
+            kb_search::session_viewer_key(&session_id)
+        }
+    };
+
+    let tally = kb_votes::cast_vote(&state.db_pool, id, &voter_key, payload.helpful)
+        .await
+        .map_err(|e| match e {
+            VoteError::ArticleNotFound => StatusCode::NOT_FOUND,
+            VoteError::Database(e) => {
+                tracing::error!("Error recording vote on article {}: {}", id, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+
+    Ok((response_headers, Json(tally)))
+}
@@ -402,7 +402,7 @@ async fn check_expirations(
     headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     let _token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    let _token_data = verify_token(&_token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let _token_data = verify_token(&state.db_pool, &_token).await.map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     // Call the database function to check and create expiration alerts
     let alerts_created: i32 = sqlx::query_scalar(
@@ -489,7 +489,7 @@ async fn acknowledge_alert(
     headers: HeaderMap,
 ) -> Result<StatusCode, StatusCode> {
     let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    let token_data = verify_token(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let token_data = verify_token(&state.db_pool, &token).await.map_err(|_| StatusCode::UNAUTHORIZED)?;
     let user_id = token_data.claims.sub.parse::<Uuid>().map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     sqlx::query(
@@ -1,12 +1,20 @@
-use axum::{http::StatusCode, response::Json, routing::get, Router, extract::State};
+use axum::{http::StatusCode, response::Json, routing::get, Router, extract::{Query, State}};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use serde::Serialize;
+use uuid::Uuid;
+use std::convert::Infallible;
 use std::sync::Arc;
-use chrono::{Utc, Datelike};
-use rust_decimal::Decimal;
-use crate::AppState;
+use chrono::Utc;
+use crate::auth::middleware::AuthUserWithRole;
+use crate::auth::rbac::{Action, Resource};
+use crate::services::dashboard::{self, DashboardScope, DashboardStats};
+use crate::services::dashboard_stream;
+use crate::{ApiError, ApiResult, AppState};
 
 pub mod clients;
+pub mod contacts;
 pub mod tickets;
 pub mod ticket_advanced;
 pub mod assets;
@@ -26,10 +34,22 @@ pub mod documentation;
 pub mod reporting;
 pub mod email;
 pub mod billing;
+pub mod contracts;
 pub mod analytics;
 pub mod teams;
+pub mod workflows;
+pub mod webhooks;
+pub mod audit_logs;
+pub mod encryption_keys;
+pub mod vendors;
+pub mod expenses;
+pub mod admin_jobs;
+pub mod retention_policies;
+pub mod search;
+pub mod workspace;
 
 pub use clients::client_routes;
+pub use contacts::contact_routes;
 pub use tickets::ticket_routes;
 pub use ticket_advanced::{ticket_queue_routes, canned_response_routes, ticket_link_routes, ticket_tag_routes, routing_rule_routes};
 pub use assets::asset_routes;
@@ -49,8 +69,19 @@ pub use documentation::documentation_routes;
 pub use reporting::reporting_routes;
 pub use email::email_routes;
 pub use billing::billing_routes;
+pub use contracts::contract_routes;
 pub use analytics::analytics_routes;
 pub use teams::teams_routes;
+pub use workflows::workflow_routes;
+pub use webhooks::webhook_routes;
+pub use audit_logs::audit_log_routes;
+pub use encryption_keys::encryption_key_routes;
+pub use vendors::vendor_routes;
+pub use expenses::expense_routes;
+pub use admin_jobs::admin_job_routes;
+pub use retention_policies::retention_policy_routes;
+pub use search::search_routes;
+pub use workspace::me_routes;
 
 // Add user routes function
 pub fn user_routes() -> axum::Router<std::sync::Arc<crate::AppState>> {
@@ -59,129 +90,112 @@ pub fn user_routes() -> axum::Router<std::sync::Arc<crate::AppState>> {
         .route("/", get(|| async { "Users endpoint" }))
 }
 
-#[derive(Debug, Serialize)]
-pub struct DashboardStats {
-    pub overview: OverviewStats,
-    pub tickets: TicketStats,
-    pub time: TimeStats,
-    pub invoices: InvoiceStats,
-    pub clients: ClientStats,
-    pub assets: AssetStats,
+pub async fn health_check() -> (StatusCode, Json<serde_json::Value>) {
+    (StatusCode::OK, Json(json!({"status": "healthy", "service": "resolve-api"})))
 }
 
-#[derive(Debug, Serialize)]
-pub struct OverviewStats {
-    pub total_clients: i64,
-    pub active_tickets: i64,
-    pub monthly_revenue: Decimal,
-    pub unbilled_time: Decimal,
-    pub overdue_invoices: i64,
+#[derive(Debug, Deserialize)]
+pub struct DashboardQuery {
+    pub client_id: Option<Uuid>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct TicketStats {
-    pub open: i64,
-    pub in_progress: i64,
-    pub pending: i64,
-    pub resolved_today: i64,
-    pub sla_breached: i64,
-    pub avg_response_time_hours: Option<f64>,
+/// Main dashboard figures. Financial figures need `invoices.read`; time
+/// figures cover the whole team for callers with `time_entries.approve`
+/// and only the caller's own entries otherwise.
+///
+/// Account managers see only the clients they manage, and may narrow that
+/// to one of them with `?client_id=`; any other client is 403. Admins and
+/// users who manage no clients see every client.
+pub async fn dashboard_stats(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(query): Query<DashboardQuery>,
+) -> ApiResult<Json<DashboardStats>> {
+    let scope = dashboard_scope(&state, &auth, query.client_id).await?;
+    let stats = load_dashboard(&state, scope).await?;
+    Ok(Json(stats))
 }
 
+/// A `dashboard` update on a live dashboard stream
 #[derive(Debug, Serialize)]
-pub struct TimeStats {
-    pub hours_today: Decimal,
-    pub billable_hours_today: Decimal,
-    pub hours_this_week: Decimal,
-    pub active_timers: i64,
-    pub team_utilization: Option<f64>,
+pub struct DashboardUpdate {
+    /// The bus events behind this update; empty for the first one
+    pub events: Vec<String>,
+    pub stats: DashboardStats,
 }
 
-#[derive(Debug, Serialize)]
-pub struct InvoiceStats {
-    pub outstanding_amount: Decimal,
-    pub overdue_amount: Decimal,
-    pub draft_count: i64,
-    pub paid_this_month: Decimal,
-    pub collection_ratio: Option<f64>,
-}
+/// The dashboard as server-sent events, scoped as for `GET /dashboard`.
+/// The current figures are sent straight away, then again after ticket,
+/// invoice payment and timer changes, debounced; idle streams get a
+/// heartbeat comment.
+pub async fn dashboard_stream(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(query): Query<DashboardQuery>,
+) -> ApiResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let scope = dashboard_scope(&state, &auth, query.client_id).await?;
+    // Subscribe before the first load so nothing between the two is missed
+    let refreshes = dashboard_stream::refreshes(state.ws_manager.subscribe(), dashboard_stream::DEBOUNCE);
 
-#[derive(Debug, Serialize)]
-pub struct ClientStats {
-    pub total_clients: i64,
-    pub new_this_month: i64,
-    pub top_clients_by_revenue: Vec<TopClient>,
-}
+    let updates = stream::once(async { Vec::new() })
+        .chain(refreshes)
+        .then(move |events| {
+            let state = state.clone();
+            let scope = scope.clone();
+            async move {
+                let event = match load_dashboard(&state, scope).await {
+                    Ok(stats) => Event::default()
+                        .event("dashboard")
+                        .json_data(DashboardUpdate { events, stats })
+                        .unwrap_or_else(|_| Event::default().event("error").data("Failed to load dashboard")),
+                    Err(_) => Event::default().event("error").data("Failed to load dashboard"),
+                };
+                Ok(event)
+            }
+        });
 
-#[derive(Debug, Serialize)]
-pub struct TopClient {
-    pub name: String,
-    pub revenue: Decimal,
+    Ok(Sse::new(updates).keep_alive(
+        KeepAlive::new().interval(dashboard_stream::HEARTBEAT).text("heartbeat"),
+    ))
 }
 
-#[derive(Debug, Serialize)]
-pub struct AssetStats {
-    pub total_assets: i64,
-    pub critical_alerts: i64,
-    pub warranty_expiring: i64,
-    pub online_percentage: Option<f64>,
-}
+async fn dashboard_scope(
+    state: &AppState,
+    auth: &AuthUserWithRole,
+    client_id: Option<Uuid>,
+) -> ApiResult<DashboardScope> {
+    let managed = if auth.is_admin() {
+        Vec::new()
+    } else {
+        dashboard::managed_clients(&state.db_pool, auth.user.id)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error loading managed clients: {}", e);
+                ApiError::internal("Failed to load dashboard")
+            })?
+    };
 
-pub async fn health_check() -> (StatusCode, Json<serde_json::Value>) {
-    (StatusCode::OK, Json(json!({"status": "healthy", "service": "resolve-api"})))
+    let client_ids = match client_id {
+        Some(client_id) if !managed.is_empty() && !managed.contains(&client_id) => {
+            return Err(ApiError::forbidden("You don't manage this client"));
+        }
+        Some(client_id) => Some(vec![client_id]),
+        None if managed.is_empty() => None,
+        None => Some(managed),
+    };
+
+    Ok(DashboardScope {
+        financials: auth.can(Resource::Invoices, Action::Read),
+        time_user_id: (!auth.can(Resource::TimeEntries, Action::Approve)).then_some(auth.user.id),
+        client_ids,
+    })
 }
 
-pub async fn dashboard_stats(
-    State(state): State<Arc<AppState>>,
-) -> Result<Json<DashboardStats>, StatusCode> {
-    let now = Utc::now();
-    let today = now.date_naive();
-    let week_start = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
-    let month_start = today.with_day(1).unwrap();
-    
-    // Simplified dashboard stats for compilation
-    let dashboard = DashboardStats {
-        overview: OverviewStats {
-            total_clients: 0,
-            active_tickets: 0,
-            monthly_revenue: Decimal::ZERO,
-            unbilled_time: Decimal::ZERO,
-            overdue_invoices: 0,
-        },
-        tickets: TicketStats {
-            open: 0,
-            in_progress: 0,
-            pending: 0,
-            resolved_today: 0,
-            sla_breached: 0,
-            avg_response_time_hours: None,
-        },
-        time: TimeStats {
-            hours_today: Decimal::ZERO,
-            billable_hours_today: Decimal::ZERO,
-            hours_this_week: Decimal::ZERO,
-            active_timers: 0,
-            team_utilization: None,
-        },
-        invoices: InvoiceStats {
-            outstanding_amount: Decimal::ZERO,
-            overdue_amount: Decimal::ZERO,
-            draft_count: 0,
-            paid_this_month: Decimal::ZERO,
-            collection_ratio: None,
-        },
-        clients: ClientStats {
-            total_clients: 0,
-            new_this_month: 0,
-            top_clients_by_revenue: vec![],
-        },
-        assets: AssetStats {
-            total_assets: 0,
-            critical_alerts: 0,
-            warranty_expiring: 0,
-            online_percentage: None,
-        },
-    };
-    
-    Ok(Json(dashboard))
-}
\ No newline at end of file
+async fn load_dashboard(state: &AppState, scope: DashboardScope) -> ApiResult<DashboardStats> {
+    dashboard::dashboard_stats(&state.db_pool, scope, Utc::now())
+        .await
+        .map_err(|e| {
+            tracing::error!("Error computing dashboard stats: {}", e);
+            ApiError::internal("Failed to load dashboard")
+        })
+}
@@ -10,8 +10,10 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::sync::Arc;
 use uuid::Uuid;
-use crate::AppState;
+use crate::{AppState, ApiError, ApiResult, AppError};
 use crate::auth::{extract_token, verify_token};
+use crate::auth::middleware::AuthUser;
+use crate::services::network_discovery::{self, DiscoveryConfig, DiscoveryError, ImportedDevice, ScanReport, TcpScanner};
 use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -320,7 +322,8 @@ pub fn network_topology_routes() -> Router<Arc<AppState>> {
         // Network Topology Views
         .route("/topology/:client_id", get(get_network_topology_view))
         .route("/utilization/:client_id", get(get_network_utilization))
-        .route("/discovery/:client_id/scan", post(trigger_network_discovery))
+        .route("/discovery/:network_id/scan", post(trigger_network_discovery))
+        .route("/discovery/scans/:scan_id/import", post(import_discovered_devices))
         .route("/templates", get(list_network_templates))
 }
 
@@ -357,7 +360,7 @@ async fn create_wifi_profile(
     Json(payload): Json<serde_json::Value>,
 ) -> Result<(StatusCode, Json<WifiProfile>), StatusCode> {
     let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    let token_data = verify_token(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let token_data = verify_token(&state.db_pool, &token).await.map_err(|_| StatusCode::UNAUTHORIZED)?;
     let user_id = token_data.claims.sub.parse::<Uuid>().map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     let profile = sqlx::query_as::<_, WifiProfile>(
@@ -469,6 +472,67 @@ async fn get_network_topology_view(
     }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DiscoveryScanRequest {
+    /// Ports to report as open on each live host; none skips the port probe
+    #[serde(default)]
+    pub ports: Vec<u16>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportDiscoveredRequest {
+    pub device_ids: Vec<Uuid>,
+    pub asset_type: Option<String>,
+    /// Must be `true`; assets are never created from a scan implicitly
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+fn discovery_error(e: DiscoveryError) -> AppError {
+    match e {
+        DiscoveryError::Disabled | DiscoveryError::RangeNotAllowed(_) => ApiError::forbidden(e.to_string()),
+        DiscoveryError::NetworkNotFound => ApiError::not_found("Network"),
+        DiscoveryError::ScanNotFound => ApiError::not_found("Discovery scan"),
+        DiscoveryError::InvalidRange(_) | DiscoveryError::TooManyHosts { .. } => {
+            ApiError::validation_single("ip_range", e.to_string())
+        }
+        DiscoveryError::Database(e) => e.into(),
+    }
+}
+
+/// Sweep a network for live hosts and stage what answered, marking which
+/// are already assets
+async fn trigger_network_discovery(
+    State(state): State<Arc<AppState>>,
+    Path(network_id): Path<Uuid>,
+    auth: AuthUser,
+    Json(payload): Json<DiscoveryScanRequest>,
+) -> ApiResult<Json<ScanReport>> {
+    let config = DiscoveryConfig::from_env();
+    let scanner = TcpScanner { connect_timeout: config.connect_timeout };
+    let report = network_discovery::discover(&state.db_pool, &scanner, &config, network_id, &payload.ports, auth.0.id)
+        .await
+        .map_err(discovery_error)?;
+    Ok(Json(report))
+}
+
+/// Turn staged hosts from a scan into assets, once the caller confirms
+async fn import_discovered_devices(
+    State(state): State<Arc<AppState>>,
+    Path(scan_id): Path<Uuid>,
+    auth: AuthUser,
+    Json(payload): Json<ImportDiscoveredRequest>,
+) -> ApiResult<(StatusCode, Json<Vec<ImportedDevice>>)> {
+    if !payload.confirm {
+        return Err(ApiError::validation_single("confirm", "must be true to create assets from discovered hosts"));
+    }
+    let asset_type = payload.asset_type.as_deref().unwrap_or(network_discovery::DEFAULT_ASSET_TYPE);
+    let imported = network_discovery::import_devices(&state.db_pool, scan_id, &payload.device_ids, asset_type, auth.0.id)
+        .await
+        .map_err(discovery_error)?;
+    Ok((StatusCode::CREATED, Json(imported)))
+}
+
 // Placeholder implementations for other handlers
 async fn get_wifi_profile(State(_): State<Arc<AppState>>, Path(_): Path<Uuid>) -> Result<Json<WifiProfile>, StatusCode> { Err(StatusCode::NOT_IMPLEMENTED) }
 async fn update_wifi_profile(State(_): State<Arc<AppState>>, Path(_): Path<Uuid>, Json(_): Json<serde_json::Value>) -> Result<Json<WifiProfile>, StatusCode> { Err(StatusCode::NOT_IMPLEMENTED) }
@@ -505,5 +569,4 @@ async fn update_network_cable(State(_): State<Arc<AppState>>, Path(_): Path<Uuid
 async fn delete_network_cable(State(_): State<Arc<AppState>>, Path(_): Path<Uuid>) -> Result<StatusCode, StatusCode> { Err(StatusCode::NOT_IMPLEMENTED) }
 async fn test_network_cable(State(_): State<Arc<AppState>>, Path(_): Path<Uuid>) -> Result<Json<serde_json::Value>, StatusCode> { Ok(Json(serde_json::json!({}))) }
 async fn get_network_utilization(State(_): State<Arc<AppState>>, Path(_): Path<Uuid>) -> Result<Json<NetworkUtilizationSummary>, StatusCode> { Ok(Json(NetworkUtilizationSummary { total_subnets: 0, total_addresses: 0, used_addresses: 0, available_addresses: 0, avg_utilization: rust_decimal::Decimal::ZERO, critical_subnets: 0 })) }
-async fn trigger_network_discovery(State(_): State<Arc<AppState>>, Path(_): Path<Uuid>) -> Result<StatusCode, StatusCode> { Err(StatusCode::NOT_IMPLEMENTED) }
 async fn list_network_templates(State(_): State<Arc<AppState>>) -> Result<Json<Vec<serde_json::Value>>, StatusCode> { Ok(Json(vec![])) }
\ No newline at end of file
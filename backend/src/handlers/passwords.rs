@@ -1,5 +1,12 @@
+use crate::audit::RequestMeta;
 use crate::auth::jwt::Claims;
+use crate::auth::middleware::AuthUserWithRole;
+use crate::auth::rbac::{Action, Resource};
 use crate::models::passwords::*;
+use crate::pagination::{PaginatedResponse, PaginationParams};
+use crate::services::audit_log_query::{self, AuditLogFilters, AuditLogRecord};
+use crate::services::credential_grants::{self, CredentialGrant, GrantError};
+use crate::services::password_health::{self, PasswordAssessment, WeakCredential};
 use crate::services::{PasswordManagerService, EncryptionService};
 use crate::AppState;
 use axum::{
@@ -21,8 +28,13 @@ pub fn password_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(list_passwords).post(create_password))
         .route("/generate", post(generate_password))
-        .route("/:id", get(get_password).delete(delete_password))
+        .route("/health", get(password_health_report))
+        .route("/:id", get(get_password).put(update_password).delete(delete_password))
         .route("/:id/favorite", put(update_password_favorite))
+        .route("/:id/grant", post(create_grant))
+        .route("/:id/grants", get(list_grants))
+        .route("/:id/grants/:grant_id", delete(revoke_grant))
+        .route("/:id/access-log", get(password_access_log))
         .route("/folders", post(create_folder))
         .route("/shares", get(list_password_shares).post(create_password_share))
         .route("/shares/:id/deactivate", put(deactivate_password_share))
@@ -63,12 +75,36 @@ pub struct PasswordQuery {
     pub favorite: Option<bool>,
 }
 
+/// Returned from create and update so the caller sees the strength and
+/// breach status of what was just saved
+#[derive(Debug, Serialize)]
+pub struct PasswordSaved {
+    pub id: Uuid,
+    pub assessment: Option<PasswordAssessment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordHealthQuery {
+    pub client_id: Option<Uuid>,
+}
+
+async fn assess_password(password: &str, name: Option<&str>, username: Option<&str>) -> PasswordAssessment {
+    let user_inputs: Vec<&str> = name.into_iter().chain(username).collect();
+    password_health::assess(
+        &reqwest::Client::new(),
+        password,
+        &user_inputs,
+        password_health::breach_check_enabled(),
+    )
+    .await
+}
+
 pub async fn create_password(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<Claims>,
     Json(request): Json<CreatePasswordRequest>,
-) -> Result<Json<ApiResponse<Uuid>>, StatusCode> {
-    let pool = &state.db;
+) -> Result<Json<ApiResponse<PasswordSaved>>, StatusCode> {
+    let pool = &state.db_pool;
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -80,10 +116,12 @@ pub async fn create_password(
 
     let password_manager = PasswordManagerService::new(pool.clone(), encryption_service);
 
-    match password_manager.create_password(request, claims.sub).await {
+    let assessment = assess_password(&request.password, Some(&request.name), request.username.as_deref()).await;
+
+    match password_manager.create_password(request, claims.sub, &assessment).await {
         Ok(password_id) => {
             info!("Password created successfully: {}", password_id);
-            Ok(Json(ApiResponse::success(password_id)))
+            Ok(Json(ApiResponse::success(PasswordSaved { id: password_id, assessment: Some(assessment) })))
         }
         Err(e) => {
             error!("Failed to create password: {}", e);
@@ -92,12 +130,79 @@ pub async fn create_password(
     }
 }
 
-pub async fn get_password(
+pub async fn update_password(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<Claims>,
     Path(password_id): Path<Uuid>,
+    Json(request): Json<UpdatePasswordRequest>,
+) -> Result<Json<ApiResponse<PasswordSaved>>, StatusCode> {
+    let pool = &state.db_pool;
+    
+    let encryption_service = match EncryptionService::new() {
+        Ok(service) => service,
+        Err(e) => {
+            error!("Failed to initialize encryption service: {}", e);
+            return Ok(Json(ApiResponse::error("Internal server error")));
+        }
+    };
+
+    let password_manager = PasswordManagerService::new(pool.clone(), encryption_service);
+
+    let assessment = match &request.password {
+        Some(password) => Some(assess_password(password, request.name.as_deref(), request.username.as_deref()).await),
+        None => None,
+    };
+
+    match password_manager.update_password(password_id, request, claims.sub, assessment.as_ref()).await {
+        Ok(true) => {
+            info!("Password updated successfully: {}", password_id);
+            Ok(Json(ApiResponse::success(PasswordSaved { id: password_id, assessment })))
+        }
+        Ok(false) => Ok(Json(ApiResponse::error("Password not found or access denied"))),
+        Err(e) => {
+            error!("Failed to update password {}: {}", password_id, e);
+            Ok(Json(ApiResponse::error("Failed to update password")))
+        }
+    }
+}
+
+/// Weak and breached passwords, optionally for one client. Without
+/// `passwords.read` only the caller's own and granted passwords are listed.
+pub async fn password_health_report(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(params): Query<PasswordHealthQuery>,
+) -> Result<Json<ApiResponse<Vec<WeakCredential>>>, StatusCode> {
+    let viewer = (!auth.can(Resource::Passwords, Action::Read)).then_some(auth.user.id);
+    match password_health::weak_credentials(&state.db_pool, params.client_id, viewer).await {
+        Ok(credentials) => Ok(Json(ApiResponse::success(credentials))),
+        Err(e) => {
+            error!("Failed to build password health report: {}", e);
+            Ok(Json(ApiResponse::error("Failed to retrieve password health")))
+        }
+    }
+}
+
+/// Readable by the creator, roles with `passwords.read`, and holders of
+/// an unexpired grant. Every attempt is audited.
+pub async fn get_password(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Path(password_id): Path<Uuid>,
 ) -> Result<Json<ApiResponse<PasswordResponse>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
+
+    let has_permission = auth.can(Resource::Passwords, Action::Read);
+    match credential_grants::authorize_read(pool, &meta, password_id, auth.user.id, has_permission).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::FORBIDDEN),
+        Err(GrantError::PasswordNotFound) => return Ok(Json(ApiResponse::error("Password not found"))),
+        Err(e) => {
+            error!("Failed to check access to password {}: {}", password_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -109,7 +214,7 @@ pub async fn get_password(
 
     let password_manager = PasswordManagerService::new(pool.clone(), encryption_service);
 
-    match password_manager.get_password(password_id, claims.sub).await {
+    match password_manager.get_password(password_id, auth.user.id).await {
         Ok(Some(password)) => Ok(Json(ApiResponse::success(password))),
         Ok(None) => Ok(Json(ApiResponse::error("Password not found"))),
         Err(e) => {
@@ -119,12 +224,146 @@ pub async fn get_password(
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateGrantRequest {
+    #[serde(alias = "user_id")]
+    pub grantee_id: Uuid,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GrantView {
+    #[serde(flatten)]
+    pub grant: CredentialGrant,
+    pub active: bool,
+}
+
+/// Owners and roles allowed to assign passwords manage grants and read the
+/// access log
+async fn can_manage_access(pool: &PgPool, auth: &AuthUserWithRole, password_id: Uuid) -> Result<(), StatusCode> {
+    let owner = credential_grants::password_owner(pool, password_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch password {}: {}", password_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if owner == auth.user.id || auth.can(Resource::Passwords, Action::Assign) {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+pub async fn create_grant(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Path(password_id): Path<Uuid>,
+    Json(request): Json<CreateGrantRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<CredentialGrant>>), StatusCode> {
+    let pool = &state.db_pool;
+    can_manage_access(pool, &auth, password_id).await?;
+
+    match credential_grants::create_grant(
+        pool,
+        &meta,
+        password_id,
+        request.grantee_id,
+        auth.user.id,
+        request.expires_at,
+        request.reason.as_deref(),
+    )
+    .await
+    {
+        Ok(grant) => {
+            info!("Password {} granted to {} until {}", password_id, grant.grantee_id, grant.expires_at);
+            Ok((StatusCode::CREATED, Json(ApiResponse::success(grant))))
+        }
+        Err(GrantError::Database(e)) => {
+            error!("Failed to create grant for password {}: {}", password_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        Err(e) => Ok((StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e.to_string())))),
+    }
+}
+
+pub async fn list_grants(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(password_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<Vec<GrantView>>>, StatusCode> {
+    let pool = &state.db_pool;
+    can_manage_access(pool, &auth, password_id).await?;
+
+    match credential_grants::list_grants(pool, password_id).await {
+        Ok(grants) => {
+            let now = chrono::Utc::now();
+            let grants = grants
+                .into_iter()
+                .map(|grant| GrantView { active: grant.is_active(now), grant })
+                .collect();
+            Ok(Json(ApiResponse::success(grants)))
+        }
+        Err(e) => {
+            error!("Failed to list grants for password {}: {}", password_id, e);
+            Ok(Json(ApiResponse::error("Failed to retrieve grants")))
+        }
+    }
+}
+
+pub async fn revoke_grant(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Path((password_id, grant_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let pool = &state.db_pool;
+    can_manage_access(pool, &auth, password_id).await?;
+
+    match credential_grants::revoke_grant(pool, &meta, password_id, grant_id, auth.user.id).await {
+        Ok(true) => Ok(Json(ApiResponse::success(()))),
+        Ok(false) => Ok(Json(ApiResponse::error("Grant not found or already revoked"))),
+        Err(e) => {
+            error!("Failed to revoke grant {}: {}", grant_id, e);
+            Ok(Json(ApiResponse::error("Failed to revoke grant")))
+        }
+    }
+}
+
+/// Reads, refused reads and grant changes for one password, newest first
+pub async fn password_access_log(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(password_id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> Result<Json<ApiResponse<PaginatedResponse<AuditLogRecord>>>, StatusCode> {
+    let pool = &state.db_pool;
+    can_manage_access(pool, &auth, password_id).await?;
+
+    let filters = AuditLogFilters {
+        entity_type: Some(credential_grants::ENTITY_TYPE.to_string()),
+        entity_id: Some(password_id),
+        ..Default::default()
+    };
+
+    match audit_log_query::search(pool, &filters, &params).await {
+        Ok(page) => Ok(Json(ApiResponse::success(page))),
+        Err(e) => {
+            error!("Failed to fetch access log for password {}: {}", password_id, e);
+            Ok(Json(ApiResponse::error("Failed to retrieve access log")))
+        }
+    }
+}
+
 pub async fn list_passwords(
     State(state): State<Arc<AppState>>,
     Extension(_claims): Extension<Claims>,
     Query(params): Query<PasswordQuery>,
 ) -> Result<Json<ApiResponse<PasswordListResponse>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -150,7 +389,7 @@ pub async fn generate_password(
     Extension(_claims): Extension<Claims>,
     Json(request): Json<GeneratePasswordRequest>,
 ) -> Result<Json<ApiResponse<GeneratePasswordResponse>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -173,10 +412,11 @@ pub async fn generate_password(
 
 pub async fn create_password_share(
     State(state): State<Arc<AppState>>,
-    Extension(claims): Extension<Claims>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
     Json(request): Json<CreatePasswordShareRequest>,
 ) -> Result<Json<ApiResponse<PasswordShareResponse>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -189,11 +429,13 @@ pub async fn create_password_share(
     let password_manager = PasswordManagerService::new(pool.clone(), encryption_service);
     let base_url = std::env::var("APP_BASE_URL").unwrap_or_else(|_| "https://resolve.local".to_string());
 
-    match password_manager.create_password_share(request, claims.sub, &base_url).await {
-        Ok(share) => {
+    let has_permission = auth.can(Resource::Passwords, Action::Read);
+    match password_manager.create_password_share(request, auth.user.id, has_permission, &meta, &base_url).await {
+        Ok(Some(share)) => {
             info!("Password share created: {}", share.id);
             Ok(Json(ApiResponse::success(share)))
         }
+        Ok(None) => Err(StatusCode::FORBIDDEN),
         Err(e) => {
             error!("Failed to create password share: {}", e);
             Ok(Json(ApiResponse::error("Failed to create password share")))
@@ -203,9 +445,10 @@ pub async fn create_password_share(
 
 pub async fn access_shared_password(
     State(state): State<Arc<AppState>>,
+    meta: RequestMeta,
     Json(request): Json<AccessPasswordShareRequest>,
 ) -> Result<Json<ApiResponse<PasswordShareAccessResponse>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -217,7 +460,7 @@ pub async fn access_shared_password(
 
     let password_manager = PasswordManagerService::new(pool.clone(), encryption_service);
 
-    match password_manager.access_shared_password(request).await {
+    match password_manager.access_shared_password(request, &meta).await {
         Ok(Some(response)) => Ok(Json(ApiResponse::success(response))),
         Ok(None) => Ok(Json(ApiResponse::error("Share not found or expired"))),
         Err(e) => {
@@ -232,7 +475,7 @@ pub async fn list_password_shares(
     Extension(claims): Extension<Claims>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<ApiResponse<Vec<PasswordShareResponse>>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -263,7 +506,7 @@ pub async fn create_folder(
     Extension(claims): Extension<Claims>,
     Json(request): Json<CreateFolderRequest>,
 ) -> Result<Json<ApiResponse<Uuid>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     let encryption_service = match EncryptionService::new() {
         Ok(service) => service,
@@ -292,7 +535,7 @@ pub async fn delete_password(
     Extension(claims): Extension<Claims>,
     Path(password_id): Path<Uuid>,
 ) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     match sqlx::query!(
         "DELETE FROM passwords WHERE id = $1 AND created_by = $2",
@@ -323,7 +566,7 @@ pub async fn update_password_favorite(
     Path(password_id): Path<Uuid>,
     Json(favorite): Json<serde_json::Value>,
 ) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     let favorite = favorite.get("favorite")
         .and_then(|v| v.as_bool())
@@ -357,7 +600,7 @@ pub async fn deactivate_password_share(
     Extension(claims): Extension<Claims>,
     Path(share_id): Path<Uuid>,
 ) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    let pool = &state.db;
+    let pool = &state.db_pool;
     
     match sqlx::query!(
         "UPDATE password_shares SET is_active = false WHERE id = $1 AND created_by = $2",
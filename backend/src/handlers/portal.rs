@@ -12,6 +12,9 @@ use std::sync::Arc;
 use uuid::Uuid;
 use bcrypt::{hash, verify, DEFAULT_COST};
 use crate::AppState;
+use crate::services::portal_tickets::{
+    self, NewPortalTicket, PortalReply, PortalTicketDetail, PortalTicketError, PortalTicketSummary,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PortalLoginRequest {
@@ -398,7 +401,7 @@ async fn get_portal_dashboard(
     // Get recent tickets
     let recent_tickets = sqlx::query_as::<_, PortalTicket>(
         "SELECT id, number, subject, details, status, priority, created_at, updated_at, 
-         (SELECT MAX(created_at) FROM ticket_replies WHERE ticket_id = tickets.id) as last_reply_at
+         (SELECT MAX(created_at) FROM ticket_replies WHERE ticket_id = tickets.id AND NOT internal) as last_reply_at
          FROM tickets 
          WHERE client_id = $1 
          ORDER BY created_at DESC 
@@ -465,25 +468,16 @@ async fn get_portal_dashboard(
 async fn list_portal_tickets(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
-) -> Result<Json<Vec<PortalTicket>>, StatusCode> {
+) -> Result<Json<Vec<PortalTicketSummary>>, StatusCode> {
     let token = extract_portal_token(&headers)?;
     let (contact_id, client_id) = verify_token(&state, &token).await?;
     
-    let tickets = sqlx::query_as::<_, PortalTicket>(
-        "SELECT id, number, subject, details, status, priority, created_at, updated_at,
-         (SELECT MAX(created_at) FROM ticket_replies WHERE ticket_id = tickets.id) as last_reply_at
-         FROM tickets 
-         WHERE client_id = $1 AND (contact_id = $2 OR contact_id IS NULL)
-         ORDER BY created_at DESC"
-    )
-    .bind(client_id)
-    .bind(contact_id)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error fetching tickets: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let tickets = portal_tickets::list_tickets(&state.db_pool, client_id, contact_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching tickets: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
     
     Ok(Json(tickets))
 }
@@ -492,56 +486,31 @@ async fn create_portal_ticket(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Json(payload): Json<CreatePortalTicket>,
-) -> Result<(StatusCode, Json<PortalTicket>), StatusCode> {
+) -> Result<(StatusCode, Json<PortalTicketSummary>), StatusCode> {
     let token = extract_portal_token(&headers)?;
     let (contact_id, client_id) = verify_token(&state, &token).await?;
     
-    let ticket_id = Uuid::new_v4();
-    let ticket_number = sqlx::query_scalar!(
-        "SELECT COALESCE(MAX(number), 0) + 1 FROM tickets WHERE client_id = $1",
-        client_id
-    )
-    .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error getting ticket number: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?
-    .unwrap_or(1);
-    
-    let priority = payload.priority.unwrap_or_else(|| "medium".to_string());
-    let now = Utc::now();
-    
-    let ticket = sqlx::query_as::<_, PortalTicket>(
-        "INSERT INTO tickets (
-            id, client_id, contact_id, asset_id, number, subject, details,
-            status, priority, source, opened_by, created_at
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, 'open', $8, 'portal', $3, $9)
-        RETURNING id, number, subject, details, status, priority, created_at, updated_at, NULL as last_reply_at"
+    let ticket = portal_tickets::create_ticket(
+        &state.db_pool,
+        client_id,
+        contact_id,
+        NewPortalTicket {
+            subject: payload.subject,
+            details: payload.details,
+            priority: payload.priority,
+            asset_id: payload.asset_id,
+        },
     )
-    .bind(ticket_id)
-    .bind(client_id)
-    .bind(contact_id)
-    .bind(payload.asset_id)
-    .bind(ticket_number)
-    .bind(payload.subject)
-    .bind(payload.details)
-    .bind(priority)
-    .bind(now)
-    .fetch_one(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Error creating ticket: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(portal_ticket_error)?;
     
     // Send notification to support team
     state.broadcast_notification(
         "portal_ticket_created",
         serde_json::json!({
-            "ticket_id": ticket_id,
-            "ticket_number": ticket_number,
-            "subject": payload.subject,
+            "ticket_id": ticket.id,
+            "ticket_number": ticket.number,
+            "subject": ticket.subject,
             "client_id": client_id,
             "contact_id": contact_id
         })
@@ -554,27 +523,13 @@ async fn get_portal_ticket(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<Uuid>,
-) -> Result<Json<PortalTicket>, StatusCode> {
+) -> Result<Json<PortalTicketDetail>, StatusCode> {
     let token = extract_portal_token(&headers)?;
-    let (_contact_id, client_id) = verify_token(&state, &token).await?;
+    let (contact_id, client_id) = verify_token(&state, &token).await?;
     
-    let ticket = sqlx::query_as::<_, PortalTicket>(
-        "SELECT id, number, subject, details, status, priority, created_at, updated_at,
-         (SELECT MAX(created_at) FROM ticket_replies WHERE ticket_id = tickets.id) as last_reply_at
-         FROM tickets 
-         WHERE id = $1 AND client_id = $2"
-    )
-    .bind(id)
-    .bind(client_id)
-    .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| match e {
-        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-        _ => {
-            tracing::error!("Error fetching ticket: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        }
-    })?;
+    let ticket = portal_tickets::ticket_detail(&state.db_pool, client_id, contact_id, id)
+        .await
+        .map_err(portal_ticket_error)?;
     
     Ok(Json(ticket))
 }
@@ -583,55 +538,15 @@ async fn get_ticket_replies(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<Uuid>,
-) -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
+) -> Result<Json<Vec<PortalReply>>, StatusCode> {
     let token = extract_portal_token(&headers)?;
-    let (_contact_id, client_id) = verify_token(&state, &token).await?;
-    
-    // Verify ticket belongs to client
-    let _ticket = sqlx::query!(
-        "SELECT id FROM tickets WHERE id = $1 AND client_id = $2",
-        id,
-        client_id
-    )
-    .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| match e {
-        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-        _ => {
-            tracing::error!("Error verifying ticket: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        }
-    })?;
-    
-    let replies = sqlx::query!(
-        "SELECT tr.id, tr.details as message, tr.created_at,
-         (u.first_name || ' ' || u.last_name) as author_name,
-         'support' as author_type
-         FROM ticket_replies tr
-         LEFT JOIN users u ON tr.user_id = u.id
-         WHERE tr.ticket_id = $1
-         ORDER BY tr.created_at ASC",
-        id
-    )
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error fetching replies: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let (contact_id, client_id) = verify_token(&state, &token).await?;
     
-    let result: Vec<serde_json::Value> = replies
-        .into_iter()
-        .map(|r| serde_json::json!({
-            "id": r.id,
-            "message": r.message,
-            "created_at": r.created_at,
-            "author_name": r.author_name,
-            "author_type": r.author_type
-        }))
-        .collect();
+    let ticket = portal_tickets::ticket_detail(&state.db_pool, client_id, contact_id, id)
+        .await
+        .map_err(portal_ticket_error)?;
     
-    Ok(Json(result))
+    Ok(Json(ticket.replies))
 }
 
 async fn add_ticket_reply(
@@ -643,52 +558,13 @@ async fn add_ticket_reply(
     let token = extract_portal_token(&headers)?;
     let (contact_id, client_id) = verify_token(&state, &token).await?;
     
-    // Verify ticket belongs to client
-    let _ticket = sqlx::query!(
-        "SELECT id FROM tickets WHERE id = $1 AND client_id = $2",
-        id,
-        client_id
-    )
-    .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| match e {
-        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-        _ => {
-            tracing::error!("Error verifying ticket: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        }
-    })?;
-    
     let message = payload.get("message")
         .and_then(|v| v.as_str())
         .ok_or(StatusCode::BAD_REQUEST)?;
     
-    let reply_id = Uuid::new_v4();
-    
-    sqlx::query(
-        "INSERT INTO ticket_replies (id, ticket_id, contact_id, message, internal_note)
-         VALUES ($1, $2, $3, $4, false)"
-    )
-    .bind(reply_id)
-    .bind(id)
-    .bind(contact_id)
-    .bind(message)
-    .execute(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error creating reply: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    
-    // Update ticket status if closed
-    sqlx::query(
-        "UPDATE tickets SET status = 'open', updated_at = NOW() 
-         WHERE id = $1 AND status = 'closed'"
-    )
-    .bind(id)
-    .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let reply_id = portal_tickets::add_reply(&state.db_pool, client_id, contact_id, id, message)
+        .await
+        .map_err(portal_ticket_error)?;
     
     // Send notification
     state.broadcast_notification(
@@ -703,6 +579,17 @@ async fn add_ticket_reply(
     Ok(StatusCode::CREATED)
 }
 
+fn portal_ticket_error(e: PortalTicketError) -> StatusCode {
+    match e {
+        PortalTicketError::NotFound => StatusCode::NOT_FOUND,
+        PortalTicketError::Validation(_) => StatusCode::BAD_REQUEST,
+        PortalTicketError::Database(e) => {
+            tracing::error!("Error handling portal ticket: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
 async fn list_portal_invoices(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -996,7 +883,7 @@ async fn list_guest_tickets(
     
     let tickets = sqlx::query_as::<_, PortalTicket>(
         "SELECT id, number, subject, details, status, priority, created_at, updated_at,
-         (SELECT MAX(created_at) FROM ticket_replies WHERE ticket_id = tickets.id) as last_reply_at
+         (SELECT MAX(created_at) FROM ticket_replies WHERE ticket_id = tickets.id AND NOT internal) as last_reply_at
          FROM tickets 
          WHERE client_id = $1
          ORDER BY created_at DESC"
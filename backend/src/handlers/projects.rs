@@ -10,7 +10,9 @@ use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc, NaiveDate};
 use rust_decimal::Decimal;
-use crate::AppState;
+use crate::{AppState, ApiError, ApiResult, AppError};
+use crate::services::project_budget::{self, ProjectBudget};
+use crate::services::task_dependencies::{self, DependencyError, DependencyStatus, ScheduledTask};
 
 #[derive(Serialize, Deserialize)]
 pub struct ProjectCreate {
@@ -123,7 +125,11 @@ pub fn project_routes() -> Router<Arc<AppState>> {
         .route("/:id/tasks", get(get_project_tasks).post(create_task))
         .route("/:id/time-entries", get(get_project_time_entries))
         .route("/:id/stats", get(get_project_stats))
+        .route("/:id/schedule", get(get_project_schedule))
+        .route("/:id/budget", get(get_project_budget))
         .route("/tasks/:task_id", get(get_task).put(update_task).delete(delete_task))
+        .route("/tasks/:task_id/dependencies", get(get_task_dependencies).post(add_task_dependency))
+        .route("/tasks/:task_id/dependencies/:depends_on_id", delete(remove_task_dependency))
 }
 
 async fn list_projects(
@@ -509,34 +515,100 @@ async fn delete_task(
     }
 }
 
+#[derive(Deserialize)]
+pub struct TaskDependencyCreate {
+    pub depends_on_task_id: Uuid,
+}
+
+fn dependency_error(e: DependencyError) -> AppError {
+    match e {
+        DependencyError::NotFound => ApiError::not_found("Task"),
+        DependencyError::SelfDependency => ApiError::validation_single("depends_on_task_id", "A task can't depend on itself"),
+        DependencyError::DifferentProject => {
+            ApiError::validation_single("depends_on_task_id", "Tasks must belong to the same project")
+        }
+        DependencyError::Cycle => ApiError::conflict("Dependency would create a cycle"),
+        DependencyError::Database(e) => {
+            tracing::error!("Error updating task dependencies: {}", e);
+            ApiError::internal("Failed to update task dependencies")
+        }
+    }
+}
+
+/// A task's prerequisites and whether it is blocked on them
+async fn get_task_dependencies(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+) -> ApiResult<Json<DependencyStatus>> {
+    let status = task_dependencies::status(&state.db_pool, task_id).await.map_err(dependency_error)?;
+    Ok(Json(status))
+}
+
+/// Make a task wait on another task in the same project
+async fn add_task_dependency(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<Uuid>,
+    Json(payload): Json<TaskDependencyCreate>,
+) -> ApiResult<(StatusCode, Json<DependencyStatus>)> {
+    task_dependencies::add(&state.db_pool, task_id, payload.depends_on_task_id)
+        .await
+        .map_err(dependency_error)?;
+    let status = task_dependencies::status(&state.db_pool, task_id).await.map_err(dependency_error)?;
+    Ok((StatusCode::CREATED, Json(status)))
+}
+
+async fn remove_task_dependency(
+    State(state): State<Arc<AppState>>,
+    Path((task_id, depends_on_id)): Path<(Uuid, Uuid)>,
+) -> ApiResult<StatusCode> {
+    let removed = task_dependencies::remove(&state.db_pool, task_id, depends_on_id)
+        .await
+        .map_err(|e| dependency_error(e.into()))?;
+    if !removed {
+        return Err(ApiError::not_found("Task dependency"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The project's tasks in dependency order, with earliest start and
+/// critical path worked out from estimated hours
+async fn get_project_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Vec<ScheduledTask>>> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM projects WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err(|e| dependency_error(e.into()))?;
+    if !exists {
+        return Err(ApiError::not_found("Project"));
+    }
+
+    let schedule = task_dependencies::project_schedule(&state.db_pool, id).await.map_err(dependency_error)?;
+    Ok(Json(schedule))
+}
+
+async fn get_project_budget(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ProjectBudget>> {
+    let budget = project_budget::project_budget(&state.db_pool, id, Utc::now().date_naive())
+        .await?
+        .ok_or_else(|| ApiError::not_found("Project"))?;
+    Ok(Json(budget))
+}
+
 async fn get_project_time_entries(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<Vec<crate::handlers::time_tracking::TimeEntryWithDetails>>, StatusCode> {
     // Reuse the time tracking handler logic
-    match sqlx::query_as!(
-        crate::handlers::time_tracking::TimeEntryWithDetails,
-        "SELECT 
-            te.id, te.user_id, u.first_name || ' ' || u.last_name as user_name,
-            te.ticket_id, t.number as ticket_number, t.subject as ticket_subject,
-            te.project_id, p.name as project_name,
-            te.task_id, tk.name as task_name,
-            COALESCE(t.client_id, p.client_id) as client_id,
-            c.name as client_name,
-            te.start_time, te.end_time, te.duration_minutes,
-            te.description, te.billable, te.billed,
-            te.hourly_rate, te.total_amount,
-            te.created_at, te.updated_at
-         FROM time_entries te
-         LEFT JOIN users u ON te.user_id = u.id
-         LEFT JOIN tickets t ON te.ticket_id = t.id
-         LEFT JOIN projects p ON te.project_id = p.id
-         LEFT JOIN tasks tk ON te.task_id = tk.id
-         LEFT JOIN clients c ON COALESCE(t.client_id, p.client_id) = c.id
-         WHERE te.project_id = $1
-         ORDER BY te.start_time DESC",
-        id
-    )
+    match sqlx::query_as::<_, crate::handlers::time_tracking::TimeEntryWithDetails>(&format!(
+        "{} WHERE te.project_id = $1 ORDER BY te.start_time DESC",
+        crate::handlers::time_tracking::TIME_ENTRY_DETAILS_QUERY
+    ))
+    .bind(id)
     .fetch_all(&state.db_pool)
     .await
     {
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
@@ -10,8 +10,15 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::sync::Arc;
 use uuid::Uuid;
-use crate::AppState;
-use crate::auth::middleware::AuthUser;
+use crate::{AppState, ApiError, ApiResult, Validator};
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::auth::rbac::{Action, Resource};
+use crate::services::scheduled_reports::{
+    self, Cadence, ScheduledReport, ScheduledReportRun, CADENCES, FORMATS, REPORT_TYPES,
+    RUN_COLUMNS, SCHEDULED_REPORT_COLUMNS,
+};
+use crate::validation::email;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Report {
@@ -121,6 +128,13 @@ pub fn reporting_routes() -> Router<Arc<AppState>> {
         .route("/client-health/:client_id", get(get_client_health_score))
         .route("/dashboard/stats", get(get_dashboard_stats))
         .route("/dashboard/widgets", get(get_dashboard_widgets))
+        .route("/scheduled-reports", get(list_scheduled_reports).post(create_scheduled_report))
+        .route(
+            "/scheduled-reports/:id",
+            get(get_scheduled_report).put(update_scheduled_report).delete(delete_scheduled_report),
+        )
+        .route("/scheduled-reports/:id/runs", get(list_scheduled_report_runs))
+        .route("/scheduled-reports/runs/:run_id/download", get(download_scheduled_report_run))
 }
 
 async fn list_reports(
@@ -480,4 +494,305 @@ async fn get_dashboard_widgets(
     ];
 
     Ok(Json(widgets))
-}
\ No newline at end of file
+}
+
+// ==================== Scheduled Reports ====================
+
+/// Body for creating a scheduled report; on update every field is optional
+/// and omitted fields keep their current value
+#[derive(Debug, Default, Deserialize)]
+pub struct ScheduledReportRequest {
+    pub name: Option<String>,
+    pub report_type: Option<String>,
+    pub client_id: Option<Uuid>,
+    pub parameters: Option<serde_json::Value>,
+    pub cadence: Option<String>,
+    pub recipients: Option<Vec<String>>,
+    pub format: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+/// A scheduled report's settings once a request has been applied
+#[derive(Debug)]
+struct ScheduledReportFields {
+    name: String,
+    report_type: String,
+    client_id: Option<Uuid>,
+    parameters: serde_json::Value,
+    cadence: String,
+    recipients: Vec<String>,
+    format: String,
+    is_active: bool,
+}
+
+impl ScheduledReportFields {
+    fn apply(existing: Option<&ScheduledReport>, payload: ScheduledReportRequest) -> Self {
+        let lower = |value: Option<String>, current: Option<&String>, default: &str| {
+            value
+                .map(|v| v.trim().to_lowercase())
+                .or_else(|| current.cloned())
+                .unwrap_or_else(|| default.to_string())
+        };
+        Self {
+            name: payload
+                .name
+                .map(|n| n.trim().to_string())
+                .or_else(|| existing.map(|r| r.name.clone()))
+                .unwrap_or_default(),
+            report_type: lower(payload.report_type, existing.map(|r| &r.report_type), ""),
+            client_id: payload.client_id.or_else(|| existing.and_then(|r| r.client_id)),
+            parameters: payload
+                .parameters
+                .or_else(|| existing.map(|r| r.parameters.clone()))
+                .unwrap_or_else(|| serde_json::json!({})),
+            cadence: lower(payload.cadence, existing.map(|r| &r.cadence), ""),
+            recipients: payload
+                .recipients
+                .map(|list| list.iter().map(|r| r.trim().to_lowercase()).collect())
+                .or_else(|| existing.map(|r| r.recipients.clone()))
+                .unwrap_or_default(),
+            format: lower(payload.format, existing.map(|r| &r.format), "pdf"),
+            is_active: payload.is_active.or_else(|| existing.map(|r| r.is_active)).unwrap_or(true),
+        }
+    }
+
+    async fn validate(&self, pool: &sqlx::PgPool) -> ApiResult<()> {
+        let mut validator = Validator::new()
+            .not_blank(&self.name, "name")
+            .length(Some(self.name.as_str()), "name", 1, 255)
+            .not_blank(&self.report_type, "report_type")
+            .one_of(Some(&self.report_type).filter(|t| !t.is_empty()).map(String::as_str), "report_type", REPORT_TYPES)
+            .not_blank(&self.cadence, "cadence")
+            .one_of(Some(&self.cadence).filter(|c| !c.is_empty()).map(String::as_str), "cadence", CADENCES)
+            .one_of(Some(self.format.as_str()), "format", FORMATS)
+            .error_if(!self.parameters.is_object(), "parameters", "parameters must be an object")
+            .error_if(
+                self.report_type == "client_summary" && self.client_id.is_none(),
+                "client_id",
+                "client_id is required for client summaries",
+            )
+            .error_if(
+                self.recipients.is_empty() && self.client_id.is_none(),
+                "recipients",
+                "At least one recipient is required",
+            );
+        for recipient in &self.recipients {
+            validator = validator.check(email::validate(recipient, "recipients"));
+        }
+        validator
+            .exists(pool, "clients", self.client_id, "client_id")
+            .await?
+            .finish()
+    }
+}
+
+async fn load_scheduled_report(pool: &sqlx::PgPool, id: Uuid) -> ApiResult<ScheduledReport> {
+    sqlx::query_as::<_, ScheduledReport>(&format!(
+        "SELECT {} FROM scheduled_reports WHERE id = $1",
+        SCHEDULED_REPORT_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Scheduled report not found"))
+}
+
+/// Scheduled reports mail report data out of the app, so managing them and
+/// reading their output takes the export permission
+fn require_report_export(auth: &AuthUserWithRole) -> ApiResult<()> {
+    if auth.can(Resource::Reports, Action::Export) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("You do not have permission to manage scheduled reports"))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduledReportQuery {
+    pub client_id: Option<Uuid>,
+}
+
+async fn list_scheduled_reports(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(query): Query<ScheduledReportQuery>,
+) -> ApiResult<Json<Vec<ScheduledReport>>> {
+    require_report_export(&auth)?;
+    let reports = sqlx::query_as::<_, ScheduledReport>(&format!(
+        "SELECT {} FROM scheduled_reports
+         WHERE ($1::uuid IS NULL OR client_id = $1)
+         ORDER BY name",
+        SCHEDULED_REPORT_COLUMNS
+    ))
+    .bind(query.client_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(Json(reports))
+}
+
+async fn get_scheduled_report(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ScheduledReport>> {
+    require_report_export(&auth)?;
+    Ok(Json(load_scheduled_report(&state.db_pool, id).await?))
+}
+
+async fn create_scheduled_report(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Json(payload): Json<ScheduledReportRequest>,
+) -> ApiResult<(StatusCode, Json<ScheduledReport>)> {
+    require_report_export(&auth)?;
+    let fields = ScheduledReportFields::apply(None, payload);
+    fields.validate(&state.db_pool).await?;
+
+    let cadence = Cadence::parse(&fields.cadence).unwrap_or(Cadence::Monthly);
+    let report = sqlx::query_as::<_, ScheduledReport>(&format!(
+        "INSERT INTO scheduled_reports
+            (name, report_type, client_id, parameters, cadence, recipients, format, is_active, next_run_at, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+         RETURNING {}",
+        SCHEDULED_REPORT_COLUMNS
+    ))
+    .bind(&fields.name)
+    .bind(&fields.report_type)
+    .bind(fields.client_id)
+    .bind(&fields.parameters)
+    .bind(&fields.cadence)
+    .bind(&fields.recipients)
+    .bind(&fields.format)
+    .bind(fields.is_active)
+    .bind(cadence.next_run_after(Utc::now()))
+    .bind(auth.user.id)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.user.id, "CREATE", "scheduled_report", report.id).after(&report),
+    )
+    .await;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+async fn update_scheduled_report(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<ScheduledReportRequest>,
+) -> ApiResult<Json<ScheduledReport>> {
+    require_report_export(&auth)?;
+    let existing = load_scheduled_report(&state.db_pool, id).await?;
+    let fields = ScheduledReportFields::apply(Some(&existing), payload);
+    fields.validate(&state.db_pool).await?;
+
+    // A new cadence starts from its own next slot rather than the old one
+    let next_run_at = if fields.cadence != existing.cadence {
+        Cadence::parse(&fields.cadence).unwrap_or(Cadence::Monthly).next_run_after(Utc::now())
+    } else {
+        existing.next_run_at
+    };
+
+    let report = sqlx::query_as::<_, ScheduledReport>(&format!(
+        "UPDATE scheduled_reports
+         SET name = $2, report_type = $3, client_id = $4, parameters = $5, cadence = $6,
+             recipients = $7, format = $8, is_active = $9, next_run_at = $10, updated_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        SCHEDULED_REPORT_COLUMNS
+    ))
+    .bind(id)
+    .bind(&fields.name)
+    .bind(&fields.report_type)
+    .bind(fields.client_id)
+    .bind(&fields.parameters)
+    .bind(&fields.cadence)
+    .bind(&fields.recipients)
+    .bind(&fields.format)
+    .bind(fields.is_active)
+    .bind(next_run_at)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.user.id, "UPDATE", "scheduled_report", id).before(&existing).after(&report),
+    )
+    .await;
+
+    Ok(Json(report))
+}
+
+async fn delete_scheduled_report(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    require_report_export(&auth)?;
+    let existing = load_scheduled_report(&state.db_pool, id).await?;
+
+    sqlx::query("DELETE FROM scheduled_reports WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.user.id, "DELETE", "scheduled_report", id).before(&existing),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_scheduled_report_runs(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Vec<ScheduledReportRun>>> {
+    require_report_export(&auth)?;
+    load_scheduled_report(&state.db_pool, id).await?;
+
+    let runs = sqlx::query_as::<_, ScheduledReportRun>(&format!(
+        "SELECT {} FROM scheduled_report_runs
+         WHERE scheduled_report_id = $1
+         ORDER BY started_at DESC
+         LIMIT 50",
+        RUN_COLUMNS
+    ))
+    .bind(id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(Json(runs))
+}
+
+async fn download_scheduled_report_run(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(run_id): Path<Uuid>,
+) -> ApiResult<Response> {
+    require_report_export(&auth)?;
+    let (filename, content_type, content) = scheduled_reports::run_content(&state.db_pool, run_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Report output not found"))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        content,
+    )
+        .into_response())
+}
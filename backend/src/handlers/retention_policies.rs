@@ -0,0 +1,77 @@
+//! Data Retention Policies
+//!
+//! Admin-only view and edit of how long each entity type is kept. The
+//! `data_retention` job applies them nightly; run it now through
+//! `POST /api/v1/admin/jobs/data_retention/run`.
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::{get, put},
+    Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::auth::middleware::AuthUserWithRole;
+use crate::services::data_retention::{self, RetentionEntity, RetentionPolicy};
+use crate::{ApiError, ApiResult, AppState};
+
+/// Longest limit accepted, about a century
+const MAX_RETENTION_DAYS: i32 = 36_500;
+
+pub fn retention_policy_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_retention_policies))
+        .route("/:entity_type", put(update_retention_policy))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRetentionPolicyRequest {
+    pub retention_days: i32,
+    pub enabled: bool,
+}
+
+fn require_admin(auth: &AuthUserWithRole) -> ApiResult<()> {
+    if auth.is_admin() {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("Retention policies are only available to administrators"))
+    }
+}
+
+async fn list_retention_policies(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+) -> ApiResult<Json<Vec<RetentionPolicy>>> {
+    require_admin(&auth)?;
+    Ok(Json(data_retention::list_policies(&state.db_pool).await?))
+}
+
+async fn update_retention_policy(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(entity_type): Path<String>,
+    Json(req): Json<UpdateRetentionPolicyRequest>,
+) -> ApiResult<Json<RetentionPolicy>> {
+    require_admin(&auth)?;
+    let entity = RetentionEntity::parse(&entity_type)
+        .ok_or_else(|| ApiError::not_found(format!("Unknown retention entity: {}", entity_type)))?;
+    if !(1..=MAX_RETENTION_DAYS).contains(&req.retention_days) {
+        return Err(ApiError::validation_single(
+            "retention_days",
+            format!("must be between 1 and {}", MAX_RETENTION_DAYS),
+        ));
+    }
+
+    let policy = data_retention::update_policy(&state.db_pool, entity, req.retention_days, req.enabled).await?;
+    tracing::info!(
+        "User {} set {} retention to {} days ({})",
+        auth.user.id,
+        entity.as_str(),
+        req.retention_days,
+        if req.enabled { "enabled" } else { "disabled" }
+    );
+
+    Ok(Json(policy))
+}
@@ -0,0 +1,86 @@
+//! Global Search
+//!
+//! `GET /search?q=` across tickets, clients, contacts, assets and knowledge
+//! base articles. Each type is searched only if the caller may read it, and
+//! non-admins who are account manager for some clients only see records of
+//! those clients, as on the dashboard.
+
+use axum::{
+    extract::{Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::auth::middleware::AuthUserWithRole;
+use crate::auth::rbac::{Action, Resource};
+use crate::services::dashboard;
+use crate::services::global_search::{
+    self, EntityType, GlobalSearchResults, SearchAccess, DEFAULT_PER_TYPE_LIMIT, ENTITY_TYPES, MAX_PER_TYPE_LIMIT,
+};
+use crate::{ApiError, ApiResult, AppState};
+
+pub fn search_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", get(global_search))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GlobalSearchQuery {
+    pub q: Option<String>,
+    /// Hits per type
+    pub limit: Option<i64>,
+}
+
+/// Permission needed to see a type in results. Contacts are part of a
+/// client's record and the knowledge base is open to all staff, as on their
+/// own routes.
+fn required_permission(entity_type: EntityType) -> Option<Resource> {
+    match entity_type {
+        EntityType::Ticket => Some(Resource::Tickets),
+        EntityType::Client | EntityType::Contact => Some(Resource::Clients),
+        EntityType::Asset => Some(Resource::Assets),
+        EntityType::KbArticle => None,
+    }
+}
+
+async fn search_access(state: &AppState, auth: &AuthUserWithRole) -> ApiResult<SearchAccess> {
+    let entity_types = ENTITY_TYPES
+        .into_iter()
+        .filter(|entity_type| required_permission(*entity_type).map_or(true, |r| auth.can(r, Action::Read)))
+        .collect();
+
+    let managed = if auth.is_admin() {
+        Vec::new()
+    } else {
+        dashboard::managed_clients(&state.db_pool, auth.user.id).await?
+    };
+
+    Ok(SearchAccess {
+        entity_types,
+        client_ids: (!managed.is_empty()).then_some(managed),
+    })
+}
+
+async fn global_search(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(params): Query<GlobalSearchQuery>,
+) -> ApiResult<Json<GlobalSearchResults>> {
+    let query = params.q.as_deref().map(str::trim).unwrap_or_default();
+    if query.is_empty() {
+        return Err(ApiError::validation_single("q", "A search query is required"));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_PER_TYPE_LIMIT).clamp(1, MAX_PER_TYPE_LIMIT);
+
+    let access = search_access(&state, &auth).await?;
+    let results = global_search::search(&state.db_pool, query, &access, limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error running global search: {}", e);
+            ApiError::internal("Search failed")
+        })?;
+
+    Ok(Json(results))
+}
@@ -10,8 +10,11 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::sync::Arc;
 use uuid::Uuid;
-use crate::AppState;
+use crate::{ApiError, ApiResult, AppState};
 use crate::auth::{extract_token, verify_token};
+use crate::auth::middleware::AuthUserWithRole;
+use crate::auth::rbac::{Action, Resource};
+use resolve_shared::{BusinessHoliday, BusinessHours};
 use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -193,6 +196,30 @@ pub struct SlaBreachAlert {
     pub escalation_required: bool,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CalendarQuery {
+    /// Omit for the global schedule and holidays
+    pub client_id: Option<Uuid>,
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BusinessHoursInput {
+    pub client_id: Option<Uuid>,
+    pub day_of_week: i32,
+    pub start_time: chrono::NaiveTime,
+    pub end_time: chrono::NaiveTime,
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HolidayInput {
+    pub client_id: Option<Uuid>,
+    pub holiday_date: chrono::NaiveDate,
+    pub name: String,
+}
+
 pub fn sla_routes() -> Router<Arc<AppState>> {
     Router::new()
         // SLA Policies
@@ -208,6 +235,12 @@ pub fn sla_routes() -> Router<Arc<AppState>> {
         .route("/performance/:policy_id", get(get_sla_performance))
         .route("/breaches", get(list_sla_breaches))
         
+        // Business Hours & Holidays
+        .route("/business-hours", get(list_business_hours).post(create_business_hours))
+        .route("/business-hours/:id", put(update_business_hours).delete(delete_business_hours))
+        .route("/holidays", get(list_holidays).post(create_holiday))
+        .route("/holidays/:id", put(update_holiday).delete(delete_holiday))
+        
         // Workflows
         .route("/workflows", get(list_workflows).post(create_workflow))
         .route("/workflows/:id", get(get_workflow).put(update_workflow).delete(delete_workflow))
@@ -260,7 +293,7 @@ async fn create_sla_policy(
     Json(payload): Json<serde_json::Value>,
 ) -> Result<(StatusCode, Json<SlaPolicy>), StatusCode> {
     let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    let token_data = verify_token(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let token_data = verify_token(&state.db_pool, &token).await.map_err(|_| StatusCode::UNAUTHORIZED)?;
     let user_id = token_data.claims.sub.parse::<Uuid>().map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     let policy_id = Uuid::new_v4();
@@ -428,6 +461,204 @@ async fn resume_sla_tracking(
     Ok(StatusCode::NO_CONTENT)
 }
 
+const BUSINESS_HOURS_COLUMNS: &str = "id, client_id, day_of_week, start_time, end_time, timezone, created_at";
+const HOLIDAY_COLUMNS: &str = "id, client_id, holiday_date, name, created_at";
+
+fn validate_business_hours(input: &BusinessHoursInput) -> ApiResult<String> {
+    if !(0..=6).contains(&input.day_of_week) {
+        return Err(ApiError::validation_single("day_of_week", "Must be 0 (Sunday) to 6 (Saturday)"));
+    }
+    if input.end_time <= input.start_time {
+        return Err(ApiError::validation_single("end_time", "Must be after start_time"));
+    }
+    let timezone = input.timezone.as_deref().unwrap_or("UTC").trim().to_string();
+    if timezone.parse::<chrono_tz::Tz>().is_err() {
+        return Err(ApiError::validation_single("timezone", "Unknown timezone"));
+    }
+    Ok(timezone)
+}
+
+/// Business hours and holidays shape every SLA deadline, so changing them
+/// is a settings change
+fn require_calendar_admin(auth: &AuthUserWithRole) -> ApiResult<()> {
+    if auth.can(Resource::Settings, Action::Update) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("You do not have permission to change business hours"))
+    }
+}
+
+fn holiday_conflict(e: sqlx::Error) -> crate::AppError {
+    match &e {
+        sqlx::Error::Database(db) if db.code().as_deref() == Some("23505") => {
+            ApiError::conflict("A holiday already exists on that date")
+        }
+        _ => e.into(),
+    }
+}
+
+async fn list_business_hours(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CalendarQuery>,
+) -> ApiResult<Json<Vec<BusinessHours>>> {
+    let hours = sqlx::query_as::<_, BusinessHours>(&format!(
+        "SELECT {} FROM business_hours WHERE client_id IS NOT DISTINCT FROM $1 ORDER BY day_of_week, start_time",
+        BUSINESS_HOURS_COLUMNS
+    ))
+    .bind(params.client_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+    Ok(Json(hours))
+}
+
+async fn create_business_hours(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Json(payload): Json<BusinessHoursInput>,
+) -> ApiResult<(StatusCode, Json<BusinessHours>)> {
+    require_calendar_admin(&auth)?;
+    let timezone = validate_business_hours(&payload)?;
+    let hours = sqlx::query_as::<_, BusinessHours>(&format!(
+        "INSERT INTO business_hours (client_id, day_of_week, start_time, end_time, timezone)
+         VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        BUSINESS_HOURS_COLUMNS
+    ))
+    .bind(payload.client_id)
+    .bind(payload.day_of_week)
+    .bind(payload.start_time)
+    .bind(payload.end_time)
+    .bind(timezone)
+    .fetch_one(&state.db_pool)
+    .await?;
+    Ok((StatusCode::CREATED, Json(hours)))
+}
+
+async fn update_business_hours(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<BusinessHoursInput>,
+) -> ApiResult<Json<BusinessHours>> {
+    require_calendar_admin(&auth)?;
+    let timezone = validate_business_hours(&payload)?;
+    let hours = sqlx::query_as::<_, BusinessHours>(&format!(
+        "UPDATE business_hours SET client_id = $2, day_of_week = $3, start_time = $4, end_time = $5, timezone = $6
+         WHERE id = $1 RETURNING {}",
+        BUSINESS_HOURS_COLUMNS
+    ))
+    .bind(id)
+    .bind(payload.client_id)
+    .bind(payload.day_of_week)
+    .bind(payload.start_time)
+    .bind(payload.end_time)
+    .bind(timezone)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Business hours"))?;
+    Ok(Json(hours))
+}
+
+async fn delete_business_hours(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    require_calendar_admin(&auth)?;
+    let deleted = sqlx::query("DELETE FROM business_hours WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError::not_found("Business hours"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_holidays(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CalendarQuery>,
+) -> ApiResult<Json<Vec<BusinessHoliday>>> {
+    let holidays = sqlx::query_as::<_, BusinessHoliday>(&format!(
+        "SELECT {} FROM business_holidays
+         WHERE client_id IS NOT DISTINCT FROM $1
+           AND ($2::date IS NULL OR holiday_date >= $2)
+           AND ($3::date IS NULL OR holiday_date <= $3)
+         ORDER BY holiday_date",
+        HOLIDAY_COLUMNS
+    ))
+    .bind(params.client_id)
+    .bind(params.from)
+    .bind(params.to)
+    .fetch_all(&state.db_pool)
+    .await?;
+    Ok(Json(holidays))
+}
+
+async fn create_holiday(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Json(payload): Json<HolidayInput>,
+) -> ApiResult<(StatusCode, Json<BusinessHoliday>)> {
+    require_calendar_admin(&auth)?;
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::validation_single("name", "Name is required"));
+    }
+    let holiday = sqlx::query_as::<_, BusinessHoliday>(&format!(
+        "INSERT INTO business_holidays (client_id, holiday_date, name) VALUES ($1, $2, $3) RETURNING {}",
+        HOLIDAY_COLUMNS
+    ))
+    .bind(payload.client_id)
+    .bind(payload.holiday_date)
+    .bind(payload.name.trim())
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(holiday_conflict)?;
+    Ok((StatusCode::CREATED, Json(holiday)))
+}
+
+async fn update_holiday(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<HolidayInput>,
+) -> ApiResult<Json<BusinessHoliday>> {
+    require_calendar_admin(&auth)?;
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::validation_single("name", "Name is required"));
+    }
+    let holiday = sqlx::query_as::<_, BusinessHoliday>(&format!(
+        "UPDATE business_holidays SET client_id = $2, holiday_date = $3, name = $4 WHERE id = $1 RETURNING {}",
+        HOLIDAY_COLUMNS
+    ))
+    .bind(id)
+    .bind(payload.client_id)
+    .bind(payload.holiday_date)
+    .bind(payload.name.trim())
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(holiday_conflict)?
+    .ok_or_else(|| ApiError::not_found("Holiday"))?;
+    Ok(Json(holiday))
+}
+
+async fn delete_holiday(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    require_calendar_admin(&auth)?;
+    let deleted = sqlx::query("DELETE FROM business_holidays WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError::not_found("Holiday"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
 async fn list_categories(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Vec<TicketCategory>>, StatusCode> {
@@ -450,7 +681,7 @@ async fn create_portal_token(
     Json(payload): Json<serde_json::Value>,
 ) -> Result<(StatusCode, Json<ClientPortalToken>), StatusCode> {
     let token = extract_token(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    let token_data = verify_token(&token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let token_data = verify_token(&state.db_pool, &token).await.map_err(|_| StatusCode::UNAUTHORIZED)?;
     let user_id = token_data.claims.sub.parse::<Uuid>().map_err(|_| StatusCode::UNAUTHORIZED)?;
     
     use sha2::{Sha256, Digest};
@@ -1,9 +1,12 @@
 //! Microsoft Teams Integration Handlers
 //!
-//! API endpoints for managing Teams webhook configurations and sending notifications.
+//! API endpoints for managing Teams webhook configurations and sending notifications,
+//! plus the Teams/Slack chat channels that announce high-priority tickets, SLA
+//! breaches and failed jobs (see `services::chat_notifications`).
 
 use axum::{
     extract::{Path, Query, State},
+    http::StatusCode,
     response::Json,
     routing::{get, post, put, delete},
     Router,
@@ -14,9 +17,15 @@ use uuid::Uuid;
 use chrono::{DateTime, Utc, NaiveDate};
 use crate::{
     AppState, ApiResult, ApiError,
-    PaginatedResponse, PaginationParams,
+    PaginatedResponse, PaginationParams, Validator,
 };
-use crate::auth::middleware::AuthUser;
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::auth::rbac::{Action, Resource};
+use crate::services::chat_notifications::{
+    self, ChatChannel, ChatDelivery, ChatEvent, CHANNEL_COLUMNS, EVENT_TYPES, PROVIDERS,
+};
+use crate::services::outbound_webhooks::{http_client, RetryPolicy};
 use crate::services::teams_integration::{
     TeamsNotificationService, TicketNotification, DailySummary,
 };
@@ -102,6 +111,9 @@ pub fn teams_routes() -> Router<Arc<AppState>> {
         .route("/logs", get(list_notification_logs))
         .route("/notify/ticket/:ticket_id", post(notify_ticket))
         .route("/notify/daily-summary", post(send_daily_summary))
+        .route("/channels", get(list_chat_channels).post(create_chat_channel))
+        .route("/channels/:id", get(get_chat_channel).put(update_chat_channel).delete(delete_chat_channel))
+        .route("/channels/:id/test", post(test_chat_channel))
 }
 
 // ==================== Handlers ====================
@@ -618,3 +630,244 @@ async fn log_notification(
 
     Ok(())
 }
+
+// ==================== Chat Channels ====================
+
+/// Body for creating a chat channel; on update every field is optional and
+/// omitted fields keep their current value
+#[derive(Debug, Default, Deserialize)]
+pub struct ChatChannelRequest {
+    pub name: Option<String>,
+    pub provider: Option<String>,
+    pub integration_id: Option<Uuid>,
+    pub channel: Option<String>,
+    pub event_types: Option<Vec<String>>,
+    pub queue_ids: Option<Vec<Uuid>>,
+    pub enabled: Option<bool>,
+}
+
+/// A chat channel's settings once a request has been applied
+#[derive(Debug)]
+struct ChatChannelFields {
+    name: String,
+    provider: String,
+    integration_id: Option<Uuid>,
+    channel: Option<String>,
+    event_types: Vec<String>,
+    queue_ids: Vec<Uuid>,
+    enabled: bool,
+}
+
+impl ChatChannelFields {
+    fn apply(existing: Option<&ChatChannel>, payload: ChatChannelRequest) -> Self {
+        Self {
+            name: payload
+                .name
+                .map(|n| n.trim().to_string())
+                .or_else(|| existing.map(|c| c.name.clone()))
+                .unwrap_or_default(),
+            provider: payload
+                .provider
+                .map(|p| p.trim().to_lowercase())
+                .or_else(|| existing.map(|c| c.provider.clone()))
+                .unwrap_or_default(),
+            integration_id: payload.integration_id.or_else(|| existing.map(|c| c.integration_id)),
+            channel: match payload.channel {
+                Some(channel) => Some(channel.trim().to_string()).filter(|c| !c.is_empty()),
+                None => existing.and_then(|c| c.channel.clone()),
+            },
+            event_types: payload
+                .event_types
+                .map(|types| types.iter().map(|t| t.trim().to_lowercase()).collect())
+                .or_else(|| existing.map(|c| c.event_types.clone()))
+                .unwrap_or_default(),
+            queue_ids: payload
+                .queue_ids
+                .or_else(|| existing.map(|c| c.queue_ids.clone()))
+                .unwrap_or_default(),
+            enabled: payload.enabled.or_else(|| existing.map(|c| c.enabled)).unwrap_or(true),
+        }
+    }
+
+    async fn validate(&self, pool: &sqlx::PgPool) -> ApiResult<()> {
+        let mut validator = Validator::new()
+            .not_blank(&self.name, "name")
+            .length(Some(self.name.as_str()), "name", 1, 100)
+            .not_blank(&self.provider, "provider")
+            .one_of(Some(&self.provider).filter(|p| !p.is_empty()).map(String::as_str), "provider", PROVIDERS)
+            .error_if(self.integration_id.is_none(), "integration_id", "integration_id is required")
+            .error_if(self.event_types.is_empty(), "event_types", "At least one event type is required")
+            .length(self.channel.as_deref(), "channel", 1, 255);
+        for event_type in &self.event_types {
+            validator = validator.one_of(Some(event_type.as_str()), "event_types", EVENT_TYPES);
+        }
+        validator
+            .exists(pool, "integrations", self.integration_id, "integration_id")
+            .await?
+            .finish()
+    }
+}
+
+/// Chat channels post to webhooks held in the integration credential store,
+/// so they're managed under the integrations permissions
+fn require_integrations(auth: &AuthUserWithRole, action: Action) -> ApiResult<()> {
+    if auth.can(Resource::Integrations, action) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("You do not have permission to manage chat channels"))
+    }
+}
+
+async fn load_chat_channel(pool: &sqlx::PgPool, id: Uuid) -> ApiResult<ChatChannel> {
+    sqlx::query_as::<_, ChatChannel>(&format!(
+        "SELECT {} FROM chat_notification_channels WHERE id = $1",
+        CHANNEL_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Chat channel not found"))
+}
+
+async fn list_chat_channels(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+) -> ApiResult<Json<Vec<ChatChannel>>> {
+    require_integrations(&auth, Action::Read)?;
+    let channels = sqlx::query_as::<_, ChatChannel>(&format!(
+        "SELECT {} FROM chat_notification_channels ORDER BY name",
+        CHANNEL_COLUMNS
+    ))
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(Json(channels))
+}
+
+async fn get_chat_channel(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ChatChannel>> {
+    require_integrations(&auth, Action::Read)?;
+    Ok(Json(load_chat_channel(&state.db_pool, id).await?))
+}
+
+async fn create_chat_channel(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Json(payload): Json<ChatChannelRequest>,
+) -> ApiResult<(StatusCode, Json<ChatChannel>)> {
+    require_integrations(&auth, Action::Create)?;
+    let fields = ChatChannelFields::apply(None, payload);
+    fields.validate(&state.db_pool).await?;
+
+    let channel = sqlx::query_as::<_, ChatChannel>(&format!(
+        "INSERT INTO chat_notification_channels
+            (name, provider, integration_id, channel, event_types, queue_ids, enabled, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+         RETURNING {}",
+        CHANNEL_COLUMNS
+    ))
+    .bind(&fields.name)
+    .bind(&fields.provider)
+    .bind(fields.integration_id)
+    .bind(&fields.channel)
+    .bind(&fields.event_types)
+    .bind(&fields.queue_ids)
+    .bind(fields.enabled)
+    .bind(auth.user.id)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.user.id, "CREATE", "chat_channel", channel.id).after(&channel),
+    )
+    .await;
+
+    Ok((StatusCode::CREATED, Json(channel)))
+}
+
+async fn update_chat_channel(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<ChatChannelRequest>,
+) -> ApiResult<Json<ChatChannel>> {
+    require_integrations(&auth, Action::Update)?;
+    let existing = load_chat_channel(&state.db_pool, id).await?;
+    let fields = ChatChannelFields::apply(Some(&existing), payload);
+    fields.validate(&state.db_pool).await?;
+
+    let channel = sqlx::query_as::<_, ChatChannel>(&format!(
+        "UPDATE chat_notification_channels
+         SET name = $2, provider = $3, integration_id = $4, channel = $5, event_types = $6,
+             queue_ids = $7, enabled = $8, updated_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        CHANNEL_COLUMNS
+    ))
+    .bind(id)
+    .bind(&fields.name)
+    .bind(&fields.provider)
+    .bind(fields.integration_id)
+    .bind(&fields.channel)
+    .bind(&fields.event_types)
+    .bind(&fields.queue_ids)
+    .bind(fields.enabled)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.user.id, "UPDATE", "chat_channel", id).before(&existing).after(&channel),
+    )
+    .await;
+
+    Ok(Json(channel))
+}
+
+async fn delete_chat_channel(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    require_integrations(&auth, Action::Delete)?;
+    let existing = load_chat_channel(&state.db_pool, id).await?;
+
+    sqlx::query("DELETE FROM chat_notification_channels WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.user.id, "DELETE", "chat_channel", id).before(&existing),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Send a test message straight away, whether or not the channel is enabled,
+/// and report how it went
+async fn test_chat_channel(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<ChatDelivery>> {
+    require_integrations(&auth, Action::Update)?;
+    let channel = load_chat_channel(&state.db_pool, id).await?;
+    let event = ChatEvent::Test { channel_name: channel.name.clone() };
+    let policy = RetryPolicy { max_attempts: 1, ..RetryPolicy::default() };
+
+    let delivery = chat_notifications::deliver(&state.db_pool, http_client(), &channel, &event, policy).await?;
+    Ok(Json(delivery))
+}
@@ -12,11 +12,14 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
 use crate::{
-    AppState, ApiResult, ApiError,
+    AppState, ApiResult, ApiError, AppError,
     PaginatedResponse, PaginationParams,
 };
 use crate::auth::middleware::AuthUser;
+use crate::transaction::Tx;
+use crate::services::canned_response_render::{self, TemplateVariables};
 
 // ==================== Ticket Queues ====================
 
@@ -124,6 +127,37 @@ pub struct CreateCannedResponseRequest {
     pub variables: Option<Vec<String>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RenderCannedResponseRequest {
+    pub ticket_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenderedCannedResponse {
+    pub canned_response_id: Uuid,
+    pub ticket_id: Uuid,
+    pub subject: Option<String>,
+    pub content: String,
+    pub content_html: Option<String>,
+    /// Placeholders left in the output because no value was available
+    pub unresolved_variables: Vec<String>,
+}
+
+/// Ticket context used to fill canned response placeholders
+#[derive(Debug, sqlx::FromRow)]
+struct CannedResponseContext {
+    ticket_number: i32,
+    ticket_subject: String,
+    ticket_status: String,
+    ticket_priority: String,
+    client_name: String,
+    contact_name: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
+    assignee_name: Option<String>,
+    assignee_email: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct CannedResponseQuery {
     pub category: Option<String>,
@@ -234,6 +268,7 @@ pub fn canned_response_routes() -> Router<Arc<AppState>> {
         .route("/", get(list_canned_responses).post(create_canned_response))
         .route("/:id", get(get_canned_response).put(update_canned_response).delete(delete_canned_response))
         .route("/:id/use", post(use_canned_response))
+        .route("/:id/render", post(render_canned_response))
         .route("/search", get(search_canned_responses))
 }
 
@@ -830,6 +865,86 @@ async fn use_canned_response(
     Ok(Json(response))
 }
 
+async fn render_canned_response(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+    Json(req): Json<RenderCannedResponseRequest>,
+) -> ApiResult<Json<RenderedCannedResponse>> {
+    let response = sqlx::query_as::<_, (Option<String>, String, Option<String>)>(
+        "SELECT subject, content, content_html FROM canned_responses WHERE id = $1 AND is_active = true"
+    )
+    .bind(id)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Canned response not found"))?;
+
+    let ctx = sqlx::query_as::<_, CannedResponseContext>(
+        r#"SELECT
+            t.number as ticket_number, t.subject as ticket_subject,
+            t.status as ticket_status, t.priority as ticket_priority,
+            c.name as client_name,
+            ct.name as contact_name, ct.email as contact_email, ct.phone as contact_phone,
+            u.first_name || ' ' || u.last_name as assignee_name, u.email as assignee_email
+         FROM tickets t
+         JOIN clients c ON t.client_id = c.id
+         LEFT JOIN contacts ct ON t.contact_id = ct.id
+         LEFT JOIN users u ON t.assigned_to = u.id
+         WHERE t.id = $1"#
+    )
+    .bind(req.ticket_id)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Ticket not found"))?;
+
+    let contact_first_name = ctx
+        .contact_name
+        .as_deref()
+        .and_then(|name| name.split_whitespace().next())
+        .map(String::from);
+
+    let mut vars = TemplateVariables::new();
+    vars.set("ticket.id", Some(req.ticket_id.to_string()))
+        .set("ticket.number", Some(ctx.ticket_number.to_string()))
+        .set("ticket.subject", Some(ctx.ticket_subject))
+        .set("ticket.status", Some(ctx.ticket_status))
+        .set("ticket.priority", Some(ctx.ticket_priority))
+        .set("client.name", Some(ctx.client_name))
+        .set("contact.name", ctx.contact_name)
+        .set("contact.first_name", contact_first_name)
+        .set("contact.email", ctx.contact_email)
+        .set("contact.phone", ctx.contact_phone)
+        .set("assignee.name", ctx.assignee_name)
+        .set("assignee.email", ctx.assignee_email)
+        .set("agent.name", Some(format!("{} {}", user.first_name, user.last_name)))
+        .set("agent.first_name", Some(user.first_name.clone()))
+        .set("agent.email", Some(user.email.clone()))
+        .set("date.today", Some(Utc::now().format("%B %-d, %Y").to_string()));
+
+    let (subject, content, content_html) = response;
+    let subject = subject.map(|s| canned_response_render::render(&s, &vars, false));
+    let content = canned_response_render::render(&content, &vars, false);
+    let content_html = content_html.map(|h| canned_response_render::render(&h, &vars, true));
+
+    let unresolved_variables = canned_response_render::merge_unresolved(
+        subject.iter().chain(Some(&content)).chain(content_html.iter()),
+    );
+
+    sqlx::query("UPDATE canned_responses SET usage_count = usage_count + 1, last_used_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await?;
+
+    Ok(Json(RenderedCannedResponse {
+        canned_response_id: id,
+        ticket_id: req.ticket_id,
+        subject: subject.map(|s| s.text),
+        content: content.text,
+        content_html: content_html.map(|h| h.text),
+        unresolved_variables,
+    }))
+}
+
 async fn search_canned_responses(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
@@ -972,8 +1087,8 @@ async fn delete_ticket_link(
 }
 
 async fn merge_tickets(
-    State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    mut tx: Tx,
     Json(req): Json<MergeTicketsRequest>,
 ) -> ApiResult<Json<MergeResult>> {
     if req.source_ticket_ids.is_empty() {
@@ -984,17 +1099,27 @@ async fn merge_tickets(
         return Err(ApiError::validation_single("target_ticket_id", "Target ticket cannot be in source list"));
     }
 
+    let result = merge_into_target(&mut tx, auth.0.id, &req).await;
+    tx.finish(result).await.map(Json)
+}
+
+/// Every step of a merge; any failure undoes the whole merge
+async fn merge_into_target(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    req: &MergeTicketsRequest,
+) -> ApiResult<MergeResult> {
     let mut merged_numbers = Vec::new();
 
     for source_id in &req.source_ticket_ids {
-        // Get source ticket info
+        // Get source ticket info, locked so a concurrent merge can't take it too
         let source = sqlx::query!(
-            "SELECT number, subject FROM tickets WHERE id = $1 AND is_merged = false",
+            "SELECT number, subject FROM tickets WHERE id = $1 AND is_merged = false FOR UPDATE",
             source_id
         )
-        .fetch_optional(&state.db_pool)
+        .fetch_optional(&mut *conn)
         .await
-        .map_err(|e| ApiError::internal("Failed to fetch source ticket"))?
+        .map_err(|e| merge_step_error("fetch source ticket", e))?
         .ok_or_else(|| ApiError::not_found("Source ticket not found or already merged"))?;
 
         // Record the merge
@@ -1006,11 +1131,11 @@ async fn merge_tickets(
             source.number,
             source.subject,
             req.merge_reason,
-            auth.0.id
+            user_id
         )
-        .execute(&state.db_pool)
+        .execute(&mut *conn)
         .await
-        .map_err(|e| ApiError::internal("Failed to record merge"))?;
+        .map_err(|e| merge_step_error("record merge", e))?;
 
         // Mark source ticket as merged
         sqlx::query!(
@@ -1018,9 +1143,9 @@ async fn merge_tickets(
             source_id,
             req.target_ticket_id
         )
-        .execute(&state.db_pool)
+        .execute(&mut *conn)
         .await
-        .map_err(|e| ApiError::internal("Failed to mark ticket as merged"))?;
+        .map_err(|e| merge_step_error("mark ticket as merged", e))?;
 
         // Move replies from source to target
         sqlx::query!(
@@ -1028,9 +1153,9 @@ async fn merge_tickets(
             source_id,
             req.target_ticket_id
         )
-        .execute(&state.db_pool)
+        .execute(&mut *conn)
         .await
-        .ok();
+        .map_err(|e| merge_step_error("move replies", e))?;
 
         // Move time entries from source to target
         sqlx::query!(
@@ -1038,9 +1163,9 @@ async fn merge_tickets(
             source_id,
             req.target_ticket_id
         )
-        .execute(&state.db_pool)
+        .execute(&mut *conn)
         .await
-        .ok();
+        .map_err(|e| merge_step_error("move time entries", e))?;
 
         merged_numbers.push(source.number);
     }
@@ -1056,18 +1181,23 @@ async fn merge_tickets(
         "INSERT INTO ticket_replies (id, ticket_id, user_id, type, details) VALUES ($1, $2, $3, 'note', $4)",
         Uuid::new_v4(),
         req.target_ticket_id,
-        auth.0.id,
+        user_id,
         merge_note
     )
-    .execute(&state.db_pool)
+    .execute(&mut *conn)
     .await
-    .ok();
+    .map_err(|e| merge_step_error("add merge note", e))?;
 
-    Ok(Json(MergeResult {
+    Ok(MergeResult {
         primary_ticket_id: req.target_ticket_id,
         merged_count: merged_numbers.len() as i32,
         merged_ticket_numbers: merged_numbers,
-    }))
+    })
+}
+
+fn merge_step_error(step: &str, e: sqlx::Error) -> AppError {
+    tracing::error!("Ticket merge failed to {}: {}", step, e);
+    ApiError::internal(format!("Failed to {}", step))
 }
 
 // ==================== Tag Handlers ====================
@@ -1,15 +1,32 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, patch},
     Router,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use crate::AppState;
+use crate::{ApiError, ApiResult, AppState, PaginatedResponse, PaginationParams};
+use crate::pagination::Cursor;
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::concurrency::{self, Precondition};
+use crate::auth::middleware::{scopes, ApiKeyAuth, AuthUserWithRole};
+use crate::auth::rbac::{Action, Resource};
+use crate::notifications;
+use crate::services::{chat_notifications, dashboard_stream, outbound_webhooks};
+use crate::services::queue_assignment::auto_assign_ticket;
+use crate::services::ticket_routing::{apply_routing_rules, RoutableTicket};
+use crate::services::ticket_replies::{self, NewTicketReply, TicketReplyError, TicketReplyView};
+use crate::services::ticket_search::{self, TicketSearchFilters, TicketSearchResult};
+use crate::services::ticket_sla::{self, TicketSla, TicketSlaError};
+use crate::services::ticket_watchers::{self, TicketWatcher};
+use crate::transaction::Tx;
+use crate::validation::{enums, Validate, Validator};
+use resolve_shared::{Priority, TextEnum, TicketStatus};
 
 #[derive(Serialize, Deserialize)]
 pub struct TicketCreate {
@@ -17,7 +34,9 @@ pub struct TicketCreate {
     pub contact_id: Option<Uuid>,
     pub asset_id: Option<Uuid>,
     pub category_id: Option<Uuid>,
+    #[serde(default)]
     pub subject: String,
+    #[serde(default)]
     pub details: String,
     pub priority: Option<String>,
     pub source: Option<String>,
@@ -25,6 +44,27 @@ pub struct TicketCreate {
     pub estimated_hours: Option<rust_decimal::Decimal>,
 }
 
+impl Validate for TicketCreate {
+    fn rules(&self, v: Validator) -> Validator {
+        v.not_blank(&self.subject, "subject")
+            .error_if(self.subject.trim().len() > 255, "subject", "subject must be 255 characters or less")
+            .not_blank(&self.details, "details")
+            .text_enum::<Priority>(self.priority.as_deref(), "priority")
+            .range(self.estimated_hours, "estimated_hours", rust_decimal::Decimal::ZERO, rust_decimal::Decimal::from(10_000))
+    }
+}
+
+/// Every problem with a new ticket, including references that don't exist
+async fn validate_ticket_create(pool: &PgPool, payload: &TicketCreate) -> ApiResult<()> {
+    payload
+        .rules(Validator::new())
+        .exists(pool, "clients", Some(payload.client_id), "client_id").await?
+        .exists(pool, "contacts", payload.contact_id, "contact_id").await?
+        .exists(pool, "assets", payload.asset_id, "asset_id").await?
+        .exists(pool, "ticket_categories", payload.category_id, "category_id").await?
+        .finish()
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct TicketUpdate {
     pub subject: Option<String>,
@@ -35,6 +75,8 @@ pub struct TicketUpdate {
     pub category_id: Option<Uuid>,
     pub billable: Option<bool>,
     pub estimated_hours: Option<rust_decimal::Decimal>,
+    /// The `updated_at` the client last saw; a newer one gets `409 Conflict`
+    pub expected_updated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -43,12 +85,26 @@ pub struct TicketReplyCreate {
     pub reply_type: Option<String>, // reply, note, status_change
     pub time_worked: Option<i32>,   // minutes
     pub billable: Option<bool>,
+    /// Hidden from the client; notes always are
+    #[serde(default)]
+    pub internal: bool,
+    /// Uploaded files to attach
+    #[serde(default)]
+    pub attachment_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReplyListQuery {
+    /// `false` lists only what the client sees
+    pub internal: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TicketQuery {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    /// Switches to cursor pagination; empty for the first page
+    pub cursor: Option<String>,
     pub status: Option<String>,
     pub priority: Option<String>,
     pub assigned_to: Option<Uuid>,
@@ -59,6 +115,27 @@ pub struct TicketQuery {
 }
 
 #[derive(Serialize, Deserialize)]
+pub struct TicketSearchQuery {
+    pub q: String,
+    pub status: Option<String>,
+    pub client_id: Option<Uuid>,
+    pub priority: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WatcherCreate {
+    /// Defaults to the current user
+    pub user_id: Option<Uuid>,
+    pub notification_preferences: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WatcherQuery {
+    /// Defaults to the current user
+    pub user_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
 pub struct TicketWithDetails {
     pub id: Uuid,
     pub number: i32,
@@ -112,16 +189,23 @@ pub fn ticket_routes() -> Router<Arc<AppState>> {
         .route("/:id", get(get_ticket).put(update_ticket))
         .route("/:id/assign", patch(assign_ticket))
         .route("/:id/escalate", patch(escalate_ticket))
+        .route("/:id/sla", put(update_ticket_sla))
         .route("/:id/replies", get(get_ticket_replies).post(add_reply))
         .route("/:id/replies/:reply_id", put(update_reply))
+        .route("/:id/watchers", get(list_watchers).post(add_watcher).delete(remove_watcher))
         .route("/categories", get(get_categories))
         .route("/stats", get(get_ticket_stats))
+        .route("/search", get(search_tickets))
 }
 
 async fn list_tickets(
     State(state): State<Arc<AppState>>,
     Query(params): Query<TicketQuery>,
-) -> Result<Json<Vec<TicketWithDetails>>, StatusCode> {
+) -> Result<Response, StatusCode> {
+    if params.cursor.is_some() {
+        return list_tickets_by_cursor(&state, &params).await.map(IntoResponse::into_response);
+    }
+    
     let limit = params.limit.unwrap_or(50);
     let offset = params.offset.unwrap_or(0);
     
@@ -223,7 +307,7 @@ async fn list_tickets(
     .fetch_all(&state.db_pool)
     .await
     {
-        Ok(tickets) => Ok(Json(tickets)),
+        Ok(tickets) => Ok(Json(tickets).into_response()),
         Err(e) => {
             tracing::error!("Error fetching tickets: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -231,10 +315,112 @@ async fn list_tickets(
     }
 }
 
+/// Keyset page of tickets, newest first, ordered by `(created_at, id)`
+async fn list_tickets_by_cursor(
+    state: &AppState,
+    params: &TicketQuery,
+) -> Result<Json<PaginatedResponse<TicketWithDetails>>, StatusCode> {
+    let pagination = PaginationParams {
+        per_page: params.limit.unwrap_or(50),
+        cursor: params.cursor.clone(),
+        ..Default::default()
+    };
+    let cursor = pagination.decoded_cursor().map_err(|_| StatusCode::BAD_REQUEST)?;
+    
+    let mut conditions = Vec::new();
+    let mut param_count = 0;
+    let mut next_param = || {
+        param_count += 1;
+        param_count
+    };
+    
+    if params.status.is_some() { conditions.push(format!("t.status = ${}", next_param())); }
+    if params.priority.is_some() { conditions.push(format!("t.priority = ${}", next_param())); }
+    if params.assigned_to.is_some() { conditions.push(format!("t.assigned_to = ${}", next_param())); }
+    if params.client_id.is_some() { conditions.push(format!("t.client_id = ${}", next_param())); }
+    if params.category_id.is_some() { conditions.push(format!("t.category_id = ${}", next_param())); }
+    if params.sla_breached.is_some() { conditions.push(format!("t.sla_breached = ${}", next_param())); }
+    if params.search.is_some() {
+        let p = next_param();
+        conditions.push(format!("(t.subject ILIKE ${} OR t.details ILIKE ${})", p, p));
+    }
+    if cursor.is_some() {
+        let p = next_param();
+        next_param();
+        conditions.push(Cursor::condition("t.created_at", "t.id", true, p));
+    }
+    let limit_param = next_param();
+    
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    
+    let query = format!(
+        "SELECT 
+            t.id, t.number, t.client_id, COALESCE(c.name, '') as client_name,
+            t.contact_id, ct.name as contact_name,
+            t.asset_id, a.name as asset_name,
+            t.assigned_to, 
+            CASE WHEN u1.id IS NOT NULL THEN u1.first_name || ' ' || u1.last_name ELSE NULL END as assigned_name,
+            t.opened_by, COALESCE(u2.first_name || ' ' || u2.last_name, '') as opened_by_name,
+            t.subject, t.details, t.status, t.priority,
+            t.category_id, tc.name as category_name,
+            t.sla_id, t.response_due_at, t.resolution_due_at,
+            t.first_response_at, t.resolved_at, COALESCE(t.sla_breached, false) as sla_breached,
+            COALESCE(t.billable, true) as billable, t.estimated_hours, t.actual_hours,
+            COALESCE(t.source, 'manual') as source,
+            t.created_at, t.updated_at, t.closed_at
+         FROM tickets t
+         LEFT JOIN clients c ON t.client_id = c.id
+         LEFT JOIN contacts ct ON t.contact_id = ct.id
+         LEFT JOIN assets a ON t.asset_id = a.id
+         LEFT JOIN users u1 ON t.assigned_to = u1.id
+         LEFT JOIN users u2 ON t.opened_by = u2.id
+         LEFT JOIN ticket_categories tc ON t.category_id = tc.id
+         {}
+         ORDER BY t.created_at DESC, t.id DESC
+         LIMIT ${}",
+        where_clause, limit_param
+    );
+    
+    let mut db_query = sqlx::query_as::<_, TicketWithDetails>(&query);
+    if let Some(status) = &params.status { db_query = db_query.bind(status); }
+    if let Some(priority) = &params.priority { db_query = db_query.bind(priority); }
+    if let Some(assigned_to) = params.assigned_to { db_query = db_query.bind(assigned_to); }
+    if let Some(client_id) = params.client_id { db_query = db_query.bind(client_id); }
+    if let Some(category_id) = params.category_id { db_query = db_query.bind(category_id); }
+    if let Some(sla_breached) = params.sla_breached { db_query = db_query.bind(sla_breached); }
+    if let Some(search) = &params.search { db_query = db_query.bind(format!("%{}%", search)); }
+    if let Some(cursor) = cursor {
+        db_query = db_query.bind(cursor.created_at).bind(cursor.id);
+    }
+    
+    let tickets = db_query
+        .bind(pagination.limit() + 1)
+        .fetch_all(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching tickets: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    
+    Ok(Json(PaginatedResponse::from_cursor(tickets, &pagination, |t| Cursor::new(t.created_at, t.id))))
+}
+
 async fn create_ticket(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<TicketCreate>,
-) -> Result<(StatusCode, Json<TicketWithDetails>), StatusCode> {
+) -> Result<(StatusCode, Json<TicketWithDetails>), Response> {
+    validate_ticket_create(&state.db_pool, &payload)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let priority = payload.priority.as_deref()
+        .and_then(Priority::parse)
+        .unwrap_or_default()
+        .to_string();
+
     let ticket_id = Uuid::new_v4();
     
     // Get the next ticket number
@@ -245,16 +431,14 @@ async fn create_ticket(
     .await
     {
         Ok(num) => num.unwrap_or(1),
-        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
     };
     
-    // TODO: Calculate SLA due dates based on client contract
-    // For now, set basic defaults
+    // Default targets until the ticket's SLA is applied below
     let now = Utc::now();
-    let response_due = now + chrono::Duration::hours(4); // 4 hour response SLA
-    let resolution_due = now + chrono::Duration::hours(24); // 24 hour resolution SLA
+    let response_due = now + chrono::Duration::hours(4);
+    let resolution_due = now + chrono::Duration::hours(24);
     
-    let priority = payload.priority.unwrap_or_else(|| "medium".to_string());
     let source = payload.source.unwrap_or_else(|| "manual".to_string());
     let billable = payload.billable.unwrap_or(true);
     
@@ -275,7 +459,7 @@ async fn create_ticket(
         payload.category_id,
         payload.subject,
         payload.details,
-        "open",
+        TicketStatus::Open.as_str(),
         priority,
         source,
         billable,
@@ -288,21 +472,57 @@ async fn create_ticket(
     .await
     {
         Ok(_) => {
+            crate::middleware::prometheus::record_ticket_created(&source);
+            let routable = RoutableTicket {
+                id: ticket_id,
+                client_id: payload.client_id,
+                contact_id: payload.contact_id,
+                category_id: payload.category_id,
+                subject: payload.subject.clone(),
+                priority: priority.clone(),
+                source: source.clone(),
+                source_email: None,
+            };
+            // Routing and assignment failures shouldn't lose the ticket
+            match apply_routing_rules(&state.db_pool, &routable).await {
+                Ok(outcome) => {
+                    if let Some(queue_id) = outcome.queue_id {
+                        if let Err(e) = auto_assign_ticket(&state.db_pool, ticket_id, queue_id).await {
+                            tracing::error!("Error auto-assigning ticket {}: {}", ticket_id, e);
+                        }
+                    }
+                }
+                Err(e) => tracing::error!("Error applying routing rules to ticket {}: {}", ticket_id, e),
+            }
+            // After routing, which may change the priority, category or queue
+            if let Err(e) = ticket_sla::apply_sla(&state.db_pool, ticket_id).await {
+                tracing::error!("Error applying SLA to ticket {}: {}", ticket_id, e);
+            }
+
             // Fetch the created ticket with all details
             match get_ticket_by_id(&state, ticket_id).await {
-                Ok(ticket) => Ok((StatusCode::CREATED, Json(ticket))),
-                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                Ok(ticket) => {
+                    let data = serde_json::to_value(&ticket).unwrap_or_default();
+                    outbound_webhooks::notify(&state.db_pool, outbound_webhooks::TICKET_CREATED, data).await;
+                    chat_notifications::notify_ticket_created(&state.db_pool, ticket_id).await;
+                    state
+                        .broadcast_notification(dashboard_stream::TICKET_CREATED, serde_json::json!({ "ticket_id": ticket_id }))
+                        .await;
+                    Ok((StatusCode::CREATED, Json(ticket)))
+                }
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
             }
         }
         Err(e) => {
             tracing::error!("Error creating ticket: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
         }
     }
 }
 
 async fn get_ticket(
     State(state): State<Arc<AppState>>,
+    _auth: ApiKeyAuth<scopes::TicketsRead>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<TicketWithDetails>, StatusCode> {
     match get_ticket_by_id(&state, id).await {
@@ -352,9 +572,18 @@ async fn get_ticket_by_id(state: &AppState, id: Uuid) -> Result<TicketWithDetail
 
 async fn update_ticket(
     State(state): State<Arc<AppState>>,
+    auth: ApiKeyAuth<scopes::TicketsWrite>,
+    meta: RequestMeta,
+    precondition: Precondition,
     Path(id): Path<Uuid>,
     Json(payload): Json<TicketUpdate>,
-) -> Result<Json<TicketWithDetails>, StatusCode> {
+) -> Result<Json<TicketWithDetails>, Response> {
+    let status = enums::parse_optional::<TicketStatus>(&payload.status, "status").map_err(IntoResponse::into_response)?;
+    let priority = enums::parse_optional::<Priority>(&payload.priority, "priority").map_err(IntoResponse::into_response)?;
+
+    let before = get_ticket_by_id(&state, id).await.map_err(IntoResponse::into_response)?;
+    let unmodified_since = precondition.unmodified_since(payload.expected_updated_at);
+
     // Update ticket - simplified version
     match sqlx::query!(
         "UPDATE tickets SET 
@@ -367,32 +596,104 @@ async fn update_ticket(
          billable = COALESCE($8, billable),
          estimated_hours = COALESCE($9, estimated_hours),
          updated_at = NOW()
-         WHERE id = $1",
+         WHERE id = $1
+           AND ($10::timestamptz IS NULL OR updated_at IS NULL OR updated_at <= $10)",
         id,
         payload.subject,
         payload.details,
-        payload.status,
-        payload.priority,
+        status.map(|s| s.as_str()),
+        priority.map(|p| p.as_str()),
         payload.assigned_to,
         payload.category_id,
         payload.billable,
-        payload.estimated_hours
+        payload.estimated_hours,
+        unmodified_since
     )
     .execute(&state.db_pool)
     .await
     {
         Ok(result) => {
             if result.rows_affected() > 0 {
+                sync_sla(&state, id, &before, &payload).await;
                 match get_ticket_by_id(&state, id).await {
-                    Ok(ticket) => Ok(Json(ticket)),
-                    Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                    Ok(ticket) => {
+                        audit::record(
+                            &state.db_pool,
+                            &meta,
+                            AuditEvent::new(auth.user.id, "UPDATE", "ticket", id).before(&before).after(&ticket),
+                        )
+                        .await;
+                        if ticket.status != before.status {
+                            let pool = state.db_pool.clone();
+                            let message = format!("Status changed from {} to {}.", before.status, ticket.status);
+                            tokio::spawn(async move {
+                                if let Err(e) = notifications::email_ticket_update(&pool, id, &message).await {
+                                    tracing::error!("Error emailing ticket {} update: {}", id, e);
+                                }
+                            });
+                            if ticket_sla::is_resolved_status(&ticket.status) && !ticket_sla::is_resolved_status(&before.status) {
+                                state
+                                    .broadcast_notification(dashboard_stream::TICKET_CLOSED, serde_json::json!({ "ticket_id": id }))
+                                    .await;
+                            }
+                        }
+                        Ok(Json(ticket))
+                    }
+                    Err(status) => Err(status.into_response()),
                 }
             } else {
-                Err(StatusCode::NOT_FOUND)
+                // Changed since the client loaded it, or deleted meanwhile
+                match get_ticket_by_id(&state, id).await {
+                    Ok(current) => Err(concurrency::conflict(current)),
+                    Err(status) => Err(status.into_response()),
+                }
             }
         }
         Err(e) => {
             tracing::error!("Error updating ticket: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Recalculate SLA due times when an update changes what the SLA depends
+/// on, and keep `resolved_at` in step with the status
+async fn sync_sla(state: &AppState, id: Uuid, before: &TicketWithDetails, update: &TicketUpdate) {
+    let priority_changed = update.priority.as_ref().is_some_and(|p| *p != before.priority);
+    let category_changed = update.category_id.is_some_and(|c| Some(c) != before.category_id);
+    if priority_changed || category_changed {
+        if let Err(e) = ticket_sla::apply_sla(&state.db_pool, id).await {
+            tracing::error!("Error applying SLA to ticket {}: {}", id, e);
+        }
+    }
+    if let Some(status) = update.status.as_deref().filter(|s| *s != before.status) {
+        if let Err(e) = ticket_sla::record_status(&state.db_pool, id, status).await {
+            tracing::error!("Error recording resolution of ticket {}: {}", id, e);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TicketSlaUpdate {
+    /// Pin this SLA policy to the ticket; `null` to go back to the resolved one
+    pub sla_policy_id: Option<Uuid>,
+}
+
+async fn update_ticket_sla(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<TicketSlaUpdate>,
+) -> Result<Json<Option<TicketSla>>, StatusCode> {
+    if !auth.can(Resource::Tickets, Action::Update) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    match ticket_sla::pin_policy(&state.db_pool, id, payload.sla_policy_id).await {
+        Ok(sla) => Ok(Json(sla)),
+        Err(TicketSlaError::TicketNotFound) => Err(StatusCode::NOT_FOUND),
+        Err(TicketSlaError::PolicyNotFound) | Err(TicketSlaError::NoRuleForPriority(_)) => Err(StatusCode::UNPROCESSABLE_ENTITY),
+        Err(TicketSlaError::Database(e)) => {
+            tracing::error!("Error updating ticket {} SLA: {}", id, e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
@@ -459,6 +760,10 @@ async fn escalate_ticket(
     {
         Ok(result) => {
             if result.rows_affected() > 0 {
+                // Escalation raises the priority
+                if let Err(e) = ticket_sla::apply_sla(&state.db_pool, id).await {
+                    tracing::error!("Error applying SLA to ticket {}: {}", id, e);
+                }
                 match get_ticket_by_id(&state, id).await {
                     Ok(ticket) => Ok(Json(ticket)),
                     Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
@@ -474,99 +779,68 @@ async fn escalate_ticket(
     }
 }
 
+/// The ticket's replies with their attachments. Staff see internal ones
+/// too unless they pass `?internal=false`; the portal never does.
 async fn get_ticket_replies(
     State(state): State<Arc<AppState>>,
+    _auth: AuthUserWithRole,
     Path(id): Path<Uuid>,
-) -> Result<Json<Vec<TicketReply>>, StatusCode> {
-    match sqlx::query_as!(
-        TicketReply,
-        "SELECT 
-            tr.id, tr.ticket_id, tr.user_id, 
-            u.first_name || ' ' || u.last_name as user_name,
-            tr.type as reply_type, tr.details, tr.time_worked, tr.billable,
-            tr.created_at
-         FROM ticket_replies tr
-         LEFT JOIN users u ON tr.user_id = u.id
-         WHERE tr.ticket_id = $1
-         ORDER BY tr.created_at ASC",
-        id
-    )
-    .fetch_all(&state.db_pool)
-    .await
-    {
-        Ok(replies) => Ok(Json(replies)),
-        Err(e) => {
-            tracing::error!("Error fetching ticket replies: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+    Query(params): Query<ReplyListQuery>,
+) -> ApiResult<Json<Vec<TicketReplyView>>> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM tickets WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db_pool)
+        .await?;
+    if !exists {
+        return Err(ApiError::not_found("Ticket not found"));
     }
+
+    let replies = ticket_replies::list_replies(&state.db_pool, id, params.internal.unwrap_or(true)).await?;
+    Ok(Json(replies))
 }
 
+/// Reply to a ticket as the caller. A public reply is emailed to the client
+/// and the first one is the ticket's SLA response; an internal one is only
+/// for staff.
 async fn add_reply(
     State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    mut tx: Tx,
     Path(id): Path<Uuid>,
     Json(payload): Json<TicketReplyCreate>,
-) -> Result<(StatusCode, Json<TicketReply>), StatusCode> {
-    let reply_id = Uuid::new_v4();
-    // TODO: Get current user from auth context
-    let current_user_id = Uuid::new_v4();
-    
-    let reply_type = payload.reply_type.unwrap_or_else(|| "reply".to_string());
-    let time_worked = payload.time_worked.unwrap_or(0);
-    let billable = payload.billable.unwrap_or(false);
-    
-    match sqlx::query!(
-        "INSERT INTO ticket_replies (
-            id, ticket_id, user_id, type, details, time_worked, billable
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7)",
-        reply_id,
-        id,
-        current_user_id,
-        reply_type,
-        payload.details,
-        time_worked,
-        billable
-    )
-    .execute(&state.db_pool)
-    .await
-    {
-        Ok(_) => {
-            // Update first response time if this is the first reply
-            if reply_type == "reply" {
-                let _ = sqlx::query!(
-                    "UPDATE tickets SET 
-                     first_response_at = COALESCE(first_response_at, NOW()),
-                     updated_at = NOW()
-                     WHERE id = $1",
-                    id
-                ).execute(&state.db_pool).await;
+) -> ApiResult<(StatusCode, Json<TicketReplyView>)> {
+    let reply = NewTicketReply {
+        details: payload.details,
+        reply_type: payload.reply_type.unwrap_or_else(|| "reply".to_string()),
+        internal: payload.internal,
+        time_worked: payload.time_worked.unwrap_or(0),
+        billable: payload.billable.unwrap_or(false),
+        attachment_ids: payload.attachment_ids,
+    };
+
+    let result = ticket_replies::add_reply(&mut tx, id, auth.user.id, reply)
+        .await
+        .map_err(|e| match e {
+            TicketReplyError::TicketNotFound => ApiError::not_found("Ticket not found"),
+            TicketReplyError::Validation { field, message } => ApiError::validation_single(field, message),
+            TicketReplyError::Database(e) => {
+                tracing::error!("Error creating reply on ticket {}: {}", id, e);
+                ApiError::internal("Failed to add reply")
             }
-            
-            // Fetch the created reply
-            match sqlx::query_as!(
-                TicketReply,
-                "SELECT 
-                    tr.id, tr.ticket_id, tr.user_id, 
-                    u.first_name || ' ' || u.last_name as user_name,
-                    tr.type as reply_type, tr.details, tr.time_worked, tr.billable,
-                    tr.created_at
-                 FROM ticket_replies tr
-                 LEFT JOIN users u ON tr.user_id = u.id
-                 WHERE tr.id = $1",
-                reply_id
-            )
-            .fetch_one(&state.db_pool)
-            .await
-            {
-                Ok(reply) => Ok((StatusCode::CREATED, Json(reply))),
-                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        });
+    let reply = tx.finish(result).await?;
+
+    if ticket_replies::is_public_response(&reply.reply_type, reply.internal) {
+        let pool = state.db_pool.clone();
+        let details = reply.details.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifications::email_ticket_update(&pool, id, &details).await {
+                tracing::error!("Error emailing ticket {} reply: {}", id, e);
             }
-        }
-        Err(e) => {
-            tracing::error!("Error creating ticket reply: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        });
     }
+
+    Ok((StatusCode::CREATED, Json(reply)))
 }
 
 async fn update_reply(
@@ -680,4 +954,127 @@ async fn get_ticket_stats(
     };
     
     Ok(Json(stats))
-}
\ No newline at end of file
+}
+async fn search_tickets(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<TicketSearchQuery>,
+    Query(pagination): Query<PaginationParams>,
+) -> Result<Json<PaginatedResponse<TicketSearchResult>>, StatusCode> {
+    let query = params.q.trim();
+    if query.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let filters = TicketSearchFilters {
+        status: params.status,
+        client_id: params.client_id,
+        priority: params.priority,
+    };
+
+    match ticket_search::search_tickets(
+        &state.db_pool,
+        query,
+        &filters,
+        pagination.limit(),
+        pagination.offset(),
+    )
+    .await
+    {
+        Ok((results, total, _mode)) => Ok(Json(PaginatedResponse::new(results, &pagination, total))),
+        Err(e) => {
+            tracing::error!("Error searching tickets: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn ticket_exists(state: &AppState, id: Uuid) -> Result<(), StatusCode> {
+    match sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM tickets WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db_pool)
+        .await
+    {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error checking ticket {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn fetch_watchers(state: &AppState, id: Uuid) -> Result<Json<Vec<TicketWatcher>>, StatusCode> {
+    match ticket_watchers::list_watchers(&state.db_pool, id).await {
+        Ok(watchers) => Ok(Json(watchers)),
+        Err(e) => {
+            tracing::error!("Error fetching watchers for ticket {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn list_watchers(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<TicketWatcher>>, StatusCode> {
+    ticket_exists(&state, id).await?;
+    fetch_watchers(&state, id).await
+}
+
+/// Watch a ticket. Users can always subscribe themselves; subscribing someone
+/// else needs ticket assign permission. Watching twice is a no-op.
+async fn add_watcher(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<WatcherCreate>,
+) -> Result<(StatusCode, Json<Vec<TicketWatcher>>), StatusCode> {
+    let user_id = payload.user_id.unwrap_or(auth.user.id);
+    if user_id != auth.user.id && !auth.can(Resource::Tickets, Action::Assign) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    ticket_exists(&state, id).await?;
+
+    let created = match ticket_watchers::add_watcher(
+        &state.db_pool,
+        id,
+        user_id,
+        auth.user.id,
+        payload.notification_preferences,
+    )
+    .await
+    {
+        Ok(created) => created,
+        Err(e) => {
+            tracing::error!("Error adding watcher to ticket {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, fetch_watchers(&state, id).await?))
+}
+
+/// Stop watching a ticket. Removing another user needs ticket assign permission.
+async fn remove_watcher(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Query(params): Query<WatcherQuery>,
+) -> Result<Json<Vec<TicketWatcher>>, StatusCode> {
+    let user_id = params.user_id.unwrap_or(auth.user.id);
+    if user_id != auth.user.id && !auth.can(Resource::Tickets, Action::Assign) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    match ticket_watchers::remove_watcher(&state.db_pool, id, user_id).await {
+        Ok(true) => fetch_watchers(&state, id).await,
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!("Error removing watcher from ticket {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -1,20 +1,28 @@
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
-    response::Json,
+    http::header,
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
 use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc, NaiveDate};
 use rust_decimal::Decimal;
 use crate::{
-    AppState, ApiResult, ApiError,
+    AppState, ApiResult, ApiError, AppError,
     PaginatedResponse, PaginationParams, PaginationMeta,
     validation::{self, Validator},
 };
-use crate::auth::middleware::AuthUser;
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::auth::rbac::{Action, Resource};
+use crate::services::time_overlaps::{self, OverlapPair, OverlappingEntry};
+use crate::services::time_rounding::{RoundingDirection, TimeRounding};
+use crate::services::time_export::{self, TimeExportFilters, TimeExportFormat};
+use crate::services::{dashboard_stream, time_timers};
 
 #[derive(Serialize, Deserialize)]
 pub struct TimeEntryCreate {
@@ -73,7 +81,7 @@ pub struct TimeEntryQuery {
     pub q: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
 pub struct TimeEntryWithDetails {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -89,7 +97,10 @@ pub struct TimeEntryWithDetails {
     pub client_name: Option<String>,
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
+    /// Raw minutes worked
     pub duration_minutes: Option<i32>,
+    /// Minutes billed after the client's rounding rule
+    pub billable_minutes: Option<i32>,
     pub description: Option<String>,
     pub billable: bool,
     pub billed: bool,
@@ -131,16 +142,44 @@ pub struct TimeStats {
     pub active_timers: i32,
 }
 
+/// Select list and joins for [`TimeEntryWithDetails`]; callers append the
+/// WHERE clause
+pub(crate) const TIME_ENTRY_DETAILS_QUERY: &str = r#"SELECT
+            te.id, te.user_id,
+            COALESCE(u.first_name || ' ' || u.last_name, 'Unknown') AS user_name,
+            te.ticket_id, t.number AS ticket_number, t.subject AS ticket_subject,
+            te.project_id, p.name AS project_name,
+            te.task_id, tk.name AS task_name,
+            COALESCE(t.client_id, p.client_id) AS client_id,
+            c.name AS client_name,
+            te.start_time, te.end_time, te.duration_minutes, te.billable_minutes,
+            te.description, COALESCE(te.billable, true) AS billable, COALESCE(te.billed, false) AS billed,
+            te.hourly_rate, te.total_amount,
+            COALESCE(te.created_at, te.start_time) AS created_at, te.updated_at
+         FROM time_entries te
+         LEFT JOIN users u ON te.user_id = u.id
+         LEFT JOIN tickets t ON te.ticket_id = t.id
+         LEFT JOIN projects p ON te.project_id = p.id
+         LEFT JOIN tasks tk ON te.task_id = tk.id
+         LEFT JOIN clients c ON COALESCE(t.client_id, p.client_id) = c.id"#;
+
 pub fn time_tracking_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/entries", get(list_time_entries).post(create_manual_entry))
         .route("/entries/:id", get(get_time_entry).put(update_time_entry).delete(delete_time_entry))
+        .route("/start", post(start_timer))
+        .route("/stop", post(stop_timer))
+        .route("/active", get(get_active_timer))
         .route("/timer/start", post(start_timer))
         .route("/timer/stop", post(stop_timer))
         .route("/timer/active", get(get_active_timers))
         .route("/timer/switch", post(switch_timer))
+        .route("/overlaps", get(get_overlaps))
+        .route("/rounding/clients/:id", get(get_client_rounding).put(update_client_rounding))
+        .route("/rounding/contracts/:id", get(get_contract_rounding).put(update_contract_rounding))
         .route("/stats", get(get_time_stats))
         .route("/timesheet", get(get_timesheet))
+        .route("/export", get(export_time_entries))
 }
 
 /// List time entries with pagination and filtering
@@ -188,26 +227,8 @@ async fn list_time_entries(
     })?;
 
     // Get entries with details
-    let entries = sqlx::query_as!(
-        TimeEntryWithDetails,
-        r#"SELECT
-            te.id, te.user_id,
-            COALESCE(u.first_name || ' ' || u.last_name, 'Unknown') as "user_name!",
-            te.ticket_id, t.number as ticket_number, t.subject as ticket_subject,
-            te.project_id, p.name as project_name,
-            te.task_id, tk.name as task_name,
-            COALESCE(t.client_id, p.client_id) as client_id,
-            c.name as client_name,
-            te.start_time, te.end_time, te.duration_minutes,
-            te.description, te.billable as "billable!", te.billed as "billed!",
-            te.hourly_rate, te.total_amount,
-            te.created_at, te.updated_at
-         FROM time_entries te
-         LEFT JOIN users u ON te.user_id = u.id
-         LEFT JOIN tickets t ON te.ticket_id = t.id
-         LEFT JOIN projects p ON te.project_id = p.id
-         LEFT JOIN tasks tk ON te.task_id = tk.id
-         LEFT JOIN clients c ON COALESCE(t.client_id, p.client_id) = c.id
+    let entries = sqlx::query_as::<_, TimeEntryWithDetails>(&format!(
+        r#"{TIME_ENTRY_DETAILS_QUERY}
          WHERE te.user_id = $1
            AND ($2::uuid IS NULL OR te.ticket_id = $2)
            AND ($3::uuid IS NULL OR te.project_id = $3)
@@ -218,19 +239,19 @@ async fn list_time_entries(
            AND ($8::date IS NULL OR te.start_time::date <= $8)
            AND ($9::text IS NULL OR te.description ILIKE '%' || $9 || '%')
          ORDER BY te.start_time DESC
-         LIMIT $10 OFFSET $11"#,
-        user_filter,
-        params.ticket_id,
-        params.project_id,
-        params.client_id,
-        params.billable,
-        params.billed,
-        params.from_date,
-        params.to_date,
-        params.q,
-        limit,
-        offset
-    )
+         LIMIT $10 OFFSET $11"#
+    ))
+    .bind(user_filter)
+    .bind(params.ticket_id)
+    .bind(params.project_id)
+    .bind(params.client_id)
+    .bind(params.billable)
+    .bind(params.billed)
+    .bind(params.from_date)
+    .bind(params.to_date)
+    .bind(&params.q)
+    .bind(limit)
+    .bind(offset)
     .fetch_all(&state.db_pool)
     .await
     .map_err(|e| {
@@ -241,62 +262,71 @@ async fn list_time_entries(
     Ok(Json(PaginatedResponse::new(entries, &params.pagination, total)))
 }
 
-/// Start a new timer for the authenticated user
-async fn start_timer(
-    State(state): State<Arc<AppState>>,
-    AuthUser(user): AuthUser,
-    Json(payload): Json<TimeEntryCreate>,
-) -> ApiResult<Json<ActiveTimer>> {
-    let entry_id = Uuid::new_v4();
-    let now = Utc::now();
-    let billable = payload.billable.unwrap_or(true);
-
-    // Stop any existing active timer for this user (auto-stop feature)
-    let stopped = sqlx::query!(
-        "UPDATE time_entries SET
-         end_time = NOW(),
-         duration_minutes = EXTRACT(EPOCH FROM (NOW() - start_time))::int / 60
-         WHERE user_id = $1 AND end_time IS NULL
-         RETURNING id",
-        user.id
-    )
-    .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error stopping existing timer: {}", e);
-        ApiError::internal("Failed to stop existing timer")
-    })?;
+/// Response for starting a timer
+#[derive(Serialize)]
+pub struct StartTimerResponse {
+    #[serde(flatten)]
+    pub timer: ActiveTimer,
+    /// The previously running timer, if starting this one stopped it
+    pub stopped_timer_id: Option<Uuid>,
+}
 
-    // If we stopped a timer, calculate its billing
-    if let Some(stopped_row) = stopped {
-        let _ = calculate_and_update_billing(&state, stopped_row.id).await;
+fn timer_error(e: time_timers::TimerError) -> AppError {
+    match e {
+        time_timers::TimerError::AlreadyRunning(_) => {
+            ApiError::conflict("A timer is already running; stop it before starting another")
+        }
+        time_timers::TimerError::NotRunning => ApiError::not_found("Active timer"),
+        time_timers::TimerError::Database(e) => {
+            tracing::error!("Error updating timer: {}", e);
+            ApiError::internal("Failed to update timer")
+        }
     }
+}
 
-    // Start new timer
-    sqlx::query!(
-        "INSERT INTO time_entries (
-            id, user_id, ticket_id, project_id, task_id,
-            start_time, description, billable
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
-        entry_id,
-        user.id,
-        payload.ticket_id,
-        payload.project_id,
-        payload.task_id,
-        now,
-        payload.description,
-        billable
+async fn start_timer_with(
+    state: &AppState,
+    user_id: Uuid,
+    payload: TimeEntryCreate,
+    auto_stop: bool,
+) -> ApiResult<Json<StartTimerResponse>> {
+    let started = time_timers::start(
+        &state.db_pool,
+        user_id,
+        time_timers::StartTimer {
+            ticket_id: payload.ticket_id,
+            project_id: payload.project_id,
+            task_id: payload.task_id,
+            description: payload.description,
+            billable: payload.billable.unwrap_or(true),
+        },
+        auto_stop,
     )
-    .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Error starting timer: {}", e);
-        ApiError::internal("Failed to start timer")
-    })?;
+    .map_err(timer_error)?;
+
+    state
+        .broadcast_notification(dashboard_stream::TIMER_STARTED, serde_json::json!({ "timer_id": started.timer_id, "user_id": user_id }))
+        .await;
+    if let Some(stopped_id) = started.stopped_id {
+        state
+            .broadcast_notification(dashboard_stream::TIMER_STOPPED, serde_json::json!({ "timer_id": stopped_id, "user_id": user_id }))
+            .await;
+    }
 
-    // Fetch and return the active timer details
-    let timer = get_active_timer_by_id(&state, entry_id).await?;
-    Ok(Json(timer))
+    let timer = get_active_timer_by_id(state, started.timer_id).await?;
+    Ok(Json(StartTimerResponse { timer, stopped_timer_id: started.stopped_id }))
+}
+
+/// Start a new timer for the authenticated user. A running timer is
+/// stopped first unless `TIME_TIMER_AUTO_STOP` is off, in which case this
+/// is a conflict.
+async fn start_timer(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<TimeEntryCreate>,
+) -> ApiResult<Json<StartTimerResponse>> {
+    start_timer_with(&state, user.id, payload, time_timers::auto_stop_enabled()).await
 }
 
 /// Request body for stopping a timer
@@ -306,54 +336,65 @@ pub struct StopTimerRequest {
     pub timer_id: Option<Uuid>,
 }
 
-/// Stop a running timer for the authenticated user
+/// Stop the running timer for the authenticated user, recording its
+/// duration and billable amount
 async fn stop_timer(
     State(state): State<Arc<AppState>>,
     AuthUser(user): AuthUser,
-    Json(payload): Json<StopTimerRequest>,
-) -> ApiResult<Json<TimeEntryWithDetails>> {
-    let end_time = Utc::now();
-
-    // Stop specific timer or the user's active timer
-    let stopped_id = if let Some(timer_id) = payload.timer_id {
-        sqlx::query_scalar!(
-            "UPDATE time_entries SET
-             end_time = $2,
-             duration_minutes = EXTRACT(EPOCH FROM ($2 - start_time))::int / 60
-             WHERE id = $1 AND user_id = $3 AND end_time IS NULL
-             RETURNING id",
-            timer_id,
-            end_time,
-            user.id
-        )
-        .fetch_optional(&state.db_pool)
-        .await
-    } else {
-        sqlx::query_scalar!(
-            "UPDATE time_entries SET
-             end_time = $2,
-             duration_minutes = EXTRACT(EPOCH FROM ($2 - start_time))::int / 60
-             WHERE user_id = $1 AND end_time IS NULL
-             RETURNING id",
-            user.id,
-            end_time
-        )
-        .fetch_optional(&state.db_pool)
+    payload: Option<Json<StopTimerRequest>>,
+) -> ApiResult<Json<TimeEntryResponse>> {
+    let timer_id = match payload.and_then(|Json(p)| p.timer_id) {
+        Some(id) => id,
+        None => time_timers::running_timer_id(&state.db_pool, user.id)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error fetching active timer: {}", e);
+                ApiError::internal("Failed to stop timer")
+            })?
+            .ok_or_else(|| ApiError::not_found("Active timer"))?,
+    };
+
+    let running = get_time_entry_by_id(&state, timer_id).await?;
+    if running.user_id != user.id {
+        return Err(ApiError::not_found("Active timer"));
+    }
+    let overlaps = time_overlaps::find_overlaps(&state.db_pool, user.id, running.start_time, Some(Utc::now()), Some(timer_id))
         .await
+        .map_err(|e| {
+            tracing::error!("Error checking time entry overlaps: {}", e);
+            ApiError::internal("Failed to check for overlapping time entries")
+        })?;
+    if !overlaps.is_empty() && time_overlaps::strict_mode() {
+        return Err(overlap_conflict(overlaps.len()));
     }
-    .map_err(|e| {
-        tracing::error!("Error stopping timer: {}", e);
-        ApiError::internal("Failed to stop timer")
-    })?;
-
-    let entry_id = stopped_id.ok_or_else(|| ApiError::not_found("No active timer found"))?;
 
-    // Calculate billable amount
-    let _ = calculate_and_update_billing(&state, entry_id).await;
+    let entry_id = time_timers::stop(&state.db_pool, user.id, Some(timer_id))
+        .await
+        .map_err(timer_error)?;
+    state
+        .broadcast_notification(dashboard_stream::TIMER_STOPPED, serde_json::json!({ "timer_id": entry_id, "user_id": user.id }))
+        .await;
 
-    // Fetch and return the updated entry
     let entry = get_time_entry_by_id(&state, entry_id).await?;
-    Ok(Json(entry))
+    Ok(Json(TimeEntryResponse { entry, overlap_warnings: overlaps }))
+}
+
+/// Get the running timer for the authenticated user, if any
+async fn get_active_timer(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user): AuthUser,
+) -> ApiResult<Json<Option<ActiveTimer>>> {
+    let running = time_timers::running_timer_id(&state.db_pool, user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching active timer: {}", e);
+            ApiError::internal("Failed to fetch active timer")
+        })?;
+
+    match running {
+        Some(id) => Ok(Json(Some(get_active_timer_by_id(&state, id).await?))),
+        None => Ok(Json(None)),
+    }
 }
 
 /// Get all active (running) timers for the authenticated user
@@ -408,9 +449,49 @@ async fn switch_timer(
     State(state): State<Arc<AppState>>,
     AuthUser(user): AuthUser,
     Json(payload): Json<TimeEntryCreate>,
-) -> ApiResult<Json<ActiveTimer>> {
-    // This is effectively the same as start_timer (which auto-stops existing)
-    start_timer(State(state), AuthUser(user), Json(payload)).await
+) -> ApiResult<Json<StartTimerResponse>> {
+    // Switching always stops the running timer, whatever the auto-stop setting
+    start_timer_with(&state, user.id, payload, true).await
+}
+
+/// A saved time entry along with the user's other entries it overlaps
+#[derive(Serialize)]
+pub struct TimeEntryResponse {
+    #[serde(flatten)]
+    pub entry: TimeEntryWithDetails,
+    pub overlap_warnings: Vec<OverlappingEntry>,
+}
+
+/// Entries of the user's that `[start, end)` would overlap, other than
+/// `exclude`. In strict mode any overlap is a conflict. Run on the
+/// transaction that saves the entry: the user is locked until it commits.
+async fn check_overlaps(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+    exclude: Option<Uuid>,
+) -> ApiResult<Vec<OverlappingEntry>> {
+    time_overlaps::lock_user(&mut *conn, user_id).await?;
+    let overlaps = time_overlaps::find_overlaps(&mut *conn, user_id, start, end, exclude)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error checking time entry overlaps: {}", e);
+            ApiError::internal("Failed to check for overlapping time entries")
+        })?;
+
+    if !overlaps.is_empty() && time_overlaps::strict_mode() {
+        return Err(overlap_conflict(overlaps.len()));
+    }
+    Ok(overlaps)
+}
+
+fn overlap_conflict(count: usize) -> AppError {
+    ApiError::conflict(format!(
+        "Time entry overlaps {} existing entr{}",
+        count,
+        if count == 1 { "y" } else { "ies" }
+    ))
 }
 
 /// Create a manual time entry (not a running timer)
@@ -418,7 +499,7 @@ async fn create_manual_entry(
     State(state): State<Arc<AppState>>,
     AuthUser(user): AuthUser,
     Json(payload): Json<ManualTimeEntry>,
-) -> ApiResult<Json<TimeEntryWithDetails>> {
+) -> ApiResult<Json<TimeEntryResponse>> {
     // Validate: end_time must be after start_time
     if payload.end_time <= payload.start_time {
         return Err(ApiError::validation_single(
@@ -435,6 +516,9 @@ async fn create_manual_entry(
         ));
     }
 
+    let mut tx = state.db_pool.begin().await?;
+    let overlaps = check_overlaps(&mut tx, user.id, payload.start_time, Some(payload.end_time), None).await?;
+
     let entry_id = Uuid::new_v4();
     let duration = payload.end_time.signed_duration_since(payload.start_time);
     let duration_minutes = duration.num_minutes() as i32;
@@ -455,18 +539,50 @@ async fn create_manual_entry(
         payload.description,
         payload.billable
     )
-    .execute(&state.db_pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         tracing::error!("Error creating manual time entry: {}", e);
         ApiError::internal("Failed to create time entry")
     })?;
+    tx.commit().await?;
 
     // Calculate billing
     let _ = calculate_and_update_billing(&state, entry_id).await;
 
     let entry = get_time_entry_by_id(&state, entry_id).await?;
-    Ok(Json(entry))
+    Ok(Json(TimeEntryResponse { entry, overlap_warnings: overlaps }))
+}
+
+/// Query parameters for reviewing overlapping entries
+#[derive(Debug, Deserialize)]
+pub struct OverlapParams {
+    /// Whose entries to check (defaults to the current user; others need admin)
+    pub user_id: Option<Uuid>,
+    /// Day to check, UTC (defaults to today)
+    pub date: Option<NaiveDate>,
+}
+
+/// Pairs of a user's time entries on a day that overlap each other
+async fn get_overlaps(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(params): Query<OverlapParams>,
+) -> ApiResult<Json<Vec<OverlapPair>>> {
+    let user_id = params.user_id.unwrap_or(auth.user.id);
+    if user_id != auth.user.id && !auth.is_admin() {
+        return Err(ApiError::forbidden("You can only review your own time entries"));
+    }
+    let date = params.date.unwrap_or_else(|| Utc::now().date_naive());
+
+    let pairs = time_overlaps::overlaps_on(&state.db_pool, user_id, date)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error finding overlapping time entries: {}", e);
+            ApiError::internal("Failed to find overlapping time entries")
+        })?;
+
+    Ok(Json(pairs))
 }
 
 /// Get a single time entry by ID
@@ -491,7 +607,7 @@ async fn update_time_entry(
     AuthUser(user): AuthUser,
     Path(id): Path<Uuid>,
     Json(payload): Json<TimeEntryUpdate>,
-) -> ApiResult<Json<TimeEntryWithDetails>> {
+) -> ApiResult<Json<TimeEntryResponse>> {
     // Verify ownership first
     let existing = get_time_entry_by_id(&state, id).await?;
     if existing.user_id != user.id {
@@ -511,6 +627,15 @@ async fn update_time_entry(
         payload.duration_minutes
     };
 
+    let mut tx = state.db_pool.begin().await?;
+    let overlaps = if payload.start_time.is_some() || payload.end_time.is_some() {
+        let start = payload.start_time.unwrap_or(existing.start_time);
+        let end = payload.end_time.or(existing.end_time);
+        check_overlaps(&mut tx, user.id, start, end, Some(id)).await?
+    } else {
+        Vec::new()
+    };
+
     let result = sqlx::query!(
         "UPDATE time_entries SET
          ticket_id = COALESCE($2, ticket_id),
@@ -534,7 +659,7 @@ async fn update_time_entry(
         duration,
         user.id
     )
-    .execute(&state.db_pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         tracing::error!("Error updating time entry: {}", e);
@@ -544,12 +669,13 @@ async fn update_time_entry(
     if result.rows_affected() == 0 {
         return Err(ApiError::not_found("Time entry not found"));
     }
+    tx.commit().await?;
 
     // Recalculate billing
     let _ = calculate_and_update_billing(&state, id).await;
 
     let entry = get_time_entry_by_id(&state, id).await?;
-    Ok(Json(entry))
+    Ok(Json(TimeEntryResponse { entry, overlap_warnings: overlaps }))
 }
 
 /// Delete a time entry
@@ -730,42 +856,200 @@ async fn get_timesheet(
     Ok(Json(result))
 }
 
-// Helper functions
+/// Time export parameters
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeExportParams {
+    /// First day of the period (inclusive)
+    pub from: NaiveDate,
+    /// Last day of the period (inclusive)
+    pub to: NaiveDate,
+    /// Whose entries; defaults to the caller, or everyone for approvers
+    pub user_id: Option<Uuid>,
+    pub client_id: Option<Uuid>,
+    #[serde(default)]
+    pub format: TimeExportFormat,
+}
 
-async fn get_time_entry_by_id(state: &AppState, id: Uuid) -> Result<TimeEntryWithDetails, ApiError> {
-    sqlx::query_as!(
-        TimeEntryWithDetails,
-        r#"SELECT
-            te.id, te.user_id,
-            COALESCE(u.first_name || ' ' || u.last_name, 'Unknown') as "user_name!",
-            te.ticket_id, t.number as ticket_number, t.subject as ticket_subject,
-            te.project_id, p.name as project_name,
-            te.task_id, tk.name as task_name,
-            COALESCE(t.client_id, p.client_id) as client_id,
-            c.name as client_name,
-            te.start_time, te.end_time, te.duration_minutes,
-            te.description, te.billable as "billable!", te.billed as "billed!",
-            te.hourly_rate, te.total_amount,
-            te.created_at, te.updated_at
-         FROM time_entries te
-         LEFT JOIN users u ON te.user_id = u.id
-         LEFT JOIN tickets t ON te.ticket_id = t.id
-         LEFT JOIN projects p ON te.project_id = p.id
-         LEFT JOIN tasks tk ON te.task_id = tk.id
-         LEFT JOIN clients c ON COALESCE(t.client_id, p.client_id) = c.id
-         WHERE te.id = $1"#,
-        id
+/// Export completed time entries for a period as CSV or iCalendar. Anyone
+/// may export their own; exporting other users' time takes the same
+/// `time_entries.approve` permission as seeing it on the dashboard.
+async fn export_time_entries(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Query(params): Query<TimeExportParams>,
+) -> ApiResult<Response> {
+    if params.to < params.from {
+        return Err(ApiError::validation_single("to", "must not be before from"));
+    }
+
+    let privileged = auth.can(Resource::TimeEntries, Action::Approve);
+    let user_id = match params.user_id {
+        Some(user_id) if user_id != auth.user.id && !privileged => {
+            return Err(ApiError::forbidden("You can only export your own time entries"));
+        }
+        Some(user_id) => Some(user_id),
+        None if privileged => None,
+        None => Some(auth.user.id),
+    };
+
+    let filters = TimeExportFilters {
+        from: params.from,
+        to: params.to,
+        user_id,
+        client_id: params.client_id,
+    };
+    let filename = format!("time-entries-{}-to-{}.{}", params.from, params.to, params.format.extension());
+    let body = Body::from_stream(time_export::export(state.db_pool.clone(), filters, params.format));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, params.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
     )
+        .into_response())
+}
+
+// Helper functions
+
+/// Clients and contracts share the rounding columns
+#[derive(Clone, Copy)]
+enum RoundingOwner {
+    Client,
+    Contract,
+}
+
+impl RoundingOwner {
+    fn table(self) -> &'static str {
+        match self {
+            RoundingOwner::Client => "clients",
+            RoundingOwner::Contract => "contracts",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            RoundingOwner::Client => "Client",
+            RoundingOwner::Contract => "Contract",
+        }
+    }
+}
+
+async fn load_rounding(state: &AppState, owner: RoundingOwner, id: Uuid) -> ApiResult<TimeRounding> {
+    let (increment_minutes, direction, minimum_minutes) = sqlx::query_as::<_, (Option<i32>, String, Option<i32>)>(&format!(
+        "SELECT time_rounding_increment, time_rounding_direction, time_rounding_minimum FROM {} WHERE id = $1",
+        owner.table()
+    ))
+    .bind(id)
     .fetch_optional(&state.db_pool)
     .await
     .map_err(|e| {
-        tracing::error!("Error fetching time entry: {}", e);
-        ApiError::internal("Failed to fetch time entry")
+        tracing::error!("Error fetching time rounding: {}", e);
+        ApiError::internal("Failed to fetch time rounding")
     })?
-    .ok_or_else(|| ApiError::not_found("Time entry not found"))
+    .ok_or_else(|| ApiError::not_found(owner.label()))?;
+
+    Ok(TimeRounding {
+        increment_minutes,
+        direction: RoundingDirection::parse(&direction).unwrap_or_default(),
+        minimum_minutes,
+    })
+}
+
+async fn save_rounding(state: &AppState, owner: RoundingOwner, id: Uuid, rule: TimeRounding) -> ApiResult<TimeRounding> {
+    if rule.increment_minutes.is_some_and(|i| !(1..=1440).contains(&i)) {
+        return Err(ApiError::validation_single("increment_minutes", "Increment must be between 1 and 1440 minutes"));
+    }
+    if rule.minimum_minutes.is_some_and(|m| !(0..=1440).contains(&m)) {
+        return Err(ApiError::validation_single("minimum_minutes", "Minimum must be between 0 and 1440 minutes"));
+    }
+
+    let updated = sqlx::query(&format!(
+        "UPDATE {} SET time_rounding_increment = $2, time_rounding_direction = $3, time_rounding_minimum = $4,
+         updated_at = NOW() WHERE id = $1",
+        owner.table()
+    ))
+    .bind(id)
+    .bind(rule.increment_minutes)
+    .bind(rule.direction.as_str())
+    .bind(rule.minimum_minutes)
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error updating time rounding: {}", e);
+        ApiError::internal("Failed to update time rounding")
+    })?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::not_found(owner.label()));
+    }
+    Ok(rule)
+}
+
+/// Rounding changes what clients are billed, so only those who can edit
+/// invoices may set it
+fn require_billing_settings(auth: &AuthUserWithRole) -> ApiResult<()> {
+    if auth.can(Resource::Invoices, Action::Update) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("You do not have permission to change time rounding"))
+    }
+}
+
+/// A client's billing increment for time; applies where no active
+/// contract sets one
+async fn get_client_rounding(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<TimeRounding>> {
+    Ok(Json(load_rounding(&state, RoundingOwner::Client, id).await?))
+}
+
+async fn update_client_rounding(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<TimeRounding>,
+) -> ApiResult<Json<TimeRounding>> {
+    require_billing_settings(&auth)?;
+    Ok(Json(save_rounding(&state, RoundingOwner::Client, id, payload).await?))
+}
+
+/// A contract's billing increment for time, overriding its client's while
+/// the contract is active
+async fn get_contract_rounding(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<TimeRounding>> {
+    Ok(Json(load_rounding(&state, RoundingOwner::Contract, id).await?))
+}
+
+async fn update_contract_rounding(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<TimeRounding>,
+) -> ApiResult<Json<TimeRounding>> {
+    require_billing_settings(&auth)?;
+    Ok(Json(save_rounding(&state, RoundingOwner::Contract, id, payload).await?))
+}
+
+async fn get_time_entry_by_id(state: &AppState, id: Uuid) -> ApiResult<TimeEntryWithDetails> {
+    sqlx::query_as::<_, TimeEntryWithDetails>(&format!("{TIME_ENTRY_DETAILS_QUERY} WHERE te.id = $1"))
+        .bind(id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching time entry: {}", e);
+            ApiError::internal("Failed to fetch time entry")
+        })?
+        .ok_or_else(|| ApiError::not_found("Time entry not found"))
 }
 
-async fn get_active_timer_by_id(state: &AppState, id: Uuid) -> Result<ActiveTimer, ApiError> {
+async fn get_active_timer_by_id(state: &AppState, id: Uuid) -> ApiResult<ActiveTimer> {
     let row = sqlx::query!(
         r#"SELECT
             te.id, te.user_id, te.ticket_id, t.subject as ticket_subject,
@@ -803,26 +1087,14 @@ async fn get_active_timer_by_id(state: &AppState, id: Uuid) -> Result<ActiveTime
     })
 }
 
-async fn calculate_and_update_billing(state: &AppState, entry_id: Uuid) -> Result<(), ApiError> {
-    // TODO: Get user's hourly rate or project/client rate from settings
-    let default_rate = Decimal::from(75); // $75/hour default
-
-    sqlx::query!(
-        "UPDATE time_entries SET
-         hourly_rate = COALESCE(hourly_rate, $2),
-         total_amount = CASE WHEN billable THEN
-                           COALESCE(hourly_rate, $2) * (COALESCE(duration_minutes, 0)::decimal / 60)
-                        ELSE 0 END
-         WHERE id = $1",
-        entry_id,
-        default_rate
-    )
-    .execute(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error calculating billing: {}", e);
+async fn calculate_and_update_billing(state: &AppState, entry_id: Uuid) -> ApiResult<()> {
+    let mut conn = state.db_pool.acquire().await.map_err(|e| {
+        tracing::error!("Error acquiring connection: {}", e);
         ApiError::internal("Failed to calculate billing")
     })?;
 
-    Ok(())
-}
\ No newline at end of file
+    time_timers::price_entry(&mut conn, entry_id).await.map_err(|e| {
+        tracing::error!("Error calculating billing: {}", e);
+        ApiError::internal("Failed to calculate billing")
+    })
+}
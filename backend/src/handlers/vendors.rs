@@ -0,0 +1,298 @@
+//! Vendor management
+//!
+//! Vendors are the suppliers expenses are paid to. Deleting a vendor
+//! archives it: it drops out of the list (unless `include_archived=true`)
+//! and can't take new expenses, but it stays readable along with the
+//! expenses already recorded against it.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+use resolve_shared::Vendor;
+use crate::{AppState, ApiError, ApiResult, PaginatedResponse, PaginationParams};
+use crate::auth::middleware::AuthUser;
+use crate::validation::Validator;
+
+const VENDOR_COLUMNS: &str = "id, name, email, phone, website, address, city, state, zip,
+    contact_name, account_number, payment_terms, notes, created_at, updated_at, archived_at";
+
+pub fn vendor_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_vendors).post(create_vendor))
+        .route("/:id", get(get_vendor).put(update_vendor).delete(delete_vendor))
+        .route("/:id/expenses", get(vendor_expenses))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VendorQuery {
+    #[serde(flatten)]
+    pub pagination: PaginationParams,
+    /// Matches name, contact name, email or account number
+    pub search: Option<String>,
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VendorRequest {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub website: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub zip: Option<String>,
+    pub contact_name: Option<String>,
+    pub account_number: Option<String>,
+    /// e.g. `net_30`
+    pub payment_terms: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl VendorRequest {
+    /// On create `name` is required; on update it may be left out but not blanked
+    fn validate(&self, creating: bool) -> ApiResult<()> {
+        let validator = if creating || self.name.is_some() {
+            Validator::new().required_string(&self.name, "name")
+        } else {
+            Validator::new()
+        };
+        validator
+            .max_length(&self.name, "name", 255)
+            .email(&self.email, "email")
+            .max_length(&self.phone, "phone", 50)
+            .max_length(&self.payment_terms, "payment_terms", 50)
+            .max_length(&self.account_number, "account_number", 100)
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct VendorExpense {
+    pub id: Uuid,
+    pub client_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub category: String,
+    pub description: String,
+    pub amount: Decimal,
+    pub tax_amount: Option<Decimal>,
+    pub expense_date: NaiveDate,
+    pub status: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VendorExpenseRollup {
+    pub vendor_id: Uuid,
+    pub expense_count: i64,
+    pub total_amount: Decimal,
+    pub total_tax: Decimal,
+    /// Newest first
+    pub expenses: Vec<VendorExpense>,
+}
+
+/// The vendor, `None` if it doesn't exist; archived vendors are included
+pub async fn find_vendor(pool: &PgPool, vendor_id: Uuid) -> Result<Option<Vendor>, sqlx::Error> {
+    sqlx::query_as::<_, Vendor>(&format!("SELECT {} FROM vendors WHERE id = $1", VENDOR_COLUMNS))
+        .bind(vendor_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// The vendor an expense is being recorded against, which must exist and
+/// not be archived
+pub async fn expense_vendor(pool: &PgPool, vendor_id: Uuid) -> ApiResult<Vendor> {
+    match find_vendor(pool, vendor_id).await? {
+        Some(vendor) if vendor.archived_at.is_none() => Ok(vendor),
+        Some(_) => Err(ApiError::validation_single("vendor_id", "Vendor is archived")),
+        None => Err(ApiError::validation_single("vendor_id", "Vendor does not exist")),
+    }
+}
+
+async fn list_vendors(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Query(query): Query<VendorQuery>,
+) -> ApiResult<Json<PaginatedResponse<Vendor>>> {
+    let search = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("%{}%", s));
+    let filter = "($1::text IS NULL OR name ILIKE $1 OR contact_name ILIKE $1 OR email ILIKE $1 OR account_number ILIKE $1)
+        AND ($2 OR archived_at IS NULL)";
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM vendors WHERE {}", filter))
+        .bind(&search)
+        .bind(query.include_archived)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    let vendors = sqlx::query_as::<_, Vendor>(&format!(
+        "SELECT {} FROM vendors WHERE {} ORDER BY name, id LIMIT $3 OFFSET $4",
+        VENDOR_COLUMNS, filter
+    ))
+    .bind(&search)
+    .bind(query.include_archived)
+    .bind(query.pagination.limit())
+    .bind(query.pagination.offset())
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(Json(PaginatedResponse::new(vendors, &query.pagination, total)))
+}
+
+async fn create_vendor(
+    State(state): State<Arc<AppState>>,
+    AuthUser(user): AuthUser,
+    Json(payload): Json<VendorRequest>,
+) -> ApiResult<(StatusCode, Json<Vendor>)> {
+    payload.validate(true)?;
+
+    let vendor = sqlx::query_as::<_, Vendor>(&format!(
+        "INSERT INTO vendors (name, email, phone, website, address, city, state, zip,
+            contact_name, account_number, payment_terms, notes, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+         RETURNING {}",
+        VENDOR_COLUMNS
+    ))
+    .bind(payload.name.as_deref().map(str::trim))
+    .bind(&payload.email)
+    .bind(&payload.phone)
+    .bind(&payload.website)
+    .bind(&payload.address)
+    .bind(&payload.city)
+    .bind(&payload.state)
+    .bind(&payload.zip)
+    .bind(&payload.contact_name)
+    .bind(&payload.account_number)
+    .bind(&payload.payment_terms)
+    .bind(&payload.notes)
+    .bind(user.id)
+    .fetch_one(&state.db_pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(vendor)))
+}
+
+async fn get_vendor(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(vendor_id): Path<Uuid>,
+) -> ApiResult<Json<Vendor>> {
+    let vendor = find_vendor(&state.db_pool, vendor_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Vendor"))?;
+
+    Ok(Json(vendor))
+}
+
+/// Update the fields given, leaving the rest as they are
+async fn update_vendor(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(vendor_id): Path<Uuid>,
+    Json(payload): Json<VendorRequest>,
+) -> ApiResult<Json<Vendor>> {
+    payload.validate(false)?;
+
+    let vendor = sqlx::query_as::<_, Vendor>(&format!(
+        "UPDATE vendors SET
+            name = COALESCE($2, name),
+            email = COALESCE($3, email),
+            phone = COALESCE($4, phone),
+            website = COALESCE($5, website),
+            address = COALESCE($6, address),
+            city = COALESCE($7, city),
+            state = COALESCE($8, state),
+            zip = COALESCE($9, zip),
+            contact_name = COALESCE($10, contact_name),
+            account_number = COALESCE($11, account_number),
+            payment_terms = COALESCE($12, payment_terms),
+            notes = COALESCE($13, notes),
+            updated_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        VENDOR_COLUMNS
+    ))
+    .bind(vendor_id)
+    .bind(payload.name.as_deref().map(str::trim))
+    .bind(&payload.email)
+    .bind(&payload.phone)
+    .bind(&payload.website)
+    .bind(&payload.address)
+    .bind(&payload.city)
+    .bind(&payload.state)
+    .bind(&payload.zip)
+    .bind(&payload.contact_name)
+    .bind(&payload.account_number)
+    .bind(&payload.payment_terms)
+    .bind(&payload.notes)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("Vendor"))?;
+
+    Ok(Json(vendor))
+}
+
+/// Archive the vendor
+async fn delete_vendor(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(vendor_id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let result = sqlx::query(
+        "UPDATE vendors SET archived_at = NOW(), updated_at = NOW() WHERE id = $1 AND archived_at IS NULL"
+    )
+    .bind(vendor_id)
+    .execute(&state.db_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::not_found("Vendor"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The vendor's expenses with their count and totals
+async fn vendor_expenses(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(vendor_id): Path<Uuid>,
+) -> ApiResult<Json<VendorExpenseRollup>> {
+    if find_vendor(&state.db_pool, vendor_id).await?.is_none() {
+        return Err(ApiError::not_found("Vendor"));
+    }
+
+    let expenses = sqlx::query_as::<_, VendorExpense>(
+        "SELECT id, client_id, project_id, category, description, amount, tax_amount,
+                expense_date, status, created_at
+         FROM expenses
+         WHERE vendor_id = $1
+         ORDER BY expense_date DESC, created_at DESC"
+    )
+    .bind(vendor_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(Json(VendorExpenseRollup {
+        vendor_id,
+        expense_count: expenses.len() as i64,
+        total_amount: expenses.iter().map(|e| e.amount).sum(),
+        total_tax: expenses.iter().filter_map(|e| e.tax_amount).sum(),
+        expenses,
+    }))
+}
@@ -0,0 +1,327 @@
+//! Outbound Webhook Subscriptions
+//!
+//! CRUD for webhook subscriptions, their delivery history, and a ping
+//! endpoint that sends a test delivery. Subscriptions are managed under the
+//! integrations permissions and may only target public addresses.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::middleware::AuthUserWithRole;
+use crate::auth::rbac::{Action, Resource};
+use crate::services::outbound_webhooks::{self, DeliveryOutcome, RetryPolicy, WebhookSubscription};
+use crate::services::public_address;
+use crate::{ApiError, ApiResult, AppState};
+
+pub fn webhook_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_subscriptions).post(create_subscription))
+        .route("/events", get(list_event_types))
+        .route("/:id", get(get_subscription).put(update_subscription).delete(delete_subscription))
+        .route("/:id/deliveries", get(list_deliveries))
+        .route("/:id/ping", post(ping_subscription))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSubscriptionRequest {
+    pub name: Option<String>,
+    pub url: String,
+    /// Generated when omitted
+    pub secret: Option<String>,
+    #[serde(alias = "events")]
+    pub event_types: Vec<String>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSubscriptionRequest {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    #[serde(alias = "events")]
+    pub event_types: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+/// The secret is only returned when a subscription is created
+#[derive(Debug, Serialize)]
+pub struct CreatedSubscription {
+    #[serde(flatten)]
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+fn validate(url: Option<&str>, event_types: Option<&[String]>, secret: Option<&str>) -> ApiResult<()> {
+    let mut errors: HashMap<String, Vec<String>> = HashMap::new();
+
+    if let Some(url) = url {
+        match url::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.host().is_some() => {}
+            _ => errors.entry("url".to_string()).or_default().push("must be an http(s) URL".to_string()),
+        }
+    }
+
+    if let Some(event_types) = event_types {
+        if event_types.is_empty() {
+            errors.entry("event_types".to_string()).or_default().push("must not be empty".to_string());
+        }
+        for event_type in event_types {
+            if !outbound_webhooks::is_event_type(event_type) {
+                errors
+                    .entry("event_types".to_string())
+                    .or_default()
+                    .push(format!("unknown event type '{}'", event_type));
+            }
+        }
+    }
+
+    if secret.is_some_and(|s| s.trim().len() < 16) {
+        errors.entry("secret".to_string()).or_default().push("must be at least 16 characters".to_string());
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::validation(errors))
+    }
+}
+
+fn require(auth: &AuthUserWithRole, action: Action) -> ApiResult<()> {
+    if auth.can(Resource::Integrations, action) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("You do not have permission to manage webhooks"))
+    }
+}
+
+/// Deliveries are checked again when sent, since DNS can change
+async fn check_target(url: Option<&str>) -> ApiResult<()> {
+    match url {
+        Some(url) => public_address::check_public_url(url)
+            .await
+            .map_err(|e| ApiError::validation_single("url", e.to_string())),
+        None => Ok(()),
+    }
+}
+
+async fn fetch_subscription(state: &AppState, id: Uuid) -> ApiResult<WebhookSubscription> {
+    sqlx::query_as::<_, WebhookSubscription>("SELECT * FROM webhook_subscriptions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error fetching webhook subscription: {}", e);
+            ApiError::internal("Failed to fetch webhook subscription")
+        })?
+        .ok_or_else(|| ApiError::not_found("Webhook subscription not found"))
+}
+
+async fn list_event_types(auth: AuthUserWithRole) -> ApiResult<Json<&'static [&'static str]>> {
+    require(&auth, Action::Read)?;
+    Ok(Json(outbound_webhooks::EVENT_TYPES))
+}
+
+async fn list_subscriptions(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+) -> ApiResult<Json<Vec<WebhookSubscription>>> {
+    require(&auth, Action::Read)?;
+    let subscriptions = sqlx::query_as::<_, WebhookSubscription>(
+        "SELECT * FROM webhook_subscriptions ORDER BY created_at DESC"
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error fetching webhook subscriptions: {}", e);
+        ApiError::internal("Failed to fetch webhook subscriptions")
+    })?;
+
+    Ok(Json(subscriptions))
+}
+
+async fn get_subscription(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<WebhookSubscription>> {
+    require(&auth, Action::Read)?;
+    Ok(Json(fetch_subscription(&state, id).await?))
+}
+
+async fn create_subscription(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Json(req): Json<CreateSubscriptionRequest>,
+) -> ApiResult<(StatusCode, Json<CreatedSubscription>)> {
+    require(&auth, Action::Create)?;
+    validate(Some(&req.url), Some(&req.event_types), req.secret.as_deref())?;
+    check_target(Some(&req.url)).await?;
+
+    let secret = req.secret.unwrap_or_else(outbound_webhooks::generate_secret);
+    let subscription = sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        INSERT INTO webhook_subscriptions (name, url, secret, event_types, active, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *
+        "#
+    )
+    .bind(&req.name)
+    .bind(&req.url)
+    .bind(&secret)
+    .bind(&req.event_types)
+    .bind(req.active.unwrap_or(true))
+    .bind(auth.user.id)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error creating webhook subscription: {}", e);
+        ApiError::internal("Failed to create webhook subscription")
+    })?;
+
+    Ok((StatusCode::CREATED, Json(CreatedSubscription { subscription, secret })))
+}
+
+async fn update_subscription(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateSubscriptionRequest>,
+) -> ApiResult<Json<WebhookSubscription>> {
+    require(&auth, Action::Update)?;
+    validate(req.url.as_deref(), req.event_types.as_deref(), req.secret.as_deref())?;
+    check_target(req.url.as_deref()).await?;
+
+    let subscription = sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        UPDATE webhook_subscriptions SET
+            name = COALESCE($2, name),
+            url = COALESCE($3, url),
+            secret = COALESCE($4, secret),
+            event_types = COALESCE($5, event_types),
+            active = COALESCE($6, active),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(&req.name)
+    .bind(&req.url)
+    .bind(&req.secret)
+    .bind(&req.event_types)
+    .bind(req.active)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error updating webhook subscription: {}", e);
+        ApiError::internal("Failed to update webhook subscription")
+    })?
+    .ok_or_else(|| ApiError::not_found("Webhook subscription not found"))?;
+
+    Ok(Json(subscription))
+}
+
+async fn delete_subscription(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    require(&auth, Action::Delete)?;
+    let deleted = sqlx::query("DELETE FROM webhook_subscriptions WHERE id = $1")
+        .bind(id)
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error deleting webhook subscription: {}", e);
+            ApiError::internal("Failed to delete webhook subscription")
+        })?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(ApiError::not_found("Webhook subscription not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Vec<WebhookDelivery>>> {
+    require(&auth, Action::Read)?;
+    fetch_subscription(&state, id).await?;
+
+    let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        SELECT id, event_id, event_type, status, attempts, last_status_code, last_error, created_at, completed_at
+        FROM webhook_deliveries
+        WHERE subscription_id = $1
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#
+    )
+    .bind(id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error fetching webhook deliveries: {}", e);
+        ApiError::internal("Failed to fetch webhook deliveries")
+    })?;
+
+    Ok(Json(deliveries))
+}
+
+/// Send a single `ping` delivery now and report how it went
+async fn ping_subscription(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUserWithRole,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<DeliveryOutcome>> {
+    require(&auth, Action::Update)?;
+    let subscription = fetch_subscription(&state, id).await?;
+    let data = serde_json::json!({
+        "subscription_id": subscription.id,
+        "requested_by": auth.user.id
+    });
+    let policy = RetryPolicy { max_attempts: 1, ..RetryPolicy::default() };
+
+    let outcome = outbound_webhooks::deliver(
+        &state.db_pool,
+        outbound_webhooks::http_client(),
+        &subscription,
+        outbound_webhooks::PING,
+        &data,
+        policy,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Error recording webhook ping: {}", e);
+        ApiError::internal("Failed to send test delivery")
+    })?;
+
+    Ok(Json(outcome))
+}
@@ -0,0 +1,101 @@
+//! Workflow Automation Handlers
+//!
+//! Execution history for workflow runs and replay of past trigger payloads.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    AppState, ApiResult, ApiError,
+    PaginatedResponse, PaginationParams,
+};
+use crate::auth::middleware::AuthUser;
+use crate::workflows::{WorkflowEngine, WorkflowExecution, WorkflowStatus};
+
+pub fn workflow_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:id/executions", get(list_workflow_executions))
+        .route("/executions/:exec_id", get(get_workflow_execution))
+        .route("/executions/:exec_id/replay", post(replay_workflow_execution))
+}
+
+// ==================== Handlers ====================
+
+async fn list_workflow_executions(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(workflow_id): Path<Uuid>,
+    Query(params): Query<PaginationParams>,
+) -> ApiResult<Json<PaginatedResponse<WorkflowExecution>>> {
+    let (executions, total) = WorkflowEngine::get_execution_history(
+        &state.db_pool,
+        workflow_id,
+        params.limit(),
+        params.offset(),
+    )
+    .await?;
+
+    Ok(Json(PaginatedResponse::new(executions, &params, total)))
+}
+
+async fn get_workflow_execution(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(exec_id): Path<Uuid>,
+) -> ApiResult<Json<WorkflowExecution>> {
+    let execution = WorkflowEngine::get_execution(&state.db_pool, exec_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Workflow execution not found"))?;
+
+    Ok(Json(execution))
+}
+
+/// Re-run the trigger payload of a past execution. The new run is recorded
+/// as its own execution with `replayed_from_id` pointing at the original.
+/// Runs that completed are not replayed, so their actions aren't repeated.
+async fn replay_workflow_execution(
+    State(state): State<Arc<AppState>>,
+    AuthUser(_user): AuthUser,
+    Path(exec_id): Path<Uuid>,
+) -> ApiResult<(StatusCode, Json<WorkflowExecution>)> {
+    let original = WorkflowEngine::get_execution(&state.db_pool, exec_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Workflow execution not found"))?;
+    if original.status == WorkflowStatus::Completed.as_str() {
+        return Err(ApiError::conflict("Completed executions can't be replayed"));
+    }
+
+    let engine = workflow_engine(&state).await?;
+    let workflow = engine
+        .active_workflow(original.workflow_id)
+        .await
+        .ok_or_else(|| ApiError::conflict("Workflow is inactive or no longer exists"))?;
+
+    let replay_id = engine
+        .replay_execution(&workflow, &original)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to replay workflow execution: {}", e)))?;
+
+    let replay = WorkflowEngine::get_execution(&state.db_pool, replay_id)
+        .await?
+        .ok_or_else(|| ApiError::internal("Replayed execution was not recorded"))?;
+
+    Ok((StatusCode::CREATED, Json(replay)))
+}
+
+async fn workflow_engine(state: &AppState) -> ApiResult<WorkflowEngine> {
+    let email_service = state
+        .email_service
+        .clone()
+        .ok_or_else(|| ApiError::internal("Email is not configured, so workflows can't be run"))?;
+
+    WorkflowEngine::new(state.db_pool.clone(), email_service, state.ws_manager.clone())
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to load workflows: {}", e)))
+}
@@ -0,0 +1,29 @@
+//! The caller's own workspace
+//!
+//! `GET /me/workspace` gathers a technician's open tickets, running timer,
+//! today's logged time and upcoming tasks, so their home screen needs one
+//! call instead of several.
+
+use axum::{extract::State, response::Json, routing::get, Router};
+use chrono::Utc;
+use std::sync::Arc;
+
+use crate::auth::middleware::AuthUser;
+use crate::services::my_workspace::{self, Workspace};
+use crate::{ApiError, ApiResult, AppState};
+
+pub fn me_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/workspace", get(get_workspace))
+}
+
+/// Only ever the caller's own items; there is no way to ask for another
+/// user's workspace
+async fn get_workspace(State(state): State<Arc<AppState>>, auth: AuthUser) -> ApiResult<Json<Workspace>> {
+    let workspace = my_workspace::workspace(&state.db_pool, auth.0.id, Utc::now())
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading workspace for user {}: {}", auth.0.id, e);
+            ApiError::internal("Failed to load workspace")
+        })?;
+    Ok(Json(workspace))
+}
@@ -1,6 +1,7 @@
 use axum::{
+    body::Bytes,
     extract::{Query, State},
-    http::StatusCode,
+    http::HeaderMap,
     response::{IntoResponse, Json},
     routing::{get, post},
     Router,
@@ -10,10 +11,13 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::auth::api_keys::constant_time_eq;
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::services::azure_cost_alerts;
+use crate::{AppState, ApiError, ApiResult};
 use resolve_shared::Integration;
-use super::decrypt_json;
+use super::connection_test::{IntegrationTestDetail, IntegrationTestResult};
+use super::{credentials_error, decrypt_json, provider_error};
 
 pub fn azure_routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -27,6 +31,7 @@ pub fn azure_routes() -> Router<Arc<AppState>> {
         .route("/subscriptions", get(list_azure_subscriptions))
         .route("/resources", get(list_azure_resources))
         .route("/security", get(get_azure_security_overview))
+        .route("/cost-alerts", post(receive_cost_alert))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -213,12 +218,12 @@ async fn list_azure_tenants(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let tenants = fetch_azure_tenants(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let tenants = fetch_azure_tenants(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(tenants))
 }
@@ -227,12 +232,12 @@ async fn list_azure_users(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let users = fetch_azure_users(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let users = fetch_azure_users(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(users))
 }
@@ -241,12 +246,12 @@ async fn list_azure_groups(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let groups = fetch_azure_groups(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let groups = fetch_azure_groups(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(groups))
 }
@@ -255,12 +260,12 @@ async fn list_azure_devices(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let devices = fetch_azure_devices(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let devices = fetch_azure_devices(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(devices))
 }
@@ -269,12 +274,12 @@ async fn list_azure_applications(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let applications = fetch_azure_applications(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let applications = fetch_azure_applications(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(applications))
 }
@@ -283,12 +288,12 @@ async fn list_azure_licenses(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let licenses = fetch_azure_licenses(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let licenses = fetch_azure_licenses(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(licenses))
 }
@@ -297,12 +302,12 @@ async fn list_azure_domains(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let domains = fetch_azure_domains(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let domains = fetch_azure_domains(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(domains))
 }
@@ -311,12 +316,12 @@ async fn list_azure_subscriptions(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let subscriptions = fetch_azure_subscriptions(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let subscriptions = fetch_azure_subscriptions(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(subscriptions))
 }
@@ -325,12 +330,12 @@ async fn list_azure_resources(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let resources = fetch_azure_resources(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let resources = fetch_azure_resources(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(resources))
 }
@@ -339,18 +344,65 @@ async fn get_azure_security_overview(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_azure_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_azure_client(&credentials)?;
-    let security_overview = fetch_azure_security_overview(&client, &credentials).await?;
+    let client = create_azure_client(&credentials).map_err(|e| provider_error("Azure", e))?;
+    let security_overview = fetch_azure_security_overview(&client, &credentials).await.map_err(|e| provider_error("Azure", e))?;
     
     Ok(Json(security_overview))
 }
 
 // Implementation functions
 
+#[derive(Debug, Deserialize)]
+pub struct CostAlertTokenQuery {
+    pub token: Option<String>,
+}
+
+/// Receive a budget alert from an Azure Monitor action group webhook.
+/// Azure can't sign these, so the action group is given a URL carrying
+/// `AZURE_COST_ALERT_TOKEN` as the `token` query parameter (or the
+/// `X-Webhook-Token` header). Without the variable set the endpoint
+/// doesn't exist.
+async fn receive_cost_alert(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CostAlertTokenQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Json<serde_json::Value>> {
+    let expected = std::env::var("AZURE_COST_ALERT_TOKEN").unwrap_or_default();
+    if expected.is_empty() {
+        return Err(ApiError::not_found("Azure cost alert endpoint"));
+    }
+
+    let provided = headers
+        .get("x-webhook-token")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or(query.token)
+        .unwrap_or_default();
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        return Err(ApiError::unauthorized("Invalid webhook token"));
+    }
+
+    let payload: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|_| ApiError::bad_request("Invalid Azure alert payload"))?;
+    let notification = azure_cost_alerts::parse_notification(&payload)
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let outcome = azure_cost_alerts::ingest(&state.db_pool, &payload, &notification, chrono::Utc::now())
+        .await
+        .map_err(|e| {
+            // Azure retries failed deliveries, which is what we want here
+            tracing::error!("Error handling Azure budget alert: {}", e);
+            ApiError::internal("Failed to handle Azure budget alert")
+        })?;
+
+    Ok(Json(serde_json::json!({ "received": true, "outcome": outcome })))
+}
+
 pub async fn sync_azure_integration(
     db_pool: &sqlx::PgPool,
     integration: &Integration,
@@ -420,66 +472,65 @@ pub async fn sync_azure_integration(
     Ok(serde_json::Value::Object(sync_results))
 }
 
-pub async fn test_azure_connection(
-    integration: &Integration,
-) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-    let credentials_json = decrypt_json(&integration.credentials)?;
-    let credentials: AzureCredentials = serde_json::from_value(credentials_json)?;
-    
-    let client = create_azure_client(&credentials)?;
-    
-    // Test connection by fetching organization info
-    let response = client
-        .get("https://graph.microsoft.com/v1.0/organization")
-        .bearer_auth(&get_access_token(&credentials).await?)
-        .send()
-        .await?;
-    
-    if response.status().is_success() {
+pub async fn test_azure_connection(integration: &Integration) -> IntegrationTestResult {
+    IntegrationTestResult::measure(|| async {
+        let credentials_json = decrypt_json(&integration.credentials).map_err(|e| e.to_string())?;
+        let credentials: AzureCredentials = serde_json::from_value(credentials_json)?;
+
+        let client = create_azure_client(&credentials)?;
+
+        // Test connection by fetching organization info
+        let response = client
+            .get("https://graph.microsoft.com/v1.0/organization")
+            .bearer_auth(&get_access_token(&credentials).await?)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}: {}", response.status(), response.text().await?).into());
+        }
+
         let org_info: serde_json::Value = response.json().await?;
-        Ok(serde_json::json!({
-            "status": "success",
-            "organization": org_info.get("value")
-                .and_then(|v| v.as_array())
-                .and_then(|arr| arr.first())
-                .and_then(|org| org.get("displayName"))
-        }))
-    } else {
-        Ok(serde_json::json!({
-            "status": "error",
-            "error": format!("HTTP {}: {}", response.status(), response.text().await?)
-        }))
-    }
+        let organization = org_info.get("value")
+            .and_then(|v| v.as_array())
+            .and_then(|arr| arr.first())
+            .and_then(|org| org.get("displayName"))
+            .and_then(|name| name.as_str())
+            .map(str::to_string);
+
+        Ok(IntegrationTestDetail::Azure { organization })
+    })
+    .await
 }
 
 // Helper functions
 
-fn get_integration_id(query: &serde_json::Value) -> Result<Uuid, StatusCode> {
+fn get_integration_id(query: &serde_json::Value) -> ApiResult<Uuid> {
     query.get("integration_id")
         .and_then(|v| v.as_str())
         .and_then(|s| Uuid::parse_str(s).ok())
-        .ok_or(StatusCode::BAD_REQUEST)
+        .ok_or_else(|| ApiError::bad_request("integration_id query parameter must be a valid UUID"))
 }
 
 async fn get_azure_credentials(
     db_pool: &sqlx::PgPool,
     integration_id: Uuid,
-) -> Result<AzureCredentials, StatusCode> {
+) -> ApiResult<AzureCredentials> {
     let integration = sqlx::query_as!(
         Integration,
-        "SELECT * FROM integrations WHERE id = $1 AND integration_type = 'azure' AND enabled = true",
+        "SELECT id, name, integration_type, config, credentials, enabled, last_sync, created_at, updated_at
+         FROM integrations WHERE id = $1 AND integration_type = 'azure' AND enabled = true",
         integration_id
     )
     .fetch_optional(db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("Azure integration"))?;
     
     let credentials_json = decrypt_json(&integration.credentials)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(credentials_error)?;
     
     serde_json::from_value(credentials_json)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        .map_err(credentials_error)
 }
 
 fn create_azure_client(credentials: &AzureCredentials) -> Result<reqwest::Client, Box<dyn std::error::Error + Send + Sync>> {
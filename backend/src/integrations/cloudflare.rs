@@ -1,6 +1,5 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
     response::{IntoResponse, Json},
     routing::{get, post},
     Router,
@@ -11,9 +10,11 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::services::cloudflare_dns_import::{self, CloudflareDnsClient};
+use crate::{AppState, ApiError, ApiResult};
 use resolve_shared::Integration;
-use super::decrypt_json;
+use super::connection_test::{IntegrationTestDetail, IntegrationTestResult};
+use super::{credentials_error, decrypt_json, provider_error};
 
 pub fn cloudflare_routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -363,12 +364,12 @@ async fn list_cloudflare_zones(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_cloudflare_client(&credentials)?;
-    let zones = fetch_cloudflare_zones(&client, &credentials).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let zones = fetch_cloudflare_zones(&client, &credentials).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(zones))
 }
@@ -377,15 +378,15 @@ async fn list_cloudflare_dns_records(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let zone_id = query.get("zone_id")
         .and_then(|v| v.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("zone_id query parameter is required"))?;
     
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
-    let client = create_cloudflare_client(&credentials)?;
-    let dns_records = fetch_cloudflare_dns_records(&client, &credentials, zone_id).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let dns_records = fetch_cloudflare_dns_records(&client, &credentials, zone_id).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(dns_records))
 }
@@ -394,15 +395,15 @@ async fn list_cloudflare_ssl_certificates(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let zone_id = query.get("zone_id")
         .and_then(|v| v.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("zone_id query parameter is required"))?;
     
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
-    let client = create_cloudflare_client(&credentials)?;
-    let certificates = fetch_cloudflare_ssl_certificates(&client, &credentials, zone_id).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let certificates = fetch_cloudflare_ssl_certificates(&client, &credentials, zone_id).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(certificates))
 }
@@ -411,15 +412,15 @@ async fn list_cloudflare_firewall_rules(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let zone_id = query.get("zone_id")
         .and_then(|v| v.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("zone_id query parameter is required"))?;
     
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
-    let client = create_cloudflare_client(&credentials)?;
-    let firewall_rules = fetch_cloudflare_firewall_rules(&client, &credentials, zone_id).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let firewall_rules = fetch_cloudflare_firewall_rules(&client, &credentials, zone_id).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(firewall_rules))
 }
@@ -428,15 +429,15 @@ async fn list_cloudflare_page_rules(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let zone_id = query.get("zone_id")
         .and_then(|v| v.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("zone_id query parameter is required"))?;
     
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
-    let client = create_cloudflare_client(&credentials)?;
-    let page_rules = fetch_cloudflare_page_rules(&client, &credentials, zone_id).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let page_rules = fetch_cloudflare_page_rules(&client, &credentials, zone_id).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(page_rules))
 }
@@ -445,12 +446,12 @@ async fn list_cloudflare_workers(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_cloudflare_client(&credentials)?;
-    let workers = fetch_cloudflare_workers(&client, &credentials).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let workers = fetch_cloudflare_workers(&client, &credentials).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(workers))
 }
@@ -459,15 +460,15 @@ async fn get_cloudflare_analytics(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let zone_id = query.get("zone_id")
         .and_then(|v| v.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("zone_id query parameter is required"))?;
     
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
-    let client = create_cloudflare_client(&credentials)?;
-    let analytics = fetch_cloudflare_analytics(&client, &credentials, zone_id).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let analytics = fetch_cloudflare_analytics(&client, &credentials, zone_id).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(analytics))
 }
@@ -476,15 +477,15 @@ async fn get_cloudflare_security_overview(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let zone_id = query.get("zone_id")
         .and_then(|v| v.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("zone_id query parameter is required"))?;
     
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
-    let client = create_cloudflare_client(&credentials)?;
-    let security = fetch_cloudflare_security_overview(&client, &credentials, zone_id).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let security = fetch_cloudflare_security_overview(&client, &credentials, zone_id).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(security))
 }
@@ -493,15 +494,15 @@ async fn get_cloudflare_cache_stats(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let zone_id = query.get("zone_id")
         .and_then(|v| v.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("zone_id query parameter is required"))?;
     
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
-    let client = create_cloudflare_client(&credentials)?;
-    let cache_stats = fetch_cloudflare_cache_stats(&client, &credentials, zone_id).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let cache_stats = fetch_cloudflare_cache_stats(&client, &credentials, zone_id).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(cache_stats))
 }
@@ -510,12 +511,12 @@ async fn list_cloudflare_load_balancers(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_cloudflare_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_cloudflare_client(&credentials)?;
-    let load_balancers = fetch_cloudflare_load_balancers(&client, &credentials).await?;
+    let client = create_cloudflare_client(&credentials).map_err(|e| provider_error("Cloudflare", e))?;
+    let load_balancers = fetch_cloudflare_load_balancers(&client, &credentials).await.map_err(|e| provider_error("Cloudflare", e))?;
     
     Ok(Json(load_balancers))
 }
@@ -530,31 +531,22 @@ pub async fn sync_cloudflare_integration(
     let credentials: CloudflareCredentials = serde_json::from_value(credentials_json)?;
     
     let client = create_cloudflare_client(&credentials)?;
+    let api_url = integration.config.get("api_url").and_then(|v| v.as_str());
+    let client_id = integration.config
+        .get("client_id")
+        .and_then(|v| v.as_str())
+        .and_then(|s| Uuid::parse_str(s).ok());
     let mut sync_results = serde_json::Map::new();
     
-    // Sync zones and their DNS records
-    match fetch_cloudflare_zones(&client, &credentials).await {
-        Ok(zones) => {
+    // Import zones and their DNS records into itdoc domains
+    let dns_client = CloudflareDnsClient::new(client, api_url);
+    match cloudflare_dns_import::import_zones(db_pool, &dns_client, client_id).await {
+        Ok(summary) => {
             sync_results.insert("zones".to_string(), serde_json::json!({
                 "status": "success",
-                "count": zones.len(),
+                "summary": summary,
                 "synced_at": chrono::Utc::now()
             }));
-            
-            // Auto-sync domains from Cloudflare to our domains table
-            for zone in &zones {
-                let _ = sqlx::query!(
-                    r#"
-                    INSERT INTO domains (id, client_id, name, registrar, nameservers, created_at, updated_at)
-                    VALUES (gen_random_uuid(), NULL, $1, 'Cloudflare', $2, NOW(), NOW())
-                    ON CONFLICT (name) DO UPDATE SET
-                        nameservers = $2,
-                        updated_at = NOW()
-                    "#,
-                    zone.name,
-                    &zone.name_servers
-                ).execute(db_pool).await;
-            }
         }
         Err(e) => {
             sync_results.insert("zones".to_string(), serde_json::json!({
@@ -567,63 +559,65 @@ pub async fn sync_cloudflare_integration(
     Ok(serde_json::Value::Object(sync_results))
 }
 
-pub async fn test_cloudflare_connection(
-    integration: &Integration,
-) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-    let credentials_json = decrypt_json(&integration.credentials)?;
-    let credentials: CloudflareCredentials = serde_json::from_value(credentials_json)?;
-    
-    let client = create_cloudflare_client(&credentials)?;
-    
-    // Test connection by fetching account info
-    let response = client
-        .get("https://api.cloudflare.com/client/v4/user")
-        .bearer_auth(&credentials.api_token)
-        .send()
-        .await?;
-    
-    if response.status().is_success() {
+pub async fn test_cloudflare_connection(integration: &Integration) -> IntegrationTestResult {
+    IntegrationTestResult::measure(|| async {
+        let credentials_json = decrypt_json(&integration.credentials).map_err(|e| e.to_string())?;
+        let credentials: CloudflareCredentials = serde_json::from_value(credentials_json)?;
+
+        let client = create_cloudflare_client(&credentials)?;
+
+        // Test connection by fetching account info
+        let response = client
+            .get("https://api.cloudflare.com/client/v4/user")
+            .bearer_auth(&credentials.api_token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}: {}", response.status(), response.text().await?).into());
+        }
+
         let user_info: serde_json::Value = response.json().await?;
-        Ok(serde_json::json!({
-            "status": "success",
-            "user": user_info.get("result")
-        }))
-    } else {
-        Ok(serde_json::json!({
-            "status": "error",
-            "error": format!("HTTP {}: {}", response.status(), response.text().await?)
-        }))
-    }
+        let field = |name: &str| {
+            user_info.get("result")
+                .and_then(|user| user.get(name))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+
+        Ok(IntegrationTestDetail::Cloudflare { user_id: field("id"), email: field("email") })
+    })
+    .await
 }
 
 // Helper functions
 
-fn get_integration_id(query: &serde_json::Value) -> Result<Uuid, StatusCode> {
+fn get_integration_id(query: &serde_json::Value) -> ApiResult<Uuid> {
     query.get("integration_id")
         .and_then(|v| v.as_str())
         .and_then(|s| Uuid::parse_str(s).ok())
-        .ok_or(StatusCode::BAD_REQUEST)
+        .ok_or_else(|| ApiError::bad_request("integration_id query parameter must be a valid UUID"))
 }
 
 async fn get_cloudflare_credentials(
     db_pool: &sqlx::PgPool,
     integration_id: Uuid,
-) -> Result<CloudflareCredentials, StatusCode> {
+) -> ApiResult<CloudflareCredentials> {
     let integration = sqlx::query_as!(
         Integration,
-        "SELECT * FROM integrations WHERE id = $1 AND integration_type = 'cloudflare' AND enabled = true",
+        "SELECT id, name, integration_type, config, credentials, enabled, last_sync, created_at, updated_at
+         FROM integrations WHERE id = $1 AND integration_type = 'cloudflare' AND enabled = true",
         integration_id
     )
     .fetch_optional(db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("Cloudflare integration"))?;
     
     let credentials_json = decrypt_json(&integration.credentials)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(credentials_error)?;
     
     serde_json::from_value(credentials_json)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        .map_err(credentials_error)
 }
 
 fn create_cloudflare_client(credentials: &CloudflareCredentials) -> Result<reqwest::Client, Box<dyn std::error::Error + Send + Sync>> {
@@ -0,0 +1,147 @@
+//! Integration connection tests
+//!
+//! Every provider's `test_*_connection` reports an [`IntegrationTestResult`].
+//! The last result is kept on the integration row and served again for
+//! [`TEST_RESULT_TTL`] so repeated checks don't hit the provider each time.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::future::Future;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// How long a stored result is returned instead of testing again
+pub const TEST_RESULT_TTL: Duration = Duration::minutes(5);
+
+/// What a successful test learned from the provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum IntegrationTestDetail {
+    Azure { organization: Option<String> },
+    Cloudflare { user_id: Option<String>, email: Option<String> },
+    Github,
+    Google,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrationTestResult {
+    pub ok: bool,
+    pub latency_ms: u64,
+    pub detail: Option<IntegrationTestDetail>,
+    pub error: Option<String>,
+    pub tested_at: DateTime<Utc>,
+    /// Whether this is a stored result rather than a fresh test
+    #[serde(default)]
+    pub cached: bool,
+}
+
+impl IntegrationTestResult {
+    /// Run a provider check, timing it and folding any error into the result
+    pub async fn measure<F, Fut>(check: F) -> Self
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<IntegrationTestDetail, Box<dyn std::error::Error + Send + Sync>>>,
+    {
+        let started = Instant::now();
+        let outcome = check().await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match outcome {
+            Ok(detail) => Self {
+                ok: true,
+                latency_ms,
+                detail: Some(detail),
+                error: None,
+                tested_at: Utc::now(),
+                cached: false,
+            },
+            Err(e) => Self { latency_ms, ..Self::failed(e.to_string()) },
+        }
+    }
+
+    /// A test that failed before reaching the provider
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            latency_ms: 0,
+            detail: None,
+            error: Some(error.into()),
+            tested_at: Utc::now(),
+            cached: false,
+        }
+    }
+
+    /// Whether a stored result is recent enough to return at `now`
+    pub fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        now.signed_duration_since(self.tested_at) < TEST_RESULT_TTL
+    }
+}
+
+/// The stored result for an integration, if it is still within the TTL
+pub async fn cached_result(
+    pool: &PgPool,
+    integration_id: Uuid,
+) -> Result<Option<IntegrationTestResult>, sqlx::Error> {
+    let stored: Option<sqlx::types::Json<IntegrationTestResult>> = sqlx::query_scalar(
+        "SELECT last_test_result FROM integrations WHERE id = $1 AND last_test_result IS NOT NULL",
+    )
+    .bind(integration_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(stored
+        .map(|json| json.0)
+        .filter(|result| result.is_fresh(Utc::now()))
+        .map(|result| IntegrationTestResult { cached: true, ..result }))
+}
+
+/// Keep `result` as the integration's last test result
+pub async fn store_result(
+    pool: &PgPool,
+    integration_id: Uuid,
+    result: &IntegrationTestResult,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE integrations SET last_test_result = $2, last_tested_at = $3 WHERE id = $1")
+        .bind(integration_id)
+        .bind(sqlx::types::Json(result))
+        .bind(result.tested_at)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_measure_folds_errors_into_result() {
+        let ok = IntegrationTestResult::measure(|| async { Ok(IntegrationTestDetail::Github) }).await;
+        assert!(ok.ok);
+        assert_eq!(ok.detail, Some(IntegrationTestDetail::Github));
+        assert!(ok.error.is_none());
+
+        let failed = IntegrationTestResult::measure(|| async { Err("HTTP 401: bad token".into()) }).await;
+        assert!(!failed.ok);
+        assert!(failed.detail.is_none());
+        assert_eq!(failed.error.as_deref(), Some("HTTP 401: bad token"));
+    }
+
+    #[test]
+    fn test_freshness_window() {
+        let result = IntegrationTestResult::failed("timeout");
+        assert!(result.is_fresh(result.tested_at + Duration::minutes(4)));
+        assert!(!result.is_fresh(result.tested_at + TEST_RESULT_TTL));
+    }
+
+    #[test]
+    fn test_detail_is_tagged_by_provider() {
+        let detail = IntegrationTestDetail::Azure { organization: Some("Contoso".to_string()) };
+        assert_eq!(
+            serde_json::to_value(&detail).unwrap(),
+            serde_json::json!({ "provider": "azure", "organization": "Contoso" })
+        );
+    }
+}
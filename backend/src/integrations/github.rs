@@ -10,9 +10,11 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::services::github_issues::{self, GitHubError, GitHubIssueClient};
+use crate::{AppState, ApiError, ApiResult, AppError};
 use resolve_shared::Integration;
-use super::decrypt_json;
+use super::connection_test::{IntegrationTestDetail, IntegrationTestResult};
+use super::{credentials_error, decrypt_json, provider_error};
 
 pub fn github_routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -23,6 +25,13 @@ pub fn github_routes() -> Router<Arc<AppState>> {
         .route("/pull_requests", get(list_github_pull_requests))
         .route("/actions", get(list_github_actions))
         .route("/security", get(get_github_security_overview))
+        .route("/issues/push", post(push_ticket_to_github))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PushTicketRequest {
+    pub integration_id: Uuid,
+    pub ticket_id: Uuid,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,81 +68,151 @@ async fn list_github_repositories(
     State(state): State<Arc<AppState>>,
     Query(query): Query<serde_json::Value>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integration_id = get_integration_id(&query)?;
     let credentials = get_github_credentials(&state.db_pool, integration_id).await?;
     
-    let client = create_github_client(&credentials)?;
-    let repositories = fetch_github_repositories(&client, &credentials).await?;
+    let client = create_github_client(&credentials).map_err(|e| provider_error("GitHub", e))?;
+    let repositories = fetch_github_repositories(&client, &credentials).await.map_err(|e| provider_error("GitHub", e))?;
     
     Ok(Json(repositories))
 }
 
 // Placeholder implementations
-async fn list_github_organizations(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_github_organizations(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_github_users(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_github_users(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_github_issues(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_github_issues(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_github_pull_requests(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_github_pull_requests(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_github_actions(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_github_actions(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn get_github_security_overview(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn get_github_security_overview(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!({})))
 }
 
+async fn push_ticket_to_github(
+    State(state): State<Arc<AppState>>,
+    auth: AuthUser,
+    Json(req): Json<PushTicketRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let integration = sqlx::query_as!(
+        Integration,
+        r#"
+        SELECT id, name, integration_type, config, credentials, enabled as "enabled!",
+               last_sync, created_at as "created_at!", updated_at
+        FROM integrations
+        WHERE id = $1 AND integration_type = 'github' AND enabled = true
+        "#,
+        req.integration_id
+    )
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found("GitHub integration"))?;
+
+    let repository = github_issues::configured_repository(&integration.config)
+        .ok_or_else(|| ApiError::bad_request("No repository configured for the GitHub integration"))?;
+    let client = issue_client(&integration).map_err(|e| {
+        tracing::error!("Failed to set up GitHub client: {}", e);
+        ApiError::internal("Failed to set up GitHub client")
+    })?;
+
+    let external_ref = github_issues::push_ticket(
+        &state.db_pool,
+        &client,
+        integration.id,
+        &repository,
+        req.ticket_id,
+        Some(auth.0.id),
+    )
+    .await
+    .map_err(|e| {
+        tracing::warn!("Failed to push ticket {} to GitHub: {}", req.ticket_id, e);
+        match e {
+            GitHubError::TicketNotFound => ApiError::not_found("Ticket"),
+            GitHubError::AlreadyLinked(issue) => ApiError::conflict(format!("Ticket is already linked to {}", issue)),
+            GitHubError::RateLimited { reset_at } => AppError::TooManyRequests {
+                retry_after: reset_at
+                    .map(|t| (t - chrono::Utc::now()).num_seconds().max(1) as u64)
+                    .unwrap_or(60),
+            },
+            GitHubError::Api { .. } | GitHubError::Http(_) => {
+                AppError::ExternalServiceError { service: "GitHub".to_string(), message: e.to_string() }
+            }
+            _ => ApiError::internal("Failed to push ticket to GitHub"),
+        }
+    })?;
+
+    Ok((StatusCode::CREATED, Json(external_ref)))
+}
+
+fn issue_client(integration: &Integration) -> Result<GitHubIssueClient, Box<dyn std::error::Error + Send + Sync>> {
+    let credentials: GitHubCredentials = serde_json::from_value(
+        decrypt_json(&integration.credentials).map_err(|e| e.to_string())?,
+    )?;
+    let api_url = integration.config.get("api_url").and_then(|v| v.as_str());
+
+    Ok(GitHubIssueClient::new(credentials.token, api_url)?)
+}
+
 pub async fn sync_github_integration(
-    _db_pool: &sqlx::PgPool,
-    _integration: &Integration,
+    db_pool: &sqlx::PgPool,
+    integration: &Integration,
 ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(serde_json::json!({"status": "placeholder"}))
+    let client = issue_client(integration)?;
+    let issues = github_issues::sync_issue_status(db_pool, &client, integration.id).await?;
+
+    Ok(serde_json::json!({ "issues": issues }))
 }
 
-pub async fn test_github_connection(
-    _integration: &Integration,
-) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(serde_json::json!({"status": "placeholder"}))
+pub async fn test_github_connection(integration: &Integration) -> IntegrationTestResult {
+    IntegrationTestResult::measure(|| async {
+        issue_client(integration)?.check_token().await?;
+        Ok(IntegrationTestDetail::Github)
+    })
+    .await
 }
 
 // Helper functions
-fn get_integration_id(query: &serde_json::Value) -> Result<Uuid, StatusCode> {
+fn get_integration_id(query: &serde_json::Value) -> ApiResult<Uuid> {
     query.get("integration_id")
         .and_then(|v| v.as_str())
         .and_then(|s| Uuid::parse_str(s).ok())
-        .ok_or(StatusCode::BAD_REQUEST)
+        .ok_or_else(|| ApiError::bad_request("integration_id query parameter must be a valid UUID"))
 }
 
 async fn get_github_credentials(
     db_pool: &sqlx::PgPool,
     integration_id: Uuid,
-) -> Result<GitHubCredentials, StatusCode> {
+) -> ApiResult<GitHubCredentials> {
     let integration = sqlx::query_as!(
         Integration,
-        "SELECT * FROM integrations WHERE id = $1 AND integration_type = 'github' AND enabled = true",
+        r#"SELECT id, name, integration_type, config, credentials, enabled as "enabled!", last_sync,
+                  created_at as "created_at!", updated_at
+         FROM integrations WHERE id = $1 AND integration_type = 'github' AND enabled = true"#,
         integration_id
     )
     .fetch_optional(db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("GitHub integration"))?;
     
     let credentials_json = decrypt_json(&integration.credentials)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(credentials_error)?;
     
     serde_json::from_value(credentials_json)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        .map_err(credentials_error)
 }
 
 fn create_github_client(_credentials: &GitHubCredentials) -> Result<reqwest::Client, Box<dyn std::error::Error + Send + Sync>> {
@@ -1,6 +1,5 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
     response::{IntoResponse, Json},
     routing::{get, post},
     Router,
@@ -10,8 +9,9 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::{AppState, ApiResult};
 use resolve_shared::Integration;
+use super::connection_test::IntegrationTestResult;
 use super::decrypt_json;
 
 pub fn google_routes() -> Router<Arc<AppState>> {
@@ -32,27 +32,27 @@ pub struct GoogleCredentials {
     pub domain: Option<String>,
 }
 
-async fn list_google_workspace_users(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_google_workspace_users(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_google_workspace_groups(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_google_workspace_groups(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_google_workspace_domains(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_google_workspace_domains(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_google_cloud_projects(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_google_cloud_projects(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_google_cloud_resources(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_google_cloud_resources(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_google_drive_files(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_google_drive_files(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
@@ -63,8 +63,7 @@ pub async fn sync_google_integration(
     Ok(serde_json::json!({"status": "placeholder"}))
 }
 
-pub async fn test_google_connection(
-    _integration: &Integration,
-) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(serde_json::json!({"status": "placeholder"}))
+/// The Google integration has nothing to call yet, so a test can't pass
+pub async fn test_google_connection(_integration: &Integration) -> IntegrationTestResult {
+    IntegrationTestResult::failed("connection test not implemented")
 }
\ No newline at end of file
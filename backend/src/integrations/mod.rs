@@ -1,12 +1,12 @@
 pub mod azure;
 pub mod cloudflare;
+pub mod connection_test;
 pub mod github;
 pub mod google;
 pub mod stripe;
 
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
     response::{IntoResponse, Json},
     routing::{get, post, put, delete},
     Router,
@@ -14,11 +14,13 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
-use aes_gcm::{Aes256Gcm, Key};
 
+use crate::audit::{self, AuditEvent, RequestMeta};
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::keyring::{KeyPurpose, KeyRing};
+use crate::{AppState, ApiError, ApiResult, AppError};
 use resolve_shared::Integration;
+use connection_test::IntegrationTestResult;
 
 pub fn integration_routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -48,7 +50,7 @@ pub struct CreateIntegrationRequest {
 async fn list_integrations(
     State(state): State<Arc<AppState>>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let integrations = sqlx::query_as!(
         Integration,
         r#"
@@ -59,8 +61,7 @@ async fn list_integrations(
         "#
     )
     .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // Remove sensitive credential data before returning
     let safe_integrations: Vec<_> = integrations.into_iter().map(|mut integration| {
@@ -75,7 +76,13 @@ async fn get_integration(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
+    Ok(Json(fetch_integration(&state, id).await?))
+}
+
+/// An integration with its credentials replaced by whether any are set, as
+/// returned by the API and recorded in the audit log
+async fn fetch_integration(state: &AppState, id: Uuid) -> ApiResult<Integration> {
     let mut integration = sqlx::query_as!(
         Integration,
         r#"
@@ -87,26 +94,25 @@ async fn get_integration(
         id
     )
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("Integration"))?;
 
     // Remove sensitive credential data
     integration.credentials = serde_json::json!({ "configured": !integration.credentials.is_null() });
 
-    Ok(Json(integration))
+    Ok(integration)
 }
 
 async fn create_integration(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateIntegrationRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let id = Uuid::new_v4();
 
     // Encrypt credentials before storing
-    let encrypted_credentials = encrypt_json(&req.credentials)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let encrypted_credentials = encrypt_json(&req.credentials).map_err(credentials_error)?;
 
     sqlx::query!(
         r#"
@@ -124,11 +130,10 @@ async fn create_integration(
         req.enabled
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // Log the creation
-    log_audit_action(&state.db_pool, auth.0.id, "CREATE", "integration", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "CREATE", "integration", id)).await;
 
     Ok(Json(serde_json::json!({ 
         "id": id, 
@@ -140,30 +145,32 @@ async fn update_integration(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateIntegrationRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
+    let before = fetch_integration(&state, id).await?;
+
     // Get current integration for credential handling
     let current = sqlx::query!(
         "SELECT credentials FROM integrations WHERE id = $1",
         id
     )
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("Integration"))?;
 
     // Handle credential encryption
     let encrypted_credentials = if req.credentials.get("configured").is_some() {
         current.credentials // Keep existing if placeholder
     } else {
-        encrypt_json(&req.credentials).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        encrypt_json(&req.credentials).map_err(credentials_error)?
     };
 
     let result = sqlx::query!(
         r#"
         UPDATE integrations SET
             name = $2, integration_type = $3, config = $4, credentials = $5,
-            enabled = $6, updated_at = NOW()
+            enabled = $6, updated_at = NOW(), last_test_result = NULL, last_tested_at = NULL
         WHERE id = $1
         "#,
         id,
@@ -174,14 +181,19 @@ async fn update_integration(
         req.enabled
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::not_found("Integration"));
     }
 
-    log_audit_action(&state.db_pool, auth.0.id, "UPDATE", "integration", id).await;
+    let after = fetch_integration(&state, id).await?;
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "UPDATE", "integration", id).before(&before).after(&after),
+    )
+    .await;
 
     Ok(Json(serde_json::json!({ "message": "Integration updated successfully" })))
 }
@@ -190,17 +202,19 @@ async fn delete_integration(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+    meta: RequestMeta,
+) -> ApiResult<impl IntoResponse> {
+    let before = fetch_integration(&state, id).await?;
+
     let result = sqlx::query!("DELETE FROM integrations WHERE id = $1", id)
         .execute(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::not_found("Integration"));
     }
 
-    log_audit_action(&state.db_pool, auth.0.id, "DELETE", "integration", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "DELETE", "integration", id).before(&before)).await;
 
     Ok(Json(serde_json::json!({ "message": "Integration deleted successfully" })))
 }
@@ -209,7 +223,8 @@ async fn sync_integration(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+    meta: RequestMeta,
+) -> ApiResult<impl IntoResponse> {
     let integration = sqlx::query_as!(
         Integration,
         r#"
@@ -221,13 +236,13 @@ async fn sync_integration(
         id
     )
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("Integration"))?;
 
     let sync_result = match integration.integration_type.as_str() {
         "azure" => azure::sync_azure_integration(&state.db_pool, &integration).await,
         "cloudflare" => cloudflare::sync_cloudflare_integration(&state.db_pool, &integration).await,
+        "forticloud" => crate::handlers::forticloud::sync_forticloud_integration(&state.db_pool, &integration).await,
         "github" => github::sync_github_integration(&state.db_pool, &integration).await,
         "google" => google::sync_google_integration(&state.db_pool, &integration).await,
         _ => Err("Unsupported integration type".into()),
@@ -241,10 +256,9 @@ async fn sync_integration(
                 id
             )
             .execute(&state.db_pool)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
 
-            log_audit_action(&state.db_pool, auth.0.id, "SYNC", "integration", id).await;
+            audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "SYNC", "integration", id)).await;
 
             Ok(Json(serde_json::json!({
                 "message": "Integration synchronized successfully",
@@ -253,16 +267,24 @@ async fn sync_integration(
         }
         Err(error) => {
             tracing::error!("Integration sync failed: {}", error);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal("Integration sync failed"))
         }
     }
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct TestIntegrationQuery {
+    /// Test against the provider even if a recent result is stored
+    #[serde(default)]
+    pub force: bool,
+}
+
 async fn test_integration(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
+    Query(query): Query<TestIntegrationQuery>,
     _auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<Json<IntegrationTestResult>> {
     let integration = sqlx::query_as!(
         Integration,
         r#"
@@ -274,119 +296,52 @@ async fn test_integration(
         id
     )
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or_else(|| ApiError::not_found("Integration"))?;
 
-    let test_result = match integration.integration_type.as_str() {
+    if !query.force {
+        if let Some(cached) = connection_test::cached_result(&state.db_pool, id).await? {
+            return Ok(Json(cached));
+        }
+    }
+
+    let result = match integration.integration_type.as_str() {
         "azure" => azure::test_azure_connection(&integration).await,
         "cloudflare" => cloudflare::test_cloudflare_connection(&integration).await,
         "github" => github::test_github_connection(&integration).await,
         "google" => google::test_google_connection(&integration).await,
-        _ => Err("Unsupported integration type".into()),
+        _ => IntegrationTestResult::failed("Unsupported integration type"),
     };
 
-    match test_result {
-        Ok(test_info) => Ok(Json(serde_json::json!({
-            "status": "success",
-            "message": "Integration test successful",
-            "test_info": test_info
-        }))),
-        Err(error) => Ok(Json(serde_json::json!({
-            "status": "error",
-            "message": "Integration test failed",
-            "error": error.to_string()
-        })))
-    }
+    connection_test::store_result(&state.db_pool, id, &result).await?;
+
+    Ok(Json(result))
 }
 
-fn encrypt_json(data: &serde_json::Value) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-    use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit}};
-    use rand::RngCore;
+/// Credentials that can't be encrypted, decrypted or parsed
+fn credentials_error(e: impl std::fmt::Display) -> AppError {
+    tracing::error!("Integration credentials error: {}", e);
+    ApiError::internal("Failed to process integration credentials")
+}
 
+/// A failed call to a provider's API
+fn provider_error(provider: &str, e: Box<dyn std::error::Error + Send + Sync>) -> AppError {
+    AppError::ExternalServiceError { service: provider.to_string(), message: e.to_string() }
+}
+
+fn encrypt_json(data: &serde_json::Value) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
     let json_str = serde_json::to_string(data)?;
-    
-    let key = get_integration_encryption_key();
-    let cipher = Aes256Gcm::new(&key);
-    
-    let mut nonce_bytes = [0u8; 12];
-    rand::thread_rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    
-    let ciphertext = cipher.encrypt(nonce, json_str.as_bytes())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
-    
-    // Combine nonce and ciphertext
-    let mut encrypted = Vec::with_capacity(12 + ciphertext.len());
-    encrypted.extend_from_slice(&nonce_bytes);
-    encrypted.extend_from_slice(&ciphertext);
-    
-    let encrypted_b64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &encrypted);
-    
-    Ok(serde_json::json!({ "encrypted": encrypted_b64 }))
+    let encrypted = KeyRing::from_env(KeyPurpose::Integration)?.encrypt_str(&json_str)?;
+
+    Ok(serde_json::json!({ "encrypted": encrypted }))
 }
 
 pub fn decrypt_json(encrypted_data: &serde_json::Value) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-    use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit}};
-
     let encrypted_str = encrypted_data.get("encrypted")
         .and_then(|v| v.as_str())
         .ok_or("Invalid encrypted data format")?;
-    
-    let encrypted = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encrypted_str)?;
-    
-    if encrypted.len() < 12 {
-        return Err("Invalid encrypted data".into());
-    }
-    
-    let (nonce_bytes, ciphertext) = encrypted.split_at(12);
-    let nonce = Nonce::from_slice(nonce_bytes);
-    
-    let key = get_integration_encryption_key();
-    let cipher = Aes256Gcm::new(&key);
-    
-    let plaintext = cipher.decrypt(nonce, ciphertext)
-        .map_err(|e| format!("Decryption failed: {}", e))?;
-    let json_str = String::from_utf8(plaintext)?;
-    
-    Ok(serde_json::from_str(&json_str)?)
-}
 
-fn get_integration_encryption_key() -> Key<Aes256Gcm> {
-    use aes_gcm::Key;
-    
-    let key_env = std::env::var("INTEGRATION_ENCRYPTION_KEY").unwrap_or_else(|_| {
-        tracing::warn!("INTEGRATION_ENCRYPTION_KEY not set, using default (insecure for production)");
-        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef".to_string()
-    });
-    
-    let key_bytes = hex::decode(&key_env).unwrap_or_else(|_| {
-        tracing::error!("Invalid INTEGRATION_ENCRYPTION_KEY format, using default");
-        vec![0u8; 32]
-    });
-    
-    if key_bytes.len() != 32 {
-        tracing::error!("INTEGRATION_ENCRYPTION_KEY must be 32 bytes (64 hex chars), using default");
-        return *Key::<Aes256Gcm>::from_slice(&[0u8; 32]);
-    }
-    
-    *Key::<Aes256Gcm>::from_slice(&key_bytes)
-}
+    let json_str = KeyRing::from_env(KeyPurpose::Integration)?.decrypt_str(encrypted_str)?;
 
-async fn log_audit_action(
-    db_pool: &sqlx::PgPool,
-    user_id: Uuid,
-    action: &str,
-    entity_type: &str,
-    entity_id: Uuid,
-) {
-    let _ = sqlx::query!(
-        r#"
-        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        user_id, action, entity_type, entity_id
-    )
-    .execute(db_pool)
-    .await;
-}
\ No newline at end of file
+    Ok(serde_json::from_str(&json_str)?)
+}
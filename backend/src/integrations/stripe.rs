@@ -1,6 +1,7 @@
 use axum::{
+    body::Bytes,
     extract::{Query, State},
-    http::StatusCode,
+    http::HeaderMap,
     response::{IntoResponse, Json},
     routing::{get, post},
     Router,
@@ -10,7 +11,8 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::{AppState, ApiError, ApiResult};
+use crate::services::stripe_payments::{self, StripeEvent};
 use resolve_shared::Integration;
 use super::decrypt_json;
 
@@ -22,39 +24,78 @@ pub fn stripe_routes() -> Router<Arc<AppState>> {
         .route("/payments", get(list_stripe_payments))
         .route("/products", get(list_stripe_products))
         .route("/balance", get(get_stripe_balance))
+        .route("/webhook", post(handle_stripe_webhook))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StripeCredentials {
     pub secret_key: String,
     pub publishable_key: String,
     pub webhook_endpoint_secret: Option<String>,
 }
 
-async fn list_stripe_customers(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_stripe_customers(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_stripe_subscriptions(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_stripe_subscriptions(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_stripe_invoices(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_stripe_invoices(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_stripe_payments(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_stripe_payments(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn list_stripe_products(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn list_stripe_products(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!([])))
 }
 
-async fn get_stripe_balance(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> Result<impl IntoResponse, StatusCode> {
+async fn get_stripe_balance(_state: State<Arc<AppState>>, _query: Query<serde_json::Value>, _auth: AuthUser) -> ApiResult<impl IntoResponse> {
     Ok(Json(serde_json::json!({})))
 }
 
+/// Stripe webhook receiver. Unauthenticated; requests must carry a valid
+/// `Stripe-Signature` for the integration's webhook endpoint secret.
+async fn handle_stripe_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<impl IntoResponse> {
+    let settings = stripe_payments::load_settings(&state.db_pool).await.map_err(|e| {
+        tracing::warn!("Rejecting Stripe webhook: {}", e);
+        ApiError::unavailable("Stripe integration is not configured")
+    })?;
+
+    let secret = settings.credentials.webhook_endpoint_secret.ok_or_else(|| {
+        tracing::warn!("Rejecting Stripe webhook: no webhook endpoint secret configured");
+        ApiError::unavailable("Stripe webhook endpoint secret is not configured")
+    })?;
+
+    let signature = headers
+        .get("stripe-signature")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| ApiError::bad_request("Missing Stripe-Signature header"))?;
+
+    stripe_payments::verify_signature(&body, signature, &secret, chrono::Utc::now().timestamp()).map_err(|e| {
+        tracing::warn!("Rejecting Stripe webhook: {}", e);
+        ApiError::bad_request("Invalid Stripe signature")
+    })?;
+
+    let event: StripeEvent = serde_json::from_slice(&body).map_err(|_| ApiError::bad_request("Invalid Stripe event payload"))?;
+
+    let outcome = stripe_payments::handle_event(&state.db_pool, &event).await.map_err(|e| {
+        // Stripe retries non-2xx responses, which is what we want here
+        tracing::error!("Error handling Stripe event {}: {}", event.id, e);
+        ApiError::internal("Failed to handle Stripe event")
+    })?;
+
+    Ok(Json(serde_json::json!({ "received": true, "outcome": outcome.as_str() })))
+}
+
 pub async fn sync_stripe_integration(
     _db_pool: &sqlx::PgPool,
     _integration: &Integration,
@@ -8,9 +8,10 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
-use aes_gcm::{Aes256Gcm, Key};
 
+use crate::audit::{self, AuditEvent, RequestMeta};
 use crate::auth::middleware::AuthUser;
+use crate::keyring::{KeyPurpose, KeyRing};
 use crate::AppState;
 use resolve_shared::Credential;
 
@@ -19,6 +20,8 @@ pub fn credential_routes() -> Router<Arc<AppState>> {
         .route("/", get(list_credentials).post(create_credential))
         .route("/:id", get(get_credential).put(update_credential).delete(delete_credential))
         .route("/:id/access", post(record_credential_access))
+        .route("/:id/history", get(get_credential_history))
+        .route("/:id/rotate", post(rotate_credential))
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,6 +48,26 @@ pub struct CreateCredentialRequest {
     pub notes: Option<String>,
     pub tags: Option<Vec<String>>,
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub rotation_interval_days: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateCredentialRequest {
+    pub password: String,
+    pub notes: Option<String>,
+}
+
+/// A password change. The replaced value is never returned, only whether a
+/// fingerprint of it was kept.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct CredentialHistoryEntry {
+    pub id: Uuid,
+    pub change_type: String,
+    pub has_previous_value: bool,
+    pub notes: Option<String>,
+    pub changed_by: Option<Uuid>,
+    pub changed_by_name: Option<String>,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
 }
 
 async fn list_credentials(
@@ -101,7 +124,7 @@ async fn list_credentials(
             r#"
             SELECT id, client_id, asset_id, name, username, password, private_key,
                    public_key, certificate, uri, notes, tags, last_accessed,
-                   expires_at, created_at, updated_at
+                   expires_at, rotation_interval_days, rotation_flagged_at, created_at, updated_at
             FROM credentials
             WHERE client_id = $1
             ORDER BY name ASC
@@ -120,7 +143,7 @@ async fn list_credentials(
             r#"
             SELECT id, client_id, asset_id, name, username, password, private_key,
                    public_key, certificate, uri, notes, tags, last_accessed,
-                   expires_at, created_at, updated_at
+                   expires_at, rotation_interval_days, rotation_flagged_at, created_at, updated_at
             FROM credentials
             ORDER BY name ASC
             LIMIT $1 OFFSET $2
@@ -153,7 +176,7 @@ async fn get_credential(
         r#"
         SELECT id, client_id, asset_id, name, username, password, private_key,
                public_key, certificate, uri, notes, tags, last_accessed,
-               expires_at, created_at, updated_at
+               expires_at, rotation_interval_days, rotation_flagged_at, created_at, updated_at
         FROM credentials
         WHERE id = $1
         "#,
@@ -176,6 +199,7 @@ async fn get_credential(
 async fn create_credential(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateCredentialRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let id = Uuid::new_v4();
@@ -200,10 +224,10 @@ async fn create_credential(
         INSERT INTO credentials (
             id, client_id, asset_id, name, username, password, private_key,
             public_key, certificate, uri, notes, tags, expires_at,
-            created_at, updated_at
+            rotation_interval_days, created_at, updated_at
         )
         VALUES (
-            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, NOW(), NULL
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, NOW(), NULL
         )
         "#,
         id,
@@ -218,14 +242,21 @@ async fn create_credential(
         req.uri,
         req.notes,
         &tags,
-        req.expires_at
+        req.expires_at,
+        req.rotation_interval_days
     )
     .execute(&state.db_pool)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if encrypted_password.is_some() {
+        record_password_change(&state.db_pool, id, "created", None, None, auth.0.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
     // Log the creation in audit log
-    log_audit_action(&state.db_pool, auth.0.id, "CREATE", "credential", id, None, None).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "CREATE", "credential", id)).await;
 
     Ok(Json(serde_json::json!({ "id": id, "message": "Credential created successfully" })))
 }
@@ -234,6 +265,7 @@ async fn update_credential(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateCredentialRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     // Get the current credential for audit logging
@@ -247,6 +279,9 @@ async fn update_credential(
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     .ok_or(StatusCode::NOT_FOUND)?;
 
+    let password_changed = req.password.as_deref().is_some_and(|p| p != "***ENCRYPTED***");
+    let previous_password = current.password.clone();
+
     // Encrypt sensitive data if provided
     let encrypted_password = if let Some(password) = &req.password {
         if password != "***ENCRYPTED***" {
@@ -275,7 +310,8 @@ async fn update_credential(
         UPDATE credentials SET
             client_id = $2, asset_id = $3, name = $4, username = $5,
             password = $6, private_key = $7, public_key = $8, certificate = $9,
-            uri = $10, notes = $11, tags = $12, expires_at = $13, updated_at = NOW()
+            uri = $10, notes = $11, tags = $12, expires_at = $13,
+            rotation_interval_days = $14, updated_at = NOW()
         WHERE id = $1
         "#,
         id,
@@ -290,14 +326,22 @@ async fn update_credential(
         req.uri,
         req.notes,
         &tags,
-        req.expires_at
+        req.expires_at,
+        req.rotation_interval_days
     )
     .execute(&state.db_pool)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if password_changed {
+        let old_value_hash = previous_password.as_deref().and_then(fingerprint_encrypted);
+        record_password_change(&state.db_pool, id, "updated", old_value_hash, None, auth.0.id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
     // Log the update in audit log
-    log_audit_action(&state.db_pool, auth.0.id, "UPDATE", "credential", id, None, None).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UPDATE", "credential", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "Credential updated successfully" })))
 }
@@ -306,6 +350,7 @@ async fn delete_credential(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
 ) -> Result<impl IntoResponse, StatusCode> {
     let result = sqlx::query!("DELETE FROM credentials WHERE id = $1", id)
         .execute(&state.db_pool)
@@ -317,7 +362,7 @@ async fn delete_credential(
     }
 
     // Log the deletion in audit log
-    log_audit_action(&state.db_pool, auth.0.id, "DELETE", "credential", id, None, None).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "DELETE", "credential", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "Credential deleted successfully" })))
 }
@@ -326,6 +371,7 @@ async fn record_credential_access(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
 ) -> Result<impl IntoResponse, StatusCode> {
     sqlx::query!(
         "UPDATE credentials SET last_accessed = NOW() WHERE id = $1",
@@ -336,77 +382,206 @@ async fn record_credential_access(
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Log the access in audit log
-    log_audit_action(&state.db_pool, auth.0.id, "ACCESS", "credential", id, None, None).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "ACCESS", "credential", id)).await;
 
     Ok(StatusCode::OK)
 }
 
-// Encryption helper functions
-fn encrypt_data(data: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // This is a simplified encryption implementation
-    // In production, use proper encryption with AES-GCM or similar
-    use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit}};
-    use rand::RngCore;
-
-    let key = get_encryption_key();
-    let cipher = Aes256Gcm::new(&key);
-    
-    let mut nonce_bytes = [0u8; 12];
-    rand::thread_rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    
-    let ciphertext = cipher.encrypt(nonce, data.as_bytes())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
-    
-    // Combine nonce and ciphertext
-    let mut encrypted = Vec::with_capacity(12 + ciphertext.len());
-    encrypted.extend_from_slice(&nonce_bytes);
-    encrypted.extend_from_slice(&ciphertext);
-    
-    Ok(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &encrypted))
+async fn get_credential_history(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    _auth: AuthUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM credentials WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.db_pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let history = sqlx::query_as::<_, CredentialHistoryEntry>(
+        r#"
+        SELECT
+            h.id, h.change_type, h.old_value_hash IS NOT NULL as has_previous_value,
+            h.notes, h.changed_by, u.first_name || ' ' || u.last_name as changed_by_name,
+            h.changed_at
+        FROM credential_history h
+        LEFT JOIN users u ON h.changed_by = u.id
+        WHERE h.credential_id = $1
+        ORDER BY h.changed_at DESC
+        "#
+    )
+    .bind(id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(history))
 }
 
-fn get_encryption_key() -> Key<Aes256Gcm> {
-    
-    let key_env = std::env::var("CREDENTIAL_ENCRYPTION_KEY").unwrap_or_else(|_| {
-        tracing::warn!("CREDENTIAL_ENCRYPTION_KEY not set, using default (insecure for production)");
-        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef".to_string()
-    });
-    
-    let key_bytes = hex::decode(&key_env).unwrap_or_else(|_| {
-        tracing::error!("Invalid CREDENTIAL_ENCRYPTION_KEY format, using default");
-        vec![0u8; 32]
-    });
-    
-    if key_bytes.len() != 32 {
-        tracing::error!("CREDENTIAL_ENCRYPTION_KEY must be 32 bytes (64 hex chars), using default");
-        return *Key::<Aes256Gcm>::from_slice(&[0u8; 32]);
+/// Replace the password, recording the change and clearing any overdue flag.
+/// Reusing the current or any previously recorded password is rejected.
+async fn rotate_credential(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Json(req): Json<RotateCredentialRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if req.password.is_empty() || req.password == "***ENCRYPTED***" {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let current = sqlx::query_as::<_, (Option<String>, Option<i32>)>(
+        "SELECT password, rotation_interval_days FROM credentials WHERE id = $1"
+    )
+    .bind(id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let Some((current_password, rotation_interval_days)) = current else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let old_value_hash = current_password.as_deref().and_then(fingerprint_encrypted);
+    let new_value_hash = password_fingerprint(&req.password);
+
+    let previous_hashes = sqlx::query_scalar::<_, String>(
+        "SELECT old_value_hash FROM credential_history WHERE credential_id = $1 AND old_value_hash IS NOT NULL"
+    )
+    .bind(id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if old_value_hash.as_ref() == Some(&new_value_hash) || previous_hashes.contains(&new_value_hash) {
+        return Err(StatusCode::CONFLICT);
     }
-    
-    *Key::<Aes256Gcm>::from_slice(&key_bytes)
+
+    let encrypted_password = encrypt_data(&req.password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sqlx::query(
+        "UPDATE credentials SET password = $2, rotation_flagged_at = NULL, updated_at = NOW() WHERE id = $1"
+    )
+    .bind(id)
+    .bind(&encrypted_password)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let rotated_at = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+        r#"
+        INSERT INTO credential_history (credential_id, change_type, old_value_hash, notes, changed_by)
+        VALUES ($1, 'rotated', $2, $3, $4)
+        RETURNING changed_at
+        "#
+    )
+    .bind(id)
+    .bind(&old_value_hash)
+    .bind(&req.notes)
+    .bind(auth.0.id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "ROTATE", "credential", id)).await;
+
+    Ok(Json(serde_json::json!({
+        "message": "Credential rotated successfully",
+        "rotated_at": rotated_at,
+        "next_rotation_due": rotation_interval_days
+            .map(|days| crate::jobs::credential_rotation::rotation_due_at(rotated_at, days)),
+    })))
 }
 
-async fn log_audit_action(
+async fn record_password_change(
     db_pool: &sqlx::PgPool,
-    user_id: Uuid,
-    action: &str,
-    entity_type: &str,
-    entity_id: Uuid,
-    old_values: Option<serde_json::Value>,
-    new_values: Option<serde_json::Value>,
-) {
-    let _ = sqlx::query!(
+    credential_id: Uuid,
+    change_type: &str,
+    old_value_hash: Option<String>,
+    notes: Option<&str>,
+    changed_by: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
         r#"
-        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, old_values, new_values, created_at)
-        VALUES ($1, $2, $3, $4, $5, $6, NOW())
-        "#,
-        user_id,
-        action,
-        entity_type,
-        entity_id,
-        old_values,
-        new_values
+        INSERT INTO credential_history (credential_id, change_type, old_value_hash, notes, changed_by)
+        VALUES ($1, $2, $3, $4, $5)
+        "#
     )
+    .bind(credential_id)
+    .bind(change_type)
+    .bind(old_value_hash)
+    .bind(notes)
+    .bind(changed_by)
     .execute(db_pool)
-    .await;
-}
\ No newline at end of file
+    .await?;
+
+    if change_type != "created" {
+        sqlx::query("UPDATE credentials SET rotation_flagged_at = NULL WHERE id = $1")
+            .bind(credential_id)
+            .execute(db_pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Keyed fingerprint of a plaintext password. Lets history detect reuse
+/// without storing anything that can be reversed or cracked offline.
+fn password_fingerprint(password: &str) -> String {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(get_encryption_key().as_slice())
+        .expect("HMAC accepts keys of any length");
+    mac.update(password.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Fingerprint of a stored (encrypted) password, if it can be decrypted
+fn fingerprint_encrypted(encrypted: &str) -> Option<String> {
+    decrypt_data(encrypted).ok().map(|password| password_fingerprint(&password))
+}
+
+// Encryption helper functions
+fn encrypt_data(data: &str) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(KeyRing::from_env(KeyPurpose::Credential)?.encrypt_str(data)?)
+}
+
+fn decrypt_data(encrypted: &str) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(KeyRing::from_env(KeyPurpose::Credential)?.decrypt_str(encrypted)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_password_round_trip() {
+        let encrypted = encrypt_data("hunter2!").unwrap();
+        assert_ne!(encrypted, "hunter2!");
+        assert_eq!(decrypt_data(&encrypted).unwrap(), "hunter2!");
+    }
+
+    #[test]
+    fn test_fingerprint_matches_across_encryptions() {
+        // Each encryption uses a fresh nonce, but the fingerprint is stable
+        let first = encrypt_data("Summer2024!").unwrap();
+        let second = encrypt_data("Summer2024!").unwrap();
+        assert_ne!(first, second);
+
+        let fingerprint = fingerprint_encrypted(&first).unwrap();
+        assert_eq!(fingerprint, fingerprint_encrypted(&second).unwrap());
+        assert_eq!(fingerprint, password_fingerprint("Summer2024!"));
+        assert_ne!(fingerprint, password_fingerprint("Autumn2024!"));
+        assert_eq!(fingerprint.len(), 64);
+    }
+}
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Json},
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
@@ -10,7 +10,10 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::audit::{self, AuditEvent, RequestMeta};
 use crate::auth::middleware::AuthUser;
+use crate::error::ApiError;
+use crate::services::dns_verification::{self, HickoryLookup, RecordDrift};
 use crate::AppState;
 use resolve_shared::Domain;
 
@@ -20,6 +23,7 @@ pub fn domain_routes() -> Router<Arc<AppState>> {
         .route("/:id", get(get_domain).put(update_domain).delete(delete_domain))
         .route("/expiring", get(get_expiring_domains))
         .route("/:id/dns", get(get_dns_records).put(update_dns_records))
+        .route("/:id/verify", get(verify_dns_records))
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,6 +48,15 @@ pub struct CreateDomainRequest {
     pub notes: Option<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct DnsVerification {
+    pub domain_id: Uuid,
+    pub domain: String,
+    pub in_sync: bool,
+    pub records: Vec<RecordDrift>,
+    pub checked_at: chrono::DateTime<chrono::Utc>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct DomainWithExpiry {
     #[serde(flatten)]
@@ -143,11 +156,15 @@ async fn get_domain(
 async fn create_domain(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateDomainRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
     let id = Uuid::new_v4();
     let nameservers = req.nameservers.unwrap_or_default();
-    let dns_records = req.dns_records.unwrap_or_else(|| serde_json::json!({}));
+    let dns_records = match validated_dns_records(req.dns_records.as_ref()) {
+        Ok(records) => records,
+        Err(response) => return Ok(response),
+    };
 
     sqlx::query!(
         r#"
@@ -175,19 +192,23 @@ async fn create_domain(
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Log the creation
-    log_audit_action(&state.db_pool, auth.0.id, "CREATE", "domain", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "CREATE", "domain", id)).await;
 
-    Ok(Json(serde_json::json!({ "id": id, "message": "Domain created successfully" })))
+    Ok(Json(serde_json::json!({ "id": id, "message": "Domain created successfully" })).into_response())
 }
 
 async fn update_domain(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateDomainRequest>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
     let nameservers = req.nameservers.unwrap_or_default();
-    let dns_records = req.dns_records.unwrap_or_else(|| serde_json::json!({}));
+    let dns_records = match validated_dns_records(req.dns_records.as_ref()) {
+        Ok(records) => records,
+        Err(response) => return Ok(response),
+    };
 
     let result = sqlx::query!(
         r#"
@@ -217,15 +238,16 @@ async fn update_domain(
     }
 
     // Log the update
-    log_audit_action(&state.db_pool, auth.0.id, "UPDATE", "domain", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UPDATE", "domain", id)).await;
 
-    Ok(Json(serde_json::json!({ "message": "Domain updated successfully" })))
+    Ok(Json(serde_json::json!({ "message": "Domain updated successfully" })).into_response())
 }
 
 async fn delete_domain(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
 ) -> Result<impl IntoResponse, StatusCode> {
     let result = sqlx::query!("DELETE FROM domains WHERE id = $1", id)
         .execute(&state.db_pool)
@@ -237,7 +259,7 @@ async fn delete_domain(
     }
 
     // Log the deletion
-    log_audit_action(&state.db_pool, auth.0.id, "DELETE", "domain", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "DELETE", "domain", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "Domain deleted successfully" })))
 }
@@ -305,8 +327,14 @@ async fn update_dns_records(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(dns_records): Json<serde_json::Value>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
+    let dns_records = match validated_dns_records(Some(&dns_records)) {
+        Ok(records) => records,
+        Err(response) => return Ok(response),
+    };
+
     let result = sqlx::query!(
         "UPDATE domains SET dns_records = $2, updated_at = NOW() WHERE id = $1",
         id,
@@ -321,28 +349,56 @@ async fn update_dns_records(
     }
 
     // Log the DNS update
-    log_audit_action(&state.db_pool, auth.0.id, "UPDATE_DNS", "domain", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UPDATE_DNS", "domain", id)).await;
 
-    Ok(Json(serde_json::json!({ "message": "DNS records updated successfully" })))
+    Ok(Json(serde_json::json!({ "message": "DNS records updated successfully" })).into_response())
 }
 
-async fn log_audit_action(
-    db_pool: &sqlx::PgPool,
-    user_id: Uuid,
-    action: &str,
-    entity_type: &str,
-    entity_id: Uuid,
-) {
-    let _ = sqlx::query!(
-        r#"
-        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        user_id,
-        action,
-        entity_type,
-        entity_id
+async fn verify_dns_records(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    _auth: AuthUser,
+) -> Result<Response, StatusCode> {
+    let domain = sqlx::query!(
+        "SELECT name, dns_records FROM domains WHERE id = $1",
+        id
     )
-    .execute(db_pool)
-    .await;
-}
\ No newline at end of file
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Records saved before validation existed may not parse
+    let records = match dns_verification::parse_dns_records(&domain.dns_records.unwrap_or_default()) {
+        Ok(records) => records,
+        Err(details) => return Ok(ApiError::validation(details).into_response()),
+    };
+
+    let lookup = HickoryLookup::from_system_conf().map_err(|e| {
+        tracing::error!("Failed to create DNS resolver: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let records = dns_verification::verify_records(&lookup, &domain.name, &records).await;
+    let in_sync = records
+        .iter()
+        .all(|r| r.status == dns_verification::DriftStatus::InSync);
+
+    Ok(Json(DnsVerification {
+        domain_id: id,
+        domain: domain.name,
+        in_sync,
+        records,
+        checked_at: chrono::Utc::now(),
+    })
+    .into_response())
+}
+
+/// Validate submitted DNS records, returning them in canonical form or a
+/// 422 response with field-level errors
+fn validated_dns_records(dns_records: Option<&serde_json::Value>) -> Result<serde_json::Value, Response> {
+    let records = dns_verification::parse_dns_records(dns_records.unwrap_or(&serde_json::Value::Null))
+        .map_err(|details| ApiError::validation(details).into_response())?;
+
+    serde_json::to_value(records).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
@@ -9,14 +9,20 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::audit::{self, AuditEvent, RequestMeta};
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::services::ipam::{self, AllocationRequest, IpStatus, IpamError};
+use crate::{AppState, ApiError, ApiResult, AppError};
 use resolve_shared::Network;
+use std::net::Ipv4Addr;
 
 pub fn network_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(list_networks).post(create_network))
         .route("/:id", get(get_network).put(update_network).delete(delete_network))
+        .route("/:id/addresses", get(list_addresses))
+        .route("/:id/addresses/next", post(allocate_next_address))
+        .route("/:id/addresses/:ip", put(assign_address).delete(release_address))
 }
 
 #[derive(Debug, Deserialize)]
@@ -98,6 +104,7 @@ async fn get_network(
 async fn create_network(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateNetworkRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let id = Uuid::new_v4();
@@ -120,7 +127,7 @@ async fn create_network(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    log_audit_action(&state.db_pool, auth.0.id, "CREATE", "network", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "CREATE", "network", id)).await;
 
     Ok(Json(serde_json::json!({ "id": id, "message": "Network created successfully" })))
 }
@@ -129,6 +136,7 @@ async fn update_network(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateNetworkRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let dns_servers = req.dns_servers.unwrap_or_default();
@@ -153,7 +161,7 @@ async fn update_network(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    log_audit_action(&state.db_pool, auth.0.id, "UPDATE", "network", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UPDATE", "network", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "Network updated successfully" })))
 }
@@ -162,6 +170,7 @@ async fn delete_network(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
 ) -> Result<impl IntoResponse, StatusCode> {
     let result = sqlx::query!("DELETE FROM networks WHERE id = $1", id)
         .execute(&state.db_pool)
@@ -172,25 +181,109 @@ async fn delete_network(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    log_audit_action(&state.db_pool, auth.0.id, "DELETE", "network", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "DELETE", "network", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "Network deleted successfully" })))
 }
 
-async fn log_audit_action(
-    db_pool: &sqlx::PgPool,
-    user_id: Uuid,
-    action: &str,
-    entity_type: &str,
-    entity_id: Uuid,
-) {
-    let _ = sqlx::query!(
-        r#"
-        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        user_id, action, entity_type, entity_id
+#[derive(Debug, Deserialize)]
+pub struct AssignAddressRequest {
+    /// `used` or `reserved`
+    #[serde(default = "default_assign_status")]
+    pub status: IpStatus,
+    #[serde(flatten)]
+    pub allocation: AllocationRequest,
+}
+
+fn default_assign_status() -> IpStatus {
+    IpStatus::Used
+}
+
+fn ipam_error(e: IpamError) -> AppError {
+    match e {
+        IpamError::NetworkNotFound => ApiError::not_found("Network not found"),
+        IpamError::InvalidRange(_) => ApiError::validation_single("ip_range", e.to_string()),
+        IpamError::OutOfRange { .. } => ApiError::validation_single("ip", e.to_string()),
+        IpamError::Conflict { .. } | IpamError::Exhausted(_) => ApiError::conflict(e.to_string()),
+        IpamError::NotAllocated(_) => ApiError::not_found("IP allocation not found"),
+        IpamError::Database(e) => e.into(),
+    }
+}
+
+fn parse_ip(ip: &str) -> ApiResult<Ipv4Addr> {
+    ip.parse().map_err(|_| ApiError::validation_single("ip", "must be an IPv4 address"))
+}
+
+/// Every host address of the network with its free/used/reserved status
+async fn list_addresses(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    _auth: AuthUser,
+) -> ApiResult<impl IntoResponse> {
+    let view = ipam::view_subnet(&state.db_pool, id).await.map_err(ipam_error)?;
+    Ok(Json(view))
+}
+
+async fn allocate_next_address(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Json(req): Json<AllocationRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let allocation = ipam::allocate_next(&state.db_pool, id, &req, auth.0.id).await.map_err(ipam_error)?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "ALLOCATE", "ip_allocation", allocation.id).after(&allocation),
     )
-    .execute(db_pool)
     .await;
-}
\ No newline at end of file
+
+    Ok((StatusCode::CREATED, Json(allocation)))
+}
+
+async fn assign_address(
+    State(state): State<Arc<AppState>>,
+    Path((id, ip)): Path<(Uuid, String)>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Json(req): Json<AssignAddressRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let ip = parse_ip(&ip)?;
+    if !matches!(req.status, IpStatus::Used | IpStatus::Reserved) {
+        return Err(ApiError::validation_single("status", "must be used or reserved"));
+    }
+
+    let allocation = ipam::assign(&state.db_pool, id, ip, req.status, &req.allocation, auth.0.id)
+        .await
+        .map_err(ipam_error)?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "ALLOCATE", "ip_allocation", allocation.id).after(&allocation),
+    )
+    .await;
+
+    Ok((StatusCode::CREATED, Json(allocation)))
+}
+
+async fn release_address(
+    State(state): State<Arc<AppState>>,
+    Path((id, ip)): Path<(Uuid, String)>,
+    auth: AuthUser,
+    meta: RequestMeta,
+) -> ApiResult<impl IntoResponse> {
+    let ip = parse_ip(&ip)?;
+    let released = ipam::release(&state.db_pool, id, ip).await.map_err(ipam_error)?;
+
+    audit::record(
+        &state.db_pool,
+        &meta,
+        AuditEvent::new(auth.0.id, "RELEASE", "ip_allocation", released.id).before(&released),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::{IntoResponse, Json},
+    response::{IntoResponse, Json, Response},
     routing::{get, post, put, delete},
     Router,
 };
@@ -9,10 +9,13 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
-use aes_gcm::{Aes256Gcm, Key};
 use rust_decimal::Decimal;
 
+use crate::audit::{self, AuditEvent, RequestMeta};
 use crate::auth::middleware::AuthUser;
+use crate::keyring::{KeyPurpose, KeyRing};
+use crate::error::ApiError;
+use crate::services::license_seats::{self, SeatError, SeatHolder};
 use crate::AppState;
 use resolve_shared::SoftwareLicense;
 
@@ -22,6 +25,9 @@ pub fn license_routes() -> Router<Arc<AppState>> {
         .route("/:id", get(get_software_license).put(update_software_license).delete(delete_software_license))
         .route("/expiring", get(get_expiring_licenses))
         .route("/usage", get(get_license_usage_summary))
+        .route("/:id/assignments", get(list_license_assignments))
+        .route("/:id/assign", post(assign_license_seat))
+        .route("/:id/unassign", post(unassign_license_seat))
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,6 +47,23 @@ pub struct CreateSoftwareLicenseRequest {
     pub notes: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct AssignSeatRequest {
+    pub contact_id: Option<Uuid>,
+    pub asset_id: Option<Uuid>,
+    pub notes: Option<String>,
+    /// Assign even when every seat is taken, raising an overage alert
+    #[serde(default)]
+    pub allow_overage: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnassignSeatRequest {
+    pub assignment_id: Option<Uuid>,
+    pub contact_id: Option<Uuid>,
+    pub asset_id: Option<Uuid>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SoftwareLicenseWithUsage {
     #[serde(flatten)]
@@ -162,6 +185,7 @@ async fn get_software_license(
 async fn create_software_license(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateSoftwareLicenseRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let id = Uuid::new_v4();
@@ -193,7 +217,7 @@ async fn create_software_license(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    log_audit_action(&state.db_pool, auth.0.id, "CREATE", "software_license", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "CREATE", "software_license", id)).await;
 
     Ok(Json(serde_json::json!({ "id": id, "message": "Software license created successfully" })))
 }
@@ -202,6 +226,7 @@ async fn update_software_license(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateSoftwareLicenseRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     // Get current license for key handling
@@ -229,14 +254,14 @@ async fn update_software_license(
         r#"
         UPDATE software_licenses SET
             client_id = $2, name = $3, vendor = $4, version = $5,
-            license_key = $6, license_type = $7, seats = $8, used_seats = $9,
+            license_key = $6, license_type = $7, seats = $8, used_seats = COALESCE($9, used_seats),
             purchase_date = $10, expiry_date = $11, renewal_date = $12,
             cost = $13, notes = $14, updated_at = NOW()
         WHERE id = $1
         "#,
         id, req.client_id, req.name, req.vendor, req.version,
         encrypted_license_key, req.license_type, req.seats,
-        req.used_seats.unwrap_or(0), req.purchase_date, req.expiry_date,
+        req.used_seats, req.purchase_date, req.expiry_date,
         req.renewal_date, req.cost, req.notes
     )
     .execute(&state.db_pool)
@@ -247,7 +272,7 @@ async fn update_software_license(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    log_audit_action(&state.db_pool, auth.0.id, "UPDATE", "software_license", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UPDATE", "software_license", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "Software license updated successfully" })))
 }
@@ -256,6 +281,7 @@ async fn delete_software_license(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
 ) -> Result<impl IntoResponse, StatusCode> {
     let result = sqlx::query!("DELETE FROM software_licenses WHERE id = $1", id)
         .execute(&state.db_pool)
@@ -266,7 +292,7 @@ async fn delete_software_license(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    log_audit_action(&state.db_pool, auth.0.id, "DELETE", "software_license", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "DELETE", "software_license", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "Software license deleted successfully" })))
 }
@@ -365,62 +391,89 @@ async fn get_license_usage_summary(
 }
 
 fn encrypt_license_key(license_key: &str) -> Result<String, Box<dyn std::error::Error>> {
-    use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit}};
-    use rand::RngCore;
-
-    let key = get_license_encryption_key();
-    let cipher = Aes256Gcm::new(&key);
-    
-    let mut nonce_bytes = [0u8; 12];
-    rand::thread_rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    
-    let ciphertext = cipher.encrypt(nonce, license_key.as_bytes())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
-    
-    // Combine nonce and ciphertext
-    let mut encrypted = Vec::with_capacity(12 + ciphertext.len());
-    encrypted.extend_from_slice(&nonce_bytes);
-    encrypted.extend_from_slice(&ciphertext);
-    
-    Ok(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &encrypted))
+    Ok(KeyRing::from_env(KeyPurpose::LicenseKey)?.encrypt_str(license_key)?)
 }
 
-fn get_license_encryption_key() -> Key<Aes256Gcm> {
-    use aes_gcm::Key;
-    
-    let key_env = std::env::var("LICENSE_ENCRYPTION_KEY").unwrap_or_else(|_| {
-        tracing::warn!("LICENSE_ENCRYPTION_KEY not set, using default (insecure for production)");
-        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef".to_string()
-    });
-    
-    let key_bytes = hex::decode(&key_env).unwrap_or_else(|_| {
-        tracing::error!("Invalid LICENSE_ENCRYPTION_KEY format, using default");
-        vec![0u8; 32]
-    });
-    
-    if key_bytes.len() != 32 {
-        tracing::error!("LICENSE_ENCRYPTION_KEY must be 32 bytes (64 hex chars), using default");
-        return *Key::<Aes256Gcm>::from_slice(&[0u8; 32]);
-    }
-    
-    *Key::<Aes256Gcm>::from_slice(&key_bytes)
+async fn list_license_assignments(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    _auth: AuthUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let assignments = license_seats::list_assignments(&state.db_pool, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(assignments))
 }
 
-async fn log_audit_action(
-    db_pool: &sqlx::PgPool,
-    user_id: Uuid,
-    action: &str,
-    entity_type: &str,
-    entity_id: Uuid,
-) {
-    let _ = sqlx::query!(
-        r#"
-        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        "#,
-        user_id, action, entity_type, entity_id
+async fn assign_license_seat(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Json(req): Json<AssignSeatRequest>,
+) -> Result<Response, StatusCode> {
+    let Some(holder) = SeatHolder::from_ids(req.contact_id, req.asset_id) else {
+        return Ok(ApiError::bad_request("Provide exactly one of contact_id or asset_id").into_response());
+    };
+
+    let result = license_seats::assign_seat(
+        &state.db_pool,
+        id,
+        holder,
+        auth.0.id,
+        req.notes.as_deref(),
+        req.allow_overage,
     )
-    .execute(db_pool)
     .await;
-}
\ No newline at end of file
+
+    let (assignment_id, usage) = match result {
+        Ok(assigned) => assigned,
+        Err(e) => return seat_error_response(e),
+    };
+
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "ASSIGN_SEAT", "software_license", id)).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "assignment_id": assignment_id, "usage": usage })),
+    )
+        .into_response())
+}
+
+async fn unassign_license_seat(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    auth: AuthUser,
+    meta: RequestMeta,
+    Json(req): Json<UnassignSeatRequest>,
+) -> Result<Response, StatusCode> {
+    let holder = SeatHolder::from_ids(req.contact_id, req.asset_id);
+    if req.assignment_id.is_none() && holder.is_none() {
+        return Ok(ApiError::bad_request("Provide assignment_id, contact_id or asset_id").into_response());
+    }
+
+    let usage = match license_seats::unassign_seat(&state.db_pool, id, req.assignment_id, holder).await {
+        Ok(usage) => usage,
+        Err(e) => return seat_error_response(e),
+    };
+
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UNASSIGN_SEAT", "software_license", id)).await;
+
+    Ok(Json(serde_json::json!({ "usage": usage })).into_response())
+}
+
+fn seat_error_response(e: SeatError) -> Result<Response, StatusCode> {
+    match e {
+        SeatError::LicenseNotFound | SeatError::AssignmentNotFound => {
+            Ok(ApiError::not_found(e.to_string()).into_response())
+        }
+        SeatError::AlreadyAssigned | SeatError::NoSeatsAvailable { .. } => {
+            Ok(ApiError::conflict(e.to_string()).into_response())
+        }
+        SeatError::Database(e) => {
+            tracing::error!("License seat update failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -9,9 +9,12 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
-use aes_gcm::{Aes256Gcm, Key};
 
-use crate::auth::middleware::AuthUser;
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::auth::middleware::{AuthUser, AuthUserWithRole};
+use crate::auth::rbac::{Action, Resource};
+use crate::keyring::{KeyPurpose, KeyRing};
+use crate::services::certificate_probe::{self, ProbeError, ProbeOptions};
 use crate::AppState;
 use resolve_shared::SslCertificate;
 
@@ -20,6 +23,7 @@ pub fn ssl_routes() -> Router<Arc<AppState>> {
         .route("/", get(list_ssl_certificates).post(create_ssl_certificate))
         .route("/:id", get(get_ssl_certificate).put(update_ssl_certificate).delete(delete_ssl_certificate))
         .route("/expiring", get(get_expiring_ssl_certificates))
+        .route("/:id/check", post(check_ssl_certificate))
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,6 +53,14 @@ pub struct CreateSslCertificateRequest {
     pub status: Option<String>,
 }
 
+/// Overrides for a live check; the host defaults to the certificate's common name
+#[derive(Debug, Default, Deserialize)]
+pub struct CheckSslCertificateRequest {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub timeout_seconds: Option<u64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SslCertificateWithExpiry {
     #[serde(flatten)]
@@ -156,6 +168,7 @@ async fn get_ssl_certificate(
 async fn create_ssl_certificate(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateSslCertificateRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let id = Uuid::new_v4();
@@ -198,7 +211,7 @@ async fn create_ssl_certificate(
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Log the creation
-    log_audit_action(&state.db_pool, auth.0.id, "CREATE", "ssl_certificate", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "CREATE", "ssl_certificate", id)).await;
 
     Ok(Json(serde_json::json!({ "id": id, "message": "SSL certificate created successfully" })))
 }
@@ -207,6 +220,7 @@ async fn update_ssl_certificate(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
     Json(req): Json<CreateSslCertificateRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let subject_alt_names = req.subject_alt_names.unwrap_or_default();
@@ -264,7 +278,7 @@ async fn update_ssl_certificate(
     }
 
     // Log the update
-    log_audit_action(&state.db_pool, auth.0.id, "UPDATE", "ssl_certificate", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "UPDATE", "ssl_certificate", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "SSL certificate updated successfully" })))
 }
@@ -273,6 +287,7 @@ async fn delete_ssl_certificate(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
+    meta: RequestMeta,
 ) -> Result<impl IntoResponse, StatusCode> {
     let result = sqlx::query!("DELETE FROM ssl_certificates WHERE id = $1", id)
         .execute(&state.db_pool)
@@ -284,7 +299,7 @@ async fn delete_ssl_certificate(
     }
 
     // Log the deletion
-    log_audit_action(&state.db_pool, auth.0.id, "DELETE", "ssl_certificate", id).await;
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.0.id, "DELETE", "ssl_certificate", id)).await;
 
     Ok(Json(serde_json::json!({ "message": "SSL certificate deleted successfully" })))
 }
@@ -333,66 +348,69 @@ async fn get_expiring_ssl_certificates(
     Ok(Json(certificates_with_expiry))
 }
 
-fn encrypt_private_key(private_key: &str) -> Result<String, Box<dyn std::error::Error>> {
-    use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit}};
-    use rand::RngCore;
-
-    let key = get_ssl_encryption_key();
-    let cipher = Aes256Gcm::new(&key);
-    
-    let mut nonce_bytes = [0u8; 12];
-    rand::thread_rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-    
-    let ciphertext = cipher.encrypt(nonce, private_key.as_bytes())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
-    
-    // Combine nonce and ciphertext
-    let mut encrypted = Vec::with_capacity(12 + ciphertext.len());
-    encrypted.extend_from_slice(&nonce_bytes);
-    encrypted.extend_from_slice(&ciphertext);
-    
-    Ok(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &encrypted))
-}
-
-fn get_ssl_encryption_key() -> Key<Aes256Gcm> {
-    use aes_gcm::Key;
-    
-    let key_env = std::env::var("SSL_ENCRYPTION_KEY").unwrap_or_else(|_| {
-        tracing::warn!("SSL_ENCRYPTION_KEY not set, using default (insecure for production)");
-        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef".to_string()
-    });
-    
-    let key_bytes = hex::decode(&key_env).unwrap_or_else(|_| {
-        tracing::error!("Invalid SSL_ENCRYPTION_KEY format, using default");
-        vec![0u8; 32]
-    });
-    
-    if key_bytes.len() != 32 {
-        tracing::error!("SSL_ENCRYPTION_KEY must be 32 bytes (64 hex chars), using default");
-        return *Key::<Aes256Gcm>::from_slice(&[0u8; 32]);
+async fn check_ssl_certificate(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    auth: AuthUserWithRole,
+    meta: RequestMeta,
+    req: Option<Json<CheckSslCertificateRequest>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !auth.can(Resource::Documentation, Action::Update) {
+        return Err(StatusCode::FORBIDDEN);
     }
-    
-    *Key::<Aes256Gcm>::from_slice(&key_bytes)
-}
+    let req = req.map(|Json(req)| req).unwrap_or_default();
 
-async fn log_audit_action(
-    db_pool: &sqlx::PgPool,
-    user_id: Uuid,
-    action: &str,
-    entity_type: &str,
-    entity_id: Uuid,
-) {
-    let _ = sqlx::query!(
+    let certificate = sqlx::query_as!(
+        SslCertificate,
         r#"
-        INSERT INTO audit_logs (user_id, action, entity_type, entity_id, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
+        SELECT id, domain_id, client_id, name, common_name, subject_alt_names,
+               issuer, issued_date, expiry_date, certificate_chain, private_key,
+               auto_renew, status, created_at, updated_at
+        FROM ssl_certificates
+        WHERE id = $1
         "#,
-        user_id,
-        action,
-        entity_type,
-        entity_id
+        id
     )
-    .execute(db_pool)
-    .await;
-}
\ No newline at end of file
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    let host = req
+        .host
+        .as_deref()
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| certificate_probe::probe_host(&certificate.common_name))
+        .to_string();
+
+    let options = ProbeOptions {
+        port: req.port.unwrap_or(certificate_probe::DEFAULT_PORT),
+        timeout: std::time::Duration::from_secs(
+            req.timeout_seconds
+                .unwrap_or(certificate_probe::DEFAULT_TIMEOUT_SECONDS)
+                .clamp(1, 60),
+        ),
+        public_only: true,
+    };
+
+    let check = certificate_probe::check_certificate(&state.db_pool, &certificate, &host, &options)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Live check of SSL certificate {} against {} failed: {}", id, host, e);
+            match e {
+                ProbeError::InvalidHost(_) | ProbeError::Address(_) => StatusCode::BAD_REQUEST,
+                ProbeError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+                ProbeError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::BAD_GATEWAY,
+            }
+        })?;
+
+    audit::record(&state.db_pool, &meta, AuditEvent::new(auth.user.id, "CHECK", "ssl_certificate", id)).await;
+
+    Ok(Json(check))
+}
+
+fn encrypt_private_key(private_key: &str) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(KeyRing::from_env(KeyPurpose::SslPrivateKey)?.encrypt_str(private_key)?)
+}
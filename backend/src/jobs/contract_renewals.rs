@@ -0,0 +1,58 @@
+// Contract Renewal Job - Reminds account managers of ending contracts and renews auto-renew ones
+
+use chrono::Utc;
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::services::contract_renewals;
+
+#[derive(Debug)]
+pub struct ContractRenewalJob {
+    db_pool: PgPool,
+    warning_days: Vec<i32>,
+    auto_renew_days: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct ContractRenewalJobResult {
+    pub contracts_checked: i32,
+    pub reminders_sent: i32,
+    pub contracts_renewed: i32,
+    pub errors: Vec<String>,
+}
+
+impl ContractRenewalJob {
+    /// `warning_days` are reminder thresholds before `end_date`, e.g. `[60, 30]`;
+    /// auto-renew contracts renew once within `auto_renew_days` of it
+    pub fn new(db_pool: PgPool, warning_days: Vec<i32>, auto_renew_days: i32) -> Self {
+        Self { db_pool, warning_days, auto_renew_days }
+    }
+
+    pub async fn run(&self) -> Result<ContractRenewalJobResult, Box<dyn std::error::Error + Send + Sync>> {
+        let today = Utc::now().date_naive();
+        let check = contract_renewals::check_renewals(&self.db_pool, today, &self.warning_days, self.auto_renew_days).await?;
+
+        let mut result = ContractRenewalJobResult {
+            contracts_checked: check.checked as i32,
+            ..Default::default()
+        };
+
+        for reminder in check.reminders {
+            result.reminders_sent += 1;
+            info!(
+                "Renewal reminder for contract {}: ends {} ({} day threshold), {} notified",
+                reminder.contract.contract_name, reminder.contract.end_date, reminder.threshold_days, reminder.notified
+            );
+        }
+
+        for renewed in check.renewals {
+            result.contracts_renewed += 1;
+            info!(
+                "Auto-renewed contract {}: {} -> {}, {} notified",
+                renewed.contract.contract_name, renewed.renewal.previous_end_date, renewed.renewal.new_end_date, renewed.notified
+            );
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,207 @@
+// Credential Rotation Job - Flags credentials whose password is overdue for rotation
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{FromRow, PgPool};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::notifications::create_notifications_for_users;
+
+#[derive(Debug)]
+pub struct CredentialRotationJob {
+    db_pool: PgPool,
+}
+
+#[derive(Debug, Default)]
+pub struct CredentialRotationJobResult {
+    pub credentials_checked: i32,
+    pub credentials_flagged: i32,
+    pub notifications_sent: i32,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct RotatingCredential {
+    id: Uuid,
+    name: String,
+    client_id: Option<Uuid>,
+    client_name: Option<String>,
+    rotation_interval_days: i32,
+    /// Most recent password change, falling back to creation
+    last_changed_at: DateTime<Utc>,
+}
+
+/// When a password set at `last_changed_at` is next due for rotation
+pub fn rotation_due_at(last_changed_at: DateTime<Utc>, rotation_interval_days: i32) -> DateTime<Utc> {
+    last_changed_at + Duration::days(rotation_interval_days as i64)
+}
+
+fn is_overdue(credential: &RotatingCredential, now: DateTime<Utc>) -> bool {
+    rotation_due_at(credential.last_changed_at, credential.rotation_interval_days) <= now
+}
+
+impl CredentialRotationJob {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn run(&self) -> Result<CredentialRotationJobResult, Box<dyn std::error::Error + Send + Sync>> {
+        let mut result = CredentialRotationJobResult::default();
+        let now = Utc::now();
+
+        // Already-flagged credentials were notified when first flagged
+        let credentials = sqlx::query_as::<_, RotatingCredential>(
+            r#"
+            SELECT
+                cr.id, cr.name, cr.client_id, c.name as client_name, cr.rotation_interval_days,
+                COALESCE(
+                    (SELECT MAX(h.changed_at) FROM credential_history h WHERE h.credential_id = cr.id),
+                    cr.created_at,
+                    NOW()
+                ) as last_changed_at
+            FROM credentials cr
+            LEFT JOIN clients c ON cr.client_id = c.id
+            WHERE cr.rotation_interval_days IS NOT NULL
+                AND cr.password IS NOT NULL
+                AND cr.rotation_flagged_at IS NULL
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        result.credentials_checked = credentials.len() as i32;
+
+        for credential in credentials.iter().filter(|c| is_overdue(c, now)) {
+            let flagged = sqlx::query(
+                "UPDATE credentials SET rotation_flagged_at = NOW() WHERE id = $1 AND rotation_flagged_at IS NULL"
+            )
+            .bind(credential.id)
+            .execute(&self.db_pool)
+            .await;
+
+            match flagged {
+                Ok(r) if r.rows_affected() > 0 => result.credentials_flagged += 1,
+                Ok(_) => continue,
+                Err(e) => {
+                    result.errors.push(format!("Failed to flag credential {}: {}", credential.id, e));
+                    continue;
+                }
+            }
+
+            match self.notify_technicians(credential, now).await {
+                Ok(0) => warn!("No technicians to notify about overdue credential {}", credential.id),
+                Ok(sent) => result.notifications_sent += sent as i32,
+                Err(e) => result.errors.push(format!(
+                    "Failed to notify technicians about credential {}: {}",
+                    credential.id, e
+                )),
+            }
+        }
+
+        info!(
+            "Credential rotation check: {} checked, {} flagged overdue",
+            result.credentials_checked, result.credentials_flagged
+        );
+
+        Ok(result)
+    }
+
+    /// Notify the client's account manager and technicians recently assigned
+    /// to its tickets. Credentials without a client go to admins.
+    async fn notify_technicians(&self, credential: &RotatingCredential, now: DateTime<Utc>) -> Result<usize, sqlx::Error> {
+        let recipients = match credential.client_id {
+            Some(client_id) => {
+                sqlx::query_scalar::<_, Uuid>(
+                    r#"
+                    SELECT DISTINCT user_id FROM (
+                        SELECT account_manager_id as user_id FROM clients WHERE id = $1
+                        UNION
+                        SELECT assigned_to as user_id FROM tickets
+                        WHERE client_id = $1 AND created_at > NOW() - INTERVAL '90 days'
+                    ) AS techs
+                    JOIN users u ON u.id = techs.user_id
+                    WHERE COALESCE(u.is_active, true)
+                    "#
+                )
+                .bind(client_id)
+                .fetch_all(&self.db_pool)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar::<_, Uuid>(
+                    r#"
+                    SELECT u.id FROM users u
+                    JOIN roles r ON u.role_id = r.id
+                    WHERE r.name = 'admin' AND u.is_active = true
+                    "#
+                )
+                .fetch_all(&self.db_pool)
+                .await?
+            }
+        };
+
+        if recipients.is_empty() {
+            return Ok(0);
+        }
+
+        let days_overdue = (now - rotation_due_at(credential.last_changed_at, credential.rotation_interval_days)).num_days();
+        let owner = credential
+            .client_name
+            .as_deref()
+            .map(|name| format!(" for {}", name))
+            .unwrap_or_default();
+
+        let ids = create_notifications_for_users(
+            &self.db_pool,
+            recipients,
+            format!("Credential rotation overdue: {}", credential.name),
+            format!(
+                "The password for '{}'{} was last changed {} and is {} day(s) past its {}-day rotation interval.",
+                credential.name,
+                owner,
+                credential.last_changed_at.format("%Y-%m-%d"),
+                days_overdue,
+                credential.rotation_interval_days
+            ),
+            "warning".to_string(),
+            Some("credential".to_string()),
+            Some(credential.id),
+        )
+        .await?;
+
+        Ok(ids.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn credential(last_changed_at: DateTime<Utc>, rotation_interval_days: i32) -> RotatingCredential {
+        RotatingCredential {
+            id: Uuid::new_v4(),
+            name: "Domain admin".to_string(),
+            client_id: Some(Uuid::new_v4()),
+            client_name: Some("Acme Corp".to_string()),
+            rotation_interval_days,
+            last_changed_at,
+        }
+    }
+
+    #[test]
+    fn test_rotation_due_at() {
+        let changed = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        assert_eq!(rotation_due_at(changed, 90), Utc.with_ymd_and_hms(2024, 3, 31, 9, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_overdue_from_last_change() {
+        let changed = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        let cred = credential(changed, 30);
+
+        assert!(!is_overdue(&cred, Utc.with_ymd_and_hms(2024, 1, 31, 8, 59, 0).unwrap()));
+        assert!(is_overdue(&cred, Utc.with_ymd_and_hms(2024, 1, 31, 9, 0, 0).unwrap()));
+        assert!(is_overdue(&cred, Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()));
+    }
+}
@@ -6,6 +6,8 @@ use sqlx::{FromRow, PgPool};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+use crate::services::asset_warranty::{self, ExpiringWarranty};
+use crate::services::license_seats;
 use crate::services::EmailService;
 
 #[derive(Debug)]
@@ -24,6 +26,7 @@ pub struct ExpirationCheckResult {
     pub domains_expiring: i32,
     pub ssl_expiring: i32,
     pub licenses_expiring: i32,
+    pub licenses_high_utilization: i32,
     pub warranties_expiring: i32,
     pub alerts_sent: i32,
     pub errors: Vec<String>,
@@ -80,20 +83,6 @@ struct LicenseExpiry {
     annual_cost: Option<rust_decimal::Decimal>,
 }
 
-#[derive(Debug, FromRow)]
-struct WarrantyExpiry {
-    id: Uuid,
-    asset_name: String,
-    asset_type: String,
-    client_id: Uuid,
-    client_name: String,
-    client_email: Option<String>,
-    warranty_expiry: NaiveDate,
-    last_notification_date: Option<NaiveDate>,
-    manufacturer: Option<String>,
-    serial_number: Option<String>,
-}
-
 impl ExpirationMonitorJob {
     pub fn new(
         db_pool: PgPool,
@@ -131,6 +120,11 @@ impl ExpirationMonitorJob {
             result.errors.push(format!("License check error: {}", e));
         }
 
+        // Check software license seat utilization
+        if let Err(e) = self.check_license_seat_utilization(&mut result).await {
+            result.errors.push(format!("License seat check error: {}", e));
+        }
+
         // Check warranty expirations
         if let Err(e) = self.check_warranty_expirations(&mut result).await {
             result.errors.push(format!("Warranty check error: {}", e));
@@ -340,68 +334,91 @@ impl ExpirationMonitorJob {
         Ok(())
     }
 
-    async fn check_warranty_expirations(&self, result: &mut ExpirationCheckResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let today = Utc::now().date_naive();
-        let max_days = *self.warranty_warning_days.iter().max().unwrap_or(&90);
-        let end_date = today + chrono::Duration::days(max_days as i64);
-
-        let warranties = sqlx::query_as::<_, WarrantyExpiry>(
+    /// Flag licenses with at least 90% of their seats assigned, and close
+    /// the alert once utilization drops back below that
+    async fn check_license_seat_utilization(&self, result: &mut ExpirationCheckResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let licenses = sqlx::query_as::<_, (Uuid, String, String, Option<i32>, i32)>(
             r#"
-            SELECT
-                a.id, a.name as asset_name, a.asset_type, a.warranty_expiry,
-                a.last_notification_date, a.manufacturer, a.serial_number,
-                c.id as client_id, c.name as client_name, c.email as client_email
-            FROM assets a
-            JOIN clients c ON a.client_id = c.id
-            WHERE a.warranty_expiry BETWEEN $1 AND $2
-                AND a.is_active = true
-                AND a.warranty_expiry IS NOT NULL
-            ORDER BY a.warranty_expiry ASC
+            SELECT l.id, l.name, c.name as client_name, l.seats, COALESCE(l.used_seats, 0)
+            FROM software_licenses l
+            JOIN clients c ON l.client_id = c.id
+            WHERE l.seats > 0
+                AND (l.expiry_date IS NULL OR l.expiry_date >= CURRENT_DATE)
             "#
         )
-        .bind(today)
-        .bind(end_date)
         .fetch_all(&self.db_pool)
         .await?;
 
-        result.total_items_checked += warranties.len() as i32;
+        result.total_items_checked += licenses.len() as i32;
 
-        for warranty in warranties {
-            let days_until = (warranty.warranty_expiry - today).num_days() as i32;
+        for (id, name, client_name, seats, used_seats) in licenses {
+            if !license_seats::is_high_utilization(seats, used_seats) {
+                sqlx::query(
+                    "UPDATE software_license_alerts SET resolved_at = NOW() WHERE license_id = $1 AND alert_type = 'high_utilization' AND resolved_at IS NULL"
+                )
+                .bind(id)
+                .execute(&self.db_pool)
+                .await?;
+                continue;
+            }
 
-            if self.should_notify(&warranty.last_notification_date, days_until, &self.warranty_warning_days) {
-                result.warranties_expiring += 1;
+            let message = format!(
+                "{} for {} has {} of {} seats assigned",
+                name,
+                client_name,
+                used_seats,
+                seats.unwrap_or_default()
+            );
+
+            if license_seats::raise_alert(&self.db_pool, id, "high_utilization", seats, used_seats, &message).await? {
+                result.licenses_high_utilization += 1;
+                warn!("{}", message);
+            }
+        }
 
-                let alert = ExpirationAlert {
-                    item_type: "warranty".to_string(),
-                    item_name: warranty.asset_name.clone(),
-                    client_id: warranty.client_id,
-                    client_name: warranty.client_name.clone(),
-                    expiration_date: warranty.warranty_expiry,
-                    days_until_expiry: days_until,
-                    details: serde_json::json!({
-                        "asset_type": warranty.asset_type,
-                        "manufacturer": warranty.manufacturer,
-                        "serial_number": warranty.serial_number
-                    }),
-                };
+        Ok(())
+    }
 
-                if let Some(email) = &warranty.client_email {
-                    if let Err(e) = self.send_warranty_expiration_email(&warranty, days_until).await {
-                        result.errors.push(format!("Failed to send warranty alert for {}: {}", warranty.asset_name, e));
-                    } else {
-                        result.alerts_sent += 1;
-                    }
+    /// Alerts and notifications for warranties are raised by
+    /// [`asset_warranty::check_warranties`]; the client is also emailed.
+    async fn check_warranty_expirations(&self, result: &mut ExpirationCheckResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let today = Utc::now().date_naive();
+        let check = asset_warranty::check_warranties(&self.db_pool, today, &self.warranty_warning_days).await?;
+
+        result.total_items_checked += check.checked as i32;
+
+        for raised in check.alerts {
+            let warranty = &raised.warranty;
+            result.warranties_expiring += 1;
+            info!(
+                "Warranty alert for {} at {}: {} day(s) remaining ({}-day threshold), {} notified",
+                warranty.asset_name, warranty.client_name, raised.days_remaining, raised.threshold_days, raised.notified
+            );
+
+            if warranty.client_email.is_some() {
+                if let Err(e) = self.send_warranty_expiration_email(warranty, raised.days_remaining).await {
+                    result.errors.push(format!("Failed to send warranty alert for {}: {}", warranty.asset_name, e));
+                } else {
+                    result.alerts_sent += 1;
                 }
-
-                sqlx::query("UPDATE assets SET last_notification_date = $2 WHERE id = $1")
-                    .bind(warranty.id)
-                    .bind(today)
-                    .execute(&self.db_pool)
-                    .await?;
-
-                self.log_expiration_alert(&alert).await?;
             }
+
+            let alert = ExpirationAlert {
+                item_type: "warranty".to_string(),
+                item_name: warranty.asset_name.clone(),
+                client_id: warranty.client_id,
+                client_name: warranty.client_name.clone(),
+                expiration_date: warranty.warranty_expire,
+                days_until_expiry: raised.days_remaining,
+                details: serde_json::json!({
+                    "asset_id": warranty.asset_id,
+                    "alert_id": raised.alert_id,
+                    "asset_type": warranty.asset_type,
+                    "make": warranty.make,
+                    "serial": warranty.serial
+                }),
+            };
+            self.log_expiration_alert(&alert).await?;
         }
 
         Ok(())
@@ -529,7 +546,7 @@ impl ExpirationMonitorJob {
         Ok(())
     }
 
-    async fn send_warranty_expiration_email(&self, warranty: &WarrantyExpiry, days_until: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn send_warranty_expiration_email(&self, warranty: &ExpiringWarranty, days_until: i32) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let urgency_class = self.get_urgency_class(days_until);
         let subject = format!(
             "[{}] Warranty for {} expires in {} days",
@@ -540,13 +557,13 @@ impl ExpirationMonitorJob {
             "Warranty Expiration",
             &warranty.asset_name,
             &warranty.client_name,
-            warranty.warranty_expiry,
+            warranty.warranty_expire,
             days_until,
             &urgency_class,
             vec![
                 ("Asset Type", &warranty.asset_type),
-                ("Manufacturer", warranty.manufacturer.as_deref().unwrap_or("Unknown")),
-                ("Serial Number", warranty.serial_number.as_deref().unwrap_or("N/A")),
+                ("Manufacturer", warranty.make.as_deref().unwrap_or("Unknown")),
+                ("Serial Number", warranty.serial.as_deref().unwrap_or("N/A")),
             ],
             "Consider extended warranty options or plan for potential replacement.",
         );
@@ -0,0 +1,202 @@
+// Job Run History - Persists job runs to `job_runs` and alerts admins when a
+// job keeps failing
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::scheduler::{JobExecutionLog, JobStatus};
+use crate::notifications::create_notifications_for_users;
+
+/// Runs of one job kept in its history listing by default
+pub const DEFAULT_HISTORY_LIMIT: i64 = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct JobRun {
+    pub id: Uuid,
+    pub job_name: String,
+    pub trigger: String,
+    pub triggered_by: Option<Uuid>,
+    pub status: String,
+    pub items_processed: i32,
+    pub error: Option<String>,
+    pub errors: serde_json::Value,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub duration_ms: Option<i64>,
+}
+
+pub const JOB_RUN_COLUMNS: &str =
+    "id, job_name, trigger, triggered_by, status, items_processed, error, errors,
+     started_at, completed_at, duration_ms";
+
+/// What started a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobTrigger {
+    Schedule,
+    /// Run on demand, by the given user when there is one
+    Manual(Option<Uuid>),
+}
+
+impl JobTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Schedule => "schedule",
+            Self::Manual(_) => "manual",
+        }
+    }
+
+    fn user_id(&self) -> Option<Uuid> {
+        match self {
+            Self::Schedule => None,
+            Self::Manual(user_id) => *user_id,
+        }
+    }
+}
+
+/// Record a run as started
+pub async fn start(
+    pool: &PgPool,
+    id: Uuid,
+    job_key: &str,
+    trigger: JobTrigger,
+    started_at: DateTime<Utc>,
+) -> Result<JobRun, sqlx::Error> {
+    sqlx::query_as::<_, JobRun>(&format!(
+        "INSERT INTO job_runs (id, job_name, trigger, triggered_by, status, started_at)
+         VALUES ($1, $2, $3, $4, 'running', $5)
+         RETURNING {}",
+        JOB_RUN_COLUMNS
+    ))
+    .bind(id)
+    .bind(job_key)
+    .bind(trigger.as_str())
+    .bind(trigger.user_id())
+    .bind(started_at)
+    .fetch_one(pool)
+    .await
+}
+
+/// Record how a started run ended. A failed run's error is why it failed;
+/// otherwise errors are the per-item failures of a partial run.
+pub async fn finish(pool: &PgPool, log: &JobExecutionLog) -> Result<(), sqlx::Error> {
+    let (error, errors): (Option<&String>, &[String]) = match log.status {
+        JobStatus::Failed => (log.errors.first(), &[]),
+        _ => (None, &log.errors),
+    };
+
+    sqlx::query(
+        "UPDATE job_runs
+         SET status = $2, items_processed = $3, error = $4, errors = $5,
+             completed_at = $6, duration_ms = $7
+         WHERE id = $1"
+    )
+    .bind(log.id)
+    .bind(log.status.as_str())
+    .bind(log.items_processed)
+    .bind(error)
+    .bind(serde_json::json!(errors))
+    .bind(log.completed_at)
+    .bind(log.duration_ms)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Failed runs of a job since it last finished without failing
+pub async fn consecutive_failures(pool: &PgPool, job_key: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM job_runs
+         WHERE job_name = $1 AND status = 'failed'
+           AND started_at > COALESCE(
+               (SELECT MAX(started_at) FROM job_runs
+                WHERE job_name = $1 AND status IN ('completed', 'partial_failure')),
+               '-infinity'::timestamptz)"
+    )
+    .bind(job_key)
+    .fetch_one(pool)
+    .await
+}
+
+/// Notify every active admin once a job's failing streak reaches
+/// `threshold`. Later failures in the same streak don't alert again.
+/// Returns whether an alert was sent.
+pub async fn alert_on_repeated_failure(
+    pool: &PgPool,
+    job_key: &str,
+    log: &JobExecutionLog,
+    threshold: u32,
+) -> Result<bool, sqlx::Error> {
+    if threshold == 0 || consecutive_failures(pool, job_key).await? != i64::from(threshold) {
+        return Ok(false);
+    }
+
+    let admins = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT u.id FROM users u
+        JOIN roles r ON u.role_id = r.id
+        WHERE r.name = 'admin' AND u.is_active = true
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let last_error = log.errors.first().map(String::as_str).unwrap_or("unknown error");
+    create_notifications_for_users(
+        pool,
+        admins,
+        format!("{} job is failing", log.job_name),
+        format!("{} has failed {} runs in a row. Last error: {}", log.job_name, threshold, last_error),
+        "job_failure".to_string(),
+        Some("job_run".to_string()),
+        Some(log.id),
+    )
+    .await?;
+
+    Ok(true)
+}
+
+/// Most recent run of each job that has run, keyed by job
+pub async fn latest_runs(pool: &PgPool) -> Result<HashMap<String, JobRun>, sqlx::Error> {
+    let runs = sqlx::query_as::<_, JobRun>(&format!(
+        "SELECT DISTINCT ON (job_name) {} FROM job_runs ORDER BY job_name, started_at DESC",
+        JOB_RUN_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(runs.into_iter().map(|run| (run.job_name.clone(), run)).collect())
+}
+
+/// Every job's current failing streak, as [`consecutive_failures`] counts
+/// it; jobs not failing are left out
+pub async fn failure_streaks(pool: &PgPool) -> Result<HashMap<String, i64>, sqlx::Error> {
+    let streaks = sqlx::query_as::<_, (String, i64)>(
+        "SELECT r.job_name, COUNT(*) FROM job_runs r
+         WHERE r.status = 'failed'
+           AND r.started_at > COALESCE(
+               (SELECT MAX(s.started_at) FROM job_runs s
+                WHERE s.job_name = r.job_name AND s.status IN ('completed', 'partial_failure')),
+               '-infinity'::timestamptz)
+         GROUP BY r.job_name"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(streaks.into_iter().collect())
+}
+
+/// A job's runs, newest first
+pub async fn runs_for(pool: &PgPool, job_key: &str, limit: i64) -> Result<Vec<JobRun>, sqlx::Error> {
+    sqlx::query_as::<_, JobRun>(&format!(
+        "SELECT {} FROM job_runs WHERE job_name = $1 ORDER BY started_at DESC LIMIT $2",
+        JOB_RUN_COLUMNS
+    ))
+    .bind(job_key)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
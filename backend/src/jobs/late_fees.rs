@@ -0,0 +1,272 @@
+// Late Fee Job - Applies monthly late fees to overdue invoices
+
+use chrono::{Datelike, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgPool};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::notifications::create_notification;
+use crate::services::invoice_tax::round_money;
+
+#[derive(Debug)]
+pub struct LateFeeJob {
+    db_pool: PgPool,
+}
+
+#[derive(Debug, Default)]
+pub struct LateFeeJobResult {
+    pub invoices_checked: i32,
+    pub fees_applied: i32,
+    pub total_fees: Decimal,
+    pub notifications_sent: i32,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct OverdueInvoice {
+    id: Uuid,
+    number: String,
+    client_name: String,
+    account_manager_id: Option<Uuid>,
+    due_date: NaiveDate,
+    balance: Decimal,
+    late_fee_percentage: Decimal,
+}
+
+/// A fee that should be applied for a given billing period
+#[derive(Debug, Clone, PartialEq)]
+struct LateFeeAssessment {
+    billing_period: NaiveDate,
+    amount: Decimal,
+}
+
+impl LateFeeJob {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn run(&self) -> Result<LateFeeJobResult, Box<dyn std::error::Error + Send + Sync>> {
+        let mut result = LateFeeJobResult::default();
+        let today = Utc::now().date_naive();
+
+        let invoices = sqlx::query_as::<_, OverdueInvoice>(
+            r#"
+            SELECT
+                i.id, i.number, i.due_date, i.balance, i.late_fee_percentage,
+                c.name as client_name, c.account_manager_id
+            FROM invoices i
+            JOIN clients c ON i.client_id = c.id
+            WHERE i.due_date < $1
+                AND i.balance > 0
+                AND i.late_fee_percentage > 0
+                AND i.status NOT IN ('draft', 'paid', 'void', 'cancelled')
+            ORDER BY i.due_date ASC
+            "#
+        )
+        .bind(today)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        result.invoices_checked = invoices.len() as i32;
+
+        for invoice in invoices {
+            let Some(assessment) = assess_late_fee(&invoice, today) else {
+                continue;
+            };
+
+            match self.apply_late_fee(&invoice, &assessment).await {
+                Ok(true) => {
+                    result.fees_applied += 1;
+                    result.total_fees += assessment.amount;
+                    info!("Applied late fee of ${} to invoice {}", assessment.amount, invoice.number);
+
+                    if let Some(manager_id) = invoice.account_manager_id {
+                        match self.notify_account_manager(manager_id, &invoice, &assessment).await {
+                            Ok(_) => result.notifications_sent += 1,
+                            Err(e) => result.errors.push(format!(
+                                "Failed to notify account manager for invoice {}: {}",
+                                invoice.number, e
+                            )),
+                        }
+                    } else {
+                        warn!("Client {} has no account manager; late fee on {} not notified", invoice.client_name, invoice.number);
+                    }
+                }
+                // Already applied for this billing period
+                Ok(false) => {}
+                Err(e) => {
+                    result.errors.push(format!("Failed to apply late fee to invoice {}: {}", invoice.number, e));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Record the fee in the ledger, add the line item and bump the invoice totals.
+    /// Returns `false` when this period's fee has already been applied.
+    async fn apply_late_fee(
+        &self,
+        invoice: &OverdueInvoice,
+        assessment: &LateFeeAssessment,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let ledger_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO applied_late_fees
+                (invoice_id, billing_period, balance_before, late_fee_percentage, fee_amount)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (invoice_id, billing_period) DO NOTHING
+            RETURNING id
+            "#
+        )
+        .bind(invoice.id)
+        .bind(assessment.billing_period)
+        .bind(invoice.balance)
+        .bind(invoice.late_fee_percentage)
+        .bind(assessment.amount)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(ledger_id) = ledger_id else {
+            tx.rollback().await?;
+            return Ok(false);
+        };
+
+        let line_item_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO invoice_line_items
+                (invoice_id, description, quantity, unit_price, line_total, tax_rate, tax_amount, created_at)
+            VALUES ($1, $2, 1, $3, $3, 0, 0, NOW())
+            RETURNING id
+            "#
+        )
+        .bind(invoice.id)
+        .bind(format!(
+            "Late fee - {} ({}% of ${} outstanding)",
+            assessment.billing_period.format("%B %Y"),
+            invoice.late_fee_percentage.normalize(),
+            invoice.balance
+        ))
+        .bind(assessment.amount)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE applied_late_fees SET line_item_id = $2 WHERE id = $1")
+            .bind(ledger_id)
+            .bind(line_item_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "UPDATE invoices
+             SET total = total + $2, balance = balance + $2, status = 'overdue', updated_at = NOW()
+             WHERE id = $1"
+        )
+        .bind(invoice.id)
+        .bind(assessment.amount)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn notify_account_manager(
+        &self,
+        manager_id: Uuid,
+        invoice: &OverdueInvoice,
+        assessment: &LateFeeAssessment,
+    ) -> Result<Uuid, sqlx::Error> {
+        create_notification(
+            &self.db_pool,
+            manager_id,
+            format!("Late fee applied to invoice {}", invoice.number),
+            format!(
+                "A late fee of ${} was added to invoice {} for {} ({} days overdue, ${} outstanding).",
+                assessment.amount,
+                invoice.number,
+                invoice.client_name,
+                (Utc::now().date_naive() - invoice.due_date).num_days(),
+                invoice.balance + assessment.amount
+            ),
+            "warning".to_string(),
+            Some("invoice".to_string()),
+            Some(invoice.id),
+        )
+        .await
+    }
+}
+
+/// Work out the fee due for `today`, or `None` if the invoice isn't overdue.
+/// The billing period is the first of the current month, so reruns within the
+/// same month resolve to the same ledger key.
+fn assess_late_fee(invoice: &OverdueInvoice, today: NaiveDate) -> Option<LateFeeAssessment> {
+    if invoice.due_date >= today || invoice.balance <= Decimal::ZERO {
+        return None;
+    }
+
+    let amount = round_money(invoice.balance * invoice.late_fee_percentage / Decimal::ONE_HUNDRED);
+    if amount <= Decimal::ZERO {
+        return None;
+    }
+
+    Some(LateFeeAssessment {
+        billing_period: today.with_day(1).unwrap_or(today),
+        amount,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn overdue_invoice(today: NaiveDate, days_overdue: i64) -> OverdueInvoice {
+        OverdueInvoice {
+            id: Uuid::new_v4(),
+            number: "INV-000042".to_string(),
+            client_name: "Acme Corp".to_string(),
+            account_manager_id: Some(Uuid::new_v4()),
+            due_date: today - chrono::Duration::days(days_overdue),
+            balance: dec("1250.00"),
+            late_fee_percentage: dec("1.5"),
+        }
+    }
+
+    #[test]
+    fn test_35_day_overdue_invoice_is_charged_once_per_month() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        let invoice = overdue_invoice(today, 35);
+
+        let assessment = assess_late_fee(&invoice, today).unwrap();
+        assert_eq!(assessment.amount, dec("18.75"));
+        assert_eq!(assessment.billing_period, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+
+        // A rerun later in the month maps to the same ledger key
+        let later = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        assert_eq!(assess_late_fee(&invoice, later).unwrap().billing_period, assessment.billing_period);
+
+        // Next month is a new period
+        let next_month = NaiveDate::from_ymd_opt(2024, 4, 2).unwrap();
+        assert_eq!(
+            assess_late_fee(&invoice, next_month).unwrap().billing_period,
+            NaiveDate::from_ymd_opt(2024, 4, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invoice_not_yet_overdue_has_no_fee() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        assert_eq!(assess_late_fee(&overdue_invoice(today, 0), today), None);
+
+        let mut paid = overdue_invoice(today, 35);
+        paid.balance = Decimal::ZERO;
+        assert_eq!(assess_late_fee(&paid, today), None);
+    }
+}
@@ -0,0 +1,87 @@
+// Job Locks - Postgres advisory locks so that when several replicas run the
+// scheduler, only one of them runs a given job at a time
+
+use sqlx::pool::PoolConnection;
+use sqlx::{PgPool, Postgres};
+use tracing::warn;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Advisory lock key for a job: a 64-bit FNV-1a hash of the namespaced job
+/// key, so every replica derives the same key without coordinating
+pub fn lock_key(job_key: &str) -> i64 {
+    let hash = format!("resolve:job:{}", job_key)
+        .bytes()
+        .fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME));
+    hash as i64
+}
+
+/// A held job lock. Session-level advisory locks belong to the connection
+/// that took them, so the lock keeps that connection out of the pool until
+/// released. Dropping it without `release` closes the connection, which
+/// frees the lock too.
+#[derive(Debug)]
+pub struct JobLock {
+    key: i64,
+    conn: Option<PoolConnection<Postgres>>,
+}
+
+impl JobLock {
+    /// Take the job's lock if no one holds it. Returns `None` rather than
+    /// waiting when another instance is running the job.
+    pub async fn try_acquire(pool: &PgPool, job_key: &str) -> Result<Option<Self>, sqlx::Error> {
+        let key = lock_key(job_key);
+        let mut conn = pool.acquire().await?;
+
+        let acquired: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(key)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        Ok(acquired.then_some(Self { key, conn: Some(conn) }))
+    }
+
+    pub async fn release(mut self) -> Result<(), sqlx::Error> {
+        if let Some(mut conn) = self.conn.take() {
+            let released = sqlx::query_scalar::<_, bool>("SELECT pg_advisory_unlock($1)")
+                .bind(self.key)
+                .fetch_one(&mut *conn)
+                .await;
+            match released {
+                Ok(true) => {}
+                Ok(false) => warn!("Job lock {} was not held when released", self.key),
+                Err(e) => {
+                    // The lock may still be held; closing the connection frees it
+                    drop(conn.detach());
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for JobLock {
+    fn drop(&mut self) {
+        // Never hand a connection still holding the lock back to the pool
+        if let Some(conn) = self.conn.take() {
+            drop(conn.detach());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::scheduler::JOBS;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_lock_key_is_stable_and_distinct_per_job() {
+        assert_eq!(lock_key("recurring_billing"), lock_key("recurring_billing"));
+
+        let keys: HashSet<i64> = JOBS.iter().map(|job| lock_key(job.key)).collect();
+        assert_eq!(keys.len(), JOBS.len());
+    }
+}
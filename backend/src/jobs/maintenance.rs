@@ -4,6 +4,8 @@ use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use tracing::{error, info, warn};
 
+use crate::files::reconcile::{self, ReconcileOptions, ReconcileReport};
+
 pub struct MaintenanceJobs;
 
 impl MaintenanceJobs {
@@ -189,103 +191,42 @@ impl MaintenanceJobs {
             info!("Deleted {} expired refresh tokens", refresh_deleted);
         }
 
-        // Clean up expired API keys
+        // Revoke expired API keys; they're kept so audit entries still
+        // point at them
         let api_key_result = sqlx::query(
-            "DELETE FROM api_keys WHERE expires_at IS NOT NULL AND expires_at < NOW()"
+            "UPDATE api_keys SET is_active = false, revoked_at = NOW()
+             WHERE expires_at IS NOT NULL AND expires_at < NOW() AND revoked_at IS NULL"
         )
         .execute(db_pool)
         .await?;
 
-        let api_keys_deleted = api_key_result.rows_affected() as i64;
+        let api_keys_revoked = api_key_result.rows_affected() as i64;
 
-        if api_keys_deleted > 0 {
-            info!("Deleted {} expired API keys", api_keys_deleted);
+        if api_keys_revoked > 0 {
+            info!("Revoked {} expired API keys", api_keys_revoked);
         }
 
-        Ok(deleted + refresh_deleted + api_keys_deleted)
+        Ok(deleted + refresh_deleted + api_keys_revoked)
     }
 
-    /// Clean up old audit logs beyond retention period
-    pub async fn cleanup_old_audit_logs(db_pool: &PgPool, retention_days: i32) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
-        info!("Cleaning up audit logs older than {} days", retention_days);
-
-        // First, archive important audit entries before deletion
-        sqlx::query(
-            r#"
-            INSERT INTO audit_log_archive (id, user_id, action, resource_type, resource_id, details, ip_address, created_at)
-            SELECT id, user_id, action, resource_type, resource_id, details, ip_address, created_at
-            FROM audit_log
-            WHERE created_at < NOW() - ($1 || ' days')::interval
-                AND severity IN ('critical', 'high')
-            ON CONFLICT (id) DO NOTHING
-            "#
-        )
-        .bind(retention_days)
-        .execute(db_pool)
-        .await?;
-
-        // Delete old audit logs
-        let result = sqlx::query(
-            "DELETE FROM audit_log WHERE created_at < NOW() - ($1 || ' days')::interval"
-        )
-        .bind(retention_days)
-        .execute(db_pool)
-        .await?;
-
-        let deleted = result.rows_affected() as i64;
-
-        if deleted > 0 {
-            info!("Deleted {} old audit log entries", deleted);
-        }
-
-        Ok(deleted)
-    }
-
-    /// Clean up orphaned files not referenced by any record
-    pub async fn cleanup_orphaned_files(db_pool: &PgPool) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
-        info!("Cleaning up orphaned files");
-
-        // Mark files for deletion that have no references
-        let result = sqlx::query(
-            r#"
-            UPDATE files
-            SET deleted_at = NOW()
-            WHERE id IN (
-                SELECT f.id FROM files f
-                LEFT JOIN ticket_attachments ta ON f.id = ta.file_id
-                LEFT JOIN kb_article_attachments ka ON f.id = ka.file_id
-                LEFT JOIN asset_documents ad ON f.id = ad.file_id
-                WHERE ta.id IS NULL
-                    AND ka.id IS NULL
-                    AND ad.id IS NULL
-                    AND f.created_at < NOW() - INTERVAL '24 hours'
-                    AND f.deleted_at IS NULL
-            )
-            "#
-        )
-        .execute(db_pool)
-        .await?;
-
-        let marked = result.rows_affected() as i64;
-
-        if marked > 0 {
-            info!("Marked {} orphaned files for deletion", marked);
-        }
-
-        // Actually delete files marked more than 7 days ago
-        let delete_result = sqlx::query(
-            "DELETE FROM files WHERE deleted_at < NOW() - INTERVAL '7 days'"
-        )
-        .execute(db_pool)
-        .await?;
-
-        let deleted = delete_result.rows_affected() as i64;
-
-        if deleted > 0 {
-            info!("Permanently deleted {} orphaned files", deleted);
+    /// Reconcile file records against storage. Dry run unless
+    /// FILE_RECONCILE_APPLY=true; see `files::reconcile`.
+    pub async fn reconcile_files(db_pool: &PgPool) -> Result<ReconcileReport, Box<dyn std::error::Error + Send + Sync>> {
+        info!("Reconciling files against storage");
+
+        let report = reconcile::reconcile(db_pool, &ReconcileOptions::from_env()).await?;
+
+        if report.total() > 0 {
+            info!(
+                "File reconciliation{}: {} missing objects, {} orphaned files, {} unreferenced objects",
+                if report.dry_run { " (dry run)" } else { "" },
+                report.missing_objects.len(),
+                report.orphaned_files.len(),
+                report.unreferenced_objects.len()
+            );
         }
 
-        Ok(marked + deleted)
+        Ok(report)
     }
 
     /// Run VACUUM ANALYZE to optimize database performance
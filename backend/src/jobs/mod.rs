@@ -4,13 +4,27 @@
 // Jobs are scheduled using tokio-cron-scheduler and run automatically at specified intervals.
 
 pub mod scheduler;
+pub mod history;
+pub mod lock;
 pub mod sla_checker;
 pub mod expiration_monitor;
 pub mod recurring_billing;
+pub mod late_fees;
+pub mod recurring_tickets;
+pub mod project_budgets;
+pub mod contract_renewals;
+pub mod credential_rotation;
+pub mod scheduled_reports;
 pub mod maintenance;
 
-pub use scheduler::{JobScheduler, JobConfig, JobResult, JobError};
+pub use scheduler::{JobScheduler, JobRunner, JobConfig, JobError};
 pub use sla_checker::SlaCheckerJob;
 pub use expiration_monitor::ExpirationMonitorJob;
 pub use recurring_billing::RecurringBillingJob;
+pub use late_fees::LateFeeJob;
+pub use recurring_tickets::RecurringTicketJob;
+pub use project_budgets::ProjectBudgetJob;
+pub use contract_renewals::ContractRenewalJob;
+pub use credential_rotation::CredentialRotationJob;
+pub use scheduled_reports::ScheduledReportJob;
 pub use maintenance::MaintenanceJobs;
@@ -0,0 +1,45 @@
+// Project Budget Job - Alerts project managers as budgets are consumed
+
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::services::project_budget;
+
+#[derive(Debug)]
+pub struct ProjectBudgetJob {
+    db_pool: PgPool,
+    thresholds: Vec<i32>,
+}
+
+#[derive(Debug, Default)]
+pub struct ProjectBudgetJobResult {
+    pub projects_checked: i32,
+    pub alerts_raised: i32,
+    pub errors: Vec<String>,
+}
+
+impl ProjectBudgetJob {
+    /// `thresholds` are percentages of budget consumed, e.g. `[80, 100]`
+    pub fn new(db_pool: PgPool, thresholds: Vec<i32>) -> Self {
+        Self { db_pool, thresholds }
+    }
+
+    pub async fn run(&self) -> Result<ProjectBudgetJobResult, Box<dyn std::error::Error + Send + Sync>> {
+        let check = project_budget::check_budgets(&self.db_pool, &self.thresholds).await?;
+
+        let mut result = ProjectBudgetJobResult {
+            projects_checked: check.checked as i32,
+            ..Default::default()
+        };
+
+        for alert in check.alerts {
+            result.alerts_raised += 1;
+            info!(
+                "Budget alert for project {}: {}% consumed ({}% threshold), {} notified",
+                alert.project_name, alert.percent_consumed, alert.threshold_percent, alert.notified
+            );
+        }
+
+        Ok(result)
+    }
+}
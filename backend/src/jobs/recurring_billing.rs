@@ -7,6 +7,8 @@ use sqlx::{FromRow, PgPool};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+use crate::services::canned_response_render::TemplateVariables;
+use crate::services::{email_templates, invoice_numbering};
 use crate::services::EmailService;
 
 #[derive(Debug)]
@@ -151,8 +153,9 @@ impl RecurringBillingJob {
 
     async fn create_invoice_for_service(&self, service: &RecurringService) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
         let invoice_id = Uuid::new_v4();
-        let invoice_number = self.generate_invoice_number().await?;
         let today = Utc::now().date_naive();
+        let mut tx = self.db_pool.begin().await?;
+        let invoice_number = invoice_numbering::next_invoice_number(&mut tx, today).await?;
         let due_date = today + chrono::Duration::days(30); // Net 30
 
         // Create invoice
@@ -173,7 +176,7 @@ impl RecurringBillingJob {
         .bind(Decimal::ZERO)
         .bind(service.amount)
         .bind(format!("Recurring service: {}", service.service_name))
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
         .await?;
 
         // Create line item
@@ -190,8 +193,10 @@ impl RecurringBillingJob {
         .bind(invoice_id)
         .bind(format!("{} - {}", service.service_name, billing_period))
         .bind(service.amount)
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
         .await?;
+        tx.commit().await?;
+        crate::middleware::prometheus::record_invoice_generated("recurring");
 
         Ok(invoice_id)
     }
@@ -229,17 +234,6 @@ impl RecurringBillingJob {
         format!("{} - {}", billing_date.format("%b %d, %Y"), end_date.format("%b %d, %Y"))
     }
 
-    async fn generate_invoice_number(&self) -> Result<String, sqlx::Error> {
-        let result = sqlx::query_scalar::<_, Option<i32>>(
-            "SELECT COALESCE(MAX(CAST(SUBSTRING(invoice_number FROM '^INV-(\\d+)$') AS INTEGER)), 0) + 1 FROM invoices WHERE invoice_number ~ '^INV-\\d+$'"
-        )
-        .fetch_one(&self.db_pool)
-        .await?;
-
-        let next_number = result.unwrap_or(1);
-        Ok(format!("INV-{:06}", next_number))
-    }
-
     async fn send_invoice_email(&self, service: &RecurringService, invoice_id: Uuid) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let invoice = sqlx::query_as::<_, (String, Decimal, NaiveDate)>(
             "SELECT invoice_number, total_amount, due_date FROM invoices WHERE id = $1"
@@ -248,80 +242,23 @@ impl RecurringBillingJob {
         .fetch_one(&self.db_pool)
         .await?;
 
-        let subject = format!("Invoice {} - {} - ${}", invoice.0, service.service_name, invoice.1);
+        let mut vars = TemplateVariables::new();
+        vars.set("invoice_number", Some(invoice.0.as_str()))
+            .set("amount", Some(invoice.1.to_string()))
+            .set("invoice_date", Some(Utc::now().format("%B %d, %Y").to_string()))
+            .set("due_date", Some(invoice.2.format("%B %d, %Y").to_string()))
+            .set("description", Some(service.service_name.as_str()))
+            .set("client_name", Some(service.client_name.as_str()))
+            .set("portal_url", Some(email_templates::portal_link(&format!("invoices/{}", invoice_id))));
 
-        let html_body = format!(
-            r#"
-            <html>
-            <head>
-                <style>
-                    body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; margin: 0; padding: 20px; background: #f5f5f5; }}
-                    .container {{ max-width: 600px; margin: 0 auto; background: white; border-radius: 12px; overflow: hidden; box-shadow: 0 4px 12px rgba(0,0,0,0.1); }}
-                    .header {{ background: linear-gradient(135deg, #1f2937 0%, #374151 100%); color: white; padding: 24px; text-align: center; }}
-                    .content {{ padding: 24px; }}
-                    .invoice-details {{ background: #f9fafb; border-radius: 8px; padding: 20px; margin: 20px 0; }}
-                    .amount {{ font-size: 32px; font-weight: 700; color: #1f2937; text-align: center; margin: 20px 0; }}
-                    .detail-row {{ display: flex; justify-content: space-between; padding: 10px 0; border-bottom: 1px solid #e5e7eb; }}
-                    .detail-row:last-child {{ border-bottom: none; }}
-                    .footer {{ background: #f9fafb; padding: 16px 24px; text-align: center; color: #6b7280; font-size: 14px; }}
-                </style>
-            </head>
-            <body>
-                <div class="container">
-                    <div class="header">
-                        <h1 style="margin: 0;">Invoice</h1>
-                        <p style="margin: 8px 0 0; opacity: 0.9;">{}</p>
-                    </div>
-                    <div class="content">
-                        <p>Dear {},</p>
-                        <p>Please find attached your invoice for recurring services.</p>
-
-                        <div class="invoice-details">
-                            <div class="amount">${}</div>
-                            <div class="detail-row">
-                                <span>Invoice Number</span>
-                                <strong>{}</strong>
-                            </div>
-                            <div class="detail-row">
-                                <span>Service</span>
-                                <strong>{}</strong>
-                            </div>
-                            <div class="detail-row">
-                                <span>Due Date</span>
-                                <strong>{}</strong>
-                            </div>
-                        </div>
-
-                        <p style="background: #f0f9ff; padding: 16px; border-radius: 8px; color: #0369a1;">
-                            Payment is due by <strong>{}</strong>. Please ensure timely payment to avoid any service interruptions.
-                        </p>
-
-                        <p>If you have any questions about this invoice, please don't hesitate to contact us.</p>
-
-                        <p>Thank you for your business!</p>
-                    </div>
-                    <div class="footer">
-                        <p>Resolve MSP Platform - Billing Department</p>
-                    </div>
-                </div>
-            </body>
-            </html>
-            "#,
-            invoice.0, // invoice number in header
-            service.client_name,
-            invoice.1, // amount
-            invoice.0, // invoice number
-            service.service_name,
-            invoice.2.format("%B %d, %Y"),
-            invoice.2.format("%B %d, %Y")
-        );
+        let email = email_templates::render(&self.db_pool, email_templates::INVOICE_SENT, Some(service.client_id), &vars).await?;
 
         self.email_service.send_email(
             service.client_email.as_deref().unwrap_or(""),
             Some(&service.client_name),
-            &subject,
-            &html_body,
-            None
+            &email.subject,
+            &email.html_body,
+            email.text_body.as_deref()
         ).await?;
 
         Ok(())
@@ -410,108 +347,42 @@ impl RecurringBillingJob {
     }
 
     async fn send_payment_reminder_email(&self, invoice: &UnpaidInvoice) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let is_overdue = invoice.days_overdue > 0;
-        let urgency = if invoice.days_overdue > 14 {
-            ("FINAL NOTICE", "#dc2626", "#fef2f2")
+        let (status, message) = if invoice.days_overdue > 14 {
+            (
+                format!("{} days overdue", invoice.days_overdue),
+                "This is a final notice. Please arrange payment immediately to avoid service interruption and late fees.",
+            )
         } else if invoice.days_overdue > 0 {
-            ("OVERDUE", "#f97316", "#fff7ed")
+            (
+                format!("{} days overdue", invoice.days_overdue),
+                "Please arrange payment immediately to avoid service interruption and potential late fees.",
+            )
         } else if invoice.days_overdue == 0 {
-            ("DUE TODAY", "#eab308", "#fefce8")
+            ("due today".to_string(), "Please arrange payment today.")
         } else {
-            ("REMINDER", "#3b82f6", "#eff6ff")
+            (
+                format!("due in {} days", -invoice.days_overdue),
+                "This is a friendly reminder that your payment is due soon.",
+            )
         };
 
-        let subject = if is_overdue {
-            format!("[{}] Invoice {} is {} days overdue - ${}", urgency.0, invoice.invoice_number, invoice.days_overdue, invoice.total_amount)
-        } else {
-            format!("[{}] Invoice {} - Payment Due {} - ${}", urgency.0, invoice.invoice_number,
-                    if invoice.days_overdue == 0 { "Today".to_string() } else { format!("in {} days", -invoice.days_overdue) },
-                    invoice.total_amount)
-        };
+        let mut vars = TemplateVariables::new();
+        vars.set("invoice_number", Some(invoice.invoice_number.as_str()))
+            .set("amount", Some(invoice.total_amount.to_string()))
+            .set("due_date", Some(invoice.due_date.format("%B %d, %Y").to_string()))
+            .set("reminder_status", Some(status))
+            .set("reminder_message", Some(message))
+            .set("client_name", Some(invoice.client_name.as_str()))
+            .set("portal_url", Some(email_templates::portal_link(&format!("invoices/{}", invoice.id))));
 
-        let html_body = format!(
-            r#"
-            <html>
-            <head>
-                <style>
-                    body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; margin: 0; padding: 20px; background: #f5f5f5; }}
-                    .container {{ max-width: 600px; margin: 0 auto; background: white; border-radius: 12px; overflow: hidden; box-shadow: 0 4px 12px rgba(0,0,0,0.1); }}
-                    .header {{ background: {}; color: white; padding: 24px; text-align: center; }}
-                    .badge {{ display: inline-block; background: rgba(255,255,255,0.2); padding: 4px 12px; border-radius: 999px; font-size: 12px; font-weight: 600; margin-bottom: 8px; }}
-                    .content {{ padding: 24px; }}
-                    .alert-box {{ background: {}; border-left: 4px solid {}; padding: 16px; margin-bottom: 20px; border-radius: 0 8px 8px 0; }}
-                    .amount {{ font-size: 40px; font-weight: 700; color: {}; text-align: center; margin: 20px 0; }}
-                    .detail-row {{ display: flex; justify-content: space-between; padding: 10px 0; border-bottom: 1px solid #e5e7eb; }}
-                    .footer {{ background: #f9fafb; padding: 16px 24px; text-align: center; color: #6b7280; font-size: 14px; }}
-                </style>
-            </head>
-            <body>
-                <div class="container">
-                    <div class="header">
-                        <span class="badge">{}</span>
-                        <h1 style="margin: 0;">Payment {}</h1>
-                    </div>
-                    <div class="content">
-                        <div class="alert-box">
-                            <strong>Invoice {}</strong> {} requires your attention.
-                        </div>
-
-                        <div class="amount">${}</div>
-
-                        <div style="background: #f9fafb; border-radius: 8px; padding: 16px; margin: 20px 0;">
-                            <div class="detail-row">
-                                <span>Invoice Number</span>
-                                <strong>{}</strong>
-                            </div>
-                            <div class="detail-row">
-                                <span>Due Date</span>
-                                <strong>{}</strong>
-                            </div>
-                            <div class="detail-row">
-                                <span>Status</span>
-                                <strong style="color: {};">{}</strong>
-                            </div>
-                        </div>
-
-                        <p>{}</p>
-
-                        <p>If you have already made this payment, please disregard this reminder. If you have any questions or need to arrange a payment plan, please contact us immediately.</p>
-
-                        <p>Thank you for your prompt attention to this matter.</p>
-                    </div>
-                    <div class="footer">
-                        <p>Resolve MSP Platform - Billing Department</p>
-                    </div>
-                </div>
-            </body>
-            </html>
-            "#,
-            urgency.1, // header bg
-            urgency.2, // alert box bg
-            urgency.1, // alert box border
-            urgency.1, // amount color
-            urgency.0, // badge text
-            if is_overdue { "Overdue" } else { "Reminder" },
-            invoice.invoice_number,
-            if is_overdue { format!("is {} days overdue and", invoice.days_overdue) } else { "".to_string() },
-            invoice.total_amount,
-            invoice.invoice_number,
-            invoice.due_date.format("%B %d, %Y"),
-            urgency.1, // status color
-            if is_overdue { format!("{} Days Overdue", invoice.days_overdue) } else { "Pending".to_string() },
-            if is_overdue {
-                "Please arrange payment immediately to avoid service interruption and potential late fees."
-            } else {
-                "This is a friendly reminder that your payment is due soon. Please arrange payment at your earliest convenience."
-            }
-        );
+        let email = email_templates::render(&self.db_pool, email_templates::INVOICE_REMINDER, Some(invoice.client_id), &vars).await?;
 
         self.email_service.send_email(
             invoice.client_email.as_deref().unwrap_or(""),
             Some(&invoice.client_name),
-            &subject,
-            &html_body,
-            None
+            &email.subject,
+            &email.html_body,
+            email.text_body.as_deref()
         ).await?;
 
         Ok(())
@@ -0,0 +1,284 @@
+// Recurring Ticket Job - Creates tickets from templates on a schedule
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgPool};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::services::outbound_webhooks;
+
+/// Most missed runs skipped in one pass, so a bad schedule can't spin forever
+const MAX_CATCH_UP_RUNS: usize = 10_000;
+
+#[derive(Debug)]
+pub struct RecurringTicketJob {
+    db_pool: PgPool,
+}
+
+#[derive(Debug, Default)]
+pub struct RecurringTicketJobResult {
+    pub recurrences_checked: i32,
+    pub tickets_created: i32,
+    pub errors: Vec<String>,
+}
+
+/// A due recurrence joined to its template. Times are wall-clock times in
+/// the recurrence's time zone; Postgres converts to and from UTC.
+#[derive(Debug, Clone, FromRow)]
+struct DueRecurrence {
+    id: Uuid,
+    client_id: Uuid,
+    frequency: String,
+    interval_value: Option<i32>,
+    anchor_day: Option<i16>,
+    next_run: DateTime<Utc>,
+    next_run_local: NaiveDateTime,
+    now_local: NaiveDateTime,
+    subject: String,
+    details: String,
+    priority: Option<String>,
+    category_id: Option<Uuid>,
+    assigned_to: Option<Uuid>,
+    billable: Option<bool>,
+    estimated_hours: Option<Decimal>,
+    created_by: Uuid,
+}
+
+/// What a recurrence run produced
+enum RunOutcome {
+    Created { ticket_id: Uuid, number: i32 },
+    /// Another run already handled this occurrence
+    AlreadyHandled,
+}
+
+impl RecurringTicketJob {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    pub async fn run(&self) -> Result<RecurringTicketJobResult, Box<dyn std::error::Error + Send + Sync>> {
+        let mut result = RecurringTicketJobResult::default();
+
+        let due = sqlx::query_scalar::<_, Uuid>(
+            "SELECT id FROM recurring_tickets WHERE enabled = true AND next_run <= NOW() ORDER BY next_run"
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        result.recurrences_checked = due.len() as i32;
+
+        for recurrence_id in due {
+            match self.run_recurrence(recurrence_id).await {
+                Ok(RunOutcome::Created { ticket_id, number }) => {
+                    result.tickets_created += 1;
+                    info!("Created ticket #{} from recurring ticket {}", number, recurrence_id);
+                    let data = serde_json::json!({
+                        "ticket_id": ticket_id,
+                        "number": number,
+                        "recurring_ticket_id": recurrence_id,
+                        "source": "recurring"
+                    });
+                    outbound_webhooks::notify(&self.db_pool, outbound_webhooks::TICKET_CREATED, data).await;
+                }
+                Ok(RunOutcome::AlreadyHandled) => {}
+                Err(e) => {
+                    warn!("Recurring ticket {} failed: {}", recurrence_id, e);
+                    result.errors.push(format!("Failed to run recurring ticket {}: {}", recurrence_id, e));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Create the ticket for one due occurrence and move the schedule past
+    /// now, in one transaction. The row lock and the per-run unique index
+    /// mean overlapping runs create the ticket once.
+    async fn run_recurrence(&self, recurrence_id: Uuid) -> Result<RunOutcome, Box<dyn std::error::Error + Send + Sync>> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let recurrence = sqlx::query_as::<_, DueRecurrence>(
+            r#"
+            SELECT
+                r.id, r.client_id, r.frequency, r.interval_value, r.anchor_day, r.next_run,
+                r.next_run AT TIME ZONE r.timezone AS next_run_local,
+                NOW() AT TIME ZONE r.timezone AS now_local,
+                t.subject, t.details, t.priority, t.category_id, t.assigned_to,
+                t.billable, t.estimated_hours, t.created_by
+            FROM recurring_tickets r
+            JOIN ticket_templates t ON t.id = r.template_id
+            WHERE r.id = $1 AND r.enabled = true AND r.next_run <= NOW()
+            FOR UPDATE OF r SKIP LOCKED
+            "#
+        )
+        .bind(recurrence_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(recurrence) = recurrence else {
+            return Ok(RunOutcome::AlreadyHandled);
+        };
+
+        let next_local = next_run_after(
+            recurrence.next_run_local,
+            recurrence.now_local,
+            &recurrence.frequency,
+            recurrence.interval_value.unwrap_or(1),
+            recurrence.anchor_day.map(|d| d as u32),
+        )
+        .ok_or_else(|| format!("unsupported frequency '{}'", recurrence.frequency))?;
+
+        let created = sqlx::query_as::<_, (Uuid, i32)>(
+            r#"
+            INSERT INTO tickets (number, client_id, opened_by, assigned_to, category_id, subject, details,
+                                 status, priority, billable, estimated_hours, source,
+                                 recurring_ticket_id, recurring_run_at)
+            SELECT COALESCE(MAX(number), 0) + 1, $1, $2, $3, $4, $5, $6, 'open', $7, $8, $9, 'recurring', $10, $11
+            FROM tickets
+            ON CONFLICT (recurring_ticket_id, recurring_run_at) WHERE recurring_ticket_id IS NOT NULL DO NOTHING
+            RETURNING id, number
+            "#
+        )
+        .bind(recurrence.client_id)
+        .bind(recurrence.created_by)
+        .bind(recurrence.assigned_to)
+        .bind(recurrence.category_id)
+        .bind(&recurrence.subject)
+        .bind(&recurrence.details)
+        .bind(recurrence.priority.as_deref().unwrap_or("medium"))
+        .bind(recurrence.billable.unwrap_or(true))
+        .bind(recurrence.estimated_hours)
+        .bind(recurrence.id)
+        .bind(recurrence.next_run)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE recurring_tickets
+            SET next_run = $2::timestamp AT TIME ZONE timezone, last_run = NOW(), updated_at = NOW()
+            WHERE id = $1
+            "#
+        )
+        .bind(recurrence.id)
+        .bind(next_local)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(match created {
+            Some((ticket_id, number)) => {
+                crate::middleware::prometheus::record_ticket_created("recurring");
+                RunOutcome::Created { ticket_id, number }
+            }
+            None => RunOutcome::AlreadyHandled,
+        })
+    }
+}
+
+/// The occurrence after `from` for `frequency` every `interval` periods.
+/// Monthly and longer schedules land on `anchor_day` (default `from`'s
+/// day), or the month's last day when it is shorter. `None` for an unknown
+/// frequency.
+pub fn advance(from: NaiveDateTime, frequency: &str, interval: i32, anchor_day: Option<u32>) -> Option<NaiveDateTime> {
+    let n = interval.max(1) as u32;
+    let months = match frequency {
+        "daily" => return from.checked_add_days(Days::new(u64::from(n))),
+        "weekly" => return from.checked_add_days(Days::new(7 * u64::from(n))),
+        "monthly" => n,
+        "quarterly" => 3 * n,
+        "yearly" => 12 * n,
+        _ => return None,
+    };
+
+    let month_index = from.year() * 12 + from.month0() as i32 + months as i32;
+    let (year, month) = (month_index.div_euclid(12), month_index.rem_euclid(12) as u32 + 1);
+    let day = anchor_day.unwrap_or(from.day()).min(days_in_month(year, month)?);
+    Some(NaiveDate::from_ymd_opt(year, month, day)?.and_time(from.time()))
+}
+
+/// The first occurrence after `now`, skipping any missed while the job
+/// wasn't running
+pub fn next_run_after(
+    from: NaiveDateTime,
+    now: NaiveDateTime,
+    frequency: &str,
+    interval: i32,
+    anchor_day: Option<u32>,
+) -> Option<NaiveDateTime> {
+    let mut next = advance(from, frequency, interval, anchor_day)?;
+    for _ in 0..MAX_CATCH_UP_RUNS {
+        if next > now {
+            return Some(next);
+        }
+        next = advance(next, frequency, interval, anchor_day)?;
+    }
+    None
+}
+
+fn days_in_month(year: i32, month: u32) -> Option<u32> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((next - first).num_days() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_daily_and_weekly_keep_wall_clock_time() {
+        // 9 AM local stays 9 AM across the March DST change
+        assert_eq!(advance(at(2024, 3, 9, 9), "daily", 1, None), Some(at(2024, 3, 10, 9)));
+        assert_eq!(advance(at(2024, 3, 9, 9), "daily", 3, None), Some(at(2024, 3, 12, 9)));
+        assert_eq!(advance(at(2024, 10, 28, 9), "weekly", 1, None), Some(at(2024, 11, 4, 9)));
+        assert_eq!(advance(at(2024, 12, 30, 9), "weekly", 2, None), Some(at(2025, 1, 13, 9)));
+    }
+
+    #[test]
+    fn test_monthly_clamps_and_returns_to_anchor_day() {
+        let jan = at(2024, 1, 31, 8);
+        let feb = advance(jan, "monthly", 1, Some(31)).unwrap();
+        assert_eq!(feb, at(2024, 2, 29, 8));
+        // The anchor brings March back to the 31st rather than drifting to the 29th
+        assert_eq!(advance(feb, "monthly", 1, Some(31)), Some(at(2024, 3, 31, 8)));
+        assert_eq!(advance(at(2024, 11, 15, 8), "monthly", 2, None), Some(at(2025, 1, 15, 8)));
+    }
+
+    #[test]
+    fn test_quarterly_and_yearly() {
+        assert_eq!(advance(at(2024, 11, 30, 8), "quarterly", 1, Some(30)), Some(at(2025, 2, 28, 8)));
+        assert_eq!(advance(at(2024, 2, 29, 8), "yearly", 1, Some(29)), Some(at(2025, 2, 28, 8)));
+        assert_eq!(advance(at(2025, 2, 28, 8), "yearly", 3, Some(29)), Some(at(2028, 2, 29, 8)));
+    }
+
+    #[test]
+    fn test_next_run_skips_missed_occurrences() {
+        let now = at(2024, 3, 20, 12);
+        assert_eq!(next_run_after(at(2024, 3, 1, 9), now, "daily", 1, None), Some(at(2024, 3, 21, 9)));
+        assert_eq!(next_run_after(at(2024, 3, 20, 9), now, "weekly", 1, None), Some(at(2024, 3, 27, 9)));
+        // A run due exactly now is not next
+        assert_eq!(next_run_after(at(2024, 3, 19, 12), now, "daily", 1, None), Some(at(2024, 3, 21, 12)));
+    }
+
+    #[test]
+    fn test_interval_below_one_is_treated_as_one() {
+        assert_eq!(advance(at(2024, 3, 1, 9), "daily", 0, None), Some(at(2024, 3, 2, 9)));
+    }
+
+    #[test]
+    fn test_unknown_frequency() {
+        assert_eq!(advance(at(2024, 3, 1, 9), "fortnightly", 1, None), None);
+        assert_eq!(next_run_after(at(2024, 3, 1, 9), at(2024, 4, 1, 9), "hourly", 1, None), None);
+    }
+}
@@ -0,0 +1,44 @@
+// Scheduled Report Job - Renders due scheduled reports and emails them to their recipients
+
+use chrono::Utc;
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::services::scheduled_reports;
+use crate::services::EmailService;
+
+#[derive(Debug)]
+pub struct ScheduledReportJob {
+    db_pool: PgPool,
+    email_service: EmailService,
+}
+
+#[derive(Debug, Default)]
+pub struct ScheduledReportJobResult {
+    pub reports_due: i32,
+    pub reports_sent: i32,
+    pub errors: Vec<String>,
+}
+
+impl ScheduledReportJob {
+    pub fn new(db_pool: PgPool, email_service: EmailService) -> Self {
+        Self { db_pool, email_service }
+    }
+
+    pub async fn run(&self) -> Result<ScheduledReportJobResult, Box<dyn std::error::Error + Send + Sync>> {
+        let summary = scheduled_reports::run_due(&self.db_pool, &self.email_service, Utc::now()).await?;
+
+        let mut result = ScheduledReportJobResult {
+            reports_due: summary.due as i32,
+            reports_sent: summary.completed as i32,
+            ..Default::default()
+        };
+        if summary.failed > 0 {
+            result.errors.push(format!("{} scheduled report(s) failed", summary.failed));
+        }
+
+        info!("Scheduled reports: {} due, {} sent, {} failed", summary.due, summary.completed, summary.failed);
+
+        Ok(result)
+    }
+}
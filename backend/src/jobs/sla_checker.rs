@@ -6,8 +6,9 @@ use sqlx::{FromRow, PgPool};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::services::EmailService;
+use crate::services::{chat_notifications, EmailService};
 use crate::websocket::WsManager;
+use crate::workflows::{SlaBreachType, TriggerEvent, WorkflowEngine};
 
 #[derive(Debug)]
 pub struct SlaCheckerJob {
@@ -23,6 +24,7 @@ pub struct SlaCheckResult {
     pub breaches_detected: i32,
     pub escalations_triggered: i32,
     pub notifications_sent: i32,
+    pub workflows_triggered: i32,
     pub errors: Vec<String>,
 }
 
@@ -86,6 +88,19 @@ impl SlaCheckerJob {
 
         let now = Utc::now();
 
+        // Workflows are loaded once per run; breaches are still processed if this fails
+        let workflow_engine = match WorkflowEngine::new(
+            self.db_pool.clone(),
+            self.email_service.clone(),
+            self.ws_manager.clone(),
+        ).await {
+            Ok(engine) => Some(engine),
+            Err(e) => {
+                warn!("Failed to load workflows for SLA breach triggers: {}", e);
+                None
+            }
+        };
+
         for ticket in tickets {
             // Skip paused tickets
             if ticket.pause_start.is_some() {
@@ -110,9 +125,14 @@ impl SlaCheckerJob {
                     } else {
                         result.notifications_sent += 1;
                     }
+                    chat_notifications::notify_sla_breach(&self.db_pool, ticket.ticket_id, "response", breach_minutes).await;
 
                     // Broadcast via WebSocket
                     self.broadcast_breach_alert(&ticket, "response", breach_minutes).await;
+
+                    if let Some(engine) = &workflow_engine {
+                        self.fire_breach_workflows(engine, &ticket, SlaBreachType::FirstResponse, breach_minutes, &mut result).await;
+                    }
                 }
             }
 
@@ -134,6 +154,7 @@ impl SlaCheckerJob {
                     } else {
                         result.notifications_sent += 1;
                     }
+                    chat_notifications::notify_sla_breach(&self.db_pool, ticket.ticket_id, "resolution", breach_minutes).await;
 
                     // Check for escalation
                     if self.auto_escalation_enabled {
@@ -150,6 +171,10 @@ impl SlaCheckerJob {
 
                     // Broadcast via WebSocket
                     self.broadcast_breach_alert(&ticket, "resolution", breach_minutes).await;
+
+                    if let Some(engine) = &workflow_engine {
+                        self.fire_breach_workflows(engine, &ticket, SlaBreachType::Resolution, breach_minutes, &mut result).await;
+                    }
                 }
             }
 
@@ -463,6 +488,73 @@ impl SlaCheckerJob {
         }
     }
 
+    /// Emit an `SlaBreach` trigger for the ticket, at most once per breach type.
+    /// Workflow actions may change priority or reassign the ticket, which can reset
+    /// its SLA tracking; the ledger keeps that from re-firing the same automation.
+    /// The ledger entry only commits once the workflows have run, so a failed
+    /// run is tried again on the next check.
+    async fn fire_breach_workflows(
+        &self,
+        engine: &WorkflowEngine,
+        ticket: &TicketSlaInfo,
+        breach_type: SlaBreachType,
+        breach_minutes: i32,
+        result: &mut SlaCheckResult,
+    ) {
+        let mut tx = match self.db_pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                result.errors.push(format!("Failed to record SLA breach trigger for ticket {}: {}", ticket.ticket_id, e));
+                return;
+            }
+        };
+        let claimed = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO sla_breach_triggers (ticket_id, breach_type)
+            VALUES ($1, $2)
+            ON CONFLICT (ticket_id, breach_type) DO NOTHING
+            RETURNING id
+            "#
+        )
+        .bind(ticket.ticket_id)
+        .bind(breach_type.as_str())
+        .fetch_optional(&mut *tx)
+        .await;
+
+        match claimed {
+            Ok(Some(_)) => {}
+            // Already fired for this ticket and breach type
+            Ok(None) => return,
+            Err(e) => {
+                result.errors.push(format!("Failed to record SLA breach trigger for ticket {}: {}", ticket.ticket_id, e));
+                return;
+            }
+        }
+
+        let event = TriggerEvent::sla_breach(
+            ticket.ticket_id,
+            breach_type,
+            &ticket.priority,
+            ticket.client_id,
+            breach_minutes,
+            ticket.assigned_to,
+        );
+
+        match engine.process_event(event).await {
+            Ok(instances) => {
+                result.workflows_triggered += instances.len() as i32;
+                if let Err(e) = tx.commit().await {
+                    result.errors.push(format!("Failed to record SLA breach trigger for ticket {}: {}", ticket.ticket_id, e));
+                }
+            }
+            // Dropping the transaction leaves the breach unstamped
+            Err(e) => result.errors.push(format!(
+                "Failed to run SLA breach workflows for ticket {}: {}",
+                ticket.ticket_id, e
+            )),
+        }
+    }
+
     async fn check_approaching_breach(&self, ticket: &TicketSlaInfo, now: &DateTime<Utc>, result: &mut SlaCheckResult) {
         // Warning thresholds (in minutes)
         let warning_thresholds = vec![60, 30, 15, 5]; // 1 hour, 30 min, 15 min, 5 min
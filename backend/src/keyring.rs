@@ -0,0 +1,399 @@
+//! Versioned AES-256-GCM keys for encrypted columns
+//!
+//! Each kind of secret (integration credentials, MFA secrets, itdoc
+//! credentials and passwords, SSL private keys, license keys) has its own
+//! key ring read from the environment. For `INTEGRATION_ENCRYPTION_KEY`:
+//!
+//! - `INTEGRATION_ENCRYPTION_KEYS` lists keys as `id:hex,id:hex`
+//! - `INTEGRATION_ENCRYPTION_KEY` is the original single key, kept under
+//!   the id `legacy`
+//! - `INTEGRATION_ENCRYPTION_KEY_ID` picks the key new values are encrypted
+//!   with, defaulting to the first listed key
+//!
+//! The credential ring also holds `ENCRYPTION_KEY`, which passwords were
+//! encrypted under before they moved onto it, as the id `passwords`.
+//!
+//! Ciphertext is `id:base64(nonce || ciphertext)`, so any configured key can
+//! still decrypt what it wrote. Values written before key ids existed have no
+//! prefix and are tried against the legacy key first. Rotating is a matter
+//! of adding a new key, making it current, and re-encrypting stored values
+//! with [`crate::services::key_rotation`].
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+use std::fmt;
+
+pub const LEGACY_KEY_ID: &str = "legacy";
+/// Id of the key passwords were encrypted under before they joined the
+/// credential ring
+const PASSWORDS_KEY_ID: &str = "passwords";
+/// The development fallback the old per-module helpers shipped with
+const INSECURE_DEFAULT_KEY: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyRingError {
+    #[error("Invalid encryption key configuration: {0}")]
+    Config(String),
+    #[error("No key with id '{0}' is configured")]
+    UnknownKey(String),
+    #[error("Malformed ciphertext")]
+    Malformed,
+    #[error("Encryption failed")]
+    Encrypt,
+    #[error("Decryption failed")]
+    Decrypt,
+    #[error("Decrypted value is not UTF-8")]
+    Utf8(#[from] std::string::FromUtf8Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPurpose {
+    Integration,
+    Mfa,
+    Credential,
+    SslPrivateKey,
+    LicenseKey,
+}
+
+impl KeyPurpose {
+    pub const ALL: [KeyPurpose; 5] = [
+        KeyPurpose::Integration,
+        KeyPurpose::Mfa,
+        KeyPurpose::Credential,
+        KeyPurpose::SslPrivateKey,
+        KeyPurpose::LicenseKey,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyPurpose::Integration => "integration",
+            KeyPurpose::Mfa => "mfa",
+            KeyPurpose::Credential => "credential",
+            KeyPurpose::SslPrivateKey => "ssl_private_key",
+            KeyPurpose::LicenseKey => "license_key",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str() == value)
+    }
+
+    /// Base name of the environment variables for this ring
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            KeyPurpose::Integration => "INTEGRATION_ENCRYPTION_KEY",
+            KeyPurpose::Mfa => "MFA_ENCRYPTION_KEY",
+            KeyPurpose::Credential => "CREDENTIAL_ENCRYPTION_KEY",
+            KeyPurpose::SslPrivateKey => "SSL_ENCRYPTION_KEY",
+            KeyPurpose::LicenseKey => "LICENSE_ENCRYPTION_KEY",
+        }
+    }
+
+    /// A variable from before key rings whose key this ring also holds
+    fn predecessor_env_var(&self) -> Option<&'static str> {
+        match self {
+            KeyPurpose::Credential => Some("ENCRYPTION_KEY"),
+            _ => None,
+        }
+    }
+}
+
+pub struct KeyRing {
+    purpose: KeyPurpose,
+    current: String,
+    keys: Vec<(String, Key<Aes256Gcm>)>,
+    insecure: bool,
+}
+
+// Hand-written so key material never ends up in logs
+impl fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyRing")
+            .field("purpose", &self.purpose)
+            .field("current", &self.current)
+            .field("key_ids", &self.key_ids())
+            .field("insecure", &self.insecure)
+            .finish()
+    }
+}
+
+fn valid_key_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 32 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn parse_key(name: &str, hex_key: &str) -> Result<[u8; 32], KeyRingError> {
+    hex::decode(hex_key.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| KeyRingError::Config(format!("{} must be 32 bytes (64 hex chars)", name)))
+}
+
+/// Whether this deployment is production (`RESOLVE_ENV` or `APP_ENV`)
+pub fn is_production() -> bool {
+    ["RESOLVE_ENV", "APP_ENV"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .any(|env| env.eq_ignore_ascii_case("production") || env.eq_ignore_ascii_case("prod"))
+}
+
+/// Refuse to run in production with a missing or known-insecure key
+pub fn check_production_keys() -> Result<(), KeyRingError> {
+    if !is_production() {
+        return Ok(());
+    }
+    for purpose in KeyPurpose::ALL {
+        if KeyRing::from_env(purpose)?.is_insecure() {
+            return Err(KeyRingError::Config(format!(
+                "{} is unset or uses the insecure default; configure a real key before running in production",
+                purpose.env_var()
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl KeyRing {
+    /// Build a ring from `(id, key)` pairs, encrypting with `current`
+    pub fn new(purpose: KeyPurpose, current: &str, keys: Vec<(String, [u8; 32])>) -> Result<Self, KeyRingError> {
+        let mut ring: Vec<(String, Key<Aes256Gcm>)> = Vec::with_capacity(keys.len());
+        for (id, key) in keys {
+            if !valid_key_id(&id) {
+                return Err(KeyRingError::Config(format!("invalid key id '{}'", id)));
+            }
+            if ring.iter().any(|(existing, _)| *existing == id) {
+                return Err(KeyRingError::Config(format!("duplicate key id '{}'", id)));
+            }
+            ring.push((id, *Key::<Aes256Gcm>::from_slice(&key)));
+        }
+
+        if !ring.iter().any(|(id, _)| id == current) {
+            return Err(KeyRingError::UnknownKey(current.to_string()));
+        }
+
+        let default = Key::<Aes256Gcm>::clone_from_slice(&parse_key("default", INSECURE_DEFAULT_KEY)?);
+        let insecure = ring.iter().any(|(_, key)| *key == default);
+
+        Ok(Self { purpose, current: current.to_string(), keys: ring, insecure })
+    }
+
+    /// Read the ring for `purpose` from the environment. With nothing
+    /// configured this falls back to the insecure development key, which
+    /// [`check_production_keys`] refuses at startup.
+    pub fn from_env(purpose: KeyPurpose) -> Result<Self, KeyRingError> {
+        let name = purpose.env_var();
+        let mut keys = Vec::new();
+
+        if let Ok(list) = std::env::var(format!("{}S", name)) {
+            for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let (id, hex_key) = entry
+                    .split_once(':')
+                    .ok_or_else(|| KeyRingError::Config(format!("{}S entries must be id:hex", name)))?;
+                keys.push((id.trim().to_string(), parse_key(name, hex_key)?));
+            }
+        }
+        if let Ok(legacy) = std::env::var(name) {
+            keys.push((LEGACY_KEY_ID.to_string(), parse_key(name, &legacy)?));
+        }
+        if let Some(var) = purpose.predecessor_env_var() {
+            if let Ok(value) = std::env::var(var) {
+                // Hex like the others, or the 32 raw bytes it used to take
+                let key = match <[u8; 32]>::try_from(value.as_bytes()) {
+                    Ok(raw) => raw,
+                    Err(_) => parse_key(var, &value)?,
+                };
+                keys.push((PASSWORDS_KEY_ID.to_string(), key));
+            }
+        }
+
+        if keys.is_empty() {
+            tracing::warn!("{} not set, using default (insecure for production)", name);
+            keys.push((LEGACY_KEY_ID.to_string(), parse_key(name, INSECURE_DEFAULT_KEY)?));
+        }
+
+        let current = match std::env::var(format!("{}_ID", name)) {
+            Ok(id) => id.trim().to_string(),
+            Err(_) => keys[0].0.clone(),
+        };
+
+        Self::new(purpose, &current, keys)
+    }
+
+    pub fn purpose(&self) -> KeyPurpose {
+        self.purpose
+    }
+
+    pub fn current_key_id(&self) -> &str {
+        &self.current
+    }
+
+    pub fn key_ids(&self) -> Vec<&str> {
+        self.keys.iter().map(|(id, _)| id.as_str()).collect()
+    }
+
+    /// True when any configured key is the well-known development default
+    pub fn is_insecure(&self) -> bool {
+        self.insecure
+    }
+
+    fn key(&self, id: &str) -> Option<&Key<Aes256Gcm>> {
+        self.keys.iter().find(|(key_id, _)| key_id == id).map(|(_, key)| key)
+    }
+
+    /// The key id a value was encrypted with; `None` for unprefixed values
+    pub fn key_id_of(ciphertext: &str) -> Option<&str> {
+        ciphertext.split_once(':').map(|(id, _)| id)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, KeyRingError> {
+        let key = self.key(&self.current).ok_or_else(|| KeyRingError::UnknownKey(self.current.clone()))?;
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let ciphertext = Aes256Gcm::new(key)
+            .encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+            .map_err(|_| KeyRingError::Encrypt)?;
+
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce_bytes);
+        sealed.extend_from_slice(&ciphertext);
+
+        Ok(format!("{}:{}", self.current, general_purpose::STANDARD.encode(sealed)))
+    }
+
+    pub fn encrypt_str(&self, plaintext: &str) -> Result<String, KeyRingError> {
+        self.encrypt(plaintext.as_bytes())
+    }
+
+    pub fn decrypt(&self, ciphertext: &str) -> Result<Vec<u8>, KeyRingError> {
+        let (candidates, encoded): (Vec<&Key<Aes256Gcm>>, &str) = match ciphertext.split_once(':') {
+            Some((id, encoded)) => {
+                let key = self.key(id).ok_or_else(|| KeyRingError::UnknownKey(id.to_string()))?;
+                (vec![key], encoded)
+            }
+            // Written before key ids: the legacy key first, then the rest
+            None => {
+                let mut keys: Vec<&Key<Aes256Gcm>> = self.key(LEGACY_KEY_ID).into_iter().collect();
+                keys.extend(self.keys.iter().filter(|(id, _)| id != LEGACY_KEY_ID).map(|(_, key)| key));
+                (keys, ciphertext)
+            }
+        };
+
+        let sealed = general_purpose::STANDARD.decode(encoded).map_err(|_| KeyRingError::Malformed)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(KeyRingError::Malformed);
+        }
+        let (nonce, body) = sealed.split_at(NONCE_LEN);
+
+        candidates
+            .into_iter()
+            .find_map(|key| Aes256Gcm::new(key).decrypt(Nonce::from_slice(nonce), body).ok())
+            .ok_or(KeyRingError::Decrypt)
+    }
+
+    pub fn decrypt_str(&self, ciphertext: &str) -> Result<String, KeyRingError> {
+        Ok(String::from_utf8(self.decrypt(ciphertext)?)?)
+    }
+
+    /// Whether a value is already under the current key
+    pub fn is_current(&self, ciphertext: &str) -> bool {
+        Self::key_id_of(ciphertext) == Some(self.current.as_str())
+    }
+
+    /// Re-encrypt a value under the current key, or `None` if it already is
+    pub fn reencrypt(&self, ciphertext: &str) -> Result<Option<String>, KeyRingError> {
+        if self.is_current(ciphertext) {
+            return Ok(None);
+        }
+        let plaintext = self.decrypt(ciphertext)?;
+        self.encrypt(&plaintext).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: [u8; 32] = [7u8; 32];
+    const NEW: [u8; 32] = [9u8; 32];
+
+    fn ring(current: &str, keys: &[(&str, [u8; 32])]) -> KeyRing {
+        let keys = keys.iter().map(|(id, key)| (id.to_string(), *key)).collect();
+        KeyRing::new(KeyPurpose::Integration, current, keys).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_with_new_decrypt_with_old() {
+        let before = ring("k1", &[("k1", OLD)]);
+        let old_value = before.encrypt_str("api-token").unwrap();
+        assert!(old_value.starts_with("k1:"));
+
+        let after = ring("k2", &[("k2", NEW), ("k1", OLD)]);
+        assert_eq!(after.decrypt_str(&old_value).unwrap(), "api-token");
+
+        let new_value = after.encrypt_str("api-token").unwrap();
+        assert!(new_value.starts_with("k2:"));
+        assert_eq!(after.decrypt_str(&new_value).unwrap(), "api-token");
+
+        // The old ring can't read values written under the new key
+        assert!(matches!(before.decrypt(&new_value), Err(KeyRingError::UnknownKey(id)) if id == "k2"));
+    }
+
+    #[test]
+    fn test_reencrypt_moves_values_to_current_key() {
+        let after = ring("k2", &[("k2", NEW), ("k1", OLD)]);
+        let old_value = ring("k1", &[("k1", OLD)]).encrypt_str("secret").unwrap();
+
+        let rotated = after.reencrypt(&old_value).unwrap().unwrap();
+        assert!(after.is_current(&rotated));
+        assert_eq!(ring("k2", &[("k2", NEW)]).decrypt_str(&rotated).unwrap(), "secret");
+        assert!(after.reencrypt(&rotated).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unprefixed_values_use_the_legacy_key() {
+        // The pre-rotation format: base64(nonce || ciphertext), no key id
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&OLD));
+        let nonce = [1u8; NONCE_LEN];
+        let mut sealed = nonce.to_vec();
+        sealed.extend(cipher.encrypt(Nonce::from_slice(&nonce), b"totp-seed".as_ref()).unwrap());
+        let legacy_value = general_purpose::STANDARD.encode(sealed);
+
+        let rotated = ring("k2", &[("k2", NEW), (LEGACY_KEY_ID, OLD)]);
+        assert_eq!(KeyRing::key_id_of(&legacy_value), None);
+        assert_eq!(rotated.decrypt_str(&legacy_value).unwrap(), "totp-seed");
+        assert!(!rotated.is_current(&legacy_value));
+
+        assert!(matches!(ring("k2", &[("k2", NEW)]).decrypt(&legacy_value), Err(KeyRingError::Decrypt)));
+    }
+
+    #[test]
+    fn test_configuration_errors() {
+        let keys = |ids: &[&str]| ids.iter().map(|id| (id.to_string(), OLD)).collect::<Vec<_>>();
+        assert!(matches!(KeyRing::new(KeyPurpose::Mfa, "k3", keys(&["k1"])), Err(KeyRingError::UnknownKey(_))));
+        assert!(matches!(KeyRing::new(KeyPurpose::Mfa, "k1", keys(&["k1", "k1"])), Err(KeyRingError::Config(_))));
+        assert!(matches!(KeyRing::new(KeyPurpose::Mfa, "a:b", keys(&["a:b"])), Err(KeyRingError::Config(_))));
+        assert!(parse_key("TEST_KEY", "abcd").is_err());
+
+        let default = parse_key("TEST_KEY", INSECURE_DEFAULT_KEY).unwrap();
+        assert!(ring("dev", &[("dev", default)]).is_insecure());
+        assert!(!ring("k1", &[("k1", OLD)]).is_insecure());
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_is_rejected() {
+        let keys = ring("k1", &[("k1", OLD)]);
+        assert!(matches!(keys.decrypt("k1:not base64!"), Err(KeyRingError::Malformed)));
+        assert!(matches!(keys.decrypt("k1:AAAA"), Err(KeyRingError::Malformed)));
+
+        let value = keys.encrypt_str("secret").unwrap();
+        let mut sealed = general_purpose::STANDARD.decode(&value[3..]).unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+        let tampered = format!("k1:{}", general_purpose::STANDARD.encode(sealed));
+        assert!(matches!(keys.decrypt(&tampered), Err(KeyRingError::Decrypt)));
+    }
+}
@@ -1,18 +1,21 @@
 use axum::{
     extract::State,
-    http::{HeaderValue, Method},
     routing::{get, post},
     Router,
 };
+use std::future::IntoFuture;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower::ServiceBuilder;
-use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod audit;
 mod auth;
+mod concurrency;
 mod config;
 mod database;
 mod error;
+mod etag;
 mod handlers;
 mod jobs;
 mod middleware;
@@ -27,6 +30,9 @@ mod itdoc;
 mod files;
 mod notifications;
 mod integrations;
+mod keyring;
+mod shutdown;
+mod transaction;
 
 pub use error::{ApiError, ApiResult, AppError};
 pub use pagination::{PaginatedResponse, PaginationParams, PaginationMeta};
@@ -38,6 +44,8 @@ mod tests;
 pub struct AppState {
     pub db_pool: sqlx::PgPool,
     pub ws_manager: websocket::WsManager,
+    /// Built once at startup; `None` if email couldn't be configured
+    pub email_service: Option<services::EmailService>,
 }
 
 #[tokio::main]
@@ -49,26 +57,84 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let config = config::Config::from_env()?;
+    keyring::check_production_keys()?;
     let db_pool = database::create_pool(&config.database_url).await?;
     
     database::migrate(&db_pool).await?;
 
+    middleware::prometheus::install_recorder();
+
+    // Registered before anything else starts so an early SIGTERM isn't lost
+    let shutdown_signal = shutdown::Signal::install()?;
+    let shutdown_timeout = shutdown::timeout_from_env();
+
     let ws_manager = websocket::WsManager::new();
-    let app_state = Arc::new(AppState { db_pool, ws_manager });
+    let email_service = match services::EmailService::new(&config.smtp).await {
+        Ok(email_service) => Some(email_service),
+        Err(e) => {
+            tracing::error!("Failed to configure email: {}", e);
+            None
+        }
+    };
+    let app_state = Arc::new(AppState {
+        db_pool: db_pool.clone(),
+        ws_manager: ws_manager.clone(),
+        email_service: email_service.clone(),
+    });
+
+    // Scheduled jobs run unless BACKGROUND_JOBS_ENABLED=false. Without
+    // working email the scheduler is left off rather than keeping the API down.
+    let jobs_enabled = std::env::var("BACKGROUND_JOBS_ENABLED").map_or(true, |v| v != "false");
+    let mut scheduler = match (jobs_enabled, email_service) {
+        (true, Some(email_service)) => {
+            let scheduler = jobs::JobScheduler::new(
+                db_pool.clone(),
+                email_service,
+                ws_manager.clone(),
+                jobs::JobConfig::default(),
+            )
+            .await?;
+            scheduler.start().await?;
+            Some(scheduler)
+        }
+        (true, None) => {
+            tracing::error!("Email isn't configured, background jobs are not running");
+            None
+        }
+        (false, _) => None,
+    };
+
+    let auth_rate_limiter = Arc::new(middleware::AuthRateLimiter::from_env());
+    {
+        let limiter = auth_rate_limiter.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                limiter.purge_stale();
+            }
+        });
+    }
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_headers(Any);
+    let cors = middleware::CorsConfig::from_env().layer();
 
     let app = Router::new()
         .route("/", get(|| async { "Resolve MSP Platform API v1.0.0" }))
         .route("/health", get(handlers::health_check))
         .route("/health/detailed", get(middleware::detailed_health_check))
-        .route("/metrics", get(middleware::metrics_endpoint))
+        .route("/metrics", get(middleware::prometheus_metrics))
+        .route("/metrics/json", get(middleware::metrics_endpoint))
         .route("/api/v1/dashboard", get(handlers::dashboard_stats))
-        .nest("/api/v1/auth", auth::auth_routes())
+        .route("/api/v1/dashboard/stream", get(handlers::dashboard_stream))
+        .nest(
+            "/api/v1/auth",
+            auth::auth_routes().layer(axum::middleware::from_fn_with_state(
+                auth_rate_limiter,
+                middleware::auth_rate_limit,
+            )),
+        )
         .nest("/api/v1/clients", handlers::client_routes())
+        .nest("/api/v1/contacts", handlers::contact_routes())
         .nest("/api/v1/tickets", handlers::ticket_routes())
         .nest("/api/v1/queues", handlers::ticket_queue_routes())
         .nest("/api/v1/canned-responses", handlers::canned_response_routes())
@@ -97,17 +163,47 @@ async fn main() -> anyhow::Result<()> {
         .nest("/api/v1/reporting", handlers::reporting_routes())
         .nest("/api/v1/email", handlers::email_routes())
         .nest("/api/v1/billing", handlers::billing_routes())
+        .nest("/api/v1/contracts", handlers::contract_routes())
         .nest("/api/v1/analytics", handlers::analytics_routes())
         .nest("/api/v1/teams", handlers::teams_routes())
+        .nest("/api/v1/workflows", handlers::workflow_routes())
+        .nest("/api/v1/webhooks", handlers::webhook_routes())
+        .nest("/api/v1/audit-logs", handlers::audit_log_routes())
+        .nest("/api/v1/encryption-keys", handlers::encryption_key_routes())
+        .nest("/api/v1/vendors", handlers::vendor_routes())
+        .nest("/api/v1/expenses", handlers::expense_routes())
+        .nest("/api/v1/admin/jobs", handlers::admin_job_routes())
+        .nest("/api/v1/admin/retention-policies", handlers::retention_policy_routes())
+        .nest("/api/v1/search", handlers::search_routes())
+        .nest("/api/v1/me", handlers::me_routes())
         .nest("/api/v1/docs", openapi::openapi_routes())
         .route("/ws", get(websocket::websocket_handler))
+        .route_layer(axum::middleware::from_fn(middleware::track_metrics))
         .layer(ServiceBuilder::new().layer(cors))
+        .layer(axum::middleware::from_fn(middleware::request_id_layer))
         .with_state(app_state);
 
     let listener = tokio::net::TcpListener::bind(&config.server_addr).await?;
     tracing::info!("Server running on {}", config.server_addr);
     
-    axum::serve(listener, app).await?;
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    shutdown_signal.cancel_on_signal(shutdown.clone());
+
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned());
+    shutdown::serve_until_drained(server.into_future(), shutdown, shutdown_timeout).await?;
+
+    if let Some(scheduler) = scheduler.as_mut() {
+        scheduler.shutdown(shutdown_timeout).await?;
+    }
+
+    let open = ws_manager.close_all(shutdown_timeout).await;
+    if open > 0 {
+        tracing::warn!("{} WebSocket connection(s) still open at shutdown", open);
+    }
+
+    db_pool.close().await;
+    tracing::info!("Shutdown complete");
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,139 @@
+//! Cross-origin resource sharing
+//!
+//! Browsers may call the API from the origins listed in
+//! `CORS_ALLOWED_ORIGINS` (comma-separated), with credentials. Without a
+//! list, development allows any origin without credentials and production
+//! allows none. Only the request headers the API reads are allowed.
+
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::keyring;
+use crate::middleware::REQUEST_ID_HEADER;
+
+const ALLOWED_METHODS: [Method; 5] = [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE];
+
+#[derive(Debug, Clone, Default)]
+pub struct CorsConfig {
+    /// Origins allowed to make credentialed requests, e.g. `https://app.example.com`
+    pub allowed_origins: Vec<String>,
+    /// Deny cross-origin requests when no origins are listed
+    pub production: bool,
+}
+
+impl CorsConfig {
+    pub fn from_env() -> Self {
+        Self {
+            allowed_origins: std::env::var("CORS_ALLOWED_ORIGINS")
+                .map(|v| parse_origins(&v))
+                .unwrap_or_default(),
+            production: keyring::is_production(),
+        }
+    }
+
+    pub fn layer(&self) -> CorsLayer {
+        let cors = CorsLayer::new()
+            .allow_methods(ALLOWED_METHODS)
+            .allow_headers(allowed_headers())
+            .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER)]);
+
+        let origins: Vec<HeaderValue> = self
+            .allowed_origins
+            .iter()
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    tracing::warn!("Ignoring invalid CORS origin {:?}", origin);
+                    None
+                }
+            })
+            .collect();
+
+        if !origins.is_empty() {
+            cors.allow_origin(AllowOrigin::list(origins)).allow_credentials(true)
+        } else if self.production {
+            cors.allow_origin(AllowOrigin::list(Vec::<HeaderValue>::new()))
+        } else {
+            cors.allow_origin(Any)
+        }
+    }
+}
+
+fn allowed_headers() -> [HeaderName; 5] {
+    [
+        header::AUTHORIZATION,
+        header::CONTENT_TYPE,
+        HeaderName::from_static("x-api-key"),
+        HeaderName::from_static("idempotency-key"),
+        HeaderName::from_static(REQUEST_ID_HEADER),
+    ]
+}
+
+/// Split a comma-separated origin list, dropping blanks and trailing slashes
+pub fn parse_origins(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|origin| origin.trim().trim_end_matches('/'))
+        .filter(|origin| !origin.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn preflight_headers(config: &CorsConfig, origin: &str) -> axum::http::HeaderMap {
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(config.layer());
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap().headers().clone()
+    }
+
+    fn allowlist() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: parse_origins("https://app.example.com/, https://portal.example.com"),
+            production: true,
+        }
+    }
+
+    #[test]
+    fn test_parse_origins() {
+        assert_eq!(
+            parse_origins(" https://a.example.com/,,https://b.example.com "),
+            vec!["https://a.example.com", "https://b.example.com"]
+        );
+        assert!(parse_origins("").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_listed_origin_gets_credentialed_cors_headers() {
+        let headers = preflight_headers(&allowlist(), "https://portal.example.com").await;
+        assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "https://portal.example.com");
+        assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_origin_gets_no_cors_headers() {
+        let headers = preflight_headers(&allowlist(), "https://evil.example.net").await;
+        assert!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+
+        let production = CorsConfig { allowed_origins: Vec::new(), production: true };
+        let headers = preflight_headers(&production, "https://app.example.com").await;
+        assert!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_development_allows_any_origin_without_credentials() {
+        let headers = preflight_headers(&CorsConfig::default(), "http://localhost:3000").await;
+        assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "*");
+        assert!(headers.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+    }
+}
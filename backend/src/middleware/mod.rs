@@ -1,4 +1,8 @@
+pub mod cors;
 pub mod observability;
+pub mod prometheus;
+pub mod rate_limit;
+pub mod request_id;
 
 pub use observability::{
     observability_layer,
@@ -8,3 +12,9 @@ pub use observability::{
     ServiceStatus,
     MetricsResponse,
 };
+
+pub use cors::CorsConfig;
+pub use prometheus::{prometheus_metrics, track_metrics};
+
+pub use rate_limit::{auth_rate_limit, AuthRateLimiter};
+pub use request_id::{request_id_layer, REQUEST_ID_HEADER};
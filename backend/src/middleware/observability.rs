@@ -1,16 +1,21 @@
 use axum::{
     body::Body,
-    extract::{ConnectInfo, State},
+    extract::{ConnectInfo, Query, State},
     http::{Request, Response, StatusCode},
     middleware::Next,
 };
+use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 use std::net::SocketAddr;
+use std::time::Duration;
+use sqlx::PgPool;
 use uuid::Uuid;
 use chrono::Utc;
 
 use crate::AppState;
-use crate::services::{MetricsService, RequestLog, Timer, metric_names};
+use crate::middleware::request_id::RequestId;
+use crate::services::{HealthStatus, MetricsService, RequestLog, Timer, metric_names};
 
 /// Middleware layer for request observability
 /// Tracks request timing, status codes, and errors
@@ -20,7 +25,11 @@ pub async fn observability_layer(
     request: Request<Body>,
     next: Next,
 ) -> Response<Body> {
-    let request_id = Uuid::new_v4();
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| Uuid::parse_str(&id.0).ok())
+        .unwrap_or_else(Uuid::new_v4);
     let timer = Timer::start();
     let method = request.method().to_string();
     let path = request.uri().path().to_string();
@@ -92,60 +101,190 @@ fn normalize_path(path: &str) -> String {
     normalized.join("/")
 }
 
-/// Health check endpoint with detailed status
+/// Query parameters for the readiness probe
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct HealthCheckQuery {
+    /// Also check reachability of configured integrations (ClamAV, SMTP)
+    #[serde(default)]
+    pub integrations: bool,
+}
+
+const DEFAULT_CHECK_TIMEOUT_MS: u64 = 2000;
+
+/// Components whose failure means this instance can't serve traffic
+const CRITICAL_COMPONENTS: &[&str] = &["database", "storage"];
+
+/// Per-check deadline, `HEALTH_CHECK_TIMEOUT_MS` or 2s
+fn check_timeout() -> Duration {
+    let ms = std::env::var("HEALTH_CHECK_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT_MS);
+    Duration::from_millis(ms)
+}
+
+/// Detailed health check (readiness probe)
+///
+/// Checks the database (with pool stats), the upload storage and, with
+/// `?integrations=true`, the configured integrations. Each check has its own
+/// timeout. Returns 503 when a critical component is unhealthy or degraded so
+/// the instance is taken out of rotation; `/health` stays a cheap liveness
+/// probe.
 pub async fn detailed_health_check(
     State(state): State<Arc<AppState>>,
-) -> Result<axum::Json<HealthCheckResponse>, StatusCode> {
+    Query(query): Query<HealthCheckQuery>,
+) -> (StatusCode, axum::Json<HealthCheckResponse>) {
     let metrics = MetricsService::new(state.db_pool.clone());
-    let timer = Timer::start();
+    let timeout = check_timeout();
 
-    // Check database connection
-    let db_status = match sqlx::query("SELECT 1").execute(&state.db_pool).await {
-        Ok(_) => ServiceStatus {
-            status: "healthy".to_string(),
-            response_time_ms: Some(timer.elapsed_ms()),
-            details: None,
-        },
-        Err(e) => ServiceStatus {
-            status: "unhealthy".to_string(),
-            response_time_ms: Some(timer.elapsed_ms()),
-            details: Some(serde_json::json!({ "error": e.to_string() })),
-        },
-    };
+    let mut services = HashMap::new();
+    services.insert("database".to_string(), check_database(&state.db_pool, timeout).await);
+    services.insert("storage".to_string(), check_storage(timeout).await);
 
-    // Record health check
-    let _ = metrics
-        .record_health_check(
-            "database",
-            if db_status.status == "healthy" {
-                crate::services::HealthStatus::Healthy
-            } else {
-                crate::services::HealthStatus::Unhealthy
-            },
-            db_status.response_time_ms,
-            db_status.details.clone(),
-        )
-        .await;
-
-    let overall_status = if db_status.status == "healthy" {
-        "healthy"
-    } else {
-        "unhealthy"
-    };
+    if query.integrations {
+        if let Ok(address) = std::env::var("CLAMAV_ADDRESS") {
+            services.insert("clamav".to_string(), check_tcp(address, timeout).await);
+        }
+        if let Some(address) = smtp_address() {
+            services.insert("smtp".to_string(), check_tcp(address, timeout).await);
+        }
+    }
 
+    for (name, status) in &services {
+        let _ = metrics
+            .record_health_check(
+                name,
+                health_status(&status.status),
+                status.response_time_ms,
+                status.details.clone(),
+            )
+            .await;
+    }
+
+    let (overall, code) = overall_status(&services);
     let response = HealthCheckResponse {
-        status: overall_status.to_string(),
+        status: overall.as_str().to_string(),
         timestamp: Utc::now(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        services: vec![("database".to_string(), db_status)]
-            .into_iter()
-            .collect(),
+        services,
+    };
+
+    (code, axum::Json(response))
+}
+
+/// Run one dependency check under a deadline. A failed check is unhealthy;
+/// one that doesn't answer in time is degraded.
+pub async fn probe<F, E>(timeout: Duration, check: F) -> ServiceStatus
+where
+    F: Future<Output = Result<Option<serde_json::Value>, E>>,
+    E: std::fmt::Display,
+{
+    let timer = Timer::start();
+    let (status, details) = match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(details)) => (HealthStatus::Healthy, details),
+        Ok(Err(e)) => (HealthStatus::Unhealthy, Some(serde_json::json!({ "error": e.to_string() }))),
+        Err(_) => (
+            HealthStatus::Degraded,
+            Some(serde_json::json!({ "error": format!("timed out after {}ms", timeout.as_millis()) })),
+        ),
     };
 
-    if overall_status == "healthy" {
-        Ok(axum::Json(response))
+    ServiceStatus {
+        status: status.as_str().to_string(),
+        response_time_ms: Some(timer.elapsed_ms()),
+        details,
+    }
+}
+
+/// `SELECT 1` against the pool, reporting connection counts
+async fn check_database(pool: &PgPool, timeout: Duration) -> ServiceStatus {
+    let mut status = probe(timeout, async {
+        sqlx::query("SELECT 1").execute(pool).await.map(|_| None)
+    })
+    .await;
+
+    let stats = crate::database::get_pool_stats(pool);
+    let max_connections = pool.options().get_max_connections();
+    let pool_details = serde_json::json!({
+        "size": stats.size,
+        "idle": stats.idle,
+        "active": stats.in_use,
+        "max_connections": max_connections,
+    });
+
+    // Every connection busy means new requests queue for one
+    if status.status == HealthStatus::Healthy.as_str() && stats.idle == 0 && stats.size >= max_connections {
+        status.status = HealthStatus::Degraded.as_str().to_string();
+    }
+
+    let mut details = status.details.take().unwrap_or_else(|| serde_json::json!({}));
+    details["pool"] = pool_details;
+    status.details = Some(details);
+    status
+}
+
+/// The upload directory exists and is writable
+async fn check_storage(timeout: Duration) -> ServiceStatus {
+    let directory = crate::files::get_upload_directory();
+    probe(timeout, async {
+        tokio::fs::create_dir_all(&directory).await?;
+        let path = std::path::Path::new(&directory).join(format!(".health-{}", Uuid::new_v4()));
+        tokio::fs::write(&path, b"ok").await?;
+        tokio::fs::remove_file(&path).await?;
+        Ok::<_, std::io::Error>(Some(serde_json::json!({ "backend": "local", "path": directory })))
+    })
+    .await
+}
+
+/// A TCP connection can be opened to the address
+async fn check_tcp(address: String, timeout: Duration) -> ServiceStatus {
+    probe(timeout, async {
+        tokio::net::TcpStream::connect(&address).await?;
+        Ok::<_, std::io::Error>(Some(serde_json::json!({ "address": address })))
+    })
+    .await
+}
+
+fn smtp_address() -> Option<String> {
+    let host = std::env::var("SMTP_HOST").ok().filter(|h| !h.is_empty())?;
+    let port = std::env::var("SMTP_PORT").unwrap_or_else(|_| "587".to_string());
+    Some(format!("{}:{}", host, port))
+}
+
+fn health_status(status: &str) -> HealthStatus {
+    match status {
+        "healthy" => HealthStatus::Healthy,
+        "degraded" => HealthStatus::Degraded,
+        _ => HealthStatus::Unhealthy,
+    }
+}
+
+/// Overall status and readiness HTTP code for a set of component results.
+/// A critical component that is unhealthy or degraded fails readiness (503);
+/// problems with optional integrations only degrade the status.
+pub fn overall_status(services: &HashMap<String, ServiceStatus>) -> (HealthStatus, StatusCode) {
+    let mut critical_unhealthy = false;
+    let mut critical_degraded = false;
+    let mut degraded = false;
+
+    for (name, service) in services {
+        let critical = CRITICAL_COMPONENTS.contains(&name.as_str());
+        match health_status(&service.status) {
+            HealthStatus::Healthy => {}
+            HealthStatus::Degraded if critical => critical_degraded = true,
+            HealthStatus::Unhealthy if critical => critical_unhealthy = true,
+            _ => degraded = true,
+        }
+    }
+
+    if critical_unhealthy {
+        (HealthStatus::Unhealthy, StatusCode::SERVICE_UNAVAILABLE)
+    } else if critical_degraded {
+        (HealthStatus::Degraded, StatusCode::SERVICE_UNAVAILABLE)
+    } else if degraded {
+        (HealthStatus::Degraded, StatusCode::OK)
     } else {
-        Err(StatusCode::SERVICE_UNAVAILABLE)
+        (HealthStatus::Healthy, StatusCode::OK)
     }
 }
 
@@ -154,7 +293,7 @@ pub struct HealthCheckResponse {
     pub status: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub version: String,
-    pub services: std::collections::HashMap<String, ServiceStatus>,
+    pub services: HashMap<String, ServiceStatus>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -200,3 +339,61 @@ pub struct MetricsResponse {
     pub health_status: Vec<crate::services::metrics::ServiceHealth>,
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(status: HealthStatus) -> ServiceStatus {
+        ServiceStatus {
+            status: status.as_str().to_string(),
+            response_time_ms: Some(1),
+            details: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_times_out_as_degraded() {
+        // Stands in for a database that never answers
+        let hung = std::future::pending::<Result<Option<serde_json::Value>, sqlx::Error>>();
+        let database = probe(Duration::from_millis(20), hung).await;
+        assert_eq!(database.status, "degraded");
+        assert!(database.details.unwrap()["error"].as_str().unwrap().contains("timed out"));
+
+        let services = HashMap::from([
+            ("database".to_string(), database),
+            ("storage".to_string(), status(HealthStatus::Healthy)),
+        ]);
+        let (overall, code) = overall_status(&services);
+        assert_eq!(overall.as_str(), "degraded");
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_probe_error_is_unhealthy() {
+        let failed = async { Err::<Option<serde_json::Value>, _>("connection refused") };
+        let result = probe(Duration::from_secs(1), failed).await;
+        assert_eq!(result.status, "unhealthy");
+    }
+
+    #[test]
+    fn test_overall_status() {
+        let healthy = HashMap::from([
+            ("database".to_string(), status(HealthStatus::Healthy)),
+            ("storage".to_string(), status(HealthStatus::Healthy)),
+        ]);
+        let (overall, code) = overall_status(&healthy);
+        assert_eq!((overall.as_str(), code), ("healthy", StatusCode::OK));
+
+        // An unreachable integration doesn't take the instance out of rotation
+        let mut integration_down = healthy;
+        integration_down.insert("smtp".to_string(), status(HealthStatus::Unhealthy));
+        let (overall, code) = overall_status(&integration_down);
+        assert_eq!((overall.as_str(), code), ("degraded", StatusCode::OK));
+
+        let mut database_down = integration_down;
+        database_down.insert("database".to_string(), status(HealthStatus::Unhealthy));
+        let (overall, code) = overall_status(&database_down);
+        assert_eq!((overall.as_str(), code), ("unhealthy", StatusCode::SERVICE_UNAVAILABLE));
+    }
+}
@@ -0,0 +1,135 @@
+//! Prometheus metrics
+//!
+//! Request counters and latency histograms, database pool gauges and
+//! business counters recorded through the `metrics` crate and rendered in
+//! the Prometheus text format on `/metrics`. Requests are labelled with the
+//! matched route template (`/api/v1/tickets/:id`), never the raw path, so
+//! label cardinality stays bounded by the number of routes.
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::AppState;
+
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+pub const DB_POOL_CONNECTIONS: &str = "db_pool_connections";
+pub const DB_POOL_MAX_CONNECTIONS: &str = "db_pool_max_connections";
+pub const TICKETS_CREATED_TOTAL: &str = "tickets_created_total";
+pub const INVOICES_GENERATED_TOTAL: &str = "invoices_generated_total";
+
+/// Label for requests that didn't match a route
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Install the global Prometheus recorder, once per process
+pub fn install_recorder() -> &'static PrometheusHandle {
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Full(HTTP_REQUEST_DURATION_SECONDS.to_string()), LATENCY_BUCKETS)
+            .expect("latency buckets are not empty")
+            .install_recorder()
+            .expect("failed to install Prometheus recorder")
+    })
+}
+
+/// Count and time each request by method, route template and status.
+/// Add with `route_layer` so the matched route is known.
+pub async fn track_metrics(request: Request<Body>, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!(HTTP_REQUESTS_TOTAL, &labels[..]).increment(1);
+    metrics::histogram!(HTTP_REQUEST_DURATION_SECONDS, &labels[..]).record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Prometheus exposition endpoint
+pub async fn prometheus_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let handle = install_recorder();
+
+    let stats = crate::database::get_pool_stats(&state.db_pool);
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "idle").set(stats.idle as f64);
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "active").set(stats.in_use as f64);
+    metrics::gauge!(DB_POOL_MAX_CONNECTIONS).set(state.db_pool.options().get_max_connections() as f64);
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+}
+
+/// Ticket sources kept as labels; anything else is counted as `other`
+const TICKET_SOURCES: &[&str] = &["manual", "email", "phone", "portal", "recurring", "chat", "monitoring"];
+
+/// Count a created ticket by source
+pub fn record_ticket_created(source: &str) {
+    let source = TICKET_SOURCES.iter().copied().find(|s| *s == source).unwrap_or("other");
+    metrics::counter!(TICKETS_CREATED_TOTAL, "source" => source).increment(1);
+}
+
+/// Count a generated invoice by how it was produced (`manual`, `time_entries`, `recurring`)
+pub fn record_invoice_generated(source: &'static str) {
+    metrics::counter!(INVOICES_GENERATED_TOTAL, "source" => source).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use tower::ServiceExt;
+
+    fn sample_value(rendered: &str, metric: &str, route: &str) -> Option<f64> {
+        rendered
+            .lines()
+            .filter(|line| line.starts_with(&format!("{}{{", metric)))
+            .find(|line| line.contains(&format!("route=\"{}\"", route)) && line.contains("status=\"200\""))
+            .and_then(|line| line.rsplit(' ').next())
+            .and_then(|value| value.parse().ok())
+    }
+
+    #[tokio::test]
+    async fn test_request_increments_route_counter() {
+        let handle = install_recorder();
+        let app = Router::new()
+            .route("/widgets/:id", get(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn(track_metrics));
+
+        for id in ["1", "2"] {
+            let request = Request::builder().uri(format!("/widgets/{}", id)).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // Both paths are counted under the one route template
+        let rendered = handle.render();
+        assert_eq!(sample_value(&rendered, HTTP_REQUESTS_TOTAL, "/widgets/:id"), Some(2.0));
+        assert!(!rendered.contains("/widgets/1"));
+        assert!(rendered.contains(HTTP_REQUEST_DURATION_SECONDS));
+    }
+}
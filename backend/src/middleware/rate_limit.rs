@@ -0,0 +1,331 @@
+//! Rate limiting for the authentication endpoints
+//!
+//! A token bucket per client IP, and optionally per submitted email, in
+//! front of `/api/v1/auth/*`. Requests over the limit get `429` with
+//! `Retry-After`. `X-Forwarded-For` is only believed when the connection
+//! comes from a configured trusted proxy; otherwise the socket address is
+//! the client.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, HeaderValue, Request, Response, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+    Json,
+};
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::auth::api_keys::ip_in_cidr;
+
+/// Login bodies are tiny; anything bigger isn't inspected for an email
+const MAX_INSPECTED_BODY: usize = 16 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct AuthRateLimitConfig {
+    /// Sustained requests per minute per client IP
+    pub per_minute: u32,
+    /// Requests allowed in a burst before the per-minute rate applies
+    pub burst: u32,
+    /// Per-email requests per minute; 0 turns the email bucket off
+    pub email_per_minute: u32,
+    /// Proxies whose `X-Forwarded-For` is trusted (IPs or IPv4 CIDRs)
+    pub trusted_proxies: Vec<String>,
+    /// Client IPs never limited (IPs or IPv4 CIDRs)
+    pub exempt: Vec<String>,
+}
+
+impl Default for AuthRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_minute: 10,
+            burst: 5,
+            email_per_minute: 0,
+            trusted_proxies: Vec::new(),
+            exempt: Vec::new(),
+        }
+    }
+}
+
+impl AuthRateLimitConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let number = |name: &str, default: u32| {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+        };
+        let list = |name: &str| {
+            std::env::var(name)
+                .map(|v| v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+                .unwrap_or_default()
+        };
+
+        Self {
+            per_minute: number("AUTH_RATE_LIMIT_PER_MINUTE", defaults.per_minute),
+            burst: number("AUTH_RATE_LIMIT_BURST", defaults.burst),
+            email_per_minute: number("AUTH_RATE_LIMIT_EMAIL_PER_MINUTE", defaults.email_per_minute),
+            trusted_proxies: trusted_proxies().to_vec(),
+            exempt: list("AUTH_RATE_LIMIT_EXEMPT"),
+        }
+    }
+}
+
+/// Proxies named in `TRUSTED_PROXIES`, read once
+pub fn trusted_proxies() -> &'static [String] {
+    static TRUSTED: OnceLock<Vec<String>> = OnceLock::new();
+    TRUSTED.get_or_init(|| {
+        std::env::var("TRUSTED_PROXIES")
+            .map(|v| v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default()
+    })
+}
+
+fn matches_any(ip: IpAddr, entries: &[String]) -> bool {
+    let ip_str = ip.to_string();
+    entries.iter().any(|entry| {
+        if entry.contains('/') {
+            ip_in_cidr(&ip_str, entry)
+        } else {
+            entry.parse::<IpAddr>().map(|e| e == ip).unwrap_or(false)
+        }
+    })
+}
+
+/// The client address for `peer`. Behind a trusted proxy this is the
+/// right-most `X-Forwarded-For` hop that isn't itself a trusted proxy, so a
+/// client can't pick its own bucket by prepending addresses.
+pub fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted_proxies: &[String]) -> Option<IpAddr> {
+    let peer = peer?;
+    if !matches_any(peer, trusted_proxies) {
+        return Some(peer);
+    }
+
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .filter_map(|hop| hop.trim().parse().ok())
+        .collect();
+
+    forwarded
+        .iter()
+        .rev()
+        .find(|hop| !matches_any(**hop, trusted_proxies))
+        .or_else(|| forwarded.first())
+        .copied()
+        .or(Some(peer))
+}
+
+pub struct AuthRateLimiter {
+    config: AuthRateLimitConfig,
+    by_ip: DefaultKeyedRateLimiter<IpAddr>,
+    by_email: Option<DefaultKeyedRateLimiter<String>>,
+}
+
+fn quota(per_minute: u32, burst: u32) -> Quota {
+    let rate = NonZeroU32::new(per_minute).unwrap_or(NonZeroU32::MIN);
+    let burst = NonZeroU32::new(burst).unwrap_or(rate);
+    Quota::per_minute(rate).allow_burst(burst)
+}
+
+impl AuthRateLimiter {
+    pub fn new(config: AuthRateLimitConfig) -> Self {
+        let by_ip = RateLimiter::keyed(quota(config.per_minute, config.burst));
+        let by_email = (config.email_per_minute > 0)
+            .then(|| RateLimiter::keyed(quota(config.email_per_minute, config.email_per_minute)));
+        Self { config, by_ip, by_email }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(AuthRateLimitConfig::from_env())
+    }
+
+    pub fn config(&self) -> &AuthRateLimitConfig {
+        &self.config
+    }
+
+    /// Take a token for `ip`. `Err` carries how long until one is available.
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), Duration> {
+        if matches_any(ip, &self.config.exempt) {
+            return Ok(());
+        }
+        self.by_ip
+            .check_key(&ip)
+            .map_err(|not_until| not_until.wait_time_from(DefaultClock::default().now()))
+    }
+
+    pub fn check_email(&self, email: &str) -> Result<(), Duration> {
+        let Some(by_email) = &self.by_email else {
+            return Ok(());
+        };
+        by_email
+            .check_key(&email.trim().to_lowercase())
+            .map_err(|not_until| not_until.wait_time_from(DefaultClock::default().now()))
+    }
+
+    pub fn limits_email(&self) -> bool {
+        self.by_email.is_some()
+    }
+
+    /// Drop buckets that have refilled, so idle clients don't accumulate
+    pub fn purge_stale(&self) {
+        self.by_ip.retain_recent();
+        if let Some(by_email) = &self.by_email {
+            by_email.retain_recent();
+        }
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response<Body> {
+    // Round up so clients never retry a moment too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(serde_json::json!({
+            "error": "rate_limited",
+            "message": "Too many requests, try again later",
+            "retry_after": seconds
+        })),
+    )
+        .into_response();
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+    response
+}
+
+/// Middleware for the auth router, used with `from_fn_with_state`
+pub async fn auth_rate_limit(
+    State(limiter): State<Arc<AuthRateLimiter>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| addr.ip());
+    let ip = client_ip(peer, request.headers(), &limiter.config.trusted_proxies);
+
+    match ip {
+        Some(ip) => {
+            if let Err(retry_after) = limiter.check_ip(ip) {
+                tracing::warn!("Auth rate limit exceeded for {} on {}", ip, request.uri().path());
+                return too_many_requests(retry_after);
+            }
+        }
+        None => tracing::debug!("No client address for {}; skipping IP rate limit", request.uri().path()),
+    }
+
+    if !limiter.limits_email() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_INSPECTED_BODY).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let email = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .ok()
+        .and_then(|body| body.get("email").and_then(|e| e.as_str()).map(str::to_string));
+
+    if let Some(email) = email {
+        if let Err(retry_after) = limiter.check_email(&email) {
+            tracing::warn!("Auth rate limit exceeded for an email on {}", parts.uri.path());
+            return too_many_requests(retry_after);
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware::from_fn_with_state, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn limiter(per_minute: u32, burst: u32) -> AuthRateLimiter {
+        AuthRateLimiter::new(AuthRateLimitConfig { per_minute, burst, ..Default::default() })
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_nth_request_in_window_is_rejected() {
+        let limiter = limiter(3, 3);
+        let client = ip("203.0.113.7");
+
+        for _ in 0..3 {
+            assert!(limiter.check_ip(client).is_ok());
+        }
+        let retry_after = limiter.check_ip(client).unwrap_err();
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(20));
+
+        // Other clients have their own bucket
+        assert!(limiter.check_ip(ip("203.0.113.8")).is_ok());
+    }
+
+    #[test]
+    fn test_exempt_addresses_are_not_limited() {
+        let limiter = AuthRateLimiter::new(AuthRateLimitConfig {
+            per_minute: 1,
+            burst: 1,
+            exempt: vec!["10.0.0.0/8".to_string()],
+            ..Default::default()
+        });
+        for _ in 0..5 {
+            assert!(limiter.check_ip(ip("10.1.2.3")).is_ok());
+        }
+        assert!(limiter.check_ip(ip("192.0.2.1")).is_ok());
+        assert!(limiter.check_ip(ip("192.0.2.1")).is_err());
+    }
+
+    #[test]
+    fn test_forwarded_for_only_trusted_from_proxies() {
+        let trusted = vec!["10.0.0.0/8".to_string()];
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("198.51.100.1, 203.0.113.9, 10.0.0.2"));
+
+        // Direct connection: the header is ignored
+        assert_eq!(client_ip(Some(ip("192.0.2.50")), &headers, &trusted), Some(ip("192.0.2.50")));
+        // Behind the proxy: the right-most untrusted hop, not the spoofable first one
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), &headers, &trusted), Some(ip("203.0.113.9")));
+        // Trusted proxy without the header
+        assert_eq!(client_ip(Some(ip("10.0.0.1")), &HeaderMap::new(), &trusted), Some(ip("10.0.0.1")));
+        assert_eq!(client_ip(None, &headers, &trusted), None);
+    }
+
+    #[test]
+    fn test_email_bucket_is_case_insensitive() {
+        let limiter = AuthRateLimiter::new(AuthRateLimitConfig { email_per_minute: 2, ..Default::default() });
+        assert!(limiter.check_email("Admin@Example.com").is_ok());
+        assert!(limiter.check_email("admin@example.com ").is_ok());
+        assert!(limiter.check_email("ADMIN@example.com").is_err());
+        assert!(limiter.check_email("other@example.com").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_middleware_returns_429_with_retry_after() {
+        let limiter = Arc::new(limiter(2, 2));
+        let app = Router::new()
+            .route("/login", post(|| async { StatusCode::OK }))
+            .layer(from_fn_with_state(limiter, auth_rate_limit));
+
+        let login = || {
+            let mut request = Request::builder().method("POST").uri("/login").body(Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 10], 40000))));
+            request
+        };
+
+        assert_eq!(app.clone().oneshot(login()).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(app.clone().oneshot(login()).await.unwrap().status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(login()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert!(retry_after >= 1);
+    }
+}
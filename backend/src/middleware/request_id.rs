@@ -0,0 +1,140 @@
+//! Request correlation ids
+//!
+//! Every request gets an id: the caller's `X-Request-Id` when it's usable,
+//! otherwise a new UUID. The id is recorded on a tracing span wrapping the
+//! request so every log line for it carries the id, echoed back on the
+//! response and forwarded on outbound webhook and integration calls made
+//! while handling the request (see [`with_request_id`]).
+
+use axum::{
+    body::Body,
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::future::Future;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest caller-supplied id that is kept
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The current request's id, available as a request extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// The id of the request being handled, if any
+pub fn current() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Run `future` under the current request id, for work spawned onto other
+/// tasks that should still be correlated with the request
+pub fn propagate<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let id = current();
+    async move {
+        match id {
+            Some(id) => CURRENT_REQUEST_ID.scope(id, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// Add the current request id to an outbound HTTP call
+pub fn with_request_id(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match current() {
+        Some(id) => request.header(REQUEST_ID_HEADER, id),
+        None => request,
+    }
+}
+
+/// A caller-supplied id is kept when it's short printable ASCII
+fn accept_request_id(value: &HeaderValue) -> Option<String> {
+    let value = value.to_str().ok()?.trim();
+    let valid = !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value.bytes().all(|b| b.is_ascii_graphic());
+    valid.then(|| value.to_string())
+}
+
+/// Read or generate the request id, log under it and echo it back
+pub async fn request_id_layer(mut request: Request<Body>, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(accept_request_id)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    request.extensions_mut().insert(RequestId(id.clone()));
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+
+    let mut response = CURRENT_REQUEST_ID
+        .scope(id.clone(), next.run(request).instrument(span))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", get(|| async { current().unwrap_or_default() }))
+            .layer(axum::middleware::from_fn(request_id_layer))
+    }
+
+    async fn send(request_id: Option<&str>) -> (Option<String>, String) {
+        let mut request = Request::builder().uri("/");
+        if let Some(id) = request_id {
+            request = request.header(REQUEST_ID_HEADER, id);
+        }
+        let response = app().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let header = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_generates_request_id() {
+        let (header, seen_by_handler) = send(None).await;
+        let header = header.expect("response has a request id");
+        assert!(Uuid::parse_str(&header).is_ok());
+        assert_eq!(seen_by_handler, header);
+    }
+
+    #[tokio::test]
+    async fn test_preserves_provided_request_id() {
+        let (header, seen_by_handler) = send(Some("edge-7f3a:42")).await;
+        assert_eq!(header.as_deref(), Some("edge-7f3a:42"));
+        assert_eq!(seen_by_handler, "edge-7f3a:42");
+    }
+
+    #[tokio::test]
+    async fn test_replaces_unusable_request_id() {
+        let (header, _) = send(Some(&"x".repeat(MAX_REQUEST_ID_LEN + 1))).await;
+        assert!(Uuid::parse_str(&header.unwrap()).is_ok());
+    }
+}
@@ -1,6 +1,6 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap},
     response::{IntoResponse, Json},
     routing::{get, post, put, delete},
     Router,
@@ -10,7 +10,11 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::middleware::AuthUser;
-use crate::AppState;
+use crate::pagination::{split_total, WithTotal};
+use crate::services::business_hours::parse_timezone;
+use crate::services::canned_response_render::TemplateVariables;
+use crate::services::email_templates;
+use crate::{AppState, ApiError, ApiResult, PaginatedResponse, PaginationParams};
 use resolve_shared::Notification;
 
 pub fn notification_routes() -> Router<Arc<AppState>> {
@@ -27,8 +31,6 @@ pub struct ListNotificationsQuery {
     pub read: Option<bool>,
     pub notification_type: Option<String>,
     pub entity_type: Option<String>,
-    pub page: Option<u32>,
-    pub limit: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -36,6 +38,21 @@ pub struct NotificationResponse {
     #[serde(flatten)]
     pub notification: Notification,
     pub relative_time: String,
+    /// `created_at` in the reading user's timezone, RFC 3339 with offset
+    pub created_at_local: String,
+    pub timezone: String,
+}
+
+impl NotificationResponse {
+    fn new(notification: Notification, tz: chrono_tz::Tz, locale: Locale) -> Self {
+        let now = chrono::Utc::now();
+        Self {
+            relative_time: format_relative_time(notification.created_at, now, locale),
+            created_at_local: notification.created_at.with_timezone(&tz).to_rfc3339(),
+            timezone: tz.name().to_string(),
+            notification,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -43,94 +60,86 @@ pub struct UnreadCountResponse {
     pub unread_count: i64,
 }
 
+/// Filters for `list_notifications` after the owning user ($1); unset
+/// filters bind NULL and match everything
+const NOTIFICATION_LIST_FILTER: &str = "user_id = $1
+    AND ($2::boolean IS NULL OR read = $2)
+    AND ($3::text IS NULL OR notification_type = $3)
+    AND ($4::text IS NULL OR entity_type = $4)";
+
 async fn list_notifications(
     State(state): State<Arc<AppState>>,
     Query(query): Query<ListNotificationsQuery>,
+    Query(pagination): Query<PaginationParams>,
+    headers: HeaderMap,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
-    let page = query.page.unwrap_or(1);
-    let limit = query.limit.unwrap_or(50).min(100);
-    let offset = (page - 1) * limit;
-
-    let mut sql = String::from(
+) -> ApiResult<Json<PaginatedResponse<NotificationResponse>>> {
+    let rows = sqlx::query_as::<_, WithTotal<Notification>>(&format!(
         r#"
         SELECT id, user_id, title, message, notification_type,
-               entity_type, entity_id, read, created_at
+               entity_type, entity_id, read, created_at,
+               COUNT(*) OVER() AS total_count
         FROM notifications
-        WHERE user_id = $1
-        "#
-    );
-
-    let mut conditions = Vec::new();
-    let mut param_count = 1;
-
-    if let Some(read) = query.read {
-        param_count += 1;
-        conditions.push(format!("read = ${}", param_count));
-    }
-
-    if let Some(notification_type) = &query.notification_type {
-        param_count += 1;
-        conditions.push(format!("notification_type = ${}", param_count));
-    }
-
-    if let Some(entity_type) = &query.entity_type {
-        param_count += 1;
-        conditions.push(format!("entity_type = ${}", param_count));
-    }
-
-    if !conditions.is_empty() {
-        sql.push_str(&format!(" AND {}", conditions.join(" AND ")));
-    }
-
-    sql.push_str(&format!(" ORDER BY created_at DESC LIMIT ${} OFFSET ${}", param_count + 1, param_count + 2));
-
-    // For simplicity, use a basic query. In production, use a query builder
-    let notifications = sqlx::query_as!(
-        Notification,
-        r#"
-        SELECT id, user_id, title, message, notification_type,
-               entity_type, entity_id, read, created_at
-        FROM notifications
-        WHERE user_id = $1
-        ORDER BY created_at DESC
-        LIMIT $2 OFFSET $3
+        WHERE {}
+        ORDER BY created_at DESC, id DESC
+        LIMIT $5 OFFSET $6
         "#,
-        auth.0.id,
-        limit as i64,
-        offset as i64
-    )
+        NOTIFICATION_LIST_FILTER
+    ))
+    .bind(auth.0.id)
+    .bind(query.read)
+    .bind(&query.notification_type)
+    .bind(&query.entity_type)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
     .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
-    // Add relative time information
-    let notification_responses: Vec<NotificationResponse> = notifications.into_iter().map(|notification| {
-        NotificationResponse {
-            relative_time: format_relative_time(notification.created_at),
-            notification,
+    let (notifications, total) = split_total(rows);
+    let total = match total {
+        Some(total) => total,
+        None => {
+            sqlx::query_scalar::<_, i64>(&format!(
+                "SELECT COUNT(*) FROM notifications WHERE {}",
+                NOTIFICATION_LIST_FILTER
+            ))
+            .bind(auth.0.id)
+            .bind(query.read)
+            .bind(&query.notification_type)
+            .bind(&query.entity_type)
+            .fetch_one(&state.db_pool)
+            .await?
         }
-    }).collect();
+    };
+
+    // Add relative and local time information
+    let tz = parse_timezone(&auth.0.timezone);
+    let locale = Locale::from_accept_language(
+        headers.get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()),
+    );
+    let notification_responses: Vec<NotificationResponse> = notifications
+        .into_iter()
+        .map(|notification| NotificationResponse::new(notification, tz, locale))
+        .collect();
 
-    Ok(Json(notification_responses))
+    Ok(Json(PaginatedResponse::new(notification_responses, &pagination, total)))
 }
 
 async fn mark_as_read(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let result = sqlx::query!(
         "UPDATE notifications SET read = true WHERE id = $1 AND user_id = $2",
         id,
         auth.0.id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::not_found("Notification"));
     }
 
     Ok(Json(serde_json::json!({ "message": "Notification marked as read" })))
@@ -139,14 +148,13 @@ async fn mark_as_read(
 async fn mark_all_as_read(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let result = sqlx::query!(
         "UPDATE notifications SET read = true WHERE user_id = $1 AND read = false",
         auth.0.id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     Ok(Json(serde_json::json!({ 
         "message": "All notifications marked as read",
@@ -158,18 +166,17 @@ async fn delete_notification(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let result = sqlx::query!(
         "DELETE FROM notifications WHERE id = $1 AND user_id = $2",
         id,
         auth.0.id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::not_found("Notification"));
     }
 
     Ok(Json(serde_json::json!({ "message": "Notification deleted" })))
@@ -178,14 +185,13 @@ async fn delete_notification(
 async fn get_unread_count(
     State(state): State<Arc<AppState>>,
     auth: AuthUser,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     let unread_count = sqlx::query_scalar!(
         "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read = false",
         auth.0.id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .await?
     .unwrap_or(0);
 
     Ok(Json(UnreadCountResponse { unread_count }))
@@ -225,7 +231,8 @@ pub async fn create_notification(
     Ok(notification_id)
 }
 
-// Bulk notification creation for multiple users
+// Bulk notification creation for multiple users, in a single INSERT.
+// Returned ids are in the same order as `user_ids`.
 pub async fn create_notifications_for_users(
     db_pool: &sqlx::PgPool,
     user_ids: Vec<Uuid>,
@@ -235,25 +242,50 @@ pub async fn create_notifications_for_users(
     entity_type: Option<String>,
     entity_id: Option<Uuid>,
 ) -> Result<Vec<Uuid>, sqlx::Error> {
-    let mut notification_ids = Vec::new();
-    
-    for user_id in user_ids {
-        let notification_id = create_notification(
-            db_pool,
-            user_id,
-            title.clone(),
-            message.clone(),
-            notification_type.clone(),
-            entity_type.clone(),
-            entity_id,
-        ).await?;
-        
-        notification_ids.push(notification_id);
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
     }
-    
+
+    let notification_ids: Vec<Uuid> = user_ids.iter().map(|_| Uuid::new_v4()).collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO notifications (
+            id, user_id, title, message, notification_type,
+            entity_type, entity_id, read, created_at
+        )
+        SELECT n.id, n.user_id, $3, $4, $5, $6, $7, false, NOW()
+        FROM UNNEST($1::uuid[], $2::uuid[]) AS n(id, user_id)
+        "#
+    )
+    .bind(&notification_ids)
+    .bind(&user_ids)
+    .bind(title)
+    .bind(message)
+    .bind(notification_type)
+    .bind(entity_type)
+    .bind(entity_id)
+    .execute(db_pool)
+    .await?;
+
     Ok(notification_ids)
 }
 
+/// The watcher preference that governs notifications for a ticket `action`,
+/// or `None` when the action isn't covered by a preference
+pub fn watcher_preference_for_action(action: &str) -> Option<&'static str> {
+    let action = action.to_lowercase();
+    if action.contains("assign") {
+        Some("assignments")
+    } else if action.contains("repl") || action.contains("comment") {
+        Some("new_replies")
+    } else if ["status", "resolved", "closed", "reopened"].iter().any(|s| action.contains(s)) {
+        Some("status_changes")
+    } else {
+        None
+    }
+}
+
 // Helper to create ticket-related notifications
 pub async fn notify_ticket_update(
     db_pool: &sqlx::PgPool,
@@ -262,21 +294,25 @@ pub async fn notify_ticket_update(
     action: &str,
     details: &str,
 ) -> Result<(), sqlx::Error> {
-    // Get users who should be notified (assigned user, watchers, etc.)
-    let user_ids = sqlx::query_scalar!(
+    // Get users who should be notified (assigned user, watchers, etc.).
+    // Watchers who turned off the preference for this kind of update are skipped.
+    let user_ids = sqlx::query_scalar::<_, Uuid>(
         r#"
         SELECT DISTINCT user_id FROM (
             SELECT assigned_to as user_id FROM tickets WHERE id = $1 AND assigned_to IS NOT NULL
             UNION
-            SELECT user_id FROM ticket_watchers WHERE ticket_id = $1
+            SELECT user_id FROM ticket_watchers
+            WHERE ticket_id = $1
+              AND ($2::text IS NULL OR COALESCE((notification_preferences ->> $2)::boolean, true))
             UNION
             SELECT id as user_id FROM users WHERE role_id IN (
                 SELECT id FROM roles WHERE name IN ('admin', 'technician')
             )
         ) AS users
-        "#,
-        ticket_id
+        "#
     )
+    .bind(ticket_id)
+    .bind(watcher_preference_for_action(action))
     .fetch_all(db_pool)
     .await?;
 
@@ -296,6 +332,51 @@ pub async fn notify_ticket_update(
     Ok(())
 }
 
+/// Email the ticket's contact using the `ticket_updated` template.
+/// Tickets without a contact email are skipped; send failures are logged.
+pub async fn email_ticket_update(
+    db_pool: &sqlx::PgPool,
+    ticket_id: Uuid,
+    update_message: &str,
+) -> Result<(), sqlx::Error> {
+    let ticket = sqlx::query_as::<_, (i32, String, String, Uuid, String, Option<String>, Option<String>)>(
+        r#"
+        SELECT t.number, t.subject, COALESCE(t.status, 'open'), t.client_id, c.name, ct.email, ct.name
+        FROM tickets t
+        JOIN clients c ON c.id = t.client_id
+        LEFT JOIN contacts ct ON ct.id = t.contact_id
+        WHERE t.id = $1
+        "#
+    )
+    .bind(ticket_id)
+    .fetch_optional(db_pool)
+    .await?;
+
+    let Some((number, subject, status, client_id, client_name, Some(contact_email), contact_name)) = ticket else {
+        return Ok(());
+    };
+
+    let mut vars = TemplateVariables::new();
+    vars.set("ticket_number", Some(number.to_string()))
+        .set("subject", Some(subject))
+        .set("status", Some(status))
+        .set("update_message", Some(update_message))
+        .set("client_name", contact_name.as_deref().or(Some(client_name.as_str())))
+        .set("portal_url", Some(email_templates::portal_link(&format!("tickets/{}", ticket_id))));
+
+    email_templates::send(
+        db_pool,
+        email_templates::TICKET_UPDATED,
+        Some(client_id),
+        &contact_email,
+        contact_name.as_deref(),
+        &vars,
+    )
+    .await;
+
+    Ok(())
+}
+
 // Helper to create expiry notifications
 pub async fn notify_expiring_items(
     db_pool: &sqlx::PgPool,
@@ -392,23 +473,235 @@ pub async fn notify_expiring_items(
     Ok(())
 }
 
-fn format_relative_time(timestamp: chrono::DateTime<chrono::Utc>) -> String {
-    let now = chrono::Utc::now();
-    let duration = now.signed_duration_since(timestamp);
+/// Phrase set for relative times, picked from the request's `Accept-Language`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Es,
+    De,
+}
+
+impl Locale {
+    /// First supported language in the header, in the client's order of preference;
+    /// English when nothing matches
+    pub fn from_accept_language(header: Option<&str>) -> Self {
+        let Some(header) = header else {
+            return Locale::En;
+        };
+
+        let mut ranges: Vec<(f32, &str)> = header
+            .split(',')
+            .filter_map(|part| {
+                let mut pieces = part.split(';');
+                let tag = pieces.next()?.trim();
+                let q = pieces
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && q > 0.0).then_some((q, tag))
+            })
+            .collect();
+        // Stable sort keeps header order among equal weights
+        ranges.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        ranges
+            .into_iter()
+            .find_map(|(_, tag)| {
+                let primary = tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+                match primary.as_str() {
+                    "en" => Some(Locale::En),
+                    "es" => Some(Locale::Es),
+                    "de" => Some(Locale::De),
+                    _ => None,
+                }
+            })
+            .unwrap_or(Locale::En)
+    }
+
+    fn just_now(self) -> &'static str {
+        match self {
+            Locale::En => "just now",
+            Locale::Es => "justo ahora",
+            Locale::De => "gerade eben",
+        }
+    }
+
+    /// (singular, plural) for a unit
+    fn unit(self, unit: TimeUnit) -> (&'static str, &'static str) {
+        match (self, unit) {
+            (Locale::En, TimeUnit::Minute) => ("minute", "minutes"),
+            (Locale::En, TimeUnit::Hour) => ("hour", "hours"),
+            (Locale::En, TimeUnit::Day) => ("day", "days"),
+            (Locale::En, TimeUnit::Week) => ("week", "weeks"),
+            (Locale::En, TimeUnit::Month) => ("month", "months"),
+            (Locale::En, TimeUnit::Year) => ("year", "years"),
+            (Locale::Es, TimeUnit::Minute) => ("minuto", "minutos"),
+            (Locale::Es, TimeUnit::Hour) => ("hora", "horas"),
+            (Locale::Es, TimeUnit::Day) => ("día", "días"),
+            (Locale::Es, TimeUnit::Week) => ("semana", "semanas"),
+            (Locale::Es, TimeUnit::Month) => ("mes", "meses"),
+            (Locale::Es, TimeUnit::Year) => ("año", "años"),
+            // Dative forms, which both "vor" and "in" take
+            (Locale::De, TimeUnit::Minute) => ("Minute", "Minuten"),
+            (Locale::De, TimeUnit::Hour) => ("Stunde", "Stunden"),
+            (Locale::De, TimeUnit::Day) => ("Tag", "Tagen"),
+            (Locale::De, TimeUnit::Week) => ("Woche", "Wochen"),
+            (Locale::De, TimeUnit::Month) => ("Monat", "Monaten"),
+            (Locale::De, TimeUnit::Year) => ("Jahr", "Jahren"),
+        }
+    }
+
+    fn phrase(self, amount: i64, unit: TimeUnit, future: bool) -> String {
+        let (singular, plural) = self.unit(unit);
+        let quantity = format!("{} {}", amount, if amount == 1 { singular } else { plural });
+        match (self, future) {
+            (Locale::En, false) => format!("{} ago", quantity),
+            (Locale::En, true) => format!("in {}", quantity),
+            (Locale::Es, false) => format!("hace {}", quantity),
+            (Locale::Es, true) => format!("dentro de {}", quantity),
+            (Locale::De, false) => format!("vor {}", quantity),
+            (Locale::De, true) => format!("in {}", quantity),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TimeUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
 
-    if duration.num_minutes() < 1 {
-        "just now".to_string()
-    } else if duration.num_minutes() < 60 {
-        format!("{} minutes ago", duration.num_minutes())
+/// `timestamp` relative to `now`, e.g. "1 minute ago" or "in 3 days"
+pub fn format_relative_time(
+    timestamp: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+    locale: Locale,
+) -> String {
+    let duration = now.signed_duration_since(timestamp);
+    let future = duration < chrono::Duration::zero();
+    let duration = if future { -duration } else { duration };
+
+    let minutes = duration.num_minutes();
+    let days = duration.num_days();
+    let (amount, unit) = if minutes < 1 {
+        return locale.just_now().to_string();
+    } else if minutes < 60 {
+        (minutes, TimeUnit::Minute)
     } else if duration.num_hours() < 24 {
-        format!("{} hours ago", duration.num_hours())
-    } else if duration.num_days() < 7 {
-        format!("{} days ago", duration.num_days())
-    } else if duration.num_days() < 30 {
-        format!("{} weeks ago", duration.num_days() / 7)
-    } else if duration.num_days() < 365 {
-        format!("{} months ago", duration.num_days() / 30)
+        (duration.num_hours(), TimeUnit::Hour)
+    } else if days < 7 {
+        (days, TimeUnit::Day)
+    } else if days < 30 {
+        (days / 7, TimeUnit::Week)
+    } else if days < 365 {
+        // 360-364 days is still under a year, not "12 months"
+        ((days / 30).min(11), TimeUnit::Month)
     } else {
-        format!("{} years ago", duration.num_days() / 365)
+        (days / 365, TimeUnit::Year)
+    };
+
+    locale.phrase(amount, unit, future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher_preference_for_action() {
+        assert_eq!(watcher_preference_for_action("Assigned"), Some("assignments"));
+        assert_eq!(watcher_preference_for_action("Replied"), Some("new_replies"));
+        assert_eq!(watcher_preference_for_action("Status Changed"), Some("status_changes"));
+        assert_eq!(watcher_preference_for_action("Resolved"), Some("status_changes"));
+        assert_eq!(watcher_preference_for_action("Escalated"), None);
+    }
+
+    fn at(s: &str) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&chrono::Utc)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_relative_time_pluralization() {
+        let now = at("2024-06-15T12:00:00Z");
+        let rel = |s: &str| format_relative_time(at(s), now, Locale::En);
+
+        assert_eq!(rel("2024-06-15T11:59:30Z"), "just now");
+        assert_eq!(rel("2024-06-15T11:59:00Z"), "1 minute ago");
+        assert_eq!(rel("2024-06-15T11:58:00Z"), "2 minutes ago");
+        assert_eq!(rel("2024-06-15T11:00:00Z"), "1 hour ago");
+        assert_eq!(rel("2024-06-15T10:00:00Z"), "2 hours ago");
+        assert_eq!(rel("2024-06-14T12:00:00Z"), "1 day ago");
+        assert_eq!(rel("2024-06-08T12:00:00Z"), "1 week ago");
+        assert_eq!(rel("2024-06-01T12:00:00Z"), "2 weeks ago");
+        assert_eq!(rel("2024-05-16T12:00:00Z"), "1 month ago");
+        assert_eq!(rel("2023-06-21T12:00:00Z"), "11 months ago");
+        assert_eq!(rel("2023-06-17T12:00:00Z"), "11 months ago");
+        assert_eq!(rel("2023-06-16T12:00:00Z"), "1 year ago");
+        assert_eq!(rel("2023-06-15T12:00:00Z"), "1 year ago");
+        assert_eq!(rel("2022-06-15T12:00:00Z"), "2 years ago");
+    }
+
+    #[test]
+    fn test_relative_time_future() {
+        let now = at("2024-06-15T12:00:00Z");
+        let rel = |s: &str| format_relative_time(at(s), now, Locale::En);
+
+        assert_eq!(rel("2024-06-15T12:00:20Z"), "just now");
+        assert_eq!(rel("2024-06-15T12:01:00Z"), "in 1 minute");
+        assert_eq!(rel("2024-06-15T15:00:00Z"), "in 3 hours");
+        assert_eq!(rel("2024-06-16T12:00:00Z"), "in 1 day");
+    }
+
+    #[test]
+    fn test_relative_time_locales() {
+        let now = at("2024-06-15T12:00:00Z");
+        let hour_ago = at("2024-06-15T11:00:00Z");
+        let in_two_days = at("2024-06-17T12:00:00Z");
+
+        assert_eq!(format_relative_time(hour_ago, now, Locale::Es), "hace 1 hora");
+        assert_eq!(format_relative_time(in_two_days, now, Locale::Es), "dentro de 2 días");
+        assert_eq!(format_relative_time(hour_ago, now, Locale::De), "vor 1 Stunde");
+        assert_eq!(format_relative_time(in_two_days, now, Locale::De), "in 2 Tagen");
+    }
+
+    #[test]
+    fn test_locale_from_accept_language() {
+        assert_eq!(Locale::from_accept_language(None), Locale::En);
+        assert_eq!(Locale::from_accept_language(Some("de-DE,de;q=0.9,en;q=0.8")), Locale::De);
+        assert_eq!(Locale::from_accept_language(Some("fr-FR, es;q=0.7, en;q=0.5")), Locale::Es);
+        assert_eq!(Locale::from_accept_language(Some("en;q=0.5, es-MX")), Locale::Es);
+        assert_eq!(Locale::from_accept_language(Some("de;q=0, fr")), Locale::En);
+    }
+
+    #[test]
+    fn test_notification_response_uses_user_timezone() {
+        let notification = Notification {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            title: "Ticket Assigned".to_string(),
+            message: "Ticket has been assigned.".to_string(),
+            notification_type: "ticket_update".to_string(),
+            entity_type: Some("ticket".to_string()),
+            entity_id: None,
+            read: false,
+            created_at: at("2024-01-15T14:30:00Z"),
+        };
+
+        let response = NotificationResponse::new(
+            notification,
+            parse_timezone("America/New_York"),
+            Locale::En,
+        );
+
+        assert_eq!(response.created_at_local, "2024-01-15T09:30:00-05:00");
+        assert_eq!(response.timezone, "America/New_York");
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["created_at"], "2024-01-15T14:30:00Z");
+        assert_eq!(json["created_at_local"], "2024-01-15T09:30:00-05:00");
+    }
+}
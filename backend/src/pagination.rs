@@ -1,9 +1,19 @@
 //! Pagination and query helpers for Resolve API
 //!
 //! Provides standardized pagination, sorting, and filtering across all endpoints.
-
+//!
+//! Offset pagination (`?page=&per_page=`) is the default. Passing `cursor`
+//! switches an endpoint that supports it to keyset pagination over
+//! `(created_at, id)`: an empty cursor starts at the first page and each
+//! response carries `meta.next_cursor` for the one after. Rows inserted
+//! while a client pages through can't shift the pages it hasn't read yet.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
 
 /// Default page size if not specified
 pub const DEFAULT_PAGE_SIZE: i64 = 25;
@@ -19,13 +29,15 @@ pub struct PaginationParams {
     #[serde(default = "default_page")]
     pub page: i64,
     /// Number of items per page
-    #[serde(default = "default_per_page")]
+    #[serde(default = "default_per_page", alias = "limit")]
     pub per_page: i64,
     /// Sort field
     pub sort_by: Option<String>,
     /// Sort direction (asc/desc)
     #[serde(default = "default_sort_order")]
     pub sort_order: String,
+    /// Opaque keyset cursor; present (even empty) to use cursor mode
+    pub cursor: Option<String>,
 }
 
 fn default_page() -> i64 {
@@ -62,6 +74,20 @@ impl PaginationParams {
         }
     }
 
+    /// Whether the caller asked for cursor pagination
+    pub fn is_cursor_mode(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    /// The decoded cursor: `Ok(None)` for the first page, `Err` when the
+    /// cursor isn't one we issued
+    pub fn decoded_cursor(&self) -> Result<Option<Cursor>, InvalidCursor> {
+        match self.cursor.as_deref().map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(cursor) => Cursor::decode(cursor).map(Some),
+        }
+    }
+
     /// Validate and sanitize sort field against allowed fields
     pub fn validated_sort_field(&self, allowed: &[&str], default: &str) -> String {
         self.sort_by
@@ -79,6 +105,7 @@ impl Default for PaginationParams {
             per_page: DEFAULT_PAGE_SIZE,
             sort_by: None,
             sort_order: "desc".to_string(),
+            cursor: None,
         }
     }
 }
@@ -98,6 +125,9 @@ pub struct PaginationMeta {
     pub has_next: bool,
     /// Whether there's a previous page
     pub has_prev: bool,
+    /// Cursor for the next page, in cursor mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 impl PaginationMeta {
@@ -110,8 +140,70 @@ impl PaginationMeta {
             total_pages,
             has_next: page < total_pages,
             has_prev: page > 1,
+            next_cursor: None,
         }
     }
+
+    /// Metadata for a cursor page. Totals aren't counted in cursor mode, so
+    /// `page`, `total` and `total_pages` are 0.
+    pub fn cursor(per_page: i64, has_prev: bool, next_cursor: Option<String>) -> Self {
+        Self {
+            page: 0,
+            per_page,
+            total: 0,
+            total_pages: 0,
+            has_next: next_cursor.is_some(),
+            has_prev,
+            next_cursor,
+        }
+    }
+}
+
+/// Position after the last row of a page, ordered by `(created_at, id)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid pagination cursor")]
+pub struct InvalidCursor;
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: Uuid) -> Self {
+        Self { created_at, id }
+    }
+
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}", self.created_at.timestamp_micros(), self.id);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(value: &str) -> Result<Self, InvalidCursor> {
+        let bytes = URL_SAFE_NO_PAD.decode(value).map_err(|_| InvalidCursor)?;
+        let raw = String::from_utf8(bytes).map_err(|_| InvalidCursor)?;
+        let (micros, id) = raw.split_once('|').ok_or(InvalidCursor)?;
+
+        let micros = micros.parse::<i64>().map_err(|_| InvalidCursor)?;
+        let created_at = Utc.timestamp_micros(micros).single().ok_or(InvalidCursor)?;
+        let id = Uuid::parse_str(id).map_err(|_| InvalidCursor)?;
+
+        Ok(Self { created_at, id })
+    }
+
+    /// SQL condition selecting rows after this cursor, with `created_at` and
+    /// `id` bound as `$param` and `$param + 1`
+    pub fn condition(created_at_column: &str, id_column: &str, descending: bool, param: usize) -> String {
+        format!(
+            "({}, {}) {} (${}, ${})",
+            created_at_column,
+            id_column,
+            if descending { "<" } else { ">" },
+            param,
+            param + 1
+        )
+    }
 }
 
 /// Standard paginated response wrapper
@@ -130,6 +222,48 @@ impl<T> PaginatedResponse<T> {
             meta: PaginationMeta::new(params.page, params.limit(), total),
         }
     }
+
+    /// Build a cursor page from up to `limit + 1` rows fetched after the
+    /// request's cursor; the extra row only signals that another page exists
+    pub fn from_cursor(mut data: Vec<T>, params: &PaginationParams, key: impl Fn(&T) -> Cursor) -> Self {
+        let limit = params.limit() as usize;
+        let next_cursor = if data.len() > limit {
+            data.truncate(limit);
+            data.last().map(|last| key(last).encode())
+        } else {
+            None
+        };
+        let has_prev = params.cursor.as_deref().is_some_and(|c| !c.trim().is_empty());
+
+        Self {
+            data,
+            meta: PaginationMeta::cursor(params.limit(), has_prev, next_cursor),
+        }
+    }
+}
+
+/// A row selected with `COUNT(*) OVER() AS total_count`, so a page and the
+/// filtered total come back in one round trip
+#[derive(Debug, Clone)]
+pub struct WithTotal<T> {
+    pub row: T,
+    pub total_count: i64,
+}
+
+impl<'r, T: FromRow<'r, PgRow>> FromRow<'r, PgRow> for WithTotal<T> {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            row: T::from_row(row)?,
+            total_count: row.try_get("total_count")?,
+        })
+    }
+}
+
+/// Split windowed rows into the page and its total. A page past the end has
+/// no row to carry the total, so it is `None` and the caller must count.
+pub fn split_total<T>(rows: Vec<WithTotal<T>>) -> (Vec<T>, Option<i64>) {
+    let total = rows.first().map(|r| r.total_count);
+    (rows.into_iter().map(|r| r.row).collect(), total)
 }
 
 /// Search parameters common across entities
@@ -355,6 +489,78 @@ mod tests {
         assert!(meta.has_prev);
     }
 
+    #[test]
+    fn test_pagination_meta_edges() {
+        let empty = PaginationMeta::new(1, 25, 0);
+        assert_eq!(empty.total_pages, 0);
+        assert!(!empty.has_next);
+        assert!(!empty.has_prev);
+
+        let exact = PaginationMeta::new(2, 25, 50);
+        assert_eq!(exact.total_pages, 2);
+        assert!(!exact.has_next);
+
+        let partial = PaginationMeta::new(1, 25, 51);
+        assert_eq!(partial.total_pages, 3);
+        assert!(partial.has_next);
+    }
+
+    #[test]
+    fn test_split_total() {
+        let rows = vec![
+            WithTotal { row: "a", total_count: 7 },
+            WithTotal { row: "b", total_count: 7 },
+        ];
+        assert_eq!(split_total(rows), (vec!["a", "b"], Some(7)));
+        assert_eq!(split_total::<&str>(vec![]), (vec![], None));
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor::new(
+            Utc.timestamp_micros(1_700_000_000_123_456).unwrap(),
+            Uuid::new_v4(),
+        );
+        assert_eq!(Cursor::decode(&cursor.encode()), Ok(cursor));
+        assert_eq!(Cursor::decode("not a cursor"), Err(InvalidCursor));
+        assert_eq!(Cursor::decode(&URL_SAFE_NO_PAD.encode("12|nope")), Err(InvalidCursor));
+    }
+
+    #[test]
+    fn test_cursor_mode_is_opt_in() {
+        let params: PaginationParams = serde_json::from_value(serde_json::json!({ "limit": 10 })).unwrap();
+        assert!(!params.is_cursor_mode());
+        assert_eq!(params.limit(), 10);
+
+        let params: PaginationParams = serde_json::from_value(serde_json::json!({ "cursor": "" })).unwrap();
+        assert!(params.is_cursor_mode());
+        assert_eq!(params.decoded_cursor(), Ok(None));
+    }
+
+    #[test]
+    fn test_cursor_page_trims_lookahead_row() {
+        let rows: Vec<Cursor> = (0..4)
+            .map(|i| Cursor::new(Utc.timestamp_opt(1_700_000_000 - i, 0).unwrap(), Uuid::new_v4()))
+            .collect();
+        let params = PaginationParams { per_page: 3, cursor: Some(String::new()), ..Default::default() };
+
+        let page = PaginatedResponse::from_cursor(rows.clone(), &params, |c| *c);
+        assert_eq!(page.data.len(), 3);
+        assert_eq!(page.meta.next_cursor, Some(rows[2].encode()));
+        assert!(page.meta.has_next);
+        assert!(!page.meta.has_prev);
+
+        let last = PaginatedResponse::from_cursor(rows[..2].to_vec(), &params, |c| *c);
+        assert!(last.meta.next_cursor.is_none());
+        assert!(!last.meta.has_next);
+    }
+
+    #[test]
+    fn test_cursor_condition() {
+        assert_eq!(Cursor::condition("t.created_at", "t.id", true, 3), "(t.created_at, t.id) < ($3, $4)");
+        assert_eq!(Cursor::condition("created_at", "id", false, 1), "(created_at, id) > ($1, $2)");
+    }
+
     #[test]
     fn test_query_builder() {
         let mut qb = QueryBuilder::new();
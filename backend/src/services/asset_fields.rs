@@ -0,0 +1,408 @@
+//! Typed custom fields on assets
+//!
+//! An asset's custom fields come from the active layout for its asset
+//! type. Each field's type decides what a submitted value must look like;
+//! values are stored as text in `asset_field_values`, except password
+//! fields, which are encrypted with the credential [`KeyRing`] into
+//! `field_value_encrypted` and never returned in the clear.
+//!
+//! Submitted values are JSON. Numbers and checkboxes may also be given as
+//! strings ("42", "true") so the older string-only endpoint keeps working.
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::keyring::{KeyPurpose, KeyRing, KeyRingError};
+use crate::validation::{ValidationResult, Validator};
+
+/// Prefix for custom field keys in validation error details
+pub const ERROR_PREFIX: &str = "custom_fields";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Text,
+    Number,
+    Date,
+    Select,
+    Password,
+    Url,
+    Checkbox,
+}
+
+impl FieldKind {
+    /// The kind for an `asset_field_types.name`; types without their own
+    /// rules (email, phone, json, ...) are free text
+    pub fn from_type_name(name: &str) -> Self {
+        match name {
+            "number" | "decimal" | "rating" => Self::Number,
+            "date" => Self::Date,
+            "select" => Self::Select,
+            "password" => Self::Password,
+            "url" => Self::Url,
+            "checkbox" | "boolean" => Self::Checkbox,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// One field of a layout, as the frontend needs it to render a form
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDefinition {
+    pub id: Uuid,
+    pub field_name: String,
+    pub display_name: String,
+    pub kind: FieldKind,
+    /// The field type's name, e.g. `textarea` for a text field
+    pub field_type: String,
+    pub ui_component: Option<String>,
+    pub is_required: bool,
+    /// Choices for select fields
+    pub options: Vec<String>,
+    pub default_value: Option<String>,
+    pub placeholder: Option<String>,
+    pub help_text: Option<String>,
+    pub validation_rules: Option<Value>,
+    pub display_order: i32,
+}
+
+#[derive(FromRow)]
+struct DefinitionRow {
+    id: Uuid,
+    field_name: String,
+    display_name: String,
+    type_name: String,
+    ui_component: Option<String>,
+    is_required: Option<bool>,
+    field_options: Option<Value>,
+    default_value: Option<String>,
+    placeholder: Option<String>,
+    help_text: Option<String>,
+    validation_rules: Option<Value>,
+    display_order: Option<i32>,
+}
+
+impl From<DefinitionRow> for FieldDefinition {
+    fn from(row: DefinitionRow) -> Self {
+        let options = row
+            .field_options
+            .as_ref()
+            .and_then(|o| o.get("options"))
+            .and_then(Value::as_array)
+            .map(|options| options.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        Self {
+            id: row.id,
+            field_name: row.field_name,
+            display_name: row.display_name,
+            kind: FieldKind::from_type_name(&row.type_name),
+            field_type: row.type_name,
+            ui_component: row.ui_component,
+            is_required: row.is_required.unwrap_or(false),
+            options,
+            default_value: row.default_value,
+            placeholder: row.placeholder,
+            help_text: row.help_text,
+            validation_rules: row.validation_rules,
+            display_order: row.display_order.unwrap_or(0),
+        }
+    }
+}
+
+impl FieldDefinition {
+    fn error_key(&self) -> String {
+        format!("{}.{}", ERROR_PREFIX, self.field_name)
+    }
+
+    fn rule(&self, name: &str) -> Option<f64> {
+        self.validation_rules.as_ref()?.get(name)?.as_f64()
+    }
+
+    /// The value as stored, or why it doesn't fit this field. `None` for an
+    /// empty value.
+    pub fn normalize(&self, value: &Value) -> Result<Option<String>, String> {
+        let text = match value {
+            Value::Null => return Ok(None),
+            Value::String(s) if s.trim().is_empty() => return Ok(None),
+            Value::String(s) => s.trim().to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => return Err(format!("{} must be a single value", self.display_name)),
+        };
+
+        match self.kind {
+            FieldKind::Text | FieldKind::Password if !value.is_string() => {
+                Err(format!("{} must be text", self.display_name))
+            }
+            FieldKind::Text | FieldKind::Password => {
+                match self.rule("max_length") {
+                    Some(max) if text.chars().count() as f64 > max => {
+                        Err(format!("{} must be at most {} characters", self.display_name, max))
+                    }
+                    _ => Ok(Some(text)),
+                }
+            }
+            FieldKind::Number => {
+                let number: f64 = text
+                    .parse()
+                    .ok()
+                    .filter(|n: &f64| n.is_finite())
+                    .ok_or_else(|| format!("{} must be a number", self.display_name))?;
+                if let Some(min) = self.rule("min").filter(|min| number < *min) {
+                    return Err(format!("{} must be at least {}", self.display_name, min));
+                }
+                if let Some(max) = self.rule("max").filter(|max| number > *max) {
+                    return Err(format!("{} must be at most {}", self.display_name, max));
+                }
+                Ok(Some(text))
+            }
+            FieldKind::Date => NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                .map(|date| Some(date.to_string()))
+                .map_err(|_| format!("{} must be a date (YYYY-MM-DD)", self.display_name)),
+            FieldKind::Select => {
+                if self.options.iter().any(|o| *o == text) {
+                    Ok(Some(text))
+                } else {
+                    Err(format!("{} must be one of: {}", self.display_name, self.options.join(", ")))
+                }
+            }
+            FieldKind::Url => match url::Url::parse(&text) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(Some(text)),
+                _ => Err(format!("{} must be an http or https URL", self.display_name)),
+            },
+            FieldKind::Checkbox => match text.to_ascii_lowercase().as_str() {
+                "true" => Ok(Some("true".to_string())),
+                "false" => Ok(Some("false".to_string())),
+                _ => Err(format!("{} must be true or false", self.display_name)),
+            },
+        }
+    }
+}
+
+/// A validated value ready to store; `None` clears the field
+#[derive(Debug, Clone)]
+pub struct FieldValue {
+    pub field_id: Uuid,
+    pub kind: FieldKind,
+    pub value: Option<String>,
+}
+
+/// Check `values` (keyed by field name) against the layout's fields. On
+/// create every required field must have a value; on update fields left
+/// out keep what they had, but a required field can't be cleared. Every
+/// problem is reported at once under `custom_fields.<name>`.
+pub fn validate(
+    definitions: &[FieldDefinition],
+    values: &HashMap<String, Value>,
+    creating: bool,
+) -> ValidationResult<Vec<FieldValue>> {
+    let mut validator = Validator::new();
+    let mut accepted = Vec::new();
+
+    let mut unknown: Vec<&String> = values
+        .keys()
+        .filter(|name| !definitions.iter().any(|d| d.field_name == **name))
+        .collect();
+    unknown.sort();
+    for name in unknown {
+        validator = validator.error(
+            &format!("{}.{}", ERROR_PREFIX, name),
+            "is not a field on this asset's layout",
+        );
+    }
+
+    for definition in definitions {
+        let given = values.get(&definition.field_name);
+        let normalized = match given {
+            Some(value) => definition.normalize(value),
+            None => Ok(None),
+        };
+        match normalized {
+            Err(message) => validator = validator.error(&definition.error_key(), &message),
+            Ok(None) if definition.is_required && (creating || given.is_some()) => {
+                validator = validator.error(&definition.error_key(), &format!("{} is required", definition.display_name));
+            }
+            Ok(value) => {
+                if given.is_some() {
+                    accepted.push(FieldValue { field_id: definition.id, kind: definition.kind, value });
+                }
+            }
+        }
+    }
+
+    validator.finish()?;
+    Ok(accepted)
+}
+
+/// The fields of the active layout for `asset_type`, in display order;
+/// empty when the type has no layout
+pub async fn definitions_for_type(pool: &PgPool, asset_type: &str) -> Result<Vec<FieldDefinition>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, DefinitionRow>(
+        "SELECT f.id, f.field_name, f.display_name, t.name AS type_name, t.ui_component, f.is_required,
+                f.field_options, f.default_value, f.placeholder, f.help_text, f.validation_rules, f.display_order
+         FROM asset_layouts l
+         JOIN asset_layout_fields f ON f.layout_id = l.id
+         JOIN asset_field_types t ON t.id = f.field_type_id
+         WHERE l.asset_type = $1 AND l.is_active = true
+         ORDER BY f.display_order, f.display_name"
+    )
+    .bind(asset_type)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(FieldDefinition::from).collect())
+}
+
+/// Store the values for `asset_id`, encrypting password fields
+pub async fn save(conn: &mut PgConnection, asset_id: Uuid, values: &[FieldValue]) -> Result<(), AssetFieldError> {
+    let mut ring = None;
+    for field in values {
+        let (plain, encrypted) = match (&field.value, field.kind) {
+            (Some(value), FieldKind::Password) => {
+                let ring = match &ring {
+                    Some(ring) => ring,
+                    None => ring.insert(KeyRing::from_env(KeyPurpose::Credential)?),
+                };
+                (None, Some(ring.encrypt_str(value)?))
+            }
+            (value, _) => (value.clone(), None),
+        };
+
+        sqlx::query(
+            "INSERT INTO asset_field_values (asset_id, field_id, field_value, field_value_encrypted, updated_at)
+             VALUES ($1, $2, $3, $4, NOW())
+             ON CONFLICT (asset_id, field_id)
+             DO UPDATE SET field_value = $3, field_value_encrypted = $4, updated_at = NOW()"
+        )
+        .bind(asset_id)
+        .bind(field.field_id)
+        .bind(plain)
+        .bind(encrypted)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AssetFieldError {
+    #[error(transparent)]
+    KeyRing(#[from] KeyRingError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// A field with the asset's value, for rendering
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedField {
+    #[serde(flatten)]
+    pub definition: FieldDefinition,
+    /// Typed value; always `null` for passwords
+    pub value: Option<Value>,
+    /// Whether a value is stored, which for passwords is all that's shown
+    pub has_value: bool,
+}
+
+/// Every field of the asset's layout with its stored value, if any
+pub async fn render(pool: &PgPool, asset_id: Uuid, asset_type: &str) -> Result<Vec<RenderedField>, sqlx::Error> {
+    let definitions = definitions_for_type(pool, asset_type).await?;
+    if definitions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let stored: HashMap<Uuid, (Option<String>, Option<String>)> = sqlx::query_as::<_, (Uuid, Option<String>, Option<String>)>(
+        "SELECT field_id, field_value, field_value_encrypted FROM asset_field_values WHERE asset_id = $1"
+    )
+    .bind(asset_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(field_id, plain, encrypted)| (field_id, (plain, encrypted)))
+    .collect();
+
+    Ok(definitions
+        .into_iter()
+        .map(|definition| {
+            let (plain, encrypted) = stored.get(&definition.id).cloned().unwrap_or_default();
+            let has_value = plain.is_some() || encrypted.is_some();
+            let value = match definition.kind {
+                FieldKind::Password => None,
+                kind => plain.map(|text| typed(kind, text)),
+            };
+            RenderedField { definition, value, has_value }
+        })
+        .collect())
+}
+
+fn typed(kind: FieldKind, text: String) -> Value {
+    match kind {
+        FieldKind::Number => text
+            .parse::<serde_json::Number>()
+            .map(Value::Number)
+            .unwrap_or(Value::String(text)),
+        FieldKind::Checkbox => Value::Bool(text == "true"),
+        _ => Value::String(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn field(name: &str, type_name: &str, required: bool) -> FieldDefinition {
+        FieldDefinition {
+            id: Uuid::new_v4(),
+            field_name: name.to_string(),
+            display_name: name.to_string(),
+            kind: FieldKind::from_type_name(type_name),
+            field_type: type_name.to_string(),
+            ui_component: None,
+            is_required: required,
+            options: vec!["Proxmox".to_string(), "KVM".to_string()],
+            default_value: None,
+            placeholder: None,
+            help_text: None,
+            validation_rules: Some(json!({ "min": 1, "max": 256 })),
+            display_order: 0,
+        }
+    }
+
+    #[test]
+    fn test_values_are_checked_against_their_type() {
+        assert_eq!(field("cores", "number", false).normalize(&json!(8)), Ok(Some("8".to_string())));
+        assert_eq!(field("cores", "number", false).normalize(&json!("16")), Ok(Some("16".to_string())));
+        assert!(field("cores", "number", false).normalize(&json!("eight")).is_err());
+        assert!(field("cores", "number", false).normalize(&json!(512)).is_err());
+        assert_eq!(field("bought", "date", false).normalize(&json!("2024-02-29")), Ok(Some("2024-02-29".to_string())));
+        assert!(field("bought", "date", false).normalize(&json!("29/02/2024")).is_err());
+        assert!(field("platform", "select", false).normalize(&json!("KVM")).is_ok());
+        assert!(field("platform", "select", false).normalize(&json!("Xen")).is_err());
+        assert!(field("console", "url", false).normalize(&json!("https://idrac.local")).is_ok());
+        assert!(field("console", "url", false).normalize(&json!("ftp://idrac.local")).is_err());
+        assert_eq!(field("backed_up", "checkbox", false).normalize(&json!(true)), Ok(Some("true".to_string())));
+        assert!(field("backed_up", "boolean", false).normalize(&json!("yes")).is_err());
+        assert!(field("notes", "text", false).normalize(&json!(5)).is_err());
+        assert_eq!(field("notes", "text", false).normalize(&json!("  ")), Ok(None));
+    }
+
+    #[test]
+    fn test_required_fields() {
+        let definitions = vec![field("serial", "text", true), field("cores", "number", false)];
+
+        let errors = validate(&definitions, &HashMap::new(), true).unwrap_err();
+        assert!(format!("{:?}", errors).contains("custom_fields.serial"));
+
+        // Left out of an update, it keeps its value; cleared, it's an error
+        assert!(validate(&definitions, &HashMap::new(), false).unwrap().is_empty());
+        let cleared = HashMap::from([("serial".to_string(), json!(""))]);
+        assert!(validate(&definitions, &cleared, false).is_err());
+
+        let unknown = HashMap::from([("serial".to_string(), json!("X1")), ("colour".to_string(), json!("red"))]);
+        assert!(format!("{:?}", validate(&definitions, &unknown, true).unwrap_err()).contains("custom_fields.colour"));
+    }
+}
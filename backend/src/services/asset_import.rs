@@ -0,0 +1,525 @@
+//! Bulk asset import from CSV
+//!
+//! The first line is a header naming any of [`COLUMNS`] in any order;
+//! `name` and `asset_type` are required. `location` and `contact` are
+//! matched by name (case-insensitive) against the client's active
+//! locations and contacts. A name that matches nothing, or more than one
+//! row, is reported as a warning and the asset is imported without it.
+//!
+//! Every row is validated first. Valid rows are then inserted in one
+//! transaction, or only reported on when `dry_run` is set. Line numbers
+//! in the report are the physical line each record starts on, counting
+//! the header as line 1.
+
+use chrono::NaiveDate;
+use resolve_shared::{AssetStatus, TextEnum};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// Recognised header names, in the order the template lists them
+pub const COLUMNS: &[&str] = &[
+    "name",
+    "asset_type",
+    "description",
+    "make",
+    "model",
+    "serial",
+    "os",
+    "ip",
+    "mac",
+    "uri",
+    "status",
+    "location",
+    "contact",
+    "purchase_date",
+    "warranty_expire",
+    "install_date",
+    "notes",
+];
+const REQUIRED_COLUMNS: &[&str] = &["name", "asset_type"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum AssetImportError {
+    #[error("Line {line}: {message}")]
+    Csv { line: usize, message: String },
+    #[error("Unknown column '{0}'")]
+    UnknownColumn(String),
+    #[error("Duplicate column '{0}'")]
+    DuplicateColumn(String),
+    #[error("Missing required column '{0}'")]
+    MissingColumn(String),
+    #[error("The file has no header row")]
+    Empty,
+    #[error("Client not found")]
+    ClientNotFound,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Created,
+    /// Passed validation in a dry run
+    Valid,
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowResult {
+    pub line: usize,
+    pub status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub created: usize,
+    pub invalid: usize,
+    pub rows: Vec<RowResult>,
+}
+
+/// One CSV record and the line it starts on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// Split CSV text into records. Handles quoted fields with embedded
+/// commas, doubled quotes and line breaks, CRLF or LF endings, and a
+/// leading byte-order mark. Blank lines are skipped.
+pub fn parse_csv(input: &str) -> Result<Vec<Record>, AssetImportError> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start_line = 1;
+    let mut chars = input.chars().peekable();
+
+    let mut end_record = |fields: &mut Vec<String>, field: &mut String, start_line: usize| {
+        fields.push(std::mem::take(field));
+        let record = std::mem::take(fields);
+        if !(record.len() == 1 && record[0].is_empty()) {
+            records.push(Record { line: start_line, fields: record });
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() && !quoted => {
+                in_quotes = true;
+                quoted = true;
+            }
+            '"' => {
+                return Err(AssetImportError::Csv { line, message: "unexpected quote in unquoted field".into() });
+            }
+            ',' => {
+                fields.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                end_record(&mut fields, &mut field, start_line);
+                quoted = false;
+                line += 1;
+                start_line = line;
+            }
+            _ if quoted => {
+                return Err(AssetImportError::Csv { line, message: "text after closing quote".into() });
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(AssetImportError::Csv { line: start_line, message: "unterminated quoted field".into() });
+    }
+    end_record(&mut fields, &mut field, start_line);
+
+    Ok(records)
+}
+
+/// Column positions taken from the header row
+#[derive(Debug)]
+pub struct Header {
+    positions: HashMap<&'static str, usize>,
+    width: usize,
+}
+
+impl Header {
+    pub fn parse(record: &Record) -> Result<Self, AssetImportError> {
+        let mut positions = HashMap::new();
+        for (i, raw) in record.fields.iter().enumerate() {
+            let name = raw.trim().to_ascii_lowercase();
+            let column = COLUMNS
+                .iter()
+                .find(|c| **c == name)
+                .ok_or_else(|| AssetImportError::UnknownColumn(raw.trim().to_string()))?;
+            if positions.insert(*column, i).is_some() {
+                return Err(AssetImportError::DuplicateColumn(name));
+            }
+        }
+        for required in REQUIRED_COLUMNS {
+            if !positions.contains_key(required) {
+                return Err(AssetImportError::MissingColumn(required.to_string()));
+            }
+        }
+        Ok(Self { positions, width: record.fields.len() })
+    }
+
+    /// A trimmed cell, `None` when the column is absent or the cell empty
+    fn get<'a>(&self, record: &'a Record, column: &str) -> Option<&'a str> {
+        self.positions
+            .get(column)
+            .and_then(|i| record.fields.get(*i))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+/// A row that passed validation, before name references are resolved
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetRow {
+    pub name: String,
+    pub asset_type: String,
+    pub description: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub os: Option<String>,
+    pub ip: Option<IpAddr>,
+    pub mac: Option<String>,
+    pub uri: Option<String>,
+    pub status: String,
+    pub location: Option<String>,
+    pub contact: Option<String>,
+    pub purchase_date: Option<NaiveDate>,
+    pub warranty_expire: Option<NaiveDate>,
+    pub install_date: Option<NaiveDate>,
+    pub notes: Option<String>,
+}
+
+fn is_mac(value: &str) -> bool {
+    let groups: Vec<&str> = value.split([':', '-']).collect();
+    groups.len() == 6 && groups.iter().all(|g| g.len() == 2 && g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Validate one data record against the header
+pub fn validate_row(header: &Header, record: &Record) -> Result<AssetRow, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut error = |field: &str, message: &str| {
+        errors.push(FieldError { field: field.to_string(), message: message.to_string() });
+    };
+    let text = |column: &str| header.get(record, column).map(str::to_string);
+
+    if record.fields.len() != header.width {
+        error(
+            "row",
+            &format!("expected {} fields, found {}", header.width, record.fields.len()),
+        );
+    }
+
+    let name = text("name").unwrap_or_default();
+    if name.is_empty() {
+        error("name", "is required");
+    }
+    let asset_type = text("asset_type").unwrap_or_default();
+    if asset_type.is_empty() {
+        error("asset_type", "is required");
+    }
+
+    let ip = header.get(record, "ip").and_then(|v| match v.parse::<IpAddr>() {
+        Ok(ip) => Some(ip),
+        Err(_) => {
+            error("ip", "must be an IPv4 or IPv6 address");
+            None
+        }
+    });
+
+    let mac = header.get(record, "mac").and_then(|v| {
+        if is_mac(v) {
+            Some(v.to_ascii_lowercase().replace('-', ":"))
+        } else {
+            error("mac", "must be six hex pairs, e.g. 00:1a:2b:3c:4d:5e");
+            None
+        }
+    });
+
+    let uri = header.get(record, "uri").and_then(|v| match url::Url::parse(v) {
+        Ok(_) => Some(v.to_string()),
+        Err(_) => {
+            error("uri", "must be an absolute URL");
+            None
+        }
+    });
+
+    let status = match header.get(record, "status") {
+        Some(v) => AssetStatus::parse(v).unwrap_or_else(|| {
+            error("status", &format!("must be one of: {}", AssetStatus::NAMES.join(", ")));
+            AssetStatus::default()
+        }),
+        None => AssetStatus::default(),
+    };
+
+    let mut date = |column: &str| {
+        header.get(record, column).and_then(|v| match NaiveDate::parse_from_str(v, "%Y-%m-%d") {
+            Ok(date) => Some(date),
+            Err(_) => {
+                error(column, "must be a date in YYYY-MM-DD format");
+                None
+            }
+        })
+    };
+    let purchase_date = date("purchase_date");
+    let warranty_expire = date("warranty_expire");
+    let install_date = date("install_date");
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(AssetRow {
+        name,
+        asset_type,
+        description: text("description"),
+        make: text("make"),
+        model: text("model"),
+        serial: text("serial"),
+        os: text("os"),
+        ip,
+        mac,
+        uri,
+        status: status.as_str().to_string(),
+        location: text("location"),
+        contact: text("contact"),
+        purchase_date,
+        warranty_expire,
+        install_date,
+        notes: text("notes"),
+    })
+}
+
+/// Lower-cased name to id for a client's active locations or contacts.
+/// Names shared by several rows map to `None`.
+async fn name_index(pool: &PgPool, table: &str, client_id: Uuid) -> Result<HashMap<String, Option<Uuid>>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (Uuid, String)>(&format!(
+        "SELECT id, name FROM {} WHERE client_id = $1 AND archived_at IS NULL",
+        table
+    ))
+    .bind(client_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut index: HashMap<String, Option<Uuid>> = HashMap::new();
+    for (id, name) in rows {
+        index
+            .entry(name.trim().to_lowercase())
+            .and_modify(|existing| *existing = None)
+            .or_insert(Some(id));
+    }
+    Ok(index)
+}
+
+fn resolve(index: &HashMap<String, Option<Uuid>>, kind: &str, name: Option<&str>, warnings: &mut Vec<String>) -> Option<Uuid> {
+    let name = name?;
+    match index.get(&name.to_lowercase()) {
+        Some(Some(id)) => Some(*id),
+        Some(None) => {
+            warnings.push(format!("{} '{}' matches more than one {}; left unset", kind, name, kind));
+            None
+        }
+        None => {
+            warnings.push(format!("{} '{}' not found; left unset", kind, name));
+            None
+        }
+    }
+}
+
+/// Validate `csv` and, unless `dry_run`, insert every valid row for
+/// `client_id`. Header and parse problems fail the whole file; row
+/// problems are reported per row.
+pub async fn import_assets(
+    pool: &PgPool,
+    client_id: Uuid,
+    csv: &str,
+    dry_run: bool,
+) -> Result<ImportReport, AssetImportError> {
+    let client_exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM clients WHERE id = $1 AND archived_at IS NULL)")
+        .bind(client_id)
+        .fetch_one(pool)
+        .await?;
+    if !client_exists {
+        return Err(AssetImportError::ClientNotFound);
+    }
+
+    let mut records = parse_csv(csv)?.into_iter();
+    let header = Header::parse(&records.next().ok_or(AssetImportError::Empty)?)?;
+
+    let locations = name_index(pool, "locations", client_id).await?;
+    let contacts = name_index(pool, "contacts", client_id).await?;
+
+    let mut rows = Vec::new();
+    let mut valid = Vec::new();
+    for record in records {
+        match validate_row(&header, &record) {
+            Ok(asset) => {
+                let mut warnings = Vec::new();
+                let location_id = resolve(&locations, "location", asset.location.as_deref(), &mut warnings);
+                let contact_id = resolve(&contacts, "contact", asset.contact.as_deref(), &mut warnings);
+                valid.push((rows.len(), asset, location_id, contact_id));
+                rows.push(RowResult { line: record.line, status: RowStatus::Valid, id: None, errors: Vec::new(), warnings });
+            }
+            Err(errors) => {
+                rows.push(RowResult { line: record.line, status: RowStatus::Invalid, id: None, errors, warnings: Vec::new() });
+            }
+        }
+    }
+
+    if !dry_run && !valid.is_empty() {
+        let mut tx = pool.begin().await?;
+        for (index, asset, location_id, contact_id) in &valid {
+            let id = sqlx::query_scalar::<_, Uuid>(
+                r#"
+                INSERT INTO assets (client_id, name, asset_type, description, make, model, serial, os,
+                    ip, mac, uri, status, location_id, contact_id, purchase_date, warranty_expire,
+                    install_date, notes, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::inet, $10::macaddr, $11, $12, $13, $14,
+                    $15, $16, $17, $18, NOW())
+                RETURNING id
+                "#
+            )
+            .bind(client_id)
+            .bind(&asset.name)
+            .bind(&asset.asset_type)
+            .bind(&asset.description)
+            .bind(&asset.make)
+            .bind(&asset.model)
+            .bind(&asset.serial)
+            .bind(&asset.os)
+            .bind(asset.ip.map(|ip| ip.to_string()))
+            .bind(&asset.mac)
+            .bind(&asset.uri)
+            .bind(&asset.status)
+            .bind(location_id)
+            .bind(contact_id)
+            .bind(asset.purchase_date)
+            .bind(asset.warranty_expire)
+            .bind(asset.install_date)
+            .bind(&asset.notes)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            rows[*index].status = RowStatus::Created;
+            rows[*index].id = Some(id);
+        }
+        tx.commit().await?;
+    }
+
+    let invalid = rows.iter().filter(|r| r.status == RowStatus::Invalid).count();
+    Ok(ImportReport {
+        dry_run,
+        total: rows.len(),
+        created: rows.iter().filter(|r| r.status == RowStatus::Created).count(),
+        invalid,
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(columns: &str) -> Header {
+        Header::parse(&parse_csv(columns).unwrap()[0]).unwrap()
+    }
+
+    fn record(line: &str) -> Record {
+        parse_csv(line).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_parse_csv_quoting_and_line_numbers() {
+        let csv = "\u{feff}name,notes\r\nFW-01,\"rack 2, top\"\r\n\r\nSW-01,\"says \"\"hi\"\"\nand more\"\nAP-01,\n";
+        let records = parse_csv(csv).unwrap();
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1], Record { line: 2, fields: vec!["FW-01".into(), "rack 2, top".into()] });
+        assert_eq!(records[2].line, 4);
+        assert_eq!(records[2].fields[1], "says \"hi\"\nand more");
+        assert_eq!(records[3], Record { line: 6, fields: vec!["AP-01".into(), String::new()] });
+    }
+
+    #[test]
+    fn test_parse_csv_rejects_malformed_quotes() {
+        assert!(matches!(parse_csv("name\n\"open"), Err(AssetImportError::Csv { line: 2, .. })));
+        assert!(matches!(parse_csv("name\nab\"c"), Err(AssetImportError::Csv { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_header_validation() {
+        let parse = |s: &str| Header::parse(&record(s));
+        assert!(parse("Name, Asset_Type ,ip").is_ok());
+        assert!(matches!(parse("name,asset_type,colour"), Err(AssetImportError::UnknownColumn(c)) if c == "colour"));
+        assert!(matches!(parse("name,ip"), Err(AssetImportError::MissingColumn(c)) if c == "asset_type"));
+        assert!(matches!(parse("name,asset_type,name"), Err(AssetImportError::DuplicateColumn(_))));
+    }
+
+    #[test]
+    fn test_validate_row() {
+        let header = header("name,asset_type,ip,mac,purchase_date,status");
+
+        let row = validate_row(&header, &record("FW-01,firewall,10.0.0.1,00-1A-2B-3C-4D-5E,2023-04-01,")).unwrap();
+        assert_eq!(row.ip, Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(row.mac.as_deref(), Some("00:1a:2b:3c:4d:5e"));
+        assert_eq!(row.purchase_date, NaiveDate::from_ymd_opt(2023, 4, 1));
+        assert_eq!(row.status, "active");
+
+        let errors = validate_row(&header, &record(",firewall,10.0.0.300,nope,04/01/2023,")).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["name", "ip", "mac", "purchase_date"]);
+
+        let row = validate_row(&header, &record("FW-02,firewall,,,, Retired")).unwrap();
+        assert_eq!(row.status, "retired");
+        let errors = validate_row(&header, &record("FW-03,firewall,,,,actve")).unwrap_err();
+        assert_eq!(errors[0].field, "status");
+
+        let errors = validate_row(&header, &record("FW-01,firewall")).unwrap_err();
+        assert_eq!(errors[0].field, "row");
+    }
+}
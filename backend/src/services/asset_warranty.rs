@@ -0,0 +1,265 @@
+//! Asset warranty expiry alerts
+//!
+//! The expiration monitor passes a set of warning thresholds in days. Once
+//! an asset's `warranty_expire` comes within one, an `alerts` row is opened
+//! and the client's technicians are notified. `asset_warranty_alerts`
+//! records which threshold each alert was for, so every threshold fires at
+//! most once per warranty date.
+
+use chrono::NaiveDate;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::notifications::create_notifications_for_users;
+
+pub const ALERT_TYPE: &str = "warranty_expiring";
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ExpiringWarranty {
+    pub asset_id: Uuid,
+    pub asset_name: String,
+    pub asset_type: String,
+    pub make: Option<String>,
+    pub serial: Option<String>,
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub client_email: Option<String>,
+    pub warranty_expire: NaiveDate,
+}
+
+/// An alert opened by [`check_warranties`]
+#[derive(Debug, Clone)]
+pub struct WarrantyAlert {
+    pub alert_id: Uuid,
+    pub warranty: ExpiringWarranty,
+    pub threshold_days: i32,
+    pub days_remaining: i32,
+    pub notified: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct WarrantyCheck {
+    pub checked: usize,
+    pub alerts: Vec<WarrantyAlert>,
+}
+
+/// The tightest threshold that `days_remaining` has reached, if any. An
+/// asset already 10 days out against `[90, 60, 30]` has crossed 30, and
+/// alerting on that alone avoids a burst of stale 90- and 60-day alerts.
+pub fn crossed_threshold(days_remaining: i32, thresholds: &[i32]) -> Option<i32> {
+    if days_remaining < 0 {
+        return None;
+    }
+    thresholds.iter().copied().filter(|t| days_remaining <= *t).min()
+}
+
+/// Severity rises as the expiry date gets closer
+pub fn severity(days_remaining: i32) -> &'static str {
+    match days_remaining {
+        ..=7 => "critical",
+        8..=14 => "high",
+        15..=30 => "medium",
+        _ => "low",
+    }
+}
+
+pub fn alert_message(warranty: &ExpiringWarranty, days_remaining: i32) -> String {
+    format!(
+        "Warranty for {} ({}) at {} expires on {} ({} day(s) remaining)",
+        warranty.asset_name,
+        warranty.asset_type,
+        warranty.client_name,
+        warranty.warranty_expire.format("%Y-%m-%d"),
+        days_remaining
+    )
+}
+
+/// Active assets whose warranty ends between `today` and `today + max_days`
+pub async fn expiring_warranties(pool: &PgPool, today: NaiveDate, max_days: i32) -> Result<Vec<ExpiringWarranty>, sqlx::Error> {
+    sqlx::query_as::<_, ExpiringWarranty>(
+        r#"
+        SELECT a.id AS asset_id, a.name AS asset_name, a.asset_type, a.make, a.serial,
+               c.id AS client_id, c.name AS client_name, c.email AS client_email, a.warranty_expire
+        FROM assets a
+        JOIN clients c ON a.client_id = c.id
+        WHERE a.warranty_expire BETWEEN $1 AND $1 + $2::int
+            AND a.archived_at IS NULL
+            AND c.archived_at IS NULL
+        ORDER BY a.warranty_expire ASC
+        "#
+    )
+    .bind(today)
+    .bind(max_days)
+    .fetch_all(pool)
+    .await
+}
+
+/// Open the alert for `threshold_days` unless it was already raised for this
+/// warranty date. Returns the new alert's id.
+pub async fn raise_alert(
+    pool: &PgPool,
+    warranty: &ExpiringWarranty,
+    threshold_days: i32,
+    days_remaining: i32,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let claimed = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO asset_warranty_alerts (asset_id, threshold_days, warranty_expire)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (asset_id, threshold_days, warranty_expire) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(warranty.asset_id)
+    .bind(threshold_days)
+    .bind(warranty.warranty_expire)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(claim_id) = claimed else {
+        return Ok(None);
+    };
+
+    let alert_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO alerts (asset_id, alert_type, severity, title, message)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#
+    )
+    .bind(warranty.asset_id)
+    .bind(ALERT_TYPE)
+    .bind(severity(days_remaining))
+    .bind(format!("Warranty expiring: {}", warranty.asset_name))
+    .bind(alert_message(warranty, days_remaining))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE asset_warranty_alerts SET alert_id = $2 WHERE id = $1")
+        .bind(claim_id)
+        .bind(alert_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Some(alert_id))
+}
+
+/// The client's account manager and technicians recently assigned to its
+/// tickets, falling back to every active admin and technician.
+pub async fn client_technicians(pool: &PgPool, client_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let techs = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT DISTINCT user_id FROM (
+            SELECT account_manager_id as user_id FROM clients WHERE id = $1
+            UNION
+            SELECT assigned_to as user_id FROM tickets
+            WHERE client_id = $1 AND created_at > NOW() - INTERVAL '90 days'
+        ) AS techs
+        JOIN users u ON u.id = techs.user_id
+        WHERE COALESCE(u.is_active, true)
+        "#
+    )
+    .bind(client_id)
+    .fetch_all(pool)
+    .await?;
+
+    if !techs.is_empty() {
+        return Ok(techs);
+    }
+
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT u.id FROM users u
+        JOIN roles r ON u.role_id = r.id
+        WHERE r.name IN ('admin', 'technician') AND COALESCE(u.is_active, true)
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Raise and notify an alert for every warranty that has crossed a new
+/// threshold as of `today`
+pub async fn check_warranties(pool: &PgPool, today: NaiveDate, thresholds: &[i32]) -> Result<WarrantyCheck, sqlx::Error> {
+    let Some(max_days) = thresholds.iter().copied().max() else {
+        return Ok(WarrantyCheck::default());
+    };
+
+    let warranties = expiring_warranties(pool, today, max_days).await?;
+    let mut check = WarrantyCheck { checked: warranties.len(), alerts: Vec::new() };
+
+    for warranty in warranties {
+        let days_remaining = (warranty.warranty_expire - today).num_days() as i32;
+        let Some(threshold_days) = crossed_threshold(days_remaining, thresholds) else {
+            continue;
+        };
+        let Some(alert_id) = raise_alert(pool, &warranty, threshold_days, days_remaining).await? else {
+            continue;
+        };
+
+        let recipients = client_technicians(pool, warranty.client_id).await?;
+        let notified = create_notifications_for_users(
+            pool,
+            recipients,
+            format!("Warranty expiring: {}", warranty.asset_name),
+            alert_message(&warranty, days_remaining),
+            "warranty_expiry".to_string(),
+            Some("asset".to_string()),
+            Some(warranty.asset_id),
+        )
+        .await?
+        .len();
+
+        check.alerts.push(WarrantyAlert { alert_id, warranty, threshold_days, days_remaining, notified });
+    }
+
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossed_threshold_is_the_tightest_reached() {
+        let thresholds = [90, 60, 30];
+        assert_eq!(crossed_threshold(95, &thresholds), None);
+        assert_eq!(crossed_threshold(90, &thresholds), Some(90));
+        assert_eq!(crossed_threshold(45, &thresholds), Some(60));
+        assert_eq!(crossed_threshold(10, &thresholds), Some(30));
+        assert_eq!(crossed_threshold(0, &thresholds), Some(30));
+        assert_eq!(crossed_threshold(-1, &thresholds), None);
+        assert_eq!(crossed_threshold(10, &[]), None);
+    }
+
+    #[test]
+    fn test_severity_scales_with_proximity() {
+        assert_eq!(severity(0), "critical");
+        assert_eq!(severity(7), "critical");
+        assert_eq!(severity(10), "high");
+        assert_eq!(severity(30), "medium");
+        assert_eq!(severity(60), "low");
+    }
+
+    #[test]
+    fn test_alert_message_names_asset_client_and_days() {
+        let warranty = ExpiringWarranty {
+            asset_id: Uuid::new_v4(),
+            asset_name: "FS-01".to_string(),
+            asset_type: "server".to_string(),
+            make: None,
+            serial: None,
+            client_id: Uuid::new_v4(),
+            client_name: "Acme Corp".to_string(),
+            client_email: None,
+            warranty_expire: NaiveDate::from_ymd_opt(2024, 3, 11).unwrap(),
+        };
+        assert_eq!(
+            alert_message(&warranty, 10),
+            "Warranty for FS-01 (server) at Acme Corp expires on 2024-03-11 (10 day(s) remaining)"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Reading the audit trail
+//!
+//! Handlers across the app write `audit_logs` rows keyed by
+//! `(entity_type, entity_id)` with optional `old_values`/`new_values`
+//! snapshots. This module filters and pages through them, newest first,
+//! and turns each pair of snapshots into a field-level change list.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::postgres::PgArguments;
+use sqlx::query::{QueryAs, QueryScalar};
+use sqlx::{FromRow, PgPool, Postgres};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+use crate::pagination::{Cursor, InvalidCursor, PaginatedResponse, PaginationParams, QueryBuilder};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuditQueryError {
+    #[error(transparent)]
+    InvalidCursor(#[from] InvalidCursor),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditLogFilters {
+    pub user_id: Option<Uuid>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditLogRecord {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub user_email: Option<String>,
+    pub user_name: Option<String>,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    pub old_values: Option<Value>,
+    pub new_values: Option<Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    pub changes: Vec<FieldChange>,
+}
+
+const SELECT: &str = r#"
+    SELECT l.id, l.user_id, u.email AS user_email,
+           NULLIF(TRIM(CONCAT(u.first_name, ' ', u.last_name)), '') AS user_name,
+           l.action, l.entity_type, l.entity_id, l.old_values, l.new_values,
+           l.ip_address::text AS ip_address, l.user_agent, l.created_at
+    FROM audit_logs l
+    LEFT JOIN users u ON l.user_id = u.id
+"#;
+
+/// Fields that differ between two snapshots, in field order. A missing
+/// snapshot or field reads as `null`, so a create lists every new field.
+/// Snapshots that aren't objects are compared as a single `value` field.
+pub fn field_changes(old: Option<&Value>, new: Option<&Value>) -> Vec<FieldChange> {
+    fn as_object<'a>(v: Option<&'a Value>, empty: &'a Map<String, Value>) -> Option<&'a Map<String, Value>> {
+        match v {
+            None | Some(Value::Null) => Some(empty),
+            Some(Value::Object(map)) => Some(map),
+            Some(_) => None,
+        }
+    }
+
+    let empty = Map::new();
+    match (as_object(old, &empty), as_object(new, &empty)) {
+        (Some(old), Some(new)) => {
+            let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            fields
+                .into_iter()
+                .filter_map(|field| {
+                    let before = old.get(field).cloned().unwrap_or(Value::Null);
+                    let after = new.get(field).cloned().unwrap_or(Value::Null);
+                    (before != after).then(|| FieldChange { field: field.clone(), old: before, new: after })
+                })
+                .collect()
+        }
+        _ => {
+            let before = old.cloned().unwrap_or(Value::Null);
+            let after = new.cloned().unwrap_or(Value::Null);
+            if before == after {
+                Vec::new()
+            } else {
+                vec![FieldChange { field: "value".to_string(), old: before, new: after }]
+            }
+        }
+    }
+}
+
+impl AuditLogFilters {
+    /// Conditions for the set filters, numbered in bind order
+    fn conditions(&self) -> QueryBuilder {
+        let mut qb = QueryBuilder::new();
+        qb.add_optional("l.user_id = {}", &self.user_id);
+        qb.add_optional("l.entity_type = {}", &self.entity_type);
+        qb.add_optional("l.entity_id = {}", &self.entity_id);
+        qb.add_optional("LOWER(l.action) = LOWER({})", &self.action);
+        qb.add_optional("l.created_at >= {}", &self.from);
+        qb.add_optional("l.created_at < {}", &self.to);
+        qb
+    }
+
+    fn bind_as<'q, O>(&self, mut query: QueryAs<'q, Postgres, O, PgArguments>) -> QueryAs<'q, Postgres, O, PgArguments> {
+        if let Some(user_id) = self.user_id {
+            query = query.bind(user_id);
+        }
+        if let Some(entity_type) = &self.entity_type {
+            query = query.bind(entity_type.clone());
+        }
+        if let Some(entity_id) = self.entity_id {
+            query = query.bind(entity_id);
+        }
+        if let Some(action) = &self.action {
+            query = query.bind(action.clone());
+        }
+        if let Some(from) = self.from {
+            query = query.bind(from);
+        }
+        if let Some(to) = self.to {
+            query = query.bind(to);
+        }
+        query
+    }
+
+    fn bind_scalar<'q, O>(&self, mut query: QueryScalar<'q, Postgres, O, PgArguments>) -> QueryScalar<'q, Postgres, O, PgArguments> {
+        if let Some(user_id) = self.user_id {
+            query = query.bind(user_id);
+        }
+        if let Some(entity_type) = &self.entity_type {
+            query = query.bind(entity_type.clone());
+        }
+        if let Some(entity_id) = self.entity_id {
+            query = query.bind(entity_id);
+        }
+        if let Some(action) = &self.action {
+            query = query.bind(action.clone());
+        }
+        if let Some(from) = self.from {
+            query = query.bind(from);
+        }
+        if let Some(to) = self.to {
+            query = query.bind(to);
+        }
+        query
+    }
+}
+
+fn with_changes(mut records: Vec<AuditLogRecord>) -> Vec<AuditLogRecord> {
+    for record in &mut records {
+        record.changes = field_changes(record.old_values.as_ref(), record.new_values.as_ref());
+    }
+    records
+}
+
+/// Filtered audit entries, newest first. Pages by offset unless the params
+/// carry a cursor.
+pub async fn search(
+    pool: &PgPool,
+    filters: &AuditLogFilters,
+    params: &PaginationParams,
+) -> Result<PaginatedResponse<AuditLogRecord>, AuditQueryError> {
+    let qb = filters.conditions();
+
+    if !params.is_cursor_mode() {
+        let count_sql = format!("SELECT COUNT(*) FROM audit_logs l {}", qb.where_clause());
+        let total: i64 = filters.bind_scalar(sqlx::query_scalar(&count_sql)).fetch_one(pool).await?;
+
+        let sql = format!(
+            "{} {} ORDER BY l.created_at DESC, l.id DESC LIMIT ${} OFFSET ${}",
+            SELECT,
+            qb.where_clause(),
+            qb.param_count() + 1,
+            qb.param_count() + 2
+        );
+        let records = filters
+            .bind_as(sqlx::query_as::<_, AuditLogRecord>(&sql))
+            .bind(params.limit())
+            .bind(params.offset())
+            .fetch_all(pool)
+            .await?;
+
+        return Ok(PaginatedResponse::new(with_changes(records), params, total));
+    }
+
+    let cursor = params.decoded_cursor()?;
+    let mut where_clause = qb.where_clause();
+    let mut used = qb.param_count();
+    if cursor.is_some() {
+        let condition = Cursor::condition("l.created_at", "l.id", true, used + 1);
+        where_clause = if where_clause.is_empty() {
+            format!("WHERE {}", condition)
+        } else {
+            format!("{} AND {}", where_clause, condition)
+        };
+        used += 2;
+    }
+
+    let sql = format!(
+        "{} {} ORDER BY l.created_at DESC, l.id DESC LIMIT ${}",
+        SELECT,
+        where_clause,
+        used + 1
+    );
+    let mut query = filters.bind_as(sqlx::query_as::<_, AuditLogRecord>(&sql));
+    if let Some(cursor) = cursor {
+        query = query.bind(cursor.created_at).bind(cursor.id);
+    }
+    let records = query.bind(params.limit() + 1).fetch_all(pool).await?;
+
+    Ok(PaginatedResponse::from_cursor(with_changes(records), params, |r| Cursor::new(r.created_at, r.id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_update_lists_only_changed_fields() {
+        let old = json!({ "status": "open", "priority": "low", "subject": "VPN down" });
+        let new = json!({ "status": "closed", "priority": "low", "assigned_to": "tess" });
+
+        assert_eq!(
+            field_changes(Some(&old), Some(&new)),
+            vec![
+                FieldChange { field: "assigned_to".into(), old: Value::Null, new: json!("tess") },
+                FieldChange { field: "status".into(), old: json!("open"), new: json!("closed") },
+                FieldChange { field: "subject".into(), old: json!("VPN down"), new: Value::Null },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_and_missing_snapshots() {
+        let created = field_changes(None, Some(&json!({ "name": "Acme" })));
+        assert_eq!(created, vec![FieldChange { field: "name".into(), old: Value::Null, new: json!("Acme") }]);
+
+        assert!(field_changes(None, None).is_empty());
+        assert_eq!(field_changes(Some(&json!(1)), Some(&json!(2)))[0].field, "value");
+    }
+
+    #[test]
+    fn test_filter_placeholders_follow_bind_order() {
+        let filters = AuditLogFilters {
+            entity_type: Some("ticket".into()),
+            action: Some("update".into()),
+            to: Some(Utc::now()),
+            ..Default::default()
+        };
+        let qb = filters.conditions();
+        assert_eq!(qb.param_count(), 3);
+        assert_eq!(
+            qb.where_clause(),
+            "WHERE l.entity_type = $1 AND LOWER(l.action) = LOWER($2) AND l.created_at < $3"
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! Azure Cost Management budget alerts
+//!
+//! Azure budgets notify an action group, whose webhook posts here. Both the
+//! legacy `AIP Budget Notification` payload and the common alert schema are
+//! understood. Each notification is matched to an `azure_subscriptions` row
+//! by subscription id and raised as an alert on the subscription's asset,
+//! creating a `cloud_subscription` asset for it on first use.
+//!
+//! Azure re-sends a notification while spend stays over the threshold, so
+//! only the first one per subscription, budget, calendar month and threshold
+//! opens an alert and notifies the client's technicians. Every payload is
+//! stored verbatim in `azure_cost_alerts` either way.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::notifications::create_notifications_for_users;
+use crate::services::asset_warranty::client_technicians;
+
+pub const ALERT_TYPE: &str = "azure_budget";
+pub const ASSET_TYPE: &str = "cloud_subscription";
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum PayloadError {
+    #[error("Unrecognized Azure alert payload")]
+    UnknownSchema,
+    #[error("Missing or invalid field: {0}")]
+    Field(&'static str),
+}
+
+/// The parts of a budget notification we act on
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetNotification {
+    pub subscription_id: String,
+    pub subscription_name: Option<String>,
+    pub budget_name: String,
+    pub budget_amount: Option<Decimal>,
+    pub threshold_amount: Decimal,
+    pub spent_amount: Decimal,
+    pub currency: Option<String>,
+}
+
+impl BudgetNotification {
+    /// Spend has reached the notification threshold (rather than being
+    /// forecast to)
+    pub fn crossed_threshold(&self) -> bool {
+        self.spent_amount >= self.threshold_amount
+    }
+
+    pub fn severity(&self) -> &'static str {
+        match self.budget_amount {
+            Some(budget) if self.spent_amount >= budget => "critical",
+            _ if self.crossed_threshold() => "high",
+            _ => "warning",
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let name = self.subscription_name.as_deref().unwrap_or(&self.subscription_id);
+        format!("Azure budget '{}' threshold reached: {}", self.budget_name, name)
+    }
+
+    pub fn message(&self) -> String {
+        let money = |amount: Decimal| match &self.currency {
+            Some(currency) => format!("{} {}", amount.round_dp(2), currency),
+            None => amount.round_dp(2).to_string(),
+        };
+        let budget = self
+            .budget_amount
+            .map(|budget| format!(" of a {} budget", money(budget)))
+            .unwrap_or_default();
+        format!(
+            "Spend is {} against a {} threshold{}",
+            money(self.spent_amount),
+            money(self.threshold_amount),
+            budget
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestOutcome {
+    /// Alert opened and technicians notified
+    Alerted,
+    /// Already alerted for this budget period and threshold
+    Duplicate,
+    /// No `azure_subscriptions` row for the subscription id
+    Unmatched,
+}
+
+/// Read a budget notification from either payload schema
+pub fn parse_notification(payload: &serde_json::Value) -> Result<BudgetNotification, PayloadError> {
+    let schema = payload.get("schemaId").and_then(|v| v.as_str()).unwrap_or_default();
+    let data = payload.get("data").ok_or(PayloadError::UnknownSchema)?;
+
+    if schema == "azureMonitorCommonAlertSchema" {
+        let alert = data
+            .pointer("/alertContext/AlertData")
+            .ok_or(PayloadError::Field("alertContext.AlertData"))?;
+        let subscription_id = alert
+            .get("Scope")
+            .and_then(|v| v.as_str())
+            .and_then(subscription_from_scope)
+            .ok_or(PayloadError::Field("Scope"))?;
+
+        Ok(BudgetNotification {
+            subscription_id,
+            subscription_name: None,
+            budget_name: text(alert, "BudgetName").ok_or(PayloadError::Field("BudgetName"))?,
+            budget_amount: amount(alert, "BudgetThreshold"),
+            threshold_amount: amount(alert, "NotificationThresholdAmount")
+                .ok_or(PayloadError::Field("NotificationThresholdAmount"))?,
+            spent_amount: amount(alert, "SpentAmount").ok_or(PayloadError::Field("SpentAmount"))?,
+            currency: text(alert, "Unit"),
+        })
+    } else if schema.eq_ignore_ascii_case("AIP Budget Notification") {
+        Ok(BudgetNotification {
+            subscription_id: text(data, "SubscriptionId").ok_or(PayloadError::Field("SubscriptionId"))?,
+            subscription_name: text(data, "SubscriptionName"),
+            budget_name: text(data, "BudgetName").ok_or(PayloadError::Field("BudgetName"))?,
+            budget_amount: amount(data, "Budget"),
+            threshold_amount: amount(data, "NotificationThresholdAmount")
+                .ok_or(PayloadError::Field("NotificationThresholdAmount"))?,
+            spent_amount: amount(data, "SpendingAmount").ok_or(PayloadError::Field("SpendingAmount"))?,
+            currency: text(data, "Unit"),
+        })
+    } else {
+        Err(PayloadError::UnknownSchema)
+    }
+}
+
+fn text(object: &serde_json::Value, field: &str) -> Option<String> {
+    object
+        .get(field)
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Amounts arrive as strings, sometimes formatted ("$1,250.00"), or numbers
+fn amount(object: &serde_json::Value, field: &str) -> Option<Decimal> {
+    match object.get(field)? {
+        serde_json::Value::Number(n) => Decimal::from_str(&n.to_string()).ok(),
+        serde_json::Value::String(s) => {
+            let digits: String = s.chars().filter(|c| c.is_ascii_digit() || *c == '.' || *c == '-').collect();
+            Decimal::from_str(&digits).ok()
+        }
+        _ => None,
+    }
+}
+
+/// `/subscriptions/<id>/...` to `<id>`
+fn subscription_from_scope(scope: &str) -> Option<String> {
+    let mut parts = scope.split('/').filter(|p| !p.is_empty());
+    while let Some(part) = parts.next() {
+        if part.eq_ignore_ascii_case("subscriptions") {
+            return parts.next().map(str::to_string);
+        }
+    }
+    None
+}
+
+/// The budget period a notification received at `at` belongs to
+pub fn period_start(at: DateTime<Utc>) -> NaiveDate {
+    NaiveDate::from_ymd_opt(at.year(), at.month(), 1).expect("first of the month is a valid date")
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct LinkedSubscription {
+    id: Uuid,
+    client_id: Uuid,
+    subscription_name: String,
+    asset_id: Option<Uuid>,
+}
+
+/// The subscription's asset, creating and linking one if it has none
+async fn subscription_asset(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    subscription: &LinkedSubscription,
+    subscription_id: &str,
+) -> Result<Uuid, sqlx::Error> {
+    if let Some(asset_id) = subscription.asset_id {
+        return Ok(asset_id);
+    }
+
+    let asset_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO assets (client_id, name, asset_type, make, serial, status, created_at, updated_at)
+        VALUES ($1, $2, $3, 'Microsoft', $4, 'active', NOW(), NOW())
+        RETURNING id
+        "#
+    )
+    .bind(subscription.client_id)
+    .bind(&subscription.subscription_name)
+    .bind(ASSET_TYPE)
+    .bind(subscription_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    sqlx::query("UPDATE azure_subscriptions SET asset_id = $2, updated_at = NOW() WHERE id = $1")
+        .bind(subscription.id)
+        .bind(asset_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(asset_id)
+}
+
+/// Store a delivery. Unless it is already known to be a `duplicate`, this
+/// claims the period's dedup slot and returns `None` if it was taken.
+async fn record_payload(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    azure_subscription_id: Option<Uuid>,
+    notification: &BudgetNotification,
+    payload: &serde_json::Value,
+    received_at: DateTime<Utc>,
+    duplicate: bool,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let on_conflict = if duplicate {
+        ""
+    } else {
+        "ON CONFLICT (subscription_id, budget_name, period_start, threshold_amount) WHERE NOT duplicate DO NOTHING"
+    };
+
+    sqlx::query_scalar::<_, Uuid>(&format!(
+        r#"
+        INSERT INTO azure_cost_alerts (
+            azure_subscription_id, subscription_id, budget_name, period_start, threshold_amount,
+            spent_amount, budget_amount, currency, raw_payload, received_at, duplicate
+        )
+        VALUES ($1, LOWER($2), $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        {}
+        RETURNING id
+        "#,
+        on_conflict
+    ))
+    .bind(azure_subscription_id)
+    .bind(&notification.subscription_id)
+    .bind(&notification.budget_name)
+    .bind(period_start(received_at))
+    .bind(notification.threshold_amount.round_dp(2))
+    .bind(notification.spent_amount)
+    .bind(notification.budget_amount)
+    .bind(&notification.currency)
+    .bind(payload)
+    .bind(received_at)
+    .bind(duplicate)
+    .fetch_optional(&mut **tx)
+    .await
+}
+
+/// Record a budget notification and, the first time it's seen this period,
+/// open an alert and notify the client's technicians
+pub async fn ingest(
+    pool: &PgPool,
+    payload: &serde_json::Value,
+    notification: &BudgetNotification,
+    received_at: DateTime<Utc>,
+) -> Result<IngestOutcome, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let subscription = sqlx::query_as::<_, LinkedSubscription>(
+        "SELECT id, client_id, subscription_name, asset_id FROM azure_subscriptions
+         WHERE LOWER(subscription_id) = LOWER($1)"
+    )
+    .bind(&notification.subscription_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    // The first delivery for the period claims the dedup slot
+    let subscription_ref = subscription.as_ref().map(|s| s.id);
+    let claimed = record_payload(&mut tx, subscription_ref, notification, payload, received_at, false).await?;
+    let Some(claim_id) = claimed else {
+        record_payload(&mut tx, subscription_ref, notification, payload, received_at, true).await?;
+        tx.commit().await?;
+        return Ok(IngestOutcome::Duplicate);
+    };
+
+    let Some(subscription) = subscription else {
+        tx.commit().await?;
+        tracing::warn!("Azure budget alert for unknown subscription {}", notification.subscription_id);
+        return Ok(IngestOutcome::Unmatched);
+    };
+
+    let notification = BudgetNotification {
+        subscription_name: notification.subscription_name.clone().or_else(|| Some(subscription.subscription_name.clone())),
+        ..notification.clone()
+    };
+    let asset_id = subscription_asset(&mut tx, &subscription, &notification.subscription_id).await?;
+
+    let alert_id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO alerts (asset_id, alert_type, severity, title, message)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id"
+    )
+    .bind(asset_id)
+    .bind(ALERT_TYPE)
+    .bind(notification.severity())
+    .bind(notification.title())
+    .bind(notification.message())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE azure_cost_alerts SET alert_id = $2 WHERE id = $1")
+        .bind(claim_id)
+        .bind(alert_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    // Forecast notifications are kept as alerts without paging anyone. The
+    // alert is committed, so a failed notification is logged rather than
+    // failing the delivery Azure would then retry as a duplicate.
+    if notification.crossed_threshold() {
+        if let Err(e) = notify_technicians(pool, subscription.client_id, &notification, asset_id).await {
+            tracing::error!("Error notifying technicians of Azure budget alert {}: {}", alert_id, e);
+        }
+    }
+
+    Ok(IngestOutcome::Alerted)
+}
+
+async fn notify_technicians(
+    pool: &PgPool,
+    client_id: Uuid,
+    notification: &BudgetNotification,
+    asset_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let recipients = client_technicians(pool, client_id).await?;
+    create_notifications_for_users(
+        pool,
+        recipients,
+        notification.title(),
+        notification.message(),
+        ALERT_TYPE.to_string(),
+        Some("asset".to_string()),
+        Some(asset_id),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn legacy_payload() -> serde_json::Value {
+        json!({
+            "schemaId": "AIP Budget Notification",
+            "data": {
+                "SubscriptionName": "Contoso Production",
+                "SubscriptionId": "11111111-2222-3333-4444-555555555555",
+                "SpendingAmount": "850.12",
+                "BudgetStartDate": "1/1/2024 12:00:00 AM -08:00",
+                "Budget": "1000",
+                "Unit": "USD",
+                "BudgetName": "monthly-prod",
+                "BudgetType": "Cost",
+                "NotificationThresholdAmount": "800.0"
+            }
+        })
+    }
+
+    #[test]
+    fn test_parses_legacy_budget_payload() {
+        let notification = parse_notification(&legacy_payload()).unwrap();
+        assert_eq!(notification.subscription_id, "11111111-2222-3333-4444-555555555555");
+        assert_eq!(notification.subscription_name.as_deref(), Some("Contoso Production"));
+        assert_eq!(notification.budget_name, "monthly-prod");
+        assert_eq!(notification.budget_amount, Some(Decimal::new(1000, 0)));
+        assert_eq!(notification.threshold_amount, Decimal::new(800, 0));
+        assert_eq!(notification.spent_amount, Decimal::new(85012, 2));
+        assert_eq!(notification.currency.as_deref(), Some("USD"));
+        assert!(notification.crossed_threshold());
+        assert_eq!(notification.severity(), "high");
+    }
+
+    #[test]
+    fn test_parses_common_alert_schema_payload() {
+        let payload = json!({
+            "schemaId": "azureMonitorCommonAlertSchema",
+            "data": {
+                "essentials": { "monitoringService": "CostAlerts" },
+                "alertContext": {
+                    "AlertCategory": "budgets",
+                    "AlertData": {
+                        "Scope": "/subscriptions/aaaa-bbbb/",
+                        "ThresholdType": "Actual",
+                        "BudgetType": "Cost",
+                        "BudgetThreshold": "$1,000.00",
+                        "NotificationThresholdAmount": "$1,000.00",
+                        "BudgetName": "dev",
+                        "Unit": "USD",
+                        "SpentAmount": "$1,204.50"
+                    }
+                }
+            }
+        });
+
+        let notification = parse_notification(&payload).unwrap();
+        assert_eq!(notification.subscription_id, "aaaa-bbbb");
+        assert_eq!(notification.budget_amount, Some(Decimal::new(1000, 0)));
+        assert_eq!(notification.spent_amount, Decimal::new(120450, 2));
+        assert_eq!(notification.severity(), "critical");
+    }
+
+    #[test]
+    fn test_forecast_below_threshold_is_a_warning() {
+        let mut payload = legacy_payload();
+        payload["data"]["SpendingAmount"] = json!("640");
+        let notification = parse_notification(&payload).unwrap();
+        assert!(!notification.crossed_threshold());
+        assert_eq!(notification.severity(), "warning");
+    }
+
+    #[test]
+    fn test_rejects_unknown_or_incomplete_payloads() {
+        assert_eq!(parse_notification(&json!({ "schemaId": "Microsoft.Insights/activityLogs", "data": {} })), Err(PayloadError::UnknownSchema));
+
+        let mut payload = legacy_payload();
+        payload["data"].as_object_mut().unwrap().remove("BudgetName");
+        assert_eq!(parse_notification(&payload), Err(PayloadError::Field("BudgetName")));
+    }
+
+    #[test]
+    fn test_message_and_period() {
+        let notification = parse_notification(&legacy_payload()).unwrap();
+        assert_eq!(notification.message(), "Spend is 850.12 USD against a 800.0 USD threshold of a 1000 USD budget");
+
+        let at = DateTime::parse_from_rfc3339("2024-03-17T08:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(period_start(at), NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+    }
+}
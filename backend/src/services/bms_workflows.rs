@@ -1,4 +1,4 @@
-use crate::services::EmailService;
+use crate::services::{invoice_numbering, EmailService};
 use chrono::{DateTime, Utc, NaiveDate, Datelike};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -270,8 +270,10 @@ impl BmsWorkflowService {
         client_data: &ClientBillingData,
     ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
         let invoice_id = Uuid::new_v4();
-        let invoice_number = self.generate_invoice_number().await?;
-        let due_date = Utc::now().date_naive() + chrono::Duration::days(self.config.payment_terms_days as i64);
+        let today = Utc::now().date_naive();
+        let due_date = today + chrono::Duration::days(self.config.payment_terms_days as i64);
+        let mut tx = self.db_pool.begin().await?;
+        let invoice_number = invoice_numbering::next_invoice_number(&mut tx, today).await?;
 
         // Create invoice
         sqlx::query!(
@@ -291,7 +293,7 @@ impl BmsWorkflowService {
             client_data.total_amount,
             format!("Invoice for services rendered - {} hours", client_data.total_hours)
         )
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
         .await?;
 
         // Create invoice line items
@@ -322,7 +324,7 @@ impl BmsWorkflowService {
                 time_entry.rate,
                 time_entry.amount
             )
-            .execute(&self.db_pool)
+            .execute(&mut *tx)
             .await?;
         }
 
@@ -332,7 +334,7 @@ impl BmsWorkflowService {
             "UPDATE time_entries SET billed = true WHERE id = ANY($1)",
             &time_entry_ids
         )
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
         .await?;
 
         // Set invoice to 'sent' status
@@ -340,21 +342,12 @@ impl BmsWorkflowService {
             "UPDATE invoices SET status = 'sent' WHERE id = $1",
             invoice_id
         )
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(invoice_id)
-    }
-
-    async fn generate_invoice_number(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let result = sqlx::query!(
-            "SELECT COALESCE(MAX(CAST(SUBSTRING(invoice_number FROM '^INV-(\\d+)$') AS INTEGER)), 0) + 1 as next_number FROM invoices WHERE invoice_number ~ '^INV-\\d+$'"
-        )
-        .fetch_one(&self.db_pool)
-        .await?;
+        tx.commit().await?;
 
-        let next_number = result.next_number.unwrap_or(1);
-        Ok(format!("INV-{:06}", next_number))
+        Ok(invoice_id)
     }
 
     async fn send_invoice_email(
@@ -0,0 +1,341 @@
+//! Business hours and SLA due times
+//!
+//! A client's business calendar is its weekly schedule (its own rows in
+//! `business_hours`, or the global rows when it has none), its own and the
+//! global holidays, and the timezone of its primary location. SLAs with
+//! `business_hours_only` set only count time inside that calendar, so a
+//! ticket opened on Friday evening is due on Monday rather than Saturday.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use resolve_shared::{BusinessHours, Sla};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How far ahead to look for business time before giving up and treating
+/// the calendar as round-the-clock
+const MAX_SEARCH_DAYS: i64 = 3 * 366;
+
+#[derive(Debug, Clone)]
+pub struct BusinessCalendar {
+    timezone: Tz,
+    /// Open windows per weekday, indexed from Sunday
+    windows: [Vec<(NaiveTime, NaiveTime)>; 7],
+    holidays: HashSet<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SlaDueDates {
+    pub response_due: DateTime<Utc>,
+    pub resolution_due: DateTime<Utc>,
+}
+
+/// Parse an IANA timezone name, falling back to UTC
+pub fn parse_timezone(name: &str) -> Tz {
+    name.trim().parse::<Tz>().unwrap_or_else(|_| {
+        tracing::warn!("Unknown timezone '{}', using UTC", name);
+        Tz::UTC
+    })
+}
+
+impl BusinessCalendar {
+    pub fn new(timezone: Tz, hours: &[BusinessHours], holidays: impl IntoIterator<Item = NaiveDate>) -> Self {
+        let mut windows: [Vec<(NaiveTime, NaiveTime)>; 7] = Default::default();
+        for h in hours {
+            if (0..7).contains(&h.day_of_week) && h.end_time > h.start_time {
+                windows[h.day_of_week as usize].push((h.start_time, h.end_time));
+            }
+        }
+        for day in windows.iter_mut() {
+            day.sort();
+        }
+        Self { timezone, windows, holidays: holidays.into_iter().collect() }
+    }
+
+    fn is_open_ever(&self) -> bool {
+        self.windows.iter().any(|w| !w.is_empty())
+    }
+
+    /// `date` and `time` in the calendar's timezone as UTC. Times skipped by
+    /// a DST change move forward to the first valid instant.
+    fn instant(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        let naive = date.and_time(time);
+        let local = self
+            .timezone
+            .from_local_datetime(&naive)
+            .earliest()
+            .or_else(|| self.timezone.from_local_datetime(&(naive + Duration::hours(1))).earliest())
+            .unwrap_or_else(|| self.timezone.from_utc_datetime(&naive));
+        local.with_timezone(&Utc)
+    }
+
+    /// `start` plus `minutes` of business time. An empty schedule counts
+    /// every minute.
+    pub fn add_business_minutes(&self, start: DateTime<Utc>, minutes: i64) -> DateTime<Utc> {
+        if minutes <= 0 || !self.is_open_ever() {
+            return start + Duration::minutes(minutes.max(0));
+        }
+
+        let mut remaining = Duration::minutes(minutes);
+        let mut date = start.with_timezone(&self.timezone).date_naive();
+
+        for _ in 0..MAX_SEARCH_DAYS {
+            if !self.holidays.contains(&date) {
+                let weekday = date.weekday().num_days_from_sunday() as usize;
+                for &(open, close) in &self.windows[weekday] {
+                    let window_end = self.instant(date, close);
+                    let from = self.instant(date, open).max(start);
+                    if from >= window_end {
+                        continue;
+                    }
+                    let available = window_end - from;
+                    if remaining <= available {
+                        return from + remaining;
+                    }
+                    remaining -= available;
+                }
+            }
+            date = match date.succ_opt() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+
+        tracing::warn!("No business time found within {} days; counting all hours", MAX_SEARCH_DAYS);
+        start + Duration::minutes(minutes)
+    }
+}
+
+/// Due times `response_minutes` and `resolution_minutes` after `opened_at`,
+/// counted in `calendar` when `business_hours_only` is set
+pub fn due_after(
+    calendar: &BusinessCalendar,
+    opened_at: DateTime<Utc>,
+    response_minutes: i64,
+    resolution_minutes: i64,
+    business_hours_only: bool,
+) -> SlaDueDates {
+    if business_hours_only {
+        SlaDueDates {
+            response_due: calendar.add_business_minutes(opened_at, response_minutes),
+            resolution_due: calendar.add_business_minutes(opened_at, resolution_minutes),
+        }
+    } else {
+        SlaDueDates {
+            response_due: opened_at + Duration::minutes(response_minutes),
+            resolution_due: opened_at + Duration::minutes(resolution_minutes),
+        }
+    }
+}
+
+/// Response and resolution due times for `sla`, counting business hours
+/// only when the SLA asks for it
+pub fn due_dates(sla: &Sla, calendar: &BusinessCalendar, opened_at: DateTime<Utc>) -> SlaDueDates {
+    due_after(
+        calendar,
+        opened_at,
+        sla.response_time_minutes as i64,
+        sla.resolution_time_hours as i64 * 60,
+        sla.business_hours_only,
+    )
+}
+
+/// The schedule a client works to: its own rows, or the global ones
+pub async fn schedule_for(pool: &PgPool, client_id: Option<Uuid>) -> Result<Vec<BusinessHours>, sqlx::Error> {
+    sqlx::query_as::<_, BusinessHours>(
+        r#"
+        SELECT id, client_id, day_of_week, start_time, end_time, timezone, created_at
+        FROM business_hours
+        WHERE client_id IS NOT DISTINCT FROM $1
+           OR (client_id IS NULL AND NOT EXISTS (SELECT 1 FROM business_hours WHERE client_id = $1))
+        ORDER BY day_of_week, start_time
+        "#
+    )
+    .bind(client_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Timezone of the client's primary location, or its first location
+pub async fn client_timezone(pool: &PgPool, client_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT timezone FROM locations
+        WHERE client_id = $1 AND timezone IS NOT NULL AND timezone <> ''
+        ORDER BY COALESCE(is_primary, false) DESC, created_at ASC
+        LIMIT 1
+        "#
+    )
+    .bind(client_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Load the business calendar for a client, or the global calendar, with
+/// holidays from `from` onwards
+pub async fn load_calendar(pool: &PgPool, client_id: Option<Uuid>, from: NaiveDate) -> Result<BusinessCalendar, sqlx::Error> {
+    let hours = schedule_for(pool, client_id).await?;
+
+    let holidays = sqlx::query_scalar::<_, NaiveDate>(
+        "SELECT holiday_date FROM business_holidays WHERE (client_id IS NULL OR client_id = $1) AND holiday_date >= $2"
+    )
+    .bind(client_id)
+    .bind(from)
+    .fetch_all(pool)
+    .await?;
+
+    // The client's location decides the timezone; a schedule's own zone is
+    // only used when the client has no location with one
+    let location_tz = match client_id {
+        Some(id) => client_timezone(pool, id).await?,
+        None => None,
+    };
+    let timezone = location_tz
+        .or_else(|| hours.first().map(|h| h.timezone.clone()))
+        .map(|tz| parse_timezone(&tz))
+        .unwrap_or(Tz::UTC);
+
+    Ok(BusinessCalendar::new(timezone, &hours, holidays))
+}
+
+/// The SLA covering a client's tickets of `priority`, from its newest
+/// active contract
+pub async fn find_sla(pool: &PgPool, client_id: Uuid, priority: &str) -> Result<Option<Sla>, sqlx::Error> {
+    sqlx::query_as::<_, Sla>(
+        r#"
+        SELECT s.id, s.contract_id, s.name, s.priority, s.response_time_minutes, s.resolution_time_hours,
+               COALESCE(s.business_hours_only, true) as business_hours_only, s.description,
+               COALESCE(s.created_at, NOW()) as created_at
+        FROM slas s
+        JOIN contracts c ON c.id = s.contract_id
+        WHERE c.client_id = $1 AND s.priority = $2
+          AND COALESCE(c.status, 'active') = 'active'
+          AND c.start_date <= CURRENT_DATE AND (c.end_date IS NULL OR c.end_date >= CURRENT_DATE)
+        ORDER BY c.start_date DESC
+        LIMIT 1
+        "#
+    )
+    .bind(client_id)
+    .bind(priority)
+    .fetch_optional(pool)
+    .await
+}
+
+/// The calendar SLA time is counted in for a client's ticket opened at
+/// `opened_at`
+pub async fn calendar_for_ticket(pool: &PgPool, client_id: Uuid, opened_at: DateTime<Utc>) -> Result<BusinessCalendar, sqlx::Error> {
+    load_calendar(pool, Some(client_id), opened_at.date_naive() - Duration::days(1)).await
+}
+
+/// Due times for a ticket opened at `opened_at` under `sla`, in the client's
+/// business calendar
+pub async fn sla_due_dates(pool: &PgPool, client_id: Uuid, sla: &Sla, opened_at: DateTime<Utc>) -> Result<SlaDueDates, sqlx::Error> {
+    let calendar = if sla.business_hours_only {
+        calendar_for_ticket(pool, client_id, opened_at).await?
+    } else {
+        BusinessCalendar::new(Tz::UTC, &[], [])
+    };
+    Ok(due_dates(sla, &calendar, opened_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(day: i32, start: (u32, u32), end: (u32, u32)) -> BusinessHours {
+        BusinessHours {
+            id: Uuid::new_v4(),
+            client_id: None,
+            day_of_week: day,
+            start_time: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+            timezone: "UTC".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn weekdays_9_to_5(tz: Tz, holidays: Vec<NaiveDate>) -> BusinessCalendar {
+        let schedule: Vec<_> = (1..=5).map(|d| hours(d, (9, 0), (17, 0))).collect();
+        BusinessCalendar::new(tz, &schedule, holidays)
+    }
+
+    fn sla(response_minutes: i32, resolution_hours: i32, business_hours_only: bool) -> Sla {
+        Sla {
+            id: Uuid::new_v4(),
+            contract_id: None,
+            name: "Standard".to_string(),
+            priority: "high".to_string(),
+            response_time_minutes: response_minutes,
+            resolution_time_hours: resolution_hours,
+            business_hours_only,
+            description: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_friday_evening_ticket_skips_weekend() {
+        let calendar = weekdays_9_to_5(Tz::UTC, vec![]);
+        // Friday 2024-03-15 18:30, after close
+        let opened = utc(2024, 3, 15, 18, 30);
+
+        let due = due_dates(&sla(60, 8, true), &calendar, opened);
+        assert_eq!(due.response_due, utc(2024, 3, 18, 10, 0));
+        assert_eq!(due.resolution_due, utc(2024, 3, 18, 17, 0));
+
+        let round_the_clock = due_dates(&sla(60, 8, false), &calendar, opened);
+        assert_eq!(round_the_clock.response_due, utc(2024, 3, 15, 19, 30));
+        assert_eq!(round_the_clock.resolution_due, utc(2024, 3, 16, 2, 30));
+    }
+
+    #[test]
+    fn test_time_carries_over_into_next_business_day() {
+        let calendar = weekdays_9_to_5(Tz::UTC, vec![]);
+        // Friday 16:00 with four hours to go: one on Friday, three on Monday
+        assert_eq!(calendar.add_business_minutes(utc(2024, 3, 15, 16, 0), 240), utc(2024, 3, 18, 12, 0));
+        // Inside a window the clock runs normally
+        assert_eq!(calendar.add_business_minutes(utc(2024, 3, 13, 10, 15), 30), utc(2024, 3, 13, 10, 45));
+    }
+
+    #[test]
+    fn test_holidays_are_skipped() {
+        let monday = NaiveDate::from_ymd_opt(2024, 3, 18).unwrap();
+        let calendar = weekdays_9_to_5(Tz::UTC, vec![monday]);
+        assert_eq!(calendar.add_business_minutes(utc(2024, 3, 15, 18, 30), 60), utc(2024, 3, 19, 10, 0));
+    }
+
+    #[test]
+    fn test_schedule_is_in_local_time() {
+        let calendar = weekdays_9_to_5(chrono_tz::America::New_York, vec![]);
+        // Friday 2024-03-15 17:30 in New York is 21:30 UTC (EDT, UTC-4);
+        // Monday 09:00 there is 13:00 UTC
+        assert_eq!(calendar.add_business_minutes(utc(2024, 3, 15, 21, 30), 30), utc(2024, 3, 18, 13, 30));
+    }
+
+    #[test]
+    fn test_split_windows_and_empty_schedule() {
+        let calendar = BusinessCalendar::new(
+            Tz::UTC,
+            &[hours(3, (13, 0), (17, 0)), hours(3, (8, 0), (12, 0))],
+            [],
+        );
+        // Wednesday 11:30 with an hour to go: 30 minutes before lunch, 30 after
+        assert_eq!(calendar.add_business_minutes(utc(2024, 3, 13, 11, 30), 60), utc(2024, 3, 13, 13, 30));
+
+        let always_open = BusinessCalendar::new(Tz::UTC, &[], []);
+        assert_eq!(always_open.add_business_minutes(utc(2024, 3, 16, 3, 0), 90), utc(2024, 3, 16, 4, 30));
+    }
+
+    #[test]
+    fn test_parse_timezone_falls_back_to_utc() {
+        assert_eq!(parse_timezone("Europe/London"), chrono_tz::Europe::London);
+        assert_eq!(parse_timezone("Mars/Olympus"), Tz::UTC);
+    }
+}
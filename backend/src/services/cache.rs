@@ -201,6 +201,11 @@ pub mod cache_keys {
         format!("analytics:profitability:{}:{}", start, end)
     }
 
+    /// Rendered invoice PDF, versioned by the invoice's last modification time
+    pub fn invoice_pdf(id: Uuid, version: i64) -> String {
+        format!("invoice:{}:pdf:{}", id, version)
+    }
+
     /// Pattern to invalidate all client-related caches
     pub fn client_pattern(id: Uuid) -> String {
         format!("client:{}%", id)
@@ -211,6 +216,11 @@ pub mod cache_keys {
         format!("ticket:{}%", id)
     }
 
+    /// Pattern to invalidate all invoice-related caches
+    pub fn invoice_pattern(id: Uuid) -> String {
+        format!("invoice:{}%", id)
+    }
+
     /// Pattern to invalidate all analytics caches
     pub fn analytics_pattern() -> String {
         "analytics:%".to_string()
@@ -0,0 +1,160 @@
+//! Canned response rendering
+//!
+//! Substitutes `{{placeholder}}` variables in canned response text with
+//! ticket context. Placeholders without a value are left intact and reported
+//! so the UI can warn before the response is sent.
+
+use regex::{Captures, Regex};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::OnceLock;
+
+fn placeholder_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z0-9_.]+)\s*\}\}").unwrap())
+}
+
+/// Variable values available to a template, keyed by dotted name (e.g. `ticket.number`)
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVariables {
+    values: HashMap<String, String>,
+}
+
+impl TemplateVariables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a variable; `None` or blank values are treated as missing
+    pub fn set(&mut self, name: &str, value: Option<impl Into<String>>) -> &mut Self {
+        if let Some(value) = value.map(Into::into).filter(|v| !v.trim().is_empty()) {
+            self.values.insert(name.to_string(), value);
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+}
+
+/// Output of rendering one template
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedText {
+    pub text: String,
+    /// Placeholders that had no value, in order of first appearance
+    pub unresolved: Vec<String>,
+}
+
+/// Render `template`, escaping substituted values when `html` is set
+pub fn render(template: &str, variables: &TemplateVariables, html: bool) -> RenderedText {
+    render_placeholders(template, variables, html, true)
+}
+
+/// Like [`render`], but placeholders without a value render as nothing.
+/// For text that goes straight out (emails) rather than back to an editor.
+pub fn render_or_blank(template: &str, variables: &TemplateVariables, html: bool) -> RenderedText {
+    render_placeholders(template, variables, html, false)
+}
+
+fn render_placeholders(template: &str, variables: &TemplateVariables, html: bool, keep_missing: bool) -> RenderedText {
+    let mut unresolved = Vec::new();
+
+    let text = placeholder_regex()
+        .replace_all(template, |caps: &Captures| {
+            let name = &caps[1];
+            match variables.get(name) {
+                Some(value) if html => escape_html(value),
+                Some(value) => value.to_string(),
+                None => {
+                    if !unresolved.iter().any(|u| u == name) {
+                        unresolved.push(name.to_string());
+                    }
+                    if keep_missing { caps[0].to_string() } else { String::new() }
+                }
+            }
+        })
+        .into_owned();
+
+    RenderedText { text, unresolved }
+}
+
+/// Merge unresolved placeholder lists from several rendered fields
+pub fn merge_unresolved<'a>(parts: impl IntoIterator<Item = &'a RenderedText>) -> Vec<String> {
+    parts
+        .into_iter()
+        .flat_map(|p| p.unresolved.iter().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> TemplateVariables {
+        let mut vars = TemplateVariables::new();
+        vars.set("ticket.number", Some("1042"))
+            .set("client.name", Some("Acme & Sons"))
+            .set("contact.name", Some("Jane Smith"))
+            .set("agent.name", Some("Tess Tech"));
+        vars
+    }
+
+    #[test]
+    fn test_substitutes_known_variables() {
+        let rendered = render(
+            "Hi {{contact.name}}, ticket #{{ ticket.number }} for {{client.name}} is with {{agent.name}}.",
+            &variables(),
+            false,
+        );
+
+        assert_eq!(rendered.text, "Hi Jane Smith, ticket #1042 for Acme & Sons is with Tess Tech.");
+        assert!(rendered.unresolved.is_empty());
+    }
+
+    #[test]
+    fn test_missing_and_unknown_placeholders_are_left_intact() {
+        let mut vars = variables();
+        // A ticket without a contact
+        vars.set("contact.email", None::<String>);
+
+        let rendered = render(
+            "Reply to {{contact.email}} about {{ticket.number}}. {{foo.bar}} {{contact.email}}",
+            &vars,
+            false,
+        );
+
+        assert_eq!(rendered.text, "Reply to {{contact.email}} about 1042. {{foo.bar}} {{contact.email}}");
+        assert_eq!(rendered.unresolved, vec!["contact.email", "foo.bar"]);
+    }
+
+    #[test]
+    fn test_html_values_are_escaped() {
+        let rendered = render("<p>{{client.name}}</p>", &variables(), true);
+        assert_eq!(rendered.text, "<p>Acme &amp; Sons</p>");
+    }
+
+    #[test]
+    fn test_render_or_blank_drops_missing_placeholders() {
+        let rendered = render_or_blank("Hi {{contact.name}}{{contact.title}}!", &variables(), false);
+        assert_eq!(rendered.text, "Hi Jane Smith!");
+        assert_eq!(rendered.unresolved, vec!["contact.title"]);
+    }
+
+    #[test]
+    fn test_merge_unresolved_dedupes() {
+        let a = render("{{x.one}} {{ticket.number}}", &variables(), false);
+        let b = render("{{x.one}} {{x.two}}", &variables(), false);
+        assert_eq!(merge_unresolved([&a, &b]), vec!["x.one", "x.two"]);
+    }
+}
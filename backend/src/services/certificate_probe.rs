@@ -0,0 +1,429 @@
+//! Live SSL certificate checks
+//!
+//! Connects to the host a certificate record describes, reads the leaf
+//! certificate it presents and compares it with what was entered by hand.
+//! The handshake deliberately accepts any certificate, since expired and
+//! self-signed certificates are exactly the ones worth recording.
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tracing::warn;
+use uuid::Uuid;
+use x509_parser::extensions::GeneralName;
+
+use crate::services::public_address::{self, AddressError};
+use resolve_shared::SslCertificate;
+
+pub const DEFAULT_PORT: u16 = 443;
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProbeError {
+    #[error("Invalid host name: {0}")]
+    InvalidHost(String),
+    #[error("Timed out connecting to {0}")]
+    Timeout(String),
+    #[error("Connection failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Host presented no certificate")]
+    NoCertificate,
+    #[error("Could not parse certificate: {0}")]
+    Parse(String),
+    #[error(transparent)]
+    Address(#[from] AddressError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeOptions {
+    pub port: u16,
+    pub timeout: Duration,
+    /// Refuse hosts that resolve to a private, loopback or link-local
+    /// address; set when the host comes from the request
+    pub public_only: bool,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECONDS),
+            public_only: false,
+        }
+    }
+}
+
+/// Fields read from the presented leaf certificate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiveCertificate {
+    pub common_name: Option<String>,
+    pub subject_alt_names: Vec<String>,
+    /// Issuer organisation, falling back to its common name
+    pub issuer: String,
+    pub issued_date: NaiveDate,
+    pub expiry_date: NaiveDate,
+    pub serial_number: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CertificateMismatch {
+    pub field: String,
+    pub stored: String,
+    pub live: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CertificateCheck {
+    pub certificate_id: Uuid,
+    pub host: String,
+    pub port: u16,
+    pub live: LiveCertificate,
+    pub mismatches: Vec<CertificateMismatch>,
+}
+
+struct AcceptAnyCertificate;
+
+impl rustls::client::ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
+
+/// Parse a DER-encoded certificate
+pub fn parse_certificate(der: &[u8]) -> Result<LiveCertificate, ProbeError> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).map_err(|e| ProbeError::Parse(e.to_string()))?;
+
+    let date = |timestamp: i64| {
+        Utc.timestamp_opt(timestamp, 0)
+            .single()
+            .map(|dt| dt.date_naive())
+            .ok_or_else(|| ProbeError::Parse("Validity date out of range".to_string()))
+    };
+
+    let common_name = cert
+        .subject()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(String::from);
+
+    let issuer = cert
+        .issuer()
+        .iter_organization()
+        .next()
+        .or_else(|| cert.issuer().iter_common_name().next())
+        .and_then(|attr| attr.as_str().ok())
+        .map(String::from)
+        .unwrap_or_else(|| cert.issuer().to_string());
+
+    let subject_alt_names = cert
+        .subject_alternative_name()
+        .map_err(|e| ProbeError::Parse(e.to_string()))?
+        .map(|san| {
+            san.value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    GeneralName::IPAddress(bytes) => match bytes.len() {
+                        4 => Some(std::net::Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()),
+                        16 => <[u8; 16]>::try_from(*bytes).ok().map(|b| std::net::Ipv6Addr::from(b).to_string()),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(LiveCertificate {
+        common_name,
+        subject_alt_names,
+        issuer,
+        issued_date: date(cert.validity().not_before.timestamp())?,
+        expiry_date: date(cert.validity().not_after.timestamp())?,
+        serial_number: cert.raw_serial_as_string(),
+    })
+}
+
+/// Connect to `host` and read the leaf certificate it presents
+pub async fn fetch_certificate(host: &str, options: &ProbeOptions) -> Result<LiveCertificate, ProbeError> {
+    let server_name = rustls::ServerName::try_from(host).map_err(|_| ProbeError::InvalidHost(host.to_string()))?;
+    let address = format!("{}:{}", host, options.port);
+
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+
+    let handshake = async {
+        let tcp = if options.public_only {
+            // Connect to the addresses that were checked, not a fresh lookup
+            let addrs = public_address::resolve_public(host, options.port).await?;
+            TcpStream::connect(&addrs[..]).await?
+        } else {
+            TcpStream::connect(&address).await?
+        };
+        Ok::<_, ProbeError>(connector.connect(server_name, tcp).await?)
+    };
+
+    let tls = tokio::time::timeout(options.timeout, handshake)
+        .await
+        .map_err(|_| ProbeError::Timeout(address.clone()))??;
+
+    let (_, connection) = tls.get_ref();
+    let leaf = connection
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .ok_or(ProbeError::NoCertificate)?;
+
+    parse_certificate(&leaf.0)
+}
+
+/// The host to connect to for a certificate record. Wildcard names are
+/// checked against their base domain.
+pub fn probe_host(common_name: &str) -> &str {
+    let host = common_name.trim();
+    host.strip_prefix("*.").unwrap_or(host)
+}
+
+/// Differences between a stored record and the live certificate. SANs are
+/// only compared when the record lists some.
+pub fn compare(stored: &SslCertificate, live: &LiveCertificate) -> Vec<CertificateMismatch> {
+    let mut mismatches = Vec::new();
+    let mut check = |field: &str, stored: String, live: String, differs: bool| {
+        if differs {
+            mismatches.push(CertificateMismatch { field: field.to_string(), stored, live });
+        }
+    };
+
+    check(
+        "expiry_date",
+        stored.expiry_date.to_string(),
+        live.expiry_date.to_string(),
+        stored.expiry_date != live.expiry_date,
+    );
+    check(
+        "issued_date",
+        stored.issued_date.to_string(),
+        live.issued_date.to_string(),
+        stored.issued_date != live.issued_date,
+    );
+    check(
+        "issuer",
+        stored.issuer.clone(),
+        live.issuer.clone(),
+        !stored.issuer.trim().eq_ignore_ascii_case(live.issuer.trim()),
+    );
+
+    if !stored.subject_alt_names.is_empty() {
+        let normalise = |names: &[String]| names.iter().map(|n| n.trim().to_lowercase()).collect::<BTreeSet<_>>();
+        let (stored_sans, live_sans) = (normalise(&stored.subject_alt_names), normalise(&live.subject_alt_names));
+        check(
+            "subject_alt_names",
+            stored_sans.iter().cloned().collect::<Vec<_>>().join(", "),
+            live_sans.iter().cloned().collect::<Vec<_>>().join(", "),
+            stored_sans != live_sans,
+        );
+    }
+
+    mismatches
+}
+
+/// Check a certificate record against its live host, raise an alert for
+/// each field that drifted, then update the record from the live data
+pub async fn check_certificate(
+    pool: &PgPool,
+    stored: &SslCertificate,
+    host: &str,
+    options: &ProbeOptions,
+) -> Result<CertificateCheck, ProbeError> {
+    let live = fetch_certificate(host, options).await?;
+    let mismatches = compare(stored, &live);
+
+    let persisted = async {
+        let mut tx = pool.begin().await?;
+
+        for mismatch in &mismatches {
+            sqlx::query(
+                r#"
+                INSERT INTO ssl_certificate_alerts (certificate_id, field, stored_value, live_value, host, port)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#
+            )
+            .bind(stored.id)
+            .bind(&mismatch.field)
+            .bind(&mismatch.stored)
+            .bind(&mismatch.live)
+            .bind(host)
+            .bind(options.port as i32)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE ssl_certificates SET
+                issued_date = $2, expiry_date = $3, issuer = $4, subject_alt_names = $5,
+                last_live_check_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            "#
+        )
+        .bind(stored.id)
+        .bind(live.issued_date)
+        .bind(live.expiry_date)
+        .bind(&live.issuer)
+        .bind(&live.subject_alt_names)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    };
+
+    persisted.await?;
+
+    if !mismatches.is_empty() {
+        warn!(
+            "Certificate '{}' differs from {}:{} in {} field(s)",
+            stored.name,
+            host,
+            options.port,
+            mismatches.len()
+        );
+    }
+
+    Ok(CertificateCheck {
+        certificate_id: stored.id,
+        host: host.to_string(),
+        port: options.port,
+        live,
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{date_time_ymd, CertificateParams, DistinguishedName, DnType, SanType};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    fn self_signed() -> rcgen::Certificate {
+        let mut params = CertificateParams::new(vec!["localhost".to_string(), "portal.localhost".to_string()]);
+        params.subject_alt_names.push(SanType::IpAddress("127.0.0.1".parse().unwrap()));
+        params.not_before = date_time_ymd(2024, 1, 15);
+        params.not_after = date_time_ymd(2025, 1, 14);
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, "localhost");
+        name.push(DnType::OrganizationName, "Resolve Test CA");
+        params.distinguished_name = name;
+        rcgen::Certificate::from_params(params).unwrap()
+    }
+
+    /// Serve `cert` over TLS on a random local port, for a single connection
+    async fn tls_server(cert: &rcgen::Certificate) -> u16 {
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(cert.serialize_der().unwrap())],
+                rustls::PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _tls = acceptor.accept(stream).await;
+        });
+
+        port
+    }
+
+    fn stored(live: &LiveCertificate) -> SslCertificate {
+        SslCertificate {
+            id: Uuid::new_v4(),
+            domain_id: None,
+            client_id: Uuid::new_v4(),
+            name: "Portal".to_string(),
+            common_name: "localhost".to_string(),
+            subject_alt_names: live.subject_alt_names.clone(),
+            issuer: live.issuer.clone(),
+            issued_date: live.issued_date,
+            expiry_date: live.expiry_date,
+            certificate_chain: None,
+            private_key: None,
+            auto_renew: false,
+            status: "active".to_string(),
+            created_at: Utc::now(),
+            updated_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reads_certificate_from_live_host() {
+        let port = tls_server(&self_signed()).await;
+        let options = ProbeOptions { port, timeout: Duration::from_secs(5), public_only: false };
+
+        let live = fetch_certificate("localhost", &options).await.unwrap();
+
+        assert_eq!(live.common_name.as_deref(), Some("localhost"));
+        assert_eq!(live.subject_alt_names, vec!["localhost", "portal.localhost", "127.0.0.1"]);
+        assert_eq!(live.issuer, "Resolve Test CA");
+        assert_eq!(live.issued_date, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+        assert_eq!(live.expiry_date, NaiveDate::from_ymd_opt(2025, 1, 14).unwrap());
+        assert!(!live.serial_number.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_host_times_out_or_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let options = ProbeOptions { port, timeout: Duration::from_secs(2), public_only: false };
+        assert!(fetch_certificate("127.0.0.1", &options).await.is_err());
+    }
+
+    #[test]
+    fn test_compare_flags_drifted_fields() {
+        let live = parse_certificate(&self_signed().serialize_der().unwrap()).unwrap();
+        let mut record = stored(&live);
+        assert!(compare(&record, &live).is_empty());
+
+        record.expiry_date = NaiveDate::from_ymd_opt(2024, 12, 31).unwrap();
+        record.issuer = "resolve test ca".to_string();
+        record.subject_alt_names = vec!["localhost".to_string()];
+
+        let fields: Vec<String> = compare(&record, &live).into_iter().map(|m| m.field).collect();
+        assert_eq!(fields, vec!["expiry_date", "subject_alt_names"]);
+
+        // Records without SANs aren't flagged for them
+        record.subject_alt_names.clear();
+        assert_eq!(compare(&record, &live).len(), 1);
+    }
+
+    #[test]
+    fn test_probe_host_strips_wildcard() {
+        assert_eq!(probe_host("*.acme.com"), "acme.com");
+        assert_eq!(probe_host(" mail.acme.com "), "mail.acme.com");
+    }
+}
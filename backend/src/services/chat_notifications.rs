@@ -0,0 +1,739 @@
+//! Chat notifications
+//!
+//! Posts ticket and operations events to Microsoft Teams (an incoming
+//! webhook carrying an Adaptive Card) and Slack (an incoming webhook
+//! carrying Block Kit blocks). Each `chat_notification_channels` row routes
+//! the event types it lists, optionally only for some queues, to one
+//! destination. The destination's webhook URL lives encrypted in the
+//! credentials of the `integrations` row the channel references, as
+//! `{"webhook_url": "..."}`.
+//!
+//! Messages to a channel are spaced at least [`MIN_SEND_INTERVAL`] apart,
+//! and transient failures are retried with backoff, waiting out any
+//! `Retry-After` the provider sends with a 429.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::integrations::decrypt_json;
+use crate::middleware::request_id::{self, with_request_id};
+use crate::services::email_templates::portal_link;
+use crate::services::outbound_webhooks::{http_client, RetryPolicy};
+use crate::services::teams_integration::{
+    TeamsAdaptiveCard, TeamsCardAction, TeamsCardElement, TeamsFact, TeamsWebhookPayload,
+};
+use crate::workflows::webhook::{backoff_delay, is_retryable_status, response_snippet};
+
+pub const HIGH_PRIORITY_TICKET: &str = "ticket.high_priority";
+pub const SLA_BREACH: &str = "sla.breach";
+pub const JOB_FAILED: &str = "job.failed";
+
+pub const EVENT_TYPES: &[&str] = &[HIGH_PRIORITY_TICKET, SLA_BREACH, JOB_FAILED];
+pub const PROVIDERS: &[&str] = &["teams", "slack"];
+
+/// Ticket priorities announced on creation
+pub const HIGH_PRIORITIES: &[&str] = &["high", "critical", "urgent"];
+
+/// Minimum gap between two messages to the same channel. Slack allows about
+/// one message a second per incoming webhook; Teams a little more.
+pub const MIN_SEND_INTERVAL: Duration = Duration::from_millis(1100);
+
+/// Longest `Retry-After` honoured before giving up on the attempt's backoff
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChatEvent {
+    HighPriorityTicket {
+        ticket_id: Uuid,
+        number: i32,
+        subject: String,
+        client_name: String,
+        priority: String,
+        queue_id: Option<Uuid>,
+    },
+    SlaBreach {
+        ticket_id: Uuid,
+        number: i32,
+        subject: String,
+        client_name: String,
+        priority: String,
+        breach_type: String,
+        minutes_over: i32,
+        queue_id: Option<Uuid>,
+    },
+    JobFailed {
+        job_name: String,
+        error: String,
+    },
+    /// Sent by the test endpoint to check a channel is wired up
+    Test {
+        channel_name: String,
+    },
+}
+
+impl ChatEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            Self::HighPriorityTicket { .. } => HIGH_PRIORITY_TICKET,
+            Self::SlaBreach { .. } => SLA_BREACH,
+            Self::JobFailed { .. } => JOB_FAILED,
+            Self::Test { .. } => "test",
+        }
+    }
+
+    fn ticket_id(&self) -> Option<Uuid> {
+        match self {
+            Self::HighPriorityTicket { ticket_id, .. } | Self::SlaBreach { ticket_id, .. } => Some(*ticket_id),
+            _ => None,
+        }
+    }
+
+    fn queue_id(&self) -> Option<Uuid> {
+        match self {
+            Self::HighPriorityTicket { queue_id, .. } | Self::SlaBreach { queue_id, .. } => *queue_id,
+            _ => None,
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Self::HighPriorityTicket { number, priority, .. } => {
+                format!("New {} priority ticket #{}", priority.to_uppercase(), number)
+            }
+            Self::SlaBreach { number, breach_type, .. } => {
+                format!("SLA breach: {} due on ticket #{}", breach_type, number)
+            }
+            Self::JobFailed { job_name, .. } => format!("Background job failed: {}", job_name),
+            Self::Test { .. } => "Test notification".to_string(),
+        }
+    }
+
+    fn summary(&self) -> String {
+        match self {
+            Self::HighPriorityTicket { subject, .. } | Self::SlaBreach { subject, .. } => subject.clone(),
+            Self::JobFailed { error, .. } => error.chars().take(500).collect(),
+            Self::Test { channel_name } => format!("Notifications for {} are set up correctly.", channel_name),
+        }
+    }
+
+    fn facts(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Self::HighPriorityTicket { client_name, priority, .. } => vec![
+                ("Client", client_name.clone()),
+                ("Priority", priority.to_uppercase()),
+            ],
+            Self::SlaBreach { client_name, priority, minutes_over, .. } => vec![
+                ("Client", client_name.clone()),
+                ("Priority", priority.to_uppercase()),
+                ("Overdue by", format_minutes(*minutes_over)),
+            ],
+            Self::JobFailed { .. } | Self::Test { .. } => Vec::new(),
+        }
+    }
+
+    /// Adaptive Card colour for the title
+    fn teams_color(&self) -> &'static str {
+        match self {
+            Self::HighPriorityTicket { .. } => "warning",
+            Self::SlaBreach { .. } | Self::JobFailed { .. } => "attention",
+            Self::Test { .. } => "good",
+        }
+    }
+}
+
+fn format_minutes(minutes: i32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {}m", h, m),
+    }
+}
+
+fn ticket_url(ticket_id: Uuid) -> String {
+    portal_link(&format!("tickets/{}", ticket_id))
+}
+
+/// Teams incoming-webhook message carrying an Adaptive Card
+pub fn teams_message(event: &ChatEvent) -> serde_json::Value {
+    let mut body = vec![
+        TeamsCardElement::TextBlock {
+            text: event.title(),
+            size: Some("large".to_string()),
+            weight: Some("bolder".to_string()),
+            color: Some(event.teams_color().to_string()),
+            wrap: Some(true),
+            spacing: None,
+        },
+        TeamsCardElement::TextBlock {
+            text: event.summary(),
+            size: None,
+            weight: None,
+            color: None,
+            wrap: Some(true),
+            spacing: Some("small".to_string()),
+        },
+    ];
+    let facts = event.facts();
+    if !facts.is_empty() {
+        body.push(TeamsCardElement::FactSet {
+            facts: facts
+                .into_iter()
+                .map(|(title, value)| TeamsFact { title: title.to_string(), value })
+                .collect(),
+            spacing: Some("medium".to_string()),
+        });
+    }
+
+    let card = TeamsAdaptiveCard {
+        body,
+        actions: event.ticket_id().map(|id| {
+            vec![TeamsCardAction::OpenUrl { title: "View Ticket".to_string(), url: ticket_url(id) }]
+        }),
+        ..Default::default()
+    };
+
+    serde_json::to_value(TeamsWebhookPayload::from_card(card)).unwrap_or_default()
+}
+
+/// Slack incoming-webhook message in Block Kit, with `text` as the
+/// notification fallback. `channel` overrides the webhook's default channel.
+pub fn slack_message(event: &ChatEvent, channel: Option<&str>) -> serde_json::Value {
+    let title = event.title();
+    let mut blocks = vec![
+        serde_json::json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": title, "emoji": true }
+        }),
+        serde_json::json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": slack_escape(&event.summary()) }
+        }),
+    ];
+    let facts = event.facts();
+    if !facts.is_empty() {
+        let fields: Vec<serde_json::Value> = facts
+            .into_iter()
+            .map(|(title, value)| {
+                serde_json::json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", title, slack_escape(&value)) })
+            })
+            .collect();
+        blocks.push(serde_json::json!({ "type": "section", "fields": fields }));
+    }
+    if let Some(ticket_id) = event.ticket_id() {
+        blocks.push(serde_json::json!({
+            "type": "actions",
+            "elements": [{
+                "type": "button",
+                "text": { "type": "plain_text", "text": "View Ticket" },
+                "url": ticket_url(ticket_id)
+            }]
+        }));
+    }
+
+    let mut message = serde_json::json!({
+        "text": format!("{}: {}", title, event.summary()),
+        "blocks": blocks,
+    });
+    if let Some(channel) = channel.filter(|c| !c.trim().is_empty()) {
+        message["channel"] = serde_json::Value::String(channel.trim().to_string());
+    }
+    message
+}
+
+/// Slack treats `&`, `<` and `>` as control characters in mrkdwn
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ChatChannel {
+    pub id: Uuid,
+    pub name: String,
+    pub provider: String,
+    pub integration_id: Uuid,
+    pub channel: Option<String>,
+    pub event_types: Vec<String>,
+    pub queue_ids: Vec<Uuid>,
+    pub enabled: bool,
+    pub last_sent_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+pub const CHANNEL_COLUMNS: &str =
+    "id, name, provider, integration_id, channel, event_types, queue_ids, enabled,
+     last_sent_at, last_error, created_by, created_at, updated_at";
+
+impl ChatChannel {
+    /// Whether this channel should hear about `event`. Queue filters only
+    /// apply to ticket events; a ticket outside every queue matches only
+    /// channels without one.
+    pub fn wants(&self, event: &ChatEvent) -> bool {
+        if !self.enabled || !self.event_types.iter().any(|t| t == event.event_type()) {
+            return false;
+        }
+        if event.ticket_id().is_none() || self.queue_ids.is_empty() {
+            return true;
+        }
+        event.queue_id().is_some_and(|queue_id| self.queue_ids.contains(&queue_id))
+    }
+
+    pub fn message(&self, event: &ChatEvent) -> serde_json::Value {
+        match self.provider.as_str() {
+            "slack" => slack_message(event, self.channel.as_deref()),
+            _ => teams_message(event),
+        }
+    }
+}
+
+/// Spaces out messages to each channel, shared by every sender in the process
+#[derive(Debug, Default)]
+pub struct ChannelPacer {
+    next_slot: Mutex<HashMap<Uuid, Instant>>,
+}
+
+impl ChannelPacer {
+    /// Claim the channel's next send slot and wait for it
+    pub async fn wait(&self, channel_id: Uuid, interval: Duration) {
+        let slot = {
+            let mut slots = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let slot = slots.get(&channel_id).copied().filter(|s| *s > now).unwrap_or(now);
+            slots.insert(channel_id, slot + interval);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+fn pacer() -> &'static ChannelPacer {
+    static PACER: OnceLock<ChannelPacer> = OnceLock::new();
+    PACER.get_or_init(ChannelPacer::default)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatDelivery {
+    pub attempts: u32,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl ChatDelivery {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.status_code.is_some_and(|s| (200..300).contains(&s))
+    }
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|secs| Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+/// POST `message` to a chat webhook, retrying transport errors and
+/// retryable statuses. A 429's `Retry-After` replaces the usual backoff.
+pub async fn send_with_retries(
+    http: &reqwest::Client,
+    url: &str,
+    message: &serde_json::Value,
+    policy: RetryPolicy,
+) -> ChatDelivery {
+    let max_attempts = policy.max_attempts.max(1);
+    let mut wait = None;
+    let mut delivery = ChatDelivery { attempts: 0, status_code: None, error: None };
+
+    for attempt in 1..=max_attempts {
+        if attempt > 1 {
+            let delay = wait.take().unwrap_or_else(|| backoff_delay(policy.backoff_seconds, attempt - 1));
+            tokio::time::sleep(delay).await;
+        }
+        delivery.attempts = attempt;
+
+        let result = with_request_id(http.post(url))
+            .timeout(policy.timeout)
+            .json(message)
+            .send()
+            .await;
+
+        let retry = match result {
+            Ok(response) => {
+                let status = response.status().as_u16();
+                wait = if status == 429 { retry_after(&response) } else { None };
+                let text = response.text().await.unwrap_or_default();
+                delivery.status_code = Some(status);
+                delivery.error = (!(200..300).contains(&status))
+                    .then(|| format!("HTTP {}: {}", status, response_snippet(&text)));
+                is_retryable_status(status)
+            }
+            Err(e) => {
+                delivery.status_code = None;
+                delivery.error = Some(e.to_string());
+                true
+            }
+        };
+
+        if !retry {
+            break;
+        }
+        tracing::warn!("Chat notification failed (attempt {}/{})", attempt, max_attempts);
+    }
+
+    delivery
+}
+
+/// The channel's webhook URL from its integration's encrypted credentials
+async fn webhook_url(pool: &PgPool, channel: &ChatChannel) -> Result<String, String> {
+    let credentials = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT credentials FROM integrations WHERE id = $1 AND enabled = true"
+    )
+    .bind(channel.integration_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("Integration is missing or disabled")?;
+
+    let decrypted = decrypt_json(&credentials).map_err(|e| format!("Unable to read credentials: {}", e))?;
+    decrypted
+        .get("webhook_url")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| "Integration credentials have no webhook_url".to_string())
+}
+
+/// Send `event` to one channel and record the outcome
+pub async fn deliver(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    channel: &ChatChannel,
+    event: &ChatEvent,
+    policy: RetryPolicy,
+) -> Result<ChatDelivery, sqlx::Error> {
+    let message = channel.message(event);
+    let delivery = match webhook_url(pool, channel).await {
+        Ok(url) => {
+            pacer().wait(channel.id, MIN_SEND_INTERVAL).await;
+            send_with_retries(http, &url, &message, policy).await
+        }
+        Err(e) => ChatDelivery { attempts: 0, status_code: None, error: Some(e) },
+    };
+    if let Some(error) = &delivery.error {
+        tracing::warn!("Chat notification to channel {} failed: {}", channel.id, error);
+    }
+
+    let status = if delivery.is_success() { "succeeded" } else { "failed" };
+    sqlx::query(
+        "INSERT INTO chat_notification_deliveries
+            (channel_id, event_type, payload, status, attempts, last_status_code, error)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    )
+    .bind(channel.id)
+    .bind(event.event_type())
+    .bind(&message)
+    .bind(status)
+    .bind(delivery.attempts as i32)
+    .bind(delivery.status_code.map(i32::from))
+    .bind(&delivery.error)
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "UPDATE chat_notification_channels
+         SET last_sent_at = CASE WHEN $2 THEN NOW() ELSE last_sent_at END, last_error = $3
+         WHERE id = $1"
+    )
+    .bind(channel.id)
+    .bind(delivery.is_success())
+    .bind(&delivery.error)
+    .execute(pool)
+    .await?;
+
+    Ok(delivery)
+}
+
+/// Enabled channels that want `event`
+pub async fn channels_for(pool: &PgPool, event: &ChatEvent) -> Result<Vec<ChatChannel>, sqlx::Error> {
+    let channels = sqlx::query_as::<_, ChatChannel>(&format!(
+        "SELECT {} FROM chat_notification_channels WHERE enabled AND $1 = ANY(event_types)",
+        CHANNEL_COLUMNS
+    ))
+    .bind(event.event_type())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(channels.into_iter().filter(|channel| channel.wants(event)).collect())
+}
+
+/// Send `event` to every channel that wants it. Deliveries run in the
+/// background; returns how many were started.
+pub async fn dispatch(pool: &PgPool, event: ChatEvent) -> Result<usize, sqlx::Error> {
+    let channels = channels_for(pool, &event).await?;
+
+    let count = channels.len();
+    for channel in channels {
+        let pool = pool.clone();
+        let event = event.clone();
+        tokio::spawn(request_id::propagate(async move {
+            if let Err(e) = deliver(&pool, http_client(), &channel, &event, RetryPolicy::default()).await {
+                tracing::error!("Error recording chat notification for channel {}: {}", channel.id, e);
+            }
+        }));
+    }
+
+    Ok(count)
+}
+
+/// Dispatch without surfacing failures, for callers that shouldn't fail
+/// because chat is unreachable
+pub async fn notify(pool: &PgPool, event: ChatEvent) {
+    let event_type = event.event_type();
+    if let Err(e) = dispatch(pool, event).await {
+        tracing::error!("Error dispatching {} chat notifications: {}", event_type, e);
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct TicketSummary {
+    number: i32,
+    subject: String,
+    client_name: String,
+    priority: String,
+    queue_id: Option<Uuid>,
+}
+
+async fn ticket_summary(pool: &PgPool, ticket_id: Uuid) -> Result<Option<TicketSummary>, sqlx::Error> {
+    sqlx::query_as::<_, TicketSummary>(
+        "SELECT t.number, t.subject, COALESCE(c.name, '') AS client_name,
+                COALESCE(t.priority, 'medium') AS priority, t.queue_id
+         FROM tickets t
+         LEFT JOIN clients c ON c.id = t.client_id
+         WHERE t.id = $1"
+    )
+    .bind(ticket_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Announce a newly created ticket if its priority is high or above
+pub async fn notify_ticket_created(pool: &PgPool, ticket_id: Uuid) {
+    match ticket_summary(pool, ticket_id).await {
+        Ok(Some(ticket)) if HIGH_PRIORITIES.contains(&ticket.priority.to_lowercase().as_str()) => {
+            let event = ChatEvent::HighPriorityTicket {
+                ticket_id,
+                number: ticket.number,
+                subject: ticket.subject,
+                client_name: ticket.client_name,
+                priority: ticket.priority,
+                queue_id: ticket.queue_id,
+            };
+            notify(pool, event).await;
+        }
+        Ok(_) => {}
+        Err(e) => tracing::error!("Error loading ticket {} for chat notifications: {}", ticket_id, e),
+    }
+}
+
+/// Announce a response or resolution SLA breach
+pub async fn notify_sla_breach(pool: &PgPool, ticket_id: Uuid, breach_type: &str, minutes_over: i32) {
+    match ticket_summary(pool, ticket_id).await {
+        Ok(Some(ticket)) => {
+            let event = ChatEvent::SlaBreach {
+                ticket_id,
+                number: ticket.number,
+                subject: ticket.subject,
+                client_name: ticket.client_name,
+                priority: ticket.priority,
+                breach_type: breach_type.to_string(),
+                minutes_over,
+                queue_id: ticket.queue_id,
+            };
+            notify(pool, event).await;
+        }
+        Ok(None) => {}
+        Err(e) => tracing::error!("Error loading ticket {} for chat notifications: {}", ticket_id, e),
+    }
+}
+
+/// Announce a background job run that failed outright or in part
+pub async fn notify_job_failed(pool: &PgPool, job_name: &str, error: &str) {
+    let event = ChatEvent::JobFailed { job_name: job_name.to_string(), error: error.to_string() };
+    notify(pool, event).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn breach() -> ChatEvent {
+        ChatEvent::SlaBreach {
+            ticket_id: Uuid::nil(),
+            number: 1042,
+            subject: "Email down <urgent> & slow".to_string(),
+            client_name: "Acme".to_string(),
+            priority: "critical".to_string(),
+            breach_type: "resolution".to_string(),
+            minutes_over: 95,
+            queue_id: None,
+        }
+    }
+
+    fn channel(provider: &str) -> ChatChannel {
+        ChatChannel {
+            id: Uuid::new_v4(),
+            name: "NOC".to_string(),
+            provider: provider.to_string(),
+            integration_id: Uuid::new_v4(),
+            channel: None,
+            event_types: vec![SLA_BREACH.to_string(), JOB_FAILED.to_string()],
+            queue_ids: Vec::new(),
+            enabled: true,
+            last_sent_at: None,
+            last_error: None,
+            created_by: None,
+            created_at: Utc::now(),
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_teams_message_is_an_adaptive_card() {
+        let message = teams_message(&breach());
+
+        assert_eq!(message["type"], "message");
+        let attachment = &message["attachments"][0];
+        assert_eq!(attachment["contentType"], "application/vnd.microsoft.card.adaptive");
+        let card = &attachment["content"];
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert_eq!(card["body"][0]["text"], "SLA breach: resolution due on ticket #1042");
+        assert_eq!(card["body"][0]["color"], "attention");
+        let facts = card["body"][2]["facts"].as_array().unwrap();
+        assert_eq!(facts[2]["title"], "Overdue by");
+        assert_eq!(facts[2]["value"], "1h 35m");
+        assert_eq!(card["actions"][0]["type"], "Action.OpenUrl");
+        assert!(card["actions"][0]["url"].as_str().unwrap().ends_with(&format!("tickets/{}", Uuid::nil())));
+    }
+
+    #[test]
+    fn test_slack_message_uses_blocks_with_fallback_text() {
+        let message = slack_message(&breach(), Some("#noc"));
+
+        assert_eq!(message["channel"], "#noc");
+        assert!(message["text"].as_str().unwrap().starts_with("SLA breach: resolution due on ticket #1042"));
+        let blocks = message["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["type"], "header");
+        assert_eq!(blocks[1]["text"]["text"], "Email down &lt;urgent&gt; &amp; slow");
+        assert_eq!(blocks[2]["fields"][0]["text"], "*Client*\nAcme");
+        assert_eq!(blocks[3]["elements"][0]["type"], "button");
+
+        let job = slack_message(&ChatEvent::JobFailed { job_name: "Late Fees".to_string(), error: "timeout".to_string() }, None);
+        assert!(job.get("channel").is_none());
+        // No facts and no ticket link
+        assert_eq!(job["blocks"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_disabled_channel_wants_nothing() {
+        let mut noc = channel("teams");
+        assert!(noc.wants(&breach()));
+
+        noc.enabled = false;
+        assert!(!noc.wants(&breach()));
+    }
+
+    #[test]
+    fn test_channel_filters_by_event_type_and_queue() {
+        let mut noc = channel("slack");
+        let ticket = ChatEvent::HighPriorityTicket {
+            ticket_id: Uuid::new_v4(),
+            number: 7,
+            subject: "Server down".to_string(),
+            client_name: "Acme".to_string(),
+            priority: "urgent".to_string(),
+            queue_id: None,
+        };
+        assert!(!noc.wants(&ticket));
+
+        let queue = Uuid::new_v4();
+        noc.queue_ids = vec![queue];
+        assert!(!noc.wants(&breach()));
+        let mut in_queue = breach();
+        if let ChatEvent::SlaBreach { queue_id, .. } = &mut in_queue {
+            *queue_id = Some(queue);
+        }
+        assert!(noc.wants(&in_queue));
+        // Queue filters don't apply to job failures
+        assert!(noc.wants(&ChatEvent::JobFailed { job_name: "SLA Checker".to_string(), error: "boom".to_string() }));
+    }
+
+    #[tokio::test]
+    async fn test_pacer_spaces_messages_per_channel() {
+        tokio::time::pause();
+        let pacer = ChannelPacer::default();
+        let channel_id = Uuid::new_v4();
+        let start = Instant::now();
+
+        pacer.wait(channel_id, Duration::from_secs(1)).await;
+        pacer.wait(Uuid::new_v4(), Duration::from_secs(1)).await;
+        assert!(start.elapsed() < Duration::from_millis(10));
+
+        pacer.wait(channel_id, Duration::from_secs(1)).await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    fn no_backoff(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, backoff_seconds: 0, timeout: Duration::from_secs(5) }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_send_is_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .mount(&server)
+            .await;
+
+        let http = reqwest::Client::new();
+        let delivery = send_with_retries(&http, &format!("{}/hook", server.uri()), &teams_message(&breach()), no_backoff(3)).await;
+        assert!(delivery.is_success());
+        assert_eq!(delivery.attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_payload_is_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_payload"))
+            .mount(&server)
+            .await;
+
+        let http = reqwest::Client::new();
+        let delivery = send_with_retries(&http, &format!("{}/hook", server.uri()), &slack_message(&breach(), None), no_backoff(3)).await;
+        assert!(!delivery.is_success());
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.error.as_deref(), Some("HTTP 400: invalid_payload"));
+    }
+}
@@ -0,0 +1,269 @@
+//! Client and contact export
+//!
+//! Clients, or one client's contacts, as CSV for offboarding and backups.
+//! Like the time export, rows are streamed from the database and written
+//! out as they arrive. Archived records are left out unless asked for.
+
+use axum::body::Bytes;
+use futures::stream::{self, Stream};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgPool};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::services::contact_merge::CONTACT_COLUMNS;
+use crate::services::report_export::write_csv_record;
+use resolve_shared::{Client, Contact};
+
+/// Chunks buffered ahead of a slow client before reading pauses
+const CHANNEL_CAPACITY: usize = 32;
+
+/// Only CSV for now; the parameter is there so other formats can follow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientExportFormat {
+    #[default]
+    Csv,
+}
+
+impl ClientExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+        }
+    }
+}
+
+pub const CLIENT_CSV_HEADERS: [&str; 13] = [
+    "id",
+    "name",
+    "email",
+    "phone",
+    "address",
+    "city",
+    "state",
+    "zip",
+    "billing_address",
+    "tax_exempt",
+    "notes",
+    "created_at",
+    "archived_at",
+];
+
+pub const CONTACT_CSV_HEADERS: [&str; 13] = [
+    "id",
+    "client_id",
+    "name",
+    "title",
+    "email",
+    "phone",
+    "extension",
+    "mobile",
+    "department",
+    "primary",
+    "notes",
+    "created_at",
+    "archived_at",
+];
+
+fn text(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+fn csv_line(fields: &[String]) -> String {
+    let mut out = String::new();
+    write_csv_record(&mut out, fields);
+    out
+}
+
+pub fn client_csv_row(client: &Client) -> String {
+    csv_line(&[
+        client.id.to_string(),
+        client.name.clone(),
+        text(&client.email),
+        text(&client.phone),
+        text(&client.address),
+        text(&client.city),
+        text(&client.state),
+        text(&client.zip),
+        text(&client.billing_address),
+        client.tax_exempt.to_string(),
+        text(&client.notes),
+        client.created_at.to_rfc3339(),
+        client.archived_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+    ])
+}
+
+pub fn contact_csv_row(contact: &Contact) -> String {
+    csv_line(&[
+        contact.id.to_string(),
+        contact.client_id.to_string(),
+        contact.name.clone(),
+        text(&contact.title),
+        text(&contact.email),
+        text(&contact.phone),
+        text(&contact.extension),
+        text(&contact.mobile),
+        text(&contact.department),
+        contact.primary.to_string(),
+        text(&contact.notes),
+        contact.created_at.to_rfc3339(),
+        contact.archived_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+    ])
+}
+
+/// Every client, by name
+pub fn export_clients(
+    pool: PgPool,
+    include_archived: bool,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send {
+    let sql = "SELECT id, name, email, phone, address, city, state, zip, billing_address, notes, tax_exempt,
+                      created_at, updated_at, archived_at
+               FROM clients
+               WHERE $1 OR archived_at IS NULL
+               ORDER BY name, id"
+        .to_string();
+    stream_csv(pool, &CLIENT_CSV_HEADERS, sql, None, include_archived, client_csv_row)
+}
+
+/// One client's contacts, primary contact first
+pub fn export_contacts(
+    pool: PgPool,
+    client_id: Uuid,
+    include_archived: bool,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send {
+    let sql = format!(
+        "SELECT {} FROM contacts
+         WHERE client_id = $1 AND ($2 OR archived_at IS NULL)
+         ORDER BY is_primary DESC, name, id",
+        CONTACT_COLUMNS
+    );
+    stream_csv(pool, &CONTACT_CSV_HEADERS, sql, Some(client_id), include_archived, contact_csv_row)
+}
+
+/// Run `sql`, bound to the client (`$1`) when there is one and then to
+/// whether archived rows are wanted, and stream the header then one CSV
+/// line per row. A
+/// database error part way through ends the stream with an error, so the
+/// download fails rather than coming back short.
+fn stream_csv<T>(
+    pool: PgPool,
+    headers: &'static [&'static str],
+    sql: String,
+    client_id: Option<Uuid>,
+    include_archived: bool,
+    row: fn(&T) -> String,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send
+where
+    T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'static,
+{
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+    tokio::spawn(async move {
+        let header = csv_line(&headers.iter().map(|h| h.to_string()).collect::<Vec<_>>());
+        if sender.send(Ok(Bytes::from(header))).await.is_err() {
+            return;
+        }
+
+        let mut query = sqlx::query_as::<_, T>(&sql);
+        if let Some(client_id) = client_id {
+            query = query.bind(client_id);
+        }
+        let mut rows = query.bind(include_archived).fetch(&pool);
+
+        loop {
+            let chunk = match rows.try_next().await {
+                Ok(Some(record)) => row(&record),
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!("Error streaming client export: {}", e);
+                    let _ = sender.send(Err(std::io::Error::other("export failed"))).await;
+                    return;
+                }
+            };
+            // The client went away
+            if sender.send(Ok(Bytes::from(chunk))).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn client(name: &str) -> Client {
+        Client {
+            id: Uuid::nil(),
+            name: name.to_string(),
+            email: Some("ops@acme.example".to_string()),
+            phone: None,
+            address: Some("1 Main St\nSuite 200".to_string()),
+            city: None,
+            state: None,
+            zip: None,
+            billing_address: None,
+            notes: None,
+            tax_exempt: false,
+            created_at: Utc.with_ymd_and_hms(2024, 5, 14, 9, 0, 0).unwrap(),
+            updated_at: None,
+            archived_at: None,
+        }
+    }
+
+    fn contact(name: &str) -> Contact {
+        Contact {
+            id: Uuid::nil(),
+            client_id: Uuid::nil(),
+            name: name.to_string(),
+            title: None,
+            email: Some("jo@acme.example".to_string()),
+            phone: None,
+            extension: None,
+            mobile: None,
+            department: None,
+            notes: None,
+            primary: true,
+            created_at: Utc.with_ymd_and_hms(2024, 5, 14, 9, 0, 0).unwrap(),
+            updated_at: None,
+            archived_at: None,
+        }
+    }
+
+    #[test]
+    fn test_client_row_escapes_commas_quotes_and_newlines() {
+        let row = client_csv_row(&client("Acme, \"The\" Widget Co"));
+        assert_eq!(
+            row,
+            "00000000-0000-0000-0000-000000000000,\"Acme, \"\"The\"\" Widget Co\",ops@acme.example,,\
+             \"1 Main St\nSuite 200\",,,,,false,,2024-05-14T09:00:00+00:00,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_headers_match_row_widths() {
+        let header = csv_line(&CLIENT_CSV_HEADERS.map(String::from));
+        assert!(header.starts_with("id,name,email,"));
+        let plain = client_csv_row(&Client { address: None, ..client("Acme") });
+        assert_eq!(plain.matches(',').count(), CLIENT_CSV_HEADERS.len() - 1);
+
+        let header = csv_line(&CONTACT_CSV_HEADERS.map(String::from));
+        assert!(header.starts_with("id,client_id,name,"));
+        let row = contact_csv_row(&contact("Jo Smith"));
+        assert_eq!(row.matches(',').count(), CONTACT_CSV_HEADERS.len() - 1);
+    }
+}
@@ -0,0 +1,347 @@
+//! Importing Cloudflare zones into itdoc domains
+//!
+//! Each zone visible to a `cloudflare` integration is matched to a domain,
+//! first by the zone id recorded on an earlier import and then by name.
+//! Zones with no domain are created under the client configured on the
+//! integration (`config.client_id`). Nameservers and the zone's A, AAAA,
+//! CNAME, MX and TXT records are written in the `dns_records` format from
+//! [`crate::services::dns_verification`].
+//!
+//! Zones whose `modified_on` matches the last import are skipped without
+//! fetching their records.
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use uuid::Uuid;
+
+use crate::services::dns_verification::DnsRecord;
+
+const DEFAULT_API_URL: &str = "https://api.cloudflare.com/client/v4";
+/// Cloudflare caps zone listings at 50 per page
+const ZONES_PER_PAGE: u32 = 50;
+const RECORDS_PER_PAGE: u32 = 100;
+/// A TTL of 1 means "automatic" in Cloudflare
+const AUTOMATIC_TTL: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CloudflareError {
+    #[error("Cloudflare API returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("Cloudflare request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiMessage>,
+    result: Option<T>,
+    result_info: Option<ResultInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiMessage {
+    code: Option<i64>,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultInfo {
+    page: u32,
+    total_pages: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Zone {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub name_servers: Vec<String>,
+    pub modified_on: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneRecord {
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub name: String,
+    pub content: String,
+    pub ttl: Option<u32>,
+    pub priority: Option<u16>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub zones_seen: i32,
+    pub created: i32,
+    pub updated: i32,
+    pub unchanged: i32,
+    /// Zones with no matching domain and no client configured to create it under
+    pub skipped: i32,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CloudflareDnsClient {
+    http: reqwest::Client,
+    api_url: String,
+}
+
+impl CloudflareDnsClient {
+    /// `http` must already carry the integration's auth headers
+    pub fn new(http: reqwest::Client, api_url: Option<&str>) -> Self {
+        Self {
+            http,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn get_page<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        page: u32,
+        per_page: u32,
+    ) -> Result<(Vec<T>, bool), CloudflareError> {
+        let response = self
+            .http
+            .get(format!("{}{}", self.api_url, path))
+            .query(&[("page", page), ("per_page", per_page)])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        let envelope: Envelope<Vec<T>> = serde_json::from_str(&body).map_err(|e| CloudflareError::Api {
+            status: status.as_u16(),
+            message: if status.is_success() { e.to_string() } else { body.clone() },
+        })?;
+
+        if !status.is_success() || !envelope.success {
+            let message = envelope
+                .errors
+                .iter()
+                .map(|e| match e.code {
+                    Some(code) => format!("{} ({})", e.message, code),
+                    None => e.message.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(CloudflareError::Api { status: status.as_u16(), message });
+        }
+
+        let more = envelope.result_info.is_some_and(|info| info.page < info.total_pages);
+        Ok((envelope.result.unwrap_or_default(), more))
+    }
+
+    async fn get_all<T: DeserializeOwned>(&self, path: &str, per_page: u32) -> Result<Vec<T>, CloudflareError> {
+        let mut items = Vec::new();
+        let mut page = 1;
+        loop {
+            let (batch, more) = self.get_page(path, page, per_page).await?;
+            items.extend(batch);
+            if !more {
+                return Ok(items);
+            }
+            page += 1;
+        }
+    }
+
+    pub async fn list_zones(&self) -> Result<Vec<Zone>, CloudflareError> {
+        self.get_all("/zones", ZONES_PER_PAGE).await
+    }
+
+    pub async fn list_records(&self, zone_id: &str) -> Result<Vec<ZoneRecord>, CloudflareError> {
+        self.get_all(&format!("/zones/{}/dns_records", zone_id), RECORDS_PER_PAGE).await
+    }
+}
+
+/// Record name relative to the zone: `@` for the apex, a trailing dot for
+/// names outside it
+fn relative_name(name: &str, zone: &str) -> String {
+    let name = name.trim().trim_end_matches('.').to_ascii_lowercase();
+    let zone = zone.trim().trim_end_matches('.').to_ascii_lowercase();
+
+    if name == zone {
+        "@".to_string()
+    } else if let Some(host) = name.strip_suffix(&format!(".{}", zone)) {
+        host.to_string()
+    } else {
+        format!("{}.", name)
+    }
+}
+
+/// Map a Cloudflare record onto a stored record. Types itdoc doesn't track
+/// (SRV, CAA, NS, ...) and malformed content give `None`.
+pub fn to_dns_record(record: &ZoneRecord, zone: &str) -> Option<DnsRecord> {
+    let name = relative_name(&record.name, zone);
+    let ttl = record.ttl.filter(|ttl| *ttl != AUTOMATIC_TTL);
+    let content = record.content.trim();
+
+    match record.record_type.to_ascii_uppercase().as_str() {
+        "A" => content.parse::<Ipv4Addr>().ok().map(|value| DnsRecord::A { name, value, ttl }),
+        "AAAA" => content.parse::<Ipv6Addr>().ok().map(|value| DnsRecord::Aaaa { name, value, ttl }),
+        "CNAME" => Some(DnsRecord::Cname { name, value: content.to_string(), ttl }),
+        "MX" => Some(DnsRecord::Mx {
+            name,
+            value: content.to_string(),
+            priority: record.priority.unwrap_or(0),
+            ttl,
+        }),
+        "TXT" => {
+            let value = content
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(content);
+            Some(DnsRecord::Txt { name, value: value.to_string(), ttl })
+        }
+        _ => None,
+    }
+}
+
+async fn find_domain(
+    pool: &PgPool,
+    zone: &Zone,
+    client_id: Option<Uuid>,
+) -> Result<Option<(Uuid, Option<DateTime<Utc>>)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, Option<DateTime<Utc>>)>(
+        r#"
+        SELECT id, cloudflare_modified_on FROM domains
+        WHERE cloudflare_zone_id = $1 OR LOWER(name) = LOWER($2)
+        ORDER BY cloudflare_zone_id IS NOT DISTINCT FROM $1 DESC,
+                 client_id IS NOT DISTINCT FROM $3 DESC,
+                 created_at
+        LIMIT 1
+        "#
+    )
+    .bind(&zone.id)
+    .bind(&zone.name)
+    .bind(client_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Import every zone visible to `client`. Listing failures abort the
+/// import; a failure on one zone is recorded and the rest carry on.
+pub async fn import_zones(
+    pool: &PgPool,
+    client: &CloudflareDnsClient,
+    client_id: Option<Uuid>,
+) -> Result<ImportSummary, CloudflareError> {
+    let zones = client.list_zones().await?;
+    let mut summary = ImportSummary::default();
+
+    for zone in &zones {
+        summary.zones_seen += 1;
+
+        let existing = find_domain(pool, zone, client_id).await?;
+        if let Some((_, Some(modified_on))) = existing {
+            if modified_on == zone.modified_on {
+                summary.unchanged += 1;
+                continue;
+            }
+        }
+        if existing.is_none() && client_id.is_none() {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let records = match client.list_records(&zone.id).await {
+            Ok(records) => records,
+            Err(e) => {
+                summary.errors.push(format!("{}: {}", zone.name, e));
+                continue;
+            }
+        };
+        let dns_records: Vec<DnsRecord> = records.iter().filter_map(|r| to_dns_record(r, &zone.name)).collect();
+        let dns_records = serde_json::to_value(&dns_records).unwrap_or_default();
+
+        match existing {
+            Some((domain_id, _)) => {
+                sqlx::query(
+                    r#"
+                    UPDATE domains SET nameservers = $2, dns_records = $3,
+                        cloudflare_zone_id = $4, cloudflare_modified_on = $5, updated_at = NOW()
+                    WHERE id = $1
+                    "#
+                )
+                .bind(domain_id)
+                .bind(&zone.name_servers)
+                .bind(&dns_records)
+                .bind(&zone.id)
+                .bind(zone.modified_on)
+                .execute(pool)
+                .await?;
+                summary.updated += 1;
+            }
+            None => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO domains (client_id, name, nameservers, dns_records,
+                        cloudflare_zone_id, cloudflare_modified_on, created_at, updated_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW())
+                    "#
+                )
+                .bind(client_id)
+                .bind(zone.name.to_ascii_lowercase())
+                .bind(&zone.name_servers)
+                .bind(&dns_records)
+                .bind(&zone.id)
+                .bind(zone.modified_on)
+                .execute(pool)
+                .await?;
+                summary.created += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(record_type: &str, name: &str, content: &str, ttl: u32, priority: Option<u16>) -> ZoneRecord {
+        ZoneRecord {
+            record_type: record_type.to_string(),
+            name: name.to_string(),
+            content: content.to_string(),
+            ttl: Some(ttl),
+            priority,
+        }
+    }
+
+    #[test]
+    fn test_relative_name() {
+        assert_eq!(relative_name("example.com", "example.com"), "@");
+        assert_eq!(relative_name("WWW.Example.com", "example.com"), "www");
+        assert_eq!(relative_name("_dmarc.mail.example.com", "example.com"), "_dmarc.mail");
+        assert_eq!(relative_name("example.net", "example.com"), "example.net.");
+    }
+
+    #[test]
+    fn test_records_map_to_itdoc_format() {
+        let zone = "example.com";
+        assert_eq!(
+            to_dns_record(&record("A", "example.com", "203.0.113.10", 1, None), zone),
+            Some(DnsRecord::A { name: "@".into(), value: "203.0.113.10".parse().unwrap(), ttl: None })
+        );
+        assert_eq!(
+            to_dns_record(&record("MX", "example.com", "mail.example.com", 3600, Some(10)), zone),
+            Some(DnsRecord::Mx { name: "@".into(), value: "mail.example.com".into(), priority: 10, ttl: Some(3600) })
+        );
+        assert_eq!(
+            to_dns_record(&record("TXT", "example.com", "\"v=spf1 -all\"", 300, None), zone),
+            Some(DnsRecord::Txt { name: "@".into(), value: "v=spf1 -all".into(), ttl: Some(300) })
+        );
+        assert_eq!(to_dns_record(&record("A", "www.example.com", "not-an-ip", 1, None), zone), None);
+        assert_eq!(to_dns_record(&record("SRV", "_sip._tcp.example.com", "0 5 5060 sip", 1, None), zone), None);
+    }
+}
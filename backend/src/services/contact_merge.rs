@@ -0,0 +1,386 @@
+//! Contact deduplication and merge
+//!
+//! Imports and syncs can leave the same person on a client's roster more
+//! than once. [`find_duplicates`] groups a client's active contacts that
+//! share a normalized email, phone number or name; [`merge`] folds source
+//! contacts into a target in one transaction, moving everything that points
+//! at the sources over to the target and archiving the sources.
+
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use uuid::Uuid;
+
+use resolve_shared::Contact;
+
+pub const CONTACT_COLUMNS: &str =
+    r#"id, client_id, name, title, email, phone, extension, mobile, department, notes,
+       is_primary AS "primary", created_at, updated_at, archived_at"#;
+
+/// Columns that plainly point at a contact and move to the merge target
+const CONTACT_REFERENCES: &[(&str, &str)] = &[
+    ("tickets", "contact_id"),
+    ("ticket_replies", "contact_id"),
+    ("assets", "contact_id"),
+    ("password_change_requests", "requested_by_contact"),
+    ("email_threads", "contact_id"),
+    ("sms_messages", "contact_id"),
+    ("portal_messages", "contact_id"),
+    ("doc_feedback", "contact_id"),
+    ("kb_feedback", "contact_id"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error("At least one source contact is required")]
+    NoSources,
+    #[error("A contact can't be merged into itself")]
+    TargetIsSource,
+    #[error("Contact {0} not found")]
+    ContactNotFound(Uuid),
+    #[error("Contact {0} is archived")]
+    ContactArchived(Uuid),
+    #[error("Contact {0} belongs to a different client")]
+    DifferentClient(Uuid),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchReason {
+    Email,
+    Phone,
+    Name,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    /// What the contacts in the group have in common
+    pub reasons: Vec<MatchReason>,
+    pub contacts: Vec<Contact>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MergeResult {
+    pub target: Contact,
+    pub archived: Vec<Uuid>,
+    /// Rows moved to the target, per table
+    pub repointed: BTreeMap<&'static str, u64>,
+}
+
+pub fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim().to_lowercase();
+    email.contains('@').then_some(email)
+}
+
+/// Digits only, with a leading country code of 1 dropped, so
+/// "+1 (555) 010-2000" and "555.010.2000" agree. Too short to identify
+/// anyone is no match at all.
+pub fn normalize_phone(phone: &str) -> Option<String> {
+    let digits: String = phone.chars().filter(char::is_ascii_digit).collect();
+    let digits = match digits.strip_prefix('1') {
+        Some(rest) if digits.len() == 11 => rest.to_string(),
+        _ => digits,
+    };
+    (digits.len() >= 7).then_some(digits)
+}
+
+/// Lowercased words in sorted order, so "Smith, Jo" matches "jo smith"
+pub fn normalize_name(name: &str) -> Option<String> {
+    let mut words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    words.sort();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+fn match_keys(contact: &Contact) -> Vec<(MatchReason, String)> {
+    let mut keys = Vec::new();
+    keys.extend(contact.email.as_deref().and_then(normalize_email).map(|k| (MatchReason::Email, k)));
+    for phone in [&contact.phone, &contact.mobile] {
+        keys.extend(phone.as_deref().and_then(normalize_phone).map(|k| (MatchReason::Phone, k)));
+    }
+    keys.extend(normalize_name(&contact.name).map(|k| (MatchReason::Name, k)));
+    keys
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// Group contacts sharing any key. Matches chain, so A and C land together
+/// when A shares an email with B and B a phone with C. Groups come back in
+/// the order of their first contact; single contacts are left out.
+pub fn group_duplicates(contacts: Vec<Contact>) -> Vec<DuplicateGroup> {
+    let mut parents: Vec<usize> = (0..contacts.len()).collect();
+    let mut reasons: Vec<BTreeSet<MatchReason>> = vec![BTreeSet::new(); contacts.len()];
+    let mut first_with_key: HashMap<(MatchReason, String), usize> = HashMap::new();
+
+    for (i, contact) in contacts.iter().enumerate() {
+        for key in match_keys(contact) {
+            let reason = key.0;
+            match first_with_key.get(&key) {
+                Some(&j) => {
+                    let (a, b) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                    let (root, other) = (a.min(b), a.max(b));
+                    parents[other] = root;
+                    let merged = std::mem::take(&mut reasons[other]);
+                    reasons[root].extend(merged);
+                    reasons[root].insert(reason);
+                }
+                None => {
+                    first_with_key.insert(key, i);
+                }
+            }
+        }
+    }
+
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..contacts.len() {
+        let root = find_root(&mut parents, i);
+        members.entry(root).or_default().push(i);
+    }
+
+    let mut contacts: Vec<Option<Contact>> = contacts.into_iter().map(Some).collect();
+    members
+        .into_iter()
+        .filter(|(_, indexes)| indexes.len() > 1)
+        .map(|(root, indexes)| DuplicateGroup {
+            reasons: reasons[root].iter().copied().collect(),
+            contacts: indexes.into_iter().filter_map(|i| contacts[i].take()).collect(),
+        })
+        .collect()
+}
+
+/// Likely duplicates among a client's active contacts
+pub async fn find_duplicates(pool: &PgPool, client_id: Uuid) -> Result<Vec<DuplicateGroup>, sqlx::Error> {
+    let contacts = sqlx::query_as::<_, Contact>(&format!(
+        "SELECT {} FROM contacts WHERE client_id = $1 AND archived_at IS NULL ORDER BY created_at, id",
+        CONTACT_COLUMNS
+    ))
+    .bind(client_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(group_duplicates(contacts))
+}
+
+async fn lock_contact(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> Result<Contact, MergeError> {
+    sqlx::query_as::<_, Contact>(&format!("SELECT {} FROM contacts WHERE id = $1 FOR UPDATE", CONTACT_COLUMNS))
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(MergeError::ContactNotFound(id))
+}
+
+/// Merge `source_ids` into `target_id`. The target keeps its own details
+/// and takes any it is missing from the sources, in the order given.
+pub async fn merge(pool: &PgPool, target_id: Uuid, source_ids: &[Uuid]) -> Result<MergeResult, MergeError> {
+    let mut sources: Vec<Uuid> = Vec::new();
+    for id in source_ids {
+        if !sources.contains(id) {
+            sources.push(*id);
+        }
+    }
+    if sources.is_empty() {
+        return Err(MergeError::NoSources);
+    }
+    if sources.contains(&target_id) {
+        return Err(MergeError::TargetIsSource);
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let target = lock_contact(&mut tx, target_id).await?;
+    if target.archived_at.is_some() {
+        return Err(MergeError::ContactArchived(target_id));
+    }
+    for &id in &sources {
+        let source = lock_contact(&mut tx, id).await?;
+        if source.archived_at.is_some() {
+            return Err(MergeError::ContactArchived(id));
+        }
+        if source.client_id != target.client_id {
+            return Err(MergeError::DifferentClient(id));
+        }
+    }
+
+    let mut repointed = BTreeMap::new();
+    for &(table, column) in CONTACT_REFERENCES {
+        let moved = sqlx::query(&format!("UPDATE {table} SET {column} = $1 WHERE {column} = ANY($2)"))
+            .bind(target_id)
+            .bind(&sources)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        repointed.insert(table, moved);
+    }
+
+    // One row of portal password access per client and contact: where the
+    // target already has one, fold the source's grants into it
+    let mut moved = 0;
+    for &source_id in &sources {
+        sqlx::query(
+            "UPDATE password_portal_access t SET
+                 allowed_passwords = ARRAY(SELECT DISTINCT unnest(COALESCE(t.allowed_passwords, '{}') || COALESCE(s.allowed_passwords, '{}'))),
+                 allowed_categories = ARRAY(SELECT DISTINCT unnest(COALESCE(t.allowed_categories, '{}') || COALESCE(s.allowed_categories, '{}')))
+             FROM password_portal_access s
+             WHERE s.contact_id = $2 AND t.contact_id = $1 AND t.client_id = s.client_id"
+        )
+        .bind(target_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM password_portal_access s
+             WHERE s.contact_id = $2
+               AND EXISTS (SELECT 1 FROM password_portal_access t WHERE t.contact_id = $1 AND t.client_id = s.client_id)"
+        )
+        .bind(target_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+        moved += sqlx::query("UPDATE password_portal_access SET contact_id = $1 WHERE contact_id = $2")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+    repointed.insert("password_portal_access", moved);
+
+    // A contact holds at most one active seat per license; a source's seat
+    // on a license the target already holds is released rather than moved
+    let mut moved = 0;
+    for &source_id in &sources {
+        sqlx::query(
+            "UPDATE software_license_assignments s SET unassigned_at = NOW()
+             WHERE s.contact_id = $2 AND s.unassigned_at IS NULL
+               AND EXISTS (SELECT 1 FROM software_license_assignments t
+                           WHERE t.contact_id = $1 AND t.license_id = s.license_id AND t.unassigned_at IS NULL)"
+        )
+        .bind(target_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+        moved += sqlx::query("UPDATE software_license_assignments SET contact_id = $1 WHERE contact_id = $2")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    }
+    repointed.insert("software_license_assignments", moved);
+
+    // Fill the target's gaps from the sources, first source first
+    sqlx::query(
+        "UPDATE contacts t SET
+             title = COALESCE(t.title, s.title),
+             email = COALESCE(t.email, s.email),
+             phone = COALESCE(t.phone, s.phone),
+             extension = COALESCE(t.extension, s.extension),
+             mobile = COALESCE(t.mobile, s.mobile),
+             department = COALESCE(t.department, s.department),
+             is_primary = t.is_primary OR s.is_primary,
+             updated_at = NOW()
+         FROM (
+             SELECT
+                 (array_agg(c.title ORDER BY o.n) FILTER (WHERE c.title IS NOT NULL))[1] AS title,
+                 (array_agg(c.email ORDER BY o.n) FILTER (WHERE c.email IS NOT NULL))[1] AS email,
+                 (array_agg(c.phone ORDER BY o.n) FILTER (WHERE c.phone IS NOT NULL))[1] AS phone,
+                 (array_agg(c.extension ORDER BY o.n) FILTER (WHERE c.extension IS NOT NULL))[1] AS extension,
+                 (array_agg(c.mobile ORDER BY o.n) FILTER (WHERE c.mobile IS NOT NULL))[1] AS mobile,
+                 (array_agg(c.department ORDER BY o.n) FILTER (WHERE c.department IS NOT NULL))[1] AS department,
+                 COALESCE(bool_or(c.is_primary), false) AS is_primary
+             FROM unnest($2::uuid[]) WITH ORDINALITY AS o(id, n)
+             JOIN contacts c ON c.id = o.id
+         ) s
+         WHERE t.id = $1"
+    )
+    .bind(target_id)
+    .bind(&sources)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE contacts SET archived_at = NOW(), is_primary = false, updated_at = NOW() WHERE id = ANY($1)")
+        .bind(&sources)
+        .execute(&mut *tx)
+        .await?;
+
+    let target = lock_contact(&mut tx, target_id).await?;
+    tx.commit().await?;
+
+    Ok(MergeResult { target, archived: sources, repointed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn contact(name: &str, email: Option<&str>, phone: Option<&str>) -> Contact {
+        Contact {
+            id: Uuid::new_v4(),
+            client_id: Uuid::nil(),
+            name: name.to_string(),
+            title: None,
+            email: email.map(str::to_string),
+            phone: phone.map(str::to_string),
+            extension: None,
+            mobile: None,
+            department: None,
+            notes: None,
+            primary: false,
+            created_at: Utc::now(),
+            updated_at: None,
+            archived_at: None,
+        }
+    }
+
+    #[test]
+    fn test_normalization() {
+        assert_eq!(normalize_email(" Jo@Acme.COM "), Some("jo@acme.com".to_string()));
+        assert_eq!(normalize_email("n/a"), None);
+        assert_eq!(normalize_phone("+1 (555) 010-2000"), normalize_phone("555.010.2000"));
+        assert_eq!(normalize_phone("x12"), None);
+        assert_eq!(normalize_name("Smith, Jo"), normalize_name("jo  smith"));
+        assert_eq!(normalize_name(" - "), None);
+    }
+
+    #[test]
+    fn test_matches_chain_into_one_group() {
+        let contacts = vec![
+            contact("Jo Smith", Some("jo@acme.com"), None),
+            contact("Pat Lee", None, Some("555-010-3000")),
+            contact("Joanna Smith", Some("JO@acme.com"), Some("(555) 010 3000")),
+            contact("Sam Green", Some("sam@acme.com"), None),
+        ];
+        let ids: Vec<Uuid> = contacts.iter().map(|c| c.id).collect();
+
+        let groups = group_duplicates(contacts);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reasons, vec![MatchReason::Email, MatchReason::Phone]);
+        assert_eq!(
+            groups[0].contacts.iter().map(|c| c.id).collect::<Vec<_>>(),
+            vec![ids[0], ids[1], ids[2]]
+        );
+    }
+
+    #[test]
+    fn test_name_only_match_is_reported() {
+        let groups = group_duplicates(vec![
+            contact("Lee, Pat", None, None),
+            contact("Pat Lee", Some("pat@acme.com"), None),
+        ]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reasons, vec![MatchReason::Name]);
+    }
+}
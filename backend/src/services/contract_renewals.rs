@@ -0,0 +1,384 @@
+//! Contract renewal reminders and renewals
+//!
+//! The renewal job passes a set of warning thresholds in days. Once an
+//! active contract's `end_date` comes within one, the account manager is
+//! reminded, with `contract_renewal_reminders` keeping each threshold to
+//! one reminder per end date. `auto_renew` contracts are renewed instead
+//! once they're within the auto-renew window: `end_date` moves out by the
+//! contract's `renewal_term_months` and the new period is recorded in
+//! `contract_renewals`. Manual renewals go through the same path.
+
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::notifications::create_notifications_for_users;
+use crate::services::asset_warranty::{client_technicians, crossed_threshold};
+
+pub const ACTIVE: &str = "active";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenewalError {
+    #[error("Contract not found")]
+    NotFound,
+    #[error("Contract has no end date")]
+    NoEndDate,
+    #[error("Only active contracts can be renewed")]
+    NotActive,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ExpiringContract {
+    pub contract_id: Uuid,
+    pub contract_name: String,
+    pub contract_type: Option<String>,
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub account_manager_id: Option<Uuid>,
+    pub end_date: NaiveDate,
+    pub days_remaining: i32,
+    pub auto_renew: bool,
+    pub renewal_term_months: i32,
+    pub monthly_value: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ContractRenewal {
+    pub id: Uuid,
+    pub contract_id: Uuid,
+    pub previous_end_date: NaiveDate,
+    pub new_end_date: NaiveDate,
+    pub automatic: bool,
+    pub renewed_by: Option<Uuid>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A reminder sent by [`check_renewals`]
+#[derive(Debug, Clone)]
+pub struct RenewalReminder {
+    pub contract: ExpiringContract,
+    pub threshold_days: i32,
+    pub notified: usize,
+}
+
+/// An automatic renewal made by [`check_renewals`]
+#[derive(Debug, Clone)]
+pub struct AutoRenewal {
+    pub contract: ExpiringContract,
+    pub renewal: ContractRenewal,
+    pub notified: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct RenewalCheck {
+    pub checked: usize,
+    pub reminders: Vec<RenewalReminder>,
+    pub renewals: Vec<AutoRenewal>,
+}
+
+/// `end_date` extended by `term_months`, clamped to the end of shorter
+/// months (Jan 31 + 1 month is the last day of February)
+pub fn renewed_end_date(end_date: NaiveDate, term_months: i32) -> NaiveDate {
+    end_date
+        .checked_add_months(Months::new(term_months.max(1) as u32))
+        .unwrap_or(end_date)
+}
+
+pub fn reminder_message(contract: &ExpiringContract) -> String {
+    let outcome = if contract.auto_renew { "renews automatically" } else { "expires" };
+    format!(
+        "Contract {} for {} {} on {} ({} day(s) remaining)",
+        contract.contract_name,
+        contract.client_name,
+        outcome,
+        contract.end_date.format("%Y-%m-%d"),
+        contract.days_remaining
+    )
+}
+
+pub fn renewal_message(contract: &ExpiringContract, renewal: &ContractRenewal) -> String {
+    format!(
+        "Contract {} for {} was renewed automatically; it now ends on {}",
+        contract.contract_name,
+        contract.client_name,
+        renewal.new_end_date.format("%Y-%m-%d")
+    )
+}
+
+/// Active contracts ending between `today` and `today + max_days`, plus
+/// auto-renew contracts whose end date has already passed so a missed run
+/// still renews them
+pub async fn expiring_contracts(pool: &PgPool, today: NaiveDate, max_days: i32) -> Result<Vec<ExpiringContract>, sqlx::Error> {
+    sqlx::query_as::<_, ExpiringContract>(
+        r#"
+        SELECT ct.id AS contract_id, ct.name AS contract_name, ct.contract_type,
+               c.id AS client_id, c.name AS client_name, c.account_manager_id,
+               ct.end_date, (ct.end_date - $1)::int AS days_remaining,
+               COALESCE(ct.auto_renew, false) AS auto_renew, ct.renewal_term_months, ct.monthly_value
+        FROM contracts ct
+        JOIN clients c ON ct.client_id = c.id
+        WHERE ct.end_date <= $1 + $2::int
+            AND (ct.end_date >= $1 OR COALESCE(ct.auto_renew, false))
+            AND COALESCE(ct.status, 'active') = $3
+            AND c.archived_at IS NULL
+        ORDER BY ct.end_date ASC
+        "#
+    )
+    .bind(today)
+    .bind(max_days)
+    .bind(ACTIVE)
+    .fetch_all(pool)
+    .await
+}
+
+/// Extend an active contract by its renewal term and record the renewal.
+/// `renewed_by` is `None` for automatic renewals.
+pub async fn renew_contract(
+    pool: &PgPool,
+    contract_id: Uuid,
+    renewed_by: Option<Uuid>,
+    notes: Option<String>,
+) -> Result<ContractRenewal, RenewalError> {
+    let mut tx = pool.begin().await?;
+
+    let contract = sqlx::query_as::<_, (Option<NaiveDate>, i32, Option<String>)>(
+        "SELECT end_date, renewal_term_months, status FROM contracts WHERE id = $1 FOR UPDATE"
+    )
+    .bind(contract_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((end_date, term_months, status)) = contract else {
+        return Err(RenewalError::NotFound);
+    };
+    if status.as_deref().unwrap_or(ACTIVE) != ACTIVE {
+        return Err(RenewalError::NotActive);
+    }
+    let Some(end_date) = end_date else {
+        return Err(RenewalError::NoEndDate);
+    };
+    let new_end_date = renewed_end_date(end_date, term_months);
+
+    sqlx::query("UPDATE contracts SET end_date = $2, updated_at = NOW() WHERE id = $1")
+        .bind(contract_id)
+        .bind(new_end_date)
+        .execute(&mut *tx)
+        .await?;
+
+    let renewal = record_renewal(&mut tx, contract_id, end_date, new_end_date, renewed_by, notes).await?;
+
+    tx.commit().await?;
+    Ok(renewal)
+}
+
+/// Renew a contract the renewal job found ending on `contract.end_date`.
+/// The end date only moves if it is still that date, so a contract renewed
+/// by hand since the job read it isn't renewed twice; `None` when it was.
+async fn auto_renew(pool: &PgPool, contract: &ExpiringContract) -> Result<Option<ContractRenewal>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let new_end_date = renewed_end_date(contract.end_date, contract.renewal_term_months);
+
+    let renewed = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE contracts SET end_date = $3, updated_at = NOW()
+        WHERE id = $1 AND end_date = $2 AND COALESCE(status, $4) = $4
+        RETURNING id
+        "#
+    )
+    .bind(contract.contract_id)
+    .bind(contract.end_date)
+    .bind(new_end_date)
+    .bind(ACTIVE)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if renewed.is_none() {
+        return Ok(None);
+    }
+
+    let renewal = record_renewal(&mut tx, contract.contract_id, contract.end_date, new_end_date, None, None).await?;
+
+    tx.commit().await?;
+    Ok(Some(renewal))
+}
+
+async fn record_renewal(
+    conn: &mut PgConnection,
+    contract_id: Uuid,
+    previous_end_date: NaiveDate,
+    new_end_date: NaiveDate,
+    renewed_by: Option<Uuid>,
+    notes: Option<String>,
+) -> Result<ContractRenewal, sqlx::Error> {
+    sqlx::query_as::<_, ContractRenewal>(
+        r#"
+        INSERT INTO contract_renewals (contract_id, previous_end_date, new_end_date, automatic, renewed_by, notes)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, contract_id, previous_end_date, new_end_date, automatic, renewed_by, notes, created_at
+        "#
+    )
+    .bind(contract_id)
+    .bind(previous_end_date)
+    .bind(new_end_date)
+    .bind(renewed_by.is_none())
+    .bind(renewed_by)
+    .bind(notes)
+    .fetch_one(conn)
+    .await
+}
+
+/// A contract's renewal history, newest first
+pub async fn contract_renewals(pool: &PgPool, contract_id: Uuid) -> Result<Vec<ContractRenewal>, sqlx::Error> {
+    sqlx::query_as::<_, ContractRenewal>(
+        r#"
+        SELECT id, contract_id, previous_end_date, new_end_date, automatic, renewed_by, notes, created_at
+        FROM contract_renewals
+        WHERE contract_id = $1
+        ORDER BY created_at DESC
+        "#
+    )
+    .bind(contract_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Record the reminder for `threshold_days` unless it was already sent for
+/// this end date. Returns whether this call claimed it.
+async fn claim_reminder(pool: &PgPool, contract: &ExpiringContract, threshold_days: i32) -> Result<bool, sqlx::Error> {
+    let claimed = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO contract_renewal_reminders (contract_id, threshold_days, end_date)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (contract_id, threshold_days, end_date) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(contract.contract_id)
+    .bind(threshold_days)
+    .bind(contract.end_date)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(claimed.is_some())
+}
+
+/// The client's account manager, or its technicians when it has none
+async fn renewal_recipients(pool: &PgPool, contract: &ExpiringContract) -> Result<Vec<Uuid>, sqlx::Error> {
+    match contract.account_manager_id {
+        Some(manager_id) => Ok(vec![manager_id]),
+        None => client_technicians(pool, contract.client_id).await,
+    }
+}
+
+async fn notify(pool: &PgPool, contract: &ExpiringContract, title: String, message: String) -> Result<usize, sqlx::Error> {
+    let recipients = renewal_recipients(pool, contract).await?;
+    Ok(create_notifications_for_users(
+        pool,
+        recipients,
+        title,
+        message,
+        "contract_renewal".to_string(),
+        Some("contract".to_string()),
+        Some(contract.contract_id),
+    )
+    .await?
+    .len())
+}
+
+/// Renew auto-renew contracts within `auto_renew_days` of their end date
+/// and remind account managers of every other contract that has crossed a
+/// new threshold as of `today`
+pub async fn check_renewals(
+    pool: &PgPool,
+    today: NaiveDate,
+    thresholds: &[i32],
+    auto_renew_days: i32,
+) -> Result<RenewalCheck, RenewalError> {
+    let max_days = thresholds.iter().copied().max().unwrap_or(0).max(auto_renew_days);
+    let contracts = expiring_contracts(pool, today, max_days).await?;
+    let mut check = RenewalCheck { checked: contracts.len(), ..Default::default() };
+
+    for contract in contracts {
+        if contract.auto_renew && contract.days_remaining <= auto_renew_days {
+            let Some(renewal) = auto_renew(pool, &contract).await? else {
+                continue;
+            };
+            let notified = notify(
+                pool,
+                &contract,
+                format!("Contract renewed: {}", contract.contract_name),
+                renewal_message(&contract, &renewal),
+            )
+            .await?;
+            check.renewals.push(AutoRenewal { contract, renewal, notified });
+            continue;
+        }
+
+        let Some(threshold_days) = crossed_threshold(contract.days_remaining, thresholds) else {
+            continue;
+        };
+        if !claim_reminder(pool, &contract, threshold_days).await? {
+            continue;
+        }
+
+        let notified = notify(
+            pool,
+            &contract,
+            format!("Contract ending: {}", contract.contract_name),
+            reminder_message(&contract),
+        )
+        .await?;
+        check.reminders.push(RenewalReminder { contract, threshold_days, notified });
+    }
+
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn contract(auto_renew: bool) -> ExpiringContract {
+        ExpiringContract {
+            contract_id: Uuid::new_v4(),
+            contract_name: "Managed Services".to_string(),
+            contract_type: Some("yearly".to_string()),
+            client_id: Uuid::new_v4(),
+            client_name: "Acme Corp".to_string(),
+            account_manager_id: None,
+            end_date: date(2024, 6, 30),
+            days_remaining: 14,
+            auto_renew,
+            renewal_term_months: 12,
+            monthly_value: None,
+        }
+    }
+
+    #[test]
+    fn test_renewed_end_date_adds_the_term() {
+        assert_eq!(renewed_end_date(date(2024, 6, 30), 12), date(2025, 6, 30));
+        assert_eq!(renewed_end_date(date(2024, 6, 30), 3), date(2024, 9, 30));
+        assert_eq!(renewed_end_date(date(2024, 1, 31), 1), date(2024, 2, 29));
+        assert_eq!(renewed_end_date(date(2024, 6, 30), 0), date(2024, 7, 30));
+    }
+
+    #[test]
+    fn test_reminder_message_says_whether_it_renews() {
+        assert_eq!(
+            reminder_message(&contract(false)),
+            "Contract Managed Services for Acme Corp expires on 2024-06-30 (14 day(s) remaining)"
+        );
+        assert_eq!(
+            reminder_message(&contract(true)),
+            "Contract Managed Services for Acme Corp renews automatically on 2024-06-30 (14 day(s) remaining)"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Contract included-hours usage and overage
+//!
+//! A contract with `included_hours` gets that block of hours each billing
+//! cycle (a calendar month); billable time beyond it is overage, billed at
+//! the contract's `overage_rate` (or its `hourly_rate` when unset). Time is
+//! attributed to a client's contracts the same way time rounding picks a
+//! rule: the latest-starting active block contract covering the day the
+//! time was worked, so overlapping contracts never both count an hour.
+
+use chrono::{Datelike, Months, NaiveDate};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UsageError {
+    #[error("Contract not found")]
+    NotFound,
+    #[error("Contract has no included hours")]
+    NoIncludedHours,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Billable time and the block contract it counts against. `$1` is the
+/// contract.
+const ATTRIBUTED_TIME: &str = r#"
+    FROM time_entries te
+    LEFT JOIN tickets t ON te.ticket_id = t.id
+    LEFT JOIN projects p ON te.project_id = p.id
+    CROSS JOIN LATERAL (
+        SELECT ct.id FROM contracts ct
+        WHERE ct.client_id = COALESCE(t.client_id, p.client_id)
+          AND COALESCE(ct.status, 'active') = 'active'
+          AND ct.included_hours IS NOT NULL
+          AND ct.start_date <= te.start_time::date
+          AND (ct.end_date IS NULL OR ct.end_date >= te.start_time::date)
+        ORDER BY ct.start_date DESC, ct.id
+        LIMIT 1
+    ) covering
+    WHERE covering.id = $1
+      AND te.billable = true
+      AND te.end_time IS NOT NULL
+"#;
+
+/// The calendar month an allotment covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct BillingCycle {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl BillingCycle {
+    pub fn containing(date: NaiveDate) -> Self {
+        let start = date.with_day(1).unwrap_or(date);
+        let end = start
+            .checked_add_months(Months::new(1))
+            .and_then(|next| next.pred_opt())
+            .unwrap_or(start);
+        Self { start, end }
+    }
+
+    /// Parse a `YYYY-MM` period
+    pub fn parse(period: &str) -> Option<Self> {
+        let start = NaiveDate::parse_from_str(&format!("{}-01", period.trim()), "%Y-%m-%d").ok()?;
+        Some(Self::containing(start))
+    }
+
+    pub fn label(&self) -> String {
+        self.start.format("%b %Y").to_string()
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct BlockContract {
+    pub id: Uuid,
+    pub client_id: Uuid,
+    pub name: String,
+    pub included_hours: Option<i32>,
+    pub overage_rate: Option<Decimal>,
+    pub hourly_rate: Option<Decimal>,
+}
+
+impl BlockContract {
+    pub fn included(&self) -> Decimal {
+        Decimal::from(self.included_hours.unwrap_or(0).max(0))
+    }
+
+    /// Overage is billed at `overage_rate`, falling back to `hourly_rate`
+    pub fn overage_rate(&self) -> Option<Decimal> {
+        self.overage_rate.or(self.hourly_rate)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContractUsage {
+    pub contract_id: Uuid,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub included_hours: Decimal,
+    pub used_hours: Decimal,
+    pub remaining_hours: Decimal,
+    pub overage_hours: Decimal,
+    pub overage_rate: Option<Decimal>,
+    pub overage_amount: Decimal,
+}
+
+/// What a billing run owes for a contract's unbilled time in one cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractTimeCharge {
+    pub cycle: BillingCycle,
+    pub included_hours: Decimal,
+    pub overage_hours: Decimal,
+    pub overage_rate: Decimal,
+    pub overage_amount: Decimal,
+    pub entry_ids: Vec<Uuid>,
+}
+
+fn minutes_to_hours(minutes: i64) -> Decimal {
+    (Decimal::from(minutes) / Decimal::from(60)).round_dp(2)
+}
+
+/// Split `hours` into the part still covered by the block, given
+/// `used_before` hours already counted this cycle, and the overage
+pub fn allocate(included: Decimal, used_before: Decimal, hours: Decimal) -> (Decimal, Decimal) {
+    let remaining = (included - used_before).max(Decimal::ZERO);
+    let covered = hours.min(remaining);
+    (covered, hours - covered)
+}
+
+pub fn usage_for(contract: &BlockContract, cycle: BillingCycle, used_hours: Decimal) -> ContractUsage {
+    let included = contract.included();
+    let (_, overage_hours) = allocate(included, Decimal::ZERO, used_hours);
+    let overage_rate = contract.overage_rate();
+    ContractUsage {
+        contract_id: contract.id,
+        period_start: cycle.start,
+        period_end: cycle.end,
+        included_hours: included,
+        used_hours,
+        remaining_hours: (included - used_hours).max(Decimal::ZERO),
+        overage_hours,
+        overage_rate,
+        overage_amount: (overage_hours * overage_rate.unwrap_or(Decimal::ZERO)).round_dp(2),
+    }
+}
+
+pub async fn block_contract(conn: &mut PgConnection, contract_id: Uuid) -> Result<Option<BlockContract>, sqlx::Error> {
+    sqlx::query_as::<_, BlockContract>(
+        "SELECT id, client_id, name, included_hours, overage_rate, hourly_rate FROM contracts WHERE id = $1"
+    )
+    .bind(contract_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Billable minutes counted against the contract in `cycle`, optionally
+/// only those already billed
+async fn cycle_minutes(
+    conn: &mut PgConnection,
+    contract_id: Uuid,
+    cycle: BillingCycle,
+    billed_only: bool,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(&format!(
+        r#"
+        SELECT COALESCE(SUM(COALESCE(te.billable_minutes, te.duration_minutes)), 0)::bigint
+        {ATTRIBUTED_TIME}
+          AND te.start_time::date BETWEEN $2 AND $3
+          AND (te.billed = true OR NOT $4)
+        "#
+    ))
+    .bind(contract_id)
+    .bind(cycle.start)
+    .bind(cycle.end)
+    .bind(billed_only)
+    .fetch_one(conn)
+    .await
+}
+
+/// Hours used against a contract's allotment in `cycle`
+pub async fn contract_usage(pool: &PgPool, contract_id: Uuid, cycle: BillingCycle) -> Result<ContractUsage, UsageError> {
+    let mut conn = pool.acquire().await?;
+    let contract = block_contract(&mut conn, contract_id).await?.ok_or(UsageError::NotFound)?;
+    if contract.included_hours.is_none() {
+        return Err(UsageError::NoIncludedHours);
+    }
+
+    let minutes = cycle_minutes(&mut conn, contract_id, cycle, false).await?;
+    Ok(usage_for(&contract, cycle, minutes_to_hours(minutes)))
+}
+
+/// Charges for the contract's unbilled time, one per cycle it falls in.
+/// Time already billed in a cycle uses up its block first. Returns nothing
+/// for contracts without included hours or a rate to bill overage at, whose
+/// time bills as ordinary hourly work.
+pub async fn contract_time_charges(conn: &mut PgConnection, contract_id: Uuid) -> Result<Vec<ContractTimeCharge>, sqlx::Error> {
+    let Some(contract) = block_contract(conn, contract_id).await? else {
+        return Ok(Vec::new());
+    };
+    let (Some(_), Some(overage_rate)) = (contract.included_hours, contract.overage_rate()) else {
+        return Ok(Vec::new());
+    };
+
+    let entries = sqlx::query_as::<_, (Uuid, NaiveDate, i32)>(&format!(
+        r#"
+        SELECT te.id, te.start_time::date, COALESCE(te.billable_minutes, te.duration_minutes, 0)
+        {ATTRIBUTED_TIME}
+          AND te.billed = false
+        ORDER BY te.start_time
+        "#
+    ))
+    .bind(contract_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut cycles: BTreeMap<BillingCycle, (i64, Vec<Uuid>)> = BTreeMap::new();
+    for (id, worked_on, minutes) in entries {
+        let (total, ids) = cycles.entry(BillingCycle::containing(worked_on)).or_default();
+        *total += i64::from(minutes);
+        ids.push(id);
+    }
+
+    let mut charges = Vec::with_capacity(cycles.len());
+    for (cycle, (minutes, entry_ids)) in cycles {
+        let billed = cycle_minutes(conn, contract_id, cycle, true).await?;
+        let (included_hours, overage_hours) =
+            allocate(contract.included(), minutes_to_hours(billed), minutes_to_hours(minutes));
+        charges.push(ContractTimeCharge {
+            cycle,
+            included_hours,
+            overage_hours,
+            overage_rate,
+            overage_amount: (overage_hours * overage_rate).round_dp(2),
+            entry_ids,
+        });
+    }
+
+    Ok(charges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: i64) -> Decimal {
+        Decimal::from(value)
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_billing_cycle_is_the_calendar_month() {
+        let cycle = BillingCycle::containing(date(2024, 2, 17));
+        assert_eq!(cycle.start, date(2024, 2, 1));
+        assert_eq!(cycle.end, date(2024, 2, 29));
+        assert_eq!(BillingCycle::parse("2024-12"), Some(BillingCycle::containing(date(2024, 12, 31))));
+        assert_eq!(BillingCycle::parse("2024-13"), None);
+        assert_eq!(BillingCycle::parse("May"), None);
+        assert_eq!(cycle.label(), "Feb 2024");
+    }
+
+    #[test]
+    fn test_allocate_rolls_into_overage_once_the_block_is_used() {
+        assert_eq!(allocate(dec(10), dec(0), dec(4)), (dec(4), dec(0)));
+        assert_eq!(allocate(dec(10), dec(6), dec(7)), (dec(4), dec(3)));
+        assert_eq!(allocate(dec(10), dec(12), dec(2)), (dec(0), dec(2)));
+    }
+
+    #[test]
+    fn test_usage_reports_remaining_and_overage() {
+        let contract = BlockContract {
+            id: Uuid::new_v4(),
+            client_id: Uuid::new_v4(),
+            name: "Support Block".to_string(),
+            included_hours: Some(10),
+            overage_rate: None,
+            hourly_rate: Some(dec(120)),
+        };
+        let cycle = BillingCycle::containing(date(2024, 5, 1));
+
+        let under = usage_for(&contract, cycle, dec(6));
+        assert_eq!((under.remaining_hours, under.overage_hours, under.overage_amount), (dec(4), dec(0), dec(0)));
+
+        let over = usage_for(&contract, cycle, dec(13));
+        assert_eq!((over.remaining_hours, over.overage_hours), (dec(0), dec(3)));
+        assert_eq!(over.overage_rate, Some(dec(120)));
+        assert_eq!(over.overage_amount, dec(360));
+    }
+}
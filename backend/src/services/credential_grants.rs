@@ -0,0 +1,249 @@
+//! Time-limited access to stored passwords
+//!
+//! A password can be read by its creator or anyone whose role carries
+//! `passwords.read`. A grant lets one more user read it until `expires_at`
+//! without changing their role; once that passes, or the grant is revoked,
+//! the grant simply stops matching. Every read and every refused read is
+//! written to the audit trail under entity type `password`.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::audit::{self, AuditEvent, RequestMeta};
+
+pub const ENTITY_TYPE: &str = "password";
+/// Longest a single grant may run
+pub const MAX_GRANT_DAYS: i64 = 30;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GrantError {
+    #[error("Password not found")]
+    PasswordNotFound,
+    #[error("Grantee not found")]
+    GranteeNotFound,
+    #[error("expires_at must be in the future")]
+    ExpiryInPast,
+    #[error("Grants can last at most {MAX_GRANT_DAYS} days")]
+    ExpiryTooFar,
+    #[error("The password's owner doesn't need a grant")]
+    GranteeIsOwner,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct CredentialGrant {
+    pub id: Uuid,
+    pub password_id: Uuid,
+    pub grantee_id: Uuid,
+    pub granted_by: Uuid,
+    pub reason: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CredentialGrant {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+/// Why a read was allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessBasis {
+    Owner,
+    Permission,
+    Grant(Uuid),
+}
+
+impl AccessBasis {
+    fn describe(&self) -> serde_json::Value {
+        match self {
+            AccessBasis::Owner => serde_json::json!({ "via": "owner" }),
+            AccessBasis::Permission => serde_json::json!({ "via": "permission" }),
+            AccessBasis::Grant(grant_id) => serde_json::json!({ "via": "grant", "grant_id": grant_id }),
+        }
+    }
+}
+
+pub fn validate_expiry(expires_at: DateTime<Utc>, now: DateTime<Utc>) -> Result<(), GrantError> {
+    if expires_at <= now {
+        return Err(GrantError::ExpiryInPast);
+    }
+    if expires_at > now + Duration::days(MAX_GRANT_DAYS) {
+        return Err(GrantError::ExpiryTooFar);
+    }
+    Ok(())
+}
+
+/// The password's creator, or `None` when it doesn't exist
+pub async fn password_owner(pool: &PgPool, password_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT created_by FROM passwords WHERE id = $1")
+        .bind(password_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn active_grant(pool: &PgPool, password_id: Uuid, user_id: Uuid) -> Result<Option<CredentialGrant>, sqlx::Error> {
+    sqlx::query_as::<_, CredentialGrant>(
+        r#"
+        SELECT * FROM credential_grants
+        WHERE password_id = $1 AND grantee_id = $2
+            AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY expires_at DESC
+        LIMIT 1
+        "#
+    )
+    .bind(password_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Decide whether `user_id` may read the password, recording the read or
+/// the refusal. `has_permission` is the caller's `passwords.read` RBAC check.
+pub async fn authorize_read(
+    pool: &PgPool,
+    meta: &RequestMeta,
+    password_id: Uuid,
+    user_id: Uuid,
+    has_permission: bool,
+) -> Result<Option<AccessBasis>, GrantError> {
+    let owner = password_owner(pool, password_id).await?.ok_or(GrantError::PasswordNotFound)?;
+
+    let basis = if owner == user_id {
+        Some(AccessBasis::Owner)
+    } else if has_permission {
+        Some(AccessBasis::Permission)
+    } else {
+        active_grant(pool, password_id, user_id).await?.map(|grant| AccessBasis::Grant(grant.id))
+    };
+
+    let event = match basis {
+        Some(basis) => AuditEvent::new(user_id, "READ", ENTITY_TYPE, password_id).after(&basis.describe()),
+        None => AuditEvent::new(user_id, "ACCESS_DENIED", ENTITY_TYPE, password_id),
+    };
+    audit::record(pool, meta, event).await;
+
+    Ok(basis)
+}
+
+pub async fn create_grant(
+    pool: &PgPool,
+    meta: &RequestMeta,
+    password_id: Uuid,
+    grantee_id: Uuid,
+    granted_by: Uuid,
+    expires_at: DateTime<Utc>,
+    reason: Option<&str>,
+) -> Result<CredentialGrant, GrantError> {
+    validate_expiry(expires_at, Utc::now())?;
+
+    let owner = password_owner(pool, password_id).await?.ok_or(GrantError::PasswordNotFound)?;
+    if owner == grantee_id {
+        return Err(GrantError::GranteeIsOwner);
+    }
+
+    let grantee_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+        .bind(grantee_id)
+        .fetch_one(pool)
+        .await?;
+    if !grantee_exists {
+        return Err(GrantError::GranteeNotFound);
+    }
+
+    let grant = sqlx::query_as::<_, CredentialGrant>(
+        r#"
+        INSERT INTO credential_grants (password_id, grantee_id, granted_by, reason, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#
+    )
+    .bind(password_id)
+    .bind(grantee_id)
+    .bind(granted_by)
+    .bind(reason)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    audit::record(pool, meta, AuditEvent::new(granted_by, "GRANT", ENTITY_TYPE, password_id).after(&grant)).await;
+
+    Ok(grant)
+}
+
+pub async fn list_grants(pool: &PgPool, password_id: Uuid) -> Result<Vec<CredentialGrant>, sqlx::Error> {
+    sqlx::query_as::<_, CredentialGrant>(
+        "SELECT * FROM credential_grants WHERE password_id = $1 ORDER BY created_at DESC"
+    )
+    .bind(password_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Revoke a grant early. Returns false when there was no such unrevoked grant.
+pub async fn revoke_grant(
+    pool: &PgPool,
+    meta: &RequestMeta,
+    password_id: Uuid,
+    grant_id: Uuid,
+    revoked_by: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let revoked = sqlx::query_as::<_, CredentialGrant>(
+        r#"
+        UPDATE credential_grants SET revoked_at = NOW()
+        WHERE id = $1 AND password_id = $2 AND revoked_at IS NULL
+        RETURNING *
+        "#
+    )
+    .bind(grant_id)
+    .bind(password_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(grant) = revoked else {
+        return Ok(false);
+    };
+
+    audit::record(pool, meta, AuditEvent::new(revoked_by, "REVOKE_GRANT", ENTITY_TYPE, password_id).after(&grant)).await;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_expiry() {
+        let now = Utc::now();
+        assert!(validate_expiry(now + Duration::hours(4), now).is_ok());
+        assert!(matches!(validate_expiry(now, now), Err(GrantError::ExpiryInPast)));
+        assert!(matches!(
+            validate_expiry(now + Duration::days(MAX_GRANT_DAYS + 1), now),
+            Err(GrantError::ExpiryTooFar)
+        ));
+    }
+
+    #[test]
+    fn test_grant_is_active_until_expiry_or_revocation() {
+        let now = Utc::now();
+        let mut grant = CredentialGrant {
+            id: Uuid::new_v4(),
+            password_id: Uuid::new_v4(),
+            grantee_id: Uuid::new_v4(),
+            granted_by: Uuid::new_v4(),
+            reason: None,
+            expires_at: now + Duration::hours(1),
+            revoked_at: None,
+            created_at: now,
+        };
+        assert!(grant.is_active(now));
+        assert!(!grant.is_active(now + Duration::hours(2)));
+
+        grant.revoked_at = Some(now);
+        assert!(!grant.is_active(now));
+    }
+}
@@ -0,0 +1,422 @@
+//! Main dashboard statistics
+//!
+//! Aggregates for the landing dashboard, each section computed by one query
+//! and the sections run concurrently. A [`DashboardScope`] limits what the
+//! caller sees: financial figures are zeroed unless they may read invoices,
+//! time figures cover only their own entries unless they may approve the
+//! team's time, and every figure can be limited to a set of clients (an
+//! account manager's own clients). Time entries belong to a client through
+//! their ticket or project.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::services::invoice_payments::CLOSED_STATUSES;
+use crate::services::ticket_sla::RESOLVED_STATUSES;
+
+/// Warranties ending within this many days count as expiring
+pub const WARRANTY_WINDOW_DAYS: i64 = 30;
+/// Assets seen within this many minutes count as online
+pub const ONLINE_WINDOW_MINUTES: i64 = 15;
+/// How many clients are listed by revenue
+pub const TOP_CLIENTS: i64 = 5;
+
+#[derive(Debug, Serialize)]
+pub struct DashboardStats {
+    pub overview: OverviewStats,
+    pub tickets: TicketStats,
+    pub time: TimeStats,
+    pub invoices: InvoiceStats,
+    pub clients: ClientStats,
+    pub assets: AssetStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OverviewStats {
+    pub total_clients: i64,
+    pub active_tickets: i64,
+    /// Payments received this month
+    pub monthly_revenue: Decimal,
+    /// Hours of billable time not yet invoiced
+    pub unbilled_time: Decimal,
+    pub overdue_invoices: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TicketStats {
+    pub open: i64,
+    pub in_progress: i64,
+    pub pending: i64,
+    pub resolved_today: i64,
+    pub sla_breached: i64,
+    /// Mean hours to first response over the last 30 days
+    pub avg_response_time_hours: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimeStats {
+    pub hours_today: Decimal,
+    pub billable_hours_today: Decimal,
+    pub hours_this_week: Decimal,
+    pub active_timers: i64,
+    /// Billable share of this week's hours
+    pub team_utilization: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceStats {
+    pub outstanding_amount: Decimal,
+    pub overdue_amount: Decimal,
+    pub draft_count: i64,
+    pub paid_this_month: Decimal,
+    /// Share of the last 90 days' invoiced total that has been collected
+    pub collection_ratio: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClientStats {
+    pub total_clients: i64,
+    pub new_this_month: i64,
+    /// By payments received over the last 12 months
+    pub top_clients_by_revenue: Vec<TopClient>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TopClient {
+    pub name: String,
+    pub revenue: Decimal,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AssetStats {
+    pub total_assets: i64,
+    /// Unresolved critical alerts raised against assets
+    pub critical_alerts: i64,
+    pub warranty_expiring: i64,
+    pub online_percentage: Option<f64>,
+}
+
+/// What part of the data the caller may see
+#[derive(Debug, Clone, Default)]
+pub struct DashboardScope {
+    /// Include revenue, invoice and top-client figures
+    pub financials: bool,
+    /// Restrict time figures to this user's entries
+    pub time_user_id: Option<Uuid>,
+    /// Restrict every figure to these clients
+    pub client_ids: Option<Vec<Uuid>>,
+}
+
+impl DashboardScope {
+    /// Everything, for callers with full access
+    pub fn full() -> Self {
+        Self { financials: true, ..Default::default() }
+    }
+
+    fn clients(&self) -> Option<&[Uuid]> {
+        self.client_ids.as_deref()
+    }
+}
+
+/// Clients the user is account manager for
+pub async fn managed_clients(pool: &PgPool, user_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM clients WHERE account_manager_id = $1 AND archived_at IS NULL ORDER BY name"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Start of the day, week (Monday) and month containing `now`, in UTC
+struct Periods {
+    today: NaiveDate,
+    day_start: DateTime<Utc>,
+    week_start: DateTime<Utc>,
+    month_start: NaiveDate,
+}
+
+impl Periods {
+    fn new(now: DateTime<Utc>) -> Self {
+        let today = now.date_naive();
+        let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        Self {
+            today,
+            day_start: today.and_time(NaiveTime::MIN).and_utc(),
+            week_start: week_start.and_time(NaiveTime::MIN).and_utc(),
+            month_start: today.with_day(1).unwrap_or(today),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct ClientCounts {
+    total_clients: i64,
+    new_this_month: i64,
+}
+
+async fn client_counts(pool: &PgPool, periods: &Periods, scope: &DashboardScope) -> Result<ClientCounts, sqlx::Error> {
+    sqlx::query_as::<_, ClientCounts>(
+        r#"
+        SELECT COUNT(*) as total_clients,
+               COUNT(*) FILTER (WHERE created_at >= $1) as new_this_month
+        FROM clients
+        WHERE archived_at IS NULL AND ($2::uuid[] IS NULL OR id = ANY($2))
+        "#
+    )
+    .bind(periods.month_start.and_time(NaiveTime::MIN).and_utc())
+    .bind(scope.clients())
+    .fetch_one(pool)
+    .await
+}
+
+#[derive(FromRow)]
+struct TicketCounts {
+    active: i64,
+    #[sqlx(flatten)]
+    stats: TicketStats,
+}
+
+async fn ticket_stats(pool: &PgPool, periods: &Periods, now: DateTime<Utc>, scope: &DashboardScope) -> Result<TicketCounts, sqlx::Error> {
+    sqlx::query_as::<_, TicketCounts>(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE NOT resolved) as active,
+            COUNT(*) FILTER (WHERE COALESCE(status, 'open') = 'open') as open,
+            COUNT(*) FILTER (WHERE status = 'in_progress') as in_progress,
+            COUNT(*) FILTER (WHERE status = 'pending') as pending,
+            COUNT(*) FILTER (WHERE resolved AND resolved_at >= $2) as resolved_today,
+            COUNT(*) FILTER (WHERE NOT resolved AND (
+                COALESCE(sla_breached, false)
+                OR sla_resolution_due < $3
+                OR (sla_response_due < $3 AND sla_response_at IS NULL)
+            )) as sla_breached,
+            (AVG(EXTRACT(EPOCH FROM (sla_response_at - created_at)) / 3600.0)
+                FILTER (WHERE sla_response_at IS NOT NULL AND created_at >= $3 - INTERVAL '30 days'))::float8
+                as avg_response_time_hours
+        FROM (
+            SELECT t.*, COALESCE(t.status, 'open') = ANY($1) as resolved
+            FROM tickets t
+            WHERE $4::uuid[] IS NULL OR t.client_id = ANY($4)
+        ) t
+        "#
+    )
+    .bind(RESOLVED_STATUSES)
+    .bind(periods.day_start)
+    .bind(now)
+    .bind(scope.clients())
+    .fetch_one(pool)
+    .await
+}
+
+#[derive(FromRow)]
+struct TimeTotals {
+    hours_today: Decimal,
+    billable_hours_today: Decimal,
+    hours_this_week: Decimal,
+    billable_hours_this_week: Decimal,
+    unbilled_hours: Decimal,
+    active_timers: i64,
+}
+
+async fn time_totals(pool: &PgPool, periods: &Periods, scope: &DashboardScope) -> Result<TimeTotals, sqlx::Error> {
+    sqlx::query_as::<_, TimeTotals>(
+        r#"
+        SELECT
+            ROUND(COALESCE(SUM(duration_minutes) FILTER (WHERE start_time >= $1), 0) / 60.0, 2) as hours_today,
+            ROUND(COALESCE(SUM(duration_minutes) FILTER (WHERE start_time >= $1 AND billable), 0) / 60.0, 2)
+                as billable_hours_today,
+            ROUND(COALESCE(SUM(duration_minutes) FILTER (WHERE start_time >= $2), 0) / 60.0, 2) as hours_this_week,
+            ROUND(COALESCE(SUM(duration_minutes) FILTER (WHERE start_time >= $2 AND billable), 0) / 60.0, 2)
+                as billable_hours_this_week,
+            ROUND(COALESCE(SUM(COALESCE(billable_minutes, duration_minutes))
+                FILTER (WHERE billable AND NOT billed AND end_time IS NOT NULL), 0) / 60.0, 2) as unbilled_hours,
+            COUNT(*) FILTER (WHERE end_time IS NULL) as active_timers
+        FROM (
+            SELECT e.duration_minutes, e.billable_minutes, e.start_time, e.end_time,
+                   COALESCE(e.billable, true) as billable, COALESCE(e.billed, false) as billed
+            FROM time_entries e
+            LEFT JOIN tickets t ON t.id = e.ticket_id
+            LEFT JOIN projects p ON p.id = e.project_id
+            WHERE ($3::uuid IS NULL OR e.user_id = $3)
+              AND ($4::uuid[] IS NULL OR COALESCE(t.client_id, p.client_id) = ANY($4))
+        ) te
+        "#
+    )
+    .bind(periods.day_start)
+    .bind(periods.week_start)
+    .bind(scope.time_user_id)
+    .bind(scope.clients())
+    .fetch_one(pool)
+    .await
+}
+
+#[derive(Default, FromRow)]
+struct InvoiceTotals {
+    outstanding_amount: Decimal,
+    overdue_amount: Decimal,
+    overdue_count: i64,
+    draft_count: i64,
+    paid_this_month: Decimal,
+    collection_ratio: Option<f64>,
+}
+
+async fn invoice_totals(pool: &PgPool, periods: &Periods, scope: &DashboardScope) -> Result<InvoiceTotals, sqlx::Error> {
+    sqlx::query_as::<_, InvoiceTotals>(
+        r#"
+        SELECT
+            COALESCE(SUM(balance) FILTER (WHERE unpaid), 0) as outstanding_amount,
+            COALESCE(SUM(balance) FILTER (WHERE unpaid AND due_date < $2), 0) as overdue_amount,
+            COUNT(*) FILTER (WHERE unpaid AND due_date < $2) as overdue_count,
+            COUNT(*) FILTER (WHERE status = 'draft') as draft_count,
+            (SELECT COALESCE(SUM(p.amount), 0) FROM payments p
+             JOIN invoices pi ON pi.id = p.invoice_id
+             WHERE p.payment_date >= $3 AND ($4::uuid[] IS NULL OR pi.client_id = ANY($4))) as paid_this_month,
+            (SUM(total - balance) FILTER (WHERE issued AND date >= $2 - 90)
+                / NULLIF(SUM(total) FILTER (WHERE issued AND date >= $2 - 90), 0))::float8 as collection_ratio
+        FROM (
+            SELECT COALESCE(status, 'draft') as status, date, due_date,
+                   COALESCE(total, 0) as total, COALESCE(balance, 0) as balance,
+                   COALESCE(status, 'draft') <> 'draft' AND COALESCE(status, 'draft') NOT IN ('void', 'cancelled') as issued,
+                   COALESCE(status, 'draft') <> 'draft' AND NOT (COALESCE(status, 'draft') = ANY($1))
+                       AND COALESCE(balance, 0) > 0 as unpaid
+            FROM invoices
+            WHERE $4::uuid[] IS NULL OR client_id = ANY($4)
+        ) i
+        "#
+    )
+    .bind(CLOSED_STATUSES)
+    .bind(periods.today)
+    .bind(periods.month_start)
+    .bind(scope.clients())
+    .fetch_one(pool)
+    .await
+}
+
+async fn top_clients(pool: &PgPool, periods: &Periods, scope: &DashboardScope) -> Result<Vec<TopClient>, sqlx::Error> {
+    sqlx::query_as::<_, TopClient>(
+        r#"
+        SELECT c.name, SUM(p.amount) as revenue
+        FROM payments p
+        JOIN invoices i ON i.id = p.invoice_id
+        JOIN clients c ON c.id = i.client_id
+        WHERE p.payment_date > $1 - INTERVAL '12 months'
+          AND ($3::uuid[] IS NULL OR c.id = ANY($3))
+        GROUP BY c.id, c.name
+        ORDER BY revenue DESC, c.name
+        LIMIT $2
+        "#
+    )
+    .bind(periods.today)
+    .bind(TOP_CLIENTS)
+    .bind(scope.clients())
+    .fetch_all(pool)
+    .await
+}
+
+async fn asset_stats(pool: &PgPool, periods: &Periods, now: DateTime<Utc>, scope: &DashboardScope) -> Result<AssetStats, sqlx::Error> {
+    sqlx::query_as::<_, AssetStats>(
+        r#"
+        SELECT
+            COUNT(*) as total_assets,
+            (SELECT COUNT(*) FROM alerts al
+             JOIN assets a ON a.id = al.asset_id AND a.archived_at IS NULL
+             WHERE al.severity = 'critical' AND al.resolved_at IS NULL
+               AND ($4::uuid[] IS NULL OR a.client_id = ANY($4))) as critical_alerts,
+            COUNT(*) FILTER (WHERE warranty_expire >= $1 AND warranty_expire <= $2) as warranty_expiring,
+            ((COUNT(*) FILTER (WHERE last_seen >= $3))::float8 * 100.0
+                / NULLIF(COUNT(*) FILTER (WHERE last_seen IS NOT NULL), 0)) as online_percentage
+        FROM assets
+        WHERE archived_at IS NULL AND ($4::uuid[] IS NULL OR client_id = ANY($4))
+        "#
+    )
+    .bind(periods.today)
+    .bind(periods.today + Duration::days(WARRANTY_WINDOW_DAYS))
+    .bind(now - Duration::minutes(ONLINE_WINDOW_MINUTES))
+    .bind(scope.clients())
+    .fetch_one(pool)
+    .await
+}
+
+/// All dashboard figures as of `now`
+pub async fn dashboard_stats(pool: &PgPool, scope: DashboardScope, now: DateTime<Utc>) -> Result<DashboardStats, sqlx::Error> {
+    let periods = Periods::new(now);
+
+    let invoices = async {
+        if scope.financials {
+            invoice_totals(pool, &periods, &scope).await
+        } else {
+            Ok(InvoiceTotals::default())
+        }
+    };
+    let top = async {
+        if scope.financials {
+            top_clients(pool, &periods, &scope).await
+        } else {
+            Ok(Vec::new())
+        }
+    };
+
+    let (clients, tickets, time, invoices, top, assets) = tokio::try_join!(
+        client_counts(pool, &periods, &scope),
+        ticket_stats(pool, &periods, now, &scope),
+        time_totals(pool, &periods, &scope),
+        invoices,
+        top,
+        asset_stats(pool, &periods, now, &scope),
+    )?;
+
+    let team_utilization = (time.hours_this_week > Decimal::ZERO).then(|| {
+        use rust_decimal::prelude::ToPrimitive;
+        (time.billable_hours_this_week / time.hours_this_week).to_f64().unwrap_or(0.0)
+    });
+    Ok(DashboardStats {
+        overview: OverviewStats {
+            total_clients: clients.total_clients,
+            active_tickets: tickets.active,
+            monthly_revenue: invoices.paid_this_month,
+            unbilled_time: time.unbilled_hours,
+            overdue_invoices: invoices.overdue_count,
+        },
+        tickets: tickets.stats,
+        time: TimeStats {
+            hours_today: time.hours_today,
+            billable_hours_today: time.billable_hours_today,
+            hours_this_week: time.hours_this_week,
+            active_timers: time.active_timers,
+            team_utilization,
+        },
+        invoices: InvoiceStats {
+            outstanding_amount: invoices.outstanding_amount,
+            overdue_amount: invoices.overdue_amount,
+            draft_count: invoices.draft_count,
+            paid_this_month: invoices.paid_this_month,
+            collection_ratio: invoices.collection_ratio,
+        },
+        clients: ClientStats {
+            total_clients: clients.total_clients,
+            new_this_month: clients.new_this_month,
+            top_clients_by_revenue: top,
+        },
+        assets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_periods() {
+        // Thursday
+        let now = Utc.with_ymd_and_hms(2024, 5, 16, 14, 30, 0).unwrap();
+        let periods = Periods::new(now);
+        assert_eq!(periods.day_start, Utc.with_ymd_and_hms(2024, 5, 16, 0, 0, 0).unwrap());
+        assert_eq!(periods.week_start, Utc.with_ymd_and_hms(2024, 5, 13, 0, 0, 0).unwrap());
+        assert_eq!(periods.month_start, NaiveDate::from_ymd_opt(2024, 5, 1).unwrap());
+    }
+}
@@ -0,0 +1,133 @@
+//! Live dashboard updates
+//!
+//! Handlers publish the events below on the WebSocket event bus; each
+//! dashboard stream subscribes to the bus and turns bursts of relevant
+//! events into a single refresh. After the first event of a burst the
+//! stream waits out the debounce window, drains whatever else arrived and
+//! yields the distinct event types once, so a flurry of changes costs one
+//! stats query per subscriber rather than one per change.
+//!
+//! The bus is a bounded broadcast channel. A subscriber that falls behind
+//! (a slow client the stream isn't being polled for) loses the oldest
+//! messages rather than holding them; the stream treats that as a refresh,
+//! since fresh stats make up for whatever was missed.
+
+use futures::stream::{self, Stream};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::{RecvError, TryRecvError}};
+
+use crate::websocket::WsMessage;
+
+pub const TICKET_CREATED: &str = "ticket.created";
+pub const TICKET_CLOSED: &str = "ticket.closed";
+pub const INVOICE_PAID: &str = "invoice.paid";
+pub const TIMER_STARTED: &str = "timer.started";
+pub const TIMER_STOPPED: &str = "timer.stopped";
+
+/// Bus events that change the dashboard figures
+pub const DASHBOARD_EVENTS: &[&str] = &[TICKET_CREATED, TICKET_CLOSED, INVOICE_PAID, TIMER_STARTED, TIMER_STOPPED];
+
+/// Reported as the cause of a refresh when the subscriber fell behind
+pub const LAGGED: &str = "lagged";
+
+/// How long to gather events before refreshing
+pub const DEBOUNCE: Duration = Duration::from_secs(1);
+/// Comment line sent to idle streams to keep proxies from closing them
+pub const HEARTBEAT: Duration = Duration::from_secs(15);
+
+pub fn is_dashboard_event(event_type: &str) -> bool {
+    DASHBOARD_EVENTS.contains(&event_type)
+}
+
+/// One item per burst of dashboard events, holding the distinct event types
+/// in the order first seen. Ends when the bus closes.
+pub fn refreshes(
+    receiver: broadcast::Receiver<WsMessage>,
+    debounce: Duration,
+) -> impl Stream<Item = Vec<String>> + Send {
+    stream::unfold(receiver, move |mut receiver| async move {
+        let mut events = Vec::new();
+        while events.is_empty() {
+            match receiver.recv().await {
+                Ok(message) => note(&mut events, message),
+                Err(RecvError::Lagged(_)) => add(&mut events, LAGGED),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+
+        tokio::time::sleep(debounce).await;
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => note(&mut events, message),
+                Err(TryRecvError::Lagged(_)) => add(&mut events, LAGGED),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+
+        Some((events, receiver))
+    })
+}
+
+fn note(events: &mut Vec<String>, message: WsMessage) {
+    if is_dashboard_event(&message.event_type) {
+        add(events, &message.event_type);
+    }
+}
+
+fn add(events: &mut Vec<String>, event_type: &str) {
+    if !events.iter().any(|e| e == event_type) {
+        events.push(event_type.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn message(event_type: &str) -> WsMessage {
+        WsMessage {
+            event_type: event_type.to_string(),
+            payload: serde_json::json!({}),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_burst_becomes_one_refresh() {
+        let (sender, receiver) = broadcast::channel(16);
+        let mut refreshes = Box::pin(refreshes(receiver, Duration::from_millis(50)));
+
+        sender.send(message("kb_article_published")).unwrap();
+        sender.send(message(TICKET_CREATED)).unwrap();
+        sender.send(message(TIMER_STARTED)).unwrap();
+        sender.send(message(TICKET_CREATED)).unwrap();
+
+        assert_eq!(refreshes.next().await.unwrap(), vec![TICKET_CREATED, TIMER_STARTED]);
+
+        sender.send(message(INVOICE_PAID)).unwrap();
+        assert_eq!(refreshes.next().await.unwrap(), vec![INVOICE_PAID]);
+
+        drop(sender);
+        assert!(refreshes.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_falling_behind_still_refreshes() {
+        let (sender, receiver) = broadcast::channel(2);
+        let mut refreshes = Box::pin(refreshes(receiver, Duration::from_millis(10)));
+
+        for _ in 0..5 {
+            sender.send(message(TICKET_CLOSED)).unwrap();
+        }
+
+        assert_eq!(refreshes.next().await.unwrap(), vec![LAGGED, TICKET_CLOSED]);
+    }
+
+    #[test]
+    fn test_only_dashboard_events_count() {
+        assert!(is_dashboard_event(TICKET_CREATED));
+        assert!(is_dashboard_event(TIMER_STOPPED));
+        assert!(!is_dashboard_event("pong"));
+    }
+}
@@ -0,0 +1,273 @@
+//! Data retention and anonymization
+//!
+//! Each entity type in `retention_policies` has a limit in days that the
+//! `data_retention` job enforces: audit logs and notifications past it are
+//! deleted, and closed tickets have their text scrubbed instead, since time
+//! entries and invoices still point at them. Audit entries recording an
+//! anonymization are kept as the proof that the erasure happened.
+//!
+//! [`anonymize_client`] scrubs the personal data of a client and its
+//! contacts for erasure requests. Invoices, payments and time entries are
+//! left alone so the books still balance, and nothing is kept that would
+//! let the change be undone.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+/// What closed tickets' text is replaced with
+pub const REDACTED: &str = "[Redacted]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionEntity {
+    AuditLogs,
+    ClosedTickets,
+    Notifications,
+}
+
+impl RetentionEntity {
+    pub const ALL: [RetentionEntity; 3] =
+        [RetentionEntity::AuditLogs, RetentionEntity::ClosedTickets, RetentionEntity::Notifications];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetentionEntity::AuditLogs => "audit_logs",
+            RetentionEntity::ClosedTickets => "closed_tickets",
+            RetentionEntity::Notifications => "notifications",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|entity| entity.as_str() == s)
+    }
+
+    /// What happens to records past the limit
+    pub fn action(&self) -> &'static str {
+        match self {
+            RetentionEntity::ClosedTickets => "anonymize",
+            RetentionEntity::AuditLogs | RetentionEntity::Notifications => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct RetentionPolicy {
+    pub entity_type: String,
+    pub retention_days: i32,
+    pub enabled: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Records deleted or anonymized by the last run
+    pub last_affected: Option<i32>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One policy applied by [`apply_policies`]
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionOutcome {
+    pub entity_type: RetentionEntity,
+    pub action: &'static str,
+    pub affected: u64,
+}
+
+const POLICY_COLUMNS: &str = "entity_type, retention_days, enabled, last_run_at, last_affected, updated_at";
+
+pub async fn list_policies(pool: &PgPool) -> Result<Vec<RetentionPolicy>, sqlx::Error> {
+    sqlx::query_as::<_, RetentionPolicy>(&format!(
+        "SELECT {} FROM retention_policies ORDER BY entity_type",
+        POLICY_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn update_policy(
+    pool: &PgPool,
+    entity: RetentionEntity,
+    retention_days: i32,
+    enabled: bool,
+) -> Result<RetentionPolicy, sqlx::Error> {
+    sqlx::query_as::<_, RetentionPolicy>(&format!(
+        "INSERT INTO retention_policies (entity_type, retention_days, enabled)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (entity_type) DO UPDATE
+            SET retention_days = EXCLUDED.retention_days, enabled = EXCLUDED.enabled, updated_at = NOW()
+         RETURNING {}",
+        POLICY_COLUMNS
+    ))
+    .bind(entity.as_str())
+    .bind(retention_days)
+    .bind(enabled)
+    .fetch_one(pool)
+    .await
+}
+
+/// Apply every enabled policy, recording on each when it last ran and what
+/// it touched
+pub async fn apply_policies(pool: &PgPool) -> Result<Vec<RetentionOutcome>, sqlx::Error> {
+    let policies = list_policies(pool).await?;
+    let mut outcomes = Vec::new();
+
+    for policy in policies.iter().filter(|p| p.enabled) {
+        let Some(entity) = RetentionEntity::parse(&policy.entity_type) else {
+            continue;
+        };
+        let affected = apply_policy(pool, entity, policy.retention_days).await?;
+
+        sqlx::query("UPDATE retention_policies SET last_run_at = NOW(), last_affected = $2 WHERE entity_type = $1")
+            .bind(entity.as_str())
+            .bind(affected as i32)
+            .execute(pool)
+            .await?;
+
+        outcomes.push(RetentionOutcome { entity_type: entity, action: entity.action(), affected });
+    }
+
+    Ok(outcomes)
+}
+
+async fn apply_policy(pool: &PgPool, entity: RetentionEntity, retention_days: i32) -> Result<u64, sqlx::Error> {
+    let affected = match entity {
+        RetentionEntity::AuditLogs => sqlx::query(
+            "DELETE FROM audit_logs
+             WHERE created_at < NOW() - make_interval(days => $1) AND action <> 'ANONYMIZE'",
+        )
+        .bind(retention_days)
+        .execute(pool)
+        .await?
+        .rows_affected(),
+        RetentionEntity::Notifications => sqlx::query(
+            "DELETE FROM notifications WHERE created_at < NOW() - make_interval(days => $1)",
+        )
+        .bind(retention_days)
+        .execute(pool)
+        .await?
+        .rows_affected(),
+        RetentionEntity::ClosedTickets => {
+            let mut tx = pool.begin().await?;
+            let tickets: Vec<Uuid> = sqlx::query_scalar(
+                "UPDATE tickets SET subject = $2, details = $2, contact_id = NULL, anonymized_at = NOW()
+                 WHERE status = 'closed' AND anonymized_at IS NULL
+                   AND COALESCE(closed_at, updated_at) < NOW() - make_interval(days => $1)
+                 RETURNING id",
+            )
+            .bind(retention_days)
+            .bind(REDACTED)
+            .fetch_all(&mut *tx)
+            .await?;
+            sqlx::query("UPDATE ticket_replies SET details = $2 WHERE ticket_id = ANY($1)")
+                .bind(&tickets)
+                .bind(REDACTED)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            tickets.len() as u64
+        }
+    };
+
+    Ok(affected)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnonymizeError {
+    #[error("Client not found")]
+    ClientNotFound,
+    #[error("Client was already anonymized")]
+    AlreadyAnonymized,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnonymizedClient {
+    pub client_id: Uuid,
+    pub contacts_anonymized: u64,
+    pub anonymized_at: DateTime<Utc>,
+}
+
+/// The name an anonymized client is listed under, still telling clients
+/// apart on old invoices
+pub fn anonymized_client_name(client_id: Uuid) -> String {
+    format!("Anonymized client {}", &client_id.simple().to_string()[..8])
+}
+
+/// Scrub the client's and its contacts' personal data, including the
+/// snapshots earlier audit entries hold of them. Run on the request's
+/// transaction; the client row stays locked until it ends.
+pub async fn anonymize_client(conn: &mut PgConnection, client_id: Uuid) -> Result<AnonymizedClient, AnonymizeError> {
+    let anonymized_at: Option<DateTime<Utc>> = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        "SELECT anonymized_at FROM clients WHERE id = $1 FOR UPDATE",
+    )
+    .bind(client_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AnonymizeError::ClientNotFound)?;
+    if anonymized_at.is_some() {
+        return Err(AnonymizeError::AlreadyAnonymized);
+    }
+
+    let anonymized_at: DateTime<Utc> = sqlx::query_scalar(
+        "UPDATE clients SET
+            name = $2, email = NULL, phone = NULL, address = NULL, city = NULL, state = NULL,
+            zip = NULL, billing_address = NULL, notes = NULL,
+            anonymized_at = NOW(), updated_at = NOW()
+         WHERE id = $1
+         RETURNING anonymized_at",
+    )
+    .bind(client_id)
+    .bind(anonymized_client_name(client_id))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let contacts_anonymized = sqlx::query(
+        "UPDATE contacts SET
+            name = 'Anonymized contact', title = NULL, email = NULL, phone = NULL, extension = NULL,
+            mobile = NULL, department = NULL, notes = NULL,
+            anonymized_at = $2, updated_at = NOW()
+         WHERE client_id = $1",
+    )
+    .bind(client_id)
+    .bind(anonymized_at)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    // Earlier audit entries carry snapshots of the client and its contacts;
+    // keep the entries, drop what they copied
+    sqlx::query(
+        r#"
+        UPDATE audit_logs SET
+            old_values = CASE WHEN old_values IS NULL THEN NULL ELSE '{"redacted": true}'::jsonb END,
+            new_values = CASE WHEN new_values IS NULL THEN NULL ELSE '{"redacted": true}'::jsonb END
+        WHERE (entity_type = 'client' AND entity_id = $1)
+           OR (entity_type = 'contact' AND entity_id IN (SELECT id FROM contacts WHERE client_id = $1))
+        "#
+    )
+    .bind(client_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(AnonymizedClient { client_id, contacts_anonymized, anonymized_at })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entities_round_trip() {
+        for entity in RetentionEntity::ALL {
+            assert_eq!(RetentionEntity::parse(entity.as_str()), Some(entity));
+        }
+        assert_eq!(RetentionEntity::parse("invoices"), None);
+        assert_eq!(RetentionEntity::ClosedTickets.action(), "anonymize");
+        assert_eq!(RetentionEntity::AuditLogs.action(), "delete");
+    }
+
+    #[test]
+    fn test_anonymized_name_has_no_personal_data() {
+        let id = Uuid::parse_str("3f2b8c1e-0000-4000-8000-000000000000").unwrap();
+        assert_eq!(anonymized_client_name(id), "Anonymized client 3f2b8c1e");
+    }
+}
@@ -0,0 +1,513 @@
+//! DNS record validation and live verification
+//!
+//! `domains.dns_records` holds a JSON array of typed records:
+//!
+//! ```json
+//! [
+//!   { "type": "A", "name": "@", "value": "203.0.113.10", "ttl": 3600 },
+//!   { "type": "MX", "name": "@", "value": "mail.example.com", "priority": 10 }
+//! ]
+//! ```
+//!
+//! Names are relative to the domain (`@` is the apex); a trailing dot marks
+//! a fully-qualified name. Records are validated on write and can be
+//! compared against what public DNS actually serves.
+
+use async_trait::async_trait;
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::TokioAsyncResolver;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DnsRecordType {
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Txt,
+}
+
+impl DnsRecordType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DnsRecordType::A => "A",
+            DnsRecordType::Aaaa => "AAAA",
+            DnsRecordType::Cname => "CNAME",
+            DnsRecordType::Mx => "MX",
+            DnsRecordType::Txt => "TXT",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "A" => Some(DnsRecordType::A),
+            "AAAA" => Some(DnsRecordType::Aaaa),
+            "CNAME" => Some(DnsRecordType::Cname),
+            "MX" => Some(DnsRecordType::Mx),
+            "TXT" => Some(DnsRecordType::Txt),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "UPPERCASE")]
+pub enum DnsRecord {
+    A { name: String, value: Ipv4Addr, ttl: Option<u32> },
+    Aaaa { name: String, value: Ipv6Addr, ttl: Option<u32> },
+    Cname { name: String, value: String, ttl: Option<u32> },
+    Mx { name: String, value: String, priority: u16, ttl: Option<u32> },
+    Txt { name: String, value: String, ttl: Option<u32> },
+}
+
+impl DnsRecord {
+    pub fn record_type(&self) -> DnsRecordType {
+        match self {
+            DnsRecord::A { .. } => DnsRecordType::A,
+            DnsRecord::Aaaa { .. } => DnsRecordType::Aaaa,
+            DnsRecord::Cname { .. } => DnsRecordType::Cname,
+            DnsRecord::Mx { .. } => DnsRecordType::Mx,
+            DnsRecord::Txt { .. } => DnsRecordType::Txt,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            DnsRecord::A { name, .. }
+            | DnsRecord::Aaaa { name, .. }
+            | DnsRecord::Cname { name, .. }
+            | DnsRecord::Mx { name, .. }
+            | DnsRecord::Txt { name, .. } => name,
+        }
+    }
+
+    /// Value in the form lookups return it, for comparison
+    pub fn normalized_value(&self) -> String {
+        match self {
+            DnsRecord::A { value, .. } => value.to_string(),
+            DnsRecord::Aaaa { value, .. } => value.to_string(),
+            DnsRecord::Cname { value, .. } => normalize_host(value),
+            DnsRecord::Mx { value, priority, .. } => format!("{} {}", priority, normalize_host(value)),
+            DnsRecord::Txt { value, .. } => value.clone(),
+        }
+    }
+}
+
+pub type FieldErrors = HashMap<String, Vec<String>>;
+
+fn normalize_host(host: &str) -> String {
+    host.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Absolute name for a record `name` relative to `domain`
+pub fn qualify_name(name: &str, domain: &str) -> String {
+    let name = name.trim();
+    let domain = normalize_host(domain);
+
+    if name.is_empty() || name == "@" {
+        domain
+    } else if name.ends_with('.') {
+        normalize_host(name)
+    } else {
+        format!("{}.{}", name.to_ascii_lowercase(), domain)
+    }
+}
+
+fn is_valid_hostname(host: &str, allow_wildcard: bool) -> bool {
+    let host = host.trim_end_matches('.');
+    if host.is_empty() || host.len() > 253 {
+        return false;
+    }
+
+    host.split('.').enumerate().all(|(i, label)| {
+        (allow_wildcard && i == 0 && label == "*")
+            || (!label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    })
+}
+
+fn parse_record(field: &str, value: &Value, errors: &mut FieldErrors) -> Option<DnsRecord> {
+    let Some(object) = value.as_object() else {
+        errors.entry(field.to_string()).or_default().push("must be an object".to_string());
+        return None;
+    };
+
+    let mut error = |suffix: &str, message: &str| {
+        errors
+            .entry(format!("{}.{}", field, suffix))
+            .or_default()
+            .push(message.to_string());
+    };
+
+    let record_type = match object.get("type").and_then(Value::as_str) {
+        Some(t) => match DnsRecordType::parse(t) {
+            Some(t) => Some(t),
+            None => {
+                error("type", "must be one of A, AAAA, CNAME, MX, TXT");
+                None
+            }
+        },
+        None => {
+            error("type", "is required");
+            None
+        }
+    };
+
+    let name = match object.get("name").and_then(Value::as_str).map(str::trim) {
+        Some(name) if name == "@" || is_valid_hostname(name, true) => Some(name.to_string()),
+        Some(_) => {
+            error("name", "must be @ or a valid host name");
+            None
+        }
+        None => {
+            error("name", "is required");
+            None
+        }
+    };
+
+    let ttl = match object.get("ttl") {
+        None | Some(Value::Null) => Some(None),
+        Some(ttl) => match ttl.as_u64().filter(|t| (1..=u32::MAX as u64).contains(t)) {
+            Some(ttl) => Some(Some(ttl as u32)),
+            None => {
+                error("ttl", "must be a positive integer");
+                None
+            }
+        },
+    };
+
+    let raw_value = object.get("value").and_then(Value::as_str).map(str::trim);
+    if raw_value.is_none() {
+        error("value", "is required");
+    }
+
+    let (record_type, name, ttl, raw_value) = (record_type?, name?, ttl?, raw_value?);
+
+    match record_type {
+        DnsRecordType::A => match raw_value.parse::<Ipv4Addr>() {
+            Ok(value) => Some(DnsRecord::A { name, value, ttl }),
+            Err(_) => {
+                error("value", "must be an IPv4 address");
+                None
+            }
+        },
+        DnsRecordType::Aaaa => match raw_value.parse::<Ipv6Addr>() {
+            Ok(value) => Some(DnsRecord::Aaaa { name, value, ttl }),
+            Err(_) => {
+                error("value", "must be an IPv6 address");
+                None
+            }
+        },
+        DnsRecordType::Cname => {
+            if is_valid_hostname(raw_value, false) {
+                Some(DnsRecord::Cname { name, value: raw_value.to_string(), ttl })
+            } else {
+                error("value", "must be a valid host name");
+                None
+            }
+        }
+        DnsRecordType::Mx => {
+            let priority = match object.get("priority").and_then(Value::as_u64) {
+                Some(p) if p <= u16::MAX as u64 => Some(p as u16),
+                Some(_) => {
+                    error("priority", "must be between 0 and 65535");
+                    None
+                }
+                None => {
+                    error("priority", "is required for MX records");
+                    None
+                }
+            };
+            if !is_valid_hostname(raw_value, false) {
+                error("value", "must be a valid host name");
+                return None;
+            }
+            Some(DnsRecord::Mx { name, value: raw_value.to_string(), priority: priority?, ttl })
+        }
+        DnsRecordType::Txt => {
+            if raw_value.is_empty() {
+                error("value", "must not be empty");
+                None
+            } else {
+                Some(DnsRecord::Txt { name, value: raw_value.to_string(), ttl })
+            }
+        }
+    }
+}
+
+/// Validate a `dns_records` payload, collecting every problem keyed by
+/// field path (e.g. `dns_records[2].value`). `null` and the legacy empty
+/// object are treated as no records.
+pub fn parse_dns_records(value: &Value) -> Result<Vec<DnsRecord>, FieldErrors> {
+    let items = match value {
+        Value::Null => return Ok(Vec::new()),
+        Value::Object(map) if map.is_empty() => return Ok(Vec::new()),
+        Value::Array(items) => items,
+        _ => {
+            let mut errors = FieldErrors::new();
+            errors.insert("dns_records".to_string(), vec!["must be an array of records".to_string()]);
+            return Err(errors);
+        }
+    };
+
+    let mut errors = FieldErrors::new();
+    let records: Vec<DnsRecord> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| parse_record(&format!("dns_records[{}]", i), item, &mut errors))
+        .collect();
+
+    if errors.is_empty() {
+        Ok(records)
+    } else {
+        Err(errors)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("DNS lookup failed: {0}")]
+pub struct DnsLookupError(pub String);
+
+/// Source of live DNS answers; abstracted so verification can be tested
+#[async_trait]
+pub trait DnsLookup: Send + Sync {
+    /// Normalized values for `name`, empty when no such records exist
+    async fn lookup(&self, name: &str, record_type: DnsRecordType) -> Result<Vec<String>, DnsLookupError>;
+}
+
+pub struct HickoryLookup {
+    resolver: TokioAsyncResolver,
+}
+
+impl HickoryLookup {
+    pub fn from_system_conf() -> Result<Self, DnsLookupError> {
+        TokioAsyncResolver::tokio_from_system_conf()
+            .map(|resolver| Self { resolver })
+            .map_err(|e| DnsLookupError(e.to_string()))
+    }
+}
+
+fn lookup_error(e: ResolveError) -> Result<Vec<String>, DnsLookupError> {
+    match e.kind() {
+        ResolveErrorKind::NoRecordsFound { .. } => Ok(Vec::new()),
+        _ => Err(DnsLookupError(e.to_string())),
+    }
+}
+
+#[async_trait]
+impl DnsLookup for HickoryLookup {
+    async fn lookup(&self, name: &str, record_type: DnsRecordType) -> Result<Vec<String>, DnsLookupError> {
+        // Fully-qualified so the system search domains aren't appended
+        let fqdn = format!("{}.", name.trim_end_matches('.'));
+        let query_type = match record_type {
+            DnsRecordType::A => RecordType::A,
+            DnsRecordType::Aaaa => RecordType::AAAA,
+            DnsRecordType::Cname => RecordType::CNAME,
+            DnsRecordType::Mx => RecordType::MX,
+            DnsRecordType::Txt => RecordType::TXT,
+        };
+
+        let lookup = match self.resolver.lookup(fqdn.as_str(), query_type).await {
+            Ok(lookup) => lookup,
+            Err(e) => return lookup_error(e),
+        };
+
+        // Answers can include the CNAME chain; keep only the requested type
+        Ok(lookup
+            .iter()
+            .filter_map(|rdata| match (record_type, rdata) {
+                (DnsRecordType::A, RData::A(a)) => Some(a.to_string()),
+                (DnsRecordType::Aaaa, RData::AAAA(aaaa)) => Some(aaaa.to_string()),
+                (DnsRecordType::Cname, RData::CNAME(cname)) => Some(normalize_host(&cname.to_string())),
+                (DnsRecordType::Mx, RData::MX(mx)) => {
+                    Some(format!("{} {}", mx.preference(), normalize_host(&mx.exchange().to_string())))
+                }
+                (DnsRecordType::Txt, RData::TXT(txt)) => Some(
+                    txt.txt_data()
+                        .iter()
+                        .map(|chunk| String::from_utf8_lossy(chunk))
+                        .collect::<String>(),
+                ),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftStatus {
+    InSync,
+    /// Live DNS serves different values
+    Drifted,
+    /// Nothing is served for this name and type
+    Missing,
+    LookupFailed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordDrift {
+    pub record_type: DnsRecordType,
+    pub name: String,
+    pub stored: Vec<String>,
+    pub actual: Vec<String>,
+    pub status: DriftStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Compare stored records with live DNS, one entry per name and type
+pub async fn verify_records(lookup: &dyn DnsLookup, domain: &str, records: &[DnsRecord]) -> Vec<RecordDrift> {
+    let mut groups: Vec<((DnsRecordType, String), BTreeSet<String>)> = Vec::new();
+    for record in records {
+        let key = (record.record_type(), qualify_name(record.name(), domain));
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => {
+                values.insert(record.normalized_value());
+            }
+            None => groups.push((key, BTreeSet::from([record.normalized_value()]))),
+        }
+    }
+
+    let mut drift = Vec::with_capacity(groups.len());
+    for ((record_type, name), stored) in groups {
+        let stored: Vec<String> = stored.into_iter().collect();
+
+        let (actual, status, error) = match lookup.lookup(&name, record_type).await {
+            Ok(actual) => {
+                let actual: Vec<String> = actual.into_iter().collect::<BTreeSet<_>>().into_iter().collect();
+                let status = if actual.is_empty() {
+                    DriftStatus::Missing
+                } else if actual == stored {
+                    DriftStatus::InSync
+                } else {
+                    DriftStatus::Drifted
+                };
+                (actual, status, None)
+            }
+            Err(e) => (Vec::new(), DriftStatus::LookupFailed, Some(e.to_string())),
+        };
+
+        drift.push(RecordDrift { record_type, name, stored, actual, status, error });
+    }
+
+    drift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct MockLookup {
+        answers: HashMap<(String, DnsRecordType), Vec<String>>,
+    }
+
+    impl MockLookup {
+        fn with(mut self, name: &str, record_type: DnsRecordType, values: &[&str]) -> Self {
+            self.answers.insert(
+                (name.to_string(), record_type),
+                values.iter().map(|v| v.to_string()).collect(),
+            );
+            self
+        }
+    }
+
+    #[async_trait]
+    impl DnsLookup for MockLookup {
+        async fn lookup(&self, name: &str, record_type: DnsRecordType) -> Result<Vec<String>, DnsLookupError> {
+            if name.starts_with("broken.") {
+                return Err(DnsLookupError("SERVFAIL".to_string()));
+            }
+            Ok(self.answers.get(&(name.to_string(), record_type)).cloned().unwrap_or_default())
+        }
+    }
+
+    #[test]
+    fn test_parses_valid_records() {
+        let records = parse_dns_records(&json!([
+            { "type": "A", "name": "@", "value": "203.0.113.10", "ttl": 3600 },
+            { "type": "aaaa", "name": "www", "value": "2001:db8::1" },
+            { "type": "CNAME", "name": "portal", "value": "acme.example.net." },
+            { "type": "MX", "name": "@", "value": "mail.acme.com", "priority": 10 },
+            { "type": "TXT", "name": "_dmarc", "value": "v=DMARC1; p=none" }
+        ]))
+        .unwrap();
+
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[1].record_type(), DnsRecordType::Aaaa);
+        assert_eq!(records[3].normalized_value(), "10 mail.acme.com");
+        assert!(parse_dns_records(&json!({})).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_malformed_records_with_field_errors() {
+        let errors = parse_dns_records(&json!([
+            { "type": "A", "name": "@", "value": "203.0.113.300" },
+            { "type": "MX", "name": "@", "value": "mail.acme.com" },
+            { "type": "SRV", "name": "bad name!", "value": "x" },
+            "not a record"
+        ]))
+        .unwrap_err();
+
+        assert!(errors.contains_key("dns_records[0].value"));
+        assert!(errors.contains_key("dns_records[1].priority"));
+        assert!(errors.contains_key("dns_records[2].type"));
+        assert!(errors.contains_key("dns_records[2].name"));
+        assert!(errors.contains_key("dns_records[3]"));
+
+        assert!(parse_dns_records(&json!({ "a": "1.2.3.4" })).unwrap_err().contains_key("dns_records"));
+    }
+
+    #[test]
+    fn test_qualify_name() {
+        assert_eq!(qualify_name("@", "Acme.com"), "acme.com");
+        assert_eq!(qualify_name("www", "acme.com."), "www.acme.com");
+        assert_eq!(qualify_name("mail.other.net.", "acme.com"), "mail.other.net");
+    }
+
+    #[tokio::test]
+    async fn test_detects_drift_against_live_dns() {
+        let records = parse_dns_records(&json!([
+            { "type": "A", "name": "@", "value": "203.0.113.10" },
+            { "type": "A", "name": "@", "value": "203.0.113.11" },
+            { "type": "A", "name": "www", "value": "203.0.113.10" },
+            { "type": "MX", "name": "@", "value": "Mail.Acme.com.", "priority": 10 },
+            { "type": "TXT", "name": "@", "value": "v=spf1 -all" },
+            { "type": "CNAME", "name": "broken", "value": "elsewhere.net" }
+        ]))
+        .unwrap();
+
+        let lookup = MockLookup::default()
+            .with("acme.com", DnsRecordType::A, &["203.0.113.11", "203.0.113.10"])
+            .with("www.acme.com", DnsRecordType::A, &["198.51.100.7"])
+            .with("acme.com", DnsRecordType::Mx, &["10 mail.acme.com"]);
+
+        let drift = verify_records(&lookup, "acme.com", &records).await;
+        let status = |name: &str, t: DnsRecordType| drift.iter().find(|d| d.name == name && d.record_type == t).unwrap();
+
+        assert_eq!(drift.len(), 5);
+        assert_eq!(status("acme.com", DnsRecordType::A).status, DriftStatus::InSync);
+
+        let www = status("www.acme.com", DnsRecordType::A);
+        assert_eq!(www.status, DriftStatus::Drifted);
+        assert_eq!(www.stored, vec!["203.0.113.10"]);
+        assert_eq!(www.actual, vec!["198.51.100.7"]);
+
+        assert_eq!(status("acme.com", DnsRecordType::Mx).status, DriftStatus::InSync);
+        assert_eq!(status("acme.com", DnsRecordType::Txt).status, DriftStatus::Missing);
+
+        let broken = status("broken.acme.com", DnsRecordType::Cname);
+        assert_eq!(broken.status, DriftStatus::LookupFailed);
+        assert!(broken.error.is_some());
+    }
+}
@@ -11,8 +11,8 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::time::{interval, timeout};
 use tracing::{error, info, warn, debug};
 use uuid::Uuid;
-use trust_dns_resolver::{Resolver, config::*};
-use trust_dns_resolver::proto::rr::{RecordType, RData};
+use hickory_resolver::{Resolver, config::*};
+use hickory_resolver::proto::rr::{RecordType, RData};
 
 #[derive(Debug, Clone)]
 pub struct DomainSslMonitorService {
@@ -22,6 +22,14 @@ pub struct EmailTemplate {
     pub text_body: Option<String>,
 }
 
+/// A file sent along with an email
+#[derive(Debug, Clone)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TicketNotificationTemplate {
     pub ticket_number: i32,
@@ -109,6 +117,61 @@ impl EmailService {
         }
     }
 
+    pub async fn send_email_with_attachment(
+        &self,
+        to_email: &str,
+        to_name: Option<&str>,
+        subject: &str,
+        html_body: &str,
+        text_body: &str,
+        attachment: &EmailAttachment,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let from = format!("{} <{}>", self.from_name, self.from_email)
+            .parse::<Mailbox>()?;
+
+        let to = if let Some(name) = to_name {
+            format!("{} <{}>", name, to_email).parse::<Mailbox>()?
+        } else {
+            to_email.parse::<Mailbox>()?
+        };
+
+        let file = lettre::message::Attachment::new(attachment.filename.clone())
+            .body(attachment.content.clone(), ContentType::parse(&attachment.content_type)?);
+
+        let message = Message::builder()
+            .from(from)
+            .to(to)
+            .subject(subject)
+            .multipart(
+                lettre::message::MultiPart::mixed()
+                    .multipart(
+                        lettre::message::MultiPart::alternative()
+                            .singlepart(
+                                lettre::message::SinglePart::builder()
+                                    .header(ContentType::TEXT_PLAIN)
+                                    .body(text_body.to_string()),
+                            )
+                            .singlepart(
+                                lettre::message::SinglePart::builder()
+                                    .header(ContentType::TEXT_HTML)
+                                    .body(html_body.to_string()),
+                            ),
+                    )
+                    .singlepart(file),
+            )?;
+
+        match self.transport.send(message).await {
+            Ok(_) => {
+                info!("Email with attachment {} sent to {}", attachment.filename, to_email);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to send email to {}: {}", to_email, e);
+                Err(Box::new(e))
+            }
+        }
+    }
+
     // Template for new ticket notifications
     pub fn ticket_created_template(&self, data: &TicketNotificationTemplate) -> EmailTemplate {
         let subject = format!("New Ticket #{} - {}", data.ticket_number, data.subject);
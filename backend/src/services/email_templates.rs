@@ -0,0 +1,455 @@
+//! Outbound email templates
+//!
+//! Notification emails are rendered from `email_templates` rows looked up
+//! by slug, with `{{variable}}` placeholders filled from the caller's
+//! context. When a slug has no active row the built-in default below is
+//! used, so a deleted or never-seeded template doesn't stop mail going out.
+//! Placeholders without a value render as nothing and are reported.
+//!
+//! Every template also gets `brand.*` variables: the defaults come from
+//! `EMAIL_BRAND_*` and a client's row in `client_email_branding` overrides
+//! them field by field. `{{brand.logo}}` expands to the logo image, or the
+//! company name when there's no logo.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::services::canned_response_render::{merge_unresolved, render_or_blank, TemplateVariables};
+use crate::services::EmailService;
+
+pub const TICKET_UPDATED: &str = "ticket_updated";
+pub const INVOICE_SENT: &str = "invoice_sent";
+pub const INVOICE_REMINDER: &str = "invoice_reminder";
+
+const DEFAULT_PRIMARY_COLOR: &str = "#2563eb";
+const DEFAULT_ACCENT_COLOR: &str = "#f8fafc";
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("No email template named '{0}'")]
+    Unknown(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Unrendered subject and bodies
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct TemplateSource {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: Option<String>,
+}
+
+/// Where a rendered email's template came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateOrigin {
+    Stored,
+    BuiltIn,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: Option<String>,
+    /// Placeholders that had no value
+    pub missing: Vec<String>,
+    pub origin: TemplateOrigin,
+}
+
+/// A client's overrides; unset fields fall back to the defaults
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ClientBranding {
+    pub client_id: Uuid,
+    pub company_name: Option<String>,
+    pub logo_url: Option<String>,
+    pub primary_color: Option<String>,
+    pub accent_color: Option<String>,
+    pub footer_text: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branding {
+    pub company_name: String,
+    pub logo_url: Option<String>,
+    pub primary_color: String,
+    pub accent_color: String,
+    pub footer_text: Option<String>,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            company_name: "Resolve".to_string(),
+            logo_url: None,
+            primary_color: DEFAULT_PRIMARY_COLOR.to_string(),
+            accent_color: DEFAULT_ACCENT_COLOR.to_string(),
+            footer_text: None,
+        }
+    }
+}
+
+fn env_value(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+impl Branding {
+    /// Defaults from `EMAIL_BRAND_NAME`, `EMAIL_BRAND_LOGO_URL`,
+    /// `EMAIL_BRAND_PRIMARY_COLOR`, `EMAIL_BRAND_ACCENT_COLOR` and
+    /// `EMAIL_BRAND_FOOTER`. Invalid colours are ignored.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            company_name: env_value("EMAIL_BRAND_NAME").unwrap_or(defaults.company_name),
+            logo_url: env_value("EMAIL_BRAND_LOGO_URL"),
+            primary_color: env_value("EMAIL_BRAND_PRIMARY_COLOR").filter(|c| is_valid_color(c)).unwrap_or(defaults.primary_color),
+            accent_color: env_value("EMAIL_BRAND_ACCENT_COLOR").filter(|c| is_valid_color(c)).unwrap_or(defaults.accent_color),
+            footer_text: env_value("EMAIL_BRAND_FOOTER"),
+        }
+    }
+
+    pub fn with_overrides(mut self, overrides: &ClientBranding) -> Self {
+        let set = |value: &Option<String>| value.as_ref().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        if let Some(name) = set(&overrides.company_name) {
+            self.company_name = name;
+        }
+        if let Some(logo) = set(&overrides.logo_url) {
+            self.logo_url = Some(logo);
+        }
+        if let Some(color) = set(&overrides.primary_color).filter(|c| is_valid_color(c)) {
+            self.primary_color = color;
+        }
+        if let Some(color) = set(&overrides.accent_color).filter(|c| is_valid_color(c)) {
+            self.accent_color = color;
+        }
+        if let Some(footer) = set(&overrides.footer_text) {
+            self.footer_text = Some(footer);
+        }
+        self
+    }
+
+    fn apply(&self, variables: &mut TemplateVariables) {
+        let footer = self.footer_text.clone().unwrap_or_else(|| self.company_name.clone());
+        variables
+            .set("brand.company_name", Some(self.company_name.as_str()))
+            .set("brand.logo_url", self.logo_url.as_deref())
+            .set("brand.primary_color", Some(self.primary_color.as_str()))
+            .set("brand.accent_color", Some(self.accent_color.as_str()))
+            .set("brand.footer", Some(footer));
+        // Older templates use the bare name
+        if variables.get("company_name").is_none() {
+            variables.set("company_name", Some(self.company_name.as_str()));
+        }
+    }
+
+    /// Markup for `{{brand.logo}}`; inserted before rendering because the
+    /// renderer escapes values
+    fn logo_html(&self) -> String {
+        let name = escape_attr(&self.company_name);
+        match &self.logo_url {
+            Some(url) => format!(r#"<img src="{}" alt="{}" style="max-height:48px;">"#, escape_attr(url), name),
+            None => format!(r#"<div style="font-size:14px;font-weight:bold;">{}</div>"#, name),
+        }
+    }
+}
+
+fn escape_attr(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// `#rgb` or `#rrggbb`. Colours are placed into inline CSS, so nothing
+/// else is accepted.
+pub fn is_valid_color(value: &str) -> bool {
+    let Some(hex) = value.strip_prefix('#') else {
+        return false;
+    };
+    matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn layout(title: &str, content: &str) -> String {
+    format!(
+        r#"<html>
+<body style="margin:0;padding:20px;background:#f5f5f5;font-family:Arial,sans-serif;">
+  <div style="max-width:600px;margin:0 auto;background:#ffffff;border-radius:8px;overflow:hidden;">
+    <div style="background:{{{{brand.primary_color}}}};color:#ffffff;padding:20px;text-align:center;">
+      {{{{brand.logo}}}}
+      <h1 style="margin:8px 0 0;font-size:22px;">{title}</h1>
+    </div>
+    <div style="padding:30px;">
+{content}
+    </div>
+    <div style="background:{{{{brand.accent_color}}}};padding:16px;text-align:center;color:#666666;font-size:13px;">{{{{brand.footer}}}}</div>
+  </div>
+</body>
+</html>"#
+    )
+}
+
+/// Defaults used when a slug has no active row. The seeded rows start out
+/// identical to these.
+pub fn builtin_template(slug: &str) -> Option<TemplateSource> {
+    let source = match slug {
+        TICKET_UPDATED => TemplateSource {
+            subject: "[Ticket #{{ticket_number}}] Update: {{subject}}".to_string(),
+            html_body: layout(
+                "Ticket Updated",
+                r#"      <p>Hello {{client_name}},</p>
+      <p>Your ticket #{{ticket_number}} has been updated.</p>
+      <p><strong>Status:</strong> {{status}}</p>
+      <p>{{update_message}}</p>
+      <p><a href="{{portal_url}}" style="color:{{brand.primary_color}};">View ticket</a></p>"#,
+            ),
+            text_body: Some(
+                "Your ticket #{{ticket_number}} has been updated.\n\nStatus: {{status}}\n\n{{update_message}}\n\nView ticket: {{portal_url}}"
+                    .to_string(),
+            ),
+        },
+        INVOICE_SENT => TemplateSource {
+            subject: "Invoice #{{invoice_number}} from {{brand.company_name}}".to_string(),
+            html_body: layout(
+                "Invoice",
+                r#"      <p>Hello {{client_name}},</p>
+      <p>Your invoice for {{description}} is ready.</p>
+      <p><strong>Invoice #:</strong> {{invoice_number}}<br>
+         <strong>Date:</strong> {{invoice_date}}<br>
+         <strong>Due Date:</strong> {{due_date}}</p>
+      <p style="font-size:22px;font-weight:bold;color:{{brand.primary_color}};">Amount Due: ${{amount}}</p>
+      <p><a href="{{portal_url}}" style="color:{{brand.primary_color}};">View invoice</a></p>"#,
+            ),
+            text_body: Some(
+                "Invoice #{{invoice_number}} - Amount Due: ${{amount}}\n\nDue Date: {{due_date}}\n\nView invoice: {{portal_url}}"
+                    .to_string(),
+            ),
+        },
+        INVOICE_REMINDER => TemplateSource {
+            subject: "Payment reminder: invoice #{{invoice_number}} ({{reminder_status}})".to_string(),
+            html_body: layout(
+                "Payment Reminder",
+                r#"      <p>Hello {{client_name}},</p>
+      <p>Invoice <strong>#{{invoice_number}}</strong> for <strong>${{amount}}</strong> is due on {{due_date}} ({{reminder_status}}).</p>
+      <p>{{reminder_message}}</p>
+      <p>If you have already paid, please disregard this reminder.</p>
+      <p><a href="{{portal_url}}" style="color:{{brand.primary_color}};">View invoice</a></p>"#,
+            ),
+            text_body: Some(
+                "Invoice #{{invoice_number}} for ${{amount}} is due on {{due_date}} ({{reminder_status}}).\n\n{{reminder_message}}\n\nView invoice: {{portal_url}}"
+                    .to_string(),
+            ),
+        },
+        _ => return None,
+    };
+    Some(source)
+}
+
+/// Render `source` with `variables` and `branding`. Values are escaped in
+/// the HTML body and inserted as-is in the subject and text body.
+pub fn render_source(source: &TemplateSource, branding: &Branding, variables: &TemplateVariables, origin: TemplateOrigin) -> RenderedEmail {
+    let mut variables = variables.clone();
+    branding.apply(&mut variables);
+
+    let html_template = source.html_body.replace("{{brand.logo}}", &branding.logo_html());
+    let subject = render_or_blank(&source.subject, &variables, false);
+    let html = render_or_blank(&html_template, &variables, true);
+    let text = source.text_body.as_deref().map(|body| render_or_blank(body, &variables, false));
+
+    let missing = merge_unresolved([&subject, &html].into_iter().chain(text.as_ref()));
+    RenderedEmail {
+        subject: subject.text.trim().to_string(),
+        html_body: html.text,
+        text_body: text.map(|t| t.text),
+        missing,
+        origin,
+    }
+}
+
+pub async fn load_template(pool: &PgPool, slug: &str) -> Result<Option<TemplateSource>, sqlx::Error> {
+    sqlx::query_as::<_, TemplateSource>(
+        "SELECT subject, html_body, text_body FROM email_templates WHERE slug = $1 AND is_active = true"
+    )
+    .bind(slug)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn client_branding(pool: &PgPool, client_id: Uuid) -> Result<Option<ClientBranding>, sqlx::Error> {
+    sqlx::query_as::<_, ClientBranding>("SELECT * FROM client_email_branding WHERE client_id = $1")
+        .bind(client_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// The branding mail to `client_id` goes out with
+pub async fn branding_for(pool: &PgPool, client_id: Option<Uuid>) -> Result<Branding, sqlx::Error> {
+    let branding = Branding::from_env();
+    let Some(client_id) = client_id else {
+        return Ok(branding);
+    };
+    Ok(match client_branding(pool, client_id).await? {
+        Some(overrides) => branding.with_overrides(&overrides),
+        None => branding,
+    })
+}
+
+/// Render the template named `slug` for a client
+pub async fn render(
+    pool: &PgPool,
+    slug: &str,
+    client_id: Option<Uuid>,
+    variables: &TemplateVariables,
+) -> Result<RenderedEmail, TemplateError> {
+    let (source, origin) = match load_template(pool, slug).await? {
+        Some(source) => (source, TemplateOrigin::Stored),
+        None => (builtin_template(slug).ok_or_else(|| TemplateError::Unknown(slug.to_string()))?, TemplateOrigin::BuiltIn),
+    };
+
+    let branding = branding_for(pool, client_id).await?;
+    let rendered = render_source(&source, &branding, variables, origin);
+    if !rendered.missing.is_empty() {
+        tracing::warn!("Email template '{}' rendered without: {}", slug, rendered.missing.join(", "));
+    }
+    Ok(rendered)
+}
+
+/// Render and send, logging rather than returning failures. For
+/// notifications that shouldn't fail the request that triggered them.
+pub async fn send(
+    pool: &PgPool,
+    slug: &str,
+    client_id: Option<Uuid>,
+    to_email: &str,
+    to_name: Option<&str>,
+    variables: &TemplateVariables,
+) {
+    let rendered = match render(pool, slug, client_id, variables).await {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            tracing::error!("Failed to render email template '{}': {}", slug, e);
+            return;
+        }
+    };
+
+    let config = match Config::from_env() {
+        Ok(config) if config.smtp.is_configured() => config,
+        _ => {
+            tracing::warn!("SMTP not configured; not sending '{}' email", slug);
+            return;
+        }
+    };
+    let email_service = match EmailService::new(&config.smtp).await {
+        Ok(service) => service,
+        Err(e) => {
+            tracing::error!("Failed to initialise email service: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = email_service
+        .send_email(to_email, to_name, &rendered.subject, &rendered.html_body, rendered.text_body.as_deref())
+        .await
+    {
+        tracing::error!("Failed to send '{}' email: {}", slug, e);
+    }
+}
+
+/// Links in notification emails point at the client portal
+pub fn portal_link(path: &str) -> String {
+    let base = std::env::var("PORTAL_BASE_URL").unwrap_or_else(|_| "https://app.example.com".to_string());
+    format!("{}/{}", base.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket_variables() -> TemplateVariables {
+        let mut vars = TemplateVariables::new();
+        vars.set("ticket_number", Some("1042"))
+            .set("subject", Some("Printer <offline>"))
+            .set("status", Some("resolved"))
+            .set("client_name", Some("Acme"))
+            .set("portal_url", Some("https://portal.example/tickets/1"));
+        vars
+    }
+
+    #[test]
+    fn test_missing_variable_renders_blank_and_is_reported() {
+        let source = builtin_template(TICKET_UPDATED).unwrap();
+        let rendered = render_source(&source, &Branding::default(), &ticket_variables(), TemplateOrigin::BuiltIn);
+
+        assert_eq!(rendered.subject, "[Ticket #1042] Update: Printer <offline>");
+        assert!(rendered.html_body.contains("<strong>Status:</strong> resolved"));
+        assert!(!rendered.html_body.contains("{{"));
+        assert!(!rendered.text_body.as_deref().unwrap().contains("{{update_message}}"));
+        assert_eq!(rendered.missing, vec!["update_message"]);
+    }
+
+    #[test]
+    fn test_html_values_are_escaped_but_subject_is_not() {
+        let source = TemplateSource {
+            subject: "{{subject}}".to_string(),
+            html_body: "<p>{{subject}}</p>".to_string(),
+            text_body: None,
+        };
+        let rendered = render_source(&source, &Branding::default(), &ticket_variables(), TemplateOrigin::Stored);
+        assert_eq!(rendered.subject, "Printer <offline>");
+        assert_eq!(rendered.html_body, "<p>Printer &lt;offline&gt;</p>");
+    }
+
+    #[test]
+    fn test_builtin_defaults_exist_for_wired_templates() {
+        for slug in [TICKET_UPDATED, INVOICE_SENT, INVOICE_REMINDER] {
+            let source = builtin_template(slug).unwrap_or_else(|| panic!("{}", slug));
+            assert!(source.html_body.contains("{{brand.logo}}"));
+            assert!(source.html_body.contains("{{brand.primary_color}}"));
+        }
+        assert!(builtin_template("no_such_template").is_none());
+    }
+
+    #[test]
+    fn test_client_branding_overrides_defaults() {
+        let overrides = ClientBranding {
+            client_id: Uuid::new_v4(),
+            company_name: Some("Acme IT".to_string()),
+            logo_url: Some("https://cdn.example/logo.png".to_string()),
+            primary_color: Some("#ff0000".to_string()),
+            accent_color: Some("red; background:url(x)".to_string()),
+            footer_text: None,
+            updated_at: None,
+        };
+        let branding = Branding::default().with_overrides(&overrides);
+        assert_eq!(branding.company_name, "Acme IT");
+        assert_eq!(branding.primary_color, "#ff0000");
+        // Invalid colours keep the default
+        assert_eq!(branding.accent_color, DEFAULT_ACCENT_COLOR);
+
+        let source = builtin_template(INVOICE_SENT).unwrap();
+        let rendered = render_source(&source, &branding, &TemplateVariables::new(), TemplateOrigin::BuiltIn);
+        assert!(rendered.html_body.contains(r#"<img src="https://cdn.example/logo.png" alt="Acme IT""#));
+        assert!(rendered.html_body.contains("background:#ff0000"));
+        assert_eq!(rendered.subject, "Invoice # from Acme IT");
+    }
+
+    #[test]
+    fn test_logo_falls_back_to_company_name() {
+        let rendered = render_source(
+            &TemplateSource { subject: String::new(), html_body: "{{brand.logo}}".to_string(), text_body: None },
+            &Branding::default(),
+            &TemplateVariables::new(),
+            TemplateOrigin::BuiltIn,
+        );
+        assert!(rendered.html_body.contains(">Resolve</div>"));
+    }
+
+    #[test]
+    fn test_is_valid_color() {
+        assert!(is_valid_color("#fff"));
+        assert!(is_valid_color("#2563EB"));
+        assert!(!is_valid_color("2563eb"));
+        assert!(!is_valid_color("#12345"));
+        assert!(!is_valid_color("red"));
+    }
+}
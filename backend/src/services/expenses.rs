@@ -0,0 +1,263 @@
+//! Expense approval and billing
+//!
+//! Expenses are submitted, then approved or rejected by someone holding
+//! `expenses.approve` other than whoever submitted them. Submitted and
+//! rejected expenses can still be edited, and editing a rejected one submits
+//! it again. Reimbursed expenses are settled and never change. Approved
+//! billable expenses
+//! are picked up by invoices created from time and by recurring runs whose
+//! template includes unbilled expenses; each becomes a line at its amount
+//! plus markup and the expense is marked billed against the invoice.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use resolve_shared::ExpenseStatus;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+pub const EXPENSE_COLUMNS: &str = "id, client_id, project_id, vendor_id, vendor, category, description,
+    amount, tax_amount, markup_percent, billed_amount, expense_date, is_billable, receipt_file_id,
+    status, approved_by, approved_at, approval_notes, invoice_id, created_by, created_at, updated_at";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpenseError {
+    #[error("Expense not found")]
+    NotFound,
+    #[error("Expense is already {0}")]
+    AlreadyReviewed(ExpenseStatus),
+    #[error("Expenses can't be reviewed by whoever submitted them")]
+    OwnExpense,
+    #[error("Expense {0}'s billed amount is out of range")]
+    AmountOutOfRange(Uuid),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Expense {
+    pub id: Uuid,
+    pub client_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub vendor_id: Option<Uuid>,
+    /// The vendor's name
+    pub vendor: String,
+    pub category: String,
+    pub description: String,
+    pub amount: Decimal,
+    pub tax_amount: Option<Decimal>,
+    pub markup_percent: Option<Decimal>,
+    /// What the client was invoiced, set once billed
+    pub billed_amount: Option<Decimal>,
+    pub expense_date: NaiveDate,
+    pub is_billable: Option<bool>,
+    pub receipt_file_id: Option<Uuid>,
+    pub status: ExpenseStatus,
+    /// Who approved or rejected the expense
+    pub approved_by: Option<Uuid>,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub approval_notes: Option<String>,
+    pub invoice_id: Option<Uuid>,
+    pub created_by: Option<Uuid>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// An approver's decision on a submitted expense
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Review {
+    Approve,
+    Reject,
+}
+
+impl Review {
+    pub fn status(self) -> ExpenseStatus {
+        match self {
+            Self::Approve => ExpenseStatus::Approved,
+            Self::Reject => ExpenseStatus::Rejected,
+        }
+    }
+}
+
+/// Whether an expense in `status` may still be edited
+pub fn is_editable(status: ExpenseStatus) -> bool {
+    matches!(status, ExpenseStatus::Submitted | ExpenseStatus::Rejected)
+}
+
+/// Whether an expense in `status` may be deleted; billed ones are on an
+/// invoice and reimbursed ones are settled
+pub fn is_deletable(status: ExpenseStatus) -> bool {
+    !matches!(status, ExpenseStatus::Billed | ExpenseStatus::Reimbursed)
+}
+
+pub async fn find_expense(pool: &PgPool, expense_id: Uuid) -> Result<Option<Expense>, sqlx::Error> {
+    sqlx::query_as::<_, Expense>(&format!("SELECT {} FROM expenses WHERE id = $1", EXPENSE_COLUMNS))
+        .bind(expense_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Approve or reject a submitted expense someone else submitted
+pub async fn review_expense(
+    pool: &PgPool,
+    expense_id: Uuid,
+    reviewer_id: Uuid,
+    review: Review,
+    notes: Option<String>,
+) -> Result<Expense, ExpenseError> {
+    let reviewed = sqlx::query_as::<_, Expense>(&format!(
+        "UPDATE expenses SET status = $2, approved_by = $3, approved_at = NOW(), approval_notes = $4, updated_at = NOW()
+         WHERE id = $1 AND status = $5 AND created_by IS DISTINCT FROM $3
+         RETURNING {}",
+        EXPENSE_COLUMNS
+    ))
+    .bind(expense_id)
+    .bind(review.status())
+    .bind(reviewer_id)
+    .bind(notes)
+    .bind(ExpenseStatus::Submitted)
+    .fetch_optional(pool)
+    .await?;
+
+    match reviewed {
+        Some(expense) => Ok(expense),
+        None => match find_expense(pool, expense_id).await? {
+            Some(expense) if expense.status != ExpenseStatus::Submitted => {
+                Err(ExpenseError::AlreadyReviewed(expense.status))
+            }
+            Some(_) => Err(ExpenseError::OwnExpense),
+            None => Err(ExpenseError::NotFound),
+        },
+    }
+}
+
+/// An approved billable expense waiting for an invoice
+#[derive(Debug, Clone, FromRow)]
+pub struct BillableExpense {
+    pub id: Uuid,
+    pub vendor: String,
+    pub description: String,
+    pub amount: Decimal,
+    pub markup_percent: Option<Decimal>,
+    pub expense_date: NaiveDate,
+    /// The amount plus markup, filled in by [`unbilled_expenses`]
+    #[sqlx(skip)]
+    pub billed_amount: Decimal,
+}
+
+/// `amount` plus `markup_percent` of it, to the cent; `None` if that
+/// overflows
+pub fn billed_amount(amount: Decimal, markup_percent: Option<Decimal>) -> Option<Decimal> {
+    let markup = amount
+        .checked_mul(markup_percent.unwrap_or(Decimal::ZERO))?
+        .checked_div(Decimal::ONE_HUNDRED)?;
+    Some(amount.checked_add(markup)?.round_dp(2))
+}
+
+impl BillableExpense {
+    pub fn line_description(&self) -> String {
+        format!("{} - {} ({})", self.vendor, self.description, self.expense_date.format("%Y-%m-%d"))
+    }
+}
+
+/// The client's approved, billable, unbilled expenses, oldest first, locked
+/// until the transaction ends so two invoices can't bill the same expense
+pub async fn unbilled_expenses(conn: &mut PgConnection, client_id: Uuid) -> Result<Vec<BillableExpense>, ExpenseError> {
+    let mut expenses = sqlx::query_as::<_, BillableExpense>(
+        "SELECT e.id, e.vendor, e.description, e.amount, e.markup_percent, e.expense_date
+         FROM expenses e
+         LEFT JOIN projects p ON e.project_id = p.id
+         WHERE e.status = $2
+           AND e.is_billable = true
+           AND e.invoice_id IS NULL
+           AND COALESCE(e.client_id, p.client_id) = $1
+         ORDER BY e.expense_date, e.created_at
+         FOR UPDATE OF e"
+    )
+    .bind(client_id)
+    .bind(ExpenseStatus::Approved)
+    .fetch_all(conn)
+    .await?;
+
+    for expense in &mut expenses {
+        expense.billed_amount = billed_amount(expense.amount, expense.markup_percent)
+            .ok_or(ExpenseError::AmountOutOfRange(expense.id))?;
+    }
+    Ok(expenses)
+}
+
+/// Record the expenses as billed on `invoice_id`
+pub async fn mark_billed(conn: &mut PgConnection, invoice_id: Uuid, expenses: &[BillableExpense]) -> Result<(), sqlx::Error> {
+    if expenses.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<Uuid> = expenses.iter().map(|e| e.id).collect();
+    let amounts: Vec<Decimal> = expenses.iter().map(|e| e.billed_amount).collect();
+    sqlx::query(
+        "UPDATE expenses e SET status = $4, invoice_id = $1, billed_amount = b.amount, updated_at = NOW()
+         FROM UNNEST($2::uuid[], $3::numeric[]) AS b(id, amount)
+         WHERE e.id = b.id"
+    )
+    .bind(invoice_id)
+    .bind(&ids)
+    .bind(&amounts)
+    .bind(ExpenseStatus::Billed)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_billed_amount_applies_markup() {
+        assert_eq!(billed_amount(dec("200.00"), None), Some(dec("200.00")));
+        assert_eq!(billed_amount(dec("200.00"), Some(dec("15"))), Some(dec("230.00")));
+        assert_eq!(billed_amount(dec("10.01"), Some(dec("12.5"))), Some(dec("11.26")));
+        assert_eq!(billed_amount(Decimal::MAX, Some(dec("50"))), None);
+    }
+
+    #[test]
+    fn test_line_description() {
+        let expense = BillableExpense {
+            id: Uuid::new_v4(),
+            vendor: "Contoso Hardware".to_string(),
+            description: "Replacement switch".to_string(),
+            amount: Decimal::ONE,
+            markup_percent: None,
+            expense_date: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            billed_amount: Decimal::ONE,
+        };
+        assert_eq!(
+            expense.line_description(),
+            "Contoso Hardware - Replacement switch (2024-05-01)"
+        );
+    }
+
+    #[test]
+    fn test_lifecycle_rules() {
+        assert_eq!(Review::Approve.status(), ExpenseStatus::Approved);
+        assert_eq!(Review::Reject.status(), ExpenseStatus::Rejected);
+
+        assert!(is_editable(ExpenseStatus::Submitted));
+        assert!(is_editable(ExpenseStatus::Rejected));
+        assert!(!is_editable(ExpenseStatus::Approved));
+        assert!(!is_editable(ExpenseStatus::Billed));
+
+        assert!(!is_editable(ExpenseStatus::Reimbursed));
+
+        assert!(is_deletable(ExpenseStatus::Approved));
+        assert!(!is_deletable(ExpenseStatus::Billed));
+        assert!(!is_deletable(ExpenseStatus::Reimbursed));
+    }
+}
@@ -0,0 +1,565 @@
+//! Syncing FortiGate devices from FortiCloud into assets and alerts
+//!
+//! Devices registered to a `forticloud` integration become `firewall`
+//! assets under the client configured on the integration
+//! (`config.client_id`), matched on serial number. A device FortiCloud
+//! reports as offline opens an alert that is resolved once it checks in
+//! again, and FortiGuard licenses ending within [`LICENSE_WARNING_DAYS`]
+//! open one alert per license and expiry date.
+//!
+//! Syncs are incremental: only devices changed since the integration's
+//! `last_sync`, less [`SYNC_OVERLAP_MINUTES`], are listed. Re-seeing a
+//! device is harmless, so the overlap covers changes made while the
+//! previous sync was running.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+const DEFAULT_API_URL: &str = "https://www.forticloud.com/forticloudapi/v1";
+const DEFAULT_AUTH_URL: &str = "https://customerapiauth.fortinet.com/api/v1";
+const DEFAULT_OAUTH_CLIENT: &str = "fortigatecloud";
+const DEVICES_PER_PAGE: u32 = 100;
+/// Tokens are renewed this long before FortiCloud would expire them
+const TOKEN_LEEWAY_SECS: i64 = 60;
+
+pub const ASSET_TYPE: &str = "firewall";
+pub const OFFLINE_ALERT: &str = "forticloud_device_offline";
+pub const LICENSE_ALERT: &str = "forticloud_license_expiring";
+pub const LICENSE_WARNING_DAYS: i64 = 30;
+pub const SYNC_OVERLAP_MINUTES: i64 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FortiCloudError {
+    #[error("FortiCloud API returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("FortiCloud authentication failed: {0}")]
+    Auth(String),
+    #[error("FortiCloud request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// The API user stored in the integration's credentials
+#[derive(Debug, Clone, Deserialize)]
+pub struct FortiCloudLogin {
+    pub api_id: String,
+    pub password: String,
+    /// OAuth client of the FortiCloud portal the API user belongs to
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: i64,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DevicePage {
+    #[serde(default)]
+    devices: Vec<Device>,
+    page: u32,
+    total_pages: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Device {
+    pub sn: String,
+    pub name: Option<String>,
+    pub model: Option<String>,
+    pub firmware: Option<String>,
+    pub management_ip: Option<String>,
+    /// `online` or `offline`
+    pub connection_status: String,
+    pub last_seen: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub licenses: Vec<License>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct License {
+    pub name: String,
+    pub expires: NaiveDate,
+}
+
+impl Device {
+    pub fn is_online(&self) -> bool {
+        self.connection_status.eq_ignore_ascii_case("online")
+    }
+
+    fn display_name(&self) -> &str {
+        self.name.as_deref().filter(|n| !n.trim().is_empty()).unwrap_or(&self.sn)
+    }
+}
+
+/// The asset columns a device fills in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirewallAsset {
+    pub name: String,
+    pub serial: String,
+    pub model: Option<String>,
+    pub os: Option<String>,
+    pub ip: Option<String>,
+}
+
+pub fn to_asset(device: &Device) -> FirewallAsset {
+    FirewallAsset {
+        name: device.display_name().to_string(),
+        serial: device.sn.trim().to_ascii_uppercase(),
+        model: device.model.clone(),
+        os: device
+            .firmware
+            .as_deref()
+            .map(|v| format!("FortiOS {}", v.trim().trim_start_matches(['v', 'V']))),
+        ip: device
+            .management_ip
+            .as_deref()
+            .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+            .map(|ip| ip.to_string()),
+    }
+}
+
+/// Licenses ending within [`LICENSE_WARNING_DAYS`] of `today`, including
+/// ones already expired, with the days left
+pub fn expiring_licenses(device: &Device, today: NaiveDate) -> Vec<(&License, i64)> {
+    device
+        .licenses
+        .iter()
+        .map(|license| (license, (license.expires - today).num_days()))
+        .filter(|(_, days)| *days <= LICENSE_WARNING_DAYS)
+        .collect()
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SyncSummary {
+    pub devices_seen: i32,
+    pub created: i32,
+    pub updated: i32,
+    pub offline_alerts: i32,
+    pub license_alerts: i32,
+    pub resolved_alerts: i32,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct FortiCloudClient {
+    http: reqwest::Client,
+    api_url: String,
+    auth_url: String,
+    login: FortiCloudLogin,
+    token: Mutex<Option<Token>>,
+}
+
+impl FortiCloudClient {
+    pub fn new(http: reqwest::Client, login: FortiCloudLogin, api_url: Option<&str>, auth_url: Option<&str>) -> Self {
+        Self {
+            http,
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_string(),
+            auth_url: auth_url.unwrap_or(DEFAULT_AUTH_URL).trim_end_matches('/').to_string(),
+            login,
+            token: Mutex::new(None),
+        }
+    }
+
+    async fn request_token(&self, body: serde_json::Value) -> Result<Token, FortiCloudError> {
+        let response = self.http.post(format!("{}/oauth/token/", self.auth_url)).json(&body).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(FortiCloudError::Auth(format!("{}: {}", status.as_u16(), response.text().await?)));
+        }
+        let token: TokenResponse = response.json().await?;
+        Ok(Token {
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+            expires_at: Utc::now() + Duration::seconds(token.expires_in),
+        })
+    }
+
+    /// A usable access token: the cached one while it lasts, else a
+    /// refreshed one, falling back to logging in again if the refresh
+    /// token has been revoked or expired
+    async fn access_token(&self) -> Result<String, FortiCloudError> {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if token.expires_at - Duration::seconds(TOKEN_LEEWAY_SECS) > Utc::now() {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let oauth_client = self.login.client_id.as_deref().unwrap_or(DEFAULT_OAUTH_CLIENT);
+        let refreshed = match cached.as_ref().and_then(|t| t.refresh_token.clone()) {
+            Some(refresh_token) => self
+                .request_token(serde_json::json!({
+                    "client_id": oauth_client,
+                    "grant_type": "refresh_token",
+                    "refresh_token": refresh_token,
+                }))
+                .await
+                .ok(),
+            None => None,
+        };
+        let token = match refreshed {
+            Some(token) => token,
+            None => {
+                self.request_token(serde_json::json!({
+                    "username": self.login.api_id,
+                    "password": self.login.password,
+                    "client_id": oauth_client,
+                    "grant_type": "password",
+                }))
+                .await?
+            }
+        };
+
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// Treat the cached token as expired so the next call refreshes it
+    async fn expire_token(&self) {
+        if let Some(token) = self.token.lock().await.as_mut() {
+            token.expires_at = Utc::now();
+        }
+    }
+
+    async fn get_page(&self, page: u32, since: Option<DateTime<Utc>>) -> Result<DevicePage, FortiCloudError> {
+        let mut query = vec![("page", page.to_string()), ("per_page", DEVICES_PER_PAGE.to_string())];
+        if let Some(since) = since {
+            query.push(("updated_since", since.to_rfc3339()));
+        }
+
+        // A token revoked early gets one refresh and retry
+        for attempt in 0..2 {
+            let token = self.access_token().await?;
+            let response = self
+                .http
+                .get(format!("{}/devices", self.api_url))
+                .bearer_auth(token)
+                .query(&query)
+                .send()
+                .await?;
+
+            let status = response.status();
+            if status == reqwest::StatusCode::UNAUTHORIZED && attempt == 0 {
+                self.expire_token().await;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(FortiCloudError::Api { status: status.as_u16(), message: response.text().await? });
+            }
+            return Ok(response.json().await?);
+        }
+        Err(FortiCloudError::Auth("access token rejected after refresh".to_string()))
+    }
+
+    /// Every device changed since `since`, or all of them
+    pub async fn list_devices(&self, since: Option<DateTime<Utc>>) -> Result<Vec<Device>, FortiCloudError> {
+        let mut devices = Vec::new();
+        let mut page = 1;
+        loop {
+            let batch = self.get_page(page, since).await?;
+            devices.extend(batch.devices);
+            if batch.page >= batch.total_pages {
+                return Ok(devices);
+            }
+            page += 1;
+        }
+    }
+}
+
+/// Create or update the device's asset; `true` when it was created
+async fn upsert_asset(pool: &PgPool, client_id: Uuid, asset: &FirewallAsset) -> Result<(Uuid, bool), sqlx::Error> {
+    let existing = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT id FROM assets
+        WHERE client_id = $1 AND asset_type = $2 AND UPPER(serial) = $3 AND archived_at IS NULL
+        ORDER BY created_at
+        LIMIT 1
+        "#
+    )
+    .bind(client_id)
+    .bind(ASSET_TYPE)
+    .bind(&asset.serial)
+    .fetch_optional(pool)
+    .await?;
+
+    match existing {
+        Some(asset_id) => {
+            sqlx::query(
+                r#"
+                UPDATE assets SET name = $2, model = COALESCE($3, model), os = COALESCE($4, os),
+                    ip = COALESCE($5::inet, ip), updated_at = NOW()
+                WHERE id = $1
+                "#
+            )
+            .bind(asset_id)
+            .bind(&asset.name)
+            .bind(&asset.model)
+            .bind(&asset.os)
+            .bind(&asset.ip)
+            .execute(pool)
+            .await?;
+            Ok((asset_id, false))
+        }
+        None => {
+            let asset_id = sqlx::query_scalar::<_, Uuid>(
+                r#"
+                INSERT INTO assets (client_id, name, asset_type, make, model, serial, os, ip, status, created_at, updated_at)
+                VALUES ($1, $2, $3, 'Fortinet', $4, $5, $6, $7::inet, 'active', NOW(), NOW())
+                RETURNING id
+                "#
+            )
+            .bind(client_id)
+            .bind(&asset.name)
+            .bind(ASSET_TYPE)
+            .bind(&asset.model)
+            .bind(&asset.serial)
+            .bind(&asset.os)
+            .bind(&asset.ip)
+            .fetch_one(pool)
+            .await?;
+            Ok((asset_id, true))
+        }
+    }
+}
+
+/// Open an alert unless an unresolved one with the same type and title is
+/// already open for the asset. Returns whether one was opened.
+async fn open_alert(
+    pool: &PgPool,
+    asset_id: Uuid,
+    alert_type: &str,
+    severity: &str,
+    title: &str,
+    message: &str,
+) -> Result<bool, sqlx::Error> {
+    let opened = sqlx::query(
+        r#"
+        INSERT INTO alerts (asset_id, alert_type, severity, title, message)
+        SELECT $1, $2, $3, $4, $5
+        WHERE NOT EXISTS (
+            SELECT 1 FROM alerts
+            WHERE asset_id = $1 AND alert_type = $2 AND title = $4 AND NOT COALESCE(resolved, false)
+        )
+        "#
+    )
+    .bind(asset_id)
+    .bind(alert_type)
+    .bind(severity)
+    .bind(title)
+    .bind(message)
+    .execute(pool)
+    .await?;
+    Ok(opened.rows_affected() > 0)
+}
+
+async fn resolve_offline_alerts(pool: &PgPool, asset_id: Uuid) -> Result<u64, sqlx::Error> {
+    let resolved = sqlx::query(
+        "UPDATE alerts SET resolved = true, resolved_at = NOW()
+         WHERE asset_id = $1 AND alert_type = $2 AND NOT COALESCE(resolved, false)"
+    )
+    .bind(asset_id)
+    .bind(OFFLINE_ALERT)
+    .execute(pool)
+    .await?;
+    Ok(resolved.rows_affected())
+}
+
+async fn sync_device(
+    pool: &PgPool,
+    client_id: Uuid,
+    device: &Device,
+    today: NaiveDate,
+    summary: &mut SyncSummary,
+) -> Result<(), sqlx::Error> {
+    let asset = to_asset(device);
+    let (asset_id, created) = upsert_asset(pool, client_id, &asset).await?;
+    if created {
+        summary.created += 1;
+    } else {
+        summary.updated += 1;
+    }
+
+    if device.is_online() {
+        summary.resolved_alerts += resolve_offline_alerts(pool, asset_id).await? as i32;
+    } else {
+        let last_seen = device
+            .last_seen
+            .map(|at| format!("last seen {}", at.format("%Y-%m-%d %H:%M UTC")))
+            .unwrap_or_else(|| "never seen".to_string());
+        let opened = open_alert(
+            pool,
+            asset_id,
+            OFFLINE_ALERT,
+            "high",
+            &format!("FortiGate offline: {}", asset.name),
+            &format!("{} ({}) is not connected to FortiCloud, {}", asset.name, asset.serial, last_seen),
+        )
+        .await?;
+        if opened {
+            summary.offline_alerts += 1;
+        }
+    }
+
+    for (license, days) in expiring_licenses(device, today) {
+        let (severity, when) = match days {
+            ..=-1 => ("critical", format!("expired on {}", license.expires)),
+            0..=7 => ("high", format!("expires on {} ({} day(s) remaining)", license.expires, days)),
+            _ => ("medium", format!("expires on {} ({} day(s) remaining)", license.expires, days)),
+        };
+        // The expiry date is in the title so a renewal that lapses again alerts again
+        let opened = open_alert(
+            pool,
+            asset_id,
+            LICENSE_ALERT,
+            severity,
+            &format!("{} expiring on {}: {}", license.name, license.expires, asset.name),
+            &format!("{} for {} ({}) {}", license.name, asset.name, asset.serial, when),
+        )
+        .await?;
+        if opened {
+            summary.license_alerts += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sync devices changed since `last_sync` into assets for `client_id`.
+/// Listing failures abort the sync; a failure on one device is recorded
+/// and the rest carry on.
+pub async fn sync_devices(
+    pool: &PgPool,
+    client: &FortiCloudClient,
+    client_id: Uuid,
+    last_sync: Option<DateTime<Utc>>,
+    today: NaiveDate,
+) -> Result<SyncSummary, FortiCloudError> {
+    let devices = client.list_devices(last_sync.map(|at| at - Duration::minutes(SYNC_OVERLAP_MINUTES))).await?;
+    let mut summary = SyncSummary::default();
+
+    for device in &devices {
+        summary.devices_seen += 1;
+        if let Err(e) = sync_device(pool, client_id, device, today, &mut summary).await {
+            summary.errors.push(format!("{}: {}", device.sn, e));
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn device(status: &str, licenses: Vec<License>) -> Device {
+        Device {
+            sn: "fgt60ftk2109abcd".to_string(),
+            name: Some("hq-fw01".to_string()),
+            model: Some("FortiGate-60F".to_string()),
+            firmware: Some("v7.2.8".to_string()),
+            management_ip: Some("203.0.113.1".to_string()),
+            connection_status: status.to_string(),
+            last_seen: None,
+            licenses,
+        }
+    }
+
+    #[test]
+    fn test_device_maps_to_firewall_asset() {
+        let asset = to_asset(&device("online", vec![]));
+        assert_eq!(
+            asset,
+            FirewallAsset {
+                name: "hq-fw01".to_string(),
+                serial: "FGT60FTK2109ABCD".to_string(),
+                model: Some("FortiGate-60F".to_string()),
+                os: Some("FortiOS 7.2.8".to_string()),
+                ip: Some("203.0.113.1".to_string()),
+            }
+        );
+
+        let mut unnamed = device("offline", vec![]);
+        unnamed.name = Some(" ".to_string());
+        unnamed.management_ip = Some("not-an-ip".to_string());
+        let asset = to_asset(&unnamed);
+        assert_eq!(asset.name, "fgt60ftk2109abcd");
+        assert_eq!(asset.ip, None);
+    }
+
+    #[test]
+    fn test_only_licenses_inside_the_window_are_expiring() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let license = |name: &str, expires: NaiveDate| License { name: name.to_string(), expires };
+        let fw = device("online", vec![
+            license("FortiGuard IPS", today + Duration::days(10)),
+            license("FortiGuard Web Filtering", today + Duration::days(200)),
+            license("FortiCare", today - Duration::days(2)),
+        ]);
+
+        let expiring: Vec<(&str, i64)> = expiring_licenses(&fw, today).into_iter().map(|(l, d)| (l.name.as_str(), d)).collect();
+        assert_eq!(expiring, vec![("FortiGuard IPS", 10), ("FortiCare", -2)]);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_token_is_refreshed_and_request_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token/"))
+            .and(body_partial_json(serde_json::json!({ "grant_type": "password" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "first", "refresh_token": "renew-me", "expires_in": 3600
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token/"))
+            .and(body_partial_json(serde_json::json!({ "grant_type": "refresh_token", "refresh_token": "renew-me" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "second", "refresh_token": "renew-again", "expires_in": 3600
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/devices"))
+            .and(header("authorization", "Bearer first"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/devices"))
+            .and(header("authorization", "Bearer second"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "devices": [{ "sn": "FGT60F0000000001", "connection_status": "online" }],
+                "page": 1,
+                "total_pages": 1
+            })))
+            .mount(&server)
+            .await;
+
+        let login = FortiCloudLogin { api_id: "api-user".to_string(), password: "secret".to_string(), client_id: None };
+        let client = FortiCloudClient::new(reqwest::Client::new(), login, Some(&server.uri()), Some(&server.uri()));
+
+        let devices = client.list_devices(None).await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert!(devices[0].is_online());
+    }
+}
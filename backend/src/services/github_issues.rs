@@ -0,0 +1,436 @@
+//! Mirroring tickets to GitHub issues
+//!
+//! A ticket pushed to the repository configured on a `github` integration
+//! (`config.repository`, as `owner/repo`) becomes an issue, linked through
+//! `ticket_external_refs`. Integration syncs then carry closures across in
+//! both directions: a closed issue closes its ticket and a closed ticket
+//! closes its issue.
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::middleware::request_id::with_request_id;
+use crate::services::ticket_status::{self, AUTOMATION_USER_ID};
+use resolve_shared::TicketStatus;
+
+pub const PROVIDER: &str = "github";
+const DEFAULT_API_URL: &str = "https://api.github.com";
+/// GitHub asks for at least a second between content-creating requests
+const MUTATION_INTERVAL: Duration = Duration::from_secs(1);
+const CLOSED_TICKET_STATUSES: &[&str] = &["closed", "resolved"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum GitHubError {
+    #[error("GitHub rate limit exceeded{}", reset_at.map(|t| format!(", resets at {}", t)).unwrap_or_default())]
+    RateLimited { reset_at: Option<DateTime<Utc>> },
+    #[error("GitHub API returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("GitHub request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("No repository configured for the GitHub integration")]
+    NoRepository,
+    #[error("Ticket not found")]
+    TicketNotFound,
+    #[error("Ticket is already linked to {0}")]
+    AlreadyLinked(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NewIssue {
+    pub title: String,
+    pub body: String,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Issue {
+    pub number: i64,
+    pub html_url: String,
+    pub state: String,
+}
+
+impl Issue {
+    pub fn is_open(&self) -> bool {
+        self.state == "open"
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct TicketForIssue {
+    pub id: Uuid,
+    pub number: i32,
+    pub subject: String,
+    pub details: String,
+    pub priority: Option<String>,
+    pub client_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TicketExternalRef {
+    pub id: Uuid,
+    pub ticket_id: Uuid,
+    pub integration_id: Uuid,
+    pub provider: String,
+    pub repository: String,
+    pub external_id: String,
+    pub external_url: Option<String>,
+    pub external_state: Option<String>,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a sync should do to bring a ticket and its issue into line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncAction {
+    None,
+    CloseTicket,
+    CloseIssue,
+}
+
+pub fn reconcile(ticket_status: &str, issue_open: bool) -> SyncAction {
+    let ticket_closed = CLOSED_TICKET_STATUSES.contains(&ticket_status);
+    match (ticket_closed, issue_open) {
+        (false, false) => SyncAction::CloseTicket,
+        (true, true) => SyncAction::CloseIssue,
+        _ => SyncAction::None,
+    }
+}
+
+pub fn priority_label(priority: Option<&str>) -> String {
+    format!("priority: {}", priority.unwrap_or("medium").to_lowercase())
+}
+
+/// Map a ticket onto a new issue: subject to title, details to body and
+/// priority to a label
+pub fn issue_from_ticket(ticket: &TicketForIssue) -> NewIssue {
+    let client = ticket
+        .client_name
+        .as_deref()
+        .map(|name| format!(" for {}", name))
+        .unwrap_or_default();
+
+    NewIssue {
+        title: ticket.subject.clone(),
+        body: format!(
+            "{}\n\n---\n_Escalated from Resolve ticket #{}{}._",
+            ticket.details.trim_end(),
+            ticket.number,
+            client
+        ),
+        labels: vec![priority_label(ticket.priority.as_deref())],
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GitHubIssueClient {
+    http: reqwest::Client,
+    token: String,
+    api_url: String,
+    mutation_interval: Duration,
+}
+
+impl GitHubIssueClient {
+    pub fn new(token: impl Into<String>, api_url: Option<&str>) -> Result<Self, GitHubError> {
+        Ok(Self {
+            http: reqwest::Client::builder()
+                .user_agent("Resolve/1.0")
+                .timeout(Duration::from_secs(30))
+                .build()?,
+            token: token.into(),
+            api_url: api_url.unwrap_or(DEFAULT_API_URL).trim_end_matches('/').to_string(),
+            mutation_interval: MUTATION_INTERVAL,
+        })
+    }
+
+    pub fn with_mutation_interval(mut self, interval: Duration) -> Self {
+        self.mutation_interval = interval;
+        self
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        with_request_id(self.http.request(method, format!("{}{}", self.api_url, path)))
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Issue, GitHubError> {
+        let response = request.send().await?;
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response.json::<Issue>().await?);
+        }
+
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<i64>().ok())
+        };
+        let exhausted = header("x-ratelimit-remaining") == Some(0) || response.headers().contains_key("retry-after");
+        if status.as_u16() == 429 || (status.as_u16() == 403 && exhausted) {
+            let reset_at = header("x-ratelimit-reset").and_then(|t| Utc.timestamp_opt(t, 0).single());
+            return Err(GitHubError::RateLimited { reset_at });
+        }
+
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        Err(GitHubError::Api {
+            status: status.as_u16(),
+            message: body["message"].as_str().unwrap_or("unknown error").to_string(),
+        })
+    }
+
+    /// Check the token by fetching the account it belongs to
+    pub async fn check_token(&self) -> Result<(), GitHubError> {
+        let response = self.request(reqwest::Method::GET, "/user").send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        Err(GitHubError::Api {
+            status: status.as_u16(),
+            message: body["message"].as_str().unwrap_or("unknown error").to_string(),
+        })
+    }
+
+    pub async fn create_issue(&self, repository: &str, issue: &NewIssue) -> Result<Issue, GitHubError> {
+        let issue = self
+            .send(self.request(reqwest::Method::POST, &format!("/repos/{}/issues", repository)).json(issue))
+            .await?;
+        tokio::time::sleep(self.mutation_interval).await;
+        Ok(issue)
+    }
+
+    pub async fn get_issue(&self, repository: &str, number: &str) -> Result<Issue, GitHubError> {
+        self.send(self.request(reqwest::Method::GET, &format!("/repos/{}/issues/{}", repository, number)))
+            .await
+    }
+
+    pub async fn close_issue(&self, repository: &str, number: &str) -> Result<Issue, GitHubError> {
+        let issue = self
+            .send(
+                self.request(reqwest::Method::PATCH, &format!("/repos/{}/issues/{}", repository, number))
+                    .json(&serde_json::json!({ "state": "closed", "state_reason": "completed" })),
+            )
+            .await?;
+        tokio::time::sleep(self.mutation_interval).await;
+        Ok(issue)
+    }
+}
+
+/// Repository an integration mirrors to, from `config.repository`
+pub fn configured_repository(config: &serde_json::Value) -> Option<String> {
+    config
+        .get("repository")
+        .and_then(|r| r.as_str())
+        .map(str::trim)
+        .filter(|r| r.split('/').count() == 2 && !r.starts_with('/') && !r.ends_with('/'))
+        .map(String::from)
+}
+
+/// Create an issue for a ticket and record the link
+pub async fn push_ticket(
+    pool: &PgPool,
+    client: &GitHubIssueClient,
+    integration_id: Uuid,
+    repository: &str,
+    ticket_id: Uuid,
+    pushed_by: Option<Uuid>,
+) -> Result<TicketExternalRef, GitHubError> {
+    if let Some(existing) = find_ref(pool, ticket_id, integration_id).await? {
+        return Err(GitHubError::AlreadyLinked(
+            existing.external_url.unwrap_or(format!("{}#{}", existing.repository, existing.external_id)),
+        ));
+    }
+
+    let ticket = sqlx::query_as::<_, TicketForIssue>(
+        r#"
+        SELECT t.id, t.number, t.subject, t.details, t.priority, c.name as client_name
+        FROM tickets t
+        LEFT JOIN clients c ON t.client_id = c.id
+        WHERE t.id = $1
+        "#
+    )
+    .bind(ticket_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(GitHubError::TicketNotFound)?;
+
+    let issue = client.create_issue(repository, &issue_from_ticket(&ticket)).await?;
+
+    let external_ref = sqlx::query_as::<_, TicketExternalRef>(
+        r#"
+        INSERT INTO ticket_external_refs (
+            ticket_id, integration_id, provider, repository, external_id,
+            external_url, external_state, last_synced_at, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(), $8)
+        RETURNING id, ticket_id, integration_id, provider, repository, external_id,
+                  external_url, external_state, last_synced_at, created_at
+        "#
+    )
+    .bind(ticket_id)
+    .bind(integration_id)
+    .bind(PROVIDER)
+    .bind(repository)
+    .bind(issue.number.to_string())
+    .bind(&issue.html_url)
+    .bind(&issue.state)
+    .bind(pushed_by)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(external_ref)
+}
+
+pub async fn find_ref(pool: &PgPool, ticket_id: Uuid, integration_id: Uuid) -> Result<Option<TicketExternalRef>, sqlx::Error> {
+    sqlx::query_as::<_, TicketExternalRef>(
+        r#"
+        SELECT id, ticket_id, integration_id, provider, repository, external_id,
+               external_url, external_state, last_synced_at, created_at
+        FROM ticket_external_refs
+        WHERE ticket_id = $1 AND integration_id = $2
+        "#
+    )
+    .bind(ticket_id)
+    .bind(integration_id)
+    .fetch_optional(pool)
+    .await
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IssueSyncSummary {
+    pub issues_checked: i32,
+    pub tickets_closed: i32,
+    pub issues_closed: i32,
+    /// Sync stopped early because GitHub's rate limit was hit
+    pub rate_limited: bool,
+    pub errors: Vec<String>,
+}
+
+/// Bring every linked ticket and issue for an integration into line.
+/// Failures on one link are recorded and the rest carry on; hitting the
+/// rate limit stops the sync.
+pub async fn sync_issue_status(
+    pool: &PgPool,
+    client: &GitHubIssueClient,
+    integration_id: Uuid,
+) -> Result<IssueSyncSummary, sqlx::Error> {
+    let links = sqlx::query_as::<_, (Uuid, Uuid, String, String, String)>(
+        r#"
+        SELECT r.id, r.ticket_id, r.repository, r.external_id, COALESCE(t.status, 'open')
+        FROM ticket_external_refs r
+        JOIN tickets t ON r.ticket_id = t.id
+        WHERE r.integration_id = $1 AND r.provider = $2
+        ORDER BY r.last_synced_at NULLS FIRST
+        "#
+    )
+    .bind(integration_id)
+    .bind(PROVIDER)
+    .fetch_all(pool)
+    .await?;
+
+    let mut summary = IssueSyncSummary::default();
+
+    for (ref_id, ticket_id, repository, number, ticket_status) in links {
+        let reference = format!("{}#{}", repository, number);
+
+        let result = async {
+            let mut issue = client.get_issue(&repository, &number).await?;
+            summary.issues_checked += 1;
+
+            match reconcile(&ticket_status, issue.is_open()) {
+                SyncAction::CloseTicket => {
+                    let reason = format!("The linked GitHub issue {} was closed.", reference);
+                    if ticket_status::change_status(pool, ticket_id, TicketStatus::Closed, AUTOMATION_USER_ID, &reason)
+                        .await?
+                        .is_some()
+                    {
+                        summary.tickets_closed += 1;
+                    }
+                }
+                SyncAction::CloseIssue => {
+                    issue = client.close_issue(&repository, &number).await?;
+                    summary.issues_closed += 1;
+                }
+                SyncAction::None => {}
+            }
+
+            sqlx::query("UPDATE ticket_external_refs SET external_state = $2, last_synced_at = NOW() WHERE id = $1")
+                .bind(ref_id)
+                .bind(&issue.state)
+                .execute(pool)
+                .await?;
+
+            Ok::<_, GitHubError>(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => {}
+            Err(GitHubError::RateLimited { reset_at }) => {
+                summary.rate_limited = true;
+                summary.errors.push(GitHubError::RateLimited { reset_at }.to_string());
+                break;
+            }
+            Err(e) => summary.errors.push(format!("{}: {}", reference, e)),
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket(priority: Option<&str>) -> TicketForIssue {
+        TicketForIssue {
+            id: Uuid::new_v4(),
+            number: 4821,
+            subject: "API returns 500 on invoice export".to_string(),
+            details: "Export fails for invoices with zero line items.\n".to_string(),
+            priority: priority.map(String::from),
+            client_name: Some("Acme Corp".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_issue_fields_map_from_ticket() {
+        let issue = issue_from_ticket(&ticket(Some("High")));
+
+        assert_eq!(issue.title, "API returns 500 on invoice export");
+        assert!(issue.body.starts_with("Export fails for invoices with zero line items.\n\n---\n"));
+        assert!(issue.body.contains("Resolve ticket #4821 for Acme Corp"));
+        assert_eq!(issue.labels, vec!["priority: high"]);
+
+        assert_eq!(issue_from_ticket(&ticket(None)).labels, vec!["priority: medium"]);
+    }
+
+    #[test]
+    fn test_reconcile() {
+        assert_eq!(reconcile("open", true), SyncAction::None);
+        assert_eq!(reconcile("in_progress", false), SyncAction::CloseTicket);
+        assert_eq!(reconcile("closed", true), SyncAction::CloseIssue);
+        assert_eq!(reconcile("resolved", true), SyncAction::CloseIssue);
+        assert_eq!(reconcile("closed", false), SyncAction::None);
+    }
+
+    #[test]
+    fn test_configured_repository() {
+        let config = |repo: &str| serde_json::json!({ "repository": repo });
+        assert_eq!(configured_repository(&config("acme/platform")), Some("acme/platform".to_string()));
+        assert_eq!(configured_repository(&config("acme")), None);
+        assert_eq!(configured_repository(&config("acme/")), None);
+        assert_eq!(configured_repository(&serde_json::json!({})), None);
+    }
+}
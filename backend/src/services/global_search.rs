@@ -0,0 +1,307 @@
+//! Global search
+//!
+//! One query over tickets, clients, contacts, assets and knowledge base
+//! articles. Each type is searched concurrently and capped at its own limit,
+//! then returned as a group of hits ranked best first. Tickets and articles
+//! go through their weighted `search_vector` columns, as their own searches
+//! do; clients, contacts and assets have no full-text index and are matched
+//! by substring, names ahead of other fields. [`SearchAccess`] decides which
+//! types the caller may see and which clients' records.
+
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::services::ticket_search::{search_mode, substring_pattern, SearchMode};
+
+/// Hits per type unless the caller asks for fewer or more
+pub const DEFAULT_PER_TYPE_LIMIT: i64 = 5;
+pub const MAX_PER_TYPE_LIMIT: i64 = 25;
+
+const SNIPPET_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxWords=20, MinWords=8, MaxFragments=1";
+const FALLBACK_SNIPPET_LENGTH: i32 = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityType {
+    Ticket,
+    Client,
+    Contact,
+    Asset,
+    KbArticle,
+}
+
+/// Groups come back in this order
+pub const ENTITY_TYPES: [EntityType; 5] = [
+    EntityType::Ticket,
+    EntityType::Client,
+    EntityType::Contact,
+    EntityType::Asset,
+    EntityType::KbArticle,
+];
+
+/// What the caller may search
+#[derive(Debug, Clone, Default)]
+pub struct SearchAccess {
+    pub entity_types: Vec<EntityType>,
+    /// Clients whose records are visible; `None` for every client
+    pub client_ids: Option<Vec<Uuid>>,
+}
+
+impl SearchAccess {
+    fn allows(&self, entity_type: EntityType) -> bool {
+        self.entity_types.contains(&entity_type)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SearchHit {
+    pub id: Uuid,
+    pub title: String,
+    /// Context for the hit, matched terms in `<mark>` where full-text
+    /// search produced it
+    pub snippet: String,
+    pub client_id: Option<Uuid>,
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchGroup {
+    pub entity_type: EntityType,
+    pub hits: Vec<SearchHit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GlobalSearchResults {
+    pub query: String,
+    pub mode: SearchMode,
+    /// Types with at least one hit
+    pub groups: Vec<SearchGroup>,
+}
+
+/// Search every type `access` allows, at most `per_type_limit` hits each
+pub async fn search(
+    pool: &PgPool,
+    query: &str,
+    access: &SearchAccess,
+    per_type_limit: i64,
+) -> Result<GlobalSearchResults, sqlx::Error> {
+    let mode = search_mode(pool, query).await?;
+    let search = Search {
+        pool,
+        query,
+        pattern: substring_pattern(query.trim()),
+        mode,
+        client_ids: access.client_ids.as_deref(),
+        limit: per_type_limit,
+    };
+
+    let skip = |entity_type| !access.allows(entity_type);
+    let (tickets, clients, contacts, assets, articles) = tokio::try_join!(
+        search.run(skip(EntityType::Ticket), search.tickets()),
+        search.run(skip(EntityType::Client), search.clients()),
+        search.run(skip(EntityType::Contact), search.contacts()),
+        search.run(skip(EntityType::Asset), search.assets()),
+        search.run(skip(EntityType::KbArticle), search.articles()),
+    )?;
+
+    let groups = ENTITY_TYPES
+        .into_iter()
+        .zip([tickets, clients, contacts, assets, articles])
+        .filter(|(_, hits)| !hits.is_empty())
+        .map(|(entity_type, hits)| SearchGroup { entity_type, hits })
+        .collect();
+
+    Ok(GlobalSearchResults { query: query.to_string(), mode, groups })
+}
+
+struct Search<'a> {
+    pool: &'a PgPool,
+    query: &'a str,
+    pattern: String,
+    mode: SearchMode,
+    client_ids: Option<&'a [Uuid]>,
+    limit: i64,
+}
+
+impl<'a> Search<'a> {
+    async fn run(
+        &self,
+        skip: bool,
+        search: impl std::future::Future<Output = Result<Vec<SearchHit>, sqlx::Error>>,
+    ) -> Result<Vec<SearchHit>, sqlx::Error> {
+        if skip {
+            return Ok(Vec::new());
+        }
+        search.await
+    }
+
+    /// Runs `sql` with the term as $1, the client scope as $2 and the limit as $3
+    async fn fetch(&self, term: &str, sql: &str) -> Result<Vec<SearchHit>, sqlx::Error> {
+        sqlx::query_as::<_, SearchHit>(sql)
+            .bind(term)
+            .bind(self.client_ids)
+            .bind(self.limit)
+            .fetch_all(self.pool)
+            .await
+    }
+
+    /// Rank for the substring-matched types: names starting with the query
+    /// first, then names containing it, then hits on other fields
+    fn name_rank(column: &str) -> String {
+        format!(
+            "(CASE WHEN {column} ILIKE LTRIM($1, '%') THEN 1.0 WHEN {column} ILIKE $1 THEN 0.75 ELSE 0.5 END)::real"
+        )
+    }
+
+    async fn tickets(&self) -> Result<Vec<SearchHit>, sqlx::Error> {
+        let (term, matches, rank, snippet) = match self.mode {
+            SearchMode::FullText => (
+                self.query.to_string(),
+                "t.search_vector @@ websearch_to_tsquery('english', $1)".to_string(),
+                "ts_rank(t.search_vector, websearch_to_tsquery('english', $1))".to_string(),
+                format!(
+                    "ts_headline('english', COALESCE(r.details, ''), websearch_to_tsquery('english', $1), '{}')",
+                    SNIPPET_OPTIONS
+                ),
+            ),
+            SearchMode::Substring => (
+                self.pattern.clone(),
+                "(t.subject ILIKE $1 OR t.details ILIKE $1)".to_string(),
+                "(CASE WHEN t.subject ILIKE $1 THEN 1.0 ELSE 0.5 END)::real".to_string(),
+                format!("LEFT(COALESCE(r.details, ''), {})", FALLBACK_SNIPPET_LENGTH),
+            ),
+        };
+
+        // Headlines are expensive, so only build them for the hits returned
+        self.fetch(
+            &term,
+            &format!(
+                r#"
+                WITH ranked AS (
+                    SELECT t.id, t.number, t.subject, t.details, t.client_id, t.created_at, {rank} as rank
+                    FROM tickets t
+                    WHERE {matches} AND ($2::uuid[] IS NULL OR t.client_id = ANY($2))
+                    ORDER BY rank DESC, t.created_at DESC
+                    LIMIT $3
+                )
+                SELECT r.id, '#' || r.number || ' ' || r.subject as title, {snippet} as snippet,
+                       r.client_id, r.rank
+                FROM ranked r
+                ORDER BY r.rank DESC, r.created_at DESC
+                "#
+            ),
+        )
+        .await
+    }
+
+    async fn clients(&self) -> Result<Vec<SearchHit>, sqlx::Error> {
+        self.fetch(
+            &self.pattern,
+            &format!(
+                r#"
+                SELECT c.id, c.name as title,
+                       CONCAT_WS(' · ', c.email, c.phone, c.city) as snippet,
+                       c.id as client_id, {rank} as rank
+                FROM clients c
+                WHERE c.archived_at IS NULL
+                  AND (c.name ILIKE $1 OR c.email ILIKE $1 OR c.phone ILIKE $1)
+                  AND ($2::uuid[] IS NULL OR c.id = ANY($2))
+                ORDER BY rank DESC, c.name
+                LIMIT $3
+                "#,
+                rank = Self::name_rank("c.name")
+            ),
+        )
+        .await
+    }
+
+    async fn contacts(&self) -> Result<Vec<SearchHit>, sqlx::Error> {
+        self.fetch(
+            &self.pattern,
+            &format!(
+                r#"
+                SELECT ct.id, ct.name as title,
+                       CONCAT_WS(' · ', cl.name, ct.title, ct.email, ct.phone) as snippet,
+                       ct.client_id, {rank} as rank
+                FROM contacts ct
+                JOIN clients cl ON ct.client_id = cl.id
+                WHERE ct.archived_at IS NULL
+                  AND (ct.name ILIKE $1 OR ct.email ILIKE $1 OR ct.phone ILIKE $1 OR ct.mobile ILIKE $1)
+                  AND ($2::uuid[] IS NULL OR ct.client_id = ANY($2))
+                ORDER BY rank DESC, ct.name
+                LIMIT $3
+                "#,
+                rank = Self::name_rank("ct.name")
+            ),
+        )
+        .await
+    }
+
+    async fn assets(&self) -> Result<Vec<SearchHit>, sqlx::Error> {
+        self.fetch(
+            &self.pattern,
+            &format!(
+                r#"
+                SELECT a.id, a.name as title,
+                       CONCAT_WS(' · ', cl.name, a.asset_type, NULLIF(CONCAT_WS(' ', a.make, a.model), ''), a.serial) as snippet,
+                       a.client_id, {rank} as rank
+                FROM assets a
+                JOIN clients cl ON a.client_id = cl.id
+                WHERE a.archived_at IS NULL
+                  AND (a.name ILIKE $1 OR a.serial ILIKE $1 OR a.model ILIKE $1 OR a.description ILIKE $1)
+                  AND ($2::uuid[] IS NULL OR a.client_id = ANY($2))
+                ORDER BY rank DESC, a.name
+                LIMIT $3
+                "#,
+                rank = Self::name_rank("a.name")
+            ),
+        )
+        .await
+    }
+
+    async fn articles(&self) -> Result<Vec<SearchHit>, sqlx::Error> {
+        let (term, matches, rank, snippet) = match self.mode {
+            SearchMode::FullText => (
+                self.query.to_string(),
+                "a.search_vector @@ websearch_to_tsquery('english', $1)".to_string(),
+                "ts_rank(a.search_vector, websearch_to_tsquery('english', $1))".to_string(),
+                format!(
+                    "ts_headline('english', COALESCE(r.summary, r.excerpt, r.content), websearch_to_tsquery('english', $1), '{}')",
+                    SNIPPET_OPTIONS
+                ),
+            ),
+            SearchMode::Substring => (
+                self.pattern.clone(),
+                "(a.title ILIKE $1 OR a.summary ILIKE $1 OR a.content ILIKE $1)".to_string(),
+                "(CASE WHEN a.title ILIKE $1 THEN 1.0 ELSE 0.5 END)::real".to_string(),
+                format!("LEFT(COALESCE(r.summary, r.excerpt, r.content), {})", FALLBACK_SNIPPET_LENGTH),
+            ),
+        };
+
+        // Articles shared with specific clients stay hidden from callers
+        // scoped away from all of those clients
+        self.fetch(
+            &term,
+            &format!(
+                r#"
+                WITH ranked AS (
+                    SELECT a.id, a.title, a.summary, a.excerpt, a.content, a.updated_at, {rank} as rank
+                    FROM kb_articles a
+                    WHERE {matches} AND a.archived_at IS NULL
+                      AND ($2::uuid[] IS NULL
+                           OR NOT EXISTS (SELECT 1 FROM kb_article_access aa WHERE aa.article_id = a.id)
+                           OR EXISTS (SELECT 1 FROM kb_article_access aa
+                                      WHERE aa.article_id = a.id AND aa.client_id = ANY($2)))
+                    ORDER BY rank DESC, a.updated_at DESC NULLS LAST
+                    LIMIT $3
+                )
+                SELECT r.id, r.title, {snippet} as snippet, NULL::uuid as client_id, r.rank
+                FROM ranked r
+                ORDER BY r.rank DESC, r.updated_at DESC NULLS LAST
+                "#
+            ),
+        )
+        .await
+    }
+}
@@ -0,0 +1,611 @@
+//! Email-to-ticket ingestion
+//!
+//! Inbound mail arrives as a provider's parsed webhook (Postmark's inbound
+//! JSON) or as raw MIME. The recipient address picks the ticket queue, the
+//! sender is matched to an existing contact, and a `[#123]` token in the
+//! subject threads the message onto that ticket as a reply. Automatic mail
+//! (`Auto-Submitted` other than `no`, vacation responders, bulk mail) is
+//! dropped so two systems can't reply to each other forever. Each
+//! Message-ID is handled once, so provider retries are harmless. The claim
+//! on a Message-ID commits with the ticket or reply it produced, so a
+//! message that fails part way is taken again on the provider's retry.
+
+use base64::{engine::general_purpose, Engine as _};
+use mail_parser::{MessageParser, MimeHeaders};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::files::{self, NewFile, StoreFileError};
+use crate::services::{chat_notifications, outbound_webhooks};
+use crate::services::queue_assignment::auto_assign_ticket;
+use crate::services::ticket_routing::{apply_routing_rules, RoutableTicket};
+use crate::services::ticket_sla;
+
+/// The built-in inactive user that email-created tickets, replies and
+/// attachments are attributed to
+pub const INBOUND_EMAIL_USER_ID: Uuid = Uuid::from_u128(0xe4a1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum InboundError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct InboundAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// An inbound message, whichever way it arrived
+#[derive(Debug, Clone, Default)]
+pub struct InboundEmail {
+    pub from_email: String,
+    pub from_name: Option<String>,
+    /// To and Cc addresses
+    pub recipients: Vec<String>,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+    pub message_id: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub attachments: Vec<InboundAttachment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum InboundOutcome {
+    TicketCreated { ticket_id: Uuid, number: i32, attachments: usize },
+    ReplyAdded { ticket_id: Uuid, reply_id: Uuid, attachments: usize },
+    Ignored { reason: String },
+    Duplicate { message_id: String },
+}
+
+/// Postmark inbound webhook payload; only the fields used here
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkInbound {
+    pub from: String,
+    #[serde(default)]
+    pub from_name: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub cc: Option<String>,
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub text_body: Option<String>,
+    #[serde(default)]
+    pub html_body: Option<String>,
+    #[serde(rename = "MessageID", default)]
+    pub message_id: Option<String>,
+    #[serde(default)]
+    pub headers: Vec<PostmarkHeader>,
+    #[serde(default)]
+    pub attachments: Vec<PostmarkAttachment>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkAttachment {
+    pub name: String,
+    /// Base64
+    pub content: String,
+    pub content_type: String,
+}
+
+/// Bare addresses from a header value like `"Help Desk" <help@x.com>, b@y.com`
+fn parse_address_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter_map(|part| {
+            let part = part.trim();
+            let address = match (part.rfind('<'), part.rfind('>')) {
+                (Some(start), Some(end)) if start < end => &part[start + 1..end],
+                _ => part,
+            };
+            let address = address.trim().trim_matches('"');
+            address.contains('@').then(|| address.to_lowercase())
+        })
+        .collect()
+}
+
+impl From<PostmarkInbound> for InboundEmail {
+    fn from(payload: PostmarkInbound) -> Self {
+        let mut recipients = parse_address_list(payload.to.as_deref().unwrap_or_default());
+        recipients.extend(parse_address_list(payload.cc.as_deref().unwrap_or_default()));
+
+        // Postmark's MessageID is its own; prefer the sender's header when present
+        let header_message_id = payload
+            .headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("Message-ID"))
+            .map(|h| h.value.trim().trim_matches(|c| c == '<' || c == '>').to_string());
+
+        let attachments = payload
+            .attachments
+            .into_iter()
+            .filter_map(|a| match general_purpose::STANDARD.decode(a.content.as_bytes()) {
+                Ok(data) => Some(InboundAttachment { filename: a.name, content_type: a.content_type, data }),
+                Err(e) => {
+                    tracing::warn!("Skipping inbound attachment '{}' with invalid base64: {}", a.name, e);
+                    None
+                }
+            })
+            .collect();
+
+        InboundEmail {
+            from_email: parse_address_list(&payload.from).into_iter().next().unwrap_or_default(),
+            from_name: payload.from_name.filter(|n| !n.trim().is_empty()),
+            recipients,
+            subject: payload.subject.unwrap_or_default(),
+            text_body: payload.text_body.unwrap_or_default(),
+            html_body: payload.html_body,
+            message_id: header_message_id.or(payload.message_id),
+            headers: payload.headers.into_iter().map(|h| (h.name, h.value)).collect(),
+            attachments,
+        }
+    }
+}
+
+impl InboundEmail {
+    /// Parse a raw RFC 5322 message
+    pub fn from_mime(raw: &[u8]) -> Option<Self> {
+        let message = MessageParser::default().parse(raw)?;
+
+        let sender = message.from().and_then(|from| from.first());
+        let recipients = message
+            .to()
+            .into_iter()
+            .chain(message.cc())
+            .flat_map(|list| list.iter())
+            .filter_map(|addr| addr.address())
+            .map(|addr| addr.to_lowercase())
+            .collect();
+
+        let headers = message
+            .headers_raw()
+            .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+            .collect();
+
+        let attachments = message
+            .attachments()
+            .map(|part| InboundAttachment {
+                filename: part.attachment_name().unwrap_or("attachment").to_string(),
+                content_type: part
+                    .content_type()
+                    .map(|ct| match ct.subtype() {
+                        Some(subtype) => format!("{}/{}", ct.ctype(), subtype),
+                        None => ct.ctype().to_string(),
+                    })
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                data: part.contents().to_vec(),
+            })
+            .collect();
+
+        Some(InboundEmail {
+            from_email: sender.and_then(|addr| addr.address()).unwrap_or_default().to_lowercase(),
+            from_name: sender.and_then(|addr| addr.name()).map(str::to_string),
+            recipients,
+            subject: message.subject().unwrap_or_default().to_string(),
+            text_body: message.body_text(0).map(|b| b.to_string()).unwrap_or_default(),
+            html_body: message.body_html(0).map(|b| b.to_string()),
+            message_id: message.message_id().map(str::to_string),
+            headers,
+            attachments,
+        })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim())
+    }
+
+    /// Out-of-office replies, bounces and list mail. Answering these with a
+    /// ticket confirmation is how mail loops start.
+    pub fn is_auto_generated(&self) -> bool {
+        if let Some(value) = self.header("Auto-Submitted") {
+            if !value.eq_ignore_ascii_case("no") {
+                return true;
+            }
+        }
+        if self.header("X-Autoreply").is_some() || self.header("X-Autorespond").is_some() {
+            return true;
+        }
+        if let Some(precedence) = self.header("Precedence") {
+            if ["bulk", "junk", "list", "auto_reply"].iter().any(|p| precedence.eq_ignore_ascii_case(p)) {
+                return true;
+            }
+        }
+        let local_part = self.from_email.split('@').next().unwrap_or_default();
+        local_part.eq_ignore_ascii_case("mailer-daemon") || local_part.eq_ignore_ascii_case("postmaster")
+    }
+
+    /// The body to store, falling back to the HTML part when there's no text
+    pub fn body(&self) -> String {
+        if !self.text_body.trim().is_empty() {
+            return self.text_body.trim().to_string();
+        }
+        self.html_body.as_deref().map(str::trim).unwrap_or_default().to_string()
+    }
+
+    /// Message-ID used to de-duplicate. Mail without one gets an id from
+    /// its sender, subject and body so a retried webhook still matches.
+    pub fn dedupe_key(&self) -> String {
+        match &self.message_id {
+            Some(id) if !id.trim().is_empty() => id.trim().to_string(),
+            _ => {
+                use sha2::{Digest, Sha256};
+                let digest = Sha256::digest(format!("{}\n{}\n{}", self.from_email, self.subject, self.text_body));
+                format!("generated-{}", hex::encode(digest))
+            }
+        }
+    }
+}
+
+/// The ticket number from a `[#123]` subject token
+pub fn ticket_number_from_subject(subject: &str) -> Option<i32> {
+    let token = regex::Regex::new(r"\[#(\d+)\]").ok()?;
+    token.captures(subject)?.get(1)?.as_str().parse().ok()
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct QueueMatch {
+    id: Uuid,
+    default_priority: Option<String>,
+    default_category_id: Option<Uuid>,
+}
+
+async fn find_queue(pool: &PgPool, recipients: &[String]) -> Result<Option<QueueMatch>, sqlx::Error> {
+    sqlx::query_as::<_, QueueMatch>(
+        r#"
+        SELECT id, default_priority, default_category_id FROM ticket_queues
+        WHERE LOWER(email_address) = ANY($1) AND COALESCE(is_active, true)
+        ORDER BY display_order ASC
+        LIMIT 1
+        "#
+    )
+    .bind(recipients)
+    .fetch_optional(pool)
+    .await
+}
+
+/// `(contact_id, client_id)` for the sender, ignoring archived contacts
+async fn find_contact(pool: &PgPool, email: &str) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, Uuid)>(
+        r#"
+        SELECT ct.id, ct.client_id FROM contacts ct
+        JOIN clients c ON c.id = ct.client_id
+        WHERE LOWER(ct.email) = LOWER($1) AND ct.archived_at IS NULL AND c.archived_at IS NULL
+        ORDER BY ct.is_primary DESC, ct.created_at ASC
+        LIMIT 1
+        "#
+    )
+    .bind(email)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Claim the message id. `false` means it was already processed.
+async fn claim<'e>(executor: impl PgExecutor<'e>, key: &str, email: &InboundEmail) -> Result<bool, sqlx::Error> {
+    let claimed = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO inbound_emails (message_id, from_email, subject)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (message_id) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(key)
+    .bind(&email.from_email)
+    .bind(&email.subject)
+    .fetch_optional(executor)
+    .await?;
+    Ok(claimed.is_some())
+}
+
+async fn finish<'e>(
+    executor: impl PgExecutor<'e>,
+    key: &str,
+    result: &str,
+    queue_id: Option<Uuid>,
+    ticket_id: Option<Uuid>,
+    reason: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE inbound_emails SET result = $2, queue_id = $3, ticket_id = $4, reason = $5 WHERE message_id = $1")
+        .bind(key)
+        .bind(result)
+        .bind(queue_id)
+        .bind(ticket_id)
+        .bind(reason)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Store attachments against the ticket. Infected or unstorable files are
+/// logged and skipped; they don't lose the message.
+async fn attach_files(pool: &PgPool, email: &InboundEmail, client_id: Uuid, ticket_id: Uuid) -> usize {
+    let mut stored = 0;
+    for attachment in &email.attachments {
+        let file = NewFile {
+            id: Uuid::new_v4(),
+            client_id: Some(client_id),
+            ticket_id: Some(ticket_id),
+            asset_id: None,
+            project_id: None,
+            kb_article_id: None,
+            uploaded_by: INBOUND_EMAIL_USER_ID,
+            original_filename: &attachment.filename,
+            mime_type: &attachment.content_type,
+            data: &attachment.data,
+        };
+        match files::store_file(pool, file).await {
+            Ok(_) => stored += 1,
+            Err(StoreFileError::Infected(signature)) => {
+                tracing::warn!("Dropped infected email attachment '{}' ({})", attachment.filename, signature)
+            }
+            Err(e) => tracing::error!("Failed to store email attachment '{}': {}", attachment.filename, e),
+        }
+    }
+    stored
+}
+
+/// Add the reply in `tx` and commit it, then apply the SLA and store
+/// attachments
+async fn add_reply(
+    pool: &PgPool,
+    mut tx: Transaction<'static, Postgres>,
+    email: &InboundEmail,
+    ticket_id: Uuid,
+    contact_id: Uuid,
+    client_id: Uuid,
+) -> Result<InboundOutcome, sqlx::Error> {
+    let reply_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO ticket_replies (ticket_id, user_id, contact_id, type, details)
+        VALUES ($1, $2, $3, 'reply', $4)
+        RETURNING id
+        "#
+    )
+    .bind(ticket_id)
+    .bind(INBOUND_EMAIL_USER_ID)
+    .bind(contact_id)
+    .bind(email.body())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE tickets SET updated_at = NOW() WHERE id = $1")
+        .bind(ticket_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    if let Err(e) = ticket_sla::apply_sla(pool, ticket_id).await {
+        tracing::error!("Error applying SLA to ticket {}: {}", ticket_id, e);
+    }
+
+    let attachments = attach_files(pool, email, client_id, ticket_id).await;
+    Ok(InboundOutcome::ReplyAdded { ticket_id, reply_id, attachments })
+}
+
+/// Open the ticket and record the message as handled in `tx`, then route,
+/// assign, store attachments and notify once it has committed
+async fn create_ticket(
+    pool: &PgPool,
+    mut tx: Transaction<'static, Postgres>,
+    key: &str,
+    email: &InboundEmail,
+    queue: Option<&QueueMatch>,
+    contact_id: Uuid,
+    client_id: Uuid,
+) -> Result<InboundOutcome, sqlx::Error> {
+    let subject = if email.subject.trim().is_empty() { "(no subject)".to_string() } else { email.subject.trim().to_string() };
+    let priority = queue.and_then(|q| q.default_priority.clone()).unwrap_or_else(|| "medium".to_string());
+    let queue_id = queue.map(|q| q.id);
+
+    let (ticket_id, number) = sqlx::query_as::<_, (Uuid, i32)>(
+        r#"
+        INSERT INTO tickets (number, client_id, contact_id, queue_id, category_id, opened_by,
+                             subject, details, status, priority, source)
+        SELECT COALESCE(MAX(number), 0) + 1, $1, $2, $3, $4, $5, $6, $7, 'open', $8, 'email'
+        FROM tickets
+        RETURNING id, number
+        "#
+    )
+    .bind(client_id)
+    .bind(contact_id)
+    .bind(queue_id)
+    .bind(queue.and_then(|q| q.default_category_id))
+    .bind(INBOUND_EMAIL_USER_ID)
+    .bind(&subject)
+    .bind(email.body())
+    .bind(&priority)
+    .fetch_one(&mut *tx)
+    .await?;
+    finish(&mut *tx, key, "ticket_created", queue_id, Some(ticket_id), None).await?;
+    tx.commit().await?;
+    crate::middleware::prometheus::record_ticket_created("email");
+
+    // Routing and assignment failures shouldn't lose the ticket
+    let routable = RoutableTicket {
+        id: ticket_id,
+        client_id,
+        contact_id: Some(contact_id),
+        category_id: queue.and_then(|q| q.default_category_id),
+        subject,
+        priority,
+        source: "email".to_string(),
+        source_email: Some(email.from_email.clone()),
+    };
+    let routed_queue = match apply_routing_rules(pool, &routable).await {
+        Ok(outcome) => outcome.queue_id.or(queue_id),
+        Err(e) => {
+            tracing::error!("Error applying routing rules to ticket {}: {}", ticket_id, e);
+            queue_id
+        }
+    };
+    if let Some(queue_id) = routed_queue {
+        if let Err(e) = auto_assign_ticket(pool, ticket_id, queue_id).await {
+            tracing::error!("Error auto-assigning ticket {}: {}", ticket_id, e);
+        }
+    }
+
+    let attachments = attach_files(pool, email, client_id, ticket_id).await;
+
+    let data = serde_json::json!({
+        "ticket_id": ticket_id,
+        "number": number,
+        "client_id": client_id,
+        "contact_id": contact_id,
+        "subject": routable.subject,
+        "source": "email"
+    });
+    outbound_webhooks::notify(pool, outbound_webhooks::TICKET_CREATED, data).await;
+    chat_notifications::notify_ticket_created(pool, ticket_id).await;
+
+    Ok(InboundOutcome::TicketCreated { ticket_id, number, attachments })
+}
+
+/// Turn one inbound message into a new ticket or a reply
+pub async fn ingest(pool: &PgPool, email: &InboundEmail) -> Result<InboundOutcome, InboundError> {
+    let key = email.dedupe_key();
+    let mut tx = pool.begin().await?;
+    if !claim(&mut *tx, &key, email).await? {
+        return Ok(InboundOutcome::Duplicate { message_id: key });
+    }
+
+    let ignore = |reason: &str| InboundOutcome::Ignored { reason: reason.to_string() };
+
+    if email.is_auto_generated() {
+        finish(&mut *tx, &key, "ignored", None, None, Some("auto_generated")).await?;
+        tx.commit().await?;
+        return Ok(ignore("auto_generated"));
+    }
+
+    let queue = find_queue(pool, &email.recipients).await?;
+    let queue_id = queue.as_ref().map(|q| q.id);
+
+    let Some((contact_id, client_id)) = find_contact(pool, &email.from_email).await? else {
+        tracing::info!("Ignoring inbound email from unknown sender {}", email.from_email);
+        finish(&mut *tx, &key, "ignored", queue_id, None, Some("unknown_sender")).await?;
+        tx.commit().await?;
+        return Ok(ignore("unknown_sender"));
+    };
+
+    // Only thread onto tickets that belong to the sender's client, so a
+    // guessed ticket number can't be used to post into someone else's ticket
+    if let Some(number) = ticket_number_from_subject(&email.subject) {
+        let ticket_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM tickets WHERE number = $1 AND client_id = $2")
+            .bind(number)
+            .bind(client_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(ticket_id) = ticket_id {
+            finish(&mut *tx, &key, "reply_added", queue_id, Some(ticket_id), None).await?;
+            return Ok(add_reply(pool, tx, email, ticket_id, contact_id, client_id).await?);
+        }
+    }
+
+    Ok(create_ticket(pool, tx, &key, email, queue.as_ref(), contact_id, client_id).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &str = "From: \"Jane Doe\" <Jane@Acme.example>\r\n\
+To: Support <support@msp.example>\r\n\
+Cc: boss@acme.example\r\n\
+Subject: Re: [#1042] Printer offline\r\n\
+Message-ID: <abc123@acme.example>\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Still offline after the restart.\r\n\
+--b1\r\n\
+Content-Type: text/plain; name=\"log.txt\"\r\n\
+Content-Disposition: attachment; filename=\"log.txt\"\r\n\
+\r\n\
+paper jam\r\n\
+--b1--\r\n";
+
+    #[test]
+    fn test_parse_mime() {
+        let email = InboundEmail::from_mime(RAW.as_bytes()).unwrap();
+        assert_eq!(email.from_email, "jane@acme.example");
+        assert_eq!(email.from_name.as_deref(), Some("Jane Doe"));
+        assert_eq!(email.recipients, vec!["support@msp.example", "boss@acme.example"]);
+        assert_eq!(email.message_id.as_deref(), Some("abc123@acme.example"));
+        assert_eq!(email.body(), "Still offline after the restart.");
+        assert_eq!(email.attachments.len(), 1);
+        assert_eq!(email.attachments[0].filename, "log.txt");
+        assert_eq!(email.attachments[0].content_type, "text/plain");
+        assert!(!email.is_auto_generated());
+    }
+
+    #[test]
+    fn test_ticket_number_from_subject() {
+        assert_eq!(ticket_number_from_subject("Re: [#1042] Printer offline"), Some(1042));
+        assert_eq!(ticket_number_from_subject("[Ticket #7] Created"), None);
+        assert_eq!(ticket_number_from_subject("Order #1042 shipped"), None);
+    }
+
+    #[test]
+    fn test_auto_generated_mail_is_detected() {
+        let with_header = |name: &str, value: &str| InboundEmail {
+            from_email: "jane@acme.example".to_string(),
+            headers: vec![(name.to_string(), value.to_string())],
+            ..Default::default()
+        };
+        assert!(with_header("Auto-Submitted", "auto-replied").is_auto_generated());
+        assert!(!with_header("Auto-Submitted", "no").is_auto_generated());
+        assert!(with_header("Precedence", "bulk").is_auto_generated());
+        assert!(with_header("X-Autoreply", "yes").is_auto_generated());
+
+        let bounce = InboundEmail { from_email: "MAILER-DAEMON@mx.example".to_string(), ..Default::default() };
+        assert!(bounce.is_auto_generated());
+    }
+
+    #[test]
+    fn test_postmark_payload() {
+        let payload: PostmarkInbound = serde_json::from_value(serde_json::json!({
+            "From": "jane@acme.example",
+            "FromName": "Jane Doe",
+            "To": "\"Support\" <Support@MSP.example>",
+            "Subject": "VPN down",
+            "TextBody": "Can't connect",
+            "MessageID": "postmark-id",
+            "Headers": [{ "Name": "Message-ID", "Value": "<orig@acme.example>" }],
+            "Attachments": [{ "Name": "shot.png", "Content": "aGVsbG8=", "ContentType": "image/png" }]
+        }))
+        .unwrap();
+
+        let email = InboundEmail::from(payload);
+        assert_eq!(email.recipients, vec!["support@msp.example"]);
+        assert_eq!(email.message_id.as_deref(), Some("orig@acme.example"));
+        assert_eq!(email.attachments[0].data, b"hello");
+    }
+
+    #[test]
+    fn test_dedupe_key_without_message_id_is_stable() {
+        let email = InboundEmail { from_email: "a@b.example".to_string(), subject: "Hi".to_string(), ..Default::default() };
+        assert_eq!(email.dedupe_key(), email.clone().dedupe_key());
+        assert!(email.dedupe_key().starts_with("generated-"));
+    }
+}
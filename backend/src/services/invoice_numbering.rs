@@ -0,0 +1,229 @@
+//! Invoice numbering
+//!
+//! Numbers are a prefix followed by a zero-padded counter, e.g. `INV-00042`
+//! or, with a `{YYYY}` token and a yearly reset, `INV-2024-0007`. Each
+//! numbering period has its own row in `invoice_number_sequences`; taking
+//! the next number locks that row until the invoice's transaction ends, so
+//! concurrent invoices queue for it and a rolled-back invoice gives its
+//! number back. A number can still collide with one typed in by hand, so
+//! callers insert under a savepoint and take another number on
+//! [`is_number_conflict`], up to [`MAX_NUMBER_ATTEMPTS`] times.
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+/// Period key used when numbers never reset
+const ALL_TIME_PERIOD: &str = "all";
+
+pub const MAX_PADDING: i32 = 12;
+
+/// Numbers to try for one invoice before giving up on conflicts
+pub const MAX_NUMBER_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetCadence {
+    #[default]
+    Never,
+    Yearly,
+    Monthly,
+}
+
+impl ResetCadence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResetCadence::Never => "never",
+            ResetCadence::Yearly => "yearly",
+            ResetCadence::Monthly => "monthly",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "never" => Some(ResetCadence::Never),
+            "yearly" => Some(ResetCadence::Yearly),
+            "monthly" => Some(ResetCadence::Monthly),
+            _ => None,
+        }
+    }
+}
+
+/// The organisation's invoice number format, kept on `company_settings`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvoiceNumbering {
+    /// Text before the counter; `{YYYY}` and `{MM}` are replaced with the
+    /// invoice date's year and month
+    pub prefix: String,
+    /// Minimum digits in the counter
+    pub padding: i32,
+    #[serde(default)]
+    pub reset: ResetCadence,
+}
+
+impl Default for InvoiceNumbering {
+    fn default() -> Self {
+        Self { prefix: "INV-".to_string(), padding: 5, reset: ResetCadence::Never }
+    }
+}
+
+impl InvoiceNumbering {
+    /// Why the scheme can't be used, if it can't. A counter that resets has
+    /// to be paired with the date tokens or the numbers would repeat.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.prefix.len() > 50 {
+            return Err("Prefix must be at most 50 characters".to_string());
+        }
+        if !(1..=MAX_PADDING).contains(&self.padding) {
+            return Err(format!("Padding must be between 1 and {}", MAX_PADDING));
+        }
+        let has_year = self.prefix.contains("{YYYY}");
+        match self.reset {
+            ResetCadence::Yearly if !has_year => {
+                Err("A yearly reset needs {YYYY} in the prefix".to_string())
+            }
+            ResetCadence::Monthly if !(has_year && self.prefix.contains("{MM}")) => {
+                Err("A monthly reset needs {YYYY} and {MM} in the prefix".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The counter an invoice dated `date` draws from
+    pub fn period_key(&self, date: NaiveDate) -> String {
+        match self.reset {
+            ResetCadence::Never => ALL_TIME_PERIOD.to_string(),
+            ResetCadence::Yearly => format!("{:04}", date.year()),
+            ResetCadence::Monthly => format!("{:04}-{:02}", date.year(), date.month()),
+        }
+    }
+
+    /// The invoice number for the `sequence`th invoice of its period
+    pub fn format(&self, date: NaiveDate, sequence: i64) -> String {
+        let prefix = self
+            .prefix
+            .replace("{YYYY}", &format!("{:04}", date.year()))
+            .replace("{MM}", &format!("{:02}", date.month()));
+        format!("{}{:0width$}", prefix, sequence, width = self.padding.max(1) as usize)
+    }
+}
+
+/// The configured scheme, or the default if company settings are missing
+pub async fn load(conn: &mut PgConnection) -> Result<InvoiceNumbering, sqlx::Error> {
+    let row = sqlx::query_as::<_, (String, i32, String)>(
+        "SELECT invoice_number_prefix, invoice_number_padding, invoice_number_reset
+         FROM company_settings ORDER BY created_at LIMIT 1",
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row
+        .map(|(prefix, padding, reset)| InvoiceNumbering {
+            prefix,
+            padding,
+            reset: ResetCadence::parse(&reset).unwrap_or_default(),
+        })
+        .unwrap_or_default())
+}
+
+pub async fn save(conn: &mut PgConnection, numbering: &InvoiceNumbering) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE company_settings SET
+            invoice_number_prefix = $1, invoice_number_padding = $2, invoice_number_reset = $3,
+            updated_at = NOW()
+         WHERE id = (SELECT id FROM company_settings ORDER BY created_at LIMIT 1)",
+    )
+    .bind(&numbering.prefix)
+    .bind(numbering.padding)
+    .bind(numbering.reset.as_str())
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// The number the next invoice dated `date` would get, without taking it
+pub async fn peek_invoice_number(conn: &mut PgConnection, date: NaiveDate) -> Result<String, sqlx::Error> {
+    let numbering = load(&mut *conn).await?;
+    let last: Option<i64> = sqlx::query_scalar("SELECT last_value FROM invoice_number_sequences WHERE period_key = $1")
+        .bind(numbering.period_key(date))
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    Ok(numbering.format(date, last.unwrap_or(0) + 1))
+}
+
+/// Take the next invoice number for an invoice dated `date`. Call this on
+/// the transaction that inserts the invoice: the period's counter stays
+/// locked until it commits or rolls back.
+pub async fn next_invoice_number(conn: &mut PgConnection, date: NaiveDate) -> Result<String, sqlx::Error> {
+    let numbering = load(&mut *conn).await?;
+    let sequence: i64 = sqlx::query_scalar(
+        "INSERT INTO invoice_number_sequences (period_key, last_value)
+         VALUES ($1, 1)
+         ON CONFLICT (period_key) DO UPDATE
+            SET last_value = invoice_number_sequences.last_value + 1, updated_at = NOW()
+         RETURNING last_value",
+    )
+    .bind(numbering.period_key(date))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(numbering.format(date, sequence))
+}
+
+/// Whether an insert failed because its invoice number is already used
+pub fn is_number_conflict(e: &sqlx::Error) -> bool {
+    matches!(
+        e,
+        sqlx::Error::Database(db)
+            if db.code().as_deref() == Some("23505") && db.constraint() == Some("invoices_number_key")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_default_matches_existing_numbers() {
+        let numbering = InvoiceNumbering::default();
+        assert_eq!(numbering.format(date(2024, 3, 1), 42), "INV-00042");
+        assert_eq!(numbering.format(date(2024, 3, 1), 123456), "INV-123456");
+        assert_eq!(numbering.period_key(date(2024, 3, 1)), "all");
+    }
+
+    #[test]
+    fn test_date_tokens_and_periods() {
+        let yearly = InvoiceNumbering { prefix: "INV-{YYYY}-".to_string(), padding: 4, reset: ResetCadence::Yearly };
+        assert_eq!(yearly.format(date(2024, 12, 31), 7), "INV-2024-0007");
+        assert_eq!(yearly.period_key(date(2024, 12, 31)), "2024");
+        assert_eq!(yearly.period_key(date(2025, 1, 1)), "2025");
+
+        let monthly = InvoiceNumbering { prefix: "{YYYY}{MM}/".to_string(), padding: 3, reset: ResetCadence::Monthly };
+        assert_eq!(monthly.format(date(2024, 3, 9), 12), "202403/012");
+        assert_eq!(monthly.period_key(date(2024, 3, 9)), "2024-03");
+    }
+
+    #[test]
+    fn test_resetting_schemes_need_date_tokens() {
+        assert!(InvoiceNumbering::default().validate().is_ok());
+
+        let mut numbering = InvoiceNumbering { reset: ResetCadence::Yearly, ..Default::default() };
+        assert!(numbering.validate().is_err());
+        numbering.prefix = "INV-{YYYY}-".to_string();
+        assert!(numbering.validate().is_ok());
+
+        numbering.reset = ResetCadence::Monthly;
+        assert!(numbering.validate().is_err());
+        numbering.prefix = "INV-{YYYY}{MM}-".to_string();
+        assert!(numbering.validate().is_ok());
+
+        numbering.padding = 0;
+        assert!(numbering.validate().is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! Invoice payment application
+//!
+//! Works out the new balance and status for an invoice when a payment is
+//! recorded. Kept free of I/O so the handler can run it inside the same
+//! transaction that locks the invoice row.
+
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PaymentError {
+    #[error("Payment amount must be greater than zero")]
+    NonPositiveAmount,
+    #[error("Payment of {amount} exceeds the remaining balance of {balance}")]
+    Overpayment { amount: Decimal, balance: Decimal },
+    #[error("Payments cannot be recorded against a {0} invoice")]
+    InvalidStatus(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentOutcome {
+    pub balance: Decimal,
+    pub status: String,
+}
+
+/// Statuses that can no longer accept payments
+pub const CLOSED_STATUSES: &[&str] = &["paid", "void", "cancelled"];
+
+/// Apply `amount` to an invoice with the given `total`, current `balance` and `status`
+pub fn apply_payment(
+    total: Decimal,
+    balance: Decimal,
+    status: &str,
+    amount: Decimal,
+) -> Result<PaymentOutcome, PaymentError> {
+    if CLOSED_STATUSES.contains(&status) {
+        return Err(PaymentError::InvalidStatus(status.to_string()));
+    }
+    if amount <= Decimal::ZERO {
+        return Err(PaymentError::NonPositiveAmount);
+    }
+    if amount > balance {
+        return Err(PaymentError::Overpayment { amount, balance });
+    }
+
+    let balance = balance - amount;
+    let status = if balance.is_zero() {
+        "paid".to_string()
+    } else if balance < total {
+        "partial".to_string()
+    } else {
+        // Balance still at or above the total (e.g. after late fees); leave status alone
+        status.to_string()
+    };
+
+    Ok(PaymentOutcome { balance, status })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_partial_then_paid() {
+        let total = dec("1000.00");
+
+        let first = apply_payment(total, total, "sent", dec("400.00")).unwrap();
+        assert_eq!(first.balance, dec("600.00"));
+        assert_eq!(first.status, "partial");
+
+        let second = apply_payment(total, first.balance, &first.status, dec("250.00")).unwrap();
+        assert_eq!(second.balance, dec("350.00"));
+        assert_eq!(second.status, "partial");
+
+        let last = apply_payment(total, second.balance, &second.status, dec("350.00")).unwrap();
+        assert_eq!(last.balance, Decimal::ZERO);
+        assert_eq!(last.status, "paid");
+    }
+
+    #[test]
+    fn test_overpayment_rejected() {
+        let err = apply_payment(dec("500.00"), dec("100.00"), "partial", dec("100.01")).unwrap_err();
+        assert_eq!(
+            err,
+            PaymentError::Overpayment {
+                amount: dec("100.01"),
+                balance: dec("100.00"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_non_positive_and_closed_invoices_rejected() {
+        assert_eq!(
+            apply_payment(dec("500.00"), dec("500.00"), "sent", Decimal::ZERO),
+            Err(PaymentError::NonPositiveAmount)
+        );
+        assert_eq!(
+            apply_payment(dec("500.00"), Decimal::ZERO, "paid", dec("1.00")),
+            Err(PaymentError::InvalidStatus("paid".to_string()))
+        );
+    }
+}
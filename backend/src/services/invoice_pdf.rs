@@ -0,0 +1,482 @@
+//! Invoice PDF rendering
+//!
+//! Lays out an invoice (company branding, client details, line items, totals,
+//! payment terms and notes) on A4 pages using the built-in PDF fonts.
+
+use chrono::NaiveDate;
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference,
+    PdfLayerReference, Point,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 18.0;
+const BOTTOM_MARGIN: f32 = 25.0;
+const LINE_HEIGHT: f32 = 6.0;
+
+// Column x-positions for the line item table
+const COL_DESCRIPTION: f32 = MARGIN;
+const COL_QUANTITY_RIGHT: f32 = 128.0;
+const COL_UNIT_PRICE_RIGHT: f32 = 158.0;
+const COL_TOTAL_RIGHT: f32 = PAGE_WIDTH - MARGIN;
+const MAX_DESCRIPTION_CHARS: usize = 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvoicePdfError {
+    #[error("PDF rendering error: {0}")]
+    Render(String),
+}
+
+/// MSP branding loaded from the `company_settings` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct CompanyBranding {
+    pub company_name: String,
+    pub tax_id: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub zip: Option<String>,
+    pub country: Option<String>,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub website: Option<String>,
+    pub invoice_footer: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct InvoicePdfClient {
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub zip: Option<String>,
+    pub billing_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct InvoicePdfLine {
+    pub description: String,
+    pub quantity: Decimal,
+    pub unit_price: Decimal,
+    pub line_total: Decimal,
+    pub tax_rate: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoicePdfData {
+    pub number: String,
+    pub date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub status: String,
+    pub payment_terms: String,
+    pub subtotal: Decimal,
+    pub tax_amount: Decimal,
+    pub discount_amount: Option<Decimal>,
+    pub total: Decimal,
+    pub balance: Decimal,
+    pub notes: Option<String>,
+    pub terms: Option<String>,
+    pub company: CompanyBranding,
+    pub client: InvoicePdfClient,
+    pub line_items: Vec<InvoicePdfLine>,
+}
+
+/// File name used in the `Content-Disposition` header for a downloaded invoice
+pub fn pdf_filename(invoice_number: &str) -> String {
+    let safe: String = invoice_number
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("invoice-{}.pdf", safe)
+}
+
+/// Render an invoice to PDF bytes
+pub fn render_invoice_pdf(data: &InvoicePdfData) -> Result<Vec<u8>, InvoicePdfError> {
+    let title = format!("Invoice {}", data.number);
+    let (doc, page, layer) = PdfDocument::new(&title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+
+    let regular = doc
+        .add_builtin_font(BuiltinFont::Helvetica)
+        .map_err(|e| InvoicePdfError::Render(e.to_string()))?;
+    let bold = doc
+        .add_builtin_font(BuiltinFont::HelveticaBold)
+        .map_err(|e| InvoicePdfError::Render(e.to_string()))?;
+
+    let mut writer = PageWriter {
+        doc: &doc,
+        layer: doc.get_page(page).get_layer(layer),
+        y: PAGE_HEIGHT - MARGIN,
+        regular,
+        bold,
+    };
+
+    writer.write_header(data);
+    writer.write_parties(data);
+    writer.write_line_items(&data.line_items);
+    writer.write_totals(data);
+    writer.write_notes(data);
+
+    doc.save_to_bytes()
+        .map_err(|e| InvoicePdfError::Render(e.to_string()))
+}
+
+struct PageWriter<'a> {
+    doc: &'a PdfDocumentReference,
+    layer: PdfLayerReference,
+    y: f32,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+}
+
+impl PageWriter<'_> {
+    fn write_header(&mut self, data: &InvoicePdfData) {
+        let company = &data.company;
+        self.text(&company.company_name, 18.0, MARGIN, true);
+        self.text_right("INVOICE", 18.0, COL_TOTAL_RIGHT, true);
+        self.advance(LINE_HEIGHT + 2.0);
+
+        let mut company_lines: Vec<String> = Vec::new();
+        company_lines.extend(company.address.clone());
+        if let Some(locality) = locality_line(&company.city, &company.state, &company.zip) {
+            company_lines.push(locality);
+        }
+        company_lines.extend(company.country.clone());
+        company_lines.extend(company.phone.clone());
+        company_lines.extend(company.email.clone());
+        company_lines.extend(company.website.clone());
+        if let Some(tax_id) = &company.tax_id {
+            company_lines.push(format!("Tax ID: {}", tax_id));
+        }
+
+        let meta_lines = [
+            format!("Invoice #: {}", data.number),
+            format!("Date: {}", data.date.format("%Y-%m-%d")),
+            format!("Due: {}", data.due_date.format("%Y-%m-%d")),
+            format!("Terms: {}", humanize_terms(&data.payment_terms)),
+            format!("Status: {}", data.status.to_uppercase()),
+        ];
+
+        let rows = company_lines.len().max(meta_lines.len());
+        for i in 0..rows {
+            if let Some(line) = company_lines.get(i) {
+                self.text(line, 9.0, MARGIN, false);
+            }
+            if let Some(line) = meta_lines.get(i) {
+                self.text_right(line, 9.0, COL_TOTAL_RIGHT, false);
+            }
+            self.advance(LINE_HEIGHT - 1.5);
+        }
+
+        self.advance(LINE_HEIGHT);
+    }
+
+    fn write_parties(&mut self, data: &InvoicePdfData) {
+        let client = &data.client;
+        self.text("Bill To", 11.0, MARGIN, true);
+        self.advance(LINE_HEIGHT);
+
+        self.text(&client.name, 10.0, MARGIN, false);
+        self.advance(LINE_HEIGHT - 1.5);
+
+        let address = client.billing_address.as_ref().or(client.address.as_ref());
+        for line in address.into_iter().flat_map(|a| a.lines()) {
+            self.text(line.trim(), 9.0, MARGIN, false);
+            self.advance(LINE_HEIGHT - 1.5);
+        }
+        if client.billing_address.is_none() {
+            if let Some(locality) = locality_line(&client.city, &client.state, &client.zip) {
+                self.text(&locality, 9.0, MARGIN, false);
+                self.advance(LINE_HEIGHT - 1.5);
+            }
+        }
+        for line in [&client.email, &client.phone].into_iter().flatten() {
+            self.text(line, 9.0, MARGIN, false);
+            self.advance(LINE_HEIGHT - 1.5);
+        }
+
+        self.advance(LINE_HEIGHT);
+    }
+
+    fn write_line_items(&mut self, items: &[InvoicePdfLine]) {
+        self.write_table_header();
+
+        for item in items {
+            if self.y < BOTTOM_MARGIN + LINE_HEIGHT {
+                self.new_page();
+                self.write_table_header();
+            }
+
+            self.text(&truncate(&item.description, MAX_DESCRIPTION_CHARS), 9.0, COL_DESCRIPTION, false);
+            self.text_right(&format_quantity(item.quantity), 9.0, COL_QUANTITY_RIGHT, false);
+            self.text_right(&format_money(item.unit_price), 9.0, COL_UNIT_PRICE_RIGHT, false);
+            self.text_right(&format_money(item.line_total), 9.0, COL_TOTAL_RIGHT, false);
+            self.advance(LINE_HEIGHT);
+        }
+
+        self.rule();
+        self.advance(LINE_HEIGHT);
+    }
+
+    fn write_table_header(&mut self) {
+        self.text("Description", 9.0, COL_DESCRIPTION, true);
+        self.text_right("Qty", 9.0, COL_QUANTITY_RIGHT, true);
+        self.text_right("Unit Price", 9.0, COL_UNIT_PRICE_RIGHT, true);
+        self.text_right("Amount", 9.0, COL_TOTAL_RIGHT, true);
+        self.advance(2.0);
+        self.rule();
+        self.advance(LINE_HEIGHT - 1.0);
+    }
+
+    fn write_totals(&mut self, data: &InvoicePdfData) {
+        if self.y < BOTTOM_MARGIN + LINE_HEIGHT * 5.0 {
+            self.new_page();
+        }
+
+        let mut rows = vec![("Subtotal", data.subtotal)];
+        if let Some(discount) = data.discount_amount.filter(|d| !d.is_zero()) {
+            rows.push(("Discount", -discount));
+        }
+        rows.push(("Tax", data.tax_amount));
+        rows.push(("Total", data.total));
+        if data.balance != data.total {
+            rows.push(("Amount Paid", data.total - data.balance));
+        }
+
+        for (label, amount) in rows {
+            self.text_right(label, 10.0, COL_UNIT_PRICE_RIGHT, label == "Total");
+            self.text_right(&format_money(amount), 10.0, COL_TOTAL_RIGHT, label == "Total");
+            self.advance(LINE_HEIGHT);
+        }
+
+        self.advance(2.0);
+        self.text_right("Balance Due", 12.0, COL_UNIT_PRICE_RIGHT, true);
+        self.text_right(&format_money(data.balance), 12.0, COL_TOTAL_RIGHT, true);
+        self.advance(LINE_HEIGHT * 2.0);
+    }
+
+    fn write_notes(&mut self, data: &InvoicePdfData) {
+        let sections = [
+            ("Notes", data.notes.as_deref()),
+            ("Terms", data.terms.as_deref()),
+            ("", data.company.invoice_footer.as_deref()),
+        ];
+
+        for (heading, body) in sections {
+            let Some(body) = body.filter(|b| !b.trim().is_empty()) else {
+                continue;
+            };
+
+            if self.y < BOTTOM_MARGIN + LINE_HEIGHT * 2.0 {
+                self.new_page();
+            }
+            if !heading.is_empty() {
+                self.text(heading, 10.0, MARGIN, true);
+                self.advance(LINE_HEIGHT);
+            }
+            for line in wrap(body, 95) {
+                if self.y < BOTTOM_MARGIN {
+                    self.new_page();
+                }
+                self.text(&line, 9.0, MARGIN, false);
+                self.advance(LINE_HEIGHT - 1.5);
+            }
+            self.advance(LINE_HEIGHT);
+        }
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn advance(&mut self, amount: f32) {
+        self.y -= amount;
+    }
+
+    fn text(&self, text: &str, size: f32, x: f32, bold: bool) {
+        let font = if bold { &self.bold } else { &self.regular };
+        self.layer.use_text(text, size, Mm(x), Mm(self.y), font);
+    }
+
+    fn text_right(&self, text: &str, size: f32, right_edge: f32, bold: bool) {
+        let x = (right_edge - approx_text_width(text, size)).max(MARGIN);
+        self.text(text, size, x, bold);
+    }
+
+    fn rule(&self) {
+        let line = Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(self.y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y)), false),
+            ],
+            is_closed: false,
+        };
+        self.layer.add_line(line);
+    }
+}
+
+/// Approximate rendered width in mm for Helvetica (average glyph ~0.5em)
+fn approx_text_width(text: &str, size_pt: f32) -> f32 {
+    const PT_TO_MM: f32 = 0.3528;
+    text.chars().count() as f32 * size_pt * 0.5 * PT_TO_MM
+}
+
+fn locality_line(city: &Option<String>, state: &Option<String>, zip: &Option<String>) -> Option<String> {
+    let city_state = [city.as_deref(), state.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let line = match zip.as_deref() {
+        Some(zip) if city_state.is_empty() => zip.to_string(),
+        Some(zip) => format!("{} {}", city_state, zip),
+        None => city_state,
+    };
+    (!line.is_empty()).then_some(line)
+}
+
+fn humanize_terms(terms: &str) -> String {
+    match terms.strip_prefix("net_") {
+        Some(days) => format!("Net {}", days),
+        None => terms.replace('_', " "),
+    }
+}
+
+fn format_money(amount: Decimal) -> String {
+    let rounded = amount.round_dp(2);
+    if rounded.is_sign_negative() {
+        format!("-${:.2}", rounded.abs())
+    } else {
+        format!("${:.2}", rounded)
+    }
+}
+
+fn format_quantity(quantity: Decimal) -> String {
+    quantity.round_dp(2).normalize().to_string()
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            if !current.is_empty() && current.len() + word.len() + 1 > width {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn sample_invoice() -> InvoicePdfData {
+        InvoicePdfData {
+            number: "INV-00042".to_string(),
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            due_date: NaiveDate::from_ymd_opt(2024, 3, 31).unwrap(),
+            status: "sent".to_string(),
+            payment_terms: "net_30".to_string(),
+            subtotal: dec("1650.00"),
+            tax_amount: dec("82.50"),
+            discount_amount: None,
+            total: dec("1732.50"),
+            balance: dec("1732.50"),
+            notes: Some("Thank you for your business.".to_string()),
+            terms: None,
+            company: CompanyBranding {
+                company_name: "Resolve MSP".to_string(),
+                ..Default::default()
+            },
+            client: InvoicePdfClient {
+                name: "Acme Corp".to_string(),
+                email: Some("billing@acme.test".to_string()),
+                phone: None,
+                address: Some("1 Main St".to_string()),
+                city: Some("Springfield".to_string()),
+                state: Some("IL".to_string()),
+                zip: Some("62701".to_string()),
+                billing_address: None,
+            },
+            line_items: vec![
+                InvoicePdfLine {
+                    description: "Managed IT Services".to_string(),
+                    quantity: dec("1"),
+                    unit_price: dec("1500.00"),
+                    line_total: dec("1500.00"),
+                    tax_rate: Some(dec("5")),
+                },
+                InvoicePdfLine {
+                    description: "Cloud Backup".to_string(),
+                    quantity: dec("2"),
+                    unit_price: dec("75.00"),
+                    line_total: dec("150.00"),
+                    tax_rate: Some(dec("5")),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_produces_pdf() {
+        let bytes = render_invoice_pdf(&sample_invoice()).expect("render failed");
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_render_paginates_long_invoices() {
+        let mut invoice = sample_invoice();
+        let line = invoice.line_items[0].clone();
+        invoice.line_items = vec![line; 120];
+
+        let bytes = render_invoice_pdf(&invoice).expect("render failed");
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_pdf_filename_is_header_safe() {
+        assert_eq!(pdf_filename("INV-00042"), "invoice-INV-00042.pdf");
+        assert_eq!(pdf_filename("2024/03 \"A\""), "invoice-2024_03__A_.pdf");
+    }
+
+    #[test]
+    fn test_formatting_helpers() {
+        assert_eq!(format_money(dec("1732.5")), "$1732.50");
+        assert_eq!(format_money(dec("-25")), "-$25.00");
+        assert_eq!(format_quantity(dec("1.50")), "1.5");
+        assert_eq!(humanize_terms("net_30"), "Net 30");
+        assert_eq!(humanize_terms("due_on_receipt"), "due on receipt");
+        assert_eq!(
+            locality_line(&Some("Springfield".into()), &Some("IL".into()), &Some("62701".into())),
+            Some("Springfield, IL 62701".to_string())
+        );
+        assert_eq!(locality_line(&None, &None, &None), None);
+    }
+}
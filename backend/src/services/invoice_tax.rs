@@ -0,0 +1,142 @@
+//! Invoice tax calculation
+//!
+//! Tax rates are stored as percentages (e.g. `8.25` for 8.25%). Tax is computed
+//! per line and rounded to cents before summing so invoice headers always
+//! reconcile with the stored line items.
+
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// A single invoice line as far as tax is concerned
+#[derive(Debug, Clone, Copy)]
+pub struct TaxableLine {
+    /// Pre-tax amount for the line (normally `quantity * unit_price`)
+    pub amount: Decimal,
+    /// Line-specific rate; falls back to the invoice default when `None`
+    pub tax_rate: Option<Decimal>,
+}
+
+impl TaxableLine {
+    pub fn new(quantity: Decimal, unit_price: Decimal, tax_rate: Option<Decimal>) -> Self {
+        Self {
+            amount: quantity * unit_price,
+            tax_rate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvoiceTotals {
+    pub subtotal: Decimal,
+    pub tax_amount: Decimal,
+    pub total: Decimal,
+}
+
+/// Round a monetary amount to cents, half away from zero
+pub fn round_money(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// Effective rate for a line, or `None` when the line is untaxed
+pub fn effective_rate(line: &TaxableLine, default_rate: Option<Decimal>, tax_exempt: bool) -> Option<Decimal> {
+    if tax_exempt {
+        return None;
+    }
+    line.tax_rate.or(default_rate).filter(|rate| *rate > Decimal::ZERO)
+}
+
+/// Tax owed on a single line, rounded to cents
+pub fn line_tax(line: &TaxableLine, default_rate: Option<Decimal>, tax_exempt: bool) -> Decimal {
+    match effective_rate(line, default_rate, tax_exempt) {
+        Some(rate) => round_money(line.amount * rate / Decimal::ONE_HUNDRED),
+        None => Decimal::ZERO,
+    }
+}
+
+/// Compute subtotal, tax and total for a set of invoice lines
+pub fn calculate_invoice_totals(
+    lines: &[TaxableLine],
+    default_rate: Option<Decimal>,
+    tax_exempt: bool,
+) -> InvoiceTotals {
+    let subtotal = round_money(lines.iter().map(|line| line.amount).sum());
+    let tax_amount = lines
+        .iter()
+        .map(|line| line_tax(line, default_rate, tax_exempt))
+        .sum();
+
+    InvoiceTotals {
+        subtotal,
+        tax_amount,
+        total: subtotal + tax_amount,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_mixed_tax_rates() {
+        let lines = [
+            TaxableLine::new(dec("1"), dec("1000.00"), Some(dec("8.25"))),
+            TaxableLine::new(dec("3"), dec("50.00"), Some(dec("0"))),
+            TaxableLine::new(dec("2"), dec("100.00"), None),
+        ];
+
+        let totals = calculate_invoice_totals(&lines, Some(dec("5")), false);
+
+        assert_eq!(totals.subtotal, dec("1350.00"));
+        // 82.50 on the first line, none on the zero-rated line, 10.00 at the default rate
+        assert_eq!(totals.tax_amount, dec("92.50"));
+        assert_eq!(totals.total, dec("1442.50"));
+    }
+
+    #[test]
+    fn test_tax_exempt_client_pays_no_tax() {
+        let lines = [
+            TaxableLine::new(dec("1"), dec("1000.00"), Some(dec("8.25"))),
+            TaxableLine::new(dec("2"), dec("100.00"), None),
+        ];
+
+        let totals = calculate_invoice_totals(&lines, Some(dec("5")), true);
+
+        assert_eq!(totals.tax_amount, Decimal::ZERO);
+        assert_eq!(totals.total, totals.subtotal);
+        assert_eq!(totals.subtotal, dec("1200.00"));
+    }
+
+    #[test]
+    fn test_tax_rounds_to_cents_per_line() {
+        // 3 x 33.33 at 7.5% = 7.49925 -> 7.50
+        let line = TaxableLine::new(dec("3"), dec("33.33"), Some(dec("7.5")));
+        assert_eq!(line_tax(&line, None, false), dec("7.50"));
+
+        // 0.05 at 10% = 0.005 -> rounds half away from zero to 0.01
+        let line = TaxableLine::new(dec("1"), dec("0.05"), Some(dec("10")));
+        assert_eq!(line_tax(&line, None, false), dec("0.01"));
+
+        let totals = calculate_invoice_totals(
+            &[
+                TaxableLine::new(dec("1"), dec("0.05"), Some(dec("10"))),
+                TaxableLine::new(dec("1"), dec("0.05"), Some(dec("10"))),
+            ],
+            None,
+            false,
+        );
+        assert_eq!(totals.tax_amount, dec("0.02"));
+        assert_eq!(totals.total, dec("0.12"));
+    }
+
+    #[test]
+    fn test_no_rates_means_no_tax() {
+        let lines = [TaxableLine::new(dec("1.5"), dec("120.00"), None)];
+        let totals = calculate_invoice_totals(&lines, None, false);
+
+        assert_eq!(totals.tax_amount, Decimal::ZERO);
+        assert_eq!(totals.total, dec("180.00"));
+    }
+}
@@ -0,0 +1,466 @@
+//! IP address management within documented networks
+//!
+//! A network's host range comes from its `ip_range` and `subnet_mask` (see
+//! [`Ipv4Range::for_network`]). `ip_allocations` holds a row for every
+//! address that is used or reserved; anything else in the range is free,
+//! except the gateway, which is never handed out.
+//!
+//! An address also counts as taken when an asset on the network already
+//! has it as its `ip` but no allocation says so, which is how addresses
+//! entered on assets before IPAM existed show up as conflicts instead of
+//! being handed out twice. Clients reuse private ranges across sites and
+//! VLANs, so an asset is only taken to be on the network when it belongs to
+//! the network's client, isn't at another location, and isn't allocated in
+//! another of the client's networks.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv4Addr;
+use uuid::Uuid;
+
+use crate::services::network_discovery::Ipv4Range;
+
+/// Larger networks list only their allocated addresses
+pub const MAX_LISTED_ADDRESSES: u64 = 4096;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IpamError {
+    #[error("Network not found")]
+    NetworkNotFound,
+    #[error("{0}")]
+    InvalidRange(String),
+    #[error("{ip} is not a host address in {range}")]
+    OutOfRange { ip: Ipv4Addr, range: String },
+    #[error("{ip} is already {holder}")]
+    Conflict { ip: Ipv4Addr, holder: String },
+    #[error("No free addresses left in {0}")]
+    Exhausted(String),
+    #[error("{0} is not allocated")]
+    NotAllocated(Ipv4Addr),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpStatus {
+    Free,
+    Used,
+    Reserved,
+    Gateway,
+}
+
+impl IpStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Free => "free",
+            Self::Used => "used",
+            Self::Reserved => "reserved",
+            Self::Gateway => "gateway",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct IpAllocation {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub ip: String,
+    pub status: String,
+    pub assigned_asset_id: Option<Uuid>,
+    pub hostname: Option<String>,
+    pub notes: Option<String>,
+    pub allocated_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+const ALLOCATION_COLUMNS: &str =
+    "id, network_id, host(ip) AS ip, status, assigned_asset_id, hostname, notes, allocated_by, created_at, updated_at";
+
+/// One address of the network as the IPAM view shows it
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressEntry {
+    pub ip: Ipv4Addr,
+    pub status: IpStatus,
+    pub allocation_id: Option<Uuid>,
+    pub assigned_asset_id: Option<Uuid>,
+    pub hostname: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubnetView {
+    pub network_id: Uuid,
+    pub range: String,
+    pub gateway: Option<Ipv4Addr>,
+    pub total: u64,
+    pub used: u64,
+    pub reserved: u64,
+    pub free: u64,
+    /// Only allocated addresses are listed; the network is too large to
+    /// list every free one
+    pub truncated: bool,
+    pub addresses: Vec<AddressEntry>,
+}
+
+/// Conditions on asset `a` for it to be on the network: `$1` is the
+/// network's client, `$2` the network and `$3` its location
+const ASSET_ON_NETWORK: &str = "a.client_id = $1 AND a.archived_at IS NULL
+    AND ($3::uuid IS NULL OR a.location_id IS NULL OR a.location_id = $3)
+    AND NOT EXISTS (SELECT 1 FROM ip_allocations other WHERE other.assigned_asset_id = a.id AND other.network_id <> $2)";
+
+#[derive(Debug, FromRow)]
+struct NetworkRow {
+    client_id: Uuid,
+    location_id: Option<Uuid>,
+    ip_range: String,
+    subnet_mask: String,
+    gateway: Option<String>,
+}
+
+struct Network {
+    client_id: Uuid,
+    location_id: Option<Uuid>,
+    range: Ipv4Range,
+    gateway: Option<Ipv4Addr>,
+}
+
+async fn load_network(conn: &mut PgConnection, network_id: Uuid, lock: bool) -> Result<Network, IpamError> {
+    let query = if lock {
+        "SELECT client_id, location_id, ip_range, subnet_mask, gateway FROM networks WHERE id = $1 FOR UPDATE"
+    } else {
+        "SELECT client_id, location_id, ip_range, subnet_mask, gateway FROM networks WHERE id = $1"
+    };
+    let row = sqlx::query_as::<_, NetworkRow>(query)
+        .bind(network_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(IpamError::NetworkNotFound)?;
+
+    let range = Ipv4Range::for_network(&row.ip_range, &row.subnet_mask).map_err(|e| IpamError::InvalidRange(e.to_string()))?;
+    let gateway = row.gateway.as_deref().and_then(|g| g.trim().parse().ok());
+    Ok(Network { client_id: row.client_id, location_id: row.location_id, range, gateway })
+}
+
+async fn allocations(conn: &mut PgConnection, network_id: Uuid) -> Result<Vec<IpAllocation>, sqlx::Error> {
+    sqlx::query_as::<_, IpAllocation>(&format!(
+        "SELECT {} FROM ip_allocations WHERE network_id = $1 ORDER BY ip",
+        ALLOCATION_COLUMNS
+    ))
+    .bind(network_id)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+/// Addresses set on the network's assets with no allocation behind them,
+/// keyed to the asset's name
+async fn unallocated_asset_ips(
+    conn: &mut PgConnection,
+    network_id: Uuid,
+    network: &Network,
+) -> Result<HashMap<Ipv4Addr, String>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String)>(&format!(
+        r#"
+        SELECT host(a.ip), a.name FROM assets a
+        WHERE {} AND a.ip IS NOT NULL
+            AND NOT EXISTS (SELECT 1 FROM ip_allocations ia WHERE ia.network_id = $2 AND ia.ip = a.ip)
+        "#,
+        ASSET_ON_NETWORK
+    ))
+    .bind(network.client_id)
+    .bind(network_id)
+    .bind(network.location_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows.into_iter().filter_map(|(ip, name)| Some((ip.parse().ok()?, name))).collect())
+}
+
+/// The first host address that isn't the gateway or in `taken`
+pub fn next_free(range: &Ipv4Range, gateway: Option<Ipv4Addr>, taken: &HashSet<Ipv4Addr>) -> Option<Ipv4Addr> {
+    range.hosts().find(|ip| Some(*ip) != gateway && !taken.contains(ip))
+}
+
+/// Every host address with its status, or only the allocated ones (and the
+/// gateway) when the range is larger than [`MAX_LISTED_ADDRESSES`]
+pub fn subnet_listing(
+    network_id: Uuid,
+    range: &Ipv4Range,
+    gateway: Option<Ipv4Addr>,
+    allocations: &[IpAllocation],
+    asset_ips: &HashMap<Ipv4Addr, String>,
+) -> SubnetView {
+    let mut allocated: HashMap<Ipv4Addr, &IpAllocation> = HashMap::new();
+    for allocation in allocations {
+        if let Ok(ip) = allocation.ip.parse() {
+            allocated.insert(ip, allocation);
+        }
+    }
+
+    let entry = |ip: Ipv4Addr| -> AddressEntry {
+        if let Some(allocation) = allocated.get(&ip) {
+            let status = if allocation.status == "reserved" { IpStatus::Reserved } else { IpStatus::Used };
+            return AddressEntry {
+                ip,
+                status,
+                allocation_id: Some(allocation.id),
+                assigned_asset_id: allocation.assigned_asset_id,
+                hostname: allocation.hostname.clone(),
+            };
+        }
+        let status = if Some(ip) == gateway {
+            IpStatus::Gateway
+        } else if asset_ips.contains_key(&ip) {
+            IpStatus::Used
+        } else {
+            IpStatus::Free
+        };
+        AddressEntry { ip, status, allocation_id: None, assigned_asset_id: None, hostname: asset_ips.get(&ip).cloned() }
+    };
+
+    let total = range.host_count();
+    let truncated = total > MAX_LISTED_ADDRESSES;
+    let addresses: Vec<AddressEntry> = if truncated {
+        let mut ips: Vec<Ipv4Addr> = allocated
+            .keys()
+            .chain(asset_ips.keys())
+            .chain(gateway.iter())
+            .copied()
+            .filter(|ip| range.is_host(*ip))
+            .collect();
+        ips.sort();
+        ips.dedup();
+        ips.into_iter().map(entry).collect()
+    } else {
+        range.hosts().map(entry).collect()
+    };
+
+    // Counts cover the whole range, not just what is listed
+    let in_range: Vec<AddressEntry> = if truncated {
+        addresses.clone()
+    } else {
+        addresses.iter().filter(|a| a.status != IpStatus::Free).cloned().collect()
+    };
+    let used = in_range.iter().filter(|a| matches!(a.status, IpStatus::Used | IpStatus::Gateway)).count() as u64;
+    let reserved = in_range.iter().filter(|a| a.status == IpStatus::Reserved).count() as u64;
+
+    SubnetView {
+        network_id,
+        range: range.to_string(),
+        gateway,
+        total,
+        used,
+        reserved,
+        free: total - used - reserved,
+        truncated,
+        addresses,
+    }
+}
+
+pub async fn view_subnet(pool: &PgPool, network_id: Uuid) -> Result<SubnetView, IpamError> {
+    let mut conn = pool.acquire().await?;
+    let network = load_network(&mut conn, network_id, false).await?;
+    let allocations = allocations(&mut conn, network_id).await?;
+    let asset_ips = unallocated_asset_ips(&mut conn, network_id, &network).await?;
+    Ok(subnet_listing(network_id, &network.range, network.gateway, &allocations, &asset_ips))
+}
+
+/// What an allocation records besides its address
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AllocationRequest {
+    pub assigned_asset_id: Option<Uuid>,
+    pub hostname: Option<String>,
+    pub notes: Option<String>,
+}
+
+async fn insert_allocation(
+    conn: &mut PgConnection,
+    network_id: Uuid,
+    ip: Ipv4Addr,
+    status: IpStatus,
+    request: &AllocationRequest,
+    user_id: Uuid,
+) -> Result<IpAllocation, sqlx::Error> {
+    sqlx::query_as::<_, IpAllocation>(&format!(
+        r#"
+        INSERT INTO ip_allocations (network_id, ip, status, assigned_asset_id, hostname, notes, allocated_by)
+        VALUES ($1, $2::inet, $3, $4, $5, $6, $7)
+        RETURNING {}
+        "#,
+        ALLOCATION_COLUMNS
+    ))
+    .bind(network_id)
+    .bind(ip.to_string())
+    .bind(status.as_str())
+    .bind(request.assigned_asset_id)
+    .bind(&request.hostname)
+    .bind(&request.notes)
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await
+}
+
+/// Allocate the lowest free address in the network as used
+pub async fn allocate_next(
+    pool: &PgPool,
+    network_id: Uuid,
+    request: &AllocationRequest,
+    user_id: Uuid,
+) -> Result<IpAllocation, IpamError> {
+    let mut tx = pool.begin().await?;
+    // Locking the network serializes allocations within it
+    let network = load_network(&mut tx, network_id, true).await?;
+
+    let mut taken: HashSet<Ipv4Addr> = allocations(&mut tx, network_id)
+        .await?
+        .iter()
+        .filter_map(|a| a.ip.parse().ok())
+        .collect();
+    taken.extend(unallocated_asset_ips(&mut tx, network_id, &network).await?.into_keys());
+
+    let ip = next_free(&network.range, network.gateway, &taken)
+        .ok_or_else(|| IpamError::Exhausted(network.range.to_string()))?;
+    let allocation = insert_allocation(&mut tx, network_id, ip, IpStatus::Used, request, user_id).await?;
+    tx.commit().await?;
+    Ok(allocation)
+}
+
+/// Mark a specific address used or reserved, refusing one that is the
+/// gateway, outside the network or already taken
+pub async fn assign(
+    pool: &PgPool,
+    network_id: Uuid,
+    ip: Ipv4Addr,
+    status: IpStatus,
+    request: &AllocationRequest,
+    user_id: Uuid,
+) -> Result<IpAllocation, IpamError> {
+    let status = match status {
+        IpStatus::Used | IpStatus::Reserved => status,
+        IpStatus::Free | IpStatus::Gateway => IpStatus::Used,
+    };
+
+    let mut tx = pool.begin().await?;
+    let network = load_network(&mut tx, network_id, true).await?;
+    if !network.range.is_host(ip) {
+        return Err(IpamError::OutOfRange { ip, range: network.range.to_string() });
+    }
+    if network.gateway == Some(ip) {
+        return Err(IpamError::Conflict { ip, holder: "the network's gateway".to_string() });
+    }
+
+    let existing = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT status, hostname FROM ip_allocations WHERE network_id = $1 AND ip = $2::inet"
+    )
+    .bind(network_id)
+    .bind(ip.to_string())
+    .fetch_optional(&mut *tx)
+    .await?;
+    if let Some((held_as, hostname)) = existing {
+        let holder = match hostname {
+            Some(hostname) => format!("{} by {}", held_as, hostname),
+            None => held_as,
+        };
+        return Err(IpamError::Conflict { ip, holder });
+    }
+
+    // An asset already using the address counts unless it's the one being assigned
+    let asset_holder = sqlx::query_as::<_, (Uuid, String)>(&format!(
+        "SELECT a.id, a.name FROM assets a WHERE {} AND a.ip = $4::inet LIMIT 1",
+        ASSET_ON_NETWORK
+    ))
+    .bind(network.client_id)
+    .bind(network_id)
+    .bind(network.location_id)
+    .bind(ip.to_string())
+    .fetch_optional(&mut *tx)
+    .await?;
+    if let Some((asset_id, name)) = asset_holder {
+        if request.assigned_asset_id != Some(asset_id) {
+            return Err(IpamError::Conflict { ip, holder: format!("used by asset {}", name) });
+        }
+    }
+
+    let allocation = insert_allocation(&mut tx, network_id, ip, status, request, user_id).await?;
+    tx.commit().await?;
+    Ok(allocation)
+}
+
+/// Free an address; returns the allocation that held it
+pub async fn release(pool: &PgPool, network_id: Uuid, ip: Ipv4Addr) -> Result<IpAllocation, IpamError> {
+    sqlx::query_as::<_, IpAllocation>(&format!(
+        "DELETE FROM ip_allocations WHERE network_id = $1 AND ip = $2::inet RETURNING {}",
+        ALLOCATION_COLUMNS
+    ))
+    .bind(network_id)
+    .bind(ip.to_string())
+    .fetch_optional(pool)
+    .await?
+    .ok_or(IpamError::NotAllocated(ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocation(ip: &str, status: &str) -> IpAllocation {
+        IpAllocation {
+            id: Uuid::new_v4(),
+            network_id: Uuid::nil(),
+            ip: ip.to_string(),
+            status: status.to_string(),
+            assigned_asset_id: None,
+            hostname: None,
+            notes: None,
+            allocated_by: None,
+            created_at: Utc::now(),
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_next_free_skips_gateway_and_taken() {
+        let range = Ipv4Range::parse("10.0.0.0/29").unwrap();
+        let gateway = Some("10.0.0.1".parse().unwrap());
+        let mut taken: HashSet<Ipv4Addr> = ["10.0.0.2", "10.0.0.3"].iter().map(|ip| ip.parse().unwrap()).collect();
+
+        assert_eq!(next_free(&range, gateway, &taken), Some("10.0.0.4".parse().unwrap()));
+
+        taken.extend(["10.0.0.4", "10.0.0.5", "10.0.0.6"].iter().map(|ip| ip.parse::<Ipv4Addr>().unwrap()));
+        assert_eq!(next_free(&range, gateway, &taken), None);
+    }
+
+    #[test]
+    fn test_listing_covers_every_host_address() {
+        let range = Ipv4Range::parse("192.168.1.0/28").unwrap();
+        let gateway = Some("192.168.1.1".parse().unwrap());
+        let allocations = vec![allocation("192.168.1.5", "used"), allocation("192.168.1.10", "reserved")];
+        let asset_ips = HashMap::from([("192.168.1.7".parse().unwrap(), "NAS".to_string())]);
+
+        let view = subnet_listing(Uuid::nil(), &range, gateway, &allocations, &asset_ips);
+        assert_eq!(view.total, 14);
+        assert_eq!(view.addresses.len(), 14);
+        assert!(!view.truncated);
+        assert_eq!((view.used, view.reserved, view.free), (3, 1, 10));
+
+        let status = |ip: &str| view.addresses.iter().find(|a| a.ip.to_string() == ip).unwrap().status;
+        assert_eq!(status("192.168.1.1"), IpStatus::Gateway);
+        assert_eq!(status("192.168.1.5"), IpStatus::Used);
+        assert_eq!(status("192.168.1.7"), IpStatus::Used);
+        assert_eq!(status("192.168.1.10"), IpStatus::Reserved);
+        assert_eq!(status("192.168.1.2"), IpStatus::Free);
+    }
+
+    #[test]
+    fn test_large_networks_list_only_allocated_addresses() {
+        let range = Ipv4Range::parse("10.20.0.0/16").unwrap();
+        let allocations = vec![allocation("10.20.3.4", "used")];
+
+        let view = subnet_listing(Uuid::nil(), &range, Some("10.20.0.1".parse().unwrap()), &allocations, &HashMap::new());
+        assert!(view.truncated);
+        assert_eq!(view.total, 65534);
+        assert_eq!(view.addresses.len(), 2);
+        assert_eq!((view.used, view.reserved, view.free), (2, 0, 65532));
+    }
+}
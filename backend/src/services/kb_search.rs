@@ -0,0 +1,201 @@
+//! Knowledge base search and view counting
+//!
+//! Full-text search over article titles, summaries and content using the
+//! weighted `kb_articles.search_vector` column, ranked with `ts_rank`. Staff
+//! searches see every article; the public portal only sees articles that
+//! are both `is_public` and published. Queries without lexemes fall back to
+//! a substring match, as ticket search does.
+//!
+//! Views are counted at most once per viewer per [`VIEW_DEBOUNCE_MINUTES`],
+//! so refreshing an article doesn't inflate its `views`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::services::ticket_search::{search_mode, substring_pattern, SearchMode};
+
+/// Repeat views by the same viewer inside this window aren't counted
+pub const VIEW_DEBOUNCE_MINUTES: i32 = 30;
+
+const SNIPPET_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxWords=35, MinWords=15, MaxFragments=2";
+const FALLBACK_SNIPPET_LENGTH: i32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    /// Staff: every article, whatever its status
+    Internal,
+    /// Public portal: published articles marked public
+    Public,
+}
+
+impl SearchScope {
+    fn clause(self) -> &'static str {
+        match self {
+            SearchScope::Internal => "",
+            SearchScope::Public => " AND a.is_public = true AND a.status = 'published'",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct KbSearchResult {
+    pub id: Uuid,
+    pub title: String,
+    pub slug: Option<String>,
+    pub category_id: Option<Uuid>,
+    pub category_name: Option<String>,
+    pub status: String,
+    pub is_public: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub rank: f32,
+    /// Best-matching fragments of the article, matched terms in `<mark>`
+    pub snippet: String,
+}
+
+/// Search articles in `scope`, best match first, optionally within one
+/// category. Returns the page of results and the total number of matches.
+pub async fn search_articles(
+    pool: &PgPool,
+    query: &str,
+    scope: SearchScope,
+    category_id: Option<Uuid>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<KbSearchResult>, i64), sqlx::Error> {
+    let (term, matches, rank, snippet) = match search_mode(pool, query).await? {
+        SearchMode::FullText => (
+            query.to_string(),
+            "a.search_vector @@ websearch_to_tsquery('english', $1)".to_string(),
+            "ts_rank(a.search_vector, websearch_to_tsquery('english', $1))".to_string(),
+            format!(
+                "ts_headline('english', COALESCE(r.summary, r.excerpt, r.content), websearch_to_tsquery('english', $1), '{}')",
+                SNIPPET_OPTIONS
+            ),
+        ),
+        SearchMode::Substring => (
+            substring_pattern(query.trim()),
+            "(a.title ILIKE $1 OR a.summary ILIKE $1 OR a.content ILIKE $1)".to_string(),
+            // Title hits ahead of body-only hits
+            "(CASE WHEN a.title ILIKE $1 THEN 1.0 ELSE 0.5 END)::real".to_string(),
+            format!("LEFT(COALESCE(r.summary, r.excerpt, r.content), {})", FALLBACK_SNIPPET_LENGTH),
+        ),
+    };
+
+    let filter_clause = format!("{} AND ($2::uuid IS NULL OR a.category_id = $2)", scope.clause());
+
+    let total = sqlx::query_scalar::<_, i64>(&format!(
+        "SELECT COUNT(*) FROM kb_articles a WHERE {}{}",
+        matches, filter_clause
+    ))
+    .bind(&term)
+    .bind(category_id)
+    .fetch_one(pool)
+    .await?;
+
+    let sql = format!(
+        r#"
+        WITH ranked AS (
+            SELECT a.id, a.title, a.slug, a.category_id, a.summary, a.excerpt, a.content,
+                   COALESCE(a.status, 'draft') as status,
+                   COALESCE(a.is_public, false) as is_public,
+                   a.updated_at,
+                   {rank} as rank
+            FROM kb_articles a
+            WHERE {matches}{filter_clause}
+            ORDER BY rank DESC, a.updated_at DESC NULLS LAST
+            LIMIT $3 OFFSET $4
+        )
+        SELECT r.id, r.title, r.slug, r.category_id, c.name as category_name,
+               r.status, r.is_public, r.updated_at, r.rank,
+               {snippet} as snippet
+        FROM ranked r
+        LEFT JOIN kb_categories c ON r.category_id = c.id
+        ORDER BY r.rank DESC, r.updated_at DESC NULLS LAST
+        "#
+    );
+
+    let results = sqlx::query_as::<_, KbSearchResult>(&sql)
+        .bind(&term)
+        .bind(category_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+    Ok((results, total))
+}
+
+/// Viewer key for a signed-in user
+pub fn user_viewer_key(user_id: Uuid) -> String {
+    format!("user:{}", user_id)
+}
+
+/// Viewer key for an anonymous session token, hashed so tokens aren't stored
+pub fn session_viewer_key(session: &str) -> String {
+    format!("session:{}", hex::encode(Sha256::digest(session.as_bytes())))
+}
+
+/// Count a view of `article_id` unless `viewer_key` already viewed it within
+/// the debounce window. Returns whether the view was counted.
+pub async fn record_view(pool: &PgPool, article_id: Uuid, viewer_key: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // Inserts a first view, or refreshes a stale one; a recent view leaves
+    // the row alone and returns nothing
+    let counted = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO kb_article_views (article_id, viewer_key)
+        VALUES ($1, $2)
+        ON CONFLICT (article_id, viewer_key) DO UPDATE SET viewed_at = NOW()
+        WHERE kb_article_views.viewed_at < NOW() - make_interval(mins => $3)
+        RETURNING article_id
+        "#
+    )
+    .bind(article_id)
+    .bind(viewer_key)
+    .bind(VIEW_DEBOUNCE_MINUTES)
+    .fetch_optional(&mut *tx)
+    .await?
+    .is_some();
+
+    if counted {
+        sqlx::query(
+            "UPDATE kb_articles SET views = COALESCE(views, 0) + 1, view_count = COALESCE(view_count, 0) + 1 WHERE id = $1"
+        )
+        .bind(article_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(counted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_scope_requires_public_and_published() {
+        assert_eq!(SearchScope::Internal.clause(), "");
+        let public = SearchScope::Public.clause();
+        assert!(public.contains("a.is_public = true"));
+        assert!(public.contains("a.status = 'published'"));
+    }
+
+    #[test]
+    fn test_viewer_keys() {
+        let user = Uuid::new_v4();
+        assert_eq!(user_viewer_key(user), format!("user:{}", user));
+
+        let key = session_viewer_key("portal-session-token");
+        assert!(key.starts_with("session:"));
+        assert!(!key.contains("portal-session-token"));
+        assert_eq!(key.len(), "session:".len() + 64);
+        assert_eq!(key, session_viewer_key("portal-session-token"));
+        assert_ne!(key, session_viewer_key("another-session"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Knowledge base article versions
+//!
+//! Every save of an article records a full snapshot of its title, summary,
+//! excerpt and content in `kb_article_versions`, numbered from 1 per article.
+//! Snapshots hold the whole text rather than deltas, so any two versions can
+//! be diffed directly. Reverting copies an older snapshot back onto the
+//! article and records it as a new version, leaving the history intact.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VersionError {
+    #[error("Article not found")]
+    ArticleNotFound,
+    #[error("Version {0} not found")]
+    VersionNotFound(i32),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct KbArticleVersion {
+    pub id: Uuid,
+    pub article_id: Uuid,
+    pub version_number: i32,
+    pub title: String,
+    pub summary: Option<String>,
+    pub excerpt: Option<String>,
+    pub content: String,
+    pub author_id: Option<Uuid>,
+    pub author_name: Option<String>,
+    pub reverted_from: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+const VERSION_COLUMNS: &str = r#"
+    v.id, v.article_id, v.version_number, v.title, v.summary, v.excerpt, v.content,
+    v.author_id, u.first_name || ' ' || u.last_name as author_name,
+    v.reverted_from, v.created_at
+"#;
+
+/// Snapshot the article's current text as its next version. Call inside the
+/// transaction that saved the article; the article row is locked so
+/// concurrent saves get consecutive numbers.
+pub async fn record_version(
+    conn: &mut PgConnection,
+    article_id: Uuid,
+    author_id: Option<Uuid>,
+    reverted_from: Option<i32>,
+) -> Result<KbArticleVersion, VersionError> {
+    sqlx::query_scalar::<_, Uuid>("SELECT id FROM kb_articles WHERE id = $1 FOR UPDATE")
+        .bind(article_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(VersionError::ArticleNotFound)?;
+
+    let version_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO kb_article_versions (article_id, version_number, title, summary, excerpt, content, author_id, reverted_from)
+        SELECT a.id,
+               COALESCE((SELECT MAX(version_number) FROM kb_article_versions WHERE article_id = a.id), 0) + 1,
+               a.title, a.summary, a.excerpt, a.content, $2, $3
+        FROM kb_articles a
+        WHERE a.id = $1
+        RETURNING id
+        "#
+    )
+    .bind(article_id)
+    .bind(author_id)
+    .bind(reverted_from)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(sqlx::query_as::<_, KbArticleVersion>(&format!(
+        "SELECT {} FROM kb_article_versions v LEFT JOIN users u ON u.id = v.author_id WHERE v.id = $1",
+        VERSION_COLUMNS
+    ))
+    .bind(version_id)
+    .fetch_one(conn)
+    .await?)
+}
+
+/// An article's versions, newest first
+pub async fn list_versions(pool: &PgPool, article_id: Uuid) -> Result<Vec<KbArticleVersion>, VersionError> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM kb_articles WHERE id = $1)")
+        .bind(article_id)
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Err(VersionError::ArticleNotFound);
+    }
+
+    Ok(sqlx::query_as::<_, KbArticleVersion>(&format!(
+        r#"
+        SELECT {} FROM kb_article_versions v
+        LEFT JOIN users u ON u.id = v.author_id
+        WHERE v.article_id = $1
+        ORDER BY v.version_number DESC
+        "#,
+        VERSION_COLUMNS
+    ))
+    .bind(article_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Restore `version_number`'s text onto the article and record that as a
+/// new version by `author_id`
+pub async fn revert(
+    pool: &PgPool,
+    article_id: Uuid,
+    version_number: i32,
+    author_id: Option<Uuid>,
+) -> Result<KbArticleVersion, VersionError> {
+    let mut tx = pool.begin().await?;
+
+    let restored = sqlx::query(
+        r#"
+        UPDATE kb_articles a SET
+            title = v.title, summary = v.summary, excerpt = v.excerpt, content = v.content,
+            updated_at = NOW()
+        FROM kb_article_versions v
+        WHERE a.id = $1 AND v.article_id = a.id AND v.version_number = $2
+        "#
+    )
+    .bind(article_id)
+    .bind(version_number)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if restored == 0 {
+        let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM kb_articles WHERE id = $1)")
+            .bind(article_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        return Err(if exists {
+            VersionError::VersionNotFound(version_number)
+        } else {
+            VersionError::ArticleNotFound
+        });
+    }
+
+    let version = record_version(&mut *tx, article_id, author_id, Some(version_number)).await?;
+    tx.commit().await?;
+    Ok(version)
+}
@@ -0,0 +1,183 @@
+//! Knowledge base helpful/not-helpful votes
+//!
+//! Each voter gets one vote per article in `kb_article_votes`. A voter is a
+//! signed-in user or, on the public portal, an anonymous session identified
+//! by a signed `kb_session` cookie. Voting again replaces the earlier vote,
+//! and the article's `helpful_count` and `not_helpful_count` are moved in the
+//! same transaction so they always match the recorded votes.
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const SESSION_COOKIE: &str = "kb_session";
+/// Anonymous sessions last a year
+pub const SESSION_MAX_AGE_SECONDS: i64 = 365 * 24 * 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VoteError {
+    #[error("Article not found")]
+    ArticleNotFound,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct VoteTally {
+    pub helpful_count: i32,
+    pub not_helpful_count: i32,
+    /// The voter's current vote
+    pub helpful: bool,
+}
+
+/// Secret for signing session cookies (`KB_SESSION_SECRET`, else
+/// `JWT_SECRET`)
+pub fn session_secret() -> String {
+    std::env::var("KB_SESSION_SECRET")
+        .or_else(|_| std::env::var("JWT_SECRET"))
+        .unwrap_or_else(|_| "your-secret-key".to_string())
+}
+
+fn signature(secret: &str, session_id: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(session_id.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// A fresh session id and its signed cookie value, `<id>.<signature>`
+pub fn new_session(secret: &str) -> (String, String) {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let session_id = hex::encode(bytes);
+    let value = format!("{}.{}", session_id, signature(secret, &session_id));
+    (session_id, value)
+}
+
+/// The session id in a signed cookie value, if the signature holds
+pub fn verify_session(value: &str, secret: &str) -> Option<String> {
+    let (session_id, sig) = value.split_once('.')?;
+    let expected = hex::decode(sig).ok()?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(session_id.as_bytes());
+    mac.verify_slice(&expected).ok()?;
+    Some(session_id.to_string())
+}
+
+/// The verified session id from a `Cookie` header
+pub fn session_from_cookies(cookie_header: &str, secret: &str) -> Option<String> {
+    cookie_header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .and_then(|(_, value)| verify_session(value, secret))
+}
+
+/// `Set-Cookie` value for a new session
+pub fn session_cookie(value: &str) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        SESSION_COOKIE, value, SESSION_MAX_AGE_SECONDS
+    )
+}
+
+/// Count adjustments for moving from `previous` to `helpful`, as
+/// `(helpful, not_helpful)` deltas
+pub fn count_changes(previous: Option<bool>, helpful: bool) -> (i32, i32) {
+    match (previous, helpful) {
+        (Some(p), h) if p == h => (0, 0),
+        (Some(_), true) => (1, -1),
+        (Some(_), false) => (-1, 1),
+        (None, true) => (1, 0),
+        (None, false) => (0, 1),
+    }
+}
+
+/// Record `voter_key`'s vote on `article_id`, replacing any earlier one,
+/// and return the article's updated tallies
+pub async fn cast_vote(pool: &PgPool, article_id: Uuid, voter_key: &str, helpful: bool) -> Result<VoteTally, VoteError> {
+    let mut tx = pool.begin().await?;
+
+    // Lock the article so concurrent votes adjust the counts in turn
+    sqlx::query_scalar::<_, Uuid>("SELECT id FROM kb_articles WHERE id = $1 FOR UPDATE")
+        .bind(article_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(VoteError::ArticleNotFound)?;
+
+    let previous = sqlx::query_scalar::<_, bool>(
+        "SELECT helpful FROM kb_article_votes WHERE article_id = $1 AND voter_key = $2"
+    )
+    .bind(article_id)
+    .bind(voter_key)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO kb_article_votes (article_id, voter_key, helpful)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (article_id, voter_key) DO UPDATE SET helpful = $3, updated_at = NOW()
+        "#
+    )
+    .bind(article_id)
+    .bind(voter_key)
+    .bind(helpful)
+    .execute(&mut *tx)
+    .await?;
+
+    let (helpful_delta, not_helpful_delta) = count_changes(previous, helpful);
+    let (helpful_count, not_helpful_count) = sqlx::query_as::<_, (i32, i32)>(
+        r#"
+        UPDATE kb_articles SET
+            helpful_count = GREATEST(COALESCE(helpful_count, 0) + $2, 0),
+            not_helpful_count = GREATEST(COALESCE(not_helpful_count, 0) + $3, 0)
+        WHERE id = $1
+        RETURNING helpful_count, not_helpful_count
+        "#
+    )
+    .bind(article_id)
+    .bind(helpful_delta)
+    .bind(not_helpful_delta)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(VoteTally { helpful_count, not_helpful_count, helpful })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_changes() {
+        assert_eq!(count_changes(None, true), (1, 0));
+        assert_eq!(count_changes(None, false), (0, 1));
+        assert_eq!(count_changes(Some(true), true), (0, 0));
+        assert_eq!(count_changes(Some(false), false), (0, 0));
+        assert_eq!(count_changes(Some(false), true), (1, -1));
+        assert_eq!(count_changes(Some(true), false), (-1, 1));
+    }
+
+    #[test]
+    fn test_session_cookie_round_trip() {
+        let (session_id, value) = new_session("secret");
+        assert_eq!(verify_session(&value, "secret"), Some(session_id.clone()));
+        // Another key, or a tampered id, fails
+        assert_eq!(verify_session(&value, "other"), None);
+        let forged = value.replacen(&session_id[..1], if &session_id[..1] == "a" { "b" } else { "a" }, 1);
+        assert_eq!(verify_session(&forged, "secret"), None);
+        assert_eq!(verify_session("no-signature", "secret"), None);
+
+        let header = format!("theme=dark; {}={}; other=1", SESSION_COOKIE, value);
+        assert_eq!(session_from_cookies(&header, "secret"), Some(session_id));
+        assert_eq!(session_from_cookies("theme=dark", "secret"), None);
+        assert!(session_cookie(&value).starts_with("kb_session="));
+        assert!(session_cookie(&value).contains("HttpOnly"));
+    }
+}
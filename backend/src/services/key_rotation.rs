@@ -0,0 +1,186 @@
+//! Re-encrypting stored secrets after a key rotation
+//!
+//! Walks every column that holds [`KeyRing`] ciphertext and rewrites values
+//! that aren't under the ring's current key. Updates are conditional on the
+//! old value, so a row changed mid-rotation is left for the next run rather
+//! than overwritten.
+
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::keyring::{KeyPurpose, KeyRing, KeyRingError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RotationError {
+    #[error(transparent)]
+    KeyRing(#[from] KeyRingError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// A column holding ciphertext. `json_field` is set when the column is
+/// JSONB wrapping the ciphertext, as integration credentials are.
+#[derive(Debug, Clone, Copy)]
+pub struct EncryptedColumn {
+    pub purpose: KeyPurpose,
+    pub table: &'static str,
+    pub column: &'static str,
+    pub json_field: Option<&'static str>,
+}
+
+pub const ENCRYPTED_COLUMNS: &[EncryptedColumn] = &[
+    EncryptedColumn { purpose: KeyPurpose::Integration, table: "integrations", column: "credentials", json_field: Some("encrypted") },
+    EncryptedColumn { purpose: KeyPurpose::Mfa, table: "users", column: "mfa_secret", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::Credential, table: "credentials", column: "password", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::Credential, table: "credentials", column: "private_key", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::Credential, table: "asset_field_values", column: "field_value_encrypted", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::Credential, table: "passwords", column: "password_encrypted", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::Credential, table: "passwords", column: "notes_encrypted", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::Credential, table: "passwords", column: "otp_secret_encrypted", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::SslPrivateKey, table: "ssl_certificates", column: "private_key", json_field: None },
+    EncryptedColumn { purpose: KeyPurpose::LicenseKey, table: "software_licenses", column: "license_key", json_field: None },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnReport {
+    pub purpose: &'static str,
+    pub table: &'static str,
+    pub column: &'static str,
+    pub current_key_id: String,
+    pub scanned: usize,
+    pub already_current: usize,
+    pub rotated: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RotationReport {
+    pub dry_run: bool,
+    pub columns: Vec<ColumnReport>,
+}
+
+impl EncryptedColumn {
+    fn value_expr(&self) -> String {
+        match self.json_field {
+            Some(field) => format!("{}->>'{}'", self.column, field),
+            None => self.column.to_string(),
+        }
+    }
+
+    fn update_sql(&self) -> String {
+        let assignment = match self.json_field {
+            Some(field) => format!("{col} = jsonb_set({col}, '{{{field}}}', to_jsonb($2::text))", col = self.column, field = field),
+            None => format!("{} = $2", self.column),
+        };
+        format!("UPDATE {} SET {} WHERE id = $1 AND {} = $3", self.table, assignment, self.value_expr())
+    }
+}
+
+async fn rotate_column(
+    pool: &PgPool,
+    ring: &KeyRing,
+    column: &EncryptedColumn,
+    dry_run: bool,
+) -> Result<ColumnReport, sqlx::Error> {
+    let mut report = ColumnReport {
+        purpose: column.purpose.as_str(),
+        table: column.table,
+        column: column.column,
+        current_key_id: ring.current_key_id().to_string(),
+        scanned: 0,
+        already_current: 0,
+        rotated: 0,
+        failed: 0,
+    };
+
+    let select = format!(
+        "SELECT id, {value} FROM {table} WHERE {value} IS NOT NULL AND {value} <> ''",
+        value = column.value_expr(),
+        table = column.table
+    );
+    let rows = sqlx::query_as::<_, (Uuid, String)>(&select).fetch_all(pool).await?;
+    let update = column.update_sql();
+
+    for (id, ciphertext) in rows {
+        report.scanned += 1;
+        let rotated = match ring.reencrypt(&ciphertext) {
+            Ok(None) => {
+                report.already_current += 1;
+                continue;
+            }
+            Ok(Some(rotated)) => rotated,
+            Err(e) => {
+                tracing::warn!("Can't re-encrypt {}.{} for {}: {}", column.table, column.column, id, e);
+                report.failed += 1;
+                continue;
+            }
+        };
+
+        if !dry_run {
+            let updated = sqlx::query(&update)
+                .bind(id)
+                .bind(&rotated)
+                .bind(&ciphertext)
+                .execute(pool)
+                .await?
+                .rows_affected();
+            if updated == 0 {
+                continue;
+            }
+        }
+        report.rotated += 1;
+    }
+
+    Ok(report)
+}
+
+/// Re-encrypt every stored value for `purposes` under its ring's current
+/// key. A dry run only counts what would change.
+pub async fn rotate(pool: &PgPool, purposes: &[KeyPurpose], dry_run: bool) -> Result<RotationReport, RotationError> {
+    let mut columns = Vec::new();
+
+    for purpose in purposes {
+        let ring = KeyRing::from_env(*purpose)?;
+        for column in ENCRYPTED_COLUMNS.iter().filter(|c| c.purpose == *purpose) {
+            let report = rotate_column(pool, &ring, column, dry_run).await?;
+            tracing::info!(
+                "Key rotation {}.{}: {} scanned, {} rotated, {} failed{}",
+                report.table,
+                report.column,
+                report.scanned,
+                report.rotated,
+                report.failed,
+                if dry_run { " (dry run)" } else { "" }
+            );
+            columns.push(report);
+        }
+    }
+
+    Ok(RotationReport { dry_run, columns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_sql() {
+        let mfa = ENCRYPTED_COLUMNS.iter().find(|c| c.purpose == KeyPurpose::Mfa).unwrap();
+        assert_eq!(mfa.update_sql(), "UPDATE users SET mfa_secret = $2 WHERE id = $1 AND mfa_secret = $3");
+
+        let integration = ENCRYPTED_COLUMNS[0];
+        assert_eq!(
+            integration.update_sql(),
+            "UPDATE integrations SET credentials = jsonb_set(credentials, '{encrypted}', to_jsonb($2::text)) \
+             WHERE id = $1 AND credentials->>'encrypted' = $3"
+        );
+    }
+
+    #[test]
+    fn test_every_purpose_has_a_column() {
+        for purpose in KeyPurpose::ALL {
+            assert!(ENCRYPTED_COLUMNS.iter().any(|c| c.purpose == purpose), "{:?}", purpose);
+        }
+    }
+}
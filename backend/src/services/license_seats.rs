@@ -0,0 +1,280 @@
+//! Software license seat assignments
+//!
+//! Seats of an itdoc software license are held by a contact or an asset.
+//! Assigning and unassigning keep `software_licenses.used_seats` in step
+//! under a row lock, so concurrent assignments can't both take the last seat.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Utilization at which the expiration monitor flags a license
+pub const HIGH_UTILIZATION_THRESHOLD: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeatHolder {
+    Contact(Uuid),
+    Asset(Uuid),
+}
+
+impl SeatHolder {
+    /// Exactly one of a contact or an asset
+    pub fn from_ids(contact_id: Option<Uuid>, asset_id: Option<Uuid>) -> Option<Self> {
+        match (contact_id, asset_id) {
+            (Some(contact_id), None) => Some(SeatHolder::Contact(contact_id)),
+            (None, Some(asset_id)) => Some(SeatHolder::Asset(asset_id)),
+            _ => None,
+        }
+    }
+
+    fn ids(&self) -> (Option<Uuid>, Option<Uuid>) {
+        match self {
+            SeatHolder::Contact(id) => (Some(*id), None),
+            SeatHolder::Asset(id) => (None, Some(*id)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SeatAssignment {
+    pub id: Uuid,
+    pub license_id: Uuid,
+    pub contact_id: Option<Uuid>,
+    pub contact_name: Option<String>,
+    pub asset_id: Option<Uuid>,
+    pub asset_name: Option<String>,
+    pub notes: Option<String>,
+    pub assigned_by: Option<Uuid>,
+    pub assigned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeatUsage {
+    pub seats: Option<i32>,
+    pub used_seats: i32,
+    pub over_allocated: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SeatError {
+    #[error("License not found")]
+    LicenseNotFound,
+    #[error("Assignment not found")]
+    AssignmentNotFound,
+    #[error("Already holds a seat on this license")]
+    AlreadyAssigned,
+    #[error("All {seats} seats are in use")]
+    NoSeatsAvailable { seats: i32, used_seats: i32 },
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Fraction of seats in use, when the license has a seat count
+pub fn utilization(seats: Option<i32>, used_seats: i32) -> Option<f64> {
+    seats.filter(|s| *s > 0).map(|s| used_seats as f64 / s as f64)
+}
+
+pub fn is_high_utilization(seats: Option<i32>, used_seats: i32) -> bool {
+    utilization(seats, used_seats).is_some_and(|u| u >= HIGH_UTILIZATION_THRESHOLD)
+}
+
+/// Open an alert unless one of the same type is already open. Returns
+/// whether a new alert was raised.
+pub async fn raise_alert<'e>(
+    executor: impl PgExecutor<'e>,
+    license_id: Uuid,
+    alert_type: &str,
+    seats: Option<i32>,
+    used_seats: i32,
+    message: &str,
+) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO software_license_alerts (license_id, alert_type, seats, used_seats, message)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (license_id, alert_type) WHERE resolved_at IS NULL DO NOTHING
+        "#
+    )
+    .bind(license_id)
+    .bind(alert_type)
+    .bind(seats)
+    .bind(used_seats)
+    .bind(message)
+    .execute(executor)
+    .await?
+    .rows_affected();
+
+    Ok(inserted > 0)
+}
+
+async fn lock_license(tx: &mut Transaction<'_, Postgres>, license_id: Uuid) -> Result<(String, Option<i32>, i32), SeatError> {
+    sqlx::query_as::<_, (String, Option<i32>, i32)>(
+        "SELECT name, seats, COALESCE(used_seats, 0) FROM software_licenses WHERE id = $1 FOR UPDATE"
+    )
+    .bind(license_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or(SeatError::LicenseNotFound)
+}
+
+/// Give `holder` a seat. Fails when every seat is taken unless
+/// `allow_overage` is set, in which case an overage alert is raised.
+pub async fn assign_seat(
+    pool: &PgPool,
+    license_id: Uuid,
+    holder: SeatHolder,
+    assigned_by: Uuid,
+    notes: Option<&str>,
+    allow_overage: bool,
+) -> Result<(Uuid, SeatUsage), SeatError> {
+    let mut tx = pool.begin().await?;
+    let (name, seats, used_seats) = lock_license(&mut tx, license_id).await?;
+
+    if let Some(seats) = seats {
+        if used_seats >= seats && !allow_overage {
+            return Err(SeatError::NoSeatsAvailable { seats, used_seats });
+        }
+    }
+
+    let (contact_id, asset_id) = holder.ids();
+    let assignment_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO software_license_assignments (license_id, contact_id, asset_id, notes, assigned_by)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(license_id)
+    .bind(contact_id)
+    .bind(asset_id)
+    .bind(notes)
+    .bind(assigned_by)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(SeatError::AlreadyAssigned)?;
+
+    let used_seats = sqlx::query_scalar::<_, i32>(
+        "UPDATE software_licenses SET used_seats = COALESCE(used_seats, 0) + 1, updated_at = NOW() WHERE id = $1 RETURNING used_seats"
+    )
+    .bind(license_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let over_allocated = seats.is_some_and(|s| used_seats > s);
+    if over_allocated {
+        let message = format!(
+            "{} is over-allocated: {} of {} seats assigned",
+            name,
+            used_seats,
+            seats.unwrap_or_default()
+        );
+        raise_alert(&mut *tx, license_id, "overage", seats, used_seats, &message).await?;
+        tracing::warn!("{}", message);
+    }
+
+    tx.commit().await?;
+
+    Ok((assignment_id, SeatUsage { seats, used_seats, over_allocated }))
+}
+
+/// Release a seat, by assignment id or by holder. Resolves the overage
+/// alert once the license is back within its seat count.
+pub async fn unassign_seat(
+    pool: &PgPool,
+    license_id: Uuid,
+    assignment_id: Option<Uuid>,
+    holder: Option<SeatHolder>,
+) -> Result<SeatUsage, SeatError> {
+    let mut tx = pool.begin().await?;
+    let (_, seats, _) = lock_license(&mut tx, license_id).await?;
+
+    let (contact_id, asset_id) = holder.map(|h| h.ids()).unwrap_or_default();
+    let released = sqlx::query(
+        r#"
+        UPDATE software_license_assignments SET unassigned_at = NOW()
+        WHERE license_id = $1 AND unassigned_at IS NULL
+            AND ($2::uuid IS NULL OR id = $2)
+            AND ($3::uuid IS NULL OR contact_id = $3)
+            AND ($4::uuid IS NULL OR asset_id = $4)
+            AND ($2::uuid IS NOT NULL OR $3::uuid IS NOT NULL OR $4::uuid IS NOT NULL)
+        "#
+    )
+    .bind(license_id)
+    .bind(assignment_id)
+    .bind(contact_id)
+    .bind(asset_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if released == 0 {
+        return Err(SeatError::AssignmentNotFound);
+    }
+
+    let used_seats = sqlx::query_scalar::<_, i32>(
+        "UPDATE software_licenses SET used_seats = GREATEST(COALESCE(used_seats, 0) - $2, 0), updated_at = NOW() WHERE id = $1 RETURNING used_seats"
+    )
+    .bind(license_id)
+    .bind(released as i32)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let over_allocated = seats.is_some_and(|s| used_seats > s);
+    if !over_allocated {
+        sqlx::query(
+            "UPDATE software_license_alerts SET resolved_at = NOW() WHERE license_id = $1 AND alert_type = 'overage' AND resolved_at IS NULL"
+        )
+        .bind(license_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(SeatUsage { seats, used_seats, over_allocated })
+}
+
+pub async fn list_assignments(pool: &PgPool, license_id: Uuid) -> Result<Vec<SeatAssignment>, sqlx::Error> {
+    sqlx::query_as::<_, SeatAssignment>(
+        r#"
+        SELECT a.id, a.license_id, a.contact_id, c.name as contact_name,
+               a.asset_id, s.name as asset_name, a.notes, a.assigned_by, a.assigned_at
+        FROM software_license_assignments a
+        LEFT JOIN contacts c ON a.contact_id = c.id
+        LEFT JOIN assets s ON a.asset_id = s.id
+        WHERE a.license_id = $1 AND a.unassigned_at IS NULL
+        ORDER BY a.assigned_at
+        "#
+    )
+    .bind(license_id)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utilization() {
+        assert_eq!(utilization(Some(10), 9), Some(0.9));
+        assert_eq!(utilization(Some(0), 3), None);
+        assert_eq!(utilization(None, 3), None);
+
+        assert!(is_high_utilization(Some(10), 9));
+        assert!(is_high_utilization(Some(10), 12));
+        assert!(!is_high_utilization(Some(10), 8));
+        assert!(!is_high_utilization(None, 100));
+    }
+
+    #[test]
+    fn test_seat_holder_requires_exactly_one_id() {
+        let id = Uuid::new_v4();
+        assert_eq!(SeatHolder::from_ids(Some(id), None), Some(SeatHolder::Contact(id)));
+        assert_eq!(SeatHolder::from_ids(None, Some(id)), Some(SeatHolder::Asset(id)));
+        assert_eq!(SeatHolder::from_ids(Some(id), Some(id)), None);
+        assert_eq!(SeatHolder::from_ids(None, None), None);
+    }
+}
@@ -1,13 +1,69 @@
 pub mod email;
 pub mod email_processor;
+pub mod email_templates;
+pub mod github_issues;
+pub mod global_search;
 pub mod bms_workflows;
 pub mod password_manager;
 pub mod encryption;
 pub mod teams_integration;
 pub mod domain_ssl_monitor;
 pub mod cache;
-pub mod audit;
+pub mod asset_fields;
+pub mod asset_import;
+pub mod asset_warranty;
+pub mod azure_cost_alerts;
+pub mod audit_log_query;
+pub mod business_hours;
+pub mod canned_response_render;
+pub mod certificate_probe;
+pub mod client_export;
+pub mod chat_notifications;
+pub mod credential_grants;
+pub mod cloudflare_dns_import;
+pub mod forticloud_sync;
+pub mod contact_merge;
+pub mod contract_renewals;
+pub mod contract_usage;
+pub mod dashboard;
+pub mod dashboard_stream;
+pub mod data_retention;
+pub mod dns_verification;
+pub mod expenses;
 pub mod metrics;
+pub mod my_workspace;
+pub mod network_discovery;
+pub mod outbound_webhooks;
+pub mod password_health;
+pub mod portal_tickets;
+pub mod project_budget;
+pub mod public_address;
+pub mod inbound_email;
+pub mod invoice_numbering;
+pub mod invoice_payments;
+pub mod invoice_pdf;
+pub mod invoice_tax;
+pub mod ipam;
+pub mod kb_search;
+pub mod kb_versions;
+pub mod kb_votes;
+pub mod key_rotation;
+pub mod license_seats;
+pub mod queue_assignment;
+pub mod report_export;
+pub mod scheduled_reports;
+pub mod stripe_payments;
+pub mod task_dependencies;
+pub mod ticket_replies;
+pub mod ticket_routing;
+pub mod ticket_search;
+pub mod ticket_sla;
+pub mod ticket_status;
+pub mod ticket_watchers;
+pub mod time_export;
+pub mod time_overlaps;
+pub mod time_rounding;
+pub mod time_timers;
 
 pub use email::EmailService;
 pub use email_processor::{EmailProcessor, EmailProcessorConfig};
@@ -16,5 +72,4 @@ pub use password_manager::PasswordManagerService;
 pub use encryption::EncryptionService;
 pub use teams_integration::{TeamsNotificationService, TicketNotification, DailySummary, TeamsError};
 pub use cache::{CacheService, CacheError, CacheResult, cache_keys, ttl};
-pub use audit::{AuditService, AuditAction, AuditSeverity, AuditEntryBuilder, AuditLogEntry, ChangeTracker};
-pub use metrics::{MetricsService, MetricType, HealthStatus, RequestLog, RequestStats, Timer, metric_names};
\ No newline at end of file
+pub use metrics::{MetricsService, MetricType, HealthStatus, RequestLog, RequestStats, Timer, metric_names};
@@ -0,0 +1,229 @@
+//! A technician's own workspace
+//!
+//! Everything on a technician's plate in one response: their open tickets,
+//! the timer they have running, what they have logged today and the project
+//! tasks coming due. Each section is one query and the sections run
+//! concurrently. Tickets come most urgent first: the nearest SLA deadline
+//! (an unanswered ticket's response deadline, otherwise its resolution
+//! deadline), then priority. Tickets merged into another are left out
+//! whatever their status, since their work continues on the target.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::services::ticket_sla::RESOLVED_STATUSES;
+
+/// Items listed per section; the counts cover every item
+pub const SECTION_LIMIT: i64 = 5;
+/// Tasks due within this many days count as upcoming
+pub const TASK_WINDOW_DAYS: i64 = 7;
+
+/// Task statuses that need no more work
+const DONE_TASK_STATUSES: &[&str] = &["completed", "cancelled"];
+
+/// SQL ordering `column`'s priorities from most to least urgent, unknown
+/// ones last
+fn priority_rank_sql(column: &str) -> String {
+    format!(
+        "CASE {} WHEN 'critical' THEN 1 WHEN 'urgent' THEN 1 WHEN 'high' THEN 2 \
+         WHEN 'medium' THEN 3 WHEN 'low' THEN 4 ELSE 5 END",
+        column
+    )
+}
+
+#[derive(Debug, Serialize)]
+pub struct Workspace {
+    pub tickets: Section<WorkspaceTicket>,
+    pub running_timer: Option<RunningTimer>,
+    pub today: TodayTime,
+    pub tasks: Section<WorkspaceTask>,
+}
+
+/// The first [`SECTION_LIMIT`] items and how many there are in all
+#[derive(Debug, Serialize)]
+pub struct Section<T> {
+    pub total: i64,
+    pub items: Vec<T>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WorkspaceTicket {
+    pub id: Uuid,
+    pub number: i32,
+    pub subject: String,
+    pub status: String,
+    pub priority: String,
+    pub client_id: Uuid,
+    pub client_name: String,
+    /// The SLA deadline the ticket is next measured against
+    pub next_sla_due: Option<DateTime<Utc>>,
+    pub sla_breached: bool,
+    #[serde(skip)]
+    total: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct RunningTimer {
+    pub id: Uuid,
+    pub ticket_id: Option<Uuid>,
+    pub ticket_subject: Option<String>,
+    pub project_id: Option<Uuid>,
+    pub description: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub elapsed_minutes: i64,
+}
+
+/// Time logged on entries started today (UTC); a running timer isn't
+/// counted until it stops
+#[derive(Debug, Serialize, FromRow)]
+pub struct TodayTime {
+    pub hours: Decimal,
+    pub billable_hours: Decimal,
+    pub entries: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WorkspaceTask {
+    pub id: Uuid,
+    pub name: String,
+    pub status: String,
+    pub priority: String,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub due_date: NaiveDate,
+    pub overdue: bool,
+    #[serde(skip)]
+    total: i64,
+}
+
+fn section<T>(items: Vec<T>, total: impl Fn(&T) -> i64) -> Section<T> {
+    Section { total: items.first().map(total).unwrap_or(0), items }
+}
+
+async fn open_tickets(pool: &PgPool, user_id: Uuid, now: DateTime<Utc>) -> Result<Vec<WorkspaceTicket>, sqlx::Error> {
+    sqlx::query_as::<_, WorkspaceTicket>(&format!(
+        r#"
+        SELECT t.id, t.number, t.subject, t.status, t.priority, t.client_id, c.name as client_name,
+               t.next_sla_due, t.breached OR COALESCE(t.next_sla_due < $3, false) as sla_breached,
+               COUNT(*) OVER () as total
+        FROM (
+            SELECT id, number, subject, client_id, created_at,
+                   COALESCE(status, 'open') as status, COALESCE(priority, 'medium') as priority,
+                   COALESCE(sla_breached, false) as breached,
+                   LEAST(CASE WHEN sla_response_at IS NULL THEN sla_response_due END, sla_resolution_due)
+                       as next_sla_due
+            FROM tickets
+            WHERE assigned_to = $1
+              AND NOT (COALESCE(status, 'open') = ANY($2))
+              AND NOT COALESCE(is_merged, false) AND merged_into_id IS NULL
+        ) t
+        JOIN clients c ON c.id = t.client_id
+        ORDER BY t.next_sla_due ASC NULLS LAST, {}, t.created_at, t.id
+        LIMIT $4
+        "#,
+        priority_rank_sql("t.priority")
+    ))
+    .bind(user_id)
+    .bind(RESOLVED_STATUSES)
+    .bind(now)
+    .bind(SECTION_LIMIT)
+    .fetch_all(pool)
+    .await
+}
+
+async fn running_timer(pool: &PgPool, user_id: Uuid, now: DateTime<Utc>) -> Result<Option<RunningTimer>, sqlx::Error> {
+    sqlx::query_as::<_, RunningTimer>(
+        r#"
+        SELECT e.id, e.ticket_id, t.subject as ticket_subject, e.project_id, e.description, e.start_time,
+               GREATEST(FLOOR(EXTRACT(EPOCH FROM ($2 - e.start_time)) / 60), 0)::int8 as elapsed_minutes
+        FROM time_entries e
+        LEFT JOIN tickets t ON t.id = e.ticket_id
+        WHERE e.user_id = $1 AND e.end_time IS NULL
+        ORDER BY e.start_time DESC
+        LIMIT 1
+        "#
+    )
+    .bind(user_id)
+    .bind(now)
+    .fetch_optional(pool)
+    .await
+}
+
+async fn today_time(pool: &PgPool, user_id: Uuid, day_start: DateTime<Utc>) -> Result<TodayTime, sqlx::Error> {
+    sqlx::query_as::<_, TodayTime>(
+        r#"
+        SELECT ROUND(COALESCE(SUM(duration_minutes), 0) / 60.0, 2) as hours,
+               ROUND(COALESCE(SUM(duration_minutes) FILTER (WHERE COALESCE(billable, true)), 0) / 60.0, 2)
+                   as billable_hours,
+               COUNT(*) as entries
+        FROM time_entries
+        WHERE user_id = $1 AND start_time >= $2 AND end_time IS NOT NULL
+        "#
+    )
+    .bind(user_id)
+    .bind(day_start)
+    .fetch_one(pool)
+    .await
+}
+
+async fn upcoming_tasks(pool: &PgPool, user_id: Uuid, today: NaiveDate) -> Result<Vec<WorkspaceTask>, sqlx::Error> {
+    sqlx::query_as::<_, WorkspaceTask>(&format!(
+        r#"
+        SELECT t.id, t.name, COALESCE(t.status, 'todo') as status, COALESCE(t.priority, 'medium') as priority,
+               t.project_id, p.name as project_name, t.due_date, t.due_date < $3 as overdue,
+               COUNT(*) OVER () as total
+        FROM tasks t
+        JOIN projects p ON p.id = t.project_id
+        WHERE t.assigned_to = $1
+          AND NOT (COALESCE(t.status, 'todo') = ANY($2))
+          AND t.due_date <= $4
+        ORDER BY t.due_date, {}, t.name, t.id
+        LIMIT $5
+        "#,
+        priority_rank_sql("t.priority")
+    ))
+    .bind(user_id)
+    .bind(DONE_TASK_STATUSES)
+    .bind(today)
+    .bind(today + Duration::days(TASK_WINDOW_DAYS))
+    .bind(SECTION_LIMIT)
+    .fetch_all(pool)
+    .await
+}
+
+/// The workspace of `user_id` as of `now`
+pub async fn workspace(pool: &PgPool, user_id: Uuid, now: DateTime<Utc>) -> Result<Workspace, sqlx::Error> {
+    let today = now.date_naive();
+
+    let (tickets, running_timer, today_time, tasks) = tokio::try_join!(
+        open_tickets(pool, user_id, now),
+        running_timer(pool, user_id, now),
+        today_time(pool, user_id, today.and_time(NaiveTime::MIN).and_utc()),
+        upcoming_tasks(pool, user_id, today),
+    )?;
+
+    Ok(Workspace {
+        tickets: section(tickets, |t| t.total),
+        running_timer,
+        today: today_time,
+        tasks: section(tasks, |t| t.total),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section_counts_beyond_listed_items() {
+        let empty: Section<i64> = section(Vec::new(), |n| *n);
+        assert_eq!(empty.total, 0);
+
+        let listed = section(vec![12, 12, 12], |n| *n);
+        assert_eq!(listed.total, 12);
+        assert_eq!(listed.items.len(), 3);
+    }
+}
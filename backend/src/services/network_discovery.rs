@@ -0,0 +1,755 @@
+//! Finding live hosts on a documented network
+//!
+//! Discovery is opt-in: nothing can be scanned until
+//! `NETWORK_DISCOVERY_ALLOWED_RANGES` lists the ranges the server may
+//! probe, and a network's `ip_range` must sit entirely inside one of them.
+//! That keeps a mistyped range from sweeping someone else's address space.
+//!
+//! A sweep tries a TCP connection to a handful of common ports on every
+//! address, a bounded number at a time. An accepted or refused connection
+//! shows the host is up; on a local segment the attempt also makes the
+//! kernel ARP for the address, so hosts that drop every port still show up
+//! in the ARP table, which also gives their MAC address. The whole sweep
+//! stops at the scan timeout and reports what it found so far.
+//!
+//! Results are staged in `discovery_scans` / `discovered_devices` and
+//! matched against the client's assets by MAC, then IP. Hosts that match
+//! nothing only become assets through [`import_devices`], which the API
+//! runs only with explicit confirmation.
+
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Ports tried on every host to see whether it is up
+pub const LIVENESS_PORTS: &[u16] = &[22, 80, 135, 443, 445, 3389];
+pub const DEFAULT_ASSET_TYPE: &str = "network_device";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiscoveryError {
+    #[error("Network discovery is not enabled")]
+    Disabled,
+    #[error("Network not found")]
+    NetworkNotFound,
+    #[error("Discovery scan not found")]
+    ScanNotFound,
+    #[error("Invalid IP range: {0}")]
+    InvalidRange(String),
+    #[error("{0} is outside the ranges discovery may scan")]
+    RangeNotAllowed(String),
+    #[error("{hosts} addresses is more than the {max} one scan may probe")]
+    TooManyHosts { hosts: u64, max: u64 },
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// Ranges scans may cover; empty turns discovery off
+    pub allowed_ranges: Vec<Ipv4Range>,
+    pub max_hosts: u64,
+    /// Hosts probed at once
+    pub concurrency: usize,
+    /// Per connection attempt
+    pub connect_timeout: Duration,
+    /// For the whole sweep
+    pub scan_timeout: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            allowed_ranges: Vec::new(),
+            max_hosts: 1024,
+            concurrency: 64,
+            connect_timeout: Duration::from_millis(500),
+            scan_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl DiscoveryConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let number = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+        };
+
+        let allowed_ranges = std::env::var("NETWORK_DISCOVERY_ALLOWED_RANGES")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .filter_map(|s| match Ipv4Range::parse(s) {
+                        Ok(range) => Some(range),
+                        Err(e) => {
+                            tracing::warn!("Ignoring NETWORK_DISCOVERY_ALLOWED_RANGES entry {}: {}", s, e);
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            allowed_ranges,
+            max_hosts: number("NETWORK_DISCOVERY_MAX_HOSTS", defaults.max_hosts),
+            concurrency: number("NETWORK_DISCOVERY_CONCURRENCY", defaults.concurrency as u64).max(1) as usize,
+            connect_timeout: Duration::from_millis(number(
+                "NETWORK_DISCOVERY_CONNECT_TIMEOUT_MS",
+                defaults.connect_timeout.as_millis() as u64,
+            )),
+            scan_timeout: Duration::from_secs(number("NETWORK_DISCOVERY_SCAN_TIMEOUT_SECS", defaults.scan_timeout.as_secs())),
+        }
+    }
+
+    /// Whether this config lets `range` be scanned
+    pub fn check(&self, range: &Ipv4Range) -> Result<(), DiscoveryError> {
+        if self.allowed_ranges.is_empty() {
+            return Err(DiscoveryError::Disabled);
+        }
+        if !self.allowed_ranges.iter().any(|allowed| allowed.covers(range)) {
+            return Err(DiscoveryError::RangeNotAllowed(range.to_string()));
+        }
+        let hosts = range.address_count();
+        if hosts > self.max_hosts {
+            return Err(DiscoveryError::TooManyHosts { hosts, max: self.max_hosts });
+        }
+        Ok(())
+    }
+}
+
+/// An inclusive run of IPv4 addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Range {
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+    /// Given as a CIDR block, so the ends are network and broadcast addresses
+    pub subnet: bool,
+}
+
+impl Ipv4Range {
+    /// `10.0.0.0/24`, `10.0.0.10-10.0.0.50` or a single address
+    pub fn parse(text: &str) -> Result<Self, DiscoveryError> {
+        let invalid = || DiscoveryError::InvalidRange(text.to_string());
+        let addr = |s: &str| s.trim().parse::<Ipv4Addr>().map_err(|_| invalid());
+
+        if let Some((network, prefix)) = text.split_once('/') {
+            let prefix: u32 = prefix.trim().parse().ok().filter(|p| *p <= 32).ok_or_else(invalid)?;
+            return Ok(Self::cidr(addr(network)?, prefix));
+        }
+        if let Some((start, end)) = text.split_once('-') {
+            let (start, end) = (addr(start)?, addr(end)?);
+            if start > end {
+                return Err(invalid());
+            }
+            return Ok(Self { start, end, subnet: false });
+        }
+        let single = addr(text)?;
+        Ok(Self { start: single, end: single, subnet: false })
+    }
+
+    /// A network's range from its `ip_range`, falling back to the subnet
+    /// mask when the range is a bare network address
+    pub fn for_network(ip_range: &str, subnet_mask: &str) -> Result<Self, DiscoveryError> {
+        if ip_range.contains('/') || ip_range.contains('-') {
+            return Self::parse(ip_range);
+        }
+        let network = ip_range.trim().parse::<Ipv4Addr>().map_err(|_| DiscoveryError::InvalidRange(ip_range.to_string()))?;
+        let mask = u32::from(
+            subnet_mask.trim().parse::<Ipv4Addr>().map_err(|_| DiscoveryError::InvalidRange(subnet_mask.to_string()))?,
+        );
+        if mask.leading_ones() + mask.trailing_zeros() != 32 {
+            return Err(DiscoveryError::InvalidRange(subnet_mask.to_string()));
+        }
+        Ok(Self::cidr(network, mask.leading_ones()))
+    }
+
+    fn cidr(network: Ipv4Addr, prefix: u32) -> Self {
+        let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+        let start = u32::from(network) & mask;
+        Self { start: Ipv4Addr::from(start), end: Ipv4Addr::from(start | !mask), subnet: true }
+    }
+
+    pub fn address_count(&self) -> u64 {
+        u64::from(u32::from(self.end)) - u64::from(u32::from(self.start)) + 1
+    }
+
+    pub fn covers(&self, other: &Ipv4Range) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Addresses worth probing: a subnet's network and broadcast addresses
+    /// are left out
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let (first, last) = self.host_bounds();
+        (first..=last).map(Ipv4Addr::from)
+    }
+
+    pub fn host_count(&self) -> u64 {
+        let (first, last) = self.host_bounds();
+        u64::from(last) - u64::from(first) + 1
+    }
+
+    /// Whether `ip` is one of [`Self::hosts`]
+    pub fn is_host(&self, ip: Ipv4Addr) -> bool {
+        let (first, last) = self.host_bounds();
+        (first..=last).contains(&u32::from(ip))
+    }
+
+    fn host_bounds(&self) -> (u32, u32) {
+        let (start, end) = (u32::from(self.start), u32::from(self.end));
+        if self.subnet && end - start >= 2 { (start + 1, end - 1) } else { (start, end) }
+    }
+}
+
+impl std::fmt::Display for Ipv4Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// A host that answered a probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbedHost {
+    pub ip: Ipv4Addr,
+    pub mac: Option<String>,
+    pub open_ports: Vec<u16>,
+}
+
+#[async_trait]
+pub trait HostScanner: Send + Sync {
+    /// The host at `ip` if it is up, with whichever of `ports` accepted a
+    /// connection
+    async fn probe(&self, ip: Ipv4Addr, ports: &[u16]) -> Option<ProbedHost>;
+}
+
+/// Probes with plain TCP connections and reads MACs from the kernel's ARP
+/// table; needs no raw-socket privileges
+pub struct TcpScanner {
+    pub connect_timeout: Duration,
+}
+
+enum PortState {
+    Open,
+    Closed,
+    Silent,
+}
+
+impl TcpScanner {
+    async fn connect(&self, ip: Ipv4Addr, port: u16) -> PortState {
+        let address = SocketAddr::from((ip, port));
+        match tokio::time::timeout(self.connect_timeout, TcpStream::connect(address)).await {
+            Ok(Ok(_)) => PortState::Open,
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => PortState::Closed,
+            _ => PortState::Silent,
+        }
+    }
+
+    /// The MAC for `ip` from a complete entry in `/proc/net/arp`
+    async fn arp_entry(ip: Ipv4Addr) -> Option<String> {
+        let table = tokio::fs::read_to_string("/proc/net/arp").await.ok()?;
+        let ip = ip.to_string();
+        table.lines().skip(1).find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // IP address, HW type, flags (0x2 = complete), HW address, ...
+            match fields.as_slice() {
+                [address, _, "0x2", mac, ..] if *address == ip => normalize_mac(mac),
+                _ => None,
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl HostScanner for TcpScanner {
+    async fn probe(&self, ip: Ipv4Addr, ports: &[u16]) -> Option<ProbedHost> {
+        let mut candidates: Vec<u16> = LIVENESS_PORTS.iter().chain(ports).copied().collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut answered = false;
+        let mut open_ports = Vec::new();
+        for port in candidates {
+            match self.connect(ip, port).await {
+                PortState::Open => {
+                    answered = true;
+                    if ports.contains(&port) {
+                        open_ports.push(port);
+                    }
+                }
+                PortState::Closed => answered = true,
+                PortState::Silent => {}
+            }
+        }
+
+        let mac = Self::arp_entry(ip).await;
+        (answered || mac.is_some()).then_some(ProbedHost { ip, mac, open_ports })
+    }
+}
+
+/// Lower-case, colon-separated; `None` for the all-zero placeholder
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let hex: String = mac.chars().filter(|c| c.is_ascii_hexdigit()).collect::<String>().to_ascii_lowercase();
+    if hex.len() != 12 || hex.chars().all(|c| c == '0') {
+        return None;
+    }
+    Some(hex.as_bytes().chunks(2).map(|pair| std::str::from_utf8(pair).unwrap_or_default()).collect::<Vec<_>>().join(":"))
+}
+
+#[derive(Debug)]
+pub struct Sweep {
+    pub hosts: Vec<ProbedHost>,
+    /// The scan timeout cut the sweep short
+    pub timed_out: bool,
+}
+
+/// Probe every host in `range`, `concurrency` at a time, until done or
+/// `scan_timeout` passes
+pub async fn sweep(scanner: &dyn HostScanner, range: &Ipv4Range, ports: &[u16], config: &DiscoveryConfig) -> Sweep {
+    let deadline = Instant::now() + config.scan_timeout;
+    let mut probes = stream::iter(range.hosts())
+        .map(|ip| scanner.probe(ip, ports))
+        .buffer_unordered(config.concurrency.max(1));
+
+    let mut hosts = Vec::new();
+    let mut timed_out = false;
+    loop {
+        match tokio::time::timeout_at(deadline, probes.next()).await {
+            Ok(Some(Some(host))) => hosts.push(host),
+            Ok(Some(None)) => {}
+            Ok(None) => break,
+            Err(_) => {
+                timed_out = true;
+                break;
+            }
+        }
+    }
+
+    hosts.sort_by_key(|host| host.ip);
+    Sweep { hosts, timed_out }
+}
+
+/// An asset of the client the scan might have found again
+#[derive(Debug, Clone, FromRow)]
+pub struct KnownAsset {
+    pub id: Uuid,
+    pub name: String,
+    pub ip: Option<String>,
+    pub mac: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum HostMatch {
+    Known { asset_id: Uuid, asset_name: String, matched_on: &'static str },
+    New,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiscoveredHost {
+    /// The staged `discovered_devices` row, once recorded
+    pub device_id: Option<Uuid>,
+    #[serde(flatten)]
+    pub host: ProbedHost,
+    #[serde(flatten)]
+    pub matched: HostMatch,
+}
+
+/// Pair each host with the asset it already is, if any. MAC addresses win
+/// over IPs, since DHCP moves a machine's IP but not its MAC; an IP match
+/// only counts when the MACs don't contradict it.
+pub fn merge(hosts: Vec<ProbedHost>, known: &[KnownAsset]) -> Vec<DiscoveredHost> {
+    let by_mac: HashMap<String, &KnownAsset> = known
+        .iter()
+        .filter_map(|asset| Some((normalize_mac(asset.mac.as_deref()?)?, asset)))
+        .collect();
+    let by_ip: HashMap<Ipv4Addr, (&KnownAsset, Option<String>)> = known
+        .iter()
+        .filter_map(|asset| {
+            let ip = asset.ip.as_deref()?.trim().parse().ok()?;
+            Some((ip, (asset, asset.mac.as_deref().and_then(normalize_mac))))
+        })
+        .collect();
+
+    hosts
+        .into_iter()
+        .map(|host| {
+            let found = host
+                .mac
+                .as_ref()
+                .and_then(|mac| by_mac.get(mac))
+                .map(|asset| (*asset, "mac"))
+                .or_else(|| {
+                    by_ip
+                        .get(&host.ip)
+                        .filter(|(_, mac)| mac.is_none() || host.mac.is_none() || *mac == host.mac)
+                        .map(|(asset, _)| (*asset, "ip"))
+                });
+            let matched = match found {
+                Some((asset, matched_on)) => HostMatch::Known { asset_id: asset.id, asset_name: asset.name.clone(), matched_on },
+                None => HostMatch::New,
+            };
+            DiscoveredHost { device_id: None, host, matched }
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanReport {
+    pub scan_id: Uuid,
+    pub network_id: Uuid,
+    pub ip_range: String,
+    pub hosts_in_range: u64,
+    pub timed_out: bool,
+    pub known: usize,
+    pub new: usize,
+    pub hosts: Vec<DiscoveredHost>,
+}
+
+#[derive(Debug, FromRow)]
+struct NetworkRow {
+    client_id: Uuid,
+    ip_range: String,
+    subnet_mask: String,
+}
+
+async fn known_assets(pool: &PgPool, client_id: Uuid) -> Result<Vec<KnownAsset>, sqlx::Error> {
+    sqlx::query_as::<_, KnownAsset>(
+        r#"
+        SELECT id, name, host(ip) AS ip, COALESCE(mac_address, mac::TEXT) AS mac
+        FROM assets
+        WHERE client_id = $1 AND archived_at IS NULL
+            AND (ip IS NOT NULL OR mac IS NOT NULL OR mac_address IS NOT NULL)
+        "#
+    )
+    .bind(client_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Sweep a network, match what answered against the client's assets and
+/// stage the results. No assets are created.
+pub async fn discover(
+    pool: &PgPool,
+    scanner: &dyn HostScanner,
+    config: &DiscoveryConfig,
+    network_id: Uuid,
+    ports: &[u16],
+    user_id: Uuid,
+) -> Result<ScanReport, DiscoveryError> {
+    let network = sqlx::query_as::<_, NetworkRow>("SELECT client_id, ip_range, subnet_mask FROM networks WHERE id = $1")
+        .bind(network_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(DiscoveryError::NetworkNotFound)?;
+
+    let range = Ipv4Range::for_network(&network.ip_range, &network.subnet_mask)?;
+    config.check(&range)?;
+
+    let started_at = chrono::Utc::now();
+    let swept = sweep(scanner, &range, ports, config).await;
+    let known = known_assets(pool, network.client_id).await?;
+    let mut hosts = merge(swept.hosts, &known);
+    let known_count = hosts.iter().filter(|h| h.matched != HostMatch::New).count();
+    let new_count = hosts.len() - known_count;
+
+    let mut tx = pool.begin().await?;
+    let scan_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO discovery_scans (client_id, scan_type, ip_range, subnet_mask, status, started_at, completed_at,
+            assets_discovered, assets_updated, new_assets, scan_results, created_by)
+        VALUES ($1, 'network', $2, $3, 'completed', $4, NOW(), $5, 0, $6, $7, $8)
+        RETURNING id
+        "#
+    )
+    .bind(network.client_id)
+    .bind(range.to_string())
+    .bind(&network.subnet_mask)
+    .bind(started_at)
+    .bind(hosts.len() as i32)
+    .bind(new_count as i32)
+    .bind(serde_json::json!({ "network_id": network_id, "timed_out": swept.timed_out, "ports": ports }))
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for host in &mut hosts {
+        let known_asset_id = match &host.matched {
+            HostMatch::Known { asset_id, .. } => Some(*asset_id),
+            HostMatch::New => None,
+        };
+        let open_ports: Vec<i32> = host.host.open_ports.iter().map(|p| i32::from(*p)).collect();
+        let device_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO discovered_devices (scan_id, client_id, ip_address, mac_address, open_ports, network_info)
+            VALUES ($1, $2, $3::inet, $4, $5, $6)
+            RETURNING id
+            "#
+        )
+        .bind(scan_id)
+        .bind(network.client_id)
+        .bind(host.host.ip.to_string())
+        .bind(&host.host.mac)
+        .bind(&open_ports)
+        .bind(serde_json::json!({ "network_id": network_id, "known_asset_id": known_asset_id }))
+        .fetch_one(&mut *tx)
+        .await?;
+        host.device_id = Some(device_id);
+    }
+    tx.commit().await?;
+
+    Ok(ScanReport {
+        scan_id,
+        network_id,
+        ip_range: range.to_string(),
+        hosts_in_range: range.host_count(),
+        timed_out: swept.timed_out,
+        known: known_count,
+        new: new_count,
+        hosts,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedDevice {
+    pub device_id: Uuid,
+    pub asset_id: Uuid,
+}
+
+#[derive(Debug, FromRow)]
+struct StagedDevice {
+    id: Uuid,
+    client_id: Uuid,
+    hostname: Option<String>,
+    ip: Option<String>,
+    mac_address: Option<String>,
+}
+
+/// Create assets for staged hosts of `scan_id`. Hosts that matched an
+/// existing asset or were already imported are left alone.
+pub async fn import_devices(
+    pool: &PgPool,
+    scan_id: Uuid,
+    device_ids: &[Uuid],
+    asset_type: &str,
+    user_id: Uuid,
+) -> Result<Vec<ImportedDevice>, DiscoveryError> {
+    let mut tx = pool.begin().await?;
+    let scan_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM discovery_scans WHERE id = $1)")
+        .bind(scan_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !scan_exists {
+        return Err(DiscoveryError::ScanNotFound);
+    }
+
+    let devices = sqlx::query_as::<_, StagedDevice>(
+        r#"
+        SELECT id, client_id, hostname, host(ip_address) AS ip, mac_address
+        FROM discovered_devices
+        WHERE scan_id = $1 AND id = ANY($2)
+            AND converted_to_asset_id IS NULL
+            AND NOT COALESCE(ignore, false)
+            AND network_info->>'known_asset_id' IS NULL
+        ORDER BY ip_address
+        FOR UPDATE
+        "#
+    )
+    .bind(scan_id)
+    .bind(device_ids)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut imported = Vec::with_capacity(devices.len());
+    for device in devices {
+        let name = device.hostname.clone().or_else(|| device.ip.clone()).unwrap_or_else(|| "Discovered host".to_string());
+        let asset_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO assets (client_id, name, asset_type, ip, mac_address, status,
+                discovered_at, discovery_method, last_seen, created_at, updated_at)
+            VALUES ($1, $2, $3, $4::inet, $5, 'active', NOW(), 'scan', NOW(), NOW(), NOW())
+            RETURNING id
+            "#
+        )
+        .bind(device.client_id)
+        .bind(&name)
+        .bind(asset_type)
+        .bind(&device.ip)
+        .bind(&device.mac_address)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE discovered_devices SET approved = true, approved_by = $2, converted_to_asset_id = $3 WHERE id = $1")
+            .bind(device.id)
+            .bind(user_id)
+            .bind(asset_id)
+            .execute(&mut *tx)
+            .await?;
+        imported.push(ImportedDevice { device_id: device.id, asset_id });
+    }
+
+    tx.commit().await?;
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    /// Answers for a fixed set of hosts and remembers what it was asked
+    struct MockScanner {
+        hosts: HashMap<Ipv4Addr, ProbedHost>,
+        probed: Mutex<HashSet<Ipv4Addr>>,
+        delay: Duration,
+    }
+
+    impl MockScanner {
+        fn new(hosts: Vec<ProbedHost>) -> Self {
+            Self {
+                hosts: hosts.into_iter().map(|h| (h.ip, h)).collect(),
+                probed: Mutex::new(HashSet::new()),
+                delay: Duration::ZERO,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl HostScanner for MockScanner {
+        async fn probe(&self, ip: Ipv4Addr, _ports: &[u16]) -> Option<ProbedHost> {
+            tokio::time::sleep(self.delay).await;
+            self.probed.lock().unwrap().insert(ip);
+            self.hosts.get(&ip).cloned()
+        }
+    }
+
+    fn host(ip: &str, mac: Option<&str>, open_ports: Vec<u16>) -> ProbedHost {
+        ProbedHost { ip: ip.parse().unwrap(), mac: mac.map(str::to_string), open_ports }
+    }
+
+    fn asset(name: &str, ip: Option<&str>, mac: Option<&str>) -> KnownAsset {
+        KnownAsset { id: Uuid::new_v4(), name: name.to_string(), ip: ip.map(str::to_string), mac: mac.map(str::to_string) }
+    }
+
+    fn config(allowed: &[&str]) -> DiscoveryConfig {
+        DiscoveryConfig {
+            allowed_ranges: allowed.iter().map(|r| Ipv4Range::parse(r).unwrap()).collect(),
+            ..DiscoveryConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_ranges_parse() {
+        let subnet = Ipv4Range::parse("192.168.10.77/24").unwrap();
+        assert_eq!(subnet.to_string(), "192.168.10.0-192.168.10.255");
+        assert_eq!(subnet.address_count(), 256);
+        assert_eq!(subnet.hosts().count(), 254);
+        assert_eq!(subnet.hosts().next(), Some("192.168.10.1".parse().unwrap()));
+
+        assert_eq!(Ipv4Range::for_network("10.1.0.0", "255.255.255.0").unwrap(), Ipv4Range::parse("10.1.0.0/24").unwrap());
+        assert_eq!(Ipv4Range::parse("10.0.0.5-10.0.0.9").unwrap().hosts().count(), 5);
+        assert!(Ipv4Range::parse("10.0.0.9-10.0.0.5").is_err());
+        assert!(Ipv4Range::parse("10.0.0.0/33").is_err());
+        assert!(Ipv4Range::for_network("10.1.0.0", "255.0.255.0").is_err());
+    }
+
+    #[test]
+    fn test_config_limits_what_can_be_scanned() {
+        let range = Ipv4Range::parse("192.168.1.0/24").unwrap();
+        assert!(matches!(DiscoveryConfig::default().check(&range), Err(DiscoveryError::Disabled)));
+
+        let config = config(&["192.168.0.0/16"]);
+        assert!(config.check(&range).is_ok());
+        assert!(matches!(config.check(&Ipv4Range::parse("8.8.8.0/24").unwrap()), Err(DiscoveryError::RangeNotAllowed(_))));
+        // Overlapping isn't enough; the whole range must be allowed
+        assert!(matches!(config.check(&Ipv4Range::parse("192.167.255.0/23").unwrap()), Err(DiscoveryError::RangeNotAllowed(_))));
+        assert!(matches!(
+            config.check(&Ipv4Range::parse("192.168.0.0/20").unwrap()),
+            Err(DiscoveryError::TooManyHosts { hosts: 4096, max: 1024 })
+        ));
+    }
+
+    #[test]
+    fn test_macs_normalize() {
+        assert_eq!(normalize_mac("AA-BB-CC-00-11-22").as_deref(), Some("aa:bb:cc:00:11:22"));
+        assert_eq!(normalize_mac("aabb.cc00.1122").as_deref(), Some("aa:bb:cc:00:11:22"));
+        assert_eq!(normalize_mac("00:00:00:00:00:00"), None);
+        assert_eq!(normalize_mac("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_finds_live_hosts_with_a_mocked_scanner() {
+        let scanner = MockScanner::new(vec![
+            host("10.0.0.20", Some("aa:bb:cc:00:00:20"), vec![443]),
+            host("10.0.0.3", None, vec![]),
+        ]);
+        let range = Ipv4Range::parse("10.0.0.0/27").unwrap();
+
+        let swept = sweep(&scanner, &range, &[443], &config(&["10.0.0.0/8"])).await;
+        assert!(!swept.timed_out);
+        assert_eq!(swept.hosts.iter().map(|h| h.ip.to_string()).collect::<Vec<_>>(), vec!["10.0.0.3", "10.0.0.20"]);
+        assert_eq!(scanner.probed.lock().unwrap().len(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_stops_at_the_scan_timeout() {
+        let mut scanner = MockScanner::new(vec![host("10.0.0.1", None, vec![])]);
+        scanner.delay = Duration::from_millis(50);
+        let range = Ipv4Range::parse("10.0.0.0/24").unwrap();
+        let config = DiscoveryConfig { concurrency: 2, scan_timeout: Duration::from_millis(120), ..config(&["10.0.0.0/8"]) };
+
+        let swept = sweep(&scanner, &range, &[], &config).await;
+        assert!(swept.timed_out);
+        assert!(scanner.probed.lock().unwrap().len() < 254);
+    }
+
+    #[test]
+    fn test_merge_separates_known_and_new_hosts() {
+        let printer = asset("Front Printer", Some("10.0.0.50"), None);
+        let laptop = asset("Sam's Laptop", Some("10.0.0.99"), Some("AA-BB-CC-00-00-21"));
+        let known = vec![printer.clone(), laptop.clone()];
+
+        let merged = merge(
+            vec![
+                host("10.0.0.21", Some("aa:bb:cc:00:00:21"), vec![]),
+                host("10.0.0.50", Some("aa:bb:cc:00:00:50"), vec![9100]),
+                host("10.0.0.60", None, vec![22]),
+            ],
+            &known,
+        );
+
+        // The laptop moved to a new IP but kept its MAC
+        assert_eq!(
+            merged[0].matched,
+            HostMatch::Known { asset_id: laptop.id, asset_name: laptop.name.clone(), matched_on: "mac" }
+        );
+        assert_eq!(
+            merged[1].matched,
+            HostMatch::Known { asset_id: printer.id, asset_name: printer.name.clone(), matched_on: "ip" }
+        );
+        assert_eq!(merged[2].matched, HostMatch::New);
+        assert_eq!(merged[2].host.open_ports, vec![22]);
+
+        let json = serde_json::to_value(&merged[2]).unwrap();
+        assert_eq!(json["status"], "new");
+        assert_eq!(json["ip"], "10.0.0.60");
+    }
+
+    #[test]
+    fn test_ip_match_needs_agreeing_macs() {
+        // The old asset's IP was handed to a different machine
+        let old = asset("Old PC", Some("10.0.0.5"), Some("aa:aa:aa:aa:aa:aa"));
+        let merged = merge(vec![host("10.0.0.5", Some("bb:bb:bb:bb:bb:bb"), vec![])], &[old]);
+        assert_eq!(merged[0].matched, HostMatch::New);
+    }
+}
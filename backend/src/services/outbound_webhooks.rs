@@ -0,0 +1,431 @@
+//! Outbound webhook subscriptions
+//!
+//! Integrators subscribe a URL to domain events. When an event happens each
+//! active subscription for it gets a delivery: a JSON payload POSTed with an
+//! `X-Resolve-Signature: sha256=<hex>` header, the HMAC-SHA256 of the body
+//! under the subscription secret. Retryable failures are retried with
+//! exponential backoff and every attempt is recorded. Deliveries only go
+//! to public addresses and don't follow redirects.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::{FromRow, PgPool};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::middleware::request_id::{self, with_request_id};
+use crate::services::public_address;
+use crate::workflows::webhook::{backoff_delay, is_retryable_status, response_snippet, SIGNATURE_HEADER};
+
+pub const TICKET_CREATED: &str = "ticket.created";
+pub const INVOICE_PAID: &str = "invoice.paid";
+pub const ASSET_CHANGED: &str = "asset.changed";
+/// Sent by the test-delivery endpoint; not subscribable
+pub const PING: &str = "ping";
+
+pub const EVENT_TYPES: &[&str] = &[TICKET_CREATED, INVOICE_PAID, ASSET_CHANGED];
+
+pub const EVENT_HEADER: &str = "X-Resolve-Event";
+pub const DELIVERY_HEADER: &str = "X-Resolve-Delivery";
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub event_types: Vec<String>,
+    pub active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff_seconds: u64,
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff_seconds: 2,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventPayload<'a> {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub data: &'a serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryAttempt {
+    pub attempt: u32,
+    pub status_code: Option<u16>,
+    pub response_snippet: String,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
+
+impl DeliveryAttempt {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.status_code.is_some_and(|s| (200..300).contains(&s))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryOutcome {
+    pub delivery_id: Uuid,
+    pub status: String,
+    pub attempts: Vec<DeliveryAttempt>,
+}
+
+/// The client every delivery is sent with
+pub fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// `sha256=<hex>` HMAC-SHA256 of `body` under `secret`
+pub fn sign_body(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+pub fn is_event_type(event_type: &str) -> bool {
+    EVENT_TYPES.contains(&event_type)
+}
+
+/// POST `body` to `url`, retrying transport errors and retryable statuses
+/// with exponential backoff. Returns every attempt made, the last being the
+/// outcome.
+pub async fn send_with_retries(
+    http: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    event_type: &str,
+    delivery_id: Uuid,
+    body: &str,
+    policy: RetryPolicy,
+) -> Vec<DeliveryAttempt> {
+    let signature = sign_body(secret, body);
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempts = Vec::new();
+
+    for attempt in 1..=max_attempts {
+        if attempt > 1 {
+            tokio::time::sleep(backoff_delay(policy.backoff_seconds, attempt - 1)).await;
+        }
+
+        let started = Instant::now();
+        let result = with_request_id(http.post(url))
+            .timeout(policy.timeout)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, event_type)
+            .header(DELIVERY_HEADER, delivery_id.to_string())
+            .body(body.to_string())
+            .send()
+            .await;
+
+        let (status_code, snippet, error) = match result {
+            Ok(response) => {
+                let status = response.status().as_u16();
+                let text = response.text().await.unwrap_or_default();
+                (Some(status), response_snippet(&text), None)
+            }
+            Err(e) => (None, String::new(), Some(e.to_string())),
+        };
+
+        let retry = match status_code {
+            Some(status) => is_retryable_status(status),
+            None => true,
+        };
+        attempts.push(DeliveryAttempt {
+            attempt,
+            status_code,
+            response_snippet: snippet,
+            error,
+            duration_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+        });
+
+        if !retry {
+            break;
+        }
+        tracing::warn!("Webhook delivery {} to {} failed (attempt {}/{})", delivery_id, url, attempt, max_attempts);
+    }
+
+    attempts
+}
+
+async fn create_delivery(
+    pool: &PgPool,
+    subscription_id: Uuid,
+    event_id: Uuid,
+    event_type: &str,
+    payload: &serde_json::Value,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO webhook_deliveries (subscription_id, event_id, event_type, payload)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#
+    )
+    .bind(subscription_id)
+    .bind(event_id)
+    .bind(event_type)
+    .bind(payload)
+    .fetch_one(pool)
+    .await
+}
+
+async fn record_attempts(pool: &PgPool, delivery_id: Uuid, attempts: &[DeliveryAttempt]) -> Result<String, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    for attempt in attempts {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_delivery_attempts
+                (delivery_id, attempt, status_code, response_snippet, error, duration_ms)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(delivery_id)
+        .bind(attempt.attempt as i32)
+        .bind(attempt.status_code.map(i32::from))
+        .bind(&attempt.response_snippet)
+        .bind(&attempt.error)
+        .bind(attempt.duration_ms)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let last = attempts.last();
+    let status = if last.is_some_and(DeliveryAttempt::is_success) { "succeeded" } else { "failed" };
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2, attempts = $3, last_status_code = $4, last_error = $5, completed_at = NOW()
+        WHERE id = $1
+        "#
+    )
+    .bind(delivery_id)
+    .bind(status)
+    .bind(attempts.len() as i32)
+    .bind(last.and_then(|a| a.status_code).map(i32::from))
+    .bind(last.and_then(|a| a.error.clone()))
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(status.to_string())
+}
+
+/// Deliver one event to one subscription and record the outcome
+pub async fn deliver(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    subscription: &WebhookSubscription,
+    event_type: &str,
+    data: &serde_json::Value,
+    policy: RetryPolicy,
+) -> Result<DeliveryOutcome, sqlx::Error> {
+    let event_id = Uuid::new_v4();
+    let payload = serde_json::to_value(EventPayload {
+        id: event_id,
+        event_type,
+        created_at: Utc::now(),
+        data,
+    })
+    .unwrap_or_default();
+    let delivery_id = create_delivery(pool, subscription.id, event_id, event_type, &payload).await?;
+
+    let body = payload.to_string();
+    let attempts = match public_address::check_public_url(&subscription.url).await {
+        Ok(()) => send_with_retries(http, &subscription.url, &subscription.secret, event_type, delivery_id, &body, policy).await,
+        Err(e) => vec![DeliveryAttempt {
+            attempt: 1,
+            status_code: None,
+            response_snippet: String::new(),
+            error: Some(e.to_string()),
+            duration_ms: 0,
+        }],
+    };
+    let status = record_attempts(pool, delivery_id, &attempts).await?;
+
+    Ok(DeliveryOutcome { delivery_id, status, attempts })
+}
+
+/// Queue `event_type` for every active subscription to it. Deliveries run
+/// in the background; returns how many were started.
+pub async fn dispatch(pool: &PgPool, event_type: &str, data: serde_json::Value) -> Result<usize, sqlx::Error> {
+    let subscriptions = sqlx::query_as::<_, WebhookSubscription>(
+        "SELECT * FROM webhook_subscriptions WHERE active AND $1 = ANY(event_types)"
+    )
+    .bind(event_type)
+    .fetch_all(pool)
+    .await?;
+
+    let count = subscriptions.len();
+    for subscription in subscriptions {
+        let pool = pool.clone();
+        let event_type = event_type.to_string();
+        let data = data.clone();
+        tokio::spawn(request_id::propagate(async move {
+            if let Err(e) = deliver(&pool, http_client(), &subscription, &event_type, &data, RetryPolicy::default()).await {
+                tracing::error!("Error recording webhook delivery for subscription {}: {}", subscription.id, e);
+            }
+        }));
+    }
+
+    Ok(count)
+}
+
+/// Dispatch from a request handler: failures are logged, never surfaced
+pub async fn notify(pool: &PgPool, event_type: &str, data: serde_json::Value) {
+    if let Err(e) = dispatch(pool, event_type, data).await {
+        tracing::error!("Error dispatching {} webhooks: {}", event_type, e);
+    }
+}
+
+/// Send `invoice.paid` once an invoice's balance has been cleared
+pub async fn notify_invoice_paid(pool: &PgPool, invoice_id: Uuid) {
+    let invoice = sqlx::query_as::<_, (Uuid, String, Uuid, rust_decimal::Decimal, String)>(
+        "SELECT id, number, client_id, COALESCE(total, 0), COALESCE(status, 'draft') FROM invoices WHERE id = $1"
+    )
+    .bind(invoice_id)
+    .fetch_optional(pool)
+    .await;
+
+    match invoice {
+        Ok(Some((id, number, client_id, total, status))) if status == "paid" => {
+            let data = serde_json::json!({
+                "invoice_id": id,
+                "number": number,
+                "client_id": client_id,
+                "total": total,
+                "status": status
+            });
+            notify(pool, INVOICE_PAID, data).await;
+        }
+        Ok(_) => {}
+        Err(e) => tracing::error!("Error loading invoice {} for webhooks: {}", invoice_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn no_backoff(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, backoff_seconds: 0, timeout: Duration::from_secs(5) }
+    }
+
+    #[test]
+    fn test_signature_is_hmac_of_body() {
+        let body = r#"{"id":"1","type":"ping"}"#;
+        assert_eq!(
+            sign_body("whsec_test", body),
+            "sha256=657d0a2b6cf31df4ec7b9180bde603cc8e29d625f9e5dd29a0c9190bb218a1b6"
+        );
+        assert_ne!(sign_body("whsec_other", body), sign_body("whsec_test", body));
+    }
+
+    #[test]
+    fn test_event_types() {
+        assert!(is_event_type("ticket.created"));
+        assert!(!is_event_type(PING));
+        assert!(generate_secret().starts_with("whsec_"));
+    }
+
+    #[tokio::test]
+    async fn test_retries_on_5xx_until_success() {
+        let server = MockServer::start().await;
+        let body = r#"{"type":"ticket.created"}"#;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header(SIGNATURE_HEADER, sign_body("s3cret", body).as_str()))
+            .and(header(EVENT_HEADER, "ticket.created"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let attempts = send_with_retries(
+            &reqwest::Client::new(),
+            &format!("{}/hook", server.uri()),
+            "s3cret",
+            "ticket.created",
+            Uuid::new_v4(),
+            body,
+            no_backoff(5),
+        )
+        .await;
+
+        let statuses: Vec<_> = attempts.iter().map(|a| a.status_code).collect();
+        assert_eq!(statuses, vec![Some(503), Some(503), Some(200)]);
+        assert!(attempts.last().unwrap().is_success());
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts_and_skips_4xx() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/down"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/gone"))
+            .respond_with(ResponseTemplate::new(410))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let http = reqwest::Client::new();
+        let down = send_with_retries(&http, &format!("{}/down", server.uri()), "s", "ping", Uuid::new_v4(), "{}", no_backoff(3)).await;
+        assert_eq!(down.len(), 3);
+        assert!(!down.last().unwrap().is_success());
+
+        let gone = send_with_retries(&http, &format!("{}/gone", server.uri()), "s", "ping", Uuid::new_v4(), "{}", no_backoff(3)).await;
+        assert_eq!(gone.len(), 1);
+        assert_eq!(gone[0].status_code, Some(410));
+    }
+}
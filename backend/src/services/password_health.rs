@@ -0,0 +1,290 @@
+//! Password strength and breach checks
+//!
+//! Saved passwords are scored with zxcvbn (0 to 4). When
+//! `PASSWORD_BREACH_CHECK_ENABLED` is set they are also looked up in the
+//! Have I Been Pwned range API using k-anonymity: only the first five hex
+//! characters of the SHA-1 hash leave the server, and the returned suffixes
+//! are matched locally. The score and breach flag are stored on the
+//! password row; the password itself never is.
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+const HIBP_API_BASE: &str = "https://api.pwnedpasswords.com";
+const HIBP_PREFIX_LEN: usize = 5;
+
+/// Scores at or below this are listed as weak in the health report
+pub const WEAK_SCORE: i32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BreachCheckError {
+    #[error("Breach lookup failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Breach lookup returned {0}")]
+    Status(reqwest::StatusCode),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordStrength {
+    pub score: i32,
+    pub label: String,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// Strength plus breach status. `breached` is `None` when the lookup is
+/// disabled or failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordAssessment {
+    pub strength: PasswordStrength,
+    pub breached: Option<bool>,
+    pub breach_count: Option<u64>,
+}
+
+impl PasswordAssessment {
+    pub fn breach_detected(&self) -> bool {
+        self.breached.unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WeakCredential {
+    pub id: Uuid,
+    pub name: String,
+    pub username: Option<String>,
+    pub client_id: Option<Uuid>,
+    pub client_name: Option<String>,
+    pub strength_score: i32,
+    pub breach_detected: bool,
+    pub breach_checked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Whether breach lookups are turned on
+pub fn breach_check_enabled() -> bool {
+    std::env::var("PASSWORD_BREACH_CHECK_ENABLED")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+pub fn strength_label(score: i32) -> String {
+    match score {
+        ..=0 => "Very Weak",
+        1 => "Weak",
+        2 => "Fair",
+        3 => "Good",
+        _ => "Strong",
+    }
+    .to_string()
+}
+
+/// zxcvbn estimate. `user_inputs` such as the entry name and username count
+/// against the password when it contains them.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+    let Ok(entropy) = zxcvbn::zxcvbn(password, user_inputs) else {
+        // zxcvbn rejects empty passwords
+        return PasswordStrength {
+            score: 0,
+            label: strength_label(0),
+            warning: Some("Password is empty".to_string()),
+            suggestions: Vec::new(),
+        };
+    };
+
+    let score = entropy.score() as i32;
+    let (warning, suggestions) = match entropy.feedback() {
+        Some(feedback) => (
+            feedback.warning().map(|w| w.to_string()),
+            feedback.suggestions().iter().map(|s| s.to_string()).collect(),
+        ),
+        None => (None, Vec::new()),
+    };
+
+    PasswordStrength { score, label: strength_label(score), warning, suggestions }
+}
+
+/// Uppercase SHA-1 of the password split into the five-character prefix
+/// sent to the range API and the suffix matched locally
+pub fn hash_prefix_suffix(password: &str) -> (String, String) {
+    let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(HIBP_PREFIX_LEN);
+    (prefix.to_string(), suffix.to_string())
+}
+
+/// Times `suffix` appears in a range response. Each line is
+/// `SUFFIX:COUNT`; padding entries have a count of zero.
+pub fn breach_count_in_range(range: &str, suffix: &str) -> u64 {
+    range
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub async fn breach_count(http: &reqwest::Client, password: &str) -> Result<u64, BreachCheckError> {
+    breach_count_at(HIBP_API_BASE, http, password).await
+}
+
+pub async fn breach_count_at(base_url: &str, http: &reqwest::Client, password: &str) -> Result<u64, BreachCheckError> {
+    let (prefix, suffix) = hash_prefix_suffix(password);
+    let response = http
+        .get(format!("{}/range/{}", base_url.trim_end_matches('/'), prefix))
+        .header("Add-Padding", "true")
+        .header(reqwest::header::USER_AGENT, "resolve-password-health")
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(BreachCheckError::Status(response.status()));
+    }
+
+    Ok(breach_count_in_range(&response.text().await?, &suffix))
+}
+
+/// Score `password` and, when enabled, check it against known breaches. A
+/// failed lookup is logged and leaves the breach status unknown rather
+/// than blocking the save.
+pub async fn assess(http: &reqwest::Client, password: &str, user_inputs: &[&str], check_breaches: bool) -> PasswordAssessment {
+    let strength = estimate_strength(password, user_inputs);
+
+    let breach_count = if check_breaches && !password.is_empty() {
+        match breach_count(http, password).await {
+            Ok(count) => Some(count),
+            Err(e) => {
+                tracing::warn!("Password breach check failed: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    PasswordAssessment { strength, breached: breach_count.map(|c| c > 0), breach_count }
+}
+
+/// Passwords that are weak or known to be breached, weakest first. With a
+/// `viewer`, only those they created or hold an active grant for are listed;
+/// `None` is for callers with `passwords.read`, who see every password.
+pub async fn weak_credentials(
+    pool: &PgPool,
+    client_id: Option<Uuid>,
+    viewer: Option<Uuid>,
+) -> Result<Vec<WeakCredential>, sqlx::Error> {
+    sqlx::query_as::<_, WeakCredential>(
+        r#"
+        SELECT p.id, p.name, p.username, p.client_id, c.name AS client_name,
+               COALESCE(p.strength_score, 0) AS strength_score,
+               COALESCE(p.breach_detected, false) AS breach_detected,
+               p.breach_checked_at, p.updated_at
+        FROM passwords p
+        LEFT JOIN clients c ON p.client_id = c.id
+        WHERE (COALESCE(p.strength_score, 0) <= $1 OR p.breach_detected = true)
+            AND ($2::uuid IS NULL OR p.client_id = $2)
+            AND ($3::uuid IS NULL OR p.created_by = $3 OR EXISTS (
+                SELECT 1 FROM credential_grants g
+                WHERE g.password_id = p.id AND g.grantee_id = $3
+                    AND g.revoked_at IS NULL AND g.expires_at > NOW()
+            ))
+        ORDER BY p.breach_detected DESC, p.strength_score ASC, p.name ASC
+        "#
+    )
+    .bind(WEAK_SCORE)
+    .bind(client_id)
+    .bind(viewer)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // SHA-1 of "password"
+    const PASSWORD_SHA1: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+    #[test]
+    fn test_hash_prefix_suffix() {
+        let (prefix, suffix) = hash_prefix_suffix("password");
+        assert_eq!(prefix, "5BAA6");
+        assert_eq!(suffix, "1E4C9B93F3F0682250B6CF8331B7EE68FD8");
+        assert_eq!(format!("{}{}", prefix, suffix), PASSWORD_SHA1);
+    }
+
+    #[test]
+    fn test_breach_count_in_range() {
+        let range = "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n\
+                     1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n\
+                     FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:0\r\n";
+        assert_eq!(breach_count_in_range(range, "1E4C9B93F3F0682250B6CF8331B7EE68FD8"), 9659365);
+        assert_eq!(breach_count_in_range(range, "1e4c9b93f3f0682250b6cf8331b7ee68fd8"), 9659365);
+        assert_eq!(breach_count_in_range(range, "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"), 0);
+        assert_eq!(breach_count_in_range(range, "0000000000000000000000000000000000A"), 0);
+    }
+
+    #[test]
+    fn test_strength_scores() {
+        let weak = estimate_strength("password", &[]);
+        assert_eq!(weak.score, 0);
+        assert!(weak.warning.is_some());
+
+        let strong = estimate_strength("correct-horse-battery-staple-42!", &[]);
+        assert_eq!(strong.score, 4);
+        assert_eq!(strong.label, "Strong");
+
+        assert_eq!(estimate_strength("", &[]).score, 0);
+        assert!(estimate_strength("AcmeCorp2024", &["acmecorp"]).score <= WEAK_SCORE);
+    }
+
+    #[tokio::test]
+    async fn test_breach_lookup_sends_only_the_prefix() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/range/5BAA6"))
+            .and(header("add-padding", "true"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let http = reqwest::Client::new();
+        assert_eq!(breach_count_at(&server.uri(), &http, "password").await.unwrap(), 9659365);
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].url.path(), "/range/5BAA6");
+        assert!(!requests[0].url.as_str().contains(&PASSWORD_SHA1[HIBP_PREFIX_LEN..]));
+        assert!(requests[0].body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_breach_lookup_unlisted_suffix() {
+        let server = MockServer::start().await;
+        let (prefix, _) = hash_prefix_suffix("password");
+        Mock::given(method("GET"))
+            .and(path(format!("/range/{}", prefix)))
+            .respond_with(ResponseTemplate::new(200).set_body_string("0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n"))
+            .mount(&server)
+            .await;
+
+        assert_eq!(breach_count_at(&server.uri(), &reqwest::Client::new(), "password").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_breach_lookup_error_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let result = breach_count_at(&server.uri(), &reqwest::Client::new(), "password").await;
+        assert!(matches!(result, Err(BreachCheckError::Status(status)) if status.as_u16() == 503));
+    }
+}
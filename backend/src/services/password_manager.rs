@@ -1,5 +1,8 @@
+use crate::audit::{self, AuditEvent, RequestMeta};
 use crate::models::passwords::*;
+use crate::services::credential_grants;
 use crate::services::encryption::EncryptionService;
+use crate::services::password_health::{self, PasswordAssessment};
 use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
 use chrono::{DateTime, Duration, Utc};
 use rand::Rng;
@@ -102,7 +105,7 @@ impl PasswordManagerService {
         }
     }
 
-    pub async fn create_password(&self, request: CreatePasswordRequest, created_by: Uuid) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn create_password(&self, request: CreatePasswordRequest, created_by: Uuid, assessment: &PasswordAssessment) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
         let password_id = Uuid::new_v4();
         
         // Encrypt password and notes
@@ -120,37 +123,37 @@ impl PasswordManagerService {
             None
         };
 
-        // Calculate password strength
-        let strength_score = self.calculate_password_strength(&request.password);
-
         // Serialize tags
         let tags_json = serde_json::to_string(&request.tags)?;
 
-        sqlx::query!(
+        sqlx::query(
             r#"
             INSERT INTO passwords (id, client_id, name, description, username, password_encrypted,
                                  url, notes_encrypted, category, tags, favorite, otp_secret_encrypted,
                                  phonetic_enabled, created_by, created_at, updated_at, expires_at,
-                                 strength_score, breach_detected, folder_id)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, false, $11, $12, $13, NOW(), NOW(), $14, $15, false, $16)
-            "#,
-            password_id,
-            request.client_id,
-            request.name,
-            request.description,
-            request.username,
-            encrypted_password,
-            request.url,
-            encrypted_notes,
-            request.category,
-            tags_json,
-            encrypted_otp_secret,
-            request.phonetic_enabled,
-            created_by,
-            request.expires_at,
-            strength_score,
-            request.folder_id
+                                 strength_score, breach_detected, breach_checked_at, folder_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10::jsonb, false, $11, $12, $13, NOW(), NOW(), $14, $15, $16,
+                    CASE WHEN $17 THEN NOW() END, $18)
+            "#
         )
+        .bind(password_id)
+        .bind(request.client_id)
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(&request.username)
+        .bind(&encrypted_password)
+        .bind(&request.url)
+        .bind(&encrypted_notes)
+        .bind(&request.category)
+        .bind(&tags_json)
+        .bind(&encrypted_otp_secret)
+        .bind(request.phonetic_enabled)
+        .bind(created_by)
+        .bind(request.expires_at)
+        .bind(assessment.strength.score)
+        .bind(assessment.breach_detected())
+        .bind(assessment.breached.is_some())
+        .bind(request.folder_id)
         .execute(&self.db_pool)
         .await?;
 
@@ -158,6 +161,80 @@ impl PasswordManagerService {
         Ok(password_id)
     }
 
+    /// Update a password owned by `user_id`. `assessment` is only given when
+    /// the password itself changes. Returns false when no such password.
+    pub async fn update_password(
+        &self,
+        id: Uuid,
+        request: UpdatePasswordRequest,
+        user_id: Uuid,
+        assessment: Option<&PasswordAssessment>,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let encrypted_password = match &request.password {
+            Some(password) => Some(self.encryption_service.encrypt(password)?),
+            None => None,
+        };
+        let encrypted_notes = match &request.notes {
+            Some(notes) => Some(self.encryption_service.encrypt(notes)?),
+            None => None,
+        };
+        let encrypted_otp_secret = match &request.otp_secret {
+            Some(otp_secret) => Some(self.encryption_service.encrypt(otp_secret)?),
+            None => None,
+        };
+        let tags_json = match &request.tags {
+            Some(tags) => Some(serde_json::to_string(tags)?),
+            None => None,
+        };
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE passwords SET
+                name = COALESCE($3, name),
+                description = COALESCE($4, description),
+                username = COALESCE($5, username),
+                password_encrypted = COALESCE($6, password_encrypted),
+                url = COALESCE($7, url),
+                notes_encrypted = COALESCE($8, notes_encrypted),
+                category = COALESCE($9, category),
+                tags = COALESCE($10::jsonb, tags),
+                otp_secret_encrypted = COALESCE($11, otp_secret_encrypted),
+                phonetic_enabled = COALESCE($12, phonetic_enabled),
+                expires_at = COALESCE($13, expires_at),
+                folder_id = COALESCE($14, folder_id),
+                strength_score = COALESCE($15, strength_score),
+                breach_detected = CASE WHEN $16::boolean IS NULL THEN breach_detected ELSE $16 END,
+                breach_checked_at = CASE WHEN $16::boolean IS NULL THEN breach_checked_at ELSE NOW() END,
+                updated_at = NOW()
+            WHERE id = $1 AND created_by = $2
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(&request.username)
+        .bind(&encrypted_password)
+        .bind(&request.url)
+        .bind(&encrypted_notes)
+        .bind(&request.category)
+        .bind(&tags_json)
+        .bind(&encrypted_otp_secret)
+        .bind(request.phonetic_enabled)
+        .bind(request.expires_at)
+        .bind(request.folder_id)
+        .bind(assessment.map(|a| a.strength.score))
+        .bind(assessment.and_then(|a| a.breached))
+        .execute(&self.db_pool)
+        .await?
+        .rows_affected();
+
+        if updated > 0 {
+            info!("Updated password {}", id);
+        }
+        Ok(updated > 0)
+    }
+
     pub async fn get_password(&self, id: Uuid, user_id: Uuid) -> Result<Option<PasswordResponse>, Box<dyn std::error::Error + Send + Sync>> {
         let result = sqlx::query!(
             r#"
@@ -309,90 +386,18 @@ impl PasswordManagerService {
             None
         };
         
-        let strength_score = self.calculate_password_strength(&password);
+        let strength = password_health::estimate_strength(&password, &[]);
         
         Ok(GeneratePasswordResponse {
             password,
             phonetic_password,
-            strength_score,
-            strength_label: self.get_strength_label(strength_score),
+            strength_score: strength.score,
+            strength_label: strength.label,
         })
     }
 
-    fn calculate_password_strength(&self, password: &str) -> i32 {
-        let mut score = 0;
-        
-        // Base score from length
-        score += password.len() as i32 * 4;
-        
-        // Character diversity bonuses
-        if password.chars().any(|c| c.is_lowercase()) {
-            score += 5;
-        }
-        if password.chars().any(|c| c.is_uppercase()) {
-            score += 5;
-        }
-        if password.chars().any(|c| c.is_numeric()) {
-            score += 10;
-        }
-        if password.chars().any(|c| !c.is_alphanumeric()) {
-            score += 15;
-        }
-        
-        // Length bonuses
-        if password.len() >= 8 {
-            score += 10;
-        }
-        if password.len() >= 12 {
-            score += 20;
-        }
-        if password.len() >= 16 {
-            score += 30;
-        }
-        
-        // Deduct for common patterns
-        if self.has_sequential_chars(password) {
-            score -= 15;
-        }
-        if self.has_repeated_chars(password) {
-            score -= 10;
-        }
-        
-        score.max(0).min(100)
-    }
-
-    fn has_sequential_chars(&self, password: &str) -> bool {
-        let chars: Vec<char> = password.chars().collect();
-        for i in 0..chars.len().saturating_sub(2) {
-            let a = chars[i] as u32;
-            let b = chars[i + 1] as u32;
-            let c = chars[i + 2] as u32;
-            
-            if (b == a + 1 && c == b + 1) || (b == a - 1 && c == b - 1) {
-                return true;
-            }
-        }
-        false
-    }
-
-    fn has_repeated_chars(&self, password: &str) -> bool {
-        let mut char_counts = HashMap::new();
-        for ch in password.chars() {
-            *char_counts.entry(ch).or_insert(0) += 1;
-        }
-        
-        char_counts.values().any(|&count| count >= 3)
-    }
-
     fn get_strength_label(&self, score: i32) -> String {
-        match score {
-            0..=30 => "Very Weak".to_string(),
-            31..=50 => "Weak".to_string(),
-            51..=70 => "Fair".to_string(),
-            71..=85 => "Good".to_string(),
-            86..=95 => "Strong".to_string(),
-            _ => "Very Strong".to_string(),
-        }
+        password_health::strength_label(score)
     }
 
     fn generate_totp(&self, secret: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
@@ -442,8 +447,24 @@ impl PasswordManagerService {
         Ok(folder_id)
     }
 
-    pub async fn create_password_share(&self, request: CreatePasswordShareRequest, created_by: Uuid, base_url: &str) -> Result<PasswordShareResponse, Box<dyn std::error::Error + Send + Sync>> {
-        // Verify password exists and user has access
+    /// Share a password by link. Only someone who may read the password can
+    /// share it; `None` when they may not. `has_permission` is the caller's
+    /// `passwords.read` RBAC check.
+    pub async fn create_password_share(
+        &self,
+        request: CreatePasswordShareRequest,
+        created_by: Uuid,
+        has_permission: bool,
+        meta: &RequestMeta,
+        base_url: &str,
+    ) -> Result<Option<PasswordShareResponse>, Box<dyn std::error::Error + Send + Sync>> {
+        if credential_grants::authorize_read(&self.db_pool, meta, request.password_id, created_by, has_permission)
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+
         let password = sqlx::query!(
             "SELECT name FROM passwords WHERE id = $1",
             request.password_id
@@ -499,7 +520,7 @@ impl PasswordManagerService {
             info!("Password share created for {}", recipient_email);
         }
 
-        Ok(PasswordShareResponse {
+        Ok(Some(PasswordShareResponse {
             id: share_id,
             password_id: request.password_id,
             password_name: password.name,
@@ -519,10 +540,12 @@ impl PasswordManagerService {
             is_expired: false,
             created_by,
             created_by_name,
-        })
+        }))
     }
 
-    pub async fn access_shared_password(&self, request: AccessPasswordShareRequest) -> Result<Option<PasswordShareAccessResponse>, Box<dyn std::error::Error + Send + Sync>> {
+    /// Open a share link. Every view, and every refused view of a live
+    /// share, is written to the password's audit trail.
+    pub async fn access_shared_password(&self, request: AccessPasswordShareRequest, meta: &RequestMeta) -> Result<Option<PasswordShareAccessResponse>, Box<dyn std::error::Error + Send + Sync>> {
         let share = sqlx::query!(
             r#"
             SELECT ps.*, p.name as password_name, p.password_encrypted, p.username, p.url,
@@ -550,25 +573,21 @@ impl PasswordManagerService {
             }
 
             // Check view limits
-            if let Some(max_views) = share.max_views {
-                if share.view_count >= max_views {
-                    return Ok(None);
-                }
-            }
+            let within_limit = share.max_views.is_none_or(|max_views| share.view_count < max_views);
 
             // Verify access password if required
-            if share.require_password {
-                if let (Some(provided_password), Some(stored_hash)) = (&request.access_password, &share.access_password) {
-                    if !bcrypt::verify(provided_password, stored_hash)? {
-                        return Ok(None);
-                    }
-                } else {
-                    return Ok(None);
-                }
-            }
+            let password_ok = !share.require_password
+                || match (&request.access_password, &share.access_password) {
+                    (Some(provided_password), Some(stored_hash)) => bcrypt::verify(provided_password, stored_hash)?,
+                    _ => false,
+                };
 
             // TODO: Verify email verification code if required
-            if share.require_email_verification && request.email_verification_code.is_none() {
+            let verified = !share.require_email_verification || request.email_verification_code.is_some();
+
+            let allowed = within_limit && password_ok && verified;
+            self.record_share_access(meta, share.id, share.password_id, allowed).await;
+            if !allowed {
                 return Ok(None);
             }
 
@@ -629,6 +648,18 @@ impl PasswordManagerService {
         }
     }
 
+    async fn record_share_access(&self, meta: &RequestMeta, share_id: Uuid, password_id: Uuid, allowed: bool) {
+        let event = AuditEvent {
+            user_id: None,
+            action: if allowed { "READ" } else { "ACCESS_DENIED" }.to_string(),
+            entity_type: credential_grants::ENTITY_TYPE.to_string(),
+            entity_id: password_id,
+            old_values: None,
+            new_values: Some(serde_json::json!({ "via": "share", "share_id": share_id })),
+        };
+        audit::record(&self.db_pool, meta, event).await;
+    }
+
     fn generate_secure_token(&self) -> String {
         use rand::Rng;
         const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
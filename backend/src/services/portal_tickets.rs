@@ -0,0 +1,326 @@
+//! Client portal tickets
+//!
+//! What a portal contact can do with tickets. Every query is scoped to the
+//! contact's own client: a contact can list their client's tickets with a
+//! limited set of fields, open tickets for that client, and read or reply
+//! to the tickets they opened. Replies flagged `internal` (all notes) are
+//! never returned. Portal tickets are attributed to the built-in
+//! [`PORTAL_USER_ID`] user and go through the same routing rules, queue
+//! assignment and SLA as tickets created by staff or email.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::services::{chat_notifications, outbound_webhooks};
+use crate::services::queue_assignment::auto_assign_ticket;
+use crate::services::ticket_routing::{apply_routing_rules, RoutableTicket};
+use crate::services::ticket_sla;
+
+/// The inactive system user portal tickets and replies are recorded under
+pub const PORTAL_USER_ID: Uuid = Uuid::from_u128(0x70a1);
+
+pub const PRIORITIES: &[&str] = &["low", "medium", "high", "critical"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum PortalTicketError {
+    #[error("Ticket not found")]
+    NotFound,
+    #[error("{0}")]
+    Validation(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NewPortalTicket {
+    pub subject: String,
+    pub details: String,
+    pub priority: Option<String>,
+    pub asset_id: Option<Uuid>,
+}
+
+/// A ticket as listed in the portal
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PortalTicketSummary {
+    pub id: Uuid,
+    pub number: i32,
+    pub subject: String,
+    pub status: String,
+    pub priority: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub last_reply_at: Option<DateTime<Utc>>,
+    /// Whether the requesting contact opened it
+    pub opened_by_me: bool,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PortalReply {
+    pub id: Uuid,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+    pub author_name: Option<String>,
+    /// `contact` for replies from the client, `support` otherwise
+    pub author_type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortalTicketDetail {
+    #[serde(flatten)]
+    pub ticket: PortalTicketSummary,
+    pub details: String,
+    pub replies: Vec<PortalReply>,
+}
+
+/// Check a new ticket and normalise its priority
+pub fn validate(ticket: &NewPortalTicket) -> Result<String, PortalTicketError> {
+    if ticket.subject.trim().is_empty() {
+        return Err(PortalTicketError::Validation("Subject is required".to_string()));
+    }
+    if ticket.details.trim().is_empty() {
+        return Err(PortalTicketError::Validation("Details are required".to_string()));
+    }
+    let priority = ticket.priority.as_deref().unwrap_or("medium").trim().to_ascii_lowercase();
+    if !PRIORITIES.contains(&priority.as_str()) {
+        return Err(PortalTicketError::Validation(format!("Unknown priority '{}'", priority)));
+    }
+    Ok(priority)
+}
+
+const SUMMARY_COLUMNS: &str = r#"
+    t.id, t.number, t.subject, COALESCE(t.status, 'open') as status,
+    COALESCE(t.priority, 'medium') as priority, COALESCE(t.created_at, NOW()) as created_at, t.updated_at,
+    (SELECT MAX(r.created_at) FROM ticket_replies r WHERE r.ticket_id = t.id AND NOT r.internal) as last_reply_at,
+    COALESCE(t.contact_id = $2, false) as opened_by_me
+"#;
+
+/// Every ticket for the contact's client, newest first
+pub async fn list_tickets(pool: &PgPool, client_id: Uuid, contact_id: Uuid) -> Result<Vec<PortalTicketSummary>, sqlx::Error> {
+    sqlx::query_as::<_, PortalTicketSummary>(&format!(
+        "SELECT {} FROM tickets t WHERE t.client_id = $1 ORDER BY t.created_at DESC",
+        SUMMARY_COLUMNS
+    ))
+    .bind(client_id)
+    .bind(contact_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// A ticket the contact opened, `None` for anyone else's
+async fn own_ticket(pool: &PgPool, client_id: Uuid, contact_id: Uuid, ticket_id: Uuid) -> Result<Option<(PortalTicketSummary, String)>, sqlx::Error> {
+    #[derive(FromRow)]
+    struct Row {
+        #[sqlx(flatten)]
+        summary: PortalTicketSummary,
+        details: String,
+    }
+
+    let row = sqlx::query_as::<_, Row>(&format!(
+        "SELECT {}, t.details FROM tickets t WHERE t.client_id = $1 AND t.contact_id = $2 AND t.id = $3",
+        SUMMARY_COLUMNS
+    ))
+    .bind(client_id)
+    .bind(contact_id)
+    .bind(ticket_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| (r.summary, r.details)))
+}
+
+/// Replies the client may see, oldest first
+pub async fn visible_replies(pool: &PgPool, ticket_id: Uuid) -> Result<Vec<PortalReply>, sqlx::Error> {
+    sqlx::query_as::<_, PortalReply>(
+        r#"
+        SELECT r.id, r.details as message, COALESCE(r.created_at, NOW()) as created_at,
+               CASE WHEN r.contact_id IS NOT NULL THEN ct.name
+                    ELSE u.first_name || ' ' || u.last_name END as author_name,
+               CASE WHEN r.contact_id IS NOT NULL THEN 'contact' ELSE 'support' END as author_type
+        FROM ticket_replies r
+        LEFT JOIN users u ON u.id = r.user_id
+        LEFT JOIN contacts ct ON ct.id = r.contact_id
+        WHERE r.ticket_id = $1 AND NOT r.internal
+        ORDER BY r.created_at ASC
+        "#
+    )
+    .bind(ticket_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Status and visible replies of a ticket the contact opened
+pub async fn ticket_detail(
+    pool: &PgPool,
+    client_id: Uuid,
+    contact_id: Uuid,
+    ticket_id: Uuid,
+) -> Result<PortalTicketDetail, PortalTicketError> {
+    let (ticket, details) = own_ticket(pool, client_id, contact_id, ticket_id)
+        .await?
+        .ok_or(PortalTicketError::NotFound)?;
+    let replies = visible_replies(pool, ticket_id).await?;
+    Ok(PortalTicketDetail { ticket, details, replies })
+}
+
+/// Open a ticket for the contact's client, route it and assign it
+pub async fn create_ticket(
+    pool: &PgPool,
+    client_id: Uuid,
+    contact_id: Uuid,
+    ticket: NewPortalTicket,
+) -> Result<PortalTicketSummary, PortalTicketError> {
+    let priority = validate(&ticket)?;
+
+    if let Some(asset_id) = ticket.asset_id {
+        let owned = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM assets WHERE id = $1 AND client_id = $2)")
+            .bind(asset_id)
+            .bind(client_id)
+            .fetch_one(pool)
+            .await?;
+        if !owned {
+            return Err(PortalTicketError::Validation("Unknown asset".to_string()));
+        }
+    }
+
+    let subject = ticket.subject.trim().to_string();
+    let (ticket_id, number) = sqlx::query_as::<_, (Uuid, i32)>(
+        r#"
+        INSERT INTO tickets (number, client_id, contact_id, asset_id, opened_by,
+                             subject, details, status, priority, source)
+        SELECT COALESCE(MAX(number), 0) + 1, $1, $2, $3, $4, $5, $6, 'open', $7, 'portal'
+        FROM tickets
+        RETURNING id, number
+        "#
+    )
+    .bind(client_id)
+    .bind(contact_id)
+    .bind(ticket.asset_id)
+    .bind(PORTAL_USER_ID)
+    .bind(&subject)
+    .bind(ticket.details.trim())
+    .bind(&priority)
+    .fetch_one(pool)
+    .await?;
+    crate::middleware::prometheus::record_ticket_created("portal");
+
+    // Routing and assignment failures shouldn't lose the ticket
+    let routable = RoutableTicket {
+        id: ticket_id,
+        client_id,
+        contact_id: Some(contact_id),
+        category_id: None,
+        subject: subject.clone(),
+        priority,
+        source: "portal".to_string(),
+        source_email: None,
+    };
+    match apply_routing_rules(pool, &routable).await {
+        Ok(outcome) => {
+            if let Some(queue_id) = outcome.queue_id {
+                if let Err(e) = auto_assign_ticket(pool, ticket_id, queue_id).await {
+                    tracing::error!("Error auto-assigning ticket {}: {}", ticket_id, e);
+                }
+            }
+        }
+        Err(e) => tracing::error!("Error applying routing rules to ticket {}: {}", ticket_id, e),
+    }
+    if let Err(e) = ticket_sla::apply_sla(pool, ticket_id).await {
+        tracing::error!("Error applying SLA to ticket {}: {}", ticket_id, e);
+    }
+
+    let data = serde_json::json!({
+        "ticket_id": ticket_id,
+        "number": number,
+        "client_id": client_id,
+        "contact_id": contact_id,
+        "subject": subject,
+        "source": "portal"
+    });
+    outbound_webhooks::notify(pool, outbound_webhooks::TICKET_CREATED, data).await;
+    chat_notifications::notify_ticket_created(pool, ticket_id).await;
+
+    let (summary, _) = own_ticket(pool, client_id, contact_id, ticket_id)
+        .await?
+        .ok_or(PortalTicketError::NotFound)?;
+    Ok(summary)
+}
+
+/// Add the contact's reply to a ticket they opened, reopening it if it was
+/// closed or resolved
+pub async fn add_reply(
+    pool: &PgPool,
+    client_id: Uuid,
+    contact_id: Uuid,
+    ticket_id: Uuid,
+    message: &str,
+) -> Result<Uuid, PortalTicketError> {
+    if message.trim().is_empty() {
+        return Err(PortalTicketError::Validation("Message is required".to_string()));
+    }
+    if own_ticket(pool, client_id, contact_id, ticket_id).await?.is_none() {
+        return Err(PortalTicketError::NotFound);
+    }
+
+    let mut tx = pool.begin().await?;
+    let reply_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO ticket_replies (ticket_id, user_id, contact_id, type, details, internal)
+        VALUES ($1, $2, $3, 'reply', $4, false)
+        RETURNING id
+        "#
+    )
+    .bind(ticket_id)
+    .bind(PORTAL_USER_ID)
+    .bind(contact_id)
+    .bind(message.trim())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE tickets SET
+            status = CASE WHEN status IN ('closed', 'resolved') THEN 'open' ELSE status END,
+            closed_at = CASE WHEN status IN ('closed', 'resolved') THEN NULL ELSE closed_at END,
+            updated_at = NOW()
+        WHERE id = $1
+        "#
+    )
+    .bind(ticket_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(reply_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket(subject: &str, details: &str, priority: Option<&str>) -> NewPortalTicket {
+        NewPortalTicket {
+            subject: subject.to_string(),
+            details: details.to_string(),
+            priority: priority.map(str::to_string),
+            asset_id: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_normalises_priority() {
+        assert_eq!(validate(&ticket("Printer", "Jammed", None)).unwrap(), "medium");
+        assert_eq!(validate(&ticket("Printer", "Jammed", Some(" High "))).unwrap(), "high");
+        assert!(matches!(
+            validate(&ticket("Printer", "Jammed", Some("urgent!"))),
+            Err(PortalTicketError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_requires_subject_and_details() {
+        assert!(matches!(validate(&ticket("  ", "Jammed", None)), Err(PortalTicketError::Validation(_))));
+        assert!(matches!(validate(&ticket("Printer", "", None)), Err(PortalTicketError::Validation(_))));
+    }
+}
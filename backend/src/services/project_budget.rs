@@ -0,0 +1,421 @@
+//! Project budget burn-down and over-budget alerts
+//!
+//! A project's consumption is its billable time, at the project's hourly
+//! rate (else the entry's, the user's, or the default), plus its expenses.
+//! Projection assumes spend keeps the average daily rate seen since the
+//! project started, through its `end_date`. The budget job passes a set of
+//! percentage thresholds; once consumption reaches one, an `alerts` row is
+//! opened and the project manager notified. `project_budget_alerts` records
+//! which threshold each alert was for, so every threshold fires at most once
+//! per budget amount.
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::notifications::create_notifications_for_users;
+use crate::services::asset_warranty::client_technicians;
+use crate::services::time_timers::DEFAULT_HOURLY_RATE;
+
+pub const ALERT_TYPE: &str = "project_budget";
+
+/// Spend recorded against a project so far
+#[derive(Debug, Clone, FromRow)]
+pub struct ProjectSpend {
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub client_id: Uuid,
+    pub project_manager_id: Option<Uuid>,
+    pub budget: Option<Decimal>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub billable_minutes: i64,
+    pub time_amount: Decimal,
+    pub expense_amount: Decimal,
+    /// Earliest time entry or expense, used when the project has no start date
+    pub first_activity: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectBudget {
+    pub project_id: Uuid,
+    /// `None` when the project has no budget; the percentages and
+    /// projections that depend on it are `None` too
+    pub budget: Option<Decimal>,
+    pub billable_minutes: i64,
+    pub time_amount: Decimal,
+    pub expense_amount: Decimal,
+    pub consumed: Decimal,
+    pub remaining: Option<Decimal>,
+    pub percent_consumed: Option<Decimal>,
+    /// Average spend per day since the project started
+    pub burn_rate_per_day: Option<Decimal>,
+    /// Spend at `end_date` if the burn rate holds
+    pub projected_total: Option<Decimal>,
+    /// How far `projected_total` exceeds the budget, zero when it doesn't
+    pub projected_overrun: Option<Decimal>,
+}
+
+/// An alert opened by [`check_budgets`]
+#[derive(Debug, Clone)]
+pub struct BudgetAlert {
+    pub alert_id: Uuid,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub threshold_percent: i32,
+    pub percent_consumed: Decimal,
+    pub notified: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct BudgetCheck {
+    pub checked: usize,
+    pub alerts: Vec<BudgetAlert>,
+}
+
+/// Share of `budget` consumed as a percentage to two places. `None` without
+/// a positive budget.
+pub fn percent_consumed(consumed: Decimal, budget: Option<Decimal>) -> Option<Decimal> {
+    let budget = budget.filter(|b| *b > Decimal::ZERO)?;
+    Some((consumed * Decimal::from(100) / budget).round_dp(2))
+}
+
+/// Average spend per day from `started` through `today`, counting both ends.
+/// `None` before the project has started.
+pub fn burn_rate(consumed: Decimal, started: NaiveDate, today: NaiveDate) -> Option<Decimal> {
+    let days = (today - started).num_days() + 1;
+    if days < 1 {
+        return None;
+    }
+    Some((consumed / Decimal::from(days)).round_dp(2))
+}
+
+/// Spend at `end` if the average rate since `started` holds. Once `end` has
+/// passed, what's been spent is the total.
+pub fn projected_total(consumed: Decimal, started: NaiveDate, end: NaiveDate, today: NaiveDate) -> Option<Decimal> {
+    if end < started {
+        return None;
+    }
+    if today >= end {
+        return Some(consumed);
+    }
+    let elapsed = (today - started).num_days() + 1;
+    if elapsed < 1 {
+        return None;
+    }
+    let total = (end - started).num_days() + 1;
+    Some((consumed * Decimal::from(total) / Decimal::from(elapsed)).round_dp(2))
+}
+
+/// The highest threshold that `percent` has reached, if any. A project
+/// that jumps straight to 105% against `[80, 100]` alerts once for 100.
+pub fn crossed_threshold(percent: Decimal, thresholds: &[i32]) -> Option<i32> {
+    thresholds.iter().copied().filter(|t| percent >= Decimal::from(*t)).max()
+}
+
+/// Severity rises once the budget is spent
+pub fn severity(threshold_percent: i32) -> &'static str {
+    match threshold_percent {
+        100.. => "high",
+        _ => "medium",
+    }
+}
+
+pub fn alert_message(spend: &ProjectSpend, percent: Decimal) -> String {
+    format!(
+        "Project {} has consumed {}% of its {} budget ({} spent)",
+        spend.project_name,
+        percent.round_dp(0),
+        spend.budget.unwrap_or_default().round_dp(2),
+        (spend.time_amount + spend.expense_amount).round_dp(2)
+    )
+}
+
+impl ProjectSpend {
+    /// Burn-down figures as of `today`
+    pub fn summarize(&self, today: NaiveDate) -> ProjectBudget {
+        let consumed = self.time_amount + self.expense_amount;
+        let started = self.start_date.or(self.first_activity);
+        let burn_rate_per_day = started.and_then(|s| burn_rate(consumed, s, today));
+        let projected = match (started, self.end_date) {
+            (Some(s), Some(e)) => projected_total(consumed, s, e, today),
+            _ => None,
+        };
+        let budget = self.budget;
+
+        ProjectBudget {
+            project_id: self.project_id,
+            budget,
+            billable_minutes: self.billable_minutes,
+            time_amount: self.time_amount,
+            expense_amount: self.expense_amount,
+            consumed,
+            remaining: budget.map(|b| b - consumed),
+            percent_consumed: percent_consumed(consumed, budget),
+            burn_rate_per_day,
+            projected_total: projected,
+            projected_overrun: budget
+                .zip(projected)
+                .map(|(b, p)| (p - b).max(Decimal::ZERO)),
+        }
+    }
+}
+
+const SPEND_QUERY: &str = r#"
+    SELECT p.id AS project_id, p.name AS project_name, p.client_id, p.project_manager_id,
+           p.budget, p.start_date, p.end_date,
+           COALESCE(t.billable_minutes, 0)::bigint AS billable_minutes,
+           COALESCE(t.amount, 0) AS time_amount,
+           COALESCE(e.amount, 0) AS expense_amount,
+           LEAST(t.first_date, e.first_date) AS first_activity
+    FROM projects p
+    LEFT JOIN LATERAL (
+        SELECT SUM(COALESCE(te.billable_minutes, te.duration_minutes, 0)) AS billable_minutes,
+               ROUND(SUM(
+                   COALESCE(te.billable_minutes, te.duration_minutes, 0)
+                   * COALESCE(p.hourly_rate, te.hourly_rate, u.hourly_rate, $1) / 60
+               ), 2) AS amount,
+               MIN(te.start_time)::date AS first_date
+        FROM time_entries te
+        JOIN users u ON u.id = te.user_id
+        WHERE te.project_id = p.id AND te.end_time IS NOT NULL AND COALESCE(te.billable, true)
+    ) t ON true
+    LEFT JOIN LATERAL (
+        SELECT SUM(ex.amount) AS amount, MIN(ex.expense_date) AS first_date
+        FROM expenses ex
+        WHERE ex.project_id = p.id
+    ) e ON true
+"#;
+
+/// Spend for one project, `None` if it doesn't exist
+pub async fn project_spend(pool: &PgPool, project_id: Uuid) -> Result<Option<ProjectSpend>, sqlx::Error> {
+    sqlx::query_as::<_, ProjectSpend>(&format!("{} WHERE p.id = $2", SPEND_QUERY))
+        .bind(Decimal::from(DEFAULT_HOURLY_RATE))
+        .bind(project_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Burn-down for one project as of `today`, `None` if it doesn't exist
+pub async fn project_budget(pool: &PgPool, project_id: Uuid, today: NaiveDate) -> Result<Option<ProjectBudget>, sqlx::Error> {
+    Ok(project_spend(pool, project_id).await?.map(|spend| spend.summarize(today)))
+}
+
+/// Active projects that have a budget to check
+pub async fn budgeted_projects(pool: &PgPool) -> Result<Vec<ProjectSpend>, sqlx::Error> {
+    sqlx::query_as::<_, ProjectSpend>(&format!(
+        "{} WHERE p.budget > 0 AND COALESCE(p.status, 'active') IN ('active', 'on_hold') ORDER BY p.name",
+        SPEND_QUERY
+    ))
+    .bind(Decimal::from(DEFAULT_HOURLY_RATE))
+    .fetch_all(pool)
+    .await
+}
+
+/// Open the alert for `threshold_percent` on `conn` unless it was already
+/// raised for this budget. Returns the new alert's id.
+pub async fn raise_alert(
+    conn: &mut PgConnection,
+    spend: &ProjectSpend,
+    threshold_percent: i32,
+    percent: Decimal,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let claimed = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO project_budget_alerts (project_id, threshold_percent, budget)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (project_id, threshold_percent, budget) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(spend.project_id)
+    .bind(threshold_percent)
+    .bind(spend.budget)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(claim_id) = claimed else {
+        return Ok(None);
+    };
+
+    let alert_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO alerts (alert_type, severity, title, message)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#
+    )
+    .bind(ALERT_TYPE)
+    .bind(severity(threshold_percent))
+    .bind(format!("Project budget {}% consumed: {}", threshold_percent, spend.project_name))
+    .bind(alert_message(spend, percent))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query("UPDATE project_budget_alerts SET alert_id = $2 WHERE id = $1")
+        .bind(claim_id)
+        .bind(alert_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(Some(alert_id))
+}
+
+/// Raise and notify an alert for every budgeted project that has crossed a
+/// new threshold. The project manager is notified, or the client's
+/// technicians when the project has none.
+pub async fn check_budgets(pool: &PgPool, thresholds: &[i32]) -> Result<BudgetCheck, sqlx::Error> {
+    if thresholds.is_empty() {
+        return Ok(BudgetCheck::default());
+    }
+
+    let projects = budgeted_projects(pool).await?;
+    let mut check = BudgetCheck { checked: projects.len(), alerts: Vec::new() };
+
+    for spend in projects {
+        let consumed = spend.time_amount + spend.expense_amount;
+        let Some(percent) = percent_consumed(consumed, spend.budget) else {
+            continue;
+        };
+        let Some(threshold_percent) = crossed_threshold(percent, thresholds) else {
+            continue;
+        };
+        // The alert and its threshold marker only commit once the
+        // notifications are out, so a failed send is retried next run
+        let mut tx = pool.begin().await?;
+        let Some(alert_id) = raise_alert(&mut tx, &spend, threshold_percent, percent).await? else {
+            continue;
+        };
+
+        let recipients = match spend.project_manager_id {
+            Some(manager_id) => vec![manager_id],
+            None => client_technicians(pool, spend.client_id).await?,
+        };
+        let notified = create_notifications_for_users(
+            pool,
+            recipients,
+            format!("Project budget {}% consumed: {}", threshold_percent, spend.project_name),
+            alert_message(&spend, percent),
+            "project_budget".to_string(),
+            Some("project".to_string()),
+            Some(spend.project_id),
+        )
+        .await?
+        .len();
+        tx.commit().await?;
+
+        check.alerts.push(BudgetAlert {
+            alert_id,
+            project_id: spend.project_id,
+            project_name: spend.project_name,
+            threshold_percent,
+            percent_consumed: percent,
+            notified,
+        });
+    }
+
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn spend(budget: Option<&str>, time: &str, expenses: &str) -> ProjectSpend {
+        ProjectSpend {
+            project_id: Uuid::new_v4(),
+            project_name: "Office move".to_string(),
+            client_id: Uuid::new_v4(),
+            project_manager_id: None,
+            budget: budget.map(dec),
+            start_date: Some(date(3, 1)),
+            end_date: Some(date(3, 30)),
+            billable_minutes: 0,
+            time_amount: dec(time),
+            expense_amount: dec(expenses),
+            first_activity: None,
+        }
+    }
+
+    #[test]
+    fn test_percent_consumed() {
+        assert_eq!(percent_consumed(dec("800"), Some(dec("1000"))), Some(dec("80")));
+        assert_eq!(percent_consumed(dec("1"), Some(dec("3"))), Some(dec("33.33")));
+        assert_eq!(percent_consumed(dec("800"), None), None);
+        assert_eq!(percent_consumed(dec("800"), Some(Decimal::ZERO)), None);
+    }
+
+    #[test]
+    fn test_projection_follows_burn_rate() {
+        // 10 of 30 days gone with 1,000 spent projects 3,000
+        assert_eq!(burn_rate(dec("1000"), date(3, 1), date(3, 10)), Some(dec("100")));
+        assert_eq!(projected_total(dec("1000"), date(3, 1), date(3, 30), date(3, 10)), Some(dec("3000")));
+        // After the end date spend is final
+        assert_eq!(projected_total(dec("1000"), date(3, 1), date(3, 30), date(4, 2)), Some(dec("1000")));
+        // Not started yet
+        assert_eq!(burn_rate(dec("0"), date(3, 1), date(2, 20)), None);
+        assert_eq!(projected_total(dec("0"), date(3, 1), date(3, 30), date(2, 20)), None);
+        assert_eq!(projected_total(dec("10"), date(3, 30), date(3, 1), date(3, 10)), None);
+    }
+
+    #[test]
+    fn test_summary_with_and_without_budget() {
+        let summary = spend(Some("2500"), "900", "100").summarize(date(3, 10));
+        assert_eq!(summary.consumed, dec("1000"));
+        assert_eq!(summary.remaining, Some(dec("1500")));
+        assert_eq!(summary.percent_consumed, Some(dec("40")));
+        assert_eq!(summary.projected_total, Some(dec("3000")));
+        assert_eq!(summary.projected_overrun, Some(dec("500")));
+
+        let on_track = spend(Some("5000"), "900", "100").summarize(date(3, 10));
+        assert_eq!(on_track.projected_overrun, Some(Decimal::ZERO));
+
+        let unbudgeted = spend(None, "900", "100").summarize(date(3, 10));
+        assert_eq!(unbudgeted.consumed, dec("1000"));
+        assert_eq!(unbudgeted.remaining, None);
+        assert_eq!(unbudgeted.percent_consumed, None);
+        assert_eq!(unbudgeted.projected_total, Some(dec("3000")));
+        assert_eq!(unbudgeted.projected_overrun, None);
+    }
+
+    #[test]
+    fn test_summary_starts_from_first_activity_without_start_date() {
+        let mut undated = spend(Some("2500"), "1000", "0");
+        undated.start_date = None;
+        undated.end_date = None;
+        assert_eq!(undated.summarize(date(3, 10)).burn_rate_per_day, None);
+
+        undated.first_activity = Some(date(3, 6));
+        let summary = undated.summarize(date(3, 10));
+        assert_eq!(summary.burn_rate_per_day, Some(dec("200")));
+        assert_eq!(summary.projected_total, None);
+    }
+
+    #[test]
+    fn test_crossed_threshold_is_the_highest_reached() {
+        let thresholds = [80, 100];
+        assert_eq!(crossed_threshold(dec("79.99"), &thresholds), None);
+        assert_eq!(crossed_threshold(dec("80"), &thresholds), Some(80));
+        assert_eq!(crossed_threshold(dec("99.5"), &thresholds), Some(80));
+        assert_eq!(crossed_threshold(dec("140"), &thresholds), Some(100));
+        assert_eq!(crossed_threshold(dec("140"), &[]), None);
+    }
+
+    #[test]
+    fn test_severity() {
+        assert_eq!(severity(80), "medium");
+        assert_eq!(severity(100), "high");
+        assert_eq!(severity(120), "high");
+    }
+}
@@ -0,0 +1,121 @@
+//! Public address checks for outbound connections
+//!
+//! Webhooks and live certificate checks connect to hosts users type in, so
+//! without a check they could be pointed at the server's own network:
+//! loopback, private ranges, link-local (cloud metadata) and the like. A
+//! host is only connected to when every address it resolves to is public.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AddressError {
+    #[error("Invalid URL")]
+    InvalidUrl,
+    #[error("Could not resolve {0}")]
+    Unresolved(String),
+    #[error("{0} resolves to a private or reserved address")]
+    NotPublic(String),
+}
+
+/// Whether `ip` is reachable on the public internet: not loopback, private,
+/// link-local, shared (CGNAT), documentation, multicast or unspecified
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(v6),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    let shared = a == 100 && (64..128).contains(&b);
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || shared
+        || a == 0
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let [first, second, ..] = ip.segments();
+    let unique_local = first & 0xfe00 == 0xfc00;
+    let link_local = first & 0xffc0 == 0xfe80;
+    let documentation = first == 0x2001 && second == 0x0db8;
+    !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() || unique_local || link_local || documentation)
+}
+
+/// Resolve `host` and return its addresses, failing if any is not public
+pub async fn resolve_public(host: &str, port: u16) -> Result<Vec<SocketAddr>, AddressError> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| AddressError::Unresolved(host.to_string()))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(AddressError::Unresolved(host.to_string()));
+    }
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err(AddressError::NotPublic(host.to_string()));
+    }
+    Ok(addrs)
+}
+
+/// Check that an http(s) URL's host resolves only to public addresses
+pub async fn check_public_url(url: &str) -> Result<(), AddressError> {
+    let parsed = url::Url::parse(url).map_err(|_| AddressError::InvalidUrl)?;
+    let host = parsed.host_str().ok_or(AddressError::InvalidUrl)?;
+    let port = parsed.port_or_known_default().ok_or(AddressError::InvalidUrl)?;
+    resolve_public(host, port).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!public(ip), "{} should not be public", ip);
+        }
+    }
+
+    #[test]
+    fn test_internet_addresses_are_public() {
+        for ip in ["1.1.1.1", "93.184.216.34", "100.128.0.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(public(ip), "{} should be public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_literal_and_localhost_targets_are_rejected() {
+        assert!(matches!(check_public_url("http://127.0.0.1:8080/hook").await, Err(AddressError::NotPublic(_))));
+        assert!(matches!(check_public_url("https://[::1]/hook").await, Err(AddressError::NotPublic(_))));
+        assert!(matches!(check_public_url("not a url").await, Err(AddressError::InvalidUrl)));
+    }
+}
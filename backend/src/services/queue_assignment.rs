@@ -0,0 +1,249 @@
+//! Queue auto-assignment
+//!
+//! Picks an assignee for tickets that land in a queue with `auto_assign`.
+//! Round-robin queues walk their members in join order from the queue's
+//! `last_assigned_user_id` cursor; other queues go to the member with the
+//! fewest open tickets. Only active members with `can_assign` are considered.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::notifications::notify_ticket_update;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssignmentStrategy {
+    RoundRobin,
+    LeastLoaded,
+}
+
+impl AssignmentStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AssignmentStrategy::RoundRobin => "round_robin",
+            AssignmentStrategy::LeastLoaded => "least_loaded",
+        }
+    }
+}
+
+/// A queue member eligible for assignment, in queue join order
+#[derive(Debug, Clone, FromRow)]
+pub struct AssignableMember {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub open_tickets: i64,
+    /// Most recent automatic assignment from this queue
+    pub last_assigned_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueAssignment {
+    pub ticket_id: Uuid,
+    pub queue_id: Uuid,
+    pub user_id: Uuid,
+    pub strategy: AssignmentStrategy,
+}
+
+#[derive(Debug, FromRow)]
+struct QueueSettings {
+    name: String,
+    auto_assign: bool,
+    round_robin: bool,
+    last_assigned_user_id: Option<Uuid>,
+}
+
+/// The member after `last` in join order, wrapping around. Starts from the
+/// first member when there is no cursor or the cursor has left the queue.
+pub fn next_round_robin(members: &[AssignableMember], last: Option<Uuid>) -> Option<&AssignableMember> {
+    let next_index = last
+        .and_then(|last| members.iter().position(|m| m.user_id == last))
+        .map(|i| (i + 1) % members.len())
+        .unwrap_or(0);
+    members.get(next_index)
+}
+
+/// The member with the fewest open tickets. Ties go to whoever has gone
+/// longest without an automatic assignment, then to join order.
+pub fn least_loaded(members: &[AssignableMember]) -> Option<&AssignableMember> {
+    members
+        .iter()
+        .enumerate()
+        .min_by_key(|(index, m)| (m.open_tickets, m.last_assigned_at, *index))
+        .map(|(_, m)| m)
+}
+
+/// Auto-assign `ticket_id` from `queue_id` if the queue has `auto_assign` and
+/// the ticket is still unassigned. Returns the assignment that was made.
+pub async fn auto_assign_ticket(
+    pool: &PgPool,
+    ticket_id: Uuid,
+    queue_id: Uuid,
+) -> Result<Option<QueueAssignment>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // Lock the queue so concurrent assignments advance the cursor one at a time
+    let queue = sqlx::query_as::<_, QueueSettings>(
+        r#"
+        SELECT name, COALESCE(auto_assign, false) as auto_assign,
+               COALESCE(round_robin, false) as round_robin, last_assigned_user_id
+        FROM ticket_queues
+        WHERE id = $1 AND COALESCE(is_active, true)
+        FOR UPDATE
+        "#
+    )
+    .bind(queue_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(queue) = queue.filter(|q| q.auto_assign) else {
+        return Ok(None);
+    };
+
+    let ticket = sqlx::query_as::<_, (Uuid, Option<Uuid>)>(
+        "SELECT client_id, assigned_to FROM tickets WHERE id = $1 FOR UPDATE"
+    )
+    .bind(ticket_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((client_id, None)) = ticket else {
+        return Ok(None);
+    };
+
+    let members = sqlx::query_as::<_, AssignableMember>(
+        r#"
+        SELECT
+            m.user_id,
+            u.first_name || ' ' || u.last_name as user_name,
+            (SELECT COUNT(*) FROM tickets t
+             WHERE t.assigned_to = m.user_id
+               AND t.status NOT IN ('resolved', 'closed', 'cancelled')) as open_tickets,
+            (SELECT MAX(a.assigned_at) FROM ticket_queue_assignments a
+             WHERE a.queue_id = m.queue_id AND a.user_id = m.user_id) as last_assigned_at
+        FROM ticket_queue_members m
+        JOIN users u ON m.user_id = u.id
+        WHERE m.queue_id = $1
+            AND COALESCE(m.can_assign, true)
+            AND COALESCE(m.is_active, true)
+            AND COALESCE(u.is_active, true)
+        ORDER BY m.created_at, m.id
+        "#
+    )
+    .bind(queue_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let (strategy, member) = if queue.round_robin {
+        (AssignmentStrategy::RoundRobin, next_round_robin(&members, queue.last_assigned_user_id))
+    } else {
+        (AssignmentStrategy::LeastLoaded, least_loaded(&members))
+    };
+
+    let Some(member) = member else {
+        warn!("Queue '{}' has auto-assign enabled but no assignable members", queue.name);
+        return Ok(None);
+    };
+
+    sqlx::query("UPDATE tickets SET assigned_to = $2, updated_at = NOW() WHERE id = $1")
+        .bind(ticket_id)
+        .bind(member.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE ticket_queues SET last_assigned_user_id = $2, updated_at = NOW() WHERE id = $1")
+        .bind(queue_id)
+        .bind(member.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO ticket_queue_assignments (ticket_id, queue_id, user_id, strategy, open_tickets_at_assignment)
+        VALUES ($1, $2, $3, $4, $5)
+        "#
+    )
+    .bind(ticket_id)
+    .bind(queue_id)
+    .bind(member.user_id)
+    .bind(strategy.as_str())
+    .bind(member.open_tickets)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    notify_ticket_update(
+        pool,
+        ticket_id,
+        client_id,
+        "Assigned",
+        &format!("Automatically assigned to {} from the {} queue.", member.user_name, queue.name),
+    )
+    .await?;
+
+    Ok(Some(QueueAssignment {
+        ticket_id,
+        queue_id,
+        user_id: member.user_id,
+        strategy,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn member(open_tickets: i64, last_assigned_hour: Option<u32>) -> AssignableMember {
+        AssignableMember {
+            user_id: Uuid::new_v4(),
+            user_name: "Tech".to_string(),
+            open_tickets,
+            last_assigned_at: last_assigned_hour.map(|h| Utc.with_ymd_and_hms(2024, 1, 15, h, 0, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_round_robin_cursor_advances_and_wraps() {
+        let members = vec![member(0, None), member(0, None), member(0, None)];
+        let ids: Vec<Uuid> = members.iter().map(|m| m.user_id).collect();
+
+        let mut cursor = None;
+        let mut picked = Vec::new();
+        for _ in 0..4 {
+            let next = next_round_robin(&members, cursor).unwrap().user_id;
+            picked.push(next);
+            cursor = Some(next);
+        }
+
+        assert_eq!(picked, vec![ids[0], ids[1], ids[2], ids[0]]);
+    }
+
+    #[test]
+    fn test_round_robin_restarts_when_cursor_left_queue() {
+        let members = vec![member(0, None), member(0, None)];
+        let departed = Uuid::new_v4();
+
+        assert_eq!(next_round_robin(&members, Some(departed)).unwrap().user_id, members[0].user_id);
+        assert!(next_round_robin(&[], None).is_none());
+    }
+
+    #[test]
+    fn test_least_loaded_picks_fewest_open_tickets() {
+        let members = vec![member(5, None), member(2, Some(9)), member(3, None)];
+        assert_eq!(least_loaded(&members).unwrap().user_id, members[1].user_id);
+    }
+
+    #[test]
+    fn test_least_loaded_tie_breaking() {
+        // Equal load: never-assigned beats assigned, then oldest assignment, then join order
+        let members = vec![member(2, Some(10)), member(2, Some(8)), member(2, None), member(2, None)];
+        assert_eq!(least_loaded(&members).unwrap().user_id, members[2].user_id);
+
+        let members = vec![member(2, Some(10)), member(2, Some(8))];
+        assert_eq!(least_loaded(&members).unwrap().user_id, members[1].user_id);
+    }
+}
@@ -0,0 +1,270 @@
+//! Report export
+//!
+//! Flattens serializable report rows into a header + string-cell table and
+//! renders that table as CSV, XLSX or PDF. Columns follow struct field order
+//! (serde_json is built with `preserve_order`).
+
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfLayerReference};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+
+// Landscape A4, so wide reports keep their columns readable
+const PDF_PAGE_WIDTH: f32 = 297.0;
+const PDF_PAGE_HEIGHT: f32 = 210.0;
+const PDF_MARGIN: f32 = 12.0;
+const PDF_LINE_HEIGHT: f32 = 5.0;
+const PDF_FONT_SIZE: f32 = 7.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportExportError {
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Report rows must serialize to objects")]
+    NotAnObject,
+    #[error("XLSX error: {0}")]
+    Xlsx(#[from] XlsxError),
+    #[error("PDF error: {0}")]
+    Pdf(String),
+}
+
+/// Output format requested via `?format=`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+    Xlsx,
+    Pdf,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ExportFormat::Pdf => "application/pdf",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// Rows flattened to strings, ready for CSV/XLSX output
+#[derive(Debug, Clone, Default)]
+pub struct ReportTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Flatten a slice of structs into a table. Headers are taken from the
+/// serialized field names; nested values are written as JSON.
+pub fn flatten_rows<T: Serialize>(rows: &[T]) -> Result<ReportTable, ReportExportError> {
+    let mut table = ReportTable::default();
+
+    for row in rows {
+        let JsonValue::Object(fields) = serde_json::to_value(row)? else {
+            return Err(ReportExportError::NotAnObject);
+        };
+
+        if table.headers.is_empty() {
+            table.headers = fields.keys().cloned().collect();
+        }
+        table.rows.push(fields.into_iter().map(|(_, value)| cell_text(value)).collect());
+    }
+
+    Ok(table)
+}
+
+fn cell_text(value: JsonValue) -> String {
+    match value {
+        JsonValue::Null => String::new(),
+        JsonValue::String(s) => s,
+        JsonValue::Bool(b) => b.to_string(),
+        JsonValue::Number(n) => n.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Render a table as RFC 4180 CSV. An empty report renders as an empty body.
+pub fn to_csv(table: &ReportTable) -> String {
+    let mut out = String::new();
+    if table.headers.is_empty() {
+        return out;
+    }
+    write_csv_record(&mut out, &table.headers);
+    for row in &table.rows {
+        write_csv_record(&mut out, row);
+    }
+    out
+}
+
+/// Append one CRLF-terminated CSV record, quoting fields as needed
+pub fn write_csv_record(out: &mut String, fields: &[String]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Render a table as a single-sheet XLSX workbook. Numeric cells are written
+/// as numbers so they can be summed in the spreadsheet.
+pub fn to_xlsx(table: &ReportTable, sheet_name: &str) -> Result<Vec<u8>, ReportExportError> {
+    let mut workbook = Workbook::new();
+    let header_format = Format::new().set_bold();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet_name)?;
+
+    for (col, header) in table.headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, header, &header_format)?;
+    }
+
+    for (row_idx, row) in table.rows.iter().enumerate() {
+        let row_num = row_idx as u32 + 1;
+        for (col, cell) in row.iter().enumerate() {
+            match cell.parse::<f64>() {
+                Ok(number) if number.is_finite() => {
+                    worksheet.write_number(row_num, col as u16, number)?;
+                }
+                _ => {
+                    worksheet.write_string(row_num, col as u16, cell)?;
+                }
+            }
+        }
+    }
+
+    worksheet.set_freeze_panes(1, 0)?;
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// Render a table as a PDF with `title` above it. Columns share the page
+/// width equally and cells too long for their column are cut short.
+pub fn to_pdf(table: &ReportTable, title: &str) -> Result<Vec<u8>, ReportExportError> {
+    let (doc, page, layer) = PdfDocument::new(title, Mm(PDF_PAGE_WIDTH), Mm(PDF_PAGE_HEIGHT), "Layer 1");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(pdf_error)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(pdf_error)?;
+
+    let columns = table.headers.len().max(1);
+    let column_width = (PDF_PAGE_WIDTH - 2.0 * PDF_MARGIN) / columns as f32;
+    // Helvetica averages about half an em per glyph
+    let max_chars = ((column_width / (PDF_FONT_SIZE * 0.5 * 0.3528)) as usize).max(4) - 1;
+
+    let write_row = |layer: &PdfLayerReference, cells: &[String], y: f32, font: &IndirectFontRef| {
+        for (i, cell) in cells.iter().enumerate() {
+            let x = PDF_MARGIN + i as f32 * column_width;
+            layer.use_text(truncate_cell(cell, max_chars), PDF_FONT_SIZE, Mm(x), Mm(y), font);
+        }
+    };
+
+    let mut layer = doc.get_page(page).get_layer(layer);
+    let mut y = PDF_PAGE_HEIGHT - PDF_MARGIN;
+    layer.use_text(title, 14.0, Mm(PDF_MARGIN), Mm(y), &bold);
+    y -= PDF_LINE_HEIGHT * 2.0;
+
+    if table.headers.is_empty() {
+        layer.use_text("No data for this period", 10.0, Mm(PDF_MARGIN), Mm(y), &regular);
+    } else {
+        write_row(&layer, &table.headers, y, &bold);
+        y -= PDF_LINE_HEIGHT;
+        for row in &table.rows {
+            if y < PDF_MARGIN {
+                let (page, new_layer) = doc.add_page(Mm(PDF_PAGE_WIDTH), Mm(PDF_PAGE_HEIGHT), "Layer 1");
+                layer = doc.get_page(page).get_layer(new_layer);
+                y = PDF_PAGE_HEIGHT - PDF_MARGIN;
+                write_row(&layer, &table.headers, y, &bold);
+                y -= PDF_LINE_HEIGHT;
+            }
+            write_row(&layer, row, y, &regular);
+            y -= PDF_LINE_HEIGHT;
+        }
+    }
+
+    doc.save_to_bytes().map_err(pdf_error)
+}
+
+fn pdf_error(e: impl std::fmt::Display) -> ReportExportError {
+    ReportExportError::Pdf(e.to_string())
+}
+
+fn truncate_cell(cell: &str, max_chars: usize) -> String {
+    if cell.chars().count() <= max_chars {
+        return cell.to_string();
+    }
+    let mut cut: String = cell.chars().take(max_chars.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Row {
+        name: String,
+        hours: f64,
+        note: Option<String>,
+    }
+
+    fn rows() -> Vec<Row> {
+        vec![
+            Row { name: "Smith, Jane".to_string(), hours: 12.5, note: None },
+            Row { name: "Bob \"The Tech\"".to_string(), hours: 3.0, note: Some("ok".to_string()) },
+        ]
+    }
+
+    #[test]
+    fn test_flatten_keeps_field_order() {
+        let table = flatten_rows(&rows()).unwrap();
+        assert_eq!(table.headers, vec!["name", "hours", "note"]);
+        assert_eq!(table.rows[0], vec!["Smith, Jane", "12.5", ""]);
+    }
+
+    #[test]
+    fn test_csv_quotes_special_characters() {
+        let csv = to_csv(&flatten_rows(&rows()).unwrap());
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+
+        assert_eq!(lines[0], "name,hours,note");
+        assert_eq!(lines[1], "\"Smith, Jane\",12.5,");
+        assert_eq!(lines[2], "\"Bob \"\"The Tech\"\"\",3.0,ok");
+    }
+
+    #[test]
+    fn test_empty_rows_produce_empty_table() {
+        let table = flatten_rows::<Row>(&[]).unwrap();
+        assert!(table.headers.is_empty());
+        assert!(to_csv(&table).is_empty());
+    }
+
+    #[test]
+    fn test_pdf_renders_a_document() {
+        let pdf = to_pdf(&flatten_rows(&rows()).unwrap(), "Utilization").unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_long_cells_are_truncated() {
+        assert_eq!(truncate_cell("short", 10), "short");
+        assert_eq!(truncate_cell("a much longer cell", 6), "a muc…");
+    }
+}
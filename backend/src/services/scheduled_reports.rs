@@ -0,0 +1,562 @@
+//! Scheduled reports
+//!
+//! A scheduled report names one of the analytics reports, a cadence, an
+//! output format and who receives it. When a report falls due it is
+//! rendered for the period its cadence covers (yesterday, last week or last
+//! month), stored on a `scheduled_report_runs` row so it can be downloaded
+//! again, and emailed to each recipient as an attachment.
+//!
+//! `client_summary` reports are client-facing: they cover a single client
+//! and go to that client's primary contact when no recipients are listed.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc, Weekday};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::handlers::analytics::{self, TabularReport, DEFAULT_TREND_THRESHOLD};
+use crate::services::email::{EmailAttachment, EmailService};
+use crate::services::report_export::{self, ExportFormat, ReportExportError};
+
+/// Scheduled reports are sent at this hour (UTC) on the day they fall due
+pub const RUN_HOUR_UTC: u32 = 6;
+
+pub const REPORT_TYPES: &[&str] = &["utilization", "profitability", "sla_compliance", "client_summary"];
+pub const CADENCES: &[&str] = &["daily", "weekly", "monthly"];
+pub const FORMATS: &[&str] = &["csv", "xlsx", "pdf"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportType {
+    Utilization,
+    Profitability,
+    SlaCompliance,
+    ClientSummary,
+}
+
+impl ReportType {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "utilization" => Some(Self::Utilization),
+            "profitability" => Some(Self::Profitability),
+            "sla_compliance" => Some(Self::SlaCompliance),
+            "client_summary" => Some(Self::ClientSummary),
+            _ => None,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Utilization => "Technician Utilization",
+            Self::Profitability => "Client Profitability",
+            Self::SlaCompliance => "SLA Compliance",
+            Self::ClientSummary => "Monthly Service Summary",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cadence {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Cadence {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "daily" => Some(Self::Daily),
+            "weekly" => Some(Self::Weekly),
+            "monthly" => Some(Self::Monthly),
+            _ => None,
+        }
+    }
+
+    /// The completed period a run on `today` reports on: yesterday, the
+    /// previous Monday-Sunday week, or the previous calendar month
+    pub fn period(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            Self::Daily => {
+                let yesterday = today - Duration::days(1);
+                (yesterday, yesterday)
+            }
+            Self::Weekly => {
+                let this_monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                (this_monday - Duration::days(7), this_monday - Duration::days(1))
+            }
+            Self::Monthly => {
+                let first_of_month = today.with_day(1).unwrap_or(today);
+                let last_of_previous = first_of_month - Duration::days(1);
+                (last_of_previous.with_day(1).unwrap_or(last_of_previous), last_of_previous)
+            }
+        }
+    }
+
+    fn runs_on(&self, date: NaiveDate) -> bool {
+        match self {
+            Self::Daily => true,
+            Self::Weekly => date.weekday() == Weekday::Mon,
+            Self::Monthly => date.day() == 1,
+        }
+    }
+
+    /// The first scheduled send time strictly after `after`
+    pub fn next_run_after(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        let mut date = after.date_naive();
+        loop {
+            if self.runs_on(date) {
+                let at = Utc.from_utc_datetime(&date.and_hms_opt(RUN_HOUR_UTC, 0, 0).unwrap_or_default());
+                if at > after {
+                    return at;
+                }
+            }
+            date += Duration::days(1);
+        }
+    }
+}
+
+fn parse_format(value: &str) -> Option<ExportFormat> {
+    match value {
+        "csv" => Some(ExportFormat::Csv),
+        "xlsx" => Some(ExportFormat::Xlsx),
+        "pdf" => Some(ExportFormat::Pdf),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScheduledReport {
+    pub id: Uuid,
+    pub name: String,
+    pub report_type: String,
+    pub client_id: Option<Uuid>,
+    pub parameters: serde_json::Value,
+    pub cadence: String,
+    pub recipients: Vec<String>,
+    pub format: String,
+    pub is_active: bool,
+    pub next_run_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+pub const SCHEDULED_REPORT_COLUMNS: &str =
+    "id, name, report_type, client_id, parameters, cadence, recipients, format, is_active,
+     next_run_at, last_run_at, created_by, created_at, updated_at";
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScheduledReportRun {
+    pub id: Uuid,
+    pub scheduled_report_id: Uuid,
+    pub status: String,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub filename: Option<String>,
+    pub recipients_sent: i32,
+    pub error: Option<String>,
+    pub download_url: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+pub const RUN_COLUMNS: &str =
+    "id, scheduled_report_id, status, period_start, period_end, filename, recipients_sent, error,
+     CASE WHEN content IS NOT NULL
+          THEN '/api/v1/reporting/scheduled-reports/runs/' || id || '/download' END AS download_url,
+     started_at, completed_at";
+
+/// Optional settings stored in `parameters`
+#[derive(Debug, Default, Deserialize)]
+struct ReportParameters {
+    trend_threshold: Option<Decimal>,
+}
+
+/// A rendered report ready to attach or download
+#[derive(Debug, Clone)]
+pub struct RenderedReport {
+    pub title: String,
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// Sends rendered reports; implemented by [`EmailService`] and by mocks in tests
+#[async_trait]
+pub trait ReportMailer: Send + Sync {
+    async fn send_report(
+        &self,
+        to_email: &str,
+        subject: &str,
+        html_body: &str,
+        text_body: &str,
+        attachment: &EmailAttachment,
+    ) -> Result<(), String>;
+}
+
+#[async_trait]
+impl ReportMailer for EmailService {
+    async fn send_report(
+        &self,
+        to_email: &str,
+        subject: &str,
+        html_body: &str,
+        text_body: &str,
+        attachment: &EmailAttachment,
+    ) -> Result<(), String> {
+        self.send_email_with_attachment(to_email, None, subject, html_body, text_body, attachment)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// The metrics a client sees in its summary, one per row
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryLine {
+    pub metric: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientSummary {
+    pub client_name: String,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub lines: Vec<SummaryLine>,
+}
+
+impl TabularReport for ClientSummary {
+    type Row = SummaryLine;
+    const NAME: &'static str = "client-summary";
+
+    fn rows(&self) -> &[SummaryLine] {
+        &self.lines
+    }
+
+    fn period(&self) -> (NaiveDate, NaiveDate) {
+        (self.period_start, self.period_end)
+    }
+}
+
+async fn client_summary(pool: &PgPool, client_id: Uuid, from: NaiveDate, to: NaiveDate) -> Result<ClientSummary, String> {
+    let profitability = analytics::compute_client_profitability(pool, client_id, from, to)
+        .await
+        .map_err(|e| e.message())?;
+    let sla = analytics::compute_sla_by_client(pool, from, to)
+        .await
+        .map_err(|e| e.message())?
+        .into_iter()
+        .find(|row| row.client_id == client_id);
+
+    let mut lines = vec![
+        SummaryLine { metric: "Tickets opened".to_string(), value: profitability.tickets_opened.to_string() },
+        SummaryLine { metric: "Tickets resolved".to_string(), value: profitability.tickets_resolved.to_string() },
+        SummaryLine {
+            metric: "Average resolution time (hours)".to_string(),
+            value: profitability.avg_resolution_time_hours.map(|h| h.round_dp(1).to_string()).unwrap_or_default(),
+        },
+        SummaryLine { metric: "Hours worked".to_string(), value: profitability.labor_hours.round_dp(2).to_string() },
+    ];
+    if let Some(sla) = sla {
+        lines.push(SummaryLine { metric: "Tickets within SLA".to_string(), value: sla.met_sla.to_string() });
+        lines.push(SummaryLine { metric: "SLA compliance (%)".to_string(), value: sla.compliance_rate.round_dp(1).to_string() });
+    }
+
+    Ok(ClientSummary { client_name: profitability.client_name, period_start: from, period_end: to, lines })
+}
+
+fn export<T: TabularReport>(report: &T, format: ExportFormat, title: &str) -> Result<Vec<u8>, ReportExportError> {
+    let table = report_export::flatten_rows(report.rows())?;
+    match format {
+        ExportFormat::Xlsx => report_export::to_xlsx(&table, T::NAME),
+        ExportFormat::Pdf => report_export::to_pdf(&table, title),
+        _ => Ok(report_export::to_csv(&table).into_bytes()),
+    }
+}
+
+/// Render `report` for `from..=to` in its configured format
+pub async fn render(pool: &PgPool, report: &ScheduledReport, from: NaiveDate, to: NaiveDate) -> Result<RenderedReport, String> {
+    let report_type = ReportType::parse(&report.report_type)
+        .ok_or_else(|| format!("Unknown report type '{}'", report.report_type))?;
+    let format = parse_format(&report.format).ok_or_else(|| format!("Unknown format '{}'", report.format))?;
+    let parameters: ReportParameters = serde_json::from_value(report.parameters.clone()).unwrap_or_default();
+
+    let mut title = format!("{} {} to {}", report_type.title(), from, to);
+    let export_error = |e: ReportExportError| e.to_string();
+    let (name, content) = match report_type {
+        ReportType::Utilization => {
+            let threshold = parameters.trend_threshold.unwrap_or(DEFAULT_TREND_THRESHOLD).abs();
+            let data = analytics::compute_utilization(pool, from, to, threshold).await.map_err(|e| e.message())?;
+            ("utilization", export(&data, format, &title).map_err(export_error)?)
+        }
+        ReportType::Profitability => {
+            let data = analytics::compute_profitability(pool, from, to).await.map_err(|e| e.message())?;
+            ("profitability", export(&data, format, &title).map_err(export_error)?)
+        }
+        ReportType::SlaCompliance => {
+            let data = analytics::compute_sla_compliance(pool, from, to).await.map_err(|e| e.message())?;
+            ("sla-compliance", export(&data, format, &title).map_err(export_error)?)
+        }
+        ReportType::ClientSummary => {
+            let client_id = report.client_id.ok_or("Client summary has no client")?;
+            let data = client_summary(pool, client_id, from, to).await?;
+            title = format!("{} - {}", data.client_name, title);
+            ("client-summary", export(&data, format, &title).map_err(export_error)?)
+        }
+    };
+
+    Ok(RenderedReport {
+        title,
+        filename: format!("{}-{}-to-{}.{}", name, from, to, format.extension()),
+        content_type: format.content_type().to_string(),
+        content,
+    })
+}
+
+/// The listed recipients, or the client's primary contact for client
+/// reports that list none
+async fn recipients(pool: &PgPool, report: &ScheduledReport) -> Result<Vec<String>, sqlx::Error> {
+    if !report.recipients.is_empty() {
+        return Ok(report.recipients.clone());
+    }
+    let Some(client_id) = report.client_id else {
+        return Ok(Vec::new());
+    };
+    sqlx::query_scalar::<_, String>(
+        "SELECT email FROM contacts
+         WHERE client_id = $1 AND email IS NOT NULL AND archived_at IS NULL
+         ORDER BY is_primary DESC NULLS LAST, created_at
+         LIMIT 1"
+    )
+    .bind(client_id)
+    .fetch_all(pool)
+    .await
+}
+
+fn email_bodies(report: &RenderedReport) -> (String, String) {
+    let html = format!(
+        "<p>Hello,</p><p>Please find attached the <strong>{}</strong> report.</p><p>This report was sent automatically on a schedule.</p>",
+        report.title
+    );
+    let text = format!(
+        "Hello,\n\nPlease find attached the {} report.\n\nThis report was sent automatically on a schedule.\n",
+        report.title
+    );
+    (html, text)
+}
+
+/// Render and send one scheduled report for the period ending before
+/// `today`, recording the run and moving `next_run_at` on. A report that
+/// fails to render or send is still rescheduled so it doesn't retry every
+/// tick, and a run that started is never left `running`.
+pub async fn run_report(
+    pool: &PgPool,
+    mailer: &dyn ReportMailer,
+    report: &ScheduledReport,
+    now: DateTime<Utc>,
+) -> Result<ScheduledReportRun, sqlx::Error> {
+    let (from, to) = Cadence::parse(&report.cadence).unwrap_or(Cadence::Monthly).period(now.date_naive());
+
+    let run_id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO scheduled_report_runs (scheduled_report_id, status, period_start, period_end, started_at)
+         VALUES ($1, 'running', $2, $3, $4)
+         RETURNING id"
+    )
+    .bind(report.id)
+    .bind(from)
+    .bind(to)
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    let run = send_run(pool, mailer, report, run_id, from, to).await;
+    if let Err(e) = &run {
+        mark_run_failed(pool, run_id, &e.to_string()).await;
+    }
+
+    let next_run_at = Cadence::parse(&report.cadence).unwrap_or(Cadence::Monthly).next_run_after(now);
+    sqlx::query("UPDATE scheduled_reports SET last_run_at = $2, next_run_at = $3 WHERE id = $1")
+        .bind(report.id)
+        .bind(now)
+        .bind(next_run_at)
+        .execute(pool)
+        .await?;
+
+    run
+}
+
+async fn send_run(
+    pool: &PgPool,
+    mailer: &dyn ReportMailer,
+    report: &ScheduledReport,
+    run_id: Uuid,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<ScheduledReportRun, sqlx::Error> {
+    let mut sent = 0;
+    let mut errors = Vec::new();
+    let rendered = match render(pool, report, from, to).await {
+        Ok(rendered) => Some(rendered),
+        Err(e) => {
+            errors.push(format!("Rendering failed: {}", e));
+            None
+        }
+    };
+
+    if let Some(rendered) = &rendered {
+        let to_list = match recipients(pool, report).await {
+            Ok(to_list) if to_list.is_empty() => {
+                errors.push("No recipients".to_string());
+                to_list
+            }
+            Ok(to_list) => to_list,
+            Err(e) => {
+                errors.push(format!("Loading recipients failed: {}", e));
+                Vec::new()
+            }
+        };
+        let (html, text) = email_bodies(rendered);
+        let attachment = EmailAttachment {
+            filename: rendered.filename.clone(),
+            content_type: rendered.content_type.clone(),
+            content: rendered.content.clone(),
+        };
+        let subject = format!("{}: {}", report.name, rendered.title);
+        for recipient in &to_list {
+            match mailer.send_report(recipient, &subject, &html, &text, &attachment).await {
+                Ok(()) => sent += 1,
+                Err(e) => errors.push(format!("{}: {}", recipient, e)),
+            }
+        }
+    }
+
+    let status = if errors.is_empty() { "completed" } else { "failed" };
+    let error = (!errors.is_empty()).then(|| errors.join("; "));
+    sqlx::query_as::<_, ScheduledReportRun>(&format!(
+        "UPDATE scheduled_report_runs
+         SET status = $2, filename = $3, content_type = $4, content = $5,
+             recipients_sent = $6, error = $7, completed_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        RUN_COLUMNS
+    ))
+    .bind(run_id)
+    .bind(status)
+    .bind(rendered.as_ref().map(|r| r.filename.clone()))
+    .bind(rendered.as_ref().map(|r| r.content_type.clone()))
+    .bind(rendered.map(|r| r.content))
+    .bind(sent)
+    .bind(error)
+    .fetch_one(pool)
+    .await
+}
+
+/// Best effort: the error that stopped a run is usually the database's too
+async fn mark_run_failed(pool: &PgPool, run_id: Uuid, error: &str) {
+    let result = sqlx::query(
+        "UPDATE scheduled_report_runs SET status = 'failed', error = $2, completed_at = NOW()
+         WHERE id = $1 AND status = 'running'"
+    )
+    .bind(run_id)
+    .bind(error)
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        tracing::error!("Error marking scheduled report run {} failed: {}", run_id, e);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DueRunSummary {
+    pub due: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+/// Run every active scheduled report whose `next_run_at` has passed
+pub async fn run_due(pool: &PgPool, mailer: &dyn ReportMailer, now: DateTime<Utc>) -> Result<DueRunSummary, sqlx::Error> {
+    let due = sqlx::query_as::<_, ScheduledReport>(&format!(
+        "SELECT {} FROM scheduled_reports WHERE is_active AND next_run_at <= $1 ORDER BY next_run_at",
+        SCHEDULED_REPORT_COLUMNS
+    ))
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    let mut summary = DueRunSummary { due: due.len(), ..Default::default() };
+    for report in &due {
+        match run_report(pool, mailer, report, now).await {
+            Ok(run) if run.status == "completed" => summary.completed += 1,
+            Ok(run) => {
+                tracing::warn!("Scheduled report {} failed: {}", report.name, run.error.as_deref().unwrap_or_default());
+                summary.failed += 1;
+            }
+            // One report's database error doesn't hold up the rest
+            Err(e) => {
+                tracing::error!("Error running scheduled report {}: {}", report.name, e);
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Stored output of a run, for download
+pub async fn run_content(pool: &PgPool, run_id: Uuid) -> Result<Option<(String, String, Vec<u8>)>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (Option<String>, Option<String>, Option<Vec<u8>>)>(
+        "SELECT filename, content_type, content FROM scheduled_report_runs WHERE id = $1"
+    )
+    .bind(run_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(match row {
+        Some((Some(filename), Some(content_type), Some(content))) => Some((filename, content_type, content)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&date(y, m, d).and_hms_opt(h, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn test_periods_cover_the_previous_completed_span() {
+        // 2024-03-13 is a Wednesday
+        let today = date(2024, 3, 13);
+        assert_eq!(Cadence::Daily.period(today), (date(2024, 3, 12), date(2024, 3, 12)));
+        assert_eq!(Cadence::Weekly.period(today), (date(2024, 3, 4), date(2024, 3, 10)));
+        assert_eq!(Cadence::Monthly.period(today), (date(2024, 2, 1), date(2024, 2, 29)));
+        assert_eq!(Cadence::Monthly.period(date(2024, 1, 1)), (date(2023, 12, 1), date(2023, 12, 31)));
+    }
+
+    #[test]
+    fn test_next_run_is_strictly_after() {
+        assert_eq!(Cadence::Daily.next_run_after(at(2024, 3, 13, 5)), at(2024, 3, 13, RUN_HOUR_UTC));
+        assert_eq!(Cadence::Daily.next_run_after(at(2024, 3, 13, RUN_HOUR_UTC)), at(2024, 3, 14, RUN_HOUR_UTC));
+        assert_eq!(Cadence::Weekly.next_run_after(at(2024, 3, 13, 12)), at(2024, 3, 18, RUN_HOUR_UTC));
+        assert_eq!(Cadence::Monthly.next_run_after(at(2024, 3, 1, 12)), at(2024, 4, 1, RUN_HOUR_UTC));
+        assert_eq!(Cadence::Monthly.next_run_after(at(2024, 12, 15, 0)), at(2025, 1, 1, RUN_HOUR_UTC));
+    }
+
+    #[test]
+    fn test_parsing_rejects_unknown_values() {
+        assert_eq!(ReportType::parse("client_summary"), Some(ReportType::ClientSummary));
+        assert_eq!(ReportType::parse("invoices"), None);
+        assert_eq!(Cadence::parse("hourly"), None);
+        assert_eq!(parse_format("json"), None);
+        assert!(REPORT_TYPES.iter().all(|t| ReportType::parse(t).is_some()));
+        assert!(CADENCES.iter().all(|c| Cadence::parse(c).is_some()));
+        assert!(FORMATS.iter().all(|f| parse_format(f).is_some()));
+    }
+}
@@ -0,0 +1,449 @@
+//! Online invoice payments through Stripe
+//!
+//! Creates PaymentIntents for an invoice's outstanding balance using the
+//! enabled `stripe` integration, and applies `payment_intent.succeeded`
+//! webhook events to the invoice. Events are recorded by Stripe event id so
+//! redelivered webhooks are only applied once.
+
+use hmac::{Hmac, Mac};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::integrations::decrypt_json;
+use crate::integrations::stripe::StripeCredentials;
+use crate::services::{invoice_payments, outbound_webhooks};
+
+const STRIPE_API_BASE: &str = "https://api.stripe.com";
+/// Maximum age of a webhook signature timestamp, as recommended by Stripe
+pub const SIGNATURE_TOLERANCE_SECONDS: i64 = 300;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StripeError {
+    #[error("Stripe integration is not configured")]
+    NotConfigured,
+    #[error("Invalid Stripe credentials: {0}")]
+    InvalidCredentials(String),
+    #[error("Stripe request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Stripe API error: {0}")]
+    Api(String),
+    #[error("Amount cannot be charged: {0}")]
+    InvalidAmount(Decimal),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum SignatureError {
+    #[error("Malformed Stripe-Signature header")]
+    Malformed,
+    #[error("Signature timestamp outside tolerance")]
+    Expired,
+    #[error("No matching signature")]
+    Mismatch,
+}
+
+/// Stripe integration settings: decrypted credentials plus the currency
+/// from the integration config
+#[derive(Debug, Clone)]
+pub struct StripeSettings {
+    pub credentials: StripeCredentials,
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PaymentIntent {
+    pub id: String,
+    pub client_secret: Option<String>,
+    pub amount: i64,
+    #[serde(default)]
+    pub amount_received: i64,
+    pub currency: String,
+    pub status: String,
+    #[serde(default)]
+    pub metadata: std::collections::HashMap<String, String>,
+}
+
+impl PaymentIntent {
+    pub fn invoice_id(&self) -> Option<Uuid> {
+        self.metadata.get("invoice_id").and_then(|id| Uuid::parse_str(id).ok())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StripeEvent {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub data: StripeEventData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StripeEventData {
+    pub object: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookOutcome {
+    /// Payment recorded against the invoice
+    Applied,
+    /// Event id seen before
+    Duplicate,
+    /// Event type we don't act on
+    Ignored,
+    /// Recorded but couldn't be applied, e.g. the invoice was already paid
+    NeedsReview,
+}
+
+impl WebhookOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookOutcome::Applied => "applied",
+            WebhookOutcome::Duplicate => "duplicate",
+            WebhookOutcome::Ignored => "ignored",
+            WebhookOutcome::NeedsReview => "needs_review",
+        }
+    }
+}
+
+/// Convert a two-decimal amount to Stripe's minor units (cents)
+pub fn to_minor_units(amount: Decimal) -> Option<i64> {
+    (amount * Decimal::ONE_HUNDRED).round().to_i64().filter(|minor| *minor > 0)
+}
+
+pub fn from_minor_units(amount: i64) -> Decimal {
+    Decimal::new(amount, 2)
+}
+
+/// Check a `Stripe-Signature` header (`t=<unix>,v1=<hex hmac>,...`) against
+/// the raw request body
+pub fn verify_signature(payload: &[u8], header: &str, secret: &str, now: i64) -> Result<(), SignatureError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", t)) => timestamp = t.parse::<i64>().ok(),
+            Some(("v1", sig)) => signatures.push(sig),
+            _ => {}
+        }
+    }
+
+    let timestamp = timestamp.ok_or(SignatureError::Malformed)?;
+    if signatures.is_empty() {
+        return Err(SignatureError::Malformed);
+    }
+    if (now - timestamp).abs() > SIGNATURE_TOLERANCE_SECONDS {
+        return Err(SignatureError::Expired);
+    }
+
+    let matches = signatures.iter().any(|sig| {
+        let Ok(expected) = hex::decode(sig) else {
+            return false;
+        };
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(payload);
+        mac.verify_slice(&expected).is_ok()
+    });
+
+    if matches {
+        Ok(())
+    } else {
+        Err(SignatureError::Mismatch)
+    }
+}
+
+/// Settings from the first enabled `stripe` integration
+pub async fn load_settings(pool: &PgPool) -> Result<StripeSettings, StripeError> {
+    let (config, credentials) = sqlx::query_as::<_, (Option<serde_json::Value>, Option<serde_json::Value>)>(
+        r#"
+        SELECT config, credentials FROM integrations
+        WHERE integration_type = 'stripe' AND COALESCE(enabled, true)
+        ORDER BY created_at
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(StripeError::NotConfigured)?;
+
+    let decrypted = decrypt_json(&credentials.unwrap_or_default())
+        .map_err(|e| StripeError::InvalidCredentials(e.to_string()))?;
+    let credentials: StripeCredentials =
+        serde_json::from_value(decrypted).map_err(|e| StripeError::InvalidCredentials(e.to_string()))?;
+
+    let currency = config
+        .as_ref()
+        .and_then(|c| c.get("currency"))
+        .and_then(|c| c.as_str())
+        .unwrap_or("usd")
+        .to_lowercase();
+
+    Ok(StripeSettings { credentials, currency })
+}
+
+/// Create a PaymentIntent for `amount`, tagged with the invoice so the
+/// webhook can find it again. The idempotency key ties retries for the same
+/// invoice and balance to a single intent.
+pub async fn create_payment_intent(
+    settings: &StripeSettings,
+    invoice_id: Uuid,
+    invoice_number: &str,
+    amount: Decimal,
+) -> Result<PaymentIntent, StripeError> {
+    create_payment_intent_at(STRIPE_API_BASE, settings, invoice_id, invoice_number, amount).await
+}
+
+async fn create_payment_intent_at(
+    api_base: &str,
+    settings: &StripeSettings,
+    invoice_id: Uuid,
+    invoice_number: &str,
+    amount: Decimal,
+) -> Result<PaymentIntent, StripeError> {
+    let minor = to_minor_units(amount).ok_or(StripeError::InvalidAmount(amount))?;
+
+    let params = [
+        ("amount", minor.to_string()),
+        ("currency", settings.currency.clone()),
+        ("automatic_payment_methods[enabled]", "true".to_string()),
+        ("description", format!("Invoice {}", invoice_number)),
+        ("metadata[invoice_id]", invoice_id.to_string()),
+        ("metadata[invoice_number]", invoice_number.to_string()),
+    ];
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/payment_intents", api_base))
+        .bearer_auth(&settings.credentials.secret_key)
+        .header("Idempotency-Key", format!("invoice-{}-{}", invoice_id, minor))
+        .form(&params)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        let message = body["error"]["message"].as_str().unwrap_or("unknown error").to_string();
+        return Err(StripeError::Api(message));
+    }
+
+    Ok(response.json::<PaymentIntent>().await?)
+}
+
+/// Apply a verified webhook event. Every event id is stored, so a
+/// redelivery returns `Duplicate` without touching the invoice.
+pub async fn handle_event(pool: &PgPool, event: &StripeEvent) -> Result<WebhookOutcome, StripeError> {
+    let intent = if event.event_type == "payment_intent.succeeded" {
+        serde_json::from_value::<PaymentIntent>(event.data.object.clone()).ok()
+    } else {
+        None
+    };
+    let invoice_id = intent.as_ref().and_then(PaymentIntent::invoice_id);
+
+    let mut tx = pool.begin().await?;
+
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO stripe_webhook_events (event_id, event_type, payment_intent_id, invoice_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (event_id) DO NOTHING
+        "#
+    )
+    .bind(&event.id)
+    .bind(&event.event_type)
+    .bind(intent.as_ref().map(|i| i.id.as_str()))
+    .bind(invoice_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if inserted == 0 {
+        return Ok(WebhookOutcome::Duplicate);
+    }
+
+    let outcome = match (intent, invoice_id) {
+        (Some(intent), Some(invoice_id)) => apply_intent(&mut tx, invoice_id, &intent).await?,
+        (Some(intent), None) => {
+            tracing::warn!("PaymentIntent {} has no invoice_id metadata", intent.id);
+            WebhookOutcome::NeedsReview
+        }
+        _ => WebhookOutcome::Ignored,
+    };
+
+    sqlx::query("UPDATE stripe_webhook_events SET outcome = $2 WHERE event_id = $1")
+        .bind(&event.id)
+        .bind(outcome.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    if let (WebhookOutcome::Applied, Some(invoice_id)) = (&outcome, invoice_id) {
+        outbound_webhooks::notify_invoice_paid(pool, invoice_id).await;
+    }
+
+    Ok(outcome)
+}
+
+async fn apply_intent(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    invoice_id: Uuid,
+    intent: &PaymentIntent,
+) -> Result<WebhookOutcome, StripeError> {
+    let invoice = sqlx::query_as::<_, (Decimal, Decimal, String)>(
+        "SELECT COALESCE(total, 0), COALESCE(balance, 0), COALESCE(status, 'draft')
+         FROM invoices WHERE id = $1 FOR UPDATE"
+    )
+    .bind(invoice_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let Some((total, balance, status)) = invoice else {
+        tracing::warn!("PaymentIntent {} references missing invoice {}", intent.id, invoice_id);
+        return Ok(WebhookOutcome::NeedsReview);
+    };
+
+    let received = if intent.amount_received > 0 { intent.amount_received } else { intent.amount };
+    let amount = from_minor_units(received);
+
+    let outcome = match invoice_payments::apply_payment(total, balance, &status, amount) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            // The money has been taken; leave it for someone to reconcile
+            tracing::error!("Stripe payment {} for invoice {} needs review: {}", intent.id, invoice_id, e);
+            return Ok(WebhookOutcome::NeedsReview);
+        }
+    };
+
+    sqlx::query(
+        "INSERT INTO payments (invoice_id, amount, payment_date, payment_method, reference_number, notes)
+         VALUES ($1, $2, CURRENT_DATE, 'stripe', $3, 'Paid online')"
+    )
+    .bind(invoice_id)
+    .bind(amount)
+    .bind(&intent.id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query("UPDATE invoices SET balance = $2, status = $3, updated_at = NOW() WHERE id = $1")
+        .bind(invoice_id)
+        .bind(outcome.balance)
+        .bind(&outcome.status)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(WebhookOutcome::Applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn sign(payload: &[u8], secret: &str, timestamp: i64) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(payload);
+        format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn settings() -> StripeSettings {
+        StripeSettings {
+            credentials: StripeCredentials {
+                secret_key: "sk_test_123".to_string(),
+                publishable_key: "pk_test_123".to_string(),
+                webhook_endpoint_secret: Some("whsec_test".to_string()),
+            },
+            currency: "usd".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_minor_units() {
+        assert_eq!(to_minor_units("125.50".parse().unwrap()), Some(12550));
+        assert_eq!(to_minor_units("0.005".parse().unwrap()), Some(1));
+        assert_eq!(to_minor_units(Decimal::ZERO), None);
+        assert_eq!(from_minor_units(12550), "125.50".parse::<Decimal>().unwrap());
+    }
+
+    #[test]
+    fn test_signature_verification() {
+        let payload = br#"{"id":"evt_1","type":"payment_intent.succeeded"}"#;
+        let now = 1_700_000_000;
+        let header = sign(payload, "whsec_test", now);
+
+        assert_eq!(verify_signature(payload, &header, "whsec_test", now + 10), Ok(()));
+        assert_eq!(
+            verify_signature(payload, &header, "whsec_other", now),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_signature(b"{\"tampered\":true}", &header, "whsec_test", now),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_signature(payload, &header, "whsec_test", now + SIGNATURE_TOLERANCE_SECONDS + 1),
+            Err(SignatureError::Expired)
+        );
+        assert_eq!(
+            verify_signature(payload, "v1=abc", "whsec_test", now),
+            Err(SignatureError::Malformed)
+        );
+
+        // Rolled secrets send several v1 signatures; any match is enough
+        let rolled = format!("{},v1=deadbeef", header);
+        assert_eq!(verify_signature(payload, &rolled, "whsec_test", now), Ok(()));
+    }
+
+    #[test]
+    fn test_invoice_id_from_metadata() {
+        let invoice_id = Uuid::new_v4();
+        let intent: PaymentIntent = serde_json::from_value(serde_json::json!({
+            "id": "pi_1",
+            "amount": 5000,
+            "amount_received": 5000,
+            "currency": "usd",
+            "status": "succeeded",
+            "metadata": { "invoice_id": invoice_id.to_string() }
+        }))
+        .unwrap();
+
+        assert_eq!(intent.invoice_id(), Some(invoice_id));
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_intent() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/payment_intents"))
+            .and(header("authorization", "Bearer sk_test_123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "pi_123",
+                "client_secret": "pi_123_secret_abc",
+                "amount": 12550,
+                "currency": "usd",
+                "status": "requires_payment_method",
+                "metadata": {}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let intent = create_payment_intent_at(&server.uri(), &settings(), Uuid::new_v4(), "INV-1001", "125.50".parse().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(intent.client_secret.as_deref(), Some("pi_123_secret_abc"));
+        assert_eq!(intent.amount, 12550);
+
+        let request = &server.received_requests().await.unwrap()[0];
+        let body = String::from_utf8_lossy(&request.body);
+        assert!(body.contains("amount=12550"));
+        assert!(body.contains("metadata%5Binvoice_number%5D=INV-1001"));
+    }
+}
@@ -0,0 +1,366 @@
+//! Project task dependencies and scheduling
+//!
+//! A task may depend on other tasks in its project and is blocked while any
+//! prerequisite isn't completed. Dependencies can't form cycles, which is
+//! checked under a lock on the project when one is added. The schedule
+//! orders tasks so prerequisites come first and works out each task's
+//! earliest start, in hours from the project start, from the estimated
+//! hours of what it waits on. Tasks with no slack are on the critical path.
+
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const COMPLETED: &str = "completed";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DependencyError {
+    #[error("Task not found")]
+    NotFound,
+    #[error("A task can't depend on itself")]
+    SelfDependency,
+    #[error("Tasks must belong to the same project")]
+    DifferentProject,
+    #[error("Dependency would create a cycle")]
+    Cycle,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// A task as the scheduler sees it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScheduleTask {
+    pub id: Uuid,
+    pub name: String,
+    pub status: String,
+    pub estimated_hours: Option<Decimal>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl ScheduleTask {
+    fn is_completed(&self) -> bool {
+        self.status == COMPLETED
+    }
+
+    /// Hours still to spend; completed work takes none
+    fn remaining_hours(&self) -> Decimal {
+        if self.is_completed() {
+            Decimal::ZERO
+        } else {
+            self.estimated_hours.unwrap_or(Decimal::ZERO).max(Decimal::ZERO)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledTask {
+    pub id: Uuid,
+    pub name: String,
+    pub status: String,
+    pub estimated_hours: Option<Decimal>,
+    pub depends_on: Vec<Uuid>,
+    /// Some prerequisite isn't completed
+    pub blocked: bool,
+    pub earliest_start_hours: Decimal,
+    pub earliest_finish_hours: Decimal,
+    /// How long the task can slip without delaying the project
+    pub slack_hours: Decimal,
+    pub critical: bool,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Prerequisite {
+    pub id: Uuid,
+    pub name: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyStatus {
+    pub task_id: Uuid,
+    pub depends_on: Vec<Prerequisite>,
+    /// Tasks waiting on this one
+    pub required_by: Vec<Uuid>,
+    pub blocked: bool,
+}
+
+/// Order `tasks` so every prerequisite comes before its dependents and
+/// work out start times and slack. `edges` are `(task, depends_on)`; ties
+/// keep the input order. Fails if the edges contain a cycle.
+pub fn build_schedule(tasks: &[ScheduleTask], edges: &[(Uuid, Uuid)]) -> Result<Vec<ScheduledTask>, DependencyError> {
+    let index: HashMap<Uuid, usize> = tasks.iter().enumerate().map(|(i, t)| (t.id, i)).collect();
+    let mut prerequisites: Vec<Vec<usize>> = vec![Vec::new(); tasks.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); tasks.len()];
+    for (task, depends_on) in edges {
+        if let (Some(&t), Some(&d)) = (index.get(task), index.get(depends_on)) {
+            prerequisites[t].push(d);
+            dependents[d].push(t);
+        }
+    }
+
+    // Kahn's algorithm; the ready set is keyed by input position for a stable order
+    let mut waiting: Vec<usize> = prerequisites.iter().map(Vec::len).collect();
+    let mut ready: BTreeSet<usize> = (0..tasks.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut order = Vec::with_capacity(tasks.len());
+    while let Some(i) = ready.pop_first() {
+        order.push(i);
+        for &dependent in &dependents[i] {
+            waiting[dependent] -= 1;
+            if waiting[dependent] == 0 {
+                ready.insert(dependent);
+            }
+        }
+    }
+    if order.len() != tasks.len() {
+        return Err(DependencyError::Cycle);
+    }
+
+    let mut earliest_start = vec![Decimal::ZERO; tasks.len()];
+    let mut earliest_finish = vec![Decimal::ZERO; tasks.len()];
+    for &i in &order {
+        earliest_start[i] = prerequisites[i].iter().map(|&p| earliest_finish[p]).max().unwrap_or(Decimal::ZERO);
+        earliest_finish[i] = earliest_start[i] + tasks[i].remaining_hours();
+    }
+
+    let project_finish = earliest_finish.iter().copied().max().unwrap_or(Decimal::ZERO);
+    let mut latest_finish = vec![project_finish; tasks.len()];
+    for &i in order.iter().rev() {
+        if let Some(finish) = dependents[i].iter().map(|&d| latest_finish[d] - tasks[d].remaining_hours()).min() {
+            latest_finish[i] = finish;
+        }
+    }
+
+    Ok(order
+        .into_iter()
+        .map(|i| {
+            let task = &tasks[i];
+            let slack = latest_finish[i] - earliest_finish[i];
+            ScheduledTask {
+                id: task.id,
+                name: task.name.clone(),
+                status: task.status.clone(),
+                estimated_hours: task.estimated_hours,
+                depends_on: prerequisites[i].iter().map(|&p| tasks[p].id).collect(),
+                blocked: prerequisites[i].iter().any(|&p| !tasks[p].is_completed()),
+                earliest_start_hours: earliest_start[i],
+                earliest_finish_hours: earliest_finish[i],
+                slack_hours: slack,
+                critical: slack.is_zero() && !task.is_completed(),
+            }
+        })
+        .collect())
+}
+
+/// Make `task_id` depend on `depends_on`. Adding an existing dependency
+/// is a no-op.
+pub async fn add(pool: &PgPool, task_id: Uuid, depends_on: Uuid) -> Result<(), DependencyError> {
+    if task_id == depends_on {
+        return Err(DependencyError::SelfDependency);
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let projects = sqlx::query_as::<_, (Uuid, Uuid)>("SELECT id, project_id FROM tasks WHERE id = ANY($1)")
+        .bind(vec![task_id, depends_on])
+        .fetch_all(&mut *tx)
+        .await?;
+    let project_of = |id: Uuid| projects.iter().find(|(t, _)| *t == id).map(|(_, p)| *p);
+    let (Some(project_id), Some(other_project)) = (project_of(task_id), project_of(depends_on)) else {
+        return Err(DependencyError::NotFound);
+    };
+    if project_id != other_project {
+        return Err(DependencyError::DifferentProject);
+    }
+
+    // Serialize dependency changes within the project so two concurrent
+    // inserts can't close a cycle between them
+    sqlx::query("SELECT id FROM projects WHERE id = $1 FOR UPDATE")
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // A cycle forms if the prerequisite already depends, directly or not, on the task
+    let creates_cycle = sqlx::query_scalar::<_, bool>(
+        r#"
+        WITH RECURSIVE upstream(id) AS (
+            SELECT depends_on_task_id FROM task_dependencies WHERE task_id = $1
+            UNION
+            SELECT d.depends_on_task_id FROM task_dependencies d JOIN upstream u ON d.task_id = u.id
+        )
+        SELECT EXISTS (SELECT 1 FROM upstream WHERE id = $2)
+        "#
+    )
+    .bind(depends_on)
+    .bind(task_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if creates_cycle {
+        return Err(DependencyError::Cycle);
+    }
+
+    sqlx::query(
+        "INSERT INTO task_dependencies (task_id, depends_on_task_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+    )
+    .bind(task_id)
+    .bind(depends_on)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Remove a dependency; `false` if there wasn't one
+pub async fn remove(pool: &PgPool, task_id: Uuid, depends_on: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM task_dependencies WHERE task_id = $1 AND depends_on_task_id = $2")
+        .bind(task_id)
+        .bind(depends_on)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A task's prerequisites, dependents and whether it is blocked
+pub async fn status(pool: &PgPool, task_id: Uuid) -> Result<DependencyStatus, DependencyError> {
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM tasks WHERE id = $1)")
+        .bind(task_id)
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Err(DependencyError::NotFound);
+    }
+
+    let depends_on = sqlx::query_as::<_, Prerequisite>(
+        r#"
+        SELECT t.id, t.name, COALESCE(t.status, 'todo') AS status
+        FROM task_dependencies d
+        JOIN tasks t ON t.id = d.depends_on_task_id
+        WHERE d.task_id = $1
+        ORDER BY t.created_at, t.id
+        "#
+    )
+    .bind(task_id)
+    .fetch_all(pool)
+    .await?;
+
+    let required_by = sqlx::query_scalar::<_, Uuid>(
+        "SELECT task_id FROM task_dependencies WHERE depends_on_task_id = $1 ORDER BY created_at"
+    )
+    .bind(task_id)
+    .fetch_all(pool)
+    .await?;
+
+    let blocked = depends_on.iter().any(|p| p.status != COMPLETED);
+    Ok(DependencyStatus { task_id, depends_on, required_by, blocked })
+}
+
+/// The project's tasks in dependency order with their schedule
+pub async fn project_schedule(pool: &PgPool, project_id: Uuid) -> Result<Vec<ScheduledTask>, DependencyError> {
+    let tasks = sqlx::query_as::<_, ScheduleTask>(
+        r#"
+        SELECT id, name, COALESCE(status, 'todo') AS status, estimated_hours, created_at
+        FROM tasks
+        WHERE project_id = $1
+        ORDER BY created_at, id
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    let edges = sqlx::query_as::<_, (Uuid, Uuid)>(
+        r#"
+        SELECT d.task_id, d.depends_on_task_id
+        FROM task_dependencies d
+        JOIN tasks t ON t.id = d.task_id
+        WHERE t.project_id = $1
+        "#
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    build_schedule(&tasks, &edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(name: &str, hours: i64, status: &str) -> ScheduleTask {
+        ScheduleTask {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            status: status.to_string(),
+            estimated_hours: Some(Decimal::from(hours)),
+            created_at: None,
+        }
+    }
+
+    fn names(schedule: &[ScheduledTask]) -> Vec<&str> {
+        schedule.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_prerequisites_come_first_with_earliest_starts() {
+        // deploy waits on both build (4h) and order hardware (2h); docs is independent
+        let deploy = task("deploy", 3, "todo");
+        let build = task("build", 4, "todo");
+        let hardware = task("order hardware", 2, "todo");
+        let docs = task("docs", 1, "todo");
+        let tasks = vec![deploy.clone(), build.clone(), hardware.clone(), docs.clone()];
+        let edges = vec![(deploy.id, build.id), (deploy.id, hardware.id)];
+
+        let schedule = build_schedule(&tasks, &edges).unwrap();
+        assert_eq!(names(&schedule), vec!["build", "order hardware", "deploy", "docs"]);
+
+        let deploy_row = schedule.iter().find(|t| t.id == deploy.id).unwrap();
+        assert_eq!(deploy_row.earliest_start_hours, Decimal::from(4));
+        assert_eq!(deploy_row.earliest_finish_hours, Decimal::from(7));
+        assert!(deploy_row.blocked);
+
+        // build -> deploy is the critical path; hardware and docs can slip
+        let by_name = |n: &str| schedule.iter().find(|t| t.name == n).unwrap();
+        assert!(by_name("build").critical && by_name("deploy").critical);
+        assert_eq!(by_name("order hardware").slack_hours, Decimal::from(2));
+        assert_eq!(by_name("docs").slack_hours, Decimal::from(6));
+        assert!(!by_name("docs").critical);
+    }
+
+    #[test]
+    fn test_completed_prerequisite_unblocks_and_takes_no_time() {
+        let install = task("install", 5, COMPLETED);
+        let configure = task("configure", 2, "todo");
+        let verify = task("verify", 1, "todo");
+        let tasks = vec![install.clone(), configure.clone(), verify.clone()];
+        let edges = vec![(configure.id, install.id), (verify.id, configure.id)];
+
+        let schedule = build_schedule(&tasks, &edges).unwrap();
+        let configure_row = schedule.iter().find(|t| t.id == configure.id).unwrap();
+        assert!(!configure_row.blocked);
+        assert_eq!(configure_row.earliest_start_hours, Decimal::ZERO);
+        // verify still waits on configure
+        let verify_row = schedule.iter().find(|t| t.id == verify.id).unwrap();
+        assert!(verify_row.blocked);
+        assert_eq!(verify_row.earliest_start_hours, Decimal::from(2));
+    }
+
+    #[test]
+    fn test_cycle_is_an_error() {
+        let a = task("a", 1, "todo");
+        let b = task("b", 1, "todo");
+        let c = task("c", 1, "todo");
+        let tasks = vec![a.clone(), b.clone(), c.clone()];
+        let edges = vec![(a.id, b.id), (b.id, c.id), (c.id, a.id)];
+        assert!(matches!(build_schedule(&tasks, &edges), Err(DependencyError::Cycle)));
+    }
+
+    #[test]
+    fn test_no_tasks() {
+        assert!(build_schedule(&[], &[]).unwrap().is_empty());
+    }
+}
@@ -7,6 +7,8 @@ use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 
+use crate::middleware::request_id::with_request_id;
+
 /// Teams Adaptive Card for rich notifications
 #[derive(Debug, Clone, Serialize)]
 pub struct TeamsAdaptiveCard {
@@ -139,9 +141,7 @@ impl TeamsNotificationService {
         webhook_url: &str,
         payload: &TeamsWebhookPayload,
     ) -> Result<(), TeamsError> {
-        let response = self
-            .client
-            .post(webhook_url)
+        let response = with_request_id(self.client.post(webhook_url))
             .json(payload)
             .send()
             .await
@@ -0,0 +1,269 @@
+//! Ticket replies
+//!
+//! Agents answer a ticket with public replies, which the client sees in the
+//! portal and is emailed, or internal ones the client never sees. Notes are
+//! always internal (a database trigger enforces it). The first public agent
+//! reply is the ticket's SLA response; internal replies don't stop the
+//! response clock.
+//!
+//! Files uploaded through `/files/upload` are attached to a reply by id.
+//! An attachment belongs to one reply and inherits its visibility.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgExecutor, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::services::ticket_sla;
+
+/// Attachments allowed on one reply
+pub const MAX_ATTACHMENTS: usize = 20;
+
+pub const REPLY_TYPES: &[&str] = &["reply", "note", "status_change"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum TicketReplyError {
+    #[error("Ticket not found")]
+    TicketNotFound,
+    #[error("{message}")]
+    Validation { field: &'static str, message: String },
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl TicketReplyError {
+    fn validation(field: &'static str, message: impl Into<String>) -> Self {
+        Self::Validation { field, message: message.into() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewTicketReply {
+    pub details: String,
+    pub reply_type: String,
+    pub internal: bool,
+    pub time_worked: i32,
+    pub billable: bool,
+    pub attachment_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ReplyAttachment {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub ticket_reply_id: Uuid,
+    pub original_filename: String,
+    pub mime_type: String,
+    pub file_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TicketReplyView {
+    pub id: Uuid,
+    pub ticket_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub user_name: Option<String>,
+    /// Set when the client wrote the reply, by email or in the portal
+    pub contact_id: Option<Uuid>,
+    pub reply_type: String,
+    pub details: String,
+    pub internal: bool,
+    pub time_worked: i32,
+    pub billable: bool,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    pub attachments: Vec<ReplyAttachment>,
+}
+
+const REPLY_COLUMNS: &str = "r.id, r.ticket_id, r.user_id,
+    u.first_name || ' ' || u.last_name as user_name, r.contact_id,
+    COALESCE(r.type, 'reply') as reply_type, r.details, r.internal,
+    COALESCE(r.time_worked, 0) as time_worked, COALESCE(r.billable, false) as billable,
+    COALESCE(r.created_at, NOW()) as created_at";
+
+/// Whether an agent's reply counts as the SLA response: only one the client
+/// can see does
+pub fn is_public_response(reply_type: &str, internal: bool) -> bool {
+    reply_type == "reply" && !internal
+}
+
+/// A ticket's replies, oldest first; internal ones only if asked for
+pub async fn list_replies(pool: &PgPool, ticket_id: Uuid, include_internal: bool) -> Result<Vec<TicketReplyView>, sqlx::Error> {
+    let mut replies = sqlx::query_as::<_, TicketReplyView>(&format!(
+        "SELECT {} FROM ticket_replies r
+         LEFT JOIN users u ON u.id = r.user_id
+         WHERE r.ticket_id = $1 AND ($2 OR NOT r.internal)
+         ORDER BY r.created_at ASC, r.id",
+        REPLY_COLUMNS
+    ))
+    .bind(ticket_id)
+    .bind(include_internal)
+    .fetch_all(pool)
+    .await?;
+
+    let reply_ids: Vec<Uuid> = replies.iter().map(|r| r.id).collect();
+    let mut by_reply: HashMap<Uuid, Vec<ReplyAttachment>> = HashMap::new();
+    for attachment in attachments(pool, &reply_ids).await? {
+        by_reply.entry(attachment.ticket_reply_id).or_default().push(attachment);
+    }
+    for reply in &mut replies {
+        reply.attachments = by_reply.remove(&reply.id).unwrap_or_default();
+    }
+    Ok(replies)
+}
+
+async fn attachments<'e>(executor: impl PgExecutor<'e>, reply_ids: &[Uuid]) -> Result<Vec<ReplyAttachment>, sqlx::Error> {
+    sqlx::query_as::<_, ReplyAttachment>(
+        "SELECT id, ticket_reply_id, original_filename, mime_type, file_size
+         FROM files WHERE ticket_reply_id = ANY($1)
+         ORDER BY created_at, id",
+    )
+    .bind(reply_ids)
+    .fetch_all(executor)
+    .await
+}
+
+fn validate(reply: &NewTicketReply) -> Result<(), TicketReplyError> {
+    if reply.details.trim().is_empty() {
+        return Err(TicketReplyError::validation("details", "Reply text is required"));
+    }
+    if !REPLY_TYPES.contains(&reply.reply_type.as_str()) {
+        return Err(TicketReplyError::validation("reply_type", format!("must be one of {}", REPLY_TYPES.join(", "))));
+    }
+    if reply.time_worked < 0 {
+        return Err(TicketReplyError::validation("time_worked", "must not be negative"));
+    }
+    if reply.attachment_ids.len() > MAX_ATTACHMENTS {
+        return Err(TicketReplyError::validation(
+            "attachment_ids",
+            format!("At most {} files can be attached", MAX_ATTACHMENTS),
+        ));
+    }
+    Ok(())
+}
+
+/// Add an agent's reply to a ticket, attach its files and, for the first
+/// public reply, stamp the SLA response. Run on the request's transaction
+/// so a bad attachment leaves no reply behind.
+pub async fn add_reply(
+    conn: &mut PgConnection,
+    ticket_id: Uuid,
+    user_id: Uuid,
+    reply: NewTicketReply,
+) -> Result<TicketReplyView, TicketReplyError> {
+    validate(&reply)?;
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM tickets WHERE id = $1)")
+        .bind(ticket_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !exists {
+        return Err(TicketReplyError::TicketNotFound);
+    }
+
+    let (reply_id, internal): (Uuid, bool) = sqlx::query_as(
+        "INSERT INTO ticket_replies (ticket_id, user_id, type, details, internal, time_worked, billable)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING id, internal",
+    )
+    .bind(ticket_id)
+    .bind(user_id)
+    .bind(&reply.reply_type)
+    .bind(reply.details.trim())
+    .bind(reply.internal)
+    .bind(reply.time_worked)
+    .bind(reply.billable)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut attachment_ids = reply.attachment_ids.clone();
+    attachment_ids.sort();
+    attachment_ids.dedup();
+    if !attachment_ids.is_empty() {
+        // Only files not yet attached elsewhere, not uploaded for another
+        // client and not flagged by the virus scanner can be attached
+        let attached = sqlx::query(
+            "UPDATE files SET ticket_reply_id = $2, ticket_id = $1
+             WHERE id = ANY($3) AND ticket_reply_id IS NULL
+               AND (ticket_id IS NULL OR ticket_id = $1)
+               AND (client_id IS NULL OR client_id = (SELECT client_id FROM tickets WHERE id = $1))
+               AND scan_status <> 'infected'",
+        )
+        .bind(ticket_id)
+        .bind(reply_id)
+        .bind(&attachment_ids)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        if attached != attachment_ids.len() as u64 {
+            return Err(TicketReplyError::validation(
+                "attachment_ids",
+                "Files must exist and not belong to another client, ticket or reply",
+            ));
+        }
+    }
+
+    if is_public_response(&reply.reply_type, internal) {
+        ticket_sla::record_first_response(&mut *conn, ticket_id).await?;
+    }
+    sqlx::query("UPDATE tickets SET updated_at = NOW() WHERE id = $1")
+        .bind(ticket_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let mut view = sqlx::query_as::<_, TicketReplyView>(&format!(
+        "SELECT {} FROM ticket_replies r LEFT JOIN users u ON u.id = r.user_id WHERE r.id = $1",
+        REPLY_COLUMNS
+    ))
+    .bind(reply_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    view.attachments = attachments(&mut *conn, &[reply_id]).await?;
+
+    Ok(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(details: &str, reply_type: &str) -> NewTicketReply {
+        NewTicketReply {
+            details: details.to_string(),
+            reply_type: reply_type.to_string(),
+            internal: false,
+            time_worked: 0,
+            billable: false,
+            attachment_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_only_public_replies_are_responses() {
+        assert!(is_public_response("reply", false));
+        assert!(!is_public_response("reply", true));
+        assert!(!is_public_response("note", false));
+        assert!(!is_public_response("status_change", false));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&reply("Rebooted the printer", "reply")).is_ok());
+        assert!(matches!(
+            validate(&reply("  ", "reply")),
+            Err(TicketReplyError::Validation { field: "details", .. })
+        ));
+        assert!(matches!(
+            validate(&reply("Hi", "email")),
+            Err(TicketReplyError::Validation { field: "reply_type", .. })
+        ));
+
+        let mut many = reply("Logs attached", "reply");
+        many.attachment_ids = (0..=MAX_ATTACHMENTS).map(|_| Uuid::new_v4()).collect();
+        assert!(matches!(
+            validate(&many),
+            Err(TicketReplyError::Validation { field: "attachment_ids", .. })
+        ));
+    }
+}
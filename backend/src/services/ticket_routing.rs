@@ -0,0 +1,307 @@
+//! Ticket routing
+//!
+//! Evaluates the active `ticket_routing_rules` against a newly created ticket
+//! and applies the matching rules' actions. Rules are checked highest
+//! `priority` first; once a rule sets a field, lower-priority rules cannot
+//! override it, while tags accumulate. A matching rule with `stop_processing`
+//! ends evaluation.
+
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::handlers::ticket_advanced::RoutingRule;
+use crate::workflows::{Condition, ConditionGroup};
+
+/// Ticket fields that routing conditions can match on
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutableTicket {
+    pub id: Uuid,
+    pub client_id: Uuid,
+    pub contact_id: Option<Uuid>,
+    pub category_id: Option<Uuid>,
+    pub subject: String,
+    pub priority: String,
+    pub source: String,
+    /// Filled from the contact's email when not supplied
+    pub source_email: Option<String>,
+}
+
+/// Changes to apply to the ticket after evaluating all rules
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RoutingOutcome {
+    pub matched_rule_ids: Vec<Uuid>,
+    pub queue_id: Option<Uuid>,
+    pub assigned_to: Option<Uuid>,
+    pub priority: Option<String>,
+    pub category_id: Option<Uuid>,
+    pub tags: Vec<String>,
+}
+
+impl RoutingOutcome {
+    pub fn is_empty(&self) -> bool {
+        self.matched_rule_ids.is_empty()
+    }
+}
+
+/// Translate a rule's stored conditions into a condition group.
+///
+/// Accepts either a full `ConditionGroup` (`{"logic": ..., "conditions": [...]}`)
+/// or the flat shorthand `{"subject_contains", "client_id", "priority",
+/// "category_id", "source", "source_email"}`. A `source_email` starting with `@`
+/// matches the whole domain. Returns `None` for conditions that can't be
+/// understood, so a malformed rule never matches.
+fn rule_conditions(conditions: &serde_json::Value) -> Option<ConditionGroup> {
+    let map = conditions.as_object()?;
+
+    if map.contains_key("logic") {
+        return serde_json::from_value(conditions.clone()).ok();
+    }
+
+    let mut group = ConditionGroup::and(Vec::new());
+    for (key, value) in map {
+        let condition = match key.as_str() {
+            "subject_contains" => Condition::contains("subject", value.as_str()?),
+            "client_id" | "priority" | "category_id" | "source" => Condition::equals(key, value.clone()),
+            "source_email" => {
+                let email = value.as_str()?;
+                if email.starts_with('@') {
+                    Condition::ends_with("source_email", email)
+                } else {
+                    Condition::regex("source_email", &format!("(?i)^{}$", regex::escape(email)))
+                }
+            }
+            _ => return None,
+        };
+        group = group.add_condition(condition);
+    }
+
+    Some(group)
+}
+
+/// Work out the routing outcome for a ticket. `rules` must already be
+/// ordered by priority, highest first.
+pub fn route(rules: &[RoutingRule], ticket: &serde_json::Value) -> RoutingOutcome {
+    let mut outcome = RoutingOutcome::default();
+
+    for rule in rules.iter().filter(|r| r.is_active) {
+        let Some(conditions) = rule_conditions(&rule.conditions) else {
+            warn!("Routing rule '{}' has unrecognised conditions; skipping", rule.name);
+            continue;
+        };
+        if !conditions.evaluate(ticket) {
+            continue;
+        }
+
+        outcome.matched_rule_ids.push(rule.id);
+        outcome.queue_id = outcome.queue_id.or(rule.assign_queue_id);
+        outcome.assigned_to = outcome.assigned_to.or(rule.assign_user_id);
+        outcome.priority = outcome.priority.take().or_else(|| rule.set_priority.clone());
+        outcome.category_id = outcome.category_id.or(rule.set_category_id);
+        for tag in rule.add_tags.iter().flatten() {
+            if !outcome.tags.contains(tag) {
+                outcome.tags.push(tag.clone());
+            }
+        }
+
+        if rule.stop_processing {
+            break;
+        }
+    }
+
+    outcome
+}
+
+/// Evaluate active routing rules against `ticket` and apply the result
+pub async fn apply_routing_rules(pool: &PgPool, ticket: &RoutableTicket) -> Result<RoutingOutcome, sqlx::Error> {
+    let rules = sqlx::query_as::<_, RoutingRule>(
+        r#"
+        SELECT
+            id, name, description, conditions,
+            assign_queue_id, assign_user_id, set_priority, set_category_id,
+            add_tags, COALESCE(stop_processing, true) as stop_processing,
+            COALESCE(is_active, true) as is_active, COALESCE(priority, 0) as priority,
+            created_by, COALESCE(created_at, NOW()) as created_at, updated_at
+        FROM ticket_routing_rules
+        WHERE is_active = true
+        ORDER BY priority DESC, created_at ASC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if rules.is_empty() {
+        return Ok(RoutingOutcome::default());
+    }
+
+    let mut ticket = ticket.clone();
+    if ticket.source_email.is_none() {
+        if let Some(contact_id) = ticket.contact_id {
+            ticket.source_email = sqlx::query_scalar::<_, Option<String>>("SELECT email FROM contacts WHERE id = $1")
+                .bind(contact_id)
+                .fetch_optional(pool)
+                .await?
+                .flatten();
+        }
+    }
+
+    let payload = serde_json::to_value(&ticket).unwrap_or_default();
+    let outcome = route(&rules, &payload);
+    if outcome.is_empty() {
+        return Ok(outcome);
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        r#"
+        UPDATE tickets SET
+            queue_id = COALESCE($2, queue_id),
+            assigned_to = COALESCE($3, assigned_to),
+            priority = COALESCE($4, priority),
+            category_id = COALESCE($5, category_id),
+            updated_at = NOW()
+        WHERE id = $1
+        "#
+    )
+    .bind(ticket.id)
+    .bind(outcome.queue_id)
+    .bind(outcome.assigned_to)
+    .bind(&outcome.priority)
+    .bind(outcome.category_id)
+    .execute(&mut *tx)
+    .await?;
+
+    for tag in &outcome.tags {
+        let tag_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO ticket_tags (name) VALUES ($1)
+             ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+             RETURNING id"
+        )
+        .bind(tag)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO ticket_tag_assignments (ticket_id, tag_id) VALUES ($1, $2)
+             ON CONFLICT DO NOTHING"
+        )
+        .bind(ticket.id)
+        .bind(tag_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn rule(priority: i32, conditions: serde_json::Value, stop_processing: bool) -> RoutingRule {
+        RoutingRule {
+            id: Uuid::new_v4(),
+            name: format!("Rule {}", priority),
+            description: None,
+            conditions,
+            assign_queue_id: None,
+            assign_user_id: None,
+            set_priority: None,
+            set_category_id: None,
+            add_tags: None,
+            stop_processing,
+            is_active: true,
+            priority,
+            created_by: None,
+            created_at: Utc::now(),
+            updated_at: None,
+        }
+    }
+
+    fn ticket(subject: &str, source_email: Option<&str>) -> serde_json::Value {
+        serde_json::to_value(RoutableTicket {
+            id: Uuid::new_v4(),
+            client_id: Uuid::new_v4(),
+            contact_id: None,
+            category_id: None,
+            subject: subject.to_string(),
+            priority: "medium".to_string(),
+            source: "email".to_string(),
+            source_email: source_email.map(String::from),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_higher_priority_rule_wins() {
+        let queue_a = Uuid::new_v4();
+        let queue_b = Uuid::new_v4();
+
+        let mut high = rule(10, serde_json::json!({ "subject_contains": "server" }), false);
+        high.assign_queue_id = Some(queue_a);
+        high.add_tags = Some(vec!["infrastructure".to_string()]);
+
+        let mut low = rule(1, serde_json::json!({ "subject_contains": "down" }), false);
+        low.assign_queue_id = Some(queue_b);
+        low.set_priority = Some("critical".to_string());
+        low.add_tags = Some(vec!["outage".to_string(), "infrastructure".to_string()]);
+
+        let outcome = route(&[high.clone(), low.clone()], &ticket("Server is DOWN", None));
+
+        assert_eq!(outcome.matched_rule_ids, vec![high.id, low.id]);
+        assert_eq!(outcome.queue_id, Some(queue_a));
+        // Fields the higher rule left unset still come from lower rules
+        assert_eq!(outcome.priority.as_deref(), Some("critical"));
+        assert_eq!(outcome.tags, vec!["infrastructure", "outage"]);
+    }
+
+    #[test]
+    fn test_stop_processing_ends_evaluation() {
+        let mut first = rule(10, serde_json::json!({ "source_email": "@acme.com" }), true);
+        first.set_priority = Some("high".to_string());
+
+        let mut second = rule(5, serde_json::json!({}), false);
+        second.add_tags = Some(vec!["catch-all".to_string()]);
+
+        let outcome = route(&[first.clone(), second.clone()], &ticket("Printer jam", Some("Jane@ACME.com")));
+        assert_eq!(outcome.matched_rule_ids, vec![first.id]);
+        assert!(outcome.tags.is_empty());
+
+        // A ticket that misses the first rule falls through to the second
+        let outcome = route(&[first, second.clone()], &ticket("Printer jam", Some("bob@other.org")));
+        assert_eq!(outcome.matched_rule_ids, vec![second.id]);
+        assert_eq!(outcome.tags, vec!["catch-all"]);
+    }
+
+    #[test]
+    fn test_exact_email_and_unknown_conditions() {
+        let exact = rule(10, serde_json::json!({ "source_email": "bob@acme.com" }), true);
+        assert!(route(&[exact.clone()], &ticket("Hi", Some("jimbob@acme.com"))).is_empty());
+        assert!(!route(&[exact], &ticket("Hi", Some("BOB@acme.com"))).is_empty());
+
+        let unknown = rule(10, serde_json::json!({ "moon_phase": "full" }), true);
+        assert!(route(&[unknown], &ticket("Hi", None)).is_empty());
+    }
+
+    #[test]
+    fn test_condition_group_rules() {
+        let group = rule(
+            10,
+            serde_json::json!({
+                "logic": "OR",
+                "conditions": [
+                    { "field": "priority", "operator": "equals", "value": "critical" },
+                    { "field": "subject", "operator": "contains", "value": "urgent" }
+                ]
+            }),
+            true,
+        );
+
+        assert!(!route(&[group.clone()], &ticket("URGENT: email down", None)).is_empty());
+        assert!(route(&[group], &ticket("Question about invoice", None)).is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+//! Ticket search
+//!
+//! Full-text search over ticket subjects and details using the weighted
+//! `tickets.search_vector` column, ranked with `ts_rank` and highlighted with
+//! `ts_headline`. Queries that produce no lexemes (a single character, or
+//! nothing but stop words) fall back to a substring match.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+const SNIPPET_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxWords=35, MinWords=15, MaxFragments=2";
+const SUBJECT_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, HighlightAll=true";
+const FALLBACK_SNIPPET_LENGTH: i32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    FullText,
+    Substring,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TicketSearchFilters {
+    pub status: Option<String>,
+    pub client_id: Option<Uuid>,
+    pub priority: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TicketSearchResult {
+    pub id: Uuid,
+    pub number: i32,
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub subject: String,
+    pub status: String,
+    pub priority: String,
+    pub assigned_to: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub rank: f32,
+    /// Subject with matched terms wrapped in `<mark>`
+    pub subject_highlight: String,
+    /// Best-matching fragments of the ticket details
+    pub snippet: String,
+}
+
+/// Build an `ILIKE` pattern matching `query` anywhere, with wildcards in the
+/// query itself treated literally
+pub fn substring_pattern(query: &str) -> String {
+    let escaped = query
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Decide whether `query` can be run through the full-text index
+pub async fn search_mode(pool: &PgPool, query: &str) -> Result<SearchMode, sqlx::Error> {
+    let nodes = sqlx::query_scalar::<_, i32>("SELECT numnode(websearch_to_tsquery('english', $1))")
+        .bind(query)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(if nodes > 0 { SearchMode::FullText } else { SearchMode::Substring })
+}
+
+/// Search tickets, best match first. Returns the page of results and the
+/// total number of matches.
+pub async fn search_tickets(
+    pool: &PgPool,
+    query: &str,
+    filters: &TicketSearchFilters,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<TicketSearchResult>, i64, SearchMode), sqlx::Error> {
+    let mode = search_mode(pool, query).await?;
+
+    let (term, matches, rank, subject_highlight, snippet) = match mode {
+        SearchMode::FullText => (
+            query.to_string(),
+            "t.search_vector @@ websearch_to_tsquery('english', $1)".to_string(),
+            "ts_rank(t.search_vector, websearch_to_tsquery('english', $1))".to_string(),
+            format!("ts_headline('english', r.subject, websearch_to_tsquery('english', $1), '{}')", SUBJECT_OPTIONS),
+            format!("ts_headline('english', r.details, websearch_to_tsquery('english', $1), '{}')", SNIPPET_OPTIONS),
+        ),
+        SearchMode::Substring => (
+            substring_pattern(query.trim()),
+            "(t.subject ILIKE $1 OR t.details ILIKE $1)".to_string(),
+            // Subject hits ahead of details-only hits
+            "(CASE WHEN t.subject ILIKE $1 THEN 1.0 ELSE 0.5 END)::real".to_string(),
+            "r.subject".to_string(),
+            format!("LEFT(r.details, {})", FALLBACK_SNIPPET_LENGTH),
+        ),
+    };
+
+    let filter_clause = r#"
+            AND ($2::text IS NULL OR t.status = $2)
+            AND ($3::uuid IS NULL OR t.client_id = $3)
+            AND ($4::text IS NULL OR t.priority = $4)"#;
+
+    let total = sqlx::query_scalar::<_, i64>(&format!(
+        "SELECT COUNT(*) FROM tickets t WHERE {}{}",
+        matches, filter_clause
+    ))
+    .bind(&term)
+    .bind(&filters.status)
+    .bind(filters.client_id)
+    .bind(&filters.priority)
+    .fetch_one(pool)
+    .await?;
+
+    // Headlines are expensive, so only build them for the page being returned
+    let sql = format!(
+        r#"
+        WITH ranked AS (
+            SELECT t.id, t.number, t.client_id, t.subject, t.details,
+                   COALESCE(t.status, 'open') as status,
+                   COALESCE(t.priority, 'medium') as priority,
+                   t.assigned_to, COALESCE(t.created_at, NOW()) as created_at,
+                   {rank} as rank
+            FROM tickets t
+            WHERE {matches}{filter_clause}
+            ORDER BY rank DESC, t.created_at DESC
+            LIMIT $5 OFFSET $6
+        )
+        SELECT r.id, r.number, r.client_id, c.name as client_name, r.subject,
+               r.status, r.priority, r.assigned_to, r.created_at, r.rank,
+               {subject_highlight} as subject_highlight,
+               {snippet} as snippet
+        FROM ranked r
+        JOIN clients c ON r.client_id = c.id
+        ORDER BY r.rank DESC, r.created_at DESC
+        "#
+    );
+
+    let results = sqlx::query_as::<_, TicketSearchResult>(&sql)
+        .bind(&term)
+        .bind(&filters.status)
+        .bind(filters.client_id)
+        .bind(&filters.priority)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+    Ok((results, total, mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substring_pattern_escapes_wildcards() {
+        assert_eq!(substring_pattern("vp"), "%vp%");
+        assert_eq!(substring_pattern("50%_off"), "%50\\%\\_off%");
+        assert_eq!(substring_pattern("C:\\"), "%C:\\\\%");
+    }
+}
@@ -0,0 +1,353 @@
+//! Ticket SLA timestamps
+//!
+//! Resolves the SLA that applies to a ticket and stores its due times on
+//! the ticket (`sla_response_due`, `sla_resolution_due`, mirrored into
+//! `response_due_at`/`resolution_due_at`). The first SLA found wins:
+//!
+//! 1. a policy pinned to the ticket by hand
+//! 2. the default policy of the ticket's category
+//! 3. the default policy of the ticket's queue
+//! 4. the SLA on the client's active contract
+//! 5. the client's own active policy
+//! 6. the active global policy
+//!
+//! Policies only apply when they have a rule for the ticket's priority.
+//! Due times run from the ticket's creation in the client's business
+//! calendar. The first agent reply stamps `sla_response_at` and resolving
+//! the ticket stamps `resolved_at`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::services::business_hours::{self, BusinessCalendar, SlaDueDates};
+
+pub const RESOLVED_STATUSES: &[&str] = &["resolved", "closed"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum TicketSlaError {
+    #[error("Ticket not found")]
+    TicketNotFound,
+    #[error("SLA policy not found")]
+    PolicyNotFound,
+    #[error("SLA policy has no rule for priority '{0}'")]
+    NoRuleForPriority(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlaSource {
+    Pinned,
+    Category,
+    Queue,
+    Contract,
+    Client,
+    Global,
+}
+
+/// The SLA terms that apply to a ticket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolvedSla {
+    pub source: SlaSource,
+    pub policy_id: Option<Uuid>,
+    /// Set when the terms come from a contract SLA
+    pub sla_id: Option<Uuid>,
+    pub response_minutes: i64,
+    pub resolution_minutes: i64,
+    pub business_hours_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TicketSla {
+    #[serde(flatten)]
+    pub sla: ResolvedSla,
+    #[serde(flatten)]
+    pub due: SlaDueDates,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct SlaTicket {
+    client_id: Uuid,
+    priority: String,
+    category_id: Option<Uuid>,
+    queue_id: Option<Uuid>,
+    sla_policy_id: Option<Uuid>,
+    sla_policy_pinned: bool,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+struct PolicyRule {
+    policy_id: Uuid,
+    response_time_minutes: i32,
+    resolution_time_hours: i32,
+}
+
+impl PolicyRule {
+    fn resolved(self, source: SlaSource) -> ResolvedSla {
+        ResolvedSla {
+            source,
+            policy_id: Some(self.policy_id),
+            sla_id: None,
+            response_minutes: self.response_time_minutes as i64,
+            resolution_minutes: self.resolution_time_hours as i64 * 60,
+            // Policies carry business hours of their own; time counts
+            // in the client's calendar
+            business_hours_only: true,
+        }
+    }
+}
+
+/// Due times for `sla` on a ticket opened at `opened_at`
+pub fn due_dates(sla: &ResolvedSla, calendar: &BusinessCalendar, opened_at: DateTime<Utc>) -> SlaDueDates {
+    business_hours::due_after(calendar, opened_at, sla.response_minutes, sla.resolution_minutes, sla.business_hours_only)
+}
+
+pub fn is_resolved_status(status: &str) -> bool {
+    RESOLVED_STATUSES.contains(&status)
+}
+
+const POLICY_RULE_SQL: &str = r#"
+    SELECT p.id as policy_id, r.response_time_minutes, r.resolution_time_hours
+    FROM sla_policies p
+    JOIN sla_rules r ON r.policy_id = p.id AND r.priority = $1
+    WHERE COALESCE(p.is_active, true)
+"#;
+
+/// An active policy's rule for `priority`
+async fn policy_rule(pool: &PgPool, policy_id: Uuid, priority: &str) -> Result<Option<PolicyRule>, sqlx::Error> {
+    sqlx::query_as::<_, PolicyRule>(&format!("{} AND p.id = $2 LIMIT 1", POLICY_RULE_SQL))
+        .bind(priority)
+        .bind(policy_id)
+        .fetch_optional(pool)
+        .await
+}
+
+async fn default_policy(pool: &PgPool, table: &str, id: Option<Uuid>) -> Result<Option<Uuid>, sqlx::Error> {
+    let Some(id) = id else { return Ok(None) };
+    Ok(sqlx::query_scalar::<_, Option<Uuid>>(&format!("SELECT default_sla_policy_id FROM {} WHERE id = $1", table))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .flatten())
+}
+
+async fn resolve(pool: &PgPool, ticket: &SlaTicket) -> Result<Option<ResolvedSla>, sqlx::Error> {
+    let priority = ticket.priority.as_str();
+
+    let pinned = if ticket.sla_policy_pinned { ticket.sla_policy_id } else { None };
+    let defaults = [
+        (SlaSource::Pinned, pinned),
+        (SlaSource::Category, default_policy(pool, "ticket_categories", ticket.category_id).await?),
+        (SlaSource::Queue, default_policy(pool, "ticket_queues", ticket.queue_id).await?),
+    ];
+    for (source, policy_id) in defaults {
+        if let Some(policy_id) = policy_id {
+            if let Some(rule) = policy_rule(pool, policy_id, priority).await? {
+                return Ok(Some(rule.resolved(source)));
+            }
+        }
+    }
+
+    if let Some(sla) = business_hours::find_sla(pool, ticket.client_id, priority).await? {
+        return Ok(Some(ResolvedSla {
+            source: SlaSource::Contract,
+            policy_id: None,
+            sla_id: Some(sla.id),
+            response_minutes: sla.response_time_minutes as i64,
+            resolution_minutes: sla.resolution_time_hours as i64 * 60,
+            business_hours_only: sla.business_hours_only,
+        }));
+    }
+
+    let client = sqlx::query_as::<_, PolicyRule>(&format!(
+        "{} AND p.client_id = $2 ORDER BY p.created_at DESC LIMIT 1",
+        POLICY_RULE_SQL
+    ))
+    .bind(priority)
+    .bind(ticket.client_id)
+    .fetch_optional(pool)
+    .await?;
+    if let Some(rule) = client {
+        return Ok(Some(rule.resolved(SlaSource::Client)));
+    }
+
+    let global = sqlx::query_as::<_, PolicyRule>(&format!(
+        "{} AND p.is_global = true AND p.client_id IS NULL ORDER BY p.created_at ASC LIMIT 1",
+        POLICY_RULE_SQL
+    ))
+    .bind(priority)
+    .fetch_optional(pool)
+    .await?;
+    Ok(global.map(|rule| rule.resolved(SlaSource::Global)))
+}
+
+async fn load_ticket(pool: &PgPool, ticket_id: Uuid) -> Result<SlaTicket, TicketSlaError> {
+    sqlx::query_as::<_, SlaTicket>(
+        r#"
+        SELECT client_id, COALESCE(priority, 'medium') as priority, category_id, queue_id,
+               sla_policy_id, sla_policy_pinned, COALESCE(created_at, NOW()) as created_at
+        FROM tickets WHERE id = $1
+        "#
+    )
+    .bind(ticket_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(TicketSlaError::TicketNotFound)
+}
+
+/// Resolve the ticket's SLA and store its due times. Call after creating a
+/// ticket and whenever its priority, category, queue or SLA changes.
+/// Tickets no SLA applies to keep whatever due times they have.
+pub async fn apply_sla(pool: &PgPool, ticket_id: Uuid) -> Result<Option<TicketSla>, TicketSlaError> {
+    let ticket = load_ticket(pool, ticket_id).await?;
+    let Some(sla) = resolve(pool, &ticket).await? else {
+        return Ok(None);
+    };
+
+    let calendar = if sla.business_hours_only {
+        business_hours::calendar_for_ticket(pool, ticket.client_id, ticket.created_at).await?
+    } else {
+        BusinessCalendar::new(chrono_tz::Tz::UTC, &[], [])
+    };
+    let due = due_dates(&sla, &calendar, ticket.created_at);
+
+    sqlx::query(
+        r#"
+        UPDATE tickets SET
+            sla_policy_id = $2, sla_id = $3,
+            sla_response_due = $4, sla_resolution_due = $5,
+            response_due_at = $4, resolution_due_at = $5
+        WHERE id = $1
+        "#
+    )
+    .bind(ticket_id)
+    .bind(sla.policy_id)
+    .bind(sla.sla_id)
+    .bind(due.response_due)
+    .bind(due.resolution_due)
+    .execute(pool)
+    .await?;
+
+    Ok(Some(TicketSla { sla, due }))
+}
+
+/// Pin `policy_id` to the ticket, or unpin with `None` to go back to the
+/// resolved SLA, then recalculate its due times
+pub async fn pin_policy(pool: &PgPool, ticket_id: Uuid, policy_id: Option<Uuid>) -> Result<Option<TicketSla>, TicketSlaError> {
+    let ticket = load_ticket(pool, ticket_id).await?;
+
+    if let Some(policy_id) = policy_id {
+        let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM sla_policies WHERE id = $1 AND COALESCE(is_active, true))")
+            .bind(policy_id)
+            .fetch_one(pool)
+            .await?;
+        if !exists {
+            return Err(TicketSlaError::PolicyNotFound);
+        }
+        if policy_rule(pool, policy_id, &ticket.priority).await?.is_none() {
+            return Err(TicketSlaError::NoRuleForPriority(ticket.priority));
+        }
+    }
+
+    sqlx::query("UPDATE tickets SET sla_policy_id = $2, sla_policy_pinned = $3 WHERE id = $1")
+        .bind(ticket_id)
+        .bind(policy_id)
+        .bind(policy_id.is_some())
+        .execute(pool)
+        .await?;
+
+    apply_sla(pool, ticket_id).await
+}
+
+/// Stamp the ticket's first agent response. Returns whether this call
+/// stamped it; later replies leave the original time alone. Takes the
+/// pool or the transaction that adds the reply.
+pub async fn record_first_response<'e>(executor: impl PgExecutor<'e>, ticket_id: Uuid) -> Result<bool, sqlx::Error> {
+    let stamped = sqlx::query(
+        r#"
+        UPDATE tickets SET
+            sla_response_at = NOW(),
+            first_response_at = COALESCE(first_response_at, NOW())
+        WHERE id = $1 AND sla_response_at IS NULL
+        "#
+    )
+    .bind(ticket_id)
+    .execute(executor)
+    .await?
+    .rows_affected();
+    Ok(stamped > 0)
+}
+
+/// Keep `resolved_at` in step with the ticket's status: stamped when it is
+/// first resolved or closed, cleared if it is reopened
+pub async fn record_status<'e>(executor: impl PgExecutor<'e>, ticket_id: Uuid, status: &str) -> Result<(), sqlx::Error> {
+    let sql = if is_resolved_status(status) {
+        "UPDATE tickets SET resolved_at = COALESCE(resolved_at, NOW()) WHERE id = $1"
+    } else {
+        "UPDATE tickets SET resolved_at = NULL WHERE id = $1 AND resolved_at IS NOT NULL"
+    };
+    sqlx::query(sql).bind(ticket_id).execute(executor).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::Tz;
+    use resolve_shared::BusinessHours;
+
+    fn sla(business_hours_only: bool) -> ResolvedSla {
+        ResolvedSla {
+            source: SlaSource::Global,
+            policy_id: Some(Uuid::new_v4()),
+            sla_id: None,
+            response_minutes: 120,
+            resolution_minutes: 16 * 60,
+            business_hours_only,
+        }
+    }
+
+    fn weekdays_9_to_5() -> BusinessCalendar {
+        let hours: Vec<_> = (1..=5)
+            .map(|day| BusinessHours {
+                id: Uuid::new_v4(),
+                client_id: None,
+                day_of_week: day,
+                start_time: chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                end_time: chrono::NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+                timezone: "UTC".to_string(),
+                created_at: Utc::now(),
+            })
+            .collect();
+        BusinessCalendar::new(Tz::UTC, &hours, [])
+    }
+
+    #[test]
+    fn test_due_dates_follow_the_business_hours_flag() {
+        let calendar = weekdays_9_to_5();
+        // Thursday 2024-03-14 15:00
+        let opened = Utc.with_ymd_and_hms(2024, 3, 14, 15, 0, 0).unwrap();
+
+        let due = due_dates(&sla(true), &calendar, opened);
+        assert_eq!(due.response_due, Utc.with_ymd_and_hms(2024, 3, 14, 17, 0, 0).unwrap());
+        // Two hours Thursday, eight Friday, six Monday
+        assert_eq!(due.resolution_due, Utc.with_ymd_and_hms(2024, 3, 18, 15, 0, 0).unwrap());
+
+        let due = due_dates(&sla(false), &calendar, opened);
+        assert_eq!(due.response_due, Utc.with_ymd_and_hms(2024, 3, 14, 17, 0, 0).unwrap());
+        assert_eq!(due.resolution_due, Utc.with_ymd_and_hms(2024, 3, 15, 7, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_resolved_statuses() {
+        assert!(is_resolved_status("resolved"));
+        assert!(is_resolved_status("closed"));
+        assert!(!is_resolved_status("open"));
+        assert!(!is_resolved_status("pending"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Ticket status changes made by the system
+//!
+//! Jobs, integrations and workflows that move a ticket to another status
+//! go through [`change_status`], so the change leaves the same trail as one
+//! made in the app: a `status_change` entry in the ticket's history,
+//! `resolved_at` and `closed_at` kept in step, an audit entry, and the
+//! usual notifications to staff and the client.
+
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::audit::{self, AuditEvent, RequestMeta};
+use crate::notifications;
+use crate::services::ticket_sla;
+use resolve_shared::{TextEnum, TicketStatus};
+
+/// The built-in "Automation" user system status changes are attributed to
+pub const AUTOMATION_USER_ID: Uuid = Uuid::from_u128(0xa070);
+
+/// A status change that was made
+#[derive(Debug, Clone)]
+pub struct StatusChange {
+    pub ticket_id: Uuid,
+    pub from: String,
+    pub to: TicketStatus,
+}
+
+impl StatusChange {
+    pub fn message(&self) -> String {
+        format!("Status changed from {} to {}.", self.from, self.to)
+    }
+}
+
+/// Move a ticket to `status` on behalf of `changed_by`, with `reason`
+/// recorded in its history. Returns `None` if the ticket doesn't exist or
+/// already has that status. Notification failures are logged; the change
+/// stands.
+pub async fn change_status(
+    pool: &PgPool,
+    ticket_id: Uuid,
+    status: TicketStatus,
+    changed_by: Uuid,
+    reason: &str,
+) -> Result<Option<StatusChange>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let current: Option<(String, Uuid)> =
+        sqlx::query_as("SELECT COALESCE(status, 'open'), client_id FROM tickets WHERE id = $1 FOR UPDATE")
+            .bind(ticket_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let Some((from, client_id)) = current else {
+        return Ok(None);
+    };
+    if from == status.as_str() {
+        return Ok(None);
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE tickets SET
+            status = $2,
+            closed_at = CASE WHEN $2 = 'closed' THEN COALESCE(closed_at, NOW()) ELSE NULL END,
+            updated_at = NOW()
+        WHERE id = $1
+        "#
+    )
+    .bind(ticket_id)
+    .bind(status.as_str())
+    .execute(&mut *tx)
+    .await?;
+    ticket_sla::record_status(&mut *tx, ticket_id, status.as_str()).await?;
+
+    let change = StatusChange { ticket_id, from, to: status };
+    let message = change.message();
+    sqlx::query(
+        "INSERT INTO ticket_replies (ticket_id, user_id, type, details, internal)
+         VALUES ($1, $2, 'status_change', $3, true)",
+    )
+    .bind(ticket_id)
+    .bind(changed_by)
+    .bind(format!("{} {}", message, reason.trim()).trim_end())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    audit::record(
+        pool,
+        &RequestMeta::default(),
+        AuditEvent::new(changed_by, "UPDATE", "ticket", ticket_id)
+            .before(&json!({ "status": change.from }))
+            .after(&json!({ "status": change.to })),
+    )
+    .await;
+
+    if let Err(e) = notifications::notify_ticket_update(pool, ticket_id, client_id, "Status Changed", &message).await {
+        tracing::error!("Error notifying staff of ticket {} status change: {}", ticket_id, e);
+    }
+    if let Err(e) = notifications::email_ticket_update(pool, ticket_id, &message).await {
+        tracing::error!("Error emailing ticket {} update: {}", ticket_id, e);
+    }
+
+    Ok(Some(change))
+}
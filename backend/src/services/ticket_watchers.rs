@@ -0,0 +1,80 @@
+//! Ticket watchers
+//!
+//! Users subscribed to a ticket's updates. Each watcher row carries its own
+//! `notification_preferences`, which `notify_ticket_update` checks before
+//! notifying the watcher.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TicketWatcher {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub email: String,
+    pub notification_preferences: serde_json::Value,
+    pub added_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn list_watchers(pool: &PgPool, ticket_id: Uuid) -> Result<Vec<TicketWatcher>, sqlx::Error> {
+    sqlx::query_as::<_, TicketWatcher>(
+        r#"
+        SELECT
+            w.user_id,
+            u.first_name || ' ' || u.last_name as user_name,
+            u.email,
+            COALESCE(w.notification_preferences, '{}'::jsonb) as notification_preferences,
+            w.added_by,
+            COALESCE(w.created_at, NOW()) as created_at
+        FROM ticket_watchers w
+        JOIN users u ON w.user_id = u.id
+        WHERE w.ticket_id = $1
+        ORDER BY w.created_at, u.last_name, u.first_name
+        "#
+    )
+    .bind(ticket_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Subscribe `user_id` to the ticket. Returns `false` if they were already
+/// watching, in which case the existing row is left untouched.
+pub async fn add_watcher(
+    pool: &PgPool,
+    ticket_id: Uuid,
+    user_id: Uuid,
+    added_by: Uuid,
+    notification_preferences: Option<serde_json::Value>,
+) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO ticket_watchers (ticket_id, user_id, added_by, notification_preferences)
+        VALUES ($1, $2, $3, COALESCE($4, '{"status_changes": true, "new_replies": true, "assignments": true}'::jsonb))
+        ON CONFLICT (ticket_id, user_id) DO NOTHING
+        "#
+    )
+    .bind(ticket_id)
+    .bind(user_id)
+    .bind(added_by)
+    .bind(notification_preferences)
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(inserted > 0)
+}
+
+/// Unsubscribe `user_id` from the ticket. Returns `false` if they weren't watching.
+pub async fn remove_watcher(pool: &PgPool, ticket_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let removed = sqlx::query("DELETE FROM ticket_watchers WHERE ticket_id = $1 AND user_id = $2")
+        .bind(ticket_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(removed > 0)
+}
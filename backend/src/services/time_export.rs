@@ -0,0 +1,320 @@
+//! Time entry export
+//!
+//! Completed time entries for a period as CSV, for payroll and client
+//! reporting, or as an iCalendar feed with one event per entry. Rows are
+//! read from the database as a stream and written out as they arrive, so a
+//! large export never sits in memory. Running timers have no end yet and
+//! are left out.
+
+use axum::body::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::stream::{self, Stream};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::handlers::time_tracking::{TimeEntryWithDetails, TIME_ENTRY_DETAILS_QUERY};
+use crate::services::report_export::write_csv_record;
+
+/// Chunks buffered ahead of a slow client before reading pauses
+const CHANNEL_CAPACITY: usize = 32;
+
+pub const CSV_HEADERS: [&str; 13] = [
+    "user",
+    "client",
+    "ticket",
+    "project",
+    "task",
+    "description",
+    "start",
+    "end",
+    "duration_minutes",
+    "billable_minutes",
+    "billable",
+    "hourly_rate",
+    "amount",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeExportFormat {
+    #[default]
+    Csv,
+    Ics,
+}
+
+impl TimeExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ics => "ics",
+        }
+    }
+}
+
+/// Entries to export: those starting on `from` through `to`, inclusive, in UTC
+#[derive(Debug, Clone)]
+pub struct TimeExportFilters {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// `None` exports every user's entries
+    pub user_id: Option<Uuid>,
+    pub client_id: Option<Uuid>,
+}
+
+fn ticket_label(entry: &TimeEntryWithDetails) -> Option<String> {
+    match (entry.ticket_number, &entry.ticket_subject) {
+        (Some(number), Some(subject)) => Some(format!("#{} {}", number, subject)),
+        (Some(number), None) => Some(format!("#{}", number)),
+        (None, subject) => subject.clone(),
+    }
+}
+
+pub fn csv_header() -> String {
+    let mut out = String::new();
+    write_csv_record(&mut out, &CSV_HEADERS.map(String::from));
+    out
+}
+
+pub fn csv_row(entry: &TimeEntryWithDetails) -> String {
+    let text = |value: Option<String>| value.unwrap_or_default();
+    let fields = [
+        entry.user_name.clone(),
+        text(entry.client_name.clone()),
+        text(ticket_label(entry)),
+        text(entry.project_name.clone()),
+        text(entry.task_name.clone()),
+        text(entry.description.clone()),
+        entry.start_time.to_rfc3339(),
+        text(entry.end_time.map(|t| t.to_rfc3339())),
+        text(entry.duration_minutes.map(|m| m.to_string())),
+        text(entry.billable_minutes.map(|m| m.to_string())),
+        entry.billable.to_string(),
+        text(entry.hourly_rate.map(|r| r.to_string())),
+        text(entry.total_amount.map(|a| a.to_string())),
+    ];
+
+    let mut out = String::new();
+    write_csv_record(&mut out, &fields);
+    out
+}
+
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value (RFC 5545 §3.3.11)
+fn ics_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line at 75 octets (RFC 5545 §3.1), never inside a character
+fn ics_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+pub fn ics_header() -> String {
+    let mut out = String::new();
+    for line in ["BEGIN:VCALENDAR", "VERSION:2.0", "PRODID:-//Resolve//Time Entries//EN", "CALSCALE:GREGORIAN"] {
+        ics_line(&mut out, line);
+    }
+    out
+}
+
+pub fn ics_footer() -> String {
+    "END:VCALENDAR\r\n".to_string()
+}
+
+/// The entry as a VEVENT, or nothing for a timer still running
+pub fn ics_event(entry: &TimeEntryWithDetails, stamp: DateTime<Utc>) -> String {
+    let Some(end_time) = entry.end_time else {
+        return String::new();
+    };
+
+    let summary = ticket_label(entry)
+        .or_else(|| entry.project_name.clone())
+        .or_else(|| entry.description.clone())
+        .unwrap_or_else(|| "Time entry".to_string());
+    let mut description = vec![format!("User: {}", entry.user_name)];
+    description.extend(entry.client_name.as_ref().map(|c| format!("Client: {}", c)));
+    description.extend(entry.task_name.as_ref().map(|t| format!("Task: {}", t)));
+    description.extend(entry.description.clone());
+    description.push(format!("Billable: {}", if entry.billable { "yes" } else { "no" }));
+
+    let mut out = String::new();
+    ics_line(&mut out, "BEGIN:VEVENT");
+    ics_line(&mut out, &format!("UID:time-entry-{}@resolve", entry.id));
+    ics_line(&mut out, &format!("DTSTAMP:{}", ics_time(stamp)));
+    ics_line(&mut out, &format!("DTSTART:{}", ics_time(entry.start_time)));
+    ics_line(&mut out, &format!("DTEND:{}", ics_time(end_time)));
+    ics_line(&mut out, &format!("SUMMARY:{}", ics_text(&summary)));
+    ics_line(&mut out, &format!("DESCRIPTION:{}", ics_text(&description.join("\n"))));
+    if let Some(client_name) = &entry.client_name {
+        ics_line(&mut out, &format!("CATEGORIES:{}", ics_text(client_name)));
+    }
+    ics_line(&mut out, "END:VEVENT");
+    out
+}
+
+/// The export as a stream of body chunks. A database error part way through
+/// ends the stream with an error, so the client sees a failed download
+/// rather than a silently short file.
+pub fn export(
+    pool: PgPool,
+    filters: TimeExportFilters,
+    format: TimeExportFormat,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send {
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+    tokio::spawn(async move {
+        let stamp = Utc::now();
+        let header = match format {
+            TimeExportFormat::Csv => csv_header(),
+            TimeExportFormat::Ics => ics_header(),
+        };
+        if sender.send(Ok(Bytes::from(header))).await.is_err() {
+            return;
+        }
+
+        let sql = format!(
+            "{TIME_ENTRY_DETAILS_QUERY}
+             WHERE te.end_time IS NOT NULL
+               AND te.start_time::date >= $1 AND te.start_time::date <= $2
+               AND ($3::uuid IS NULL OR te.user_id = $3)
+               AND ($4::uuid IS NULL OR COALESCE(t.client_id, p.client_id) = $4)
+             ORDER BY te.start_time, te.id"
+        );
+        let mut rows = sqlx::query_as::<_, TimeEntryWithDetails>(&sql)
+            .bind(filters.from)
+            .bind(filters.to)
+            .bind(filters.user_id)
+            .bind(filters.client_id)
+            .fetch(&pool);
+
+        loop {
+            let chunk = match rows.try_next().await {
+                Ok(Some(entry)) => match format {
+                    TimeExportFormat::Csv => csv_row(&entry),
+                    TimeExportFormat::Ics => ics_event(&entry, stamp),
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!("Error exporting time entries: {}", e);
+                    let _ = sender.send(Err(std::io::Error::other("time entry export failed"))).await;
+                    return;
+                }
+            };
+            // The client went away
+            if sender.send(Ok(Bytes::from(chunk))).await.is_err() {
+                return;
+            }
+        }
+
+        if format == TimeExportFormat::Ics {
+            let _ = sender.send(Ok(Bytes::from(ics_footer()))).await;
+        }
+    });
+
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+
+    fn entry() -> TimeEntryWithDetails {
+        let start = Utc.with_ymd_and_hms(2024, 5, 14, 9, 15, 0).unwrap();
+        TimeEntryWithDetails {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            user_name: "Sam Tech".to_string(),
+            ticket_id: Some(Uuid::nil()),
+            ticket_number: Some(1042),
+            ticket_subject: Some("Printer offline, again".to_string()),
+            project_id: None,
+            project_name: None,
+            task_id: None,
+            task_name: None,
+            client_id: Some(Uuid::nil()),
+            client_name: Some("Acme; Ltd".to_string()),
+            start_time: start,
+            end_time: Some(start + chrono::Duration::minutes(45)),
+            duration_minutes: Some(45),
+            billable_minutes: Some(60),
+            description: Some("Cleared the spooler".to_string()),
+            billable: true,
+            billed: false,
+            hourly_rate: Some(Decimal::new(12000, 2)),
+            total_amount: Some(Decimal::new(12000, 2)),
+            created_at: start,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_csv_row_matches_headers() {
+        assert_eq!(
+            csv_header(),
+            "user,client,ticket,project,task,description,start,end,duration_minutes,billable_minutes,billable,hourly_rate,amount\r\n"
+        );
+        assert_eq!(
+            csv_row(&entry()),
+            "Sam Tech,Acme; Ltd,\"#1042 Printer offline, again\",,,Cleared the spooler,\
+             2024-05-14T09:15:00+00:00,2024-05-14T10:00:00+00:00,45,60,true,120.00,120.00\r\n"
+        );
+    }
+
+    #[test]
+    fn test_ics_event_times_and_escaping() {
+        let stamp = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let event = ics_event(&entry(), stamp);
+        let lines: Vec<&str> = event.split("\r\n").collect();
+
+        assert_eq!(lines[0], "BEGIN:VEVENT");
+        assert!(lines.contains(&"DTSTART:20240514T091500Z"));
+        assert!(lines.contains(&"DTEND:20240514T100000Z"));
+        assert!(lines.contains(&"DTSTAMP:20240601T000000Z"));
+        assert!(lines.contains(&"SUMMARY:#1042 Printer offline\\, again"));
+        assert!(lines.contains(&"CATEGORIES:Acme\\; Ltd"));
+
+        let running = TimeEntryWithDetails { end_time: None, ..entry() };
+        assert!(ics_event(&running, stamp).is_empty());
+    }
+
+    #[test]
+    fn test_ics_lines_fold_at_75_octets() {
+        let mut out = String::new();
+        ics_line(&mut out, &format!("SUMMARY:{}", "é".repeat(60)));
+        for line in out.trim_end_matches("\r\n").split("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!(out.replace("\r\n ", ""), format!("SUMMARY:{}\r\n", "é".repeat(60)));
+    }
+}
@@ -0,0 +1,216 @@
+//! Overlapping time entry detection
+//!
+//! Entries are half-open `[start_time, end_time)` intervals, so one ending
+//! exactly when the next starts does not overlap it. A running timer's
+//! interval is open-ended. Overlaps are reported as warnings unless
+//! `TIME_OVERLAP_STRICT` is set, in which case callers reject the entry.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+/// Whether overlapping entries are rejected rather than warned about
+/// (`TIME_OVERLAP_STRICT`, default off)
+pub fn strict_mode() -> bool {
+    std::env::var("TIME_OVERLAP_STRICT")
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+/// Another entry intersecting the one being checked
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OverlappingEntry {
+    pub id: Uuid,
+    pub ticket_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub description: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Minutes shared with the checked entry
+    #[sqlx(skip)]
+    pub overlap_minutes: i64,
+}
+
+/// Two of a user's entries that intersect
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlapPair {
+    pub first: OverlappingEntry,
+    pub second: OverlappingEntry,
+    pub overlap_minutes: i64,
+}
+
+/// Whether `[a_start, a_end)` and `[b_start, b_end)` share any time. A
+/// missing end is open-ended.
+pub fn intervals_overlap(
+    a_start: DateTime<Utc>,
+    a_end: Option<DateTime<Utc>>,
+    b_start: DateTime<Utc>,
+    b_end: Option<DateTime<Utc>>,
+) -> bool {
+    a_end.map_or(true, |end| b_start < end) && b_end.map_or(true, |end| a_start < end)
+}
+
+/// Whole minutes the two intervals share, with open ends cut off at `now`
+pub fn overlap_minutes(
+    a_start: DateTime<Utc>,
+    a_end: Option<DateTime<Utc>>,
+    b_start: DateTime<Utc>,
+    b_end: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> i64 {
+    let start = a_start.max(b_start);
+    let end = a_end.unwrap_or(now).min(b_end.unwrap_or(now));
+    (end - start).num_minutes().max(0)
+}
+
+const OVERLAP_COLUMNS: &str = "id, ticket_id, project_id, description, start_time, end_time";
+
+/// Hold the user's row for the rest of the transaction, so concurrent saves
+/// of their entries check for overlaps one at a time
+pub async fn lock_user(conn: &mut PgConnection, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR NO KEY UPDATE")
+        .bind(user_id)
+        .fetch_optional(conn)
+        .await?;
+    Ok(())
+}
+
+/// The user's entries intersecting `[start, end)`, excluding `exclude`
+/// (the entry being checked, when it already exists)
+pub async fn find_overlaps<'e>(
+    executor: impl PgExecutor<'e>,
+    user_id: Uuid,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+    exclude: Option<Uuid>,
+) -> Result<Vec<OverlappingEntry>, sqlx::Error> {
+    let mut entries = sqlx::query_as::<_, OverlappingEntry>(&format!(
+        r#"
+        SELECT {OVERLAP_COLUMNS}
+        FROM time_entries
+        WHERE user_id = $1
+          AND ($4::uuid IS NULL OR id <> $4)
+          AND tstzrange(start_time, COALESCE(end_time, 'infinity'::timestamptz), '[)')
+              && tstzrange($2, COALESCE($3, 'infinity'::timestamptz), '[)')
+        ORDER BY start_time
+        "#
+    ))
+    .bind(user_id)
+    .bind(start)
+    .bind(end)
+    .bind(exclude)
+    .fetch_all(executor)
+    .await?;
+
+    let now = Utc::now();
+    for entry in &mut entries {
+        entry.overlap_minutes = overlap_minutes(entry.start_time, entry.end_time, start, end, now);
+    }
+    Ok(entries)
+}
+
+/// Every overlapping pair among the user's entries touching `date` (UTC)
+pub async fn overlaps_on(pool: &PgPool, user_id: Uuid, date: NaiveDate) -> Result<Vec<OverlapPair>, sqlx::Error> {
+    let day_start = date.and_hms_opt(0, 0, 0).expect("midnight is valid").and_utc();
+    let day_end = day_start + Duration::days(1);
+
+    let entries = sqlx::query_as::<_, OverlappingEntry>(&format!(
+        r#"
+        SELECT {OVERLAP_COLUMNS}
+        FROM time_entries
+        WHERE user_id = $1
+          AND tstzrange(start_time, COALESCE(end_time, 'infinity'::timestamptz), '[)')
+              && tstzrange($2, $3, '[)')
+        ORDER BY start_time, id
+        "#
+    ))
+    .bind(user_id)
+    .bind(day_start)
+    .bind(day_end)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(pair_overlaps(&entries, Utc::now()))
+}
+
+/// Overlapping pairs among `entries`, which must be sorted by start time
+fn pair_overlaps(entries: &[OverlappingEntry], now: DateTime<Utc>) -> Vec<OverlapPair> {
+    let mut pairs = Vec::new();
+    for (i, first) in entries.iter().enumerate() {
+        for second in &entries[i + 1..] {
+            // Later entries start even later, so none of them can overlap
+            if first.end_time.is_some_and(|end| second.start_time >= end) {
+                break;
+            }
+            if intervals_overlap(first.start_time, first.end_time, second.start_time, second.end_time) {
+                pairs.push(OverlapPair {
+                    first: first.clone(),
+                    second: second.clone(),
+                    overlap_minutes: overlap_minutes(
+                        first.start_time, first.end_time, second.start_time, second.end_time, now,
+                    ),
+                });
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 4, hour, minute, 0).unwrap()
+    }
+
+    fn entry(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> OverlappingEntry {
+        OverlappingEntry {
+            id: Uuid::new_v4(),
+            ticket_id: None,
+            project_id: None,
+            description: None,
+            start_time: start,
+            end_time: end,
+            overlap_minutes: 0,
+        }
+    }
+
+    #[test]
+    fn test_intervals_overlap_boundaries() {
+        assert!(intervals_overlap(at(9, 0), Some(at(10, 0)), at(9, 30), Some(at(11, 0))));
+        assert!(intervals_overlap(at(9, 0), Some(at(12, 0)), at(10, 0), Some(at(11, 0))));
+        // Back-to-back entries share only the boundary instant
+        assert!(!intervals_overlap(at(9, 0), Some(at(10, 0)), at(10, 0), Some(at(11, 0))));
+        assert!(!intervals_overlap(at(10, 0), Some(at(11, 0)), at(9, 0), Some(at(10, 0))));
+        // A running timer overlaps anything after its start
+        assert!(intervals_overlap(at(9, 0), None, at(15, 0), Some(at(16, 0))));
+        assert!(!intervals_overlap(at(9, 0), None, at(8, 0), Some(at(9, 0))));
+    }
+
+    #[test]
+    fn test_overlap_minutes() {
+        let now = at(18, 0);
+        assert_eq!(overlap_minutes(at(9, 0), Some(at(10, 0)), at(9, 45), Some(at(11, 0)), now), 15);
+        assert_eq!(overlap_minutes(at(9, 0), Some(at(10, 0)), at(10, 0), Some(at(11, 0)), now), 0);
+        assert_eq!(overlap_minutes(at(17, 0), None, at(16, 0), None, now), 60);
+    }
+
+    #[test]
+    fn test_pair_overlaps() {
+        let entries = vec![
+            entry(at(9, 0), Some(at(10, 0))),
+            entry(at(9, 30), Some(at(10, 30))),
+            entry(at(10, 30), Some(at(11, 0))),
+            entry(at(10, 45), None),
+        ];
+        let pairs = pair_overlaps(&entries, at(12, 0));
+        let found: Vec<(Uuid, Uuid, i64)> = pairs.iter().map(|p| (p.first.id, p.second.id, p.overlap_minutes)).collect();
+        assert_eq!(
+            found,
+            vec![(entries[0].id, entries[1].id, 30), (entries[2].id, entries[3].id, 15)]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Rounding rules for billable time
+//!
+//! Clients can bill time in increments (up to the next 15 minutes, tenths
+//! of an hour) with a minimum charge; an active contract's rule overrides
+//! its client's. The raw `duration_minutes` is never changed: the rounded
+//! result goes to `billable_minutes`, which `total_amount` is priced on.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingDirection {
+    #[default]
+    Up,
+    Nearest,
+    Down,
+}
+
+impl RoundingDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoundingDirection::Up => "up",
+            RoundingDirection::Nearest => "nearest",
+            RoundingDirection::Down => "down",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "up" => Some(RoundingDirection::Up),
+            "nearest" => Some(RoundingDirection::Nearest),
+            "down" => Some(RoundingDirection::Down),
+            _ => None,
+        }
+    }
+}
+
+/// A client's or contract's `time_rounding` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TimeRounding {
+    /// Billing increment in minutes; none bills the raw minutes
+    pub increment_minutes: Option<i32>,
+    #[serde(default)]
+    pub direction: RoundingDirection,
+    /// Least that any worked time is billed as
+    pub minimum_minutes: Option<i32>,
+}
+
+impl TimeRounding {
+    /// Whether the rule changes anything
+    pub fn is_set(&self) -> bool {
+        self.increment_minutes.is_some_and(|i| i > 1) || self.minimum_minutes.is_some_and(|m| m > 0)
+    }
+
+    /// Billable minutes for `raw` worked minutes. No time worked is never
+    /// billed, whatever the minimum.
+    pub fn apply(&self, raw: i32) -> i32 {
+        if raw <= 0 {
+            return 0;
+        }
+        let rounded = match self.increment_minutes {
+            Some(increment) if increment > 1 => {
+                let whole = raw / increment;
+                let rest = raw % increment;
+                let round_up = match self.direction {
+                    RoundingDirection::Up => rest > 0,
+                    RoundingDirection::Nearest => rest * 2 >= increment,
+                    RoundingDirection::Down => false,
+                };
+                (whole + i32::from(round_up)) * increment
+            }
+            _ => raw,
+        };
+        rounded.max(self.minimum_minutes.unwrap_or(0))
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RoundingRow {
+    time_rounding_increment: Option<i32>,
+    time_rounding_direction: String,
+    time_rounding_minimum: Option<i32>,
+}
+
+impl From<RoundingRow> for TimeRounding {
+    fn from(row: RoundingRow) -> Self {
+        TimeRounding {
+            increment_minutes: row.time_rounding_increment,
+            direction: RoundingDirection::parse(&row.time_rounding_direction).unwrap_or_default(),
+            minimum_minutes: row.time_rounding_minimum,
+        }
+    }
+}
+
+/// The rule for `client_id`'s time worked on `date`: the latest-starting
+/// active contract covering the date that has one, else the client's own
+pub async fn rule_for(
+    conn: &mut PgConnection,
+    client_id: Uuid,
+    date: NaiveDate,
+) -> Result<Option<TimeRounding>, sqlx::Error> {
+    let row = sqlx::query_as::<_, RoundingRow>(
+        r#"
+        SELECT time_rounding_increment, time_rounding_direction, time_rounding_minimum
+        FROM (
+            SELECT time_rounding_increment, time_rounding_direction, time_rounding_minimum,
+                   0 AS precedence, start_date
+            FROM contracts
+            WHERE client_id = $1 AND status = 'active'
+              AND start_date <= $2 AND (end_date IS NULL OR end_date >= $2)
+              AND (time_rounding_increment IS NOT NULL OR time_rounding_minimum IS NOT NULL)
+            UNION ALL
+            SELECT time_rounding_increment, time_rounding_direction, time_rounding_minimum,
+                   1 AS precedence, NULL
+            FROM clients
+            WHERE id = $1
+              AND (time_rounding_increment IS NOT NULL OR time_rounding_minimum IS NOT NULL)
+        ) rules
+        ORDER BY precedence, start_date DESC NULLS LAST
+        LIMIT 1
+        "#
+    )
+    .bind(client_id)
+    .bind(date)
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(TimeRounding::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(increment: Option<i32>, direction: RoundingDirection, minimum: Option<i32>) -> TimeRounding {
+        TimeRounding { increment_minutes: increment, direction, minimum_minutes: minimum }
+    }
+
+    #[test]
+    fn test_round_up() {
+        let r = rule(Some(15), RoundingDirection::Up, None);
+        assert_eq!(r.apply(1), 15);
+        assert_eq!(r.apply(15), 15);
+        assert_eq!(r.apply(16), 30);
+        assert_eq!(r.apply(44), 45);
+    }
+
+    #[test]
+    fn test_round_nearest() {
+        let r = rule(Some(15), RoundingDirection::Nearest, None);
+        assert_eq!(r.apply(7), 0);
+        assert_eq!(r.apply(8), 15);
+        assert_eq!(r.apply(22), 15);
+        assert_eq!(r.apply(23), 30);
+        // Exactly half way rounds up
+        assert_eq!(rule(Some(6), RoundingDirection::Nearest, None).apply(9), 12);
+    }
+
+    #[test]
+    fn test_round_down() {
+        let r = rule(Some(6), RoundingDirection::Down, None);
+        assert_eq!(r.apply(5), 0);
+        assert_eq!(r.apply(6), 6);
+        assert_eq!(r.apply(17), 12);
+    }
+
+    #[test]
+    fn test_minimum_floor() {
+        let r = rule(Some(15), RoundingDirection::Down, Some(30));
+        assert_eq!(r.apply(10), 30);
+        assert_eq!(r.apply(44), 30);
+        assert_eq!(r.apply(50), 45);
+        // No work, no charge
+        assert_eq!(r.apply(0), 0);
+
+        let minimum_only = rule(None, RoundingDirection::Up, Some(60));
+        assert!(minimum_only.is_set());
+        assert_eq!(minimum_only.apply(20), 60);
+        assert_eq!(minimum_only.apply(61), 61);
+    }
+
+    #[test]
+    fn test_no_rule_keeps_raw_minutes() {
+        let r = TimeRounding::default();
+        assert!(!r.is_set());
+        assert_eq!(r.apply(37), 37);
+        assert_eq!(rule(Some(1), RoundingDirection::Up, None).apply(37), 37);
+    }
+}
@@ -0,0 +1,246 @@
+//! Running timers for time entries
+//!
+//! A timer is a time entry with no `end_time`. Each user has at most one,
+//! which a partial unique index enforces. Starting a timer while another
+//! runs stops the old one first and marks it `auto_stopped`; with
+//! `TIME_TIMER_AUTO_STOP=false` the start is refused instead. Stopping
+//! sets the duration in whole minutes and prices billable time, after the
+//! client's rounding rule, at the entry's rate, else the user's
+//! `hourly_rate`, else [`DEFAULT_HOURLY_RATE`].
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::services::time_rounding;
+
+/// Rate used when neither the entry nor the user has one
+pub const DEFAULT_HOURLY_RATE: i64 = 75;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TimerError {
+    #[error("A timer is already running")]
+    AlreadyRunning(Uuid),
+    #[error("No running timer")]
+    NotRunning,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Whether starting a timer stops the running one (`TIME_TIMER_AUTO_STOP`,
+/// default on)
+pub fn auto_stop_enabled() -> bool {
+    std::env::var("TIME_TIMER_AUTO_STOP")
+        .map(|v| !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no" | "off"))
+        .unwrap_or(true)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StartTimer {
+    pub ticket_id: Option<Uuid>,
+    pub project_id: Option<Uuid>,
+    pub task_id: Option<Uuid>,
+    pub description: Option<String>,
+    pub billable: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Started {
+    pub timer_id: Uuid,
+    /// The timer that was running and got stopped to make way
+    pub stopped_id: Option<Uuid>,
+}
+
+/// Whole minutes between `start` and `end`, never negative
+pub fn duration_minutes(start: DateTime<Utc>, end: DateTime<Utc>) -> i32 {
+    (end - start).num_minutes().max(0) as i32
+}
+
+/// Amount for `minutes` at `hourly_rate`, rounded to cents. Non-billable
+/// time is worth nothing.
+pub fn billable_amount(minutes: i32, hourly_rate: Decimal, billable: bool) -> Decimal {
+    if !billable {
+        return Decimal::ZERO;
+    }
+    (hourly_rate * Decimal::from(minutes) / Decimal::from(60)).round_dp(2)
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.code().as_deref() == Some("23505"))
+}
+
+/// Price a finished entry: apply its client's rounding rule to the raw
+/// duration for `billable_minutes`, then bill those at the entry's rate,
+/// else the user's, else the default
+pub async fn price_entry(conn: &mut PgConnection, entry_id: Uuid) -> Result<(), sqlx::Error> {
+    let (start_time, raw_minutes, billable, rate, client_id) =
+        sqlx::query_as::<_, (DateTime<Utc>, Option<i32>, bool, Decimal, Option<Uuid>)>(
+            r#"
+            SELECT te.start_time, te.duration_minutes, COALESCE(te.billable, true),
+                   COALESCE(te.hourly_rate, u.hourly_rate, $2),
+                   COALESCE(t.client_id, p.client_id)
+            FROM time_entries te
+            JOIN users u ON u.id = te.user_id
+            LEFT JOIN tickets t ON t.id = te.ticket_id
+            LEFT JOIN projects p ON p.id = te.project_id
+            WHERE te.id = $1
+            "#
+        )
+        .bind(entry_id)
+        .bind(Decimal::from(DEFAULT_HOURLY_RATE))
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let raw_minutes = raw_minutes.unwrap_or(0);
+    let rule = match client_id {
+        Some(client_id) => time_rounding::rule_for(&mut *conn, client_id, start_time.date_naive()).await?,
+        None => None,
+    };
+    let billable_minutes = rule.map_or(raw_minutes, |r| r.apply(raw_minutes));
+
+    sqlx::query(
+        "UPDATE time_entries SET hourly_rate = $2, billable_minutes = $3, total_amount = $4 WHERE id = $1"
+    )
+    .bind(entry_id)
+    .bind(rate)
+    .bind(billable_minutes)
+    .bind(billable_amount(billable_minutes, rate, billable))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Close `entry_id` at `end_time` and price it. `auto_stopped` marks
+/// timers stopped by another start rather than by the user.
+async fn close_entry(
+    tx: &mut Transaction<'_, Postgres>,
+    entry_id: Uuid,
+    end_time: DateTime<Utc>,
+    auto_stopped: bool,
+) -> Result<(), sqlx::Error> {
+    let start_time = sqlx::query_scalar::<_, DateTime<Utc>>("SELECT start_time FROM time_entries WHERE id = $1")
+        .bind(entry_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE time_entries SET
+            end_time = $2, duration_minutes = $3, auto_stopped = $4, updated_at = NOW()
+        WHERE id = $1
+        "#
+    )
+    .bind(entry_id)
+    .bind(end_time)
+    .bind(duration_minutes(start_time, end_time))
+    .bind(auto_stopped)
+    .execute(&mut **tx)
+    .await?;
+
+    price_entry(&mut **tx, entry_id).await
+}
+
+/// Start a timer for `user_id`. A running timer is stopped when
+/// `auto_stop` is set, otherwise it's an [`TimerError::AlreadyRunning`].
+pub async fn start(pool: &PgPool, user_id: Uuid, timer: StartTimer, auto_stop: bool) -> Result<Started, TimerError> {
+    let mut tx = pool.begin().await?;
+    let now = Utc::now();
+
+    let running = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM time_entries WHERE user_id = $1 AND end_time IS NULL FOR UPDATE"
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(running_id) = running {
+        if !auto_stop {
+            return Err(TimerError::AlreadyRunning(running_id));
+        }
+        close_entry(&mut tx, running_id, now, true).await?;
+    }
+
+    let timer_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO time_entries (user_id, ticket_id, project_id, task_id, start_time, description, billable)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#
+    )
+    .bind(user_id)
+    .bind(timer.ticket_id)
+    .bind(timer.project_id)
+    .bind(timer.task_id)
+    .bind(now)
+    .bind(&timer.description)
+    .bind(timer.billable)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        // A concurrent start for the same user got there first
+        if is_unique_violation(&e) {
+            TimerError::AlreadyRunning(Uuid::nil())
+        } else {
+            TimerError::Database(e)
+        }
+    })?;
+
+    tx.commit().await?;
+    Ok(Started { timer_id, stopped_id: running })
+}
+
+/// Stop the user's running timer, or `timer_id` when given
+pub async fn stop(pool: &PgPool, user_id: Uuid, timer_id: Option<Uuid>) -> Result<Uuid, TimerError> {
+    let mut tx = pool.begin().await?;
+
+    let running = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT id FROM time_entries
+        WHERE user_id = $1 AND end_time IS NULL AND ($2::uuid IS NULL OR id = $2)
+        FOR UPDATE
+        "#
+    )
+    .bind(user_id)
+    .bind(timer_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(TimerError::NotRunning)?;
+
+    close_entry(&mut tx, running, Utc::now(), false).await?;
+    tx.commit().await?;
+    Ok(running)
+}
+
+/// The user's running timer, if any
+pub async fn running_timer_id(pool: &PgPool, user_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT id FROM time_entries WHERE user_id = $1 AND end_time IS NULL")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_duration_minutes() {
+        let start = Utc::now();
+        assert_eq!(duration_minutes(start, start + Duration::seconds(90 * 60 + 59)), 90);
+        assert_eq!(duration_minutes(start, start + Duration::seconds(59)), 0);
+        // Clock skew never produces negative time
+        assert_eq!(duration_minutes(start, start - Duration::minutes(5)), 0);
+    }
+
+    #[test]
+    fn test_billable_amount() {
+        let rate = Decimal::from_str("120.00").unwrap();
+        assert_eq!(billable_amount(90, rate, true), Decimal::from_str("180.00").unwrap());
+        assert_eq!(billable_amount(10, Decimal::from(100), true), Decimal::from_str("16.67").unwrap());
+        assert_eq!(billable_amount(90, rate, false), Decimal::ZERO);
+        assert_eq!(billable_amount(0, rate, true), Decimal::ZERO);
+    }
+}
@@ -0,0 +1,130 @@
+//! Graceful shutdown
+//!
+//! On SIGTERM or SIGINT the server stops accepting connections and gives
+//! in-flight requests up to `SHUTDOWN_TIMEOUT_SECS` (default 30) to finish.
+//! `main` then stops the job scheduler, closes WebSocket connections and
+//! closes the database pool last, each bounded by the same timeout.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// How long each shutdown phase may take
+pub fn timeout_from_env() -> Duration {
+    let secs = std::env::var("SHUTDOWN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Termination signals, registered as soon as this is created so a signal
+/// arriving during startup isn't lost
+pub struct Signal {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl Signal {
+    pub fn install() -> std::io::Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            terminate: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?,
+        })
+    }
+
+    /// Resolves on the first SIGTERM or SIGINT
+    pub async fn recv(mut self) -> &'static str {
+        #[cfg(unix)]
+        let terminate = self.terminate.recv();
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<Option<()>>();
+
+        tokio::select! {
+            _ = terminate => "SIGTERM",
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+        }
+    }
+
+    /// Cancel `token` when a signal arrives
+    pub fn cancel_on_signal(self, token: CancellationToken) {
+        tokio::spawn(async move {
+            let signal = self.recv().await;
+            tracing::info!("Received {}, shutting down", signal);
+            token.cancel();
+        });
+    }
+}
+
+/// Run the server until it exits. Once `shutdown` is cancelled the server
+/// (started with `with_graceful_shutdown` on the same token) has `drain`
+/// to finish in-flight requests before they're abandoned.
+pub async fn serve_until_drained<F>(server: F, shutdown: CancellationToken, drain: Duration) -> std::io::Result<()>
+where
+    F: Future<Output = std::io::Result<()>>,
+{
+    tokio::pin!(server);
+    tokio::select! {
+        result = &mut server => return result,
+        _ = shutdown.cancelled() => {}
+    }
+
+    match tokio::time::timeout(drain, server).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!("In-flight requests still running after {:?}; shutting down anyway", drain);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_signal_resolves_on_sigterm() {
+        let signal = Signal::install().unwrap();
+        let status = std::process::Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let received = tokio::time::timeout(Duration::from_secs(5), signal.recv()).await;
+        assert_eq!(received.unwrap(), "SIGTERM");
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_requests_finish_within_the_drain_window() {
+        let shutdown = CancellationToken::new();
+        let finished = Arc::new(AtomicBool::new(false));
+        let server = {
+            let (shutdown, finished) = (shutdown.clone(), finished.clone());
+            async move {
+                shutdown.cancelled().await;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                finished.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+
+        shutdown.cancel();
+        serve_until_drained(server, shutdown, Duration::from_secs(5)).await.unwrap();
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_drain_is_bounded_by_the_timeout() {
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+
+        let started = std::time::Instant::now();
+        serve_until_drained(std::future::pending(), shutdown, Duration::from_millis(50)).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
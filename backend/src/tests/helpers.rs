@@ -79,6 +79,22 @@ pub async fn count_table_rows(pool: &sqlx::PgPool, table: &str) -> i64 {
         .unwrap_or(0)
 }
 
+/// Insert an active user and sign a real token for them, for tests that go
+/// through the `AuthUser` extractor
+pub async fn create_user_with_token(pool: &sqlx::PgPool) -> (resolve_shared::User, String) {
+    let user = sqlx::query_as::<_, resolve_shared::User>(
+        "INSERT INTO users (email, password_hash, first_name, last_name)
+         VALUES ($1, 'x', 'Test', 'User') RETURNING *"
+    )
+    .bind(format!("user-{}@resolve.test", Uuid::new_v4()))
+    .fetch_one(pool)
+    .await
+    .expect("Failed to insert test user");
+    let token = crate::auth::jwt::create_jwt(&user).expect("Failed to sign test JWT").token;
+
+    (user, token)
+}
+
 pub async fn table_exists(pool: &sqlx::PgPool, table: &str) -> bool {
     sqlx::query_scalar::<_, bool>(
         "SELECT EXISTS (SELECT 1 FROM information_schema.tables WHERE table_name = $1)"
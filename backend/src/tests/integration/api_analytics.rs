@@ -1,4 +1,4 @@
-// Integration tests for Analytics API endpoints
+// Integration tests for Analytics API endpoints and scheduled report delivery
 
 use axum::{
     body::Body,
@@ -78,3 +78,434 @@ mod analytics_integration_tests {
         // Admin should see all data
     }
 }
+
+#[cfg(test)]
+mod resolution_time_tests {
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::handlers::analytics::{
+        compute_sla_by_priority, compute_sla_by_technician, compute_utilization, DEFAULT_TREND_THRESHOLD,
+    };
+    use crate::tests::TestContext;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, day, hour, 0, 0).unwrap()
+    }
+
+    fn period() -> (NaiveDate, NaiveDate) {
+        (
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        )
+    }
+
+    /// One technician with two resolved tickets (4h and 22h) and one still open
+    async fn seed_tickets(pool: &PgPool) -> Uuid {
+        let tech_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash, first_name, last_name, role)
+             VALUES ($1, 'x', 'Tess', 'Tech', 'technician') RETURNING id"
+        )
+        .bind(format!("tech-{}@resolve.test", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Acme') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        let policy_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO sla_policies (name, priority_levels, business_hours)
+             VALUES ('Standard', '{}', '{}') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let tickets = [
+            (at(10, 8), Some(at(10, 12))),
+            (at(11, 9), Some(at(12, 7))),
+            (at(15, 9), None),
+        ];
+
+        for (created_at, resolved_at) in tickets {
+            let ticket_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO tickets (client_id, opened_by, assigned_to, subject, details, priority,
+                                      sla_policy_id, created_at, resolved_at)
+                 VALUES ($1, $2, $2, 'Printer offline', 'details', 'high', $3, $4, $5)
+                 RETURNING id"
+            )
+            .bind(client_id)
+            .bind(tech_id)
+            .bind(policy_id)
+            .bind(created_at)
+            .bind(resolved_at)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+            // Utilization only reports technicians with logged time
+            sqlx::query(
+                "INSERT INTO time_entries (ticket_id, user_id, start_time, end_time, duration_minutes, billable, total_amount)
+                 VALUES ($1, $2, $3, $3 + INTERVAL '1 hour', 60, true, 120)"
+            )
+            .bind(ticket_id)
+            .bind(tech_id)
+            .bind(created_at)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        tech_id
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_utilization_averages_resolved_tickets_only() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        let tech_id = seed_tickets(&ctx.db_pool).await;
+        let (from, to) = period();
+
+        let report = compute_utilization(&ctx.db_pool, from, to, DEFAULT_TREND_THRESHOLD).await.unwrap();
+        let tech = report.technicians.iter().find(|t| t.user_id == tech_id).unwrap();
+
+        assert_eq!(tech.tickets_resolved, 2);
+        assert_eq!(tech.avg_resolution_time_hours, Some(Decimal::from(13)));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_sla_breakdowns_average_resolution_minutes() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        let tech_id = seed_tickets(&ctx.db_pool).await;
+        let (from, to) = period();
+
+        let by_priority = compute_sla_by_priority(&ctx.db_pool, from, to).await.unwrap();
+        let high = by_priority.iter().find(|p| p.priority == "high").unwrap();
+        assert_eq!(high.total_tickets, 3);
+        assert_eq!(high.avg_resolution_time_minutes, 13 * 60);
+
+        let by_technician = compute_sla_by_technician(&ctx.db_pool, from, to).await.unwrap();
+        let tech = by_technician.iter().find(|t| t.user_id == tech_id).unwrap();
+        assert_eq!(tech.avg_resolution_time_minutes, 13 * 60);
+    }
+}
+
+#[cfg(test)]
+mod cost_rate_tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use crate::handlers::analytics::compute_client_profitability;
+    use crate::tests::TestContext;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_labor_cost_blends_per_user_cost_rates() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        let pool = &ctx.db_pool;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Acme') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        // Junior at $40/h for 60 min, senior at $80/h for 90 min, and a user
+        // with no rate who falls back to the $50 org default for 30 min
+        let users = [(Some(Decimal::from(40)), 60), (Some(Decimal::from(80)), 90), (None, 30)];
+        let started = Utc.with_ymd_and_hms(2024, 1, 10, 9, 0, 0).unwrap();
+
+        for (cost_rate, minutes) in users {
+            let user_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO users (email, password_hash, first_name, last_name, cost_rate)
+                 VALUES ($1, 'x', 'Test', 'Tech', $2) RETURNING id"
+            )
+            .bind(format!("tech-{}@resolve.test", Uuid::new_v4()))
+            .bind(cost_rate)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+            let ticket_id: Uuid = sqlx::query_scalar(
+                "INSERT INTO tickets (client_id, opened_by, subject, details)
+                 VALUES ($1, $2, 'Server patching', 'details') RETURNING id"
+            )
+            .bind(client_id)
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+            sqlx::query(
+                "INSERT INTO time_entries (ticket_id, user_id, start_time, end_time, duration_minutes)
+                 VALUES ($1, $2, $3, $3 + make_interval(mins => $4), $4)"
+            )
+            .bind(ticket_id)
+            .bind(user_id)
+            .bind(started)
+            .bind(minutes)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        let profitability = compute_client_profitability(pool, client_id, from, to).await.unwrap();
+
+        // 40 * 1.0 + 80 * 1.5 + 50 * 0.5
+        assert_eq!(profitability.labor_cost.round_dp(2), Decimal::from(185));
+        assert_eq!(profitability.labor_hours, Decimal::from(3));
+        assert_eq!(profitability.gross_profit.round_dp(2), Decimal::from(-185));
+    }
+}
+
+#[cfg(test)]
+mod at_risk_tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::handlers::analytics::compute_profitability;
+    use crate::tests::TestContext;
+
+    /// Client with one invoice and one hour of labor at $50/h
+    async fn seed_client(pool: &PgPool, name: &str, invoiced: Decimal) -> Uuid {
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ($1) RETURNING id")
+            .bind(name)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        sqlx::query(
+            "INSERT INTO invoices (client_id, number, date, due_date, total)
+             VALUES ($1, $2, '2024-01-05', '2024-02-04', $3)"
+        )
+        .bind(client_id)
+        .bind(format!("INV-{}", &Uuid::new_v4().simple().to_string()[..8]))
+        .bind(invoiced)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let tech_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash, first_name, last_name, cost_rate)
+             VALUES ($1, 'x', 'Test', 'Tech', 50) RETURNING id"
+        )
+        .bind(format!("tech-{}@resolve.test", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let ticket_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO tickets (client_id, opened_by, subject, details)
+             VALUES ($1, $2, 'Onboarding', 'details') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(tech_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        sqlx::query(
+            "INSERT INTO time_entries (ticket_id, user_id, start_time, end_time, duration_minutes)
+             VALUES ($1, $2, $3, $3 + INTERVAL '1 hour', 60)"
+        )
+        .bind(ticket_id)
+        .bind(tech_id)
+        .bind(Utc.with_ymd_and_hms(2024, 1, 10, 9, 0, 0).unwrap())
+        .execute(pool)
+        .await
+        .unwrap();
+
+        client_id
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_at_risk_clients_are_high_risk_only() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        let pool = &ctx.db_pool;
+
+        // 95% margin vs 10% margin
+        let healthy = seed_client(pool, "Healthy Co", Decimal::from(1000)).await;
+        let struggling = seed_client(pool, "Struggling Co", Decimal::from(55)).await;
+
+        let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        let report = compute_profitability(pool, from, to).await.unwrap();
+
+        assert!(report.at_risk_clients.iter().all(|c| c.risk_level == "high"));
+        assert!(report.at_risk_clients.iter().any(|c| c.client_id == struggling));
+        assert!(!report.at_risk_clients.iter().any(|c| c.client_id == healthy));
+    }
+}
+
+#[cfg(test)]
+mod scheduled_report_tests {
+    use async_trait::async_trait;
+    use chrono::{Duration, TimeZone, Utc};
+    use sqlx::PgPool;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    use crate::services::email::EmailAttachment;
+    use crate::services::scheduled_reports::{run_due, run_content, ReportMailer, ScheduledReportRun, RUN_COLUMNS};
+    use crate::tests::TestContext;
+
+    /// Records what would have been emailed, optionally failing every send
+    #[derive(Default)]
+    struct MockMailer {
+        sent: Mutex<Vec<(String, String, String)>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl ReportMailer for MockMailer {
+        async fn send_report(
+            &self,
+            to_email: &str,
+            subject: &str,
+            _html_body: &str,
+            _text_body: &str,
+            attachment: &EmailAttachment,
+        ) -> Result<(), String> {
+            if self.fail {
+                return Err("SMTP unavailable".to_string());
+            }
+            self.sent.lock().unwrap().push((to_email.to_string(), subject.to_string(), attachment.filename.clone()));
+            Ok(())
+        }
+    }
+
+    async fn seed_report(pool: &PgPool, next_run_at: chrono::DateTime<Utc>, recipients: &[&str]) -> (Uuid, Uuid) {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Summary Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO contacts (client_id, name, email, is_primary) VALUES ($1, 'Pat Primary', 'pat@summary.example', true)")
+            .bind(client_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        let recipients: Vec<String> = recipients.iter().map(|r| r.to_string()).collect();
+        let report_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO scheduled_reports (name, report_type, client_id, cadence, recipients, format, next_run_at)
+             VALUES ('Monthly summary', 'client_summary', $1, 'monthly', $2, 'csv', $3)
+             RETURNING id"
+        )
+        .bind(client_id)
+        .bind(&recipients)
+        .bind(next_run_at)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (client_id, report_id)
+    }
+
+    async fn runs(pool: &PgPool, report_id: Uuid) -> Vec<ScheduledReportRun> {
+        sqlx::query_as::<_, ScheduledReportRun>(&format!(
+            "SELECT {} FROM scheduled_report_runs WHERE scheduled_report_id = $1",
+            RUN_COLUMNS
+        ))
+        .bind(report_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_due_report_is_rendered_recorded_and_emailed() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 6, 5, 0).unwrap();
+        let (_, report_id) = seed_report(pool, now - Duration::minutes(5), &["ops@summary.example"]).await;
+        let (_, later_id) = seed_report(pool, now + Duration::days(1), &["ops@summary.example"]).await;
+
+        let mailer = MockMailer::default();
+        let summary = run_due(pool, &mailer, now).await.unwrap();
+        assert_eq!((summary.due, summary.completed, summary.failed), (1, 1, 0));
+
+        let sent = mailer.sent.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "ops@summary.example");
+        assert!(sent[0].1.starts_with("Monthly summary: Summary Co"));
+        assert_eq!(sent[0].2, "client-summary-2024-02-01-to-2024-02-29.csv");
+
+        let recorded = runs(pool, report_id).await;
+        assert_eq!(recorded.len(), 1);
+        let run = &recorded[0];
+        assert_eq!(run.status, "completed");
+        assert_eq!(run.recipients_sent, 1);
+        assert_eq!(run.download_url.as_deref(), Some(format!("/api/v1/reporting/scheduled-reports/runs/{}/download", run.id).as_str()));
+        let (_, content_type, content) = run_content(pool, run.id).await.unwrap().unwrap();
+        assert!(content_type.starts_with("text/csv"));
+        assert!(String::from_utf8(content).unwrap().starts_with("metric,value"));
+
+        let next_run_at: chrono::DateTime<Utc> = sqlx::query_scalar("SELECT next_run_at FROM scheduled_reports WHERE id = $1")
+            .bind(report_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(next_run_at, Utc.with_ymd_and_hms(2024, 4, 1, 6, 0, 0).unwrap());
+        assert!(runs(pool, later_id).await.is_empty());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_client_summary_falls_back_to_primary_contact() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 6, 5, 0).unwrap();
+        seed_report(pool, now - Duration::minutes(5), &[]).await;
+
+        let mailer = MockMailer::default();
+        run_due(pool, &mailer, now).await.unwrap();
+
+        let sent = mailer.sent.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "pat@summary.example");
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_failed_delivery_is_recorded_and_rescheduled() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 6, 5, 0).unwrap();
+        let (_, report_id) = seed_report(pool, now - Duration::minutes(5), &["ops@summary.example"]).await;
+
+        let mailer = MockMailer { fail: true, ..Default::default() };
+        let summary = run_due(pool, &mailer, now).await.unwrap();
+        assert_eq!(summary.failed, 1);
+
+        let run = &runs(pool, report_id).await[0];
+        assert_eq!(run.status, "failed");
+        assert_eq!(run.recipients_sent, 0);
+        assert!(run.error.as_deref().unwrap().contains("SMTP unavailable"));
+        // The output is kept so it can still be downloaded
+        assert!(run.download_url.is_some());
+
+        // Not retried until the next slot
+        let again = run_due(pool, &mailer, now + Duration::minutes(15)).await.unwrap();
+        assert_eq!(again.due, 0);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,506 @@
+// Integration tests for asset import
+
+#[cfg(test)]
+mod asset_import_tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::asset_import::{import_assets, RowStatus};
+    use crate::tests::TestContext;
+
+    async fn seed_client(pool: &PgPool) -> Uuid {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Import Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO locations (client_id, name) VALUES ($1, 'Head Office')")
+            .bind(client_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO contacts (client_id, name) VALUES ($1, 'Pat Doyle')")
+            .bind(client_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        client_id
+    }
+
+    async fn asset_count(pool: &PgPool, client_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM assets WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_clean_import_resolves_references() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let client_id = seed_client(pool).await;
+
+        let csv = "name,asset_type,ip,mac,location,contact,purchase_date\n\
+                   FW-01,firewall,10.0.0.1,00:1a:2b:3c:4d:5e,head office,Pat Doyle,2023-04-01\n\
+                   SW-01,switch,10.0.0.2,,,,\n";
+        let report = import_assets(pool, client_id, csv, false).await.unwrap();
+
+        assert_eq!((report.total, report.created, report.invalid), (2, 2, 0));
+        assert!(report.rows.iter().all(|r| r.status == RowStatus::Created && r.warnings.is_empty()));
+
+        let (location, contact, ip): (Option<String>, Option<String>, Option<String>) = sqlx::query_as(
+            "SELECT l.name, c.name, a.ip::text FROM assets a
+             LEFT JOIN locations l ON a.location_id = l.id
+             LEFT JOIN contacts c ON a.contact_id = c.id
+             WHERE a.id = $1"
+        )
+        .bind(report.rows[0].id.unwrap())
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(location.as_deref(), Some("Head Office"));
+        assert_eq!(contact.as_deref(), Some("Pat Doyle"));
+        assert_eq!(ip.as_deref(), Some("10.0.0.1/32"));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_partially_invalid_file_imports_valid_rows() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let client_id = seed_client(pool).await;
+
+        let csv = "name,asset_type,ip,location\n\
+                   FW-01,firewall,10.0.0.1,Basement\n\
+                   ,switch,10.0.0.2,\n\
+                   AP-01,access_point,not-an-ip,\n\
+                   NAS-01,storage,,\n";
+        let report = import_assets(pool, client_id, csv, false).await.unwrap();
+
+        assert_eq!((report.total, report.created, report.invalid), (4, 2, 2));
+        assert_eq!(report.rows[0].warnings, vec!["location 'Basement' not found; left unset".to_string()]);
+        assert_eq!(report.rows[1].line, 3);
+        assert_eq!(report.rows[1].errors[0].field, "name");
+        assert_eq!(report.rows[2].line, 4);
+        assert_eq!(report.rows[2].errors[0].field, "ip");
+        assert_eq!(report.rows[3].status, RowStatus::Created);
+        assert_eq!(asset_count(pool, client_id).await, 2);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_dry_run_validates_without_inserting() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let client_id = seed_client(pool).await;
+
+        let csv = "name,asset_type,install_date\nFW-01,firewall,2024-01-15\nSW-01,switch,15/01/2024\n";
+        let report = import_assets(pool, client_id, csv, true).await.unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!((report.total, report.created, report.invalid), (2, 0, 1));
+        assert_eq!(report.rows[0].status, RowStatus::Valid);
+        assert!(report.rows[0].id.is_none());
+        assert_eq!(report.rows[1].errors[0].field, "install_date");
+        assert_eq!(asset_count(pool, client_id).await, 0);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod warranty_alert_tests {
+    use chrono::{Duration, NaiveDate};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::asset_warranty::check_warranties;
+    use crate::tests::TestContext;
+
+    async fn alert_count(pool: &PgPool, asset_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM alerts WHERE asset_id = $1 AND alert_type = 'warranty_expiring'")
+            .bind(asset_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_one_alert_per_threshold_crossing() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let thresholds = [30, 14, 7];
+
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Warranty Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let asset_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO assets (client_id, name, asset_type, warranty_expire) VALUES ($1, 'FS-01', 'server', $2) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(today + Duration::days(10))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let first = check_warranties(pool, today, &thresholds).await.unwrap();
+        assert_eq!(first.alerts.len(), 1);
+        assert_eq!(first.alerts[0].threshold_days, 14);
+        assert_eq!(first.alerts[0].days_remaining, 10);
+
+        let (severity, message) = sqlx::query_as::<_, (String, String)>("SELECT severity, message FROM alerts WHERE id = $1")
+            .bind(first.alerts[0].alert_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(severity, "high");
+        assert!(message.contains("FS-01") && message.contains("Warranty Co") && message.contains("10 day(s)"));
+
+        // Later runs inside the same threshold don't alert again
+        let repeat = check_warranties(pool, today, &thresholds).await.unwrap();
+        assert!(repeat.alerts.is_empty());
+        let next_day = check_warranties(pool, today + Duration::days(1), &thresholds).await.unwrap();
+        assert!(next_day.alerts.is_empty());
+        assert_eq!(alert_count(pool, asset_id).await, 1);
+
+        // Crossing the 7-day threshold raises one more
+        let crossed = check_warranties(pool, today + Duration::days(3), &thresholds).await.unwrap();
+        assert_eq!(crossed.alerts.len(), 1);
+        assert_eq!(crossed.alerts[0].threshold_days, 7);
+        assert_eq!(alert_count(pool, asset_id).await, 2);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod asset_custom_field_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::asset_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    /// A layout for a fresh asset type with a required text field and a
+    /// bounded number field
+    async fn seed_layout(pool: &PgPool) -> String {
+        let asset_type = format!("rack-{}", &Uuid::new_v4().to_string()[..8]);
+        let layout_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO asset_layouts (name, asset_type) VALUES ('Rack', $1) RETURNING id"
+        )
+        .bind(&asset_type)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO asset_layout_fields (layout_id, field_type_id, field_name, display_name, is_required, display_order)
+             SELECT $1, id, 'cabinet', 'Cabinet', true, 1 FROM asset_field_types WHERE name = 'text'"
+        )
+        .bind(layout_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO asset_layout_fields (layout_id, field_type_id, field_name, display_name, validation_rules, display_order)
+             SELECT $1, id, 'rack_units', 'Rack units', '{\"min\": 1, \"max\": 48}', 2 FROM asset_field_types WHERE name = 'number'"
+        )
+        .bind(layout_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        asset_type
+    }
+
+    async fn post_asset(ctx: &TestContext, token: &str, body: Value) -> (StatusCode, Value) {
+        let state = Arc::new(AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None });
+        let app = Router::new().nest("/assets", asset_routes()).with_state(state);
+        let request = Request::builder()
+            .method("POST")
+            .uri("/assets")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    async fn client(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Rack Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_type_mismatch_is_rejected() {
+        let ctx = TestContext::new().await;
+        let asset_type = seed_layout(&ctx.db_pool).await;
+        let client_id = client(&ctx.db_pool).await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+
+        let (status, body) = post_asset(&ctx, &token, json!({
+            "client_id": client_id,
+            "name": "RK-01",
+            "asset_type": asset_type,
+            "status": "active",
+            "custom_fields": { "cabinet": "B2", "rack_units": "forty-two" },
+        }))
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "VALIDATION_ERROR");
+        assert!(body["details"]["custom_fields.rack_units"][0].as_str().unwrap().contains("must be a number"));
+        assert!(body["details"]["custom_fields.cabinet"].is_null());
+
+        // Nothing is stored for a rejected asset
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM assets WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_one(&ctx.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, 0);
+
+        // A valid value is stored and rendered back with the field definitions
+        let (status, body) = post_asset(&ctx, &token, json!({
+            "client_id": client_id,
+            "name": "RK-01",
+            "asset_type": asset_type,
+            "status": "active",
+            "custom_fields": { "cabinet": "B2", "rack_units": 42 },
+        }))
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let fields = body["custom_fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0]["field_name"], "cabinet");
+        assert_eq!(fields[0]["value"], "B2");
+        assert_eq!(fields[1]["kind"], "number");
+        assert_eq!(fields[1]["value"], 42);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_required_field_omission_is_rejected() {
+        let ctx = TestContext::new().await;
+        let asset_type = seed_layout(&ctx.db_pool).await;
+        let client_id = client(&ctx.db_pool).await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+
+        let (status, body) = post_asset(&ctx, &token, json!({
+            "client_id": client_id,
+            "name": "RK-02",
+            "asset_type": asset_type,
+            "status": "active",
+            "custom_fields": { "rack_units": 12 },
+        }))
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"]["custom_fields.cabinet"][0], "Cabinet is required");
+        assert!(body["details"]["custom_fields.rack_units"].is_null());
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod forticloud_sync_tests {
+    use chrono::{Duration, NaiveDate};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::services::forticloud_sync::{
+        sync_devices, FortiCloudClient, FortiCloudLogin, LICENSE_ALERT, OFFLINE_ALERT,
+    };
+    use crate::tests::TestContext;
+
+    async fn mount_token(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/oauth/token/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "token", "refresh_token": "refresh", "expires_in": 3600
+            })))
+            .mount(server)
+            .await;
+    }
+
+    async fn mount_devices(server: &MockServer, page: u32, total_pages: u32, devices: serde_json::Value) {
+        Mock::given(method("GET"))
+            .and(path("/devices"))
+            .and(query_param("page", page.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "devices": devices, "page": page, "total_pages": total_pages
+            })))
+            .mount(server)
+            .await;
+    }
+
+    fn client(server: &MockServer) -> FortiCloudClient {
+        let login = FortiCloudLogin { api_id: "api-user".to_string(), password: "secret".to_string(), client_id: None };
+        FortiCloudClient::new(reqwest::Client::new(), login, Some(&server.uri()), Some(&server.uri()))
+    }
+
+    async fn open_alerts(pool: &PgPool, client_id: Uuid, alert_type: &str) -> i64 {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM alerts al JOIN assets a ON al.asset_id = a.id
+             WHERE a.client_id = $1 AND al.alert_type = $2 AND NOT COALESCE(al.resolved, false)"
+        )
+        .bind(client_id)
+        .bind(alert_type)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_devices_upsert_as_firewalls_and_offline_devices_alert() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Forti Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        // A firewall already on file by serial is updated rather than duplicated
+        let existing_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO assets (client_id, name, asset_type, serial, notes) VALUES ($1, 'old-name', 'firewall', 'fgt60f0000000001', 'rack 2') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let server = MockServer::start().await;
+        mount_token(&server).await;
+        mount_devices(&server, 1, 2, json!([{
+            "sn": "FGT60F0000000001", "name": "hq-fw01", "model": "FortiGate-60F", "firmware": "v7.2.8",
+            "management_ip": "203.0.113.1", "connection_status": "online",
+            "licenses": [{ "name": "FortiGuard IPS", "expires": (today + Duration::days(12)).to_string() }]
+        }]))
+        .await;
+        mount_devices(&server, 2, 2, json!([{
+            "sn": "FGT40F0000000002", "name": "branch-fw", "model": "FortiGate-40F", "firmware": "v7.0.15",
+            "management_ip": "198.51.100.7", "connection_status": "offline", "last_seen": "2024-02-29T22:15:00Z",
+            "licenses": [{ "name": "FortiGuard Web Filtering", "expires": "2025-01-01" }]
+        }]))
+        .await;
+
+        let summary = sync_devices(pool, &client(&server), client_id, None, today).await.unwrap();
+        assert_eq!(summary.devices_seen, 2);
+        assert_eq!(summary.updated, 1);
+        assert_eq!(summary.created, 1);
+        assert_eq!(summary.offline_alerts, 1);
+        assert_eq!(summary.license_alerts, 1);
+        assert!(summary.errors.is_empty());
+
+        let (name, model, os, ip, notes) = sqlx::query_as::<_, (String, Option<String>, Option<String>, Option<String>, Option<String>)>(
+            "SELECT name, model, os, host(ip), notes FROM assets WHERE id = $1"
+        )
+        .bind(existing_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(name, "hq-fw01");
+        assert_eq!(model.as_deref(), Some("FortiGate-60F"));
+        assert_eq!(os.as_deref(), Some("FortiOS 7.2.8"));
+        assert_eq!(ip.as_deref(), Some("203.0.113.1"));
+        assert_eq!(notes.as_deref(), Some("rack 2"));
+
+        let (make, serial, asset_type) = sqlx::query_as::<_, (Option<String>, Option<String>, String)>(
+            "SELECT make, serial, asset_type FROM assets WHERE client_id = $1 AND name = 'branch-fw'"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(make.as_deref(), Some("Fortinet"));
+        assert_eq!(serial.as_deref(), Some("FGT40F0000000002"));
+        assert_eq!(asset_type, "firewall");
+
+        let (title, severity, message) = sqlx::query_as::<_, (String, String, String)>(
+            "SELECT al.title, al.severity, al.message FROM alerts al JOIN assets a ON al.asset_id = a.id
+             WHERE a.client_id = $1 AND al.alert_type = $2"
+        )
+        .bind(client_id)
+        .bind(OFFLINE_ALERT)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(title, "FortiGate offline: branch-fw");
+        assert_eq!(severity, "high");
+        assert!(message.contains("2024-02-29 22:15 UTC"));
+
+        // Seeing the devices again doesn't raise the same alerts twice
+        let repeat = sync_devices(pool, &client(&server), client_id, None, today).await.unwrap();
+        assert_eq!(repeat.created, 0);
+        assert_eq!(repeat.offline_alerts, 0);
+        assert_eq!(repeat.license_alerts, 0);
+        assert_eq!(open_alerts(pool, client_id, OFFLINE_ALERT).await, 1);
+        assert_eq!(open_alerts(pool, client_id, LICENSE_ALERT).await, 1);
+
+        // Back online resolves the offline alert
+        server.reset().await;
+        mount_token(&server).await;
+        mount_devices(&server, 1, 1, json!([{ "sn": "FGT40F0000000002", "name": "branch-fw", "connection_status": "online" }])).await;
+        let recovered = sync_devices(pool, &client(&server), client_id, None, today).await.unwrap();
+        assert_eq!(recovered.resolved_alerts, 1);
+        assert_eq!(open_alerts(pool, client_id, OFFLINE_ALERT).await, 0);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_incremental_sync_asks_for_changes_since_last_sync() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Forti Delta') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        let server = MockServer::start().await;
+        mount_token(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/devices"))
+            .and(query_param("updated_since", "2024-03-01T07:50:00+00:00"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "devices": [], "page": 1, "total_pages": 1 })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let last_sync = "2024-03-01T08:00:00Z".parse().unwrap();
+        let summary = sync_devices(pool, &client(&server), client_id, Some(last_sync), NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(summary.devices_seen, 0);
+
+        ctx.cleanup().await;
+    }
+}
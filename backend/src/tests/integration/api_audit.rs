@@ -0,0 +1,364 @@
+// Integration tests for audit log pagination and retention
+
+#[cfg(test)]
+mod audit_cursor_tests {
+    use sqlx::PgPool;
+    use std::collections::HashSet;
+    use uuid::Uuid;
+
+    use crate::pagination::PaginationParams;
+    use crate::services::audit_log_query::{search, AuditLogFilters};
+    use crate::tests::TestContext;
+
+    async fn log_entries(pool: &PgPool, entity_type: &str, count: usize) -> Vec<Uuid> {
+        let mut ids = Vec::new();
+        for _ in 0..count {
+            let id = sqlx::query_scalar::<_, Uuid>(
+                "INSERT INTO audit_logs (action, entity_type, entity_id, created_at)
+                 VALUES ('UPDATE', $1, $2, NOW()) RETURNING id"
+            )
+            .bind(entity_type)
+            .bind(Uuid::new_v4())
+            .fetch_one(pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        ids
+    }
+
+    fn filters(entity_type: &str) -> AuditLogFilters {
+        AuditLogFilters {
+            entity_type: Some(entity_type.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_inserts_mid_pagination_do_not_duplicate_rows() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let entity_type = format!("cursor-test-{}", Uuid::new_v4());
+
+        let original = log_entries(pool, &entity_type, 5).await;
+
+        let mut params = PaginationParams { per_page: 2, cursor: Some(String::new()), ..Default::default() };
+        let mut seen = Vec::new();
+        let mut pages = 0;
+        loop {
+            let page = search(pool, &filters(&entity_type), &params).await.unwrap();
+            seen.extend(page.data.iter().map(|e| e.id));
+            pages += 1;
+
+            // Newer entries arrive while the client is paging
+            if pages == 1 {
+                log_entries(pool, &entity_type, 3).await;
+            }
+
+            match page.meta.next_cursor {
+                Some(cursor) => params.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        let unique: HashSet<_> = seen.iter().collect();
+        assert_eq!(unique.len(), seen.len(), "a row was returned twice");
+        assert_eq!(seen.len(), 5);
+        assert!(original.iter().all(|id| unique.contains(id)));
+        assert_eq!(pages, 3);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_offset_mode_is_still_the_default() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let entity_type = format!("offset-test-{}", Uuid::new_v4());
+        log_entries(pool, &entity_type, 3).await;
+
+        let params = PaginationParams { per_page: 2, ..Default::default() };
+        let page = search(pool, &filters(&entity_type), &params).await.unwrap();
+        assert_eq!(page.data.len(), 2);
+        assert_eq!(page.meta.total, 3);
+        assert_eq!(page.meta.total_pages, 2);
+        assert!(page.meta.next_cursor.is_none());
+
+        let bad = PaginationParams { cursor: Some("garbage".to_string()), ..Default::default() };
+        assert!(search(pool, &filters(&entity_type), &bad).await.is_err());
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod audit_log_query_tests {
+    use chrono::{DateTime, Duration, Utc};
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::pagination::PaginationParams;
+    use crate::services::audit_log_query::{search, AuditLogFilters, FieldChange};
+    use crate::tests::TestContext;
+
+    async fn seed_user(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name)
+             VALUES ($1, 'x', 'Audit', 'Reader') RETURNING id"
+        )
+        .bind(format!("audit-{}@resolve.test", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn log(
+        pool: &PgPool,
+        user_id: Uuid,
+        entity_type: &str,
+        entity_id: Uuid,
+        action: &str,
+        (old_values, new_values): (Option<Value>, Option<Value>),
+        created_at: DateTime<Utc>,
+    ) {
+        sqlx::query(
+            "INSERT INTO audit_logs (user_id, action, entity_type, entity_id, old_values, new_values, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)"
+        )
+        .bind(user_id)
+        .bind(action)
+        .bind(entity_type)
+        .bind(entity_id)
+        .bind(old_values)
+        .bind(new_values)
+        .bind(created_at)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_entity_history_is_newest_first_with_changes() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let user_id = seed_user(pool).await;
+        let entity_type = format!("ticket-{}", Uuid::new_v4());
+        let ticket_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        log(pool, user_id, &entity_type, ticket_id, "create", (None, Some(json!({ "status": "open" }))), now - Duration::hours(2)).await;
+        log(
+            pool,
+            user_id,
+            &entity_type,
+            ticket_id,
+            "update",
+            (
+                Some(json!({ "status": "open", "priority": "low" })),
+                Some(json!({ "status": "closed", "priority": "low" })),
+            ),
+            now - Duration::hours(1),
+        )
+        .await;
+        // Same type, different entity
+        log(pool, user_id, &entity_type, Uuid::new_v4(), "create", (None, None), now).await;
+
+        let filters = AuditLogFilters {
+            entity_type: Some(entity_type.clone()),
+            entity_id: Some(ticket_id),
+            ..Default::default()
+        };
+        let page = search(pool, &filters, &PaginationParams::default()).await.unwrap();
+
+        assert_eq!(page.meta.total, 2);
+        assert_eq!(page.data[0].action, "update");
+        assert_eq!(page.data[1].action, "create");
+        assert_eq!(
+            page.data[0].changes,
+            vec![FieldChange { field: "status".into(), old: json!("open"), new: json!("closed") }]
+        );
+        assert_eq!(page.data[0].user_name.as_deref(), Some("Audit Reader"));
+        assert!(page.data[0].user_email.as_deref().is_some_and(|e| e.starts_with("audit-")));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_date_range_filter() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let user_id = seed_user(pool).await;
+        let entity_type = format!("asset-{}", Uuid::new_v4());
+        let base = Utc::now() - Duration::days(10);
+
+        for day in 0..5 {
+            log(pool, user_id, &entity_type, Uuid::new_v4(), "update", (None, None), base + Duration::days(day)).await;
+        }
+
+        // Days 1 through 3; `to` is exclusive
+        let filters = AuditLogFilters {
+            entity_type: Some(entity_type.clone()),
+            from: Some(base + Duration::days(1)),
+            to: Some(base + Duration::days(4)),
+            ..Default::default()
+        };
+        let page = search(pool, &filters, &PaginationParams::default()).await.unwrap();
+        assert_eq!(page.meta.total, 3);
+        assert!(page.data.windows(2).all(|w| w[0].created_at >= w[1].created_at));
+
+        let by_user = AuditLogFilters {
+            entity_type: Some(entity_type),
+            user_id: Some(user_id),
+            action: Some("UPDATE".into()),
+            ..Default::default()
+        };
+        assert_eq!(search(pool, &by_user, &PaginationParams::default()).await.unwrap().meta.total, 5);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod audit_snapshot_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::{handlers, AppState};
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_client_update_records_before_and_after() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+
+        let (user, token) = create_user_with_token(pool).await;
+
+        let client_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO clients (name, phone) VALUES ('Acme', '555-0100') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let state = Arc::new(AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None });
+        let app = handlers::client_routes().with_state(state);
+        let request = Request::builder()
+            .method("PUT")
+            .uri(format!("/{}", client_id))
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .header("x-forwarded-for", "198.51.100.4")
+            .header("user-agent", "audit-test")
+            .body(Body::from(json!({ "name": "Acme Corp" }).to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let (user_id, old_values, new_values, ip, agent) =
+            sqlx::query_as::<_, (Option<Uuid>, Option<Value>, Option<Value>, Option<String>, Option<String>)>(
+                "SELECT user_id, old_values, new_values, host(ip_address), user_agent
+                 FROM audit_logs WHERE entity_type = 'client' AND entity_id = $1 AND action = 'UPDATE'"
+            )
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        assert_eq!(user_id, Some(user.id));
+        let (old_values, new_values) = (old_values.unwrap(), new_values.unwrap());
+        assert_eq!(old_values["name"], "Acme");
+        assert_eq!(new_values["name"], "Acme Corp");
+        assert_eq!(old_values["phone"], new_values["phone"]);
+        assert_eq!(ip.as_deref(), Some("198.51.100.4"));
+        assert_eq!(agent.as_deref(), Some("audit-test"));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod audit_retention_tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use crate::services::data_retention::{self, RetentionEntity};
+    use crate::tests::TestContext;
+
+    async fn log_at(pool: &sqlx::PgPool, action: &str, days_ago: i64) -> Uuid {
+        let entity_id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO audit_logs (action, entity_type, entity_id, created_at)
+             VALUES ($1, 'retention_test', $2, $3)"
+        )
+        .bind(action)
+        .bind(entity_id)
+        .bind(Utc::now() - Duration::days(days_ago))
+        .execute(pool)
+        .await
+        .unwrap();
+        entity_id
+    }
+
+    async fn logged(pool: &sqlx::PgPool, entity_id: Uuid) -> bool {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM audit_logs WHERE entity_id = $1)")
+            .bind(entity_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_retention_run_purges_expired_audit_logs() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let policies = data_retention::list_policies(pool).await.unwrap();
+        for policy in &policies {
+            let entity = RetentionEntity::parse(&policy.entity_type).unwrap();
+            let enabled = entity == RetentionEntity::AuditLogs;
+            let days = if enabled { 30 } else { policy.retention_days };
+            data_retention::update_policy(pool, entity, days, enabled).await.unwrap();
+        }
+
+        let expired = log_at(pool, "UPDATE", 45).await;
+        let recent = log_at(pool, "UPDATE", 10).await;
+        let erasure = log_at(pool, "ANONYMIZE", 45).await;
+
+        let outcomes = data_retention::apply_policies(pool).await.unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].entity_type, RetentionEntity::AuditLogs);
+        assert!(outcomes[0].affected >= 1);
+
+        assert!(!logged(pool, expired).await);
+        assert!(logged(pool, recent).await);
+        // The record of an anonymization outlives the limit
+        assert!(logged(pool, erasure).await);
+
+        let policy = data_retention::list_policies(pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|p| p.entity_type == "audit_logs")
+            .unwrap();
+        assert!(policy.last_run_at.is_some());
+        assert_eq!(policy.last_affected, Some(outcomes[0].affected as i32));
+
+        for policy in policies {
+            let entity = RetentionEntity::parse(&policy.entity_type).unwrap();
+            data_retention::update_policy(pool, entity, policy.retention_days, policy.enabled).await.unwrap();
+        }
+        ctx.cleanup().await;
+    }
+}
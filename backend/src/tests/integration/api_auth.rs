@@ -0,0 +1,543 @@
+// Authentication API integration tests
+
+#[cfg(test)]
+mod password_rehash_tests {
+    use argon2::password_hash::PasswordHash;
+    use argon2::Params;
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::auth::{self, password};
+    use crate::config::Argon2Config;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        auth::auth_routes().with_state(Arc::new(state))
+    }
+
+    async fn login(app: &Router, email: &str, password: &str) -> StatusCode {
+        let body = serde_json::json!({ "email": email, "password": password });
+        let request = Request::builder()
+            .method("POST")
+            .uri("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    async fn stored_hash(pool: &PgPool, user_id: Uuid) -> String {
+        sqlx::query_scalar("SELECT password_hash FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_weak_hash_is_upgraded_on_login() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+
+        let weak = Argon2Config { memory_kib: 8, iterations: 1, parallelism: 1 };
+        let weak_hash = password::hash_password(PASSWORD, &weak).unwrap();
+        let email = format!("rehash-{}@resolve.test", Uuid::new_v4());
+        let user_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash, first_name, last_name) VALUES ($1, $2, 'Test', 'User') RETURNING id"
+        )
+        .bind(&email)
+        .bind(&weak_hash)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        assert_eq!(login(&app, &email, "wrong password").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(stored_hash(pool, user_id).await, weak_hash);
+
+        assert_eq!(login(&app, &email, PASSWORD).await, StatusCode::OK);
+
+        let upgraded = stored_hash(pool, user_id).await;
+        assert_ne!(upgraded, weak_hash);
+        let parsed = PasswordHash::new(&upgraded).unwrap();
+        let params = Params::try_from(&parsed).unwrap();
+        let current = Argon2Config::from_env();
+        assert_eq!(params.m_cost(), current.memory_kib);
+        assert_eq!(params.t_cost(), current.iterations);
+        assert!(!password::needs_rehash(&parsed, &current));
+
+        // The new hash still authenticates and isn't rewritten again
+        assert_eq!(login(&app, &email, PASSWORD).await, StatusCode::OK);
+        assert_eq!(stored_hash(pool, user_id).await, upgraded);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod email_verification_tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::auth::{self, email_verification};
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        auth::auth_routes().with_state(Arc::new(state))
+    }
+
+    async fn post(app: &Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn register(app: &Router, email: &str) -> (StatusCode, serde_json::Value) {
+        post(app, "/register", serde_json::json!({
+            "email": email, "password": PASSWORD, "first_name": "New", "last_name": "User"
+        }))
+        .await
+    }
+
+    async fn login(app: &Router, email: &str) -> (StatusCode, serde_json::Value) {
+        post(app, "/login", serde_json::json!({ "email": email, "password": PASSWORD })).await
+    }
+
+    async fn user_id(pool: &PgPool, email: &str) -> Uuid {
+        sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_login_blocked_until_verified() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let email = format!("verify-{}@resolve.test", Uuid::new_v4());
+
+        let (status, _) = register(&app, &email).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let (status, body) = login(&app, &email).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "email_not_verified");
+
+        let token = email_verification::create_verification(pool, user_id(pool, &email).await).await.unwrap();
+        let request = Request::builder()
+            .uri(format!("/verify-email?token={}", token))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+        let (status, body) = login(&app, &email).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["token"].is_string());
+
+        // Tokens are single use
+        let (status, body) = post(&app, "/verify-email", serde_json::json!({ "token": token })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "verification_invalid");
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_expired_token_is_rejected() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let email = format!("expired-{}@resolve.test", Uuid::new_v4());
+
+        register(&app, &email).await;
+        let user_id = user_id(pool, &email).await;
+        let token = email_verification::create_verification(pool, user_id).await.unwrap();
+        sqlx::query("UPDATE email_verifications SET expires_at = NOW() - INTERVAL '1 minute' WHERE user_id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let (status, body) = post(&app, "/verify-email", serde_json::json!({ "token": token })).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert_eq!(body["error"], "verification_expired");
+
+        let active: bool = sqlx::query_scalar("SELECT is_active FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert!(!active);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_register_does_not_reveal_existing_email() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let email = format!("taken-{}@resolve.test", Uuid::new_v4());
+
+        let first = register(&app, &email).await;
+        let second = register(&app, &email).await;
+        assert_eq!(first, second);
+
+        let accounts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(accounts, 1);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod password_reset_tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::auth::{self, password, password_reset};
+    use crate::config::Argon2Config;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    const OLD_PASSWORD: &str = "correct horse battery staple";
+    const NEW_PASSWORD: &str = "violet-kettle-harbour-1987!";
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        auth::auth_routes().with_state(Arc::new(state))
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn post(app: &Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        send(app, request).await
+    }
+
+    async fn login(app: &Router, email: &str, password: &str) -> (StatusCode, serde_json::Value) {
+        post(app, "/login", serde_json::json!({ "email": email, "password": password })).await
+    }
+
+    async fn reset(app: &Router, token: &str) -> (StatusCode, serde_json::Value) {
+        post(app, "/reset-password", serde_json::json!({ "token": token, "new_password": NEW_PASSWORD })).await
+    }
+
+    async fn seed_user(pool: &PgPool) -> (Uuid, String) {
+        let email = format!("reset-{}@resolve.test", Uuid::new_v4());
+        let hash = password::hash_password(OLD_PASSWORD, &Argon2Config::default()).unwrap();
+        let id = sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash, first_name, last_name) VALUES ($1, $2, 'Test', 'User') RETURNING id"
+        )
+        .bind(&email)
+        .bind(hash)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (id, email)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reset_changes_password_and_revokes_sessions() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (user_id, email) = seed_user(pool).await;
+
+        let (status, body) = login(&app, &email, OLD_PASSWORD).await;
+        assert_eq!(status, StatusCode::OK);
+        let old_token = body["token"].as_str().unwrap().to_string();
+
+        // Unknown and known emails get the same answer
+        let known = post(&app, "/forgot-password", serde_json::json!({ "email": email })).await;
+        let unknown = post(&app, "/forgot-password", serde_json::json!({ "email": "nobody@resolve.test" })).await;
+        assert_eq!(known.0, StatusCode::ACCEPTED);
+        assert_eq!(known, unknown);
+
+        // A token issued in an earlier second than the reset
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let token = password_reset::create_reset(pool, user_id).await.unwrap();
+
+        let (status, body) = post(&app, "/reset-password", serde_json::json!({ "token": token, "new_password": "password1" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "weak_password");
+
+        assert_eq!(reset(&app, &token).await.0, StatusCode::OK);
+        assert_eq!(login(&app, &email, OLD_PASSWORD).await.0, StatusCode::UNAUTHORIZED);
+        let (status, body) = login(&app, &email, NEW_PASSWORD).await;
+        assert_eq!(status, StatusCode::OK);
+        let new_token = body["token"].as_str().unwrap().to_string();
+
+        let me = Request::builder()
+            .uri("/me")
+            .header("authorization", format!("Bearer {}", old_token))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(&app, me).await.0, StatusCode::UNAUTHORIZED);
+
+        // Handlers that verify the bearer token themselves refuse it too
+        assert_eq!(auth::verify_token(pool, &old_token).await.err(), Some(StatusCode::UNAUTHORIZED));
+        assert!(auth::verify_token(pool, &new_token).await.is_ok());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_expired_reset_token_is_rejected() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (user_id, email) = seed_user(pool).await;
+
+        let token = password_reset::create_reset(pool, user_id).await.unwrap();
+        sqlx::query("UPDATE password_resets SET expires_at = NOW() - INTERVAL '1 minute' WHERE user_id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let (status, body) = reset(&app, &token).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert_eq!(body["error"], "reset_expired");
+        assert_eq!(login(&app, &email, OLD_PASSWORD).await.0, StatusCode::OK);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reset_token_is_single_use() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (user_id, _) = seed_user(pool).await;
+
+        let token = password_reset::create_reset(pool, user_id).await.unwrap();
+        let other = password_reset::create_reset(pool, user_id).await.unwrap();
+
+        assert_eq!(reset(&app, &token).await.0, StatusCode::OK);
+
+        let (status, body) = reset(&app, &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "reset_invalid");
+
+        // Completing a reset also burns the user's other outstanding tokens
+        assert_eq!(reset(&app, &other).await.0, StatusCode::BAD_REQUEST);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod api_key_scope_tests {
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::{header, Method, Request, StatusCode};
+    use axum::Router;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::auth;
+    use crate::handlers::ticket_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        Router::new()
+            .nest("/auth", auth::auth_routes())
+            .nest("/tickets", ticket_routes())
+            .with_state(Arc::new(state))
+    }
+
+    async fn send(app: &Router, method: Method, uri: &str, bearer: &str, body: Option<Value>) -> (StatusCode, Value) {
+        // Not from a trusted proxy, so the forwarded address is ignored
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", bearer))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-forwarded-for", "198.51.100.1")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 9], 40000))));
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    /// Create a key through the API, returning its id and secret
+    async fn create_key(app: &Router, jwt: &str, body: Value) -> (Uuid, String) {
+        let (status, created) = send(app, Method::POST, "/auth/api-keys", jwt, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", created);
+        (
+            created["id"].as_str().unwrap().parse().unwrap(),
+            created["key"].as_str().unwrap().to_string(),
+        )
+    }
+
+    async fn seed_ticket(pool: &PgPool, user_id: Uuid) -> Uuid {
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Keyed') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query_scalar(
+            "INSERT INTO tickets (client_id, opened_by, subject, details, status)
+             VALUES ($1, $2, 'Backup failed', 'Nightly job', 'open') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_read_scoped_key_cannot_write() {
+        let ctx = TestContext::new().await;
+        let app = app(&ctx.db_pool);
+        let (user, jwt) = create_user_with_token(&ctx.db_pool).await;
+        let ticket = seed_ticket(&ctx.db_pool, user.id).await;
+        let (key_id, key) = create_key(&app, &jwt, json!({ "name": "Reporting", "scopes": ["tickets:read"] })).await;
+        let uri = format!("/tickets/{}", ticket);
+
+        let (status, body) = send(&app, Method::GET, &uri, &key, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["subject"], "Backup failed");
+
+        let (status, body) = send(&app, Method::PUT, &uri, &key, Some(json!({ "subject": "Changed" }))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body["message"].as_str().unwrap().contains("tickets:write"));
+
+        // JWT callers are unaffected
+        let (status, _) = send(&app, Method::PUT, &uri, &jwt, Some(json!({ "subject": "Changed" }))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, listed) = send(&app, Method::GET, &format!("/auth/api-keys/{}", key_id), &jwt, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(listed["scopes"], json!(["tickets:read"]));
+        assert_eq!(listed["last_used_ip"], "203.0.113.9");
+        assert!(listed["last_used_at"].is_string());
+
+        // Revoked keys stop working but stay listed
+        let (status, _) = send(&app, Method::DELETE, &format!("/auth/api-keys/{}", key_id), &jwt, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::GET, &uri, &key, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (_, listed) = send(&app, Method::GET, &format!("/auth/api-keys/{}", key_id), &jwt, None).await;
+        assert!(listed["revoked_at"].is_string());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_expired_key_fails() {
+        let ctx = TestContext::new().await;
+        let app = app(&ctx.db_pool);
+        let (user, jwt) = create_user_with_token(&ctx.db_pool).await;
+        let ticket = seed_ticket(&ctx.db_pool, user.id).await;
+        let (key_id, key) = create_key(
+            &app,
+            &jwt,
+            json!({ "name": "Sync", "scopes": ["tickets:read", "tickets:write"], "expires_in_days": 30 }),
+        )
+        .await;
+        let uri = format!("/tickets/{}", ticket);
+
+        let (status, _) = send(&app, Method::PUT, &uri, &key, Some(json!({ "subject": "Synced" }))).await;
+        assert_eq!(status, StatusCode::OK);
+
+        sqlx::query("UPDATE api_keys SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+            .bind(key_id)
+            .execute(&ctx.db_pool)
+            .await
+            .unwrap();
+
+        let (status, body) = send(&app, Method::GET, &uri, &key, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body["message"].as_str().unwrap().contains("expired"));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_daily_quota_is_enforced() {
+        let ctx = TestContext::new().await;
+        let app = app(&ctx.db_pool);
+        let (user, jwt) = create_user_with_token(&ctx.db_pool).await;
+        let ticket = seed_ticket(&ctx.db_pool, user.id).await;
+        let (_, key) = create_key(&app, &jwt, json!({ "name": "Trial", "scopes": ["*"], "daily_quota": 2 })).await;
+        let uri = format!("/tickets/{}", ticket);
+
+        assert_eq!(send(&app, Method::GET, &uri, &key, None).await.0, StatusCode::OK);
+        assert_eq!(send(&app, Method::GET, &uri, &key, None).await.0, StatusCode::OK);
+        assert_eq!(send(&app, Method::GET, &uri, &key, None).await.0, StatusCode::TOO_MANY_REQUESTS);
+
+        ctx.cleanup().await;
+    }
+}
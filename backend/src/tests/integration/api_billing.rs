@@ -108,3 +108,447 @@ mod billing_integration_tests {
         // Should apply credit note to an invoice
     }
 }
+
+#[cfg(test)]
+mod stripe_webhook_tests {
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use crate::services::stripe_payments::{handle_event, StripeEvent, WebhookOutcome};
+    use crate::tests::TestContext;
+
+    fn succeeded_event(event_id: &str, invoice_id: Uuid, amount: i64) -> StripeEvent {
+        serde_json::from_value(serde_json::json!({
+            "id": event_id,
+            "type": "payment_intent.succeeded",
+            "data": {
+                "object": {
+                    "id": format!("pi_{}", event_id),
+                    "amount": amount,
+                    "amount_received": amount,
+                    "currency": "usd",
+                    "status": "succeeded",
+                    "metadata": { "invoice_id": invoice_id.to_string() }
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_payment_intent_succeeded_is_applied_once() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Stripe Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let invoice_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO invoices (client_id, number, date, due_date, total, balance, status)
+             VALUES ($1, $2, CURRENT_DATE, CURRENT_DATE + 30, 250.00, 250.00, 'sent') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(format!("INV-{}", Uuid::new_v4().simple()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let event = succeeded_event("evt_paid", invoice_id, 25000);
+        assert_eq!(handle_event(pool, &event).await.unwrap(), WebhookOutcome::Applied);
+        assert_eq!(handle_event(pool, &event).await.unwrap(), WebhookOutcome::Duplicate);
+
+        let (balance, status) = sqlx::query_as::<_, (Decimal, String)>("SELECT balance, status FROM invoices WHERE id = $1")
+            .bind(invoice_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(balance, Decimal::ZERO);
+        assert_eq!(status, "paid");
+
+        let payments: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM payments WHERE invoice_id = $1 AND payment_method = 'stripe'")
+            .bind(invoice_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(payments, 1);
+
+        // A second, distinct payment against the now-paid invoice is held for review
+        let late = succeeded_event("evt_late", invoice_id, 1000);
+        assert_eq!(handle_event(pool, &late).await.unwrap(), WebhookOutcome::NeedsReview);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod recurring_run_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::billing_routes;
+    use crate::services::expenses::{review_expense, Review};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_run_bills_unbilled_time_and_expenses_together() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Recurring Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let project_id: Uuid = sqlx::query_scalar("INSERT INTO projects (client_id, name) VALUES ($1, 'Support') RETURNING id")
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let time_entry_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO time_entries (user_id, project_id, start_time, end_time, duration_minutes, billable, billed, total_amount)
+             VALUES ($1, $2, NOW() - INTERVAL '2 hours', NOW(), 120, true, false, 300.00) RETURNING id"
+        )
+        .bind(user.id)
+        .bind(project_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let expense_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO expenses (project_id, category, vendor, description, amount, expense_date, is_billable, created_by)
+             VALUES ($1, 'software', 'Northwind', 'Backup licence', 100.00, CURRENT_DATE, true, $2) RETURNING id"
+        )
+        .bind(project_id)
+        .bind(user.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        review_expense(pool, expense_id, user.id, Review::Approve, None).await.unwrap();
+
+        let template_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO recurring_invoice_templates (client_id, name, start_date, next_run_date,
+                include_unbilled_time, include_unbilled_expenses)
+             VALUES ($1, 'Monthly support', CURRENT_DATE, CURRENT_DATE, true, true) RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO recurring_invoice_line_items (template_id, description, quantity, unit_price, display_order)
+             VALUES ($1, 'Managed services', 1, 500.00, 0)"
+        )
+        .bind(template_id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/billing", billing_routes()).with_state(Arc::new(state));
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/billing/recurring/{}/run", template_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let run: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let invoice_id: Uuid = run["invoice_id"].as_str().unwrap().parse().unwrap();
+
+        let lines = sqlx::query_as::<_, (String, Decimal)>(
+            "SELECT description, line_total FROM invoice_line_items WHERE invoice_id = $1 ORDER BY line_total DESC"
+        )
+        .bind(invoice_id)
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], ("Managed services".to_string(), dec("500.00")));
+        assert!(lines[1].0.starts_with("Professional Services"));
+        assert_eq!(lines[1].1, dec("300.00"));
+        assert!(lines[2].0.starts_with("Northwind - Backup licence"));
+        assert_eq!(lines[2].1, dec("100.00"));
+
+        let subtotal: Decimal = sqlx::query_scalar("SELECT subtotal FROM invoices WHERE id = $1")
+            .bind(invoice_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(subtotal, dec("900.00"));
+
+        let (time_amount, expenses_count, expenses_amount) = sqlx::query_as::<_, (Decimal, i32, Decimal)>(
+            "SELECT time_entries_amount, expenses_count, expenses_amount FROM recurring_invoice_runs WHERE template_id = $1"
+        )
+        .bind(template_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!((time_amount, expenses_count, expenses_amount), (dec("300.00"), 1, dec("100.00")));
+
+        let time_billed: bool = sqlx::query_scalar("SELECT billed FROM time_entries WHERE id = $1")
+            .bind(time_entry_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let (expense_status, expense_invoice) = sqlx::query_as::<_, (String, Option<Uuid>)>(
+            "SELECT status, invoice_id FROM expenses WHERE id = $1"
+        )
+        .bind(expense_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert!(time_billed);
+        assert_eq!(expense_status, "billed");
+        assert_eq!(expense_invoice, Some(invoice_id));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod invoice_numbering_tests {
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use crate::services::invoice_numbering::{self, InvoiceNumbering, ResetCadence};
+    use crate::tests::TestContext;
+
+    async fn use_numbering(pool: &sqlx::PgPool, numbering: &InvoiceNumbering, periods: &[&str]) {
+        let mut conn = pool.acquire().await.unwrap();
+        invoice_numbering::save(&mut conn, numbering).await.unwrap();
+        sqlx::query("DELETE FROM invoice_number_sequences WHERE period_key = ANY($1)")
+            .bind(periods)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+
+    /// Number and insert an invoice in one transaction, as the billing handlers do
+    async fn create_invoice(pool: sqlx::PgPool, client_id: Uuid, date: NaiveDate) -> String {
+        let mut tx = pool.begin().await.unwrap();
+        let number = invoice_numbering::next_invoice_number(&mut tx, date).await.unwrap();
+        sqlx::query(
+            "INSERT INTO invoices (client_id, number, date, due_date, total, balance, status)
+             VALUES ($1, $2, $3, $3, 100.00, 100.00, 'draft')"
+        )
+        .bind(client_id)
+        .bind(&number)
+        .bind(date)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+        number
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_concurrent_invoices_get_unique_sequential_numbers() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let numbering = InvoiceNumbering { prefix: "CC-{YYYY}-".to_string(), padding: 4, reset: ResetCadence::Yearly };
+        use_numbering(pool, &numbering, &["2091"]).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Numbering Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let date = NaiveDate::from_ymd_opt(2091, 6, 1).unwrap();
+
+        let handles: Vec<_> = (0..20)
+            .map(|_| tokio::spawn(create_invoice(pool.clone(), client_id, date)))
+            .collect();
+        let mut numbers = Vec::new();
+        for handle in handles {
+            numbers.push(handle.await.unwrap());
+        }
+        numbers.sort();
+
+        let expected: Vec<String> = (1..=20).map(|n| format!("CC-2091-{:04}", n)).collect();
+        assert_eq!(numbers, expected);
+
+        use_numbering(pool, &InvoiceNumbering::default(), &["2091"]).await;
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_yearly_numbering_restarts_each_year() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let numbering = InvoiceNumbering { prefix: "YR-{YYYY}-".to_string(), padding: 3, reset: ResetCadence::Yearly };
+        use_numbering(pool, &numbering, &["2092", "2093"]).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Numbering Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let new_years_eve = NaiveDate::from_ymd_opt(2092, 12, 31).unwrap();
+        let new_year = NaiveDate::from_ymd_opt(2093, 1, 1).unwrap();
+
+        assert_eq!(create_invoice(pool.clone(), client_id, new_years_eve).await, "YR-2092-001");
+        assert_eq!(create_invoice(pool.clone(), client_id, new_years_eve).await, "YR-2092-002");
+        assert_eq!(create_invoice(pool.clone(), client_id, new_year).await, "YR-2093-001");
+        // Back-dated invoices keep counting in their own year
+        assert_eq!(create_invoice(pool.clone(), client_id, new_years_eve).await, "YR-2092-003");
+
+        // A rolled-back invoice gives its number back
+        let mut tx = pool.begin().await.unwrap();
+        invoice_numbering::next_invoice_number(&mut tx, new_year).await.unwrap();
+        tx.rollback().await.unwrap();
+        assert_eq!(create_invoice(pool.clone(), client_id, new_year).await, "YR-2093-002");
+
+        use_numbering(pool, &InvoiceNumbering::default(), &["2092", "2093"]).await;
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod invoice_number_race_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::billing_routes;
+    use crate::services::invoice_numbering;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    async fn create_template(pool: &sqlx::PgPool, client_id: Uuid, name: &str) -> Uuid {
+        let template_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO recurring_invoice_templates (client_id, name, start_date, next_run_date,
+                include_unbilled_time, include_unbilled_expenses)
+             VALUES ($1, $2, CURRENT_DATE, CURRENT_DATE, false, false) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO recurring_invoice_line_items (template_id, description, quantity, unit_price, display_order)
+             VALUES ($1, 'Managed services', 1, 250.00, 0)"
+        )
+        .bind(template_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        template_id
+    }
+
+    async fn run(app: Router, token: &str, template_id: Uuid) -> String {
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/billing/recurring/{}/run", template_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let run: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        run["invoice_number"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_parallel_recurring_runs_get_distinct_numbers() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (_, token) = create_user_with_token(pool).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Parallel Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let mut templates = Vec::new();
+        for i in 0..25 {
+            templates.push(create_template(pool, client_id, &format!("Template {}", i)).await);
+        }
+
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/billing", billing_routes()).with_state(Arc::new(state));
+
+        let handles: Vec<_> = templates
+            .into_iter()
+            .map(|template_id| {
+                let app = app.clone();
+                let token = token.clone();
+                tokio::spawn(async move { run(app, &token, template_id).await })
+            })
+            .collect();
+        let mut numbers = HashSet::new();
+        for handle in handles {
+            assert!(numbers.insert(handle.await.unwrap()), "invoice number issued twice");
+        }
+        assert_eq!(numbers.len(), 25);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_number_entered_by_hand_is_skipped() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (_, token) = create_user_with_token(pool).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Manual Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let template_id = create_template(pool, client_id, "Monthly").await;
+
+        // Someone types in the number the counter would hand out next
+        let mut conn = pool.acquire().await.unwrap();
+        let today = chrono::Utc::now().date_naive();
+        let taken = invoice_numbering::peek_invoice_number(&mut conn, today).await.unwrap();
+        sqlx::query(
+            "INSERT INTO invoices (client_id, number, date, due_date, total, balance, status)
+             VALUES ($1, $2, CURRENT_DATE, CURRENT_DATE, 10.00, 10.00, 'draft')"
+        )
+        .bind(client_id)
+        .bind(&taken)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/billing", billing_routes()).with_state(Arc::new(state));
+        let number = run(app, &token, template_id).await;
+        assert_ne!(number, taken);
+
+        let invoices: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM invoices WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(invoices, 2);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,224 @@
+// Contract renewal integration tests
+
+#[cfg(test)]
+mod contract_renewal_tests {
+    use chrono::NaiveDate;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::contract_renewals::{check_renewals, contract_renewals};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+
+    const WARNING_DAYS: [i32; 3] = [60, 30, 14];
+    const AUTO_RENEW_DAYS: i32 = 7;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    async fn managed_client(pool: &PgPool, account_manager_id: Uuid) -> Uuid {
+        sqlx::query_scalar("INSERT INTO clients (name, account_manager_id) VALUES ('Renewals Co', $1) RETURNING id")
+            .bind(account_manager_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn contract(pool: &PgPool, client_id: Uuid, end_date: NaiveDate, auto_renew: bool) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO contracts (client_id, name, contract_type, start_date, end_date, auto_renew, renewal_term_months)
+             VALUES ($1, 'Managed Services', 'yearly', $2, $3, $4, 12) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(date(2023, 6, 20))
+        .bind(end_date)
+        .bind(auto_renew)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn end_date(pool: &PgPool, contract_id: Uuid) -> NaiveDate {
+        sqlx::query_scalar("SELECT end_date FROM contracts WHERE id = $1")
+            .bind(contract_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn notifications(pool: &PgPool, user_id: Uuid, contract_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND entity_id = $2")
+            .bind(user_id)
+            .bind(contract_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_auto_renew_contract_is_extended() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (manager, _) = create_user_with_token(pool).await;
+        let client_id = managed_client(pool, manager.id).await;
+
+        let today = date(2024, 6, 15);
+        let contract_id = contract(pool, client_id, date(2024, 6, 20), true).await;
+
+        let check = check_renewals(pool, today, &WARNING_DAYS, AUTO_RENEW_DAYS).await.unwrap();
+        assert_eq!(check.renewals.len(), 1);
+        assert!(check.reminders.is_empty());
+        assert_eq!(end_date(pool, contract_id).await, date(2025, 6, 20));
+
+        let history = contract_renewals(pool, contract_id).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].previous_end_date, date(2024, 6, 20));
+        assert_eq!(history[0].new_end_date, date(2025, 6, 20));
+        assert!(history[0].automatic);
+        assert_eq!(history[0].renewed_by, None);
+        assert_eq!(notifications(pool, manager.id, contract_id).await, 1);
+
+        // The renewed period is a year out, so the next run leaves it alone
+        let check = check_renewals(pool, today, &WARNING_DAYS, AUTO_RENEW_DAYS).await.unwrap();
+        assert!(check.renewals.is_empty());
+        assert_eq!(end_date(pool, contract_id).await, date(2025, 6, 20));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_non_auto_renew_contract_only_gets_a_reminder() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (manager, _) = create_user_with_token(pool).await;
+        let client_id = managed_client(pool, manager.id).await;
+
+        let today = date(2024, 6, 15);
+        let contract_id = contract(pool, client_id, date(2024, 6, 20), false).await;
+
+        let check = check_renewals(pool, today, &WARNING_DAYS, AUTO_RENEW_DAYS).await.unwrap();
+        assert!(check.renewals.is_empty());
+        assert_eq!(check.reminders.len(), 1);
+        assert_eq!(check.reminders[0].threshold_days, 14);
+        assert_eq!(check.reminders[0].notified, 1);
+
+        assert_eq!(end_date(pool, contract_id).await, date(2024, 6, 20));
+        assert!(contract_renewals(pool, contract_id).await.unwrap().is_empty());
+        assert_eq!(notifications(pool, manager.id, contract_id).await, 1);
+
+        // A threshold reminds once per end date
+        let check = check_renewals(pool, today, &WARNING_DAYS, AUTO_RENEW_DAYS).await.unwrap();
+        assert!(check.reminders.is_empty());
+        assert_eq!(notifications(pool, manager.id, contract_id).await, 1);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod contract_usage_tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    use crate::services::contract_usage::{contract_time_charges, contract_usage, BillingCycle};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    async fn block_contract(pool: &PgPool, client_id: Uuid, start_date: NaiveDate, included_hours: i32) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO contracts (client_id, name, contract_type, start_date, included_hours, hourly_rate, overage_rate)
+             VALUES ($1, 'Support Block', 'monthly', $2, $3, 100, 150) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(start_date)
+        .bind(included_hours)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn time_entry(pool: &PgPool, user_id: Uuid, ticket_id: Uuid, day: u32, hours: i32, billed: bool) {
+        let start = Utc.with_ymd_and_hms(2024, 5, day, 9, 0, 0).unwrap();
+        sqlx::query(
+            "INSERT INTO time_entries (user_id, ticket_id, start_time, end_time, duration_minutes, billable_minutes, billable, billed)
+             VALUES ($1, $2, $3, $4, $5, $5, true, $6)"
+        )
+        .bind(user_id)
+        .bind(ticket_id)
+        .bind(start)
+        .bind(start + chrono::Duration::hours(hours as i64))
+        .bind(hours * 60)
+        .bind(billed)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_included_hours_roll_into_overage_at_the_overage_rate() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Block Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let ticket_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO tickets (client_id, opened_by, subject, details, status)
+             VALUES ($1, $2, 'Support', 'Seeded', 'open') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(user.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        // An older block contract is superseded by the newer one covering May
+        let superseded = block_contract(pool, client_id, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(), 40).await;
+        let contract_id = block_contract(pool, client_id, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), 10).await;
+
+        // 6 hours already invoiced this cycle, 7 more unbilled
+        time_entry(pool, user.id, ticket_id, 6, 6, true).await;
+        time_entry(pool, user.id, ticket_id, 14, 3, false).await;
+        time_entry(pool, user.id, ticket_id, 20, 4, false).await;
+
+        let may = BillingCycle::parse("2024-05").unwrap();
+        let usage = contract_usage(pool, contract_id, may).await.unwrap();
+        assert_eq!(usage.used_hours, dec("13"));
+        assert_eq!(usage.remaining_hours, dec("0"));
+        assert_eq!(usage.overage_hours, dec("3"));
+        assert_eq!(usage.overage_amount, dec("450"));
+
+        let untouched = contract_usage(pool, superseded, may).await.unwrap();
+        assert_eq!(untouched.used_hours, dec("0"));
+
+        // The allotment resets: nothing has been used in June
+        let june = contract_usage(pool, contract_id, BillingCycle::parse("2024-06").unwrap()).await.unwrap();
+        assert_eq!((june.used_hours, june.remaining_hours), (dec("0"), dec("10")));
+
+        // Billing covers 4 more hours from the block and the rest is overage
+        let mut conn = pool.acquire().await.unwrap();
+        let charges = contract_time_charges(&mut conn, contract_id).await.unwrap();
+        assert_eq!(charges.len(), 1);
+        assert_eq!(charges[0].cycle, may);
+        assert_eq!(charges[0].included_hours, dec("4"));
+        assert_eq!(charges[0].overage_hours, dec("3"));
+        assert_eq!(charges[0].overage_rate, dec("150"));
+        assert_eq!(charges[0].overage_amount, dec("450"));
+        assert_eq!(charges[0].entry_ids.len(), 2);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,574 @@
+// Dashboard statistics integration tests
+
+#[cfg(test)]
+mod dashboard_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::services::dashboard::{dashboard_stats, DashboardScope};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn now() -> DateTime<Utc> {
+        // Thursday; the week starts Monday 13 May
+        Utc.with_ymd_and_hms(2024, 5, 16, 12, 0, 0).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    async fn client(pool: &PgPool, name: &str, created_at: DateTime<Utc>, archived: bool) -> Uuid {
+        sqlx::query_scalar("INSERT INTO clients (name, created_at, archived_at) VALUES ($1, $2, $3) RETURNING id")
+            .bind(name)
+            .bind(created_at)
+            .bind(archived.then_some(created_at))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn ticket(
+        pool: &PgPool,
+        client_id: Uuid,
+        user_id: Uuid,
+        status: &str,
+        created_at: DateTime<Utc>,
+        response_at: Option<DateTime<Utc>>,
+        response_due: Option<DateTime<Utc>>,
+        resolved_at: Option<DateTime<Utc>>,
+    ) {
+        sqlx::query(
+            "INSERT INTO tickets (client_id, opened_by, subject, details, status, created_at,
+                                  sla_response_at, sla_response_due, resolved_at)
+             VALUES ($1, $2, 'Dashboard', 'Seeded', $3, $4, $5, $6, $7)"
+        )
+        .bind(client_id)
+        .bind(user_id)
+        .bind(status)
+        .bind(created_at)
+        .bind(response_at)
+        .bind(response_due)
+        .bind(resolved_at)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn time_entry(pool: &PgPool, user_id: Uuid, start: DateTime<Utc>, minutes: Option<i32>, billable: bool, billed: bool) {
+        sqlx::query(
+            "INSERT INTO time_entries (user_id, start_time, end_time, duration_minutes, billable, billed)
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(minutes.map(|m| start + Duration::minutes(m as i64)))
+        .bind(minutes)
+        .bind(billable)
+        .bind(billed)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn invoice(pool: &PgPool, client_id: Uuid, status: &str, total: &str, balance: &str, issued: NaiveDate, due: NaiveDate) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO invoices (client_id, number, date, due_date, total, balance, status)
+             VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(format!("DASH-{}", Uuid::new_v4()))
+        .bind(issued)
+        .bind(due)
+        .bind(dec(total))
+        .bind(dec(balance))
+        .bind(status)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn payment(pool: &PgPool, invoice_id: Uuid, amount: &str, paid_on: NaiveDate) {
+        sqlx::query("INSERT INTO payments (invoice_id, amount, payment_date) VALUES ($1, $2, $3)")
+            .bind(invoice_id)
+            .bind(dec(amount))
+            .bind(paid_on)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn asset(pool: &PgPool, client_id: Uuid, warranty_expire: Option<NaiveDate>, last_seen: Option<DateTime<Utc>>, archived: bool) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO assets (client_id, name, asset_type, warranty_expire, last_seen, archived_at)
+             VALUES ($1, 'Seeded', 'workstation', $2, $3, $4) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(warranty_expire)
+        .bind(last_seen)
+        .bind(archived.then(Utc::now))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn alert(pool: &PgPool, asset_id: Uuid, severity: &str, resolved: bool) {
+        sqlx::query(
+            "INSERT INTO alerts (asset_id, alert_type, severity, title, resolved, resolved_at)
+             VALUES ($1, 'asset_down', $2, 'Seeded', $3, $4)"
+        )
+        .bind(asset_id)
+        .bind(severity)
+        .bind(resolved)
+        .bind(resolved.then(Utc::now))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    /// Seed a known data set, returning the two technicians
+    async fn seed(pool: &PgPool) -> (Uuid, Uuid) {
+        let (user, other, _, _) = seed_clients(pool).await;
+        (user, other)
+    }
+
+    /// Seed a known data set, returning the two technicians and the Acme and
+    /// Globex client ids
+    async fn seed_clients(pool: &PgPool) -> (Uuid, Uuid, Uuid, Uuid) {
+        let now = now();
+        let (user, _) = create_user_with_token(pool).await;
+        let (other, _) = create_user_with_token(pool).await;
+
+        let acme = client(pool, "Acme", Utc.with_ymd_and_hms(2024, 5, 3, 9, 0, 0).unwrap(), false).await;
+        let globex = client(pool, "Globex", Utc.with_ymd_and_hms(2024, 1, 10, 9, 0, 0).unwrap(), false).await;
+        client(pool, "Initech", Utc.with_ymd_and_hms(2024, 5, 4, 9, 0, 0).unwrap(), true).await;
+
+        // Responses after 1h and 3h; one open ticket past its response due time
+        let opened = now - Duration::hours(2);
+        ticket(pool, acme, user.id, "open", opened, Some(opened + Duration::hours(1)), None, None).await;
+        ticket(pool, acme, user.id, "in_progress", now - Duration::hours(5), None, Some(now - Duration::hours(1)), None).await;
+        ticket(pool, acme, user.id, "pending", now - Duration::hours(1), None, None, None).await;
+        let opened = now - Duration::hours(4);
+        ticket(pool, acme, user.id, "resolved", opened, Some(opened + Duration::hours(3)), None, Some(now - Duration::hours(1))).await;
+        ticket(pool, globex, user.id, "closed", now - Duration::days(3), None, None, Some(now - Duration::days(1))).await;
+
+        let today = |h| Utc.with_ymd_and_hms(2024, 5, 16, h, 0, 0).unwrap();
+        time_entry(pool, user.id, today(8), Some(120), true, false).await;
+        time_entry(pool, user.id, today(10), Some(60), false, false).await;
+        time_entry(pool, user.id, Utc.with_ymd_and_hms(2024, 5, 13, 9, 0, 0).unwrap(), Some(90), true, true).await;
+        time_entry(pool, user.id, Utc.with_ymd_and_hms(2024, 5, 10, 9, 0, 0).unwrap(), Some(240), true, true).await;
+        time_entry(pool, user.id, now - Duration::minutes(30), None, true, false).await;
+        time_entry(pool, other.id, today(9), Some(60), true, false).await;
+
+        let overdue = invoice(pool, acme, "sent", "1000", "400", date(2024, 4, 20), date(2024, 5, 10)).await;
+        payment(pool, overdue, "600", date(2024, 5, 5)).await;
+        invoice(pool, acme, "draft", "300", "300", date(2024, 5, 14), date(2024, 6, 13)).await;
+        let paid = invoice(pool, acme, "paid", "500", "0", date(2024, 5, 2), date(2024, 6, 1)).await;
+        payment(pool, paid, "500", date(2024, 5, 6)).await;
+        invoice(pool, globex, "sent", "200", "200", date(2024, 5, 15), date(2024, 6, 15)).await;
+        let last_year = invoice(pool, globex, "paid", "250", "0", date(2023, 11, 20), date(2023, 12, 20)).await;
+        payment(pool, last_year, "250", date(2023, 12, 1)).await;
+
+        let online = asset(pool, acme, Some(date(2024, 5, 26)), Some(now - Duration::minutes(5)), false).await;
+        let offline = asset(pool, acme, Some(date(2024, 7, 15)), Some(now - Duration::hours(2)), false).await;
+        asset(pool, globex, Some(date(2024, 1, 1)), None, false).await;
+        asset(pool, globex, Some(date(2024, 5, 20)), None, true).await;
+        alert(pool, online, "critical", false).await;
+        alert(pool, online, "high", false).await;
+        alert(pool, offline, "critical", true).await;
+
+        (user.id, other.id, acme, globex)
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_dashboard_stats_full_scope() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        seed(&ctx.db_pool).await;
+
+        let stats = dashboard_stats(&ctx.db_pool, DashboardScope::full(), now()).await.unwrap();
+
+        assert_eq!(stats.overview.total_clients, 2);
+        assert_eq!(stats.clients.new_this_month, 1);
+
+        assert_eq!(stats.overview.active_tickets, 3);
+        assert_eq!(
+            (stats.tickets.open, stats.tickets.in_progress, stats.tickets.pending),
+            (1, 1, 1)
+        );
+        assert_eq!(stats.tickets.resolved_today, 1);
+        assert_eq!(stats.tickets.sla_breached, 1);
+        assert_eq!(stats.tickets.avg_response_time_hours, Some(2.0));
+
+        assert_eq!(stats.time.hours_today, dec("4.00"));
+        assert_eq!(stats.time.billable_hours_today, dec("3.00"));
+        assert_eq!(stats.time.hours_this_week, dec("5.50"));
+        assert_eq!(stats.time.active_timers, 1);
+        let utilization = stats.time.team_utilization.unwrap();
+        assert!((utilization - 4.5 / 5.5).abs() < 1e-9);
+        assert_eq!(stats.overview.unbilled_time, dec("3.00"));
+
+        assert_eq!(stats.overview.monthly_revenue, dec("1100"));
+        assert_eq!(stats.invoices.paid_this_month, dec("1100"));
+        assert_eq!(stats.invoices.outstanding_amount, dec("600"));
+        assert_eq!(stats.invoices.overdue_amount, dec("400"));
+        assert_eq!(stats.overview.overdue_invoices, 1);
+        assert_eq!(stats.invoices.draft_count, 1);
+        let ratio = stats.invoices.collection_ratio.unwrap();
+        assert!((ratio - 1100.0 / 1700.0).abs() < 1e-9);
+
+        let top: Vec<(String, Decimal)> = stats
+            .clients
+            .top_clients_by_revenue
+            .into_iter()
+            .map(|c| (c.name, c.revenue))
+            .collect();
+        assert_eq!(top, vec![("Acme".to_string(), dec("1100")), ("Globex".to_string(), dec("250"))]);
+
+        assert_eq!(stats.assets.total_assets, 3);
+        assert_eq!(stats.assets.critical_alerts, 1);
+        assert_eq!(stats.assets.warranty_expiring, 1);
+        assert_eq!(stats.assets.online_percentage, Some(50.0));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_dashboard_stats_limited_scope() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        let (user_id, _) = seed(&ctx.db_pool).await;
+
+        let scope = DashboardScope { financials: false, time_user_id: Some(user_id) };
+        let stats = dashboard_stats(&ctx.db_pool, scope, now()).await.unwrap();
+
+        // Only the caller's own time
+        assert_eq!(stats.time.hours_today, dec("3.00"));
+        assert_eq!(stats.overview.unbilled_time, dec("2.00"));
+
+        // No financial figures
+        assert_eq!(stats.overview.monthly_revenue, Decimal::ZERO);
+        assert_eq!(stats.overview.overdue_invoices, 0);
+        assert_eq!(stats.invoices.outstanding_amount, Decimal::ZERO);
+        assert!(stats.invoices.collection_ratio.is_none());
+        assert!(stats.clients.top_clients_by_revenue.is_empty());
+
+        // Operational figures are unaffected
+        assert_eq!(stats.overview.total_clients, 2);
+        assert_eq!(stats.overview.active_tickets, 3);
+
+        ctx.cleanup().await;
+    }
+
+    async fn get_dashboard(pool: &PgPool, token: &str, query: &str) -> (StatusCode, serde_json::Value) {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().route("/", get(crate::handlers::dashboard_stats)).with_state(Arc::new(state));
+        let request = Request::builder()
+            .uri(format!("/{}", query))
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_account_manager_sees_only_managed_clients() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        let (_, _, acme, globex) = seed_clients(&ctx.db_pool).await;
+        let (manager, token) = create_user_with_token(&ctx.db_pool).await;
+        sqlx::query("UPDATE clients SET account_manager_id = $1 WHERE id = $2")
+            .bind(manager.id)
+            .bind(acme)
+            .execute(&ctx.db_pool)
+            .await
+            .unwrap();
+
+        let (status, body) = get_dashboard(&ctx.db_pool, &token, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["overview"]["total_clients"], 1);
+        assert_eq!(body["assets"]["total_assets"], 2);
+        assert_eq!(body["tickets"]["resolved_today"], 1);
+
+        let (status, body) = get_dashboard(&ctx.db_pool, &token, &format!("?client_id={}", acme)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["overview"]["total_clients"], 1);
+
+        let (status, _) = get_dashboard(&ctx.db_pool, &token, &format!("?client_id={}", globex)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Scoping a full view to one client
+        let scope = DashboardScope { client_ids: Some(vec![globex]), ..DashboardScope::full() };
+        let stats = dashboard_stats(&ctx.db_pool, scope, now()).await.unwrap();
+        assert_eq!(stats.overview.total_clients, 1);
+        assert_eq!(stats.overview.active_tickets, 0);
+        assert_eq!(stats.invoices.outstanding_amount, dec("200"));
+        assert_eq!(stats.overview.monthly_revenue, Decimal::ZERO);
+        assert_eq!(stats.assets.total_assets, 1);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use axum::body::{Body, BodyDataStream};
+    use axum::http::{header, Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use futures::StreamExt;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::{dashboard_stream, ticket_routes};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    /// The next event's name and data, skipping heartbeats
+    async fn next_event(body: &mut BodyDataStream, buffer: &mut String) -> (String, Value) {
+        loop {
+            if let Some(end) = buffer.find("\n\n") {
+                let block: String = buffer.drain(..end + 2).collect();
+                let mut name = String::new();
+                let mut data = String::new();
+                for line in block.lines() {
+                    if let Some(value) = line.strip_prefix("event:") {
+                        name = value.trim().to_string();
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data.push_str(value.trim());
+                    }
+                }
+                if !name.is_empty() {
+                    return (name, serde_json::from_str(&data).unwrap_or(Value::Null));
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(10), body.next())
+                .await
+                .expect("no dashboard event in time")
+                .expect("stream ended")
+                .unwrap();
+            buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_creating_a_ticket_emits_a_dashboard_event() {
+        let ctx = TestContext::new().await;
+        ctx.cleanup().await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Streamed') RETURNING id")
+            .fetch_one(&ctx.db_pool)
+            .await
+            .unwrap();
+
+        let state = Arc::new(AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None });
+        let app = Router::new()
+            .route("/dashboard/stream", get(dashboard_stream))
+            .nest("/tickets", ticket_routes())
+            .with_state(state);
+
+        let request = Request::builder()
+            .uri("/dashboard/stream")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/event-stream"));
+        let mut body = response.into_body().into_data_stream();
+        let mut buffer = String::new();
+
+        let (name, initial) = next_event(&mut body, &mut buffer).await;
+        assert_eq!(name, "dashboard");
+        assert_eq!(initial["events"], json!([]));
+        let active_before = initial["stats"]["overview"]["active_tickets"].as_i64().unwrap();
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/tickets")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::from(json!({"client_id": client_id, "subject": "Printer", "details": "Jammed"}).to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let (name, update) = next_event(&mut body, &mut buffer).await;
+        assert_eq!(name, "dashboard");
+        assert_eq!(update["events"], json!(["ticket.created"]));
+        assert_eq!(update["stats"]["overview"]["active_tickets"].as_i64().unwrap(), active_before + 1);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod workspace_tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use chrono::{DateTime, Duration, NaiveTime, Utc};
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::{handlers, AppState};
+
+    async fn ticket(
+        pool: &PgPool,
+        client_id: Uuid,
+        assigned_to: Uuid,
+        subject: &str,
+        priority: &str,
+        response_due: Option<DateTime<Utc>>,
+        resolution_due: Option<DateTime<Utc>>,
+    ) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO tickets (client_id, opened_by, assigned_to, subject, details, status, priority,
+                                  sla_response_due, sla_resolution_due)
+             VALUES ($1, $2, $2, $3, 'Seeded', 'open', $4, $5, $6) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(assigned_to)
+        .bind(subject)
+        .bind(priority)
+        .bind(response_due)
+        .bind(resolution_due)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn seed_work(pool: &PgPool, project_id: Uuid, user_id: Uuid, label: &str, now: DateTime<Utc>) {
+        let day_start = now.date_naive().and_time(NaiveTime::MIN).and_utc();
+        sqlx::query(
+            "INSERT INTO time_entries (user_id, start_time, end_time, duration_minutes, description)
+             VALUES ($1, $2, $2 + INTERVAL '90 minutes', 90, $3), ($1, $4, NULL, NULL, $3)"
+        )
+        .bind(user_id)
+        .bind(day_start)
+        .bind(label)
+        .bind(now - Duration::minutes(20))
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO tasks (project_id, name, assigned_to, status, due_date)
+             VALUES ($1, $2, $3, 'todo', $4), ($1, $2, $3, 'completed', $4)"
+        )
+        .bind(project_id)
+        .bind(label)
+        .bind(user_id)
+        .bind(now.date_naive() + Duration::days(1))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_workspace_lists_only_callers_work_most_urgent_first() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (tech, token) = create_user_with_token(pool).await;
+        let (other, _) = create_user_with_token(pool).await;
+        let now = Utc::now();
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Workspace Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let project_id: Uuid = sqlx::query_scalar("INSERT INTO projects (client_id, name) VALUES ($1, 'Rollout') RETURNING id")
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        let later = ticket(pool, client_id, tech.id, "Resolution in 8h", "low", None, Some(now + Duration::hours(8))).await;
+        let soonest =
+            ticket(pool, client_id, tech.id, "Response in 1h", "medium", Some(now + Duration::hours(1)), Some(now + Duration::hours(24))).await;
+        let no_sla = ticket(pool, client_id, tech.id, "No SLA", "critical", None, None).await;
+        let merged = ticket(pool, client_id, tech.id, "Merged", "critical", None, Some(now + Duration::minutes(30))).await;
+        sqlx::query("UPDATE tickets SET is_merged = true, merged_into_id = $2 WHERE id = $1")
+            .bind(merged)
+            .bind(later)
+            .execute(pool)
+            .await
+            .unwrap();
+        let resolved = ticket(pool, client_id, tech.id, "Resolved", "high", None, Some(now + Duration::minutes(10))).await;
+        sqlx::query("UPDATE tickets SET status = 'resolved' WHERE id = $1")
+            .bind(resolved)
+            .execute(pool)
+            .await
+            .unwrap();
+        ticket(pool, client_id, other.id, "Someone else's", "critical", Some(now + Duration::minutes(5)), None).await;
+
+        seed_work(pool, project_id, tech.id, "Mine", now).await;
+        seed_work(pool, project_id, other.id, "Theirs", now).await;
+
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/me", handlers::me_routes()).with_state(Arc::new(state));
+        let request = Request::builder()
+            .uri("/me/workspace")
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+
+        assert_eq!(body["tickets"]["total"], 3);
+        let ids: Vec<String> = body["tickets"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(ids, vec![soonest.to_string(), later.to_string(), no_sla.to_string()]);
+
+        assert_eq!(body["running_timer"]["description"], "Mine");
+        assert!(body["running_timer"]["elapsed_minutes"].as_i64().unwrap() >= 20);
+        assert_eq!(body["today"]["entries"], 1);
+        assert_eq!(body["today"]["hours"], "1.50");
+
+        assert_eq!(body["tasks"]["total"], 1);
+        assert_eq!(body["tasks"]["items"][0]["name"], "Mine");
+        assert_eq!(body["tasks"]["items"][0]["overdue"], false);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,58 @@
+// Database connection pool integration tests
+
+#[cfg(test)]
+mod pool_config_tests {
+    use std::time::Duration;
+
+    use crate::database::PoolConfig;
+    use crate::tests::TestContext;
+
+    fn config(max_connections: u32) -> PoolConfig {
+        PoolConfig {
+            max_connections,
+            min_connections: 0,
+            acquire_timeout: Duration::from_millis(500),
+            statement_timeout: Some(Duration::from_secs(5)),
+            application_name: "resolve-pool-test".to_string(),
+            ..PoolConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_max_connections_is_respected() {
+        let ctx = TestContext::new().await;
+        let pool = config(2).pool_options().connect(&ctx.database_url).await.unwrap();
+
+        let first = pool.acquire().await.unwrap();
+        let second = pool.acquire().await.unwrap();
+
+        let third = pool.acquire().await;
+        assert!(matches!(third, Err(sqlx::Error::PoolTimedOut)));
+        assert_eq!(pool.size(), 2);
+
+        // A released connection can be acquired again
+        drop(first);
+        let _again = pool.acquire().await.unwrap();
+        drop(second);
+
+        pool.close().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_connections_get_session_settings() {
+        let ctx = TestContext::new().await;
+        let pool = config(1).pool_options().connect(&ctx.database_url).await.unwrap();
+
+        let (application_name, statement_timeout): (String, String) =
+            sqlx::query_as("SELECT current_setting('application_name'), current_setting('statement_timeout')")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(application_name, "resolve-pool-test");
+        assert_eq!(statement_timeout, "5s");
+
+        pool.close().await;
+    }
+}
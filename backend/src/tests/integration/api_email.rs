@@ -0,0 +1,273 @@
+// Email API integration tests
+
+#[cfg(test)]
+mod inbound_email_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::email_routes;
+    use crate::services::inbound_email::INBOUND_EMAIL_USER_ID;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    const TOKEN: &str = "inbound-test-token";
+
+    fn app(pool: &PgPool) -> Router {
+        // SAFETY: every test in this module sets the same value
+        unsafe { std::env::set_var("INBOUND_EMAIL_TOKEN", TOKEN) };
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        email_routes().with_state(Arc::new(state))
+    }
+
+    async fn post(app: &Router, content_type: &str, body: String, token: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/inbound")
+            .header(header::CONTENT_TYPE, content_type)
+            .header("X-Inbound-Token", token)
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// A client with one contact, plus a queue receiving `support@`
+    async fn seed(pool: &PgPool) -> (Uuid, Uuid, Uuid, String) {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Acme Mail') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let email = format!("jane-{}@acme.example", Uuid::new_v4());
+        let contact_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO contacts (client_id, name, email) VALUES ($1, 'Jane Doe', $2) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(&email)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let queue_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO ticket_queues (name, email_address, default_priority) VALUES ('Support', 'support@msp.example', 'high') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (client_id, contact_id, queue_id, email)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_new_message_creates_ticket() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (client_id, contact_id, queue_id, from) = seed(pool).await;
+
+        let payload = serde_json::json!({
+            "From": from,
+            "FromName": "Jane Doe",
+            "To": "Support <support@msp.example>",
+            "Subject": "Printer offline",
+            "TextBody": "The second floor printer is offline.",
+            "MessageID": format!("{}@acme.example", Uuid::new_v4()),
+            "Headers": [],
+            "Attachments": [{ "Name": "error.txt", "Content": "cGFwZXIgamFt", "ContentType": "text/plain" }]
+        });
+
+        let (status, _) = post(&app, "application/json", payload.to_string(), "wrong-token").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = post(&app, "application/json", payload.to_string(), TOKEN).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"], "ticket_created");
+        assert_eq!(body["attachments"], 1);
+        let ticket_id: Uuid = body["ticket_id"].as_str().unwrap().parse().unwrap();
+
+        let (ticket_client, ticket_contact, ticket_queue, opened_by, subject, priority, source): (
+            Uuid, Option<Uuid>, Option<Uuid>, Uuid, String, String, String,
+        ) = sqlx::query_as(
+            "SELECT client_id, contact_id, queue_id, opened_by, subject, priority, source FROM tickets WHERE id = $1"
+        )
+        .bind(ticket_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(ticket_client, client_id);
+        assert_eq!(ticket_contact, Some(contact_id));
+        assert_eq!(ticket_queue, Some(queue_id));
+        assert_eq!(opened_by, INBOUND_EMAIL_USER_ID);
+        assert_eq!(subject, "Printer offline");
+        assert_eq!(priority, "high");
+        assert_eq!(source, "email");
+
+        let files: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE ticket_id = $1")
+            .bind(ticket_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(files, 1);
+
+        // A provider retry of the same message is a no-op
+        let (status, body) = post(&app, "application/json", payload.to_string(), TOKEN).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"], "duplicate");
+        let tickets: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tickets WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(tickets, 1);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_subject_token_threads_reply() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (client_id, contact_id, _, from) = seed(pool).await;
+
+        let (ticket_id, number): (Uuid, i32) = sqlx::query_as(
+            r#"
+            INSERT INTO tickets (number, client_id, contact_id, opened_by, subject, details)
+            SELECT COALESCE(MAX(number), 0) + 1, $1, $2, $3, 'VPN down', 'Cannot connect'
+            FROM tickets
+            RETURNING id, number
+            "#
+        )
+        .bind(client_id)
+        .bind(contact_id)
+        .bind(INBOUND_EMAIL_USER_ID)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let raw = format!(
+            "From: Jane Doe <{from}>\r\n\
+             To: support@msp.example\r\n\
+             Subject: Re: [#{number}] VPN down\r\n\
+             Message-ID: <{id}@acme.example>\r\n\
+             Content-Type: text/plain\r\n\
+             \r\n\
+             Working again, thanks.\r\n",
+            from = from,
+            number = number,
+            id = Uuid::new_v4()
+        );
+
+        let (status, body) = post(&app, "message/rfc822", raw, TOKEN).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"], "reply_added");
+        assert_eq!(body["ticket_id"], ticket_id.to_string());
+
+        let (reply_contact, details): (Option<Uuid>, String) =
+            sqlx::query_as("SELECT contact_id, details FROM ticket_replies WHERE ticket_id = $1")
+                .bind(ticket_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        assert_eq!(reply_contact, Some(contact_id));
+        assert_eq!(details, "Working again, thanks.");
+
+        // Out-of-office replies are dropped rather than threaded
+        let auto_reply = format!(
+            "From: {from}\r\nTo: support@msp.example\r\nSubject: Re: [#{number}] VPN down\r\n\
+             Auto-Submitted: auto-replied\r\nMessage-ID: <{id}@acme.example>\r\n\r\nI'm away.\r\n",
+            from = from,
+            number = number,
+            id = Uuid::new_v4()
+        );
+        let (status, body) = post(&app, "message/rfc822", auto_reply, TOKEN).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"], "ignored");
+        assert_eq!(body["reason"], "auto_generated");
+
+        let replies: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ticket_replies WHERE ticket_id = $1")
+            .bind(ticket_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(replies, 1);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod email_template_tests {
+    use uuid::Uuid;
+
+    use crate::services::canned_response_render::TemplateVariables;
+    use crate::services::email_templates::{self, TemplateError, TemplateOrigin, INVOICE_REMINDER};
+    use crate::tests::TestContext;
+
+    fn reminder_variables() -> TemplateVariables {
+        let mut vars = TemplateVariables::new();
+        vars.set("invoice_number", Some("INV-000042"))
+            .set("amount", Some("150.00"))
+            .set("due_date", Some("March 01, 2024"))
+            .set("reminder_status", Some("due today"))
+            .set("client_name", Some("Acme"));
+        vars
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_stored_template_is_used_and_builtin_is_the_fallback() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+
+        sqlx::query("DELETE FROM email_templates WHERE slug = $1")
+            .bind(INVOICE_REMINDER)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let fallback = email_templates::render(pool, INVOICE_REMINDER, None, &reminder_variables()).await.unwrap();
+        assert_eq!(fallback.origin, TemplateOrigin::BuiltIn);
+        assert_eq!(fallback.subject, "Payment reminder: invoice #INV-000042 (due today)");
+        // Neither the message nor the portal link were supplied
+        assert_eq!(fallback.missing, vec!["portal_url", "reminder_message"]);
+        assert!(!fallback.html_body.contains("{{"));
+
+        sqlx::query(
+            r#"INSERT INTO email_templates (name, slug, subject, html_body, text_body, is_system)
+               VALUES ('Payment Reminder', $1, 'Pay {{invoice_number}} to {{brand.company_name}}', '<p>{{amount}}</p>', NULL, true)"#
+        )
+        .bind(INVOICE_REMINDER)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Branded Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO client_email_branding (client_id, company_name) VALUES ($1, 'Branded IT')")
+            .bind(client_id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let stored = email_templates::render(pool, INVOICE_REMINDER, Some(client_id), &reminder_variables()).await.unwrap();
+        assert_eq!(stored.origin, TemplateOrigin::Stored);
+        assert_eq!(stored.subject, "Pay INV-000042 to Branded IT");
+        assert_eq!(stored.html_body, "<p>150.00</p>");
+        assert!(stored.missing.is_empty());
+
+        let unknown = email_templates::render(pool, "no_such_template", None, &reminder_variables()).await;
+        assert!(matches!(unknown, Err(TemplateError::Unknown(_))));
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,149 @@
+// Expense approval and billing integration tests
+
+#[cfg(test)]
+mod expense_approval_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use resolve_shared::ExpenseStatus;
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::{billing_routes, expense_routes};
+    use crate::services::expenses::{find_expense, review_expense, ExpenseError, Review};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn app(ctx: &TestContext) -> Router {
+        let state = AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        Router::new()
+            .nest("/expenses", expense_routes())
+            .nest("/billing", billing_routes())
+            .with_state(Arc::new(state))
+    }
+
+    async fn post(app: &Router, token: &str, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn client(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Expense Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn expense(pool: &PgPool, client_id: Uuid, amount: &str, markup: &str, created_by: Uuid) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO expenses (client_id, category, vendor, description, amount, markup_percent,
+                expense_date, is_billable, created_by)
+             VALUES ($1, 'hardware', 'Contoso Hardware', 'Replacement switch', $2, $3, '2024-05-01', true, $4)
+             RETURNING id"
+        )
+        .bind(client_id)
+        .bind(dec(amount))
+        .bind(dec(markup))
+        .bind(created_by)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_submitted_expense_is_approved_once() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+        let expense_id = expense(pool, client(pool).await, "80.00", "0", user.id).await;
+        assert_eq!(find_expense(pool, expense_id).await.unwrap().unwrap().status, ExpenseStatus::Submitted);
+
+        // Approval needs expenses.approve, which a user without a role lacks
+        let (status, _) = post(&app(&ctx), &token, &format!("/expenses/{}/approve", expense_id)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Nobody approves their own expense
+        let own = review_expense(pool, expense_id, user.id, Review::Approve, None).await;
+        assert!(matches!(own, Err(ExpenseError::OwnExpense)));
+
+        let (approver, _) = create_user_with_token(pool).await;
+        let approved = review_expense(pool, expense_id, approver.id, Review::Approve, Some("OK".to_string())).await.unwrap();
+        assert_eq!(approved.status, ExpenseStatus::Approved);
+        assert_eq!(approved.approved_by, Some(approver.id));
+        assert!(approved.approved_at.is_some());
+
+        let again = review_expense(pool, expense_id, approver.id, Review::Reject, None).await;
+        assert!(matches!(again, Err(ExpenseError::AlreadyReviewed(ExpenseStatus::Approved))));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_approved_billable_expenses_are_billed_by_recurring_run() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+        let client_id = client(pool).await;
+
+        let (approver, _) = create_user_with_token(pool).await;
+        let approved = expense(pool, client_id, "200.00", "15", user.id).await;
+        review_expense(pool, approved, approver.id, Review::Approve, None).await.unwrap();
+        let pending = expense(pool, client_id, "50.00", "0", user.id).await;
+
+        let template_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO recurring_invoice_templates (client_id, name, start_date, next_run_date,
+                include_unbilled_time, include_unbilled_expenses)
+             VALUES ($1, 'Monthly', CURRENT_DATE, CURRENT_DATE, false, true) RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let (status, run) = post(&app(&ctx), &token, &format!("/billing/recurring/{}/run", template_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(run["expenses_count"], 1);
+        let invoice_id: Uuid = run["invoice_id"].as_str().unwrap().parse().unwrap();
+
+        let line_total: Decimal = sqlx::query_scalar(
+            "SELECT line_total FROM invoice_line_items WHERE invoice_id = $1 AND description LIKE 'Contoso Hardware%'"
+        )
+        .bind(invoice_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(line_total, dec("230.00"));
+
+        let billed = find_expense(pool, approved).await.unwrap().unwrap();
+        assert_eq!(billed.status, ExpenseStatus::Billed);
+        assert_eq!(billed.invoice_id, Some(invoice_id));
+        assert_eq!(billed.billed_amount, Some(dec("230.00")));
+
+        // Unapproved expenses wait for approval
+        let pending = find_expense(pool, pending).await.unwrap().unwrap();
+        assert_eq!(pending.status, ExpenseStatus::Submitted);
+        assert_eq!(pending.invoice_id, None);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,292 @@
+// File upload and storage reconciliation integration tests
+
+#[cfg(test)]
+mod file_upload_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use crate::files::file_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    const BOUNDARY: &str = "resolve-test-boundary";
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_upload_without_file_returns_json_error() {
+        let ctx = TestContext::new().await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+
+        let state = AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/files", file_routes()).with_state(Arc::new(state));
+
+        // A form with metadata but no file part
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"client_id\"\r\n\r\nnot-a-uuid\r\n--{b}--\r\n",
+            b = BOUNDARY
+        );
+        let request = Request::builder()
+            .method("POST")
+            .uri("/files/upload")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(Body::from(body))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(error["code"], "BAD_REQUEST");
+        assert!(error["message"].as_str().unwrap().contains("file"));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod file_list_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::files::file_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_list_files_paginates_with_filtered_total() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Acme') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        // Three files for the client and two that the filter must not count
+        for (i, client) in [Some(client_id), Some(client_id), Some(client_id), None, None].into_iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO files (client_id, filename, original_filename, mime_type, file_size, file_path, uploaded_by)
+                 VALUES ($1, $2, $2, 'text/plain', 5, $2, $3)"
+            )
+            .bind(client)
+            .bind(format!("list-{}.txt", i))
+            .bind(user.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/files", file_routes()).with_state(Arc::new(state));
+
+        let get = |page: u32| {
+            Request::builder()
+                .uri(format!("/files?client_id={}&per_page=2&page={}", client_id, page))
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(get(2)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(page["data"].as_array().unwrap().len(), 1);
+        assert_eq!(page["meta"]["total"], 3);
+        assert_eq!(page["meta"]["total_pages"], 2);
+        assert_eq!(page["meta"]["has_next"], false);
+        assert_eq!(page["meta"]["has_prev"], true);
+
+        // A page past the end has no rows to carry the window count
+        let response = app.oneshot(get(5)).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(page["data"].as_array().unwrap().is_empty());
+        assert_eq!(page["meta"]["total"], 3);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod file_reconcile_tests {
+    use chrono::Duration;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    use crate::files::reconcile::{reconcile, ReconcileError, ReconcileOptions};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+
+    async fn insert_file(pool: &sqlx::PgPool, user_id: Uuid, ticket_id: Option<Uuid>, path: &Path) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO files (ticket_id, filename, original_filename, mime_type, file_size, file_path, uploaded_by)
+             VALUES ($1, $2, $2, 'text/plain', 5, $3, $4) RETURNING id"
+        )
+        .bind(ticket_id)
+        .bind(path.file_name().unwrap().to_string_lossy().into_owned())
+        .bind(path.to_string_lossy().into_owned())
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn file_exists(pool: &sqlx::PgPool, id: Uuid) -> bool {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM files WHERE id = $1)")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_reconcile_reports_then_fixes_seeded_mismatches() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let kept_path = dir.path().join("kept.txt");
+        std::fs::write(&kept_path, "kept").unwrap();
+        let kept = insert_file(pool, user.id, None, &kept_path).await;
+
+        let missing = insert_file(pool, user.id, None, &dir.path().join("missing.txt")).await;
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Acme') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let ticket_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO tickets (client_id, opened_by, subject, details)
+             VALUES ($1, $2, 'Printer offline', 'details') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(user.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let orphan_path = dir.path().join("orphan.txt");
+        std::fs::write(&orphan_path, "orphan").unwrap();
+        let orphan = insert_file(pool, user.id, Some(ticket_id), &orphan_path).await;
+
+        // Hard-deleting the parent detaches the file and marks it orphaned
+        sqlx::query("DELETE FROM tickets WHERE id = $1").bind(ticket_id).execute(pool).await.unwrap();
+
+        let stray_path = dir.path().join("stray.txt");
+        std::fs::write(&stray_path, "stray").unwrap();
+
+        let options = ReconcileOptions {
+            dry_run: true,
+            delete_unreferenced_objects: true,
+            grace_period: Duration::zero(),
+            upload_dir: dir.path().to_string_lossy().into_owned(),
+            ..ReconcileOptions::default()
+        };
+
+        let report = reconcile(pool, &options).await.unwrap();
+        assert!(report.dry_run);
+        // The files table is shared with other tests, so only look at the seeded rows
+        let missing_ids: Vec<Uuid> = report.missing_objects.iter().map(|f| f.id).collect();
+        let orphaned_ids: Vec<Uuid> = report.orphaned_files.iter().map(|f| f.id).collect();
+        assert!(missing_ids.contains(&missing));
+        assert!(orphaned_ids.contains(&orphan));
+        assert!(!missing_ids.contains(&kept) && !orphaned_ids.contains(&kept));
+        assert_eq!(report.unreferenced_objects, vec![stray_path.to_string_lossy().into_owned()]);
+
+        // A dry run changes nothing
+        assert!(file_exists(pool, missing).await);
+        assert!(file_exists(pool, orphan).await);
+        assert!(orphan_path.exists());
+        assert!(stray_path.exists());
+
+        let report = reconcile(pool, &ReconcileOptions { dry_run: false, ..options.clone() }).await.unwrap();
+        assert!(!report.dry_run);
+
+        assert!(file_exists(pool, kept).await);
+        assert!(kept_path.exists());
+        assert!(!file_exists(pool, missing).await);
+        assert!(!file_exists(pool, orphan).await);
+        assert!(!orphan_path.exists());
+        assert!(!stray_path.exists());
+
+        // Nothing seeded is left to fix
+        let report = reconcile(pool, &options).await.unwrap();
+        assert!(report.unreferenced_objects.is_empty());
+        assert!(!report.orphaned_files.iter().any(|f| f.id == orphan));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_reconcile_stops_when_storage_is_missing_or_empty() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+        let dir = tempfile::tempdir().unwrap();
+        let recorded = insert_file(pool, user.id, None, &dir.path().join("report.pdf")).await;
+
+        for upload_dir in [dir.path().join("unmounted"), dir.path().to_path_buf()] {
+            let options = ReconcileOptions {
+                dry_run: false,
+                upload_dir: upload_dir.to_string_lossy().into_owned(),
+                ..ReconcileOptions::default()
+            };
+            let result = reconcile(pool, &options).await;
+            assert!(matches!(result, Err(ReconcileError::StorageUnavailable(_))));
+        }
+        assert!(file_exists(pool, recorded).await);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_reconcile_caps_deletions_per_run() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("kept.txt"), "kept").unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let options = ReconcileOptions {
+            dry_run: false,
+            delete_unreferenced_objects: true,
+            grace_period: Duration::zero(),
+            upload_dir: dir.path().to_string_lossy().into_owned(),
+            max_deletions: 0,
+        };
+        let kept = insert_file(pool, user.id, None, &dir.path().join("kept.txt")).await;
+        let report = reconcile(pool, &options).await.unwrap();
+        assert!(report.deferred >= 3);
+        assert!(["a.txt", "b.txt", "c.txt"].iter().all(|name| dir.path().join(name).exists()));
+
+        let report = reconcile(pool, &ReconcileOptions { max_deletions: 2, ..options.clone() }).await.unwrap();
+        assert!(report.deferred >= 1);
+        let left = ["a.txt", "b.txt", "c.txt"].iter().filter(|name| dir.path().join(name).exists()).count();
+        assert!(left >= 1);
+        assert!(file_exists(pool, kept).await);
+
+        ctx.cleanup().await;
+    }
+}
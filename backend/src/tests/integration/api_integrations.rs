@@ -0,0 +1,200 @@
+// Integration connection-test caching and Azure cost-alert webhook tests
+
+#[cfg(test)]
+mod integration_test_cache_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use chrono::{Duration, Utc};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::integrations::connection_test::{store_result, IntegrationTestResult};
+    use crate::integrations::integration_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    async fn run_test(app: &Router, token: &str, id: Uuid, force: bool) -> serde_json::Value {
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/integrations/{}/test{}", id, if force { "?force=true" } else { "" }))
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_cached_result_within_ttl_unless_forced() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (_, token) = create_user_with_token(pool).await;
+
+        // GitHub's connection test doesn't call out, so a fresh run always succeeds
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO integrations (name, integration_type) VALUES ('GitHub', 'github') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        // A recent failure is what a normal test should hand back
+        let seeded = IntegrationTestResult::failed("HTTP 503: upstream unavailable");
+        store_result(pool, id, &seeded).await.unwrap();
+
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/integrations", integration_routes()).with_state(Arc::new(state));
+
+        let cached = run_test(&app, &token, id, false).await;
+        assert_eq!(cached["cached"], true);
+        assert_eq!(cached["ok"], false);
+        assert_eq!(cached["error"], "HTTP 503: upstream unavailable");
+
+        let forced = run_test(&app, &token, id, true).await;
+        assert_eq!(forced["cached"], false);
+        assert_eq!(forced["ok"], true);
+        assert_eq!(forced["detail"]["provider"], "github");
+
+        // The forced run replaced the stored result
+        let cached = run_test(&app, &token, id, false).await;
+        assert_eq!(cached["cached"], true);
+        assert_eq!(cached["ok"], true);
+
+        // Past the TTL a normal test runs again
+        let stale = IntegrationTestResult { tested_at: Utc::now() - Duration::minutes(10), ..seeded };
+        store_result(pool, id, &stale).await.unwrap();
+        let fresh = run_test(&app, &token, id, false).await;
+        assert_eq!(fresh["cached"], false);
+        assert_eq!(fresh["ok"], true);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod azure_cost_alert_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::integrations::integration_routes;
+    use crate::services::azure_cost_alerts::ALERT_TYPE;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    const TOKEN: &str = "azure-cost-test-token";
+
+    fn app(pool: &PgPool) -> Router {
+        // SAFETY: every test in this module sets the same value
+        unsafe { std::env::set_var("AZURE_COST_ALERT_TOKEN", TOKEN) };
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        Router::new().nest("/integrations", integration_routes()).with_state(Arc::new(state))
+    }
+
+    async fn post(app: &Router, token: &str, payload: &serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/integrations/azure/cost-alerts?token={}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(payload.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    fn budget_payload(subscription_id: &str, spent: &str) -> serde_json::Value {
+        serde_json::json!({
+            "schemaId": "AIP Budget Notification",
+            "data": {
+                "SubscriptionName": "Acme Production",
+                "SubscriptionId": subscription_id,
+                "SpendingAmount": spent,
+                "Budget": "1000",
+                "Unit": "USD",
+                "BudgetName": "monthly",
+                "BudgetType": "Cost",
+                "NotificationThresholdAmount": "800"
+            }
+        })
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_budget_alert_opens_one_alert_per_period() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Acme') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let subscription_id = Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO azure_subscriptions (client_id, subscription_id, subscription_name, tenant_id,
+                client_id_encrypted, client_secret_encrypted, tenant_id_encrypted)
+             VALUES ($1, $2, 'Acme Production', 'tenant', 'x', 'x', 'x')"
+        )
+        .bind(client_id)
+        .bind(&subscription_id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let (status, _) = post(&app, "wrong-token", &budget_payload(&subscription_id, "850")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = post(&app, TOKEN, &budget_payload(&subscription_id, "850")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["outcome"], "alerted");
+
+        // Azure re-sends while spend stays over the threshold
+        let (_, body) = post(&app, TOKEN, &budget_payload(&subscription_id, "910")).await;
+        assert_eq!(body["outcome"], "duplicate");
+
+        let (asset_id, alerts): (Option<Uuid>, i64) = sqlx::query_as(
+            "SELECT s.asset_id, (SELECT COUNT(*) FROM alerts a WHERE a.asset_id = s.asset_id AND a.alert_type = $2)
+             FROM azure_subscriptions s WHERE s.subscription_id = $1"
+        )
+        .bind(&subscription_id)
+        .bind(ALERT_TYPE)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert!(asset_id.is_some());
+        assert_eq!(alerts, 1);
+
+        // Both deliveries are kept for audit, the repeat flagged
+        let stored: Vec<(bool, serde_json::Value)> = sqlx::query_as(
+            "SELECT duplicate, raw_payload FROM azure_cost_alerts WHERE subscription_id = LOWER($1) ORDER BY received_at"
+        )
+        .bind(&subscription_id)
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        assert_eq!(stored.len(), 2);
+        assert!(!stored[0].0 && stored[1].0);
+        assert_eq!(stored[1].1["data"]["SpendingAmount"], "910");
+
+        // An unknown subscription is recorded without an alert
+        let (_, body) = post(&app, TOKEN, &budget_payload(&Uuid::new_v4().to_string(), "850")).await;
+        assert_eq!(body["outcome"], "unmatched");
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,566 @@
+// Integration tests for itdoc software license seats, Cloudflare domain import, network discovery and IPAM
+
+#[cfg(test)]
+mod license_seat_tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::license_seats::{assign_seat, unassign_seat, SeatError, SeatHolder};
+    use crate::tests::TestContext;
+
+    struct Seeded {
+        user_id: Uuid,
+        license_id: Uuid,
+        contacts: Vec<Uuid>,
+    }
+
+    async fn seed_license(pool: &PgPool, seats: i32, contacts: usize) -> Seeded {
+        let user_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name)
+             VALUES ($1, 'x', 'License', 'Admin') RETURNING id"
+        )
+        .bind(format!("licenses-{}@resolve.test", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Seat Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        let license_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO software_licenses (client_id, name, vendor, license_type, seats, used_seats)
+             VALUES ($1, 'Office 365 E3', 'Microsoft', 'subscription', $2, 0) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(seats)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let mut contact_ids = Vec::new();
+        for i in 0..contacts {
+            let id = sqlx::query_scalar::<_, Uuid>("INSERT INTO contacts (client_id, name) VALUES ($1, $2) RETURNING id")
+                .bind(client_id)
+                .bind(format!("Contact {}", i))
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            contact_ids.push(id);
+        }
+
+        Seeded { user_id, license_id, contacts: contact_ids }
+    }
+
+    async fn used_seats(pool: &PgPool, license_id: Uuid) -> i32 {
+        sqlx::query_scalar("SELECT used_seats FROM software_licenses WHERE id = $1")
+            .bind(license_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn open_overage_alerts(pool: &PgPool, license_id: Uuid) -> i64 {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM software_license_alerts
+             WHERE license_id = $1 AND alert_type = 'overage' AND resolved_at IS NULL"
+        )
+        .bind(license_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_assignment_beyond_seats_is_rejected() {
+        let ctx = TestContext::new().await;
+        let seeded = seed_license(&ctx.db_pool, 2, 3).await;
+        let pool = &ctx.db_pool;
+
+        for contact in &seeded.contacts[..2] {
+            assign_seat(pool, seeded.license_id, SeatHolder::Contact(*contact), seeded.user_id, None, false)
+                .await
+                .unwrap();
+        }
+
+        let third = assign_seat(
+            pool,
+            seeded.license_id,
+            SeatHolder::Contact(seeded.contacts[2]),
+            seeded.user_id,
+            None,
+            false,
+        )
+        .await;
+
+        assert!(matches!(third, Err(SeatError::NoSeatsAvailable { seats: 2, used_seats: 2 })));
+        assert_eq!(used_seats(pool, seeded.license_id).await, 2);
+        assert_eq!(open_overage_alerts(pool, seeded.license_id).await, 0);
+
+        // The same contact can't hold two seats
+        let duplicate = assign_seat(
+            pool,
+            seeded.license_id,
+            SeatHolder::Contact(seeded.contacts[0]),
+            seeded.user_id,
+            None,
+            true,
+        )
+        .await;
+        assert!(matches!(duplicate, Err(SeatError::AlreadyAssigned)));
+        assert_eq!(used_seats(pool, seeded.license_id).await, 2);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_overage_raises_alert_until_back_within_seats() {
+        let ctx = TestContext::new().await;
+        let seeded = seed_license(&ctx.db_pool, 1, 2).await;
+        let pool = &ctx.db_pool;
+
+        assign_seat(pool, seeded.license_id, SeatHolder::Contact(seeded.contacts[0]), seeded.user_id, None, false)
+            .await
+            .unwrap();
+
+        let (assignment_id, usage) = assign_seat(
+            pool,
+            seeded.license_id,
+            SeatHolder::Contact(seeded.contacts[1]),
+            seeded.user_id,
+            Some("Temporary contractor"),
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert!(usage.over_allocated);
+        assert_eq!(usage.used_seats, 2);
+        assert_eq!(open_overage_alerts(pool, seeded.license_id).await, 1);
+
+        let usage = unassign_seat(pool, seeded.license_id, Some(assignment_id), None).await.unwrap();
+        assert!(!usage.over_allocated);
+        assert_eq!(usage.used_seats, 1);
+        assert_eq!(open_overage_alerts(pool, seeded.license_id).await, 0);
+
+        // Unassigning again finds no active assignment
+        assert!(matches!(
+            unassign_seat(pool, seeded.license_id, Some(assignment_id), None).await,
+            Err(SeatError::AssignmentNotFound)
+        ));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod cloudflare_import_tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::services::cloudflare_dns_import::{import_zones, CloudflareDnsClient, CloudflareError};
+    use crate::tests::TestContext;
+
+    fn zone(id: &str, name: &str, modified_on: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "name": name,
+            "status": "active",
+            "name_servers": ["ada.ns.cloudflare.com", "bob.ns.cloudflare.com"],
+            "modified_on": modified_on
+        })
+    }
+
+    fn page(result: serde_json::Value, page: u32, total_pages: u32) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": true,
+            "errors": [],
+            "result": result,
+            "result_info": { "page": page, "per_page": 50, "total_pages": total_pages }
+        }))
+    }
+
+    async fn mount_zones(server: &MockServer, modified_on: &str) {
+        Mock::given(method("GET"))
+            .and(path("/zones"))
+            .and(query_param("page", "1"))
+            .respond_with(page(serde_json::json!([zone("zone-1", "acme.example", modified_on)]), 1, 2))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/zones"))
+            .and(query_param("page", "2"))
+            .respond_with(page(serde_json::json!([zone("zone-2", "acme-new.example", "2024-01-10T08:00:00Z")]), 2, 2))
+            .mount(server)
+            .await;
+    }
+
+    async fn seed_client(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Acme DNS') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_import_creates_updates_and_skips_unchanged_zones() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let client_id = seed_client(pool).await;
+
+        let existing_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO domains (client_id, name, registrar) VALUES ($1, 'Acme.example', 'Gandi') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let cloudflare = MockServer::start().await;
+        mount_zones(&cloudflare, "2024-01-12T09:30:00Z").await;
+        Mock::given(method("GET"))
+            .and(path("/zones/zone-1/dns_records"))
+            .respond_with(page(serde_json::json!([
+                { "type": "A", "name": "acme.example", "content": "203.0.113.10", "ttl": 1 },
+                { "type": "MX", "name": "acme.example", "content": "mail.acme.example", "ttl": 3600, "priority": 10 },
+                { "type": "CAA", "name": "acme.example", "content": "0 issue \"letsencrypt.org\"", "ttl": 1 }
+            ]), 1, 1))
+            .expect(1)
+            .mount(&cloudflare)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/zones/zone-2/dns_records"))
+            .respond_with(page(serde_json::json!([
+                { "type": "CNAME", "name": "www.acme-new.example", "content": "acme-new.example", "ttl": 300 }
+            ]), 1, 1))
+            .expect(1)
+            .mount(&cloudflare)
+            .await;
+
+        let client = CloudflareDnsClient::new(reqwest::Client::new(), Some(&cloudflare.uri()));
+
+        let summary = import_zones(pool, &client, Some(client_id)).await.unwrap();
+        assert_eq!(summary.zones_seen, 2);
+        assert_eq!(summary.updated, 1);
+        assert_eq!(summary.created, 1);
+        assert!(summary.errors.is_empty());
+
+        let (nameservers, dns_records, registrar) = sqlx::query_as::<_, (Vec<String>, serde_json::Value, Option<String>)>(
+            "SELECT nameservers, dns_records, registrar FROM domains WHERE id = $1"
+        )
+        .bind(existing_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(nameservers, vec!["ada.ns.cloudflare.com", "bob.ns.cloudflare.com"]);
+        assert_eq!(registrar.as_deref(), Some("Gandi"));
+        assert_eq!(
+            dns_records,
+            serde_json::json!([
+                { "type": "A", "name": "@", "value": "203.0.113.10", "ttl": null },
+                { "type": "MX", "name": "@", "value": "mail.acme.example", "priority": 10, "ttl": 3600 }
+            ])
+        );
+
+        let created_client = sqlx::query_scalar::<_, Uuid>(
+            "SELECT client_id FROM domains WHERE cloudflare_zone_id = 'zone-2'"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(created_client, client_id);
+
+        // Nothing changed upstream: no records are fetched again
+        let summary = import_zones(pool, &client, Some(client_id)).await.unwrap();
+        assert_eq!(summary.unchanged, 2);
+        assert_eq!((summary.created, summary.updated), (0, 0));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_api_error_aborts_import() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+
+        let cloudflare = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/zones"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(serde_json::json!({
+                "success": false,
+                "errors": [{ "code": 9109, "message": "Invalid access token" }],
+                "result": null
+            })))
+            .mount(&cloudflare)
+            .await;
+
+        let client = CloudflareDnsClient::new(reqwest::Client::new(), Some(&cloudflare.uri()));
+        match import_zones(pool, &client, None).await {
+            Err(CloudflareError::Api { status, message }) => {
+                assert_eq!(status, 403);
+                assert!(message.contains("Invalid access token"));
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod network_discovery_tests {
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use uuid::Uuid;
+
+    use crate::services::network_discovery::{
+        discover, import_devices, DiscoveryConfig, DiscoveryError, HostMatch, HostScanner, Ipv4Range, ProbedHost,
+    };
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+
+    struct MockScanner(HashMap<Ipv4Addr, ProbedHost>);
+
+    #[async_trait]
+    impl HostScanner for MockScanner {
+        async fn probe(&self, ip: Ipv4Addr, _ports: &[u16]) -> Option<ProbedHost> {
+            self.0.get(&ip).cloned()
+        }
+    }
+
+    fn host(ip: &str, mac: &str, open_ports: Vec<u16>) -> (Ipv4Addr, ProbedHost) {
+        let ip: Ipv4Addr = ip.parse().unwrap();
+        (ip, ProbedHost { ip, mac: Some(mac.to_string()), open_ports })
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_scan_stages_hosts_and_import_needs_new_ones() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Scan Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let network_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO networks (client_id, name, network_type, ip_range, subnet_mask)
+             VALUES ($1, 'Office LAN', 'lan', '192.168.50.0', '255.255.255.0') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let printer_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO assets (client_id, name, asset_type, ip) VALUES ($1, 'Printer', 'printer', '192.168.50.40') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let scanner = MockScanner(HashMap::from([
+            host("192.168.50.40", "aa:bb:cc:00:00:40", vec![9100]),
+            host("192.168.50.77", "aa:bb:cc:00:00:77", vec![22, 443]),
+        ]));
+        let config = DiscoveryConfig {
+            allowed_ranges: vec![Ipv4Range::parse("192.168.0.0/16").unwrap()],
+            ..DiscoveryConfig::default()
+        };
+
+        // Ranges outside the allow-list are refused before anything is probed
+        let outside = DiscoveryConfig { allowed_ranges: vec![Ipv4Range::parse("10.0.0.0/8").unwrap()], ..config.clone() };
+        let refused = discover(pool, &scanner, &outside, network_id, &[22, 443, 9100], user.id).await;
+        assert!(matches!(refused, Err(DiscoveryError::RangeNotAllowed(_))));
+
+        let report = discover(pool, &scanner, &config, network_id, &[22, 443, 9100], user.id).await.unwrap();
+        assert_eq!(report.hosts_in_range, 254);
+        assert_eq!((report.known, report.new), (1, 1));
+        assert!(matches!(report.hosts[0].matched, HostMatch::Known { asset_id, matched_on: "ip", .. } if asset_id == printer_id));
+        assert_eq!(report.hosts[1].matched, HostMatch::New);
+        assert_eq!(report.hosts[1].host.open_ports, vec![22, 443]);
+
+        // Nothing becomes an asset until imported
+        let assets: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM assets WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(assets, 1);
+
+        // Only the new host is imported; the known one is skipped
+        let device_ids: Vec<Uuid> = report.hosts.iter().filter_map(|h| h.device_id).collect();
+        let imported = import_devices(pool, report.scan_id, &device_ids, "workstation", user.id).await.unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(Some(imported[0].device_id), report.hosts[1].device_id);
+
+        let (ip, mac, method) = sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT host(ip), mac_address, discovery_method FROM assets WHERE id = $1"
+        )
+        .bind(imported[0].asset_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(ip, "192.168.50.77");
+        assert_eq!(mac.as_deref(), Some("aa:bb:cc:00:00:77"));
+        assert_eq!(method.as_deref(), Some("scan"));
+
+        // Importing again creates nothing more
+        let again = import_devices(pool, report.scan_id, &device_ids, "workstation", user.id).await.unwrap();
+        assert!(again.is_empty());
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod ipam_tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::ipam::{allocate_next, assign, release, view_subnet, AllocationRequest, IpStatus, IpamError};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+
+    async fn seed_network(pool: &PgPool) -> (Uuid, Uuid) {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('IPAM Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let network_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO networks (client_id, name, network_type, ip_range, subnet_mask, gateway)
+             VALUES ($1, 'Servers', 'lan', '10.50.0.0/28', '255.255.255.240', '10.50.0.1') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (client_id, network_id)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_next_free_allocation_skips_taken_addresses() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+        let (client_id, network_id) = seed_network(pool).await;
+
+        // An asset entered before IPAM already holds .2
+        sqlx::query("INSERT INTO assets (client_id, name, asset_type, ip) VALUES ($1, 'DC01', 'server', '10.50.0.2')")
+            .bind(client_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        let reserved = AllocationRequest { hostname: Some("future-vip".to_string()), ..Default::default() };
+        assign(pool, network_id, "10.50.0.3".parse().unwrap(), IpStatus::Reserved, &reserved, user.id).await.unwrap();
+
+        let request = AllocationRequest { hostname: Some("app01".to_string()), ..Default::default() };
+        let first = allocate_next(pool, network_id, &request, user.id).await.unwrap();
+        assert_eq!(first.ip, "10.50.0.4");
+        assert_eq!(first.status, "used");
+        let second = allocate_next(pool, network_id, &request, user.id).await.unwrap();
+        assert_eq!(second.ip, "10.50.0.5");
+
+        // A released address is handed out again
+        release(pool, network_id, "10.50.0.4".parse().unwrap()).await.unwrap();
+        let third = allocate_next(pool, network_id, &request, user.id).await.unwrap();
+        assert_eq!(third.ip, "10.50.0.4");
+
+        let view = view_subnet(pool, network_id).await.unwrap();
+        assert_eq!(view.total, 14);
+        // Gateway, DC01, app01 twice
+        assert_eq!((view.used, view.reserved, view.free), (4, 1, 9));
+        assert_eq!(view.addresses[0].status, IpStatus::Gateway);
+        assert_eq!(view.addresses[1].hostname.as_deref(), Some("DC01"));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_already_used_address_is_rejected() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+        let (client_id, network_id) = seed_network(pool).await;
+        let ip = "10.50.0.9".parse().unwrap();
+
+        let request = AllocationRequest { hostname: Some("web01".to_string()), ..Default::default() };
+        assign(pool, network_id, ip, IpStatus::Used, &request, user.id).await.unwrap();
+
+        let again = assign(pool, network_id, ip, IpStatus::Reserved, &AllocationRequest::default(), user.id).await;
+        match again {
+            Err(IpamError::Conflict { holder, .. }) => assert_eq!(holder, "used by web01"),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+
+        // An asset's address conflicts unless that asset is the one being assigned
+        let nas_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO assets (client_id, name, asset_type, ip) VALUES ($1, 'NAS', 'storage', '10.50.0.12') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let nas_ip = "10.50.0.12".parse().unwrap();
+        let other = assign(pool, network_id, nas_ip, IpStatus::Used, &AllocationRequest::default(), user.id).await;
+        assert!(matches!(other, Err(IpamError::Conflict { .. })));
+        let own = AllocationRequest { assigned_asset_id: Some(nas_id), ..Default::default() };
+        assert!(assign(pool, network_id, nas_ip, IpStatus::Used, &own, user.id).await.is_ok());
+
+        // The gateway and addresses outside the network are refused too
+        let gateway = assign(pool, network_id, "10.50.0.1".parse().unwrap(), IpStatus::Used, &request, user.id).await;
+        assert!(matches!(gateway, Err(IpamError::Conflict { .. })));
+        let outside = assign(pool, network_id, "10.50.0.15".parse().unwrap(), IpStatus::Used, &request, user.id).await;
+        assert!(matches!(outside, Err(IpamError::OutOfRange { .. })));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_address_reused_on_another_vlan_is_not_a_conflict() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, _) = create_user_with_token(pool).await;
+        let (client_id, network_id) = seed_network(pool).await;
+        let guest_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO networks (client_id, name, network_type, ip_range, subnet_mask, gateway, vlan_id)
+             VALUES ($1, 'Guest', 'lan', '10.50.0.0/28', '255.255.255.240', '10.50.0.1', 20) RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        // The printer's .7 is on the server network
+        let printer_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO assets (client_id, name, asset_type, ip) VALUES ($1, 'Printer', 'printer', '10.50.0.7') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let ip = "10.50.0.7".parse().unwrap();
+        let printer = AllocationRequest { assigned_asset_id: Some(printer_id), ..Default::default() };
+        assign(pool, network_id, ip, IpStatus::Used, &printer, user.id).await.unwrap();
+
+        // so the guest VLAN can use .7 as well
+        let kiosk = AllocationRequest { hostname: Some("kiosk".to_string()), ..Default::default() };
+        assert!(assign(pool, guest_id, ip, IpStatus::Used, &kiosk, user.id).await.is_ok());
+        let view = view_subnet(pool, guest_id).await.unwrap();
+        assert_eq!(view.used, 2);
+
+        ctx.cleanup().await;
+    }
+}
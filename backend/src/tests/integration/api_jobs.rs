@@ -0,0 +1,142 @@
+// Integration tests for background job run history, repeated-failure alerts
+// and the advisory locks that keep replicas from running a job twice
+
+#[cfg(test)]
+mod job_history_tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::config::SmtpConfig;
+    use crate::jobs::history::{self, JobTrigger};
+    use crate::jobs::scheduler::{JobExecutionLog, JobOutcome};
+    use crate::jobs::{JobConfig, JobRunner};
+    use crate::services::EmailService;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+
+    async fn runner(pool: &PgPool) -> JobRunner {
+        let smtp = SmtpConfig {
+            host: "localhost".to_string(),
+            port: 2525,
+            username: String::new(),
+            password: String::new(),
+            from_email: "noreply@resolve.test".to_string(),
+            from_name: "Resolve".to_string(),
+            use_tls: false,
+        };
+        let email_service = EmailService::new(&smtp).await.unwrap();
+        let config = JobConfig { job_failure_alert_threshold: 3, ..JobConfig::default() };
+        JobRunner::new(pool.clone(), email_service, WsManager::new(), config)
+    }
+
+    async fn fail(runner: &JobRunner, message: &str) -> JobExecutionLog {
+        let run = runner.start("late_fees", JobTrigger::Schedule).await.unwrap();
+        runner.finish(run, Err(message.into())).await
+    }
+
+    async fn alerts(pool: &PgPool, user_id: Uuid) -> i64 {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND notification_type = 'job_failure'"
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_failing_job_records_error_and_alerts_after_threshold() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        sqlx::query("DELETE FROM job_runs").execute(pool).await.unwrap();
+        let (admin, _) = create_user_with_token(pool).await;
+        sqlx::query("UPDATE users SET role_id = (SELECT id FROM roles WHERE name = 'admin'), is_active = true WHERE id = $1")
+            .bind(admin.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        let runner = runner(pool).await;
+
+        let first = fail(&runner, "connection refused").await;
+        let row = history::runs_for(pool, "late_fees", 1).await.unwrap().remove(0);
+        assert_eq!(row.id, first.id);
+        assert_eq!(row.status, "failed");
+        assert_eq!(row.trigger, "schedule");
+        assert_eq!(row.error.as_deref(), Some("connection refused"));
+        assert!(row.completed_at.is_some());
+        assert_eq!(alerts(pool, admin.id).await, 0);
+
+        fail(&runner, "connection refused").await;
+        assert_eq!(alerts(pool, admin.id).await, 0);
+
+        // The third failure in a row reaches the threshold
+        fail(&runner, "connection refused").await;
+        assert_eq!(history::consecutive_failures(pool, "late_fees").await.unwrap(), 3);
+        assert_eq!(alerts(pool, admin.id).await, 1);
+
+        // Further failures in the same streak don't alert again
+        fail(&runner, "connection refused").await;
+        assert_eq!(alerts(pool, admin.id).await, 1);
+
+        // A successful run ends the streak
+        let run = runner.start("late_fees", JobTrigger::Manual(Some(admin.id))).await.unwrap();
+        runner.finish(run, Ok(JobOutcome { items_processed: 2, errors: Vec::new() })).await;
+        assert_eq!(history::consecutive_failures(pool, "late_fees").await.unwrap(), 0);
+        let latest = history::latest_runs(pool).await.unwrap().remove("late_fees").unwrap();
+        assert_eq!(latest.status, "completed");
+        assert_eq!(latest.triggered_by, Some(admin.id));
+        assert_eq!(latest.items_processed, 2);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod job_lock_tests {
+    use crate::config::SmtpConfig;
+    use crate::jobs::history::{self, JobTrigger};
+    use crate::jobs::lock::JobLock;
+    use crate::jobs::{JobConfig, JobError, JobRunner};
+    use crate::services::EmailService;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_second_run_is_skipped_while_the_lock_is_held() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+
+        // Each lock holds its own pool connection, as two replicas would
+        let first = JobLock::try_acquire(pool, "recurring_billing").await.unwrap().expect("lock is free");
+        assert!(JobLock::try_acquire(pool, "recurring_billing").await.unwrap().is_none());
+        // Other jobs aren't affected
+        let other = JobLock::try_acquire(pool, "late_fees").await.unwrap().expect("different job");
+        other.release().await.unwrap();
+
+        let smtp = SmtpConfig {
+            host: "localhost".to_string(),
+            port: 2525,
+            username: String::new(),
+            password: String::new(),
+            from_email: "noreply@resolve.test".to_string(),
+            from_name: "Resolve".to_string(),
+            use_tls: false,
+        };
+        let email_service = EmailService::new(&smtp).await.unwrap();
+        let runner = JobRunner::new(pool.clone(), email_service, WsManager::new(), JobConfig::default());
+        let runs_before = history::runs_for(pool, "recurring_billing", 500).await.unwrap().len();
+        let skipped = runner.start("recurring_billing", JobTrigger::Schedule).await.unwrap_err();
+        assert!(matches!(skipped, JobError::AlreadyRunning(_)));
+        // A skipped run isn't recorded
+        assert_eq!(history::runs_for(pool, "recurring_billing", 500).await.unwrap().len(), runs_before);
+
+        first.release().await.unwrap();
+        let again = JobLock::try_acquire(pool, "recurring_billing").await.unwrap().expect("released");
+        again.release().await.unwrap();
+
+        ctx.cleanup().await;
+    }
+}
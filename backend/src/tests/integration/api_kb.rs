@@ -0,0 +1,477 @@
+// Knowledge base API integration tests
+
+#[cfg(test)]
+mod kb_search_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::knowledge_base_routes;
+    use crate::services::kb_search::{self, SearchScope};
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        knowledge_base_routes().with_state(Arc::new(state))
+    }
+
+    async fn get(app: &Router, uri: &str, session: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder().method("GET").uri(uri);
+        if let Some(session) = session {
+            request = request.header("X-Session-Id", session);
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn seed_category(pool: &PgPool, name: &str) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>("INSERT INTO kb_categories (name) VALUES ($1) RETURNING id")
+            .bind(name)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn seed_article(pool: &PgPool, category_id: Uuid, title: &str, status: &str, is_public: bool) -> Uuid {
+        let author_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name) VALUES ($1, 'x', 'Kim', 'Writer') RETURNING id"
+        )
+        .bind(format!("kb-{}@msp.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO kb_articles (title, slug, content, summary, category_id, author_id, status, is_public)
+            VALUES ($1, $2, 'Power cycle the printer and clear the spooler queue.', 'Printer troubleshooting', $3, $4, $5, $6)
+            RETURNING id
+            "#
+        )
+        .bind(title)
+        .bind(format!("article-{}", Uuid::new_v4()))
+        .bind(category_id)
+        .bind(author_id)
+        .bind(status)
+        .bind(is_public)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    fn ids(body: &serde_json::Value) -> Vec<String> {
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_public_search_excludes_private_and_unpublished() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let printers = seed_category(pool, "Printers").await;
+        let network = seed_category(pool, "Network").await;
+
+        let public = seed_article(pool, printers, "Printer offline", "published", true).await;
+        let private = seed_article(pool, printers, "Printer admin passwords", "published", false).await;
+        let draft = seed_article(pool, printers, "Printer fleet rollout", "draft", true).await;
+        let other = seed_article(pool, network, "Network printer discovery", "published", true).await;
+
+        let (status, body) = get(&app, "/portal/search?q=printer", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let found = ids(&body);
+        assert!(found.contains(&public.to_string()));
+        assert!(found.contains(&other.to_string()));
+        assert!(!found.contains(&private.to_string()));
+        assert!(!found.contains(&draft.to_string()));
+
+        // Staff search sees everything
+        let (_, body) = get(&app, "/search?q=printer", None).await;
+        let found = ids(&body);
+        for id in [public, private, draft, other] {
+            assert!(found.contains(&id.to_string()));
+        }
+
+        // Category filtering applies on top of the scope
+        let (status, body) = get(&app, &format!("/portal/search?q=printer&category_id={}", printers), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&body), vec![public.to_string()]);
+
+        // Ranked results come back through the service too
+        let (results, _) = kb_search::search_articles(pool, "offline", SearchScope::Public, None, 20, 0).await.unwrap();
+        assert_eq!(results.first().map(|r| r.id), Some(public));
+
+        // Private and draft articles can't be fetched through the portal either
+        let (status, _) = get(&app, &format!("/portal/articles/{}", private), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(&app, &format!("/portal/articles/{}", draft), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(&app, "/portal/search?q=%20", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_views_are_debounced_per_session() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let category = seed_category(pool, "Email").await;
+        let article = seed_article(pool, category, "Mailbox full", "published", true).await;
+        let uri = format!("/portal/articles/{}", article);
+
+        let views = || {
+            sqlx::query_scalar::<_, i32>("SELECT views FROM kb_articles WHERE id = $1")
+                .bind(article)
+                .fetch_one(pool)
+        };
+
+        for _ in 0..3 {
+            let (status, _) = get(&app, &uri, Some("session-one")).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert_eq!(views().await.unwrap(), 1);
+
+        get(&app, &uri, Some("session-two")).await;
+        assert_eq!(views().await.unwrap(), 2);
+
+        // Without a session there is nothing to debounce on, so it isn't counted
+        get(&app, &uri, None).await;
+        assert_eq!(views().await.unwrap(), 2);
+
+        // Once the window has passed the same session counts again
+        sqlx::query("UPDATE kb_article_views SET viewed_at = NOW() - INTERVAL '1 hour' WHERE article_id = $1")
+            .bind(article)
+            .execute(pool)
+            .await
+            .unwrap();
+        get(&app, &uri, Some("session-one")).await;
+        assert_eq!(views().await.unwrap(), 3);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod kb_version_tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::kb_versions::{self, VersionError};
+    use crate::tests::TestContext;
+
+    async fn seed_author(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name) VALUES ($1, 'x', 'Kim', 'Writer') RETURNING id"
+        )
+        .bind(format!("kb-{}@msp.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    /// Save the article's text and record the version, as the update
+    /// handler does
+    async fn edit(pool: &PgPool, article_id: Uuid, author_id: Uuid, summary: &str, content: &str) {
+        let mut tx = pool.begin().await.unwrap();
+        sqlx::query("UPDATE kb_articles SET summary = $2, content = $3, updated_at = NOW() WHERE id = $1")
+            .bind(article_id)
+            .bind(summary)
+            .bind(content)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        kb_versions::record_version(&mut *tx, article_id, Some(author_id), None).await.unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_edits_create_versions_and_revert_adds_a_copy() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let author_id = seed_author(pool).await;
+        let editor_id = seed_author(pool).await;
+
+        let article_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO kb_articles (title, slug, content, summary, author_id, status)
+            VALUES ('Reset MFA', $1, 'Step 1: open the admin portal.\nStep 2: reset MFA.', 'Resetting MFA', $2, 'published')
+            RETURNING id
+            "#
+        )
+        .bind(format!("reset-mfa-{}", Uuid::new_v4()))
+        .bind(author_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        kb_versions::record_version(&mut *tx, article_id, Some(author_id), None).await.unwrap();
+        tx.commit().await.unwrap();
+
+        edit(pool, article_id, editor_id, "Resetting MFA for a user", "Step 1: open Entra.\nStep 2: reset MFA.").await;
+        edit(pool, article_id, editor_id, "Oops", "").await;
+
+        let versions = kb_versions::list_versions(pool, article_id).await.unwrap();
+        assert_eq!(versions.iter().map(|v| v.version_number).collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert_eq!(versions[0].content, "");
+        assert_eq!(versions[1].author_id, Some(editor_id));
+        assert_eq!(versions[1].author_name.as_deref(), Some("Kim Writer"));
+        assert_eq!(versions[2].author_id, Some(author_id));
+
+        // Reverting the accidental edit restores version 2 as version 4
+        let restored = kb_versions::revert(pool, article_id, 2, Some(author_id)).await.unwrap();
+        assert_eq!(restored.version_number, 4);
+        assert_eq!(restored.reverted_from, Some(2));
+        assert_eq!(restored.title, versions[1].title);
+        assert_eq!(restored.summary, versions[1].summary);
+        assert_eq!(restored.excerpt, versions[1].excerpt);
+        assert_eq!(restored.content, versions[1].content);
+
+        let (summary, content): (Option<String>, String) =
+            sqlx::query_as("SELECT summary, content FROM kb_articles WHERE id = $1")
+                .bind(article_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        assert_eq!(summary.as_deref(), Some("Resetting MFA for a user"));
+        assert_eq!(content, "Step 1: open Entra.\nStep 2: reset MFA.");
+
+        // Nothing in the history was overwritten
+        let versions = kb_versions::list_versions(pool, article_id).await.unwrap();
+        assert_eq!(versions.len(), 4);
+        assert_eq!(versions[1].content, "");
+
+        assert!(matches!(
+            kb_versions::revert(pool, article_id, 9, Some(author_id)).await,
+            Err(VersionError::VersionNotFound(9))
+        ));
+        assert!(matches!(
+            kb_versions::list_versions(pool, Uuid::new_v4()).await,
+            Err(VersionError::ArticleNotFound)
+        ));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod kb_vote_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::knowledge_base_routes;
+    use crate::services::kb_search::user_viewer_key;
+    use crate::services::kb_votes::{self, VoteError};
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        knowledge_base_routes().with_state(Arc::new(state))
+    }
+
+    /// Vote, returning the status, tallies and any cookie that was set
+    async fn vote(app: &Router, article_id: Uuid, helpful: bool, cookie: Option<&str>) -> (StatusCode, serde_json::Value, Option<String>) {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(format!("/{}/vote", article_id))
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        let body = Body::from(serde_json::json!({ "helpful": helpful }).to_string());
+        let response = app.clone().oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let set_cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .map(|v| v.to_str().unwrap().split(';').next().unwrap().to_string());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null), set_cookie)
+    }
+
+    async fn seed_article(pool: &PgPool) -> (Uuid, Uuid) {
+        let author_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name) VALUES ($1, 'x', 'Kim', 'Writer') RETURNING id"
+        )
+        .bind(format!("kb-{}@msp.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let article_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO kb_articles (title, slug, content, author_id, status, is_public)
+            VALUES ('Map a network drive', $1, 'Open File Explorer...', $2, 'published', true)
+            RETURNING id
+            "#
+        )
+        .bind(format!("map-drive-{}", Uuid::new_v4()))
+        .bind(author_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (article_id, author_id)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_second_vote_from_same_user_updates_rather_than_double_counts() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (article_id, user_id) = seed_article(pool).await;
+        let voter = user_viewer_key(user_id);
+
+        let tally = kb_votes::cast_vote(pool, article_id, &voter, true).await.unwrap();
+        assert_eq!((tally.helpful_count, tally.not_helpful_count), (1, 0));
+
+        // Repeating the same vote changes nothing
+        let tally = kb_votes::cast_vote(pool, article_id, &voter, true).await.unwrap();
+        assert_eq!((tally.helpful_count, tally.not_helpful_count), (1, 0));
+
+        // Changing it moves the count across
+        let tally = kb_votes::cast_vote(pool, article_id, &voter, false).await.unwrap();
+        assert_eq!((tally.helpful_count, tally.not_helpful_count), (0, 1));
+        assert!(!tally.helpful);
+
+        let votes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM kb_article_votes WHERE article_id = $1")
+            .bind(article_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(votes, 1);
+
+        assert!(matches!(
+            kb_votes::cast_vote(pool, Uuid::new_v4(), &voter, true).await,
+            Err(VoteError::ArticleNotFound)
+        ));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_anonymous_votes_are_keyed_on_the_signed_session_cookie() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (article_id, _) = seed_article(pool).await;
+
+        let (status, body, cookie) = vote(&app, article_id, true, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["helpful_count"], 1);
+        let cookie = cookie.expect("session cookie issued");
+
+        // The same session changing its mind doesn't add a vote
+        let (_, body, reissued) = vote(&app, article_id, false, Some(&cookie)).await;
+        assert_eq!(body["helpful_count"], 0);
+        assert_eq!(body["not_helpful_count"], 1);
+        assert!(reissued.is_none());
+
+        // A tampered cookie isn't trusted and counts as a new visitor
+        let (_, body, reissued) = vote(&app, article_id, true, Some("kb_session=forged.00")).await;
+        assert_eq!(body["helpful_count"], 1);
+        assert_eq!(body["not_helpful_count"], 1);
+        assert!(reissued.is_some());
+
+        let (status, _, _) = vote(&app, Uuid::new_v4(), true, Some(&cookie)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod kb_etag_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::response::Response;
+    use axum::Router;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::knowledge_base_routes;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    async fn get(app: &Router, uri: &str, if_none_match: Option<&str>) -> Response {
+        let mut request = Request::builder().method("GET").uri(uri);
+        if let Some(etag) = if_none_match {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    fn etag(response: &Response) -> String {
+        response.headers()[header::ETAG].to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_article_detail_honours_if_none_match() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let article_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO kb_articles (title, slug, content, status, is_public)
+             VALUES ('Reset MFA', $1, 'Remove the old device first.', 'published', true) RETURNING id"
+        )
+        .bind(format!("article-{}", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = knowledge_base_routes().with_state(Arc::new(state));
+
+        for uri in [format!("/articles/{}", article_id), format!("/portal/articles/{}", article_id)] {
+            let first = get(&app, &uri, None).await;
+            assert_eq!(first.status(), StatusCode::OK);
+            let tag = etag(&first);
+
+            let cached = get(&app, &uri, Some(&tag)).await;
+            assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(etag(&cached), tag);
+            let body = axum::body::to_bytes(cached.into_body(), usize::MAX).await.unwrap();
+            assert!(body.is_empty());
+        }
+
+        let uri = format!("/articles/{}", article_id);
+        let before = etag(&get(&app, &uri, None).await);
+
+        // A vote doesn't touch updated_at but still changes what's served
+        sqlx::query("UPDATE kb_articles SET helpful_count = helpful_count + 1 WHERE id = $1")
+            .bind(article_id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let changed = get(&app, &uri, Some(&before)).await;
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(etag(&changed), before);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,128 @@
+// Password API integration tests
+
+#[cfg(test)]
+mod credential_grant_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use chrono::{Duration, Utc};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::services::EncryptionService;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::{handlers, AppState};
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        handlers::password_routes().with_state(Arc::new(state))
+    }
+
+    async fn read(app: &Router, token: &str, password_id: Uuid) -> StatusCode {
+        let request = Request::builder()
+            .uri(format!("/{}", password_id))
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    async fn seed_password(pool: &PgPool, owner: Uuid) -> Uuid {
+        let encrypted = EncryptionService::new().unwrap().encrypt("hunter2-but-longer").unwrap();
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO passwords (name, password_encrypted, created_by) VALUES ('Firewall admin', $1, $2) RETURNING id"
+        )
+        .bind(encrypted)
+        .bind(owner)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn grant(pool: &PgPool, password_id: Uuid, grantee: Uuid, granted_by: Uuid, expires_in: Duration) {
+        sqlx::query(
+            "INSERT INTO credential_grants (password_id, grantee_id, granted_by, expires_at) VALUES ($1, $2, $3, $4)"
+        )
+        .bind(password_id)
+        .bind(grantee)
+        .bind(granted_by)
+        .bind(Utc::now() + expires_in)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn audit_actions(pool: &PgPool, password_id: Uuid, user_id: Uuid) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT action FROM audit_logs WHERE entity_type = 'password' AND entity_id = $1 AND user_id = $2 ORDER BY created_at"
+        )
+        .bind(password_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_expired_grant_is_denied() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (owner, _) = create_user_with_token(pool).await;
+        let (tech, tech_token) = create_user_with_token(pool).await;
+        let password_id = seed_password(pool, owner.id).await;
+
+        assert_eq!(read(&app, &tech_token, password_id).await, StatusCode::FORBIDDEN);
+
+        grant(pool, password_id, tech.id, owner.id, Duration::minutes(-5)).await;
+        assert_eq!(read(&app, &tech_token, password_id).await, StatusCode::FORBIDDEN);
+
+        grant(pool, password_id, tech.id, owner.id, Duration::hours(1)).await;
+        assert_eq!(read(&app, &tech_token, password_id).await, StatusCode::OK);
+
+        sqlx::query("UPDATE credential_grants SET expires_at = NOW() - INTERVAL '1 second' WHERE grantee_id = $1")
+            .bind(tech.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        assert_eq!(read(&app, &tech_token, password_id).await, StatusCode::FORBIDDEN);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reads_are_logged() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (owner, owner_token) = create_user_with_token(pool).await;
+        let (tech, tech_token) = create_user_with_token(pool).await;
+        let password_id = seed_password(pool, owner.id).await;
+
+        assert_eq!(read(&app, &owner_token, password_id).await, StatusCode::OK);
+        assert_eq!(read(&app, &tech_token, password_id).await, StatusCode::FORBIDDEN);
+        grant(pool, password_id, tech.id, owner.id, Duration::hours(1)).await;
+        assert_eq!(read(&app, &tech_token, password_id).await, StatusCode::OK);
+
+        assert_eq!(audit_actions(pool, password_id, owner.id).await, vec!["READ"]);
+        assert_eq!(audit_actions(pool, password_id, tech.id).await, vec!["ACCESS_DENIED", "READ"]);
+
+        let via: serde_json::Value = sqlx::query_scalar(
+            "SELECT new_values FROM audit_logs WHERE entity_id = $1 AND user_id = $2 AND action = 'READ'"
+        )
+        .bind(password_id)
+        .bind(tech.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(via["via"], "grant");
+
+        ctx.cleanup().await;
+    }
+}
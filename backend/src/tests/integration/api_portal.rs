@@ -0,0 +1,357 @@
+// Client portal API integration tests
+
+#[cfg(test)]
+mod portal_ticket_tests {
+    use axum::body::Body;
+    use axum::http::{header, Method, Request, StatusCode};
+    use axum::Router;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::portal_routes;
+    use crate::services::portal_tickets::PORTAL_USER_ID;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        portal_routes().with_state(Arc::new(state))
+    }
+
+    async fn send(app: &Router, method: Method, uri: &str, token: &str, body: Option<serde_json::Value>) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("X-Portal-Token", token)
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// A client with a signed-in contact, returning the client, contact and portal token
+    async fn seed_contact(pool: &PgPool, client_name: &str) -> (Uuid, Uuid, String) {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ($1) RETURNING id")
+            .bind(client_name)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let contact_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO contacts (client_id, name, email) VALUES ($1, 'Pat Portal', $2) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(format!("pat-{}@client.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let token = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO portal_access_tokens (contact_id, token, expires_at) VALUES ($1, $2, NOW() + INTERVAL '1 hour')")
+            .bind(contact_id)
+            .bind(&token)
+            .execute(pool)
+            .await
+            .unwrap();
+        (client_id, contact_id, token)
+    }
+
+    async fn create_ticket(app: &Router, token: &str, subject: &str) -> Uuid {
+        let (status, body) = send(
+            app,
+            Method::POST,
+            "/tickets",
+            token,
+            Some(serde_json::json!({ "subject": subject, "details": "Details", "priority": "high" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        body["id"].as_str().unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_portal_ticket_is_created_for_contacts_client() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (client_id, contact_id, token) = seed_contact(pool, "Portal Client").await;
+
+        let ticket_id = create_ticket(&app, &token, "VPN drops").await;
+
+        let (owner, contact, opened_by, source, priority) = sqlx::query_as::<_, (Uuid, Option<Uuid>, Uuid, Option<String>, Option<String>)>(
+            "SELECT client_id, contact_id, opened_by, source, priority FROM tickets WHERE id = $1"
+        )
+        .bind(ticket_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(owner, client_id);
+        assert_eq!(contact, Some(contact_id));
+        assert_eq!(opened_by, PORTAL_USER_ID);
+        assert_eq!(source.as_deref(), Some("portal"));
+        assert_eq!(priority.as_deref(), Some("high"));
+
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/tickets",
+            &token,
+            Some(serde_json::json!({ "subject": " ", "details": "Details" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = send(&app, Method::GET, "/tickets", "not-a-token", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_portal_never_exposes_other_clients_tickets() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (_, _, token_a) = seed_contact(pool, "Client A").await;
+        let (_, _, token_b) = seed_contact(pool, "Client B").await;
+
+        let ticket_a = create_ticket(&app, &token_a, "A's printer").await;
+        let ticket_b = create_ticket(&app, &token_b, "B's laptop").await;
+
+        let (status, body) = send(&app, Method::GET, "/tickets", &token_a, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids: Vec<&str> = body.as_array().unwrap().iter().map(|t| t["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec![ticket_a.to_string()]);
+        // Listing is limited to summary fields
+        assert!(body[0].get("details").is_none());
+
+        for uri in [format!("/tickets/{}", ticket_b), format!("/tickets/{}/replies", ticket_b)] {
+            let (status, _) = send(&app, Method::GET, &uri, &token_a, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        }
+
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            &format!("/tickets/{}/replies", ticket_b),
+            &token_a,
+            Some(serde_json::json!({ "message": "Let me in" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let replies = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM ticket_replies WHERE ticket_id = $1")
+            .bind(ticket_b)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(replies, 0);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_portal_hides_internal_notes() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let (_, _, token) = seed_contact(pool, "Notes Client").await;
+        let ticket_id = create_ticket(&app, &token, "Slow email").await;
+
+        let tech_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name) VALUES ($1, 'x', 'Tess', 'Tech') RETURNING id"
+        )
+        .bind(format!("tess-{}@msp.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO ticket_replies (ticket_id, user_id, type, details) VALUES
+             ($1, $2, 'reply', 'We are looking into it'),
+             ($1, $2, 'note', 'Client is on the legacy plan')"
+        )
+        .bind(ticket_id)
+        .bind(tech_id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            &format!("/tickets/{}/replies", ticket_id),
+            &token,
+            Some(serde_json::json!({ "message": "Thanks" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = send(&app, Method::GET, &format!("/tickets/{}", ticket_id), &token, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let replies = body["replies"].as_array().unwrap();
+        let messages: Vec<&str> = replies.iter().map(|r| r["message"].as_str().unwrap()).collect();
+        assert_eq!(messages, vec!["We are looking into it", "Thanks"]);
+        assert_eq!(replies[0]["author_type"], "support");
+        assert_eq!(replies[1]["author_type"], "contact");
+
+        let (_, body) = send(&app, Method::GET, &format!("/tickets/{}/replies", ticket_id), &token, None).await;
+        assert!(body.as_array().unwrap().iter().all(|r| r["message"] != "Client is on the legacy plan"));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod ticket_reply_tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Method, Request, StatusCode};
+    use axum::Router;
+    use chrono::{DateTime, Utc};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::{handlers, AppState};
+
+    async fn send(app: &Router, method: Method, uri: &str, auth: (&str, &str), body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(auth.0, auth.1)
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_internal_replies_are_hidden_and_first_public_reply_is_the_response() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (tech, token) = create_user_with_token(pool).await;
+        let bearer = format!("Bearer {}", token);
+        let staff = ("authorization", bearer.as_str());
+
+        let client_id: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Reply Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let contact_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO contacts (client_id, name, email) VALUES ($1, 'Pat Portal', $2) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(format!("pat-{}@client.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let portal_token = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO portal_access_tokens (contact_id, token, expires_at) VALUES ($1, $2, NOW() + INTERVAL '1 hour')")
+            .bind(contact_id)
+            .bind(&portal_token)
+            .execute(pool)
+            .await
+            .unwrap();
+        let portal = ("X-Portal-Token", portal_token.as_str());
+        let ticket_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO tickets (client_id, contact_id, opened_by, subject, details, status)
+             VALUES ($1, $2, $3, 'VPN drops', 'Every hour', 'open') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(contact_id)
+        .bind(tech.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let file_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO files (client_id, filename, original_filename, mime_type, file_size, file_path, uploaded_by)
+             VALUES ($1, 'vpn.log', 'vpn.log', 'text/plain', 42, 'vpn.log', $2) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(tech.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let state = Arc::new(AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None });
+        let app = Router::new()
+            .nest("/tickets", handlers::ticket_routes())
+            .nest("/portal", handlers::portal_routes())
+            .with_state(state);
+        let replies_uri = format!("/tickets/{}/replies", ticket_id);
+        let response_at = || async {
+            sqlx::query_scalar::<_, Option<DateTime<Utc>>>("SELECT sla_response_at FROM tickets WHERE id = $1")
+                .bind(ticket_id)
+                .fetch_one(pool)
+                .await
+                .unwrap()
+        };
+
+        let (status, body) = send(&app, Method::POST, &replies_uri, staff, Some(json!({
+            "details": "Customer is on the old VPN client", "internal": true
+        }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["internal"], true);
+        assert!(response_at().await.is_none(), "an internal reply is not a response");
+
+        let (status, body) = send(&app, Method::POST, &replies_uri, staff, Some(json!({
+            "details": "Please update your VPN client", "attachment_ids": [file_id]
+        }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["internal"], false);
+        assert_eq!(body["attachments"][0]["id"], file_id.to_string());
+        let first_response = response_at().await.expect("first public reply stamps the response");
+
+        let (status, _) = send(&app, Method::POST, &replies_uri, staff, Some(json!({
+            "details": "Following up", "attachment_ids": [file_id]
+        }))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "a file belongs to one reply");
+        let other_client: Uuid = sqlx::query_scalar("INSERT INTO clients (name) VALUES ('Other Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let other_file: Uuid = sqlx::query_scalar(
+            "INSERT INTO files (client_id, filename, original_filename, mime_type, file_size, file_path, uploaded_by)
+             VALUES ($1, 'payroll.xlsx', 'payroll.xlsx', 'application/octet-stream', 42, 'payroll.xlsx', $2) RETURNING id"
+        )
+        .bind(other_client)
+        .bind(tech.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let (status, _) = send(&app, Method::POST, &replies_uri, staff, Some(json!({
+            "details": "Here you go", "attachment_ids": [other_file]
+        }))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "another client's file can't be attached");
+        let (status, _) = send(&app, Method::POST, &replies_uri, staff, Some(json!({ "details": "Following up" }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(response_at().await, Some(first_response));
+
+        let (status, body) = send(&app, Method::GET, &replies_uri, staff, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 3);
+        let (_, body) = send(&app, Method::GET, &format!("{}?internal=false", replies_uri), staff, None).await;
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        let (status, body) = send(&app, Method::GET, &format!("/portal/tickets/{}/replies", ticket_id), portal, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let messages: Vec<&str> = body.as_array().unwrap().iter().map(|r| r["message"].as_str().unwrap()).collect();
+        assert_eq!(messages, vec!["Please update your VPN client", "Following up"]);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,313 @@
+// Project task dependency integration tests
+
+#[cfg(test)]
+mod task_dependency_tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::task_dependencies::{self, DependencyError};
+    use crate::tests::TestContext;
+
+    async fn seed_project(pool: &PgPool) -> Uuid {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Rollout Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query_scalar::<_, Uuid>("INSERT INTO projects (client_id, name) VALUES ($1, 'Office move') RETURNING id")
+            .bind(client_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn seed_task(pool: &PgPool, project_id: Uuid, name: &str, hours: i32) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO tasks (project_id, name, estimated_hours) VALUES ($1, $2, $3) RETURNING id"
+        )
+        .bind(project_id)
+        .bind(name)
+        .bind(rust_decimal::Decimal::from(hours))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn complete(pool: &PgPool, task_id: Uuid) {
+        sqlx::query("UPDATE tasks SET status = 'completed', completed_at = NOW() WHERE id = $1")
+            .bind(task_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cycles_are_rejected() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let project_id = seed_project(pool).await;
+        let cabling = seed_task(pool, project_id, "Cabling", 8).await;
+        let switches = seed_task(pool, project_id, "Switches", 4).await;
+        let desks = seed_task(pool, project_id, "Desks", 2).await;
+
+        task_dependencies::add(pool, switches, cabling).await.unwrap();
+        task_dependencies::add(pool, desks, switches).await.unwrap();
+        // Adding the same dependency twice is harmless
+        task_dependencies::add(pool, desks, switches).await.unwrap();
+
+        // Direct and transitive loops back to the start are refused
+        assert!(matches!(task_dependencies::add(pool, cabling, switches).await, Err(DependencyError::Cycle)));
+        assert!(matches!(task_dependencies::add(pool, cabling, desks).await, Err(DependencyError::Cycle)));
+        assert!(matches!(task_dependencies::add(pool, desks, desks).await, Err(DependencyError::SelfDependency)));
+
+        let other_project = seed_project(pool).await;
+        let elsewhere = seed_task(pool, other_project, "Elsewhere", 1).await;
+        assert!(matches!(
+            task_dependencies::add(pool, desks, elsewhere).await,
+            Err(DependencyError::DifferentProject)
+        ));
+
+        let edges: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM task_dependencies WHERE task_id = ANY($1)")
+            .bind(vec![cabling, switches, desks])
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(edges, 2);
+
+        // Once the chain is broken the reverse edge is allowed
+        assert!(task_dependencies::remove(pool, desks, switches).await.unwrap());
+        task_dependencies::add(pool, cabling, desks).await.unwrap();
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_blocked_status_follows_prerequisite_completion() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let project_id = seed_project(pool).await;
+        let cabling = seed_task(pool, project_id, "Cabling", 8).await;
+        let switches = seed_task(pool, project_id, "Switches", 4).await;
+        let desks = seed_task(pool, project_id, "Desks", 2).await;
+        task_dependencies::add(pool, switches, cabling).await.unwrap();
+        task_dependencies::add(pool, desks, switches).await.unwrap();
+
+        let schedule = task_dependencies::project_schedule(pool, project_id).await.unwrap();
+        let order: Vec<Uuid> = schedule.iter().map(|t| t.id).collect();
+        assert_eq!(order, vec![cabling, switches, desks]);
+        assert_eq!(schedule[2].earliest_start_hours, rust_decimal::Decimal::from(12));
+        assert!(schedule.iter().all(|t| t.critical));
+        assert!(!schedule[0].blocked && schedule[1].blocked && schedule[2].blocked);
+
+        complete(pool, cabling).await;
+        assert!(!task_dependencies::status(pool, switches).await.unwrap().blocked);
+        // Desks still waits on switches
+        assert!(task_dependencies::status(pool, desks).await.unwrap().blocked);
+
+        complete(pool, switches).await;
+        let desks_status = task_dependencies::status(pool, desks).await.unwrap();
+        assert!(!desks_status.blocked);
+        assert_eq!(desks_status.depends_on.len(), 1);
+
+        let schedule = task_dependencies::project_schedule(pool, project_id).await.unwrap();
+        assert_eq!(schedule[2].earliest_start_hours, rust_decimal::Decimal::ZERO);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod project_budget_tests {
+    use chrono::{Duration, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    use crate::services::project_budget::{self, ALERT_TYPE};
+    use crate::tests::TestContext;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    /// A project billed at 100/hour with a manager, and a technician with
+    /// their own rate
+    async fn seed(pool: &PgPool, budget: Option<&str>) -> (Uuid, Uuid, Uuid) {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Budget Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let manager_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name) VALUES ($1, 'x', 'Pat', 'Manager') RETURNING id"
+        )
+        .bind(format!("pm-{}@msp.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let tech_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name, hourly_rate) VALUES ($1, 'x', 'Sam', 'Tech', 60) RETURNING id"
+        )
+        .bind(format!("tech-{}@msp.example", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let project_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO projects (client_id, name, budget, hourly_rate, project_manager_id, start_date, end_date)
+            VALUES ($1, 'Firewall refresh', $2, 100, $3, CURRENT_DATE - 9, CURRENT_DATE + 20)
+            RETURNING id
+            "#
+        )
+        .bind(client_id)
+        .bind(budget.map(dec))
+        .bind(manager_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        (project_id, tech_id, manager_id)
+    }
+
+    async fn log_time(pool: &PgPool, project_id: Uuid, user_id: Uuid, billable_minutes: i32, billable: bool) {
+        let start = Utc::now() - Duration::days(2);
+        sqlx::query(
+            r#"
+            INSERT INTO time_entries (user_id, project_id, start_time, end_time, duration_minutes, billable_minutes, billable)
+            VALUES ($1, $2, $3, $4, $5, $5, $6)
+            "#
+        )
+        .bind(user_id)
+        .bind(project_id)
+        .bind(start)
+        .bind(start + Duration::minutes(i64::from(billable_minutes)))
+        .bind(billable_minutes)
+        .bind(billable)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn add_expense(pool: &PgPool, project_id: Uuid, amount: &str) {
+        sqlx::query(
+            r#"
+            INSERT INTO expenses (project_id, category, vendor, description, amount, expense_date)
+            VALUES ($1, 'hardware', 'Distributor', 'Firewall appliance', $2, CURRENT_DATE)
+            "#
+        )
+        .bind(project_id)
+        .bind(dec(amount))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_consumed_is_billable_time_at_project_rate_plus_expenses() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (project_id, tech_id, _) = seed(pool, Some("2000")).await;
+
+        // The project's 100/hour wins over the technician's 60
+        log_time(pool, project_id, tech_id, 90, true).await;
+        log_time(pool, project_id, tech_id, 45, true).await;
+        // Non-billable time costs the budget nothing
+        log_time(pool, project_id, tech_id, 120, false).await;
+        add_expense(pool, project_id, "300.00").await;
+
+        let budget = project_budget::project_budget(pool, project_id, Utc::now().date_naive())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(budget.billable_minutes, 135);
+        assert_eq!(budget.time_amount, dec("225.00"));
+        assert_eq!(budget.expense_amount, dec("300.00"));
+        assert_eq!(budget.consumed, dec("525.00"));
+        assert_eq!(budget.remaining, Some(dec("1475.00")));
+        assert_eq!(budget.percent_consumed, Some(dec("26.25")));
+        // 525 over 10 days of a 30-day project
+        assert_eq!(budget.burn_rate_per_day, Some(dec("52.50")));
+        assert_eq!(budget.projected_total, Some(dec("1575.00")));
+        assert_eq!(budget.projected_overrun, Some(Decimal::ZERO));
+
+        let (unbudgeted, _, _) = seed(pool, None).await;
+        let budget = project_budget::project_budget(pool, unbudgeted, Utc::now().date_naive())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(budget.consumed, Decimal::ZERO);
+        assert_eq!(budget.percent_consumed, None);
+        assert_eq!(budget.projected_overrun, None);
+
+        assert!(project_budget::project_budget(pool, Uuid::new_v4(), Utc::now().date_naive())
+            .await
+            .unwrap()
+            .is_none());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_crossing_a_threshold_alerts_the_manager_once() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (project_id, tech_id, manager_id) = seed(pool, Some("1000")).await;
+        let thresholds = [80, 100];
+
+        let alerts_for = |threshold: i32| {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM project_budget_alerts WHERE project_id = $1 AND threshold_percent = $2"
+            )
+            .bind(project_id)
+            .bind(threshold)
+            .fetch_one(pool)
+        };
+
+        // 7.5 hours at 100 is 75%, under every threshold
+        log_time(pool, project_id, tech_id, 450, true).await;
+        let check = project_budget::check_budgets(pool, &thresholds).await.unwrap();
+        assert!(check.alerts.iter().all(|a| a.project_id != project_id));
+
+        // Another hour takes it to 85%
+        log_time(pool, project_id, tech_id, 60, true).await;
+        let check = project_budget::check_budgets(pool, &thresholds).await.unwrap();
+        let alert = check.alerts.iter().find(|a| a.project_id == project_id).expect("80% alert");
+        assert_eq!(alert.threshold_percent, 80);
+        assert_eq!(alert.notified, 1);
+        assert_eq!(alerts_for(80).await.unwrap(), 1);
+
+        let (alert_type, severity): (String, String) =
+            sqlx::query_as("SELECT alert_type, severity FROM alerts WHERE id = $1")
+                .bind(alert.alert_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        assert_eq!(alert_type, ALERT_TYPE);
+        assert_eq!(severity, "medium");
+
+        let notified: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND entity_id = $2"
+        )
+        .bind(manager_id)
+        .bind(project_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(notified, 1);
+
+        // Running again doesn't repeat the alert
+        let check = project_budget::check_budgets(pool, &thresholds).await.unwrap();
+        assert!(check.alerts.iter().all(|a| a.project_id != project_id));
+
+        // An expense pushes it over budget
+        add_expense(pool, project_id, "200.00").await;
+        let check = project_budget::check_budgets(pool, &thresholds).await.unwrap();
+        let alert = check.alerts.iter().find(|a| a.project_id == project_id).expect("100% alert");
+        assert_eq!(alert.threshold_percent, 100);
+        assert_eq!(alerts_for(100).await.unwrap(), 1);
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,215 @@
+// Global search API integration tests
+
+#[cfg(test)]
+mod global_search_tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::search_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    struct Seeded {
+        acme: Uuid,
+        globex: Uuid,
+        acme_ticket: Uuid,
+        globex_ticket: Uuid,
+        open_article: Uuid,
+        globex_article: Uuid,
+    }
+
+    /// A word only this test's records contain, since articles outlive cleanup
+    fn unique_term() -> String {
+        format!("quasar{}", &Uuid::new_v4().simple().to_string()[..8])
+    }
+
+    async fn seed(pool: &PgPool, term: &str, opened_by: Uuid) -> Seeded {
+        let client = |name: &'static str| async move {
+            sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ($1) RETURNING id")
+                .bind(format!("{} {}", term, name))
+                .fetch_one(pool)
+                .await
+                .unwrap()
+        };
+        let acme = client("Acme").await;
+        let globex = client("Globex").await;
+
+        let ticket = |client_id: Uuid| async move {
+            sqlx::query_scalar::<_, Uuid>(
+                "INSERT INTO tickets (client_id, opened_by, subject, details)
+                 VALUES ($1, $2, $3, $4) RETURNING id"
+            )
+            .bind(client_id)
+            .bind(opened_by)
+            .bind(format!("{} VPN outage", term))
+            .bind(format!("Tunnel to the {} gateway drops hourly", term))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+        };
+        let acme_ticket = ticket(acme).await;
+        let globex_ticket = ticket(globex).await;
+
+        sqlx::query("INSERT INTO contacts (client_id, name, email) VALUES ($1, $2, 'pat@acme.test')")
+            .bind(acme)
+            .bind(format!("Pat {}", term))
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO assets (client_id, name, asset_type) VALUES ($1, $2, 'firewall')")
+            .bind(acme)
+            .bind(format!("{}-FW", term.to_uppercase()))
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let article = |title: &'static str| async move {
+            sqlx::query_scalar::<_, Uuid>(
+                "INSERT INTO kb_articles (title, slug, content, status)
+                 VALUES ($1, $2, $3, 'published') RETURNING id"
+            )
+            .bind(format!("{} {}", term, title))
+            .bind(format!("article-{}", Uuid::new_v4()))
+            .bind(format!("Configure the {} appliance.", term))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+        };
+        let open_article = article("setup guide").await;
+        let globex_article = article("for Globex").await;
+        sqlx::query("INSERT INTO kb_article_access (article_id, client_id) VALUES ($1, $2)")
+            .bind(globex_article)
+            .bind(globex)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        Seeded { acme, globex, acme_ticket, globex_ticket, open_article, globex_article }
+    }
+
+    /// A role holding just the given permissions
+    async fn grant(pool: &PgPool, user_id: Uuid, permissions: &[&str]) {
+        let role_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO roles (name) VALUES ($1) RETURNING id")
+            .bind(format!("search-{}", Uuid::new_v4()))
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO role_permissions (role_id, permission_id)
+             SELECT $1, id FROM permissions WHERE name = ANY($2)"
+        )
+        .bind(role_id)
+        .bind(permissions)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE users SET role_id = $1 WHERE id = $2")
+            .bind(role_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn search(pool: &PgPool, token: &str, query: &str) -> (StatusCode, Value) {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/search", search_routes()).with_state(Arc::new(state));
+        let request = Request::builder()
+            .uri(format!("/search?q={}", query))
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    fn group_types(body: &Value) -> Vec<String> {
+        body["groups"].as_array().unwrap().iter().map(|g| g["entity_type"].as_str().unwrap().to_string()).collect()
+    }
+
+    fn hit_ids(body: &Value, entity_type: &str) -> Vec<Uuid> {
+        body["groups"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|g| g["entity_type"] == entity_type)
+            .flat_map(|g| g["hits"].as_array().unwrap())
+            .map(|hit| hit["id"].as_str().unwrap().parse().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_results_span_types_the_caller_may_read() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+        let term = unique_term();
+        let seeded = seed(pool, &term, user.id).await;
+        grant(pool, user.id, &["tickets.read", "clients.read"]).await;
+
+        let (status, body) = search(pool, &token, &term).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["mode"], "full_text");
+        // No assets.read, so no asset group despite the matching firewall
+        assert_eq!(group_types(&body), vec!["ticket", "client", "contact", "kb_article"]);
+
+        let tickets = hit_ids(&body, "ticket");
+        assert!(tickets.contains(&seeded.acme_ticket) && tickets.contains(&seeded.globex_ticket));
+        let ticket = &body["groups"][0]["hits"][0];
+        assert!(ticket["title"].as_str().unwrap().ends_with(&format!("{} VPN outage", term)));
+        assert!(ticket["snippet"].as_str().unwrap().contains("<mark>"));
+        assert_eq!(hit_ids(&body, "client").len(), 2);
+        assert_eq!(hit_ids(&body, "kb_article").len(), 2);
+
+        let (status, _) = search(pool, &token, "%20").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_account_manager_only_sees_managed_clients() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+        let term = unique_term();
+        let seeded = seed(pool, &term, user.id).await;
+        grant(pool, user.id, &["tickets.read", "clients.read", "assets.read"]).await;
+        sqlx::query("UPDATE clients SET account_manager_id = $1 WHERE id = $2")
+            .bind(user.id)
+            .bind(seeded.acme)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let (status, body) = search(pool, &token, &term).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(group_types(&body), vec!["ticket", "client", "contact", "asset", "kb_article"]);
+
+        assert_eq!(hit_ids(&body, "client"), vec![seeded.acme]);
+        assert!(!hit_ids(&body, "client").contains(&seeded.globex));
+        assert_eq!(hit_ids(&body, "ticket"), vec![seeded.acme_ticket]);
+        // Articles shared only with Globex are hidden along with Globex
+        assert_eq!(hit_ids(&body, "kb_article"), vec![seeded.open_article]);
+        assert!(!hit_ids(&body, "kb_article").contains(&seeded.globex_article));
+        for group in body["groups"].as_array().unwrap() {
+            for hit in group["hits"].as_array().unwrap() {
+                assert_ne!(hit["client_id"], seeded.globex.to_string());
+            }
+        }
+
+        ctx.cleanup().await;
+    }
+}
@@ -1,4 +1,4 @@
-// Integration tests for Teams API endpoints
+// Integration tests for Teams API endpoints and Teams/Slack chat channel routing
 
 use axum::{
     body::Body,
@@ -55,3 +55,50 @@ mod teams_integration_tests {
         // Would test POST /api/v1/teams/integrations/{id}/daily-summary
     }
 }
+
+#[cfg(test)]
+mod chat_channel_tests {
+    use uuid::Uuid;
+
+    use crate::services::chat_notifications::{channels_for, ChatEvent, JOB_FAILED};
+    use crate::tests::TestContext;
+
+    async fn seed_channel(pool: &sqlx::PgPool, integration_id: Uuid, name: &str, enabled: bool) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO chat_notification_channels (name, provider, integration_id, event_types, enabled)
+             VALUES ($1, 'slack', $2, $3, $4) RETURNING id"
+        )
+        .bind(name)
+        .bind(integration_id)
+        .bind(vec![JOB_FAILED.to_string()])
+        .bind(enabled)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_disabled_channel_is_skipped() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let integration_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO integrations (name, integration_type, config, credentials)
+             VALUES ('Slack', 'slack', '{}', '{}') RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let ops = seed_channel(pool, integration_id, "Ops", true).await;
+        let muted = seed_channel(pool, integration_id, "Muted", false).await;
+
+        let event = ChatEvent::JobFailed { job_name: "Late Fees".to_string(), error: "timeout".to_string() };
+        let channels = channels_for(pool, &event).await.unwrap();
+        let ids: Vec<Uuid> = channels.iter().map(|c| c.id).collect();
+
+        assert!(ids.contains(&ops));
+        assert!(!ids.contains(&muted));
+
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,1255 @@
+// Integration tests for ticket search, watchers, updates and merges
+
+#[cfg(test)]
+mod seed {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    pub async fn seed_client(pool: &PgPool) -> (Uuid, Uuid) {
+        let user_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name)
+             VALUES ($1, 'x', 'Search', 'Tester') RETURNING id"
+        )
+        .bind(format!("search-{}@resolve.test", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Search Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+
+        (user_id, client_id)
+    }
+
+    pub async fn seed_ticket(pool: &PgPool, ids: (Uuid, Uuid), subject: &str, details: &str, status: &str) -> Uuid {
+        let (user_id, client_id) = ids;
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO tickets (client_id, opened_by, subject, details, status)
+             VALUES ($1, $2, $3, $4, $5) RETURNING id"
+        )
+        .bind(client_id)
+        .bind(user_id)
+        .bind(subject)
+        .bind(details)
+        .bind(status)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod ticket_search_tests {
+    use uuid::Uuid;
+
+    use super::seed::{seed_client, seed_ticket};
+    use crate::services::ticket_search::{search_tickets, SearchMode, TicketSearchFilters};
+    use crate::tests::TestContext;
+
+    fn for_client(client_id: Uuid) -> TicketSearchFilters {
+        TicketSearchFilters {
+            client_id: Some(client_id),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_subject_matches_rank_above_details_matches() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+
+        let details_hit = seed_ticket(
+            &ctx.db_pool,
+            ids,
+            "Printer offline",
+            "The printer dropped off after the user reconnected to the VPN from home.",
+            "open",
+        )
+        .await;
+        let subject_hit = seed_ticket(
+            &ctx.db_pool,
+            ids,
+            "VPN connection keeps dropping",
+            "Remote staff lose the VPN tunnel every few minutes since the firewall update.",
+            "open",
+        )
+        .await;
+        seed_ticket(&ctx.db_pool, ids, "Password reset", "User locked out of email.", "open").await;
+
+        let (results, total, mode) = search_tickets(&ctx.db_pool, "vpn", &for_client(ids.1), 20, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(mode, SearchMode::FullText);
+        assert_eq!(total, 2);
+        assert_eq!(results[0].id, subject_hit);
+        assert_eq!(results[1].id, details_hit);
+        assert!(results[0].rank > results[1].rank);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_results_include_highlighted_snippets() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        seed_ticket(
+            &ctx.db_pool,
+            ids,
+            "Outlook crashing",
+            "Outlook crashes on startup whenever the shared mailbox is attached.",
+            "open",
+        )
+        .await;
+
+        let (results, _, _) = search_tickets(&ctx.db_pool, "crashing mailbox", &for_client(ids.1), 20, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].subject_highlight, "Outlook <mark>crashing</mark>");
+        // Stemming highlights "crashes" for the query term "crashing"
+        assert!(results[0].snippet.contains("<mark>crashes</mark>"));
+        assert!(results[0].snippet.contains("<mark>mailbox</mark>"));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_filters_and_pagination() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        for n in 0..3 {
+            seed_ticket(&ctx.db_pool, ids, &format!("Backup failure {}", n), "Nightly backup job failed.", "open").await;
+        }
+        let resolved = seed_ticket(&ctx.db_pool, ids, "Backup failure", "Backup job failed.", "resolved").await;
+
+        let filters = TicketSearchFilters {
+            status: Some("resolved".to_string()),
+            ..for_client(ids.1)
+        };
+        let (results, total, _) = search_tickets(&ctx.db_pool, "backup", &filters, 20, 0).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(results[0].id, resolved);
+
+        let (page, total, _) = search_tickets(&ctx.db_pool, "backup", &for_client(ids.1), 2, 2).await.unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(page.len(), 2);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_untokenizable_query_falls_back_to_substring() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let disk = seed_ticket(&ctx.db_pool, ids, "Disk space alert", "C: drive is at 95% capacity.", "open").await;
+        seed_ticket(&ctx.db_pool, ids, "Disk space alert", "C: drive is nearly full.", "open").await;
+
+        let (results, total, mode) = search_tickets(&ctx.db_pool, "%", &for_client(ids.1), 20, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(mode, SearchMode::Substring);
+        assert_eq!(total, 1);
+        assert_eq!(results[0].id, disk);
+        assert_eq!(results[0].snippet, "C: drive is at 95% capacity.");
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod ticket_watcher_tests {
+    use uuid::Uuid;
+
+    use super::seed::{seed_client, seed_ticket};
+    use crate::notifications::notify_ticket_update;
+    use crate::services::ticket_watchers::{add_watcher, list_watchers, remove_watcher};
+    use crate::tests::TestContext;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_self_watch_lists_user_name() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let ticket_id = seed_ticket(&ctx.db_pool, ids, "Slow laptop", "Takes ten minutes to boot.", "open").await;
+
+        assert!(add_watcher(&ctx.db_pool, ticket_id, ids.0, ids.0, None).await.unwrap());
+
+        let watchers = list_watchers(&ctx.db_pool, ticket_id).await.unwrap();
+        assert_eq!(watchers.len(), 1);
+        assert_eq!(watchers[0].user_id, ids.0);
+        assert_eq!(watchers[0].user_name, "Search Tester");
+        assert_eq!(watchers[0].added_by, Some(ids.0));
+        assert_eq!(watchers[0].notification_preferences["new_replies"], true);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_duplicate_watch_is_ignored() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let ticket_id = seed_ticket(&ctx.db_pool, ids, "Slow laptop", "Takes ten minutes to boot.", "open").await;
+
+        assert!(add_watcher(&ctx.db_pool, ticket_id, ids.0, ids.0, None).await.unwrap());
+        let prefs = serde_json::json!({ "status_changes": false, "new_replies": false, "assignments": false });
+        assert!(!add_watcher(&ctx.db_pool, ticket_id, ids.0, ids.0, Some(prefs)).await.unwrap());
+
+        // The original row, preferences included, is kept
+        let watchers = list_watchers(&ctx.db_pool, ticket_id).await.unwrap();
+        assert_eq!(watchers.len(), 1);
+        assert_eq!(watchers[0].notification_preferences["new_replies"], true);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_unwatch() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let ticket_id = seed_ticket(&ctx.db_pool, ids, "Slow laptop", "Takes ten minutes to boot.", "open").await;
+
+        add_watcher(&ctx.db_pool, ticket_id, ids.0, ids.0, None).await.unwrap();
+        assert!(remove_watcher(&ctx.db_pool, ticket_id, ids.0).await.unwrap());
+        assert!(list_watchers(&ctx.db_pool, ticket_id).await.unwrap().is_empty());
+
+        // Unwatching again reports nothing was removed
+        assert!(!remove_watcher(&ctx.db_pool, ticket_id, Uuid::new_v4()).await.unwrap());
+        assert!(!remove_watcher(&ctx.db_pool, ticket_id, ids.0).await.unwrap());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_notifications_respect_watcher_preferences() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let ticket_id = seed_ticket(&ctx.db_pool, ids, "Slow laptop", "Takes ten minutes to boot.", "open").await;
+
+        let prefs = serde_json::json!({ "status_changes": true, "new_replies": true, "assignments": false });
+        add_watcher(&ctx.db_pool, ticket_id, ids.0, ids.0, Some(prefs)).await.unwrap();
+
+        notify_ticket_update(&ctx.db_pool, ticket_id, ids.1, "Assigned", "Assigned to Tess.").await.unwrap();
+        notify_ticket_update(&ctx.db_pool, ticket_id, ids.1, "Resolved", "Fixed.").await.unwrap();
+
+        let titles = sqlx::query_scalar::<_, String>(
+            "SELECT title FROM notifications WHERE user_id = $1 AND entity_id = $2"
+        )
+        .bind(ids.0)
+        .bind(ticket_id)
+        .fetch_all(&ctx.db_pool)
+        .await
+        .unwrap();
+        assert_eq!(titles, vec!["Ticket Resolved"]);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod github_issue_sync_tests {
+    use std::time::Duration;
+    use uuid::Uuid;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::seed::{seed_client, seed_ticket};
+    use crate::services::github_issues::{find_ref, push_ticket, sync_issue_status, GitHubError, GitHubIssueClient};
+    use crate::tests::TestContext;
+
+    const REPO: &str = "acme/platform";
+
+    async fn seed_integration(pool: &sqlx::PgPool) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO integrations (name, integration_type, config, credentials)
+             VALUES ('GitHub', 'github', $1, '{}') RETURNING id"
+        )
+        .bind(serde_json::json!({ "repository": REPO }))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    fn issue(number: i64, state: &str) -> serde_json::Value {
+        serde_json::json!({
+            "number": number,
+            "html_url": format!("https://github.com/{}/issues/{}", REPO, number),
+            "state": state
+        })
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_push_and_sync_round_trip() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let ids = seed_client(pool).await;
+        let integration_id = seed_integration(pool).await;
+        let ticket_id = seed_ticket(pool, ids, "Webhook retries flood queue", "Retries never back off.", "open").await;
+
+        let github = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(format!("/repos/{}/issues", REPO)))
+            .and(body_partial_json(serde_json::json!({
+                "title": "Webhook retries flood queue",
+                "labels": ["priority: medium"]
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(issue(42, "open")))
+            .expect(1)
+            .mount(&github)
+            .await;
+
+        let client = GitHubIssueClient::new("ghp_test", Some(&github.uri()))
+            .unwrap()
+            .with_mutation_interval(Duration::ZERO);
+
+        let external_ref = push_ticket(pool, &client, integration_id, REPO, ticket_id, Some(ids.0)).await.unwrap();
+        assert_eq!(external_ref.external_id, "42");
+        assert_eq!(external_ref.external_state.as_deref(), Some("open"));
+
+        let stored = find_ref(pool, ticket_id, integration_id).await.unwrap().unwrap();
+        assert_eq!(stored.id, external_ref.id);
+        assert_eq!(stored.repository, REPO);
+
+        // Pushing the same ticket again is refused without calling GitHub
+        assert!(matches!(
+            push_ticket(pool, &client, integration_id, REPO, ticket_id, None).await,
+            Err(GitHubError::AlreadyLinked(_))
+        ));
+
+        // Issue closed on GitHub: the sync closes the ticket
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/{}/issues/42", REPO)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue(42, "closed")))
+            .mount(&github)
+            .await;
+
+        let summary = sync_issue_status(pool, &client, integration_id).await.unwrap();
+        assert_eq!(summary.issues_checked, 1);
+        assert_eq!(summary.tickets_closed, 1);
+        assert!(summary.errors.is_empty());
+
+        let status: String = sqlx::query_scalar("SELECT status FROM tickets WHERE id = $1")
+            .bind(ticket_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "closed");
+
+        let stored = find_ref(pool, ticket_id, integration_id).await.unwrap().unwrap();
+        assert_eq!(stored.external_state.as_deref(), Some("closed"));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_closed_ticket_closes_issue_and_rate_limit_is_reported() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let ids = seed_client(pool).await;
+        let integration_id = seed_integration(pool).await;
+        let closed = seed_ticket(pool, ids, "Fixed in release", "Done.", "closed").await;
+        let limited = seed_ticket(pool, ids, "Still open", "Pending.", "open").await;
+
+        // Least recently synced first, so the closure happens before the rate limit
+        for (ticket_id, number, minutes_ago) in [(closed, "7", 10_i32), (limited, "8", 5_i32)] {
+            sqlx::query(
+                "INSERT INTO ticket_external_refs (ticket_id, integration_id, provider, repository, external_id, last_synced_at)
+                 VALUES ($1, $2, 'github', $3, $4, NOW() - make_interval(mins => $5))"
+            )
+            .bind(ticket_id)
+            .bind(integration_id)
+            .bind(REPO)
+            .bind(number)
+            .bind(minutes_ago)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        let github = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/{}/issues/7", REPO)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue(7, "open")))
+            .mount(&github)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(format!("/repos/{}/issues/7", REPO)))
+            .and(body_partial_json(serde_json::json!({ "state": "closed" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(issue(7, "closed")))
+            .expect(1)
+            .mount(&github)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/{}/issues/8", REPO)))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .insert_header("x-ratelimit-reset", "1900000000")
+                    .set_body_json(serde_json::json!({ "message": "API rate limit exceeded" })),
+            )
+            .mount(&github)
+            .await;
+
+        let client = GitHubIssueClient::new("ghp_test", Some(&github.uri()))
+            .unwrap()
+            .with_mutation_interval(Duration::ZERO);
+
+        let summary = sync_issue_status(pool, &client, integration_id).await.unwrap();
+        assert_eq!(summary.issues_closed, 1);
+        assert!(summary.rate_limited);
+        assert_eq!(summary.errors.len(), 1);
+        assert!(summary.errors[0].contains("rate limit"));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod notification_batch_tests {
+    use uuid::Uuid;
+
+    use crate::notifications::create_notifications_for_users;
+    use crate::tests::TestContext;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_broadcast_inserts_every_user_in_one_statement() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+
+        let user_ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO users (email, password_hash, first_name, last_name)
+            SELECT 'broadcast-' || g || '-' || gen_random_uuid() || '@resolve.test', 'x', 'User', g::text
+            FROM generate_series(1, 500) AS g
+            RETURNING id
+            "#
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        assert_eq!(user_ids.len(), 500);
+
+        let entity_id = Uuid::new_v4();
+        let ids = create_notifications_for_users(
+            pool,
+            user_ids.clone(),
+            "Maintenance window".to_string(),
+            "Services restart at 22:00.".to_string(),
+            "broadcast".to_string(),
+            Some("announcement".to_string()),
+            Some(entity_id),
+        )
+        .await
+        .unwrap();
+        assert_eq!(ids.len(), 500);
+
+        let rows = sqlx::query_as::<_, (Uuid, Uuid)>(
+            "SELECT id, user_id FROM notifications WHERE entity_id = $1"
+        )
+        .bind(entity_id)
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        assert_eq!(rows.len(), 500);
+
+        // Each returned id belongs to the user at the same position
+        let by_id: std::collections::HashMap<Uuid, Uuid> = rows.into_iter().collect();
+        for (id, user_id) in ids.iter().zip(&user_ids) {
+            assert_eq!(by_id.get(id), Some(user_id));
+        }
+
+        assert!(create_notifications_for_users(
+            pool,
+            Vec::new(),
+            "Nobody".to_string(),
+            String::new(),
+            "broadcast".to_string(),
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .is_empty());
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod recurring_ticket_tests {
+    use chrono::NaiveTime;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use super::seed::seed_client;
+    use crate::jobs::RecurringTicketJob;
+    use crate::tests::TestContext;
+
+    async fn seed_recurrence(pool: &PgPool, ids: (Uuid, Uuid), frequency: &str, enabled: bool) -> Uuid {
+        let (user_id, client_id) = ids;
+        let template_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO ticket_templates (name, subject, details, priority, assigned_to, estimated_hours, created_by)
+             VALUES ('Backups', 'Verify backups', 'Check last night''s backup jobs', 'high', $1, 0.5, $1)
+             RETURNING id"
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        // Due since 9 AM New York time two days ago
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO recurring_tickets (template_id, client_id, frequency, next_run, enabled, timezone)
+             VALUES ($1, $2, $3,
+                     ((NOW() AT TIME ZONE 'America/New_York')::date - 2 + TIME '09:00') AT TIME ZONE 'America/New_York',
+                     $4, 'America/New_York')
+             RETURNING id"
+        )
+        .bind(template_id)
+        .bind(client_id)
+        .bind(frequency)
+        .bind(enabled)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn tickets_from(pool: &PgPool, recurrence_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM tickets WHERE recurring_ticket_id = $1")
+            .bind(recurrence_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_due_recurrence_creates_one_ticket_and_advances() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let ids = seed_client(pool).await;
+        let daily = seed_recurrence(pool, ids, "daily", true).await;
+        let disabled = seed_recurrence(pool, ids, "daily", false).await;
+
+        let job = RecurringTicketJob::new(pool.clone());
+        let result = job.run().await.unwrap();
+        assert_eq!(result.tickets_created, 1);
+        assert!(result.errors.is_empty());
+
+        let (subject, priority, assigned_to, source): (String, String, Option<Uuid>, String) = sqlx::query_as(
+            "SELECT subject, priority, assigned_to, source FROM tickets WHERE recurring_ticket_id = $1"
+        )
+        .bind(daily)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(subject, "Verify backups");
+        assert_eq!(priority, "high");
+        assert_eq!(assigned_to, Some(ids.0));
+        assert_eq!(source, "recurring");
+
+        // Missed runs are skipped and the schedule keeps its local time
+        let (in_future, local_time, ran): (bool, NaiveTime, bool) = sqlx::query_as(
+            "SELECT next_run > NOW(), (next_run AT TIME ZONE timezone)::time, last_run IS NOT NULL
+             FROM recurring_tickets WHERE id = $1"
+        )
+        .bind(daily)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert!(in_future);
+        assert_eq!(local_time, NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        assert!(ran);
+
+        // Running again before the next occurrence does nothing
+        let again = job.run().await.unwrap();
+        assert_eq!(again.tickets_created, 0);
+        assert_eq!(tickets_from(pool, daily).await, 1);
+
+        // Disabled recurrences are never run
+        assert_eq!(tickets_from(pool, disabled).await, 0);
+        let last_run: Option<chrono::DateTime<chrono::Utc>> =
+            sqlx::query_scalar("SELECT last_run FROM recurring_tickets WHERE id = $1")
+                .bind(disabled)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        assert_eq!(last_run, None);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_weekly_and_monthly_recurrences_advance_by_their_period() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let ids = seed_client(pool).await;
+        let weekly = seed_recurrence(pool, ids, "weekly", true).await;
+        let monthly = seed_recurrence(pool, ids, "monthly", true).await;
+
+        RecurringTicketJob::new(pool.clone()).run().await.unwrap();
+
+        for (recurrence, period) in [(weekly, "7 days"), (monthly, "1 month")] {
+            let expected_next: bool = sqlx::query_scalar(&format!(
+                "SELECT (next_run AT TIME ZONE timezone)::date =
+                        ((NOW() AT TIME ZONE 'America/New_York')::date - 2 + INTERVAL '{period}')::date
+                 FROM recurring_tickets WHERE id = $1"
+            ))
+            .bind(recurrence)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+            assert!(expected_next, "{period} recurrence advanced to the wrong date");
+            assert_eq!(tickets_from(pool, recurrence).await, 1);
+        }
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod business_hours_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::sla_routes;
+    use crate::services::business_hours;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn app(pool: &PgPool) -> Router {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        sla_routes().with_state(Arc::new(state))
+    }
+
+    /// A user whose role holds just the given permissions
+    async fn token_with(pool: &PgPool, permissions: &[&str]) -> String {
+        let (user, token) = create_user_with_token(pool).await;
+        let role_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO roles (name) VALUES ($1) RETURNING id")
+            .bind(format!("calendar-{}", Uuid::new_v4()))
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO role_permissions (role_id, permission_id)
+             SELECT $1, id FROM permissions WHERE name = ANY($2)"
+        )
+        .bind(role_id)
+        .bind(permissions)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE users SET role_id = $1 WHERE id = $2")
+            .bind(role_id)
+            .bind(user.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        token
+    }
+
+    async fn post(app: &Router, token: &str, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// A client in New York with a business-hours SLA: 1 hour response, 8 hour resolution
+    async fn seed_client(pool: &PgPool) -> Uuid {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Hours Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO locations (client_id, name, timezone, is_primary) VALUES ($1, 'HQ', 'America/New_York', true)")
+            .bind(client_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        let contract_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO contracts (client_id, name, start_date) VALUES ($1, 'Managed', '2020-01-01') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO slas (contract_id, name, priority, response_time_minutes, resolution_time_hours, business_hours_only)
+             VALUES ($1, 'High', 'high', 60, 8, true)"
+        )
+        .bind(contract_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        client_id
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_friday_evening_ticket_is_due_after_weekend_and_holiday() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let app = app(pool);
+        let client_id = seed_client(pool).await;
+        let token = token_with(pool, &["settings.update"]).await;
+
+        let technician = token_with(pool, &["tickets.read"]).await;
+        let (status, _) = post(
+            &app,
+            &technician,
+            "/holidays",
+            serde_json::json!({ "client_id": client_id, "holiday_date": "2024-03-18", "name": "Founders' Day" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        for day in 1..=5 {
+            let (status, _) = post(
+                &app,
+                &token,
+                "/business-hours",
+                serde_json::json!({ "client_id": client_id, "day_of_week": day, "start_time": "08:00:00", "end_time": "16:00:00" }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let (status, _) = post(
+            &app,
+            &token,
+            "/holidays",
+            serde_json::json!({ "client_id": client_id, "holiday_date": "2024-03-18", "name": "Founders' Day" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, _) = post(
+            &app,
+            &token,
+            "/holidays",
+            serde_json::json!({ "client_id": client_id, "holiday_date": "2024-03-18", "name": "Again" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = post(
+            &app,
+            &token,
+            "/business-hours",
+            serde_json::json!({ "client_id": client_id, "day_of_week": 7, "start_time": "08:00:00", "end_time": "16:00:00" }),
+        )
+        .await;
+        assert_ne!(status, StatusCode::CREATED);
+
+        let sla = business_hours::find_sla(pool, client_id, "high").await.unwrap().expect("client SLA");
+
+        // Friday 2024-03-15 18:00 New York (22:00 UTC), after the 16:00 close.
+        // Monday is a holiday, so the clock starts Tuesday 08:00 local (12:00 UTC).
+        let opened = Utc.with_ymd_and_hms(2024, 3, 15, 22, 0, 0).unwrap();
+        let due = business_hours::sla_due_dates(pool, client_id, &sla, opened).await.unwrap();
+        assert_eq!(due.response_due, Utc.with_ymd_and_hms(2024, 3, 19, 13, 0, 0).unwrap());
+        assert_eq!(due.resolution_due, Utc.with_ymd_and_hms(2024, 3, 19, 20, 0, 0).unwrap());
+
+        // Another client falls back to the global Monday-Friday 09:00-17:00 schedule in UTC
+        let other = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Global Co') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let calendar = business_hours::load_calendar(pool, Some(other), NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            calendar.add_business_minutes(opened, 60),
+            Utc.with_ymd_and_hms(2024, 3, 18, 10, 0, 0).unwrap()
+        );
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod ticket_sla_tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use super::seed::{seed_client, seed_ticket};
+    use crate::services::ticket_sla::{self, SlaSource, TicketSlaError};
+    use crate::tests::TestContext;
+
+    async fn seed_policy(pool: &PgPool, client_id: Option<Uuid>, priority: &str, response_minutes: i32, resolution_hours: i32) -> Uuid {
+        let policy_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO sla_policies (name, client_id, is_global, priority_levels, business_hours)
+             VALUES ('Policy', $1, $2, '{}', '{}') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(client_id.is_none())
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO sla_rules (policy_id, priority, response_time_minutes, resolution_time_hours) VALUES ($1, $2, $3, $4)")
+            .bind(policy_id)
+            .bind(priority)
+            .bind(response_minutes)
+            .bind(resolution_hours)
+            .execute(pool)
+            .await
+            .unwrap();
+        policy_id
+    }
+
+    async fn sla_columns(pool: &PgPool, ticket_id: Uuid) -> (Option<Uuid>, Option<DateTime<Utc>>, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        sqlx::query_as("SELECT sla_policy_id, sla_response_due, sla_resolution_due, sla_response_at FROM tickets WHERE id = $1")
+            .bind(ticket_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_due_fields_are_populated_from_the_resolved_policy() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let ids = seed_client(pool).await;
+        let client_policy = seed_policy(pool, Some(ids.1), "medium", 30, 4).await;
+        let pinned_policy = seed_policy(pool, Some(ids.1), "medium", 60, 2).await;
+        // The newest client policy wins; make the first one newest
+        sqlx::query("UPDATE sla_policies SET created_at = NOW() + INTERVAL '1 minute' WHERE id = $1")
+            .bind(client_policy)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let ticket_id = seed_ticket(pool, ids, "Server down", "Nothing responds", "open").await;
+        // Wednesday 2024-03-13 10:00 UTC, inside the global 09:00-17:00 schedule
+        sqlx::query("UPDATE tickets SET priority = 'medium', created_at = '2024-03-13T10:00:00Z' WHERE id = $1")
+            .bind(ticket_id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let applied = ticket_sla::apply_sla(pool, ticket_id).await.unwrap().expect("an SLA applies");
+        assert_eq!(applied.sla.source, SlaSource::Client);
+        let (policy_id, response_due, resolution_due, response_at) = sla_columns(pool, ticket_id).await;
+        assert_eq!(policy_id, Some(client_policy));
+        assert_eq!(response_due, Some(Utc.with_ymd_and_hms(2024, 3, 13, 10, 30, 0).unwrap()));
+        assert_eq!(resolution_due, Some(Utc.with_ymd_and_hms(2024, 3, 13, 14, 0, 0).unwrap()));
+        assert_eq!(response_at, None);
+
+        // A pinned policy takes over, and unpinning falls back
+        let pinned = ticket_sla::pin_policy(pool, ticket_id, Some(pinned_policy)).await.unwrap().unwrap();
+        assert_eq!(pinned.sla.source, SlaSource::Pinned);
+        assert_eq!(pinned.due.resolution_due, Utc.with_ymd_and_hms(2024, 3, 13, 12, 0, 0).unwrap());
+
+        let unpinned = ticket_sla::pin_policy(pool, ticket_id, None).await.unwrap().unwrap();
+        assert_eq!(unpinned.sla.source, SlaSource::Client);
+
+        let high_only = seed_policy(pool, None, "high", 15, 1).await;
+        assert!(matches!(
+            ticket_sla::pin_policy(pool, ticket_id, Some(high_only)).await,
+            Err(TicketSlaError::NoRuleForPriority(_))
+        ));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_first_reply_stamps_response_once() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let ids = seed_client(pool).await;
+        let ticket_id = seed_ticket(pool, ids, "Slow laptop", "Takes ages to boot", "open").await;
+
+        assert!(ticket_sla::record_first_response(pool, ticket_id).await.unwrap());
+        let (_, _, _, first) = sla_columns(pool, ticket_id).await;
+        assert!(first.is_some());
+
+        assert!(!ticket_sla::record_first_response(pool, ticket_id).await.unwrap());
+        let (_, _, _, second) = sla_columns(pool, ticket_id).await;
+        assert_eq!(first, second);
+
+        ticket_sla::record_status(pool, ticket_id, "resolved").await.unwrap();
+        let resolved_at: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT resolved_at FROM tickets WHERE id = $1")
+            .bind(ticket_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert!(resolved_at.is_some());
+
+        ticket_sla::record_status(pool, ticket_id, "open").await.unwrap();
+        let reopened: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT resolved_at FROM tickets WHERE id = $1")
+            .bind(ticket_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(reopened, None);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod ticket_update_tests {
+    use super::seed::{seed_client, seed_ticket};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use chrono::{DateTime, Duration, Utc};
+    use serde_json::json;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::ticket_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    async fn put_ticket(ctx: &TestContext, token: &str, id: Uuid, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let state = AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/tickets", ticket_routes()).with_state(Arc::new(state));
+        let request = Request::builder()
+            .method("PUT")
+            .uri(format!("/tickets/{}", id))
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn updated_at(ctx: &TestContext, id: Uuid) -> DateTime<Utc> {
+        sqlx::query_scalar("SELECT updated_at FROM tickets WHERE id = $1")
+            .bind(id)
+            .fetch_one(&ctx.db_pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_stale_update_is_rejected_with_current_state() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let ticket = seed_ticket(&ctx.db_pool, ids, "Printer offline", "Second floor", "open").await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+
+        // Someone else saved the ticket after this client loaded it
+        let loaded_at = updated_at(&ctx, ticket).await;
+        sqlx::query("UPDATE tickets SET subject = 'Printer replaced', updated_at = $2 WHERE id = $1")
+            .bind(ticket)
+            .bind(loaded_at + Duration::seconds(5))
+            .execute(&ctx.db_pool)
+            .await
+            .unwrap();
+
+        let (status, body) = put_ticket(
+            &ctx,
+            &token,
+            ticket,
+            json!({ "subject": "Printer still offline", "expected_updated_at": loaded_at }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["current"]["subject"], "Printer replaced");
+
+        let subject: String = sqlx::query_scalar("SELECT subject FROM tickets WHERE id = $1")
+            .bind(ticket)
+            .fetch_one(&ctx.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(subject, "Printer replaced");
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_fresh_update_succeeds() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let ticket = seed_ticket(&ctx.db_pool, ids, "VPN drops", "Every hour", "open").await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+
+        let loaded_at = updated_at(&ctx, ticket).await;
+        let (status, body) = put_ticket(
+            &ctx,
+            &token,
+            ticket,
+            json!({ "subject": "VPN drops hourly", "expected_updated_at": loaded_at }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["subject"], "VPN drops hourly");
+
+        // No precondition keeps last-write-wins
+        let (status, _) = put_ticket(&ctx, &token, ticket, json!({ "status": "pending" })).await;
+        assert_eq!(status, StatusCode::OK);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_unknown_status_is_rejected_and_known_status_stored_canonically() {
+        let ctx = TestContext::new().await;
+        let ids = seed_client(&ctx.db_pool).await;
+        let ticket = seed_ticket(&ctx.db_pool, ids, "Mail bouncing", "All outbound", "open").await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+
+        let (status, body) = put_ticket(&ctx, &token, ticket, json!({ "status": "Opn", "priority": "hihg" })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "VALIDATION_ERROR");
+        assert!(body["details"]["status"][0].as_str().unwrap().contains("in_progress"));
+
+        let (status, body) = put_ticket(&ctx, &token, ticket, json!({ "status": "In_Progress", "priority": "HIGH" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "in_progress");
+        assert_eq!(body["priority"], "high");
+
+        let stored: resolve_shared::Ticket = sqlx::query_as("SELECT * FROM tickets WHERE id = $1")
+            .bind(ticket)
+            .fetch_one(&ctx.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(stored.status, resolve_shared::TicketStatus::InProgress);
+        assert_eq!(stored.priority, resolve_shared::Priority::High);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod create_validation_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use crate::handlers::{client_routes, ticket_routes};
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    async fn post(app: Router, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    fn fields(error: &Value) -> Vec<String> {
+        let mut fields: Vec<String> = error["details"].as_object().unwrap().keys().cloned().collect();
+        fields.sort();
+        fields
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_every_bad_field_is_reported_at_once() {
+        let ctx = TestContext::new().await;
+        let state = Arc::new(AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None });
+
+        let (status, error) = post(
+            ticket_routes().with_state(state.clone()),
+            json!({
+                "client_id": uuid::Uuid::new_v4(),
+                "subject": "  ",
+                "priority": "hihg",
+                "estimated_hours": -2
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["code"], "VALIDATION_ERROR");
+        assert_eq!(fields(&error), ["client_id", "details", "estimated_hours", "priority", "subject"]);
+
+        let (status, error) = post(
+            client_routes().with_state(state),
+            json!({ "name": "", "email": "not-an-email", "zip": "1".repeat(30) }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(fields(&error), ["email", "name", "zip"]);
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod ticket_merge_tests {
+    use super::seed::{seed_client, seed_ticket};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use serde_json::json;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::ticket_link_routes;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    async fn merge(ctx: &TestContext, token: &str, target: Uuid, sources: &[Uuid]) -> StatusCode {
+        let state = AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/ticket-links", ticket_link_routes()).with_state(Arc::new(state));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/ticket-links/merge")
+            .header("authorization", format!("Bearer {}", token))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "source_ticket_ids": sources, "target_ticket_id": target }).to_string()))
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    async fn ticket_of_reply(ctx: &TestContext, reply_id: Uuid) -> Uuid {
+        sqlx::query_scalar("SELECT ticket_id FROM ticket_replies WHERE id = $1")
+            .bind(reply_id)
+            .fetch_one(&ctx.db_pool)
+            .await
+            .unwrap()
+    }
+
+    async fn merged_flags(ctx: &TestContext, ids: &[Uuid]) -> Vec<bool> {
+        let mut flags = Vec::new();
+        for id in ids {
+            flags.push(
+                sqlx::query_scalar("SELECT is_merged FROM tickets WHERE id = $1")
+                    .bind(id)
+                    .fetch_one(&ctx.db_pool)
+                    .await
+                    .unwrap(),
+            );
+        }
+        flags
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_failure_mid_merge_rolls_back_every_step() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+        let ids = seed_client(pool).await;
+
+        let target = seed_ticket(pool, ids, "Email down", "details", "open").await;
+        let first = seed_ticket(pool, ids, "Email down again", "details", "open").await;
+        let second = seed_ticket(pool, ids, "Still no email", "details", "open").await;
+
+        let reply_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO ticket_replies (ticket_id, user_id, type, details) VALUES ($1, $2, 'reply', 'Looking into it') RETURNING id"
+        )
+        .bind(first)
+        .bind(user.id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO time_entries (user_id, ticket_id, start_time, end_time, duration_minutes, billable)
+             VALUES ($1, $2, NOW() - INTERVAL '1 hour', NOW(), 60, true)"
+        )
+        .bind(user.id)
+        .bind(second)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        // Moving the second ticket's time fails, after the first ticket is fully merged
+        sqlx::query(
+            "CREATE OR REPLACE FUNCTION fail_ticket_merge_test() RETURNS trigger AS $$
+             BEGIN RAISE EXCEPTION 'forced merge failure'; END $$ LANGUAGE plpgsql"
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(&format!(
+            "CREATE TRIGGER fail_ticket_merge_test BEFORE UPDATE OF ticket_id ON time_entries
+             FOR EACH ROW WHEN (OLD.ticket_id = '{}') EXECUTE FUNCTION fail_ticket_merge_test()",
+            second
+        ))
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let status = merge(&ctx, &token, target, &[first, second]).await;
+
+        sqlx::query("DROP TRIGGER fail_ticket_merge_test ON time_entries").execute(pool).await.unwrap();
+        sqlx::query("DROP FUNCTION fail_ticket_merge_test()").execute(pool).await.unwrap();
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(merged_flags(&ctx, &[first, second]).await, [false, false]);
+        assert_eq!(ticket_of_reply(&ctx, reply_id).await, first);
+        let merges: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ticket_merges WHERE primary_ticket_id = $1")
+            .bind(target)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(merges, 0);
+        let notes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ticket_replies WHERE ticket_id = $1")
+            .bind(target)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(notes, 0);
+
+        // Without the failure the same merge goes through whole
+        assert_eq!(merge(&ctx, &token, target, &[first, second]).await, StatusCode::OK);
+        assert_eq!(merged_flags(&ctx, &[first, second]).await, [true, true]);
+        assert_eq!(ticket_of_reply(&ctx, reply_id).await, target);
+        let time_on_target: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM time_entries WHERE ticket_id = $1")
+            .bind(target)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(time_on_target, 1);
+
+        ctx.cleanup().await;
+    }
+}
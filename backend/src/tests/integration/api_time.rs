@@ -0,0 +1,428 @@
+// Time tracking integration tests
+
+#[cfg(test)]
+mod timer_tests {
+    use chrono::{Duration, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    use crate::services::time_timers::{self, StartTimer, TimerError};
+    use crate::tests::TestContext;
+
+    async fn seed_user(pool: &PgPool, hourly_rate: Option<Decimal>) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name, hourly_rate)
+             VALUES ($1, 'x', 'Tim', 'Keeper', $2) RETURNING id"
+        )
+        .bind(format!("timer-{}@resolve.test", Uuid::new_v4()))
+        .bind(hourly_rate)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn running_count(pool: &PgPool, user_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM time_entries WHERE user_id = $1 AND end_time IS NULL")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_only_one_timer_runs_per_user() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let user_id = seed_user(pool, None).await;
+        let timer = StartTimer { billable: true, ..Default::default() };
+
+        let first = time_timers::start(pool, user_id, timer.clone(), true).await.unwrap();
+        assert_eq!(first.stopped_id, None);
+
+        // Starting another stops the first and flags it
+        let second = time_timers::start(pool, user_id, timer.clone(), true).await.unwrap();
+        assert_eq!(second.stopped_id, Some(first.timer_id));
+        assert_eq!(running_count(pool, user_id).await, 1);
+        let (auto_stopped, ended): (bool, bool) =
+            sqlx::query_as("SELECT auto_stopped, end_time IS NOT NULL FROM time_entries WHERE id = $1")
+                .bind(first.timer_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        assert!(auto_stopped);
+        assert!(ended);
+
+        // With auto-stop off the running timer is left alone
+        let refused = time_timers::start(pool, user_id, timer.clone(), false).await;
+        assert!(matches!(refused, Err(TimerError::AlreadyRunning(id)) if id == second.timer_id));
+        assert_eq!(running_count(pool, user_id).await, 1);
+
+        // The index rejects a second running entry written behind the service's back
+        let direct = sqlx::query("INSERT INTO time_entries (user_id, start_time) VALUES ($1, NOW())")
+            .bind(user_id)
+            .execute(pool)
+            .await;
+        assert!(direct.is_err());
+
+        let stopped = time_timers::stop(pool, user_id, None).await.unwrap();
+        assert_eq!(stopped, second.timer_id);
+        assert_eq!(time_timers::running_timer_id(pool, user_id).await.unwrap(), None);
+        assert!(matches!(time_timers::stop(pool, user_id, None).await, Err(TimerError::NotRunning)));
+
+        // Another user's timer is unaffected
+        let other = seed_user(pool, None).await;
+        time_timers::start(pool, other, timer.clone(), true).await.unwrap();
+        time_timers::start(pool, user_id, timer, true).await.unwrap();
+        assert_eq!(running_count(pool, other).await, 1);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_stop_records_duration_and_amount_at_user_rate() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let user_id = seed_user(pool, Some(Decimal::from(120))).await;
+
+        let started = time_timers::start(pool, user_id, StartTimer { billable: true, ..Default::default() }, true)
+            .await
+            .unwrap();
+        // Backdate the start so the timer has run 90 minutes
+        sqlx::query("UPDATE time_entries SET start_time = $2 WHERE id = $1")
+            .bind(started.timer_id)
+            .bind(Utc::now() - Duration::minutes(90) - Duration::seconds(10))
+            .execute(pool)
+            .await
+            .unwrap();
+
+        time_timers::stop(pool, user_id, Some(started.timer_id)).await.unwrap();
+
+        let (minutes, rate, total, auto_stopped): (Option<i32>, Option<Decimal>, Option<Decimal>, bool) = sqlx::query_as(
+            "SELECT duration_minutes, hourly_rate, total_amount, auto_stopped FROM time_entries WHERE id = $1"
+        )
+        .bind(started.timer_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(minutes, Some(90));
+        assert_eq!(rate, Some(Decimal::from_str("120.00").unwrap()));
+        assert_eq!(total, Some(Decimal::from_str("180.00").unwrap()));
+        assert!(!auto_stopped);
+
+        // Non-billable time is recorded but worth nothing
+        let unbilled = time_timers::start(pool, user_id, StartTimer::default(), true).await.unwrap();
+        time_timers::stop(pool, user_id, None).await.unwrap();
+        let total: Option<Decimal> = sqlx::query_scalar("SELECT total_amount FROM time_entries WHERE id = $1")
+            .bind(unbilled.timer_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(total, Some(Decimal::ZERO));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_stop_bills_rounded_minutes_and_keeps_raw_duration() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let user_id = seed_user(pool, Some(Decimal::from(120))).await;
+
+        // The client bills up to the next 15 minutes, at least 30
+        let client_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO clients (name, time_rounding_increment, time_rounding_direction, time_rounding_minimum)
+             VALUES ('Rounded Co', 15, 'up', 30) RETURNING id"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let project_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO projects (client_id, name) VALUES ($1, 'Migration') RETURNING id"
+        )
+        .bind(client_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let run_for = |minutes: i64| {
+            let timer = StartTimer { project_id: Some(project_id), billable: true, ..Default::default() };
+            async move {
+                let started = time_timers::start(pool, user_id, timer, true).await.unwrap();
+                sqlx::query("UPDATE time_entries SET start_time = $2 WHERE id = $1")
+                    .bind(started.timer_id)
+                    .bind(Utc::now() - Duration::minutes(minutes) - Duration::seconds(10))
+                    .execute(pool)
+                    .await
+                    .unwrap();
+                time_timers::stop(pool, user_id, None).await.unwrap();
+                sqlx::query_as::<_, (Option<i32>, Option<i32>, Option<Decimal>)>(
+                    "SELECT duration_minutes, billable_minutes, total_amount FROM time_entries WHERE id = $1"
+                )
+                .bind(started.timer_id)
+                .fetch_one(pool)
+                .await
+                .unwrap()
+            }
+        };
+
+        // Minimum charge
+        assert_eq!(run_for(10).await, (Some(10), Some(30), Some(Decimal::from_str("60.00").unwrap())));
+        // Rounded up to the increment
+        assert_eq!(run_for(37).await, (Some(37), Some(45), Some(Decimal::from_str("90.00").unwrap())));
+
+        // An active contract's rule wins over the client's
+        sqlx::query(
+            "INSERT INTO contracts (client_id, name, start_date, status, time_rounding_increment, time_rounding_direction)
+             VALUES ($1, 'Support', CURRENT_DATE - 30, 'active', 6, 'down')"
+        )
+        .bind(client_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        assert_eq!(run_for(37).await, (Some(37), Some(36), Some(Decimal::from_str("72.00").unwrap())));
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod overlap_tests {
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::services::time_overlaps;
+    use crate::tests::TestContext;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 4, hour, minute, 0).unwrap()
+    }
+
+    async fn seed_user(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO users (email, password_hash, first_name, last_name)
+             VALUES ($1, 'x', 'Double', 'Logger') RETURNING id"
+        )
+        .bind(format!("overlap-{}@resolve.test", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn seed_entry(pool: &PgPool, user_id: Uuid, start: DateTime<Utc>, end: DateTime<Utc>) -> Uuid {
+        sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO time_entries (user_id, start_time, end_time, duration_minutes)
+             VALUES ($1, $2, $3, $4) RETURNING id"
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .bind((end - start).num_minutes() as i32)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_overlapping_entries_are_found_and_adjacent_ones_are_not() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let user_id = seed_user(pool).await;
+        let morning = seed_entry(pool, user_id, at(9, 0), at(10, 0)).await;
+        let _afternoon = seed_entry(pool, user_id, at(13, 0), at(14, 0)).await;
+        // Someone else's entry at the same time is never an overlap
+        let other = seed_user(pool).await;
+        seed_entry(pool, other, at(9, 0), at(10, 0)).await;
+
+        let overlaps = time_overlaps::find_overlaps(pool, user_id, at(9, 30), Some(at(10, 30)), None).await.unwrap();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].id, morning);
+        assert_eq!(overlaps[0].overlap_minutes, 30);
+
+        // Starting exactly when one entry ends and ending when the next starts is fine
+        let adjacent = time_overlaps::find_overlaps(pool, user_id, at(10, 0), Some(at(13, 0)), None).await.unwrap();
+        assert!(adjacent.is_empty());
+
+        // An entry never overlaps itself when re-checked
+        let itself = time_overlaps::find_overlaps(pool, user_id, at(9, 0), Some(at(10, 0)), Some(morning)).await.unwrap();
+        assert!(itself.is_empty());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_overlaps_on_day_lists_each_pair_once() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let user_id = seed_user(pool).await;
+        let first = seed_entry(pool, user_id, at(9, 0), at(10, 0)).await;
+        let second = seed_entry(pool, user_id, at(9, 45), at(10, 15)).await;
+        seed_entry(pool, user_id, at(10, 15), at(11, 0)).await;
+
+        let pairs = time_overlaps::overlaps_on(pool, user_id, NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()).await.unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].first.id, pairs[0].second.id), (first, second));
+        assert_eq!(pairs[0].overlap_minutes, 15);
+
+        let next_day = time_overlaps::overlaps_on(pool, user_id, NaiveDate::from_ymd_opt(2024, 3, 5).unwrap()).await.unwrap();
+        assert!(next_day.is_empty());
+
+        ctx.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod export_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use chrono::{TimeZone, Utc};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::handlers::time_tracking_routes;
+    use crate::services::time_export::CSV_HEADERS;
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    async fn export(pool: &PgPool, token: &str, query: &str) -> (StatusCode, Option<String>, String) {
+        let state = AppState { db_pool: pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        let app = Router::new().nest("/time", time_tracking_routes()).with_state(Arc::new(state));
+        let request = Request::builder()
+            .uri(format!("/time/export?{}", query))
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap().to_string());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, content_type, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    async fn seed_entries(pool: &PgPool, user_id: Uuid) -> Uuid {
+        let client_id = sqlx::query_scalar::<_, Uuid>("INSERT INTO clients (name) VALUES ('Acme') RETURNING id")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        let ticket_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO tickets (client_id, opened_by, subject, details)
+             VALUES ($1, $2, 'Printer offline', 'details') RETURNING id"
+        )
+        .bind(client_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let start = Utc.with_ymd_and_hms(2024, 5, 14, 9, 15, 0).unwrap();
+        for (offset_days, minutes, end) in [(0, 45, true), (1, 30, true), (1, 0, false), (20, 60, true)] {
+            let start_time = start + chrono::Duration::days(offset_days);
+            sqlx::query(
+                "INSERT INTO time_entries (ticket_id, user_id, start_time, end_time, duration_minutes, billable, total_amount)
+                 VALUES ($1, $2, $3, $4, $5, true, 90.00)"
+            )
+            .bind(ticket_id)
+            .bind(user_id)
+            .bind(start_time)
+            .bind(end.then(|| start_time + chrono::Duration::minutes(minutes)))
+            .bind(end.then_some(minutes as i32))
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+        client_id
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_csv_export_has_the_documented_columns() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+        seed_entries(pool, user.id).await;
+
+        let (status, content_type, body) = export(pool, &token, "from=2024-05-14&to=2024-05-31").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some("text/csv; charset=utf-8"));
+
+        let lines: Vec<&str> = body.trim_end().split("\r\n").collect();
+        assert_eq!(lines[0], CSV_HEADERS.join(","));
+        // The running timer and the entry after the period are left out
+        assert_eq!(lines.len(), 3);
+        let first: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(first.len(), CSV_HEADERS.len());
+        assert_eq!(first[0], "Test User");
+        assert_eq!(first[1], "Acme");
+        assert!(first[2].ends_with(" Printer offline"));
+        assert_eq!(first[6], "2024-05-14T09:15:00+00:00");
+        assert_eq!(first[7], "2024-05-14T10:00:00+00:00");
+        assert_eq!(first[8], "45");
+        assert_eq!(first[10], "true");
+        assert_eq!(first[12], "90.00");
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_ics_export_events_carry_entry_times() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (user, token) = create_user_with_token(pool).await;
+        seed_entries(pool, user.id).await;
+
+        let (status, content_type, body) = export(pool, &token, "from=2024-05-14&to=2024-05-15&format=ics").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some("text/calendar; charset=utf-8"));
+
+        let lines: Vec<&str> = body.split("\r\n").collect();
+        assert_eq!(lines[0], "BEGIN:VCALENDAR");
+        assert!(body.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(), 2);
+        let starts: Vec<&str> = lines.iter().filter_map(|l| l.strip_prefix("DTSTART:")).collect();
+        let ends: Vec<&str> = lines.iter().filter_map(|l| l.strip_prefix("DTEND:")).collect();
+        assert_eq!(starts, vec!["20240514T091500Z", "20240515T091500Z"]);
+        assert_eq!(ends, vec!["20240514T100000Z", "20240515T094500Z"]);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_techs_only_export_their_own_time() {
+        let ctx = TestContext::new().await;
+        let pool = &ctx.db_pool;
+        let (tech, token) = create_user_with_token(pool).await;
+        let (other, _) = create_user_with_token(pool).await;
+        seed_entries(pool, tech.id).await;
+        seed_entries(pool, other.id).await;
+
+        let (status, _, _) = export(pool, &token, &format!("from=2024-05-14&to=2024-05-31&user_id={}", other.id)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Without user_id a tech gets just their own entries
+        let (status, _, body) = export(pool, &token, "from=2024-05-14&to=2024-05-31").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.trim_end().split("\r\n").count(), 3);
+
+        let (status, _, _) = export(pool, &token, "from=2024-05-31&to=2024-05-14").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        ctx.cleanup().await;
+    }
+}
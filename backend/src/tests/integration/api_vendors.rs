@@ -0,0 +1,135 @@
+// Vendor and expense API integration tests
+
+#[cfg(test)]
+mod vendor_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    use crate::handlers::{expense_routes, vendor_routes};
+    use crate::tests::helpers::create_user_with_token;
+    use crate::tests::TestContext;
+    use crate::websocket::WsManager;
+    use crate::AppState;
+
+    fn app(ctx: &TestContext) -> Router {
+        let state = AppState { db_pool: ctx.db_pool.clone(), ws_manager: WsManager::new(), email_service: None };
+        Router::new()
+            .nest("/vendors", vendor_routes())
+            .nest("/expenses", expense_routes())
+            .with_state(Arc::new(state))
+    }
+
+    async fn send(app: &Router, token: &str, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_vendor_crud_and_soft_delete() {
+        let ctx = TestContext::new().await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+        let app = app(&ctx);
+
+        let (status, vendor) = send(&app, &token, "POST", "/vendors", Some(json!({
+            "name": "Northwind Supplies",
+            "email": "orders@northwind.test",
+            "contact_name": "Dana Reyes",
+            "payment_terms": "net_30"
+        }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = vendor["id"].as_str().unwrap().to_string();
+        assert_eq!(vendor["payment_terms"], "net_30");
+
+        let (status, _) = send(&app, &token, "POST", "/vendors", Some(json!({ "name": "  " }))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, updated) = send(&app, &token, "PUT", &format!("/vendors/{}", id), Some(json!({
+            "phone": "555-0100"
+        }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["phone"], "555-0100");
+        assert_eq!(updated["contact_name"], "Dana Reyes");
+
+        let (_, list) = send(&app, &token, "GET", "/vendors?search=northwind", None).await;
+        assert!(list["data"].as_array().unwrap().iter().any(|v| v["id"] == id.as_str()));
+
+        let (status, _) = send(&app, &token, "DELETE", &format!("/vendors/{}", id), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, &token, "DELETE", &format!("/vendors/{}", id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Archived vendors leave the list but stay readable
+        let (_, list) = send(&app, &token, "GET", "/vendors?search=northwind", None).await;
+        assert!(!list["data"].as_array().unwrap().iter().any(|v| v["id"] == id.as_str()));
+        let (status, archived) = send(&app, &token, "GET", &format!("/vendors/{}", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(archived["archived_at"].is_string());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_expenses_roll_up_under_their_vendor() {
+        let ctx = TestContext::new().await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+        let app = app(&ctx);
+
+        let (_, vendor) = send(&app, &token, "POST", "/vendors", Some(json!({ "name": "Contoso Hardware" }))).await;
+        let id = vendor["id"].as_str().unwrap().to_string();
+
+        for amount in ["120.00", "30.50"] {
+            let (status, expense) = send(&app, &token, "POST", "/expenses", Some(json!({
+                "vendor_id": id,
+                "category": "hardware",
+                "description": "Switch",
+                "amount": amount,
+                "expense_date": "2024-05-01"
+            }))).await;
+            assert_eq!(status, StatusCode::CREATED);
+            assert_eq!(expense["vendor"], "Contoso Hardware");
+        }
+
+        let (status, rollup) = send(&app, &token, "GET", &format!("/vendors/{}/expenses", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(rollup["expense_count"], 2);
+        assert_eq!(rollup["total_amount"], "150.50");
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_expense_with_unknown_vendor_is_rejected() {
+        let ctx = TestContext::new().await;
+        let (_, token) = create_user_with_token(&ctx.db_pool).await;
+        let app = app(&ctx);
+
+        let (status, error) = send(&app, &token, "POST", "/expenses", Some(json!({
+            "vendor_id": uuid::Uuid::new_v4(),
+            "category": "software",
+            "description": "Licenses",
+            "amount": "99.00",
+            "expense_date": "2024-05-01"
+        }))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(error["details"]["vendor_id"].is_array());
+
+        ctx.cleanup().await;
+    }
+}
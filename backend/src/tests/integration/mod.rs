@@ -5,5 +5,24 @@ pub mod api_m365;
 pub mod api_teams;
 pub mod api_billing;
 pub mod api_analytics;
+pub mod api_workflows;
+pub mod api_itdoc;
+pub mod api_audit;
+pub mod api_assets;
+pub mod api_passwords;
+pub mod api_email;
+pub mod api_time;
+pub mod api_projects;
+pub mod api_kb;
+pub mod api_portal;
+pub mod api_dashboard;
+pub mod api_contracts;
+pub mod api_database;
+pub mod api_files;
+pub mod api_vendors;
+pub mod api_expenses;
+pub mod api_jobs;
+pub mod api_search;
+pub mod api_integrations;
 
 // Integration test utilities for API testing
\ No newline at end of file
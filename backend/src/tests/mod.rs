@@ -11,6 +11,7 @@ use uuid::Uuid;
 
 pub struct TestContext {
     pub db_pool: PgPool,
+    pub database_url: String,
     pub _container: Option<Container<'static, PostgresImage>>,
 }
 
@@ -30,6 +31,7 @@ impl TestContext {
                 
             Self {
                 db_pool: pool,
+                database_url,
                 _container: None,
             }
         } else {
@@ -59,6 +61,7 @@ impl TestContext {
                 
             Self {
                 db_pool: pool,
+                database_url: connection_string,
                 _container: Some(container),
             }
         }
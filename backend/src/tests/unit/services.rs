@@ -3,7 +3,6 @@
 use super::*;
 use crate::services::{
     cache::{CacheService, cache_keys, ttl},
-    audit::{AuditService, AuditAction, AuditSeverity, AuditEntryBuilder, ChangeTracker},
     metrics::{MetricsService, MetricType, HealthStatus, RequestLog, Timer, metric_names},
 };
 use serde_json::json;
@@ -90,115 +89,6 @@ mod cache_tests {
     }
 }
 
-// ============================================
-// Audit Service Tests
-// ============================================
-
-#[cfg(test)]
-mod audit_tests {
-    use super::*;
-
-    #[test]
-    fn test_audit_action_strings() {
-        assert_eq!(AuditAction::Create.as_str(), "create");
-        assert_eq!(AuditAction::Update.as_str(), "update");
-        assert_eq!(AuditAction::Delete.as_str(), "delete");
-        assert_eq!(AuditAction::View.as_str(), "view");
-        assert_eq!(AuditAction::Export.as_str(), "export");
-        assert_eq!(AuditAction::Login.as_str(), "login");
-        assert_eq!(AuditAction::Logout.as_str(), "logout");
-        assert_eq!(AuditAction::PasswordChange.as_str(), "password_change");
-        assert_eq!(AuditAction::PermissionChange.as_str(), "permission_change");
-        assert_eq!(AuditAction::ApiKeyCreate.as_str(), "api_key_create");
-        assert_eq!(AuditAction::ApiKeyRevoke.as_str(), "api_key_revoke");
-        assert_eq!(AuditAction::BulkOperation.as_str(), "bulk_operation");
-        assert_eq!(AuditAction::Import.as_str(), "import");
-        assert_eq!(AuditAction::Archive.as_str(), "archive");
-        assert_eq!(AuditAction::Restore.as_str(), "restore");
-    }
-
-    #[test]
-    fn test_sensitive_actions() {
-        // Sensitive actions
-        assert!(AuditAction::PasswordChange.is_sensitive());
-        assert!(AuditAction::PermissionChange.is_sensitive());
-        assert!(AuditAction::ApiKeyCreate.is_sensitive());
-        assert!(AuditAction::ApiKeyRevoke.is_sensitive());
-
-        // Non-sensitive actions
-        assert!(!AuditAction::Create.is_sensitive());
-        assert!(!AuditAction::Update.is_sensitive());
-        assert!(!AuditAction::Delete.is_sensitive());
-        assert!(!AuditAction::View.is_sensitive());
-        assert!(!AuditAction::Login.is_sensitive());
-    }
-
-    #[test]
-    fn test_audit_severity() {
-        assert_eq!(AuditSeverity::Info.as_str(), "info");
-        assert_eq!(AuditSeverity::Warning.as_str(), "warning");
-        assert_eq!(AuditSeverity::Critical.as_str(), "critical");
-
-        // Default should be Info
-        assert_eq!(AuditSeverity::default(), AuditSeverity::Info);
-    }
-
-    #[test]
-    fn test_audit_entry_builder() {
-        let user_id = Uuid::new_v4();
-        let resource_id = Uuid::new_v4();
-        let request_id = Uuid::new_v4();
-
-        let builder = AuditEntryBuilder::new(AuditAction::Create, "ticket")
-            .user(user_id, Some("test@example.com".to_string()))
-            .resource(resource_id, Some("Ticket #123".to_string()))
-            .request_id(request_id)
-            .severity(AuditSeverity::Warning);
-
-        // Builder should compile and work (actual db insert tested in integration)
-        assert!(true);
-    }
-
-    #[test]
-    fn test_change_tracker() {
-        let mut tracker = ChangeTracker::new();
-
-        // Initially no changes
-        assert!(!tracker.has_changes());
-
-        // Track a change
-        let changed = tracker.track("name", &"Old Name", &"New Name").unwrap();
-        assert!(changed);
-        assert!(tracker.has_changes());
-
-        // Track a non-change
-        let not_changed = tracker.track("status", &"active", &"active").unwrap();
-        assert!(!not_changed);
-
-        // Convert to JSON
-        let json = tracker.into_json();
-        assert!(json.is_object());
-    }
-
-    #[test]
-    fn test_change_tracker_multiple_fields() {
-        let mut tracker = ChangeTracker::new();
-
-        tracker.track("field1", &"a", &"b").unwrap();
-        tracker.track("field2", &1, &2).unwrap();
-        tracker.track("field3", &true, &false).unwrap();
-        tracker.track("field4", &"same", &"same").unwrap(); // No change
-
-        let json = tracker.into_json();
-        let obj = json.as_object().unwrap();
-
-        assert!(obj.contains_key("field1"));
-        assert!(obj.contains_key("field2"));
-        assert!(obj.contains_key("field3"));
-        assert!(!obj.contains_key("field4")); // No change, not included
-    }
-}
-
 // ============================================
 // Metrics Service Tests
 // ============================================
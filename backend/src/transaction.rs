@@ -0,0 +1,78 @@
+//! Per-request database transactions
+//!
+//! Handlers that write several rows take a [`Tx`] extractor, run every
+//! statement on it, and hand their result to [`Tx::finish`]: an `Ok`
+//! commits and an `Err` rolls back, so a step that fails part way never
+//! leaves the earlier steps behind. A `Tx` dropped without finishing (a
+//! panic, or an early return before `finish`) is rolled back as well.
+//!
+//! ```ignore
+//! async fn handler(State(state): State<Arc<AppState>>, mut tx: Tx, ...) -> ApiResult<Json<T>> {
+//!     let result = do_the_steps(&mut tx, ...).await;
+//!     tx.finish(result).await
+//! }
+//! ```
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use crate::{ApiResult, AppState};
+
+pub struct Tx(Transaction<'static, Postgres>);
+
+impl Tx {
+    pub async fn begin(pool: &PgPool) -> Result<Self, sqlx::Error> {
+        Ok(Self(pool.begin().await?))
+    }
+
+    /// Commit if the handler succeeded, roll back if it didn't. A failed
+    /// commit turns success into an error; a failed rollback is only logged
+    /// since the handler's own error is the one worth returning.
+    pub async fn finish<T>(self, result: ApiResult<T>) -> ApiResult<T> {
+        match result {
+            Ok(value) => {
+                self.0.commit().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                if let Err(rollback_error) = self.0.rollback().await {
+                    tracing::warn!("Failed to roll back request transaction: {}", rollback_error);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Deref for Tx {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        &self.0
+    }
+}
+
+impl DerefMut for Tx {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        &mut self.0
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Tx {
+    type Rejection = Response;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        Tx::begin(&state.db_pool).await.map_err(|e| {
+            tracing::error!("Failed to start request transaction: {}", e);
+            crate::ApiError::internal("Database unavailable").into_response()
+        })
+    }
+}
@@ -1,10 +1,17 @@
 //! Request validation for Resolve API
 //!
-//! Provides type-safe validation with clear error messages.
+//! Provides type-safe validation with clear error messages. The helper
+//! modules check one field and stop at the first problem; [`Validator`]
+//! collects every field's errors so a request learns about all of them in
+//! one 422 response. Request bodies implement [`Validate`] with their rules.
 
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use std::collections::HashMap;
+use std::fmt::Display;
 use crate::error::{AppError, ValidationBuilder};
+use resolve_shared::{AssetStatus, InvoiceStatus, Priority, TextEnum, TicketStatus};
 
 /// Validation result type
 pub type ValidationResult<T> = Result<T, AppError>;
@@ -247,29 +254,43 @@ pub mod number {
 pub mod enums {
     use super::*;
 
+    fn not_one_of(field: &str, allowed: &[&str]) -> AppError {
+        AppError::ValidationError {
+            details: {
+                let mut d = HashMap::new();
+                d.insert(
+                    field.to_string(),
+                    vec![format!(
+                        "{} must be one of: {}",
+                        field,
+                        allowed.join(", ")
+                    )],
+                );
+                d
+            },
+        }
+    }
+
     /// Validate value is one of allowed options
     pub fn one_of(value: &str, field: &str, allowed: &[&str]) -> ValidationResult<String> {
         let lower = value.to_lowercase();
         if allowed.iter().any(|a| a.to_lowercase() == lower) {
             Ok(lower)
         } else {
-            Err(AppError::ValidationError {
-                details: {
-                    let mut d = HashMap::new();
-                    d.insert(
-                        field.to_string(),
-                        vec![format!(
-                            "{} must be one of: {}",
-                            field,
-                            allowed.join(", ")
-                        )],
-                    );
-                    d
-                },
-            })
+            Err(not_one_of(field, allowed))
         }
     }
 
+    /// Parse a status or priority into its enum
+    pub fn parse<T: TextEnum>(value: &str, field: &str) -> ValidationResult<T> {
+        T::parse(value).ok_or_else(|| not_one_of(field, T::NAMES))
+    }
+
+    /// Parse an optional status or priority into its enum
+    pub fn parse_optional<T: TextEnum>(value: &Option<String>, field: &str) -> ValidationResult<Option<T>> {
+        value.as_deref().map(|v| parse(v, field)).transpose()
+    }
+
     /// Validate optional enum value
     pub fn one_of_optional(
         value: &Option<String>,
@@ -362,6 +383,9 @@ pub mod collection {
 }
 
 /// Validator builder for complex validations
+///
+/// Rules on an absent optional value pass; use `required` or
+/// `required_string` where the value must be given.
 pub struct Validator {
     builder: ValidationBuilder,
 }
@@ -431,6 +455,92 @@ impl Validator {
         }
     }
 
+    /// Validate a value is present
+    pub fn required<T>(self, value: &Option<T>, field: &str) -> Self {
+        match value {
+            Some(_) => self,
+            None => self.error(field, &format!("{} is required", field)),
+        }
+    }
+
+    /// Validate a required string field isn't blank
+    pub fn not_blank(self, value: &str, field: &str) -> Self {
+        if value.trim().is_empty() {
+            self.error(field, &format!("{} is required", field))
+        } else {
+            self
+        }
+    }
+
+    /// Validate the trimmed length is within `min..=max`
+    pub fn length(self, value: Option<&str>, field: &str, min: usize, max: usize) -> Self {
+        match value.map(|s| s.trim().chars().count()) {
+            Some(len) if len < min || len > max => {
+                self.error(field, &format!("{} must be between {} and {} characters", field, min, max))
+            }
+            _ => self,
+        }
+    }
+
+    /// Validate a number is within `min..=max`
+    pub fn range<T: PartialOrd + Display>(self, value: Option<T>, field: &str, min: T, max: T) -> Self {
+        match value {
+            Some(v) if v < min || v > max => {
+                self.error(field, &format!("{} must be between {} and {}", field, min, max))
+            }
+            _ => self,
+        }
+    }
+
+    /// Validate a value is one of `allowed`, ignoring case
+    pub fn one_of(self, value: Option<&str>, field: &str, allowed: &[&str]) -> Self {
+        match value {
+            Some(v) if !allowed.iter().any(|a| a.eq_ignore_ascii_case(v.trim())) => {
+                self.error(field, &format!("{} must be one of: {}", field, allowed.join(", ")))
+            }
+            _ => self,
+        }
+    }
+
+    /// Validate a value parses as a status or priority enum
+    pub fn text_enum<T: TextEnum>(self, value: Option<&str>, field: &str) -> Self {
+        self.one_of(value, field, T::NAMES)
+    }
+
+    /// Validate a value matches `pattern`, reporting `message` if not
+    pub fn matches(self, value: Option<&str>, field: &str, pattern: &Regex, message: &str) -> Self {
+        match value {
+            Some(v) if !pattern.is_match(v) => self.error(field, message),
+            _ => self,
+        }
+    }
+
+    /// Collect the errors from one of the single-field helpers
+    pub fn check<T>(mut self, result: ValidationResult<T>) -> Self {
+        if let Err(AppError::ValidationError { details }) = result {
+            for (field, messages) in details {
+                for message in messages {
+                    self.builder = self.builder.error(&field, &message);
+                }
+            }
+        }
+        self
+    }
+
+    /// Validate a referenced row exists in `table`, which must be a
+    /// trusted table name, never request input. Database errors are
+    /// returned rather than collected.
+    pub async fn exists(self, pool: &PgPool, table: &'static str, value: Option<::uuid::Uuid>, field: &str) -> ValidationResult<Self> {
+        let Some(id) = value else {
+            return Ok(self);
+        };
+        let exists: bool = sqlx::query_scalar(&format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = $1)", table))
+            .bind(id)
+            .fetch_one(pool)
+            .await?;
+        Ok(if exists { self } else { self.error(field, &format!("{} does not exist", field)) })
+    }
+
     /// Check if validation passed
     pub fn is_valid(&self) -> bool {
         !self.builder.has_errors()
@@ -457,20 +567,28 @@ impl Default for Validator {
     }
 }
 
+/// A request body with field rules; check with `payload.validate()?`, or
+/// chain onto `payload.rules(Validator::new())` to add async checks such as
+/// [`Validator::exists`] before `finish()`
+pub trait Validate {
+    fn rules(&self, validator: Validator) -> Validator;
+
+    fn validate(&self) -> ValidationResult<()> {
+        self.rules(Validator::new()).finish()
+    }
+}
+
 /// Common ticket status values
-pub const TICKET_STATUSES: &[&str] = &[
-    "new", "open", "in_progress", "pending", "waiting_on_client",
-    "scheduled", "resolved", "closed", "cancelled"
-];
+pub const TICKET_STATUSES: &[&str] = <TicketStatus as TextEnum>::NAMES;
 
 /// Common ticket priority values
-pub const TICKET_PRIORITIES: &[&str] = &["low", "medium", "high", "critical", "urgent"];
+pub const TICKET_PRIORITIES: &[&str] = <Priority as TextEnum>::NAMES;
 
 /// Common invoice statuses
-pub const INVOICE_STATUSES: &[&str] = &["draft", "sent", "viewed", "partial", "paid", "overdue", "cancelled"];
+pub const INVOICE_STATUSES: &[&str] = <InvoiceStatus as TextEnum>::NAMES;
 
 /// Common asset statuses
-pub const ASSET_STATUSES: &[&str] = &["active", "inactive", "retired", "maintenance", "disposed"];
+pub const ASSET_STATUSES: &[&str] = <AssetStatus as TextEnum>::NAMES;
 
 #[cfg(test)]
 mod tests {
@@ -496,6 +614,20 @@ mod tests {
         assert!(enums::one_of("invalid", "priority", TICKET_PRIORITIES).is_err());
     }
 
+    #[test]
+    fn test_enum_parsing() {
+        let status: TicketStatus = enums::parse("In_Progress", "status").unwrap();
+        assert_eq!(status, TicketStatus::InProgress);
+        assert_eq!(enums::parse_optional::<Priority>(&None, "priority").unwrap(), None);
+
+        match enums::parse::<TicketStatus>("Opn", "status") {
+            Err(AppError::ValidationError { details }) => {
+                assert!(details["status"][0].starts_with("status must be one of: new, open"));
+            }
+            other => panic!("expected a field error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_validator_builder() {
         let result = Validator::new()
@@ -510,4 +642,42 @@ mod tests {
             .finish();
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_validator_collects_every_field_error() {
+        let sku = Regex::new(r"^[A-Z]{3}-\d{4}$").unwrap();
+        let result = Validator::new()
+            .not_blank(" ", "name")
+            .length(Some("ab"), "name", 3, 50)
+            .range(Some(120), "percent", 0, 100)
+            .one_of(Some("sometimes"), "frequency", &["daily", "weekly"])
+            .text_enum::<Priority>(Some(" HIGH "), "priority")
+            .matches(Some("abc-1"), "sku", &sku, "sku must look like ABC-1234")
+            .required::<i32>(&None, "quantity")
+            .check(email::validate("nope", "email"))
+            .finish();
+
+        match result {
+            Err(AppError::ValidationError { details }) => {
+                assert_eq!(details["name"].len(), 2);
+                assert_eq!(details["percent"], vec!["percent must be between 0 and 100"]);
+                assert_eq!(details["frequency"], vec!["frequency must be one of: daily, weekly"]);
+                assert_eq!(details["sku"], vec!["sku must look like ABC-1234"]);
+                assert_eq!(details["quantity"], vec!["quantity is required"]);
+                assert_eq!(details["email"], vec!["Invalid email format"]);
+                assert!(!details.contains_key("priority"));
+            }
+            other => panic!("expected field errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_absent_optional_values_pass() {
+        let result = Validator::new()
+            .length(None, "name", 3, 50)
+            .range::<i64>(None, "percent", 0, 100)
+            .one_of(None, "frequency", &["daily"])
+            .finish();
+        assert!(result.is_ok());
+    }
 }
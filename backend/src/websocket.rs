@@ -36,6 +36,10 @@ pub struct WsConnection {
     pub sender: broadcast::Sender<WsMessage>,
 }
 
+/// Sent to every connection before the server closes it at shutdown
+pub const SHUTDOWN_EVENT: &str = "server_shutdown";
+
+#[derive(Clone)]
 pub struct WsManager {
     connections: Arc<RwLock<HashMap<Uuid, WsConnection>>>,
     broadcast: broadcast::Sender<WsMessage>,
@@ -78,9 +82,45 @@ impl WsManager {
         }
     }
 
+    /// Send to every staff connection and dashboard stream. Portal
+    /// connections are left out: these events cover every client.
     pub async fn broadcast_all(&self, message: WsMessage) {
+        let connections = self.connections.read().await;
+        for conn in connections.values() {
+            if conn.user_id.is_some() {
+                let _ = conn.sender.send(message.clone());
+            }
+        }
         let _ = self.broadcast.send(message);
     }
+
+    /// Receive everything sent with `broadcast_all` from now on; only for
+    /// streams served to staff
+    pub fn subscribe(&self) -> broadcast::Receiver<WsMessage> {
+        self.broadcast.subscribe()
+    }
+
+    /// Tell every connection the server is going away and wait up to
+    /// `timeout` for them to close. Returns how many were still open.
+    pub async fn close_all(&self, timeout: Duration) -> usize {
+        let message = WsMessage {
+            event_type: SHUTDOWN_EVENT.to_string(),
+            payload: serde_json::json!({}),
+            timestamp: chrono::Utc::now(),
+        };
+        for conn in self.connections.read().await.values() {
+            let _ = conn.sender.send(message.clone());
+        }
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let open = self.connections.read().await.len();
+            if open == 0 || tokio::time::Instant::now() >= deadline {
+                return open;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -102,7 +142,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>, token: Option<St
     
     // Authenticate the connection
     let (user_id, contact_id) = if let Some(token) = token {
-        match verify_token(&token) {
+        match verify_token(&state.db_pool, &token).await {
             Ok(claims) => {
                 // This is a user token
                 (Some(Uuid::parse_str(&claims.claims.sub).unwrap_or_default()), None)
@@ -175,6 +215,10 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>, token: Option<St
             if sender.send(Message::Text(serde_json::to_string(&msg).unwrap())).await.is_err() {
                 break;
             }
+            if msg.event_type == SHUTDOWN_EVENT {
+                let _ = sender.send(Message::Close(None)).await;
+                break;
+            }
         }
     });
     
@@ -292,4 +336,35 @@ impl AppState {
         };
         self.ws_manager.broadcast_all(message).await;
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(user_id: Option<Uuid>, contact_id: Option<Uuid>) -> (WsConnection, broadcast::Receiver<WsMessage>) {
+        let (sender, receiver) = broadcast::channel(4);
+        (WsConnection { id: Uuid::new_v4(), user_id, contact_id, sender }, receiver)
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_all_skips_portal_connections() {
+        let manager = WsManager::new();
+        let (staff, mut staff_rx) = connection(Some(Uuid::new_v4()), None);
+        let (portal, mut portal_rx) = connection(None, Some(Uuid::new_v4()));
+        manager.add_connection(staff).await;
+        manager.add_connection(portal).await;
+        let mut stream_rx = manager.subscribe();
+
+        manager
+            .broadcast_all(WsMessage {
+                event_type: "ticket_created".to_string(),
+                payload: serde_json::json!({}),
+                timestamp: chrono::Utc::now(),
+            })
+            .await;
+
+        assert_eq!(staff_rx.try_recv().unwrap().event_type, "ticket_created");
+        assert_eq!(stream_rx.try_recv().unwrap().event_type, "ticket_created");
+        assert!(portal_rx.try_recv().is_err());
+    }
+}
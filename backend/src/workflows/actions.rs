@@ -74,6 +74,7 @@ pub enum ActionType {
 
     // Integration actions
     CallApi,
+    Webhook,
     RunScript,
     ExecuteQuery,
 
@@ -369,6 +370,20 @@ impl Action {
         )
     }
 
+    pub fn webhook(url: &str, method: &str, headers: serde_json::Value, body: serde_json::Value, signing_secret: Option<&str>) -> Self {
+        Self::new(
+            "Webhook",
+            ActionType::Webhook,
+            serde_json::json!({
+                "url": url,
+                "method": method,
+                "headers": headers,
+                "body": body,
+                "signing_secret": signing_secret
+            }),
+        )
+    }
+
     pub fn run_script(script_id: &str, parameters: serde_json::Value) -> Self {
         Self::new(
             "Run Script",
@@ -428,6 +443,11 @@ impl ActionResult {
         }
     }
 
+    pub fn with_output(mut self, output: serde_json::Value) -> Self {
+        self.output = Some(output);
+        self
+    }
+
     pub fn with_duration(mut self, duration_ms: i64) -> Self {
         self.duration_ms = duration_ms;
         self
@@ -536,6 +556,22 @@ mod tests {
         assert!(failure.error.is_some());
     }
 
+    #[test]
+    fn test_webhook_builder_config_parses() {
+        let action = Action::webhook(
+            "https://hooks.slack.com/services/T000/B000/XXX",
+            "POST",
+            serde_json::json!({ "X-Source": "resolve" }),
+            serde_json::json!({ "text": "Ticket {{ticket_id}} breached SLA" }),
+            Some("shh"),
+        );
+
+        assert_eq!(action.action_type, ActionType::Webhook);
+        let config: crate::workflows::webhook::WebhookConfig = serde_json::from_value(action.config).unwrap();
+        assert_eq!(config.headers.get("X-Source").map(String::as_str), Some("resolve"));
+        assert_eq!(config.signing_secret.as_deref(), Some("shh"));
+    }
+
     #[test]
     fn test_preset_actions() {
         let ack = presets::auto_acknowledge();
@@ -176,6 +176,106 @@ impl Condition {
     pub fn regex(field: &str, pattern: &str) -> Self {
         Self::new(field, "regex", serde_json::Value::String(pattern.to_string()))
     }
+
+    /// Evaluate this condition against a JSON payload
+    pub fn evaluate(&self, payload: &serde_json::Value) -> bool {
+        let field_value = lookup_field(payload, &self.field);
+
+        match self.operator.as_str() {
+            "equals" | "eq" | "==" => {
+                field_value.map(|v| v == &self.value).unwrap_or(false)
+            }
+            "not_equals" | "ne" | "!=" => {
+                field_value.map(|v| v != &self.value).unwrap_or(true)
+            }
+            "contains" => {
+                if let Some(val) = field_value {
+                    if let (Some(s), Some(pattern)) = (val.as_str(), self.value.as_str()) {
+                        return s.to_lowercase().contains(&pattern.to_lowercase());
+                    }
+                }
+                false
+            }
+            "not_contains" => {
+                if let Some(val) = field_value {
+                    if let (Some(s), Some(pattern)) = (val.as_str(), self.value.as_str()) {
+                        return !s.to_lowercase().contains(&pattern.to_lowercase());
+                    }
+                }
+                true
+            }
+            "starts_with" => {
+                if let Some(val) = field_value {
+                    if let (Some(s), Some(pattern)) = (val.as_str(), self.value.as_str()) {
+                        return s.to_lowercase().starts_with(&pattern.to_lowercase());
+                    }
+                }
+                false
+            }
+            "ends_with" => {
+                if let Some(val) = field_value {
+                    if let (Some(s), Some(pattern)) = (val.as_str(), self.value.as_str()) {
+                        return s.to_lowercase().ends_with(&pattern.to_lowercase());
+                    }
+                }
+                false
+            }
+            "greater_than" | "gt" | ">" => {
+                if let Some(val) = field_value {
+                    if let (Some(v), Some(c)) = (val.as_f64(), self.value.as_f64()) {
+                        return v > c;
+                    }
+                }
+                false
+            }
+            "less_than" | "lt" | "<" => {
+                if let Some(val) = field_value {
+                    if let (Some(v), Some(c)) = (val.as_f64(), self.value.as_f64()) {
+                        return v < c;
+                    }
+                }
+                false
+            }
+            "in" => {
+                if let Some(val) = field_value {
+                    if let Some(arr) = self.value.as_array() {
+                        return arr.contains(val);
+                    }
+                }
+                false
+            }
+            "not_in" => {
+                if let Some(val) = field_value {
+                    if let Some(arr) = self.value.as_array() {
+                        return !arr.contains(val);
+                    }
+                }
+                true
+            }
+            "is_null" | "is_empty" => {
+                field_value.is_none() || field_value == Some(&serde_json::Value::Null)
+            }
+            "is_not_null" | "is_not_empty" => {
+                field_value.is_some() && field_value != Some(&serde_json::Value::Null)
+            }
+            "regex" => {
+                if let Some(val) = field_value {
+                    if let (Some(s), Some(pattern)) = (val.as_str(), self.value.as_str()) {
+                        if let Ok(re) = regex::Regex::new(pattern) {
+                            return re.is_match(s);
+                        }
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Resolve a dot-notation field path within a payload
+fn lookup_field<'a>(payload: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(payload, |current, part| current.get(part))
 }
 
 impl ConditionGroup {
@@ -204,6 +304,20 @@ impl ConditionGroup {
         self.conditions.push(condition);
         self
     }
+
+    /// Evaluate the group, including nested groups, against a JSON payload
+    pub fn evaluate(&self, payload: &serde_json::Value) -> bool {
+        let mut results = self
+            .conditions
+            .iter()
+            .map(|c| c.evaluate(payload))
+            .chain(self.groups.iter().map(|g| g.evaluate(payload)));
+
+        match self.logic.as_str() {
+            "OR" | "or" => results.any(|r| r),
+            _ => results.all(|r| r),
+        }
+    }
 }
 
 /// Common condition presets for MSP workflows
@@ -238,9 +352,9 @@ pub mod presets {
         Condition::equals("category_id", serde_json::json!(category_id.to_string()))
     }
 
-    /// Condition for response SLA breach
+    /// Condition for first response SLA breach
     pub fn response_breach() -> Condition {
-        Condition::equals("breach_type", serde_json::json!("response"))
+        Condition::equals("breach_type", serde_json::json!("first_response"))
     }
 
     /// Condition for resolution SLA breach
@@ -315,6 +429,25 @@ mod tests {
         assert_eq!(outer.groups.len(), 1);
     }
 
+    #[test]
+    fn test_group_evaluation() {
+        let group = ConditionGroup::and(vec![Condition::is_null("assigned_to")])
+            .with_nested_group(ConditionGroup::or(vec![
+                Condition::equals("priority", serde_json::json!("critical")),
+                Condition::contains("client.name", "acme"),
+            ]));
+
+        let payload = serde_json::json!({
+            "priority": "low",
+            "assigned_to": null,
+            "client": { "name": "ACME Corp" }
+        });
+        assert!(group.evaluate(&payload));
+
+        let assigned = serde_json::json!({ "priority": "critical", "assigned_to": "someone" });
+        assert!(!group.evaluate(&assigned));
+    }
+
     #[test]
     fn test_presets() {
         let crit = presets::critical_priority();
@@ -10,9 +10,10 @@ use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use super::{
-    Action, ActionResult, ActionType, Condition, ConditionGroup,
+    Action, ActionResult, ActionType, ConditionGroup,
     ExecutionContext, TriggerEvent, TriggerType, WorkflowExecutor,
 };
+use super::triggers::EventSource;
 use crate::services::EmailService;
 use crate::websocket::WsManager;
 
@@ -33,30 +34,66 @@ pub struct WorkflowDefinition {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+/// A recorded workflow run from `workflow_executions`
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct WorkflowInstance {
+pub struct WorkflowExecution {
     pub id: Uuid,
     pub workflow_id: Uuid,
-    pub trigger_event_id: Uuid,
-    pub status: WorkflowStatus,
-    pub started_at: DateTime<Utc>,
+    pub trigger_type: Option<String>,
+    pub trigger_event_id: Option<Uuid>,
+    pub trigger_data: serde_json::Value,
+    pub status: String,
+    pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
-    pub actions_completed: i32,
-    pub total_actions: i32,
+    pub duration_ms: Option<i32>,
+    pub steps_total: Option<i32>,
+    pub steps_completed: Option<i32>,
+    pub steps_failed: Option<i32>,
+    pub action_results: serde_json::Value,
     pub error_message: Option<String>,
-    pub execution_log: serde_json::Value,
+    pub replayed_from_id: Option<Uuid>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, sqlx::Type)]
-#[sqlx(type_name = "workflow_status", rename_all = "snake_case")]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum WorkflowStatus {
     Pending,
     Running,
     Completed,
+    /// Every action ran but at least one failed without `stop_on_failure`
+    PartialFailure,
     Failed,
     Cancelled,
 }
 
+impl WorkflowStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkflowStatus::Pending => "pending",
+            WorkflowStatus::Running => "running",
+            WorkflowStatus::Completed => "completed",
+            WorkflowStatus::PartialFailure => "partial_failure",
+            WorkflowStatus::Failed => "failed",
+            WorkflowStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Status of a run that reached the end of its action list
+    fn finished(actions_failed: i32) -> Self {
+        if actions_failed == 0 {
+            WorkflowStatus::Completed
+        } else {
+            WorkflowStatus::PartialFailure
+        }
+    }
+}
+
+const EXECUTION_COLUMNS: &str = r#"
+    id, workflow_id, trigger_type, trigger_event_id, COALESCE(trigger_data, '{}'::jsonb) as trigger_data,
+    status, started_at, completed_at, duration_ms, steps_total, steps_completed, steps_failed,
+    action_results, error_message, replayed_from_id
+"#;
+
 pub struct WorkflowEngine {
     db_pool: PgPool,
     email_service: EmailService,
@@ -159,27 +196,17 @@ impl WorkflowEngine {
 
             // Evaluate workflow conditions
             if let Some(conditions) = &workflow.conditions {
-                if !self.evaluate_conditions(conditions, &event.payload).await {
+                if !conditions.evaluate(&event.payload) {
                     continue;
                 }
             }
 
-            // Create workflow instance
-            let instance_id = self.create_instance(workflow.id, &event).await?;
+            let (execution_id, succeeded) = self.run_workflow(workflow, &event, None).await?;
+            if succeeded {
+                executed_instances.push(execution_id);
 
-            // Execute workflow
-            match self.execute_workflow(workflow, &event, instance_id).await {
-                Ok(_) => {
-                    executed_instances.push(instance_id);
-                    info!("Workflow '{}' executed successfully", workflow.name);
-
-                    if workflow.stop_on_first_match {
-                        break;
-                    }
-                }
-                Err(e) => {
-                    error!("Workflow '{}' failed: {}", workflow.name, e);
-                    self.mark_instance_failed(instance_id, &e.to_string()).await?;
+                if workflow.stop_on_first_match {
+                    break;
                 }
             }
         }
@@ -187,6 +214,59 @@ impl WorkflowEngine {
         Ok(executed_instances)
     }
 
+    /// Record and execute a single workflow run. Returns the execution id and
+    /// whether the run finished without being stopped by a failing action.
+    async fn run_workflow(
+        &self,
+        workflow: &WorkflowDefinition,
+        event: &TriggerEvent,
+        replayed_from: Option<Uuid>,
+    ) -> Result<(Uuid, bool), Box<dyn std::error::Error + Send + Sync>> {
+        let execution_id = self.create_execution(workflow.id, event, replayed_from).await?;
+
+        match self.execute_workflow(workflow, event, execution_id).await {
+            Ok(status) => {
+                info!("Workflow '{}' finished with status {}", workflow.name, status.as_str());
+                Ok((execution_id, true))
+            }
+            Err(e) => {
+                error!("Workflow '{}' failed: {}", workflow.name, e);
+                self.mark_execution_failed(execution_id, &e.to_string()).await?;
+                Ok((execution_id, false))
+            }
+        }
+    }
+
+    /// Look up an active workflow by id
+    pub async fn active_workflow(&self, workflow_id: Uuid) -> Option<WorkflowDefinition> {
+        self.workflows
+            .read()
+            .await
+            .iter()
+            .find(|w| w.id == workflow_id)
+            .cloned()
+    }
+
+    /// Re-run a past execution's trigger payload against its workflow. Trigger
+    /// config and conditions are skipped so the run reproduces the original.
+    pub async fn replay_execution(
+        &self,
+        workflow: &WorkflowDefinition,
+        execution: &WorkflowExecution,
+    ) -> Result<Uuid, Box<dyn std::error::Error + Send + Sync>> {
+        let trigger_type = execution
+            .trigger_type
+            .as_deref()
+            .and_then(|t| serde_json::from_value(serde_json::Value::String(t.to_string())).ok())
+            .unwrap_or_else(|| workflow.trigger_type.clone());
+
+        let event = TriggerEvent::new(trigger_type, execution.trigger_data.clone(), EventSource::Api)
+            .with_correlation_id(execution.id);
+
+        let (execution_id, _) = self.run_workflow(workflow, &event, Some(execution.id)).await?;
+        Ok(execution_id)
+    }
+
     fn matches_trigger_config(&self, event: &TriggerEvent, config: &serde_json::Value) -> bool {
         // Check specific trigger configuration
         match event.trigger_type {
@@ -217,9 +297,9 @@ impl WorkflowEngine {
                 }
             }
             TriggerType::SlaBreach => {
-                if let Some(breach_type) = config.get("breach_type") {
-                    if let Some(event_breach) = event.payload.get("breach_type") {
-                        if breach_type != event_breach {
+                for field in ["breach_type", "priority", "client_id"] {
+                    if let (Some(filter), Some(value)) = (config.get(field), event.payload.get(field)) {
+                        if filter != value {
                             return false;
                         }
                     }
@@ -231,131 +311,41 @@ impl WorkflowEngine {
         true
     }
 
-    async fn evaluate_conditions(&self, group: &ConditionGroup, payload: &serde_json::Value) -> bool {
-        let results: Vec<bool> = group
-            .conditions
-            .iter()
-            .map(|c| self.evaluate_condition(c, payload))
-            .collect();
-
-        match group.logic.as_str() {
-            "AND" | "and" => results.iter().all(|&r| r),
-            "OR" | "or" => results.iter().any(|&r| r),
-            _ => results.iter().all(|&r| r),
-        }
-    }
-
-    fn evaluate_condition(&self, condition: &Condition, payload: &serde_json::Value) -> bool {
-        let field_value = payload.get(&condition.field);
-
-        match condition.operator.as_str() {
-            "equals" | "eq" | "==" => {
-                field_value.map(|v| v == &condition.value).unwrap_or(false)
-            }
-            "not_equals" | "ne" | "!=" => {
-                field_value.map(|v| v != &condition.value).unwrap_or(true)
-            }
-            "contains" => {
-                if let Some(val) = field_value {
-                    if let (Some(s), Some(pattern)) = (val.as_str(), condition.value.as_str()) {
-                        return s.to_lowercase().contains(&pattern.to_lowercase());
-                    }
-                }
-                false
-            }
-            "not_contains" => {
-                if let Some(val) = field_value {
-                    if let (Some(s), Some(pattern)) = (val.as_str(), condition.value.as_str()) {
-                        return !s.to_lowercase().contains(&pattern.to_lowercase());
-                    }
-                }
-                true
-            }
-            "starts_with" => {
-                if let Some(val) = field_value {
-                    if let (Some(s), Some(pattern)) = (val.as_str(), condition.value.as_str()) {
-                        return s.to_lowercase().starts_with(&pattern.to_lowercase());
-                    }
-                }
-                false
-            }
-            "ends_with" => {
-                if let Some(val) = field_value {
-                    if let (Some(s), Some(pattern)) = (val.as_str(), condition.value.as_str()) {
-                        return s.to_lowercase().ends_with(&pattern.to_lowercase());
-                    }
-                }
-                false
-            }
-            "greater_than" | "gt" | ">" => {
-                if let Some(val) = field_value {
-                    if let (Some(v), Some(c)) = (val.as_f64(), condition.value.as_f64()) {
-                        return v > c;
-                    }
-                }
-                false
-            }
-            "less_than" | "lt" | "<" => {
-                if let Some(val) = field_value {
-                    if let (Some(v), Some(c)) = (val.as_f64(), condition.value.as_f64()) {
-                        return v < c;
-                    }
-                }
-                false
-            }
-            "in" => {
-                if let Some(val) = field_value {
-                    if let Some(arr) = condition.value.as_array() {
-                        return arr.contains(val);
-                    }
-                }
-                false
-            }
-            "not_in" => {
-                if let Some(val) = field_value {
-                    if let Some(arr) = condition.value.as_array() {
-                        return !arr.contains(val);
-                    }
-                }
-                true
-            }
-            "is_null" | "is_empty" => {
-                field_value.is_none() || field_value == Some(&serde_json::Value::Null)
-            }
-            "is_not_null" | "is_not_empty" => {
-                field_value.is_some() && field_value != Some(&serde_json::Value::Null)
-            }
-            "regex" => {
-                if let Some(val) = field_value {
-                    if let (Some(s), Some(pattern)) = (val.as_str(), condition.value.as_str()) {
-                        if let Ok(re) = regex::Regex::new(pattern) {
-                            return re.is_match(s);
-                        }
-                    }
-                }
-                false
-            }
-            _ => false,
-        }
-    }
-
-    async fn create_instance(&self, workflow_id: Uuid, event: &TriggerEvent) -> Result<Uuid, sqlx::Error> {
-        let instance_id = Uuid::new_v4();
+    async fn create_execution(
+        &self,
+        workflow_id: Uuid,
+        event: &TriggerEvent,
+        replayed_from: Option<Uuid>,
+    ) -> Result<Uuid, sqlx::Error> {
+        let execution_id = Uuid::new_v4();
+        let (triggered_by, trigger_user_id) = match &event.source {
+            EventSource::User(user_id) => ("user", Some(*user_id)),
+            EventSource::Scheduler => ("schedule", None),
+            EventSource::Webhook => ("webhook", None),
+            EventSource::Api => ("api", None),
+            _ => ("event", None),
+        };
 
         sqlx::query(
             r#"
-            INSERT INTO workflow_instances
-            (id, workflow_id, trigger_event_id, status, started_at, actions_completed, total_actions, execution_log)
-            VALUES ($1, $2, $3, 'running', NOW(), 0, 0, '[]'::jsonb)
+            INSERT INTO workflow_executions
+            (id, workflow_id, triggered_by, trigger_user_id, trigger_type, trigger_event_id, trigger_data,
+             status, started_at, steps_total, steps_completed, steps_failed, action_results, replayed_from_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, 'running', NOW(), 0, 0, 0, '[]'::jsonb, $8)
             "#
         )
-        .bind(instance_id)
+        .bind(execution_id)
         .bind(workflow_id)
+        .bind(triggered_by)
+        .bind(trigger_user_id)
+        .bind(serde_json::to_value(&event.trigger_type).ok().and_then(|v| v.as_str().map(String::from)))
         .bind(event.event_id)
+        .bind(&event.payload)
+        .bind(replayed_from)
         .execute(&self.db_pool)
         .await?;
 
-        Ok(instance_id)
+        Ok(execution_id)
     }
 
     async fn execute_workflow(
@@ -363,11 +353,12 @@ impl WorkflowEngine {
         workflow: &WorkflowDefinition,
         event: &TriggerEvent,
         instance_id: Uuid,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<WorkflowStatus, Box<dyn std::error::Error + Send + Sync>> {
         let total_actions = workflow.actions.len() as i32;
+        let mut actions_failed = 0;
 
         // Update total actions count
-        sqlx::query("UPDATE workflow_instances SET total_actions = $2 WHERE id = $1")
+        sqlx::query("UPDATE workflow_executions SET steps_total = $2 WHERE id = $1")
             .bind(instance_id)
             .bind(total_actions)
             .execute(&self.db_pool)
@@ -393,10 +384,15 @@ impl WorkflowEngine {
             // Log action result
             self.log_action_execution(instance_id, index as i32, action, &result).await?;
 
+            if !result.success {
+                actions_failed += 1;
+            }
+
             // Update progress
-            sqlx::query("UPDATE workflow_instances SET actions_completed = $2 WHERE id = $1")
+            sqlx::query("UPDATE workflow_executions SET steps_completed = $2, steps_failed = $3 WHERE id = $1")
                 .bind(instance_id)
                 .bind((index + 1) as i32)
+                .bind(actions_failed)
                 .execute(&self.db_pool)
                 .await?;
 
@@ -406,15 +402,22 @@ impl WorkflowEngine {
             }
         }
 
-        // Mark workflow as completed
+        let status = WorkflowStatus::finished(actions_failed);
+
         sqlx::query(
-            "UPDATE workflow_instances SET status = 'completed', completed_at = NOW() WHERE id = $1"
+            r#"
+            UPDATE workflow_executions
+            SET status = $2, completed_at = NOW(),
+                duration_ms = (EXTRACT(EPOCH FROM (NOW() - started_at)) * 1000)::integer
+            WHERE id = $1
+            "#
         )
         .bind(instance_id)
+        .bind(status.as_str())
         .execute(&self.db_pool)
         .await?;
 
-        Ok(())
+        Ok(status)
     }
 
     async fn log_action_execution(
@@ -435,7 +438,7 @@ impl WorkflowEngine {
         });
 
         sqlx::query(
-            "UPDATE workflow_instances SET execution_log = execution_log || $2 WHERE id = $1"
+            "UPDATE workflow_executions SET action_results = action_results || jsonb_build_array($2::jsonb) WHERE id = $1"
         )
         .bind(instance_id)
         .bind(log_entry)
@@ -445,9 +448,14 @@ impl WorkflowEngine {
         Ok(())
     }
 
-    async fn mark_instance_failed(&self, instance_id: Uuid, error: &str) -> Result<(), sqlx::Error> {
+    async fn mark_execution_failed(&self, instance_id: Uuid, error: &str) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "UPDATE workflow_instances SET status = 'failed', error_message = $2, completed_at = NOW() WHERE id = $1"
+            r#"
+            UPDATE workflow_executions
+            SET status = 'failed', error_message = $2, completed_at = NOW(),
+                duration_ms = (EXTRACT(EPOCH FROM (NOW() - started_at)) * 1000)::integer
+            WHERE id = $1
+            "#
         )
         .bind(instance_id)
         .bind(error)
@@ -529,30 +537,51 @@ impl WorkflowEngine {
         Ok(())
     }
 
-    /// Get workflow execution history
+    /// Get a workflow's execution history, newest first
     pub async fn get_execution_history(
-        &self,
-        workflow_id: Option<Uuid>,
+        db_pool: &PgPool,
+        workflow_id: Uuid,
         limit: i64,
-    ) -> Result<Vec<WorkflowInstance>, sqlx::Error> {
-        let mut query = "SELECT * FROM workflow_instances".to_string();
+        offset: i64,
+    ) -> Result<(Vec<WorkflowExecution>, i64), sqlx::Error> {
+        let executions = sqlx::query_as::<_, WorkflowExecution>(&format!(
+            "SELECT {} FROM workflow_executions WHERE workflow_id = $1 ORDER BY started_at DESC LIMIT $2 OFFSET $3",
+            EXECUTION_COLUMNS
+        ))
+        .bind(workflow_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db_pool)
+        .await?;
 
-        if workflow_id.is_some() {
-            query.push_str(" WHERE workflow_id = $1");
-        }
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM workflow_executions WHERE workflow_id = $1")
+            .bind(workflow_id)
+            .fetch_one(db_pool)
+            .await?;
+
+        Ok((executions, total))
+    }
+
+    /// Get a single execution record
+    pub async fn get_execution(db_pool: &PgPool, execution_id: Uuid) -> Result<Option<WorkflowExecution>, sqlx::Error> {
+        sqlx::query_as::<_, WorkflowExecution>(&format!(
+            "SELECT {} FROM workflow_executions WHERE id = $1",
+            EXECUTION_COLUMNS
+        ))
+        .bind(execution_id)
+        .fetch_optional(db_pool)
+        .await
+    }
+}
 
-        query.push_str(" ORDER BY started_at DESC LIMIT ");
-        query.push_str(&limit.to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if let Some(wf_id) = workflow_id {
-            sqlx::query_as::<_, WorkflowInstance>(&query)
-                .bind(wf_id)
-                .fetch_all(&self.db_pool)
-                .await
-        } else {
-            sqlx::query_as::<_, WorkflowInstance>(&query)
-                .fetch_all(&self.db_pool)
-                .await
-        }
+    #[test]
+    fn test_finished_status() {
+        assert_eq!(WorkflowStatus::finished(0), WorkflowStatus::Completed);
+        assert_eq!(WorkflowStatus::finished(2), WorkflowStatus::PartialFailure);
+        assert_eq!(WorkflowStatus::PartialFailure.as_str(), "partial_failure");
     }
 }
@@ -8,8 +8,11 @@ use std::time::Instant;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+use super::webhook::{self, WebhookConfig};
 use super::{Action, ActionResult, ActionType};
+use crate::services::ticket_status::{self, AUTOMATION_USER_ID};
 use crate::services::EmailService;
+use resolve_shared::TicketStatus;
 use crate::websocket::WsManager;
 
 /// Context for workflow execution
@@ -99,6 +102,7 @@ impl WorkflowExecutor {
 
             // Integration Actions
             ActionType::CallApi => self.execute_call_api(&config, context).await,
+            ActionType::Webhook => self.execute_webhook(&config).await,
 
             // Default/unimplemented
             _ => Ok(ActionResult::success(None)),
@@ -215,14 +219,11 @@ impl WorkflowExecutor {
     }
 
     async fn execute_update_status(&self, config: &serde_json::Value, context: &ExecutionContext) -> Result<ActionResult, Box<dyn std::error::Error + Send + Sync>> {
-        let status = config["status"].as_str().ok_or("Missing status")?;
+        let status: TicketStatus = config["status"].as_str().ok_or("Missing status")?.parse()?;
         let ticket_id: Uuid = serde_json::from_value(context.event_payload["ticket_id"].clone())?;
 
-        sqlx::query("UPDATE tickets SET status = $2, updated_at = NOW() WHERE id = $1")
-            .bind(ticket_id)
-            .bind(status)
-            .execute(&self.db_pool)
-            .await?;
+        let reason = format!("Set by workflow {}.", context.workflow_id);
+        ticket_status::change_status(&self.db_pool, ticket_id, status, AUTOMATION_USER_ID, &reason).await?;
 
         Ok(ActionResult::success(Some(serde_json::json!({
             "ticket_id": ticket_id,
@@ -762,6 +763,27 @@ impl WorkflowExecutor {
             "response_body": body
         }))))
     }
+
+    async fn execute_webhook(&self, config: &serde_json::Value) -> Result<ActionResult, Box<dyn std::error::Error + Send + Sync>> {
+        // Templates were already substituted into the config, body included
+        let webhook: WebhookConfig = serde_json::from_value(config.clone())?;
+        let response = webhook::deliver(&webhook).await?;
+        let output = response.to_json(&webhook.url);
+
+        if response.is_success() {
+            return Ok(ActionResult::success(Some(output)).with_retry_count(response.attempts as i32 - 1));
+        }
+
+        let error = match (&response.error, response.status_code) {
+            (Some(e), _) => format!("Webhook request failed: {}", e),
+            (None, Some(status)) => format!("Webhook returned HTTP {}", status),
+            (None, None) => "Webhook request failed".to_string(),
+        };
+
+        Ok(ActionResult::failure(&error)
+            .with_output(output)
+            .with_retry_count(response.attempts as i32 - 1))
+    }
 }
 
 impl ActionType {
@@ -8,9 +8,10 @@ pub mod triggers;
 pub mod conditions;
 pub mod actions;
 pub mod executor;
+pub mod webhook;
 
-pub use engine::{WorkflowEngine, WorkflowDefinition, WorkflowInstance};
-pub use triggers::{TriggerType, TriggerEvent, EventPayload};
+pub use engine::{WorkflowEngine, WorkflowExecution, WorkflowStatus};
+pub use triggers::{TriggerType, TriggerEvent, EventPayload, SlaBreachType};
 pub use conditions::{Condition, ConditionGroup, ConditionOperator, FieldCondition};
 pub use actions::{Action, ActionType, ActionResult};
 pub use executor::{WorkflowExecutor, ExecutionContext, ExecutionResult};
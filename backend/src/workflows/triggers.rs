@@ -56,6 +56,23 @@ pub enum TriggerType {
 /// Payload for trigger events
 pub type EventPayload = serde_json::Value;
 
+/// Which SLA target a ticket has breached
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SlaBreachType {
+    FirstResponse,
+    Resolution,
+}
+
+impl SlaBreachType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SlaBreachType::FirstResponse => "first_response",
+            SlaBreachType::Resolution => "resolution",
+        }
+    }
+}
+
 /// A trigger event that can initiate workflow execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TriggerEvent {
@@ -157,7 +174,9 @@ impl TriggerEvent {
     /// Create an SLA breach event
     pub fn sla_breach(
         ticket_id: Uuid,
-        breach_type: &str, // "response" or "resolution"
+        breach_type: SlaBreachType,
+        priority: &str,
+        client_id: Uuid,
         breach_minutes: i32,
         assigned_to: Option<Uuid>,
     ) -> Self {
@@ -165,7 +184,9 @@ impl TriggerEvent {
             TriggerType::SlaBreach,
             serde_json::json!({
                 "ticket_id": ticket_id,
-                "breach_type": breach_type,
+                "breach_type": breach_type.as_str(),
+                "priority": priority,
+                "client_id": client_id,
                 "breach_minutes": breach_minutes,
                 "assigned_to": assigned_to
             }),
@@ -325,15 +346,20 @@ mod tests {
     #[test]
     fn test_sla_breach_event() {
         let ticket_id = Uuid::new_v4();
+        let client_id = Uuid::new_v4();
         let event = TriggerEvent::sla_breach(
             ticket_id,
-            "response",
+            SlaBreachType::FirstResponse,
+            "high",
+            client_id,
             30,
             Some(Uuid::new_v4()),
         );
 
         assert_eq!(event.trigger_type, TriggerType::SlaBreach);
-        assert_eq!(event.payload.get("breach_type").unwrap(), "response");
+        assert_eq!(event.payload.get("breach_type").unwrap(), "first_response");
+        assert_eq!(event.payload.get("priority").unwrap(), "high");
+        assert_eq!(event.payload.get("client_id").unwrap(), &serde_json::json!(client_id));
         assert_eq!(event.payload.get("breach_minutes").unwrap(), 30);
     }
 }
@@ -0,0 +1,220 @@
+// Workflow Webhooks - Outbound HTTP calls to external systems (PagerDuty, Slack, Teams, ...)
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::middleware::request_id::with_request_id;
+
+/// Header carrying the hex HMAC-SHA256 signature of `{timestamp}.{body}`
+pub const SIGNATURE_HEADER: &str = "X-Resolve-Signature";
+/// Header carrying the unix timestamp included in the signature
+pub const TIMESTAMP_HEADER: &str = "X-Resolve-Timestamp";
+
+/// Maximum number of response body characters kept in the action result
+const RESPONSE_SNIPPET_CHARS: usize = 500;
+
+/// Configuration for a `Webhook` action. `body` is templated against the
+/// execution context before the request is built.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<serde_json::Value>,
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_backoff_seconds")]
+    pub backoff_seconds: u64,
+    /// When set, requests are signed so receivers can verify they came from Resolve
+    #[serde(default)]
+    pub signing_secret: Option<String>,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn default_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_backoff_seconds() -> u64 {
+    2
+}
+
+/// Final outcome of delivering a webhook
+#[derive(Debug, Clone)]
+pub struct WebhookResponse {
+    pub status_code: Option<u16>,
+    pub response_snippet: String,
+    pub attempts: u32,
+    pub error: Option<String>,
+}
+
+impl WebhookResponse {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.status_code.map(|s| (200..300).contains(&s)).unwrap_or(false)
+    }
+
+    pub fn to_json(&self, url: &str) -> serde_json::Value {
+        serde_json::json!({
+            "url": url,
+            "status_code": self.status_code,
+            "response_snippet": self.response_snippet,
+            "attempts": self.attempts
+        })
+    }
+}
+
+/// Sign `{timestamp}.{body}` with HMAC-SHA256 and return the hex digest
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Delay before retry `attempt` (1-based), doubling each time
+pub(crate) fn backoff_delay(base_seconds: u64, attempt: u32) -> Duration {
+    Duration::from_secs(base_seconds.saturating_mul(1 << attempt.saturating_sub(1).min(6)))
+}
+
+/// Server errors, rate limiting and timeouts are worth retrying; other 4xx are not
+pub(crate) fn is_retryable_status(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+pub(crate) fn response_snippet(body: &str) -> String {
+    body.chars().take(RESPONSE_SNIPPET_CHARS).collect()
+}
+
+/// Send the webhook, retrying transport errors and retryable statuses with backoff
+pub async fn deliver(config: &WebhookConfig) -> Result<WebhookResponse, Box<dyn std::error::Error + Send + Sync>> {
+    let method = reqwest::Method::from_bytes(config.method.to_uppercase().as_bytes())
+        .map_err(|_| format!("Unsupported HTTP method: {}", config.method))?;
+    let body = config.body.as_ref().map(|b| b.to_string());
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.timeout_seconds))
+        .build()?;
+
+    let max_attempts = config.max_attempts.max(1);
+    let mut last = WebhookResponse {
+        status_code: None,
+        response_snippet: String::new(),
+        attempts: 0,
+        error: None,
+    };
+
+    for attempt in 1..=max_attempts {
+        if attempt > 1 {
+            tokio::time::sleep(backoff_delay(config.backoff_seconds, attempt - 1)).await;
+        }
+
+        let mut request = with_request_id(client.request(method.clone(), &config.url));
+        for (key, value) in &config.headers {
+            request = request.header(key, value);
+        }
+        if let Some(body) = &body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+        }
+        if let Some(secret) = &config.signing_secret {
+            let timestamp = chrono::Utc::now().timestamp();
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(SIGNATURE_HEADER, format!("sha256={}", sign_payload(secret, timestamp, body.as_deref().unwrap_or(""))));
+        }
+
+        last.attempts = attempt;
+        match request.send().await {
+            Ok(response) => {
+                let status = response.status().as_u16();
+                let text = response.text().await.unwrap_or_default();
+                last.status_code = Some(status);
+                last.response_snippet = response_snippet(&text);
+                last.error = None;
+
+                if !is_retryable_status(status) {
+                    break;
+                }
+                warn!("Webhook to {} returned {} (attempt {}/{})", config.url, status, attempt, max_attempts);
+            }
+            Err(e) => {
+                last.status_code = None;
+                last.error = Some(e.to_string());
+                warn!("Webhook to {} failed (attempt {}/{}): {}", config.url, attempt, max_attempts, e);
+            }
+        }
+    }
+
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_defaults() {
+        let config: WebhookConfig = serde_json::from_value(serde_json::json!({
+            "url": "https://events.pagerduty.com/v2/enqueue"
+        }))
+        .unwrap();
+
+        assert_eq!(config.method, "POST");
+        assert_eq!(config.timeout_seconds, 10);
+        assert_eq!(config.max_attempts, 3);
+        assert!(config.headers.is_empty());
+        assert!(config.signing_secret.is_none());
+    }
+
+    #[test]
+    fn test_signature_is_stable_and_keyed() {
+        let body = r#"{"ticket_id":"123"}"#;
+        let signature = sign_payload("secret", 1700000000, body);
+
+        assert_eq!(signature, "279a5324b04f37ee0d0c345a7b30b93b9f4e53b561eab829523a1658459b4176");
+        assert_ne!(signature, sign_payload("other", 1700000000, body));
+        assert_ne!(signature, sign_payload("secret", 1700000001, body));
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        assert_eq!(backoff_delay(2, 1), Duration::from_secs(2));
+        assert_eq!(backoff_delay(2, 2), Duration::from_secs(4));
+        assert_eq!(backoff_delay(2, 3), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_non_2xx_is_failure() {
+        let mut response = WebhookResponse {
+            status_code: Some(204),
+            response_snippet: String::new(),
+            attempts: 1,
+            error: None,
+        };
+        assert!(response.is_success());
+
+        response.status_code = Some(404);
+        assert!(!response.is_success());
+        assert!(!is_retryable_status(404));
+        assert!(is_retryable_status(503));
+    }
+}
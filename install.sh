@@ -241,6 +241,7 @@ REDIS_URL=redis://redis:6379
 SERVER_ADDR=127.0.0.1:8080
 JWT_SECRET=$JWT_SECRET
 ENCRYPTION_KEY=$ENCRYPTION_KEY
+BACKGROUND_JOBS_ENABLED=true
 RUST_LOG=info
 
 # Paths
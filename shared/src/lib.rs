@@ -1,8 +1,12 @@
-use chrono::{DateTime, Utc, NaiveDate};
+use chrono::{DateTime, Utc, NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use rust_decimal::Decimal;
 
+mod status;
+
+pub use status::{AssetStatus, ExpenseStatus, InvoiceStatus, Priority, TextEnum, TicketStatus, UnknownValue};
+
 #[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthProvider {
@@ -36,6 +40,8 @@ pub struct Credential {
     pub tags: Vec<String>,
     pub last_accessed: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
+    pub rotation_interval_days: Option<i32>,
+    pub rotation_flagged_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -129,6 +135,7 @@ pub struct SoftwareLicense {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Notification {
     pub id: Uuid,
@@ -187,6 +194,7 @@ pub struct RecurringTicket {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct File {
     pub id: Uuid,
@@ -237,6 +245,7 @@ pub struct Expense {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 pub struct Vendor {
     pub id: Uuid,
     pub name: String,
@@ -253,6 +262,8 @@ pub struct Vendor {
     pub notes: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: Option<DateTime<Utc>>,
+    /// Set when the vendor is deleted; archived vendors take no new expenses
+    pub archived_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -277,6 +288,7 @@ pub struct Client {
     pub zip: Option<String>,
     pub billing_address: Option<String>,
     pub notes: Option<String>,
+    pub tax_exempt: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: Option<DateTime<Utc>>,
     pub archived_at: Option<DateTime<Utc>>,
@@ -316,7 +328,7 @@ pub struct Asset {
     pub ip: Option<String>,
     pub mac: Option<String>,
     pub uri: Option<String>,
-    pub status: String,
+    pub status: AssetStatus,
     pub location_id: Option<Uuid>,
     pub contact_id: Option<Uuid>,
     pub purchase_date: Option<DateTime<Utc>>,
@@ -338,8 +350,8 @@ pub struct Ticket {
     pub number: i32,
     pub subject: String,
     pub details: String,
-    pub status: String,
-    pub priority: String,
+    pub status: TicketStatus,
+    pub priority: Priority,
     pub assigned_to: Option<Uuid>,
     pub billable: bool,
     pub opened_by: Uuid,
@@ -356,13 +368,13 @@ pub struct Invoice {
     pub contract_id: Option<Uuid>,
     pub project_id: Option<Uuid>,
     pub number: String,
-    pub date: DateTime<Utc>,
-    pub due_date: DateTime<Utc>,
+    pub date: NaiveDate,
+    pub due_date: NaiveDate,
     pub subtotal: Decimal,
     pub tax_amount: Decimal,
     pub total: Decimal,
     pub balance: Decimal,
-    pub status: String,
+    pub status: InvoiceStatus,
     pub payment_terms: String,
     pub late_fee_percentage: Option<Decimal>,
     pub discount_percentage: Option<Decimal>,
@@ -373,6 +385,28 @@ pub struct Invoice {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceLineItem {
+    pub id: Uuid,
+    pub invoice_id: Uuid,
+    pub description: String,
+    pub quantity: Decimal,
+    pub unit_price: Decimal,
+    pub line_total: Decimal,
+    pub tax_rate: Option<Decimal>,
+    pub tax_amount: Option<Decimal>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceWithLineItems {
+    #[serde(flatten)]
+    pub invoice: Invoice,
+    pub client_name: String,
+    pub line_items: Vec<InvoiceLineItem>,
+}
+
 // Enhanced BMS types
 
 #[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
@@ -385,6 +419,7 @@ pub struct User {
     pub password_hash: Option<String>, // For local auth
     pub role_id: Option<Uuid>,
     pub hourly_rate: Option<Decimal>,
+    pub cost_rate: Option<Decimal>, // Internal cost per hour; org default applies when None
     pub timezone: String,
     pub avatar_url: Option<String>,
     pub phone: Option<String>,
@@ -429,6 +464,7 @@ pub struct Contract {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sla {
     pub id: Uuid,
@@ -442,6 +478,28 @@ pub struct Sla {
     pub created_at: DateTime<Utc>,
 }
 
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusinessHours {
+    pub id: Uuid,
+    pub client_id: Option<Uuid>, // None for the global schedule
+    pub day_of_week: i32, // 0=Sunday, 6=Saturday
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub timezone: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusinessHoliday {
+    pub id: Uuid,
+    pub client_id: Option<Uuid>, // None for global holidays
+    pub holiday_date: NaiveDate,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
 #[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeEntry {
@@ -497,6 +555,7 @@ pub struct Task {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Payment {
     pub id: Uuid,
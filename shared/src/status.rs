@@ -0,0 +1,216 @@
+//! Status and priority values
+//!
+//! The columns holding these stay free text so adding a value needs no
+//! migration, but these enums are the list of what's valid. Each value has
+//! one canonical snake_case form used in JSON and in the database; parsing
+//! accepts any case and surrounding whitespace.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// An enum stored as its canonical string
+pub trait TextEnum: Sized + Copy + 'static {
+    /// Every canonical form, in declaration order
+    const NAMES: &'static [&'static str];
+
+    fn as_str(&self) -> &'static str;
+
+    /// Parse a value in any case, e.g. `" In_Progress "`
+    fn parse(value: &str) -> Option<Self>;
+}
+
+/// A string that isn't one of an enum's values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownValue {
+    pub kind: &'static str,
+    pub value: String,
+}
+
+impl fmt::Display for UnknownValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {} '{}'", self.kind, self.value)
+    }
+}
+
+impl std::error::Error for UnknownValue {}
+
+macro_rules! text_enum {
+    ($(#[$meta:meta])* $name:ident, $kind:literal { $($(#[$vmeta:meta])* $variant:ident => $text:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum $name {
+            $($(#[$vmeta])* #[serde(rename = $text)] $variant),+
+        }
+
+        impl TextEnum for $name {
+            const NAMES: &'static [&'static str] = &[$($text),+];
+
+            fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $text),+
+                }
+            }
+
+            fn parse(value: &str) -> Option<Self> {
+                let value = value.trim();
+                $(if value.eq_ignore_ascii_case($text) {
+                    return Some(Self::$variant);
+                })+
+                None
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = UnknownValue;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                <Self as TextEnum>::parse(s).ok_or_else(|| UnknownValue { kind: $kind, value: s.to_string() })
+            }
+        }
+
+        #[cfg(feature = "sqlx")]
+        impl sqlx::Type<sqlx::Postgres> for $name {
+            fn type_info() -> sqlx::postgres::PgTypeInfo {
+                <String as sqlx::Type<sqlx::Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+                <String as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+            }
+        }
+
+        #[cfg(feature = "sqlx")]
+        impl<'r> sqlx::Decode<'r, sqlx::Postgres> for $name {
+            fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+                let text = <&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?;
+                Ok(text.parse()?)
+            }
+        }
+
+        #[cfg(feature = "sqlx")]
+        impl<'q> sqlx::Encode<'q, sqlx::Postgres> for $name {
+            fn encode_by_ref(&self, buf: &mut sqlx::postgres::PgArgumentBuffer) -> sqlx::encode::IsNull {
+                <&str as sqlx::Encode<sqlx::Postgres>>::encode(self.as_str(), buf)
+            }
+        }
+    };
+}
+
+text_enum!(
+    /// Where a ticket is in its lifecycle
+    TicketStatus, "ticket status" {
+        New => "new",
+        #[default]
+        Open => "open",
+        InProgress => "in_progress",
+        Pending => "pending",
+        WaitingOnClient => "waiting_on_client",
+        Scheduled => "scheduled",
+        Resolved => "resolved",
+        Closed => "closed",
+        Cancelled => "cancelled",
+    }
+);
+
+text_enum!(
+    /// Ticket and task priority, lowest first
+    Priority, "priority" {
+        Low => "low",
+        #[default]
+        Medium => "medium",
+        High => "high",
+        Critical => "critical",
+        Urgent => "urgent",
+    }
+);
+
+text_enum!(
+    InvoiceStatus, "invoice status" {
+        #[default]
+        Draft => "draft",
+        Sent => "sent",
+        Viewed => "viewed",
+        Partial => "partial",
+        Paid => "paid",
+        Overdue => "overdue",
+        Void => "void",
+        Cancelled => "cancelled",
+    }
+);
+
+text_enum!(
+    AssetStatus, "asset status" {
+        #[default]
+        Active => "active",
+        Inactive => "inactive",
+        Retired => "retired",
+        Maintenance => "maintenance",
+        Disposed => "disposed",
+    }
+);
+
+text_enum!(
+    /// Where an expense is in approval and billing: submitted expenses are
+    /// approved or rejected, and approved billable ones become billed once
+    /// they're on an invoice. Reimbursed expenses are settled and final.
+    ExpenseStatus, "expense status" {
+        #[default]
+        Submitted => "submitted",
+        Approved => "approved",
+        Rejected => "rejected",
+        Billed => "billed",
+        Reimbursed => "reimbursed",
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_is_lenient_and_display_is_canonical() {
+        let status: TicketStatus = " In_Progress ".parse().unwrap();
+        assert_eq!(status, TicketStatus::InProgress);
+        assert_eq!(status.to_string(), "in_progress");
+        assert_eq!("HIGH".parse::<Priority>().unwrap(), Priority::High);
+    }
+
+    #[test]
+    fn test_unknown_values_are_rejected() {
+        let err = "Opn".parse::<TicketStatus>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown ticket status 'Opn'");
+        assert!("hihg".parse::<Priority>().is_err());
+        assert_eq!(InvoiceStatus::parse(""), None);
+    }
+
+    fn assert_round_trips<T>()
+    where
+        T: TextEnum + Serialize + serde::de::DeserializeOwned + PartialEq + fmt::Debug,
+    {
+        for name in T::NAMES {
+            let value = T::parse(&name.to_uppercase()).unwrap();
+            assert_eq!(value.as_str(), *name);
+
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(json, format!("\"{}\"", name));
+            assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_preserves_canonical_form() {
+        assert_round_trips::<TicketStatus>();
+        assert_round_trips::<Priority>();
+        assert_round_trips::<InvoiceStatus>();
+        assert_round_trips::<AssetStatus>();
+        assert_round_trips::<ExpenseStatus>();
+        assert_eq!(TicketStatus::default(), TicketStatus::Open);
+    }
+}